@@ -165,10 +165,24 @@ impl Display for VersionedBalance {
     }
 }
 
+// Flags used to know which optional fields are present in a serialized version
+const FLAG_PREVIOUS_TOPOHEIGHT: u8 = 1 << 0;
+const FLAG_OUTPUT_BALANCE: u8 = 1 << 1;
+
 impl Serializer for VersionedBalance {
     fn write(&self, writer: &mut Writer) {
         self.final_balance.write(writer);
         self.balance_type.write(writer);
+
+        let mut flags = 0;
+        if self.previous_topoheight.is_some() {
+            flags |= FLAG_PREVIOUS_TOPOHEIGHT;
+        }
+        if self.output_balance.is_some() {
+            flags |= FLAG_OUTPUT_BALANCE;
+        }
+        writer.write_u8(flags);
+
         if let Some(topo) = &self.previous_topoheight {
             writer.write_u64(topo);
         }
@@ -180,21 +194,21 @@ impl Serializer for VersionedBalance {
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
         let final_balance = CiphertextCache::read(reader)?;
         let balance_type = BalanceType::read(reader)?;
-        let (previous_topoheight, output_balance) = if reader.size() == 0 {
-            (None, None)
+        let flags = reader.read_u8()?;
+        if flags & !(FLAG_PREVIOUS_TOPOHEIGHT | FLAG_OUTPUT_BALANCE) != 0 {
+            return Err(ReaderError::InvalidValue)
+        }
+
+        let previous_topoheight = if flags & FLAG_PREVIOUS_TOPOHEIGHT != 0 {
+            Some(reader.read_u64()?)
+        } else {
+            None
+        };
+
+        let output_balance = if flags & FLAG_OUTPUT_BALANCE != 0 {
+            Some(CiphertextCache::read(reader)?)
         } else {
-            // Compressed ciphertext is 32 * 2 bytes, + 8 for topoheight
-            let previous_topo = if reader.size() == 8 || (balance_type == BalanceType::Both && reader.size() == 72) {
-                Some(reader.read_u64()?)
-            } else {
-                None
-            };
-
-            if balance_type == BalanceType::Both {
-                (previous_topo, Some(CiphertextCache::read(reader)?))
-            } else {
-                (previous_topo, None)
-            }
+            None
         };
 
         Ok(Self {
@@ -208,6 +222,8 @@ impl Serializer for VersionedBalance {
     fn size(&self) -> usize {
         self.final_balance.size()
         + self.balance_type.size()
+        // flags
+        + 1
         + if let Some(topoheight) = self.previous_topoheight { topoheight.size() } else { 0 }
         + if let Some(output_balance) = &self.output_balance { output_balance.size() } else { 0 }
     }
@@ -264,4 +280,14 @@ mod tests {
         let zero_bis = VersionedBalance::from_bytes(&zero.to_bytes()).unwrap();
         assert_eq!(zero, zero_bis);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn serde_versioned_balance_both_without_output() {
+        let mut zero = VersionedBalance::zero();
+        zero.set_balance_type(BalanceType::Both);
+        zero.set_previous_topoheight(Some(42));
+
+        let zero_bis = VersionedBalance::from_bytes(&zero.to_bytes()).unwrap();
+        assert_eq!(zero, zero_bis);
+    }
+}
@@ -7,11 +7,12 @@ use indexmap::IndexSet;
 use serde::{Deserialize, Serialize, Serializer, Deserializer, de::Error};
 use crate::{
     account::{CiphertextCache, VersionedBalance, VersionedNonce},
-    block::EXTRA_NONCE_SIZE,
+    block::{BlockHeader, MerkleProof, EXTRA_NONCE_SIZE, HEADER_EXTRA_NONCE_OFFSET, MINER_WORK_EXTRA_NONCE_OFFSET},
     crypto::{Address, Hash},
     difficulty::{CumulativeDifficulty, Difficulty},
     network::Network,
-    time::{TimestampMillis, TimestampSeconds}
+    time::{TimestampMillis, TimestampSeconds},
+    varuint::VarUint
 };
 use super::RPCTransaction;
 
@@ -57,6 +58,9 @@ pub struct RPCBlockResponse<'a> {
     pub version: u8,
     pub tips: Cow<'a, IndexSet<Hash>>,
     pub timestamp: TimestampMillis,
+    // RFC3339 UTC format of the timestamp
+    #[serde(default)]
+    pub timestamp_human: String,
     pub height: u64,
     pub nonce: u64,
     #[serde(serialize_with = "serialize_extra_nonce")]
@@ -71,6 +75,24 @@ pub struct RPCBlockResponse<'a> {
 
 pub type BlockResponse = RPCBlockResponse<'static>;
 
+// Lightweight summary of a block, everything a dashboard header needs
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BlockSummary {
+    pub hash: Hash,
+    pub height: u64,
+    pub topoheight: Option<u64>,
+    pub timestamp: TimestampMillis,
+    // Address of the miner from the coinbase
+    pub miner: Address,
+    pub reward: Option<u64>,
+    pub txs_count: usize,
+    // Fees of the transactions executed in this block
+    pub total_fees: u64,
+    // Size of the header and all its transactions
+    pub size: usize,
+    pub difficulty: Difficulty
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetTopBlockParams {
     #[serde(default)]
@@ -84,11 +106,29 @@ pub struct GetBlockAtTopoHeightParams {
     pub include_txs: bool
 }
 
+fn default_true_value() -> bool {
+    true
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetBlocksAtHeightParams {
     pub height: u64,
     #[serde(default)]
-    pub include_txs: bool
+    pub include_txs: bool,
+    // Include the blocks that are not topologically ordered
+    #[serde(default = "default_true_value")]
+    pub include_orphaned: bool
+}
+
+// A block at a DAG height with its position in the DAG
+#[derive(Serialize, Deserialize)]
+pub struct BlockAtHeightResponse<T> {
+    #[serde(flatten)]
+    pub block: T,
+    // Is the block topologically ordered
+    pub is_ordered: bool,
+    // Is the block one of the current tips
+    pub is_tip: bool
 }
 
 #[derive(Serialize, Deserialize)]
@@ -100,7 +140,10 @@ pub struct GetBlockByHashParams<'a> {
 
 #[derive(Serialize, Deserialize)]
 pub struct GetBlockTemplateParams<'a> {
-    pub address: Cow<'a, Address>
+    pub address: Cow<'a, Address>,
+    // Maximum TXs to include in the template
+    #[serde(default)]
+    pub max_txs: Option<usize>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -128,6 +171,24 @@ pub struct GetBlockTemplateResult {
     pub topoheight: u64,
     // Difficulty target for the POW challenge
     pub difficulty: Difficulty,
+    // Topoheight of the block if it's ordered right after the daemon topoheight
+    pub block_topoheight: u64,
+    // Fees of the selected transactions
+    pub total_fees: u64,
+    // Miner reward (block reward + fees - dev fee) if it's not a side block
+    pub expected_reward: u64,
+    pub txs_count: usize,
+    // Serialized size of the block with its transactions
+    pub size: usize,
+    // Bytes still available in the block
+    pub remaining_size: usize,
+    // Offset of the extra nonce in the template bytes
+    #[serde(default = "default_header_extra_nonce_offset")]
+    pub extra_nonce_offset: usize
+}
+
+fn default_header_extra_nonce_offset() -> usize {
+    HEADER_EXTRA_NONCE_OFFSET
 }
 
 #[derive(Serialize, Deserialize, PartialEq)]
@@ -140,7 +201,14 @@ pub struct GetMinerWorkResult {
     // this is for visual purposes only
     pub topoheight: u64,
     // difficulty required for valid block POW
-    pub difficulty: Difficulty
+    pub difficulty: Difficulty,
+    // Offset of the extra nonce in the miner job bytes
+    #[serde(default = "default_miner_work_extra_nonce_offset")]
+    pub extra_nonce_offset: usize
+}
+
+fn default_miner_work_extra_nonce_offset() -> usize {
+    MINER_WORK_EXTRA_NONCE_OFFSET
 }
 
 #[derive(Serialize, Deserialize)]
@@ -207,7 +275,72 @@ pub struct GetNonceAtTopoHeightParams<'a> {
 pub struct GetNonceResult {
     pub topoheight: u64,
     #[serde(flatten)]
-    pub version: VersionedNonce
+    pub version: VersionedNonce,
+    // Topoheight of the chain when the response was served
+    #[serde(default)]
+    pub snapshot_topoheight: u64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetNonceHistoryParams<'a> {
+    pub address: Cow<'a, Address>,
+    // Maximum entries to return, capped by the daemon
+    #[serde(default)]
+    pub max_entries: Option<usize>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NonceHistoryEntry {
+    pub topoheight: u64,
+    pub nonce: u64
+}
+
+// Error code returned by submit_transaction when the transaction nonce is rejected
+pub const INVALID_NONCE_ERROR_CODE: i16 = 1006;
+
+// Data of the error with code INVALID_NONCE_ERROR_CODE
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct InvalidNonceErrorData {
+    // Nonce used by the transaction
+    pub nonce: u64,
+    // Next nonce accepted by the daemon for this account
+    pub expected_nonce: u64
+}
+
+// Error code returned by submit_transaction when the fees are below the node relay policy
+pub const FEE_TOO_LOW_ERROR_CODE: i16 = 1007;
+
+// Data of the error with code FEE_TOO_LOW_ERROR_CODE
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct FeeTooLowErrorData {
+    // Fee paid by the transaction
+    pub fee: u64,
+    // Minimum fee accepted by the daemon for this transaction
+    pub required_fee: u64,
+    // Fee per KB set by the node operator
+    pub min_fee_per_kb: u64
+}
+
+// Data of an invalid address error when the typo could be located
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct InvalidAddressErrorData {
+    // Position of the invalid character in the address
+    pub position: usize
+}
+
+// Data of the errors for a block, transaction or asset not found
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct NotFoundErrorData<'a> {
+    pub hash: Cow<'a, Hash>
+}
+
+// Data of the errors for data not available due to pruning
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct PrunedErrorData {
+    // Topoheight, height or timestamp requested
+    pub requested: u64,
+    // Lowest topoheight available on the daemon
+    pub pruned_topoheight: u64
 }
 
 #[derive(Serialize, Deserialize)]
@@ -218,7 +351,10 @@ pub struct HasNonceResult {
 #[derive(Serialize, Deserialize)]
 pub struct GetBalanceResult {
     pub version: VersionedBalance,
-    pub topoheight: u64
+    pub topoheight: u64,
+    // Topoheight of the chain when the response was served
+    #[serde(default)]
+    pub snapshot_topoheight: u64
 }
 
 #[derive(Serialize, Deserialize)]
@@ -238,20 +374,74 @@ pub struct GetInfoResult {
     pub block_time_target: u64,
     // Average block time of last 50 blocks
     pub average_block_time: u64,
+    // Rolling average block time of the last 100 blocks accepted
+    #[serde(default)]
+    pub rolling_block_time: Option<u64>,
+    // The rolling average deviates from the target for more than an hour
+    #[serde(default)]
+    pub block_time_drifting: bool,
     pub block_reward: u64,
     // count how many transactions are present in mempool
     pub mempool_size: usize,
     // software version on which the daemon is running
     pub version: String,
     // Network state (mainnet, testnet, devnet)
-    pub network: Network
+    pub network: Network,
+    // Is the chain synced with the network
+    // Blocks / TXs are not relayed and no block template is generated until synced
+    #[serde(default)]
+    pub is_synced: bool,
+    // Hash of the genesis block, used by clients to verify they are on the same chain
+    #[serde(default)]
+    pub genesis_block_hash: Option<Hash>,
+    // Mempool replaces a pending transaction by one with the same nonce and a higher fee
+    #[serde(default)]
+    pub replace_by_fee: bool,
+    // Offset in milliseconds applied to our clock to follow the time of our peers
+    #[serde(default)]
+    pub network_time_offset: i64,
+    // Our clock deviates from the time of our peers more than the offset can correct
+    #[serde(default)]
+    pub clock_skewed: bool
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SubmitTransactionParams {
+    pub data: String, // should be in hex format
+    // Retrying with the same key replays the first response
+    // instead of processing the transaction again
+    #[serde(default)]
+    pub idempotency_key: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SimulateTransactionParams {
     pub data: String // should be in hex format
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct SimulatedBalance<'a> {
+    pub address: Cow<'a, Address>,
+    pub asset: Cow<'a, Hash>,
+    // Encrypted balance once the transaction is applied
+    pub balance: CiphertextCache
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SimulateTransactionResult<'a> {
+    // If the transaction would be accepted in mempool
+    pub accepted: bool,
+    // Reason of the rejection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub error: Option<String>,
+    pub fee: u64,
+    // Sender nonce once the transaction is applied
+    pub nonce: u64,
+    // Balances of the sender and the receivers once the transaction is applied
+    pub balances: Vec<SimulatedBalance<'a>>
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetTransactionParams<'a> {
     pub hash: Cow<'a, Hash>
@@ -265,6 +455,52 @@ pub struct GetTransactionExecutorResult<'a> {
     pub block_hash: Cow<'a, Hash>
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct GetBlockExecutedTransactionsParams<'a> {
+    pub hash: Cow<'a, Hash>
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionExecutionStatus {
+    // Executed by this block
+    Executed,
+    // Already executed by a block ordered before it in the DAG
+    AlreadyExecuted,
+    // Rejected when this block was executed
+    Failed
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BlockExecutedTransaction<'a> {
+    pub hash: Cow<'a, Hash>,
+    pub status: TransactionExecutionStatus,
+    // Position in the transactions executed by this block
+    // None if not executed by it, or executed before the position was stored
+    pub execution_index: Option<u16>,
+    // Block that executed it, if any
+    pub executed_in_block: Option<Cow<'a, Hash>>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTxMerkleProofParams<'a> {
+    pub tx_hash: Cow<'a, Hash>,
+    // Block including the transaction, the block executing it by default
+    #[serde(default)]
+    pub block_hash: Option<Cow<'a, Hash>>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTxMerkleProofResult<'a> {
+    pub block_hash: Cow<'a, Hash>,
+    pub block_version: u8,
+    // Merkle root of the block TXs hashes
+    // Only committed in the block header since TXS_MERKLE_ROOT_VERSION
+    pub txs_merkle_root: Hash,
+    #[serde(flatten)]
+    pub proof: MerkleProof
+}
+
 // Direction is used for cache to knows from which context it got added
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
@@ -299,10 +535,37 @@ impl Direction {
     }
 }
 
+#[derive(Serialize, Deserialize, Default)]
+pub struct GetPeersParams {
+    // Only the connections we initiated
+    #[serde(default)]
+    pub only_out: bool,
+    // Only the peers advertising at least this height
+    #[serde(default)]
+    pub min_height: Option<u64>,
+    // Don't return the peers, only their counts
+    #[serde(default)]
+    pub count_only: bool
+}
+
+impl GetPeersParams {
+    // Check if the peer entry passes the requested filters
+    pub fn matches(&self, entry: &PeerEntry) -> bool {
+        if self.only_out && entry.direction != Direction::Out {
+            return false
+        }
+
+        self.min_height.map_or(true, |min| entry.height >= min)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetPeersResponse<'a> {
-    // Peers that are connected and allows to be displayed
+    // Peers that are connected, allows to be displayed and match the filters
+    // Always empty in count only mode
     pub peers: Vec<PeerEntry<'a>>,
+    // Peers matching the filters
+    pub matching_peers: usize,
     // All peers connected
     pub total_peers: usize,
     // Peers that asked to not be listed
@@ -312,6 +575,7 @@ pub struct GetPeersResponse<'a> {
 #[derive(Serialize, Deserialize)]
 pub struct PeerEntry<'a> {
     pub id: u64,
+    // Masked if the node hides the peers IPs
     pub addr: Cow<'a, SocketAddr>,
     pub local_port: u16,
     pub tag: Cow<'a, Option<String>>,
@@ -323,7 +587,23 @@ pub struct PeerEntry<'a> {
     pub pruned_topoheight: Option<u64>,
     pub peers: Cow<'a, HashMap<SocketAddr, Direction>>,
     pub cumulative_difficulty: Cow<'a, CumulativeDifficulty>,
-    pub connected_on: TimestampSeconds
+    pub connected_on: TimestampSeconds,
+    // Out if we initiated the connection
+    pub direction: Direction,
+    pub priority: bool,
+    // Round trip time in milliseconds of the last object request
+    pub latency: Option<u64>,
+    pub bytes_in: usize,
+    pub bytes_out: usize,
+    pub fail_count: u8,
+    // Object requests of this peer served and dropped because it sent too many at once
+    #[serde(default)]
+    pub object_requests_served: u64,
+    #[serde(default)]
+    pub object_requests_dropped: u64,
+    // Chain requests of this peer served
+    #[serde(default)]
+    pub chain_requests_served: u64
 }
 
 #[derive(Serialize, Deserialize)]
@@ -337,6 +617,34 @@ pub struct P2pStatusResult<'a> {
     pub peer_id: u64
 }
 
+// One of our peers in the topology snapshot
+#[derive(Serialize, Deserialize)]
+pub struct P2pTopologyPeer<'a> {
+    pub id: u64,
+    pub addr: Cow<'a, SocketAddr>,
+    pub tag: Cow<'a, Option<String>>,
+    pub version: Cow<'a, String>,
+    pub topoheight: u64,
+    pub height: u64,
+    // Round trip time in milliseconds of the last object request
+    pub latency: Option<u64>,
+    // Connections count advertised by the peer, None if it doesn't share its topology
+    pub advertised_peers_count: Option<u16>,
+    // Node tag advertised with its topology
+    pub advertised_tag: Option<String>,
+    // Last time we received its topology
+    pub last_topology_update: Option<TimestampSeconds>,
+    // Topology was not refreshed during the last 5 minutes
+    pub is_stale: bool
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct P2pTopologyResult<'a> {
+    pub peer_id: u64,
+    pub tag: Cow<'a, Option<String>>,
+    pub peers: Vec<P2pTopologyPeer<'a>>
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetTopoHeightRangeParams {
     pub start_topoheight: Option<u64>,
@@ -349,6 +657,125 @@ pub struct GetHeightRangeParams {
     pub end_height: Option<u64>
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct GetHeadersRangeParams {
+    pub start_topoheight: u64,
+    pub count: u64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HeadersRangeEntry<'a> {
+    pub hash: Cow<'a, Hash>,
+    pub topoheight: u64,
+    pub difficulty: Difficulty,
+    pub cumulative_difficulty: CumulativeDifficulty,
+    // hex of the serialized block header
+    pub data: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetDifficultyHistoryParams {
+    pub start_topoheight: u64,
+    pub end_topoheight: u64,
+    // sample every N blocks
+    pub step: u64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DifficultyHistoryEntry<'a> {
+    pub topoheight: u64,
+    pub hash: Cow<'a, Hash>,
+    pub timestamp: TimestampMillis,
+    // RFC3339 UTC format of the timestamp
+    #[serde(default)]
+    pub timestamp_human: String,
+    pub difficulty: Difficulty,
+    // milliseconds since the selected parent, none for genesis
+    pub solve_time: Option<u64>,
+    pub hashrate: Difficulty,
+    pub hashrate_formatted: String,
+    // estimated covariance P of the difficulty algorithm
+    pub covariance: Option<VarUint>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetChainStatsParams {
+    pub start_topoheight: u64,
+    pub end_topoheight: u64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetChainStatsResult {
+    pub start_topoheight: u64,
+    pub end_topoheight: u64,
+    pub blocks_count: u64,
+    // sum of the block rewards, dev fee included
+    pub total_emission: u64,
+    // sum of the fees of the transactions executed
+    pub total_fees: u64,
+    // transfers outputs of the transactions executed
+    pub transfers_count: u64,
+    // milliseconds between two blocks
+    pub average_block_interval: u64,
+    // in bytes, transactions included
+    pub average_block_size: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockProcessingStage {
+    // Header checks against the DAG, PoW excluded
+    HeaderVerification,
+    ProofOfWork,
+    // Transactions checks and proofs verification
+    TxsVerification,
+    // DAG ordering and transactions execution
+    Execution,
+    // Block and state changes written in storage
+    StorageCommit,
+    // P2P broadcast and RPC events
+    Notification
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BlockProcessingHistogramBucket {
+    // Inclusive upper bound in microseconds, None for the last bucket
+    pub upper_bound: Option<u64>,
+    pub count: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockProcessingStageStats {
+    pub stage: BlockProcessingStage,
+    // Blocks in which this stage was measured
+    pub count: u64,
+    // All durations are in microseconds
+    pub total: u64,
+    pub average: u64,
+    pub max: u64,
+    pub last: Option<u64>,
+    pub buckets: Vec<BlockProcessingHistogramBucket>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetBlockProcessingStatsResult {
+    // Blocks processed since the node started
+    pub blocks: u64,
+    pub stages: Vec<BlockProcessingStageStats>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTopoHeightAtTimestampParams {
+    pub timestamp: TimestampMillis
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetBlockCountBetweenParams {
+    // both bounds are inclusive
+    pub timestamp_start: TimestampMillis,
+    pub timestamp_end: TimestampMillis
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetTransactionsParams {
     pub tx_hashes: Vec<Hash>
@@ -366,6 +793,10 @@ pub struct TransactionResponse<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub first_seen: Option<TimestampSeconds>,
+    // RFC3339 UTC format of the first seen timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub first_seen_human: Option<String>,
     #[serde(flatten)]
     pub data: RPCTransaction<'a>
 }
@@ -389,6 +820,8 @@ pub enum AccountHistoryType {
     DevFee { reward: u64 },
     Mining { reward: u64 },
     Burn { amount: u64 },
+    // Amount burned to register the asset
+    AssetCreation { asset: Hash, amount: u64 },
     Outgoing { to: Address },
     Incoming { from: Address },
 }
@@ -399,7 +832,10 @@ pub struct AccountHistoryEntry {
     pub hash: Hash,
     #[serde(flatten)]
     pub history_type: AccountHistoryType,
-    pub block_timestamp: TimestampMillis
+    pub block_timestamp: TimestampMillis,
+    // RFC3339 UTC format of the block timestamp
+    #[serde(default)]
+    pub block_timestamp_human: String
 }
 
 #[derive(Serialize, Deserialize)]
@@ -417,7 +853,77 @@ pub struct GetAssetsParams {
     pub skip: Option<usize>,
     pub maximum: Option<usize>,
     pub minimum_topoheight: Option<u64>,
-    pub maximum_topoheight: Option<u64>
+    pub maximum_topoheight: Option<u64>,
+    // Last asset of the previous page, results start right after it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<Hash>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TipInfo<'a> {
+    pub hash: Cow<'a, Hash>,
+    pub height: u64,
+    // None if the tip is not ordered yet
+    pub topoheight: Option<u64>,
+    pub difficulty: Difficulty,
+    pub cumulative_difficulty: CumulativeDifficulty,
+    // Lowest height of the tip from the mainchain
+    pub distance_from_mainchain: u64,
+    // Distance between the tip height and the stable height
+    pub distance_to_stable_height: u64,
+    // If the tip would be selected for a new block template
+    pub selected: bool
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTipsInfoResult<'a> {
+    pub height: u64,
+    pub stable_height: u64,
+    pub tips: Vec<TipInfo<'a>>,
+    // Tips as they would be used by the block template
+    // sorted by cumulative difficulty in descending order
+    pub selected_tips: IndexSet<Hash>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetAssetSupplyParams<'a> {
+    pub asset: Cow<'a, Hash>,
+    // Use the current topoheight if not set
+    #[serde(default)]
+    pub topoheight: Option<u64>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetAssetSupplyResult<'a> {
+    pub asset: Cow<'a, Hash>,
+    pub topoheight: u64,
+    // Circulating supply: emitted supply minus the burned supply
+    pub supply: u64,
+    // Total amount burned until this topoheight
+    pub burned: u64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetAssetHoldersParams<'a> {
+    pub asset: Cow<'a, Hash>,
+    pub skip: Option<usize>,
+    pub maximum: Option<usize>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AssetHolder<'a> {
+    pub address: Cow<'a, Address>,
+    pub registered_at_topoheight: u64,
+    // Topoheight of the last balance change for this asset
+    pub last_topoheight: u64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetAssetHoldersResult<'a> {
+    pub asset: Cow<'a, Hash>,
+    pub holder_count: usize,
+    // Balances are encrypted, so accounts are ordered by their last activity
+    pub accounts: Vec<AssetHolder<'a>>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -425,7 +931,10 @@ pub struct GetAccountsParams {
     pub skip: Option<usize>,
     pub maximum: Option<usize>,
     pub minimum_topoheight: Option<u64>,
-    pub maximum_topoheight: Option<u64>
+    pub maximum_topoheight: Option<u64>,
+    // Last account of the previous page, results start right after it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<Address>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -479,6 +988,20 @@ pub struct GetMempoolCacheResult {
     balances: HashMap<Hash, CiphertextCache>
 }
 
+impl GetMempoolCacheResult {
+    pub fn get_min(&self) -> u64 {
+        self.min
+    }
+
+    pub fn get_max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn get_txs(&self) -> &Vec<Hash> {
+        &self.txs
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetDifficultyResult {
     pub difficulty: Difficulty,
@@ -488,7 +1011,8 @@ pub struct GetDifficultyResult {
 
 #[derive(Serialize, Deserialize)]
 pub struct ValidateAddressParams<'a> {
-    pub address: Cow<'a, Address>,
+    // Kept as a string to report the position of a typo
+    pub address: Cow<'a, str>,
     #[serde(default)]
     pub allow_integrated: bool,
     #[serde(default)]
@@ -561,10 +1085,38 @@ pub enum NotifyEvent {
     // and that he notified us
     // It contains PeerPeerDisconnectedEvent as value
     PeerPeerDisconnected,
+    // When the balance of an account has changed during a block execution
+    // Only sent to clients subscribed with a filter on their addresses
+    // It contains BalanceUpdatedEvent as value
+    BalanceUpdated,
+    // When a peer sent us a heavier chain going below our stable height
+    // It contains DeepReorgRejectedEvent as value
+    DeepReorgRejected,
+    // When our chain is synced again with the network
+    // It contains SyncCompletedEvent as value
+    SyncCompleted,
+    // When an executed transaction touches an address watched with `subscribe_address`
+    // Sent again with the orphaned flag if the transaction is orphaned by a reorg
+    // It contains TransactionReceivedEvent as value
+    TransactionReceived,
+    // When a TX from a new block uses the same nonce than a different TX from the same sender in mempool
+    // It contains DoubleSpendDetectedEvent as value
+    DoubleSpendDetected,
+    // When a TX in mempool is replaced by a TX from the same sender with the same nonce and a higher fee
+    // It contains TransactionReplacedEvent as value
+    TransactionReplaced,
 }
 
 // Value of NotifyEvent::NewBlock
-pub type NewBlockEvent = BlockResponse;
+#[derive(Serialize, Deserialize)]
+pub struct NewBlockEvent<'a> {
+    #[serde(flatten)]
+    pub block: RPCBlockResponse<'a>,
+    // Same as get_top_block_summary when this block is the top block
+    // Not set for the other blocks and by older daemons
+    #[serde(default)]
+    pub summary: Option<Cow<'a, BlockSummary>>
+}
 
 // Value of NotifyEvent::BlockOrdered
 #[derive(Serialize, Deserialize)]
@@ -629,4 +1181,123 @@ pub struct PeerPeerDisconnectedEvent {
     pub peer_id: u64,
     // address of the peer that disconnected from him
     pub peer_addr: SocketAddr
-}
\ No newline at end of file
+}
+
+// Value of NotifyEvent::BalanceUpdated
+#[derive(Serialize, Deserialize)]
+pub struct BalanceUpdatedEvent<'a> {
+    pub address: Address,
+    pub asset: Cow<'a, Hash>,
+    // topoheight at which the new version is stored
+    pub topoheight: u64,
+    // hex of the serialized versioned balance
+    // ciphertexts are in their compressed form
+    pub version: String
+}
+
+// Value of NotifyEvent::DeepReorgRejected
+#[derive(Serialize, Deserialize)]
+pub struct DeepReorgRejectedEvent {
+    // Peer ID of the peer that sent us the chain
+    pub peer_id: u64,
+    pub peer_addr: SocketAddr,
+    // height of the common ancestor with our chain
+    pub common_height: u64,
+    // how many blocks we would have to pop
+    pub depth: u64,
+    pub stable_height: u64
+}
+
+// Value of NotifyEvent::SyncCompleted
+#[derive(Serialize, Deserialize)]
+pub struct SyncCompletedEvent {
+    pub topoheight: u64,
+    // best topoheight advertised by our peers
+    pub best_peer_topoheight: Option<u64>
+}
+
+// Value of NotifyEvent::TransactionReceived
+#[derive(Serialize, Deserialize)]
+pub struct TransactionReceivedEvent<'a> {
+    pub tx_hash: Cow<'a, Hash>,
+    // watched address touched by the transaction
+    pub address: Address,
+    // true if the address is the source of the transaction
+    pub outgoing: bool,
+    // assets transfered to or from the address, amounts are encrypted
+    pub assets: Vec<Hash>,
+    // block in which the transaction was executed, none if orphaned
+    pub block_hash: Option<Cow<'a, Hash>>,
+    pub topoheight: Option<u64>,
+    pub confirmations: u64,
+    // transaction is no longer executed in the chain
+    pub orphaned: bool
+}
+
+// Value of NotifyEvent::DoubleSpendDetected
+#[derive(Serialize, Deserialize)]
+pub struct DoubleSpendDetectedEvent<'a> {
+    // sender of both transactions
+    pub address: Address,
+    pub nonce: u64,
+    // transaction in our mempool, not mined until the conflict is resolved
+    pub mempool_tx: Cow<'a, Hash>,
+    // transaction included in the block
+    pub block_tx: Cow<'a, Hash>,
+    pub block_hash: Cow<'a, Hash>
+}
+
+// Value of NotifyEvent::TransactionReplaced
+#[derive(Serialize, Deserialize)]
+pub struct TransactionReplacedEvent<'a> {
+    // sender of both transactions
+    pub address: Address,
+    pub nonce: u64,
+    // transaction evicted from the mempool
+    pub replaced_tx: Cow<'a, Hash>,
+    // transaction relayed in its place
+    pub replacement_tx: Cow<'a, Hash>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SetLogLevelParams {
+    pub level: String,
+    // Apply the level only to this module
+    #[serde(default)]
+    pub module: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportChainParams<'a> {
+    // File path on the daemon side
+    pub path: Cow<'a, str>,
+    // Last topoheight included, top topoheight by default
+    #[serde(default)]
+    pub until_topoheight: Option<u64>
+}
+
+// Block sent by the firehose stream with the transactions executed in it
+#[derive(Serialize, Deserialize)]
+pub struct FirehoseBlock<'a> {
+    pub topoheight: u64,
+    pub hash: Cow<'a, Hash>,
+    pub header: Cow<'a, BlockHeader>,
+    // Only the transactions executed in this block, in block order
+    pub executed_transactions: Vec<RPCTransaction<'a>>
+}
+
+// Batch of blocks sent by the firehose stream, sorted by topoheight
+// No other batch is sent until the last topoheight of this one is acked
+#[derive(Serialize, Deserialize)]
+pub struct FirehoseBatch<'a> {
+    // Blocks already sent were re-ordered by the DAG
+    // everything from the first topoheight of this batch must be dropped
+    pub rewind: bool,
+    pub blocks: Vec<FirehoseBlock<'a>>
+}
+
+// Message sent by the firehose client once a batch is processed
+#[derive(Serialize, Deserialize)]
+pub struct FirehoseAck {
+    pub topoheight: u64
+}
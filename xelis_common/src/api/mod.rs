@@ -13,10 +13,12 @@ use crate::{
         proofs::CiphertextValidityProof,
         Address,
         Hash,
+        PublicKey,
         Signature
     },
     transaction::{
         extra_data::UnknownExtraDataFormat,
+        AssetCreationPayload,
         BurnPayload,
         Reference,
         SourceCommitment,
@@ -29,7 +31,33 @@ pub use data::*;
 
 #[derive(Serialize, Deserialize)]
 pub struct SubscribeParams<'a, E: Clone> {
-    pub notify: Cow<'a, E>
+    pub notify: Cow<'a, E>,
+    // Only receive the values of this event matching the filter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<EventFilter>
+}
+
+// Addresses to watch or to stop watching through `subscribe_address` / `unsubscribe_address`
+#[derive(Serialize, Deserialize)]
+pub struct SubscribeAddressParams {
+    // Empty to stop watching all the addresses
+    #[serde(default)]
+    pub addresses: Vec<Address>
+}
+
+// Filter set by a client when subscribing to an event
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    // Addresses the client is interested in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<Address>
+}
+
+impl EventFilter {
+    // Check if the filter contains an address for this key
+    pub fn has_key(&self, key: &PublicKey) -> bool {
+        self.addresses.iter().any(|address| address.get_public_key() == key)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -76,6 +104,7 @@ impl<'a> From<RPCTransferPayload<'a>> for TransferPayload {
 pub enum RPCTransactionType<'a> {
     Transfers(Vec<RPCTransferPayload<'a>>),
     Burn(Cow<'a, BurnPayload>),
+    AssetCreation(Cow<'a, AssetCreationPayload>),
 }
 
 impl<'a> RPCTransactionType<'a> {
@@ -96,7 +125,8 @@ impl<'a> RPCTransactionType<'a> {
                 }
                 Self::Transfers(rpc_transfers)
             },
-            TransactionType::Burn(burn) => Self::Burn(Cow::Borrowed(burn))
+            TransactionType::Burn(burn) => Self::Burn(Cow::Borrowed(burn)),
+            TransactionType::AssetCreation(payload) => Self::AssetCreation(Cow::Borrowed(payload))
         }
     }
 }
@@ -107,7 +137,8 @@ impl From<RPCTransactionType<'_>> for TransactionType {
             RPCTransactionType::Transfers(transfers) => {
                 TransactionType::Transfers(transfers.into_iter().map(|transfer| transfer.into()).collect::<Vec<TransferPayload>>())
             },
-            RPCTransactionType::Burn(burn) => TransactionType::Burn(burn.into_owned())
+            RPCTransactionType::Burn(burn) => TransactionType::Burn(burn.into_owned()),
+            RPCTransactionType::AssetCreation(payload) => TransactionType::AssetCreation(payload.into_owned())
         }
     }
 }
@@ -136,6 +167,9 @@ pub struct RPCTransaction<'a> {
     pub range_proof: Cow<'a, RangeProof>,
     /// Reference at which block the transaction was built
     pub reference: Cow<'a, Reference>,
+    /// Public extra data attached to the transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_data: Option<Cow<'a, Vec<u8>>>,
     /// Signature of the transaction
     pub signature: Cow<'a, Signature>,
 }
@@ -152,6 +186,7 @@ impl<'a> RPCTransaction<'a> {
             source_commitments: Cow::Borrowed(tx.get_source_commitments()),
             range_proof: Cow::Borrowed(tx.get_range_proof()),
             reference: Cow::Borrowed(tx.get_reference()),
+            extra_data: tx.get_extra_data().as_ref().map(Cow::Borrowed),
             signature: Cow::Borrowed(tx.get_signature()),
         }
     }
@@ -167,6 +202,7 @@ impl<'a> From<RPCTransaction<'a>> for Transaction {
             tx.source_commitments.into_owned(),
             tx.range_proof.into_owned(),
             tx.reference.into_owned(),
+            tx.extra_data.map(Cow::into_owned),
             tx.signature.into_owned()
         )
     }
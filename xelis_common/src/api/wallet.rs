@@ -14,6 +14,12 @@ pub struct BuildTransactionParams {
     #[serde(flatten)]
     pub tx_type: TransactionTypeBuilder,
     pub fee: Option<FeeBuilder>,
+    // Public extra data attached to the transaction
+    #[serde(default)]
+    pub extra_data: Option<Vec<u8>>,
+    // Encrypt the extra data so only the sender and the recipients can read it
+    #[serde(default = "default_false_value")]
+    pub encrypt_extra_data: bool,
     // Cannot be broadcasted if set to false
     pub broadcast: bool,
     // Returns the TX in HEX format also
@@ -25,6 +31,10 @@ pub struct BuildTransactionParams {
 pub struct EstimateFeesParams {
     #[serde(flatten)]
     pub tx_type: TransactionTypeBuilder,
+    #[serde(default)]
+    pub extra_data: Option<Vec<u8>>,
+    #[serde(default = "default_false_value")]
+    pub encrypt_extra_data: bool
 }
 
 // :(
@@ -202,6 +212,11 @@ pub enum EntryType {
         asset: Hash,
         amount: u64
     },
+    // ASSET_CREATION_BURN_AMOUNT of XELIS_ASSET is burned
+    AssetCreation {
+        asset: Hash,
+        decimals: u8
+    },
     Incoming {
         from: Address,
         transfers: Vec<TransferIn>
@@ -223,4 +238,7 @@ pub struct TransactionEntry {
     pub topoheight: u64,
     #[serde(flatten)]
     pub entry: EntryType,
+    // Public extra data attached to the transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_data: Option<Vec<u8>>,
 }
\ No newline at end of file
@@ -34,6 +34,7 @@ impl Block {
         transactions.iter().for_each(|tx| {
             block.txs_hashes.insert(tx.hash());
         });
+        block.refresh_txs_merkle_root();
 
         Block {
             header: Immutable::Owned(block),
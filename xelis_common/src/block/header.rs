@@ -13,11 +13,10 @@ use crate::{
         Hashable,
         HASH_SIZE
     },
-    serializer::{Reader, ReaderError, Serializer, Writer},
-    time::TimestampMillis
+    serializer::{Reader, ReaderError, Serializer, Writer}
 };
 use xelis_hash::Error as XelisHashError;
-use super::{MinerWork, EXTRA_NONCE_SIZE};
+use super::{compute_txs_merkle_root, MinerWork, EXTRA_NONCE_SIZE, TXS_MERKLE_ROOT_VERSION};
 
 // Serialize the extra nonce in a hexadecimal string
 pub fn serialize_extra_nonce<S: serde::Serializer>(extra_nonce: &[u8; EXTRA_NONCE_SIZE], s: S) -> Result<S::Ok, S::Error> {
@@ -40,7 +39,7 @@ pub struct BlockHeader {
     // All TIPS of the block (previous hashes of the block)
     pub tips: IndexSet<Hash>,
     // Timestamp in milliseconds
-    pub timestamp: TimestampMillis,
+    pub timestamp: u64,
     // Height of the block
     pub height: u64,
     // Nonce of the block
@@ -55,12 +54,16 @@ pub struct BlockHeader {
     // Miner public key
     pub miner: CompressedPublicKey,
     // All transactions hashes of the block
-    pub txs_hashes: IndexSet<Hash>
+    pub txs_hashes: IndexSet<Hash>,
+    // Merkle root of the transactions hashes
+    // Only present since the version TXS_MERKLE_ROOT_VERSION
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    txs_merkle_root: Option<Hash>
 }
 
 impl BlockHeader {
-    pub fn new(version: u8, height: u64, timestamp: TimestampMillis, tips: IndexSet<Hash>, extra_nonce: [u8; EXTRA_NONCE_SIZE], miner: CompressedPublicKey, txs_hashes: IndexSet<Hash>) -> Self {
-        BlockHeader {
+    pub fn new(version: u8, height: u64, timestamp: u64, tips: IndexSet<Hash>, extra_nonce: [u8; EXTRA_NONCE_SIZE], miner: CompressedPublicKey, txs_hashes: IndexSet<Hash>) -> Self {
+        let mut header = BlockHeader {
             version,
             height,
             timestamp,
@@ -68,8 +71,11 @@ impl BlockHeader {
             nonce: 0,
             extra_nonce,
             miner,
-            txs_hashes
-        }
+            txs_hashes,
+            txs_merkle_root: None
+        };
+        header.refresh_txs_merkle_root();
+        header
     }
 
     // Apply a MinerWork to this block header to match the POW hash
@@ -97,7 +103,7 @@ impl BlockHeader {
         self.height
     }
 
-    pub fn get_timestamp(&self) -> TimestampMillis {
+    pub fn get_timestamp(&self) -> u64 {
         self.timestamp
     }
 
@@ -132,12 +138,50 @@ impl BlockHeader {
         &self.txs_hashes
     }
 
+    pub fn set_txs_hashes(&mut self, txs_hashes: IndexSet<Hash>) {
+        self.txs_hashes = txs_hashes;
+        self.refresh_txs_merkle_root();
+    }
+
+    // Does this header commit to the TXs merkle root
+    pub fn has_txs_merkle_root(&self) -> bool {
+        self.version >= TXS_MERKLE_ROOT_VERSION
+    }
+
+    // Merkle root stored in the header, none before TXS_MERKLE_ROOT_VERSION
+    pub fn get_txs_merkle_root(&self) -> Option<&Hash> {
+        self.txs_merkle_root.as_ref()
+    }
+
+    // Compute again the merkle root from the TXs hashes
+    // Must be called after modifying the TXs hashes directly
+    pub fn refresh_txs_merkle_root(&mut self) {
+        self.txs_merkle_root = if self.has_txs_merkle_root() {
+            Some(compute_txs_merkle_root(&self.txs_hashes))
+        } else {
+            None
+        };
+    }
+
+    // Verify that the merkle root stored matches the TXs hashes
+    pub fn is_txs_merkle_root_valid(&self) -> bool {
+        match &self.txs_merkle_root {
+            Some(root) => self.has_txs_merkle_root() && *root == compute_txs_merkle_root(&self.txs_hashes),
+            None => !self.has_txs_merkle_root()
+        }
+    }
+
     pub fn take_txs_hashes(self) -> IndexSet<Hash> {
         self.txs_hashes
     }
 
     // Compute a hash covering all TXs hashes
+    // Since TXS_MERKLE_ROOT_VERSION, it is the merkle root stored in the header
     pub fn get_txs_hash(&self) -> Hash {
+        if let Some(root) = &self.txs_merkle_root {
+            return root.clone()
+        }
+
         let mut bytes = Vec::with_capacity(self.txs_hashes.len() * HASH_SIZE);
         for tx in &self.txs_hashes {
             bytes.extend(tx.as_bytes())
@@ -213,13 +257,16 @@ impl Serializer for BlockHeader {
         }
         self.miner.write(writer); // 60 + (N*32) + (T*32) + 32 = 92 + (N*32) + (T*32)
         // Minimum size is 92 bytes
+
+        if let Some(root) = &self.txs_merkle_root {
+            writer.write_hash(root); // 32
+        }
     }
 
     fn read(reader: &mut Reader) -> Result<BlockHeader, ReaderError> {
         let version = reader.read_u8()?;
-        // At this moment we only support version 0, so we check it here directly
-        if version != 0 {
-            debug!("Expected version 0 got version {version}");
+        if version > TXS_MERKLE_ROOT_VERSION {
+            debug!("Expected version up to {} got version {version}", TXS_MERKLE_ROOT_VERSION);
             return Err(ReaderError::InvalidValue)
         }
 
@@ -252,6 +299,13 @@ impl Serializer for BlockHeader {
         }
 
         let miner = CompressedPublicKey::read(reader)?;
+        // It is verified against the TXs hashes during the block verification
+        let txs_merkle_root = if version >= TXS_MERKLE_ROOT_VERSION {
+            Some(reader.read_hash()?)
+        } else {
+            None
+        };
+
         Ok(
             BlockHeader {
                 version,
@@ -261,7 +315,8 @@ impl Serializer for BlockHeader {
                 tips,
                 miner,
                 nonce,
-                txs_hashes
+                txs_hashes,
+                txs_merkle_root
             }
         )
     }
@@ -273,8 +328,9 @@ impl Serializer for BlockHeader {
         let txs_size = 2 + self.txs_hashes.len() * HASH_SIZE;
         // Version is u8
         let version_size = 1;
+        let merkle_root_size = if self.txs_merkle_root.is_some() { HASH_SIZE } else { 0 };
 
-        EXTRA_NONCE_SIZE + tips_size + txs_size + version_size
+        EXTRA_NONCE_SIZE + tips_size + txs_size + version_size + merkle_root_size
         + self.miner.size()
         + self.timestamp.size()
         + self.height.size()
@@ -304,7 +360,8 @@ impl Display for BlockHeader {
 mod tests {
     use indexmap::IndexSet;
     use crate::{crypto::{Hash, Hashable, KeyPair}, serializer::Serializer};
-    use super::BlockHeader;
+    use super::{compute_txs_merkle_root, BlockHeader, MinerWork, EXTRA_NONCE_SIZE, TXS_MERKLE_ROOT_VERSION};
+    use crate::block::{HEADER_EXTRA_NONCE_OFFSET, MINER_WORK_EXTRA_NONCE_OFFSET};
 
     #[test]
     fn test_block_template() {
@@ -321,6 +378,75 @@ mod tests {
         assert!(header.hash() == deserialized.hash());
     }
 
+    #[test]
+    fn test_header_with_txs_merkle_root() {
+        let miner = KeyPair::new().get_public_key().compress();
+        let txs: IndexSet<Hash> = (1..=3u8).map(|i| Hash::new([i; 32])).collect();
+
+        // Empty block
+        let mut header = BlockHeader::new(TXS_MERKLE_ROOT_VERSION, 1, 0, IndexSet::from([Hash::zero()]), [0u8; 32], miner.clone(), IndexSet::new());
+        assert_eq!(header.get_txs_merkle_root(), Some(&Hash::zero()));
+        assert!(header.is_txs_merkle_root_valid());
+
+        header.set_txs_hashes(txs.clone());
+        assert_eq!(header.get_txs_merkle_root(), Some(&compute_txs_merkle_root(&txs)));
+        assert_eq!(header.get_txs_hash(), compute_txs_merkle_root(&txs));
+
+        let serialized = header.to_bytes();
+        assert_eq!(serialized.len(), header.size());
+        let deserialized = BlockHeader::from_bytes(&serialized).unwrap();
+        assert_eq!(header.hash(), deserialized.hash());
+        assert!(deserialized.is_txs_merkle_root_valid());
+
+        // TXs hashes modified without updating the root
+        header.txs_hashes.insert(Hash::max());
+        assert!(!header.is_txs_merkle_root_valid());
+        let deserialized = BlockHeader::from_bytes(&header.to_bytes()).unwrap();
+        assert!(!deserialized.is_txs_merkle_root_valid());
+        header.refresh_txs_merkle_root();
+        assert!(header.is_txs_merkle_root_valid());
+
+        // Version 0 doesn't have it
+        let header = BlockHeader::new(0, 1, 0, IndexSet::from([Hash::zero()]), [0u8; 32], miner, txs);
+        assert!(header.get_txs_merkle_root().is_none());
+        assert!(header.is_txs_merkle_root_valid());
+        assert_eq!(header.to_bytes().len(), header.size());
+    }
+
+    #[test]
+    fn test_extra_nonce_serialization() {
+        let miner = KeyPair::new().get_public_key().compress();
+        let extra_nonce: [u8; EXTRA_NONCE_SIZE] = std::array::from_fn(|i| i as u8 + 1);
+        for version in [0, TXS_MERKLE_ROOT_VERSION] {
+            let header = BlockHeader::new(version, 1, 0, IndexSet::from([Hash::zero()]), extra_nonce, miner.clone(), IndexSet::new());
+            let serialized = header.to_bytes();
+            assert_eq!(&serialized[HEADER_EXTRA_NONCE_OFFSET..HEADER_EXTRA_NONCE_OFFSET + EXTRA_NONCE_SIZE], &extra_nonce);
+
+            let deserialized = BlockHeader::from_bytes(&serialized).unwrap();
+            assert_eq!(deserialized.get_extra_nonce(), &extra_nonce);
+            assert_eq!(header.hash(), deserialized.hash());
+
+            let work = MinerWork::from_block(header).to_bytes();
+            assert_eq!(&work[MINER_WORK_EXTRA_NONCE_OFFSET..MINER_WORK_EXTRA_NONCE_OFFSET + EXTRA_NONCE_SIZE], &extra_nonce);
+        }
+    }
+
+    #[test]
+    fn test_extra_nonce_changes_pow_hash() {
+        let miner = KeyPair::new().get_public_key().compress();
+        let header = BlockHeader::new(TXS_MERKLE_ROOT_VERSION, 1, 0, IndexSet::from([Hash::zero()]), [0u8; EXTRA_NONCE_SIZE], miner, IndexSet::new());
+
+        // Pools splice the worker value directly in the template bytes
+        let mut bytes = header.to_bytes();
+        bytes[HEADER_EXTRA_NONCE_OFFSET + EXTRA_NONCE_SIZE - 1] = 1;
+        let worker_header = BlockHeader::from_bytes(&bytes).unwrap();
+
+        // Same work hash, only the extra nonce differs
+        assert_eq!(header.get_work_hash(), worker_header.get_work_hash());
+        assert_ne!(header.hash(), worker_header.hash());
+        assert_ne!(header.get_pow_hash().unwrap(), worker_header.get_pow_hash().unwrap());
+    }
+
     #[test]
     fn test_block_template_from_hex() {
         let serialized = "00000000000000002d0000018f1cbd697000000000000000000eded85557e887b45989a727b6786e1bd250de65042d9381822fa73d01d2c4ff01d3a0154853dbb01dc28c9102e9d94bea355b8ee0d82c3e078ac80841445e86520000d67ad13934337b85c34985491c437386c95de0d97017131088724cfbedebdc55".to_owned();
@@ -0,0 +1,255 @@
+use std::borrow::Cow;
+use serde::{Deserialize, Serialize};
+use crate::{
+    crypto::{hash, Hash, HASH_SIZE},
+    serializer::Serializer
+};
+
+// Leaves and internal nodes are hashed with a different prefix
+// so an internal node can't be given as a leaf in a proof
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+// Hash an element added to the tree
+fn hash_leaf(leaf: &Hash) -> Hash {
+    let mut bytes = [LEAF_PREFIX; 1 + HASH_SIZE];
+    bytes[1..].copy_from_slice(leaf.as_bytes());
+    hash(&bytes)
+}
+
+// Hash two nodes of the tree together
+fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+    let mut bytes = [NODE_PREFIX; 1 + HASH_SIZE * 2];
+    bytes[1..1 + HASH_SIZE].copy_from_slice(left.as_bytes());
+    bytes[1 + HASH_SIZE..].copy_from_slice(right.as_bytes());
+    hash(&bytes)
+}
+
+// This builder is used to build a merkle tree from a list of hashes
+// It uses a bottom-up approach to build the tree
+// The tree is built by taking pairs of hashes and hashing them together
+// The resulting hash is then added to the list of hashes
+// This process is repeated until there is only one hash left
+pub struct MerkleBuilder<'a> {
+    hashes: Vec<Cow<'a, Hash>>
+}
+
+impl<'a> MerkleBuilder<'a> {
+    // Create a new MerkleBuilder
+    pub fn new() -> Self {
+        MerkleBuilder {
+            hashes: Vec::new()
+        }
+    }
+
+    // Create a new MerkleBuilder with a given capacity
+    pub fn with_capacity(capacity: usize) -> Self {
+        MerkleBuilder {
+            hashes: Vec::with_capacity(capacity)
+        }
+    }
+
+    // Create a new MerkleBuilder from an iterator of hashes
+    pub fn from_iter<I>(iter: I) -> Self
+        where I: IntoIterator<Item = &'a Hash>
+    {
+        MerkleBuilder {
+            hashes: iter.into_iter().map(|hash| Cow::Borrowed(hash)).collect()
+        }
+    }
+
+    // Add a hash to the list of hashes
+    pub fn add<E: Into<Cow<'a, Hash>>>(&mut self, element: E) {
+        self.hashes.push(element.into());
+    }
+
+    // Add an element by hashing and adding it to the list of hashes
+    pub fn add_element<S: Serializer>(&mut self, element: &S) {
+        self.hashes.push(Cow::Owned(hash(&element.to_bytes())));
+    }
+
+    /// Add a byte array to the list of hashes
+    pub fn add_bytes(&mut self, bytes: &[u8]) {
+        self.hashes.push(Cow::Owned(hash(bytes)));
+    }
+
+    // Convert a byte array of HASH_SIZE to a Hash and add it to the list of hashes
+    pub fn add_as_hash(&mut self, bytes: [u8; HASH_SIZE]) {
+        self.hashes.push(Cow::Owned(Hash::new(bytes)));
+    }
+
+    // Check if no hash was added yet
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    // Replace the hashes added by the leaves of the tree
+    fn hash_leaves(&mut self) {
+        self.hashes = self.hashes.iter()
+            .map(|leaf| Cow::Owned(hash_leaf(leaf)))
+            .collect();
+    }
+
+    // Replace the hashes by the next level of the tree
+    // The last hash is paired with itself if the level has an odd count
+    fn next_level(&mut self) {
+        self.hashes = self.hashes.chunks(2)
+            .map(|pair| Cow::Owned(hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0]))))
+            .collect();
+    }
+
+    // Build the merkle tree and return the root hash
+    pub fn build(&mut self) -> Hash {
+        self.hash_leaves();
+        while self.hashes.len() > 1 {
+            self.next_level();
+        }
+        debug_assert!(self.hashes.len() == 1);
+        self.hashes.remove(0).into_owned()
+    }
+
+    // Verify the merkle tree with a given root hash
+    pub fn verify(&mut self, root: &Hash) -> bool {
+        self.build() == *root
+    }
+}
+
+// Compute the merkle root of the ordered TXs hashes of a block
+// A block without transactions has the zero hash as root
+pub fn compute_txs_merkle_root<'a, I: IntoIterator<Item = &'a Hash>>(txs_hashes: I) -> Hash {
+    let mut builder = MerkleBuilder::from_iter(txs_hashes);
+    if builder.is_empty() {
+        return Hash::zero()
+    }
+
+    builder.build()
+}
+
+// Proof of inclusion of a transaction in the TXs merkle root of a block
+// It can be verified offline by a light client having only the block header
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof {
+    // Position of the transaction in the block
+    pub index: usize,
+    // Sibling hashes from the leaf to the root
+    pub path: Vec<Hash>
+}
+
+impl MerkleProof {
+    // Build the proof for the transaction at this index
+    // Returns None if the index is out of bounds
+    pub fn new(txs_hashes: &[Hash], index: usize) -> Option<Self> {
+        if index >= txs_hashes.len() {
+            return None
+        }
+
+        let mut path = Vec::new();
+        let mut builder = MerkleBuilder::from_iter(txs_hashes);
+        builder.hash_leaves();
+        let mut position = index;
+        while builder.hashes.len() > 1 {
+            let sibling = builder.hashes.get(position ^ 1).unwrap_or(&builder.hashes[position]);
+            path.push(sibling.as_ref().clone());
+            builder.next_level();
+            position /= 2;
+        }
+
+        Some(Self { index, path })
+    }
+
+    // Compute the root from the transaction hash and the path
+    pub fn compute_root(&self, tx_hash: &Hash) -> Hash {
+        let mut position = self.index;
+        let mut current = hash_leaf(tx_hash);
+        for sibling in &self.path {
+            current = if position % 2 == 0 {
+                hash_pair(&current, sibling)
+            } else {
+                hash_pair(sibling, &current)
+            };
+            position /= 2;
+        }
+
+        current
+    }
+
+    // Verify that the transaction is included in the TXs merkle root
+    pub fn verify(&self, tx_hash: &Hash, root: &Hash) -> bool {
+        self.compute_root(tx_hash) == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txs(count: u8) -> Vec<Hash> {
+        (0..count).map(|i| Hash::new([i + 1; HASH_SIZE])).collect()
+    }
+
+    #[test]
+    fn test_empty_and_single_root() {
+        assert_eq!(compute_txs_merkle_root(&Vec::new()), Hash::zero());
+        assert!(MerkleProof::new(&[], 0).is_none());
+
+        // A single transaction is the only leaf
+        let hashes = txs(1);
+        let root = compute_txs_merkle_root(&hashes);
+        assert_eq!(root, hash_leaf(&hashes[0]));
+        let proof = MerkleProof::new(&hashes, 0).unwrap();
+        assert!(proof.path.is_empty());
+        assert!(proof.verify(&hashes[0], &root));
+        assert!(!proof.verify(&root, &root));
+    }
+
+    #[test]
+    fn test_proofs_first_middle_last() {
+        for count in [2, 5, 8] {
+            let hashes = txs(count);
+            let root = compute_txs_merkle_root(&hashes);
+            for index in [0, hashes.len() / 2, hashes.len() - 1] {
+                let proof = MerkleProof::new(&hashes, index).unwrap();
+                assert!(proof.verify(&hashes[index], &root), "proof of tx {} in {} txs", index, count);
+                // A proof is only valid for its own transaction
+                assert!(!proof.verify(&hashes[(index + 1) % hashes.len()], &root));
+            }
+            assert!(MerkleProof::new(&hashes, hashes.len()).is_none());
+        }
+    }
+
+    #[test]
+    fn test_tampered_proof() {
+        let hashes = txs(5);
+        let root = compute_txs_merkle_root(&hashes);
+        let proof = MerkleProof::new(&hashes, 2).unwrap();
+
+        let mut tampered = proof.clone();
+        tampered.path[1] = Hash::max();
+        assert!(!tampered.verify(&hashes[2], &root));
+
+        // Wrong position in the block
+        let mut tampered = proof.clone();
+        tampered.index = 3;
+        assert!(!tampered.verify(&hashes[2], &root));
+
+        // Truncated path
+        let mut tampered = proof;
+        tampered.path.pop();
+        assert!(!tampered.verify(&hashes[2], &root));
+    }
+
+    #[test]
+    fn test_internal_node_is_not_a_leaf() {
+        let hashes = txs(4);
+        let root = compute_txs_merkle_root(&hashes);
+
+        // Parent of the two first leaves, proven with the path of the second half
+        let node = hash_pair(&hash_leaf(&hashes[0]), &hash_leaf(&hashes[1]));
+        let proof = MerkleProof::new(&hashes, 0).unwrap();
+        let forged = MerkleProof { index: 0, path: proof.path[1..].to_vec() };
+        assert!(!forged.verify(&node, &root));
+
+        // The node is in the tree, only the leaf prefix rejects it
+        assert_eq!(hash_pair(&node, &proof.path[1]), root);
+    }
+}
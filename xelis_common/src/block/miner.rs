@@ -14,7 +14,6 @@ use crate::{
         XelisHashError
     },
     serializer::{Reader, ReaderError, Serializer, Writer},
-    time::TimestampMillis,
 };
 
 use super::{BlockHeader, BLOCK_WORK_SIZE, EXTRA_NONCE_SIZE};
@@ -23,7 +22,7 @@ use super::{BlockHeader, BLOCK_WORK_SIZE, EXTRA_NONCE_SIZE};
 #[derive(Clone, Debug)]
 pub struct MinerWork<'a> {
     header_work_hash: Hash, // include merkle tree of tips, txs, and height (immutable)
-    timestamp: TimestampMillis, // miners can update timestamp to keep it up-to-date
+    timestamp: u64, // miners can update timestamp to keep it up-to-date
     nonce: u64,
     miner: Option<Cow<'a, PublicKey>>,
     // Extra nonce so miner can write anything
@@ -34,7 +33,7 @@ pub struct MinerWork<'a> {
 }
 
 impl<'a> MinerWork<'a> {
-    pub fn new(header_work_hash: Hash, timestamp: TimestampMillis) -> Self {
+    pub fn new(header_work_hash: Hash, timestamp: u64) -> Self {
         Self {
             header_work_hash,
             timestamp,
@@ -85,7 +84,7 @@ impl<'a> MinerWork<'a> {
     }
 
     #[inline(always)]
-    pub fn set_timestamp(&mut self, timestamp: TimestampMillis) -> Result<(), XelisHashError> {
+    pub fn set_timestamp(&mut self, timestamp: u64) -> Result<(), XelisHashError> {
         self.timestamp = timestamp;
         if let Some(cache) = &mut self.cache {
             cache.as_mut_slice()?[32..40].copy_from_slice(&self.timestamp.to_be_bytes());
@@ -119,7 +118,7 @@ impl<'a> MinerWork<'a> {
     }
 
     #[inline(always)]
-    pub fn take(self) -> (Hash, TimestampMillis, u64, Option<Cow<'a, PublicKey>>, [u8; EXTRA_NONCE_SIZE]) {
+    pub fn take(self) -> (Hash, u64, u64, Option<Cow<'a, PublicKey>>, [u8; EXTRA_NONCE_SIZE]) {
         (self.header_work_hash, self.timestamp, self.nonce, self.miner, self.extra_nonce)
     }
 }
@@ -1,16 +1,25 @@
 mod header;
 mod block;
 mod miner;
+mod merkle;
 
 pub use header::BlockHeader;
 pub use block::Block;
 pub use miner::MinerWork;
+pub use merkle::{compute_txs_merkle_root, MerkleBuilder, MerkleProof};
 
 use crate::crypto::{Hash, HASH_SIZE};
 
 pub const EXTRA_NONCE_SIZE: usize = 32;
 pub const HEADER_WORK_SIZE: usize = 73;
 pub const BLOCK_WORK_SIZE: usize = 112; // 32 + 8 + 8 + 32 + 32 = 112
+// Offset of the extra nonce in the serialized block header (version + height + timestamp + nonce)
+// Pools can write a different value per worker at this offset of the block template
+pub const HEADER_EXTRA_NONCE_OFFSET: usize = 25;
+// Offset of the extra nonce in the serialized miner work (header work hash + timestamp + nonce)
+pub const MINER_WORK_EXTRA_NONCE_OFFSET: usize = 48;
+// First block version committing to the TXs merkle root
+pub const TXS_MERKLE_ROOT_VERSION: u8 = 1;
 
 // Get combined hash for tips
 // This is used to get a hash that is unique for a set of tips
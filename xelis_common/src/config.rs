@@ -12,6 +12,9 @@ pub const FEE_PER_ACCOUNT_CREATION: u64 = 100000;
 // 0.00005000 XEL per KB
 // Each transfer has a overhead of 5000 atomic units
 pub const FEE_PER_TRANSFER: u64 = 5000;
+// 0.00000010 XEL per byte of transaction extra data
+// Paid on top of the size based fee to discourage using the chain as storage
+pub const FEE_PER_EXTRA_DATA_BYTE: u64 = 10;
 
 // 8 decimals numbers
 pub const COIN_DECIMALS: u8 = 8;
@@ -20,6 +23,12 @@ pub const COIN_VALUE: u64 = 10u64.pow(COIN_DECIMALS as u32);
 // 18.4M full coin
 pub const MAXIMUM_SUPPLY: u64 = 18_400_000 * COIN_VALUE;
 
+// 10 XEL burned to register a new asset
+// Prevent spamming the chain with useless assets
+pub const ASSET_CREATION_BURN_AMOUNT: u64 = 10 * COIN_VALUE;
+// Maximum decimals allowed for a new asset
+pub const MAX_ASSET_DECIMALS: u8 = 18;
+
 // Addresses format
 // mainnet prefix address
 pub const PREFIX_ADDRESS: &str = "xel";
@@ -11,6 +11,7 @@ use crate::{
 };
 use super::{
     bech32::{Bech32Error, encode, convert_bits, decode},
+    elgamal::RISTRETTO_COMPRESSED_SIZE,
     PublicKey
 };
 use core::fmt;
@@ -118,7 +119,7 @@ impl Address {
     fn decompress(bytes: &[u8], hrp: &str) -> Result<Self, ReaderError> {
         let mut reader = Reader::new(bytes);
         let mainnet = hrp == PREFIX_ADDRESS;
-        let key = PublicKey::read(&mut reader)?;
+        let key = PublicKey::from_bytes_checked(reader.read_bytes_ref(RISTRETTO_COMPRESSED_SIZE)?)?;
         let addr_type = AddressType::read(&mut reader)?;
         Ok(Self::new(mainnet, addr_type, key))
     }
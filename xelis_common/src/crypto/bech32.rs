@@ -5,6 +5,12 @@ use thiserror::Error;
 const CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
 const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
 const SEPARATOR: char = ':';
+// Constants XORed in the checksum, switching the format only requires to change CHECKSUM_CONST
+pub const BECH32_CONST: u32 = 1;
+pub const BECH32M_CONST: u32 = 0x2bc830a3;
+pub const CHECKSUM_CONST: u32 = BECH32_CONST;
+// Maximum length of a string where a typo is searched on invalid checksum
+const MAX_TYPO_SEARCH_LENGTH: usize = 90;
 
 #[derive(Error, Debug)]
 pub enum Bech32Error {
@@ -20,6 +26,10 @@ pub enum Bech32Error {
     HrpInvalidCharacter(u8), // character as byte
     #[error("Mix case is not allowed in human readable part")]
     HrpMixCase,
+    #[error("Mixed case is not allowed")]
+    MixedCase,
+    #[error("Invalid character '{}' at position {}", _0, _1)]
+    InvalidCharacter(char, usize), // character, position in the string
     #[error("Invalid value: {}, max is {}", _0, _1)]
     InvalidValue(u8, usize), // value, max
     #[error("Separator not found")]
@@ -32,10 +42,22 @@ pub enum Bech32Error {
     InvalidPrefix(String, String),
     #[error("Invalid checksum")]
     InvalidChecksum,
+    #[error("Invalid checksum, probable typo at position {}", _0)]
+    InvalidChecksumAt(usize), // position in the string
     #[error("Invalid index '{}': not found", _0)]
     InvalidIndex(usize)
 }
 
+impl Bech32Error {
+    // Position in the string of the character responsible for the error, if known
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            Self::InvalidCharacter(_, position) | Self::InvalidChecksumAt(position) => Some(*position),
+            _ => None
+        }
+    }
+}
+
 fn polymod(values: &[u8]) -> u32 {
     let mut chk: u32 = 1;
     for value in values {
@@ -66,7 +88,25 @@ fn hrp_expand(hrp: &String) -> Vec<u8> {
 pub fn verify_checksum(hrp: &String, data: &[u8]) -> bool {
     let mut vec = hrp_expand(hrp);
     vec.extend(data);
-    return polymod(&vec) == 1;
+    return polymod(&vec) == CHECKSUM_CONST;
+}
+
+// Search a single character substitution that makes the checksum valid
+// Returns its index in the data part
+fn locate_typo(hrp: &String, data: &mut [u8]) -> Option<usize> {
+    for i in 0..data.len() {
+        let original = data[i];
+        for value in (0..CHARSET.len() as u8).filter(|v| *v != original) {
+            data[i] = value;
+            if verify_checksum(hrp, data) {
+                data[i] = original;
+                return Some(i)
+            }
+        }
+        data[i] = original;
+    }
+
+    None
 }
 
 pub fn create_checksum(hrp: &String, data: &[u8]) -> [u8; 6] {
@@ -75,7 +115,7 @@ pub fn create_checksum(hrp: &String, data: &[u8]) -> [u8; 6] {
     values.extend(data);
     let mut result: [u8; 6] = [0; 6];
     values.extend(&result);
-    let polymod = polymod(&values) ^ 1;
+    let polymod = polymod(&values) ^ CHECKSUM_CONST;
 
     for i in 0..6 {
         result[i] = (polymod >> (5 * (5 - i)) & 31) as u8
@@ -153,32 +193,37 @@ pub fn encode(mut hrp: String, data: &[u8]) -> Result<String, Bech32Error> {
     Ok(string)
 }
 
+// Decode a bech32 string, errors contain the position of the invalid character when known
 pub fn decode(bech: &String) -> Result<(String, Vec<u8>), Bech32Error> {
-    if bech.to_uppercase() != *bech && bech.to_lowercase() != *bech {
-        return Err(Bech32Error::HrpMixCase)
+    // Only printable ASCII characters are allowed, this also make byte and char positions the same
+    if let Some((i, c)) = bech.char_indices().find(|(_, c)| !(c.is_ascii() && (33..=126).contains(&(*c as u8)))) {
+        return Err(Bech32Error::InvalidCharacter(c, i))
     }
 
+    let lowercase = bech.to_lowercase();
+    if bech.to_uppercase() != *bech && lowercase != *bech {
+        return Err(Bech32Error::MixedCase)
+    }
+    let bech = &lowercase;
+
     let pos = bech.rfind(SEPARATOR).ok_or(Bech32Error::SeparatorNotFound)?;
     if pos < 1 || pos + 7 > bech.len() {
         return Err(Bech32Error::SeparatorInvalidPosition(pos))
     }
 
     let hrp = bech[0..pos].to_owned();
-    for value in hrp.bytes() {
-        if value < 33 || value > 126 {
-            return Err(Bech32Error::HrpInvalidCharacter(value))
-        }
-    }
-
-    let mut data: Vec<u8> = vec![];
-    for i in pos + 1..bech.len() {
-        let c = bech.chars().nth(i).ok_or(Bech32Error::InvalidIndex(i))?;
-        let value = CHARSET.find(c).ok_or(Bech32Error::HrpInvalidCharacter(c as u8))?;
-
+    let mut data: Vec<u8> = Vec::with_capacity(bech.len() - pos - 1);
+    for (i, c) in bech.char_indices().skip(pos + 1) {
+        let value = CHARSET.find(c).ok_or(Bech32Error::InvalidCharacter(c, i))?;
         data.push(value as u8);
     }
 
     if !verify_checksum(&hrp, &data) {
+        if bech.len() <= MAX_TYPO_SEARCH_LENGTH {
+            if let Some(i) = locate_typo(&hrp, &mut data) {
+                return Err(Bech32Error::InvalidChecksumAt(pos + 1 + i))
+            }
+        }
         return Err(Bech32Error::InvalidChecksum)
     }
 
@@ -187,4 +232,82 @@ pub fn decode(bech: &String) -> Result<(String, Vec<u8>), Bech32Error> {
     }
 
     Ok((hrp, data))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, RngCore};
+    use super::*;
+
+    // Build a valid string with the data part from the charset characters
+    fn encode_chars(hrp: &str, chars: &str) -> String {
+        let data: Vec<u8> = chars.chars().map(|c| CHARSET.find(c).unwrap() as u8).collect();
+        encode(hrp.to_owned(), &data).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_random_lengths() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let mut payload = vec![0u8; rng.gen_range(0..128)];
+            rng.fill_bytes(&mut payload);
+
+            let bits = convert_bits(&payload, 8, 5, true).unwrap();
+            let encoded = encode("xel".to_owned(), &bits).unwrap();
+            let (hrp, decoded) = decode(&encoded).unwrap();
+            assert_eq!(hrp, "xel");
+            assert_eq!(convert_bits(&decoded, 5, 8, false).unwrap(), payload);
+
+            // Uppercase only is valid too
+            let (_, decoded) = decode(&encoded.to_uppercase()).unwrap();
+            assert_eq!(convert_bits(&decoded, 5, 8, false).unwrap(), payload);
+        }
+    }
+
+    #[test]
+    fn test_mixed_case() {
+        let encoded = encode_chars("xel", "qpzry9x8");
+        let mixed = format!("XEL{}", &encoded[3..]);
+        assert!(matches!(decode(&mixed), Err(Bech32Error::MixedCase)));
+    }
+
+    #[test]
+    fn test_invalid_characters() {
+        let encoded = encode_chars("xel", "qpzry9x8");
+        // Characters excluded from the charset to avoid confusions
+        for c in ['1', 'b', 'i', 'o'] {
+            let mut invalid = encoded.clone();
+            invalid.replace_range(6..7, &c.to_string());
+            assert!(matches!(decode(&invalid), Err(Bech32Error::InvalidCharacter(got, 6)) if got == c));
+        }
+
+        // Outside of the printable ASCII range
+        let invalid = format!("x l{}", &encoded[3..]);
+        assert!(matches!(decode(&invalid), Err(Bech32Error::InvalidCharacter(' ', 1))));
+        let invalid = format!("xé{}", &encoded[2..]);
+        assert!(matches!(decode(&invalid), Err(Bech32Error::InvalidCharacter('é', 1))));
+    }
+
+    #[test]
+    fn test_invalid_separator() {
+        assert!(matches!(decode(&"xelqpzry9x8".to_owned()), Err(Bech32Error::SeparatorNotFound)));
+        assert!(matches!(decode(&":qpzry9x8".to_owned()), Err(Bech32Error::SeparatorInvalidPosition(0))));
+        assert!(matches!(decode(&"xel:qpzry".to_owned()), Err(Bech32Error::SeparatorInvalidPosition(3))));
+    }
+
+    #[test]
+    fn test_checksum_typo_position() {
+        let encoded = encode_chars("xel", "qpzry9x8gf2tvdw0s3jn54khce6mua7l");
+        let position = 12;
+        let mut typo = encoded.clone();
+        let replacement = if &encoded[position..position + 1] == "q" { "p" } else { "q" };
+        typo.replace_range(position..position + 1, replacement);
+        assert!(matches!(decode(&typo), Err(Bech32Error::InvalidChecksumAt(12))));
+        assert_eq!(decode(&typo).unwrap_err().to_string(), "Invalid checksum, probable typo at position 12");
+
+        // Two typos can't be located
+        let other = if &typo[position + 1..position + 2] == "q" { "p" } else { "q" };
+        typo.replace_range(position + 1..position + 2, other);
+        assert!(matches!(decode(&typo), Err(Bech32Error::InvalidChecksum)));
+    }
+}
@@ -1,4 +1,4 @@
-use curve25519_dalek::{ristretto::CompressedRistretto, Scalar};
+use curve25519_dalek::{ristretto::CompressedRistretto, traits::IsIdentity, Scalar};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use crate::{api::DataElement, crypto::{Address, AddressType}, serializer::{Reader, ReaderError, Serializer, Writer}};
@@ -28,6 +28,16 @@ impl SerializableCompressedPoint for CompressedRistretto {
 #[error("point decompression failed")]
 pub struct DecompressionError;
 
+#[derive(Error, Clone, Debug, Eq, PartialEq)]
+pub enum PublicKeyError {
+    #[error("invalid public key size: {0} bytes")]
+    InvalidSize(usize),
+    #[error("public key is not a canonical encoding of a valid point")]
+    NonCanonical,
+    #[error("public key is the identity point")]
+    Identity
+}
+
 // A Pedersen commitment compressed to 32 bytes
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CompressedCommitment(CompressedRistretto);
@@ -44,6 +54,8 @@ pub struct CompressedCiphertext {
 }
 
 // A compressed public key using only 32 bytes
+// Its bytes are not validated when read, the keys stored or in blocks may use any encoding
+// The addresses parsed are validated, see from_bytes_checked, the other keys with validate
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CompressedPublicKey(CompressedRistretto);
 
@@ -149,6 +161,26 @@ impl CompressedPublicKey {
         Self(point)
     }
 
+    // Parse a public key, it must decode to a valid point other than the identity
+    // Ristretto decoding rejects all the non-canonical encodings, and as its group
+    // has a prime order, the identity is the only low-order point to reject
+    pub fn from_bytes_checked(bytes: &[u8]) -> Result<Self, PublicKeyError> {
+        let point = CompressedRistretto::from_slice(bytes)
+            .map_err(|_| PublicKeyError::InvalidSize(bytes.len()))?;
+
+        let decompressed = point.decompress().ok_or(PublicKeyError::NonCanonical)?;
+        if decompressed.is_identity() {
+            return Err(PublicKeyError::Identity)
+        }
+
+        Ok(Self(point))
+    }
+
+    // Verify that a key read without validation is a valid point other than the identity
+    pub fn validate(&self) -> Result<(), PublicKeyError> {
+        Self::from_bytes_checked(self.as_bytes()).map(|_| ())
+    }
+
     // Serialized public key
     pub fn as_bytes(&self) -> &[u8; 32] {
         &self.0.as_bytes()
@@ -238,8 +270,79 @@ impl Serializer for Scalar {
 
 #[cfg(test)]
 mod tests {
+    use crate::crypto::KeyPair;
     use super::*;
 
+    // Encodings rejected by the Ristretto decoding, from the ristretto255 test vectors
+    const NON_CANONICAL_ENCODINGS: [&str; 8] = [
+        // Non-canonical field encodings
+        "00ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+        "f3ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+        "edffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+        // Negative field elements
+        "0100000000000000000000000000000000000000000000000000000000000000",
+        "01ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+        // Non-square x^2
+        "26948d35ca62e643e26a83177332e6b6afeb9d08e4268b650f1f5bbd8d81d371",
+        // s = -1, which causes y = 0
+        "ecffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f"
+    ];
+
+    fn identity_key() -> CompressedPublicKey {
+        CompressedPublicKey::new(CompressedRistretto::default())
+    }
+
+    #[test]
+    fn test_public_key_bad_encodings() {
+        for encoding in NON_CANONICAL_ENCODINGS {
+            let bytes = hex::decode(encoding).unwrap();
+            assert_eq!(CompressedPublicKey::from_bytes_checked(&bytes), Err(PublicKeyError::NonCanonical), "{} accepted", encoding);
+
+            // Still readable from the storage or a block, validated by the daemon verification
+            let key = CompressedPublicKey::from_bytes(&bytes).unwrap();
+            assert_eq!(key.validate(), Err(PublicKeyError::NonCanonical));
+        }
+
+        assert_eq!(CompressedPublicKey::from_bytes_checked(&[0u8; 32]), Err(PublicKeyError::Identity));
+        assert_eq!(CompressedPublicKey::from_bytes(&[0u8; 32]).unwrap(), identity_key());
+        assert_eq!(identity_key().validate(), Err(PublicKeyError::Identity));
+        assert!(KeyPair::new().get_public_key().compress().validate().is_ok());
+        assert_eq!(CompressedPublicKey::from_bytes_checked(&[1u8; 31]), Err(PublicKeyError::InvalidSize(31)));
+        assert!(CompressedPublicKey::from_bytes(&[1u8; 31]).is_err());
+    }
+
+    #[test]
+    fn test_public_key_valid_encoding() {
+        let key = KeyPair::new().get_public_key().compress();
+        assert_eq!(CompressedPublicKey::from_bytes_checked(key.as_bytes()), Ok(key.clone()));
+        assert_eq!(CompressedPublicKey::from_bytes(&key.to_bytes()).unwrap(), key);
+
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(serde_json::from_str::<CompressedPublicKey>(&json).unwrap(), key);
+    }
+
+    #[test]
+    fn test_public_key_rejected_when_parsed() {
+        // Keys already accepted stay readable in the RPC responses
+        let json = serde_json::to_string(&identity_key()).unwrap();
+        assert_eq!(serde_json::from_str::<CompressedPublicKey>(&json).unwrap(), identity_key());
+
+        // Address encoding doesn't validate the key, its parsing does
+        let address = identity_key().to_address(true).as_string().unwrap();
+        assert!(Address::from_string(&address).is_err());
+        assert!(serde_json::from_str::<Address>(&format!("\"{}\"", address)).is_err());
+
+        for encoding in NON_CANONICAL_ENCODINGS {
+            let key = CompressedPublicKey::from_bytes(&hex::decode(encoding).unwrap()).unwrap();
+            let address = key.to_address(false).as_string().unwrap();
+            assert!(Address::from_string(&address).is_err(), "{} accepted", encoding);
+        }
+
+        let valid = KeyPair::new().get_public_key().compress().to_address(false);
+        assert_eq!(Address::from_string(&valid.as_string().unwrap()).unwrap(), valid);
+    }
+
     #[test]
     fn test_compressed_ciphertext_zero() {
         let ciphertext = Ciphertext::zero();
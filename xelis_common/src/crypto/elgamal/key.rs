@@ -4,11 +4,11 @@ use curve25519_dalek::{
     Scalar
 };
 use rand::rngs::OsRng;
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 use crate::{
     api::DataElement,
     config::MAXIMUM_SUPPLY,
-    crypto::{Address, AddressType},
+    crypto::{hash, Address, AddressType},
     serializer::{
         Reader,
         ReaderError,
@@ -22,6 +22,7 @@ use super::{
     pedersen::{DecryptHandle, PedersenCommitment, PedersenOpening},
     CompressedPublicKey,
     Signature,
+    G,
     H
 };
 
@@ -31,6 +32,9 @@ pub struct PublicKey(RistrettoPoint);
 #[derive(Clone, Zeroize)]
 pub struct PrivateKey(Scalar);
 
+// Domain separator used to derive child private keys
+const CHILD_KEY_DOMAIN: &[u8] = b"xelis-child-key";
+
 #[derive(Clone)]
 pub struct KeyPair {
     public_key: PublicKey,
@@ -50,7 +54,11 @@ impl PublicKey {
         let s = &secret.0;
         assert!(s != &Scalar::ZERO);
 
-        Self(s.invert() * *H)
+        let mut inverted = s.invert();
+        let point = inverted * *H;
+        inverted.zeroize();
+
+        Self(point)
     }
 
     // Encrypt an amount to a Ciphertext
@@ -109,6 +117,21 @@ impl PrivateKey {
         &self.0
     }
 
+    // Derive a child private key for the index provided
+    // Derivation is deterministic, and children can't be linked together without this key
+    pub fn derive_child(&self, index: u32) -> Self {
+        let mut data = Zeroizing::new(Vec::with_capacity(CHILD_KEY_DOMAIN.len() + 32 + 4));
+        data.extend_from_slice(CHILD_KEY_DOMAIN);
+        data.extend_from_slice(self.0.as_bytes());
+        data.extend_from_slice(&index.to_be_bytes());
+
+        let mut bytes = hash(&data).to_bytes();
+        let scalar = Scalar::from_bytes_mod_order(bytes);
+        bytes.zeroize();
+
+        Self::from_scalar(scalar)
+    }
+
     // Decrypt a Ciphertext to a point
     pub fn decrypt_to_point(&self, ciphertext: &Ciphertext) -> RistrettoPoint {
         let commitment = ciphertext.commitment().as_point();
@@ -117,6 +140,12 @@ impl PrivateKey {
         commitment - &(self.0 * handle)
     }
 
+    // Verify that the Ciphertext encrypts the value provided
+    // This is much faster than decrypting it as no ECDLP is needed
+    pub fn verify_plaintext(&self, ciphertext: &Ciphertext, value: u64) -> bool {
+        self.decrypt_to_point(ciphertext) == Scalar::from(value) * G
+    }
+
     // Decode a point to a u64 with precomputed tables
     pub fn decode_point<const L1: usize>(&self, precomputed_tables: &ECDLPTablesFileView<L1>, point: RistrettoPoint) -> Option<u64> {
         ecdlp::decode(precomputed_tables, point, ECDLPArguments::new_with_range(0, MAXIMUM_SUPPLY as i64))
@@ -130,6 +159,14 @@ impl PrivateKey {
     }
 }
 
+// Private key is wiped from memory once dropped
+// KeyPair doesn't need its own implementation as its private key field is dropped with it
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 impl KeyPair {
     // Generate a random new KeyPair
     pub fn new() -> Self {
@@ -165,12 +202,22 @@ impl KeyPair {
         self.private_key.decrypt_to_point(ciphertext)
     }
 
+    pub fn verify_plaintext(&self, ciphertext: &Ciphertext, value: u64) -> bool {
+        self.private_key.verify_plaintext(ciphertext, value)
+    }
+
     // Sign a message with the private key
     pub fn sign(&self, message: &[u8]) -> Signature {
-        let k = Scalar::random(&mut OsRng);
+        let mut k = Scalar::random(&mut OsRng);
         let r = k * *H;
         let e = hash_and_point_to_scalar(&self.public_key.compress(), message, &r);
-        let s = self.private_key.as_scalar().invert() * e + k;
+        let mut inverted = self.private_key.as_scalar().invert();
+        let s = inverted * e + k;
+
+        // Wipe the nonce and the inverted key, they would allow to recover the private key
+        k.zeroize();
+        inverted.zeroize();
+
         Signature::new(s, e)
     }
 
@@ -197,16 +244,53 @@ impl Serializer for PrivateKey {
 
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
         let scalar = Scalar::read(reader)?;
+        // A zero private key is invalid
+        if scalar == Scalar::ZERO {
+            return Err(ReaderError::InvalidValue)
+        }
+
         Ok(PrivateKey::from_scalar(scalar))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::mem::{size_of, MaybeUninit};
     use curve25519_dalek::traits::Identity;
 
     use super::*;
-    use super::super::G;
+
+    // Run the destructor of the value in place and return its memory as left by it
+    fn memory_after_drop<T>(value: T) -> Vec<u8> {
+        let mut slot = MaybeUninit::new(value);
+        unsafe {
+            slot.assume_init_drop();
+            std::slice::from_raw_parts(slot.as_ptr() as *const u8, size_of::<T>()).to_vec()
+        }
+    }
+
+    fn contains_secret(memory: &[u8], secret: &[u8]) -> bool {
+        memory.windows(secret.len()).any(|window| window == secret)
+    }
+
+    #[test]
+    fn test_private_key_zeroized_on_drop() {
+        let private_key = KeyPair::new().split().1;
+        let secret = private_key.as_scalar().to_bytes();
+
+        let memory = memory_after_drop(private_key);
+        assert!(!contains_secret(&memory, &secret));
+        assert!(memory.iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_keypair_zeroized_on_drop() {
+        let keypair = KeyPair::new();
+        let secret = keypair.get_private_key().as_scalar().to_bytes();
+
+        let memory = memory_after_drop(keypair);
+        assert!(!contains_secret(&memory, &secret));
+    }
 
     #[test]
     fn test_signature() {
@@ -231,6 +315,21 @@ mod tests {
         assert_eq!(decrypted, amount * &G);
     }
 
+    #[test]
+    fn test_verify_plaintext() {
+        let keypair = KeyPair::new();
+        let public_key = keypair.get_public_key();
+
+        let ciphertext = public_key.encrypt(Scalar::from(1_000_000u64));
+        assert!(keypair.verify_plaintext(&ciphertext, 1_000_000));
+        assert!(!keypair.verify_plaintext(&ciphertext, 999_999));
+
+        // Balance updated by a transfer of 250
+        let updated = ciphertext + public_key.encrypt(Scalar::from(250u64));
+        assert!(keypair.verify_plaintext(&updated, 1_000_250));
+        assert!(!KeyPair::new().verify_plaintext(&updated, 1_000_250));
+    }
+
     #[test]
     fn test_identity() {
         let keypair = KeyPair::new();
@@ -33,8 +33,7 @@ pub fn hash_and_point_to_scalar(key: &CompressedPublicKey, message: &[u8], point
     hasher.update(message);
     hasher.update(point.compress().as_bytes());
 
-    let hash = hasher.finalize();
-    Scalar::from_bytes_mod_order_wide(&hash.try_into().unwrap())
+    Scalar::from_hash(hasher)
 }
 
 impl Serialize for Signature {
@@ -1,7 +1,6 @@
 use crate::serializer::{Writer, Serializer, ReaderError, Reader};
 use std::{
-    fmt::{Display, Error, Formatter},
-    convert::TryInto,
+    fmt::{Display, Error, Formatter, LowerHex},
     hash::Hasher,
     borrow::Cow,
 };
@@ -19,7 +18,11 @@ pub use xelis_hash::{
 };
 
 pub const HASH_SIZE: usize = 32; // 32 bytes / 256 bits
+// Size of the hex representation of a hash, leading zeros included
+pub const HASH_HEX_SIZE: usize = HASH_SIZE * 2;
 
+// Hashes are ordered by their big-endian bytes
+// So the lexicographic order of the bytes (used by storage keys) is also their numeric order
 #[derive(Eq, PartialEq, PartialOrd, Ord, Clone, Debug)]
 pub struct Hash([u8; HASH_SIZE]);
 
@@ -32,6 +35,7 @@ impl Hash {
         Hash::new([0; HASH_SIZE])
     }
 
+    // Highest hash possible, used as inclusive upper bound in range scans
     pub const fn max() -> Self {
         Hash::new([u8::MAX; HASH_SIZE])
     }
@@ -44,9 +48,23 @@ impl Hash {
         self.0
     }
 
+    // Always HASH_HEX_SIZE lowercase characters
     pub fn to_hex(&self) -> String {
         hex::encode(self.0)
     }
+
+    // Parse a hash from its hex representation
+    // Only exactly HASH_HEX_SIZE hex characters are accepted
+    pub fn from_hex<S: AsRef<str>>(hex: S) -> Result<Self, ReaderError> {
+        let hex = hex.as_ref();
+        if hex.len() != HASH_HEX_SIZE {
+            return Err(ReaderError::InvalidSize)
+        }
+
+        let mut bytes = [0; HASH_SIZE];
+        hex::decode_to_slice(hex, &mut bytes).map_err(|_| ReaderError::InvalidHex)?;
+        Ok(Hash(bytes))
+    }
 }
 
 pub fn pow_hash(work: &[u8]) -> Result<Hash, XelisHashError> {
@@ -93,7 +111,14 @@ impl AsRef<Hash> for Hash {
 
 impl Display for Hash {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        write!(f, "{}", &self.to_hex())
+        f.write_str(&self.to_hex())
+    }
+}
+
+// Same as Display, width and alternate flags are ignored to always have HASH_HEX_SIZE chars
+impl LowerHex for Hash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.write_str(&self.to_hex())
     }
 }
 
@@ -109,13 +134,10 @@ impl<'a> Deserialize<'a> for Hash {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where D: serde::Deserializer<'a> {
         let hex = String::deserialize(deserializer)?;
-        if hex.len() != HASH_SIZE * 2 {
-            return Err(SerdeError::custom("Invalid hex length"))
-        }
-
-        let decoded_hex = hex::decode(hex).map_err(SerdeError::custom)?;
-        let bytes: [u8; 32] = decoded_hex.try_into().map_err(|_| SerdeError::custom("Could not transform hex to bytes array for Hash"))?;
-        Ok(Hash::new(bytes))
+        Hash::from_hex(hex).map_err(|e| match e {
+            ReaderError::InvalidSize => SerdeError::custom("Invalid hex length"),
+            e => SerdeError::custom(e)
+        })
     }
 }
 
@@ -149,4 +171,66 @@ impl<'a> Into<Cow<'a, Hash>> for &'a Hash {
     fn into(self) -> Cow<'a, Hash> {
         Cow::Borrowed(self)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_round_trip() {
+        // Leading zeros are kept
+        let mut bytes = [0; HASH_SIZE];
+        bytes[HASH_SIZE - 1] = 1;
+        let hash = Hash::new(bytes);
+        let hex = hash.to_string();
+        assert_eq!(hex.len(), HASH_HEX_SIZE);
+        assert_eq!(hex, format!("{}1", "0".repeat(HASH_HEX_SIZE - 1)));
+        assert_eq!(format!("{:x}", hash), hex);
+        assert_eq!(format!("{:>80x}", hash), hex);
+        assert_eq!(Hash::from_hex(&hex).unwrap(), hash);
+
+        assert_eq!(Hash::zero().to_hex().len(), HASH_HEX_SIZE);
+        assert_eq!(Hash::from_hex(Hash::max().to_hex()).unwrap(), Hash::max());
+    }
+
+    #[test]
+    fn test_strict_from_hex() {
+        let hex = Hash::max().to_hex();
+        assert!(matches!(Hash::from_hex(&hex[1..]), Err(ReaderError::InvalidSize)));
+        assert!(matches!(Hash::from_hex(format!("{}00", hex)), Err(ReaderError::InvalidSize)));
+        assert!(matches!(Hash::from_hex(format!("{}zz", &hex[2..])), Err(ReaderError::InvalidHex)));
+        assert!(matches!(Hash::from_hex(""), Err(ReaderError::InvalidSize)));
+
+        // Same rules through serde
+        assert!(serde_json::from_str::<Hash>(&format!("\"{}00\"", hex)).is_err());
+        assert_eq!(serde_json::from_str::<Hash>(&format!("\"{}\"", hex)).unwrap(), Hash::max());
+    }
+
+    #[test]
+    fn test_ordering_is_numeric() {
+        let mut low = [0; HASH_SIZE];
+        low[HASH_SIZE - 1] = 0xff;
+        let mut high = [0; HASH_SIZE];
+        high[0] = 1;
+        let (low, high) = (Hash::new(low), Hash::new(high));
+
+        // Most significant byte first
+        assert!(low < high);
+        assert!(Hash::zero() < low);
+        assert!(high < Hash::max());
+
+        // Lexicographic order of bytes and hex is the same
+        let mut hashes = vec![Hash::max(), high.clone(), Hash::zero(), low.clone(), hash(b"xelis")];
+        hashes.sort();
+        let mut by_bytes = hashes.clone();
+        by_bytes.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+        assert_eq!(hashes, by_bytes);
+        let hexes = hashes.iter().map(Hash::to_hex).collect::<Vec<_>>();
+        let mut sorted_hexes = hexes.clone();
+        sorted_hexes.sort();
+        assert_eq!(hexes, sorted_hexes);
+        assert_eq!(hashes.first(), Some(&Hash::zero()));
+        assert_eq!(hashes.last(), Some(&Hash::max()));
+    }
+}
@@ -0,0 +1,186 @@
+// Encryption of the extra data attached to a transaction
+// It is encrypted for the sender and the recipients only, so merchants don't leak order information on chain
+//
+// Format: magic | version (u8) | recipients count (u8) | sender handle | recipients handles | AEAD cipher
+// A random opening r is generated for each memo and the shared key is derived from r * H.
+// Each handle is PK * r, so the owner of the private key can find r * H back from its handle.
+
+use crate::{
+    serializer::{Reader, Serializer, Writer},
+    transaction::extra_data::{
+        derive_shared_key_from_handle,
+        derive_shared_key_from_opening,
+        AEADCipher,
+        CipherFormatError,
+        PlaintextData,
+        TAG_SIZE
+    }
+};
+use super::elgamal::{
+    CompressedHandle,
+    PedersenOpening,
+    PrivateKey,
+    PublicKey,
+    RISTRETTO_COMPRESSED_SIZE
+};
+
+// First bytes of an encrypted memo
+// 0xFF is never present in a valid UTF-8 text memo
+// The builder refuses a plaintext extra data starting with it, so it can't be mistaken for an encrypted one
+pub const ENCRYPTED_MEMO_MAGIC: [u8; 4] = [0xFF, b'X', b'E', b'M'];
+
+// Version of the encrypted memo format
+pub const ENCRYPTED_MEMO_VERSION: u8 = 1;
+
+// Maximum recipients that can decrypt a memo, the sender excluded
+pub const MAX_MEMO_RECIPIENTS: usize = u8::MAX as usize;
+
+// Magic, version and recipients count
+const HEADER_SIZE: usize = ENCRYPTED_MEMO_MAGIC.len() + 2;
+
+// Check if the extra data starts like an encrypted memo
+pub fn has_encrypted_prefix(data: &[u8]) -> bool {
+    data.starts_with(&ENCRYPTED_MEMO_MAGIC)
+}
+
+// Check if the extra data is using the encrypted memo format
+// Its version and size must match the recipients count
+pub fn is_encrypted(data: &[u8]) -> bool {
+    if !has_encrypted_prefix(data) || data.len() < HEADER_SIZE {
+        return false
+    }
+
+    let version = data[ENCRYPTED_MEMO_MAGIC.len()];
+    let recipients = data[ENCRYPTED_MEMO_MAGIC.len() + 1] as usize;
+    version == ENCRYPTED_MEMO_VERSION && data.len() >= encrypted_size(0, recipients)
+}
+
+// Size of the encrypted memo for a plaintext of this size
+pub fn encrypted_size(plaintext_size: usize, recipients: usize) -> usize {
+    HEADER_SIZE
+    // sender and recipients handles
+    + RISTRETTO_COMPRESSED_SIZE * (recipients + 1)
+    + plaintext_size
+    + TAG_SIZE
+}
+
+// Encrypt the memo so only the sender and the recipients can decrypt it
+// Returns None if there is too many recipients
+pub fn encrypt(plaintext: Vec<u8>, sender: &PublicKey, recipients: &[PublicKey]) -> Option<Vec<u8>> {
+    if recipients.len() > MAX_MEMO_RECIPIENTS {
+        return None
+    }
+
+    let opening = PedersenOpening::generate_new();
+    let key = derive_shared_key_from_opening(&opening);
+    let cipher = PlaintextData(plaintext).encrypt_in_place_with_aead(&key);
+
+    let mut writer = Writer::new();
+    writer.write_bytes(&ENCRYPTED_MEMO_MAGIC);
+    writer.write_u8(ENCRYPTED_MEMO_VERSION);
+    writer.write_u8(recipients.len() as u8);
+    sender.decrypt_handle(&opening).compress().write(&mut writer);
+    for recipient in recipients {
+        recipient.decrypt_handle(&opening).compress().write(&mut writer);
+    }
+    writer.write_bytes(&cipher.0);
+
+    Some(writer.bytes())
+}
+
+// Decrypt the memo using our private key
+// Every handle is tried as we don't know if we are the sender or a recipient
+pub fn decrypt(data: &[u8], private_key: &PrivateKey) -> Result<Vec<u8>, CipherFormatError> {
+    if !is_encrypted(data) {
+        return Err(CipherFormatError)
+    }
+
+    // Skip the magic and the version
+    let mut reader = Reader::new(&data[HEADER_SIZE - 1..]);
+    let count = reader.read_u8().map_err(|_| CipherFormatError)? as usize;
+    let handles = (0..=count)
+        .map(|_| CompressedHandle::read(&mut reader))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| CipherFormatError)?;
+
+    let cipher = reader.read_bytes_ref(reader.size()).map_err(|_| CipherFormatError)?;
+    if cipher.len() < TAG_SIZE {
+        return Err(CipherFormatError)
+    }
+
+    let cipher = AEADCipher(cipher.to_vec());
+    for handle in handles {
+        let Ok(handle) = handle.decompress() else {
+            continue;
+        };
+
+        let key = derive_shared_key_from_handle(private_key, &handle);
+        // Only the right key pass the AEAD tag verification
+        if let Ok(plaintext) = cipher.decrypt(&key) {
+            return Ok(plaintext.0)
+        }
+    }
+
+    Err(CipherFormatError)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::KeyPair;
+    use super::*;
+
+    #[test]
+    fn test_memo_round_trip() {
+        let alice = KeyPair::new();
+        let bob = KeyPair::new();
+        let memo = b"order #42".to_vec();
+
+        let encrypted = encrypt(memo.clone(), alice.get_public_key(), &[bob.get_public_key().clone()]).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(encrypted.len(), encrypted_size(memo.len(), 1));
+        assert_eq!(decrypt(&encrypted, alice.get_private_key()).unwrap(), memo);
+        assert_eq!(decrypt(&encrypted, bob.get_private_key()).unwrap(), memo);
+
+        // Anyone else can't read it
+        let eve = KeyPair::new();
+        assert!(decrypt(&encrypted, eve.get_private_key()).is_err());
+
+        // Plaintext memo is not mistaken as encrypted
+        assert!(!is_encrypted(&memo));
+        assert!(decrypt(&memo, alice.get_private_key()).is_err());
+    }
+
+    #[test]
+    fn test_memo_format() {
+        let alice = KeyPair::new();
+        let encrypted = encrypt(b"hi".to_vec(), alice.get_public_key(), &[]).unwrap();
+        assert!(has_encrypted_prefix(&encrypted));
+        assert_eq!(encrypted[ENCRYPTED_MEMO_MAGIC.len()], ENCRYPTED_MEMO_VERSION);
+
+        // Binary data starting with 0xFF only
+        assert!(!is_encrypted(&[0xFF; 64]));
+
+        // Unknown version
+        let mut unknown = encrypted.clone();
+        unknown[ENCRYPTED_MEMO_MAGIC.len()] = ENCRYPTED_MEMO_VERSION + 1;
+        assert!(!is_encrypted(&unknown));
+
+        // Too short for the recipients count
+        let mut missing = encrypted.clone();
+        missing[ENCRYPTED_MEMO_MAGIC.len() + 1] = 2;
+        assert!(!is_encrypted(&missing));
+    }
+
+    #[test]
+    fn test_memo_tampered() {
+        let alice = KeyPair::new();
+        let bob = KeyPair::new();
+        let mut encrypted = encrypt(b"hello".to_vec(), alice.get_public_key(), &[bob.get_public_key().clone()]).unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+        assert!(decrypt(&encrypted, bob.get_private_key()).is_err());
+
+        // Truncated
+        assert!(decrypt(&encrypted[..10], bob.get_private_key()).is_err());
+    }
+}
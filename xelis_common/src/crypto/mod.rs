@@ -5,6 +5,7 @@ mod transcript;
 pub mod elgamal;
 pub mod proofs;
 pub mod bech32;
+pub mod memo;
 
 pub use hash::*;
 pub use address::*;
@@ -225,6 +225,7 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
             // Send it to the server
             if !self.send::<_, bool>("subscribe", Some(id), &SubscribeParams {
                 notify: Cow::Borrowed(&event),
+                filter: None
             }).await? {
                 error!("Error while resubscribing to event with id {}", id);
             }
@@ -467,7 +468,8 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
 
         // Send it to the server
         self.send::<_, bool>("subscribe", Some(id), &SubscribeParams {
-            notify: Cow::Borrowed(&event)
+            notify: Cow::Borrowed(&event),
+            filter: None
         }).await?;
 
         // Create a mapping from the event to the ID used for the request
@@ -27,6 +27,10 @@ pub mod prompt;
 #[cfg(feature = "rpc_server")]
 pub mod rpc_server;
 
+// Helpers for the tests of the crates depending on it
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
 #[cfg(feature = "clap")]
 // If clap feature is enabled, build the correct style for CLI
 pub fn get_cli_styles() -> clap::builder::Styles {
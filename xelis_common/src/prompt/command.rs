@@ -2,8 +2,9 @@ use std::{collections::HashMap, pin::Pin, future::Future, fmt::Display, time::{I
 
 use crate::{config::VERSION, async_handler, context::Context};
 
-use super::{argument::*, ShareablePrompt, LogLevel};
+use super::{argument::*, logger::{self, ModuleLogLevel}, ShareablePrompt, LogLevel};
 use anyhow::Error;
+use serde_json::json;
 use thiserror::Error;
 use log::{info, warn, error};
 
@@ -33,6 +34,22 @@ pub enum CommandError {
     PoisonError(String)
 }
 
+impl CommandError {
+    // Exit code of the process when running commands in non-interactive mode
+    // 2 is used for an invalid command line, 1 for a command that failed
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::ExpectedCommandName
+            | Self::CommandNotFound
+            | Self::ExpectedRequiredArg(_)
+            | Self::TooManyArguments
+            | Self::ArgError(_)
+            | Self::InvalidArgument(_) => 2,
+            _ => 1
+        }
+    }
+}
+
 impl<T> From<PoisonError<T>> for CommandError {
     fn from(err: PoisonError<T>) -> Self {
         Self::PoisonError(format!("{}", err))
@@ -143,7 +160,10 @@ pub struct CommandManager {
     commands: Mutex<Vec<Rc<Command>>>,
     context: Mutex<Context>,
     prompt: ShareablePrompt,
-    running_since: Instant
+    running_since: Instant,
+    // Messages of the command being executed in non-interactive mode
+    // They are printed on stdout instead of being logged
+    output: Mutex<Option<Vec<String>>>
 }
 
 impl CommandManager {
@@ -152,7 +172,8 @@ impl CommandManager {
             commands: Mutex::new(Vec::new()),
             context: Mutex::new(context),
             prompt,
-            running_since: Instant::now()
+            running_since: Instant::now(),
+            output: Mutex::new(None)
         }
     }
 
@@ -168,7 +189,7 @@ impl CommandManager {
         self.add_command(Command::with_optional_arguments("help", "Show this help", vec![Arg::new("command", ArgType::String)], CommandHandler::Async(async_handler!(help))))?;
         self.add_command(Command::new("version", "Show the current version", CommandHandler::Sync(version)))?;
         self.add_command(Command::new("exit", "Shutdown the daemon", CommandHandler::Sync(exit)))?;
-        self.add_command(Command::with_required_arguments("set_log_level", "Set the log level", vec![Arg::new("level", ArgType::String)], CommandHandler::Sync(set_log_level)))?;
+        self.add_command(Command::with_arguments("set_log_level", "Set the log level globally or for a module", vec![Arg::new("level", ArgType::String)], vec![Arg::new("module", ArgType::String)], CommandHandler::Sync(set_log_level)))?;
 
         Ok(())
     }
@@ -215,27 +236,111 @@ impl CommandManager {
             commands.iter().find(|command| *command.get_name() == *command_name).cloned().ok_or(CommandError::CommandNotFound)?
         };
         let mut arguments: HashMap<String, ArgValue> = HashMap::new();
+
+        // Optional arguments can also be set using a flag
+        // Example: "--estimate-only" for the "estimate_only" bool argument
+        // or "--memo order-42" for the "memo" argument of another type
+        let mut values = Vec::new();
+        while let Some(token) = command_split.next() {
+            match token.strip_prefix("--") {
+                Some(flag) => {
+                    let name = flag.replace('-', "_");
+                    let arg = command.get_optional_args().iter()
+                        .find(|arg| *arg.get_name() == name)
+                        .ok_or_else(|| CommandError::InvalidArgument(token.to_owned()))?;
+                    let value = match arg.get_type() {
+                        ArgType::Bool => ArgValue::Bool(true),
+                        arg_type => {
+                            let value = command_split.next().ok_or_else(|| CommandError::ExpectedRequiredArg(name.clone()))?;
+                            arg_type.to_value(value)?
+                        }
+                    };
+                    arguments.insert(name, value);
+                },
+                None => values.push(token)
+            };
+        }
+
+        let mut values = values.into_iter();
         for arg in command.get_required_args() {
-            let arg_value = command_split.next().ok_or_else(|| CommandError::ExpectedRequiredArg(arg.get_name().to_owned()))?;
+            let arg_value = values.next().ok_or_else(|| CommandError::ExpectedRequiredArg(arg.get_name().to_owned()))?;
             arguments.insert(arg.get_name().clone(), arg.get_type().to_value(arg_value)?);
         }
 
         // include all options args available
-        for optional_arg in command.get_optional_args() {
-            if let Some(arg_value) = command_split.next() {
+        // skip the ones already set by a flag
+        for optional_arg in command.get_optional_args().iter().filter(|arg| !arguments.contains_key(arg.get_name())) {
+            if let Some(arg_value) = values.next() {
                 arguments.insert(optional_arg.get_name().clone(), optional_arg.get_type().to_value(arg_value)?);
             } else {
                 break;
             }
         }
 
-        if command_split.next().is_some() {
+        if values.next().is_some() {
             return Err(CommandError::TooManyArguments);
         }
 
         command.execute(self, ArgumentManager::new(arguments)).await
     }
 
+    // Execute the commands in order without the interactive prompt
+    // Each command output is printed on stdout, as a JSON object per command if requested
+    // It stops at the first command failing and returns its error
+    pub async fn execute_commands(&self, commands: &[String], json: bool) -> Result<(), CommandError> {
+        for command in commands {
+            *self.output.lock()? = Some(Vec::new());
+            let res = match self.handle_command(command.clone()).await {
+                // Nothing else to run after the exit command
+                Err(CommandError::Exit) => None,
+                res => Some(res)
+            };
+            let output = self.output.lock()?.take().unwrap_or_default();
+
+            let error = match &res {
+                Some(Err(e)) => Some(e.to_string()),
+                _ => None
+            };
+            if json {
+                println!("{}", json!({
+                    "command": command,
+                    "success": error.is_none(),
+                    "output": output,
+                    "error": error
+                }));
+            } else {
+                for line in output {
+                    println!("{}", line);
+                }
+                if let Some(error) = error {
+                    eprintln!("Error while executing '{}': {}", command, error);
+                }
+            }
+
+            match res {
+                Some(res) => res?,
+                None => break
+            }
+        }
+
+        Ok(())
+    }
+
+    // Keep the message if a command is executed in non-interactive mode
+    // Returns false if it should be logged
+    fn capture_output(&self, message: String) -> bool {
+        match self.output.lock() {
+            Ok(mut output) => match output.as_mut() {
+                Some(output) => {
+                    output.push(message);
+                    true
+                },
+                None => false
+            },
+            Err(_) => false
+        }
+    }
+
     pub fn display_commands(&self) -> Result<(), CommandError> {
         let commands = self.commands.lock()?;
         self.message("Available commands:");
@@ -246,15 +351,24 @@ impl CommandManager {
     }
 
     pub fn message<D: Display>(&self, message: D) {
-        info!("{}", message);
+        let message = message.to_string();
+        if !self.capture_output(message.clone()) {
+            info!("{}", message);
+        }
     }
 
     pub fn warn<D: Display>(&self, message: D) {
-        warn!("{}", message);
+        let message = message.to_string();
+        if !self.capture_output(message.clone()) {
+            warn!("{}", message);
+        }
     }
 
     pub fn error<D: Display>(&self, message: D) {
-        error!("{}", message);
+        let message = message.to_string();
+        if !self.capture_output(message.clone()) {
+            error!("{}", message);
+        }
     }
 
     pub fn running_since(&self) -> Duration {
@@ -288,8 +402,106 @@ fn version(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandEr
 fn set_log_level(manager: &CommandManager, mut args: ArgumentManager) -> Result<(), CommandError> {
     let arg_value = args.get_value("level")?.to_string_value()?;
     let level = LogLevel::from_str(&arg_value).map_err(|e| CommandError::InvalidArgument(e))?;
-    log::set_max_level(level.into());
-    manager.message(format!("Log level set to {}", level));
+    if args.has_argument("module") {
+        let module = args.get_value("module")?.to_string_value()?;
+        logger::set_module_log_level(ModuleLogLevel { module: module.clone(), level });
+        manager.message(format!("Log level set to {} for module {}", level, module));
+    } else {
+        logger::set_log_level(level);
+        manager.message(format!("Log level set to {}", level));
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use anyhow::anyhow;
+    use crate::prompt::Prompt;
+    use super::*;
+
+    fn count(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+        let context = manager.get_context().lock()?;
+        let counter: &AtomicUsize = context.get()?;
+        let value = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        manager.message(format!("Count: {}", value));
+        Ok(())
+    }
+
+    fn fail(_: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+        Err(anyhow!("command failed").into())
+    }
+
+    fn label(manager: &CommandManager, mut args: ArgumentManager) -> Result<(), CommandError> {
+        let value = if args.has_argument("label") {
+            args.get_value("label")?.to_string_value()?
+        } else {
+            String::new()
+        };
+        let context = manager.get_context().lock()?;
+        let label: &Mutex<String> = context.get()?;
+        *label.lock()? = value;
+        Ok(())
+    }
+
+    fn get_count(manager: &CommandManager) -> usize {
+        manager.get_context().lock().unwrap().get::<AtomicUsize>().unwrap().load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_execute_commands() {
+        let prompt = Prompt::new(LogLevel::Off, Vec::new(), &"logs/".to_owned(), &"test.log".to_owned(), true, true, None, 0, true, false, true).unwrap();
+        let manager = CommandManager::new(prompt);
+        manager.register_default_commands().unwrap();
+        manager.add_command(Command::new("count", "Increment the counter", CommandHandler::Sync(count))).unwrap();
+        manager.add_command(Command::new("fail", "Always fail", CommandHandler::Sync(fail))).unwrap();
+        manager.store_in_context(AtomicUsize::new(0)).unwrap();
+
+        let commands = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+
+        manager.execute_commands(&commands(&["count", "version", "count"]), false).await.unwrap();
+        assert_eq!(get_count(&manager), 2);
+
+        // Abort on the first failure
+        let err = manager.execute_commands(&commands(&["count", "fail", "count"]), true).await.unwrap_err();
+        assert_eq!(err.exit_code(), 1);
+        assert_eq!(get_count(&manager), 3);
+
+        // Invalid command line
+        let err = manager.execute_commands(&commands(&["unknown", "count"]), false).await.unwrap_err();
+        assert_eq!(err.exit_code(), 2);
+        let err = manager.execute_commands(&commands(&["count too many"]), false).await.unwrap_err();
+        assert_eq!(err.exit_code(), 2);
+        assert_eq!(get_count(&manager), 3);
+
+        // Nothing is run after exit
+        manager.execute_commands(&commands(&["count", "exit", "count"]), false).await.unwrap();
+        assert_eq!(get_count(&manager), 4);
+
+        // Messages are logged again once the commands are executed
+        assert!(!manager.capture_output("message".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn test_optional_argument_flags() {
+        let prompt = Prompt::new(LogLevel::Off, Vec::new(), &"logs/".to_owned(), &"test.log".to_owned(), true, true, None, 0, true, false, true).unwrap();
+        let manager = CommandManager::new(prompt);
+        manager.add_command(Command::with_optional_arguments("label", "Set the label", vec![Arg::new("yes", ArgType::Bool), Arg::new("label", ArgType::String)], CommandHandler::Sync(label))).unwrap();
+        manager.store_in_context(Mutex::new(String::new())).unwrap();
+        let get_label = || manager.get_context().lock().unwrap().get::<Mutex<String>>().unwrap().lock().unwrap().clone();
+
+        manager.handle_command("label --label order-42".to_owned()).await.unwrap();
+        assert_eq!(get_label(), "order-42");
+
+        // Positional arguments are still filled in order, skipping the ones set by a flag
+        manager.handle_command("label --yes first".to_owned()).await.unwrap();
+        assert_eq!(get_label(), "first");
+        manager.handle_command("label --label second --yes".to_owned()).await.unwrap();
+        assert_eq!(get_label(), "second");
+
+        // A flag of a non bool argument requires a value
+        assert!(matches!(manager.handle_command("label --label".to_owned()).await, Err(CommandError::ExpectedRequiredArg(_))));
+        assert!(matches!(manager.handle_command("label --unknown".to_owned()).await, Err(CommandError::InvalidArgument(_))));
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::RwLock
+};
+use log::{Level, LevelFilter};
+use super::LogLevel;
+
+// Log level applied to a specific module
+// Its string format is "module=level", for example "p2p=trace"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleLogLevel {
+    pub module: String,
+    pub level: LogLevel
+}
+
+impl FromStr for ModuleLogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (module, level) = s.split_once('=')
+            .ok_or_else(|| format!("Invalid module log level '{}', expected module=level", s))?;
+
+        let module = module.trim();
+        if module.is_empty() {
+            return Err(format!("Invalid module log level '{}', module is empty", s))
+        }
+
+        let level = LogLevel::from_str(level.trim())?;
+        Ok(Self {
+            module: module.to_owned(),
+            level
+        })
+    }
+}
+
+impl Display for ModuleLogLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.module, self.level)
+    }
+}
+
+// Parse a comma separated list of module log levels
+// Example: "p2p=trace,storage=warn"
+pub fn parse_module_log_levels(value: &str) -> Result<Vec<ModuleLogLevel>, String> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(ModuleLogLevel::from_str)
+        .collect()
+}
+
+// Check if a log target is part of the requested module
+// A module can be a full path ("xelis_daemon::p2p")
+// or a single segment of it ("p2p") to match it in any crate
+fn is_target_in_module(target: &str, module: &str) -> bool {
+    if module.contains("::") {
+        target == module || (target.starts_with(module) && target[module.len()..].starts_with("::"))
+    } else {
+        target.split("::").any(|segment| segment == module)
+    }
+}
+
+// Filter shared by all the log outputs
+// It can be updated at runtime to change the verbosity
+struct LogFilter {
+    level: LevelFilter,
+    modules: Vec<(String, LevelFilter)>
+}
+
+impl LogFilter {
+    // Find the level to use for a target
+    // The most specific module override is used if any
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules.iter()
+            .filter(|(module, _)| is_target_in_module(target, module))
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+
+    // Highest level required by the filter
+    fn max_level(&self) -> LevelFilter {
+        self.modules.iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, LevelFilter::max)
+    }
+}
+
+static LOG_FILTER: RwLock<LogFilter> = RwLock::new(LogFilter {
+    level: LevelFilter::Info,
+    modules: Vec::new()
+});
+
+// Update the global max level of the log crate
+// so records that are filtered out are skipped as soon as possible
+fn update_max_level(filter: &LogFilter) {
+    log::set_max_level(filter.max_level());
+}
+
+// Check if a record should be logged based on its target and level
+pub fn is_enabled(target: &str, level: Level) -> bool {
+    match LOG_FILTER.read() {
+        Ok(filter) => level <= filter.level_for(target),
+        Err(_) => true
+    }
+}
+
+// Set the default log level used by all modules without override
+pub fn set_log_level(level: LogLevel) {
+    if let Ok(mut filter) = LOG_FILTER.write() {
+        filter.level = level.into();
+        update_max_level(&filter);
+    }
+}
+
+// Set the log level for a specific module, replacing any previous override
+pub fn set_module_log_level(module: ModuleLogLevel) {
+    if let Ok(mut filter) = LOG_FILTER.write() {
+        let level = module.level.into();
+        match filter.modules.iter_mut().find(|(name, _)| *name == module.module) {
+            Some(entry) => entry.1 = level,
+            None => filter.modules.push((module.module, level))
+        };
+        update_max_level(&filter);
+    }
+}
+
+// Replace all the module log levels overrides
+pub fn set_module_log_levels(modules: Vec<ModuleLogLevel>) {
+    if let Ok(mut filter) = LOG_FILTER.write() {
+        filter.modules = modules.into_iter()
+            .map(|module| (module.module, module.level.into()))
+            .collect();
+        update_max_level(&filter);
+    }
+}
+
+// Log file that is rotated once it reach its maximum size
+// Previous files are renamed with an incremented suffix: file.1, file.2, ...
+// Only the last N rotated files are kept
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+    // Only rotate at the beginning of a new line
+    at_line_start: bool
+}
+
+impl RotatingFile {
+    pub fn new(path: PathBuf, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let file = Self::open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+            max_files,
+            at_line_start: true
+        })
+    }
+
+    fn open(path: &PathBuf) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn should_rotate(&self) -> bool {
+        self.at_line_start && self.size >= self.max_size
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        if self.max_files == 0 {
+            // No file to keep, just truncate the current one
+            self.file = File::create(&self.path)?;
+        } else {
+            let oldest = self.rotated_path(self.max_files);
+            if oldest.exists() {
+                fs::remove_file(oldest)?;
+            }
+
+            for index in (1..self.max_files).rev() {
+                let path = self.rotated_path(index);
+                if path.exists() {
+                    fs::rename(path, self.rotated_path(index + 1))?;
+                }
+            }
+
+            fs::rename(&self.path, self.rotated_path(1))?;
+            self.file = Self::open(&self.path)?;
+        }
+
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        if written > 0 {
+            self.at_line_start = buf[written - 1] == b'\n';
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::TempDir;
+    use super::*;
+
+    #[test]
+    fn test_parse_module_log_levels() {
+        let modules = parse_module_log_levels("p2p=trace, storage=warn,").unwrap();
+        assert_eq!(modules, vec![
+            ModuleLogLevel { module: "p2p".to_owned(), level: LogLevel::Trace },
+            ModuleLogLevel { module: "storage".to_owned(), level: LogLevel::Warn }
+        ]);
+
+        assert!(parse_module_log_levels("p2p").is_err());
+        assert!(parse_module_log_levels("=trace").is_err());
+        assert!(parse_module_log_levels("p2p=verbose").is_err());
+    }
+
+    #[test]
+    fn test_module_filter() {
+        let filter = LogFilter {
+            level: LevelFilter::Info,
+            modules: vec![
+                ("p2p".to_owned(), LevelFilter::Trace),
+                ("xelis_daemon::p2p::peer".to_owned(), LevelFilter::Warn)
+            ]
+        };
+
+        assert_eq!(filter.level_for("xelis_daemon::p2p"), LevelFilter::Trace);
+        assert_eq!(filter.level_for("xelis_daemon::p2p::connection"), LevelFilter::Trace);
+        assert_eq!(filter.level_for("xelis_daemon::p2p::peer"), LevelFilter::Warn);
+        assert_eq!(filter.level_for("xelis_daemon::p2pool"), LevelFilter::Info);
+        assert_eq!(filter.level_for("xelis_daemon::core::storage"), LevelFilter::Info);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let dir = TempDir::new("rotating-file");
+        let path = dir.join("test.log");
+
+        let mut file = RotatingFile::new(path.clone(), 16, 2).unwrap();
+        // First line doesn't trigger any rotation
+        writeln!(file, "0123456789abcdef").unwrap();
+        assert!(!file.rotated_path(1).exists());
+
+        // Max size is reached, next line is written in a new file
+        writeln!(file, "second").unwrap();
+        assert!(file.rotated_path(1).exists());
+        writeln!(file, "third line is long").unwrap();
+        writeln!(file, "fourth").unwrap();
+        writeln!(file, "fifth line is long").unwrap();
+        assert!(file.rotated_path(2).exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\nfifth line is long\n");
+
+        // Only the last 2 rotated files are kept
+        writeln!(file, "sixth").unwrap();
+        assert!(!file.rotated_path(3).exists());
+        assert_eq!(fs::read_to_string(file.rotated_path(2)).unwrap(), "second\nthird line is long\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "sixth\n");
+    }
+}
@@ -1,5 +1,6 @@
 pub mod command;
 pub mod argument;
+pub mod logger;
 
 use crate::{
     crypto::Hash,
@@ -31,11 +32,15 @@ use tokio::{
     sync::{
         mpsc::{self, UnboundedSender, UnboundedReceiver, Sender, Receiver},
         oneshot,
-        Mutex as AsyncMutex
+        Mutex as AsyncMutex,
+        Notify
     },
     time::{interval, timeout}
 };
-use self::command::{CommandError, CommandManager};
+use self::{
+    command::{CommandError, CommandManager},
+    logger::{ModuleLogLevel, RotatingFile}
+};
 use anyhow::Error;
 use fern::colors::{ColoredLevelConfig, Color};
 use regex::Regex;
@@ -86,6 +91,7 @@ impl FromStr for LogLevel {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
+            "off" => Self::Off,
             "error" => Self::Error,
             "warn" => Self::Warn,
             "info" => Self::Info,
@@ -438,6 +444,8 @@ pub struct Prompt {
     // This following channel is used to cancel the read_input method
     read_input_sender: Sender<()>,
     read_input_receiver: AsyncMutex<Receiver<()>>,
+    // Used to rebuild the prompt message without waiting the next update
+    update_notifier: Notify,
     // Should we set colors or not
     disable_colors: bool
 }
@@ -448,16 +456,17 @@ type LocalBoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
 type AsyncF<'a, T1, T2, R> = Box<dyn Fn(&'a T1, T2) -> LocalBoxFuture<'a, R> + 'a>;
 
 impl Prompt {
-    pub fn new(level: LogLevel, dir_path: &String, filename_log: &String, disable_file_logging: bool, disable_file_log_date_based: bool, disable_colors: bool, interactive: bool) -> Result<ShareablePrompt, PromptError> {
+    pub fn new(level: LogLevel, module_levels: Vec<ModuleLogLevel>, dir_path: &String, filename_log: &String, disable_file_logging: bool, disable_file_log_date_based: bool, file_log_max_size: Option<u64>, file_log_max_files: usize, disable_colors: bool, interactive: bool, log_to_stderr: bool) -> Result<ShareablePrompt, PromptError> {
         let (read_input_sender, read_input_receiver) = mpsc::channel(1);
         let prompt = Self {
             state: Arc::new(State::new(interactive)),
             input_receiver: Mutex::new(None),
             read_input_receiver: AsyncMutex::new(read_input_receiver),
             read_input_sender,
+            update_notifier: Notify::new(),
             disable_colors
         };
-        prompt.setup_logger(level, dir_path, filename_log, disable_file_logging, disable_file_log_date_based)?;
+        prompt.setup_logger(level, module_levels, dir_path, filename_log, disable_file_logging, disable_file_log_date_based, file_log_max_size, file_log_max_files, log_to_stderr)?;
 
         #[cfg(target_os = "windows")]
         {
@@ -542,26 +551,28 @@ impl Prompt {
                     } else {
                         debug!("You said '{}'", input);
                     }
+                    continue;
                 }
-                _ = interval.tick() => {
-                    {
-                        // verify that we don't have any reader
-                        // as they may have changed the prompt
-                        if self.state.prompt_sender.lock()?.is_some() {
-                            continue;
-                        }
-                    }
-                    match timeout(Duration::from_secs(5), (*fn_message)(&self, command_manager)).await {
-                        Ok(res) => {
-                            let prompt = res?;
-                            self.update_prompt(prompt)?;
-                        }
-                        Err(e) => {
-                            warn!("Couldn't update prompt message: {}", e);
-                        }
-                    };
+                _ = interval.tick() => {},
+                _ = self.update_notifier.notified() => {}
+            }
+
+            {
+                // verify that we don't have any reader
+                // as they may have changed the prompt
+                if self.state.prompt_sender.lock()?.is_some() {
+                    continue;
                 }
             }
+            match timeout(Duration::from_secs(5), (*fn_message)(&self, command_manager)).await {
+                Ok(res) => {
+                    let prompt = res?;
+                    self.update_prompt(prompt)?;
+                }
+                Err(e) => {
+                    warn!("Couldn't update prompt message: {}", e);
+                }
+            };
         }
 
         if !self.state.has_exited.swap(true, Ordering::SeqCst) {
@@ -575,6 +586,12 @@ impl Prompt {
         Ok(())
     }
 
+    // Rebuild the prompt message now instead of waiting the next update
+    // It can be called from any task, several requests before the rebuild are merged
+    pub fn request_update(&self) {
+        self.update_notifier.notify_one();
+    }
+
     // Stop the prompt running
     // can only be called when it was already started
     pub fn stop(&self) -> Result<(), PromptError> {
@@ -721,7 +738,8 @@ impl Prompt {
     }
 
     // configure fern and print prompt message after each new output
-    fn setup_logger(&self, level: LogLevel, dir_path: &String, filename_log: &String, disable_file_logging: bool, disable_file_log_date_based: bool) -> Result<(), fern::InitError> {
+    // Console logs can be sent to stderr to keep stdout for the commands output
+    fn setup_logger(&self, level: LogLevel, module_levels: Vec<ModuleLogLevel>, dir_path: &String, filename_log: &String, disable_file_logging: bool, disable_file_log_date_based: bool, file_log_max_size: Option<u64>, file_log_max_files: usize, log_to_stderr: bool) -> Result<(), fern::InitError> {
         let colors = ColoredLevelConfig::new()
             .debug(Color::Green)
             .info(Color::Cyan)
@@ -769,8 +787,12 @@ impl Prompt {
 
                 res
             })
-            .chain(std::io::stdout())
-            .level(level.into());
+            .filter(|metadata| logger::is_enabled(metadata.target(), metadata.level()));
+        let stdout_log = if log_to_stderr {
+            stdout_log.chain(std::io::stderr())
+        } else {
+            stdout_log.chain(std::io::stdout())
+        };
 
         let mut base = base.chain(stdout_log);
         if !disable_file_logging {
@@ -780,7 +802,7 @@ impl Prompt {
             }
 
             let mut file_log = fern::Dispatch::new()
-            .level(level.into())
+            .filter(|metadata| logger::is_enabled(metadata.target(), metadata.level()))
             .format(move |out, message, record| {
                 let pad = " ".repeat((30i16 - record.target().len() as i16).max(0) as usize);
                 let level_pad = if record.level() == Level::Error || record.level() == Level::Debug { "" } else { " " };
@@ -795,8 +817,12 @@ impl Prompt {
                 ))
             });
 
-            // Don't rotate the log file based on date ourself if its disabled
-            if !disable_file_log_date_based {
+            // Rotate the log file based on its size if a maximum size is set
+            // Otherwise, don't rotate the log file based on date ourself if its disabled
+            if let Some(max_size) = file_log_max_size {
+                let file = RotatingFile::new(logs_path.join(filename_log), max_size, file_log_max_files)?;
+                file_log = file_log.chain(Box::new(file) as Box<dyn Write + Send>);
+            } else if !disable_file_log_date_based {
                 file_log = file_log.chain(fern::DateBased::new(logs_path, format!("%Y-%m-%d.{filename_log}")));
             } else {
                 file_log = file_log.chain(fern::log_file(format!("{}/{}", dir_path, filename_log))?)
@@ -817,6 +843,11 @@ impl Prompt {
         .level_for("tungstenite", log::LevelFilter::Warn)
         .apply()?;
 
+        // Set the filter once the logger is applied
+        // so the global max level is set based on it
+        logger::set_module_log_levels(module_levels);
+        logger::set_log_level(level);
+
         Ok(())
     }
 
@@ -1,5 +1,5 @@
 use std::fmt::{Display, Formatter};
-use actix_web::{ResponseError, HttpResponse};
+use actix_web::{http::StatusCode, ResponseError, HttpResponse};
 use serde_json::{Value, Error as SerdeError, json};
 use thiserror::Error;
 use anyhow::Error as AnyError;
@@ -43,6 +43,8 @@ pub enum InternalRpcError {
     EventNotSubscribed,
     #[error("Event is already subscribed")]
     EventAlreadySubscribed,
+    #[error("Too many addresses subscribed, maximum is {}", _0)]
+    TooManyAddresses(usize),
     #[error(transparent)]
     SerializeResponse(SerdeError),
     // Custom errors must have a code between -3 and -31999
@@ -52,6 +54,9 @@ pub enum InternalRpcError {
     Custom(i16, String),
     #[error("{}", _1)]
     CustomStr(i16, &'static str),
+    // Custom error with structured data for the client
+    #[error("{}", _1)]
+    CustomAnyWithData(i16, AnyError, Value),
 }
 
 impl InternalRpcError {
@@ -61,7 +66,7 @@ impl InternalRpcError {
             Self::ParseBodyError => -32700,
             Self::InvalidJSONRequest | Self::InvalidRequestStr(_) | InternalRpcError::InvalidVersion => -32600,
             Self::MethodNotFound(_) => -32601,
-            Self::InvalidJSONParams(_) | Self::InvalidParams(_) |  Self::InvalidParamsAny(_) | InternalRpcError::UnexpectedParams | InternalRpcError::ExpectedParams => -32602,
+            Self::InvalidJSONParams(_) | Self::InvalidParams(_) |  Self::InvalidParamsAny(_) | InternalRpcError::UnexpectedParams | InternalRpcError::ExpectedParams | Self::TooManyAddresses(_) => -32602,
             // Internal errors
             Self::InternalError(_) => -32603,
             // 32000 to -32099	Server error (Reserved for implementation-defined server-errors)
@@ -69,12 +74,32 @@ impl InternalRpcError {
             Self::InvalidContext => -32001,
             Self::ClientNotFound => -32002,
             InternalRpcError::SerializeResponse(_) => -32003,
-            InternalRpcError::AnyError(_) => -32004,
+            // Use the code of the wrapped RPC error if any (validation errors with a context)
+            InternalRpcError::AnyError(e) => e.chain()
+                .find_map(|cause| cause.downcast_ref::<InternalRpcError>())
+                .map_or(-32004, |cause| cause.get_code()),
             // Events invalid requests
             Self::EventNotSubscribed => -1,
             Self::EventAlreadySubscribed => -2,
             // Custom errors
-            Self::Custom(code, _) | Self::CustomStr(code, _) | Self::CustomAny(code, _) => *code,
+            Self::Custom(code, _) | Self::CustomStr(code, _) | Self::CustomAny(code, _) | Self::CustomAnyWithData(code, _, _) => *code,
+        }
+    }
+
+    // Additional information about the error
+    // Only the top message is displayed, so the underlying causes are set here
+    pub fn get_data(&self) -> Option<Value> {
+        let err = match self {
+            Self::CustomAnyWithData(_, _, data) => return Some(data.clone()),
+            Self::AnyError(e) | Self::InvalidParamsAny(e) | Self::CustomAny(_, e) => e,
+            _ => return None
+        };
+
+        let causes: Vec<String> = err.chain().skip(1).map(|cause| cause.to_string()).collect();
+        if causes.is_empty() {
+            None
+        } else {
+            Some(json!(causes))
         }
     }
 }
@@ -100,14 +125,24 @@ impl RpcResponseError {
         }
     }
 
+    pub fn get_error(&self) -> &InternalRpcError {
+        &self.error
+    }
+
     pub fn to_json(&self) -> Value {
+        let mut error = json!({
+            "code": self.error.get_code(),
+            "message": self.error.to_string()
+        });
+
+        if let Some(data) = self.error.get_data() {
+            error["data"] = data;
+        }
+
         json!({
             "jsonrpc": JSON_RPC_VERSION,
             "id": self.get_id(),
-            "error": {
-                "code": self.error.get_code(),
-                "message": self.error.to_string()
-            }
+            "error": error
         })
     }
 }
@@ -118,7 +153,12 @@ impl Display for RpcResponseError {
     }
 }
 
+// JSON-RPC errors are always sent with HTTP 200, the error is in the body
 impl ResponseError for RpcResponseError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+
     fn error_response(&self) -> HttpResponse {
         HttpResponse::Ok().json(self.to_json())
     }
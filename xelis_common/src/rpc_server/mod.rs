@@ -2,20 +2,27 @@ pub mod websocket;
 mod error;
 mod rpc_handler;
 
-use std::borrow::Cow;
+use std::{borrow::Cow, net::IpAddr};
 
 pub use error::{RpcResponseError, InternalRpcError};
-pub use rpc_handler::{RPCHandler, Handler};
+pub use rpc_handler::{RPCHandler, Handler, HandlerFuture, ErrorCodeResolver};
 pub use rpc_handler::parse_params;
 
 use actix_web::{HttpResponse, web::{self, Data, Payload}, Responder, HttpRequest};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::context::Context;
+
 use self::websocket::{WebSocketServerShared, WebSocketHandler};
 
 pub const JSON_RPC_VERSION: &str = "2.0";
 
+// IP address of the client that sent the request
+// Stored in the context when it is known
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientIp(pub IpAddr);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Id {
@@ -54,12 +61,19 @@ pub trait RPCServerHandler<T: Send + Clone> {
 }
 
 // JSON RPC handler endpoint
-pub async fn json_rpc<T, H>(server: Data<H>, body: web::Bytes) -> Result<impl Responder, RpcResponseError>
+pub async fn json_rpc<T, H>(server: Data<H>, request: HttpRequest, body: web::Bytes) -> Result<impl Responder, RpcResponseError>
 where
     T: Send + Sync + Clone + 'static,
     H: RPCServerHandler<T>
 {
-    let result = server.get_rpc_handler().handle_request(&body).await?;
+    let handler = server.get_rpc_handler();
+    let mut context = Context::new();
+    context.store(handler.get_data().clone());
+    if let Some(addr) = request.peer_addr() {
+        context.store(ClientIp(addr.ip()));
+    }
+
+    let result = handler.handle_request_with_context(context, &body).await?;
     Ok(HttpResponse::Ok().json(result))
 }
 
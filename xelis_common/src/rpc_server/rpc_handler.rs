@@ -1,16 +1,22 @@
 use std::{collections::HashMap, pin::Pin, future::Future};
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::{json, Map, Value};
 use crate::context::Context;
 
-use super::{InternalRpcError, RpcResponseError, RpcRequest, JSON_RPC_VERSION};
+use anyhow::Error as AnyError;
+use super::{Id, InternalRpcError, RpcResponseError, RpcRequest, JSON_RPC_VERSION};
 use log::{error, trace};
 
-pub type Handler = fn(&'_ Context, Value) -> Pin<Box<dyn Future<Output = Result<Value, InternalRpcError>> + Send + '_>>;
+pub type HandlerFuture<'a, R = Value> = Pin<Box<dyn Future<Output = Result<R, InternalRpcError>> + Send + 'a>>;
+// A handler can be a function or a closure capturing its own state
+pub type Handler = Box<dyn for<'a> Fn(&'a Context, Value) -> HandlerFuture<'a> + Send + Sync>;
+// Resolve an application error code from an error returned by a method
+pub type ErrorCodeResolver = fn(&AnyError) -> Option<i16>;
 
 pub struct RPCHandler<T: Send + Clone + 'static> {
     methods: HashMap<String, Handler>, // all RPC methods registered
-    data: T
+    data: T,
+    error_code_resolver: Option<ErrorCodeResolver>
 }
 
 impl<T> RPCHandler<T>
@@ -20,7 +26,8 @@ where
     pub fn new(data: T) -> Self {
         Self {
             methods: HashMap::new(),
-            data
+            data,
+            error_code_resolver: None
         }
     }
 
@@ -39,19 +46,20 @@ where
 
         match request {
             e @ Value::Object(_) => self.execute_method(&context, self.parse_request(e)?).await.map(|e| e.unwrap_or(Value::Null)),
-            Value::Array(requests) => {
+            Value::Array(requests) if !requests.is_empty() => {
                 let mut responses = Vec::new();
                 for value in requests {
-                    if value.is_object() {
-                        let request = self.parse_request(value)?;
-                        let response = match self.execute_method(&context, request).await {
-                            Ok(response) => json!(response),
-                            Err(e) => e.to_json()
-                        };
-                        responses.push(response);
-                    } else {
-                        responses.push(RpcResponseError::new(None, InternalRpcError::InvalidJSONRequest).to_json());
-                    }
+                    let result = match self.parse_request(value) {
+                        Ok(request) => self.execute_method(&context, request).await,
+                        Err(e) => Err(e)
+                    };
+
+                    // Notifications have no response
+                    match result {
+                        Ok(Some(response)) => responses.push(response),
+                        Ok(None) => {},
+                        Err(e) => responses.push(e.to_json())
+                    };
                 }
                 Ok(serde_json::to_value(responses).map_err(|err| RpcResponseError::new(None, InternalRpcError::SerializeResponse(err)))?)
             },
@@ -65,8 +73,16 @@ where
         self.parse_request(request)
     }
 
+    // Parse a request object
+    // The body is valid JSON here, so an invalid request is not a parse error
     pub fn parse_request(&self, body: Value) -> Result<RpcRequest, RpcResponseError> {
-        let request: RpcRequest = serde_json::from_value(body).map_err(|_| RpcResponseError::new(None, InternalRpcError::ParseBodyError))?;
+        if !body.is_object() {
+            return Err(RpcResponseError::new(None, InternalRpcError::InvalidJSONRequest));
+        }
+
+        // Keep the id to answer the request even if it's invalid
+        let id = body.get("id").and_then(|id| serde_json::from_value::<Id>(id.clone()).ok());
+        let request: RpcRequest = serde_json::from_value(body).map_err(|_| RpcResponseError::new(id, InternalRpcError::InvalidJSONRequest))?;
         if request.jsonrpc != JSON_RPC_VERSION {
             return Err(RpcResponseError::new(request.id, InternalRpcError::InvalidVersion));
         }
@@ -84,7 +100,7 @@ where
         };
         trace!("executing '{}' RPC method", request.method);
         let params = request.params.take().unwrap_or(Value::Null);
-        let result = handler(context, params).await.map_err(|err| RpcResponseError::new(request.id.clone(), err))?;
+        let result = handler(context, params).await.map_err(|err| RpcResponseError::new(request.id.clone(), self.resolve_error(err)))?;
         Ok(if request.id.is_some() {
             Some(json!({
                 "jsonrpc": JSON_RPC_VERSION,
@@ -97,12 +113,53 @@ where
     }
 
     // register a new RPC method handler
-    pub fn register_method(&mut self, name: &str, handler: Handler) {
-        if self.methods.insert(name.into(), handler).is_some() {
+    pub fn register_method<F>(&mut self, name: &str, handler: F)
+    where
+        F: for<'a> Fn(&'a Context, Value) -> HandlerFuture<'a> + Send + Sync + 'static
+    {
+        if self.methods.insert(name.into(), Box::new(handler)).is_some() {
             error!("The method '{}' was already registered !", name);
         }
     }
 
+    // register a new RPC method handler using typed params and result
+    // params are deserialized before calling the handler
+    // and the result is serialized in JSON
+    pub fn register_typed<P, R, F>(&mut self, name: &str, handler: F)
+    where
+        P: DeserializeOwned + 'static,
+        R: Serialize + 'static,
+        F: for<'a> Fn(&'a Context, P) -> HandlerFuture<'a, R> + Send + Sync + 'static
+    {
+        self.register_method(name, move |context, body| {
+            let future = match parse_params::<P>(body) {
+                Ok(params) => handler(context, params),
+                Err(e) => return Box::pin(async move { Err(e) })
+            };
+
+            Box::pin(async move {
+                let result = future.await?;
+                serde_json::to_value(result).map_err(InternalRpcError::SerializeResponse)
+            })
+        });
+    }
+
+    // set the resolver used to find the code of the application errors
+    pub fn set_error_code_resolver(&mut self, resolver: ErrorCodeResolver) {
+        self.error_code_resolver = Some(resolver);
+    }
+
+    // Errors with a context lose their code, use the resolver to find it back
+    fn resolve_error(&self, err: InternalRpcError) -> InternalRpcError {
+        match (err, self.error_code_resolver) {
+            (InternalRpcError::AnyError(e), Some(resolver)) => match resolver(&e) {
+                Some(code) => InternalRpcError::CustomAny(code, e),
+                None => InternalRpcError::AnyError(e)
+            },
+            (err, _) => err
+        }
+    }
+
     pub fn get_data(&self) -> &T {
         &self.data
     }
@@ -114,4 +171,188 @@ pub fn parse_params<P: DeserializeOwned>(mut value: Value) -> Result<P, Internal
     }
 
     serde_json::from_value(value).map_err(|e| InternalRpcError::InvalidJSONParams(e))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, ResponseError};
+    use anyhow::Context as AnyContext;
+    use serde::Deserialize;
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct AddParams {
+        value: u64
+    }
+
+    fn handler() -> RPCHandler<u64> {
+        let mut handler = RPCHandler::new(1u64);
+        handler.register_typed::<AddParams, u64, _>("add", |context, params| Box::pin(async move {
+            let base: &u64 = context.get()?;
+            Ok(params.value + *base)
+        }));
+
+        // Closures can capture their own state
+        let offset = 10u64;
+        handler.register_method("offset", move |_, _| Box::pin(async move {
+            Ok(json!(offset))
+        }));
+
+        handler.register_method("validate", |_, _| Box::pin(async move {
+            let result: Result<Value, InternalRpcError> = Err(InternalRpcError::InvalidJSONRequest);
+            Ok(result.context("Value is too big")?)
+        }));
+
+        handler.register_method("custom", |_, _| Box::pin(async move {
+            let err = anyhow::anyhow!("Not found").context("Error while retrieving value");
+            Err::<Value, _>(InternalRpcError::CustomAny(250, err))
+        }));
+
+        handler
+    }
+
+    fn request(method: &str, params: Value) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "jsonrpc": JSON_RPC_VERSION,
+            "id": 1,
+            "method": method,
+            "params": params
+        })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_typed_params() {
+        let handler = handler();
+        let response = handler.handle_request(&request("add", json!({ "value": 41 }))).await.unwrap();
+        assert_eq!(response["result"], json!(42));
+
+        let response = handler.handle_request(&request("offset", Value::Null)).await.unwrap();
+        assert_eq!(response["result"], json!(10));
+    }
+
+    #[tokio::test]
+    async fn test_typed_params_invalid() {
+        let handler = handler();
+        // Missing field
+        let err = handler.handle_request(&request("add", Value::Null)).await.unwrap_err();
+        assert_eq!(err.to_json()["error"]["code"], json!(-32602));
+
+        // Invalid type
+        let err = handler.handle_request(&request("add", json!({ "value": "hello" }))).await.unwrap_err();
+        assert_eq!(err.to_json()["error"]["code"], json!(-32602));
+    }
+
+    async fn error_json(body: &[u8]) -> Value {
+        let handler = handler();
+        let err = handler.handle_request(body).await.unwrap_err();
+        // Application level errors are sent with HTTP 200
+        assert_eq!(err.status_code(), StatusCode::OK);
+        assert_eq!(err.error_response().status(), StatusCode::OK);
+        err.to_json()
+    }
+
+    #[tokio::test]
+    async fn test_parse_error() {
+        assert_eq!(error_json(b"{ invalid").await, json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": -32700, "message": "Invalid body in request" }
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_request() {
+        assert_eq!(error_json(b"42").await, json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": -32600, "message": "Invalid JSON request" }
+        }));
+
+        // Missing method, id is kept
+        assert_eq!(error_json(br#"{"jsonrpc": "2.0", "id": 7}"#).await, json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "error": { "code": -32600, "message": "Invalid JSON request" }
+        }));
+
+        assert_eq!(error_json(br#"{"jsonrpc": "1.0", "id": "a", "method": "add"}"#).await, json!({
+            "jsonrpc": "2.0",
+            "id": "a",
+            "error": { "code": -32600, "message": "Expected json_rpc set to '2.0'" }
+        }));
+
+        assert_eq!(error_json(b"[]").await["error"]["code"], json!(-32600));
+    }
+
+    #[tokio::test]
+    async fn test_method_not_found() {
+        assert_eq!(error_json(&request("unknown", Value::Null)).await, json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": -32601, "message": "Method 'unknown' in request was not found" }
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_params() {
+        assert_eq!(error_json(&request("add", Value::Null)).await, json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": -32602, "message": "Invalid params: missing field `value`" }
+        }));
+
+        // Context is the message, the validation error keeps its code
+        assert_eq!(error_json(&request("validate", Value::Null)).await, json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": -32600, "message": "Value is too big", "data": ["Invalid JSON request"] }
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_application_error() {
+        assert_eq!(error_json(&request("custom", Value::Null)).await, json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": 250, "message": "Error while retrieving value", "data": ["Not found"] }
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_error_code_resolver() {
+        let mut handler = handler();
+        handler.register_method("any", |_, _| Box::pin(async move {
+            Err::<Value, _>(InternalRpcError::from(anyhow::anyhow!("Not found").context("Error while retrieving value")))
+        }));
+
+        let err = handler.handle_request(&request("any", Value::Null)).await.unwrap_err();
+        assert_eq!(err.to_json()["error"]["code"], json!(-32004));
+
+        handler.set_error_code_resolver(|err| err.root_cause().to_string().eq("Not found").then_some(300));
+        let err = handler.handle_request(&request("any", Value::Null)).await.unwrap_err();
+        assert_eq!(err.to_json(), json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": 300, "message": "Error while retrieving value", "data": ["Not found"] }
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_batch_errors() {
+        let handler = handler();
+        let body = serde_json::to_vec(&json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "offset" },
+            { "jsonrpc": "2.0", "id": 2 },
+            42,
+            // Notification has no response
+            { "jsonrpc": "2.0", "method": "offset" }
+        ])).unwrap();
+
+        let response = handler.handle_request(&body).await.unwrap();
+        assert_eq!(response, json!([
+            { "jsonrpc": "2.0", "id": 1, "result": 10 },
+            { "jsonrpc": "2.0", "id": 2, "error": { "code": -32600, "message": "Invalid JSON request" } },
+            { "jsonrpc": "2.0", "id": null, "error": { "code": -32600, "message": "Invalid JSON request" } }
+        ]));
+    }
+}
@@ -1,30 +1,151 @@
-use std::{collections::{HashMap, HashSet}, hash::Hash, borrow::Cow};
+use std::{collections::{HashMap, HashSet}, hash::Hash, borrow::Cow, sync::Arc};
 use actix_web::web::Bytes;
 use async_trait::async_trait;
-use log::{trace, debug};
+use log::{trace, debug, error};
 use serde_json::{Value, json};
 use serde::{de::DeserializeOwned, Serialize};
 use tokio::sync::RwLock;
 use crate::{
-    api::{EventResult, SubscribeParams},
+    api::{EventFilter, EventResult, SubscribeAddressParams, SubscribeParams},
     context::Context,
+    crypto::PublicKey,
     rpc_server::{
+        ClientIp,
         Id,
         InternalRpcError,
         RPCHandler,
         RpcRequest,
         RpcResponse,
-        RpcResponseError
+        RpcResponseError,
+        JSON_RPC_VERSION
     }
 };
 use super::{WebSocketSessionShared, WebSocketHandler};
 
+// Maximum addresses a client can watch through `subscribe_address`
+pub const MAX_SUBSCRIBED_ADDRESSES: usize = 1000;
+
+// Subscription of a client to an event
+#[derive(Clone, Debug)]
+struct Subscription {
+    // id of the subscribe request, used in each notification
+    id: Option<Id>,
+    filter: Option<EventFilter>
+}
+
+// Addresses watched by a client
+#[derive(Clone, Debug)]
+struct AddressSubscription {
+    // id of the first subscribe_address request, used in each notification
+    id: Option<Id>,
+    keys: HashSet<PublicKey>
+}
+
+// All the subscriptions of a client
+#[derive(Clone, Debug)]
+struct SessionSubscriptions<E> {
+    events: HashMap<E, Subscription>,
+    addresses: Option<AddressSubscription>
+}
+
+impl<E> Default for SessionSubscriptions<E> {
+    fn default() -> Self {
+        Self {
+            events: HashMap::new(),
+            addresses: None
+        }
+    }
+}
+
 // generic websocket handler supporting event subscriptions 
 pub struct EventWebSocketHandler<T: Sync + Send + Clone + 'static, E: Serialize + DeserializeOwned + Sync + Send + Eq + Hash + Clone + 'static> {
-    events: RwLock<HashMap<WebSocketSessionShared<Self>, HashMap<E, Option<Id>>>>,
+    events: RwLock<HashMap<WebSocketSessionShared<Self>, SessionSubscriptions<E>>>,
     handler: RPCHandler<T>
 }
 
+// Select the subscriptions to notify for an event
+// `filter` is called with the filter set by each client
+fn select_subscriptions<'a, K, E, F>(sessions: &'a HashMap<K, SessionSubscriptions<E>>, event: &'a E, filter: F) -> impl Iterator<Item = (&'a K, &'a Option<Id>)>
+where
+    E: Eq + Hash,
+    F: Fn(Option<&EventFilter>) -> bool
+{
+    sessions.iter().filter_map(move |(session, subscriptions)| {
+        subscriptions.events.get(event)
+            .filter(|subscription| filter(subscription.filter.as_ref()))
+            .map(|subscription| (session, &subscription.id))
+    })
+}
+
+// Select the clients watching this account
+fn select_address_subscriptions<'a, K, E>(sessions: &'a HashMap<K, SessionSubscriptions<E>>, key: &'a PublicKey) -> impl Iterator<Item = (&'a K, &'a Option<Id>)> {
+    sessions.iter().filter_map(move |(session, subscriptions)| {
+        subscriptions.addresses.as_ref()
+            .filter(|subscription| subscription.keys.contains(key))
+            .map(|subscription| (session, &subscription.id))
+    })
+}
+
+// Add the keys to the addresses watched by a client
+// Nothing is added if the limit would be exceeded
+fn add_subscribed_keys<I: IntoIterator<Item = PublicKey>>(subscription: &mut Option<AddressSubscription>, id: Option<Id>, keys: I) -> Result<usize, InternalRpcError> {
+    let subscription = subscription.get_or_insert_with(|| AddressSubscription { id, keys: HashSet::new() });
+    let new_keys: HashSet<PublicKey> = keys.into_iter()
+        .filter(|key| !subscription.keys.contains(key))
+        .collect();
+    if subscription.keys.len() + new_keys.len() > MAX_SUBSCRIBED_ADDRESSES {
+        return Err(InternalRpcError::TooManyAddresses(MAX_SUBSCRIBED_ADDRESSES))
+    }
+
+    subscription.keys.extend(new_keys);
+    Ok(subscription.keys.len())
+}
+
+// Borrowed subscription id used to group the clients subscribed with the same id
+#[derive(PartialEq, Eq, Hash)]
+enum IdKey<'a> {
+    None,
+    String(&'a str),
+    Number(usize)
+}
+
+impl<'a> From<&'a Option<Id>> for IdKey<'a> {
+    fn from(id: &'a Option<Id>) -> Self {
+        match id {
+            None => Self::None,
+            Some(Id::String(id)) => Self::String(id),
+            Some(Id::Number(id)) => Self::Number(*id)
+        }
+    }
+}
+
+// Build the frame to send to each selected client
+// The payload is serialized only once, and a frame is built once per distinct subscription id
+// Clients subscribed with the same id share the same frame
+fn build_event_frames<'a, K, P, I>(subscriptions: I, payload: &P) -> Result<Vec<(K, Arc<str>)>, serde_json::Error>
+where
+    K: Clone + 'a,
+    P: Serialize,
+    I: Iterator<Item = (&'a K, &'a Option<Id>)>
+{
+    let payload = serde_json::to_string(payload)?;
+    let mut frames: HashMap<IdKey<'a>, Arc<str>> = HashMap::new();
+    let mut selected = Vec::new();
+    for (client, id) in subscriptions {
+        let frame = match frames.get(&IdKey::from(id)) {
+            Some(frame) => frame.clone(),
+            None => {
+                let frame: Arc<str> = format!("{{\"jsonrpc\":\"{}\",\"id\":{},\"result\":{}}}", JSON_RPC_VERSION, serde_json::to_string(id)?, payload).into();
+                frames.insert(IdKey::from(id), frame.clone());
+                frame
+            }
+        };
+        selected.push((client.clone(), frame));
+    }
+
+    Ok(selected)
+}
+
 impl<T, E> EventWebSocketHandler<T, E>
 where
     T: Sync + Send + Clone + 'static,
@@ -41,7 +162,7 @@ where
         trace!("getting tracked events");
         let sessions = self.events.read().await;
         trace!("tracked events sessions locked");
-        HashSet::from_iter(sessions.values().map(|e| e.keys().cloned()).flatten())
+        HashSet::from_iter(sessions.values().map(|e| e.events.keys().cloned()).flatten())
     }
 
     pub async fn is_event_tracked(&self, event: &E) -> bool {
@@ -50,43 +171,87 @@ where
         trace!("tracked events sessions locked");
         sessions
             .values()
-            .find(|e| e.keys().into_iter().find(|x| *x == event).is_some())
+            .find(|e| e.events.keys().into_iter().find(|x| *x == event).is_some())
             .is_some()
     }
 
+    // Get all the accounts watched by at least one client
+    pub async fn get_subscribed_keys(&self) -> HashSet<PublicKey> {
+        trace!("getting subscribed keys");
+        let sessions = self.events.read().await;
+        trace!("subscribed keys sessions locked");
+        sessions.values()
+            .filter_map(|subscriptions| subscriptions.addresses.as_ref())
+            .flat_map(|subscription| subscription.keys.iter().cloned())
+            .collect()
+    }
+
+    // Notify all the clients subscribed to this event
     pub async fn notify(&self, event: &E, value: Value) {
-        let value = json!(EventResult { event: Cow::Borrowed(event), value });
+        self.notify_filtered(event, value, |_| true).await
+    }
+
+    // Notify only the clients subscribed to this event with a filter accepted by `filter`
+    pub async fn notify_filtered<F: Fn(Option<&EventFilter>) -> bool>(&self, event: &E, value: Value, filter: F) {
+        let result = EventResult { event: Cow::Borrowed(event), value };
         debug!("notifying event");
-        let sessions = {
+        let frames = {
             let events = self.events.read().await;
             trace!("events locked for propagation");
-            events.clone()
+            match build_event_frames(select_subscriptions(&*events, event, filter), &result) {
+                Ok(frames) => frames,
+                Err(e) => {
+                    error!("Error while serializing event: {}", e);
+                    return;
+                }
+            }
         };
 
-        for (session, subscriptions) in sessions.iter() {
-            if let Some(id) = subscriptions.get(event) {
-                let response = json!(RpcResponse::new(Cow::Borrowed(&id), Cow::Borrowed(&value)));
-                trace!("sending event to #{}", session.id);
-                if let Err(e) = session.send_text(response.to_string()).await {
-                    debug!("Error occured while notifying a new event: {}", e);
-                };
-                trace!("event sent to #{}", session.id);
+        self.send_frames(frames);
+        debug!("end event propagation");
+    }
+
+    // Notify the clients watching this account through `subscribe_address`
+    // They don't need to be subscribed to the event
+    pub async fn notify_address(&self, event: &E, key: &PublicKey, value: Value) {
+        let result = EventResult { event: Cow::Borrowed(event), value };
+        debug!("notifying address event");
+        let frames = {
+            let events = self.events.read().await;
+            trace!("events locked for address propagation");
+            match build_event_frames(select_address_subscriptions(&*events, key), &result) {
+                Ok(frames) => frames,
+                Err(e) => {
+                    error!("Error while serializing address event: {}", e);
+                    return;
+                }
             }
-        }
+        };
 
-        debug!("end event propagation");
+        self.send_frames(frames);
+        debug!("end address event propagation");
     }
 
-    async fn subscribe_session_to_event(&self, session: &WebSocketSessionShared<Self>, event: E, id: Option<Id>) -> Result<(), RpcResponseError> {
+    fn send_frames(&self, frames: Vec<(WebSocketSessionShared<Self>, Arc<str>)>) {
+        // Messages are queued to each session, a slow client doesn't delay the others
+        for (session, frame) in frames {
+            trace!("sending event to #{}", session.id);
+            if let Err(e) = session.send_shared_text(frame) {
+                debug!("Error occured while notifying a new event: {}", e);
+            };
+        }
+    }
+
+    async fn subscribe_session_to_event(&self, session: &WebSocketSessionShared<Self>, event: E, filter: Option<EventFilter>, id: Option<Id>) -> Result<(), RpcResponseError> {
         trace!("subscribing session to event");
         let mut sessions = self.events.write().await;
         trace!("subscribe events locked");
-        let events = sessions.entry(session.clone()).or_insert_with(HashMap::new);
+        let events = &mut sessions.entry(session.clone()).or_default().events;
         if events.contains_key(&event) {
             return Err(RpcResponseError::new(id, InternalRpcError::EventAlreadySubscribed));
         }
 
-        events.insert(event, id);
+        events.insert(event, Subscription { id, filter });
         Ok(())
     }
 
@@ -95,7 +260,7 @@ where
         let mut sessions = self.events.write().await;
         trace!("unsubscribe events locked");
         
-        let events = sessions.entry(session.clone()).or_insert_with(HashMap::new);
+        let events = &mut sessions.entry(session.clone()).or_default().events;
         if !events.contains_key(&event) {
             return Err(RpcResponseError::new(id, InternalRpcError::EventNotSubscribed));
         }
@@ -104,10 +269,52 @@ where
         Ok(())
     }
 
-    fn parse_event(&self, request: &mut RpcRequest) -> Result<E, RpcResponseError> {
+    // Watch the addresses for the session, returns how many addresses are watched
+    async fn subscribe_session_to_addresses(&self, session: &WebSocketSessionShared<Self>, keys: Vec<PublicKey>, id: Option<Id>) -> Result<usize, RpcResponseError> {
+        trace!("subscribing session to addresses");
+        let mut sessions = self.events.write().await;
+        trace!("subscribe addresses locked");
+        let subscriptions = sessions.entry(session.clone()).or_default();
+        add_subscribed_keys(&mut subscriptions.addresses, id.clone(), keys).map_err(|e| RpcResponseError::new(id, e))
+    }
+
+    // Stop watching the addresses for the session, all of them if none is set
+    async fn unsubscribe_session_from_addresses(&self, session: &WebSocketSessionShared<Self>, keys: Vec<PublicKey>) -> usize {
+        trace!("unsubscribing session from addresses");
+        let mut sessions = self.events.write().await;
+        trace!("unsubscribe addresses locked");
+        let Some(subscriptions) = sessions.get_mut(session) else {
+            return 0
+        };
+
+        if let Some(subscription) = subscriptions.addresses.as_mut() {
+            if keys.is_empty() {
+                subscription.keys.clear();
+            } else {
+                for key in keys.iter() {
+                    subscription.keys.remove(key);
+                }
+            }
+
+            if !subscription.keys.is_empty() {
+                return subscription.keys.len()
+            }
+        }
+
+        subscriptions.addresses = None;
+        0
+    }
+
+    fn parse_addresses(&self, request: &mut RpcRequest) -> Result<Vec<PublicKey>, RpcResponseError> {
+        let value = request.params.take().ok_or_else(|| RpcResponseError::new(request.id.clone(), InternalRpcError::ExpectedParams))?;
+        let params: SubscribeAddressParams = serde_json::from_value(value).map_err(|e| RpcResponseError::new(request.id.clone(), InternalRpcError::InvalidJSONParams(e)))?;
+        Ok(params.addresses.into_iter().map(|address| address.to_public_key()).collect())
+    }
+
+    fn parse_event(&self, request: &mut RpcRequest) -> Result<(E, Option<EventFilter>), RpcResponseError> {
         let value = request.params.take().ok_or_else(|| RpcResponseError::new(request.id.clone(), InternalRpcError::ExpectedParams))?;
         let params: SubscribeParams<E> = serde_json::from_value(value).map_err(|e| RpcResponseError::new(request.id.clone(), InternalRpcError::InvalidJSONParams(e)))?;
-        Ok(params.notify.into_owned())
+        Ok((params.notify.into_owned(), params.filter))
     }
 
     async fn execute_method_internal(&self, context: &Context, value: Value) -> Result<Option<Value>, RpcResponseError> {
@@ -115,15 +322,25 @@ where
         let method = request.method.clone();
         match method.as_str() {
             "subscribe" => {
-                let event = self.parse_event(&mut request)?;
-                self.subscribe_session_to_event(context.get::<WebSocketSessionShared<Self>>().unwrap(), event, request.id.clone()).await?;
+                let (event, filter) = self.parse_event(&mut request)?;
+                self.subscribe_session_to_event(context.get::<WebSocketSessionShared<Self>>().unwrap(), event, filter, request.id.clone()).await?;
                 Ok(Some(json!(RpcResponse::new(Cow::Borrowed(&request.id), Cow::Owned(Value::Bool(true))))))
             },
             "unsubscribe" => {
-                let event = self.parse_event(&mut request)?;
+                let (event, _) = self.parse_event(&mut request)?;
                 self.unsubscribe_session_from_event(context.get::<WebSocketSessionShared<Self>>().unwrap(), event, request.id.clone()).await?;
                 Ok(Some(json!(RpcResponse::new(Cow::Borrowed(&request.id), Cow::Owned(Value::Bool(true))))))
             },
+            "subscribe_address" => {
+                let keys = self.parse_addresses(&mut request)?;
+                let count = self.subscribe_session_to_addresses(context.get::<WebSocketSessionShared<Self>>().unwrap(), keys, request.id.clone()).await?;
+                Ok(Some(json!(RpcResponse::new(Cow::Borrowed(&request.id), Cow::Owned(json!(count))))))
+            },
+            "unsubscribe_address" => {
+                let keys = self.parse_addresses(&mut request)?;
+                let count = self.unsubscribe_session_from_addresses(context.get::<WebSocketSessionShared<Self>>().unwrap(), keys).await;
+                Ok(Some(json!(RpcResponse::new(Cow::Borrowed(&request.id), Cow::Owned(json!(count))))))
+            },
             _ => self.handler.execute_method(context, request).await
         }
     }
@@ -135,21 +352,21 @@ where
         let mut context = Context::default();
         context.store(session.clone());
         context.store(self.handler.get_data().clone());
+        if let Some(addr) = session.get_request().peer_addr() {
+            context.store(ClientIp(addr.ip()));
+        }
 
         match request {
             e @ Value::Object(_) => self.execute_method_internal(&context, e).await.map(|e| e.unwrap_or(Value::Null)),
-            Value::Array(requests) => {
+            Value::Array(requests) if !requests.is_empty() => {
                 let mut responses = Vec::new();
                 for value in requests {
-                    if value.is_object() {
-                        let response = match self.execute_method_internal(&context, value).await {
-                            Ok(response) => json!(response),
-                            Err(e) => e.to_json()
-                        };
-                        responses.push(response);
-                    } else {
-                        responses.push(RpcResponseError::new(None, InternalRpcError::InvalidJSONRequest).to_json());
-                    }
+                    // Notifications have no response
+                    match self.execute_method_internal(&context, value).await {
+                        Ok(Some(response)) => responses.push(response),
+                        Ok(None) => {},
+                        Err(e) => responses.push(e.to_json())
+                    };
                 }
                 Ok(serde_json::to_value(responses).map_err(|err| RpcResponseError::new(None, InternalRpcError::SerializeResponse(err)))?)
             },
@@ -185,4 +402,168 @@ where
         session.send_text(response.to_string()).await?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::KeyPair;
+    use super::*;
+
+    type Sessions = HashMap<u64, SessionSubscriptions<&'static str>>;
+
+    fn subscribe(sessions: &mut Sessions, client: u64, event: &'static str, filter: Option<EventFilter>) {
+        sessions.entry(client).or_default().events.insert(event, Subscription { id: Some(Id::Number(client as usize)), filter });
+    }
+
+    fn selected(sessions: &Sessions, event: &'static str, key: &crate::crypto::PublicKey) -> Vec<u64> {
+        let mut clients: Vec<u64> = select_subscriptions(sessions, &event, |filter| filter.is_some_and(|f| f.has_key(key)))
+            .map(|(client, _)| *client)
+            .collect();
+        clients.sort();
+        clients
+    }
+
+    #[test]
+    fn test_select_subscriptions_with_filter() {
+        let alice = KeyPair::new().get_public_key().compress();
+        let bob = KeyPair::new().get_public_key().compress();
+        let charlie = KeyPair::new().get_public_key().compress();
+
+        let mut sessions = HashMap::new();
+        subscribe(&mut sessions, 1, "balance", Some(EventFilter { addresses: vec![alice.as_address(true)] }));
+        subscribe(&mut sessions, 2, "balance", Some(EventFilter { addresses: vec![bob.as_address(true)] }));
+        // No filter, it receive nothing from filtered notifications
+        subscribe(&mut sessions, 3, "balance", None);
+        subscribe(&mut sessions, 4, "block", None);
+
+        assert_eq!(selected(&sessions, "balance", &alice), vec![1]);
+        assert_eq!(selected(&sessions, "balance", &bob), vec![2]);
+        assert!(selected(&sessions, "balance", &charlie).is_empty());
+
+        // Unfiltered notifications are sent to every subscribed client
+        let mut clients: Vec<u64> = select_subscriptions(&sessions, &"balance", |_| true).map(|(client, _)| *client).collect();
+        clients.sort();
+        assert_eq!(clients, vec![1, 2, 3]);
+    }
+
+    // Payload counting how many times it is serialized
+    struct CountingPayload {
+        value: Value,
+        count: std::sync::atomic::AtomicUsize
+    }
+
+    impl Serialize for CountingPayload {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.value.serialize(serializer)
+        }
+    }
+
+    #[test]
+    fn test_event_frames_serialized_once() {
+        // 1000 clients, most of them using one of the 10 same ids
+        let mut sessions: Sessions = HashMap::new();
+        for client in 0..1000u64 {
+            let id = match client {
+                0 => None,
+                1 => Some(Id::String("block".to_owned())),
+                _ => Some(Id::Number(client as usize % 10))
+            };
+            sessions.entry(client).or_default().events.insert("block", Subscription { id, filter: None });
+        }
+
+        let payload = CountingPayload {
+            value: json!({ "event": "block", "height": 10 }),
+            count: Default::default()
+        };
+        let frames = build_event_frames(select_subscriptions(&sessions, &"block", |_| true), &payload).unwrap();
+        assert_eq!(frames.len(), 1000);
+        assert_eq!(payload.count.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // One frame allocated per distinct id
+        let allocations: HashSet<*const u8> = frames.iter().map(|(_, frame)| frame.as_ptr()).collect();
+        assert_eq!(allocations.len(), 12);
+
+        // Each client still receives its own id
+        for (client, frame) in frames {
+            let response: Value = serde_json::from_str(&frame).unwrap();
+            assert_eq!(response["id"], json!(sessions[&client].events["block"].id));
+            assert_eq!(response["jsonrpc"], JSON_RPC_VERSION);
+            assert_eq!(response["result"], payload.value);
+        }
+    }
+
+    #[test]
+    fn test_subscribe_params_filter() {
+        let key = KeyPair::new().get_public_key().compress();
+        let address = key.as_address(false);
+        let params: SubscribeParams<String> = serde_json::from_value(json!({
+            "notify": "balance_updated",
+            "filter": { "addresses": [address.to_string()] }
+        })).unwrap();
+        assert!(params.filter.unwrap().has_key(&key));
+
+        // Filter is optional
+        let params: SubscribeParams<String> = serde_json::from_value(json!({ "notify": "new_block" })).unwrap();
+        assert!(params.filter.is_none());
+    }
+
+    fn address_frames(sessions: &Sessions, key: &PublicKey, value: Value) -> Vec<(u64, Value)> {
+        let result = EventResult { event: Cow::Borrowed(&"transaction_received"), value };
+        let mut frames: Vec<(u64, Value)> = build_event_frames(select_address_subscriptions(sessions, key), &result).unwrap()
+            .into_iter()
+            .map(|(client, frame)| (client, serde_json::from_str(&frame).unwrap()))
+            .collect();
+        frames.sort_by_key(|(client, _)| *client);
+        frames
+    }
+
+    #[test]
+    fn test_two_clients_disjoint_addresses() {
+        let alice = KeyPair::new().get_public_key().compress();
+        let bob = KeyPair::new().get_public_key().compress();
+        let charlie = KeyPair::new().get_public_key().compress();
+
+        let mut sessions: Sessions = HashMap::new();
+        let params: SubscribeAddressParams = serde_json::from_value(json!({ "addresses": [alice.as_address(false).to_string()] })).unwrap();
+        let keys = params.addresses.into_iter().map(|address| address.to_public_key());
+        assert_eq!(add_subscribed_keys(&mut sessions.entry(1).or_default().addresses, Some(Id::Number(1)), keys).unwrap(), 1);
+        assert_eq!(add_subscribed_keys(&mut sessions.entry(2).or_default().addresses, Some(Id::Number(2)), [bob.clone()]).unwrap(), 1);
+        // Already watched address isn't counted twice, and the first id is kept
+        assert_eq!(add_subscribed_keys(&mut sessions.entry(2).or_default().addresses, Some(Id::Number(3)), [bob.clone()]).unwrap(), 1);
+        // Subscribed to an event but not watching any address
+        subscribe(&mut sessions, 3, "transaction_received", None);
+
+        let frames = address_frames(&sessions, &alice, json!({ "topoheight": 10, "orphaned": false }));
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].0, 1);
+        assert_eq!(frames[0].1["id"], json!(1));
+        assert_eq!(frames[0].1["result"]["event"], "transaction_received");
+        assert_eq!(frames[0].1["result"]["topoheight"], 10);
+
+        // A reorg re-emits the event with the orphaned flag to the same client only
+        let frames = address_frames(&sessions, &bob, json!({ "topoheight": null, "orphaned": true }));
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].0, 2);
+        assert_eq!(frames[0].1["id"], json!(2));
+        assert_eq!(frames[0].1["result"]["orphaned"], true);
+
+        assert!(address_frames(&sessions, &charlie, json!({})).is_empty());
+
+        let keys: HashSet<&PublicKey> = sessions.values().filter_map(|s| s.addresses.as_ref()).flat_map(|s| s.keys.iter()).collect();
+        assert_eq!(keys, HashSet::from([&alice, &bob]));
+    }
+
+    #[test]
+    fn test_subscribed_addresses_limit() {
+        let mut subscription = None;
+        let keys: Vec<PublicKey> = (0..MAX_SUBSCRIBED_ADDRESSES).map(|_| KeyPair::new().get_public_key().compress()).collect();
+        assert_eq!(add_subscribed_keys(&mut subscription, None, keys.clone()).unwrap(), MAX_SUBSCRIBED_ADDRESSES);
+        // Already watched addresses are accepted
+        assert_eq!(add_subscribed_keys(&mut subscription, None, keys[0..10].to_vec()).unwrap(), MAX_SUBSCRIBED_ADDRESSES);
+
+        let extra = KeyPair::new().get_public_key().compress();
+        assert!(matches!(add_subscribed_keys(&mut subscription, None, [extra.clone()]), Err(InternalRpcError::TooManyAddresses(MAX_SUBSCRIBED_ADDRESSES))));
+        assert!(!subscription.unwrap().keys.contains(&extra));
+    }
+}
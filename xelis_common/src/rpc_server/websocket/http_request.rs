@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use actix_web::{dev::RequestHead, http::{Uri, header::HeaderMap}};
 use reqwest::{Method, Version};
 use actix_web::HttpRequest as ActixHttpRequest;
@@ -32,6 +33,12 @@ impl HttpRequest {
     self.head().version
     }
 
+    /// Address of the remote peer, if known.
+    #[inline]
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.head().peer_addr
+    }
+
     #[inline]
     /// Returns request's headers.
     pub fn headers(&self) -> &HeaderMap {
@@ -71,6 +71,8 @@ pub enum WebSocketError {
 
 enum InnerMessage {
     Text(String),
+    // Text shared between several sessions
+    SharedText(Arc<str>),
     Close(Option<CloseReason>),
 }
 
@@ -95,6 +97,15 @@ where
         Ok(())
     }
 
+    // Send a text message shared with other sessions without copying it
+    // This never waits, the message is queued for the task handling the session
+    pub fn send_shared_text(&self, value: Arc<str>) -> Result<(), WebSocketError> {
+        self.channel.send(InnerMessage::SharedText(value))
+            .map_err(|_| WebSocketError::ChannelClosed)?;
+
+        Ok(())
+    }
+
     // Send a ping message to the session
     // this must be called from the task handling the session only
     async fn ping(&self) -> Result<(), WebSocketError> {
@@ -342,6 +353,13 @@ impl<H> WebSocketServer<H> where H: WebSocketHandler + 'static {
                                 break Some(CloseReason::from(CloseCode::Error));
                             }
                         },
+                        InnerMessage::SharedText(text) => {
+                            trace!("Sending shared text message to session #{}: {}", session.id, text);
+                            if let Err(e) = session.send_text_internal(&*text).await {
+                                debug!("Error while sending shared text message to session #{}: {}", session.id, e);
+                                break Some(CloseReason::from(CloseCode::Error));
+                            }
+                        },
                         InnerMessage::Close(reason) => {
                             debug!("Closing session #{} with reason: {:?}", session.id, reason);
                             break reason;
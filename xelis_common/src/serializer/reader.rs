@@ -1,7 +1,7 @@
 use std::array::TryFromSliceError;
 use thiserror::Error;
 
-use crate::crypto::Hash;
+use crate::crypto::{elgamal::PublicKeyError, Hash};
 
 use super::Serializer;
 
@@ -18,6 +18,8 @@ pub enum ReaderError {
     #[error(transparent)]
     TryFromSliceError(#[from] TryFromSliceError),
     #[error(transparent)]
+    InvalidPublicKey(#[from] PublicKeyError),
+    #[error(transparent)]
     Any(anyhow::Error)
 }
 
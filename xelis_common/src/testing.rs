@@ -0,0 +1,66 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering}
+};
+
+// Used to give a different directory to tests using the same name
+static DIRS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// Directory created in the temporary directory of the system for a test
+// It is deleted with its content when dropped, also when the test panics
+pub struct TempDir {
+    path: PathBuf
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let id = DIRS_COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("xelis-{}-{}-{}", name, std::process::id(), id));
+        // Left by a previous run killed before the cleanup
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("temporary directory for test");
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Path with a trailing separator as expected by the storages
+    pub fn to_dir_path(&self) -> String {
+        format!("{}/", self.path.to_string_lossy())
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_dir_removed_on_panic() {
+        let path = std::panic::catch_unwind(|| {
+            let dir = TempDir::new("temp-dir-panic");
+            std::fs::write(dir.join("file"), b"data").unwrap();
+            assert!(dir.exists());
+            std::panic::resume_unwind(Box::new(dir.path().to_path_buf()))
+        }).unwrap_err();
+
+        let path = path.downcast::<PathBuf>().unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -1,9 +1,10 @@
 // A simple module to define the time types used in the project
 
-use std::time::{SystemTime, UNIX_EPOCH, Duration};
-
-// Millis timestamps used to determine it using its type
-pub type TimestampMillis = u64;
+use std::{
+    fmt::{self, Display, Formatter},
+    time::{SystemTime, UNIX_EPOCH, Duration}
+};
+use serde::{Deserialize, Serialize};
 
 // Seconds timestamps used to determine it using its type
 pub type TimestampSeconds = u64;
@@ -23,6 +24,152 @@ pub fn get_current_time_in_seconds() -> TimestampSeconds {
 // return timestamp in milliseconds
 // We cast it to u64 as we have plenty of time before it overflows
 // See more: https://github.com/xelis-project/xelis-blockchain/issues/18
-pub fn get_current_time_in_millis() -> TimestampMillis {
-    get_current_time().as_millis() as TimestampMillis
+pub fn get_current_time_in_millis() -> u64 {
+    get_current_time().as_millis() as u64
+}
+
+// Highest timestamp that can be formatted as RFC3339: 9999-12-31T23:59:59.999Z
+pub const MAX_TIMESTAMP_MILLIS: u64 = 253_402_300_799_999;
+
+const MILLIS_PER_DAY: u64 = 86_400_000;
+
+// Check if the timestamp fits in the range supported by RFC3339
+pub const fn is_timestamp_in_range(timestamp: u64) -> bool {
+    timestamp <= MAX_TIMESTAMP_MILLIS
+}
+
+// Convert a number of days since the unix epoch to a (year, month, day) date
+// in the proleptic gregorian calendar
+// Based on the civil_from_days algorithm from Howard Hinnant
+pub const fn civil_from_days(days: u64) -> (u64, u32, u32) {
+    // Shift the epoch to 0000-03-01, so the leap day is the last day of the year
+    let z = days + 719_468;
+    // 400 years eras of 146097 days
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Month starting from March
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+// Format a timestamp in milliseconds as a RFC3339 UTC string
+// Example: 2024-02-29T12:30:05.123Z
+pub fn format_timestamp_millis(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days(timestamp / MILLIS_PER_DAY);
+    let millis_of_day = timestamp % MILLIS_PER_DAY;
+    let seconds_of_day = millis_of_day / 1000;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        (seconds_of_day / 60) % 60,
+        seconds_of_day % 60,
+        millis_of_day % 1000
+    )
+}
+
+// Same as format_timestamp_millis for a timestamp in seconds
+pub fn format_timestamp_seconds(timestamp: TimestampSeconds) -> String {
+    format_timestamp_millis(timestamp.saturating_mul(1000))
+}
+
+// Millis timestamp exposed in the API responses and params
+// It is serialized as an integer, its RFC3339 UTC format is given by `to_rfc3339`
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct TimestampMillis(u64);
+
+impl TimestampMillis {
+    pub const fn new(millis: u64) -> Self {
+        Self(millis)
+    }
+
+    pub const fn as_millis(&self) -> u64 {
+        self.0
+    }
+
+    // Check if the timestamp fits in the range supported by RFC3339
+    pub const fn is_in_range(&self) -> bool {
+        is_timestamp_in_range(self.0)
+    }
+
+    // Example: 2024-02-29T12:30:05.123Z
+    pub fn to_rfc3339(&self) -> String {
+        format_timestamp_millis(self.0)
+    }
+}
+
+impl From<u64> for TimestampMillis {
+    fn from(millis: u64) -> Self {
+        Self(millis)
+    }
+}
+
+impl From<TimestampMillis> for u64 {
+    fn from(timestamp: TimestampMillis) -> Self {
+        timestamp.0
+    }
+}
+
+impl Display for TimestampMillis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(365), (1971, 1, 1));
+        // 2000 is a leap year (divisible by 400)
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+        // 2100 is not a leap year (divisible by 100)
+        assert_eq!(civil_from_days(47_540), (2100, 2, 28));
+        assert_eq!(civil_from_days(47_541), (2100, 3, 1));
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp_millis(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(format_timestamp_millis(951_782_399_999), "2000-02-28T23:59:59.999Z");
+        assert_eq!(format_timestamp_millis(951_782_400_000), "2000-02-29T00:00:00.000Z");
+        assert_eq!(format_timestamp_millis(1_709_209_805_123), "2024-02-29T12:30:05.123Z");
+        assert_eq!(format_timestamp_millis(1_735_689_599_999), "2024-12-31T23:59:59.999Z");
+        assert_eq!(format_timestamp_millis(MAX_TIMESTAMP_MILLIS), "9999-12-31T23:59:59.999Z");
+        assert_eq!(format_timestamp_seconds(1_709_251_200), "2024-03-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_timestamp_range() {
+        assert!(is_timestamp_in_range(MAX_TIMESTAMP_MILLIS));
+        assert!(!is_timestamp_in_range(MAX_TIMESTAMP_MILLIS + 1));
+        assert_eq!(format_timestamp_millis(MAX_TIMESTAMP_MILLIS + 1), "10000-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_timestamp_millis_serde() {
+        let timestamp = TimestampMillis::new(1_709_209_805_123);
+        assert_eq!(serde_json::to_string(&timestamp).unwrap(), "1709209805123");
+        assert_eq!(serde_json::from_str::<TimestampMillis>("1709209805123").unwrap(), timestamp);
+        // Not a string, even in RFC3339
+        assert!(serde_json::from_str::<TimestampMillis>("\"2024-02-29T12:30:05.123Z\"").is_err());
+
+        assert_eq!(timestamp.to_rfc3339(), "2024-02-29T12:30:05.123Z");
+        assert_eq!(timestamp.to_string(), "1709209805123");
+        assert!(!TimestampMillis::new(MAX_TIMESTAMP_MILLIS + 1).is_in_range());
+    }
 }
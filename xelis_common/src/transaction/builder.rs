@@ -12,7 +12,7 @@ use std::{
 use crate::{
     account::CiphertextCache,
     api::DataElement,
-    config::XELIS_ASSET,
+    config::{ASSET_CREATION_BURN_AMOUNT, MAX_ASSET_DECIMALS, XELIS_ASSET},
     crypto::{
         elgamal::{
             Ciphertext,
@@ -33,6 +33,7 @@ use crate::{
             PC_GENS,
             BULLET_PROOF_SIZE,
         },
+        memo,
         Address,
         Hash,
         ProtocolTranscript,
@@ -40,11 +41,12 @@ use crate::{
         SIGNATURE_SIZE
     },
     serializer::{Reader, ReaderError, Serializer, Writer},
-    utils::calculate_tx_fee
+    utils::{calculate_extra_data_fee, calculate_tx_fee}
 };
 use thiserror::Error;
 use super::{
     extra_data::{ExtraData, PlaintextData},
+    AssetCreationPayload,
     BurnPayload,
     Reference,
     Role,
@@ -53,7 +55,9 @@ use super::{
     TransactionType,
     TransferPayload,
     EXTRA_DATA_LIMIT_SIZE,
-    MAX_TRANSFER_COUNT
+    MAX_TRANSFER_COUNT,
+    TX_EXTRA_DATA_LIMIT_SIZE,
+    TX_FLAG_EXTRA_DATA
 };
 
 #[derive(Error, Debug, Clone)]
@@ -66,10 +70,24 @@ pub enum GenerationError<T> {
     MaxTransferCountReached,
     #[error("Sender is receiver")]
     SenderIsReceiver,
+    #[error("Transfer amount cannot be zero")]
+    ZeroTransferAmount,
+    #[error("Burn amount cannot be zero")]
+    ZeroBurnAmount,
+    #[error("Asset decimals cannot be above {}", MAX_ASSET_DECIMALS)]
+    InvalidAssetDecimals,
+    #[error("Overflow detected while computing the transaction cost")]
+    Overflow,
     #[error("Extra data too large")]
     ExtraDataTooLarge,
     #[error("Encrypted extra data is too large")]
     EncryptedExtraDataTooLarge,
+    #[error("Transaction extra data is empty or too large")]
+    InvalidTxExtraDataSize,
+    #[error("Too many recipients to encrypt the transaction extra data")]
+    TooManyTxExtraDataRecipients,
+    #[error("Transaction extra data in plaintext can't start like an encrypted one")]
+    AmbiguousTxExtraData,
     #[error("Address is not on the same network as us")]
     InvalidNetwork,
     #[error("Extra data was provied with an integrated address")]
@@ -135,7 +153,8 @@ pub trait AccountState: FeeHelper {
 pub enum TransactionTypeBuilder {
     Transfers(Vec<TransferBuilder>),
     // We can use the same as final transaction
-    Burn(BurnPayload)
+    Burn(BurnPayload),
+    AssetCreation(AssetCreationPayload)
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -152,7 +171,13 @@ pub struct TransactionBuilder {
     version: u8,
     source: CompressedPublicKey,
     data: TransactionTypeBuilder,
-    fee_builder: FeeBuilder
+    fee_builder: FeeBuilder,
+    // Public data attached to the transaction, up to TX_EXTRA_DATA_LIMIT_SIZE bytes
+    #[serde(default)]
+    extra_data: Option<Vec<u8>>,
+    // Encrypt the extra data for the sender and the recipients only
+    #[serde(default)]
+    encrypt_extra_data: bool
 }
 
 // Internal struct for build
@@ -194,6 +219,8 @@ impl TransactionTypeBuilder {
             TransactionTypeBuilder::Burn(payload) => {
                 consumed.insert(payload.asset.clone());
             }
+            // Registration cost is paid in XELIS_ASSET
+            TransactionTypeBuilder::AssetCreation(_) => {}
         }
 
         consumed
@@ -209,7 +236,7 @@ impl TransactionTypeBuilder {
                     used_keys.push(transfer.destination.get_public_key().clone());
                 }
             }
-            TransactionTypeBuilder::Burn(_) => {}
+            TransactionTypeBuilder::Burn(_) | TransactionTypeBuilder::AssetCreation(_) => {}
         }
 
         used_keys
@@ -227,6 +254,7 @@ struct TransactionSigner {
     source_commitments: Vec<SourceCommitment>,
     reference: Reference,
     range_proof: RangeProof,
+    extra_data: Option<Vec<u8>>,
 }
 
 impl TransactionSigner {
@@ -243,6 +271,7 @@ impl TransactionSigner {
             source_commitments: self.source_commitments,
             range_proof: self.range_proof,
             reference: self.reference,
+            extra_data: self.extra_data,
             signature,
         }
     }
@@ -255,6 +284,44 @@ impl TransactionBuilder {
             source,
             data,
             fee_builder,
+            extra_data: None,
+            encrypt_extra_data: false
+        }
+    }
+
+    // Attach public extra data to the transaction
+    pub fn with_extra_data(mut self, extra_data: Option<Vec<u8>>) -> Self {
+        self.extra_data = extra_data;
+        self
+    }
+
+    // Encrypt the extra data so only the sender and the recipients can read it
+    pub fn with_encrypted_extra_data(mut self, encrypt: bool) -> Self {
+        self.encrypt_extra_data = encrypt;
+        self
+    }
+
+    // Recipients able to decrypt the extra data, without duplicates
+    fn get_extra_data_recipients(&self) -> Vec<&CompressedPublicKey> {
+        let mut recipients = Vec::new();
+        if let TransactionTypeBuilder::Transfers(transfers) = &self.data {
+            for transfer in transfers {
+                let key = transfer.destination.get_public_key();
+                if !recipients.contains(&key) {
+                    recipients.push(key);
+                }
+            }
+        }
+        recipients
+    }
+
+    // Size of the extra data once in the final TX
+    // Encryption overhead is included so it is paid in the fees
+    fn get_final_extra_data_size(&self) -> usize {
+        match &self.extra_data {
+            Some(extra_data) if self.encrypt_extra_data => memo::encrypted_size(extra_data.len(), self.get_extra_data_recipients().len()),
+            Some(extra_data) => extra_data.len(),
+            None => 0
         }
     }
 
@@ -312,8 +379,17 @@ impl TransactionBuilder {
                 size += payload.size();
                 0
             }
+            TransactionTypeBuilder::AssetCreation(payload) => {
+                size += payload.size();
+                0
+            }
         };
 
+        if self.extra_data.is_some() {
+            // u16 length + bytes
+            size += 2 + self.get_final_extra_data_size();
+        }
+
         // Range Proof
         let lg_n = (BULLET_PROOF_SIZE * (transfers_count + assets_used)).next_power_of_two().trailing_zeros() as usize;
         // Fixed size of the range proof
@@ -348,7 +424,8 @@ impl TransactionBuilder {
                     (0, 0)
                 };
 
-                let expected_fee = calculate_tx_fee(size, transfers, new_addresses);
+                let extra_data_size = self.get_final_extra_data_size();
+                let expected_fee = calculate_tx_fee(size, transfers, new_addresses) + calculate_extra_data_fee(extra_data_size);
                 (expected_fee as f64 * multiplier) as u64
             },
             // If the value is set, use it
@@ -377,36 +454,47 @@ impl TransactionBuilder {
                     ct -= Scalar::from(payload.amount)
                 }
             }
+            TransactionTypeBuilder::AssetCreation(_) => {
+                if *asset == XELIS_ASSET {
+                    ct -= Scalar::from(ASSET_CREATION_BURN_AMOUNT)
+                }
+            }
         }
 
         ct
     }
 
     /// Compute the full cost of the transaction
-    pub fn get_transaction_cost(&self, fee: u64, asset: &Hash) -> u64 {
-        let mut cost = 0;
+    /// Returns None if the cost is overflowing
+    pub fn get_transaction_cost(&self, fee: u64, asset: &Hash) -> Option<u64> {
+        let mut cost: u64 = 0;
 
         if *asset == XELIS_ASSET {
             // Fees are applied to the native blockchain asset only.
-            cost += fee;
+            cost = fee;
         }
 
         match &self.data {
             TransactionTypeBuilder::Transfers(transfers) => {
                 for transfer in transfers {
                     if &transfer.asset == asset {
-                        cost += transfer.amount;
+                        cost = cost.checked_add(transfer.amount)?;
                     }
                 }
             }
             TransactionTypeBuilder::Burn(payload) => {
                 if *asset == payload.asset {
-                    cost += payload.amount
+                    cost = cost.checked_add(payload.amount)?;
+                }
+            }
+            TransactionTypeBuilder::AssetCreation(_) => {
+                if *asset == XELIS_ASSET {
+                    cost = cost.checked_add(ASSET_CREATION_BURN_AMOUNT)?;
                 }
             }
         }
 
-        cost
+        Some(cost)
     }
 
     pub fn build<B: AccountState>(
@@ -414,9 +502,34 @@ impl TransactionBuilder {
         state: &mut B,
         source_keypair: &KeyPair,
     ) -> Result<Transaction, GenerationError<B::Error>> {
+        if let Some(extra_data) = &self.extra_data {
+            if extra_data.is_empty() || self.get_final_extra_data_size() > TX_EXTRA_DATA_LIMIT_SIZE {
+                return Err(GenerationError::InvalidTxExtraDataSize);
+            }
+
+            if !self.encrypt_extra_data && memo::has_encrypted_prefix(extra_data) {
+                return Err(GenerationError::AmbiguousTxExtraData);
+            }
+        }
+
         // Compute the fees
         let fee = self.estimate_fees(state)?;
 
+        let extra_data = match self.extra_data.take() {
+            Some(extra_data) if self.encrypt_extra_data => {
+                let recipients = self.get_extra_data_recipients()
+                    .into_iter()
+                    .map(|key| key.decompress())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| GenerationError::Proof(err.into()))?;
+
+                let encrypted = memo::encrypt(extra_data, source_keypair.get_public_key(), &recipients)
+                    .ok_or(GenerationError::TooManyTxExtraDataRecipients)?;
+                Some(encrypted)
+            },
+            extra_data => extra_data
+        };
+
         // Get the nonce
         let nonce = state.get_nonce().map_err(GenerationError::State)?;
         state.update_nonce(nonce + 1).map_err(GenerationError::State)?;
@@ -425,6 +538,18 @@ impl TransactionBuilder {
 
         let used_assets = self.data.used_assets();
 
+        if let TransactionTypeBuilder::Burn(payload) = &self.data {
+            if payload.amount == 0 {
+                return Err(GenerationError::ZeroBurnAmount);
+            }
+        }
+
+        if let TransactionTypeBuilder::AssetCreation(payload) = &self.data {
+            if payload.decimals > MAX_ASSET_DECIMALS {
+                return Err(GenerationError::InvalidAssetDecimals);
+            }
+        }
+
         let transfers = if let TransactionTypeBuilder::Transfers(transfers) = &mut self.data {
             if transfers.len() == 0 {
                 return Err(GenerationError::EmptyTransfers);
@@ -441,6 +566,10 @@ impl TransactionBuilder {
                     return Err(GenerationError::SenderIsReceiver);
                 }
 
+                if transfer.amount == 0 {
+                    return Err(GenerationError::ZeroTransferAmount);
+                }
+
                 if state.is_mainnet() != transfer.destination.is_mainnet() {
                     return Err(GenerationError::InvalidNetwork);
                 }
@@ -505,7 +634,8 @@ impl TransactionBuilder {
         let mut range_proof_values: Vec<_> = used_assets
             .iter()
             .map(|asset| {
-                let cost = self.get_transaction_cost(fee, &asset);
+                let cost = self.get_transaction_cost(fee, &asset)
+                    .ok_or(GenerationError::Overflow)?;
                 let source_new_balance = state
                     .get_account_balance(asset)
                     .map_err(GenerationError::State)?
@@ -641,7 +771,8 @@ impl TransactionBuilder {
 
         let data = match self.data {
             TransactionTypeBuilder::Transfers(_) => TransactionType::Transfers(transfers),
-            TransactionTypeBuilder::Burn(payload) => TransactionType::Burn(payload)
+            TransactionTypeBuilder::Burn(payload) => TransactionType::Burn(payload),
+            TransactionTypeBuilder::AssetCreation(payload) => TransactionType::AssetCreation(payload)
         };
 
         // 3. Create the RangeProof
@@ -665,6 +796,7 @@ impl TransactionBuilder {
             source_commitments,
             reference,
             range_proof,
+            extra_data,
         }.sign(source_keypair);
 
         Ok(transaction)
@@ -673,7 +805,12 @@ impl TransactionBuilder {
 
 impl Serializer for TransactionSigner {
     fn write(&self, writer: &mut Writer) {
-        self.version.write(writer);
+        // Must be kept in sync with the Transaction serializer
+        let mut version = self.version;
+        if self.extra_data.is_some() {
+            version |= TX_FLAG_EXTRA_DATA;
+        }
+        writer.write_u8(version);
         self.source.write(writer);
         self.data.write(writer);
         self.fee.write(writer);
@@ -686,6 +823,10 @@ impl Serializer for TransactionSigner {
 
         self.range_proof.write(writer);
         self.reference.write(writer);
+        if let Some(extra_data) = &self.extra_data {
+            writer.write_u16(extra_data.len() as u16);
+            writer.write_bytes(extra_data);
+        }
     }
 
     // Should never be called
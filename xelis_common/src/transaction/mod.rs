@@ -3,6 +3,7 @@ use crate::{
     crypto::{
         elgamal::{CompressedCiphertext, CompressedCommitment, CompressedHandle, CompressedPublicKey},
         proofs::{CiphertextValidityProof, CommitmentEqProof},
+        hash,
         Hash,
         Hashable,
         Signature,
@@ -23,8 +24,13 @@ mod tests;
 
 // Maximum total size of payload across all transfers per transaction
 pub const EXTRA_DATA_LIMIT_SIZE: usize = 1024;
+// Maximum size of the extra data attached to the transaction itself
+pub const TX_EXTRA_DATA_LIMIT_SIZE: usize = 1024;
 pub const MAX_TRANSFER_COUNT: usize = 255;
 
+// Bit set in the version byte when the transaction contains extra data
+pub const TX_FLAG_EXTRA_DATA: u8 = 0b1000_0000;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Reference {
     pub hash: Hash,
@@ -76,12 +82,28 @@ pub struct BurnPayload {
     pub amount: u64
 }
 
+// Register a new asset on chain
+// Its hash is derived from the TX hash and ASSET_CREATION_BURN_AMOUNT is burned
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssetCreationPayload {
+    // How many atomic units is needed for a full coin
+    pub decimals: u8
+}
+
+impl AssetCreationPayload {
+    // Get the hash of the asset registered by the TX
+    pub fn get_asset(tx_hash: &Hash) -> Hash {
+        hash(tx_hash.as_bytes())
+    }
+}
+
 // this enum represent all types of transaction available on XELIS Network
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum TransactionType {
     Transfers(Vec<TransferPayload>),
     Burn(BurnPayload),
+    AssetCreation(AssetCreationPayload),
 }
 
 // Transaction to be sent over the network
@@ -104,6 +126,10 @@ pub struct Transaction {
     range_proof: RangeProof,
     /// At which block the TX is built
     reference: Reference,
+    /// Public data attached to the transaction (memo, order id...)
+    /// Up to TX_EXTRA_DATA_LIMIT_SIZE bytes, priced per byte in the fees
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extra_data: Option<Vec<u8>>,
     /// The signature of the source key
     signature: Signature,
 }
@@ -173,7 +199,7 @@ impl TransferPayload {
 }
 
 impl Transaction {
-    pub fn new(source: CompressedPublicKey, data: TransactionType, fee: u64, nonce: u64, source_commitments: Vec<SourceCommitment>, range_proof: RangeProof, reference: Reference, signature: Signature) -> Self {
+    pub fn new(source: CompressedPublicKey, data: TransactionType, fee: u64, nonce: u64, source_commitments: Vec<SourceCommitment>, range_proof: RangeProof, reference: Reference, extra_data: Option<Vec<u8>>, signature: Signature) -> Self {
         Transaction {
             version: 0,
             source,
@@ -183,6 +209,7 @@ impl Transaction {
             source_commitments,
             range_proof,
             reference,
+            extra_data,
             signature
         }
     }
@@ -232,6 +259,11 @@ impl Transaction {
         &self.reference
     }
 
+    // Get the extra data attached to the transaction if any
+    pub fn get_extra_data(&self) -> &Option<Vec<u8>> {
+        &self.extra_data
+    }
+
     pub fn consume(self) -> (CompressedPublicKey, TransactionType) {
         (self.source, self.data)
     }
@@ -324,6 +356,23 @@ impl Serializer for BurnPayload {
     }
 }
 
+impl Serializer for AssetCreationPayload {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u8(self.decimals);
+    }
+
+    fn read(reader: &mut Reader) -> Result<AssetCreationPayload, ReaderError> {
+        let decimals = reader.read_u8()?;
+        Ok(AssetCreationPayload {
+            decimals
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.decimals.size()
+    }
+}
+
 impl Serializer for TransactionType {
     fn write(&self, writer: &mut Writer) {
         match self {
@@ -340,6 +389,10 @@ impl Serializer for TransactionType {
                     tx.write(writer);
                 }
             }
+            TransactionType::AssetCreation(payload) => {
+                writer.write_u8(2);
+                payload.write(writer);
+            }
         };
     }
 
@@ -361,6 +414,10 @@ impl Serializer for TransactionType {
                 }
                 TransactionType::Transfers(txs)
             },
+            2 => {
+                let payload = AssetCreationPayload::read(reader)?;
+                TransactionType::AssetCreation(payload)
+            },
             _ => {
                 return Err(ReaderError::InvalidValue)
             }
@@ -379,6 +436,9 @@ impl Serializer for TransactionType {
                     size += tx.size();
                 }
                 size
+            },
+            TransactionType::AssetCreation(payload) => {
+                1 + payload.size()
             }
         }
     }
@@ -386,7 +446,11 @@ impl Serializer for TransactionType {
 
 impl Serializer for Transaction {
     fn write(&self, writer: &mut Writer) {
-        self.version.write(writer);
+        let mut version = self.version;
+        if self.extra_data.is_some() {
+            version |= TX_FLAG_EXTRA_DATA;
+        }
+        writer.write_u8(version);
         self.source.write(writer);
         self.data.write(writer);
         self.fee.write(writer);
@@ -399,11 +463,17 @@ impl Serializer for Transaction {
 
         self.range_proof.write(writer);
         self.reference.write(writer);
+        if let Some(extra_data) = &self.extra_data {
+            writer.write_u16(extra_data.len() as u16);
+            writer.write_bytes(extra_data);
+        }
         self.signature.write(writer);
     }
 
     fn read(reader: &mut Reader) -> Result<Transaction, ReaderError> {
-        let version = reader.read_u8()?;
+        let byte = reader.read_u8()?;
+        let has_extra_data = byte & TX_FLAG_EXTRA_DATA != 0;
+        let version = byte & !TX_FLAG_EXTRA_DATA;
         // At this moment we only support version 0, so we check it here directly
         if version != 0 {
             debug!("Expected version 0 got version {version}");
@@ -427,6 +497,17 @@ impl Serializer for Transaction {
 
         let range_proof = RangeProof::read(reader)?;
         let reference = Reference::read(reader)?;
+        let extra_data = if has_extra_data {
+            let size = reader.read_u16()? as usize;
+            // Flag must not be set without any data
+            if size == 0 || size > TX_EXTRA_DATA_LIMIT_SIZE {
+                debug!("Invalid transaction extra data size: {}", size);
+                return Err(ReaderError::InvalidSize)
+            }
+            Some(reader.read_bytes(size)?)
+        } else {
+            None
+        };
         let signature = Signature::read(reader)?;
 
         Ok(Transaction {
@@ -438,6 +519,7 @@ impl Serializer for Transaction {
             source_commitments,
             range_proof,
             reference,
+            extra_data,
             signature,
         })
     }
@@ -454,6 +536,7 @@ impl Serializer for Transaction {
         + self.source_commitments.iter().map(|c| c.size()).sum::<usize>()
         + self.range_proof.size()
         + self.reference.size()
+        + self.extra_data.as_ref().map_or(0, |data| 2 + data.len())
         + self.signature.size()
     }
 }
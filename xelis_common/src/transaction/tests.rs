@@ -3,16 +3,20 @@ use async_trait::async_trait;
 use crate::{
     account::CiphertextCache,
     api::{DataElement, DataValue},
-    config::{COIN_VALUE, XELIS_ASSET},
+    config::{ASSET_CREATION_BURN_AMOUNT, COIN_VALUE, MAX_ASSET_DECIMALS, XELIS_ASSET},
     crypto::{
         elgamal::{Ciphertext, PedersenOpening},
+        proofs::ProofGenerationError,
         Address,
         Hash,
+        memo,
+        Hashable,
         KeyPair,
         PublicKey
     },
     serializer::Serializer,
-    transaction::{TransactionType, MAX_TRANSFER_COUNT}
+    transaction::{TransactionType, MAX_TRANSFER_COUNT, TX_EXTRA_DATA_LIMIT_SIZE},
+    utils::calculate_extra_data_fee
 };
 use super::{
     extra_data::{
@@ -23,11 +27,13 @@ use super::{
         AccountState,
         FeeBuilder,
         FeeHelper,
+        GenerationError,
         TransactionBuilder,
         TransactionTypeBuilder,
         TransferBuilder
     },
-    verify::BlockchainVerificationState,
+    verify::{BlockchainVerificationState, VerificationError},
+    AssetCreationPayload,
     BurnPayload,
     Reference,
     Role,
@@ -334,6 +340,365 @@ async fn test_max_transfers() {
     assert!(tx.verify(&mut state).await.is_ok());
 }
 
+fn build_tx_for(account: &Account, data: TransactionTypeBuilder, fee_builder: FeeBuilder) -> Result<Transaction, GenerationError<()>> {
+    let mut state = AccountStateImpl {
+        balances: account.balances.clone(),
+        nonce: account.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+
+    let builder = TransactionBuilder::new(0, account.keypair.get_public_key().compress(), data, fee_builder);
+    builder.build(&mut state, &account.keypair)
+}
+
+fn chain_state_for(accounts: &[&Account]) -> ChainState {
+    let mut state = ChainState {
+        accounts: HashMap::new(),
+    };
+
+    for account in accounts {
+        let mut balances = HashMap::new();
+        for (asset, balance) in &account.balances {
+            balances.insert(asset.clone(), balance.ciphertext.clone().take_ciphertext().unwrap());
+        }
+        state.accounts.insert(account.keypair.get_public_key().compress(), AccountChainState {
+            balances,
+            nonce: account.nonce,
+        });
+    }
+
+    state
+}
+
+fn transfer_with_memo(account: &Account, destination: Address, memo: Option<Vec<u8>>) -> TransactionBuilder {
+    let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+        amount: 1,
+        destination,
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }]);
+    TransactionBuilder::new(0, account.keypair.get_public_key().compress(), data, FeeBuilder::default())
+        .with_extra_data(memo)
+}
+
+#[test]
+fn test_reject_zero_amounts() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+        amount: 0,
+        destination: bob.address(),
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }]);
+    assert!(matches!(build_tx_for(&alice, data, FeeBuilder::default()), Err(GenerationError::ZeroTransferAmount)));
+
+    let data = TransactionTypeBuilder::Burn(BurnPayload {
+        amount: 0,
+        asset: XELIS_ASSET,
+    });
+    assert!(matches!(build_tx_for(&alice, data, FeeBuilder::default()), Err(GenerationError::ZeroBurnAmount)));
+}
+
+#[test]
+fn test_reject_self_transfer() {
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+        amount: 1,
+        destination: alice.address(),
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }]);
+    assert!(matches!(build_tx_for(&alice, data, FeeBuilder::default()), Err(GenerationError::SenderIsReceiver)));
+}
+
+#[test]
+fn test_reject_cost_overflow() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+
+    alice.set_balance(XELIS_ASSET, u64::MAX);
+
+    let fee = 1000;
+    let transfer = |amount| TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+        amount,
+        destination: bob.address(),
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }]);
+
+    // Amount + fee is exactly the maximum value
+    assert!(build_tx_for(&alice, transfer(u64::MAX - fee), FeeBuilder::Value(fee)).is_ok());
+
+    // One more atomic unit is overflowing
+    assert!(matches!(build_tx_for(&alice, transfer(u64::MAX - fee + 1), FeeBuilder::Value(fee)), Err(GenerationError::Overflow)));
+
+    // Sum of all transfers is overflowing
+    let data = TransactionTypeBuilder::Transfers(vec![
+        TransferBuilder {
+            amount: u64::MAX / 2 + 1,
+            destination: bob.address(),
+            asset: XELIS_ASSET,
+            extra_data: None,
+        },
+        TransferBuilder {
+            amount: u64::MAX / 2 + 1,
+            destination: bob.address(),
+            asset: XELIS_ASSET,
+            extra_data: None,
+        }
+    ]);
+    assert!(matches!(build_tx_for(&alice, data, FeeBuilder::Value(0)), Err(GenerationError::Overflow)));
+}
+
+#[tokio::test]
+async fn test_reject_zero_burn_verify() {
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let data = TransactionTypeBuilder::Burn(BurnPayload {
+        amount: 1,
+        asset: XELIS_ASSET,
+    });
+    let mut tx = build_tx_for(&alice, data, FeeBuilder::default()).unwrap();
+    // Replace the burn amount by zero
+    tx.data = TransactionType::Burn(BurnPayload {
+        amount: 0,
+        asset: XELIS_ASSET,
+    });
+
+    let mut state = ChainState {
+        accounts: HashMap::new(),
+    };
+
+    {
+        let mut balances = HashMap::new();
+        for (asset, balance) in alice.balances {
+            balances.insert(asset, balance.ciphertext.take_ciphertext().unwrap());
+        }
+        state.accounts.insert(alice.keypair.get_public_key().compress(), AccountChainState {
+            balances,
+            nonce: alice.nonce,
+        });
+    }
+
+    assert!(matches!(tx.verify(&mut state).await, Err(VerificationError::NoValueForBurn)));
+}
+
+#[tokio::test]
+async fn test_tx_extra_data() {
+    let mut alice = Account::new();
+    let mut bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    bob.set_balance(XELIS_ASSET, 0);
+
+    let memo = "order #42".as_bytes().to_vec();
+    let builder = transfer_with_memo(&alice, bob.address(), Some(memo.clone()));
+    let estimated_size = builder.estimate_size();
+    let mut state = AccountStateImpl {
+        balances: alice.balances.clone(),
+        nonce: alice.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+    let tx = builder.build(&mut state, &alice.keypair).unwrap();
+    assert_eq!(tx.size(), estimated_size);
+    assert_eq!(tx.to_bytes().len(), estimated_size);
+
+    // Extra data is covered by the serialization and the hash
+    let decoded = Transaction::from_bytes(&tx.to_bytes()).unwrap();
+    assert_eq!(decoded.get_extra_data().as_ref(), Some(&memo));
+    assert_eq!(decoded.get_version(), 0);
+    assert_eq!(decoded.hash(), tx.hash());
+
+    let mut chain_state = chain_state_for(&[&alice, &bob]);
+    // Signature is still valid with the extra data
+    decoded.verify(&mut chain_state).await.unwrap();
+
+    // Transaction without extra data doesn't have the flag set
+    let tx = build_tx_for(&alice, TransactionTypeBuilder::Burn(BurnPayload { amount: 1, asset: XELIS_ASSET }), FeeBuilder::default()).unwrap();
+    assert_eq!(tx.to_bytes()[0], 0);
+    assert!(Transaction::from_bytes(&tx.to_bytes()).unwrap().get_extra_data().is_none());
+}
+
+#[test]
+fn test_tx_extra_data_fee() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let mut state = AccountStateImpl {
+        balances: alice.balances.clone(),
+        nonce: alice.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+
+    let without = transfer_with_memo(&alice, bob.address(), None);
+    let with = transfer_with_memo(&alice, bob.address(), Some(vec![1u8; 100]));
+    // Both are in the same KB, only the per byte fee is added
+    assert_eq!(without.estimate_size() / 1024, with.estimate_size() / 1024);
+
+    let fee_without = without.estimate_fees(&mut state).unwrap();
+    let fee_with = with.estimate_fees(&mut state).unwrap();
+    assert_eq!(fee_with, fee_without + calculate_extra_data_fee(100));
+}
+
+#[tokio::test]
+async fn test_tx_encrypted_extra_data() {
+    let mut alice = Account::new();
+    let mut bob = Account::new();
+    let eve = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    bob.set_balance(XELIS_ASSET, 0);
+
+    let memo = "order #42".as_bytes().to_vec();
+    let plaintext = transfer_with_memo(&alice, bob.address(), Some(memo.clone()));
+    let builder = plaintext.clone().with_encrypted_extra_data(true);
+    let mut state = AccountStateImpl {
+        balances: alice.balances.clone(),
+        nonce: alice.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+
+    // Encryption overhead is paid in the fees
+    let overhead = memo::encrypted_size(memo.len(), 1) - memo.len();
+    assert_eq!(builder.estimate_size(), plaintext.estimate_size() + overhead);
+    assert_eq!(builder.estimate_fees(&mut state).unwrap(), plaintext.estimate_fees(&mut state).unwrap() + calculate_extra_data_fee(overhead));
+
+    let estimated_size = builder.estimate_size();
+    let tx = builder.build(&mut state, &alice.keypair).unwrap();
+    assert_eq!(tx.size(), estimated_size);
+
+    let extra_data = tx.get_extra_data().as_ref().unwrap();
+    assert!(memo::is_encrypted(extra_data));
+    assert_ne!(*extra_data, memo);
+
+    // Both the sender and the recipient can read it, but not a third key
+    assert_eq!(memo::decrypt(extra_data, alice.keypair.get_private_key()).unwrap(), memo);
+    assert_eq!(memo::decrypt(extra_data, bob.keypair.get_private_key()).unwrap(), memo);
+    assert!(memo::decrypt(extra_data, eve.keypair.get_private_key()).is_err());
+
+    let mut chain_state = chain_state_for(&[&alice, &bob]);
+    tx.verify(&mut chain_state).await.unwrap();
+
+    // Limit applies to the encrypted size
+    let mut state = AccountStateImpl {
+        balances: alice.balances.clone(),
+        nonce: alice.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+    let too_large = transfer_with_memo(&alice, bob.address(), Some(vec![1u8; TX_EXTRA_DATA_LIMIT_SIZE - overhead + 1]))
+        .with_encrypted_extra_data(true);
+    assert!(matches!(too_large.build(&mut state, &alice.keypair), Err(GenerationError::InvalidTxExtraDataSize)));
+
+    // A plaintext can't be mistaken for an encrypted one
+    let ambiguous = transfer_with_memo(&alice, bob.address(), Some(extra_data.clone()));
+    assert!(matches!(ambiguous.build(&mut state, &alice.keypair), Err(GenerationError::AmbiguousTxExtraData)));
+}
+
+#[tokio::test]
+async fn test_reject_tx_extra_data_too_large() {
+    let mut alice = Account::new();
+    let mut bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    bob.set_balance(XELIS_ASSET, 0);
+
+    let build = |memo| {
+        let mut state = AccountStateImpl {
+            balances: alice.balances.clone(),
+            nonce: alice.nonce,
+            reference: Reference {
+                topoheight: 0,
+                hash: Hash::zero(),
+            },
+        };
+        transfer_with_memo(&alice, bob.address(), Some(memo)).build(&mut state, &alice.keypair)
+    };
+
+    assert!(build(vec![0u8; TX_EXTRA_DATA_LIMIT_SIZE]).is_ok());
+    assert!(matches!(build(vec![0u8; TX_EXTRA_DATA_LIMIT_SIZE + 1]), Err(GenerationError::InvalidTxExtraDataSize)));
+    assert!(matches!(build(Vec::new()), Err(GenerationError::InvalidTxExtraDataSize)));
+
+    // Forge a transaction above the limit
+    let mut tx = build(vec![0u8; 16]).unwrap();
+    tx.extra_data = Some(vec![0u8; TX_EXTRA_DATA_LIMIT_SIZE + 1]);
+    assert!(Transaction::from_bytes(&tx.to_bytes()).is_err());
+
+    let mut state = chain_state_for(&[&alice, &bob]);
+    assert!(matches!(tx.verify(&mut state).await, Err(VerificationError::InvalidExtraDataSize(size)) if size == TX_EXTRA_DATA_LIMIT_SIZE + 1));
+    // Rejected before the state is updated
+    assert_eq!(state.accounts[&alice.keypair.get_public_key().compress()].nonce, alice.nonce);
+}
+
+#[tokio::test]
+async fn test_asset_creation_tx() {
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let data = TransactionTypeBuilder::AssetCreation(AssetCreationPayload { decimals: 8 });
+    let builder = TransactionBuilder::new(0, alice.keypair.get_public_key().compress(), data.clone(), FeeBuilder::default());
+    // Registration cost is paid in XELIS only
+    assert_eq!(builder.get_transaction_cost(100, &XELIS_ASSET), Some(100 + ASSET_CREATION_BURN_AMOUNT));
+    assert_eq!(builder.get_transaction_cost(100, &Hash::max()), Some(0));
+
+    let estimated_size = builder.estimate_size();
+    let tx = build_tx_for(&alice, data, FeeBuilder::default()).unwrap();
+    assert_eq!(tx.size(), estimated_size);
+
+    let decoded = Transaction::from_bytes(&tx.to_bytes()).unwrap();
+    assert!(matches!(decoded.get_data(), TransactionType::AssetCreation(payload) if payload.decimals == 8));
+    assert_eq!(decoded.hash(), tx.hash());
+
+    // Asset is derived from the TX hash
+    assert_eq!(AssetCreationPayload::get_asset(&tx.hash()), AssetCreationPayload::get_asset(&decoded.hash()));
+    assert_ne!(AssetCreationPayload::get_asset(&tx.hash()), tx.hash());
+
+    // New balance commitment must include the burned amount
+    let mut state = chain_state_for(&[&alice]);
+    decoded.verify(&mut state).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_reject_asset_creation() {
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, ASSET_CREATION_BURN_AMOUNT - 1);
+
+    // Not enough funds to pay the registration cost
+    let data = TransactionTypeBuilder::AssetCreation(AssetCreationPayload { decimals: 8 });
+    assert!(matches!(build_tx_for(&alice, data, FeeBuilder::Value(0)), Err(GenerationError::Proof(ProofGenerationError::InsufficientFunds))));
+
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    let data = TransactionTypeBuilder::AssetCreation(AssetCreationPayload { decimals: MAX_ASSET_DECIMALS + 1 });
+    assert!(matches!(build_tx_for(&alice, data, FeeBuilder::default()), Err(GenerationError::InvalidAssetDecimals)));
+
+    // Forge the decimals after the build
+    let data = TransactionTypeBuilder::AssetCreation(AssetCreationPayload { decimals: MAX_ASSET_DECIMALS });
+    let mut tx = build_tx_for(&alice, data, FeeBuilder::default()).unwrap();
+    tx.data = TransactionType::AssetCreation(AssetCreationPayload { decimals: MAX_ASSET_DECIMALS + 1 });
+
+    let mut state = chain_state_for(&[&alice]);
+    assert!(matches!(tx.verify(&mut state).await, Err(VerificationError::InvalidAssetDecimals(_))));
+}
+
 #[async_trait]
 impl<'a> BlockchainVerificationState<'a, ()> for ChainState {
 
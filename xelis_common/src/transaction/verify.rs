@@ -2,7 +2,7 @@ use bulletproofs::RangeProof;
 use curve25519_dalek::{ristretto::CompressedRistretto, traits::Identity, RistrettoPoint, Scalar};
 use log::{debug, trace};
 use merlin::Transcript;
-use crate::{config::XELIS_ASSET, crypto::{elgamal::{Ciphertext, CompressedPublicKey, DecompressionError, DecryptHandle, PedersenCommitment}, proofs::{BatchCollector, ProofVerificationError, BP_GENS, BULLET_PROOF_SIZE, PC_GENS}, Hash, ProtocolTranscript, SIGNATURE_SIZE}, serializer::Serializer, transaction::{EXTRA_DATA_LIMIT_SIZE, MAX_TRANSFER_COUNT}};
+use crate::{config::{ASSET_CREATION_BURN_AMOUNT, MAX_ASSET_DECIMALS, XELIS_ASSET}, crypto::{elgamal::{Ciphertext, CompressedPublicKey, DecompressionError, DecryptHandle, PedersenCommitment}, proofs::{BatchCollector, ProofVerificationError, BP_GENS, BULLET_PROOF_SIZE, PC_GENS}, Hash, ProtocolTranscript, SIGNATURE_SIZE}, serializer::Serializer, transaction::{EXTRA_DATA_LIMIT_SIZE, MAX_TRANSFER_COUNT, TX_EXTRA_DATA_LIMIT_SIZE}};
 use super::{Reference, Role, Transaction, TransactionType, TransferPayload};
 use thiserror::Error;
 use std::iter;
@@ -65,12 +65,21 @@ pub trait BlockchainVerificationState<'a, E> {
 pub enum VerificationError<T> {
     #[error("State error: {0}")]
     State(T),
-    #[error("Invalid nonce, got {} expected {}", _0, _1)]
-    InvalidNonce(u64, u64),
+    // Sender with its stored nonce and the nonce used
+    #[error("Invalid nonce, got {} expected {}", _1, _2)]
+    InvalidNonce(CompressedPublicKey, u64, u64),
     #[error("Sender is receiver")]
     SenderIsReceiver,
     #[error("Invalid signature")]
     InvalidSignature,
+    #[error("A non-zero value is required for burn")]
+    NoValueForBurn,
+    #[error("Overflow detected")]
+    Overflow,
+    #[error("Invalid asset decimals: {}", _0)]
+    InvalidAssetDecimals(u8),
+    #[error("Invalid transaction extra data size: {}", _0)]
+    InvalidExtraDataSize(usize),
     #[error("Proof verification error: {0}")]
     Proof(#[from] ProofVerificationError),
 }
@@ -128,6 +137,12 @@ impl Transaction {
                     output += Scalar::from(payload.amount)
                 }
             }
+            TransactionType::AssetCreation(_) => {
+                // Registration cost is burned from the native asset
+                if *asset == XELIS_ASSET {
+                    output += Scalar::from(ASSET_CREATION_BURN_AMOUNT)
+                }
+            }
         }
 
         Ok(output)
@@ -181,6 +196,8 @@ impl Transaction {
                 .iter()
                 .all(|transfer| has_commitment_for_asset(&transfer.asset)),
             TransactionType::Burn(payload) => has_commitment_for_asset(&payload.asset),
+            // Only XELIS_ASSET is used, already checked above
+            TransactionType::AssetCreation(_) => true,
         }
     }
 
@@ -193,6 +210,14 @@ impl Transaction {
     ) -> Result<(Transcript, Vec<(RistrettoPoint, CompressedRistretto)>), VerificationError<E>>
     {
         trace!("Pre-verifying transaction");
+        // Checked before the state is touched
+        if let Some(extra_data) = &self.extra_data {
+            if extra_data.is_empty() || extra_data.len() > TX_EXTRA_DATA_LIMIT_SIZE {
+                debug!("transaction extra data size is invalid: {}", extra_data.len());
+                return Err(VerificationError::InvalidExtraDataSize(extra_data.len()));
+            }
+        }
+
         state.pre_verify_tx(&self).await
             .map_err(VerificationError::State)?;
 
@@ -201,7 +226,7 @@ impl Transaction {
             .map_err(VerificationError::State)?;
 
         if account_nonce != self.nonce {
-            return Err(VerificationError::InvalidNonce(account_nonce, self.nonce));
+            return Err(VerificationError::InvalidNonce(self.source.clone(), account_nonce, self.nonce));
         }
 
         // Nonce is valid, update it for next transactions if any
@@ -214,6 +239,31 @@ impl Transaction {
             return Err(VerificationError::Proof(ProofVerificationError::Format));
         }
 
+        if let TransactionType::Burn(payload) = &self.data {
+            if payload.amount == 0 {
+                debug!("burn amount cannot be zero");
+                return Err(VerificationError::NoValueForBurn);
+            }
+
+            // Total cost of the TX must fit in a u64
+            if payload.asset == XELIS_ASSET && payload.amount.checked_add(self.fee).is_none() {
+                debug!("burn amount with fee is overflowing");
+                return Err(VerificationError::Overflow);
+            }
+        }
+
+        if let TransactionType::AssetCreation(payload) = &self.data {
+            if payload.decimals > MAX_ASSET_DECIMALS {
+                debug!("asset decimals are invalid: {}", payload.decimals);
+                return Err(VerificationError::InvalidAssetDecimals(payload.decimals));
+            }
+
+            if ASSET_CREATION_BURN_AMOUNT.checked_add(self.fee).is_none() {
+                debug!("asset creation cost with fee is overflowing");
+                return Err(VerificationError::Overflow);
+            }
+        }
+
         let transfers_decompressed = if let TransactionType::Transfers(transfers) = &self.data {
             if transfers.len() > MAX_TRANSFER_COUNT || transfers.is_empty() {
                 debug!("incorrect transfers size: {}", transfers.len());
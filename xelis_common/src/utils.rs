@@ -10,6 +10,7 @@ use crate::{
     config::{
         COIN_DECIMALS,
         FEE_PER_ACCOUNT_CREATION,
+        FEE_PER_EXTRA_DATA_BYTE,
         FEE_PER_KB,
         FEE_PER_TRANSFER
     },
@@ -26,9 +27,89 @@ macro_rules! async_handler {
     };
 }
 
+// Error returned when parsing a human-readable amount
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AmountError {
+    #[error("amount is empty")]
+    Empty,
+    #[error("invalid character '{}' in amount", _0)]
+    InvalidCharacter(char),
+    #[error("only one decimal point is allowed")]
+    MultipleDecimalPoints,
+    #[error("underscore separators are only allowed between digits")]
+    InvalidSeparator,
+    #[error("max {} decimal places", _0)]
+    TooManyDecimals(u8),
+    #[error("amount is too large")]
+    Overflow
+}
+
+// Parse a human-readable amount to atomic units using the decimals of the asset
+// Underscores can be used as separators between digits: "1_000.5"
+pub fn parse_amount(value: &str, decimals: u8) -> Result<u64, AmountError> {
+    let value = value.trim();
+    let (integer_part, decimal_part) = value.split_once('.').unwrap_or((value, ""));
+    if decimal_part.contains('.') {
+        return Err(AmountError::MultipleDecimalPoints)
+    }
+
+    let integer_digits = amount_digits(integer_part)?;
+    let decimal_digits = amount_digits(decimal_part)?;
+    if integer_digits.is_empty() && decimal_digits.is_empty() {
+        return Err(AmountError::Empty)
+    }
+
+    if decimal_digits.len() > decimals as usize {
+        return Err(AmountError::TooManyDecimals(decimals))
+    }
+
+    // Missing decimals are filled with zeros
+    let padding = std::iter::repeat(0).take(decimals as usize - decimal_digits.len());
+    integer_digits.into_iter()
+        .chain(decimal_digits)
+        .chain(padding)
+        .try_fold(0u64, |amount, digit| amount.checked_mul(10).and_then(|amount| amount.checked_add(digit)))
+        .ok_or(AmountError::Overflow)
+}
+
+// Digits of one part of an amount, with the underscore separators removed
+fn amount_digits(part: &str) -> Result<Vec<u64>, AmountError> {
+    let chars: Vec<char> = part.chars().collect();
+    let mut digits = Vec::with_capacity(chars.len());
+    for (i, c) in chars.iter().enumerate() {
+        match c {
+            '0'..='9' => digits.push(*c as u64 - '0' as u64),
+            '_' => {
+                let is_between_digits = i > 0
+                    && chars[i - 1].is_ascii_digit()
+                    && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit());
+                if !is_between_digits {
+                    return Err(AmountError::InvalidSeparator)
+                }
+            },
+            c => return Err(AmountError::InvalidCharacter(*c))
+        }
+    }
+
+    Ok(digits)
+}
+
+// Format an amount in atomic units using the decimals of the asset
+// This is the inverse of parse_amount
+pub fn format_amount(value: u64, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return value.to_string()
+    }
+
+    let digits = format!("{:0>1$}", value, decimals + 1);
+    let (integer_part, decimal_part) = digits.split_at(digits.len() - decimals);
+    format!("{}.{}", integer_part, decimal_part)
+}
+
 // Format any coin value using the requested decimals count
 pub fn format_coin(value: u64, decimals: u8) -> String {
-    format!("{:.1$}", value as f64 / 10usize.pow(decimals as u32) as f64, decimals as usize)
+    format_amount(value, decimals)
 }
 
 // Format value using XELIS decimals
@@ -43,13 +124,7 @@ pub fn from_xelis(value: impl Into<String>) -> Option<u64> {
 
 // Convert a coin amount from string to a u64 based on the provided decimals
 pub fn from_coin(value: impl Into<String>, coin_decimals: u8) -> Option<u64> {
-    let value = value.into();
-    let mut split = value.split('.');
-    let value: u64 = split.next()?.parse::<u64>().ok()?;
-    let right_part = split.next().unwrap_or("0");
-    let decimals: String = right_part.chars().chain(std::iter::repeat('0')).take(coin_decimals as usize).collect();
-    let decimals_value = decimals.parse::<u64>().ok()?;
-    Some(value * 10u64.pow(coin_decimals as u32) + decimals_value)
+    parse_amount(&value.into(), coin_decimals).ok()
 }
 
 // return the fee for a transaction based on its size in bytes
@@ -57,17 +132,28 @@ pub fn from_coin(value: impl Into<String>, coin_decimals: u8) -> Option<u64> {
 // Sending to a newly created address will increase the fee
 // Each transfers output will also increase the fee
 pub fn calculate_tx_fee(tx_size: usize, output_count: usize, new_addresses: usize) -> u64 {
+    calculate_tx_fee_with_fee_per_kb(tx_size, output_count, new_addresses, FEE_PER_KB)
+}
+
+// Same as calculate_tx_fee but with a custom fee per KB
+// Used by the nodes having a relay policy above the protocol minimum
+pub fn calculate_tx_fee_with_fee_per_kb(tx_size: usize, output_count: usize, new_addresses: usize, fee_per_kb: u64) -> u64 {
     let mut size_in_kb = tx_size as u64 / 1024;
 
     if tx_size % 1024 != 0 { // we consume a full kb for fee
         size_in_kb += 1;
     }
 
-    size_in_kb * FEE_PER_KB
+    size_in_kb * fee_per_kb
     + output_count as u64 * FEE_PER_TRANSFER
     + new_addresses as u64 * FEE_PER_ACCOUNT_CREATION
 }
 
+// Additional fee for the extra data attached to a transaction
+pub fn calculate_extra_data_fee(extra_data_size: usize) -> u64 {
+    extra_data_size as u64 * FEE_PER_EXTRA_DATA_BYTE
+}
+
 const HASHRATE_FORMATS: [&str; 7] = ["H/s", "KH/s", "MH/s", "GH/s", "TH/s", "PH/s", "EH/s"];
 
 // Format a hashrate in human-readable format
@@ -187,4 +273,64 @@ mod tests {
         let value = from_xelis("100.123");
         assert_eq!(value, Some(100_123_00000));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("0", 8), Ok(0));
+        assert_eq!(parse_amount("1", 8), Ok(100_000_000));
+        assert_eq!(parse_amount(".5", 8), Ok(50_000_000));
+        assert_eq!(parse_amount("1.", 8), Ok(100_000_000));
+        assert_eq!(parse_amount("1.5", 8), Ok(150_000_000));
+        assert_eq!(parse_amount("1_000.00001", 5), Ok(100_000_001));
+        assert_eq!(parse_amount("1_000_000", 0), Ok(1_000_000));
+        assert_eq!(parse_amount(" 0.00000001 ", 8), Ok(1));
+        assert_eq!(parse_amount("0.1_5", 8), Ok(15_000_000));
+        assert_eq!(parse_amount("0", 30), Ok(0));
+    }
+
+    #[test]
+    fn test_parse_amount_errors() {
+        assert_eq!(parse_amount("", 8), Err(AmountError::Empty));
+        assert_eq!(parse_amount(".", 8), Err(AmountError::Empty));
+        assert_eq!(parse_amount("1.2.3", 8), Err(AmountError::MultipleDecimalPoints));
+        assert_eq!(parse_amount("-1", 8), Err(AmountError::InvalidCharacter('-')));
+        assert_eq!(parse_amount("1e5", 8), Err(AmountError::InvalidCharacter('e')));
+        assert_eq!(parse_amount("1,5", 8), Err(AmountError::InvalidCharacter(',')));
+        assert_eq!(parse_amount("_1", 8), Err(AmountError::InvalidSeparator));
+        assert_eq!(parse_amount("1_", 8), Err(AmountError::InvalidSeparator));
+        assert_eq!(parse_amount("1__0", 8), Err(AmountError::InvalidSeparator));
+        assert_eq!(parse_amount("1_.5", 8), Err(AmountError::InvalidSeparator));
+        assert_eq!(parse_amount("1._5", 8), Err(AmountError::InvalidSeparator));
+        assert_eq!(parse_amount("0.000000001", 8), Err(AmountError::TooManyDecimals(8)));
+        assert_eq!(parse_amount("1.5", 0), Err(AmountError::TooManyDecimals(0)));
+        assert_eq!(parse_amount("1", 20), Err(AmountError::Overflow));
+        assert_eq!(AmountError::TooManyDecimals(5).to_string(), "max 5 decimal places");
+    }
+
+    #[test]
+    fn test_parse_amount_u64_max() {
+        assert_eq!(parse_amount("18446744073709551615", 0), Ok(u64::MAX));
+        assert_eq!(parse_amount("18446744073709551616", 0), Err(AmountError::Overflow));
+        assert_eq!(parse_amount("18_446_744_073_709_551_615", 0), Ok(u64::MAX));
+        assert_eq!(parse_amount("184467440737.09551615", 8), Ok(u64::MAX));
+        assert_eq!(parse_amount("184467440737.09551616", 8), Err(AmountError::Overflow));
+        assert_eq!(parse_amount("184467440738", 8), Err(AmountError::Overflow));
+        assert_eq!(parse_amount("99999999999999999999999", 0), Err(AmountError::Overflow));
+    }
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(0, 8), "0.00000000");
+        assert_eq!(format_amount(1, 8), "0.00000001");
+        assert_eq!(format_amount(150_000_000, 8), "1.50000000");
+        assert_eq!(format_amount(1_000_000, 0), "1000000");
+        assert_eq!(format_amount(u64::MAX, 8), "184467440737.09551615");
+        assert_eq!(format_amount(u64::MAX, 25), "0.0000018446744073709551615");
+
+        for value in [0, 1, 99, 100_000_001, u64::MAX] {
+            for decimals in [0, 5, 8, 19] {
+                assert_eq!(parse_amount(&format_amount(value, decimals), decimals), Ok(value));
+            }
+        }
+    }
+}
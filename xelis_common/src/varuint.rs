@@ -297,4 +297,26 @@ mod tests {
         assert_eq!(compact.as_ref(), compact2.as_ref());
         assert!(json.contains("123456"));
     }
+
+    #[test]
+    fn test_cumulative_difficulty_above_u64() {
+        // Accumulate near u64::MAX difficulties over many blocks
+        let difficulty = VarUint::from_u64(u64::MAX - 1);
+        let mut cumulative_difficulty = VarUint::zero();
+        let mut previous = cumulative_difficulty;
+        for _ in 0..10_000 {
+            cumulative_difficulty += difficulty;
+            // It must never wrap
+            assert!(cumulative_difficulty > previous);
+            previous = cumulative_difficulty;
+        }
+
+        let expected = U256::from(u64::MAX - 1) * U256::from(10_000u64);
+        assert_eq!(*cumulative_difficulty.as_ref(), expected);
+        assert!(cumulative_difficulty > VarUint::from_u64(u64::MAX));
+
+        // Same value once stored
+        let bytes = cumulative_difficulty.to_bytes();
+        assert_eq!(VarUint::from_bytes(&bytes).unwrap(), cumulative_difficulty);
+    }
 }
\ No newline at end of file
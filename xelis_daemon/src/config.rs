@@ -1,6 +1,7 @@
 use lazy_static::lazy_static;
 use xelis_common::{
     api::daemon::DevFeeThreshold,
+    config::TIPS_LIMIT,
     crypto::{
         Address,
         Hash,
@@ -17,7 +18,31 @@ pub const NETWORK_ID: [u8; NETWORK_ID_SIZE] = [0x73, 0x6c, 0x69, 0x78, 0x65, 0x5
 
 // bind addresses
 pub const DEFAULT_P2P_BIND_ADDRESS: &str = "0.0.0.0:2125";
+// Port used for a whitelisted peer configured without one
+pub const DEFAULT_P2P_PORT: u16 = 2125;
 pub const DEFAULT_RPC_BIND_ADDRESS: &str = "0.0.0.0:8080";
+// Username of the HTTP basic authentication for the admin RPC methods
+pub const DEFAULT_RPC_ADMIN_USERNAME: &str = "admin";
+
+// Maximum topoheight difference with the median of our peers
+// before the health endpoint reports the node as behind
+pub const HEALTH_MAX_TOPOHEIGHT_BEHIND: u64 = STABLE_LIMIT * 2;
+
+// Maximum blocks sent in one firehose batch before waiting for the client ack
+pub const FIREHOSE_BATCH_SIZE: u64 = 64;
+
+// Our chain is synced if our top block is not older than this
+// or if we are within SYNC_MAX_TOPOHEIGHT_BEHIND blocks of the best topoheight of our peers
+// Until synced, we don't relay blocks / txs and don't generate block templates
+pub const SYNC_MAX_TOP_BLOCK_AGE_MILLIS: u64 = 10 * 60 * MILLIS_PER_SECOND;
+pub const SYNC_MAX_TOPOHEIGHT_BEHIND: u64 = STABLE_LIMIT * 2;
+
+// Blocks used to compute the rolling average block time
+pub const BLOCK_TIME_DRIFT_WINDOW: usize = 100;
+// Maximum deviation in percent of the rolling average from the target block time
+pub const BLOCK_TIME_DRIFT_MAX_DEVIATION_PERCENT: u64 = 50;
+// Warn once the deviation lasts for more than this duration in blocks timestamps
+pub const BLOCK_TIME_DRIFT_WARNING_DELAY_MILLIS: u64 = 60 * 60 * MILLIS_PER_SECOND;
 
 // Default cache size for storage DB
 pub const DEFAULT_CACHE_SIZE: usize = 1024;
@@ -38,12 +63,45 @@ pub const OTHER_MINIMUM_DIFFICULTY: Difficulty = Difficulty::from_u64(BLOCK_TIME
 pub const GENESIS_BLOCK_DIFFICULTY: Difficulty = Difficulty::from_u64(1);
 // 1024 * 1024 + (256 * 1024) bytes = 1.25 MB maximum size per block with txs
 pub const MAX_BLOCK_SIZE: usize = (1024 * 1024) + (256 * 1024);
+// Lower bound of a serialized transaction size in bytes
+// version (1) + source (32) + tx type (1) + fee (8) + nonce (8) + reference (40)
+// + at least one source commitment with its proof (1 + 256) + signature (64)
+pub const MIN_TX_SIZE: usize = 411;
+// Maximum number of txs hashes that a block header can reference
+pub const MAX_TXS_PER_BLOCK: usize = MAX_BLOCK_SIZE / MIN_TX_SIZE;
+// Default maximum size of the transaction extra data accepted in our mempool
+// Consensus allows up to TX_EXTRA_DATA_LIMIT_SIZE bytes, this is only a relay policy
+pub const DEFAULT_MEMPOOL_MAX_TX_EXTRA_DATA_SIZE: usize = 256;
+// Default maximum size in bytes of all the TXs in our mempool (256 MB)
+pub const DEFAULT_MEMPOOL_MAX_SIZE: usize = 256 * 1024 * 1024;
+// Maximum time in seconds spent re-validating the persisted mempool at startup
+// The remaining TXs are validated on the first access to the mempool
+pub const MEMPOOL_RELOAD_TIMEOUT_SECS: u64 = 10;
+// Minimum fee increase in percent for a TX to replace the one in mempool with the same sender and nonce
+pub const MEMPOOL_REPLACEMENT_MIN_FEE_INCREASE_PERCENT: u64 = 10;
+// Maximum count of replacements for the same sender and nonce in the window
+// This prevents to relay endless replacements paying a small increase each time
+pub const MEMPOOL_MAX_REPLACEMENTS: u32 = 5;
+pub const MEMPOOL_REPLACEMENTS_WINDOW_SECS: u64 = 10 * 60;
+// Default delay in hours between two automatic backups
+pub const DEFAULT_BACKUP_INTERVAL_HOURS: u64 = 24;
+// Default count of automatic backups kept, the oldest ones are deleted
+pub const DEFAULT_BACKUP_RETENTION: usize = 7;
 // 2 seconds maximum in future (prevent any attack on reducing difficulty but keep margin for unsynced devices)
 pub const TIMESTAMP_IN_FUTURE_LIMIT: TimestampSeconds = 2 * 1000;
+// Maximum offset in milliseconds applied to our clock based on the time of our peers
+// Our time is never adjusted more to prevent peers from warping it
+pub const NETWORK_TIME_MAX_OFFSET_MILLIS: i64 = 60 * 1000;
+// Minimum count of peers time samples required to adjust our clock
+pub const NETWORK_TIME_MIN_SAMPLES: usize = 5;
+// Maximum count of peers time samples kept, the oldest ones are replaced
+pub const NETWORK_TIME_MAX_SAMPLES: usize = 256;
 
 // keep at least last N blocks until top topoheight when pruning the chain
 // WARNING: This must be at least 50 blocks for difficulty adjustement
 pub const PRUNE_SAFETY_LIMIT: u64 = STABLE_LIMIT * 10;
+// Maximum blocks that can be popped at once from the chain without forcing it
+pub const MAX_BLOCK_REWIND: u64 = PRUNE_SAFETY_LIMIT;
 
 // BlockDAG rules
 pub const STABLE_LIMIT: u64 = 8; // in how many height we consider the block stable
@@ -109,8 +167,51 @@ pub const CHAIN_SYNC_DEFAULT_RESPONSE_BLOCKS: usize = 4096;
 pub const CHAIN_SYNC_RESPONSE_MAX_BLOCKS: usize = 16384;
 // send last 10 heights
 pub const CHAIN_SYNC_TOP_BLOCKS: usize = 10;
+// Blocks downloaded at once during a pipelined chain sync
+// The next batch is downloaded while the previous one is added to the chain
+pub const CHAIN_SYNC_PIPELINE_BATCH_BLOCKS: usize = 64;
+
+// Headers range config (light clients and headers-first sync)
+// maximum headers requested at once
+pub const HEADERS_RANGE_MAX_COUNT: u64 = 512;
+// maximum total size in bytes of the headers sent in one response
+// it must fit in a P2P packet with the object response overhead
+pub const HEADERS_RANGE_MAX_SIZE: usize = MAX_BLOCK_SIZE - 1024;
+
+// Difficulty history config (hashrate charts)
+// maximum samples returned at once
+pub const DIFFICULTY_HISTORY_MAX_SAMPLES: u64 = 1000;
+
+// Chain stats config (explorer statistics)
+// maximum blocks aggregated at once
+pub const CHAIN_STATS_MAX_BLOCKS: u64 = 10_000;
+
+// Idempotent submission config (retry-safe submit_transaction)
+// how long a response is replayed for the same key
+pub const IDEMPOTENCY_KEY_TTL_SECS: u64 = 5 * 60;
+// maximum clients remembered, the least recently seen are evicted first
+pub const IDEMPOTENCY_CACHE_CLIENTS: usize = 1024;
+// maximum keys remembered per client, the oldest are evicted first
+pub const IDEMPOTENCY_CACHE_SIZE: usize = 256;
+// maximum length of a key sent by a client
+pub const IDEMPOTENCY_KEY_MAX_LEN: usize = 128;
 
 // P2p rules
+// Version of the P2P protocol, sent in the handshake
+// It is increased each time a packet is extended in a non backward compatible way
+pub const P2P_PROTOCOL_VERSION: u8 = 5;
+// Protocol version from which the block object responses contain the metadata hints
+pub const P2P_BLOCK_HINTS_PROTOCOL_VERSION: u8 = 1;
+// Protocol version from which the ping packets with peer list can contain the topology
+pub const P2P_TOPOLOGY_PROTOCOL_VERSION: u8 = 2;
+// Protocol version from which blocks can be requested by topoheight
+pub const P2P_BLOCK_AT_TOPOHEIGHT_PROTOCOL_VERSION: u8 = 3;
+// Protocol version from which chain responses contain a continuation token
+pub const P2P_CHAIN_SYNC_TOKEN_PROTOCOL_VERSION: u8 = 4;
+// Protocol version from which pings are sent with our time to compute the network time
+pub const P2P_TIMED_PING_PROTOCOL_VERSION: u8 = 5;
+// time in seconds after which a topology received is marked as stale
+pub const P2P_TOPOLOGY_STALE_DELAY: u64 = 60 * 5;
 // time between each ping
 pub const P2P_PING_DELAY: u64 = 10;
 // time in seconds between each update of peerlist
@@ -132,6 +233,10 @@ pub const P2P_HEARTBEAT_INTERVAL: u64 = P2P_PING_DELAY / 2;
 // Timeout in seconds
 // If we didn't receive any packet from a peer during this time, we disconnect it
 pub const P2P_PING_TIMEOUT: u64 = P2P_PING_DELAY * 6;
+// Timeout in seconds to read a packet from a peer
+// Peer must send us at least a ping packet every P2P_PING_DELAY,
+// so if we don't receive anything during this time, the connection is considered dead
+pub const P2P_READ_TIMEOUT: u64 = P2P_PING_DELAY * 2;
 
 // Peer rules
 // number of seconds to reset the counter
@@ -164,11 +269,39 @@ pub const PEER_MAX_PACKET_SIZE: u32 = MAX_BLOCK_SIZE as u32 + 16;
 pub const PEER_TX_CACHE_SIZE: usize = 10240;
 // Peer Block cache size
 pub const PEER_BLOCK_CACHE_SIZE: usize = 1024;
+// TXs a peer can propagate per second once its burst is consumed
+pub const PEER_TX_RATE_LIMIT_PER_SECOND: u64 = 50;
+// TXs a peer can propagate at once
+pub const PEER_TX_RATE_LIMIT_BURST: u64 = 200;
+// TXs dropped due to the rate limit before counting a fail
+pub const PEER_TX_RATE_LIMIT_STRIKES: u64 = 100;
+// Chain continuation requests a peer can send per second once its burst is consumed
+pub const PEER_CHAIN_CONTINUATION_RATE_LIMIT_PER_SECOND: u64 = 2;
+// Chain continuation requests a peer can send at once
+pub const PEER_CHAIN_CONTINUATION_RATE_LIMIT_BURST: u64 = 8;
+// Object requests served at the same time for a peer
+pub const PEER_OBJECT_REQUESTS_MAX_CONCURRENT: usize = 8;
+// Object requests of a peer waiting to be served before dropping the next ones
+pub const PEER_OBJECT_REQUESTS_MAX_QUEUED: usize = 64;
+// Max time in millis an object request can wait to be served before being dropped
+pub const PEER_OBJECT_REQUESTS_QUEUE_TIMEOUT: u64 = 2000;
+// Object requests served at the same time for all peers
+pub const P2P_OBJECT_REQUESTS_MAX_CONCURRENT: usize = 64;
 // Peer packet channel size
 pub const PEER_PACKET_CHANNEL_SIZE: usize = 1024;
 // Peer timeout for packet channel
 // Millis
 pub const PEER_SEND_BYTES_TIMEOUT: u64 = 3_000;
+// Maximum blocks kept while waiting on their parents
+pub const P2P_ORPHAN_BLOCKS_MAX_SIZE: usize = STABLE_LIMIT as usize * TIPS_LIMIT * 4;
+// Seconds before an orphan block is evicted
+pub const P2P_ORPHAN_BLOCK_MAX_AGE: u64 = 60;
+// Seconds between each check of the orphan blocks parents
+// Parents may be added by the chain sync instead of the propagation
+pub const P2P_ORPHAN_BLOCKS_CHECK_DELAY: u64 = 1;
+// number of compact blocks a peer can fail to reconstruct
+// before we fallback to full headers propagation for it
+pub const PEER_COMPACT_BLOCKS_MAX_FAILURES: u8 = 2;
 
 // Genesis block to have the same starting point for every nodes
 // Genesis block in hexadecimal format
@@ -238,6 +371,46 @@ pub const fn get_seed_nodes(network: &Network) -> &[&str] {
     }
 }
 
+// Get the height from which the AssetCreation TXs are accepted
+// This is a hard fork: the nodes of the previous versions reject these TXs
+pub const fn get_asset_creation_activation_height(network: &Network) -> u64 {
+    match network {
+        // Not scheduled yet
+        Network::Mainnet | Network::Testnet => u64::MAX,
+        Network::Dev => 0,
+    }
+}
+
+// Get the height from which the TXs with extra data are accepted
+// Older nodes don't read the extra data flag of the TX version
+pub const fn get_extra_data_activation_height(network: &Network) -> u64 {
+    match network {
+        // Not scheduled yet
+        Network::Mainnet | Network::Testnet => u64::MAX,
+        Network::Dev => 0,
+    }
+}
+
+// Get the height from which the public keys of the TXs and blocks must be valid points other than the identity
+// They are read without validation, the blocks before it may contain such keys
+pub const fn get_public_keys_validation_activation_height(network: &Network) -> u64 {
+    match network {
+        // Not scheduled yet
+        Network::Mainnet | Network::Testnet => u64::MAX,
+        Network::Dev => 0,
+    }
+}
+
+// Get the height from which the block headers use the TXS_MERKLE_ROOT_VERSION
+// The genesis block stays on the version 0
+pub const fn get_txs_merkle_root_activation_height(network: &Network) -> u64 {
+    match network {
+        // Not scheduled yet
+        Network::Mainnet | Network::Testnet => u64::MAX,
+        Network::Dev => 1,
+    }
+}
+
 // Get minimum difficulty based on the network
 // Mainnet has a minimum difficulty to prevent spamming the network
 // Testnet has a lower difficulty to allow faster block generation
@@ -0,0 +1,252 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Weak,
+    time::Duration
+};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use xelis_common::{
+    crypto::Hash,
+    network::Network,
+    time::{get_current_time_in_seconds, TimestampSeconds},
+    utils::spawn_task
+};
+use super::{
+    blockchain::Blockchain,
+    bootstrap,
+    error::BlockchainError,
+    storage::Storage
+};
+
+// Every backup is a directory named with this prefix and its creation timestamp
+const BACKUP_DIR_PREFIX: &str = "backup-";
+// Suffix of a backup directory being written
+const BACKUP_TMP_SUFFIX: &str = ".tmp";
+pub const BACKUP_MANIFEST_FILE: &str = "manifest.json";
+// Chain export in the portable format, it can be imported with --import-chain
+pub const BACKUP_CHAIN_FILE: &str = "chain.bin";
+
+// Written with the chain export so a restore can be validated
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub network: Network,
+    pub genesis_hash: Hash,
+    // Last block exported in the backup
+    pub topoheight: u64,
+    pub top_hash: Hash,
+    pub created_at: TimestampSeconds
+}
+
+impl BackupManifest {
+    pub fn read(dir: &Path) -> Result<Self, BlockchainError> {
+        let content = fs::read_to_string(dir.join(BACKUP_MANIFEST_FILE))?;
+        serde_json::from_str(&content).map_err(|e| BlockchainError::InvalidBackupManifest(e.to_string()))
+    }
+
+    fn write(&self, dir: &Path) -> Result<(), BlockchainError> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| BlockchainError::InvalidBackupManifest(e.to_string()))?;
+        fs::write(dir.join(BACKUP_MANIFEST_FILE), content)?;
+        Ok(())
+    }
+
+    // Verify that the chain restored contains the backup
+    pub async fn verify_restore<S: Storage>(&self, blockchain: &Blockchain<S>) -> Result<(), BlockchainError> {
+        if self.network != *blockchain.get_network() {
+            return Err(BlockchainError::ChainExportNetworkMismatch(self.network, *blockchain.get_network()))
+        }
+
+        let storage = blockchain.get_storage().read().await;
+        if storage.get_hash_at_topo_height(0).await? != self.genesis_hash {
+            return Err(BlockchainError::ChainExportGenesisMismatch(self.genesis_hash.clone()))
+        }
+
+        match storage.get_hash_at_topo_height(self.topoheight).await {
+            Ok(hash) if hash == self.top_hash => Ok(()),
+            _ => Err(BlockchainError::BackupRestoreMismatch(self.top_hash.clone(), self.topoheight))
+        }
+    }
+}
+
+// Resolve the path given to --import-chain
+// A backup directory is imported from its chain export and returns its manifest to validate the restore
+pub fn resolve_import_path(path: &str) -> Result<(String, Option<BackupManifest>), BlockchainError> {
+    let dir = Path::new(path);
+    if !dir.is_dir() {
+        return Ok((path.to_owned(), None))
+    }
+
+    let manifest = BackupManifest::read(dir)?;
+    let chain = dir.join(BACKUP_CHAIN_FILE).to_string_lossy().into_owned();
+    Ok((chain, Some(manifest)))
+}
+
+// Creation timestamp and topoheight from the name of a backup directory
+// Returns None if it is not a name we generate
+fn parse_backup_name(name: &str) -> Option<(TimestampSeconds, u64)> {
+    let (created_at, topoheight) = name.strip_prefix(BACKUP_DIR_PREFIX)?.split_once('-')?;
+    Some((created_at.parse().ok()?, topoheight.parse().ok()?))
+}
+
+// List the complete backups in the directory, oldest first
+// Only the directories named and described by a manifest like we do are listed
+pub fn list_backups(backup_dir: &Path) -> Result<Vec<(PathBuf, BackupManifest)>, BlockchainError> {
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(backup_dir)? {
+        let path = entry?.path();
+        let Some(key) = path.file_name().and_then(|name| name.to_str()).and_then(parse_backup_name) else {
+            continue;
+        };
+
+        if !path.is_dir() {
+            continue;
+        }
+
+        match BackupManifest::read(&path) {
+            Ok(manifest) if (manifest.created_at, manifest.topoheight) == key => backups.push((path, manifest)),
+            Ok(_) => warn!("Ignoring backup {}: its manifest doesn't match its name", path.display()),
+            Err(e) => warn!("Ignoring backup {}: {}", path.display(), e)
+        }
+    }
+
+    backups.sort_by_key(|(_, manifest)| (manifest.created_at, manifest.topoheight));
+    Ok(backups)
+}
+
+// Delete the oldest backups of our chain to keep only `retention` of them
+// Backups interrupted before being completed are deleted too
+// The backup directory is given by the user, anything else in it is never deleted
+fn rotate_backups(backup_dir: &Path, retention: usize, network: &Network, genesis_hash: &Hash) -> Result<(), BlockchainError> {
+    for entry in fs::read_dir(backup_dir)? {
+        let path = entry?.path();
+        let interrupted = path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(BACKUP_TMP_SUFFIX))
+            .and_then(parse_backup_name)
+            .is_some();
+
+        if interrupted && path.is_dir() {
+            debug!("Deleting interrupted backup {}", path.display());
+            fs::remove_dir_all(&path)?;
+        }
+    }
+
+    let backups: Vec<_> = list_backups(backup_dir)?
+        .into_iter()
+        .filter(|(_, manifest)| manifest.network == *network && manifest.genesis_hash == *genesis_hash)
+        .collect();
+
+    let count = backups.len().saturating_sub(retention.max(1));
+    for (path, _) in backups.into_iter().take(count) {
+        info!("Deleting old backup {}", path.display());
+        fs::remove_dir_all(&path)?;
+    }
+
+    Ok(())
+}
+
+// Backup the chain until the stable topoheight in a new timestamped directory
+// The chain is locked only to flush it and read the stable state, then each chunk is exported with its own lock
+pub async fn backup_now<S: Storage>(blockchain: &Blockchain<S>, backup_dir: &Path, retention: usize) -> Result<(PathBuf, BackupManifest), BlockchainError> {
+    let manifest = {
+        let storage = blockchain.get_storage().read().await;
+        storage.flush().await?;
+
+        let topoheight = blockchain.get_stable_topoheight();
+        BackupManifest {
+            network: *blockchain.get_network(),
+            genesis_hash: storage.get_hash_at_topo_height(0).await?,
+            top_hash: storage.get_hash_at_topo_height(topoheight).await?,
+            topoheight,
+            created_at: get_current_time_in_seconds()
+        }
+    };
+
+    let name = format!("{}{}-{}", BACKUP_DIR_PREFIX, manifest.created_at, manifest.topoheight);
+    let path = backup_dir.join(&name);
+    // Written in a temporary directory so an interrupted backup is never used
+    let tmp_path = backup_dir.join(format!("{}{}", name, BACKUP_TMP_SUFFIX));
+    fs::create_dir_all(&tmp_path)?;
+
+    info!("Backing up the chain until topoheight {} to {}...", manifest.topoheight, path.display());
+    bootstrap::export_stable_chain(blockchain, &tmp_path.join(BACKUP_CHAIN_FILE), manifest.topoheight).await?;
+    manifest.write(&tmp_path)?;
+    fs::rename(&tmp_path, &path)?;
+    info!("Backup {} completed", path.display());
+
+    rotate_backups(backup_dir, retention, &manifest.network, &manifest.genesis_hash)?;
+
+    Ok((path, manifest))
+}
+
+// Backup the chain on schedule until the blockchain is dropped
+pub fn start_backup_task<S: Storage>(blockchain: Weak<Blockchain<S>>, backup_dir: PathBuf, interval: Duration, retention: usize) {
+    spawn_task("backup", async move {
+        let mut interval = tokio::time::interval(interval);
+        // First tick is instant, no backup at startup
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            let Some(blockchain) = blockchain.upgrade() else {
+                debug!("Blockchain dropped, stopping the backup task");
+                break;
+            };
+
+            if let Err(e) = backup_now(&blockchain, &backup_dir, retention).await {
+                error!("Error while backing up the chain: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::testing::TempDir;
+    use super::*;
+
+    fn add_backup(dir: &Path, name: &str, manifest: &BackupManifest) -> PathBuf {
+        let path = dir.join(name);
+        fs::create_dir_all(&path).unwrap();
+        manifest.write(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_rotate_only_our_backups() {
+        let dir = TempDir::new("backup-rotation");
+        let manifest = |created_at: TimestampSeconds, genesis_hash: Hash| BackupManifest {
+            network: Network::Dev,
+            genesis_hash,
+            topoheight: 10,
+            top_hash: Hash::zero(),
+            created_at
+        };
+        let genesis_hash = Hash::new([1u8; 32]);
+
+        let oldest = add_backup(&dir, "backup-100-10", &manifest(100, genesis_hash.clone()));
+        let newest = add_backup(&dir, "backup-200-10", &manifest(200, genesis_hash.clone()));
+        let interrupted = dir.join("backup-300-10.tmp");
+        fs::create_dir_all(&interrupted).unwrap();
+
+        // Files of the user sharing the directory
+        let other_chain = add_backup(&dir, "backup-50-10", &manifest(50, Hash::new([2u8; 32])));
+        let renamed = add_backup(&dir, "backup-60-20", &manifest(60, genesis_hash.clone()));
+        let unrelated = add_backup(&dir, "backup-photos", &manifest(10, genesis_hash.clone()));
+        let unrelated_tmp = dir.join("backup-photos.tmp");
+        fs::create_dir_all(&unrelated_tmp).unwrap();
+        let file = dir.join("backup-1-1");
+        fs::write(&file, b"data").unwrap();
+
+        rotate_backups(&dir, 1, &Network::Dev, &genesis_hash).unwrap();
+        assert!(!oldest.exists());
+        assert!(!interrupted.exists());
+        assert!(newest.exists());
+        for path in [other_chain, renamed, unrelated, unrelated_tmp, file] {
+            assert!(path.exists(), "{} was deleted", path.display());
+        }
+
+        let backups = list_backups(&dir).unwrap();
+        assert_eq!(backups.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), vec![dir.join("backup-50-10"), newest]);
+    }
+}
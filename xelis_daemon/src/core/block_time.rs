@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use crate::config::{
+    BLOCK_TIME_MILLIS,
+    BLOCK_TIME_DRIFT_WINDOW,
+    BLOCK_TIME_DRIFT_MAX_DEVIATION_PERCENT,
+    BLOCK_TIME_DRIFT_WARNING_DELAY_MILLIS
+};
+
+// Check if the average block time deviates too much from the target
+pub fn is_deviating(average: u64) -> bool {
+    average.abs_diff(BLOCK_TIME_MILLIS) * 100 > BLOCK_TIME_MILLIS * BLOCK_TIME_DRIFT_MAX_DEVIATION_PERCENT
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockTimeDrift {
+    // Rolling average solve time, None until two blocks are accepted
+    pub average: Option<u64>,
+    // The average deviates from the target for more than the warning delay
+    pub drifting: bool
+}
+
+// Rolling average of the solve time on the last blocks accepted
+// It is used to detect a sustained drift from the target block time
+pub struct BlockTimeTracker {
+    // Timestamps of the last blocks accepted
+    timestamps: VecDeque<u64>,
+    // Block timestamp since which the full window average deviates
+    deviating_since: Option<u64>,
+    // Set once the sustained drift is reported so we warn only once
+    reported: bool
+}
+
+impl BlockTimeTracker {
+    pub fn new() -> Self {
+        Self {
+            timestamps: VecDeque::with_capacity(BLOCK_TIME_DRIFT_WINDOW),
+            deviating_since: None,
+            reported: false
+        }
+    }
+
+    // Average solve time of the blocks in the window
+    // Blocks are not always accepted in timestamp order, so we use the oldest and newest ones
+    pub fn get_average(&self) -> Option<u64> {
+        if self.timestamps.len() < 2 {
+            return None
+        }
+
+        let min = self.timestamps.iter().min()?;
+        let max = self.timestamps.iter().max()?;
+        Some((max - min) / (self.timestamps.len() as u64 - 1))
+    }
+
+    // Latest block timestamp accepted
+    fn get_last_timestamp(&self) -> Option<u64> {
+        self.timestamps.iter().max().copied()
+    }
+
+    pub fn is_drifting(&self) -> bool {
+        match (self.deviating_since, self.get_last_timestamp()) {
+            (Some(since), Some(last)) => last.saturating_sub(since) > BLOCK_TIME_DRIFT_WARNING_DELAY_MILLIS,
+            _ => false
+        }
+    }
+
+    pub fn get_drift(&self) -> BlockTimeDrift {
+        BlockTimeDrift {
+            average: self.get_average(),
+            drifting: self.is_drifting()
+        }
+    }
+
+    // Record the timestamp of a new block accepted
+    // Returns true when the drift just became sustained and should be reported
+    pub fn record(&mut self, timestamp: u64) -> bool {
+        if self.timestamps.len() >= BLOCK_TIME_DRIFT_WINDOW {
+            self.timestamps.pop_front();
+        }
+        self.timestamps.push_back(timestamp);
+
+        // Only a full window is meaningful
+        let deviating = self.timestamps.len() >= BLOCK_TIME_DRIFT_WINDOW
+            && self.get_average().is_some_and(is_deviating);
+
+        if !deviating {
+            self.deviating_since = None;
+            self.reported = false;
+            return false
+        }
+
+        if self.deviating_since.is_none() {
+            self.deviating_since = Some(timestamp);
+        }
+
+        if !self.reported && self.is_drifting() {
+            self.reported = true;
+            return true
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1_700_000_000_000;
+
+    // Record `count` blocks solved every `solve_time` ms after `last`
+    // Returns the last timestamp and how many times the drift got reported
+    fn mine(tracker: &mut BlockTimeTracker, last: u64, count: u64, solve_time: u64) -> (u64, usize) {
+        let mut reported = 0;
+        let mut timestamp = last;
+        for _ in 0..count {
+            timestamp += solve_time;
+            if tracker.record(timestamp) {
+                reported += 1;
+            }
+        }
+        (timestamp, reported)
+    }
+
+    #[test]
+    fn test_deviation_threshold() {
+        assert!(!is_deviating(BLOCK_TIME_MILLIS));
+        assert!(!is_deviating(BLOCK_TIME_MILLIS * 3 / 2));
+        assert!(is_deviating(BLOCK_TIME_MILLIS * 3 / 2 + 1));
+        assert!(!is_deviating(BLOCK_TIME_MILLIS / 2));
+        assert!(is_deviating(BLOCK_TIME_MILLIS / 2 - 1));
+    }
+
+    #[test]
+    fn test_stable_block_time() {
+        let mut tracker = BlockTimeTracker::new();
+        assert_eq!(tracker.get_drift(), BlockTimeDrift { average: None, drifting: false });
+
+        let (_, reported) = mine(&mut tracker, START, 1000, BLOCK_TIME_MILLIS);
+        assert_eq!(reported, 0);
+        assert_eq!(tracker.get_drift(), BlockTimeDrift { average: Some(BLOCK_TIME_MILLIS), drifting: false });
+    }
+
+    #[test]
+    fn test_sustained_drift_reported_once() {
+        let mut tracker = BlockTimeTracker::new();
+        let (last, _) = mine(&mut tracker, START, BLOCK_TIME_DRIFT_WINDOW as u64, BLOCK_TIME_MILLIS);
+
+        // Hashrate collapse: 25s blocks instead of 15s
+        let slow = 25 * 1000;
+        // Not yet an hour since the average crossed the threshold
+        let (last, reported) = mine(&mut tracker, last, 150, slow);
+        assert_eq!(reported, 0);
+        assert!(tracker.get_average().is_some_and(is_deviating));
+        assert!(!tracker.is_drifting());
+
+        let (last, reported) = mine(&mut tracker, last, 200, slow);
+        assert_eq!(reported, 1);
+        assert!(tracker.is_drifting());
+        assert_eq!(tracker.get_average(), Some(slow));
+
+        // Back to the target block time
+        let (_, reported) = mine(&mut tracker, last, BLOCK_TIME_DRIFT_WINDOW as u64, BLOCK_TIME_MILLIS);
+        assert_eq!(reported, 0);
+        assert!(!tracker.is_drifting());
+    }
+
+    #[test]
+    fn test_short_deviation_not_reported() {
+        let mut tracker = BlockTimeTracker::new();
+        let (last, _) = mine(&mut tracker, START, BLOCK_TIME_DRIFT_WINDOW as u64, BLOCK_TIME_MILLIS);
+
+        // Fast blocks for less than an hour, then back to normal
+        let (last, reported) = mine(&mut tracker, last, 300, 5 * 1000);
+        assert_eq!(reported, 0);
+        let (_, reported) = mine(&mut tracker, last, 2 * BLOCK_TIME_DRIFT_WINDOW as u64, BLOCK_TIME_MILLIS);
+        assert_eq!(reported, 0);
+        assert!(!tracker.is_drifting());
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    time::{Duration, Instant}
+};
+use xelis_common::api::daemon::{
+    BlockProcessingHistogramBucket,
+    BlockProcessingStage,
+    BlockProcessingStageStats,
+    GetBlockProcessingStatsResult
+};
+
+// All the stages of the processing of a block, in their execution order
+pub const BLOCK_PROCESSING_STAGES: [BlockProcessingStage; 6] = [
+    BlockProcessingStage::HeaderVerification,
+    BlockProcessingStage::ProofOfWork,
+    BlockProcessingStage::TxsVerification,
+    BlockProcessingStage::Execution,
+    BlockProcessingStage::StorageCommit,
+    BlockProcessingStage::Notification
+];
+
+// Inclusive upper bounds in microseconds of the histogram buckets
+// A last bucket counts the durations above the highest bound
+const HISTOGRAM_BOUNDS: [u64; 10] = [100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 250_000, 500_000, 1_000_000];
+
+fn stage_index(stage: BlockProcessingStage) -> usize {
+    match stage {
+        BlockProcessingStage::HeaderVerification => 0,
+        BlockProcessingStage::ProofOfWork => 1,
+        BlockProcessingStage::TxsVerification => 2,
+        BlockProcessingStage::Execution => 3,
+        BlockProcessingStage::StorageCommit => 4,
+        BlockProcessingStage::Notification => 5
+    }
+}
+
+fn stage_name(stage: BlockProcessingStage) -> &'static str {
+    match stage {
+        BlockProcessingStage::HeaderVerification => "header",
+        BlockProcessingStage::ProofOfWork => "pow",
+        BlockProcessingStage::TxsVerification => "txs",
+        BlockProcessingStage::Execution => "execution",
+        BlockProcessingStage::StorageCommit => "storage",
+        BlockProcessingStage::Notification => "notification"
+    }
+}
+
+fn as_micros(duration: Duration) -> u64 {
+    duration.as_micros().try_into().unwrap_or(u64::MAX)
+}
+
+// Time spent in each stage while processing one block
+#[derive(Debug, Default, Clone)]
+pub struct BlockTimings {
+    stages: [Option<Duration>; BLOCK_PROCESSING_STAGES.len()]
+}
+
+impl BlockTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Add the time elapsed since start to the stage
+    pub fn record(&mut self, stage: BlockProcessingStage, start: Instant) {
+        self.add(stage, start.elapsed());
+    }
+
+    // A stage can be measured in several parts, they are summed
+    pub fn add(&mut self, stage: BlockProcessingStage, duration: Duration) {
+        let value = &mut self.stages[stage_index(stage)];
+        *value = Some(value.unwrap_or_default() + duration);
+    }
+
+    // None if the stage wasn't reached
+    pub fn get(&self, stage: BlockProcessingStage) -> Option<Duration> {
+        self.stages[stage_index(stage)]
+    }
+}
+
+impl Display for BlockTimings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut total = Duration::ZERO;
+        for stage in BLOCK_PROCESSING_STAGES {
+            match self.get(stage) {
+                Some(duration) => {
+                    total += duration;
+                    write!(f, "{}: {}us, ", stage_name(stage), as_micros(duration))?;
+                },
+                None => write!(f, "{}: -, ", stage_name(stage))?
+            }
+        }
+        write!(f, "total: {}us", as_micros(total))
+    }
+}
+
+#[derive(Default)]
+struct StageHistogram {
+    count: u64,
+    total: u64,
+    max: u64,
+    last: Option<u64>,
+    buckets: [u64; HISTOGRAM_BOUNDS.len() + 1]
+}
+
+impl StageHistogram {
+    fn record(&mut self, duration: Duration) {
+        let micros = as_micros(duration);
+        self.count += 1;
+        self.total = self.total.saturating_add(micros);
+        self.max = self.max.max(micros);
+        self.last = Some(micros);
+
+        let index = HISTOGRAM_BOUNDS.iter().position(|bound| micros <= *bound).unwrap_or(HISTOGRAM_BOUNDS.len());
+        self.buckets[index] += 1;
+    }
+
+    fn to_stats(&self, stage: BlockProcessingStage) -> BlockProcessingStageStats {
+        let buckets = self.buckets.iter()
+            .enumerate()
+            .map(|(i, count)| BlockProcessingHistogramBucket {
+                upper_bound: HISTOGRAM_BOUNDS.get(i).copied(),
+                count: *count
+            })
+            .collect();
+
+        BlockProcessingStageStats {
+            stage,
+            count: self.count,
+            total: self.total,
+            average: self.total.checked_div(self.count).unwrap_or(0),
+            max: self.max,
+            last: self.last,
+            buckets
+        }
+    }
+}
+
+// Timings of all the blocks processed since the node started
+#[derive(Default)]
+pub struct BlockProcessingStats {
+    blocks: u64,
+    stages: [StageHistogram; BLOCK_PROCESSING_STAGES.len()]
+}
+
+impl BlockProcessingStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Add the timings of a block processed, stages not reached are ignored
+    pub fn record(&mut self, timings: &BlockTimings) {
+        self.blocks += 1;
+        for stage in BLOCK_PROCESSING_STAGES {
+            if let Some(duration) = timings.get(stage) {
+                self.stages[stage_index(stage)].record(duration);
+            }
+        }
+    }
+
+    pub fn get_stats(&self) -> GetBlockProcessingStatsResult {
+        GetBlockProcessingStatsResult {
+            blocks: self.blocks,
+            stages: BLOCK_PROCESSING_STAGES.iter()
+                .map(|stage| self.stages[stage_index(*stage)].to_stats(*stage))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_parts_are_summed() {
+        let mut timings = BlockTimings::new();
+        assert_eq!(timings.get(BlockProcessingStage::StorageCommit), None);
+
+        timings.add(BlockProcessingStage::StorageCommit, Duration::from_micros(300));
+        timings.add(BlockProcessingStage::StorageCommit, Duration::from_micros(200));
+        assert_eq!(timings.get(BlockProcessingStage::StorageCommit), Some(Duration::from_micros(500)));
+        assert_eq!(timings.get(BlockProcessingStage::Execution), None);
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        let mut stats = BlockProcessingStats::new();
+        for micros in [50, 100, 101, 2_000_000] {
+            let mut timings = BlockTimings::new();
+            timings.add(BlockProcessingStage::ProofOfWork, Duration::from_micros(micros));
+            stats.record(&timings);
+        }
+
+        let result = stats.get_stats();
+        assert_eq!(result.blocks, 4);
+        assert_eq!(result.stages.len(), BLOCK_PROCESSING_STAGES.len());
+
+        let pow = &result.stages[stage_index(BlockProcessingStage::ProofOfWork)];
+        assert_eq!(pow.stage, BlockProcessingStage::ProofOfWork);
+        assert_eq!(pow.count, 4);
+        assert_eq!(pow.total, 2_000_251);
+        assert_eq!(pow.average, 500_062);
+        assert_eq!(pow.max, 2_000_000);
+        assert_eq!(pow.last, Some(2_000_000));
+        assert_eq!(pow.buckets[0], BlockProcessingHistogramBucket { upper_bound: Some(100), count: 2 });
+        assert_eq!(pow.buckets[1], BlockProcessingHistogramBucket { upper_bound: Some(500), count: 1 });
+        assert_eq!(pow.buckets[HISTOGRAM_BOUNDS.len()], BlockProcessingHistogramBucket { upper_bound: None, count: 1 });
+
+        // Stages never reached stay empty
+        let header = &result.stages[stage_index(BlockProcessingStage::HeaderVerification)];
+        assert_eq!(header.count, 0);
+        assert_eq!(header.average, 0);
+        assert!(header.buckets.iter().all(|bucket| bucket.count == 0));
+    }
+}
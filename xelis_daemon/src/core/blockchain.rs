@@ -1,16 +1,26 @@
 use anyhow::Error;
-use indexmap::IndexSet;
+use indexmap::{IndexMap, IndexSet};
 use lru::LruCache;
 use serde_json::{Value, json};
 use xelis_common::{
     api::{
         daemon::{
+            BalanceUpdatedEvent,
+            BlockExecutedTransaction,
             BlockOrderedEvent,
             BlockOrphanedEvent,
+            BlockProcessingStage,
+            BlockSummary,
+            DoubleSpendDetectedEvent,
             BlockType,
+            GetBlockProcessingStatsResult,
             NotifyEvent,
             StableHeightChangedEvent,
+            SyncCompletedEvent,
             TransactionExecutedEvent,
+            TransactionExecutionStatus,
+            TransactionReceivedEvent,
+            TransactionReplacedEvent,
             TransactionResponse
         },
         RPCTransaction
@@ -19,10 +29,13 @@ use xelis_common::{
     block::{
         Block,
         BlockHeader,
-        EXTRA_NONCE_SIZE
+        EXTRA_NONCE_SIZE,
+        TXS_MERKLE_ROOT_VERSION
     },
     config::{
+        ASSET_CREATION_BURN_AMOUNT,
         COIN_DECIMALS,
+        FEE_PER_KB,
         MAXIMUM_SUPPLY,
         MAX_TRANSACTION_SIZE,
         TIPS_LIMIT,
@@ -31,49 +44,64 @@ use xelis_common::{
     crypto::{
         Hash,
         Hashable,
-        PublicKey,
-        HASH_SIZE
+        PublicKey
     },
     difficulty::{check_difficulty, CumulativeDifficulty, Difficulty},
     immutable::Immutable,
     network::Network,
+    prompt::ShareablePrompt,
     serializer::Serializer,
     time::{
+        format_timestamp_seconds,
         get_current_time_in_millis,
         get_current_time_in_seconds,
-        TimestampMillis
+        is_timestamp_in_range,
+        TimestampSeconds
     },
-    transaction::{verify::BlockchainVerificationState, Transaction, TransactionType},
-    utils::{calculate_tx_fee, format_xelis, spawn_task},
+    transaction::{verify::{BlockchainVerificationState, VerificationError}, AssetCreationPayload, Transaction, TransactionType, TX_EXTRA_DATA_LIMIT_SIZE},
+    utils::{calculate_extra_data_fee, calculate_tx_fee_with_fee_per_kb, format_xelis, spawn_task},
     varuint::VarUint
 };
 use crate::{
     config::{
-        get_genesis_block_hash, get_hex_genesis_block, get_minimum_difficulty,
-        BLOCK_TIME_MILLIS, CHAIN_SYNC_RESPONSE_MAX_BLOCKS, CHAIN_SYNC_RESPONSE_MIN_BLOCKS,
-        DEFAULT_CACHE_SIZE, DEFAULT_P2P_BIND_ADDRESS, DEFAULT_RPC_BIND_ADDRESS, DEV_FEES,
+        get_asset_creation_activation_height, get_extra_data_activation_height, get_genesis_block_hash, get_hex_genesis_block, get_minimum_difficulty,
+        get_public_keys_validation_activation_height, get_txs_merkle_root_activation_height,
+        BLOCK_TIME_MILLIS, BLOCK_TIME_DRIFT_WINDOW, CHAIN_SYNC_RESPONSE_MAX_BLOCKS, CHAIN_SYNC_RESPONSE_MIN_BLOCKS,
+        DEFAULT_CACHE_SIZE, DEFAULT_P2P_BIND_ADDRESS, DEFAULT_P2P_PORT, DEFAULT_RPC_BIND_ADDRESS, DEFAULT_RPC_ADMIN_USERNAME, DEV_FEES,
         DEV_PUBLIC_KEY, EMISSION_SPEED_FACTOR, GENESIS_BLOCK_DIFFICULTY, MAX_BLOCK_SIZE,
         MILLIS_PER_SECOND, P2P_DEFAULT_MAX_PEERS, SIDE_BLOCK_REWARD_MAX_BLOCKS, PRUNE_SAFETY_LIMIT,
         SIDE_BLOCK_REWARD_PERCENT, SIDE_BLOCK_REWARD_MIN_PERCENT, STABLE_LIMIT, TIMESTAMP_IN_FUTURE_LIMIT,
-        P2P_DEFAULT_CONCURRENCY_TASK_COUNT_LIMIT
+        P2P_DEFAULT_CONCURRENCY_TASK_COUNT_LIMIT, MAX_BLOCK_REWIND, DEFAULT_MEMPOOL_MAX_TX_EXTRA_DATA_SIZE,
+        MEMPOOL_RELOAD_TIMEOUT_SECS, DEFAULT_MEMPOOL_MAX_SIZE, DEFAULT_BACKUP_INTERVAL_HOURS,
+        DEFAULT_BACKUP_RETENTION
     },
     core::{
-        blockdag,
+        backup::{self, BackupManifest},
+        blockdag::{self, TipCandidate},
+        bootstrap,
         difficulty,
         error::BlockchainError,
-        mempool::Mempool,
-        nonce_checker::NonceChecker,
+        mempool::{reload_persisted_txs, Mempool},
+        top_state::{TopState, TopStateCache},
+        chain_view::ChainStateView,
+        nonce_checker::{BlockNonces, NonceChecker},
+        reorg::DeepReorgGuard,
         simulator::Simulator,
-        storage::{DagOrderProvider, DifficultyProvider, Storage},
+        sync::{is_synced, SyncState},
+        block_time::{BlockTimeDrift, BlockTimeTracker},
+        block_timings::{BlockProcessingStats, BlockTimings},
+        network_time::NetworkTime,
+        storage::{DagOrderProvider, DifficultyProvider, MempoolProvider, Storage},
         tx_selector::{TxSelector, TxSelectorEntry},
-        state::{ChainState, ApplicableChainState},
+        state::{ChainState, ApplicableChainState, StateOverlay},
     },
     p2p::P2pServer,
     rpc::{
         rpc::{
             get_block_type_for_block,
-            get_block_response
+            get_new_block_event
         },
+        AdminAuthConfig,
         DaemonRpcServer,
         SharedDaemonRpcServer
     }
@@ -86,13 +114,12 @@ use std::{
         HashSet,
         VecDeque
     },
-    net::SocketAddr,
+    mem,
+    net::{IpAddr, SocketAddr},
     num::NonZeroUsize,
-    sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc
-    },
-    time::Instant
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant}
 };
 use tokio::{sync::{Mutex, RwLock}, net::lookup_host};
 use log::{info, error, debug, warn, trace};
@@ -110,15 +137,29 @@ pub struct Config {
     /// Optional node tag
     #[clap(long)]
     pub tag: Option<String>,
-    /// P2p bind address to listen for incoming connections
-    #[clap(long, default_value_t = String::from(DEFAULT_P2P_BIND_ADDRESS))]
-    pub p2p_bind_address: String,
+    /// P2p bind addresses to listen for incoming connections
+    /// Several addresses can be set separated by commas, for example: 0.0.0.0:2125,[::]:2125
+    #[clap(long, value_delimiter = ',', default_value = DEFAULT_P2P_BIND_ADDRESS)]
+    pub p2p_bind_address: Vec<String>,
     /// Number of maximums peers allowed
     #[clap(long, default_value_t = P2P_DEFAULT_MAX_PEERS)]
     pub max_peers: usize,
-    /// Rpc bind address to listen for HTTP requests
-    #[clap(long, default_value_t = String::from(DEFAULT_RPC_BIND_ADDRESS))]
-    pub rpc_bind_address: String,
+    /// Rpc bind addresses to listen for HTTP requests
+    /// Several addresses can be set separated by commas, for example: 0.0.0.0:8080,[::]:8080
+    #[clap(long, value_delimiter = ',', default_value = DEFAULT_RPC_BIND_ADDRESS)]
+    pub rpc_bind_address: Vec<String>,
+    /// Rpc bind addresses to listen for HTTPS requests (WSS included)
+    /// They must be different from the HTTP ones, for example: 0.0.0.0:8443
+    /// Certificate and private key must be set with `--rpc-tls-cert` and `--rpc-tls-key`.
+    #[clap(long, value_delimiter = ',')]
+    pub rpc_tls_bind_address: Vec<String>,
+    /// Path to the PEM certificate chain used by the HTTPS RPC listeners.
+    /// It is reloaded on SIGHUP or with the `reload_tls` admin RPC method.
+    #[clap(long)]
+    pub rpc_tls_cert: Option<String>,
+    /// Path to the PEM private key of the RPC certificate.
+    #[clap(long)]
+    pub rpc_tls_key: Option<String>,
     /// Add a priority node to connect when P2p is started.
     /// A priority node is connected only one time.
     #[clap(long)]
@@ -127,6 +168,11 @@ pub struct Config {
     /// it also replaces seed nodes.
     #[clap(long)]
     pub exclusive_nodes: Vec<String>,
+    /// Only connect and accept connections from these addresses, can be set several times.
+    /// Whitelisted peers are connected as priority nodes, reconnected in case of disconnect and addresses shared by peers are ignored.
+    /// If no port is set, the default P2P port is used.
+    #[clap(long)]
+    pub p2p_whitelist: Vec<String>,
     /// Set dir path for blockchain storage.
     /// This will be appended by the network name for the database directory.
     /// It must ends with a slash.
@@ -140,8 +186,21 @@ pub struct Config {
     pub disable_getwork_server: bool,
     /// Disable RPC Server
     /// This will also disable the GetWork Server as it is loaded on RPC server.
-    #[clap(long)]
+    /// No HTTP server is started at all, it is useful for relay-only nodes.
+    #[clap(long, alias = "disable-rpc")]
     pub disable_rpc_server: bool,
+    /// Username to call the admin RPC methods.
+    #[clap(long, default_value = DEFAULT_RPC_ADMIN_USERNAME)]
+    pub rpc_admin_username: String,
+    /// Password to call the admin RPC methods such as set_log_level.
+    /// They are served on /admin/json_rpc using HTTP basic authentication.
+    /// Admin methods are disabled if no password is set.
+    #[clap(long)]
+    pub rpc_admin_password: Option<String>,
+    /// Mask the peers IP addresses in the RPC responses and events.
+    /// Only the /24 (IPv4) or /48 (IPv6) network is shown.
+    #[clap(long)]
+    pub rpc_hide_peer_ips: bool,
     /// Enable the simulator (skip PoW verification, generate a new block for every BLOCK_TIME).
     #[clap(long)]
     pub simulator: Option<Simulator>,
@@ -149,8 +208,13 @@ pub struct Config {
     /// Warning: This is dangerous and should not be used in production.
     #[clap(long)]
     pub skip_pow_verification: bool,
-    /// Disable the p2p connections.
+    /// Log the time spent in each stage of the processing of every new block.
     #[clap(long)]
+    pub log_block_timings: bool,
+    /// Disable the p2p connections.
+    /// Blocks can still be submitted through the `submit_block` RPC method,
+    /// it is useful for an RPC-only node fed by a local trusted node.
+    #[clap(long, alias = "disable-p2p")]
     pub disable_p2p_server: bool,
     /// Enable the auto prune mode and prune the chain
     /// at each new block by keeping at least N blocks
@@ -192,21 +256,129 @@ pub struct Config {
     /// This is useful for seed nodes under heavy load or for nodes that don't want to connect to others.
     #[clap(long)]
     pub disable_p2p_outgoing_connections: bool,
+    /// Disable the mempool exchange with new peers.
+    /// 
+    /// By default, our mempool inventory is sent to new peers and we receive theirs.
+    /// This is useful for pruned or light nodes that don't need pending transactions.
+    #[clap(long)]
+    pub disable_p2p_mempool_sync: bool,
+    /// Propagate and receive blocks with their full header instead of compact blocks.
+    /// 
+    /// By default, only short ids of the transactions are sent to peers supporting compact blocks,
+    /// and they rebuild the block from their mempool.
+    #[clap(long)]
+    pub disable_p2p_compact_blocks: bool,
+    /// Share our connections count and node tag in the ping packets.
+    /// 
+    /// Our peers expose it in their `p2p_topology` RPC method to debug the propagation.
+    /// It is disabled by default, only direct connections are shared.
+    #[clap(long)]
+    pub p2p_share_topology: bool,
+    /// Don't evict peers when the peerlist is full.
+    /// 
+    /// By default, the newest inbound peer is evicted to accept a new inbound connection.
+    /// Priority peers, outgoing peers and the longest connected half of our peers are never evicted.
+    #[clap(long)]
+    pub disable_p2p_peer_eviction: bool,
+    /// Don't download the next blocks while adding the previous ones during the chain sync.
+    /// 
+    /// By default, one batch of blocks is downloaded ahead with peers supporting it.
+    #[clap(long)]
+    pub disable_p2p_sync_pipelining: bool,
     /// Limit of concurrent tasks accepting new incoming connections.
     #[clap(long, default_value_t = P2P_DEFAULT_CONCURRENCY_TASK_COUNT_LIMIT)]
-    pub p2p_concurrency_task_count_limit: usize
+    pub p2p_concurrency_task_count_limit: usize,
+    /// Maximum size in bytes of the transaction extra data accepted in mempool.
+    /// 
+    /// Transactions above it are still accepted in blocks up to the consensus limit.
+    #[clap(long, default_value_t = DEFAULT_MEMPOOL_MAX_TX_EXTRA_DATA_SIZE)]
+    pub mempool_max_tx_extra_data_size: usize,
+    /// Minimum fee per KB required to accept a transaction in mempool and relay it.
+    /// 
+    /// It can only be set above the protocol minimum, blocks with lower fee transactions are still accepted.
+    #[clap(long, default_value_t = FEE_PER_KB)]
+    pub min_fee_per_kb: u64,
+    /// Maximum size in bytes of all the transactions in mempool.
+    /// 
+    /// Once reached, the transactions with the lowest fee per byte are evicted first.
+    #[clap(long, default_value_t = DEFAULT_MEMPOOL_MAX_SIZE)]
+    pub mempool_max_size: usize,
+    /// Allow a single chain reorg below the stable height.
+    /// 
+    /// By default, a peer can't make us reorg below our stable height, even with a heavier chain.
+    /// This is only for recovery after a network split, the reorg must be confirmed in the prompt.
+    #[clap(long)]
+    pub allow_deep_reorg: bool,
+    /// Disable the mempool persistence.
+    /// 
+    /// By default, pending transactions are saved on disk and re-validated at next start.
+    #[clap(long)]
+    pub no_mempool_persistence: bool,
+    /// Import the chain from a file created with the `export_chain` command.
+    /// 
+    /// Each block is verified and executed before starting the P2P server.
+    /// Blocks already in our chain are skipped, so an interrupted import can be started again.
+    /// A backup directory can also be given, the chain restored is then validated with its manifest.
+    #[clap(long)]
+    pub import_chain: Option<String>,
+    /// Directory where the chain is backed up on schedule.
+    /// 
+    /// Each backup is a chain export with a manifest in a timestamped directory.
+    /// Only the blocks until the stable topoheight are saved.
+    #[clap(long)]
+    pub backup_dir: Option<String>,
+    /// Delay in hours between two automatic backups.
+    #[clap(long, default_value_t = DEFAULT_BACKUP_INTERVAL_HOURS)]
+    pub backup_interval: u64,
+    /// Count of backups kept in the backup directory, the oldest ones are deleted.
+    #[clap(long, default_value_t = DEFAULT_BACKUP_RETENTION)]
+    pub backup_retention: usize
+}
+
+// Summary of a chain rewind
+pub struct RewindSummary {
+    // New topoheight of the chain after the rewind
+    pub topoheight: u64,
+    // How many blocks were removed from the chain
+    pub removed_blocks: u64,
+    // Transactions that were added back in mempool
+    pub txs_reinjected: usize,
+    // Transactions that couldn't be added back in mempool
+    pub txs_orphaned: usize
+}
+
+// Block verified against the chain state at a top block
+// It can be executed without being verified again as long as the chain didn't change
+pub struct VerifiedBlock {
+    block: Block,
+    hash: Hash,
+    pow_hash: Hash,
+    difficulty: Difficulty,
+    p: VarUint,
+    // Top block and topoheight of the chain when it was verified
+    top_hash: Hash,
+    topoheight: u64,
+    start: Instant,
+    timings: BlockTimings
+}
+
+// Details of a block template for the miners
+pub struct BlockTemplateSummary {
+    // Fees of all the selected transactions
+    pub total_fees: u64,
+    // Miner reward (block reward + fees - dev fee) if it's not a side block
+    pub expected_reward: u64,
+    pub txs_count: usize,
+    // Serialized size of the block with its transactions
+    pub size: usize,
+    // Topoheight of the block if it's ordered right after our current topoheight
+    pub topoheight: u64
 }
 
 pub struct Blockchain<S: Storage> {
-    // current block height
-    height: AtomicU64,
-    // current topo height
-    topoheight: AtomicU64,
-    // current stable height
-    stable_height: AtomicU64,
-    // Determine which last block is stable
-    // It is used mostly for chain rewind limit
-    stable_topoheight: AtomicU64,
+    // current top block hash, heights, difficulty and stable height
+    // the stable topoheight is used mostly for chain rewind limit
+    top_state: TopStateCache,
     // mempool to retrieve/add all txs
     mempool: RwLock<Mempool>,
     // storage to retrieve/add blocks
@@ -215,9 +387,6 @@ pub struct Blockchain<S: Storage> {
     p2p: RwLock<Option<Arc<P2pServer<S>>>>,
     // RPC module
     rpc: RwLock<Option<SharedDaemonRpcServer<S>>>,
-    // current difficulty at tips
-    // its used as cache to display current network hashrate
-    difficulty: Mutex<Difficulty>,
     // if a simulator is set
     simulator: Option<Simulator>,
     // if we should skip PoW verification
@@ -232,11 +401,49 @@ pub struct Blockchain<S: Storage> {
     // using base hash, current tip hash and base height, this cache is used to store the DAG order
     full_order_cache: Mutex<LruCache<(Hash, Hash, u64), IndexSet<Hash>>>,
     // auto prune mode if enabled, will delete all blocks every N and keep only N top blocks (topoheight based)
-    auto_prune_keep_n_blocks: Option<u64>
+    auto_prune_keep_n_blocks: Option<u64>,
+    // maximum extra data size of a transaction accepted in mempool
+    mempool_max_tx_extra_data_size: usize,
+    // minimum fee per KB required by our mempool and relay policy
+    min_fee_per_kb: u64,
+    // maximum size in bytes of the mempool before evicting TXs
+    mempool_max_size: usize,
+    // refuse the reorgs going below the stable height
+    deep_reorg_guard: DeepReorgGuard,
+    // save the mempool TXs on disk to reload them at next start
+    mempool_persistence: bool,
+    // persisted TXs not re-validated in time at startup
+    deferred_mempool_txs: Mutex<Vec<(Hash, Arc<Transaction>)>>,
+    // last computed sync state with the network
+    sync_state: SyncState,
+    // mask the peers IPs exposed through RPC
+    hide_peer_ips: bool,
+    // rolling average of the block solve time
+    block_time_tracker: Mutex<BlockTimeTracker>,
+    // time spent in each stage of the blocks processed
+    block_processing_stats: Mutex<BlockProcessingStats>,
+    // log the stages timings of each block processed
+    log_block_timings: bool,
+    // offset of our clock from the time of our peers
+    network_time: Mutex<NetworkTime>,
+    // directory of the backups if enabled
+    backup_dir: Option<PathBuf>,
+    // count of backups kept in the backup directory
+    backup_retention: usize,
+    // delay in milliseconds between the storage writes of a block and the top state update
+    // used by the tests to serve reads while a block is being applied
+    #[cfg(test)]
+    block_application_delay: std::sync::atomic::AtomicU64
 }
 
 impl<S: Storage> Blockchain<S> {
-    pub async fn new(config: Config, network: Network, storage: S) -> Result<Arc<Self>, Error> {
+    pub async fn new(config: Config, network: Network, storage: S, prompt: Option<ShareablePrompt>) -> Result<Arc<Self>, Error> {
+        Self::with_genesis(config, network, storage, prompt, None).await
+    }
+
+    // Same as new but the genesis block is provided for networks without a hardcoded one
+    // It is only used if the chain is not on disk and no chain is imported
+    pub async fn with_genesis(config: Config, network: Network, mut storage: S, prompt: Option<ShareablePrompt>, genesis: Option<Block>) -> Result<Arc<Self>, Error> {
         // Do some checks on config params
         {
             if config.simulator.is_some() && network != Network::Dev {
@@ -263,64 +470,133 @@ impl<S: Storage> Blockchain<S> {
                 return Err(BlockchainError::ConfigSyncMode.into())
             }
 
+            if !config.rpc_tls_bind_address.is_empty() && (config.rpc_tls_cert.is_none() || config.rpc_tls_key.is_none()) {
+                error!("RPC TLS bind addresses require both a certificate and a private key!");
+                return Err(BlockchainError::ConfigRpcTls.into())
+            }
+
             if config.skip_pow_verification {
                 warn!("PoW verification is disabled! This is dangerous in production!");
             }
+
+            if config.mempool_max_tx_extra_data_size > TX_EXTRA_DATA_LIMIT_SIZE {
+                warn!("Mempool extra data size limit is above the consensus limit of {} bytes", TX_EXTRA_DATA_LIMIT_SIZE);
+            }
+
+            if config.min_fee_per_kb < FEE_PER_KB {
+                warn!("Minimum fee per KB is below the protocol minimum of {}, it will be ignored", format_xelis(FEE_PER_KB));
+            }
+
+            if config.allow_deep_reorg {
+                warn!("A reorg below the stable height is allowed once after confirmation!");
+            }
+
+            if config.backup_dir.is_some() && config.backup_interval == 0 {
+                error!("Backup interval must be at least 1 hour!");
+                return Err(BlockchainError::ConfigBackupInterval.into())
+            }
         }
 
-        let on_disk = storage.has_blocks().await;
-        let (height, topoheight) = if on_disk {
-            info!("Reading last metadata available...");
-            let height = storage.get_top_height()?;
-            let topoheight = storage.get_top_topoheight()?;
+        // Complete a rewind interrupted by a crash before reading the chain state
+        if let Some(topoheight) = storage.recover_pending_rewind().await? {
+            info!("Interrupted rewind completed, topoheight is now {}", topoheight);
+        }
 
-            (height, topoheight)
-        } else { (0, 0) };
+        let on_disk = storage.has_blocks().await;
 
         info!("Initializing chain...");
         let blockchain = Self {
-            height: AtomicU64::new(height),
-            topoheight: AtomicU64::new(topoheight),
-            stable_height: AtomicU64::new(0),
-            stable_topoheight: AtomicU64::new(0),
+            // Replaced once the genesis block is added or the state is read from disk
+            top_state: TopStateCache::new(TopState {
+                hash: Hash::zero(),
+                height: 0,
+                topoheight: 0,
+                difficulty: GENESIS_BLOCK_DIFFICULTY,
+                cumulative_difficulty: CumulativeDifficulty::zero(),
+                stable_height: 0,
+                stable_topoheight: 0,
+                pruned_topoheight: None,
+                block: None
+            }),
             mempool: RwLock::new(Mempool::new(network)),
             storage: RwLock::new(storage),
             p2p: RwLock::new(None),
             rpc: RwLock::new(None),
-            difficulty: Mutex::new(GENESIS_BLOCK_DIFFICULTY),
             skip_pow_verification: config.skip_pow_verification || config.simulator.is_some(),
             simulator: config.simulator,
             network,
             tip_base_cache: Mutex::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
             tip_work_score_cache: Mutex::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
             full_order_cache: Mutex::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
-            auto_prune_keep_n_blocks: config.auto_prune_keep_n_blocks
+            auto_prune_keep_n_blocks: config.auto_prune_keep_n_blocks,
+            mempool_max_tx_extra_data_size: config.mempool_max_tx_extra_data_size,
+            min_fee_per_kb: config.min_fee_per_kb.max(FEE_PER_KB),
+            mempool_max_size: config.mempool_max_size,
+            deep_reorg_guard: DeepReorgGuard::new(config.allow_deep_reorg, prompt),
+            mempool_persistence: !config.no_mempool_persistence,
+            deferred_mempool_txs: Mutex::new(Vec::new()),
+            sync_state: SyncState::new(),
+            hide_peer_ips: config.rpc_hide_peer_ips,
+            block_time_tracker: Mutex::new(BlockTimeTracker::new()),
+            block_processing_stats: Mutex::new(BlockProcessingStats::new()),
+            log_block_timings: config.log_block_timings,
+            network_time: Mutex::new(NetworkTime::new()),
+            backup_dir: config.backup_dir.map(PathBuf::from),
+            backup_retention: config.backup_retention,
+            #[cfg(test)]
+            block_application_delay: std::sync::atomic::AtomicU64::new(0)
+        };
+
+        // A backup directory is imported from its chain export and validated with its manifest
+        let import: Option<(String, Option<BackupManifest>)> = match config.import_chain.as_ref() {
+            Some(path) => Some(backup::resolve_import_path(path)?),
+            None => None
         };
 
         // include genesis block
         if !on_disk {
-            blockchain.create_genesis_block().await?;
+            // Networks without hardcoded genesis block use the one of the chain imported
+            let genesis = match import.as_ref() {
+                Some((path, _)) => Some(bootstrap::read_genesis_block(path, &network)?),
+                None => genesis
+            };
+            blockchain.create_genesis_block(genesis).await?;
         } else {
-            debug!("Retrieving tips for computing current difficulty");
+            info!("Reading last metadata available...");
             let storage = blockchain.get_storage().read().await;
-            let tips_set = storage.get_tips().await?;
-            let (difficulty, _) = blockchain.get_difficulty_at_tips(&*storage, tips_set.iter()).await?;
-            blockchain.set_difficulty(difficulty).await;
+            let top_state = blockchain.build_top_state(&*storage).await?;
+            blockchain.top_state.set(top_state);
         }
 
-        // now compute the stable height
+        if blockchain.mempool_persistence {
+            blockchain.load_persisted_mempool().await?;
+        } else {
+            // Don't reload stale TXs from a previous run with the persistence enabled
+            blockchain.get_storage().read().await.clear_mempool_txs()?;
+        }
+
+        // Initial sync state, no event is sent for it
         {
-            debug!("Retrieving tips for computing current stable height");
             let storage = blockchain.get_storage().read().await;
-            let tips = storage.get_tips().await?;
-            let (stable_hash, stable_height) = blockchain.find_common_base::<S, _>(&storage, &tips).await?;
-            blockchain.stable_height.store(stable_height, Ordering::SeqCst);
-            // Search the stable topoheight
-            let stable_topoheight = storage.get_topo_height_for_hash(&stable_hash).await?;
-            blockchain.stable_topoheight.store(stable_topoheight, Ordering::SeqCst);
+            blockchain.refresh_sync_state(&storage).await;
+            blockchain.load_block_time_tracker(&storage).await?;
         }
 
         let arc = Arc::new(blockchain);
+        if let Some((path, manifest)) = import {
+            bootstrap::import_chain(&arc, &path).await?;
+            if let Some(manifest) = manifest {
+                manifest.verify_restore(&arc).await?;
+                info!("Backup restored until topoheight {}", manifest.topoheight);
+            }
+        }
+
+        if let Some(backup_dir) = arc.backup_dir.clone() {
+            info!("Chain will be backed up every {} hours to {}", config.backup_interval, backup_dir.display());
+            let interval = Duration::from_secs(config.backup_interval * 60 * 60);
+            backup::start_backup_task(Arc::downgrade(&arc), backup_dir, interval, arc.backup_retention);
+        }
+
         // create P2P Server
         if !config.disable_p2p_server {
             info!("Starting P2p server...");
@@ -337,7 +613,23 @@ impl<S: Storage> Blockchain<S> {
                 exclusive_nodes.push(addr);
             }
 
-            match P2pServer::new(config.p2p_concurrency_task_count_limit, config.dir_path, config.tag, config.max_peers, config.p2p_bind_address, Arc::clone(&arc), exclusive_nodes.is_empty(), exclusive_nodes, config.allow_fast_sync, config.allow_boost_sync, config.max_chain_response_size, !config.disable_ip_sharing, config.disable_p2p_outgoing_connections) {
+            // setup whitelisted peers
+            let mut whitelist: Vec<SocketAddr> = Vec::with_capacity(config.p2p_whitelist.len());
+            for peer in config.p2p_whitelist {
+                let addr: SocketAddr = match peer.parse() {
+                    Ok(addr) => addr,
+                    Err(e) => match peer.parse::<IpAddr>() {
+                        Ok(ip) => SocketAddr::new(ip, DEFAULT_P2P_PORT),
+                        Err(_) => {
+                            error!("Error while parsing whitelisted peer address: {}", e);
+                            continue;
+                        }
+                    }
+                };
+                whitelist.push(addr);
+            }
+
+            match P2pServer::new(config.p2p_concurrency_task_count_limit, config.dir_path, config.tag, config.max_peers, config.p2p_bind_address, Arc::clone(&arc), exclusive_nodes.is_empty() && whitelist.is_empty(), exclusive_nodes, whitelist, config.allow_fast_sync, config.allow_boost_sync, config.max_chain_response_size, !config.disable_ip_sharing, config.disable_p2p_outgoing_connections, !config.disable_p2p_mempool_sync, !config.disable_p2p_compact_blocks, config.p2p_share_topology, !config.disable_p2p_peer_eviction, !config.disable_p2p_sync_pipelining) {
                 Ok(p2p) => {
                     // connect to priority nodes
                     for addr in config.priority_nodes {
@@ -370,8 +662,20 @@ impl<S: Storage> Blockchain<S> {
 
         // create RPC Server
         if !config.disable_rpc_server {
-            info!("RPC Server will listen on: {}", config.rpc_bind_address);
-            match DaemonRpcServer::new(config.rpc_bind_address, Arc::clone(&arc), config.disable_getwork_server).await {
+            info!("RPC Server will listen on: {}", config.rpc_bind_address.join(", "));
+            // Certificate files are only required when HTTPS listeners are configured
+            let tls = match (config.rpc_tls_cert, config.rpc_tls_key) {
+                (Some(cert), Some(key)) if !config.rpc_tls_bind_address.is_empty() => {
+                    info!("RPC Server will listen with TLS on: {}", config.rpc_tls_bind_address.join(", "));
+                    Some((config.rpc_tls_bind_address, cert, key))
+                },
+                _ => None
+            };
+            let admin = config.rpc_admin_password.map(|password| AdminAuthConfig {
+                username: config.rpc_admin_username,
+                password
+            });
+            match DaemonRpcServer::new(config.rpc_bind_address, tls, Arc::clone(&arc), config.disable_getwork_server, admin).await {
                 Ok(server) => *arc.rpc.write().await = Some(server),
                 Err(e) => error!("Error while starting RPC server: {}", e)
             };
@@ -389,6 +693,64 @@ impl<S: Storage> Blockchain<S> {
         Ok(arc)
     }
 
+    // Last computed sync state of our chain with the network
+    pub fn is_synced(&self) -> bool {
+        self.sync_state.is_synced()
+    }
+
+    // Compute the sync state of our chain with the network
+    // Returns if we are synced and the event to notify if we just became synced
+    async fn compute_sync_state(&self, storage: &S) -> Result<(bool, Option<SyncCompletedEvent>), BlockchainError> {
+        let topoheight = self.get_topo_height();
+        let top_hash = storage.get_hash_at_topo_height(topoheight).await?;
+        let top_block_timestamp = storage.get_timestamp_for_block_hash(&top_hash).await?;
+        let best_peer_topoheight = match self.p2p.read().await.as_ref() {
+            Some(p2p) if p2p.get_peer_count().await > 0 => Some(p2p.get_best_topoheight().await),
+            _ => None
+        };
+
+        let synced = is_synced(top_block_timestamp, get_current_time_in_millis(), topoheight, best_peer_topoheight);
+        let event = if self.sync_state.update(synced) {
+            info!("Sync completed at topoheight {}", topoheight);
+            Some(SyncCompletedEvent {
+                topoheight,
+                best_peer_topoheight
+            })
+        } else {
+            None
+        };
+
+        Ok((synced, event))
+    }
+
+    // Same as compute_sync_state but keep the last state known in case of error
+    async fn refresh_sync_state(&self, storage: &S) -> (bool, Option<SyncCompletedEvent>) {
+        match self.compute_sync_state(storage).await {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("Error while computing the sync state: {}", e);
+                (self.is_synced(), None)
+            }
+        }
+    }
+
+    // Compute if our chain is synced with the network and notify the RPC clients if we just became synced
+    pub async fn is_synced_for_storage(&self, storage: &S) -> bool {
+        let (synced, event) = self.refresh_sync_state(storage).await;
+        if let Some(event) = event {
+            if let Some(rpc) = self.rpc.read().await.as_ref() {
+                if rpc.is_event_tracked(&NotifyEvent::SyncCompleted).await {
+                    let rpc = rpc.clone();
+                    spawn_task("rpc-notify-sync-completed", async move {
+                        rpc.notify_clients_with(&NotifyEvent::SyncCompleted, event).await;
+                    });
+                }
+            }
+        }
+
+        synced
+    }
+
     // Detect if the simulator task has been started
     pub fn is_simulator_enabled(&self) -> bool {
         self.simulator.is_some()
@@ -438,35 +800,134 @@ impl<S: Storage> Blockchain<S> {
     pub async fn reload_from_disk(&self) -> Result<(), BlockchainError> {
         trace!("Reloading chain from disk");
         let storage = self.storage.write().await;
+        let top_state = self.build_top_state(&*storage).await?;
+        self.top_state.set(top_state);
+
+        // TXs in mempool may be outdated, clear them as they will be asked later again
+        debug!("locking mempool for cleaning");
+        let mut mempool = self.mempool.write().await;
+        debug!("Clearing mempool");
+        mempool.clear();
+        self.deferred_mempool_txs.lock().await.clear();
+        if self.mempool_persistence {
+            storage.clear_mempool_txs()?;
+        }
+
+        Ok(())
+    }
+
+    // Read the whole top state from the storage
+    // It is only done at startup and when the chain is reloaded, it is then kept up to date in cache
+    async fn build_top_state(&self, storage: &S) -> Result<TopState, BlockchainError> {
         let topoheight = storage.get_top_topoheight()?;
         let height = storage.get_top_height()?;
-        self.topoheight.store(topoheight, Ordering::SeqCst);
-        self.height.store(height, Ordering::SeqCst);
+        let hash = storage.get_hash_at_topo_height(topoheight).await?;
+        let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(&hash).await?;
 
         let tips = storage.get_tips().await?;
-        // Research stable height to update caches
-        let (stable_hash, stable_height) = self.find_common_base(&*storage, &tips).await?;
-        self.stable_height.store(stable_height, Ordering::SeqCst);
-
-        // Research stable topoheight also
+        let (difficulty, _) = self.get_difficulty_at_tips(storage, tips.iter()).await?;
+        let (stable_hash, stable_height) = self.find_common_base::<S, _>(storage, &tips).await?;
         let stable_topoheight = storage.get_topo_height_for_hash(&stable_hash).await?;
-        self.stable_topoheight.store(stable_topoheight, Ordering::SeqCst);
 
-        // Recompute the difficulty with new tips
-        let (difficulty, _) = self.get_difficulty_at_tips(&*storage, tips.iter()).await?;
-        self.set_difficulty(difficulty).await;
+        let block = self.build_block_summary(storage, &hash).await?;
+        let pruned_topoheight = storage.get_pruned_topoheight().await?;
+
+        Ok(TopState {
+            hash,
+            height,
+            topoheight,
+            difficulty,
+            cumulative_difficulty,
+            stable_height,
+            stable_topoheight,
+            pruned_topoheight,
+            block: Some(block)
+        })
+    }
 
-        // TXs in mempool may be outdated, clear them as they will be asked later again
-        debug!("locking mempool for cleaning");
-        let mut mempool = self.mempool.write().await;
-        debug!("Clearing mempool");
-        mempool.clear();
+    // Build the summary of a block, it is kept in cache for the top block
+    pub async fn build_block_summary(&self, storage: &S, hash: &Hash) -> Result<BlockSummary, BlockchainError> {
+        let header = storage.get_block_header_by_hash(hash).await?;
+        let (topoheight, reward, total_fees) = if storage.is_block_topological_ordered(hash).await {
+            let topoheight = storage.get_topo_height_for_hash(hash).await?;
+            (Some(topoheight), Some(storage.get_block_reward_at_topo_height(topoheight)?), storage.get_block_fees_at_topo_height(topoheight)?)
+        } else {
+            (None, None, 0)
+        };
+
+        let mut size = header.size();
+        for tx_hash in header.get_txs_hashes() {
+            size += storage.get_transaction_size(tx_hash).await?;
+        }
+
+        Ok(BlockSummary {
+            hash: hash.clone(),
+            height: header.get_height(),
+            topoheight,
+            timestamp: header.get_timestamp().into(),
+            miner: header.get_miner().as_address(self.network.is_mainnet()),
+            reward,
+            txs_count: header.get_txs_count(),
+            total_fees,
+            size,
+            difficulty: storage.get_difficulty_for_block_hash(hash).await?
+        })
+    }
+
+    // Reload the persisted mempool and re-validate each TX against the current chain state
+    // Re-validation is bounded in time, the remaining TXs are validated on the first access to the mempool
+    async fn load_persisted_mempool(&self) -> Result<(), BlockchainError> {
+        let storage = self.storage.read().await;
+        let mut txs = storage.get_mempool_txs().await?;
+        if txs.is_empty() {
+            return Ok(())
+        }
+
+        info!("Reloading {} persisted transactions in mempool...", txs.len());
+        // TXs from the same sender must be added in nonce order
+        txs.sort_by_key(|(_, tx)| tx.get_nonce());
+
+        let deadline = Instant::now() + Duration::from_secs(MEMPOOL_RELOAD_TIMEOUT_SECS);
+        let storage: &S = &storage;
+        let (summary, deferred) = reload_persisted_txs(txs, Some(deadline), move |(hash, tx)| self.readmit_persisted_tx(storage, hash, tx)).await;
+        info!("Persisted mempool reloaded: {} admitted, {} stale dropped, {} deferred", summary.admitted, summary.purged, summary.deferred);
+
+        *self.deferred_mempool_txs.lock().await = deferred;
 
         Ok(())
     }
 
+    // Validate the persisted TXs that couldn't be re-validated in time at startup
+    pub async fn validate_deferred_mempool_txs(&self, storage: &S) {
+        let mut deferred = self.deferred_mempool_txs.lock().await;
+        if deferred.is_empty() {
+            return
+        }
+
+        let txs = mem::take(&mut *deferred);
+        let (summary, _) = reload_persisted_txs(txs, None, move |(hash, tx)| self.readmit_persisted_tx(storage, hash, tx)).await;
+        info!("Deferred persisted transactions validated: {} admitted, {} stale dropped", summary.admitted, summary.purged);
+    }
+
+    // Add back a persisted TX in mempool, it is deleted from disk if not valid anymore
+    async fn readmit_persisted_tx(&self, storage: &S, hash: Hash, tx: Arc<Transaction>) -> bool {
+        match self.add_tx_to_mempool_with_storage_and_hash(storage, tx, hash.clone(), false).await {
+            Ok(()) => true,
+            // Received again from a peer in the meantime
+            Err(BlockchainError::TxAlreadyInMempool(_)) => true,
+            Err(e) => {
+                debug!("Persisted TX {} is not valid anymore: {}", hash, e);
+                if let Err(e) = storage.delete_mempool_tx(&hash) {
+                    warn!("Error while deleting persisted TX {}: {}", hash, e);
+                }
+                false
+            }
+        }
+    }
+
     // function to include the genesis block and register the public dev key.
-    async fn create_genesis_block(&self) -> Result<(), BlockchainError> {
+    // If no genesis block is hardcoded for the network, the one provided is used instead of generating it
+    async fn create_genesis_block(&self, genesis: Option<Block>) -> Result<(), BlockchainError> {
         let mut storage = self.storage.write().await;
 
         // register XELIS asset
@@ -488,6 +949,10 @@ impl<S: Storage> Blockchain<S> {
             }
 
             (genesis, expected_hash)
+        } else if let Some(genesis) = genesis {
+            let genesis_hash = genesis.hash();
+            info!("Using genesis block {} provided", genesis_hash);
+            (genesis, genesis_hash)
         } else {
             warn!("No genesis block found!");
             info!("Generating a new genesis block...");
@@ -590,6 +1055,7 @@ impl<S: Storage> Blockchain<S> {
 
             // Update the pruned topoheight
             storage.set_pruned_topoheight(located_sync_topoheight).await?;
+            self.top_state.set_pruned_topoheight(Some(located_sync_topoheight));
             Ok(located_sync_topoheight)
         } else {
             debug!("located_sync_topoheight <= topoheight, no pruning needed");
@@ -618,25 +1084,58 @@ impl<S: Storage> Blockchain<S> {
 
     // returns the highest (unstable) height on the chain
     pub fn get_height(&self) -> u64 {
-        self.height.load(Ordering::Acquire)
+        self.top_state.get_height()
     }
 
     // returns the highest topological height
     pub fn get_topo_height(&self) -> u64 {
-        self.topoheight.load(Ordering::Acquire)
+        self.top_state.get_topoheight()
     }
 
     // Get the current block height stable
     // No blocks can be added at or below this height
     pub fn get_stable_height(&self) -> u64 {
-        self.stable_height.load(Ordering::Acquire)
+        self.top_state.get_stable_height()
+    }
+
+    // Get the guard refusing the reorgs below the stable height
+    pub fn get_deep_reorg_guard(&self) -> &DeepReorgGuard {
+        &self.deep_reorg_guard
+    }
+
+    // Directory of the backups if configured
+    pub fn get_backup_dir(&self) -> Option<&PathBuf> {
+        self.backup_dir.as_ref()
+    }
+
+    // Count of backups kept in the backup directory
+    pub fn get_backup_retention(&self) -> usize {
+        self.backup_retention
     }
 
     // Get the stable topoheight
     // It is used to determine at which DAG topological height
     // the block is in case of rewind
     pub fn get_stable_topoheight(&self) -> u64 {
-        self.stable_topoheight.load(Ordering::Acquire)
+        self.top_state.get_stable_topoheight()
+    }
+
+    // Get a consistent copy of the whole top state
+    // No storage read is done, all fields are updated together
+    pub fn get_top_state(&self) -> TopState {
+        self.top_state.get()
+    }
+
+    // Consistent view of the storage and the top state
+    // It waits for the block being applied, if any
+    pub async fn get_chain_view(&self) -> ChainStateView<'_, S> {
+        let storage = self.storage.read().await;
+        ChainStateView::new(storage, self.top_state.get())
+    }
+
+    // Summary of the top block served from cache
+    pub fn get_top_block_summary(&self) -> Option<BlockSummary> {
+        self.top_state.get_block_summary()
     }
 
     // Get the network on which this chain is running
@@ -651,20 +1150,22 @@ impl<S: Storage> Blockchain<S> {
 
     // Get the count of transactions available in the mempool
     pub async fn get_mempool_size(&self) -> usize {
+        {
+            let storage = self.storage.read().await;
+            self.validate_deferred_mempool_txs(&*storage).await;
+        }
         self.mempool.read().await.size()
     }
 
     // Get the current top block hash in chain
     pub async fn get_top_block_hash(&self) -> Result<Hash, BlockchainError> {
-        let storage = self.storage.read().await;
-        self.get_top_block_hash_for_storage(&storage).await
+        Ok(self.top_state.get_hash())
     }
 
-    // because we are in chain, we already now the highest topoheight
-    // we call the get_hash_at_topo_height instead of get_top_block_hash to avoid reading value
-    // that we already know
-    pub async fn get_top_block_hash_for_storage(&self, storage: &S) -> Result<Hash, BlockchainError> {
-        storage.get_hash_at_topo_height(self.get_topo_height()).await
+    // The top block hash is cached, the storage is not read
+    // It is kept for the callers already holding the storage lock
+    pub async fn get_top_block_hash_for_storage(&self, _: &S) -> Result<Hash, BlockchainError> {
+        Ok(self.top_state.get_hash())
     }
 
     // Verify if we have the current block in storage by locking it ourself
@@ -1162,13 +1663,10 @@ impl<S: Storage> Blockchain<S> {
 
     // confirms whether the actual tip difficulty is withing 9% deviation with best tip (reference)
     async fn validate_tips<P: DifficultyProvider>(&self, provider: &P, best_tip: &Hash, tip: &Hash) -> Result<bool, BlockchainError> {
-        const MAX_DEVIATION: Difficulty = Difficulty::from_u64(91);
-        const PERCENTAGE: Difficulty = Difficulty::from_u64(100);
-
         let best_difficulty = provider.get_difficulty_for_block_hash(best_tip).await?;
         let block_difficulty = provider.get_difficulty_for_block_hash(tip).await?;
 
-        Ok(best_difficulty * MAX_DEVIATION / PERCENTAGE < block_difficulty)
+        Ok(blockdag::is_tip_difficulty_valid(best_difficulty, block_difficulty))
     }
 
     // Get difficulty at tips
@@ -1212,15 +1710,9 @@ impl<S: Storage> Blockchain<S> {
         Ok((difficulty, p_new))
     }
 
-    // Store the difficulty cache for the latest block
-    async fn set_difficulty(&self, difficulty: Difficulty) {
-        let mut lock = self.difficulty.lock().await;
-        *lock = difficulty;
-    }
-
     // Get the current difficulty target for the next block
     pub async fn get_difficulty(&self) -> Difficulty {
-        *self.difficulty.lock().await
+        self.top_state.get_difficulty()
     }
 
     // pass in params the already computed block hash and its tips
@@ -1247,6 +1739,70 @@ impl<S: Storage> Blockchain<S> {
         &self.p2p
     }
 
+    // Are the peers IPs masked in RPC responses
+    pub fn hide_peer_ips(&self) -> bool {
+        self.hide_peer_ips
+    }
+
+    // Rolling average block time and if it drifts from the target
+    pub async fn get_block_time_drift(&self) -> BlockTimeDrift {
+        self.block_time_tracker.lock().await.get_drift()
+    }
+
+    // Time spent in each stage of the blocks processed since the start
+    pub async fn get_block_processing_stats(&self) -> GetBlockProcessingStatsResult {
+        self.block_processing_stats.lock().await.get_stats()
+    }
+
+    // Add the time sent by a peer to compute the network time
+    pub async fn add_peer_time_sample(&self, peer_id: u64, peer_time: TimestampSeconds) {
+        self.network_time.lock().await.add_sample(peer_id, peer_time, get_current_time_in_seconds());
+    }
+
+    // Stop using the time of a peer once it is disconnected
+    pub async fn remove_peer_time_sample(&self, peer_id: u64) {
+        self.network_time.lock().await.remove_sample(peer_id);
+    }
+
+    #[cfg(test)]
+    pub fn set_block_application_delay(&self, delay: Duration) {
+        self.block_application_delay.store(delay.as_millis() as u64, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn has_peer_time_sample(&self, peer_id: u64) -> bool {
+        self.network_time.lock().await.has_sample(peer_id)
+    }
+
+    // Offset in milliseconds applied to our clock and if our clock deviates more than the cap
+    pub async fn get_network_time_offset(&self) -> (i64, bool) {
+        let network_time = self.network_time.lock().await;
+        (network_time.get_offset(), network_time.is_skewed())
+    }
+
+    // Our time adjusted with the median offset of our peers
+    // It is used to validate the block timestamps and to build the block templates
+    pub async fn get_network_time_in_millis(&self) -> u64 {
+        self.network_time.lock().await.adjust(get_current_time_in_millis())
+    }
+
+    // Fill the block time tracker with the last blocks ordered
+    // Its previous content is replaced, so it is also used once blocks are popped
+    async fn load_block_time_tracker(&self, storage: &S) -> Result<(), BlockchainError> {
+        let topoheight = self.get_topo_height();
+        // Genesis block timestamp isn't take in count
+        let pruned_topoheight = storage.get_pruned_topoheight().await?.unwrap_or(0);
+        let start = topoheight.saturating_sub(BLOCK_TIME_DRIFT_WINDOW as u64 - 1).max(pruned_topoheight).max(1);
+
+        let mut tracker = self.block_time_tracker.lock().await;
+        *tracker = BlockTimeTracker::new();
+        for topoheight in start..=topoheight {
+            let hash = storage.get_hash_at_topo_height(topoheight).await?;
+            tracker.record(storage.get_timestamp_for_block_hash(&hash).await?);
+        }
+        Ok(())
+    }
+
     // Returns the RPC server used for blockchain if enabled
     pub fn get_rpc(&self) -> &RwLock<Option<SharedDaemonRpcServer<S>>> {
         &self.rpc
@@ -1271,9 +1827,31 @@ impl<S: Storage> Blockchain<S> {
     // Add a tx to the mempool with the given hash, it is not computed and the TX is transformed into an Arc
     pub async fn add_tx_to_mempool_with_hash<'a>(&'a self, tx: Transaction, hash: Hash, broadcast: bool) -> Result<(), BlockchainError> {
         let storage = self.storage.read().await;
+        // Deferred TXs must be added first to respect the nonces order
+        self.validate_deferred_mempool_txs(&*storage).await;
         self.add_tx_to_mempool_with_storage_and_hash(&*storage, Arc::new(tx), hash, broadcast).await
     }
 
+    // Simulate the execution of a tx against the current chain state
+    // All changes are kept in the overlay, nothing is written to the storage and the mempool is not touched
+    pub async fn simulate_tx_with_storage<'a>(&self, storage: &'a S, tx: &'a Transaction, hash: &Hash, state: &mut StateOverlay<'a, S>) -> Result<(), BlockchainError> {
+        let tx_size = tx.size();
+        if tx_size > MAX_TRANSACTION_SIZE {
+            return Err(BlockchainError::TxTooBig(tx_size, MAX_TRANSACTION_SIZE))
+        }
+
+        if self.mempool.read().await.contains_tx(hash) {
+            return Err(BlockchainError::TxAlreadyInMempool(hash.clone()))
+        }
+
+        if storage.is_tx_executed_in_a_block(hash)? {
+            return Err(BlockchainError::TxAlreadyInBlockchain(hash.clone()))
+        }
+
+        tx.verify(state).await?;
+        Ok(())
+    }
+
     // Add a tx to the mempool with the given hash, it will verify the TX and check that it is not already in mempool or in blockchain
     // and its validity (nonce, balance, etc...)
     pub async fn add_tx_to_mempool_with_storage_and_hash<'a>(&'a self, storage: &S, tx: Arc<Transaction>, hash: Hash, broadcast: bool) -> Result<(), BlockchainError> {
@@ -1282,8 +1860,54 @@ impl<S: Storage> Blockchain<S> {
             return Err(BlockchainError::TxTooBig(tx_size, MAX_TRANSACTION_SIZE))
         }
 
+        // It must be includable in the next block
+        verify_tx_type_activated(&self.network, self.get_height() + 1, &hash, &tx)?;
+        verify_tx_public_keys(&self.network, self.get_height() + 1, &hash, &tx)?;
+
+        if let Some(extra_data) = tx.get_extra_data() {
+            if extra_data.len() > self.mempool_max_tx_extra_data_size {
+                debug!("TX {} extra data is above our mempool limit", hash);
+                return Err(BlockchainError::TxExtraDataAboveMempoolLimit(extra_data.len(), self.mempool_max_tx_extra_data_size))
+            }
+        }
+
+        // The protocol minimum is verified with the TX itself
+        if self.min_fee_per_kb > FEE_PER_KB {
+            let required_fees = estimate_required_tx_fees_with_fee_per_kb(storage, self.get_topo_height(), &tx, self.min_fee_per_kb).await?;
+            if required_fees > tx.get_fee() {
+                debug!("TX {} fees are below our mempool policy: {} required, {} provided", hash, format_xelis(required_fees), format_xelis(tx.get_fee()));
+                return Err(BlockchainError::TxFeeTooLow(required_fees, tx.get_fee(), self.min_fee_per_kb))
+            }
+        }
+
+        // Verify the proofs with a read lock only, so the TXs of different senders are verified in parallel
+        // A replacement is verified with the following TXs of its sender while holding the write lock
+        let verified = {
+            let mempool = self.mempool.read().await;
+            let is_replacement = mempool.get_cache_for(tx.get_source())
+                .is_some_and(|cache| cache.has_tx_with_same_nonce(tx.get_nonce()).is_some());
+
+            if is_replacement || mempool.contains_tx(&hash) {
+                None
+            } else {
+                Some(mempool.verify_tx(storage, self.get_topo_height(), &tx).await?)
+            }
+        };
+
+        // TX replaced by this one in mempool
+        let mut replaced = None;
         {
             let mut mempool = self.mempool.write().await;
+
+            // A full mempool only accepts a TX paying more per byte than the next evicted one
+            if mempool.get_total_size() + tx_size > self.mempool_max_size {
+                if let Some(required_fees) = mempool.get_eviction_fee_threshold(tx_size) {
+                    if required_fees > tx.get_fee() {
+                        debug!("Mempool is full, TX {} fees are below the eviction threshold of {}", hash, format_xelis(required_fees));
+                        return Err(BlockchainError::TxFeeTooLow(required_fees, tx.get_fee(), self.min_fee_per_kb))
+                    }
+                }
+            }
     
             if mempool.contains_tx(&hash) {
                 return Err(BlockchainError::TxAlreadyInMempool(hash))
@@ -1297,24 +1921,59 @@ impl<S: Storage> Blockchain<S> {
             let current_topoheight = self.get_topo_height();
             // get the highest nonce available
             // if presents, it means we have at least one tx from this owner in mempool
+            let mut is_replacement = false;
             if let Some(cache) = mempool.get_cache_for(tx.get_source()) {
-                // we accept to delete a tx from mempool if the new one has a higher fee
-                if let Some(hash) = cache.has_tx_with_same_nonce(tx.get_nonce()) {
-                    // A TX with the same nonce is already in mempool
-                    return Err(BlockchainError::TxNonceAlreadyUsed(tx.get_nonce(), hash.as_ref().clone()))
-                }
+                // we accept to replace a tx from mempool if the new one has a higher fee
+                is_replacement = cache.has_tx_with_same_nonce(tx.get_nonce()).is_some();
 
                 // check that the nonce is in the range
-                if !(tx.get_nonce() <= cache.get_max() + 1 && tx.get_nonce() >= cache.get_min()) {
+                if !is_replacement && !(tx.get_nonce() <= cache.get_max() + 1 && tx.get_nonce() >= cache.get_min()) {
                     debug!("TX {} nonce is not in the range of the pending TXs for this owner, received: {}, expected between {} and {}", hash, tx.get_nonce(), cache.get_min(), cache.get_max());
                     return Err(BlockchainError::InvalidTxNonceMempoolCache(tx.get_nonce(), cache.get_min(), cache.get_max()))
                 }
             }
 
-            mempool.add_tx(storage, current_topoheight, hash.clone(), tx.clone(), tx_size).await?;
+            let removed = if is_replacement {
+                let removed = mempool.replace_tx(storage, current_topoheight, hash.clone(), tx.clone(), tx_size).await?;
+                if let Some((replaced_hash, _)) = removed.first() {
+                    debug!("TX {} replaced TX {} with nonce {} in mempool", hash, replaced_hash, tx.get_nonce());
+                    replaced = Some(Arc::clone(replaced_hash));
+                }
+                removed
+            } else {
+                mempool.add_tx(storage, current_topoheight, hash.clone(), tx.clone(), tx_size, verified).await?;
+                Vec::new()
+            };
+
+            let evicted = if mempool.get_total_size() > self.mempool_max_size {
+                mempool.evict_lowest_fee_txs(storage, current_topoheight, self.mempool_max_size).await
+            } else {
+                Vec::new()
+            };
+
+            if self.mempool_persistence {
+                for (tx_hash, _) in removed.iter().chain(evicted.iter()) {
+                    if let Err(e) = storage.delete_mempool_tx(tx_hash) {
+                        warn!("Error while deleting persisted TX {}: {}", tx_hash, e);
+                    }
+                }
+            }
+
+            // Our TX didn't make it, don't persist or relay it
+            if evicted.iter().any(|(tx_hash, _)| **tx_hash == hash) {
+                let required_fees = mempool.get_eviction_fee_threshold(tx_size).unwrap_or(tx.get_fee() + 1);
+                return Err(BlockchainError::TxFeeTooLow(required_fees, tx.get_fee(), self.min_fee_per_kb))
+            }
+
+            if self.mempool_persistence {
+                if let Err(e) = storage.save_mempool_tx(&hash, &tx) {
+                    warn!("Error while persisting TX {} of mempool: {}", hash, e);
+                }
+            }
         }
 
-        if broadcast {
+        // TXs are not relayed while we are syncing
+        if broadcast && self.is_synced_for_storage(storage).await {
             // P2p broadcast to others peers
             if let Some(p2p) = self.p2p.read().await.as_ref() {
                 let p2p = p2p.clone();
@@ -1336,13 +1995,34 @@ impl<S: Storage> Blockchain<S> {
                     });
                 }
 
+                if let Some(replaced) = replaced {
+                    if rpc.is_event_tracked(&NotifyEvent::TransactionReplaced).await {
+                        let data = TransactionReplacedEvent {
+                            address: tx.get_source().as_address(storage.is_mainnet()),
+                            nonce: tx.get_nonce(),
+                            replaced_tx: Cow::Borrowed(&replaced),
+                            replacement_tx: Cow::Borrowed(&hash)
+                        };
+                        let json = json!(data);
+
+                        let rpc = rpc.clone();
+                        spawn_task("rpc-notify-tx-replaced", async move {
+                            if let Err(e) = rpc.notify_clients(&NotifyEvent::TransactionReplaced, json).await {
+                                debug!("Error while broadcasting event TransactionReplaced to websocket: {}", e);
+                            }
+                        });
+                    }
+                }
+
                 if rpc.is_event_tracked(&NotifyEvent::TransactionAddedInMempool).await {
                     let data = RPCTransaction::from_tx(&tx, &hash, storage.is_mainnet());
+                    let first_seen = get_current_time_in_seconds();
                     let data: TransactionResponse<'_> = TransactionResponse {
                         blocks: None,
                         executed_in_block: None,
                         in_mempool: true,
-                        first_seen: Some(get_current_time_in_seconds()),
+                        first_seen: Some(first_seen),
+                        first_seen_human: Some(format_timestamp_seconds(first_seen)),
                         data,
                     };
                     let json = json!(data);
@@ -1360,14 +2040,19 @@ impl<S: Storage> Blockchain<S> {
         Ok(())
     }
 
-    // this will be used in future for hard fork versions
-    pub fn get_version_at_height(&self, _height: u64) -> u8 {
-        0
+    // Block version expected at this height, each hard fork increments it
+    pub fn get_version_at_height(&self, height: u64) -> u8 {
+        if height >= get_txs_merkle_root_activation_height(&self.network) {
+            TXS_MERKLE_ROOT_VERSION
+        } else {
+            0
+        }
     }
 
     // Get a block template for the new block work (mining)
     pub async fn get_block_template(&self, address: PublicKey) -> Result<BlockHeader, BlockchainError> {
         let storage = self.storage.read().await;
+        self.validate_deferred_mempool_txs(&*storage).await;
         self.get_block_template_for_storage(&storage, address).await
     }
 
@@ -1411,54 +2096,42 @@ impl<S: Storage> Blockchain<S> {
         self.get_block_header_template_for_storage(&storage, address).await
     }
 
+    // Get all the current tips with the metadata needed to select them for a new block
+    pub async fn get_tips_candidates(&self, storage: &S) -> Result<Vec<TipCandidate>, BlockchainError> {
+        let tips = storage.get_tips().await?;
+        let mut candidates = Vec::with_capacity(tips.len());
+        for hash in tips {
+            trace!("Tip found from storage: {}", hash);
+            let height = storage.get_height_for_block_hash(&hash).await?;
+            let difficulty = storage.get_difficulty_for_block_hash(&hash).await?;
+            let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(&hash).await?;
+            let distance_from_mainchain = self.calculate_distance_from_mainchain(storage, &hash).await?;
+            candidates.push(TipCandidate {
+                hash,
+                height,
+                difficulty,
+                cumulative_difficulty,
+                distance_from_mainchain
+            });
+        }
+
+        Ok(candidates)
+    }
+
     // Generate a block header template without transactions
     pub async fn get_block_header_template_for_storage(&self, storage: &S, address: PublicKey) -> Result<BlockHeader, BlockchainError> {
         trace!("get block header template");
         let extra_nonce: [u8; EXTRA_NONCE_SIZE] = rand::thread_rng().gen::<[u8; EXTRA_NONCE_SIZE]>(); // generate random bytes
-        let tips_set = storage.get_tips().await?;
-        let mut tips = Vec::with_capacity(tips_set.len());
-        for hash in tips_set {
-            trace!("Tip found from storage: {}", hash);
-            tips.push(hash);
+        let candidates = self.get_tips_candidates(storage).await?;
+        let sorted_tips = blockdag::select_tips(&candidates, self.get_height());
+        if sorted_tips.is_empty() {
+            return Err(BlockchainError::ExpectedTips)
         }
+        debug!("Tips selected for this block template: {}", sorted_tips.iter().map(|h| h.to_string()).collect::<Vec<String>>().join(", "));
 
-        let current_height = self.get_height();
-        if tips.len() > 1 {
-            let best_tip = blockdag::find_best_tip_by_cumulative_difficulty(storage, tips.iter()).await?.clone();
-            debug!("Best tip selected for this block template is {}", best_tip);
-            let mut selected_tips = Vec::with_capacity(tips.len());
-            for hash in tips {
-                if best_tip != hash {
-                    if !self.validate_tips(storage, &best_tip, &hash).await? {
-                        warn!("Tip {} is invalid, not selecting it because difficulty can't be less than 91% of {}", hash, best_tip);
-                        continue;
-                    }
-
-                    let distance = self.calculate_distance_from_mainchain(storage, &hash).await?;
-                    debug!("Distance from mainchain for tip {} is {}", hash, distance);
-                    if distance <= current_height && current_height - distance >= STABLE_LIMIT {
-                        warn!("Tip {} is not selected for mining: too far from mainchain (distance: {}, height: {})", hash, distance, current_height);
-                        continue;
-                    }
-                }
-                selected_tips.push(hash);
-            }
-            tips = selected_tips;
-
-            if tips.is_empty() {
-                warn!("No valid tips found for block template, using best tip {}", best_tip);
-                tips.push(best_tip);
-            }
-        }
-
-        let mut sorted_tips = blockdag::sort_tips(storage, tips.into_iter()).await?;
-        if sorted_tips.len() > TIPS_LIMIT {
-            let dropped_tips = sorted_tips.drain(TIPS_LIMIT..); // keep only first 3 heavier tips
-            debug!("Dropping tips {} because they are not in the first 3 heavier tips", dropped_tips.map(|h| h.to_string()).collect::<Vec<String>>().join(", "));
-        }
-
-        let height = blockdag::calculate_height_at_tips(storage, sorted_tips.iter()).await?;
-        let block = BlockHeader::new(self.get_version_at_height(height), height, get_current_time_in_millis(), sorted_tips, extra_nonce, address, IndexSet::new());
+        let height = blockdag::calculate_height_at_tips(storage, sorted_tips.iter()).await?;
+        let timestamp = self.get_network_time_in_millis().await;
+        let block = BlockHeader::new(self.get_version_at_height(height), height, timestamp, sorted_tips, extra_nonce, address, IndexSet::new());
 
         Ok(block)
     }
@@ -1467,6 +2140,19 @@ impl<S: Storage> Blockchain<S> {
     // This function is called when a miner request a new block template
     // We create a block candidate with selected TXs from mempool
     pub async fn get_block_template_for_storage(&self, storage: &S, address: PublicKey) -> Result<BlockHeader, BlockchainError> {
+        let (block, _) = self.get_block_template_with_summary_for_storage(storage, address, None).await?;
+        Ok(block)
+    }
+
+    // Same as get_block_template_for_storage but also returns the details of the template
+    // They are computed using the same storage and mempool snapshot
+    // max_txs can be set by miners wanting smaller blocks
+    pub async fn get_block_template_with_summary_for_storage(&self, storage: &S, address: PublicKey, max_txs: Option<usize>) -> Result<(BlockHeader, BlockTemplateSummary), BlockchainError> {
+        // Don't let miners work on an outdated chain
+        if !self.is_synced_for_storage(storage).await {
+            return Err(BlockchainError::NotSynced)
+        }
+
         let mut block = self.get_block_header_template_for_storage(storage, address).await?;
 
         trace!("Locking mempool for building block template");
@@ -1481,51 +2167,93 @@ impl<S: Storage> Blockchain<S> {
             let mut txs = Vec::with_capacity(cache_txs.len());
             // Map every tx hash to a TxSelectorEntry
             for tx_hash in cache_txs.iter() {
+                // Next TXs of this source can't be mined without this nonce
+                if mempool.is_conflicted(tx_hash) {
+                    debug!("Skipping TX {} and its next nonces because it is in conflict with a TX from a block", tx_hash);
+                    break;
+                }
+
                 let sorted_tx = mempool.get_sorted_tx(tx_hash)?;
-                txs.push(TxSelectorEntry { size: sorted_tx.get_size(), hash: tx_hash, tx: sorted_tx.get_tx() });
+                if verify_tx_type_activated(&self.network, block.get_height(), tx_hash, sorted_tx.get_tx()).is_err() {
+                    debug!("Skipping TX {} and its next nonces because its type is not activated yet", tx_hash);
+                    break;
+                }
+
+                // Accepted in mempool before the validation of the keys got activated
+                if verify_tx_public_keys(&self.network, block.get_height(), tx_hash, sorted_tx.get_tx()).is_err() {
+                    debug!("Skipping TX {} and its next nonces because it uses an invalid public key", tx_hash);
+                    break;
+                }
+
+                txs.push(TxSelectorEntry::new(tx_hash, sorted_tx.get_tx(), sorted_tx.get_size()));
             }
             entries.push(txs);
         }
 
         // Build the tx selector using the mempool
-        let mut tx_selector = TxSelector::grouped(entries.into_iter());
-
-        // size of block
-        let mut block_size = block.size();
-        let mut total_txs_size = 0;
+        // TXs are selected by fee per byte until the block is full
+        let mut tx_selector = TxSelector::grouped(entries.into_iter(), MAX_BLOCK_SIZE.saturating_sub(block.size()));
 
         // data used to verify txs
         let topoheight = self.get_topo_height();
         trace!("build chain state for block template");
         let mut chain_state = ChainState::new(storage, topoheight);
 
-        let mut failed_sources = HashSet::new();
-        while let Some(TxSelectorEntry { size, hash, tx }) = tx_selector.next() {
-            if block_size + total_txs_size + size >= MAX_BLOCK_SIZE {
+        // Same nonces rule as the block verification, we must never mine an invalid block
+        let mut nonces = BlockNonces::new();
+        let mut total_fees = 0;
+        let mut txs_size = 0;
+        while let Some(entry) = tx_selector.next() {
+            if max_txs.is_some_and(|max| block.get_txs_count() >= max) {
+                trace!("Block template reached the maximum of {} TXs requested", block.get_txs_count());
                 break;
             }
 
+            let hash = entry.hash;
+            let source = entry.source;
+            // Block with this TX would be rejected
+            let Some(next_total_fees) = add_block_fee(total_fees, entry.fee) else {
+                warn!("Skipping TX {} because its fees {} are overflowing the block fees {}", hash, entry.fee, total_fees);
+                tx_selector.reject(&entry);
+                continue;
+            };
+
             // Check if the TX is valid for this potential block
-            trace!("Checking TX {} with nonce {}, {}", hash, tx.get_nonce(), tx.get_source().as_address(self.network.is_mainnet()));
-            let source = tx.get_source();
-            if failed_sources.contains(&source) {
-                debug!("Skipping TX {} because its source has failed before", hash);
+            trace!("Checking TX {} with nonce {}, {}", hash, entry.nonce, source.as_address(self.network.is_mainnet()));
+            let expected_nonce = nonces.get_or_load_expected_nonce(storage, source, topoheight).await?;
+            if !nonces.use_nonce(source, entry.nonce) {
+                debug!("Skipping TX {} because its nonce {} is not the expected one {}", hash, entry.nonce, expected_nonce);
+                tx_selector.reject(&entry);
                 continue;
             }
 
+            let tx = mempool.get_sorted_tx(hash)?.get_tx();
             if let Err(e) = tx.verify(&mut chain_state).await {
                 warn!("TX {} ({}) is not valid for mining: {}", hash, source.as_address(self.network.is_mainnet()), e);
-                failed_sources.insert(source);
+                tx_selector.reject(&entry);
             } else {
-                trace!("Selected {} (nonce: {}, fees: {}) for mining", hash, tx.get_nonce(), format_xelis(tx.get_fee()));
+                trace!("Selected {} (nonce: {}, fees: {}) for mining", hash, entry.nonce, format_xelis(entry.fee));
                 // TODO no clone
                 block.txs_hashes.insert(hash.as_ref().clone());
-                block_size += HASH_SIZE; // add the hash size
-                total_txs_size += size;
+                total_fees = next_total_fees;
+                txs_size += entry.size;
             }
         }
+        debug!("Block template has {} TXs, {} bytes still available", block.get_txs_count(), tx_selector.get_available_size());
+        block.refresh_txs_merkle_root();
+
+        // Reward of the block if it is ordered right after our current topoheight
+        let past_supply = storage.get_supply_at_topo_height(topoheight).await?;
+        let (expected_reward, _) = split_block_reward(get_block_reward(past_supply), total_fees, block.get_height())?;
+        let summary = BlockTemplateSummary {
+            total_fees,
+            expected_reward,
+            txs_count: block.get_txs_count(),
+            size: block.size() + txs_size,
+            topoheight: topoheight + 1
+        };
 
-        Ok(block)
+        Ok((block, summary))
     }
 
     // Build a block using the header and search for TXs in mempool and storage
@@ -1557,78 +2285,128 @@ impl<S: Storage> Blockchain<S> {
         self.add_new_block_for_storage(&mut storage, block, broadcast, mining).await
     }
 
-    // Add a new block in chain using the requested storage
-    pub async fn add_new_block_for_storage(&self, storage: &mut S, block: Block, broadcast: bool, mining: bool) -> Result<(), BlockchainError> {
-        let start = Instant::now();
+    // PoW hash of a block header, it is not computed if the PoW verification is skipped
+    pub fn get_block_pow_hash(&self, block: &BlockHeader) -> Result<Hash, BlockchainError> {
+        if self.skip_pow_verification() {
+            return Ok(Hash::zero())
+        }
+
+        Ok(block.get_pow_hash()?)
+    }
 
+    // Checks of a block header that don't depend on any other block
+    // It is shared with the ChainValidator for the blocks that are not in chain yet
+    pub async fn verify_block_header_format(&self, block: &BlockHeader, block_hash: &Hash) -> Result<(), BlockchainError> {
         // Verify that the block is on the correct version
         if block.get_version() != self.get_version_at_height(block.get_height()) {
             return Err(BlockchainError::InvalidBlockVersion)
         }
 
-        let block_hash = block.hash();
-        debug!("Add new block {}", block_hash);
-        if storage.has_block_with_hash(&block_hash).await? {
-            debug!("Block {} is already in chain!", block_hash);
-            return Err(BlockchainError::AlreadyInChain)
+        // Timestamp must be representable as a date
+        if !is_timestamp_in_range(block.get_timestamp()) {
+            debug!("Block timestamp {} is out of range", block.get_timestamp());
+            return Err(BlockchainError::TimestampOutOfRange(block.get_timestamp()));
         }
 
-        let current_timestamp = get_current_time_in_millis(); 
+        let current_timestamp = self.get_network_time_in_millis().await;
         if block.get_timestamp() > current_timestamp + TIMESTAMP_IN_FUTURE_LIMIT { // accept 2s in future
             debug!("Block timestamp is too much in future!");
             return Err(BlockchainError::TimestampIsInFuture(current_timestamp, block.get_timestamp()));
         }
 
+        verify_unique_txs(block_hash, block.get_txs_hashes())?;
+
+        // The identity key can't be rewarded, nobody knows its private key
+        if block.get_height() >= get_public_keys_validation_activation_height(&self.network) {
+            if let Err(e) = block.get_miner().validate() {
+                debug!("Invalid miner key for block {}: {}", block_hash, e);
+                return Err(BlockchainError::InvalidBlockMinerKey(block_hash.clone(), e))
+            }
+        }
+
+        if !block.is_txs_merkle_root_valid() {
+            debug!("Invalid TXs merkle root for block {}", block_hash);
+            return Err(BlockchainError::InvalidTxsMerkleRoot(block_hash.clone()))
+        }
+
         let tips_count = block.get_tips().len();
         debug!("Tips count for this new {}: {}", block, tips_count);
         // only 3 tips are allowed
         if tips_count > TIPS_LIMIT {
             debug!("Invalid tips count, got {} but maximum allowed is {}", tips_count, TIPS_LIMIT);
-            return Err(BlockchainError::InvalidTipsCount(block_hash, tips_count))
+            return Err(BlockchainError::InvalidTipsCount(block_hash.clone(), tips_count))
+        }
+
+        if tips_count > 0 && block.get_height() == 0 {
+            debug!("Invalid block height, got height 0 but tips are present for this block {}", block_hash);
+            return Err(BlockchainError::BlockHeightZeroNotAllowed)
+        }
+
+        Ok(())
+    }
+
+    // Checks of a block header against its tips, they must all be known by the provider
+    // It is shared with the ChainValidator for the blocks that are not in chain yet
+    pub async fn verify_block_header_tips<P: DifficultyProvider>(&self, provider: &P, block: &BlockHeader, block_hash: &Hash) -> Result<(), BlockchainError> {
+        let block_height_by_tips = blockdag::calculate_height_at_tips(provider, block.get_tips().iter()).await?;
+        if block_height_by_tips != block.get_height() {
+            debug!("Invalid block height {}, expected {} for this block {}", block.get_height(), block_height_by_tips, block_hash);
+            return Err(BlockchainError::InvalidBlockHeight(block_height_by_tips, block.get_height()))
+        }
+
+        for hash in block.get_tips() {
+            let previous_timestamp = provider.get_timestamp_for_block_hash(hash).await?;
+            // block timestamp can't be less than previous block.
+            if block.get_timestamp() < previous_timestamp {
+                debug!("Invalid block timestamp, parent ({}) is less than new block {}", hash, block_hash);
+                return Err(BlockchainError::TimestampIsLessThanParent(block.get_timestamp()));
+            }
         }
 
+        Ok(())
+    }
+
+    // Verify a block header without writing anything in the storage
+    // It checks the version, timestamp, tips, height and the PoW of the block
+    // Returns the difficulty and the estimated covariance (P) of the block
+    pub async fn verify_block_header(&self, storage: &S, block: &BlockHeader, block_hash: &Hash) -> Result<(Difficulty, VarUint), BlockchainError> {
+        let pow_hash = self.get_block_pow_hash(block)?;
+        self.verify_block_header_with_timings(storage, block, block_hash, &pow_hash, &mut BlockTimings::new()).await
+    }
+
+    // Same as verify_block_header with the PoW hash already computed
+    // The time spent in the header checks and the PoW verification is recorded
+    async fn verify_block_header_with_timings(&self, storage: &S, block: &BlockHeader, block_hash: &Hash, pow_hash: &Hash, timings: &mut BlockTimings) -> Result<(Difficulty, VarUint), BlockchainError> {
+        let start = Instant::now();
+        self.verify_block_header_format(block, block_hash).await?;
+
+        let tips_count = block.get_tips().len();
         let current_height = self.get_height();
         if tips_count == 0 && current_height != 0 {
             debug!("Expected at least one previous block for this block {}", block_hash);
             return Err(BlockchainError::ExpectedTips)
         }
 
-        if tips_count > 0 && block.get_height() == 0 {
-            debug!("Invalid block height, got height 0 but tips are present for this block {}", block_hash);
-            return Err(BlockchainError::BlockHeightZeroNotAllowed)
-        }
-
         if tips_count == 0 && block.get_height() != 0 {
             debug!("Invalid tips count, got {} but current height is {} with block height {}", tips_count, current_height, block.get_height());
-            return Err(BlockchainError::InvalidTipsCount(block_hash, tips_count))
-        }
-
-        // block contains header and full TXs
-        let block_size = block.size();
-        if block_size > MAX_BLOCK_SIZE {
-            debug!("Block size ({} bytes) is greater than the limit ({} bytes)", block.size(), MAX_BLOCK_SIZE);
-            return Err(BlockchainError::InvalidBlockSize(MAX_BLOCK_SIZE, block.size()));
+            return Err(BlockchainError::InvalidTipsCount(block_hash.clone(), tips_count))
         }
 
         for tip in block.get_tips() {
             if !storage.has_block_with_hash(tip).await? {
                 debug!("This block ({}) has a TIP ({}) which is not present in chain", block_hash, tip);
-                return Err(BlockchainError::InvalidTipsNotFound(block_hash, tip.clone()))
+                return Err(BlockchainError::InvalidTipsNotFound(block_hash.clone(), tip.clone()))
             }
         }
 
-        let block_height_by_tips = blockdag::calculate_height_at_tips(storage, block.get_tips().iter()).await?;
-        if block_height_by_tips != block.get_height() {
-            debug!("Invalid block height {}, expected {} for this block {}", block.get_height(), block_height_by_tips, block_hash);
-            return Err(BlockchainError::InvalidBlockHeight(block_height_by_tips, block.get_height()))
-        }
+        self.verify_block_header_tips(storage, block, block_hash).await?;
 
         let stable_height = self.get_stable_height();
         if tips_count > 0 {
-            debug!("Height by tips: {}, stable height: {}", block_height_by_tips, stable_height);
+            debug!("Height by tips: {}, stable height: {}", block.get_height(), stable_height);
 
-            if block_height_by_tips < stable_height {
-                debug!("Invalid block height by tips {} for this block ({}), its height is in stable height {}", block_height_by_tips, block_hash, stable_height);
+            if block.get_height() < stable_height {
+                debug!("Invalid block height by tips {} for this block ({}), its height is in stable height {}", block.get_height(), block_hash, stable_height);
                 return Err(BlockchainError::InvalidBlockHeightStableHeight)
             }
         }
@@ -1639,13 +2417,6 @@ impl<S: Storage> Blockchain<S> {
         }
 
         for hash in block.get_tips() {
-            let previous_timestamp = storage.get_timestamp_for_block_hash(hash).await?;
-            // block timestamp can't be less than previous block.
-            if block.get_timestamp() < previous_timestamp {
-                debug!("Invalid block timestamp, parent ({}) is less than new block {}", hash, block_hash);
-                return Err(BlockchainError::TimestampIsLessThanParent(block.get_timestamp()));
-            }
-
             trace!("calculate distance from mainchain for tips: {}", hash);
             let distance = self.calculate_distance_from_mainchain(storage, hash).await?;
             if distance <= current_height && current_height - distance >= STABLE_LIMIT {
@@ -1661,25 +2432,36 @@ impl<S: Storage> Blockchain<S> {
                 if best_tip != hash {
                     if !self.validate_tips(storage, best_tip, hash).await? {
                         debug!("Tip {} is invalid, difficulty can't be less than 91% of {}", hash, best_tip);
-                        return Err(BlockchainError::InvalidTipsDifficulty(block_hash, hash.clone()))
+                        return Err(BlockchainError::InvalidTipsDifficulty(block_hash.clone(), hash.clone()))
                     }
                 }
             }
         }
 
+        timings.record(BlockProcessingStage::HeaderVerification, start);
+
         // verify PoW and get difficulty for this block based on tips
-        let skip_pow = self.skip_pow_verification();
-        let pow_hash = if skip_pow {
-            // Simulator is enabled, we don't need to compute the PoW hash
-            Hash::zero()
-        } else {
-            block.get_pow_hash()?
-        };
-        debug!("POW hash: {}, skipped: {}", pow_hash, skip_pow);
-        let (difficulty, p) = self.verify_proof_of_work(storage, &pow_hash, block.get_tips().iter()).await?;
+        let start = Instant::now();
+        debug!("POW hash: {}, skipped: {}", pow_hash, self.skip_pow_verification());
+        let (difficulty, p) = self.verify_proof_of_work(storage, pow_hash, block.get_tips().iter()).await?;
         debug!("PoW is valid for difficulty {}", difficulty);
+        timings.record(BlockProcessingStage::ProofOfWork, start);
 
-        let mut current_topoheight = self.get_topo_height();
+        Ok((difficulty, p))
+    }
+
+    // Verify all the transactions of a block against the chain state at current topoheight
+    // Nothing is written in the storage
+    pub async fn verify_block_transactions(&self, storage: &S, block: &Block, block_hash: &Hash) -> Result<(), BlockchainError> {
+        // block contains header and full TXs
+        let block_size = block.size();
+        if block_size > MAX_BLOCK_SIZE {
+            debug!("Block size ({} bytes) is greater than the limit ({} bytes)", block_size, MAX_BLOCK_SIZE);
+            return Err(BlockchainError::InvalidBlockSize(MAX_BLOCK_SIZE, block_size));
+        }
+
+        let current_topoheight = self.get_topo_height();
+        let stable_height = self.get_stable_height();
         // Transaction verification
         // Here we are going to verify all TXs in the block
         // For this, we must select TXs that are not doing collisions with other TXs in block
@@ -1694,6 +2476,9 @@ impl<S: Storage> Blockchain<S> {
                 return Err(BlockchainError::InvalidBlockTxs(hashes_len, txs_len));
             }
 
+            // The fees are summed in the miner reward, they must never overflow
+            verify_block_fees(block_hash, block.get_transactions().iter().map(|tx| tx.get_fee()))?;
+
             trace!("verifying {} TXs in block {}", txs_len, block_hash);
             let mut chain_state = ChainState::new(storage, current_topoheight);
             // Cache to retrieve only one time all TXs hashes until stable height
@@ -1712,6 +2497,9 @@ impl<S: Storage> Blockchain<S> {
                     return Err(BlockchainError::InvalidTxInBlock(tx_hash))
                 }
 
+                verify_tx_type_activated(&self.network, block.get_height(), hash, tx)?;
+                verify_tx_public_keys(&self.network, block.get_height(), hash, tx)?;
+
                 debug!("Verifying TX {}", tx_hash);
                 // check that the TX included is not executed in stable height or in block TIPS
                 if chain_state.get_storage().is_tx_executed_in_a_block(hash)? {
@@ -1760,13 +2548,101 @@ impl<S: Storage> Blockchain<S> {
 
             debug!("proof verifications of TXs ({}) in block {}", batch.iter().map(|v| v.hash().to_string()).collect::<Vec<String>>().join(","), block_hash);
             // Verify all valid transactions in one batch
-            Transaction::verify_batch(batch.as_slice(), &mut chain_state).await?;
+            // Nonces of each sender must be sequential, the block is rejected if one reuse a nonce or skip one
+            Transaction::verify_batch(batch.as_slice(), &mut chain_state).await.map_err(|e| match e {
+                VerificationError::InvalidNonce(source, expected, got) => {
+                    debug!("Block {} contains an invalid nonce {} for {}, expected {}", block_hash, got, source.as_address(self.network.is_mainnet()), expected);
+                    BlockchainError::InvalidBlockTxNonce(block_hash.clone(), source.as_address(self.network.is_mainnet()), got, expected)
+                },
+                e => e.into()
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // Verify the header and the transactions of a block against the current chain state
+    // Nothing is written in the storage, the block can then be added using add_verified_block
+    pub async fn verify_block(&self, storage: &S, block: Block) -> Result<VerifiedBlock, BlockchainError> {
+        self.verify_block_with_pow_hash(storage, block, None).await
+    }
+
+    async fn verify_block_with_pow_hash(&self, storage: &S, block: Block, pow_hash: Option<Hash>) -> Result<VerifiedBlock, BlockchainError> {
+        let start = Instant::now();
+
+        let block_hash = block.hash();
+        debug!("Verify block {}", block_hash);
+        if storage.has_block_with_hash(&block_hash).await? {
+            debug!("Block {} is already in chain!", block_hash);
+            return Err(BlockchainError::AlreadyInChain)
         }
 
+        let mut timings = BlockTimings::new();
+        let pow_hash = match pow_hash {
+            Some(pow_hash) => pow_hash,
+            None => {
+                let pow_start = Instant::now();
+                let pow_hash = self.get_block_pow_hash(&block)?;
+                timings.record(BlockProcessingStage::ProofOfWork, pow_start);
+                pow_hash
+            }
+        };
+        let (difficulty, p) = self.verify_block_header_with_timings(storage, &block, &block_hash, &pow_hash, &mut timings).await?;
+
+        let txs_start = Instant::now();
+        self.verify_block_transactions(storage, &block, &block_hash).await?;
+        timings.record(BlockProcessingStage::TxsVerification, txs_start);
+
+        let top_state = self.get_top_state();
+        Ok(VerifiedBlock {
+            block,
+            hash: block_hash,
+            pow_hash,
+            difficulty,
+            p,
+            top_hash: top_state.hash,
+            topoheight: top_state.topoheight,
+            start,
+            timings
+        })
+    }
+
+    // Add a block verified previously without holding the storage write lock
+    // It is verified again only if the chain changed in the meantime
+    pub async fn add_verified_block(&self, verified: VerifiedBlock, broadcast: bool, mining: bool) -> Result<(), BlockchainError> {
+        let mut storage = self.storage.write().await;
+        let top_state = self.get_top_state();
+        let verified = if verified.top_hash == top_state.hash && verified.topoheight == top_state.topoheight {
+            verified
+        } else {
+            debug!("Chain has changed since the verification of block {}, verifying it again", verified.hash);
+            // The PoW hash doesn't depend on the chain state
+            self.verify_block_with_pow_hash(&storage, verified.block, Some(verified.pow_hash)).await?
+        };
+
+        self.execute_block(&mut storage, verified, broadcast, mining).await
+    }
+
+    // Add a new block in chain using the requested storage
+    pub async fn add_new_block_for_storage(&self, storage: &mut S, block: Block, broadcast: bool, mining: bool) -> Result<(), BlockchainError> {
+        let verified = self.verify_block(storage, block).await?;
+        self.execute_block(storage, verified, broadcast, mining).await
+    }
+
+    // Execute a block already verified and write it in storage
+    // This will update the DAG order, execute the TXs, update the mempool and notify the P2P & RPC servers
+    async fn execute_block(&self, storage: &mut S, verified: VerifiedBlock, broadcast: bool, mining: bool) -> Result<(), BlockchainError> {
+        let VerifiedBlock { block, hash: block_hash, difficulty, p, start, mut timings, .. } = verified;
+        let tips_count = block.get_tips().len();
+        let current_height = self.get_height();
+        let mut current_topoheight = self.get_topo_height();
+        let block_size = block.size();
+
         // Save transactions & block
         let (block, txs) = block.split();
         let block = block.to_arc();
         debug!("Saving block {} on disk", block_hash);
+        let commit_start = Instant::now();
         // Add block to chain
         storage.save_block(block.clone(), &txs, difficulty, p, block_hash.clone()).await?;
         storage.add_block_execution_to_order(&block_hash).await?;
@@ -1784,6 +2660,11 @@ impl<S: Storage> Blockchain<S> {
             debug!("Cumulative difficulty for block {}: {}", block_hash, cumulative_difficulty);
             cumulative_difficulty
         };
+        timings.record(BlockProcessingStage::StorageCommit, commit_start);
+
+        let execution_start = Instant::now();
+        // time spent writing the state changes during the execution
+        let mut applied = Duration::ZERO;
 
         let mut tips = storage.get_tips().await?;
         tips.insert(block_hash.clone());
@@ -1803,19 +2684,43 @@ impl<S: Storage> Blockchain<S> {
 
         // rpc server lock
         let rpc_server = self.rpc.read().await;
-        let should_track_events = if let Some(rpc) = rpc_server.as_ref() {
-            rpc.get_tracked_events().await
+        let (should_track_events, watched_keys) = if let Some(rpc) = rpc_server.as_ref() {
+            (rpc.get_tracked_events().await, rpc.get_subscribed_keys().await)
         } else {
-            HashSet::new()
+            (HashSet::new(), HashSet::new())
         };
 
         // track all events to notify websocket
         let mut events: HashMap<NotifyEvent, Vec<Value>> = HashMap::new();
+        // track all balances updated with the account they belong to
+        let mut balances_events: Vec<(PublicKey, Value)> = Vec::new();
+        // track all transactions touching a watched address
+        let mut received_events: Vec<(PublicKey, TransactionReceivedEvent<'static>)> = Vec::new();
         // Track all orphaned tranasctions
         let mut orphaned_transactions = HashSet::new();
 
+        // Detect the TXs of this block using the nonce of a different TX in our mempool
+        {
+            let mut mempool = self.mempool.write().await;
+            let double_spends = mempool.detect_double_spends(txs.iter().zip(block.get_txs_hashes()).map(|(tx, hash)| (tx.get_source(), tx.get_nonce(), hash)));
+            if should_track_events.contains(&NotifyEvent::DoubleSpendDetected) {
+                for double_spend in double_spends {
+                    let value = json!(DoubleSpendDetectedEvent {
+                        address: double_spend.source.as_address(self.network.is_mainnet()),
+                        nonce: double_spend.nonce,
+                        mempool_tx: Cow::Borrowed(&double_spend.mempool_tx),
+                        block_tx: Cow::Borrowed(&double_spend.block_tx),
+                        block_hash: Cow::Borrowed(&block_hash)
+                    });
+                    events.entry(NotifyEvent::DoubleSpendDetected).or_insert_with(Vec::new).push(value);
+                }
+            }
+        }
+
         // order the DAG (up to TOP_HEIGHT - STABLE_LIMIT)
         let mut highest_topo = 0;
+        // Lowest topoheight written again, used by the firehose clients
+        let mut reordered_from = None;
         // Tells if the new block added is ordered in DAG or not
         let block_is_ordered = full_order.contains(&block_hash);
         {
@@ -1845,6 +2750,7 @@ impl<S: Storage> Blockchain<S> {
                     }
 
                     debug!("Cleaning transactions executions at topo height {} (block {})", topoheight, hash_at_topo);
+                    reordered_from.get_or_insert(topoheight);
 
                     let block = storage.get_block_header_by_hash(&hash_at_topo).await?;
 
@@ -1865,6 +2771,14 @@ impl<S: Storage> Blockchain<S> {
                             trace!("Removing execution of {}", tx_hash);
                             storage.remove_tx_executed(&tx_hash)?;
 
+                            // The asset created by this TX is registered again only if the TX is executed again
+                            // Its hash is derived from the TX hash, only an AssetCreation TX can match it
+                            let asset = AssetCreationPayload::get_asset(tx_hash);
+                            if storage.has_asset(&asset).await? {
+                                debug!("Unregistering asset {} created by TX {}", asset, tx_hash);
+                                storage.delete_asset(&asset).await?;
+                            }
+
                             if is_orphaned {
                                 orphaned_transactions.insert(tx_hash.clone());
                             }
@@ -1875,6 +2789,7 @@ impl<S: Storage> Blockchain<S> {
                     storage.delete_versioned_balances_at_topoheight(topoheight).await?;
                     storage.delete_versioned_nonces_at_topoheight(topoheight).await?;
                     storage.delete_registrations_at_topoheight(topoheight).await?;
+                    storage.delete_burned_supply_at_topoheight(topoheight).await?;
 
                     topoheight += 1;
                 }
@@ -1896,6 +2811,7 @@ impl<S: Storage> Blockchain<S> {
                     continue;
                 }
                 is_written = true;
+                reordered_from.get_or_insert(highest_topo);
 
                 trace!("Ordering block {} at topoheight {}", hash, highest_topo);
 
@@ -1929,7 +2845,7 @@ impl<S: Storage> Blockchain<S> {
                     },
                 };
 
-                let mut block_reward = self.internal_get_block_reward(past_supply, is_side_block, *side_blocks_count).await?;
+                let block_reward = self.internal_get_block_reward(past_supply, is_side_block, *side_blocks_count).await?;
                 trace!("set block {} reward to {} at {} (height {}, side block: {}, {} {}%)", hash, block_reward, highest_topo, height, is_side_block, side_blocks_count, side_block_reward_percentage(*side_blocks_count));
                 if is_side_block {
                     *side_blocks_count += 1;
@@ -1937,15 +2853,21 @@ impl<S: Storage> Blockchain<S> {
 
                 storage.set_block_reward_at_topo_height(highest_topo, block_reward)?;
                 
-                let supply = past_supply + block_reward;
+                let supply = past_supply.checked_add(block_reward).ok_or(BlockchainError::SupplyOverflow(highest_topo))?;
                 trace!("set block supply to {} at {}", supply, highest_topo);
                 storage.set_supply_at_topo_height(highest_topo, supply)?;
 
                 // All fees from the transactions executed in this block
                 let mut total_fees = 0;
+                // All amounts burned per asset in this block
+                let mut burned_supply: HashMap<&Hash, u64> = HashMap::new();
+                // All assets created in this block with their decimals
+                let mut registered_assets = Vec::new();
                 // Chain State used for the verification
                 trace!("building chain state to execute TXs in block {}", block_hash);
                 let mut chain_state = ApplicableChainState::new(storage, highest_topo);
+                // Position of the next transaction executed in this block
+                let mut execution_index: u16 = 0;
 
                 // compute rewards & execute txs
                 for (tx, tx_hash) in block.get_transactions().iter().zip(block.get_txs_hashes()) { // execute all txs
@@ -1982,7 +2904,8 @@ impl<S: Storage> Blockchain<S> {
                         chain_state.as_mut().update_account_nonce(tx.get_source(), next_nonce).await?;
 
                         // mark tx as executed
-                        chain_state.get_mut_storage().set_tx_executed_in_block(tx_hash, &hash)?;
+                        chain_state.get_mut_storage().set_tx_executed_in_block(tx_hash, &hash, execution_index)?;
+                        execution_index += 1;
 
                         // Delete the transaction from  the list if it was marked as orphaned
                         if orphaned_transactions.remove(&tx_hash) {
@@ -1999,25 +2922,82 @@ impl<S: Storage> Blockchain<S> {
                             events.entry(NotifyEvent::TransactionExecuted).or_insert_with(Vec::new).push(value);
                         }
 
+                        if !watched_keys.is_empty() {
+                            received_events.extend(get_transaction_received_events(tx, &tx_hash, Some((&hash, highest_topo)), &watched_keys, self.network.is_mainnet()));
+                        }
+
+                        // Track the amount burned for this asset
+                        if let TransactionType::Burn(payload) = tx.get_data() {
+                            let burned = burned_supply.entry(&payload.asset).or_insert(0);
+                            *burned = burned.checked_add(payload.amount).ok_or(BlockchainError::SupplyOverflow(highest_topo))?;
+                        }
+
+                        // Registration cost is burned, the asset is registered once the TXs are applied
+                        if let TransactionType::AssetCreation(payload) = tx.get_data() {
+                            let burned = burned_supply.entry(&XELIS_ASSET).or_insert(0);
+                            *burned = burned.checked_add(ASSET_CREATION_BURN_AMOUNT).ok_or(BlockchainError::SupplyOverflow(highest_topo))?;
+                            registered_assets.push((AssetCreationPayload::get_asset(tx_hash), payload.decimals));
+                        }
+
                         // Increase total tx fees for miner
-                        total_fees += tx.get_fee();
+                        total_fees = add_block_fee(total_fees, tx.get_fee()).ok_or_else(|| BlockchainError::BlockFeesOverflow(hash.clone()))?;
                     }
                 }
 
-                let dev_fee_percentage = get_block_dev_fee(block.get_height());
-                // Dev fee are only applied on block reward
-                // Transaction fees are not affected by dev fee
-                if dev_fee_percentage != 0 {
-                    let dev_fee_part = block_reward * dev_fee_percentage / 100;
+                // Rewards are derived from the protocol and never claimed by the miner
+                let (miner_reward, dev_fee_part) = split_block_reward(block_reward, total_fees, height)?;
+                if dev_fee_part != 0 {
                     chain_state.reward_miner(&DEV_PUBLIC_KEY, dev_fee_part).await?;
-                    block_reward -= dev_fee_part;    
                 }
-                
+
                 // reward the miner
-                chain_state.reward_miner(block.get_miner(), block_reward + total_fees).await?;
+                chain_state.reward_miner(block.get_miner(), miner_reward).await?;
+
+                // Everything credited must match the emission and the fees of the executed TXs
+                verify_block_reward(block_reward, total_fees, chain_state.get_rewarded())?;
 
                 // apply changes from Chain State
-                chain_state.apply_changes().await?;
+                let mut balances_changes = Vec::new();
+                let track_balances = should_track_events.contains(&NotifyEvent::BalanceUpdated);
+                let apply_start = Instant::now();
+                chain_state.apply_changes(track_balances.then_some(&mut balances_changes)).await?;
+                applied += apply_start.elapsed();
+
+                // Balances events are only sent to the clients interested in these accounts
+                for (key, asset, version) in balances_changes {
+                    let value = json!(BalanceUpdatedEvent {
+                        address: key.as_address(self.network.is_mainnet()),
+                        asset: Cow::Owned(asset),
+                        topoheight: highest_topo,
+                        version: version.to_hex()
+                    });
+                    balances_events.push((key, value));
+                }
+
+                // Update the total burned supply of each asset burned in this block
+                for (asset, amount) in burned_supply {
+                    let burned = storage.get_burned_supply_at_maximum_topoheight(asset, highest_topo).await?
+                        .map(|(_, burned)| burned)
+                        .unwrap_or(0);
+                    let burned = burned.checked_add(amount).ok_or(BlockchainError::SupplyOverflow(highest_topo))?;
+                    trace!("set burned supply of {} to {} at {}", asset, burned, highest_topo);
+                    storage.set_burned_supply_at_topoheight(asset, highest_topo, burned)?;
+                }
+
+                // Register the assets created in this block at its topoheight
+                // They are deleted by the rewind if the block is not ordered anymore
+                for (asset, decimals) in registered_assets {
+                    if storage.has_asset(&asset).await? {
+                        warn!("Asset {} created by a TX in block {} is already registered", asset, hash);
+                        continue;
+                    }
+
+                    debug!("Registering asset {} with {} decimals at topoheight {}", asset, decimals, highest_topo);
+                    storage.add_asset(&asset, AssetData::new(highest_topo, decimals)).await?;
+                }
+
+                trace!("set block fees to {} at {}", total_fees, highest_topo);
+                storage.set_block_fees_at_topo_height(highest_topo, total_fees)?;
 
                 if should_track_events.contains(&NotifyEvent::BlockOrdered) {
                     let value = json!(BlockOrderedEvent {
@@ -2064,7 +3044,6 @@ impl<S: Storage> Blockchain<S> {
         if current_height == 0 || extended {
             debug!("Blockchain height extended, current topoheight is now {} (previous was {})", highest_topo, current_topoheight);
             storage.set_top_topoheight(highest_topo)?;
-            self.topoheight.store(highest_topo, Ordering::Release);
             current_topoheight = highest_topo;
         }
 
@@ -2077,20 +3056,6 @@ impl<S: Storage> Blockchain<S> {
             }
         }
 
-        // auto prune mode
-        if extended {
-            if let Some(keep_only) = self.auto_prune_keep_n_blocks {
-                // check that the topoheight is greater than the safety limit
-                // and that we can prune the chain using the config while respecting the safety limit
-                if current_topoheight % keep_only == 0 && current_topoheight - keep_only > 0 {
-                    info!("Auto pruning chain until topoheight {} (keep only {} blocks)", current_topoheight - keep_only, keep_only);
-                    if let Err(e) = self.prune_until_topoheight_for_storage(current_topoheight - keep_only, storage).await {
-                        warn!("Error while trying to auto prune chain: {}", e);
-                    }
-                }
-            }
-        }
-
         // Store the new tips available
         storage.store_tips(&tips)?;
 
@@ -2098,10 +3063,17 @@ impl<S: Storage> Blockchain<S> {
         if current_height == 0 || block.get_height() > current_height {
             debug!("storing new top height {}", block.get_height());
             storage.set_top_height(block.get_height())?;
-            self.height.store(block.get_height(), Ordering::Release);
             current_height = block.get_height();
         }
 
+        #[cfg(test)]
+        {
+            let delay = self.block_application_delay.load(std::sync::atomic::Ordering::Relaxed);
+            if delay > 0 {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+            }
+        }
+
         // update stable height and difficulty in cache
         {
             let (stable_hash, stable_height) = self.find_common_base::<S, _>(&storage, &tips).await?;
@@ -2117,15 +3089,41 @@ impl<S: Storage> Blockchain<S> {
                 }
             }
 
-            // Update caches
-            self.stable_height.store(stable_height, Ordering::SeqCst);
             // Search the topoheight of the stable block
             let stable_topoheight = storage.get_topo_height_for_hash(&stable_hash).await?;
-            self.stable_topoheight.store(stable_topoheight, Ordering::SeqCst);
 
             trace!("update difficulty in cache");
             let (difficulty, _) = self.get_difficulty_at_tips(storage, tips.iter()).await?;
-            self.set_difficulty(difficulty).await;
+
+            // Update the whole cache at once
+            let hash = storage.get_hash_at_topo_height(current_topoheight).await?;
+            let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(&hash).await?;
+            let block = self.build_block_summary(storage, &hash).await?;
+            self.top_state.set(TopState {
+                hash,
+                height: current_height,
+                topoheight: current_topoheight,
+                difficulty,
+                cumulative_difficulty,
+                stable_height,
+                stable_topoheight,
+                pruned_topoheight: self.top_state.get_pruned_topoheight(),
+                block: Some(block)
+            });
+        }
+
+        // auto prune mode
+        if extended {
+            if let Some(keep_only) = self.auto_prune_keep_n_blocks {
+                // check that the topoheight is greater than the safety limit
+                // and that we can prune the chain using the config while respecting the safety limit
+                if current_topoheight % keep_only == 0 && current_topoheight - keep_only > 0 {
+                    info!("Auto pruning chain until topoheight {} (keep only {} blocks)", current_topoheight - keep_only, keep_only);
+                    if let Err(e) = self.prune_until_topoheight_for_storage(current_topoheight - keep_only, storage).await {
+                        warn!("Error while trying to auto prune chain: {}", e);
+                    }
+                }
+            }
         }
 
         // Check if the event is tracked
@@ -2141,6 +3139,24 @@ impl<S: Storage> Blockchain<S> {
             Vec::new()
         };
 
+        {
+            let mut mempool = self.mempool.write().await;
+            if !mempool.get_conflicts().is_empty() {
+                if let Err(e) = mempool.resolve_conflicts(&*storage, self.get_stable_height()).await {
+                    warn!("Error while resolving conflicts in mempool: {}", e);
+                }
+            }
+        }
+
+        // Mined or evicted TXs are not pending anymore
+        if self.mempool_persistence {
+            for (tx_hash, _) in mempool_deleted_txs.iter() {
+                if let Err(e) = storage.delete_mempool_tx(tx_hash) {
+                    warn!("Error while deleting persisted TX {}: {}", tx_hash, e);
+                }
+            }
+        }
+
         if orphan_event_tracked {
             for (tx_hash, sorted_tx) in mempool_deleted_txs {
                 // Delete it from our orphaned transactions list
@@ -2160,6 +3176,7 @@ impl<S: Storage> Blockchain<S> {
                     executed_in_block: None,
                     in_mempool: false,
                     first_seen: Some(sorted_tx.get_first_seen()),
+                    first_seen_human: Some(format_timestamp_seconds(sorted_tx.get_first_seen())),
                     data,
                 };
                 events.entry(NotifyEvent::TransactionOrphaned).or_insert_with(Vec::new).push(json!(data));
@@ -2180,6 +3197,11 @@ impl<S: Storage> Blockchain<S> {
                     }
                 };
 
+                // Even if added back to mempool, it is not executed anymore
+                if !watched_keys.is_empty() {
+                    received_events.extend(get_transaction_received_events(&tx, &tx_hash, None, &watched_keys, storage.is_mainnet()));
+                }
+
                 // Clone only if its necessary
                 if !orphan_event_tracked {
                     if let Err(e) = self.add_tx_to_mempool_with_storage_and_hash(&storage, tx, tx_hash, false).await {
@@ -2195,6 +3217,7 @@ impl<S: Storage> Blockchain<S> {
                             executed_in_block: None,
                             in_mempool: false,
                             first_seen: None,
+                            first_seen_human: None,
                             data,
                         };
                         events.entry(NotifyEvent::TransactionOrphaned).or_insert_with(Vec::new).push(json!(data));
@@ -2203,8 +3226,29 @@ impl<S: Storage> Blockchain<S> {
             }
         }
 
+        timings.add(BlockProcessingStage::StorageCommit, applied);
+        timings.add(BlockProcessingStage::Execution, execution_start.elapsed().saturating_sub(applied));
+
         info!("Processed block {} at height {} in {}ms with {} txs (DAG: {})", block_hash, block.get_height(), start.elapsed().as_millis(), block.get_txs_count(), block_is_ordered);
 
+        // Track the solve time to detect a sustained drift from the target block time
+        if tips_count > 0 {
+            let mut tracker = self.block_time_tracker.lock().await;
+            if tracker.record(block.get_timestamp()) {
+                warn!("Average block time on the last {} blocks is {}ms instead of {}ms for more than an hour, the network hashrate may have changed significantly", BLOCK_TIME_DRIFT_WINDOW, tracker.get_average().unwrap_or_default(), BLOCK_TIME_MILLIS);
+            }
+        }
+
+        let notify_start = Instant::now();
+        // Don't relay the blocks while we are syncing, except the ones mined by us
+        let (synced, sync_event) = self.refresh_sync_state(storage).await;
+        if let Some(event) = sync_event {
+            if should_track_events.contains(&NotifyEvent::SyncCompleted) {
+                events.entry(NotifyEvent::SyncCompleted).or_insert_with(Vec::new).push(json!(event));
+            }
+        }
+        let broadcast = broadcast && (synced || mining);
+
         // Broadcast to p2p nodes
         if broadcast {
             trace!("Broadcasting block");
@@ -2234,10 +3278,18 @@ impl<S: Storage> Blockchain<S> {
                 }
             }
 
+            // send the new ordered blocks to the firehose clients
+            if reordered_from.is_some() {
+                let firehose = rpc.firehose_server().clone();
+                spawn_task("notify-firehose", async move {
+                    firehose.notify_new_topoheight(reordered_from).await;
+                });
+            }
+
             // atm, we always notify websocket clients
             trace!("Notifying websocket clients");
             if should_track_events.contains(&NotifyEvent::NewBlock) {
-                match get_block_response(self, storage, &block_hash, &Block::new(Immutable::Arc(block), txs), block_size).await {
+                match get_new_block_event(self, storage, &block_hash, &Block::new(Immutable::Arc(block), txs), block_size).await {
                     Ok(response) => {
                         events.entry(NotifyEvent::NewBlock).or_insert_with(Vec::new).push(response);
                     },
@@ -2247,6 +3299,13 @@ impl<S: Storage> Blockchain<S> {
                 };
             }
 
+            let topoheight = self.get_topo_height();
+            for (_, event) in received_events.iter_mut() {
+                if let Some(executed_at) = event.topoheight {
+                    event.confirmations = topoheight.saturating_sub(executed_at) + 1;
+                }
+            }
+
             let rpc = rpc.clone();
             // don't block mutex/lock more than necessary, we move it in another task
             spawn_task("rpc-notify-events", async move {
@@ -2257,8 +3316,22 @@ impl<S: Storage> Blockchain<S> {
                         }
                     }
                 }
+
+                for (key, value) in balances_events {
+                    rpc.notify_clients_for_key(&NotifyEvent::BalanceUpdated, &key, value).await;
+                }
+
+                for (key, event) in received_events {
+                    rpc.notify_clients_for_address(&NotifyEvent::TransactionReceived, &key, json!(event)).await;
+                }
             });
         }
+        timings.record(BlockProcessingStage::Notification, notify_start);
+
+        if self.log_block_timings {
+            debug!("Block {} processing timings: {}", block_hash, timings);
+        }
+        self.block_processing_stats.lock().await.record(&timings);
 
         Ok(())
     }
@@ -2267,16 +3340,7 @@ impl<S: Storage> Blockchain<S> {
     // Block shouldn't be orphaned
     pub async fn internal_get_block_reward(&self, past_supply: u64, is_side_block: bool, side_blocks_count: u64) -> Result<u64, BlockchainError> {
         trace!("internal get block reward");
-        let block_reward = if is_side_block {
-            let reward = get_block_reward(past_supply);
-            let side_block_percent = side_block_reward_percentage(side_blocks_count);
-            trace!("side block reward: {}%", side_block_percent);
-
-            reward * side_block_percent / 100
-        } else {
-            get_block_reward(past_supply)
-        };
-        Ok(block_reward)
+        Ok(get_block_reward_for(past_supply, is_side_block, side_blocks_count))
     }
 
     // Get the block reward for a block
@@ -2408,8 +3472,33 @@ impl<S: Storage> Blockchain<S> {
         self.rewind_chain_for_storage(&mut storage, count, until_stable_height).await
     }
 
+    // Pop the last N blocks from the chain on user request
+    // Refuse to go deeper than MAX_BLOCK_REWIND blocks if not forced
+    // and refuse while a chain sync is in progress to not conflict with it
+    pub async fn pop_blocks(&self, count: u64, force: bool) -> Result<RewindSummary, BlockchainError> {
+        if count > MAX_BLOCK_REWIND && !force {
+            return Err(BlockchainError::RewindTooDeep(count, MAX_BLOCK_REWIND))
+        }
+
+        if let Some(p2p) = self.p2p.read().await.as_ref() {
+            if p2p.is_syncing_chain() {
+                return Err(BlockchainError::ChainSyncInProgress)
+            }
+        }
+
+        let mut storage = self.storage.write().await;
+        self.rewind_chain_with_summary(&mut storage, count, false).await
+    }
+
     // Rewind the chain by removing N blocks from the top
     pub async fn rewind_chain_for_storage(&self, storage: &mut S, count: u64, stop_at_stable_height: bool) -> Result<u64, BlockchainError> {
+        self.rewind_chain_with_summary(storage, count, stop_at_stable_height).await
+            .map(|summary| summary.topoheight)
+    }
+
+    // Rewind the chain by removing N blocks from the top
+    // Websocket clients are notified of the orphaned blocks and transactions
+    async fn rewind_chain_with_summary(&self, storage: &mut S, count: u64, stop_at_stable_height: bool) -> Result<RewindSummary, BlockchainError> {
         trace!("rewind chain with count = {}", count);
         let current_height = self.get_height();
         let current_topoheight = self.get_topo_height();
@@ -2419,52 +3508,140 @@ impl<S: Storage> Blockchain<S> {
         } else {
             0
         };
-        let (new_height, new_topoheight, txs) = storage.pop_blocks(current_height, current_topoheight, count, until).await?;
-        debug!("New topoheight: {} (diff: {})", new_topoheight, current_topoheight - new_topoheight);
 
-        // Try to add all txs back to mempool if possible
-        // We try to prevent lost/to be orphaned
-        {
+        let (should_track_events, watched_keys) = if let Some(rpc) = self.rpc.read().await.as_ref() {
+            (rpc.get_tracked_events().await, rpc.get_subscribed_keys().await)
+        } else {
+            (HashSet::new(), HashSet::new())
+        };
+
+        // Keep the blocks that may be deleted to notify them once orphaned
+        let mut blocks_at_topoheight = Vec::new();
+        if should_track_events.contains(&NotifyEvent::BlockOrphaned) {
+            let pruned_topoheight = storage.get_pruned_topoheight().await?.unwrap_or(0);
+            let lowest_topoheight = current_topoheight.saturating_sub(count).max(pruned_topoheight);
+            for topoheight in (lowest_topoheight + 1)..=current_topoheight {
+                blocks_at_topoheight.push((storage.get_hash_at_topo_height(topoheight).await?, topoheight));
+            }
+        }
+
+        let (new_height, new_topoheight, txs) = storage.pop_blocks(current_height, current_topoheight, count, until).await?;
+        debug!("New topoheight: {} (diff: {})", new_topoheight, current_topoheight - new_topoheight);
+
+        // All the rewinded txs are not executed anymore, even if added back to mempool
+        let mut received_events = Vec::new();
+        if !watched_keys.is_empty() {
+            for (hash, tx) in txs.iter() {
+                received_events.extend(get_transaction_received_events(tx, hash, None, &watched_keys, storage.is_mainnet()));
+            }
+        }
+
+        // Try to add all txs back to mempool if possible
+        // We try to prevent lost/to be orphaned
+        let mut txs_reinjected = 0;
+        let mut orphaned_txs = Vec::new();
+        {
             for (hash, tx) in txs {
                 debug!("Trying to add TX {} to mempool again", hash);
-                if let Err(e) = self.add_tx_to_mempool_with_storage_and_hash(storage, tx, hash, false).await {
+                if let Err(e) = self.add_tx_to_mempool_with_storage_and_hash(storage, tx.clone(), hash.clone(), false).await {
                     debug!("TX rewinded is not compatible anymore: {}", e);
+                    orphaned_txs.push((hash, tx));
+                } else {
+                    txs_reinjected += 1;
                 }
             }
         }
 
-        self.height.store(new_height, Ordering::Release);
-        self.topoheight.store(new_topoheight, Ordering::Release);
+        // track all events to notify websocket
+        let mut events: HashMap<NotifyEvent, Vec<Value>> = HashMap::new();
+        for (hash, topoheight) in blocks_at_topoheight.into_iter().filter(|(_, topoheight)| *topoheight > new_topoheight) {
+            let value = json!(BlockOrphanedEvent {
+                block_hash: Cow::Owned(hash),
+                old_topoheight: topoheight,
+            });
+            events.entry(NotifyEvent::BlockOrphaned).or_insert_with(Vec::new).push(value);
+        }
+
+        if should_track_events.contains(&NotifyEvent::TransactionOrphaned) {
+            for (hash, tx) in orphaned_txs.iter() {
+                let data = RPCTransaction::from_tx(tx, hash, storage.is_mainnet());
+                let data = TransactionResponse {
+                    blocks: None,
+                    executed_in_block: None,
+                    in_mempool: false,
+                    first_seen: None,
+                    first_seen_human: None,
+                    data,
+                };
+                events.entry(NotifyEvent::TransactionOrphaned).or_insert_with(Vec::new).push(json!(data));
+            }
+        }
+
         // update stable height if it's allowed
-        if !stop_at_stable_height {
-            let tips = storage.get_tips().await?;
+        let tips = storage.get_tips().await?;
+        let (stable_height, stable_topoheight) = if !stop_at_stable_height {
             let (stable_hash, stable_height) = self.find_common_base::<S, _>(&storage, &tips).await?;
 
-            // if we have a RPC server, propagate the StableHeightChanged if necessary
+            // propagate the StableHeightChanged if necessary
+            let previous_stable_height = self.get_stable_height();
+            if stable_height != previous_stable_height && should_track_events.contains(&NotifyEvent::StableHeightChanged) {
+                let value = json!(StableHeightChangedEvent {
+                    previous_stable_height,
+                    new_stable_height: stable_height
+                });
+                events.entry(NotifyEvent::StableHeightChanged).or_insert_with(Vec::new).push(value);
+            }
+            let stable_topoheight = storage.get_topo_height_for_hash(&stable_hash).await?;
+            (stable_height, stable_topoheight)
+        } else {
+            (self.get_stable_height(), self.get_stable_topoheight())
+        };
+
+        // Update the whole cache at once
+        let (difficulty, _) = self.get_difficulty_at_tips(&*storage, tips.iter()).await?;
+        let hash = storage.get_hash_at_topo_height(new_topoheight).await?;
+        let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(&hash).await?;
+        let block = self.build_block_summary(&*storage, &hash).await?;
+        self.top_state.set(TopState {
+            hash,
+            height: new_height,
+            topoheight: new_topoheight,
+            difficulty,
+            cumulative_difficulty,
+            stable_height,
+            stable_topoheight,
+            pruned_topoheight: self.top_state.get_pruned_topoheight(),
+            block: Some(block)
+        });
+
+        // Popped blocks are not part of the solve times anymore
+        self.load_block_time_tracker(&*storage).await?;
+
+        if !events.is_empty() || !received_events.is_empty() {
             if let Some(rpc) = self.rpc.read().await.as_ref() {
-                let previous_stable_height = self.get_stable_height();
-                if stable_height != previous_stable_height {
-                    if rpc.is_event_tracked(&NotifyEvent::StableHeightChanged).await {
-                        let rpc = rpc.clone();
-                        spawn_task("rpc-notify-stable-height", async move {
-                            let event = json!(StableHeightChangedEvent {
-                                previous_stable_height,
-                                new_stable_height: stable_height
-                            });
-    
-                            if let Err(e) = rpc.notify_clients(&NotifyEvent::StableHeightChanged, event).await {
-                                debug!("Error while broadcasting event StableHeightChanged to websocket: {}", e);
+                let rpc = rpc.clone();
+                spawn_task("rpc-notify-rewind", async move {
+                    for (event, values) in events {
+                        for value in values {
+                            if let Err(e) = rpc.notify_clients(&event, value).await {
+                                debug!("Error while broadcasting event to websocket: {}", e);
                             }
-                        });
+                        }
                     }
-                }
+
+                    for (key, event) in received_events {
+                        rpc.notify_clients_for_address(&NotifyEvent::TransactionReceived, &key, json!(event)).await;
+                    }
+                });
             }
-            self.stable_height.store(stable_height, Ordering::SeqCst);
-            let stable_topoheight = storage.get_topo_height_for_hash(&stable_hash).await?;
-            self.stable_topoheight.store(stable_topoheight, Ordering::SeqCst);
         }
 
-        Ok(new_topoheight)
+        Ok(RewindSummary {
+            topoheight: new_topoheight,
+            removed_blocks: current_topoheight - new_topoheight,
+            txs_reinjected,
+            txs_orphaned: orphaned_txs.len()
+        })
     }
 
     // Calculate the average block time on the last 50 blocks
@@ -2472,7 +3649,7 @@ impl<S: Storage> Blockchain<S> {
     // We calculate it by taking the timestamp of the block at topoheight - 50 and the timestamp of the block at topoheight
     // It is the same as computing the average time between the last 50 blocks but much faster
     // Genesis block timestamp isn't take in count for this calculation
-    pub async fn get_average_block_time<P>(&self, provider: &P) -> Result<TimestampMillis, BlockchainError>
+    pub async fn get_average_block_time<P>(&self, provider: &P) -> Result<u64, BlockchainError>
     where
         P: DifficultyProvider + PrunedTopoheightProvider + DagOrderProvider
     {
@@ -2511,6 +3688,12 @@ impl<S: Storage> Blockchain<S> {
 
 // Estimate the required fees for a transaction
 pub async fn estimate_required_tx_fees<P: AccountProvider>(provider: &P, current_topoheight: u64, tx: &Transaction) -> Result<u64, BlockchainError> {
+    estimate_required_tx_fees_with_fee_per_kb(provider, current_topoheight, tx, FEE_PER_KB).await
+}
+
+// Estimate the required fees for a transaction with a custom fee per KB
+// This is used by the mempool policy only, consensus always use FEE_PER_KB
+pub async fn estimate_required_tx_fees_with_fee_per_kb<P: AccountProvider>(provider: &P, current_topoheight: u64, tx: &Transaction, fee_per_kb: u64) -> Result<u64, BlockchainError> {
     let mut output_count = 0;
     let mut new_addresses = 0;
     if let TransactionType::Transfers(transfers) = tx.get_data() {
@@ -2522,7 +3705,148 @@ pub async fn estimate_required_tx_fees<P: AccountProvider>(provider: &P, current
         }
     }
 
-    Ok(calculate_tx_fee(tx.size(), output_count, new_addresses))
+    let extra_data_size = tx.get_extra_data().as_ref().map_or(0, |data| data.len());
+    Ok(calculate_tx_fee_with_fee_per_kb(tx.size(), output_count, new_addresses, fee_per_kb) + calculate_extra_data_fee(extra_data_size))
+}
+
+// Build the TransactionReceived events for each watched address touched by the transaction
+// `executed` is the block hash and topoheight where it got executed, none if orphaned
+// Confirmations are set by the caller once the new topoheight is known
+pub fn get_transaction_received_events(tx: &Transaction, tx_hash: &Hash, executed: Option<(&Hash, u64)>, watched_keys: &HashSet<PublicKey>, mainnet: bool) -> Vec<(PublicKey, TransactionReceivedEvent<'static>)> {
+    // Assets moved for each touched account, the source first
+    let mut touched: IndexMap<&PublicKey, (bool, IndexSet<&Hash>)> = IndexMap::new();
+    let source = tx.get_source();
+    if watched_keys.contains(source) {
+        touched.insert(source, (true, IndexSet::new()));
+    }
+
+    match tx.get_data() {
+        TransactionType::Transfers(transfers) => {
+            for transfer in transfers {
+                if let Some((_, assets)) = touched.get_mut(source) {
+                    assets.insert(transfer.get_asset());
+                }
+
+                let destination = transfer.get_destination();
+                if destination != source && watched_keys.contains(destination) {
+                    touched.entry(destination).or_insert_with(|| (false, IndexSet::new())).1.insert(transfer.get_asset());
+                }
+            }
+        },
+        TransactionType::Burn(payload) => {
+            if let Some((_, assets)) = touched.get_mut(source) {
+                assets.insert(&payload.asset);
+            }
+        },
+        TransactionType::AssetCreation(_) => {
+            if let Some((_, assets)) = touched.get_mut(source) {
+                assets.insert(&XELIS_ASSET);
+            }
+        }
+    }
+
+    touched.into_iter().map(|(key, (outgoing, assets))| {
+        let event = TransactionReceivedEvent {
+            tx_hash: Cow::Owned(tx_hash.clone()),
+            address: key.as_address(mainnet),
+            outgoing,
+            assets: assets.into_iter().cloned().collect(),
+            block_hash: executed.map(|(hash, _)| Cow::Owned(hash.clone())),
+            topoheight: executed.map(|(_, topoheight)| topoheight),
+            confirmations: 0,
+            orphaned: executed.is_none()
+        };
+        (key.clone(), event)
+    }).collect()
+}
+
+// Verify that the type of a TX and its extra data are activated at the height of the block including it
+// Until then, no extra data fee can be required either
+pub fn verify_tx_type_activated(network: &Network, height: u64, tx_hash: &Hash, tx: &Transaction) -> Result<(), BlockchainError> {
+    if let TransactionType::AssetCreation(_) = tx.get_data() {
+        if height < get_asset_creation_activation_height(network) {
+            debug!("TX {} creates an asset before its activation height", tx_hash);
+            return Err(BlockchainError::TxTypeNotActivated(tx_hash.clone(), height))
+        }
+    }
+
+    if tx.get_extra_data().is_some() && height < get_extra_data_activation_height(network) {
+        debug!("TX {} has extra data before its activation height", tx_hash);
+        return Err(BlockchainError::TxExtraDataNotActivated(tx_hash.clone(), height))
+    }
+
+    Ok(())
+}
+
+// Verify that the keys of a TX are valid points other than the identity once activated
+// Keys are read without validation, the identity key would decompress without any error
+pub fn verify_tx_public_keys(network: &Network, height: u64, tx_hash: &Hash, tx: &Transaction) -> Result<(), BlockchainError> {
+    if height < get_public_keys_validation_activation_height(network) {
+        return Ok(())
+    }
+
+    tx.get_source().validate()
+        .map_err(|e| BlockchainError::InvalidTxPublicKey(tx_hash.clone(), e))?;
+
+    if let TransactionType::Transfers(transfers) = tx.get_data() {
+        for transfer in transfers {
+            if let Err(e) = transfer.get_destination().validate() {
+                debug!("TX {} has an invalid destination key: {}", tx_hash, e);
+                return Err(BlockchainError::InvalidTxPublicKey(tx_hash.clone(), e))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Verify that a block doesn't contain the same transaction multiple times
+// Otherwise the same balance changes could be applied twice
+pub fn verify_unique_txs<'a, I: IntoIterator<Item = &'a Hash>>(block_hash: &Hash, txs: I) -> Result<(), BlockchainError> {
+    let mut seen = HashSet::new();
+    for tx in txs {
+        if !seen.insert(tx) {
+            debug!("Block {} contains the tx {} multiple times", block_hash, tx);
+            return Err(BlockchainError::DuplicateTxInBlock(block_hash.clone(), tx.clone()))
+        }
+    }
+
+    Ok(())
+}
+
+// Get the transactions of an ordered block in the order they were processed during its execution
+// Each one is marked as executed by it, already executed by a previous block in the DAG, or failed
+pub async fn get_block_transactions_execution<S: Storage>(storage: &S, hash: &Hash) -> Result<Vec<BlockExecutedTransaction<'static>>, BlockchainError> {
+    if !storage.is_block_topological_ordered(hash).await {
+        return Err(BlockchainError::BlockNotOrdered)
+    }
+
+    let topoheight = storage.get_topo_height_for_hash(hash).await?;
+    let header = storage.get_block_header_by_hash(hash).await?;
+    let mut transactions = Vec::with_capacity(header.get_txs_count());
+    for tx_hash in header.get_txs_hashes() {
+        let executor = if storage.is_tx_executed_in_a_block(tx_hash)? {
+            Some(storage.get_block_executor_for_tx(tx_hash)?)
+        } else {
+            None
+        };
+
+        let (status, execution_index) = match &executor {
+            Some(executor) if executor == hash => (TransactionExecutionStatus::Executed, storage.get_tx_execution_index(tx_hash)?),
+            Some(executor) if storage.get_topo_height_for_hash(executor).await? < topoheight => (TransactionExecutionStatus::AlreadyExecuted, None),
+            // Not executed at all, or by a block ordered after this one
+            _ => (TransactionExecutionStatus::Failed, None)
+        };
+
+        transactions.push(BlockExecutedTransaction {
+            hash: Cow::Owned(tx_hash.clone()),
+            status,
+            execution_index,
+            executed_in_block: executor.map(Cow::Owned)
+        });
+    }
+
+    Ok(transactions)
 }
 
 // Get the block reward for a side block based on how many side blocks exists at same height
@@ -2553,6 +3877,67 @@ pub fn get_block_reward(supply: u64) -> u64 {
     base_reward * BLOCK_TIME_MILLIS / MILLIS_PER_SECOND / 180
 }
 
+// Calculate the block reward based on the current supply and the type of the block
+// A side block only receive a percentage of the reward depending on how many side blocks exists at same height
+pub fn get_block_reward_for(past_supply: u64, is_side_block: bool, side_blocks_count: u64) -> u64 {
+    let reward = get_block_reward(past_supply);
+    if is_side_block {
+        let side_block_percent = side_block_reward_percentage(side_blocks_count);
+        trace!("side block reward: {}%", side_block_percent);
+
+        reward * side_block_percent / 100
+    } else {
+        reward
+    }
+}
+
+// Split the block reward between the miner and the dev fee
+// Dev fee are only applied on block reward, transaction fees are fully given to the miner
+// Returns the miner part (including fees) and the dev fee part
+pub fn split_block_reward(block_reward: u64, total_fees: u64, height: u64) -> Result<(u64, u64), BlockchainError> {
+    let dev_fee_part = block_reward * get_block_dev_fee(height) / 100;
+    let miner_reward = (block_reward - dev_fee_part).checked_add(total_fees)
+        .ok_or(BlockchainError::BlockRewardOverflow(block_reward, total_fees))?;
+
+    Ok((miner_reward, dev_fee_part))
+}
+
+// Add the fee of a TX to the total fees of a block
+// The total must still fit in the miner reward with the highest block reward possible
+// Returns None if it overflows
+pub fn add_block_fee(total_fees: u64, fee: u64) -> Option<u64> {
+    let total_fees = total_fees.checked_add(fee)?;
+    get_block_reward(0).checked_add(total_fees)?;
+    Some(total_fees)
+}
+
+// Verify that the fees of all TXs in a block can be summed without overflow
+// Returns the total fees of the block
+pub fn verify_block_fees<I: IntoIterator<Item = u64>>(block_hash: &Hash, fees: I) -> Result<u64, BlockchainError> {
+    let mut total_fees = 0;
+    for fee in fees {
+        total_fees = match add_block_fee(total_fees, fee) {
+            Some(total_fees) => total_fees,
+            None => {
+                warn!("Block {} is rejected, its TXs fees are overflowing: {} + {}", block_hash, total_fees, fee);
+                return Err(BlockchainError::BlockFeesOverflow(block_hash.clone()))
+            }
+        };
+    }
+
+    Ok(total_fees)
+}
+
+// Verify that the total amount rewarded for a block is exactly the emission plus the fees of its executed transactions
+pub fn verify_block_reward(block_reward: u64, total_fees: u64, rewarded: u64) -> Result<(), BlockchainError> {
+    let expected = block_reward.checked_add(total_fees).ok_or(BlockchainError::Overflow)?;
+    if rewarded != expected {
+        return Err(BlockchainError::InvalidBlockReward(expected, rewarded))
+    }
+
+    Ok(())
+}
+
 // Returns the fee percentage for a block at a given height
 pub fn get_block_dev_fee(height: u64) -> u64 {
     let mut percentage = 0;
@@ -2581,6 +3966,34 @@ pub fn get_block_dev_fee(height: u64) -> u64 {
 
 #[cfg(test)]
 mod tests {
+    use xelis_common::{
+        account::{CiphertextCache, VersionedBalance, VersionedNonce},
+        api::daemon::NewBlockEvent,
+        block::compute_txs_merkle_root,
+        config::{COIN_VALUE, VERSION},
+        crypto::{elgamal::PublicKeyError, Address, KeyPair},
+        network::Network,
+        rpc_server::RPCServerHandler,
+        testing::TempDir,
+        transaction::builder::FeeBuilder
+    };
+    use crate::{
+        core::{
+            chain_stats::get_chain_stats,
+            state::{build_asset_creation, build_transfer, build_transfer_to, build_transfer_with_extra_data, build_transfer_with_fee, BALANCE},
+            storage::{
+                test_utils::{open_storage, open_temp_storage},
+                AssetProvider,
+                BalanceProvider,
+                BlockDagProvider,
+                BlockProvider,
+                NonceProvider,
+                SledStorage,
+                TransactionProvider
+            }
+        },
+        testing::get_dev_genesis_block
+    };
     use super::*;
 
     #[test]
@@ -2591,6 +4004,81 @@ mod tests {
         assert_eq!(side_block_reward_percentage(3), SIDE_BLOCK_REWARD_MIN_PERCENT);
     }
 
+    #[test]
+    fn test_block_reward_verification() {
+        let supply = 1_000 * COIN_VALUE;
+        let height = 100;
+        let fees = 12_345;
+        let block_reward = get_block_reward_for(supply, false, 0);
+        assert_eq!(block_reward, get_block_reward(supply));
+
+        // Correct block
+        let (miner_reward, dev_fee_part) = split_block_reward(block_reward, fees, height).unwrap();
+        assert_eq!(dev_fee_part, block_reward * get_block_dev_fee(height) / 100);
+        assert_eq!(miner_reward + dev_fee_part, block_reward + fees);
+        assert!(verify_block_reward(block_reward, fees, miner_reward + dev_fee_part).is_ok());
+
+        // Inflated reward
+        assert!(matches!(
+            verify_block_reward(block_reward, fees, miner_reward + dev_fee_part + 1),
+            Err(BlockchainError::InvalidBlockReward(expected, actual)) if expected == block_reward + fees && actual == expected + 1
+        ));
+
+        // Fees of the block not given
+        assert!(verify_block_reward(block_reward, fees, block_reward).is_err());
+    }
+
+    #[test]
+    fn test_block_fees_overflow() {
+        let hash = Hash::zero();
+        let max_reward = get_block_reward(0);
+        let max_fees = u64::MAX - max_reward;
+        assert_eq!(get_block_reward(u64::MAX), 0);
+
+        assert_eq!(verify_block_fees(&hash, [max_fees]).unwrap(), max_fees);
+        assert!(matches!(verify_block_fees(&hash, [max_fees, 1]), Err(BlockchainError::BlockFeesOverflow(_))));
+        assert!(matches!(verify_block_fees(&hash, [u64::MAX, u64::MAX]), Err(BlockchainError::BlockFeesOverflow(_))));
+        assert!(matches!(split_block_reward(max_reward, u64::MAX, 0), Err(BlockchainError::BlockRewardOverflow(_, _))));
+
+        // Adversarial fees near u64::MAX
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let fees: Vec<u64> = (0..rng.gen_range(1..4)).map(|_| u64::MAX - rng.gen_range(0..=max_reward * 2)).collect();
+            let expected = fees.iter().try_fold(0u64, |total, fee| total.checked_add(*fee))
+                .filter(|total| max_reward.checked_add(*total).is_some());
+
+            match verify_block_fees(&hash, fees.iter().copied()) {
+                Ok(total_fees) => {
+                    assert_eq!(Some(total_fees), expected);
+                    // Any block reward can be paid with these fees
+                    let block_reward = rng.gen_range(0..=max_reward);
+                    let height = rng.gen_range(0..10_000_000);
+                    let (miner_reward, dev_fee_part) = split_block_reward(block_reward, total_fees, height).unwrap();
+                    assert!(verify_block_reward(block_reward, total_fees, miner_reward + dev_fee_part).is_ok());
+                },
+                Err(e) => {
+                    assert!(expected.is_none());
+                    assert!(matches!(e, BlockchainError::BlockFeesOverflow(_)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_side_block_reward() {
+        let supply = 1_000 * COIN_VALUE;
+        let reward = get_block_reward(supply);
+        assert_eq!(get_block_reward_for(supply, true, 0), reward * SIDE_BLOCK_REWARD_PERCENT / 100);
+        assert_eq!(get_block_reward_for(supply, true, 1), reward * (SIDE_BLOCK_REWARD_PERCENT / 2) / 100);
+        assert_eq!(get_block_reward_for(supply, true, SIDE_BLOCK_REWARD_MAX_BLOCKS), reward * SIDE_BLOCK_REWARD_MIN_PERCENT / 100);
+
+        let side_reward = get_block_reward_for(supply, true, 0);
+        let (miner_reward, dev_fee_part) = split_block_reward(side_reward, 0, 0).unwrap();
+        assert!(verify_block_reward(side_reward, 0, miner_reward + dev_fee_part).is_ok());
+        // A side block can't be given the full reward
+        assert!(verify_block_reward(side_reward, 0, reward).is_err());
+    }
+
     #[test]
     fn test_block_dev_fee() {
         assert_eq!(get_block_dev_fee(0), 10);
@@ -2606,4 +4094,1499 @@ mod tests {
         assert_eq!(get_block_dev_fee(DEV_FEES[1].height), 5);
         assert_eq!(get_block_dev_fee(DEV_FEES[1].height + 1), 5);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_block_with_duplicated_tx() {
+        let block_hash = Hash::zero();
+        let a = Hash::new([1u8; 32]);
+        let b = Hash::new([2u8; 32]);
+
+        assert!(verify_unique_txs(&block_hash, [&a, &b]).is_ok());
+        assert!(matches!(
+            verify_unique_txs(&block_hash, [&a, &b, &a]),
+            Err(BlockchainError::DuplicateTxInBlock(_, tx)) if tx == a
+        ));
+    }
+
+    #[test]
+    fn test_asset_creation_activation() {
+        let sender = KeyPair::new();
+        let tx = build_asset_creation(&sender, BALANCE * 2, 8);
+        let tx_hash = tx.hash();
+
+        // Not scheduled yet on mainnet
+        assert!(matches!(
+            verify_tx_type_activated(&Network::Mainnet, 100, &tx_hash, &tx),
+            Err(BlockchainError::TxTypeNotActivated(hash, 100)) if hash == tx_hash
+        ));
+        assert!(verify_tx_type_activated(&Network::Dev, 0, &tx_hash, &tx).is_ok());
+
+        // Other types are always accepted
+        let transfer = build_transfer(&sender, &KeyPair::new(), BALANCE, COIN_VALUE);
+        assert!(verify_tx_type_activated(&Network::Mainnet, 100, &transfer.hash(), &transfer).is_ok());
+    }
+
+    #[test]
+    fn test_extra_data_activation() {
+        let sender = KeyPair::new();
+        let tx = build_transfer_with_extra_data(&sender, &KeyPair::new(), BALANCE, COIN_VALUE, vec![1u8; 32]);
+        let tx_hash = tx.hash();
+
+        // Not scheduled yet on mainnet
+        assert!(matches!(
+            verify_tx_type_activated(&Network::Mainnet, 100, &tx_hash, &tx),
+            Err(BlockchainError::TxExtraDataNotActivated(hash, 100)) if hash == tx_hash
+        ));
+        assert!(verify_tx_type_activated(&Network::Dev, 0, &tx_hash, &tx).is_ok());
+    }
+
+    #[test]
+    fn test_public_keys_validation_activation() {
+        let sender = KeyPair::new();
+        let identity = PublicKey::from_bytes(&[0u8; 32]).unwrap();
+        let tx = build_transfer_to(&sender, identity.to_address(false), BALANCE, COIN_VALUE, FeeBuilder::Multiplier(1f64));
+        let tx_hash = tx.hash();
+
+        // Not scheduled yet on mainnet, its blocks may contain such keys
+        assert!(verify_tx_public_keys(&Network::Mainnet, 100, &tx_hash, &tx).is_ok());
+        assert!(matches!(
+            verify_tx_public_keys(&Network::Dev, 0, &tx_hash, &tx),
+            Err(BlockchainError::InvalidTxPublicKey(hash, PublicKeyError::Identity)) if hash == tx_hash
+        ));
+
+        let transfer = build_transfer(&sender, &KeyPair::new(), BALANCE, COIN_VALUE);
+        assert!(verify_tx_public_keys(&Network::Dev, 0, &transfer.hash(), &transfer).is_ok());
+    }
+
+    #[test]
+    fn test_sibling_blocks_sharing_tx() {
+        let (_dir, mut storage) = open_temp_storage("sibling-blocks");
+        let tx = Hash::new([1u8; 32]);
+        let first = Hash::new([2u8; 32]);
+        let second = Hash::new([3u8; 32]);
+
+        // Both sibling blocks include the same tx
+        assert!(storage.add_block_linked_to_tx_if_not_present(&tx, &first).unwrap());
+        assert!(storage.add_block_linked_to_tx_if_not_present(&tx, &second).unwrap());
+
+        // First block in topological order execute it
+        assert!(!storage.is_tx_executed_in_a_block(&tx).unwrap());
+        storage.set_tx_executed_in_block(&tx, &first, 0).unwrap();
+
+        // Second block must see it as already executed and skip it
+        assert!(storage.is_tx_executed_in_a_block(&tx).unwrap());
+        assert!(storage.is_tx_executed_in_block(&tx, &first).unwrap());
+        assert!(!storage.is_tx_executed_in_block(&tx, &second).unwrap());
+        assert_eq!(storage.get_block_executor_for_tx(&tx).unwrap(), first);
+    }
+
+    // Store a block including the txs at the given topoheight
+    async fn save_block_with_txs(storage: &mut SledStorage, topoheight: u64, txs: &[Arc<Transaction>]) -> Hash {
+        let txs: Vec<Immutable<Transaction>> = txs.iter().map(|tx| Immutable::Arc(tx.clone())).collect();
+        let mut extra_nonce = [0u8; EXTRA_NONCE_SIZE];
+        extra_nonce[0..8].copy_from_slice(&topoheight.to_be_bytes());
+        let miner = KeyPair::new().get_public_key().compress();
+        let header = BlockHeader::new(0, 0, topoheight, IndexSet::new(), extra_nonce, miner, txs.iter().map(|tx| tx.hash()).collect());
+        let hash = header.hash();
+
+        storage.save_block(Arc::new(header), &txs, Difficulty::from_u64(1), VarUint::one(), hash.clone()).await.unwrap();
+        storage.set_topo_height_for_block(&hash, topoheight).await.unwrap();
+        storage.set_cumulative_difficulty_for_block_hash(&hash, CumulativeDifficulty::from_u64(topoheight)).await.unwrap();
+        storage.set_supply_at_topo_height(topoheight, 0).unwrap();
+        storage.set_block_reward_at_topo_height(topoheight, 0).unwrap();
+        hash
+    }
+
+    async fn test_delete_sibling_blocks_sharing_tx(name: &str, first_deleted: u64) {
+        let (_dir, mut storage) = open_temp_storage(name);
+        let tx = Arc::new(build_transfer(&KeyPair::new(), &KeyPair::new(), 10 * COIN_VALUE, COIN_VALUE));
+        let tx_hash = tx.hash();
+
+        // Both sibling blocks include the same tx, it is stored only one time
+        let blocks = [
+            save_block_with_txs(&mut storage, 0, &[tx.clone()]).await,
+            save_block_with_txs(&mut storage, 1, &[tx.clone()]).await
+        ];
+        assert_eq!(storage.count_transactions().await.unwrap(), 1);
+        assert_eq!(storage.get_blocks_for_tx(&tx_hash).unwrap().len(), 2);
+        storage.set_tx_executed_in_block(&tx_hash, &blocks[0], 0).unwrap();
+
+        // Delete one block, the tx is still referenced by the other one
+        let (hash, _, txs) = storage.delete_block_at_topoheight(first_deleted).await.unwrap();
+        assert_eq!(hash, blocks[first_deleted as usize]);
+        assert!(txs.is_empty());
+        assert!(storage.has_transaction(&tx_hash).await.unwrap());
+
+        let remaining = &blocks[1 - first_deleted as usize];
+        let linked = storage.get_blocks_for_tx(&tx_hash).unwrap();
+        assert_eq!(linked.len(), 1);
+        assert!(linked.contains(remaining));
+        // Executed mark is removed only with the block that executed it
+        assert_eq!(storage.is_tx_executed_in_a_block(&tx_hash).unwrap(), first_deleted == 1);
+
+        assert!(matches!(
+            storage.delete_transaction(&tx_hash).await,
+            Err(BlockchainError::TransactionStillReferenced(_, 1))
+        ));
+
+        // Last reference deleted, the tx is deleted too
+        let (hash, _, txs) = storage.delete_block_at_topoheight(1 - first_deleted).await.unwrap();
+        assert_eq!(hash, *remaining);
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].0, tx_hash);
+        assert!(!storage.has_transaction(&tx_hash).await.unwrap());
+        assert!(!storage.has_tx_blocks(&tx_hash).unwrap());
+        assert!(!storage.is_tx_executed_in_a_block(&tx_hash).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_delete_latest_sibling_block_sharing_tx() {
+        test_delete_sibling_blocks_sharing_tx("delete-latest-sibling", 1).await;
+    }
+
+    #[tokio::test]
+    async fn test_delete_first_sibling_block_sharing_tx() {
+        test_delete_sibling_blocks_sharing_tx("delete-first-sibling", 0).await;
+    }
+
+    #[tokio::test]
+    async fn test_block_transactions_execution() {
+        let (_dir, mut storage) = open_temp_storage("block-txs-execution");
+        let txs: Vec<Arc<Transaction>> = (0..3).map(|_| Arc::new(build_transfer(&KeyPair::new(), &KeyPair::new(), 10 * COIN_VALUE, COIN_VALUE))).collect();
+        let hashes: Vec<Hash> = txs.iter().map(|tx| tx.hash()).collect();
+
+        // Sibling blocks both include the first tx, it is executed by the first one ordered
+        let first = save_block_with_txs(&mut storage, 0, &txs[0..1]).await;
+        let second = save_block_with_txs(&mut storage, 1, &txs).await;
+        storage.set_tx_executed_in_block(&hashes[0], &first, 0).unwrap();
+        // Second tx is executed by the second block and the last one is rejected
+        storage.set_tx_executed_in_block(&hashes[1], &second, 0).unwrap();
+
+        let executed = get_block_transactions_execution(&storage, &first).await.unwrap();
+        assert_eq!(executed.len(), 1);
+        assert_eq!(executed[0].status, TransactionExecutionStatus::Executed);
+        assert_eq!(executed[0].execution_index, Some(0));
+
+        let executed = get_block_transactions_execution(&storage, &second).await.unwrap();
+        let summary: Vec<_> = executed.iter().map(|tx| (tx.hash.as_ref(), tx.status, tx.execution_index, tx.executed_in_block.as_deref())).collect();
+        assert_eq!(summary, vec![
+            (&hashes[0], TransactionExecutionStatus::AlreadyExecuted, None, Some(&first)),
+            (&hashes[1], TransactionExecutionStatus::Executed, Some(0), Some(&second)),
+            (&hashes[2], TransactionExecutionStatus::Failed, None, None)
+        ]);
+
+        // Popping the second block removes its executions with their index
+        storage.delete_block_at_topoheight(1).await.unwrap();
+        assert!(!storage.is_tx_executed_in_a_block(&hashes[1]).unwrap());
+        assert_eq!(storage.get_tx_execution_index(&hashes[1]).unwrap(), None);
+        assert_eq!(storage.get_tx_execution_index(&hashes[0]).unwrap(), Some(0));
+        assert!(matches!(get_block_transactions_execution(&storage, &second).await, Err(BlockchainError::BlockNotOrdered)));
+    }
+
+    #[tokio::test]
+    async fn test_transaction_with_metadata() {
+        let (_dir, mut storage) = open_temp_storage("tx-metadata");
+        let tx = Arc::new(build_transfer(&KeyPair::new(), &KeyPair::new(), 10 * COIN_VALUE, COIN_VALUE));
+        let tx_hash = tx.hash();
+        let block = save_block_with_txs(&mut storage, 0, &[tx.clone()]).await;
+
+        // Included but not executed yet
+        let metadata = storage.get_transaction_with_metadata(&tx_hash).await.unwrap();
+        assert_eq!(metadata.tx.hash(), tx_hash);
+        assert_eq!(metadata.blocks.len(), 1);
+        assert!(metadata.blocks.contains(&block));
+        assert!(metadata.executed_in_block.is_none());
+        assert!(metadata.executed_at_topoheight.is_none());
+
+        storage.set_tx_executed_in_block(&tx_hash, &block, 0).unwrap();
+        let metadata = storage.get_transaction_with_metadata(&tx_hash).await.unwrap();
+        assert_eq!(metadata.executed_in_block, Some(block.clone()));
+        assert_eq!(metadata.executed_at_topoheight, Some(0));
+
+        // Reorg removes the executor but the block still includes it
+        storage.remove_tx_executed(&tx_hash).unwrap();
+        let metadata = storage.get_transaction_with_metadata(&tx_hash).await.unwrap();
+        assert_eq!(metadata.blocks, storage.get_blocks_for_tx(&tx_hash).unwrap());
+        assert!(metadata.blocks.contains(&block));
+        assert!(metadata.executed_in_block.is_none());
+        assert!(metadata.executed_at_topoheight.is_none());
+
+        assert!(storage.get_transaction_with_metadata(&Hash::zero()).await.is_err());
+    }
+
+    #[derive(clap::Parser)]
+    struct TestArgs {
+        #[clap(flatten)]
+        config: Config
+    }
+
+    // Start a dev node with the given flags, its data is stored in a temporary directory
+    async fn start_node(name: &str, flags: &[&str]) -> (TempDir, Arc<Blockchain<SledStorage>>) {
+        let dir = TempDir::new(name);
+        let mut args = vec!["--no-mempool-persistence"];
+        args.extend_from_slice(flags);
+        let blockchain = start_node_in(&dir, &args).await;
+        (dir, blockchain)
+    }
+
+    // Start a dev node on the data of a directory, to restart a node stopped before
+    async fn start_node_in(dir: &TempDir, flags: &[&str]) -> Arc<Blockchain<SledStorage>> {
+        use clap::Parser;
+
+        let dir_path = dir.to_dir_path();
+        let mut args = vec!["xelis_daemon", "--dir-path", &dir_path, "--skip-pow-verification"];
+        args.extend_from_slice(flags);
+        let config = TestArgs::parse_from(args).config;
+
+        // Same genesis block for all the nodes so they can connect to each other
+        let storage = open_storage(dir);
+        Blockchain::with_genesis(config, Network::Dev, storage, None, Some(get_dev_genesis_block())).await.unwrap()
+    }
+
+    // Same steps as the submit_block RPC method
+    async fn submit_block(blockchain: &Blockchain<SledStorage>) {
+        let miner = KeyPair::new().get_public_key().compress();
+        let header = blockchain.get_block_template(miner).await.unwrap();
+        let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+        blockchain.add_new_block(block, true, true).await.unwrap();
+    }
+
+    // Give a XELIS balance to the account at the genesis topoheight and reset its nonce
+    async fn fund_account(blockchain: &Blockchain<SledStorage>, account: &KeyPair, amount: u64) {
+        let mut storage = blockchain.get_storage().write().await;
+        let key = account.get_public_key().compress();
+        let balance = VersionedBalance::new(CiphertextCache::Decompressed(account.get_public_key().encrypt(amount)), None);
+        storage.set_last_balance_to(&key, &XELIS_ASSET, 0, &balance).await.unwrap();
+        storage.set_last_nonce_to(&key, 0, &VersionedNonce::new(0, None)).await.unwrap();
+    }
+
+    // Reserve a free local address for the P2P server of a node
+    fn free_local_address() -> SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    // Reserve a free IPv6 loopback address, None if IPv6 is not available on this host
+    fn free_local_ipv6_address() -> Option<SocketAddr> {
+        let listener = std::net::TcpListener::bind("[::1]:0").ok()?;
+        listener.local_addr().ok()
+    }
+
+    // Wait until the node has the expected count of peers
+    async fn wait_for_peers(p2p: &P2pServer<SledStorage>, count: usize) {
+        for _ in 0..100 {
+            if p2p.get_peer_count().await == count {
+                return
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("Expected {} peers, got {}", count, p2p.get_peer_count().await);
+    }
+
+    #[tokio::test]
+    async fn test_p2p_ipv6_loopback() {
+        let Some(second_addr) = free_local_ipv6_address() else {
+            // IPv6 is disabled on this host
+            return
+        };
+        let (first_v4_addr, first_v6_addr) = (free_local_address(), free_local_ipv6_address().unwrap());
+        let first_bind = format!("{},{}", first_v4_addr, first_v6_addr);
+        let (_first_dir, first) = start_node("ipv6-first", &["--disable-rpc-server", "--p2p-bind-address", &first_bind]).await;
+        let (_second_dir, second) = start_node("ipv6-second", &["--disable-rpc-server", "--p2p-bind-address", &second_addr.to_string()]).await;
+        let first_p2p = first.get_p2p().read().await.clone().unwrap();
+        let second_p2p = second.get_p2p().read().await.clone().unwrap();
+
+        // First node listens on both families, second one dials it over IPv6
+        second_p2p.try_to_connect_to_peer(first_v6_addr, false).await;
+        wait_for_peers(&first_p2p, 1).await;
+        wait_for_peers(&second_p2p, 1).await;
+
+        // Both sides see an IPv6 peer, the inbound one is reachable on the port of its handshake
+        let first_peer = second_p2p.get_peer_list().get_peer(&first_p2p.get_peer_id()).await.unwrap();
+        assert_eq!(*first_peer.get_outgoing_address(), first_v6_addr);
+        let second_peer = first_p2p.get_peer_list().get_peer(&second_p2p.get_peer_id()).await.unwrap();
+        assert!(second_peer.get_connection().get_address().is_ipv6());
+        assert_eq!(*second_peer.get_outgoing_address(), second_addr);
+
+        // Blocks are propagated over the IPv6 connection
+        submit_block(&first).await;
+        for _ in 0..100 {
+            if second.get_topo_height() == 1 {
+                break
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(second.get_top_block_hash().await.unwrap(), first.get_top_block_hash().await.unwrap());
+
+        first.stop().await;
+        second.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_whitelisted_peer_reconnected() {
+        let (first_addr, second_addr) = (free_local_address(), free_local_address());
+        let (_first_dir, first) = start_node("whitelist-first", &["--disable-rpc-server", "--p2p-bind-address", &first_addr.to_string()]).await;
+        let (_second_dir, second) = start_node("whitelist-second", &["--disable-rpc-server", "--p2p-bind-address", &second_addr.to_string(), "--p2p-whitelist", &first_addr.to_string()]).await;
+        let first_p2p = first.get_p2p().read().await.clone().unwrap();
+        let second_p2p = second.get_p2p().read().await.clone().unwrap();
+
+        // Second node connects by itself to its whitelisted peer, as a priority node
+        wait_for_peers(&second_p2p, 1).await;
+        let peer = second_p2p.get_peer_list().get_peer(&first_p2p.get_peer_id()).await.unwrap();
+        assert!(peer.is_priority());
+        assert_eq!(*peer.get_outgoing_address(), first_addr);
+
+        // It reconnects once the whitelisted peer closes the connection
+        let inbound = first_p2p.get_peer_list().get_peer(&second_p2p.get_peer_id()).await.unwrap();
+        inbound.close().await.unwrap();
+        let mut reconnected = None;
+        for _ in 0..200 {
+            match second_p2p.get_peer_list().get_peer(&first_p2p.get_peer_id()).await {
+                Some(new_peer) if !Arc::ptr_eq(&new_peer, &peer) => {
+                    reconnected = Some(new_peer);
+                    break
+                },
+                _ => tokio::time::sleep(Duration::from_millis(100)).await
+            }
+        }
+        assert!(reconnected.expect("whitelisted peer reconnected").is_priority());
+
+        first.stop().await;
+        second.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_node_without_p2p() {
+        let (_dir, blockchain) = start_node("no-p2p", &["--disable-p2p", "--rpc-bind-address", "127.0.0.1:0"]).await;
+        assert!(blockchain.get_p2p().read().await.is_none());
+        assert!(blockchain.get_rpc().read().await.is_some());
+
+        // Blocks are still accepted through RPC, and events are sent without any peer
+        let topoheight = blockchain.get_topo_height();
+        submit_block(&blockchain).await;
+        assert_eq!(blockchain.get_topo_height(), topoheight + 1);
+        let storage = blockchain.get_storage().read().await;
+        assert!(blockchain.is_synced_for_storage(&storage).await);
+        drop(storage);
+
+        blockchain.stop().await;
+    }
+
+    // Raw HTTP request calling the JSON RPC method on the path
+    fn json_rpc_http_request(host: &str, path: &str, method: &str, params: Value, authorization: Option<&str>) -> String {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }).to_string();
+        let mut request = format!("POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n", path, host, body.len());
+        if let Some(authorization) = authorization {
+            request.push_str(&format!("Authorization: Basic {}\r\n", authorization));
+        }
+        request.push_str("\r\n");
+        request.push_str(&body);
+        request
+    }
+
+    fn http_status(response: &str) -> u16 {
+        response.split_whitespace().nth(1).unwrap().parse().unwrap()
+    }
+
+    // Call the method on the public endpoint and return the JSON RPC response
+    async fn rpc_request(address: SocketAddr, method: &str, params: Value) -> Value {
+        use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
+
+        let request = json_rpc_http_request(&address.to_string(), "/json_rpc", method, params, None);
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_submit_transaction_idempotency_key() {
+        let address = free_local_address();
+        let (_dir, blockchain) = start_node("submit-idempotency", &["--disable-p2p", "--rpc-bind-address", &address.to_string()]).await;
+        let sender = KeyPair::new();
+        fund_account(&blockchain, &sender, BALANCE).await;
+        let tx = build_transfer(&sender, &KeyPair::new(), BALANCE, COIN_VALUE);
+        let params = json!({ "data": tx.to_hex(), "idempotency_key": "payment-1" });
+
+        // Payment retried while its first submission is in progress
+        let (first, second) = tokio::join!(
+            rpc_request(address, "submit_transaction", params.clone()),
+            rpc_request(address, "submit_transaction", params.clone())
+        );
+        assert_eq!(first["result"], json!(true));
+        assert_eq!(first, second);
+        assert_eq!(blockchain.get_mempool().read().await.size(), 1);
+
+        // Retried later, the response is replayed instead of an already in mempool error
+        assert_eq!(rpc_request(address, "submit_transaction", params).await, first);
+
+        // Without a key the retry is processed again
+        let duplicate = rpc_request(address, "submit_transaction", json!({ "data": tx.to_hex() })).await;
+        assert_eq!(duplicate["error"]["code"], json!(BlockchainError::TxAlreadyInMempool(tx.hash()).get_rpc_code()));
+        assert_eq!(blockchain.get_mempool().read().await.size(), 1);
+
+        blockchain.stop().await;
+    }
+
+    // Call the method on the admin endpoint and return the HTTP status code
+    async fn admin_request(address: SocketAddr, method: &str, params: Value, authorization: Option<&str>) -> u16 {
+        use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
+
+        let request = json_rpc_http_request(&address.to_string(), "/admin/json_rpc", method, params, authorization);
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        http_status(&response)
+    }
+
+    #[tokio::test]
+    async fn test_admin_methods_require_credentials() {
+        let address = free_local_address();
+        let (_dir, blockchain) = start_node("rpc-admin", &["--disable-p2p", "--rpc-bind-address", &address.to_string(), "--rpc-admin-password", "secret"]).await;
+        let rpc = blockchain.get_rpc().read().await.clone().unwrap();
+        // Not reachable from the public endpoints
+        assert!(!rpc.get_rpc_handler().has_method(&"set_log_level".to_owned()));
+
+        // admin:secret
+        assert_eq!(admin_request(address, "set_log_level", json!({ "level": "info" }), Some("YWRtaW46c2VjcmV0")).await, 200);
+        // admin:wrong
+        assert_eq!(admin_request(address, "set_log_level", json!({ "level": "info" }), Some("YWRtaW46d3Jvbmc=")).await, 401);
+        assert_eq!(admin_request(address, "set_log_level", json!({ "level": "info" }), None).await, 401);
+        blockchain.stop().await;
+
+        // Disabled without any password
+        let address = free_local_address();
+        let (_dir, blockchain) = start_node("rpc-no-admin", &["--disable-p2p", "--rpc-bind-address", &address.to_string()]).await;
+        assert_eq!(admin_request(address, "set_log_level", json!({ "level": "info" }), Some("YWRtaW46c2VjcmV0")).await, 404);
+        blockchain.stop().await;
+    }
+
+    type WsClient = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+    // Connect a WebSocket client to the RPC server and watch the addresses
+    async fn watch_addresses(address: SocketAddr, addresses: &[Address]) -> WsClient {
+        use futures_util::SinkExt;
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/json_rpc", address)).await.unwrap();
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "subscribe_address", "params": { "addresses": addresses } });
+        client.send(tokio_tungstenite::tungstenite::Message::Text(request.to_string())).await.unwrap();
+        let response = next_ws_message(&mut client, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(response["result"], json!(addresses.len()));
+        client
+    }
+
+    // Connect a WebSocket client to the RPC server and subscribe to the event
+    async fn subscribe_event(address: SocketAddr, event: NotifyEvent) -> WsClient {
+        use futures_util::SinkExt;
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/json_rpc", address)).await.unwrap();
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "subscribe", "params": { "notify": event } });
+        client.send(tokio_tungstenite::tungstenite::Message::Text(request.to_string())).await.unwrap();
+        let response = next_ws_message(&mut client, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(response["result"], json!(true));
+        client
+    }
+
+    // Next JSON message received by the client, None if nothing is received in time
+    async fn next_ws_message<T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin>(client: &mut tokio_tungstenite::WebSocketStream<T>, delay: Duration) -> Option<Value> {
+        use futures_util::StreamExt;
+
+        match tokio::time::timeout(delay, client.next()).await {
+            Ok(Some(Ok(tokio_tungstenite::tungstenite::Message::Text(text)))) => Some(serde_json::from_str(&text).unwrap()),
+            Ok(message) => panic!("unexpected WebSocket message: {:?}", message),
+            Err(_) => None
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rpc_tls_listeners() {
+        use futures_util::SinkExt;
+        use crate::rpc::tls::tests::{connector, fixture, https_request, tls_connect};
+
+        let files = TempDir::new("rpc-tls-files");
+        let cert_path = files.join("cert.pem").to_string_lossy().into_owned();
+        let key_path = files.join("key.pem").to_string_lossy().into_owned();
+        std::fs::copy(fixture("first.cert.pem"), &cert_path).unwrap();
+        std::fs::copy(fixture("first.key.pem"), &key_path).unwrap();
+
+        let (address, tls_address) = (free_local_address(), free_local_address());
+        let (_dir, blockchain) = start_node("rpc-tls", &[
+            "--disable-p2p",
+            "--rpc-bind-address", &address.to_string(),
+            "--rpc-tls-bind-address", &tls_address.to_string(),
+            "--rpc-tls-cert", &cert_path,
+            "--rpc-tls-key", &key_path,
+            "--rpc-admin-password", "secret"
+        ]).await;
+        let port = tls_address.port();
+        let get_version = json_rpc_http_request("localhost", "/json_rpc", "get_version", Value::Null, None);
+
+        // HTTPS requests are served by the same RPC server
+        let response = https_request(&connector("first.cert.pem"), port, &get_version).await.unwrap();
+        assert_eq!(http_status(&response), 200);
+        assert!(response.contains(VERSION));
+        // Not trusted by a client expecting another certificate
+        assert!(https_request(&connector("second.cert.pem"), port, &get_version).await.is_err());
+
+        // WebSocket over TLS receives the events
+        let stream = tls_connect(&connector("first.cert.pem"), port).await.unwrap();
+        let (mut client, _) = tokio_tungstenite::client_async(format!("wss://localhost:{}/json_rpc", port), stream).await.unwrap();
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "subscribe", "params": { "notify": NotifyEvent::NewBlock } });
+        client.send(tokio_tungstenite::tungstenite::Message::Text(request.to_string())).await.unwrap();
+        let response = next_ws_message(&mut client, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(response["result"], json!(true));
+
+        submit_block(&blockchain).await;
+        let event = next_ws_message(&mut client, Duration::from_secs(10)).await.expect("new block event");
+        assert_eq!(event["result"]["event"], json!(NotifyEvent::NewBlock));
+        assert_eq!(event["result"]["height"], json!(blockchain.get_height()));
+
+        // Replace the files and reload them through the admin endpoint, on the plain HTTP listener
+        std::fs::copy(fixture("second.cert.pem"), &cert_path).unwrap();
+        std::fs::copy(fixture("second.key.pem"), &key_path).unwrap();
+        assert_eq!(admin_request(address, "reload_tls", Value::Null, Some("YWRtaW46c2VjcmV0")).await, 200);
+        assert_eq!(http_status(&https_request(&connector("second.cert.pem"), port, &get_version).await.unwrap()), 200);
+        assert!(https_request(&connector("first.cert.pem"), port, &get_version).await.is_err());
+
+        // The WebSocket opened before is not affected
+        submit_block(&blockchain).await;
+        assert!(next_ws_message(&mut client, Duration::from_secs(10)).await.is_some());
+
+        // Invalid files keep the current certificate
+        std::fs::write(&cert_path, "invalid").unwrap();
+        let rpc = blockchain.get_rpc().read().await.clone().unwrap();
+        assert!(rpc.reload_tls().is_err());
+        assert_eq!(http_status(&https_request(&connector("second.cert.pem"), port, &get_version).await.unwrap()), 200);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_address_clients() {
+        let address = free_local_address();
+        let (_dir, blockchain) = start_node("subscribe-address", &["--disable-p2p", "--rpc-bind-address", &address.to_string()]).await;
+        let (first_sender, second_sender) = (KeyPair::new(), KeyPair::new());
+        let (first_receiver, second_receiver) = (KeyPair::new(), KeyPair::new());
+        fund_account(&blockchain, &first_sender, BALANCE).await;
+        fund_account(&blockchain, &second_sender, BALANCE).await;
+
+        // Each client watches its own receiver
+        let first_address = first_receiver.get_public_key().to_address(false);
+        let second_address = second_receiver.get_public_key().to_address(false);
+        let mut first_client = watch_addresses(address, &[first_address.clone()]).await;
+        let mut second_client = watch_addresses(address, &[second_address.clone()]).await;
+
+        let first_tx = build_transfer(&first_sender, &first_receiver, BALANCE, COIN_VALUE);
+        let second_tx = build_transfer(&second_sender, &second_receiver, BALANCE, COIN_VALUE);
+        let (first_hash, second_hash) = (first_tx.hash(), second_tx.hash());
+        blockchain.add_tx_to_mempool(first_tx, false).await.unwrap();
+        blockchain.add_tx_to_mempool(second_tx, false).await.unwrap();
+        submit_block(&blockchain).await;
+        let block_hash = blockchain.get_top_block_hash().await.unwrap();
+
+        // Only the transaction touching its address is delivered to each client
+        for (client, watched, tx_hash) in [(&mut first_client, &first_address, &first_hash), (&mut second_client, &second_address, &second_hash)] {
+            let event = next_ws_message(client, Duration::from_secs(10)).await.expect("transaction received event");
+            assert_eq!(event["id"], json!(1));
+            let result = &event["result"];
+            assert_eq!(result["event"], json!("transaction_received"));
+            assert_eq!(result["address"], json!(watched));
+            assert_eq!(result["tx_hash"], json!(tx_hash));
+            assert_eq!(result["block_hash"], json!(block_hash));
+            assert_eq!(result["topoheight"], json!(1));
+            assert_eq!(result["outgoing"], json!(false));
+            assert_eq!(result["orphaned"], json!(false));
+
+            assert!(next_ws_message(client, Duration::from_millis(500)).await.is_none());
+        }
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_node_without_rpc() {
+        let (_dir, blockchain) = start_node("no-rpc", &["--disable-rpc", "--p2p-bind-address", "127.0.0.1:0", "--disable-p2p-outgoing-connections"]).await;
+        assert!(blockchain.get_rpc().read().await.is_none());
+        assert!(blockchain.get_p2p().read().await.is_some());
+
+        // Blocks are still processed and broadcasted, without any event emitted
+        let topoheight = blockchain.get_topo_height();
+        submit_block(&blockchain).await;
+        assert_eq!(blockchain.get_topo_height(), topoheight + 1);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_block_template_summary() {
+        let (_dir, blockchain) = start_node("template-summary", &["--disable-p2p", "--disable-rpc"]).await;
+        let miner = KeyPair::new().get_public_key().compress();
+        let senders = [KeyPair::new(), KeyPair::new(), KeyPair::new()];
+        for sender in senders.iter() {
+            fund_account(&blockchain, sender, BALANCE).await;
+        }
+
+        for (i, sender) in senders.iter().enumerate() {
+            // A TX paying fees is waiting in mempool
+            let tx = build_transfer(sender, &KeyPair::new(), BALANCE, COIN_VALUE);
+            let fee = tx.get_fee();
+            blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+
+            // Last template is limited to no TXs
+            let max_txs = if i == senders.len() - 1 { Some(0) } else { None };
+            let (header, summary) = {
+                let storage = blockchain.get_storage().read().await;
+                blockchain.get_block_template_with_summary_for_storage(&storage, miner.clone(), max_txs).await.unwrap()
+            };
+            assert_eq!(summary.txs_count, header.get_txs_count());
+            if max_txs.is_some() {
+                assert_eq!(summary.txs_count, 0);
+                assert_eq!(summary.total_fees, 0);
+            } else {
+                assert_eq!(summary.txs_count, 1);
+                assert!(fee > 0);
+                assert_eq!(summary.total_fees, fee);
+            }
+
+            let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+            assert_eq!(summary.size, block.size());
+            let height = block.get_height();
+            blockchain.add_new_block(block, false, false).await.unwrap();
+
+            // Template values match the block execution
+            assert_eq!(blockchain.get_topo_height(), summary.topoheight);
+            let storage = blockchain.get_storage().read().await;
+            let reward = storage.get_block_reward_at_topo_height(summary.topoheight).unwrap();
+            let fees = storage.get_block_fees_at_topo_height(summary.topoheight).unwrap();
+            assert_eq!(fees, summary.total_fees);
+            assert_eq!(split_block_reward(reward, fees, height).unwrap().0, summary.expected_reward);
+        }
+
+        // TX not selected is still waiting
+        assert_eq!(blockchain.get_mempool().read().await.size(), 1);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_block_template_selection() {
+        let (_dir, blockchain) = start_node("template-selection", &["--disable-p2p", "--disable-rpc"]).await;
+        let senders = [KeyPair::new(), KeyPair::new(), KeyPair::new(), KeyPair::new()];
+        let mut hashes = Vec::new();
+        for (i, sender) in senders.iter().enumerate() {
+            fund_account(&blockchain, sender, BALANCE).await;
+            // Same size for all, the fee per byte grows with each sender
+            let fee = build_transfer(sender, &KeyPair::new(), BALANCE, COIN_VALUE).get_fee() * (i as u64 + 1);
+            let tx = build_transfer_with_fee(sender, &KeyPair::new(), BALANCE, COIN_VALUE, FeeBuilder::Value(fee));
+            hashes.push(tx.hash());
+            blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        }
+
+        // Balance of the best paying sender changed since its TX got verified, it's rejected by the template
+        fund_account(&blockchain, &senders[3], BALANCE * 2).await;
+
+        let header = blockchain.get_block_template(KeyPair::new().get_public_key().compress()).await.unwrap();
+        let selected: Vec<&Hash> = header.get_txs_hashes().iter().collect();
+        assert_eq!(selected, vec![&hashes[2], &hashes[1], &hashes[0]]);
+
+        // The block built from it is valid
+        let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+        blockchain.add_new_block(block, false, false).await.unwrap();
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_block_template_picks_replacement() {
+        let (_dir, blockchain) = start_node("template-rbf", &["--disable-p2p", "--disable-rpc"]).await;
+        let sender = KeyPair::new();
+        let receiver = KeyPair::new();
+        {
+            let mut storage = blockchain.get_storage().write().await;
+            let key = sender.get_public_key().compress();
+            let balance = VersionedBalance::new(CiphertextCache::Decompressed(sender.get_public_key().encrypt(BALANCE)), None);
+            storage.set_last_balance_to(&key, &XELIS_ASSET, 0, &balance).await.unwrap();
+            storage.set_last_nonce_to(&key, 0, &VersionedNonce::new(0, None)).await.unwrap();
+        }
+
+        let tx = build_transfer(&sender, &receiver, BALANCE, COIN_VALUE);
+        let tx_hash = tx.hash();
+        let fee = tx.get_fee();
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+
+        let replacement = build_transfer_with_fee(&sender, &receiver, BALANCE, COIN_VALUE, FeeBuilder::Value(fee * 110 / 100));
+        let replacement_hash = replacement.hash();
+        blockchain.add_tx_to_mempool(replacement, false).await.unwrap();
+
+        let header = blockchain.get_block_template(KeyPair::new().get_public_key().compress()).await.unwrap();
+        assert!(header.get_txs_hashes().contains(&replacement_hash));
+        assert!(!header.get_txs_hashes().contains(&tx_hash));
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_replaced_event() {
+        let address = free_local_address();
+        let (_dir, blockchain) = start_node("rbf-event", &["--disable-p2p", "--rpc-bind-address", &address.to_string()]).await;
+        let (sender, receiver) = (KeyPair::new(), KeyPair::new());
+        fund_account(&blockchain, &sender, BALANCE).await;
+
+        let tx = build_transfer(&sender, &receiver, BALANCE, COIN_VALUE);
+        let (tx_hash, fee) = (tx.hash(), tx.get_fee());
+        let mut client = subscribe_event(address, NotifyEvent::TransactionReplaced).await;
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+
+        // First event received, the TX added before didn't send any
+        let replacement = build_transfer_with_fee(&sender, &receiver, BALANCE, COIN_VALUE, FeeBuilder::Value(fee * 110 / 100));
+        let replacement_hash = replacement.hash();
+        blockchain.add_tx_to_mempool(replacement, false).await.unwrap();
+
+        let event = next_ws_message(&mut client, Duration::from_secs(10)).await.expect("transaction replaced event");
+        let result = &event["result"];
+        assert_eq!(result["event"], json!("transaction_replaced"));
+        assert_eq!(result["address"], json!(sender.get_public_key().to_address(false)));
+        assert_eq!(result["nonce"], json!(0));
+        assert_eq!(result["replaced_tx"], json!(tx_hash));
+        assert_eq!(result["replacement_tx"], json!(replacement_hash));
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_new_block_event_summary() {
+        let address = free_local_address();
+        let (_dir, blockchain) = start_node("new-block-summary", &["--disable-p2p", "--rpc-bind-address", &address.to_string()]).await;
+        let mut client = subscribe_event(address, NotifyEvent::NewBlock).await;
+
+        // The event is built once the top state is updated with the new block
+        submit_block(&blockchain).await;
+        let event = next_ws_message(&mut client, Duration::from_secs(10)).await.expect("new block event");
+        let mut result = event["result"].clone();
+        let summary = blockchain.get_top_block_summary().unwrap();
+        assert_eq!(result["hash"], json!(summary.hash));
+        assert_eq!(result["summary"], json!(summary));
+
+        // Event sent by an older daemon without the summary
+        result.as_object_mut().unwrap().remove("summary");
+        let event: NewBlockEvent<'static> = serde_json::from_value(result).unwrap();
+        assert_eq!(*event.block.hash, summary.hash);
+        assert!(event.summary.is_none());
+
+        blockchain.stop().await;
+    }
+
+    // Header of the next block built on the template, with another timestamp and tips if requested
+    async fn next_header(blockchain: &Blockchain<SledStorage>, timestamp: Option<u64>, tips: Option<IndexSet<Hash>>) -> BlockHeader {
+        let template = blockchain.get_block_template(KeyPair::new().get_public_key().compress()).await.unwrap();
+        BlockHeader::new(
+            template.get_version(),
+            template.get_height(),
+            timestamp.unwrap_or(template.get_timestamp()),
+            tips.unwrap_or_else(|| template.get_tips().clone()),
+            *template.get_extra_nonce(),
+            template.get_miner().clone(),
+            IndexSet::new()
+        )
+    }
+
+    #[tokio::test]
+    async fn test_block_verification_stages() {
+        let (_dir, blockchain) = start_node("verification-stages", &["--disable-p2p", "--disable-rpc"]).await;
+        submit_block(&blockchain).await;
+        let topoheight = blockchain.get_topo_height();
+
+        // Rejected by the header verification
+        let header = next_header(&blockchain, Some(get_current_time_in_millis() + 60 * MILLIS_PER_SECOND), None).await;
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert!(matches!(blockchain.verify_block_header(&storage, &header, &header.hash()).await, Err(BlockchainError::TimestampIsInFuture(_, _))));
+        }
+        let header = next_header(&blockchain, None, Some(IndexSet::from([Hash::zero()]))).await;
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert!(matches!(blockchain.verify_block_header(&storage, &header, &header.hash()).await, Err(BlockchainError::InvalidTipsNotFound(_, tip)) if tip == Hash::zero()));
+        }
+        assert!(matches!(blockchain.add_new_block(Block::new(Immutable::Owned(header), Vec::new()), false, false).await, Err(BlockchainError::InvalidTipsNotFound(_, _))));
+
+        // Valid header but rejected by the transactions verification
+        let tx = build_transfer(&KeyPair::new(), &KeyPair::new(), BALANCE, COIN_VALUE);
+        let other = build_transfer(&KeyPair::new(), &KeyPair::new(), BALANCE, COIN_VALUE);
+        let other_hash = other.hash();
+        let mut header = next_header(&blockchain, None, None).await;
+        header.set_txs_hashes(IndexSet::from([tx.hash()]));
+        // The header doesn't match the transaction included
+        let block = Block::new(Immutable::Owned(header), vec![Immutable::Owned(other)]);
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert!(blockchain.verify_block_header(&storage, &block, &block.hash()).await.is_ok());
+            assert!(matches!(blockchain.verify_block_transactions(&storage, &block, &block.hash()).await, Err(BlockchainError::InvalidTxInBlock(hash)) if hash == other_hash));
+        }
+
+        // Sender has no balance on chain
+        let block = Block::with(next_header(&blockchain, None, None).await, vec![tx]);
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert!(blockchain.verify_block_header(&storage, &block, &block.hash()).await.is_ok());
+            assert!(blockchain.verify_block_transactions(&storage, &block, &block.hash()).await.is_err());
+            assert!(blockchain.verify_block(&storage, block.clone()).await.is_err());
+        }
+        assert!(blockchain.add_new_block(block, false, false).await.is_err());
+
+        // Nothing was executed
+        assert_eq!(blockchain.get_topo_height(), topoheight);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_identity_key_rejected() {
+        let (_dir, blockchain) = start_node("identity-key", &["--disable-p2p", "--disable-rpc"]).await;
+        submit_block(&blockchain).await;
+        let topoheight = blockchain.get_topo_height();
+        let sender = KeyPair::new();
+        fund_account(&blockchain, &sender, BALANCE).await;
+        let identity = PublicKey::from_bytes(&[0u8; 32]).unwrap();
+
+        // Valid otherwise, but the funds sent to the identity key could never be spent
+        let tx = build_transfer_to(&sender, identity.clone().to_address(false), BALANCE, COIN_VALUE, FeeBuilder::Multiplier(1f64));
+        let tx_hash = tx.hash();
+        assert!(matches!(
+            blockchain.add_tx_to_mempool(tx.clone(), false).await,
+            Err(BlockchainError::InvalidTxPublicKey(hash, PublicKeyError::Identity)) if hash == tx_hash
+        ));
+
+        let block = Block::with(next_header(&blockchain, None, None).await, vec![tx]);
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert!(matches!(
+                blockchain.verify_block_transactions(&storage, &block, &block.hash()).await,
+                Err(BlockchainError::InvalidTxPublicKey(hash, PublicKeyError::Identity)) if hash == tx_hash
+            ));
+        }
+        assert!(blockchain.add_new_block(block, false, false).await.is_err());
+
+        // Same for a block rewarding the identity key
+        let mut header = next_header(&blockchain, None, None).await;
+        header.set_miner(identity);
+        let block_hash = header.hash();
+        assert!(matches!(
+            blockchain.add_new_block(Block::with(header, Vec::new()), false, false).await,
+            Err(BlockchainError::InvalidBlockMinerKey(hash, PublicKeyError::Identity)) if hash == block_hash
+        ));
+
+        // Nothing was executed
+        assert_eq!(blockchain.get_topo_height(), topoheight);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_add_verified_block() {
+        let (_dir, blockchain) = start_node("verified-block", &["--disable-p2p", "--disable-rpc"]).await;
+        submit_block(&blockchain).await;
+        let topoheight = blockchain.get_topo_height();
+
+        // Two blocks on the same tips, verified before any of them is added
+        let (first, second) = {
+            let storage = blockchain.get_storage().read().await;
+            let first = Block::with(next_header(&blockchain, None, None).await, Vec::new());
+            let second = Block::with(next_header(&blockchain, None, None).await, Vec::new());
+            (blockchain.verify_block(&storage, first).await.unwrap(), blockchain.verify_block(&storage, second).await.unwrap())
+        };
+
+        blockchain.add_verified_block(first, false, false).await.unwrap();
+        assert_eq!(blockchain.get_topo_height(), topoheight + 1);
+
+        // Chain changed since its verification, it is verified again against the new state
+        blockchain.add_verified_block(second, false, false).await.unwrap();
+        assert_eq!(blockchain.get_topo_height(), topoheight + 2);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_export_import_chain() {
+        let (dir, blockchain) = start_node("export-chain", &["--disable-p2p", "--disable-rpc"]).await;
+        let miner = KeyPair::new().get_public_key().compress();
+        for _ in 0..99 {
+            let header = blockchain.get_block_template(miner.clone()).await.unwrap();
+            let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+            blockchain.add_new_block(block, false, false).await.unwrap();
+        }
+
+        // Genesis block included
+        let path = dir.join("chain.bin").to_string_lossy().into_owned();
+        assert_eq!(bootstrap::export_chain(&blockchain, &path, None).await.unwrap(), 100);
+
+        let (_imported_dir, imported) = start_node("import-chain", &["--disable-p2p", "--disable-rpc", "--import-chain", &path]).await;
+        assert_eq!(imported.get_topo_height(), blockchain.get_topo_height());
+        assert_eq!(imported.get_top_block_hash().await.unwrap(), blockchain.get_top_block_hash().await.unwrap());
+        {
+            let storage = blockchain.get_storage().read().await;
+            let imported_storage = imported.get_storage().read().await;
+            assert_eq!(
+                imported_storage.get_last_balance(&miner, &XELIS_ASSET).await.unwrap(),
+                storage.get_last_balance(&miner, &XELIS_ASSET).await.unwrap()
+            );
+            assert_eq!(imported_storage.get_supply_at_topo_height(99).await.unwrap(), storage.get_supply_at_topo_height(99).await.unwrap());
+        }
+
+        // Import again is resumed from the last block known, nothing to add
+        assert_eq!(bootstrap::import_chain(&imported, &path).await.unwrap(), 0);
+
+        blockchain.stop().await;
+        imported.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_backup_restore() {
+        let (dir, blockchain) = start_node("backup", &["--disable-p2p", "--disable-rpc"]).await;
+        let miner = KeyPair::new().get_public_key().compress();
+        let mine = |count| {
+            let blockchain = &blockchain;
+            let miner = &miner;
+            async move {
+                for _ in 0..count {
+                    let header = blockchain.get_block_template(miner.clone()).await.unwrap();
+                    let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+                    blockchain.add_new_block(block, false, false).await.unwrap();
+                }
+            }
+        };
+        mine(99).await;
+
+        let backup_dir = dir.join("backups");
+        let (path, manifest) = backup::backup_now(&blockchain, &backup_dir, 2).await.unwrap();
+        assert_eq!(manifest.topoheight, blockchain.get_stable_topoheight());
+        assert_eq!(manifest.genesis_hash, blockchain.get_storage().read().await.get_hash_at_topo_height(0).await.unwrap());
+        assert_eq!(BackupManifest::read(&path).unwrap(), manifest);
+
+        // Only the last backups are kept
+        for _ in 0..2 {
+            mine(1).await;
+            backup::backup_now(&blockchain, &backup_dir, 2).await.unwrap();
+        }
+        let backups = backup::list_backups(&backup_dir).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert!(backups.iter().all(|(backup, _)| *backup != path));
+        let (path, manifest) = backups.last().unwrap().clone();
+
+        // Restore the last backup in a new directory
+        let path = path.to_string_lossy().into_owned();
+        let (_restored_dir, restored) = start_node("backup-restored", &["--disable-p2p", "--disable-rpc", "--import-chain", &path]).await;
+        assert_eq!(restored.get_topo_height(), manifest.topoheight);
+        assert_eq!(restored.get_top_block_hash().await.unwrap(), manifest.top_hash);
+
+        // Manifest not matching the chain restored
+        let mut invalid = manifest.clone();
+        invalid.top_hash = Hash::zero();
+        assert!(matches!(invalid.verify_restore(&restored).await, Err(BlockchainError::BackupRestoreMismatch(_, _))));
+
+        blockchain.stop().await;
+        restored.stop().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_top_state_cache() {
+        let (_dir, blockchain) = start_node("top-state", &["--disable-p2p", "--disable-rpc"]).await;
+        submit_block(&blockchain).await;
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert_eq!(blockchain.get_top_state(), blockchain.build_top_state(&*storage).await.unwrap());
+        }
+
+        // Read the top state while blocks are added
+        let reader = {
+            let blockchain = Arc::clone(&blockchain);
+            tokio::spawn(async move {
+                let mut states = Vec::new();
+                for _ in 0..500 {
+                    states.push(blockchain.get_top_state());
+                    tokio::task::yield_now().await;
+                }
+                states
+            })
+        };
+        for _ in 0..10 {
+            submit_block(&blockchain).await;
+        }
+        let states = reader.await.unwrap();
+
+        {
+            let storage = blockchain.get_storage().read().await;
+            // A reader never sees the fields of two different blocks
+            for state in states {
+                assert_eq!(storage.get_hash_at_topo_height(state.topoheight).await.unwrap(), state.hash);
+                assert_eq!(storage.get_cumulative_difficulty_for_block_hash(&state.hash).await.unwrap(), state.cumulative_difficulty);
+            }
+            assert_eq!(blockchain.get_top_state(), blockchain.build_top_state(&*storage).await.unwrap());
+        }
+
+        // Kept up to date after a rewind
+        let topoheight = blockchain.rewind_chain(3, false).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert_eq!(blockchain.get_topo_height(), topoheight);
+            assert_eq!(blockchain.get_top_state(), blockchain.build_top_state(&*storage).await.unwrap());
+        }
+
+        blockchain.stop().await;
+    }
+
+    // Decrypt the last XELIS balance of the account and compare it to the amount
+    async fn assert_balance(storage: &SledStorage, account: &KeyPair, amount: u64) {
+        let (_, version) = storage.get_last_balance(&account.get_public_key().compress(), &XELIS_ASSET).await.unwrap();
+        let ct = version.take_balance().take_ciphertext().unwrap();
+        assert_eq!(
+            account.get_private_key().decrypt_to_point(&ct),
+            account.get_private_key().decrypt_to_point(&account.get_public_key().encrypt(amount))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_block_reward_executed() {
+        let (_dir, blockchain) = start_node("block-reward", &["--disable-p2p", "--disable-rpc"]).await;
+        let (sender, receiver, miner) = (KeyPair::new(), KeyPair::new(), KeyPair::new());
+        fund_account(&blockchain, &sender, BALANCE).await;
+
+        let tx = build_transfer(&sender, &receiver, BALANCE, COIN_VALUE);
+        let fee = tx.get_fee();
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        let header = blockchain.get_block_template(miner.get_public_key().compress()).await.unwrap();
+        let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+        blockchain.add_new_block(block, false, false).await.unwrap();
+
+        // Miner receives the emission minus the dev fee, plus the fees of the executed TX
+        let storage = blockchain.get_storage().read().await;
+        let past_supply = storage.get_supply_at_topo_height(0).await.unwrap();
+        let reward = storage.get_block_reward_at_topo_height(1).unwrap();
+        assert_eq!(reward, get_block_reward(past_supply));
+        assert_eq!(storage.get_supply_at_topo_height(1).await.unwrap(), past_supply + reward);
+        let dev_fee_part = reward * get_block_dev_fee(1) / 100;
+        assert!(dev_fee_part > 0);
+        assert_balance(&storage, &miner, reward - dev_fee_part + fee).await;
+        drop(storage);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_txs_merkle_root_version() {
+        let (_dir, blockchain) = start_node("txs-merkle-root-version", &["--disable-p2p", "--disable-rpc"]).await;
+        assert_eq!(blockchain.get_version_at_height(0), 0);
+        assert_eq!(blockchain.get_version_at_height(1), TXS_MERKLE_ROOT_VERSION);
+
+        // Mined blocks commit to their TXs
+        let sender = KeyPair::new();
+        fund_account(&blockchain, &sender, BALANCE).await;
+        let tx = build_transfer(&sender, &KeyPair::new(), BALANCE, COIN_VALUE);
+        let tx_hash = tx.hash();
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        submit_block(&blockchain).await;
+
+        let storage = blockchain.get_storage().read().await;
+        let hash = storage.get_hash_at_topo_height(1).await.unwrap();
+        let header = storage.get_block_header_by_hash(&hash).await.unwrap();
+        assert_eq!(header.get_version(), TXS_MERKLE_ROOT_VERSION);
+        assert_eq!(header.get_txs_merkle_root(), Some(&compute_txs_merkle_root([&tx_hash])));
+        drop(storage);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_side_block_reward_executed() {
+        let (_dir, blockchain) = start_node("side-block-reward", &["--disable-p2p", "--disable-rpc"]).await;
+        let (miner, side_miner) = (KeyPair::new(), KeyPair::new());
+
+        // Two blocks on top of the genesis block, then a block merging both
+        let side_header = blockchain.get_block_template(side_miner.get_public_key().compress()).await.unwrap();
+        let header = blockchain.get_block_template(miner.get_public_key().compress()).await.unwrap();
+        let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+        blockchain.add_new_block(block, false, false).await.unwrap();
+        let side_block = blockchain.build_block_from_header(Immutable::Owned(side_header)).await.unwrap();
+        let side_hash = side_block.hash();
+        blockchain.add_new_block(side_block, false, false).await.unwrap();
+        submit_block(&blockchain).await;
+        assert_eq!(blockchain.get_topo_height(), 3);
+
+        // Both blocks are at height 1, the one ordered second is a side block
+        let storage = blockchain.get_storage().read().await;
+        let side_miner = if storage.get_hash_at_topo_height(2).await.unwrap() == side_hash {
+            &side_miner
+        } else {
+            &miner
+        };
+        let past_supply = storage.get_supply_at_topo_height(1).await.unwrap();
+        let side_reward = get_block_reward(past_supply) * SIDE_BLOCK_REWARD_PERCENT / 100;
+        assert_eq!(storage.get_block_reward_at_topo_height(2).unwrap(), side_reward);
+        assert_eq!(storage.get_supply_at_topo_height(2).await.unwrap(), past_supply + side_reward);
+        assert_balance(&storage, side_miner, side_reward - side_reward * get_block_dev_fee(1) / 100).await;
+        drop(storage);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_block_transactions_executed_in_dag() {
+        let (_dir, blockchain) = start_node("block-txs-executed-dag", &["--disable-p2p", "--disable-rpc"]).await;
+        let (shared_sender, first_sender, second_sender, receiver) = (KeyPair::new(), KeyPair::new(), KeyPair::new(), KeyPair::new());
+        for sender in [&shared_sender, &first_sender, &second_sender] {
+            fund_account(&blockchain, sender, BALANCE).await;
+        }
+
+        let shared = build_transfer(&shared_sender, &receiver, BALANCE, COIN_VALUE);
+        let first_tx = build_transfer(&first_sender, &receiver, BALANCE, COIN_VALUE);
+        let second_tx = build_transfer(&second_sender, &receiver, BALANCE, COIN_VALUE);
+        let hashes = [shared.hash(), first_tx.hash(), second_tx.hash()];
+        for tx in [shared, first_tx, second_tx] {
+            blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        }
+
+        // Two blocks on top of the genesis block both including the shared tx first
+        let mut blocks = Vec::new();
+        for own in [&hashes[1], &hashes[2]] {
+            let header = next_header(&blockchain, None, None).await;
+            let header = BlockHeader::new(header.get_version(), header.get_height(), header.get_timestamp(), header.get_tips().clone(), *header.get_extra_nonce(), header.get_miner().clone(), IndexSet::from([hashes[0].clone(), own.clone()]));
+            blocks.push(blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap());
+        }
+        let block_hashes: Vec<Hash> = blocks.iter().map(|block| block.hash()).collect();
+        for block in blocks {
+            blockchain.add_new_block(block, false, false).await.unwrap();
+        }
+        // Merge both blocks so they are both ordered
+        submit_block(&blockchain).await;
+
+        let storage = blockchain.get_storage().read().await;
+        let (earlier, later, earlier_own, later_own) = if storage.get_topo_height_for_hash(&block_hashes[0]).await.unwrap() < storage.get_topo_height_for_hash(&block_hashes[1]).await.unwrap() {
+            (&block_hashes[0], &block_hashes[1], &hashes[1], &hashes[2])
+        } else {
+            (&block_hashes[1], &block_hashes[0], &hashes[2], &hashes[1])
+        };
+
+        // Block ordered first executes both of its transactions one after another
+        let executed = get_block_transactions_execution(&*storage, earlier).await.unwrap();
+        let summary: Vec<_> = executed.iter().map(|tx| (tx.hash.as_ref(), tx.status, tx.execution_index, tx.executed_in_block.as_deref())).collect();
+        assert_eq!(summary, vec![
+            (&hashes[0], TransactionExecutionStatus::Executed, Some(0), Some(earlier)),
+            (earlier_own, TransactionExecutionStatus::Executed, Some(1), Some(earlier))
+        ]);
+
+        // Shared tx is skipped by the other one and doesn't take an execution index
+        let executed = get_block_transactions_execution(&*storage, later).await.unwrap();
+        let summary: Vec<_> = executed.iter().map(|tx| (tx.hash.as_ref(), tx.status, tx.execution_index, tx.executed_in_block.as_deref())).collect();
+        assert_eq!(summary, vec![
+            (&hashes[0], TransactionExecutionStatus::AlreadyExecuted, None, Some(earlier)),
+            (later_own, TransactionExecutionStatus::Executed, Some(0), Some(later))
+        ]);
+        drop(storage);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_block_with_invalid_nonces() {
+        let (_dir, blockchain) = start_node("block-nonces", &["--disable-p2p", "--disable-rpc"]).await;
+        let (alice, bob) = (KeyPair::new(), KeyPair::new());
+        for sender in [&alice, &bob] {
+            fund_account(&blockchain, sender, BALANCE).await;
+        }
+        let alice_address = alice.get_public_key().compress().as_address(false);
+
+        // Same nonce used twice by alice, whatever the other senders in between
+        let block = Block::with(next_header(&blockchain, None, None).await, vec![
+            build_transfer(&alice, &KeyPair::new(), BALANCE, COIN_VALUE),
+            build_transfer(&bob, &KeyPair::new(), BALANCE, COIN_VALUE),
+            build_transfer(&alice, &KeyPair::new(), BALANCE, COIN_VALUE)
+        ]);
+        assert!(matches!(
+            blockchain.add_new_block(block, false, false).await,
+            Err(BlockchainError::InvalidBlockTxNonce(_, address, 0, 1)) if address == alice_address
+        ));
+
+        // First nonce is not the stored one
+        {
+            let mut storage = blockchain.get_storage().write().await;
+            storage.set_last_nonce_to(&alice.get_public_key().compress(), 0, &VersionedNonce::new(1, None)).await.unwrap();
+        }
+        let block = Block::with(next_header(&blockchain, None, None).await, vec![build_transfer(&alice, &KeyPair::new(), BALANCE, COIN_VALUE)]);
+        assert!(matches!(
+            blockchain.add_new_block(block, false, false).await,
+            Err(BlockchainError::InvalidBlockTxNonce(_, address, 0, 1)) if address == alice_address
+        ));
+        assert_eq!(blockchain.get_topo_height(), 0);
+
+        // Nothing was applied by the rejected blocks
+        let block = Block::with(next_header(&blockchain, None, None).await, vec![build_transfer(&bob, &KeyPair::new(), BALANCE, COIN_VALUE)]);
+        blockchain.add_new_block(block, false, false).await.unwrap();
+        assert_eq!(blockchain.get_topo_height(), 1);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_pop_blocks_rewinds_block_time() {
+        let (_dir, blockchain) = start_node("pop-blocks-block-time", &["--disable-p2p", "--disable-rpc"]).await;
+        for _ in 0..3 {
+            submit_block(&blockchain).await;
+        }
+        assert!(blockchain.get_block_time_drift().await.average.is_some());
+
+        // Only one block is left, there is no solve time to average
+        blockchain.pop_blocks(2, false).await.unwrap();
+        assert_eq!(blockchain.get_topo_height(), 1);
+        assert_eq!(blockchain.get_block_time_drift().await, BlockTimeDrift { average: None, drifting: false });
+
+        // New blocks are tracked after the remaining one
+        submit_block(&blockchain).await;
+        let timestamps = {
+            let storage = blockchain.get_storage().read().await;
+            let mut timestamps = Vec::new();
+            for topoheight in 1..=2 {
+                let hash = storage.get_hash_at_topo_height(topoheight).await.unwrap();
+                timestamps.push(storage.get_timestamp_for_block_hash(&hash).await.unwrap());
+            }
+            timestamps
+        };
+        assert_eq!(blockchain.get_block_time_drift().await.average, Some(timestamps[1] - timestamps[0]));
+    }
+
+    #[tokio::test]
+    async fn test_pop_blocks() {
+        let (_dir, blockchain) = start_node("pop-blocks", &["--disable-p2p", "--disable-rpc"]).await;
+        let sender = KeyPair::new();
+        let receiver = KeyPair::new();
+        let sender_key = sender.get_public_key().compress();
+        let receiver_key = receiver.get_public_key().compress();
+        let miner = KeyPair::new().get_public_key().compress();
+        fund_account(&blockchain, &sender, BALANCE).await;
+
+        let mine = |count| {
+            let blockchain = &blockchain;
+            let miner = &miner;
+            async move {
+                for _ in 0..count {
+                    let header = blockchain.get_block_template(miner.clone()).await.unwrap();
+                    let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+                    blockchain.add_new_block(block, false, false).await.unwrap();
+                }
+            }
+        };
+        mine(10).await;
+
+        // State at the topoheight we will go back to
+        let topoheight = blockchain.get_topo_height();
+        let top_hash = blockchain.get_top_block_hash().await.unwrap();
+        let supply = blockchain.get_supply().await.unwrap();
+        let (miner_balance, sender_balance) = {
+            let storage = blockchain.get_storage().read().await;
+            (
+                storage.get_last_balance(&miner, &XELIS_ASSET).await.unwrap(),
+                storage.get_last_balance(&sender_key, &XELIS_ASSET).await.unwrap()
+            )
+        };
+
+        // Transfer included in the next block
+        let tx = build_transfer(&sender, &receiver, BALANCE, COIN_VALUE);
+        let tx_hash = tx.hash();
+        let fee = tx.get_fee();
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        mine(10).await;
+        assert_eq!(blockchain.get_topo_height(), topoheight + 10);
+        assert!(!blockchain.get_mempool().read().await.contains_tx(&tx_hash));
+        assert!(blockchain.get_supply().await.unwrap() > supply);
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert_eq!(storage.get_last_nonce(&sender_key).await.unwrap().1.get_nonce(), 1);
+            assert!(storage.has_balance_for(&receiver_key, &XELIS_ASSET).await.unwrap());
+            assert_eq!(storage.get_block_fees_at_topo_height(topoheight + 1).unwrap(), fee);
+            assert_eq!(get_chain_stats(&*storage, 0, topoheight + 10).await.unwrap().total_fees, fee);
+        }
+
+        // Too deep without being forced
+        assert!(matches!(
+            blockchain.pop_blocks(MAX_BLOCK_REWIND + 1, false).await,
+            Err(BlockchainError::RewindTooDeep(count, max)) if count == MAX_BLOCK_REWIND + 1 && max == MAX_BLOCK_REWIND
+        ));
+        assert_eq!(blockchain.get_topo_height(), topoheight + 10);
+
+        let summary = blockchain.pop_blocks(10, false).await.unwrap();
+        assert_eq!(summary.topoheight, topoheight);
+        assert_eq!(summary.removed_blocks, 10);
+        assert_eq!(summary.txs_reinjected, 1);
+        assert_eq!(summary.txs_orphaned, 0);
+
+        // Balances, nonces and supply are back to their state at the topoheight
+        assert_eq!(blockchain.get_topo_height(), topoheight);
+        assert_eq!(blockchain.get_top_block_hash().await.unwrap(), top_hash);
+        assert_eq!(blockchain.get_supply().await.unwrap(), supply);
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert!(storage.get_supply_at_topo_height(topoheight + 1).await.is_err());
+            assert!(storage.get_block_reward_at_topo_height(topoheight + 1).is_err());
+            // Fees of the popped blocks are deleted with them
+            for popped in topoheight + 1..=topoheight + 10 {
+                assert!(storage.get_block_fees_at_topo_height(popped).is_err());
+            }
+            assert_eq!(get_chain_stats(&*storage, 0, topoheight).await.unwrap().total_fees, 0);
+            assert_eq!(storage.get_last_balance(&miner, &XELIS_ASSET).await.unwrap(), miner_balance);
+            assert_eq!(storage.get_last_balance(&sender_key, &XELIS_ASSET).await.unwrap(), sender_balance);
+            assert_eq!(storage.get_last_nonce(&sender_key).await.unwrap().1.get_nonce(), 0);
+            assert!(!storage.has_balance_for(&receiver_key, &XELIS_ASSET).await.unwrap());
+        }
+
+        // Transfer is still valid, it is back in mempool and mined again
+        // The reward of the new block is computed from the rewinded supply
+        assert!(blockchain.get_mempool().read().await.contains_tx(&tx_hash));
+        mine(1).await;
+        assert!(!blockchain.get_mempool().read().await.contains_tx(&tx_hash));
+        assert_eq!(blockchain.get_supply().await.unwrap(), supply + get_block_reward(supply));
+        assert!(blockchain.get_storage().read().await.has_balance_for(&receiver_key, &XELIS_ASSET).await.unwrap());
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_mempool_persisted_across_restart() {
+        let dir = TempDir::new("mempool-persistence");
+        let blockchain = start_node_in(&dir, &["--disable-p2p", "--disable-rpc"]).await;
+        let (kept_sender, purged_sender, receiver) = (KeyPair::new(), KeyPair::new(), KeyPair::new());
+        fund_account(&blockchain, &kept_sender, BALANCE).await;
+        fund_account(&blockchain, &purged_sender, BALANCE).await;
+
+        let kept = build_transfer(&kept_sender, &receiver, BALANCE, COIN_VALUE);
+        let purged = build_transfer(&purged_sender, &receiver, BALANCE, COIN_VALUE);
+        let (kept_hash, purged_hash) = (kept.hash(), purged.hash());
+        blockchain.add_tx_to_mempool(kept, false).await.unwrap();
+        blockchain.add_tx_to_mempool(purged, false).await.unwrap();
+        assert_eq!(blockchain.get_storage().read().await.get_mempool_txs().await.unwrap().len(), 2);
+
+        blockchain.stop().await;
+        drop(blockchain);
+
+        // While the node is stopped, the nonce of the second TX is used elsewhere
+        {
+            let mut storage = open_storage(&dir);
+            let key = purged_sender.get_public_key().compress();
+            storage.set_last_nonce_to(&key, 0, &VersionedNonce::new(1, None)).await.unwrap();
+            storage.stop().await.unwrap();
+        }
+
+        // Still valid TX is back in mempool, the other one is purged from the disk too
+        let blockchain = start_node_in(&dir, &["--disable-p2p", "--disable-rpc"]).await;
+        assert_eq!(blockchain.get_mempool_size().await, 1);
+        {
+            let mempool = blockchain.get_mempool().read().await;
+            assert!(mempool.contains_tx(&kept_hash));
+            assert!(!mempool.contains_tx(&purged_hash));
+        }
+        let persisted = blockchain.get_storage().read().await.get_mempool_txs().await.unwrap();
+        assert_eq!(persisted.into_iter().map(|(hash, _)| hash).collect::<Vec<_>>(), vec![kept_hash]);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_double_spend_from_side_block() {
+        let address = free_local_address();
+        let (_first_dir, first) = start_node("double-spend-first", &["--disable-p2p", "--rpc-bind-address", &address.to_string()]).await;
+        let (_second_dir, second) = start_node("double-spend-second", &["--disable-p2p", "--disable-rpc-server"]).await;
+        let (sender, receiver, other_receiver) = (KeyPair::new(), KeyPair::new(), KeyPair::new());
+        fund_account(&first, &sender, BALANCE).await;
+        fund_account(&second, &sender, BALANCE).await;
+
+        // First node has the heavier chain and a TX of the sender in mempool
+        submit_block(&first).await;
+        submit_block(&first).await;
+        let mempool_tx = build_transfer(&sender, &receiver, BALANCE, COIN_VALUE);
+        let mempool_hash = mempool_tx.hash();
+        first.add_tx_to_mempool(mempool_tx, false).await.unwrap();
+
+        // Second node mines a block including another TX of the sender with the same nonce
+        let block_tx = build_transfer(&sender, &other_receiver, BALANCE, COIN_VALUE);
+        let block_tx_hash = block_tx.hash();
+        second.add_tx_to_mempool(block_tx, false).await.unwrap();
+        let header = second.get_block_template(KeyPair::new().get_public_key().compress()).await.unwrap();
+        let block = second.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+        let block_hash = block.hash();
+        assert!(block.get_txs_hashes().contains(&block_tx_hash));
+
+        // Received by the first node, the block is a side block not ordered by its best tip
+        let mut client = subscribe_event(address, NotifyEvent::DoubleSpendDetected).await;
+        first.add_new_block(block, false, false).await.unwrap();
+        assert!(!first.get_storage().read().await.is_block_topological_ordered(&block_hash).await);
+
+        let event = next_ws_message(&mut client, Duration::from_secs(10)).await.expect("double spend detected event");
+        let result = &event["result"];
+        assert_eq!(result["event"], json!("double_spend_detected"));
+        assert_eq!(result["address"], json!(sender.get_public_key().to_address(false)));
+        assert_eq!(result["nonce"], json!(0));
+        assert_eq!(result["mempool_tx"], json!(mempool_hash));
+        assert_eq!(result["block_tx"], json!(block_tx_hash));
+        assert_eq!(result["block_hash"], json!(block_hash));
+
+        // Kept in mempool but not mined while the side block is not ordered
+        assert!(first.get_mempool().read().await.is_conflicted(&mempool_hash));
+        let template = first.get_block_template(KeyPair::new().get_public_key().compress()).await.unwrap();
+        assert!(!template.get_txs_hashes().contains(&mempool_hash));
+        assert!(first.get_mempool().read().await.contains_tx(&mempool_hash));
+
+        first.stop().await;
+        second.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_asset_creation_orphaned() {
+        let (_dir, blockchain) = start_node("asset-creation-orphaned", &["--disable-p2p", "--disable-rpc"]).await;
+        let sender = KeyPair::new();
+        fund_account(&blockchain, &sender, BALANCE * 2).await;
+        submit_block(&blockchain).await;
+
+        // Empty block on the same tips as the block creating the asset
+        let side_header = blockchain.get_block_template(KeyPair::new().get_public_key().compress()).await.unwrap();
+        let tx = build_asset_creation(&sender, BALANCE * 2, 4);
+        let tx_hash = tx.hash();
+        let asset = AssetCreationPayload::get_asset(&tx_hash);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        let header = blockchain.get_block_template(KeyPair::new().get_public_key().compress()).await.unwrap();
+        let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+        let block_hash = block.hash();
+        blockchain.add_new_block(block, false, false).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert_eq!(storage.get_asset(&asset).await.unwrap().get_topoheight(), storage.get_topo_height_for_hash(&block_hash).await.unwrap());
+            assert_eq!(storage.count_assets().await.unwrap(), 2);
+        }
+
+        // A heavier branch without the block orphans it
+        let side_block = blockchain.build_block_from_header(Immutable::Owned(side_header)).await.unwrap();
+        let side_hash = side_block.hash();
+        blockchain.add_new_block(side_block, false, false).await.unwrap();
+        let header = next_header(&blockchain, None, Some(IndexSet::from([side_hash]))).await;
+        let top_hash = header.hash();
+        blockchain.add_new_block(Block::new(Immutable::Owned(header), Vec::new()), false, false).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert!(!storage.is_block_topological_ordered(&block_hash).await);
+            assert!(!storage.is_tx_executed_in_a_block(&tx_hash).unwrap());
+            assert!(!storage.has_asset(&asset).await.unwrap());
+            assert_eq!(storage.count_assets().await.unwrap(), 1);
+        }
+
+        // Merged back, the asset is registered at the new topoheight of the block
+        let header = next_header(&blockchain, None, Some(IndexSet::from([top_hash, block_hash.clone()]))).await;
+        blockchain.add_new_block(Block::new(Immutable::Owned(header), Vec::new()), false, false).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert!(storage.is_tx_executed_in_block(&tx_hash, &block_hash).unwrap());
+            assert_eq!(storage.get_asset(&asset).await.unwrap().get_topoheight(), storage.get_topo_height_for_hash(&block_hash).await.unwrap());
+            assert_eq!(storage.count_assets().await.unwrap(), 2);
+        }
+
+        blockchain.stop().await;
+    }
+}
@@ -1,15 +1,13 @@
 use indexmap::IndexSet;
 use log::trace;
 use xelis_common::{
-    difficulty::CumulativeDifficulty,
-    time::TimestampMillis,
+    config::TIPS_LIMIT,
+    difficulty::{CumulativeDifficulty, Difficulty},
     crypto::Hash,
 };
+use crate::config::STABLE_LIMIT;
 use super::{    
-    storage::{
-        Storage,
-        DifficultyProvider
-    },
+    storage::DifficultyProvider,
     error::BlockchainError,
 };
 
@@ -51,32 +49,6 @@ where
     }
 }
 
-// Sort the TIPS by cumulative difficulty
-// If the cumulative difficulty is the same, the hash value is used to sort
-// Hashes are sorted in descending order
-pub async fn sort_tips<S, I>(storage: &S, tips: I) -> Result<IndexSet<Hash>, BlockchainError>
-where
-    S: Storage,
-    I: Iterator<Item = Hash> + ExactSizeIterator,
-{
-    trace!("sort tips");
-    let tips_len = tips.len();
-    match tips_len {
-        0 => Err(BlockchainError::ExpectedTips),
-        1 => Ok(tips.into_iter().collect()),
-        _ => {
-            let mut scores: Vec<(Hash, CumulativeDifficulty)> = Vec::with_capacity(tips_len);
-            for hash in tips {
-                let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(&hash).await?;
-                scores.push((hash, cumulative_difficulty));
-            }
-
-            sort_descending_by_cumulative_difficulty(&mut scores);
-            Ok(scores.into_iter().map(|(hash, _)| hash).collect())
-        }
-    }
-}
-
 // determine he lowest height possible based on tips and do N+1
 pub async fn calculate_height_at_tips<'a, D, I>(provider: &D, tips: I) -> Result<u64, BlockchainError>
 where
@@ -127,7 +99,7 @@ where
 }
 
 // Find the newest tip based on the timestamp of the blocks
-pub async fn find_newest_tip_by_timestamp<'a, D, I>(provider: &D, tips: I) -> Result<(&'a Hash, TimestampMillis), BlockchainError>
+pub async fn find_newest_tip_by_timestamp<'a, D, I>(provider: &D, tips: I) -> Result<(&'a Hash, u64), BlockchainError>
 where
     D: DifficultyProvider,
     I: Iterator<Item = &'a Hash> + ExactSizeIterator
@@ -156,4 +128,148 @@ where
             Ok((newest_tip.ok_or(BlockchainError::ExpectedTips)?, timestamp))
         }
     }
-}
\ No newline at end of file
+}
+// Tip with all the metadata needed to select it as a parent of a new block
+#[derive(Debug, Clone)]
+pub struct TipCandidate {
+    pub hash: Hash,
+    pub height: u64,
+    pub difficulty: Difficulty,
+    pub cumulative_difficulty: CumulativeDifficulty,
+    // Lowest height of the tip from the mainchain
+    pub distance_from_mainchain: u64
+}
+
+// A tip difficulty can't be less than 91% of the best tip difficulty
+pub fn is_tip_difficulty_valid(best_difficulty: Difficulty, difficulty: Difficulty) -> bool {
+    const MAX_DEVIATION: Difficulty = Difficulty::from_u64(91);
+    const PERCENTAGE: Difficulty = Difficulty::from_u64(100);
+
+    best_difficulty * MAX_DEVIATION / PERCENTAGE < difficulty
+}
+
+// Select the tips to use as parents for a new block
+// The best tip (highest cumulative difficulty) is always selected
+// Others tips are only selected if their difficulty is valid against the best tip
+// and if they didn't deviate too much from the mainchain
+// Returned tips are sorted by cumulative difficulty (and hash if equals) in descending order
+// and limited to TIPS_LIMIT
+pub fn select_tips(candidates: &[TipCandidate], current_height: u64) -> IndexSet<Hash> {
+    trace!("select tips from {} candidates", candidates.len());
+    let mut sorted: Vec<&TipCandidate> = candidates.iter().collect();
+    sorted.sort_by(|a, b| {
+        if a.cumulative_difficulty != b.cumulative_difficulty {
+            b.cumulative_difficulty.cmp(&a.cumulative_difficulty)
+        } else {
+            b.hash.cmp(&a.hash)
+        }
+    });
+
+    let mut selected = IndexSet::with_capacity(TIPS_LIMIT);
+    let Some(best_tip) = sorted.first() else {
+        return selected
+    };
+
+    for tip in sorted.iter() {
+        if tip.hash != best_tip.hash {
+            if !is_tip_difficulty_valid(best_tip.difficulty, tip.difficulty) {
+                trace!("Tip {} is not selected, difficulty can't be less than 91% of {}", tip.hash, best_tip.hash);
+                continue;
+            }
+
+            if tip.distance_from_mainchain <= current_height && current_height - tip.distance_from_mainchain >= STABLE_LIMIT {
+                trace!("Tip {} is not selected, too far from mainchain (distance: {}, height: {})", tip.hash, tip.distance_from_mainchain, current_height);
+                continue;
+            }
+        }
+
+        selected.insert(tip.hash.clone());
+        if selected.len() >= TIPS_LIMIT {
+            break;
+        }
+    }
+
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: u8, height: u64, difficulty: u64, cumulative_difficulty: u64, distance_from_mainchain: u64) -> TipCandidate {
+        TipCandidate {
+            hash: Hash::new([id; 32]),
+            height,
+            difficulty: Difficulty::from_u64(difficulty),
+            cumulative_difficulty: CumulativeDifficulty::from_u64(cumulative_difficulty),
+            distance_from_mainchain
+        }
+    }
+
+    #[test]
+    fn test_select_tips_sorted_by_cumulative_difficulty() {
+        let candidates = vec![
+            candidate(1, 100, 1000, 5000, 100),
+            candidate(2, 100, 1000, 7000, 100),
+            candidate(3, 99, 1000, 6000, 99)
+        ];
+
+        let selected = select_tips(&candidates, 100);
+        let expected: Vec<Hash> = vec![Hash::new([2; 32]), Hash::new([3; 32]), Hash::new([1; 32])];
+        assert_eq!(selected.into_iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_select_tips_same_cumulative_difficulty() {
+        let candidates = vec![
+            candidate(1, 100, 1000, 5000, 100),
+            candidate(2, 100, 1000, 5000, 100)
+        ];
+
+        // Highest hash is first
+        let selected = select_tips(&candidates, 100);
+        assert_eq!(selected.into_iter().collect::<Vec<_>>(), vec![Hash::new([2; 32]), Hash::new([1; 32])]);
+    }
+
+    #[test]
+    fn test_select_tips_invalid_difficulty() {
+        let candidates = vec![
+            candidate(1, 100, 1000, 7000, 100),
+            // 90% of the best tip difficulty
+            candidate(2, 100, 900, 6000, 100),
+            candidate(3, 100, 950, 6500, 100)
+        ];
+
+        let selected = select_tips(&candidates, 100);
+        assert_eq!(selected.into_iter().collect::<Vec<_>>(), vec![Hash::new([1; 32]), Hash::new([3; 32])]);
+    }
+
+    #[test]
+    fn test_select_tips_deviated_from_mainchain() {
+        let candidates = vec![
+            candidate(1, 100, 1000, 7000, 100),
+            candidate(2, 95, 1000, 6000, 100 - STABLE_LIMIT),
+            candidate(3, 99, 1000, 6500, 100 - STABLE_LIMIT + 1)
+        ];
+
+        let selected = select_tips(&candidates, 100);
+        assert_eq!(selected.into_iter().collect::<Vec<_>>(), vec![Hash::new([1; 32]), Hash::new([3; 32])]);
+    }
+
+    #[test]
+    fn test_select_tips_limit() {
+        let candidates: Vec<TipCandidate> = (1..=TIPS_LIMIT as u8 + 2)
+            .map(|i| candidate(i, 100, 1000, 5000 + i as u64, 100))
+            .collect();
+
+        let selected = select_tips(&candidates, 100);
+        assert_eq!(selected.len(), TIPS_LIMIT);
+        // Heaviest tip is always first
+        assert_eq!(selected.first(), Some(&Hash::new([TIPS_LIMIT as u8 + 2; 32])));
+    }
+
+    #[test]
+    fn test_select_tips_empty() {
+        assert!(select_tips(&[], 100).is_empty());
+    }
+}
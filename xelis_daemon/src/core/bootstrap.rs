@@ -0,0 +1,336 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path
+};
+use log::{debug, info};
+use xelis_common::{
+    block::Block,
+    crypto::{hash, Hash, Hashable, HASH_SIZE},
+    network::Network,
+    serializer::{Reader, ReaderError, Serializer, Writer}
+};
+use crate::config::MAX_BLOCK_SIZE;
+use super::{
+    blockchain::Blockchain,
+    error::BlockchainError,
+    storage::Storage
+};
+
+// Magic bytes at the start of every chain export file
+const CHAIN_EXPORT_MAGIC: [u8; 8] = *b"XELCHAIN";
+// Current version of the chain export format
+pub const CHAIN_EXPORT_VERSION: u8 = 1;
+// Size of the header: magic, version, network and genesis hash
+const CHAIN_EXPORT_HEADER_SIZE: usize = CHAIN_EXPORT_MAGIC.len() + 1 + 1 + HASH_SIZE;
+// Maximum blocks written in a chunk, each chunk has its own checksum
+const CHAIN_EXPORT_CHUNK_SIZE: usize = 64;
+// Maximum size of a chunk accepted, topoheight of each block included
+const CHAIN_EXPORT_MAX_CHUNK_BYTES: usize = 2 + CHAIN_EXPORT_CHUNK_SIZE * (8 + MAX_BLOCK_SIZE);
+
+// Header written at the start of a chain export file
+// The file is then a list of chunks, each one is:
+// bytes length (u32), blocks count (u16), (topoheight, block) for each block, blake3 checksum of the bytes
+// A chunk with a zero length marks the end of the export
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChainExportHeader {
+    pub version: u8,
+    pub network: Network,
+    pub genesis_hash: Hash
+}
+
+impl Serializer for ChainExportHeader {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_bytes(&CHAIN_EXPORT_MAGIC);
+        writer.write_u8(self.version);
+        self.network.write(writer);
+        writer.write_hash(&self.genesis_hash);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let magic: [u8; 8] = reader.read_bytes(CHAIN_EXPORT_MAGIC.len())?;
+        if magic != CHAIN_EXPORT_MAGIC {
+            return Err(ReaderError::InvalidValue)
+        }
+
+        Ok(Self {
+            version: reader.read_u8()?,
+            network: Network::read(reader)?,
+            genesis_hash: reader.read_hash()?
+        })
+    }
+
+    fn size(&self) -> usize {
+        CHAIN_EXPORT_HEADER_SIZE
+    }
+}
+
+// Write a chunk of blocks with its checksum
+fn write_chunk<W: Write>(output: &mut W, blocks: &[(u64, Block)]) -> Result<(), BlockchainError> {
+    let mut writer = Writer::new();
+    writer.write_u16(blocks.len() as u16);
+    for (topoheight, block) in blocks {
+        writer.write_u64(topoheight);
+        block.write(&mut writer);
+    }
+
+    let bytes = writer.bytes();
+    output.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    output.write_all(&bytes)?;
+    output.write_all(hash(&bytes).as_bytes())?;
+
+    Ok(())
+}
+
+// Read the next chunk of blocks and verify its checksum
+// Returns None once the end of the export is reached
+fn read_chunk<R: Read>(input: &mut R, index: u64) -> Result<Option<Vec<(u64, Block)>>, BlockchainError> {
+    let mut length = [0u8; 4];
+    input.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length) as usize;
+    if length == 0 {
+        return Ok(None)
+    }
+
+    if length > CHAIN_EXPORT_MAX_CHUNK_BYTES {
+        return Err(BlockchainError::InvalidChainExportChunk(index))
+    }
+
+    let mut bytes = vec![0u8; length];
+    input.read_exact(&mut bytes)?;
+    let mut checksum = [0u8; HASH_SIZE];
+    input.read_exact(&mut checksum)?;
+    if hash(&bytes) != Hash::new(checksum) {
+        return Err(BlockchainError::InvalidChainExportChunk(index))
+    }
+
+    let mut reader = Reader::new(&bytes);
+    let count = reader.read_u16()? as usize;
+    if count > CHAIN_EXPORT_CHUNK_SIZE {
+        return Err(BlockchainError::InvalidChainExportChunk(index))
+    }
+
+    let mut blocks = Vec::with_capacity(count);
+    for _ in 0..count {
+        let topoheight = reader.read_u64()?;
+        let block = Block::read(&mut reader)?;
+        blocks.push((topoheight, block));
+    }
+
+    // No trailing bytes allowed
+    if reader.size() != 0 {
+        return Err(BlockchainError::InvalidChainExportChunk(index))
+    }
+
+    Ok(Some(blocks))
+}
+
+// Read the header of the export and verify it can be imported on this network
+fn read_header<R: Read>(input: &mut R, network: &Network) -> Result<ChainExportHeader, BlockchainError> {
+    let mut bytes = [0u8; CHAIN_EXPORT_HEADER_SIZE];
+    input.read_exact(&mut bytes)?;
+    let header = ChainExportHeader::read(&mut Reader::new(&bytes))?;
+    if header.version != CHAIN_EXPORT_VERSION {
+        return Err(BlockchainError::UnsupportedChainExportVersion(header.version, CHAIN_EXPORT_VERSION))
+    }
+
+    if header.network != *network {
+        return Err(BlockchainError::ChainExportNetworkMismatch(header.network, *network))
+    }
+
+    Ok(header)
+}
+
+// Export all the blocks in topological order until the requested topoheight (included)
+// Returns the number of blocks written in the file
+pub async fn export_chain<S: Storage>(blockchain: &Blockchain<S>, path: &str, until_topoheight: Option<u64>) -> Result<u64, BlockchainError> {
+    let storage = blockchain.get_storage().read().await;
+    // All blocks from the genesis are required to verify them again
+    if let Some(pruned_topoheight) = storage.get_pruned_topoheight().await? {
+        return Err(BlockchainError::PrunedData(0, pruned_topoheight))
+    }
+
+    let until_topoheight = until_topoheight.unwrap_or(u64::MAX).min(blockchain.get_topo_height());
+    let header = ChainExportHeader {
+        version: CHAIN_EXPORT_VERSION,
+        network: *blockchain.get_network(),
+        genesis_hash: storage.get_hash_at_topo_height(0).await?
+    };
+
+    info!("Exporting chain until topoheight {} to {}...", until_topoheight, path);
+    let mut output = BufWriter::new(File::create(path)?);
+    output.write_all(&header.to_bytes())?;
+
+    let mut chunk = Vec::with_capacity(CHAIN_EXPORT_CHUNK_SIZE);
+    for topoheight in 0..=until_topoheight {
+        let hash = storage.get_hash_at_topo_height(topoheight).await?;
+        let block = storage.get_block_by_hash(&hash).await?;
+        chunk.push((topoheight, block));
+
+        if chunk.len() == CHAIN_EXPORT_CHUNK_SIZE || topoheight == until_topoheight {
+            write_chunk(&mut output, &chunk)?;
+            chunk.clear();
+            info!("Exported {}/{} blocks", topoheight + 1, until_topoheight + 1);
+        }
+    }
+
+    // End of the export
+    output.write_all(&0u32.to_be_bytes())?;
+    output.flush()?;
+
+    Ok(until_topoheight + 1)
+}
+
+// Export the chain until a stable topoheight (included)
+// Blocks below it can't be reorganized anymore, so the storage is only locked
+// while reading each chunk and new blocks can still be added during the export
+pub async fn export_stable_chain<S: Storage>(blockchain: &Blockchain<S>, path: &Path, until_topoheight: u64) -> Result<u64, BlockchainError> {
+    let header = {
+        let storage = blockchain.get_storage().read().await;
+        if let Some(pruned_topoheight) = storage.get_pruned_topoheight().await? {
+            return Err(BlockchainError::PrunedData(0, pruned_topoheight))
+        }
+
+        ChainExportHeader {
+            version: CHAIN_EXPORT_VERSION,
+            network: *blockchain.get_network(),
+            genesis_hash: storage.get_hash_at_topo_height(0).await?
+        }
+    };
+
+    debug!("Exporting stable chain until topoheight {} to {}", until_topoheight, path.display());
+    let mut output = BufWriter::new(File::create(path)?);
+    output.write_all(&header.to_bytes())?;
+
+    let mut start = 0;
+    while start <= until_topoheight {
+        let end = (start + CHAIN_EXPORT_CHUNK_SIZE as u64 - 1).min(until_topoheight);
+        let chunk = {
+            let storage = blockchain.get_storage().read().await;
+            let mut chunk = Vec::with_capacity(CHAIN_EXPORT_CHUNK_SIZE);
+            for topoheight in start..=end {
+                let hash = storage.get_hash_at_topo_height(topoheight).await?;
+                chunk.push((topoheight, storage.get_block_by_hash(&hash).await?));
+            }
+            chunk
+        };
+        write_chunk(&mut output, &chunk)?;
+        start = end + 1;
+    }
+
+    output.write_all(&0u32.to_be_bytes())?;
+    output.flush()?;
+    output.get_ref().sync_all()?;
+
+    Ok(until_topoheight + 1)
+}
+
+// Read the genesis block of an export
+// It is used to initialize a new chain on a network without hardcoded genesis block
+pub fn read_genesis_block(path: &str, network: &Network) -> Result<Block, BlockchainError> {
+    let mut input = BufReader::new(File::open(path)?);
+    let header = read_header(&mut input, network)?;
+    let genesis = read_chunk(&mut input, 0)?
+        .and_then(|blocks| blocks.into_iter().next())
+        .filter(|(topoheight, block)| *topoheight == 0 && block.hash() == header.genesis_hash)
+        .map(|(_, block)| block);
+
+    genesis.ok_or(BlockchainError::ChainExportGenesisMismatch(header.genesis_hash))
+}
+
+// Import all the blocks of an export through the normal verification of a new block
+// Blocks already in our chain are skipped, so an interrupted import is resumed
+// Returns the number of blocks added to the chain
+pub async fn import_chain<S: Storage>(blockchain: &Blockchain<S>, path: &str) -> Result<u64, BlockchainError> {
+    let mut input = BufReader::new(File::open(path)?);
+    let header = read_header(&mut input, blockchain.get_network())?;
+    {
+        let storage = blockchain.get_storage().read().await;
+        if storage.get_hash_at_topo_height(0).await? != header.genesis_hash {
+            return Err(BlockchainError::ChainExportGenesisMismatch(header.genesis_hash))
+        }
+    }
+
+    info!("Importing chain from {}...", path);
+    let mut imported = 0;
+    let mut last_block = None;
+    let mut index = 0;
+    while let Some(blocks) = read_chunk(&mut input, index)? {
+        for (topoheight, block) in blocks {
+            let hash = block.hash();
+            let known = {
+                let storage = blockchain.get_storage().read().await;
+                storage.has_block_with_hash(&hash).await?
+            };
+
+            if known {
+                debug!("Block {} at topoheight {} is already in chain, skipping it", hash, topoheight);
+            } else {
+                if imported == 0 && topoheight > 0 {
+                    info!("Resuming chain import from topoheight {}", topoheight);
+                }
+                blockchain.add_new_block(block, false, false).await?;
+                imported += 1;
+            }
+            last_block = Some((topoheight, hash));
+        }
+
+        if let Some((topoheight, _)) = last_block.as_ref() {
+            info!("Imported chain until topoheight {} ({} new blocks)", topoheight, imported);
+        }
+        index += 1;
+    }
+
+    // Same blocks must produce the same DAG order
+    if let Some((topoheight, hash)) = last_block {
+        let storage = blockchain.get_storage().read().await;
+        if storage.get_hash_at_topo_height(topoheight).await? != hash {
+            return Err(BlockchainError::ChainImportOrderMismatch(hash, topoheight))
+        }
+    }
+
+    info!("Chain import completed, {} blocks added", imported);
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_export_header() {
+        let header = ChainExportHeader {
+            version: CHAIN_EXPORT_VERSION,
+            network: Network::Testnet,
+            genesis_hash: Hash::new([1u8; 32])
+        };
+        let bytes = header.to_bytes();
+        assert_eq!(bytes.len(), CHAIN_EXPORT_HEADER_SIZE);
+        assert_eq!(read_header(&mut bytes.as_slice(), &Network::Testnet).unwrap(), header);
+
+        // Not the same network
+        assert!(matches!(
+            read_header(&mut bytes.as_slice(), &Network::Mainnet),
+            Err(BlockchainError::ChainExportNetworkMismatch(Network::Testnet, Network::Mainnet))
+        ));
+
+        // Not a chain export
+        let mut invalid = bytes.clone();
+        invalid[0] = 0;
+        assert!(read_header(&mut invalid.as_slice(), &Network::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_chunk_checksum() {
+        let mut bytes = Vec::new();
+        write_chunk(&mut bytes, &[]).unwrap();
+        assert!(read_chunk(&mut bytes.as_slice(), 0).unwrap().unwrap().is_empty());
+
+        // Corrupted chunk
+        bytes[4] = 1;
+        assert!(matches!(read_chunk(&mut bytes.as_slice(), 3), Err(BlockchainError::InvalidChainExportChunk(3))));
+
+        // End of the export
+        assert!(read_chunk(&mut [0u8; 4].as_slice(), 0).unwrap().is_none());
+    }
+}
@@ -0,0 +1,130 @@
+use log::trace;
+use xelis_common::{
+    serializer::Serializer,
+    transaction::TransactionType
+};
+use super::{
+    error::BlockchainError,
+    storage::Storage
+};
+
+// Statistics aggregated over a range of blocks in topological order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChainStats {
+    pub blocks_count: u64,
+    // Sum of the block rewards, dev fee included
+    pub total_emission: u64,
+    // Sum of the fees of the transactions executed
+    pub total_fees: u64,
+    // Transfers outputs of the transactions executed
+    pub transfers_count: u64,
+    // Average time in milliseconds between two blocks
+    pub average_block_interval: u64,
+    // Average size in bytes of a block, its transactions included
+    pub average_block_size: u64
+}
+
+// Aggregate the stats of all blocks from `start_topoheight` to `end_topoheight` (inclusive)
+// Each block is read only one time
+pub async fn get_chain_stats<S: Storage>(storage: &S, start_topoheight: u64, end_topoheight: u64) -> Result<ChainStats, BlockchainError> {
+    let mut stats = ChainStats::default();
+    if start_topoheight > end_topoheight {
+        return Ok(stats)
+    }
+
+    trace!("get chain stats from {} to {}", start_topoheight, end_topoheight);
+    let mut total_size = 0;
+    let mut first_timestamp = None;
+    let mut last_timestamp = 0;
+    for topoheight in start_topoheight..=end_topoheight {
+        let hash = storage.get_hash_at_topo_height(topoheight).await?;
+        let block = storage.get_block_by_hash(&hash).await?;
+        stats.total_emission = stats.total_emission.checked_add(storage.get_block_reward_at_topo_height(topoheight)?)
+            .ok_or(BlockchainError::SupplyOverflow(topoheight))?;
+        stats.total_fees = stats.total_fees.checked_add(storage.get_block_fees_at_topo_height(topoheight)?)
+            .ok_or(BlockchainError::Overflow)?;
+
+        for (tx, tx_hash) in block.get_transactions().iter().zip(block.get_txs_hashes()) {
+            // A TX included in several blocks is only counted in the one executing it
+            if !storage.is_tx_executed_in_block(tx_hash, &hash)? {
+                continue
+            }
+
+            if let TransactionType::Transfers(transfers) = tx.get_data() {
+                stats.transfers_count += transfers.len() as u64;
+            }
+        }
+
+        total_size += block.size() as u64;
+        first_timestamp.get_or_insert(block.get_timestamp());
+        last_timestamp = block.get_timestamp();
+        stats.blocks_count += 1;
+    }
+
+    // Timestamps are not always increasing in topological order, only the range bounds are used
+    if stats.blocks_count > 1 {
+        let first_timestamp = first_timestamp.unwrap_or(last_timestamp);
+        stats.average_block_interval = last_timestamp.saturating_sub(first_timestamp) / (stats.blocks_count - 1);
+    }
+    stats.average_block_size = total_size / stats.blocks_count;
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::Hash;
+    use crate::core::storage::{
+        test_utils::{add_linear_chain, open_temp_storage},
+        BlockDagProvider,
+        BlockProvider,
+        SledStorage
+    };
+    use super::*;
+
+    // Build a linear test chain of `count` blocks with a block every 15s
+    // Block at topoheight N has a reward of 1000 + N and fees of N
+    async fn create_test_chain(storage: &mut SledStorage, count: u64) -> Vec<Hash> {
+        let hashes = add_linear_chain(storage, count, |height, block| block.timestamp(height * 15_000)).await;
+        for topoheight in 0..count {
+            storage.set_block_reward_at_topo_height(topoheight, 1000 + topoheight).unwrap();
+            storage.set_block_fees_at_topo_height(topoheight, topoheight).unwrap();
+        }
+
+        hashes
+    }
+
+    #[tokio::test]
+    async fn test_get_chain_stats() {
+        let (_dir, mut storage) = open_temp_storage("chain-stats");
+        let hashes = create_test_chain(&mut storage, 20).await;
+        let block_size = storage.get_block_by_hash(&hashes[0]).await.unwrap().size() as u64;
+
+        let stats = get_chain_stats(&storage, 0, 19).await.unwrap();
+        assert_eq!(stats, ChainStats {
+            blocks_count: 20,
+            // 20 * 1000 + (0 + 1 + ... + 19)
+            total_emission: 20_190,
+            total_fees: 190,
+            transfers_count: 0,
+            average_block_interval: 15_000,
+            average_block_size: block_size
+        });
+
+        // Sub range
+        let stats = get_chain_stats(&storage, 5, 9).await.unwrap();
+        assert_eq!(stats.blocks_count, 5);
+        assert_eq!(stats.total_emission, 5_035);
+        assert_eq!(stats.total_fees, 35);
+        assert_eq!(stats.average_block_interval, 15_000);
+
+        // A single block has no interval
+        let stats = get_chain_stats(&storage, 7, 7).await.unwrap();
+        assert_eq!(stats.blocks_count, 1);
+        assert_eq!(stats.total_fees, 7);
+        assert_eq!(stats.average_block_interval, 0);
+
+        // Invalid range
+        assert_eq!(get_chain_stats(&storage, 9, 5).await.unwrap(), ChainStats::default());
+    }
+}
@@ -0,0 +1,41 @@
+use std::ops::Deref;
+use tokio::sync::RwLockReadGuard;
+use super::{
+    storage::Storage,
+    top_state::TopState
+};
+
+// Read only view of the chain used to serve the RPC
+// Blocks are applied while holding the storage write guard, and the top state
+// is updated before releasing it. The top state is copied only once the read guard
+// is held, so the topoheight of the view always matches the storage it reads from
+pub struct ChainStateView<'a, S: Storage> {
+    storage: RwLockReadGuard<'a, S>,
+    top_state: TopState
+}
+
+impl<'a, S: Storage> ChainStateView<'a, S> {
+    pub fn new(storage: RwLockReadGuard<'a, S>, top_state: TopState) -> Self {
+        Self {
+            storage,
+            top_state
+        }
+    }
+
+    pub fn get_top_state(&self) -> &TopState {
+        &self.top_state
+    }
+
+    // Topoheight of the snapshot this view is serving
+    pub fn get_topoheight(&self) -> u64 {
+        self.top_state.topoheight
+    }
+}
+
+impl<'a, S: Storage> Deref for ChainStateView<'a, S> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        &self.storage
+    }
+}
@@ -0,0 +1,116 @@
+use log::trace;
+use xelis_common::crypto::Hash;
+use super::{
+    error::BlockchainError,
+    storage::{BlocksAtHeightProvider, DagOrderProvider, DifficultyProvider, PrunedTopoheightProvider, Storage}
+};
+
+// A block found at a height of the DAG
+#[derive(Debug, PartialEq, Eq)]
+pub struct DagBlockAtHeight {
+    pub hash: Hash,
+    // None if the block is not topologically ordered
+    pub topoheight: Option<u64>,
+    // Is the block one of our current tips
+    pub is_tip: bool
+}
+
+// Get all the blocks at a height of the DAG in the order they were processed, ordered or not
+// Heights above our top height don't have any block yet, while heights below the pruned point are an error
+pub async fn get_dag_blocks_at_height<S: Storage>(storage: &S, height: u64, include_orphaned: bool) -> Result<Vec<DagBlockAtHeight>, BlockchainError> {
+    trace!("get dag blocks at height {}", height);
+    if let Some(pruned_topoheight) = storage.get_pruned_topoheight().await? {
+        let pruned_hash = storage.get_hash_at_topo_height(pruned_topoheight).await?;
+        let pruned_height = storage.get_height_for_block_hash(&pruned_hash).await?;
+        if height < pruned_height {
+            return Err(BlockchainError::PrunedHeight(height, pruned_height))
+        }
+    }
+
+    if height > storage.get_top_height()? || !storage.has_blocks_at_height(height).await? {
+        return Ok(Vec::new())
+    }
+
+    let tips = storage.get_tips().await?;
+    let mut blocks = Vec::new();
+    for hash in storage.get_blocks_at_height(height).await? {
+        let topoheight = if storage.is_block_topological_ordered(&hash).await {
+            Some(storage.get_topo_height_for_hash(&hash).await?)
+        } else {
+            None
+        };
+
+        if topoheight.is_none() && !include_orphaned {
+            continue;
+        }
+
+        let is_tip = tips.contains(&hash);
+        blocks.push(DagBlockAtHeight {
+            hash,
+            topoheight,
+            is_tip
+        });
+    }
+
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexSet;
+    use crate::core::storage::{
+        test_utils::{open_temp_storage, TestBlock},
+        SledStorage,
+        Tips
+    };
+    use super::*;
+
+    // Store a block at the height, ordered at the topoheight if set
+    async fn add_block(storage: &mut SledStorage, height: u64, tips: IndexSet<Hash>, salt: u8, topoheight: Option<u64>) -> Hash {
+        let block = TestBlock::new(height).timestamp(height * 1000).tips(tips).salt(salt);
+        match topoheight {
+            Some(topoheight) => block.save_at(storage, topoheight).await,
+            None => block.save(storage).await
+        }
+    }
+
+    // Genesis, then two sibling blocks at height 1, only the first one is ordered
+    async fn create_test_dag(storage: &mut SledStorage) -> (Hash, Hash, Hash) {
+        let genesis = add_block(storage, 0, IndexSet::new(), 0, Some(0)).await;
+        let tips = [genesis.clone()].into_iter().collect::<IndexSet<_>>();
+        let ordered = add_block(storage, 1, tips.clone(), 1, Some(1)).await;
+        let unordered = add_block(storage, 1, tips, 2, None).await;
+        storage.set_top_height(1).unwrap();
+        storage.store_tips(&[ordered.clone(), unordered.clone()].into_iter().collect::<Tips>()).unwrap();
+
+        (genesis, ordered, unordered)
+    }
+
+    #[tokio::test]
+    async fn test_sibling_blocks_at_height() {
+        let (_dir, mut storage) = open_temp_storage("dag-height");
+        let (genesis, ordered, unordered) = create_test_dag(&mut storage).await;
+
+        let blocks = get_dag_blocks_at_height(&storage, 1, true).await.unwrap();
+        assert_eq!(blocks, vec![
+            DagBlockAtHeight { hash: ordered.clone(), topoheight: Some(1), is_tip: true },
+            DagBlockAtHeight { hash: unordered, topoheight: None, is_tip: true }
+        ]);
+
+        // Without the unordered block
+        let blocks = get_dag_blocks_at_height(&storage, 1, false).await.unwrap();
+        assert_eq!(blocks, vec![DagBlockAtHeight { hash: ordered, topoheight: Some(1), is_tip: true }]);
+
+        // Genesis is not a tip anymore
+        let blocks = get_dag_blocks_at_height(&storage, 0, true).await.unwrap();
+        assert_eq!(blocks, vec![DagBlockAtHeight { hash: genesis, topoheight: Some(0), is_tip: false }]);
+
+        // Above our top height
+        assert!(get_dag_blocks_at_height(&storage, 2, true).await.unwrap().is_empty());
+
+        // Below the pruned point
+        storage.set_pruned_topoheight(1).await.unwrap();
+        assert!(matches!(get_dag_blocks_at_height(&storage, 0, true).await, Err(BlockchainError::PrunedHeight(0, 1))));
+        assert_eq!(get_dag_blocks_at_height(&storage, 1, true).await.unwrap().len(), 2);
+    }
+}
@@ -3,7 +3,6 @@ use humantime::format_duration;
 use log::trace;
 use xelis_common::{
     difficulty::Difficulty,
-    time::TimestampMillis,
     utils::format_difficulty,
     varuint::VarUint
 };
@@ -55,7 +54,7 @@ pub fn kalman_filter(z: VarUint, x_est_prev: VarUint, p_prev: VarUint) -> (VarUi
 
 // Calculate the required difficulty for the next block based on the solve time of the previous block
 // We are using a Kalman filter to estimate the hashrate and adjust the difficulty
-pub fn calculate_difficulty(parent_timestamp: TimestampMillis, timestamp: TimestampMillis, previous_difficulty: Difficulty, p: VarUint, minimum_difficulty: Difficulty) -> (Difficulty, VarUint) {
+pub fn calculate_difficulty(parent_timestamp: u64, timestamp: u64, previous_difficulty: Difficulty, p: VarUint, minimum_difficulty: Difficulty) -> (Difficulty, VarUint) {
     let mut solve_time = timestamp - parent_timestamp;
 
     // Someone trying to do something shady or really lucky
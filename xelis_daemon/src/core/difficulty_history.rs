@@ -0,0 +1,132 @@
+use log::trace;
+use xelis_common::{
+    crypto::Hash,
+    difficulty::Difficulty,
+    varuint::VarUint
+};
+use super::{
+    blockdag,
+    error::BlockchainError,
+    storage::Storage
+};
+
+// A sampled block used to chart the difficulty and the network hashrate
+#[derive(Clone, Debug)]
+pub struct DifficultySample {
+    pub topoheight: u64,
+    pub hash: Hash,
+    pub timestamp: u64,
+    pub difficulty: Difficulty,
+    // Time in milliseconds since the selected parent, None for the genesis block
+    pub solve_time: Option<u64>,
+    // Estimated covariance P of the difficulty Kalman filter, if stored
+    pub covariance: Option<VarUint>
+}
+
+// Compute how many samples a range will return
+pub fn count_samples(start_topoheight: u64, end_topoheight: u64, step: u64) -> u64 {
+    if step == 0 || start_topoheight > end_topoheight {
+        return 0
+    }
+
+    (end_topoheight - start_topoheight) / step + 1
+}
+
+// Sample every `step` block in topological order from `start_topoheight` to `end_topoheight` (inclusive)
+// The solve time is the timestamp delta with the selected parent (tip with the highest cumulative difficulty)
+pub async fn get_difficulty_history<S: Storage>(storage: &S, start_topoheight: u64, end_topoheight: u64, step: u64) -> Result<Vec<DifficultySample>, BlockchainError> {
+    let mut samples = Vec::with_capacity(count_samples(start_topoheight, end_topoheight, step) as usize);
+    if step == 0 {
+        return Ok(samples)
+    }
+
+    trace!("get difficulty history from {} to {} with step {}", start_topoheight, end_topoheight, step);
+    for topoheight in (start_topoheight..=end_topoheight).step_by(step as usize) {
+        let hash = storage.get_hash_at_topo_height(topoheight).await?;
+        let header = storage.get_block_header_by_hash(&hash).await?;
+        let difficulty = storage.get_difficulty_for_block_hash(&hash).await?;
+        let covariance = storage.get_estimated_covariance_for_block_hash(&hash).await.ok();
+
+        let tips = header.get_tips();
+        let solve_time = if tips.is_empty() {
+            None
+        } else {
+            let parent = blockdag::find_best_tip_by_cumulative_difficulty(storage, tips.iter()).await?;
+            let parent_timestamp = storage.get_timestamp_for_block_hash(parent).await?;
+            Some(header.get_timestamp().saturating_sub(parent_timestamp))
+        };
+
+        samples.push(DifficultySample {
+            topoheight,
+            hash,
+            timestamp: header.get_timestamp(),
+            difficulty,
+            solve_time,
+            covariance
+        });
+    }
+
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::storage::{
+        test_utils::{add_linear_chain, open_temp_storage},
+        SledStorage
+    };
+    use super::*;
+
+    // Deterministic solve time of the block at `topoheight`
+    fn solve_time_at(topoheight: u64) -> u64 {
+        10_000 + (topoheight % 7) * 1_000
+    }
+
+    // Build a linear test chain of `count` blocks with known timestamps
+    async fn create_test_chain(storage: &mut SledStorage, count: u64) -> Vec<Hash> {
+        add_linear_chain(storage, count, |height, block| block
+            .timestamp((1..=height).map(solve_time_at).sum())
+            .difficulty(VarUint::from_u64(1000 + height))
+            .covariance(VarUint::from_u64(height * 2))
+        ).await
+    }
+
+    #[test]
+    fn test_count_samples() {
+        assert_eq!(count_samples(0, 0, 1), 1);
+        assert_eq!(count_samples(0, 99, 1), 100);
+        assert_eq!(count_samples(0, 99, 10), 10);
+        assert_eq!(count_samples(0, 100, 10), 11);
+        assert_eq!(count_samples(5, 4, 1), 0);
+        assert_eq!(count_samples(0, 10, 0), 0);
+    }
+
+    #[tokio::test]
+    async fn test_get_difficulty_history() {
+        let (_dir, mut storage) = open_temp_storage("difficulty-history");
+        let hashes = create_test_chain(&mut storage, 50).await;
+
+        let samples = get_difficulty_history(&storage, 0, 49, 1).await.unwrap();
+        assert_eq!(samples.len(), 50);
+        for (topoheight, sample) in samples.iter().enumerate() {
+            let topoheight = topoheight as u64;
+            assert_eq!(sample.topoheight, topoheight);
+            assert_eq!(sample.hash, hashes[topoheight as usize]);
+            assert_eq!(sample.difficulty, VarUint::from_u64(1000 + topoheight));
+            assert_eq!(sample.covariance, Some(VarUint::from_u64(topoheight * 2)));
+
+            // Genesis has no parent
+            let expected = if topoheight == 0 { None } else { Some(solve_time_at(topoheight)) };
+            assert_eq!(sample.solve_time, expected);
+        }
+
+        // Sample every 10th block, end is not aligned on the step
+        let samples = get_difficulty_history(&storage, 3, 45, 10).await.unwrap();
+        let topoheights = samples.iter().map(|sample| sample.topoheight).collect::<Vec<_>>();
+        assert_eq!(topoheights, vec![3, 13, 23, 33, 43]);
+        assert_eq!(samples.len() as u64, count_samples(3, 45, 10));
+        for sample in samples {
+            assert_eq!(sample.solve_time, Some(solve_time_at(sample.topoheight)));
+        }
+    }
+}
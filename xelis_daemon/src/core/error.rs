@@ -1,20 +1,30 @@
 use crate::p2p::error::P2pError;
-use std::sync::PoisonError;
+use std::{borrow::Cow, sync::PoisonError};
 use thiserror::Error;
+use serde_json::{json, Value};
 use xelis_common::{
+    api::daemon::{
+        FeeTooLowErrorData,
+        InvalidAddressErrorData,
+        InvalidNonceErrorData,
+        NotFoundErrorData,
+        PrunedErrorData,
+        FEE_TOO_LOW_ERROR_CODE,
+        INVALID_NONCE_ERROR_CODE
+    },
     crypto::{
         bech32::Bech32Error,
-        elgamal::DecompressionError,
+        elgamal::{DecompressionError, PublicKeyError},
         proofs::ProofVerificationError,
         Address,
         Hash,
         XelisHashError
     },
     difficulty::DifficultyError,
+    network::Network,
     prompt::PromptError,
     rpc_server::InternalRpcError,
     serializer::ReaderError,
-    time::TimestampMillis,
     transaction::verify::VerificationError,
     utils::format_xelis
 };
@@ -44,6 +54,8 @@ pub enum DiskContext {
     LastTopoHeightForBalance,
     #[error("get block reward at topoheight")]
     BlockRewardAtTopoHeight,
+    #[error("get block fees at topoheight")]
+    BlockFeesAtTopoHeight,
     #[error("get supply at topoheight")]
     SupplyAtTopoHeight,
     #[error("get blocks at height")]
@@ -110,9 +122,11 @@ pub enum BlockchainError {
     #[error("Transaction size is {} while limit is {}", human_bytes(*_0 as f64), human_bytes(*_1 as f64))]
     TxTooBig(usize, usize),
     #[error("Timestamp {} is less than parent", _0)]
-    TimestampIsLessThanParent(TimestampMillis),
+    TimestampIsLessThanParent(u64),
     #[error("Timestamp {} is greater than current time {}", _0, _1)]
-    TimestampIsInFuture(TimestampMillis, TimestampMillis), // left is expected, right is got
+    TimestampIsInFuture(u64, u64), // left is expected, right is got
+    #[error("Timestamp {} is out of the supported range", _0)]
+    TimestampOutOfRange(u64),
     #[error("Block height mismatch, expected {}, got {}.", _0, _1)]
     InvalidBlockHeight(u64, u64),
     #[error("Block height is zero which is not allowed")]
@@ -135,6 +149,8 @@ pub enum BlockchainError {
     InvalidTxInBlock(Hash),
     #[error("Tx {} not found in mempool", _0)]
     TxNotFound(Hash),
+    #[error("Tx {} is still included in {} blocks", _0, _1)]
+    TransactionStillReferenced(Hash, usize),
     #[error("Tx {} was present in mempool but not in sorted list!", _0)]
     TxNotFoundInSortedList(Hash),
     #[error("Tx {} already in mempool", _0)]
@@ -155,6 +171,14 @@ pub enum BlockchainError {
     InvalidTxFee(u64, u64),
     #[error("Fees are lower for this TX than the overrided TX, expected at least {}, got {}", format_xelis(*_0), format_xelis(*_1))]
     FeesToLowToOverride(u64, u64),
+    #[error("Tx with nonce {} has been replaced too many times, try again later", _0)]
+    TooManyTxReplacements(u64),
+    #[error("Transaction {} uses a type not activated at height {}", _0, _1)]
+    TxTypeNotActivated(Hash, u64),
+    #[error("Transaction {} has extra data not activated at height {}", _0, _1)]
+    TxExtraDataNotActivated(Hash, u64),
+    #[error("Transaction {} uses an invalid public key: {}", _0, _1)]
+    InvalidTxPublicKey(Hash, PublicKeyError),
     #[error("No account found for {}", _0)]
     AccountNotFound(Address),
     #[error("Address {} is not registered", _0)]
@@ -237,6 +261,8 @@ pub enum BlockchainError {
     ConfigMaxChainResponseSize,
     #[error("Invalid config sync mode")]
     ConfigSyncMode,
+    #[error("Invalid config: RPC TLS requires a certificate and a private key")]
+    ConfigRpcTls,
     #[error("Expected at least one tips")]
     ExpectedTips,
     #[error("Block {0} has invalid tips count: {1}")]
@@ -275,6 +301,12 @@ pub enum BlockchainError {
     NoNonceChanges(Address),
     #[error("Overflow detected")]
     Overflow,
+    #[error("Sum of the TXs fees in block {} is overflowing", _0)]
+    BlockFeesOverflow(Hash),
+    #[error("Block reward {} with fees {} is overflowing", _0, _1)]
+    BlockRewardOverflow(u64, u64),
+    #[error("Supply is overflowing at topoheight {}", _0)]
+    SupplyOverflow(u64),
     #[error("Error, block include a dead tx {}", _0)]
     DeadTx(Hash),
     #[error("A non-zero value is required for burn")]
@@ -309,21 +341,429 @@ pub enum BlockchainError {
     TransactionProof(ProofVerificationError),
     #[error("Error while generating pow hash")]
     POWHashError(#[from] XelisHashError),
+    #[error("Cannot pop {} blocks, maximum allowed is {}", _0, _1)]
+    RewindTooDeep(u64, u64),
+    #[error("Chain sync is in progress")]
+    ChainSyncInProgress,
+    #[error("Block {} contains the transaction {} multiple times", _0, _1)]
+    DuplicateTxInBlock(Hash, Hash),
+    #[error("Storage version {} is not supported, maximum supported version is {}", _0, _1)]
+    UnsupportedStorageVersion(u64, u64),
+    #[error("Invalid transaction extra data size: {}", _0)]
+    InvalidTxExtraDataSize(usize),
+    #[error("Transaction extra data of {} bytes is above the mempool limit of {} bytes", _0, _1)]
+    TxExtraDataAboveMempoolLimit(usize, usize),
+    #[error("Data at topoheight {} is not available, chain is pruned until topoheight {}", _0, _1)]
+    PrunedData(u64, u64),
+    #[error("Reorg of {} blocks rejected, common ancestor is below our stable height {}", _0, _1)]
+    DeepReorgRejected(u64, u64),
+    #[error("Chain is not synced with the network")]
+    NotSynced,
+    #[error("Data at height {} is not available, chain is pruned until height {}", _0, _1)]
+    PrunedHeight(u64, u64),
+    #[error("Data at timestamp {} is not available, chain is pruned until topoheight {}", _0, _1)]
+    PrunedTimestamp(u64, u64),
+    #[error("Block {} contains a tx from {} with nonce {}, expected {}", _0, _1, _2, _3)]
+    InvalidBlockTxNonce(Hash, Address, u64, u64),
+    #[error("Invalid TXs merkle root in block {}", _0)]
+    InvalidTxsMerkleRoot(Hash),
+    #[error("Block {} has an invalid miner key: {}", _0, _1)]
+    InvalidBlockMinerKey(Hash, PublicKeyError),
+    #[error("Transaction {} is not in block {}", _0, _1)]
+    TxNotInBlock(Hash, Hash),
+    #[error("Fees are too low for our mempool policy, expected at least {}, got {}", format_xelis(*_0), format_xelis(*_1))]
+    TxFeeTooLow(u64, u64, u64),
+    #[error("Chain export version {} is not supported, expected version {}", _0, _1)]
+    UnsupportedChainExportVersion(u8, u8),
+    #[error("Chain export is for network {}, expected network {}", _0, _1)]
+    ChainExportNetworkMismatch(Network, Network),
+    #[error("Chain export starts from genesis block {} which is not ours", _0)]
+    ChainExportGenesisMismatch(Hash),
+    #[error("Chain export chunk {} is corrupted", _0)]
+    InvalidChainExportChunk(u64),
+    #[error("Imported block {} is not at topoheight {} as in the chain export", _0, _1)]
+    ChainImportOrderMismatch(Hash, u64),
+    #[error("Backup interval must be at least 1 hour")]
+    ConfigBackupInterval,
+    #[error("Invalid backup manifest: {}", _0)]
+    InvalidBackupManifest(String),
+    #[error("Restored chain doesn't have block {} at topoheight {} as in the backup manifest", _0, _1)]
+    BackupRestoreMismatch(Hash, u64),
+}
+
+// Stable JSON-RPC error code of each blockchain error
+// Codes are part of the API: a code must never change or be reused, new errors take the next free code of their range
+// Several errors can share a code if they have the same meaning for the client
+pub const RPC_ERROR_CODES: &[(i16, &[&str])] = &[
+    // Not found or not available
+    (1000, &["BlockNotFound", "BlockHeightNotFound"]),
+    (1001, &["TxNotFound"]),
+    (1002, &["AccountNotFound"]),
+    (1003, &["AssetNotFound"]),
+    (1004, &["NotFoundOnDisk"]),
+    (1005, &["PrunedData", "PrunedHeight", "PrunedTimestamp"]),
+    (INVALID_NONCE_ERROR_CODE, &["InvalidNonce", "InvalidTxNonceMempoolCache"]),
+    (FEE_TOO_LOW_ERROR_CODE, &["TxFeeTooLow"]),
+    (1008, &["TxNotFoundInSortedList"]),
+    (1009, &["TxNotInBlock"]),
+    (1010, &["BlockNotOrdered"]),
+    (1011, &["NoBalance"]),
+    (1012, &["NoBalanceChanges"]),
+    (1013, &["NoNonce"]),
+    (1014, &["NoNonceChanges"]),
+    (1015, &["AddressNotRegistered"]),
+    (1016, &["NoCumulativeDifficulty"]),
+    // Transaction rejected
+    (1100, &["TxTooBig"]),
+    (1101, &["TxNonceAlreadyUsed"]),
+    (1102, &["TxAlreadyInMempool"]),
+    (1103, &["TxEmpty"]),
+    (1104, &["InvalidReferenceHash"]),
+    (1105, &["InvalidReferenceTopoheight"]),
+    (1106, &["TooManyOutputInTx"]),
+    (1107, &["TxAlreadyInBlock"]),
+    (1108, &["DuplicateRegistration"]),
+    (1109, &["InvalidTxFee"]),
+    (1110, &["FeesToLowToOverride"]),
+    (1111, &["AddressAlreadyRegistered"]),
+    (1112, &["NotEnoughFunds"]),
+    (1113, &["CoinbaseTxNotAllowed"]),
+    (1114, &["InvalidTxRegistrationPoW"]),
+    (1115, &["InvalidTxRegistrationSignature"]),
+    (1116, &["InvalidTransactionNonce"]),
+    (1117, &["InvalidTransactionToSender"]),
+    (1118, &["InvalidTransactionExtraDataTooBig"]),
+    (1119, &["InvalidTransactionSignature"]),
+    (1120, &["UnexpectedTransactionSignature"]),
+    (1121, &["NoTxSignature"]),
+    (1122, &["UnexpectedTransactionVariant"]),
+    (1123, &["InvalidTxVersion"]),
+    (1124, &["InvalidTxNonce"]),
+    (1125, &["DeadTx"]),
+    (1126, &["NoValueForBurn"]),
+    (1127, &["TxAlreadyInBlockchain"]),
+    (1128, &["InvalidCiphertext"]),
+    (1129, &["NoSenderOutput"]),
+    (1130, &["NoTxSender"]),
+    (1131, &["SenderIsReceiver"]),
+    (1132, &["TransactionProof"]),
+    (1133, &["InvalidTxExtraDataSize"]),
+    (1134, &["TxExtraDataAboveMempoolLimit"]),
+    (1135, &["TransactionStillReferenced"]),
+    (1136, &["SmartContractTodo"]),
+    (1137, &["TooManyTxReplacements"]),
+    (1138, &["TxTypeNotActivated"]),
+    (1139, &["TxExtraDataNotActivated"]),
+    (1140, &["InvalidTxPublicKey"]),
+    // Block rejected
+    (1200, &["InvalidBalancesMerkleHash"]),
+    (1201, &["InvalidTipsMerkleHash"]),
+    (1202, &["TimestampIsLessThanParent"]),
+    (1203, &["TimestampIsInFuture"]),
+    (1204, &["TimestampOutOfRange"]),
+    (1205, &["InvalidBlockHeight"]),
+    (1206, &["BlockHeightZeroNotAllowed"]),
+    (1207, &["InvalidBlockHeightStableHeight"]),
+    (1208, &["InvalidDifficulty"]),
+    (1209, &["InvalidHash"]),
+    (1210, &["InvalidPreviousBlockHash"]),
+    (1211, &["InvalidBlockSize"]),
+    (1212, &["InvalidBlockTxs"]),
+    (1213, &["InvalidTxInBlock"]),
+    (1214, &["InvalidBlockReward"]),
+    (1215, &["InvalidFeeReward"]),
+    (1216, &["InvalidCirculatingSupply"]),
+    (1217, &["InvalidMinerTx"]),
+    (1218, &["GenesisBlockMiner"]),
+    (1219, &["InvalidGenesisBlock"]),
+    (1220, &["ExpectedTips"]),
+    (1221, &["InvalidTipsCount"]),
+    (1222, &["InvalidTipsNotFound"]),
+    (1223, &["InvalidTipsDifficulty"]),
+    (1224, &["InvalidBlockVersion"]),
+    (1225, &["AlreadyInChain"]),
+    (1226, &["InvalidReachability"]),
+    (1227, &["BlockDeviation"]),
+    (1228, &["InvalidGenesisHash"]),
+    (1229, &["DuplicateTxInBlock"]),
+    (1230, &["InvalidBlockTxNonce"]),
+    (1231, &["InvalidTxsMerkleRoot"]),
+    (1232, &["BlockFeesOverflow"]),
+    (1233, &["BlockRewardOverflow"]),
+    (1234, &["POWHashError"]),
+    (1235, &["LowerCumulativeDifficulty"]),
+    (1236, &["InvalidBlockMinerKey"]),
+    // Chain state
+    (1300, &["IsSyncing"]),
+    (1301, &["NotSynced"]),
+    (1302, &["ChainSyncInProgress"]),
+    (1303, &["NotEnoughBlocks"]),
+    (1304, &["RewindTooDeep"]),
+    (1305, &["DeepReorgRejected"]),
+    (1306, &["InvalidNetwork"]),
+    (1307, &["PruneHeightTooHigh"]),
+    (1308, &["PruneZero"]),
+    (1309, &["PruneLowerThanLastPruned"]),
+    (1310, &["AutoPruneMode"]),
+    (1311, &["SupplyOverflow"]),
+    (1312, &["UnsupportedChainExportVersion"]),
+    (1313, &["ChainExportNetworkMismatch"]),
+    (1314, &["ChainExportGenesisMismatch"]),
+    (1315, &["InvalidChainExportChunk"]),
+    (1316, &["ChainImportOrderMismatch"]),
+    (1317, &["InvalidBackupManifest"]),
+    (1318, &["BackupRestoreMismatch"]),
+    // Configuration
+    (1400, &["ConfigMaxChainResponseSize"]),
+    (1401, &["ConfigSyncMode"]),
+    (1402, &["ConfigRpcTls"]),
+    (1403, &["ConfigBackupInterval"]),
+    // Internal errors
+    (1500, &["Unknown"]),
+    (1501, &["Any"]),
+    (1502, &["ErrorStd"]),
+    (1503, &["ErrorOnBech32"]),
+    (1504, &["ErrorOnP2p"]),
+    (1505, &["ErrorOnReader"]),
+    (1506, &["ErrorOnPrompt"]),
+    (1507, &["ErrorOnSignature"]),
+    (1508, &["PoisonError"]),
+    (1509, &["DatabaseError"]),
+    (1510, &["UnsupportedOperation"]),
+    (1511, &["DifficultyError"]),
+    (1512, &["Overflow"]),
+    (1513, &["TryFromSliceError"]),
+    (1514, &["DecompressionError"]),
+    (1515, &["UnsupportedStorageVersion"]),
+];
+
+// Code of the Unknown error, used if an error is missing in the table
+const UNKNOWN_RPC_ERROR_CODE: i16 = 1500;
+
+// Generates the name of each variant and the list of all names
+// The match is exhaustive, so a new variant must be added here to compile
+macro_rules! variants_names {
+    ($($variant:ident $($fields:tt)?),* $(,)?) => {
+        // Names of all the variants
+        pub const NAMES: &'static [&'static str] = &[$(stringify!($variant)),*];
+
+        // Name of the variant, used to find its RPC error code
+        pub fn get_name(&self) -> &'static str {
+            match self {
+                $(Self::$variant $($fields)? => stringify!($variant)),*
+            }
+        }
+    };
 }
 
 impl BlockchainError {
     pub unsafe fn id(&self) -> usize {
         *(self as *const Self as *const _)
     }
+
+    variants_names! {
+        BlockNotOrdered,
+        InvalidBalancesMerkleHash(..),
+        InvalidTipsMerkleHash(..),
+        TxTooBig(..),
+        TimestampIsLessThanParent(..),
+        TimestampIsInFuture(..),
+        TimestampOutOfRange(..),
+        InvalidBlockHeight(..),
+        BlockHeightZeroNotAllowed,
+        InvalidBlockHeightStableHeight,
+        InvalidDifficulty,
+        TxNonceAlreadyUsed(..),
+        InvalidHash(..),
+        InvalidPreviousBlockHash(..),
+        InvalidBlockSize(..),
+        InvalidBlockTxs(..),
+        InvalidTxInBlock(..),
+        TxNotFound(..),
+        TransactionStillReferenced(..),
+        TxNotFoundInSortedList(..),
+        TxAlreadyInMempool(..),
+        TxEmpty(..),
+        InvalidReferenceHash,
+        InvalidReferenceTopoheight,
+        TooManyOutputInTx(..),
+        TxAlreadyInBlock(..),
+        DuplicateRegistration(..),
+        InvalidTxFee(..),
+        FeesToLowToOverride(..),
+        TooManyTxReplacements(..),
+        TxTypeNotActivated(..),
+        TxExtraDataNotActivated(..),
+        InvalidTxPublicKey(..),
+        AccountNotFound(..),
+        AddressNotRegistered(..),
+        AddressAlreadyRegistered(..),
+        NotEnoughFunds(..),
+        CoinbaseTxNotAllowed(..),
+        InvalidBlockReward(..),
+        InvalidFeeReward(..),
+        InvalidCirculatingSupply(..),
+        InvalidTxRegistrationPoW(..),
+        InvalidTxRegistrationSignature(..),
+        InvalidTransactionNonce(..),
+        InvalidTransactionToSender(..),
+        InvalidTransactionExtraDataTooBig(..),
+        InvalidNetwork,
+        BlockNotFound(..),
+        BlockHeightNotFound(..),
+        LowerCumulativeDifficulty,
+        NoCumulativeDifficulty,
+        ErrorStd(..),
+        ErrorOnBech32(..),
+        ErrorOnP2p(..),
+        ErrorOnReader(..),
+        ErrorOnPrompt(..),
+        ErrorOnSignature(..),
+        PoisonError(..),
+        IsSyncing,
+        InvalidTransactionSignature,
+        UnexpectedTransactionSignature,
+        InvalidMinerTx,
+        GenesisBlockMiner,
+        InvalidGenesisBlock,
+        NotEnoughBlocks,
+        Unknown,
+        NoTxSignature,
+        SmartContractTodo,
+        UnexpectedTransactionVariant,
+        DatabaseError(..),
+        UnsupportedOperation,
+        NotFoundOnDisk(..),
+        ConfigMaxChainResponseSize,
+        ConfigSyncMode,
+        ConfigRpcTls,
+        ExpectedTips,
+        InvalidTipsCount(..),
+        InvalidTipsNotFound(..),
+        InvalidTipsDifficulty(..),
+        InvalidBlockVersion,
+        InvalidTxVersion,
+        AlreadyInChain,
+        InvalidReachability,
+        BlockDeviation,
+        InvalidGenesisHash,
+        InvalidTxNonce(..),
+        InvalidTxNonceMempoolCache(..),
+        AssetNotFound(..),
+        DifficultyError(..),
+        NoBalance(..),
+        NoBalanceChanges(..),
+        NoNonce(..),
+        NoNonceChanges(..),
+        Overflow,
+        BlockFeesOverflow(..),
+        BlockRewardOverflow(..),
+        SupplyOverflow(..),
+        DeadTx(..),
+        NoValueForBurn,
+        TxAlreadyInBlockchain(..),
+        PruneHeightTooHigh,
+        PruneZero,
+        PruneLowerThanLastPruned,
+        AutoPruneMode,
+        TryFromSliceError(..),
+        InvalidCiphertext,
+        NoSenderOutput,
+        NoTxSender(..),
+        DecompressionError(..),
+        Any(..),
+        InvalidNonce(..),
+        SenderIsReceiver,
+        TransactionProof(..),
+        POWHashError(..),
+        RewindTooDeep(..),
+        ChainSyncInProgress,
+        DuplicateTxInBlock(..),
+        UnsupportedStorageVersion(..),
+        InvalidTxExtraDataSize(..),
+        TxExtraDataAboveMempoolLimit(..),
+        PrunedData(..),
+        DeepReorgRejected(..),
+        NotSynced,
+        PrunedHeight(..),
+        PrunedTimestamp(..),
+        InvalidBlockTxNonce(..),
+        InvalidTxsMerkleRoot(..),
+        InvalidBlockMinerKey(..),
+        TxNotInBlock(..),
+        TxFeeTooLow(..),
+        UnsupportedChainExportVersion(..),
+        ChainExportNetworkMismatch(..),
+        ChainExportGenesisMismatch(..),
+        InvalidChainExportChunk(..),
+        ChainImportOrderMismatch(..),
+        ConfigBackupInterval,
+        InvalidBackupManifest(..),
+        BackupRestoreMismatch(..)
+    }
+
+    // Code used in JSON-RPC error responses, see RPC_ERROR_CODES
+    pub fn get_rpc_code(&self) -> i16 {
+        let name = self.get_name();
+        RPC_ERROR_CODES.iter()
+            .find(|(_, names)| names.contains(&name))
+            .map(|(code, _)| *code)
+            .unwrap_or(UNKNOWN_RPC_ERROR_CODE)
+    }
+
+    // Structured data sent with the RPC error so the client can react to it
+    pub fn get_rpc_data(&self) -> Option<Value> {
+        let data = match self {
+            Self::InvalidNonce(expected, got) => json!(InvalidNonceErrorData {
+                nonce: *got,
+                expected_nonce: *expected
+            }),
+            // The transaction must follow the last one in mempool
+            Self::InvalidTxNonceMempoolCache(nonce, _, max) => json!(InvalidNonceErrorData {
+                nonce: *nonce,
+                expected_nonce: max + 1
+            }),
+            Self::TxFeeTooLow(required_fee, fee, min_fee_per_kb) => json!(FeeTooLowErrorData {
+                fee: *fee,
+                required_fee: *required_fee,
+                min_fee_per_kb: *min_fee_per_kb
+            }),
+            Self::ErrorOnBech32(e) => json!(InvalidAddressErrorData {
+                position: e.position()?
+            }),
+            Self::BlockNotFound(hash) | Self::TxNotFound(hash) | Self::AssetNotFound(hash) => json!(NotFoundErrorData {
+                hash: Cow::Borrowed(hash)
+            }),
+            Self::PrunedData(requested, pruned_topoheight)
+            | Self::PrunedHeight(requested, pruned_topoheight)
+            | Self::PrunedTimestamp(requested, pruned_topoheight) => json!(PrunedErrorData {
+                requested: *requested,
+                pruned_topoheight: *pruned_topoheight
+            }),
+            _ => return None
+        };
+
+        Some(data)
+    }
 }
 
 impl From<BlockchainError> for InternalRpcError {
     fn from(value: BlockchainError) -> Self {
-        let id = unsafe { value.id() } as i16;
-        InternalRpcError::CustomAny(200 + id, value.into())
+        let code = value.get_rpc_code();
+        match value.get_rpc_data() {
+            Some(data) => InternalRpcError::CustomAnyWithData(code, value.into(), data),
+            None => InternalRpcError::CustomAny(code, value.into())
+        }
     }
 }
 
+// Find the code of a blockchain error wrapped with a context in a RPC method
+pub fn resolve_rpc_error_code(err: &anyhow::Error) -> Option<i16> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<BlockchainError>())
+        .map(BlockchainError::get_rpc_code)
+}
+
 impl<T> From<PoisonError<T>> for BlockchainError {
     fn from(err: PoisonError<T>) -> Self {
         Self::PoisonError(format!("{}", err))
@@ -333,11 +773,133 @@ impl<T> From<PoisonError<T>> for BlockchainError {
 impl From<VerificationError<BlockchainError>> for BlockchainError {
     fn from(value: VerificationError<BlockchainError>) -> Self {
         match value {
-            VerificationError::InvalidNonce(expected, got) => BlockchainError::InvalidNonce(expected, got),
-            VerificationError::SenderIsReceiver => BlockchainError::NoSenderOutput,
+            VerificationError::InvalidNonce(_, expected, got) => BlockchainError::InvalidNonce(expected, got),
+            VerificationError::SenderIsReceiver => BlockchainError::SenderIsReceiver,
             VerificationError::InvalidSignature => BlockchainError::InvalidTransactionSignature,
+            VerificationError::NoValueForBurn => BlockchainError::NoValueForBurn,
+            VerificationError::Overflow => BlockchainError::Overflow,
+            VerificationError::InvalidExtraDataSize(size) => BlockchainError::InvalidTxExtraDataSize(size),
             VerificationError::State(s) => s,
             VerificationError::Proof(proof) => BlockchainError::TransactionProof(proof)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use anyhow::Context;
+    use xelis_common::rpc_server::{Id, RpcResponseError};
+    use super::*;
+
+    #[test]
+    fn test_rpc_error_codes() {
+        let err = RpcResponseError::new(Some(Id::Number(1)), BlockchainError::BlockNotFound(Hash::zero()));
+        assert_eq!(err.to_json(), json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": {
+                "code": 1000,
+                "message": format!("Error while retrieving block by hash: {} not found", Hash::zero()),
+                "data": {
+                    "hash": Hash::zero()
+                }
+            }
+        }));
+
+        let err = RpcResponseError::new(None, BlockchainError::PrunedData(10, 20));
+        assert_eq!(err.to_json(), json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": {
+                "code": 1005,
+                "message": "Data at topoheight 10 is not available, chain is pruned until topoheight 20",
+                "data": {
+                    "requested": 10,
+                    "pruned_topoheight": 20
+                }
+            }
+        }));
+    }
+
+    #[test]
+    fn test_rpc_error_codes_table() {
+        let mut codes = HashSet::new();
+        let mut names = HashSet::new();
+        for (code, variants) in RPC_ERROR_CODES {
+            assert!(codes.insert(*code), "code {} is used twice", code);
+            assert!((1000..1600).contains(code), "code {} is out of the application range", code);
+            for name in *variants {
+                assert!(names.insert(*name), "{} has several codes", name);
+            }
+        }
+
+        // Every error has a code
+        let variants = BlockchainError::NAMES.iter().copied().collect::<HashSet<_>>();
+        assert_eq!(variants.len(), BlockchainError::NAMES.len());
+        assert_eq!(variants, names);
+
+        assert_eq!(BlockchainError::BlockHeightNotFound(10).get_rpc_code(), 1000);
+        assert_eq!(BlockchainError::Unknown.get_name(), "Unknown");
+        assert_eq!(BlockchainError::Unknown.get_rpc_code(), UNKNOWN_RPC_ERROR_CODE);
+        assert_eq!(BlockchainError::DeadTx(Hash::zero()).get_rpc_code(), 1125);
+    }
+
+    // Codes are part of the API, any change here breaks the clients
+    // New errors must be added to the snapshot
+    #[test]
+    fn test_rpc_error_codes_snapshot() {
+        let table = RPC_ERROR_CODES.iter()
+            .map(|(code, names)| format!("{} {}\n", code, names.join(" ")))
+            .collect::<String>();
+        assert_eq!(table, include_str!("snapshots/rpc_error_codes.txt"));
+    }
+
+    #[test]
+    fn test_invalid_nonce_error_data() {
+        let err = RpcResponseError::new(Some(Id::Number(1)), BlockchainError::InvalidNonce(5, 3));
+        assert_eq!(err.to_json(), json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": {
+                "code": INVALID_NONCE_ERROR_CODE,
+                "message": "Invalid nonce: expected 5, got 3",
+                "data": {
+                    "nonce": 3,
+                    "expected_nonce": 5
+                }
+            }
+        }));
+
+        let err = BlockchainError::InvalidTxNonceMempoolCache(9, 2, 4);
+        assert_eq!(err.get_rpc_code(), INVALID_NONCE_ERROR_CODE);
+        assert_eq!(err.get_rpc_data(), Some(json!({ "nonce": 9, "expected_nonce": 5 })));
+
+        assert!(BlockchainError::NotSynced.get_rpc_data().is_none());
+    }
+
+    #[test]
+    fn test_fee_too_low_error_data() {
+        let err = BlockchainError::TxFeeTooLow(30000, 25000, 20000);
+        assert_eq!(err.get_rpc_code(), FEE_TOO_LOW_ERROR_CODE);
+        assert_eq!(err.get_rpc_data(), Some(json!({ "fee": 25000, "required_fee": 30000, "min_fee_per_kb": 20000 })));
+    }
+
+    #[test]
+    fn test_invalid_address_error_data() {
+        let err = BlockchainError::ErrorOnBech32(Bech32Error::InvalidCharacter('b', 12));
+        assert_eq!(err.to_string(), "Invalid character 'b' at position 12");
+        assert_eq!(err.get_rpc_data(), Some(json!({ "position": 12 })));
+
+        assert!(BlockchainError::ErrorOnBech32(Bech32Error::InvalidChecksum).get_rpc_data().is_none());
+    }
+
+    #[test]
+    fn test_resolve_rpc_error_code_with_context() {
+        let result: Result<(), BlockchainError> = Err(BlockchainError::NotFoundOnDisk(DiskContext::GetTopBlock));
+        let err = result.context("Error while retrieving top block").unwrap_err();
+        assert_eq!(resolve_rpc_error_code(&err), Some(1004));
+
+        assert_eq!(resolve_rpc_error_code(&anyhow::anyhow!("unrelated")), None);
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::Arc;
+use log::trace;
+use xelis_common::{
+    block::BlockHeader,
+    difficulty::{CumulativeDifficulty, Difficulty},
+    serializer::{Reader, ReaderError, Serializer, Writer}
+};
+use super::{error::BlockchainError, storage::Storage};
+
+// A block header with its DAG context as served to light clients
+// The hash is not included to keep the encoding compact, it must be recomputed from the header
+#[derive(Clone, Debug)]
+pub struct HeaderEntry {
+    header: Arc<BlockHeader>,
+    topoheight: u64,
+    difficulty: Difficulty,
+    cumulative_difficulty: CumulativeDifficulty
+}
+
+impl HeaderEntry {
+    pub fn new(header: Arc<BlockHeader>, topoheight: u64, difficulty: Difficulty, cumulative_difficulty: CumulativeDifficulty) -> Self {
+        Self {
+            header,
+            topoheight,
+            difficulty,
+            cumulative_difficulty
+        }
+    }
+
+    pub fn get_header(&self) -> &Arc<BlockHeader> {
+        &self.header
+    }
+
+    pub fn get_topoheight(&self) -> u64 {
+        self.topoheight
+    }
+
+    pub fn get_difficulty(&self) -> &Difficulty {
+        &self.difficulty
+    }
+
+    pub fn get_cumulative_difficulty(&self) -> &CumulativeDifficulty {
+        &self.cumulative_difficulty
+    }
+}
+
+impl Serializer for HeaderEntry {
+    fn write(&self, writer: &mut Writer) {
+        self.header.write(writer);
+        writer.write_u64(&self.topoheight);
+        self.difficulty.write(writer);
+        self.cumulative_difficulty.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let header = Arc::new(BlockHeader::read(reader)?);
+        let topoheight = reader.read_u64()?;
+        let difficulty = Difficulty::read(reader)?;
+        let cumulative_difficulty = CumulativeDifficulty::read(reader)?;
+
+        Ok(Self::new(header, topoheight, difficulty, cumulative_difficulty))
+    }
+
+    fn size(&self) -> usize {
+        self.header.size() + self.topoheight.size() + self.difficulty.size() + self.cumulative_difficulty.size()
+    }
+}
+
+// Retrieve up to `count` headers in topological order starting at `start_topoheight`
+// Headers are added one by one until the total serialized size would exceed `max_size`
+// The first header is always added so the caller can make progress
+pub async fn get_headers_range<S: Storage>(storage: &S, start_topoheight: u64, count: u64, max_size: usize) -> Result<Vec<HeaderEntry>, BlockchainError> {
+    if count == 0 {
+        return Ok(Vec::new())
+    }
+
+    let top_topoheight = storage.get_top_topoheight()?;
+    let end_topoheight = top_topoheight.min(start_topoheight.saturating_add(count).saturating_sub(1));
+    trace!("get headers range from {} to {}", start_topoheight, end_topoheight);
+
+    let mut headers = Vec::new();
+    let mut total_size = 0;
+    for topoheight in start_topoheight..=end_topoheight {
+        let hash = storage.get_hash_at_topo_height(topoheight).await?;
+        let header = storage.get_block_header_by_hash(&hash).await?;
+        let difficulty = storage.get_difficulty_for_block_hash(&hash).await?;
+        let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(&hash).await?;
+        let entry = HeaderEntry::new(header, topoheight, difficulty, cumulative_difficulty);
+
+        total_size += entry.size();
+        if total_size > max_size && !headers.is_empty() {
+            trace!("headers range budget of {} bytes reached at topoheight {}", max_size, topoheight);
+            break;
+        }
+
+        headers.push(entry);
+    }
+
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        crypto::{Hash, Hashable},
+        varuint::VarUint
+    };
+    use crate::core::storage::{
+        test_utils::{add_linear_chain, open_temp_storage},
+        SledStorage
+    };
+    use super::*;
+
+    // Build a linear test chain of `count` blocks
+    async fn create_test_chain(storage: &mut SledStorage, count: u64) -> Vec<Hash> {
+        add_linear_chain(storage, count, |height, block| block
+            .timestamp(height * 15_000)
+            .difficulty(VarUint::from_u64(1000 + height))
+            .covariance(VarUint::zero())
+        ).await
+    }
+
+    #[tokio::test]
+    async fn test_get_headers_range() {
+        let (_dir, mut storage) = open_temp_storage("headers-range");
+        let hashes = create_test_chain(&mut storage, 520).await;
+
+        let headers = get_headers_range(&storage, 4, 512, usize::MAX).await.unwrap();
+        assert_eq!(headers.len(), 512);
+        for (i, entry) in headers.iter().enumerate() {
+            // Hash must be recomputed from the returned bytes
+            let decoded = HeaderEntry::from_bytes(&entry.to_bytes()).unwrap();
+            let topoheight = 4 + i as u64;
+            assert_eq!(decoded.get_topoheight(), topoheight);
+            assert_eq!(decoded.get_header().hash(), hashes[topoheight as usize]);
+            assert_eq!(*decoded.get_difficulty(), VarUint::from_u64(1000 + topoheight));
+
+            // Same for the hex encoding served over RPC
+            let header = BlockHeader::from_hex(entry.get_header().to_hex()).unwrap();
+            assert_eq!(header.hash(), hashes[topoheight as usize]);
+        }
+
+        // Range is capped at the top topoheight
+        let headers = get_headers_range(&storage, 515, 512, usize::MAX).await.unwrap();
+        assert_eq!(headers.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_get_headers_range_budget() {
+        let (_dir, mut storage) = open_temp_storage("headers-range-budget");
+        create_test_chain(&mut storage, 16).await;
+
+        let all = get_headers_range(&storage, 0, 16, usize::MAX).await.unwrap();
+        let budget = all.iter().take(10).map(|entry| entry.size()).sum::<usize>();
+        let headers = get_headers_range(&storage, 0, 16, budget).await.unwrap();
+        assert_eq!(headers.len(), 10);
+
+        // At least one header is always returned
+        let headers = get_headers_range(&storage, 0, 16, 1).await.unwrap();
+        assert_eq!(headers.len(), 1);
+    }
+}
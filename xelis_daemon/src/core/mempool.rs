@@ -3,9 +3,17 @@ use super::{
     error::BlockchainError,
     storage::Storage
 };
+use crate::config::{
+    MEMPOOL_MAX_REPLACEMENTS,
+    MEMPOOL_REPLACEMENTS_WINDOW_SECS,
+    MEMPOOL_REPLACEMENT_MIN_FEE_INCREASE_PERCENT
+};
 use std::{
+    cmp::Ordering,
     collections::{HashMap, HashSet},
+    future::Future,
     sync::Arc,
+    time::Instant,
     mem,
 };
 use serde::{Serialize, Deserialize};
@@ -49,13 +57,96 @@ pub struct AccountCache {
     balances: HashMap<Hash, Ciphertext>
 }
 
+// A TX from a block using the same nonce than a different TX from the same sender in mempool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoubleSpend {
+    pub source: PublicKey,
+    pub nonce: u64,
+    pub mempool_tx: Arc<Hash>,
+    pub block_tx: Hash
+}
+
+// Summary of the persisted transactions reloaded in mempool
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MempoolReloadSummary {
+    // TXs re-admitted in mempool
+    pub admitted: usize,
+    // TXs not valid anymore against the current chain state
+    pub purged: usize,
+    // TXs left to validate lazily because the deadline was reached
+    pub deferred: usize
+}
+
+// Re-validate each persisted TX in order until the deadline is reached
+// `readmit` returns false if the TX got purged
+// The TXs not validated in time are returned to be validated later
+pub async fn reload_persisted_txs<T, F, Fut>(txs: Vec<T>, deadline: Option<Instant>, mut readmit: F) -> (MempoolReloadSummary, Vec<T>)
+where
+    F: FnMut(T) -> Fut,
+    Fut: Future<Output = bool>
+{
+    let mut summary = MempoolReloadSummary::default();
+    let mut deferred = Vec::new();
+    let mut txs = txs.into_iter();
+    for tx in txs.by_ref() {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            deferred.push(tx);
+            break;
+        }
+
+        if readmit(tx).await {
+            summary.admitted += 1;
+        } else {
+            summary.purged += 1;
+        }
+    }
+
+    deferred.extend(txs);
+    summary.deferred = deferred.len();
+
+    (summary, deferred)
+}
+
+// Compare the fee per byte of two TXs without any rounding
+pub(crate) fn compare_fee_rate(fee: u64, size: usize, other_fee: u64, other_size: usize) -> Ordering {
+    (fee as u128 * other_size as u128).cmp(&(other_fee as u128 * size as u128))
+}
+
+// Find the TX to evict first, the one with the lowest fee per byte
+// Candidates are the hash, fee and size of each TX
+fn find_lowest_fee_rate<'a, I: IntoIterator<Item = (&'a Arc<Hash>, u64, usize)>>(candidates: I) -> Option<(&'a Arc<Hash>, u64, usize)> {
+    candidates.into_iter()
+        .min_by(|(_, fee, size), (_, other_fee, other_size)| compare_fee_rate(*fee, *size, *other_fee, *other_size))
+}
+
+// Minimum fee for a TX to replace the one with the same sender and nonce
+pub fn get_replacement_min_fee(fee: u64) -> u64 {
+    let increase = (fee as u128 * MEMPOOL_REPLACEMENT_MIN_FEE_INCREASE_PERCENT as u128).div_ceil(100).max(1);
+    (fee as u128 + increase).min(u64::MAX as u128) as u64
+}
+
+// Result of a TX verified against the mempool state
+// It stays valid as long as the pending TXs of its sender are the same
+pub struct VerifiedTx {
+    sender_txs: Option<IndexSet<Arc<Hash>>>,
+    // Expected balances of the sender once the TX is added
+    balances: HashMap<Hash, Ciphertext>
+}
+
 pub struct Mempool {
     // Used for log purpose
     mainnet: bool,
     // store all txs waiting to be included in a block
     txs: HashMap<Arc<Hash>, SortedTx>,
     // store all sender's nonce for faster finding
-    caches: HashMap<PublicKey, AccountCache>
+    caches: HashMap<PublicKey, AccountCache>,
+    // total size in bytes of all the txs
+    total_size: usize,
+    // mempool TXs in conflict with a TX from a block, with the hash of the block TX
+    // they are not mined until the DAG order resolved the conflict
+    conflicts: HashMap<Arc<Hash>, Hash>,
+    // Replacements done for each sender and nonce, with the start of their window
+    replacements: HashMap<(PublicKey, u64), (TimestampSeconds, u32)>
 }
 
 impl Mempool {
@@ -64,19 +155,38 @@ impl Mempool {
         Mempool {
             mainnet: network.is_mainnet(),
             txs: HashMap::new(),
-            caches: HashMap::new()
+            caches: HashMap::new(),
+            total_size: 0,
+            conflicts: HashMap::new(),
+            replacements: HashMap::new()
         }
     }
 
-    // All checks are made in Blockchain before calling this function
-    pub async fn add_tx<S: Storage>(&mut self, storage: &S, topoheight: u64, hash: Hash, tx: Arc<Transaction>, size: usize) -> Result<(), BlockchainError> {
-        let mut state = MempoolState::new(&self, storage, topoheight);
+    // Verify a TX against the current mempool state
+    // Only a read access is required, so TXs from different senders can be verified in parallel
+    pub async fn verify_tx<S: Storage>(&self, storage: &S, topoheight: u64, tx: &Transaction) -> Result<VerifiedTx, BlockchainError> {
+        let mut state = MempoolState::new(self, storage, topoheight);
         tx.verify(&mut state).await?;
 
         let balances = state.get_sender_balances(tx.get_source())
             .ok_or_else(|| BlockchainError::AccountNotFound(tx.get_source().as_address(storage.is_mainnet())))?
             .iter().map(|(asset, ciphertext)| (Hash::clone(*asset), ciphertext.clone())).collect();
 
+        Ok(VerifiedTx {
+            sender_txs: self.caches.get(tx.get_source()).map(|cache| cache.txs.clone()),
+            balances
+        })
+    }
+
+    // All checks are made in Blockchain before calling this function
+    // The TX is verified again only if the pending TXs of its sender changed since `verified`
+    pub async fn add_tx<S: Storage>(&mut self, storage: &S, topoheight: u64, hash: Hash, tx: Arc<Transaction>, size: usize, verified: Option<VerifiedTx>) -> Result<(), BlockchainError> {
+        let sender_txs = self.caches.get(tx.get_source()).map(|cache| &cache.txs);
+        let balances = match verified {
+            Some(verified) if verified.sender_txs.as_ref() == sender_txs => verified.balances,
+            _ => self.verify_tx(storage, topoheight, &tx).await?.balances
+        };
+
         let hash = Arc::new(hash);
         let nonce = tx.get_nonce();
         // update the cache for this owner
@@ -86,8 +196,7 @@ impl Mempool {
             trace!("Cache found for owner {} with nonce range {}-{}, nonce = {}", tx.get_source().as_address(self.mainnet), cache.get_min(), cache.get_max(), nonce);
 
             // Support the case where the nonce is already used in cache
-            // If a user want to cancel its TX, he can just resend a TX with same nonce and higher fee
-            // NOTE: This is not possible anymore, disabled in blockchain function
+            // NOTE: Replacements go through replace_tx, which verifies again the following TXs of the sender
             if nonce >= cache.get_min() && nonce <= cache.get_max() {
                 trace!("nonce {} is in range {}-{}", nonce, cache.get_min(), cache.get_max());
                 // because it's based on order and we may have the same order
@@ -98,9 +207,12 @@ impl Mempool {
                 if let Some(tx_hash) = cache.txs.swap_remove_index(index) {
                     trace!("TX {} with same nonce found in cache, removing it from sorted txs", tx_hash);
                     // remove the tx hash from sorted txs
-                    if self.txs.remove(&tx_hash).is_none() {
+                    if let Some(sorted_tx) = self.txs.remove(&tx_hash) {
+                        self.total_size -= sorted_tx.get_size();
+                    } else {
                         warn!("TX {} not found in mempool while deleting collision with {}", tx_hash, hash);
                     }
+                    self.conflicts.remove(&tx_hash);
                 } else {
                     warn!("No TX found in cache for nonce {} while adding {}", nonce, hash);
                 }
@@ -132,15 +244,130 @@ impl Mempool {
         };
 
         // insert in map
+        self.total_size += size;
         self.txs.insert(hash, sorted_tx);
 
         Ok(())
     }
 
+    // Replace the TX of the same sender using the same nonce, the fee must be high enough
+    // The TXs of the sender are verified again in nonce order with the replacement,
+    // the following TXs that are not valid anymore are removed too
+    // Returns the replaced TX first, then the other TXs removed
+    pub async fn replace_tx<S: Storage>(&mut self, storage: &S, topoheight: u64, hash: Hash, tx: Arc<Transaction>, size: usize) -> Result<Vec<(Arc<Hash>, SortedTx)>, BlockchainError> {
+        let source = tx.get_source();
+        let nonce = tx.get_nonce();
+        let replaced = self.caches.get(source)
+            .and_then(|cache| cache.has_tx_with_same_nonce(nonce))
+            .cloned()
+            .ok_or_else(|| BlockchainError::InvalidTxNonceMempoolCache(nonce, 0, 0))?;
+
+        let replaced_fee = self.get_sorted_tx(&replaced)?.get_fee();
+        let min_fee = get_replacement_min_fee(replaced_fee);
+        if tx.get_fee() < min_fee {
+            debug!("TX {} fee is too low to replace TX {}: {} required, {} provided", hash, replaced, min_fee, tx.get_fee());
+            return Err(BlockchainError::FeesToLowToOverride(min_fee, tx.get_fee()))
+        }
+
+        let now = get_current_time_in_seconds();
+        self.replacements.retain(|_, (start, _)| start.saturating_add(MEMPOOL_REPLACEMENTS_WINDOW_SECS) > now);
+        let key = (source.clone(), nonce);
+        if self.replacements.get(&key).is_some_and(|(_, count)| *count >= MEMPOOL_MAX_REPLACEMENTS) {
+            debug!("TX {} rejected, nonce {} of {} has been replaced too many times", hash, nonce, source.as_address(self.mainnet));
+            return Err(BlockchainError::TooManyTxReplacements(nonce))
+        }
+
+        // Cache is removed so the state reads the balances from the storage
+        let mut cache = self.caches.remove(source).ok_or_else(|| BlockchainError::InvalidTxNonceMempoolCache(nonce, 0, 0))?;
+        let index = (nonce - cache.get_min()) as usize;
+        let res = {
+            let mut txs: Vec<&Arc<Transaction>> = cache.txs.iter()
+                .take(index)
+                .filter_map(|hash| self.txs.get(hash).map(SortedTx::get_tx))
+                .collect();
+            txs.push(&tx);
+
+            let mut state = MempoolState::new(&self, storage, topoheight);
+            match Transaction::verify_batch(txs.as_slice(), &mut state).await {
+                Ok(()) => {
+                    // Following TXs are kept as long as they are still valid
+                    let mut valid = 0;
+                    for hash in cache.txs.iter().skip(index + 1) {
+                        let Some(sorted_tx) = self.txs.get(hash) else {
+                            break
+                        };
+
+                        if let Err(e) = sorted_tx.get_tx().verify(&mut state).await {
+                            debug!("TX {} is not valid anymore after the replacement of TX {}: {}", hash, replaced, e);
+                            break
+                        }
+                        valid += 1;
+                    }
+
+                    state.get_sender_balances(source)
+                        .map(|balances| (valid, balances.into_iter().map(|(asset, ciphertext)| (asset.clone(), ciphertext)).collect()))
+                        .ok_or_else(|| BlockchainError::AccountNotFound(source.as_address(self.mainnet)))
+                },
+                Err(e) => Err(e.into())
+            }
+        };
+
+        let (valid, balances) = match res {
+            Ok(res) => res,
+            Err(e) => {
+                self.caches.insert(source.clone(), cache);
+                return Err(e)
+            }
+        };
+
+        // The replacement takes the place of the replaced TX to keep the nonces order
+        // and the following TXs not valid anymore are removed
+        let hash = Arc::new(hash);
+        let mut txs = IndexSet::new();
+        let mut removed = Vec::new();
+        for (i, tx_hash) in mem::take(&mut cache.txs).into_iter().enumerate() {
+            if i == index {
+                txs.insert(hash.clone());
+            } else if i <= index + valid {
+                txs.insert(tx_hash);
+                continue;
+            }
+
+            if let Some(sorted_tx) = self.txs.remove(&tx_hash) {
+                self.total_size -= sorted_tx.get_size();
+                self.conflicts.remove(&tx_hash);
+                removed.push((tx_hash, sorted_tx));
+            }
+        }
+        cache.txs = txs;
+        cache.max = nonce + valid as u64;
+        cache.set_balances(balances);
+        self.caches.insert(source.clone(), cache);
+
+        self.total_size += size;
+        self.txs.insert(hash, SortedTx {
+            size,
+            first_seen: now,
+            tx
+        });
+
+        let (_, count) = self.replacements.entry(key).or_insert((now, 0));
+        *count += 1;
+
+        Ok(removed)
+    }
+
     // Remove a TX using its hash from mempool
     // This will recalculate the cache bounds
     pub fn remove_tx(&mut self, hash: &Hash) -> Result<(), BlockchainError> {
+        self.take_tx(hash).map(|_| ())
+    }
+
+    // Same as remove_tx but returns the deleted TX
+    fn take_tx(&mut self, hash: &Hash) -> Result<SortedTx, BlockchainError> {
         let tx = self.txs.remove(hash).ok_or_else(|| BlockchainError::TxNotFound(hash.clone()))?;
+        self.total_size -= tx.get_size();
+        self.conflicts.remove(hash);
         // remove the tx hash from sorted txs
         let key = tx.get_tx().get_source();
         let mut delete = false;
@@ -195,7 +422,84 @@ impl Mempool {
             self.caches.remove(key);
         }
 
-        Ok(())
+        Ok(tx)
+    }
+
+    // Only the last TX of each account can be evicted, the nonces must stay chained
+    fn get_eviction_candidates(&self) -> impl Iterator<Item = (&Arc<Hash>, u64, usize)> {
+        self.caches.values()
+            .filter_map(|cache| cache.txs.last())
+            .filter_map(|hash| self.txs.get(hash).map(|tx| (hash, tx.get_fee(), tx.get_size())))
+    }
+
+    // Minimum fee required for a TX of this size to not be the next one evicted
+    pub fn get_eviction_fee_threshold(&self, size: usize) -> Option<u64> {
+        find_lowest_fee_rate(self.get_eviction_candidates())
+            .map(|(_, fee, tx_size)| (fee as u128 * size as u128 / tx_size.max(1) as u128) as u64 + 1)
+    }
+
+    // Evict the TXs with the lowest fee per byte until the mempool size is below the maximum
+    // The expected balances of the accounts having a TX evicted are computed again
+    pub async fn evict_lowest_fee_txs<S: Storage>(&mut self, storage: &S, topoheight: u64, max_size: usize) -> Vec<(Arc<Hash>, SortedTx)> {
+        let mut evicted = Vec::new();
+        let mut keys = HashSet::new();
+        while self.total_size > max_size {
+            let Some((hash, _, _)) = find_lowest_fee_rate(self.get_eviction_candidates()) else {
+                break
+            };
+
+            let hash = Arc::clone(hash);
+            match self.take_tx(&hash) {
+                Ok(sorted_tx) => {
+                    debug!("Evicting TX {} from mempool, size is above {} bytes", hash, max_size);
+                    keys.insert(sorted_tx.get_tx().get_source().clone());
+                    evicted.push((hash, sorted_tx));
+                },
+                Err(e) => {
+                    warn!("Error while evicting TX {}: {}", hash, e);
+                    break
+                }
+            }
+        }
+
+        for key in keys {
+            let Some(mut cache) = self.caches.remove(&key) else {
+                continue
+            };
+
+            // Cache is removed so the state reads the balances from the storage
+            let balances = {
+                let txs: Vec<&Arc<Transaction>> = cache.txs.iter()
+                    .filter_map(|hash| self.txs.get(hash).map(SortedTx::get_tx))
+                    .collect();
+                let mut state = MempoolState::new(&self, storage, topoheight);
+                match Transaction::verify_batch(txs.as_slice(), &mut state).await {
+                    Ok(()) => state.get_sender_balances(&key).map(|balances| balances.into_iter().map(|(asset, ciphertext)| (asset.clone(), ciphertext)).collect()),
+                    Err(e) => {
+                        warn!("Error while verifying TXs left for sender {} after eviction: {}", key.as_address(self.mainnet), e);
+                        None
+                    }
+                }
+            };
+
+            match balances {
+                Some(balances) => {
+                    cache.set_balances(balances);
+                    self.caches.insert(key, cache);
+                },
+                None => {
+                    for hash in cache.txs {
+                        if let Some(sorted_tx) = self.txs.remove(&hash) {
+                            self.total_size -= sorted_tx.get_size();
+                            self.conflicts.remove(&hash);
+                            evicted.push((hash, sorted_tx));
+                        }
+                    }
+                }
+            }
+        }
+
+        evicted
     }
 
     // Get the nonce cache for all keys
@@ -251,15 +555,94 @@ impl Mempool {
         false
     }
 
+    // Search the TXs from mempool using the same nonce than a different TX of a block
+    // New conflicts are marked, so these TXs are excluded from the block templates
+    pub fn detect_double_spends<'a, I: IntoIterator<Item = (&'a PublicKey, u64, &'a Hash)>>(&mut self, txs: I) -> Vec<DoubleSpend> {
+        let mut double_spends = Vec::new();
+        for (source, nonce, block_tx) in txs {
+            let Some(mempool_tx) = self.caches.get(source).and_then(|cache| cache.has_tx_with_same_nonce(nonce)) else {
+                continue
+            };
+
+            if mempool_tx.as_ref() == block_tx || self.conflicts.get(mempool_tx) == Some(block_tx) {
+                continue
+            }
+
+            warn!("Double spend detected for {} with nonce {}: TX {} in mempool and TX {} in a block", source.as_address(self.mainnet), nonce, mempool_tx, block_tx);
+            let mempool_tx = Arc::clone(mempool_tx);
+            self.conflicts.insert(mempool_tx.clone(), block_tx.clone());
+            double_spends.push(DoubleSpend {
+                source: source.clone(),
+                nonce,
+                mempool_tx,
+                block_tx: block_tx.clone()
+            });
+        }
+
+        double_spends
+    }
+
+    // Check if the TX is in conflict with a TX from a block
+    pub fn is_conflicted(&self, hash: &Hash) -> bool {
+        self.conflicts.contains_key(hash)
+    }
+
+    // Get all the TXs in conflict with the hash of the block TX
+    pub fn get_conflicts(&self) -> &HashMap<Arc<Hash>, Hash> {
+        &self.conflicts
+    }
+
+    // Conflicts are resolved once the DAG order decided which TX is executed
+    // If the block TX is executed, the mempool TX is deleted by the clean up as its nonce is used
+    // Otherwise the mempool TX can be mined again once all the blocks including the block TX
+    // are ordered or below the stable height
+    pub async fn resolve_conflicts<S: Storage>(&mut self, storage: &S, stable_height: u64) -> Result<Vec<Arc<Hash>>, BlockchainError> {
+        let mut resolved = Vec::new();
+        for (hash, block_tx) in self.conflicts.iter() {
+            if storage.is_tx_executed_in_a_block(block_tx)? {
+                continue
+            }
+
+            let mut pending = false;
+            if storage.has_tx_blocks(block_tx)? {
+                for block in storage.get_blocks_for_tx(block_tx)? {
+                    if !storage.is_block_topological_ordered(&block).await && storage.get_height_for_block_hash(&block).await? > stable_height {
+                        pending = true;
+                        break
+                    }
+                }
+            }
+
+            if !pending {
+                debug!("Conflict of TX {} with TX {} is resolved", hash, block_tx);
+                resolved.push(Arc::clone(hash));
+            }
+        }
+
+        for hash in resolved.iter() {
+            self.conflicts.remove(hash);
+        }
+
+        Ok(resolved)
+    }
+
     // Returns the count of txs in mempool
     pub fn size(&self) -> usize {
         self.txs.len()
     }
 
+    // Returns the total size in bytes of the txs in mempool
+    pub fn get_total_size(&self) -> usize {
+        self.total_size
+    }
+
     // Clear all txs and caches in mempool
     pub fn clear(&mut self) {
         self.txs.clear();
         self.caches.clear();
+        self.total_size = 0;
+        self.conflicts.clear();
+        self.replacements.clear();
     }
 
     // delete all old txs not compatible anymore with current state of chain
@@ -439,9 +822,14 @@ impl Mempool {
             }
         }
 
+        self.total_size -= deleted_transactions.iter().map(|(_, sorted_tx)| sorted_tx.get_size()).sum::<usize>();
+        for (hash, _) in deleted_transactions.iter() {
+            self.conflicts.remove(hash);
+        }
         deleted_transactions
     }
 
+    // Persisted TXs are kept on disk to be reloaded at next start
     pub async fn stop(&mut self) {
         info!("Stopping mempool...");
         self.clear();
@@ -531,4 +919,208 @@ impl AccountCache {
         let index = ((nonce - self.min) % (self.max + 1 - self.min)) as usize;
         self.txs.get_index(index)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use xelis_common::{
+        config::COIN_VALUE,
+        crypto::{Hashable, KeyPair},
+        serializer::Serializer,
+        transaction::builder::FeeBuilder
+    };
+    use crate::core::{
+        state::{build_transfer, build_transfer_with_fee, setup_storage, BALANCE},
+        storage::SledStorage
+    };
+    use super::*;
+
+    #[test]
+    fn test_eviction_ordering() {
+        let hashes: Vec<Arc<Hash>> = (1..=4u8).map(|i| Arc::new(Hash::new([i; 32]))).collect();
+        // Hash, fee and size of each account last TX
+        let mut candidates = vec![
+            (&hashes[0], 20_000, 1_000),
+            // Highest absolute fee but lowest fee per byte
+            (&hashes[1], 30_000, 4_000),
+            (&hashes[2], 10_000, 500),
+            (&hashes[3], 15_000, 1_000)
+        ];
+
+        let mut order = Vec::new();
+        while let Some((hash, _, _)) = find_lowest_fee_rate(candidates.iter().cloned()) {
+            let hash = Arc::clone(hash);
+            candidates.retain(|(candidate, _, _)| **candidate != hash);
+            order.push(hash);
+        }
+        assert_eq!(order, vec![hashes[1].clone(), hashes[3].clone(), hashes[0].clone(), hashes[2].clone()]);
+        assert!(find_lowest_fee_rate(Vec::new()).is_none());
+
+        // Same fee per byte
+        assert_eq!(compare_fee_rate(10_000, 500, 20_000, 1_000), Ordering::Equal);
+        // No overflow with large values
+        assert_eq!(compare_fee_rate(u64::MAX, usize::MAX, u64::MAX - 1, usize::MAX), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_detect_double_spends() {
+        let mut mempool = Mempool::new(Network::Dev);
+        let source = xelis_common::crypto::KeyPair::new().get_public_key().compress();
+        let mempool_tx = Arc::new(Hash::new([1u8; 32]));
+        let block_tx = Hash::new([2u8; 32]);
+        let mut txs = IndexSet::new();
+        txs.insert(Arc::clone(&mempool_tx));
+        mempool.caches.insert(source.clone(), AccountCache { min: 3, max: 3, txs, balances: HashMap::new() });
+
+        // Same TX or another nonce is not a conflict
+        assert!(mempool.detect_double_spends([(&source, 3, mempool_tx.as_ref()), (&source, 4, &block_tx)]).is_empty());
+        assert!(!mempool.is_conflicted(&mempool_tx));
+
+        let double_spends = mempool.detect_double_spends([(&source, 3, &block_tx)]);
+        assert_eq!(double_spends, vec![DoubleSpend { source: source.clone(), nonce: 3, mempool_tx: Arc::clone(&mempool_tx), block_tx: block_tx.clone() }]);
+        assert!(mempool.is_conflicted(&mempool_tx));
+
+        // Already known conflict from another block including the same TX
+        assert!(mempool.detect_double_spends([(&source, 3, &block_tx)]).is_empty());
+
+        mempool.clear();
+        assert!(!mempool.is_conflicted(&mempool_tx));
+    }
+
+    #[tokio::test]
+    async fn test_add_tx_verified_before() {
+        let sender = KeyPair::new();
+        let receiver = KeyPair::new();
+        let (_dir, storage) = setup_storage("mempool-verified", &sender).await;
+        let mut mempool = Mempool::new(Network::Dev);
+
+        // Both TXs use the same nonce and are verified against the same state
+        let tx = Arc::new(build_transfer(&sender, &receiver, BALANCE, COIN_VALUE));
+        let other = Arc::new(build_transfer_with_fee(&sender, &receiver, BALANCE, COIN_VALUE, FeeBuilder::Value(tx.get_fee() * 2)));
+        let verified = mempool.verify_tx(&storage, 0, &tx).await.unwrap();
+        let other_verified = mempool.verify_tx(&storage, 0, &other).await.unwrap();
+
+        // Sender didn't change since its verification
+        mempool.add_tx(&storage, 0, other.hash(), other.clone(), other.size(), Some(other_verified)).await.unwrap();
+        assert!(mempool.contains_tx(&other.hash()));
+
+        // Verified again as the sender has a new pending TX, its nonce is not valid anymore
+        let res = mempool.add_tx(&storage, 0, tx.hash(), tx.clone(), tx.size(), Some(verified)).await;
+        assert!(res.is_err());
+        assert!(!mempool.contains_tx(&tx.hash()));
+        assert_eq!(mempool.get_total_size(), other.size());
+    }
+
+    #[tokio::test]
+    async fn test_replace_by_fee() {
+        let sender = KeyPair::new();
+        let receiver = KeyPair::new();
+        let (_dir, storage) = setup_storage("mempool-rbf", &sender).await;
+        let mut mempool = Mempool::new(Network::Dev);
+
+        let tx = Arc::new(build_transfer(&sender, &receiver, BALANCE, COIN_VALUE));
+        let fee = tx.get_fee();
+        mempool.add_tx(&storage, 0, tx.hash(), tx.clone(), tx.size(), None).await.unwrap();
+        assert_eq!(get_replacement_min_fee(fee), fee + (fee * MEMPOOL_REPLACEMENT_MIN_FEE_INCREASE_PERCENT).div_ceil(100));
+
+        // Rejected at +1%
+        let low = Arc::new(build_transfer_with_fee(&sender, &receiver, BALANCE, COIN_VALUE, FeeBuilder::Value(fee * 101 / 100)));
+        let res = mempool.replace_tx(&storage, 0, low.hash(), low.clone(), low.size()).await;
+        assert!(matches!(res, Err(BlockchainError::FeesToLowToOverride(..))));
+        assert!(mempool.contains_tx(&tx.hash()));
+        assert!(!mempool.contains_tx(&low.hash()));
+
+        // Accepted at +10%
+        let replacement = Arc::new(build_transfer_with_fee(&sender, &receiver, BALANCE, COIN_VALUE, FeeBuilder::Value(fee * 110 / 100)));
+        let removed = mempool.replace_tx(&storage, 0, replacement.hash(), replacement.clone(), replacement.size()).await.unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(*removed[0].0, tx.hash());
+        assert!(!mempool.contains_tx(&tx.hash()));
+        assert!(mempool.contains_tx(&replacement.hash()));
+        assert_eq!(mempool.get_total_size(), replacement.size());
+
+        let cache = mempool.get_cache_for(&sender.get_public_key().compress()).unwrap();
+        assert_eq!((cache.get_min(), cache.get_max()), (0, 0));
+        assert_eq!(cache.has_tx_with_same_nonce(0).map(|hash| hash.as_ref().clone()), Some(replacement.hash()));
+    }
+
+    // Replace the TX of the sender with nonce 0 by a transfer paying this fee
+    async fn replace_with_fee(mempool: &mut Mempool, storage: &SledStorage, sender: &KeyPair, receiver: &KeyPair, fee: u64) -> Result<Hash, BlockchainError> {
+        let replacement = Arc::new(build_transfer_with_fee(sender, receiver, BALANCE, COIN_VALUE, FeeBuilder::Value(fee)));
+        let hash = replacement.hash();
+        mempool.replace_tx(storage, 0, hash.clone(), replacement.clone(), replacement.size()).await?;
+        Ok(hash)
+    }
+
+    #[tokio::test]
+    async fn test_replacements_window() {
+        let sender = KeyPair::new();
+        let receiver = KeyPair::new();
+        let (_dir, storage) = setup_storage("mempool-rbf-window", &sender).await;
+        let mut mempool = Mempool::new(Network::Dev);
+
+        let tx = Arc::new(build_transfer(&sender, &receiver, BALANCE, COIN_VALUE));
+        let mut fee = tx.get_fee();
+        mempool.add_tx(&storage, 0, tx.hash(), tx.clone(), tx.size(), None).await.unwrap();
+
+        // Each replacement pays the minimum increase over the previous one
+        for _ in 0..MEMPOOL_MAX_REPLACEMENTS {
+            fee = get_replacement_min_fee(fee);
+            replace_with_fee(&mut mempool, &storage, &sender, &receiver, fee).await.unwrap();
+        }
+
+        // One more in the window is rejected even with a higher fee
+        fee = get_replacement_min_fee(fee);
+        let res = replace_with_fee(&mut mempool, &storage, &sender, &receiver, fee).await;
+        assert!(matches!(res, Err(BlockchainError::TooManyTxReplacements(0))));
+
+        // Accepted again once the window of the nonce is over
+        let key = (sender.get_public_key().compress(), 0);
+        let (start, count) = mempool.replacements.get_mut(&key).unwrap();
+        assert_eq!(*count, MEMPOOL_MAX_REPLACEMENTS);
+        *start -= MEMPOOL_REPLACEMENTS_WINDOW_SECS;
+
+        let hash = replace_with_fee(&mut mempool, &storage, &sender, &receiver, fee).await.unwrap();
+        assert!(mempool.contains_tx(&hash));
+        assert_eq!(mempool.replacements.get(&key).map(|(_, count)| *count), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_reload_purges_invalid_txs() {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let mut admitted = Vec::new();
+        let (summary, deferred) = reload_persisted_txs(vec![1, 2, 3, 4, 5], Some(deadline), |tx| {
+            // Even TXs are not valid anymore
+            let valid = tx % 2 == 1;
+            if valid {
+                admitted.push(tx);
+            }
+            async move { valid }
+        }).await;
+
+        assert_eq!(summary, MempoolReloadSummary { admitted: 3, purged: 2, deferred: 0 });
+        assert_eq!(admitted, vec![1, 3, 5]);
+        assert!(deferred.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reload_defers_after_deadline() {
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let (summary, deferred) = reload_persisted_txs(vec![1, 2, 3, 4], Some(deadline), |tx| async move {
+            // Validating the second TX exceeds the deadline
+            if tx == 2 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            true
+        }).await;
+
+        assert_eq!(summary, MempoolReloadSummary { admitted: 2, purged: 0, deferred: 2 });
+        assert_eq!(deferred, vec![3, 4]);
+
+        // Lazily validated without deadline
+        let (summary, deferred) = reload_persisted_txs(deferred, None, |_| async { true }).await;
+        assert_eq!(summary, MempoolReloadSummary { admitted: 2, purged: 0, deferred: 0 });
+        assert!(deferred.is_empty());
+    }
+}
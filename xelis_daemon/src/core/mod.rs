@@ -8,4 +8,17 @@ pub mod simulator;
 pub mod nonce_checker;
 pub mod tx_selector;
 pub mod state;
-pub mod merkle;
\ No newline at end of file
+pub mod headers;
+pub mod difficulty_history;
+pub mod timestamp_search;
+pub mod reorg;
+pub mod sync;
+pub mod dag_height;
+pub mod top_state;
+pub mod chain_view;
+pub mod bootstrap;
+pub mod backup;
+pub mod chain_stats;
+pub mod block_time;
+pub mod block_timings;
+pub mod network_time;
@@ -0,0 +1,212 @@
+use indexmap::IndexMap;
+use log::{info, warn};
+use xelis_common::time::TimestampSeconds;
+use crate::config::{
+    NETWORK_TIME_MAX_OFFSET_MILLIS,
+    NETWORK_TIME_MAX_SAMPLES,
+    NETWORK_TIME_MIN_SAMPLES
+};
+
+// Median of the offsets, None if there is not enough samples to trust it
+pub fn median_offset(samples: impl Iterator<Item = i64>) -> Option<i64> {
+    let mut offsets: Vec<i64> = samples.collect();
+    if offsets.len() < NETWORK_TIME_MIN_SAMPLES {
+        return None
+    }
+
+    offsets.sort_unstable();
+    let middle = offsets.len() / 2;
+    let median = if offsets.len() % 2 == 0 {
+        // Average without overflow on extreme samples
+        offsets[middle - 1] / 2 + offsets[middle] / 2 + (offsets[middle - 1] % 2 + offsets[middle] % 2) / 2
+    } else {
+        offsets[middle]
+    };
+
+    Some(median)
+}
+
+// Offset in milliseconds between two times in seconds, saturated instead of wrapping on extreme times
+pub fn offset_in_millis(peer_time: TimestampSeconds, local_time: TimestampSeconds) -> i64 {
+    let offset = (peer_time as i128 - local_time as i128) * 1000;
+    offset.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+// Offset of our clock compared to the time of our peers
+// Each connected peer gives one sample, at the handshake then in its timed pings,
+// only the median is used so a minority of peers can't move it,
+// and it is capped so even a majority of peers can't warp our time
+pub struct NetworkTime {
+    // Offset in milliseconds of each peer clock from ours, by peer id
+    samples: IndexMap<u64, i64>,
+    // Capped median offset applied to our clock
+    offset: i64,
+    // Our clock deviates more than the cap from the network
+    skewed: bool
+}
+
+impl NetworkTime {
+    pub fn new() -> Self {
+        Self {
+            samples: IndexMap::new(),
+            offset: 0,
+            skewed: false
+        }
+    }
+
+    // Offset in milliseconds to add to our clock
+    pub fn get_offset(&self) -> i64 {
+        self.offset
+    }
+
+    pub fn is_skewed(&self) -> bool {
+        self.skewed
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    pub fn has_sample(&self, peer_id: u64) -> bool {
+        self.samples.contains_key(&peer_id)
+    }
+
+    // Adjust our time with the offset of the network
+    pub fn adjust(&self, local_time: u64) -> u64 {
+        local_time.saturating_add_signed(self.offset)
+    }
+
+    // Add the time of a peer, a new sample of the same peer replaces its previous one
+    // Both times are in seconds as it is the precision of the handshake and pings
+    pub fn add_sample(&mut self, peer_id: u64, peer_time: TimestampSeconds, local_time: TimestampSeconds) {
+        let sample = offset_in_millis(peer_time, local_time);
+        self.samples.shift_remove(&peer_id);
+        if self.samples.len() >= NETWORK_TIME_MAX_SAMPLES {
+            self.samples.shift_remove_index(0);
+        }
+        self.samples.insert(peer_id, sample);
+        self.update_offset();
+    }
+
+    // Forget the time of a disconnected peer
+    pub fn remove_sample(&mut self, peer_id: u64) {
+        if self.samples.shift_remove(&peer_id).is_some() {
+            self.update_offset();
+        }
+    }
+
+    fn update_offset(&mut self) {
+        // Not enough peers left to trust them, use our own clock
+        let median = median_offset(self.samples.values().copied()).unwrap_or(0);
+
+        self.offset = median.clamp(-NETWORK_TIME_MAX_OFFSET_MILLIS, NETWORK_TIME_MAX_OFFSET_MILLIS);
+        let skewed = median.unsigned_abs() > NETWORK_TIME_MAX_OFFSET_MILLIS as u64;
+        if skewed && !self.skewed {
+            warn!("==========================================================");
+            warn!("Your system clock deviates by {}ms from the network time!", median);
+            warn!("Only {}ms are corrected, blocks may be rejected until your clock is fixed", self.offset);
+            warn!("Please check the time and the time zone of your system");
+            warn!("==========================================================");
+        } else if !skewed && self.skewed {
+            info!("System clock is back in sync with the network time (offset: {}ms)", self.offset);
+        }
+        self.skewed = skewed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median_offset() {
+        assert_eq!(median_offset([1000, 2000, 3000].into_iter()), None);
+        assert_eq!(median_offset([5000, -1000, 0, 1000, 2000].into_iter()), Some(1000));
+        assert_eq!(median_offset([0, 1000, 2000, 3000, 4000, 5000].into_iter()), Some(2500));
+        assert_eq!(median_offset([i64::MAX, i64::MAX, i64::MAX, i64::MAX, i64::MAX, i64::MAX].into_iter()), Some(i64::MAX));
+    }
+
+    #[test]
+    fn test_minority_of_liars() {
+        let mut time = NetworkTime::new();
+        let now = 1_000_000;
+        for peer_id in 0..6 {
+            time.add_sample(peer_id, now + 1, now);
+        }
+
+        // Attackers far in the future and in the past can't move the median
+        for peer_id in 6..9 {
+            time.add_sample(peer_id, now + 3600, now);
+        }
+        time.add_sample(9, 0, now);
+        time.add_sample(10, u64::MAX, now);
+        assert_eq!(time.get_offset(), 1000);
+        assert!(!time.is_skewed());
+    }
+
+    #[test]
+    fn test_offset_capped() {
+        let mut time = NetworkTime::new();
+        let now = 1_000_000;
+        // Not enough samples, time is not adjusted
+        for peer_id in 0..NETWORK_TIME_MIN_SAMPLES as u64 - 1 {
+            time.add_sample(peer_id, now - 3600, now);
+        }
+        assert_eq!(time.get_offset(), 0);
+
+        // Even all peers agreeing can't move our time by more than the cap
+        time.add_sample(100, now - 3600, now);
+        assert_eq!(time.get_offset(), -NETWORK_TIME_MAX_OFFSET_MILLIS);
+        assert!(time.is_skewed());
+        assert_eq!(time.adjust(now * 1000), now * 1000 - NETWORK_TIME_MAX_OFFSET_MILLIS as u64);
+
+        // Same peers reconnecting replace their samples instead of adding new ones
+        for peer_id in 0..NETWORK_TIME_MIN_SAMPLES as u64 - 1 {
+            time.add_sample(peer_id, now, now);
+        }
+        time.add_sample(100, now, now);
+        assert_eq!(time.get_offset(), 0);
+        assert!(!time.is_skewed());
+    }
+
+    #[test]
+    fn test_offset_saturated() {
+        assert_eq!(offset_in_millis(u64::MAX, 0), i64::MAX);
+        assert_eq!(offset_in_millis(0, u64::MAX), i64::MIN);
+        assert_eq!(offset_in_millis(1 << 63, 0), i64::MAX);
+        assert_eq!(offset_in_millis(10, 12), -2000);
+
+        // A time far in the future is not seen in the past
+        let mut time = NetworkTime::new();
+        let now = 1_000_000;
+        for peer_id in 0..NETWORK_TIME_MIN_SAMPLES as u64 {
+            time.add_sample(peer_id, 1 << 63, now);
+        }
+        assert_eq!(time.get_offset(), NETWORK_TIME_MAX_OFFSET_MILLIS);
+    }
+
+    #[test]
+    fn test_remove_sample() {
+        let mut time = NetworkTime::new();
+        let now = 1_000_000;
+        for peer_id in 0..NETWORK_TIME_MIN_SAMPLES as u64 {
+            time.add_sample(peer_id, now + 2, now);
+        }
+        for peer_id in 100..104 {
+            time.add_sample(peer_id, now + 10, now);
+        }
+        assert_eq!(time.get_offset(), 2000);
+
+        // Disconnected peers don't count anymore
+        for peer_id in 0..NETWORK_TIME_MIN_SAMPLES as u64 - 1 {
+            time.remove_sample(peer_id);
+        }
+        assert_eq!(time.get_offset(), 10000);
+
+        // Unknown peer is ignored
+        time.remove_sample(42);
+        assert_eq!(time.get_offset(), 10000);
+
+        // Not enough peers left, our clock is used again
+        time.remove_sample(100);
+        assert_eq!(time.get_offset(), 0);
+        assert!(!time.is_skewed());
+    }
+}
@@ -102,4 +102,113 @@ impl NonceChecker {
         let entry = self.cache.get(key).ok_or_else(|| BlockchainError::AccountNotFound(key.as_address(mainnet)))?;
         Ok(entry.expected_nonce)
     }
-}
\ No newline at end of file
+}
+// Track the next nonce expected for each sender of a block
+// TXs of a sender must use strictly sequential nonces, starting from its stored nonce
+pub struct BlockNonces<'a> {
+    expected: HashMap<&'a PublicKey, u64>
+}
+
+impl<'a> BlockNonces<'a> {
+    pub fn new() -> Self {
+        Self {
+            expected: HashMap::new()
+        }
+    }
+
+    // Nonce expected for the next TX of the sender, None if its stored nonce is not loaded yet
+    pub fn get_expected_nonce(&self, key: &PublicKey) -> Option<u64> {
+        self.expected.get(key).copied()
+    }
+
+    // Set the stored nonce of the sender, used as the first nonce expected
+    pub fn set_stored_nonce(&mut self, key: &'a PublicKey, nonce: u64) {
+        self.expected.insert(key, nonce);
+    }
+
+    // Returns the nonce expected for the next TX of the sender
+    // Its stored nonce is loaded at the topoheight given on first call
+    pub async fn get_or_load_expected_nonce<S: Storage>(&mut self, storage: &S, key: &'a PublicKey, topoheight: u64) -> Result<u64, BlockchainError> {
+        if let Some(nonce) = self.get_expected_nonce(key) {
+            return Ok(nonce)
+        }
+
+        let (_, version) = storage.get_nonce_at_maximum_topoheight(key, topoheight).await?.ok_or_else(|| BlockchainError::AccountNotFound(key.as_address(storage.is_mainnet())))?;
+        let nonce = version.get_nonce();
+        self.set_stored_nonce(key, nonce);
+
+        Ok(nonce)
+    }
+
+    // Use the nonce for the sender
+    // Returns false if its stored nonce is not loaded or if the nonce is not the expected one
+    pub fn use_nonce(&mut self, key: &PublicKey, nonce: u64) -> bool {
+        match self.expected.get_mut(key) {
+            Some(expected) if *expected == nonce => {
+                *expected += 1;
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use super::*;
+
+    #[test]
+    fn test_block_nonces_are_sequential() {
+        let alice = KeyPair::new().get_public_key().compress();
+        let bob = KeyPair::new().get_public_key().compress();
+
+        let mut nonces = BlockNonces::new();
+        // Not loaded yet
+        assert!(!nonces.use_nonce(&alice, 0));
+
+        nonces.set_stored_nonce(&alice, 5);
+        nonces.set_stored_nonce(&bob, 0);
+        assert!(nonces.use_nonce(&alice, 5));
+        assert!(nonces.use_nonce(&bob, 0));
+        assert!(nonces.use_nonce(&alice, 6));
+
+        // Same nonce used twice
+        assert!(!nonces.use_nonce(&alice, 6));
+        // Gap in the nonces
+        assert!(!nonces.use_nonce(&alice, 8));
+        assert_eq!(nonces.get_expected_nonce(&alice), Some(7));
+        assert_eq!(nonces.get_expected_nonce(&bob), Some(1));
+    }
+
+    #[test]
+    fn test_template_selection_nonces() {
+        let alice = KeyPair::new().get_public_key().compress();
+        let bob = KeyPair::new().get_public_key().compress();
+
+        // Mempool TXs in the order given by the selector
+        // Alice has a TX with the same nonce as an already selected one, Bob has a gap
+        let candidates = [(&alice, 3), (&bob, 10), (&alice, 4), (&alice, 4), (&bob, 12), (&alice, 5), (&bob, 11)];
+
+        let mut nonces = BlockNonces::new();
+        nonces.set_stored_nonce(&alice, 3);
+        nonces.set_stored_nonce(&bob, 10);
+
+        // Same rule as the block template: a sender is skipped after its first invalid TX
+        let mut failed_sources = std::collections::HashSet::new();
+        let mut selected = Vec::new();
+        for (key, nonce) in candidates {
+            if failed_sources.contains(key) {
+                continue;
+            }
+
+            if nonces.use_nonce(key, nonce) {
+                selected.push((key, nonce));
+            } else {
+                failed_sources.insert(key);
+            }
+        }
+
+        assert_eq!(selected, vec![(&alice, 3), (&bob, 10), (&alice, 4)]);
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering}
+};
+use log::{error, warn};
+use xelis_common::prompt::ShareablePrompt;
+
+// A reorg is deep when the common ancestor with the other chain is below our stable height
+// Blocks below the stable height are considered final, going back on them means
+// that we are under attack or that the network got split
+pub fn is_deep_reorg(common_height: u64, stable_height: u64) -> bool {
+    common_height < stable_height
+}
+
+// Guard refusing every deep reorg by default
+// The operator can allow a single one with --allow-deep-reorg to recover from a network split
+pub struct DeepReorgGuard {
+    // Set by the operator, consumed by the first deep reorg confirmed
+    allowed: AtomicBool,
+    // Prompt used to confirm the deep reorg
+    prompt: Option<ShareablePrompt>,
+    // Test nodes have no prompt, the confirmation is given in advance
+    #[cfg(any(test, feature = "testing"))]
+    confirmed_without_prompt: AtomicBool
+}
+
+impl DeepReorgGuard {
+    pub fn new(allowed: bool, prompt: Option<ShareablePrompt>) -> Self {
+        Self {
+            allowed: AtomicBool::new(allowed),
+            prompt,
+            #[cfg(any(test, feature = "testing"))]
+            confirmed_without_prompt: AtomicBool::new(false)
+        }
+    }
+
+    // Check if the deep reorg is still allowed by the operator
+    pub fn is_allowed(&self) -> bool {
+        self.allowed.load(Ordering::SeqCst)
+    }
+
+    // Confirm the next deep reorg allowed without asking it in a prompt
+    #[cfg(any(test, feature = "testing"))]
+    pub fn confirm_without_prompt(&self) {
+        self.confirmed_without_prompt.store(true, Ordering::SeqCst);
+    }
+
+    // Ask the operator to confirm the deep reorg in the prompt
+    pub async fn approve(&self, depth: u64, stable_height: u64) -> bool {
+        #[cfg(any(test, feature = "testing"))]
+        if self.confirmed_without_prompt.load(Ordering::SeqCst) {
+            return self.approve_with(async { true }).await
+        }
+
+        let Some(prompt) = self.prompt.as_ref() else {
+            error!("Deep reorg of {} blocks can't be confirmed without an interactive prompt", depth);
+            return false
+        };
+
+        self.approve_with(async {
+            warn!("A peer asks us to reorg {} blocks, below our stable height {}", depth, stable_height);
+            match prompt.ask_confirmation().await {
+                Ok(confirmed) => confirmed,
+                Err(e) => {
+                    error!("Error while asking deep reorg confirmation: {}", e);
+                    false
+                }
+            }
+        }).await
+    }
+
+    // Approve the deep reorg if it's allowed and confirmed
+    // The allowance is consumed only when confirmed, so the operator can decline and wait for another peer
+    pub async fn approve_with<F: Future<Output = bool>>(&self, confirmation: F) -> bool {
+        if !self.is_allowed() {
+            return false
+        }
+
+        if !confirmation.await {
+            warn!("Deep reorg was declined");
+            return false
+        }
+
+        // Only one deep reorg can be done, even if several were confirmed at the same time
+        self.allowed.swap(false, Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_deep_reorg() {
+        assert!(is_deep_reorg(99, 100));
+        assert!(is_deep_reorg(0, 100));
+        assert!(!is_deep_reorg(100, 100));
+        assert!(!is_deep_reorg(150, 100));
+    }
+
+    #[tokio::test]
+    async fn test_deep_reorg_rejected_by_default() {
+        let guard = DeepReorgGuard::new(false, None);
+        assert!(!guard.approve_with(async { true }).await);
+        assert!(!guard.approve(1000, 100).await);
+    }
+
+    #[tokio::test]
+    async fn test_deep_reorg_allowed_once() {
+        let guard = DeepReorgGuard::new(true, None);
+        // Declined by the operator, still allowed for the next one
+        assert!(!guard.approve_with(async { false }).await);
+        assert!(guard.is_allowed());
+
+        assert!(guard.approve_with(async { true }).await);
+        // Allowance is consumed
+        assert!(!guard.is_allowed());
+        assert!(!guard.approve_with(async { true }).await);
+    }
+
+    #[tokio::test]
+    async fn test_deep_reorg_without_prompt() {
+        let guard = DeepReorgGuard::new(true, None);
+        assert!(!guard.approve(1000, 100).await);
+        assert!(guard.is_allowed());
+    }
+
+    #[tokio::test]
+    async fn test_deep_reorg_confirmed_without_prompt() {
+        let guard = DeepReorgGuard::new(false, None);
+        guard.confirm_without_prompt();
+        // The operator flag is still required
+        assert!(!guard.approve(1000, 100).await);
+
+        let guard = DeepReorgGuard::new(true, None);
+        guard.confirm_without_prompt();
+        assert!(guard.approve(1000, 100).await);
+        assert!(!guard.approve(1000, 100).await);
+    }
+}
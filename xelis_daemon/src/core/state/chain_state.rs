@@ -136,6 +136,8 @@ pub struct ChainState<'a, S: Storage> {
     // Sender accounts
     // This is used to verify ZK Proofs and store/update nonces
     accounts: HashMap<&'a PublicKey, Account<'a>>,
+    // Total XELIS credited to the miners of the block
+    rewarded: u64,
     // Current topoheight of the snapshot
     topoheight: u64
 }
@@ -186,7 +188,8 @@ impl<'a, S: Storage> ApplicableChainState<'a, S> {
     // This function is called after the verification of all needed transactions
     // This will consume ChainState and apply all changes to the storage
     // In case of incoming and outgoing transactions in same state, the final balance will be computed
-    pub async fn apply_changes(mut self) -> Result<(), BlockchainError> {
+    // If `balances_changes` is set, every balance saved is added to it
+    pub async fn apply_changes(mut self, mut balances_changes: Option<&mut Vec<(PublicKey, Hash, VersionedBalance)>>) -> Result<(), BlockchainError> {
         // Apply changes for sender accounts
         for (key, account) in &mut self.inner.accounts {
             trace!("Saving {} for {} at topoheight {}", account.nonce, key.as_address(self.inner.storage.is_mainnet()), self.inner.topoheight);
@@ -273,6 +276,9 @@ impl<'a, S: Storage> ApplicableChainState<'a, S> {
             for (asset, version) in balances {
                 trace!("Saving versioned balance {} for {} at topoheight {}", version, account.as_address(self.inner.storage.is_mainnet()), self.inner.topoheight);
                 self.inner.storage.set_last_balance_to(account, asset, self.inner.topoheight, &version).await?;
+                if let Some(changes) = balances_changes.as_mut() {
+                    changes.push((account.clone(), asset.clone(), version));
+                }
             }
 
             // If the account has no nonce set, set it to 0
@@ -297,6 +303,7 @@ impl<'a, S: Storage> ChainState<'a, S> {
             storage,
             receiver_balances: HashMap::new(),
             accounts: HashMap::new(),
+            rewarded: 0,
             topoheight
         }
     }
@@ -426,11 +433,17 @@ impl<'a, S: Storage> ChainState<'a, S> {
     // Reward a miner for the block mined
     pub async fn reward_miner(&mut self, miner: &'a PublicKey, reward: u64) -> Result<(), BlockchainError> {
         debug!("Rewarding miner {} with {} XEL at topoheight {}", miner.as_address(self.storage.is_mainnet()), format_xelis(reward), self.topoheight);
+        self.rewarded = self.rewarded.checked_add(reward).ok_or(BlockchainError::Overflow)?;
         let miner_balance = self.internal_get_receiver_balance(miner, &XELIS_ASSET).await?;
         *miner_balance += reward;
 
         Ok(())
     }
+
+    // Total amount given by reward_miner, dev fee included
+    pub fn get_rewarded(&self) -> u64 {
+        self.rewarded
+    }
 }
 
 #[async_trait]
@@ -1,9 +1,13 @@
 mod mempool_state;
 mod chain_state;
+mod overlay;
 
 use log::{trace, debug};
 pub use mempool_state::MempoolState;
 pub use chain_state::{ChainState, ApplicableChainState, StorageReference};
+pub use overlay::StateOverlay;
+#[cfg(test)]
+pub(crate) use overlay::tests::{build_asset_creation, build_transfer, build_transfer_to, build_transfer_with_extra_data, build_transfer_with_fee, setup_storage, BALANCE};
 use xelis_common::{account::VersionedBalance, crypto::{Hash, PublicKey}, transaction::Reference};
 
 use super::{error::BlockchainError, storage::Storage};
@@ -0,0 +1,404 @@
+use std::collections::{hash_map::Entry, HashMap};
+use async_trait::async_trait;
+use log::debug;
+use xelis_common::{
+    crypto::{
+        elgamal::Ciphertext,
+        Hash,
+        PublicKey
+    },
+    transaction::{
+        verify::BlockchainVerificationState,
+        Reference,
+        Transaction
+    },
+    utils::format_xelis
+};
+use crate::core::{
+    blockchain,
+    error::BlockchainError,
+    storage::Storage
+};
+
+// In-memory overlay over the storage
+// Balances and nonces are lazily read from the storage and copied on first write
+// Nothing is ever written back, so it can be used to simulate transactions safely
+pub struct StateOverlay<'a, S: Storage> {
+    // Storage from which the state is read
+    storage: &'a S,
+    // Balances of all accounts touched
+    balances: HashMap<&'a PublicKey, HashMap<&'a Hash, Ciphertext>>,
+    // Nonces of all sender accounts
+    nonces: HashMap<&'a PublicKey, u64>,
+    // Topoheight of the chain used as snapshot
+    topoheight: u64
+}
+
+impl<'a, S: Storage> StateOverlay<'a, S> {
+    pub fn new(storage: &'a S, topoheight: u64) -> Self {
+        Self {
+            storage,
+            balances: HashMap::new(),
+            nonces: HashMap::new(),
+            topoheight
+        }
+    }
+
+    // Get all the balances touched with their current value
+    pub fn get_balances(&self) -> impl Iterator<Item = (&'a PublicKey, &'a Hash, &Ciphertext)> {
+        self.balances.iter()
+            .flat_map(|(key, balances)| balances.iter().map(move |(asset, ct)| (*key, *asset, ct)))
+    }
+
+    // Get the nonce of an account if it was touched
+    pub fn get_nonce(&self, key: &PublicKey) -> Option<u64> {
+        self.nonces.get(key).copied()
+    }
+
+    // Retrieve the final balance of an account, used for receivers
+    async fn internal_get_receiver_balance<'b>(&'b mut self, key: &'a PublicKey, asset: &'a Hash) -> Result<&'b mut Ciphertext, BlockchainError> {
+        match self.balances.entry(key).or_insert_with(HashMap::new).entry(asset) {
+            Entry::Occupied(o) => Ok(o.into_mut()),
+            Entry::Vacant(e) => {
+                let version = self.storage.get_new_versioned_balance(key, asset, self.topoheight).await?;
+                Ok(e.insert(version.take_balance().take_ciphertext()?))
+            }
+        }
+    }
+
+    // Retrieve the balance used for the verification of a sender based on the TX reference
+    async fn internal_get_sender_balance<'b>(&'b mut self, key: &'a PublicKey, asset: &'a Hash, reference: &Reference) -> Result<&'b mut Ciphertext, BlockchainError> {
+        match self.balances.entry(key).or_insert_with(HashMap::new).entry(asset) {
+            Entry::Occupied(o) => Ok(o.into_mut()),
+            Entry::Vacant(e) => {
+                let (output, _, version) = super::search_versioned_balance_for_reference(self.storage, key, asset, self.topoheight, reference).await?;
+                Ok(e.insert(version.take_balance_with(output).take_ciphertext()?))
+            }
+        }
+    }
+
+    // Retrieve the nonce of an account
+    async fn internal_get_account_nonce(&mut self, key: &'a PublicKey) -> Result<u64, BlockchainError> {
+        match self.nonces.entry(key) {
+            Entry::Occupied(o) => Ok(*o.get()),
+            Entry::Vacant(e) => {
+                let nonce = self.storage.get_nonce_at_maximum_topoheight(key, self.topoheight).await?
+                    .map(|(_, v)| v.get_nonce()).unwrap_or(0);
+
+                Ok(*e.insert(nonce))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<'a, S: Storage> BlockchainVerificationState<'a, BlockchainError> for StateOverlay<'a, S> {
+    /// Verify the TX version and reference
+    async fn pre_verify_tx<'b>(
+        &'b mut self,
+        tx: &Transaction,
+    ) -> Result<(), BlockchainError> {
+        // Check the version
+        if tx.get_version() != 0 {
+            debug!("Invalid version: {}", tx.get_version());
+            return Err(BlockchainError::InvalidTxVersion);
+        }
+
+        let required_fees = blockchain::estimate_required_tx_fees(self.storage, self.topoheight, tx).await?;
+        if required_fees > tx.get_fee() {
+            debug!("Invalid fees: {} required, {} provided", format_xelis(required_fees), format_xelis(tx.get_fee()));
+            return Err(BlockchainError::InvalidTxFee(required_fees, tx.get_fee()));
+        }
+
+        // Verify that it is not a fake topoheight
+        let reference = tx.get_reference();
+        if self.topoheight < reference.topoheight {
+            debug!("Invalid reference: topoheight {} is higher than chain {}", reference.topoheight, self.topoheight);
+            return Err(BlockchainError::InvalidReferenceTopoheight);
+        }
+
+        Ok(())
+    }
+
+    /// Get the balance ciphertext for a receiver account
+    async fn get_receiver_balance<'b>(
+        &'b mut self,
+        account: &'a PublicKey,
+        asset: &'a Hash,
+    ) -> Result<&'b mut Ciphertext, BlockchainError> {
+        self.internal_get_receiver_balance(account, asset).await
+    }
+
+    /// Get the balance ciphertext used for verification of funds for the sender account
+    async fn get_sender_balance<'b>(
+        &'b mut self,
+        account: &'a PublicKey,
+        asset: &'a Hash,
+        reference: &Reference,
+    ) -> Result<&'b mut Ciphertext, BlockchainError> {
+        self.internal_get_sender_balance(account, asset, reference).await
+    }
+
+    /// Apply new output to a sender account
+    /// The sender balance is already updated in place
+    async fn add_sender_output(
+        &mut self,
+        _: &'a PublicKey,
+        _: &'a Hash,
+        _: Ciphertext,
+    ) -> Result<(), BlockchainError> {
+        Ok(())
+    }
+
+    /// Get the nonce of an account
+    async fn get_account_nonce(
+        &mut self,
+        account: &'a PublicKey
+    ) -> Result<u64, BlockchainError> {
+        self.internal_get_account_nonce(account).await
+    }
+
+    /// Apply a new nonce to an account
+    async fn update_account_nonce(
+        &mut self,
+        account: &'a PublicKey,
+        new_nonce: u64
+    ) -> Result<(), BlockchainError> {
+        self.nonces.insert(account, new_nonce);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use xelis_common::{
+        account::{CiphertextCache, VersionedBalance, VersionedNonce},
+        asset::AssetData,
+        config::{COIN_DECIMALS, COIN_VALUE, FEE_PER_KB, XELIS_ASSET},
+        crypto::{Address, KeyPair},
+        testing::TempDir,
+        transaction::{
+            builder::{
+                AccountState,
+                FeeBuilder,
+                FeeHelper,
+                TransactionBuilder,
+                TransactionTypeBuilder,
+                TransferBuilder
+            },
+            AssetCreationPayload
+        }
+    };
+    use crate::core::storage::{
+        test_utils::open_temp_storage,
+        AssetProvider,
+        BalanceProvider,
+        NonceProvider,
+        SledStorage
+    };
+    use super::*;
+
+    // Wallet side state used to build the transactions
+    struct BuilderState {
+        balance: u64,
+        ciphertext: CiphertextCache,
+        nonce: u64
+    }
+
+    impl FeeHelper for BuilderState {
+        type Error = ();
+
+        fn account_exists(&self, _: &PublicKey) -> Result<bool, Self::Error> {
+            Ok(false)
+        }
+    }
+
+    impl AccountState for BuilderState {
+        fn is_mainnet(&self) -> bool {
+            false
+        }
+
+        fn get_account_balance(&self, _: &Hash) -> Result<u64, Self::Error> {
+            Ok(self.balance)
+        }
+
+        fn get_reference(&self) -> Reference {
+            Reference {
+                topoheight: 0,
+                hash: Hash::zero()
+            }
+        }
+
+        fn get_account_ciphertext(&self, _: &Hash) -> Result<CiphertextCache, Self::Error> {
+            Ok(self.ciphertext.clone())
+        }
+
+        fn update_account_balance(&mut self, _: &Hash, new_balance: u64, ciphertext: Ciphertext) -> Result<(), Self::Error> {
+            self.balance = new_balance;
+            self.ciphertext = CiphertextCache::Decompressed(ciphertext);
+            Ok(())
+        }
+
+        fn get_nonce(&self) -> Result<u64, Self::Error> {
+            Ok(self.nonce)
+        }
+
+        fn update_nonce(&mut self, new_nonce: u64) -> Result<(), Self::Error> {
+            self.nonce = new_nonce;
+            Ok(())
+        }
+    }
+
+    pub(crate) const BALANCE: u64 = 10 * COIN_VALUE;
+
+    // Create a storage where the sender has a balance of 10 XEL
+    pub(crate) async fn setup_storage(name: &str, sender: &KeyPair) -> (TempDir, SledStorage) {
+        let (dir, mut storage) = open_temp_storage(name);
+        storage.add_asset(&XELIS_ASSET, AssetData::new(0, COIN_DECIMALS)).await.unwrap();
+
+        let key = sender.get_public_key().compress();
+        let balance = VersionedBalance::new(CiphertextCache::Decompressed(sender.get_public_key().encrypt(BALANCE)), None);
+        storage.set_last_balance_to(&key, &XELIS_ASSET, 0, &balance).await.unwrap();
+        storage.set_last_nonce_to(&key, 0, &VersionedNonce::new(0, None)).await.unwrap();
+
+        (dir, storage)
+    }
+
+    // Build a valid transfer signed by the sender
+    pub(crate) fn build_transfer(sender: &KeyPair, receiver: &KeyPair, claimed_balance: u64, amount: u64) -> Transaction {
+        build_transfer_with_fee(sender, receiver, claimed_balance, amount, FeeBuilder::Multiplier(1f64))
+    }
+
+    pub(crate) fn build_transfer_with_fee(sender: &KeyPair, receiver: &KeyPair, claimed_balance: u64, amount: u64, fee: FeeBuilder) -> Transaction {
+        build_transfer_to(sender, receiver.get_public_key().to_address(false), claimed_balance, amount, fee)
+    }
+
+    // Build a transfer to any address, even one using a key nobody owns
+    pub(crate) fn build_transfer_to(sender: &KeyPair, destination: Address, claimed_balance: u64, amount: u64, fee: FeeBuilder) -> Transaction {
+        let mut state = BuilderState {
+            balance: claimed_balance,
+            ciphertext: CiphertextCache::Decompressed(sender.get_public_key().encrypt(claimed_balance)),
+            nonce: 0
+        };
+
+        let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            destination,
+            amount,
+            asset: XELIS_ASSET,
+            extra_data: None
+        }]);
+        let builder = TransactionBuilder::new(0, sender.get_public_key().compress(), data, fee);
+        builder.build(&mut state, sender).unwrap()
+    }
+
+    // Build a valid transfer carrying the given plaintext extra data
+    pub(crate) fn build_transfer_with_extra_data(sender: &KeyPair, receiver: &KeyPair, claimed_balance: u64, amount: u64, extra_data: Vec<u8>) -> Transaction {
+        let mut state = BuilderState {
+            balance: claimed_balance,
+            ciphertext: CiphertextCache::Decompressed(sender.get_public_key().encrypt(claimed_balance)),
+            nonce: 0
+        };
+
+        let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            destination: receiver.get_public_key().to_address(false),
+            amount,
+            asset: XELIS_ASSET,
+            extra_data: None
+        }]);
+        let builder = TransactionBuilder::new(0, sender.get_public_key().compress(), data, FeeBuilder::Multiplier(1f64))
+            .with_extra_data(Some(extra_data));
+        builder.build(&mut state, sender).unwrap()
+    }
+
+    // Build a valid asset creation signed by the sender, its balance must cover the burned amount
+    pub(crate) fn build_asset_creation(sender: &KeyPair, claimed_balance: u64, decimals: u8) -> Transaction {
+        let mut state = BuilderState {
+            balance: claimed_balance,
+            ciphertext: CiphertextCache::Decompressed(sender.get_public_key().encrypt(claimed_balance)),
+            nonce: 0
+        };
+
+        let data = TransactionTypeBuilder::AssetCreation(AssetCreationPayload { decimals });
+        let builder = TransactionBuilder::new(0, sender.get_public_key().compress(), data, FeeBuilder::Multiplier(1f64));
+        builder.build(&mut state, sender).unwrap()
+    }
+
+    // Verify that the storage still contains only the initial state
+    async fn assert_storage_untouched(storage: &SledStorage, sender: &KeyPair, receiver: &KeyPair) {
+        let key = sender.get_public_key().compress();
+        let (topoheight, version) = storage.get_last_balance(&key, &XELIS_ASSET).await.unwrap();
+        assert_eq!(topoheight, 0);
+        let ct = version.take_balance().take_ciphertext().unwrap();
+        assert_eq!(
+            sender.get_private_key().decrypt_to_point(&ct),
+            sender.get_private_key().decrypt_to_point(&sender.get_public_key().encrypt(BALANCE))
+        );
+        assert_eq!(storage.get_last_nonce(&key).await.unwrap().1.get_nonce(), 0);
+        assert!(!storage.has_balance_for(&receiver.get_public_key().compress(), &XELIS_ASSET).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_simulate_valid_transaction() {
+        let sender = KeyPair::new();
+        let receiver = KeyPair::new();
+        let (_dir, storage) = setup_storage("valid", &sender).await;
+
+        let tx = build_transfer(&sender, &receiver, BALANCE, COIN_VALUE);
+        let mut state = StateOverlay::new(&storage, 0);
+        tx.verify(&mut state).await.unwrap();
+
+        let sender_key = sender.get_public_key().compress();
+        let receiver_key = receiver.get_public_key().compress();
+        assert_eq!(state.get_nonce(&sender_key), Some(1));
+
+        let balances: HashMap<_, _> = state.get_balances()
+            .map(|(key, _, ct)| (key.clone(), ct.clone()))
+            .collect();
+        assert_eq!(balances.len(), 2);
+        assert_eq!(
+            sender.get_private_key().decrypt_to_point(&balances[&sender_key]),
+            sender.get_private_key().decrypt_to_point(&sender.get_public_key().encrypt(BALANCE - COIN_VALUE - tx.get_fee()))
+        );
+        assert_eq!(
+            receiver.get_private_key().decrypt_to_point(&balances[&receiver_key]),
+            receiver.get_private_key().decrypt_to_point(&receiver.get_public_key().encrypt(COIN_VALUE))
+        );
+
+        drop(state);
+        assert_storage_untouched(&storage, &sender, &receiver).await;
+    }
+
+    #[tokio::test]
+    async fn test_simulate_insufficient_balance() {
+        let sender = KeyPair::new();
+        let receiver = KeyPair::new();
+        let (_dir, storage) = setup_storage("insufficient", &sender).await;
+
+        // Sender pretends to have more funds than on chain
+        let tx = build_transfer(&sender, &receiver, 100 * COIN_VALUE, 50 * COIN_VALUE);
+        let mut state = StateOverlay::new(&storage, 0);
+        assert!(tx.verify(&mut state).await.is_err());
+
+        drop(state);
+        assert_storage_untouched(&storage, &sender, &receiver).await;
+    }
+
+    #[tokio::test]
+    async fn test_sub_policy_fee_accepted_by_consensus() {
+        let sender = KeyPair::new();
+        let receiver = KeyPair::new();
+        let (_dir, storage) = setup_storage("sub-policy-fee", &sender).await;
+
+        // Fees paid are the protocol minimum
+        let tx = build_transfer(&sender, &receiver, BALANCE, COIN_VALUE);
+        assert!(blockchain::estimate_required_tx_fees(&storage, 0, &tx).await.unwrap() <= tx.get_fee());
+
+        // Rejected by a node relay policy above the protocol minimum
+        let required_fees = blockchain::estimate_required_tx_fees_with_fee_per_kb(&storage, 0, &tx, FEE_PER_KB * 2).await.unwrap();
+        assert!(required_fees > tx.get_fee());
+
+        // But still valid when included in a block by another miner
+        let mut state = StateOverlay::new(&storage, 0);
+        assert!(tx.verify(&mut state).await.is_ok());
+    }
+}
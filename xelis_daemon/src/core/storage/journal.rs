@@ -0,0 +1,243 @@
+use xelis_common::{
+    crypto::Hash,
+    serializer::{Reader, ReaderError, Serializer, Writer}
+};
+use super::Tips;
+
+// Intent of a rewind, written before any deletion is made by pop_blocks
+// If the node stops in the middle of a rewind, it is replayed at startup
+// All its operations are idempotent so it can be applied several times
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RewindJournal {
+    // New top height once rewinded
+    height: u64,
+    // New top topoheight once rewinded
+    topoheight: u64,
+    // Pruned topoheight when the rewind started
+    pruned_topoheight: u64,
+    // Is the pruned topoheight reached and must be reset
+    pruned_reset: bool,
+    // Blocks count to store once rewinded
+    blocks_count: u64,
+    // New tips of the chain
+    tips: Tips,
+    // Topoheight, hash and height of the blocks to delete, from the highest topoheight to the lowest
+    blocks: Vec<(u64, Hash, u64)>
+}
+
+impl RewindJournal {
+    pub fn new(height: u64, topoheight: u64, pruned_topoheight: u64, pruned_reset: bool, blocks_count: u64, tips: Tips, blocks: Vec<(u64, Hash, u64)>) -> Self {
+        Self {
+            height,
+            topoheight,
+            pruned_topoheight,
+            pruned_reset,
+            blocks_count,
+            tips,
+            blocks
+        }
+    }
+
+    pub fn get_height(&self) -> u64 {
+        self.height
+    }
+
+    pub fn get_topoheight(&self) -> u64 {
+        self.topoheight
+    }
+
+    pub fn get_pruned_topoheight(&self) -> u64 {
+        self.pruned_topoheight
+    }
+
+    pub fn is_pruned_reset(&self) -> bool {
+        self.pruned_reset
+    }
+
+    pub fn get_blocks_count(&self) -> u64 {
+        self.blocks_count
+    }
+
+    pub fn get_tips(&self) -> &Tips {
+        &self.tips
+    }
+
+    pub fn get_blocks(&self) -> &Vec<(u64, Hash, u64)> {
+        &self.blocks
+    }
+}
+
+impl Serializer for RewindJournal {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u64(&self.height);
+        writer.write_u64(&self.topoheight);
+        writer.write_u64(&self.pruned_topoheight);
+        writer.write_bool(self.pruned_reset);
+        writer.write_u64(&self.blocks_count);
+
+        writer.write_u64(&(self.tips.len() as u64));
+        for hash in &self.tips {
+            writer.write_hash(hash);
+        }
+
+        writer.write_u64(&(self.blocks.len() as u64));
+        for (topoheight, hash, height) in &self.blocks {
+            writer.write_u64(topoheight);
+            writer.write_hash(hash);
+            writer.write_u64(height);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let height = reader.read_u64()?;
+        let topoheight = reader.read_u64()?;
+        let pruned_topoheight = reader.read_u64()?;
+        let pruned_reset = reader.read_bool()?;
+        let blocks_count = reader.read_u64()?;
+
+        let tips_len = reader.read_u64()?;
+        let mut tips = Tips::new();
+        for _ in 0..tips_len {
+            tips.insert(reader.read_hash()?);
+        }
+
+        let blocks_len = reader.read_u64()?;
+        let mut blocks = Vec::new();
+        for _ in 0..blocks_len {
+            let topoheight = reader.read_u64()?;
+            let hash = reader.read_hash()?;
+            let height = reader.read_u64()?;
+            blocks.push((topoheight, hash, height));
+        }
+
+        Ok(Self::new(height, topoheight, pruned_topoheight, pruned_reset, blocks_count, tips, blocks))
+    }
+
+    fn size(&self) -> usize {
+        // height, topoheight, pruned topoheight, blocks count and both lengths
+        8 * 6 + 1 + self.tips.len() * 32 + self.blocks.len() * (8 + 32 + 8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        account::{VersionedBalance, VersionedNonce},
+        crypto::{KeyPair, PublicKey}
+    };
+    use crate::core::{
+        error::DiskContext,
+        storage::{
+            sled::BLOCKS_COUNT,
+            test_utils::{add_linear_chain, open_storage, open_temp_storage},
+            BalanceProvider,
+            BlockDagProvider,
+            BlockProvider,
+            DagOrderProvider,
+            NonceProvider,
+            SledStorage,
+            Storage
+        }
+    };
+    use super::*;
+
+    const TOPOHEIGHT: u64 = 10;
+
+    // Linear chain of blocks where each block modify the balance and nonce of the miner
+    async fn create_test_chain(storage: &mut SledStorage, miner: &PublicKey) {
+        let hashes = add_linear_chain(storage, TOPOHEIGHT + 1, |_, block| block.miner(miner.clone())).await;
+        let asset = Hash::zero();
+        for topoheight in 0..=TOPOHEIGHT {
+            storage.set_supply_at_topo_height(topoheight, topoheight).unwrap();
+            storage.set_block_reward_at_topo_height(topoheight, 1).unwrap();
+
+            let previous = if topoheight > 0 { Some(topoheight - 1) } else { None };
+            let mut balance = VersionedBalance::zero();
+            balance.set_previous_topoheight(previous);
+            storage.set_last_balance_to(miner, &asset, topoheight, &balance).await.unwrap();
+            storage.set_last_nonce_to(miner, topoheight, &VersionedNonce::new(topoheight, previous)).await.unwrap();
+        }
+        storage.store_tips(&Tips::from_iter(hashes.last().cloned())).unwrap();
+    }
+
+    // Everything that must be identical between two chains after a rewind
+    async fn chain_state(storage: &SledStorage, miner: &PublicKey) -> (u64, u64, u64, Tips, Vec<Hash>, u64, u64) {
+        let topoheight = storage.get_top_topoheight().unwrap();
+        let mut hashes = Vec::new();
+        for topo in 0..=TOPOHEIGHT {
+            if let Ok(hash) = storage.get_hash_at_topo_height(topo).await {
+                assert!(storage.has_block_with_hash(&hash).await.unwrap());
+                assert_eq!(storage.get_topo_height_for_hash(&hash).await.unwrap(), topo);
+                hashes.push(hash);
+            }
+        }
+        let balance = storage.get_last_topoheight_for_balance(miner, &Hash::zero()).await.unwrap();
+        let (nonce, _) = storage.get_last_nonce(miner).await.unwrap();
+        let blocks_count = storage.load_from_disk::<u64>(&storage.extra, BLOCKS_COUNT, DiskContext::BlocksCount).unwrap();
+
+        (topoheight, storage.get_top_height().unwrap(), blocks_count, storage.get_tips().await.unwrap(), hashes, balance, nonce)
+    }
+
+    #[test]
+    fn test_journal_serialization() {
+        let journal = RewindJournal::new(5, 6, 2, false, 7, Tips::from([Hash::zero()]), vec![(8, Hash::max(), 8), (7, Hash::zero(), 7)]);
+        let bytes = journal.to_bytes();
+        assert_eq!(bytes.len(), journal.size());
+        assert_eq!(RewindJournal::from_bytes(&bytes).unwrap(), journal);
+    }
+
+    #[tokio::test]
+    async fn test_recover_interrupted_rewind() {
+        let miner = KeyPair::new().get_public_key().compress();
+
+        // Reference chain rewinded without any interruption
+        let (_expected_dir, mut expected) = open_temp_storage("rewind-expected");
+        create_test_chain(&mut expected, &miner).await;
+        let (height, topoheight, _) = expected.pop_blocks(TOPOHEIGHT, TOPOHEIGHT, 5, 0).await.unwrap();
+        assert_eq!((height, topoheight), (5, 5));
+        assert!(!expected.has_pending_rewind().unwrap());
+        let expected_state = chain_state(&expected, &miner).await;
+
+        // Crash after deleting some of the blocks
+        let (dir, mut storage) = open_temp_storage("rewind-crashed");
+        create_test_chain(&mut storage, &miner).await;
+        storage.rewind_failpoint = Some(2);
+        assert!(storage.pop_blocks(TOPOHEIGHT, TOPOHEIGHT, 5, 0).await.is_err());
+        assert!(storage.has_pending_rewind().unwrap());
+        // Top topoheight is not yet updated while blocks were deleted
+        assert_eq!(storage.get_top_topoheight().unwrap(), TOPOHEIGHT);
+        assert!(storage.get_hash_at_topo_height(TOPOHEIGHT).await.is_err());
+
+        // Restart the node
+        drop(storage);
+        let mut storage = open_storage(&dir);
+        assert!(storage.has_pending_rewind().unwrap());
+        assert_eq!(storage.recover_pending_rewind().await.unwrap(), Some(5));
+        assert!(!storage.has_pending_rewind().unwrap());
+        assert_eq!(chain_state(&storage, &miner).await, expected_state);
+
+        // Nothing left to recover
+        assert_eq!(storage.recover_pending_rewind().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_recover_block_partially_deleted() {
+        let miner = KeyPair::new().get_public_key().compress();
+        let (_expected_dir, mut expected) = open_temp_storage("rewind-expected-partial");
+        create_test_chain(&mut expected, &miner).await;
+        expected.pop_blocks(TOPOHEIGHT, TOPOHEIGHT, 3, 0).await.unwrap();
+        let expected_state = chain_state(&expected, &miner).await;
+
+        // Crash after the topoheight pointer of the first block was deleted
+        let (dir, mut storage) = open_temp_storage("rewind-partial");
+        create_test_chain(&mut storage, &miner).await;
+        storage.rewind_failpoint = Some(0);
+        assert!(storage.pop_blocks(TOPOHEIGHT, TOPOHEIGHT, 3, 0).await.is_err());
+        storage.hash_at_topo.remove(TOPOHEIGHT.to_be_bytes()).unwrap();
+
+        drop(storage);
+        let mut storage = open_storage(&dir);
+        assert_eq!(storage.recover_pending_rewind().await.unwrap(), Some(TOPOHEIGHT - 3));
+        assert_eq!(chain_state(&storage, &miner).await, expected_state);
+    }
+}
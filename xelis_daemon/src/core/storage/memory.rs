@@ -0,0 +1,1487 @@
+// In-memory storage keeping each tree of the disk storage in a map
+// It is used by the testing harness to run several nodes in the same process
+// Nothing is persisted, the chain is lost once the storage is dropped
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Arc, Mutex}
+};
+use async_trait::async_trait;
+use indexmap::{IndexMap, IndexSet};
+use log::{debug, error, trace, warn};
+use xelis_common::{
+    account::{VersionedBalance, VersionedNonce},
+    asset::{AssetData, AssetWithData},
+    block::{Block, BlockHeader},
+    crypto::{Hash, PublicKey},
+    difficulty::{CumulativeDifficulty, Difficulty},
+    immutable::Immutable,
+    network::Network,
+    serializer::Serializer,
+    transaction::Transaction,
+    varuint::VarUint
+};
+use crate::{
+    config::PRUNE_SAFETY_LIMIT,
+    core::error::{BlockchainError, DiskContext}
+};
+use super::{
+    AccountProvider,
+    AssetProvider,
+    BalanceProvider,
+    BlockDagProvider,
+    BlockExecutionOrderProvider,
+    BlockProvider,
+    BlocksAtHeightProvider,
+    BurnedSupplyProvider,
+    ClientProtocolProvider,
+    DagOrderProvider,
+    DifficultyProvider,
+    MempoolProvider,
+    MerkleHashProvider,
+    NonceProvider,
+    PrunedTopoheightProvider,
+    Storage,
+    Tips,
+    TransactionProvider,
+    TransactionWithMetadata
+};
+
+// Accounts are indexed by their bytes so they are ordered like on disk
+type AccountKey = [u8; 32];
+
+pub struct MemoryStorage {
+    network: Network,
+    // all txs stored
+    transactions: HashMap<Hash, Arc<Transaction>>,
+    // block that executed each tx
+    txs_executed: HashMap<Hash, Hash>,
+    txs_execution_index: HashMap<Hash, u16>,
+    // all blocks hashes where a tx was included in
+    tx_blocks: HashMap<Hash, Tips>,
+    // position of each block in the execution order
+    blocks_execution_order: IndexMap<Hash, u64>,
+    blocks_execution_count: u64,
+    // all blocks headers
+    blocks: HashMap<Hash, Arc<BlockHeader>>,
+    blocks_at_height: BTreeMap<u64, IndexSet<Hash>>,
+    topo_by_hash: HashMap<Hash, u64>,
+    hash_at_topo: BTreeMap<u64, Hash>,
+    difficulty: HashMap<Hash, Difficulty>,
+    cumulative_difficulty: HashMap<Hash, CumulativeDifficulty>,
+    difficulty_covariance: HashMap<Hash, VarUint>,
+    // block reward, supply and fees for each block topoheight
+    rewards: BTreeMap<u64, u64>,
+    supply: BTreeMap<u64, u64>,
+    fees: BTreeMap<u64, u64>,
+    merkle_hashes: HashMap<u64, Hash>,
+    assets: BTreeMap<Hash, AssetData>,
+    // topoheight of the last nonce of each account
+    nonces: BTreeMap<AccountKey, u64>,
+    versioned_nonces: BTreeMap<(u64, AccountKey), VersionedNonce>,
+    // topoheight of the last balance of each account for each asset
+    balances: BTreeMap<(AccountKey, Hash), u64>,
+    versioned_balances: BTreeMap<(u64, AccountKey, Hash), VersionedBalance>,
+    // Accounts having a balance for each asset, ordered by their last balance topoheight
+    asset_holders: BTreeSet<(Hash, u64, AccountKey)>,
+    // Account registrations topoheight
+    registrations: BTreeMap<AccountKey, u64>,
+    // Total burned supply per asset, versioned by topoheight
+    burned_supply: BTreeMap<(Hash, u64), u64>,
+    // Mempool is written while the storage is only read locked
+    mempool: Mutex<BTreeMap<Hash, Arc<Transaction>>>,
+    tips: Tips,
+    top_topoheight: Option<u64>,
+    top_height: Option<u64>,
+    pruned_topoheight: Option<u64>
+}
+
+impl MemoryStorage {
+    pub fn new(network: Network) -> Self {
+        Self {
+            network,
+            transactions: HashMap::new(),
+            txs_executed: HashMap::new(),
+            txs_execution_index: HashMap::new(),
+            tx_blocks: HashMap::new(),
+            blocks_execution_order: IndexMap::new(),
+            blocks_execution_count: 0,
+            blocks: HashMap::new(),
+            blocks_at_height: BTreeMap::new(),
+            topo_by_hash: HashMap::new(),
+            hash_at_topo: BTreeMap::new(),
+            difficulty: HashMap::new(),
+            cumulative_difficulty: HashMap::new(),
+            difficulty_covariance: HashMap::new(),
+            rewards: BTreeMap::new(),
+            supply: BTreeMap::new(),
+            fees: BTreeMap::new(),
+            merkle_hashes: HashMap::new(),
+            assets: BTreeMap::new(),
+            nonces: BTreeMap::new(),
+            versioned_nonces: BTreeMap::new(),
+            balances: BTreeMap::new(),
+            versioned_balances: BTreeMap::new(),
+            asset_holders: BTreeSet::new(),
+            registrations: BTreeMap::new(),
+            burned_supply: BTreeMap::new(),
+            mempool: Mutex::new(BTreeMap::new()),
+            tips: Tips::new(),
+            top_topoheight: None,
+            top_height: None,
+            pruned_topoheight: None
+        }
+    }
+
+    fn get_versioned_nonce(&self, key: &AccountKey, topoheight: u64) -> Result<&VersionedNonce, BlockchainError> {
+        self.versioned_nonces.get(&(topoheight, *key)).ok_or(BlockchainError::NotFoundOnDisk(DiskContext::NonceAtTopoHeight))
+    }
+
+    fn get_versioned_balance(&self, key: &AccountKey, asset: &Hash, topoheight: u64) -> Result<&VersionedBalance, BlockchainError> {
+        self.versioned_balances.get(&(topoheight, *key, asset.clone())).ok_or(BlockchainError::NotFoundOnDisk(DiskContext::BalanceAtTopoHeight))
+    }
+
+    // Update the pointer to the highest versioned balance and its asset holders entry
+    fn set_balance_pointer(&mut self, key: AccountKey, asset: &Hash, topoheight: u64) {
+        if let Some(previous) = self.balances.insert((key, asset.clone()), topoheight) {
+            self.asset_holders.remove(&(asset.clone(), previous, key));
+        }
+        self.asset_holders.insert((asset.clone(), topoheight, key));
+    }
+
+    // Delete the pointer to the highest versioned balance and its asset holders entry
+    fn delete_balance_pointer(&mut self, key: AccountKey, asset: &Hash) {
+        if let Some(previous) = self.balances.remove(&(key, asset.clone())) {
+            self.asset_holders.remove(&(asset.clone(), previous, key));
+        }
+    }
+
+    // Delete the transactions of a block being deleted and returns those no longer included in any block
+    fn delete_block_transactions(&mut self, hash: &Hash, block: &BlockHeader) -> Result<Vec<(Hash, Arc<Transaction>)>, BlockchainError> {
+        let mut txs = Vec::new();
+        for tx_hash in block.get_transactions() {
+            let blocks_left = self.remove_block_for_tx(tx_hash, hash)?;
+
+            if self.is_tx_executed_in_block(tx_hash, hash)? {
+                trace!("Tx {} was executed in block {}, deleting", tx_hash, hash);
+                self.remove_tx_executed(tx_hash)?;
+            }
+
+            if blocks_left > 0 {
+                trace!("Tx {} is still included in {} blocks, keeping it", tx_hash, blocks_left);
+                continue;
+            }
+
+            if let Some(tx) = self.transactions.remove(tx_hash) {
+                trace!("Deleting TX {} in block {}", tx_hash, hash);
+                txs.push((tx_hash.clone(), tx));
+            }
+        }
+
+        Ok(txs)
+    }
+
+    // Restore the assets, nonces and balances pointers to their state at the topoheight
+    fn rewind_versions(&mut self, topoheight: u64, pruned_topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("Cleaning assets");
+        let deleted_assets: Vec<Hash> = self.assets.iter()
+            .filter(|(_, data)| data.get_topoheight() > topoheight)
+            .map(|(asset, _)| asset.clone())
+            .collect();
+
+        for asset in deleted_assets.iter() {
+            trace!("Asset {} was registered above topoheight {}, deleting", asset, topoheight);
+            self.assets.remove(asset);
+        }
+
+        trace!("Cleaning nonces");
+        for (key, highest_topoheight) in self.nonces.clone() {
+            if highest_topoheight < pruned_topoheight {
+                warn!("wrong nonce topoheight stored, highest topoheight is {}, pruned topoheight is {}", highest_topoheight, pruned_topoheight);
+                self.nonces.remove(&key);
+                continue;
+            }
+
+            if highest_topoheight > topoheight {
+                self.nonces.remove(&key);
+
+                // find the first version which is under topoheight
+                let mut version = self.get_versioned_nonce(&key, highest_topoheight)?;
+                while let Some(previous_topoheight) = version.get_previous_topoheight() {
+                    if previous_topoheight <= topoheight {
+                        self.nonces.insert(key, previous_topoheight);
+                        break;
+                    }
+
+                    version = self.get_versioned_nonce(&key, previous_topoheight)?;
+                }
+            }
+        }
+
+        trace!("Cleaning balances");
+        for ((key, asset), highest_topoheight) in self.balances.clone() {
+            let mut delete = deleted_assets.contains(&asset);
+            if !delete && highest_topoheight > topoheight && highest_topoheight >= pruned_topoheight {
+                // Mark for deletion if we can't find a version under the new topoheight
+                delete = true;
+
+                let mut version = self.get_versioned_balance(&key, &asset, highest_topoheight)?;
+                while let Some(previous_topoheight) = version.get_previous_topoheight() {
+                    if previous_topoheight <= topoheight {
+                        delete = false;
+                        self.set_balance_pointer(key, &asset, previous_topoheight);
+                        break;
+                    }
+
+                    version = self.get_versioned_balance(&key, &asset, previous_topoheight)?;
+                }
+            }
+
+            if delete {
+                self.delete_balance_pointer(key, &asset);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AccountProvider for MemoryStorage {
+    async fn get_account_registration_topoheight(&self, key: &PublicKey) -> Result<u64, BlockchainError> {
+        self.registrations.get(key.as_bytes()).copied().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::AccountRegistrationTopoHeight))
+    }
+
+    async fn set_account_registration_topoheight(&mut self, key: &PublicKey, topoheight: u64) -> Result<(), BlockchainError> {
+        self.registrations.insert(*key.as_bytes(), topoheight);
+        Ok(())
+    }
+
+    async fn is_account_registered(&self, key: &PublicKey) -> Result<bool, BlockchainError> {
+        Ok(self.registrations.contains_key(key.as_bytes()))
+    }
+
+    async fn is_account_registered_below_topoheight(&self, key: &PublicKey, topoheight: u64) -> Result<bool, BlockchainError> {
+        Ok(self.registrations.get(key.as_bytes()).is_some_and(|registration| *registration < topoheight))
+    }
+
+    async fn delete_registrations_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        self.registrations.retain(|_, registration| *registration != topoheight);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AssetProvider for MemoryStorage {
+    async fn has_asset(&self, hash: &Hash) -> Result<bool, BlockchainError> {
+        Ok(self.assets.contains_key(hash))
+    }
+
+    async fn get_asset(&self, hash: &Hash) -> Result<AssetData, BlockchainError> {
+        self.assets.get(hash).cloned().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::Asset))
+    }
+
+    async fn get_assets(&self) -> Result<Vec<Hash>, BlockchainError> {
+        Ok(self.assets.keys().cloned().collect())
+    }
+
+    async fn get_partial_assets(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64, after: Option<&Hash>) -> Result<IndexSet<AssetWithData>, BlockchainError> {
+        let assets = self.assets.iter()
+            .skip_while(|(asset, _)| after.is_some_and(|after| *asset <= after))
+            .filter(|(_, data)| data.get_topoheight() >= minimum_topoheight && data.get_topoheight() <= maximum_topoheight)
+            .skip(skip)
+            .take(maximum)
+            .map(|(asset, data)| AssetWithData::new(asset.clone(), data.clone()))
+            .collect();
+
+        Ok(assets)
+    }
+
+    async fn get_chunked_assets(&self, maximum: usize, skip: usize) -> Result<IndexSet<Hash>, BlockchainError> {
+        Ok(self.assets.keys().skip(skip).take(maximum).cloned().collect())
+    }
+
+    async fn get_assets_for(&self, key: &PublicKey) -> Result<Vec<Hash>, BlockchainError> {
+        let key = *key.as_bytes();
+        let assets = self.balances.range((key, Hash::zero())..=(key, Hash::max()))
+            .map(|((_, asset), _)| asset.clone())
+            .collect();
+
+        Ok(assets)
+    }
+
+    async fn count_assets(&self) -> Result<u64, BlockchainError> {
+        Ok(self.assets.len() as u64)
+    }
+
+    async fn add_asset(&mut self, hash: &Hash, data: AssetData) -> Result<(), BlockchainError> {
+        trace!("add asset {} at topoheight {}", hash, data.get_topoheight());
+        self.assets.insert(hash.clone(), data);
+        Ok(())
+    }
+
+    async fn delete_asset(&mut self, hash: &Hash) -> Result<(), BlockchainError> {
+        trace!("delete asset {}", hash);
+        self.assets.remove(hash);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BalanceProvider for MemoryStorage {
+    async fn has_balance_for(&self, key: &PublicKey, asset: &Hash) -> Result<bool, BlockchainError> {
+        if !self.has_asset(asset).await? {
+            return Err(BlockchainError::AssetNotFound(asset.clone()))
+        }
+
+        Ok(self.balances.contains_key(&(*key.as_bytes(), asset.clone())))
+    }
+
+    async fn has_balance_at_exact_topoheight(&self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<bool, BlockchainError> {
+        if !self.has_balance_for(key, asset).await? {
+            return Ok(false)
+        }
+
+        Ok(self.versioned_balances.contains_key(&(topoheight, *key.as_bytes(), asset.clone())))
+    }
+
+    async fn get_balance_at_exact_topoheight(&self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<VersionedBalance, BlockchainError> {
+        if !self.has_balance_at_exact_topoheight(key, asset, topoheight).await? {
+            return Err(BlockchainError::NoBalanceChanges(key.as_address(self.is_mainnet()), topoheight, asset.clone()))
+        }
+
+        self.get_versioned_balance(key.as_bytes(), asset, topoheight).cloned()
+    }
+
+    async fn get_balance_at_maximum_topoheight(&self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<Option<(u64, VersionedBalance)>, BlockchainError> {
+        if !self.has_balance_for(key, asset).await? {
+            return Ok(None)
+        }
+
+        if self.has_balance_at_exact_topoheight(key, asset, topoheight).await? {
+            return Ok(Some((topoheight, self.get_balance_at_exact_topoheight(key, asset, topoheight).await?)))
+        }
+
+        let (topo, mut version) = self.get_last_balance(key, asset).await?;
+        if topo <= topoheight {
+            return Ok(Some((topo, version)))
+        }
+
+        while let Some(previous) = version.get_previous_topoheight() {
+            let previous_version = self.get_balance_at_exact_topoheight(key, asset, previous).await?;
+            if previous <= topoheight {
+                return Ok(Some((previous, previous_version)))
+            }
+
+            if let Some(value) = previous_version.get_previous_topoheight() {
+                if value > previous {
+                    error!("FATAL ERROR: Previous topoheight ({}) should not be higher than current version ({})!", value, previous);
+                    return Err(BlockchainError::Unknown)
+                }
+            }
+            version = previous_version;
+        }
+
+        Ok(None)
+    }
+
+    async fn get_last_topoheight_for_balance(&self, key: &PublicKey, asset: &Hash) -> Result<u64, BlockchainError> {
+        Ok(self.balances.get(&(*key.as_bytes(), asset.clone())).copied().unwrap_or(0))
+    }
+
+    async fn get_new_versioned_balance(&self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<VersionedBalance, BlockchainError> {
+        let version = match self.get_balance_at_maximum_topoheight(key, asset, topoheight).await? {
+            Some((topo, mut version)) => {
+                version.prepare_new(Some(topo));
+                version
+            },
+            None => VersionedBalance::zero()
+        };
+
+        Ok(version)
+    }
+
+    async fn get_output_balance_at_maximum_topoheight(&self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<Option<(u64, VersionedBalance)>, BlockchainError> {
+        if let Some((topo, version)) = self.get_balance_at_maximum_topoheight(key, asset, topoheight).await? {
+            if version.contains_output() {
+                return Ok(Some((topo, version)))
+            }
+
+            let mut previous = version.get_previous_topoheight();
+            while let Some(topo) = previous {
+                let previous_version = self.get_balance_at_exact_topoheight(key, asset, topo).await?;
+                if previous_version.contains_output() {
+                    return Ok(Some((topo, previous_version)))
+                }
+
+                previous = previous_version.get_previous_topoheight();
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn get_last_balance(&self, key: &PublicKey, asset: &Hash) -> Result<(u64, VersionedBalance), BlockchainError> {
+        let topoheight = match self.balances.get(&(*key.as_bytes(), asset.clone())) {
+            Some(topoheight) if self.has_asset(asset).await? => *topoheight,
+            _ => return Err(BlockchainError::NoBalance(key.as_address(self.is_mainnet())))
+        };
+
+        let version = self.get_balance_at_exact_topoheight(key, asset, topoheight).await?;
+        Ok((topoheight, version))
+    }
+
+    async fn get_versioned_balances<'a, I: Iterator<Item = &'a PublicKey> + Send>(&self, asset: &Hash, keys: I, maximum_topoheight: u64) -> Result<Vec<Option<VersionedBalance>>, BlockchainError> {
+        let mut balances = Vec::new();
+        for key in keys {
+            let balance = self.get_balance_at_maximum_topoheight(key, asset, maximum_topoheight).await?
+                .map(|(_, version)| version);
+            balances.push(balance);
+        }
+
+        Ok(balances)
+    }
+
+    fn set_last_topoheight_for_balance(&mut self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<(), BlockchainError> {
+        self.set_balance_pointer(*key.as_bytes(), asset, topoheight);
+        Ok(())
+    }
+
+    async fn set_last_balance_to(&mut self, key: &PublicKey, asset: &Hash, topoheight: u64, version: &VersionedBalance) -> Result<(), BlockchainError> {
+        self.set_balance_at_topoheight(asset, topoheight, key, version).await?;
+        self.set_last_topoheight_for_balance(key, asset, topoheight)
+    }
+
+    async fn set_balance_at_topoheight(&mut self, asset: &Hash, topoheight: u64, key: &PublicKey, balance: &VersionedBalance) -> Result<(), BlockchainError> {
+        self.versioned_balances.insert((topoheight, *key.as_bytes(), asset.clone()), balance.clone());
+        Ok(())
+    }
+
+    async fn delete_balance_at_topoheight(&mut self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<VersionedBalance, BlockchainError> {
+        self.versioned_balances.remove(&(topoheight, *key.as_bytes(), asset.clone()))
+            .ok_or_else(|| BlockchainError::NoBalanceChanges(key.as_address(self.is_mainnet()), topoheight, asset.clone()))
+    }
+
+    fn delete_last_topoheight_for_balance(&mut self, key: &PublicKey, asset: &Hash) -> Result<(), BlockchainError> {
+        self.delete_balance_pointer(*key.as_bytes(), asset);
+        Ok(())
+    }
+
+    async fn count_asset_holders(&self, asset: &Hash) -> Result<usize, BlockchainError> {
+        Ok(self.asset_holders.range((asset.clone(), 0, [0u8; 32])..=(asset.clone(), u64::MAX, [u8::MAX; 32])).count())
+    }
+
+    async fn get_asset_holders(&self, asset: &Hash, skip: usize, maximum: usize) -> Result<Vec<(PublicKey, u64)>, BlockchainError> {
+        let mut holders = Vec::new();
+        for (_, topoheight, key) in self.asset_holders.range((asset.clone(), 0, [0u8; 32])..=(asset.clone(), u64::MAX, [u8::MAX; 32])).rev().skip(skip).take(maximum) {
+            holders.push((PublicKey::from_bytes(key)?, *topoheight));
+        }
+
+        Ok(holders)
+    }
+}
+
+#[async_trait]
+impl BlockProvider for MemoryStorage {
+    async fn has_blocks(&self) -> bool {
+        !self.blocks.is_empty()
+    }
+
+    async fn count_blocks(&self) -> Result<u64, BlockchainError> {
+        Ok(self.blocks.len() as u64)
+    }
+
+    async fn has_block_with_hash(&self, hash: &Hash) -> Result<bool, BlockchainError> {
+        Ok(self.blocks.contains_key(hash))
+    }
+
+    async fn get_block_by_hash(&self, hash: &Hash) -> Result<Block, BlockchainError> {
+        let block = self.get_block_header_by_hash(hash).await?;
+        let mut transactions = Vec::new();
+        for tx in block.get_transactions() {
+            let transaction = self.get_transaction(tx).await?;
+            transactions.push(Immutable::Arc(transaction));
+        }
+
+        Ok(Block::new(Immutable::Arc(block), transactions))
+    }
+
+    async fn save_block(&mut self, block: Arc<BlockHeader>, txs: &Vec<Immutable<Transaction>>, difficulty: Difficulty, p: VarUint, hash: Hash) -> Result<(), BlockchainError> {
+        debug!("Storing new {} with hash: {}, difficulty: {}", block, hash, difficulty);
+        for (tx_hash, tx) in block.get_transactions().iter().zip(txs) {
+            if !self.transactions.contains_key(tx_hash) {
+                self.transactions.insert(tx_hash.clone(), tx.clone().to_arc());
+            }
+        }
+
+        for tx_hash in block.get_transactions() {
+            self.add_block_linked_to_tx_if_not_present(tx_hash, &hash)?;
+        }
+
+        self.difficulty.insert(hash.clone(), difficulty);
+        self.difficulty_covariance.insert(hash.clone(), p);
+        self.add_block_hash_at_height(hash.clone(), block.get_height()).await?;
+        self.blocks.insert(hash, block);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BlockExecutionOrderProvider for MemoryStorage {
+    async fn get_blocks_execution_order(&self, skip: usize, count: usize) -> Result<IndexSet<Hash>, BlockchainError> {
+        Ok(self.blocks_execution_order.keys().skip(skip).take(count).cloned().collect())
+    }
+
+    async fn get_block_position_in_order(&self, hash: &Hash) -> Result<u64, BlockchainError> {
+        self.blocks_execution_order.get(hash).copied().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::SearchBlockPositionInOrder))
+    }
+
+    async fn has_block_position_in_order(&self, hash: &Hash) -> Result<bool, BlockchainError> {
+        Ok(self.blocks_execution_order.contains_key(hash))
+    }
+
+    async fn add_block_execution_to_order(&mut self, hash: &Hash) -> Result<(), BlockchainError> {
+        self.blocks_execution_order.insert(hash.clone(), self.blocks_execution_count);
+        self.blocks_execution_count += 1;
+        Ok(())
+    }
+
+    async fn get_blocks_execution_count(&self) -> u64 {
+        self.blocks_execution_count
+    }
+}
+
+#[async_trait]
+impl BlockDagProvider for MemoryStorage {
+    async fn get_block_header_at_topoheight(&self, topoheight: u64) -> Result<(Hash, Arc<BlockHeader>), BlockchainError> {
+        let hash = self.get_hash_at_topo_height(topoheight).await?;
+        let block = self.get_block_header_by_hash(&hash).await?;
+        Ok((hash, block))
+    }
+
+    fn get_block_reward_at_topo_height(&self, topoheight: u64) -> Result<u64, BlockchainError> {
+        self.rewards.get(&topoheight).copied().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::BlockRewardAtTopoHeight))
+    }
+
+    fn get_block_fees_at_topo_height(&self, topoheight: u64) -> Result<u64, BlockchainError> {
+        self.fees.get(&topoheight).copied().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::BlockFeesAtTopoHeight))
+    }
+
+    async fn get_supply_at_topo_height(&self, topoheight: u64) -> Result<u64, BlockchainError> {
+        self.supply.get(&topoheight).copied().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::SupplyAtTopoHeight))
+    }
+
+    fn set_block_reward_at_topo_height(&mut self, topoheight: u64, reward: u64) -> Result<(), BlockchainError> {
+        self.rewards.insert(topoheight, reward);
+        Ok(())
+    }
+
+    fn set_block_fees_at_topo_height(&mut self, topoheight: u64, fees: u64) -> Result<(), BlockchainError> {
+        self.fees.insert(topoheight, fees);
+        Ok(())
+    }
+
+    fn set_supply_at_topo_height(&mut self, topoheight: u64, supply: u64) -> Result<(), BlockchainError> {
+        self.supply.insert(topoheight, supply);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BlocksAtHeightProvider for MemoryStorage {
+    async fn has_blocks_at_height(&self, height: u64) -> Result<bool, BlockchainError> {
+        Ok(self.blocks_at_height.contains_key(&height))
+    }
+
+    async fn get_blocks_at_height(&self, height: u64) -> Result<IndexSet<Hash>, BlockchainError> {
+        self.blocks_at_height.get(&height).cloned().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::BlocksAtHeight))
+    }
+
+    async fn set_blocks_at_height(&mut self, tips: IndexSet<Hash>, height: u64) -> Result<(), BlockchainError> {
+        self.blocks_at_height.insert(height, tips);
+        Ok(())
+    }
+
+    async fn add_block_hash_at_height(&mut self, hash: Hash, height: u64) -> Result<(), BlockchainError> {
+        self.blocks_at_height.entry(height).or_default().insert(hash);
+        Ok(())
+    }
+
+    async fn remove_block_hash_at_height(&mut self, hash: &Hash, height: u64) -> Result<(), BlockchainError> {
+        let tips = self.blocks_at_height.get_mut(&height).ok_or(BlockchainError::NotFoundOnDisk(DiskContext::BlocksAtHeight))?;
+        tips.shift_remove(hash);
+
+        // Delete the height if there is no blocks present anymore
+        if tips.is_empty() {
+            self.blocks_at_height.remove(&height);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BurnedSupplyProvider for MemoryStorage {
+    async fn get_burned_supply_at_maximum_topoheight(&self, asset: &Hash, topoheight: u64) -> Result<Option<(u64, u64)>, BlockchainError> {
+        let version = self.burned_supply.range((asset.clone(), 0)..=(asset.clone(), topoheight))
+            .next_back()
+            .map(|((_, topo), burned)| (*topo, *burned));
+
+        Ok(version)
+    }
+
+    fn set_burned_supply_at_topoheight(&mut self, asset: &Hash, topoheight: u64, burned: u64) -> Result<(), BlockchainError> {
+        self.burned_supply.insert((asset.clone(), topoheight), burned);
+        Ok(())
+    }
+
+    async fn delete_burned_supply_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        self.burned_supply.retain(|(_, topo), _| *topo != topoheight);
+        Ok(())
+    }
+
+    async fn delete_burned_supply_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        self.burned_supply.retain(|(_, topo), _| *topo <= topoheight);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ClientProtocolProvider for MemoryStorage {
+    fn get_block_executor_for_tx(&self, tx: &Hash) -> Result<Hash, BlockchainError> {
+        self.txs_executed.get(tx).cloned().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::BlockExecutorForTx))
+    }
+
+    fn is_tx_executed_in_a_block(&self, tx: &Hash) -> Result<bool, BlockchainError> {
+        Ok(self.txs_executed.contains_key(tx))
+    }
+
+    fn is_tx_executed_in_block(&self, tx: &Hash, block: &Hash) -> Result<bool, BlockchainError> {
+        Ok(self.txs_executed.get(tx) == Some(block))
+    }
+
+    fn has_tx_blocks(&self, hash: &Hash) -> Result<bool, BlockchainError> {
+        Ok(self.tx_blocks.contains_key(hash))
+    }
+
+    fn has_block_linked_to_tx(&self, tx: &Hash, block: &Hash) -> Result<bool, BlockchainError> {
+        Ok(self.tx_blocks.get(tx).is_some_and(|blocks| blocks.contains(block)))
+    }
+
+    fn add_block_linked_to_tx_if_not_present(&mut self, tx: &Hash, block: &Hash) -> Result<bool, BlockchainError> {
+        Ok(self.tx_blocks.entry(tx.clone()).or_default().insert(block.clone()))
+    }
+
+    fn get_blocks_for_tx(&self, hash: &Hash) -> Result<Tips, BlockchainError> {
+        self.tx_blocks.get(hash).cloned().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::TxBlocks))
+    }
+
+    fn get_tx_execution_index(&self, tx: &Hash) -> Result<Option<u16>, BlockchainError> {
+        Ok(self.txs_execution_index.get(tx).copied())
+    }
+
+    fn set_tx_executed_in_block(&mut self, tx: &Hash, block: &Hash, index: u16) -> Result<(), BlockchainError> {
+        self.txs_executed.insert(tx.clone(), block.clone());
+        self.txs_execution_index.insert(tx.clone(), index);
+        Ok(())
+    }
+
+    fn remove_tx_executed(&mut self, tx: &Hash) -> Result<(), BlockchainError> {
+        self.txs_executed.remove(tx);
+        self.txs_execution_index.remove(tx);
+        Ok(())
+    }
+
+    fn set_blocks_for_tx(&mut self, tx: &Hash, blocks: &Tips) -> Result<(), BlockchainError> {
+        self.tx_blocks.insert(tx.clone(), blocks.clone());
+        Ok(())
+    }
+
+    fn add_block_for_tx(&mut self, tx: &Hash, block: &Hash) -> Result<(), BlockchainError> {
+        self.add_block_linked_to_tx_if_not_present(tx, block)?;
+        Ok(())
+    }
+
+    fn remove_block_for_tx(&mut self, tx: &Hash, block: &Hash) -> Result<usize, BlockchainError> {
+        let Some(blocks) = self.tx_blocks.get_mut(tx) else {
+            return Ok(0)
+        };
+
+        blocks.remove(block);
+        let left = blocks.len();
+        // No empty set is kept, the tx is not referenced anymore
+        if left == 0 {
+            self.tx_blocks.remove(tx);
+        }
+
+        Ok(left)
+    }
+}
+
+#[async_trait]
+impl DagOrderProvider for MemoryStorage {
+    async fn get_topo_height_for_hash(&self, hash: &Hash) -> Result<u64, BlockchainError> {
+        self.topo_by_hash.get(hash).copied().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::GetTopoHeightForHash))
+    }
+
+    async fn set_topo_height_for_block(&mut self, hash: &Hash, topoheight: u64) -> Result<(), BlockchainError> {
+        self.topo_by_hash.insert(hash.clone(), topoheight);
+        self.hash_at_topo.insert(topoheight, hash.clone());
+        Ok(())
+    }
+
+    async fn is_block_topological_ordered(&self, hash: &Hash) -> bool {
+        self.topo_by_hash.get(hash)
+            .and_then(|topoheight| self.hash_at_topo.get(topoheight))
+            .is_some_and(|hash_at_topo| hash_at_topo == hash)
+    }
+
+    async fn get_hash_at_topo_height(&self, topoheight: u64) -> Result<Hash, BlockchainError> {
+        self.hash_at_topo.get(&topoheight).cloned().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::GetBlockHashAtTopoHeight(topoheight)))
+    }
+}
+
+#[async_trait]
+impl DifficultyProvider for MemoryStorage {
+    async fn get_height_for_block_hash(&self, hash: &Hash) -> Result<u64, BlockchainError> {
+        Ok(self.get_block_header_by_hash(hash).await?.get_height())
+    }
+
+    async fn get_timestamp_for_block_hash(&self, hash: &Hash) -> Result<u64, BlockchainError> {
+        Ok(self.get_block_header_by_hash(hash).await?.get_timestamp())
+    }
+
+    async fn get_difficulty_for_block_hash(&self, hash: &Hash) -> Result<Difficulty, BlockchainError> {
+        self.difficulty.get(hash).copied().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::DifficultyForBlockHash))
+    }
+
+    async fn get_cumulative_difficulty_for_block_hash(&self, hash: &Hash) -> Result<CumulativeDifficulty, BlockchainError> {
+        self.cumulative_difficulty.get(hash).copied().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::CumulativeDifficultyForBlockHash))
+    }
+
+    async fn get_past_blocks_for_block_hash(&self, hash: &Hash) -> Result<Immutable<IndexSet<Hash>>, BlockchainError> {
+        let block = self.get_block_header_by_hash(hash).await?;
+        Ok(Immutable::Owned(block.get_tips().clone()))
+    }
+
+    async fn get_block_header_by_hash(&self, hash: &Hash) -> Result<Arc<BlockHeader>, BlockchainError> {
+        self.blocks.get(hash).cloned().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::GetBlockHeaderByHash))
+    }
+
+    async fn get_estimated_covariance_for_block_hash(&self, hash: &Hash) -> Result<VarUint, BlockchainError> {
+        self.difficulty_covariance.get(hash).copied().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::EstimatedCovarianceForBlockHash))
+    }
+
+    async fn set_estimated_covariance_for_block_hash(&mut self, hash: &Hash, p: VarUint) -> Result<(), BlockchainError> {
+        self.difficulty_covariance.insert(hash.clone(), p);
+        Ok(())
+    }
+
+    async fn set_cumulative_difficulty_for_block_hash(&mut self, hash: &Hash, cumulative_difficulty: CumulativeDifficulty) -> Result<(), BlockchainError> {
+        self.cumulative_difficulty.insert(hash.clone(), cumulative_difficulty);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MempoolProvider for MemoryStorage {
+    fn save_mempool_tx(&self, hash: &Hash, tx: &Transaction) -> Result<(), BlockchainError> {
+        self.mempool.lock()?.insert(hash.clone(), Arc::new(tx.clone()));
+        Ok(())
+    }
+
+    fn delete_mempool_tx(&self, hash: &Hash) -> Result<(), BlockchainError> {
+        self.mempool.lock()?.remove(hash);
+        Ok(())
+    }
+
+    async fn get_mempool_txs(&self) -> Result<Vec<(Hash, Arc<Transaction>)>, BlockchainError> {
+        let mempool = self.mempool.lock()?;
+        Ok(mempool.iter().map(|(hash, tx)| (hash.clone(), Arc::clone(tx))).collect())
+    }
+
+    fn clear_mempool_txs(&self) -> Result<(), BlockchainError> {
+        self.mempool.lock()?.clear();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MerkleHashProvider for MemoryStorage {
+    async fn get_balances_merkle_hash_at_topoheight(&self, topoheight: u64) -> Result<Hash, BlockchainError> {
+        self.merkle_hashes.get(&topoheight).cloned().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::BalancesMerkleHashAtTopoHeight))
+    }
+
+    async fn set_balances_merkle_hash_at_topoheight(&mut self, topoheight: u64, merkle_proof: &Hash) -> Result<(), BlockchainError> {
+        self.merkle_hashes.insert(topoheight, merkle_proof.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NonceProvider for MemoryStorage {
+    async fn has_nonce(&self, key: &PublicKey) -> Result<bool, BlockchainError> {
+        Ok(self.nonces.contains_key(key.as_bytes()))
+    }
+
+    async fn count_accounts(&self) -> Result<u64, BlockchainError> {
+        Ok(self.nonces.len() as u64)
+    }
+
+    async fn has_nonce_at_exact_topoheight(&self, key: &PublicKey, topoheight: u64) -> Result<bool, BlockchainError> {
+        Ok(self.versioned_nonces.contains_key(&(topoheight, *key.as_bytes())))
+    }
+
+    async fn get_partial_keys(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64, after: Option<&PublicKey>) -> Result<IndexSet<PublicKey>, BlockchainError> {
+        let after = after.map(|key| *key.as_bytes());
+        let mut keys = IndexSet::new();
+        let mut skip_count = 0;
+        for key in self.nonces.keys().skip_while(|key| after.is_some_and(|after| **key <= after)) {
+            let key = PublicKey::from_bytes(key)?;
+            if self.has_key_updated_in_range(&key, minimum_topoheight, maximum_topoheight).await? {
+                if skip_count < skip {
+                    skip_count += 1;
+                } else {
+                    keys.insert(key);
+                    if keys.len() == maximum {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(keys)
+    }
+
+    async fn get_last_topoheight_for_nonce(&self, key: &PublicKey) -> Result<u64, BlockchainError> {
+        self.nonces.get(key.as_bytes()).copied().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::LastTopoheightForNonce))
+    }
+
+    async fn get_last_nonce(&self, key: &PublicKey) -> Result<(u64, VersionedNonce), BlockchainError> {
+        let Some(topoheight) = self.nonces.get(key.as_bytes()).copied() else {
+            return Err(BlockchainError::NoNonce(key.as_address(self.is_mainnet())))
+        };
+
+        Ok((topoheight, self.get_nonce_at_exact_topoheight(key, topoheight).await?))
+    }
+
+    async fn get_nonce_at_exact_topoheight(&self, key: &PublicKey, topoheight: u64) -> Result<VersionedNonce, BlockchainError> {
+        self.get_versioned_nonce(key.as_bytes(), topoheight).cloned()
+    }
+
+    async fn get_nonce_at_maximum_topoheight(&self, key: &PublicKey, topoheight: u64) -> Result<Option<(u64, VersionedNonce)>, BlockchainError> {
+        if !self.has_nonce(key).await? {
+            return Ok(None)
+        }
+
+        let (topo, mut version) = self.get_last_nonce(key).await?;
+        if topo <= topoheight {
+            return Ok(Some((topo, version)))
+        }
+
+        while let Some(previous) = version.get_previous_topoheight() {
+            let previous_version = self.get_nonce_at_exact_topoheight(key, previous).await?;
+            if previous <= topoheight {
+                return Ok(Some((previous, previous_version)))
+            }
+
+            if let Some(value) = previous_version.get_previous_topoheight() {
+                if value > previous {
+                    error!("FATAL ERROR: Previous topoheight ({}) should not be higher than current version ({})!", value, previous);
+                    return Err(BlockchainError::Unknown)
+                }
+            }
+            version = previous_version;
+        }
+
+        Ok(None)
+    }
+
+    async fn get_nonce_history(&self, key: &PublicKey, maximum: usize, minimum_topoheight: u64) -> Result<Vec<(u64, VersionedNonce)>, BlockchainError> {
+        let mut history = Vec::new();
+        if maximum == 0 || !self.has_nonce(key).await? {
+            return Ok(history)
+        }
+
+        let (mut topoheight, mut version) = self.get_last_nonce(key).await?;
+        while topoheight >= minimum_topoheight {
+            let previous = version.get_previous_topoheight();
+            history.push((topoheight, version));
+            if history.len() >= maximum {
+                break;
+            }
+
+            match previous {
+                // Older versions may have been deleted by the pruning
+                Some(previous) if previous < minimum_topoheight => break,
+                Some(previous) if previous < topoheight => {
+                    version = self.get_nonce_at_exact_topoheight(key, previous).await?;
+                    topoheight = previous;
+                },
+                Some(previous) => {
+                    error!("FATAL ERROR: Previous topoheight ({}) should not be higher than current version ({})!", previous, topoheight);
+                    return Err(BlockchainError::Unknown)
+                },
+                None => break
+            }
+        }
+
+        Ok(history)
+    }
+
+    async fn has_key_updated_in_range(&self, key: &PublicKey, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<bool, BlockchainError> {
+        if !self.has_nonce(key).await? {
+            return Ok(false)
+        }
+
+        let (topo, mut version) = self.get_last_nonce(key).await?;
+        if topo >= minimum_topoheight && topo <= maximum_topoheight {
+            return Ok(true)
+        }
+
+        while let Some(previous) = version.get_previous_topoheight() {
+            if previous < minimum_topoheight {
+                break;
+            }
+
+            if previous <= maximum_topoheight {
+                return Ok(true)
+            }
+            version = self.get_nonce_at_exact_topoheight(key, previous).await?;
+        }
+
+        // No nonce found in the range, check the balances
+        for asset in self.get_assets_for(key).await? {
+            let (topo, mut version) = self.get_last_balance(key, &asset).await?;
+            if topo >= minimum_topoheight && topo <= maximum_topoheight {
+                return Ok(true)
+            }
+
+            while let Some(previous) = version.get_previous_topoheight() {
+                if previous < minimum_topoheight {
+                    break;
+                }
+
+                if previous <= maximum_topoheight {
+                    return Ok(true)
+                }
+                version = self.get_balance_at_exact_topoheight(key, &asset, previous).await?;
+            }
+        }
+
+        Ok(false)
+    }
+
+    async fn set_last_topoheight_for_nonce(&mut self, key: &PublicKey, topoheight: u64) -> Result<(), BlockchainError> {
+        self.nonces.insert(*key.as_bytes(), topoheight);
+        Ok(())
+    }
+
+    async fn delete_last_topoheight_for_nonce(&mut self, key: &PublicKey) -> Result<(), BlockchainError> {
+        self.nonces.remove(key.as_bytes());
+        Ok(())
+    }
+
+    async fn set_last_nonce_to(&mut self, key: &PublicKey, topoheight: u64, nonce: &VersionedNonce) -> Result<(), BlockchainError> {
+        self.set_nonce_at_topoheight(key, topoheight, nonce).await?;
+        self.set_last_topoheight_for_nonce(key, topoheight).await
+    }
+
+    async fn set_nonce_at_topoheight(&mut self, key: &PublicKey, topoheight: u64, version: &VersionedNonce) -> Result<(), BlockchainError> {
+        self.versioned_nonces.insert((topoheight, *key.as_bytes()), version.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PrunedTopoheightProvider for MemoryStorage {
+    async fn get_pruned_topoheight(&self) -> Result<Option<u64>, BlockchainError> {
+        Ok(self.pruned_topoheight)
+    }
+
+    async fn set_pruned_topoheight(&mut self, pruned_topoheight: u64) -> Result<(), BlockchainError> {
+        self.pruned_topoheight = Some(pruned_topoheight);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TransactionProvider for MemoryStorage {
+    async fn get_transaction(&self, hash: &Hash) -> Result<Arc<Transaction>, BlockchainError> {
+        self.transactions.get(hash).cloned().ok_or(BlockchainError::NotFoundOnDisk(DiskContext::GetTransaction))
+    }
+
+    async fn get_transaction_with_metadata(&self, hash: &Hash) -> Result<TransactionWithMetadata, BlockchainError> {
+        let tx = self.get_transaction(hash).await?;
+        let executed_in_block = self.txs_executed.get(hash).cloned();
+        // Executor may not be ordered anymore while a reorg is in progress
+        let executed_at_topoheight = match &executed_in_block {
+            Some(block) if self.is_block_topological_ordered(block).await => Some(self.get_topo_height_for_hash(block).await?),
+            _ => None
+        };
+
+        Ok(TransactionWithMetadata {
+            tx,
+            blocks: self.tx_blocks.get(hash).cloned().unwrap_or_default(),
+            executed_in_block,
+            executed_at_topoheight
+        })
+    }
+
+    async fn get_transaction_size(&self, hash: &Hash) -> Result<usize, BlockchainError> {
+        self.transactions.get(hash).map(|tx| tx.size()).ok_or(BlockchainError::NotFoundOnDisk(DiskContext::LoadData))
+    }
+
+    async fn count_transactions(&self) -> Result<u64, BlockchainError> {
+        Ok(self.transactions.len() as u64)
+    }
+
+    async fn has_transaction(&self, hash: &Hash) -> Result<bool, BlockchainError> {
+        Ok(self.transactions.contains_key(hash))
+    }
+
+    async fn delete_transaction(&mut self, hash: &Hash) -> Result<Arc<Transaction>, BlockchainError> {
+        if let Some(blocks) = self.tx_blocks.remove(hash) {
+            if !blocks.is_empty() {
+                let count = blocks.len();
+                self.tx_blocks.insert(hash.clone(), blocks);
+                return Err(BlockchainError::TransactionStillReferenced(hash.clone(), count))
+            }
+        }
+
+        self.transactions.remove(hash).ok_or(BlockchainError::NotFoundOnDisk(DiskContext::DeleteData))
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    fn is_mainnet(&self) -> bool {
+        self.network.is_mainnet()
+    }
+
+    // Nothing is cached, everything is already in memory
+    async fn clear_caches(&mut self) -> Result<(), BlockchainError> {
+        Ok(())
+    }
+
+    async fn delete_block_at_topoheight(&mut self, topoheight: u64) -> Result<(Hash, Arc<BlockHeader>, Vec<(Hash, Arc<Transaction>)>), BlockchainError> {
+        trace!("Delete block at topoheight {topoheight}");
+        let missing = || BlockchainError::NotFoundOnDisk(DiskContext::DeleteData);
+
+        let hash = self.hash_at_topo.remove(&topoheight).ok_or_else(missing)?;
+        self.topo_by_hash.remove(&hash).ok_or_else(missing)?;
+        let block = self.blocks.remove(&hash).ok_or_else(missing)?;
+        self.supply.remove(&topoheight).ok_or_else(missing)?;
+        self.rewards.remove(&topoheight).ok_or_else(missing)?;
+        self.fees.remove(&topoheight);
+        self.difficulty.remove(&hash).ok_or_else(missing)?;
+        self.cumulative_difficulty.remove(&hash).ok_or_else(missing)?;
+
+        let txs = self.delete_block_transactions(&hash, &block)?;
+
+        if self.has_blocks_at_height(block.get_height()).await? {
+            self.remove_block_hash_at_height(&hash, block.get_height()).await?;
+        }
+
+        Ok((hash, block, txs))
+    }
+
+    async fn delete_versioned_balances_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned balances at topoheight {}", topoheight);
+        let versions: Vec<(AccountKey, Hash, Option<u64>)> = self.versioned_balances.range((topoheight, [0u8; 32], Hash::zero())..=(topoheight, [u8::MAX; 32], Hash::max()))
+            .map(|((_, key, asset), version)| (*key, asset.clone(), version.get_previous_topoheight()))
+            .collect();
+
+        for (key, asset, previous_topoheight) in versions {
+            self.versioned_balances.remove(&(topoheight, key, asset.clone()));
+
+            let last_topoheight = self.balances.get(&(key, asset.clone())).copied().unwrap_or(0);
+            if last_topoheight >= topoheight {
+                match previous_topoheight {
+                    Some(previous_topoheight) => self.set_balance_pointer(key, &asset, previous_topoheight),
+                    // First version of the balance
+                    None => self.delete_balance_pointer(key, &asset)
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_versioned_nonces_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned nonces at topoheight {}", topoheight);
+        let versions: Vec<(AccountKey, Option<u64>)> = self.versioned_nonces.range((topoheight, [0u8; 32])..=(topoheight, [u8::MAX; 32]))
+            .map(|((_, key), version)| (*key, version.get_previous_topoheight()))
+            .collect();
+
+        for (key, previous_topoheight) in versions {
+            self.versioned_nonces.remove(&(topoheight, key));
+
+            // Because of chain reorg, it may have been already deleted
+            if self.nonces.get(&key).is_some_and(|last_topoheight| *last_topoheight >= topoheight) {
+                match previous_topoheight {
+                    Some(previous_topoheight) => self.nonces.insert(key, previous_topoheight),
+                    None => self.nonces.remove(&key)
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_versioned_balances_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        self.versioned_balances.retain(|(topo, _, _), _| *topo <= topoheight);
+        Ok(())
+    }
+
+    async fn delete_versioned_nonces_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        self.versioned_nonces.retain(|(topo, _), _| *topo <= topoheight);
+        Ok(())
+    }
+
+    async fn delete_registrations_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        self.registrations.retain(|_, registration| *registration <= topoheight);
+        Ok(())
+    }
+
+    async fn delete_registrations_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        self.registrations.retain(|_, registration| *registration >= topoheight);
+        Ok(())
+    }
+
+    async fn delete_versioned_balances_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        self.versioned_balances.retain(|(topo, _, _), _| *topo >= topoheight);
+        Ok(())
+    }
+
+    async fn delete_versioned_nonces_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        self.versioned_nonces.retain(|(topo, _), _| *topo >= topoheight);
+        Ok(())
+    }
+
+    // The first versioned balance that is under the topoheight is bumped to topoheight
+    async fn create_snapshot_balances_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        for ((key, asset), highest_topoheight) in self.balances.clone() {
+            let mut version = self.get_versioned_balance(&key, &asset, highest_topoheight)?.clone();
+            if highest_topoheight <= topoheight {
+                self.set_balance_pointer(key, &asset, topoheight);
+                version.set_previous_topoheight(None);
+                self.versioned_balances.insert((topoheight, key, asset), version);
+                continue;
+            }
+
+            // find the first version which is under topoheight
+            let mut current_topoheight = highest_topoheight;
+            while let Some(previous_topoheight) = version.get_previous_topoheight() {
+                if previous_topoheight <= topoheight {
+                    version.set_previous_topoheight(Some(topoheight));
+                    self.versioned_balances.insert((current_topoheight, key, asset.clone()), version);
+
+                    let mut previous_version = self.get_versioned_balance(&key, &asset, previous_topoheight)?.clone();
+                    previous_version.set_previous_topoheight(None);
+                    self.versioned_balances.insert((topoheight, key, asset.clone()), previous_version);
+                    break;
+                }
+
+                version = self.get_versioned_balance(&key, &asset, previous_topoheight)?.clone();
+                current_topoheight = previous_topoheight;
+            }
+        }
+
+        Ok(())
+    }
+
+    // same as above but for nonces
+    async fn create_snapshot_nonces_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        for (key, highest_topoheight) in self.nonces.clone() {
+            let mut version = self.get_versioned_nonce(&key, highest_topoheight)?.clone();
+            if highest_topoheight <= topoheight {
+                self.nonces.insert(key, topoheight);
+                version.set_previous_topoheight(None);
+                self.versioned_nonces.insert((topoheight, key), version);
+                continue;
+            }
+
+            let mut current_topoheight = highest_topoheight;
+            while let Some(previous_topoheight) = version.get_previous_topoheight() {
+                if previous_topoheight <= topoheight {
+                    version.set_previous_topoheight(Some(topoheight));
+                    self.versioned_nonces.insert((current_topoheight, key), version);
+
+                    let mut previous_version = self.get_versioned_nonce(&key, previous_topoheight)?.clone();
+                    previous_version.set_previous_topoheight(None);
+                    self.versioned_nonces.insert((topoheight, key), previous_version);
+                    break;
+                }
+
+                version = self.get_versioned_nonce(&key, previous_topoheight)?.clone();
+                current_topoheight = previous_topoheight;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn create_snapshot_registrations_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        for registration in self.registrations.values_mut() {
+            if *registration <= topoheight {
+                *registration = topoheight;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_network(&self) -> Result<Network, BlockchainError> {
+        Ok(self.network)
+    }
+
+    // The network is given at creation
+    fn has_network(&self) -> Result<bool, BlockchainError> {
+        Ok(true)
+    }
+
+    fn set_network(&mut self, network: &Network) -> Result<(), BlockchainError> {
+        self.network = *network;
+        Ok(())
+    }
+
+    async fn pop_blocks(&mut self, mut height: u64, mut topoheight: u64, count: u64, stable_topo_height: u64) -> Result<(u64, u64, Vec<(Hash, Arc<Transaction>)>), BlockchainError> {
+        trace!("pop blocks from height: {}, topoheight: {}, count: {}", height, topoheight, count);
+        if topoheight < count {
+            return Err(BlockchainError::NotEnoughBlocks);
+        }
+
+        let mut lowest_topo = topoheight - count;
+        let pruned_topoheight = self.pruned_topoheight.unwrap_or(0);
+        if pruned_topoheight != 0 {
+            let safety_pruned_topoheight = pruned_topoheight + PRUNE_SAFETY_LIMIT;
+            if lowest_topo <= safety_pruned_topoheight && stable_topo_height != 0 {
+                warn!("Pruned topoheight is {}, lowest topoheight is {}, rewind only until {}", pruned_topoheight, lowest_topo, safety_pruned_topoheight);
+                lowest_topo = safety_pruned_topoheight;
+            }
+        }
+
+        // Delete all orphaned blocks tips
+        let mut tips = Tips::new();
+        for tip in self.tips.iter() {
+            if self.is_block_topological_ordered(tip).await {
+                tips.insert(tip.clone());
+            }
+        }
+
+        let mut blocks = Vec::new();
+        let mut pruned_reset = false;
+        // stop rewinding if its genesis block or if we reached the lowest topo
+        while topoheight > lowest_topo && topoheight > stable_topo_height && height != 0 {
+            let (hash, block) = self.get_block_header_at_topoheight(topoheight).await?;
+            blocks.push(topoheight);
+
+            tips.remove(&hash);
+            tips.extend(block.get_tips().iter().cloned());
+
+            if topoheight <= pruned_topoheight {
+                warn!("Pruned topoheight is reached, this is not healthy, starting from 0");
+                topoheight = 0;
+                height = 0;
+
+                tips.clear();
+                tips.insert(self.get_hash_at_topo_height(0).await?);
+                pruned_reset = true;
+                break;
+            }
+
+            topoheight -= 1;
+            // height of old block become new height
+            if block.get_height() < height {
+                height = block.get_height();
+            }
+        }
+
+        let mut txs = Vec::new();
+        for block_topoheight in blocks.iter() {
+            let (_, _, block_txs) = self.delete_block_at_topoheight(*block_topoheight).await?;
+            txs.extend(block_txs);
+        }
+
+        if pruned_reset {
+            self.pruned_topoheight = None;
+        }
+
+        warn!("Blocks rewinded: {}, new topoheight: {}, new height: {}", blocks.len(), topoheight, height);
+
+        self.rewind_versions(topoheight, pruned_topoheight)?;
+        self.delete_versioned_balances_above_topoheight(topoheight).await?;
+        self.delete_versioned_nonces_above_topoheight(topoheight).await?;
+        self.delete_registrations_above_topoheight(topoheight).await?;
+        self.delete_burned_supply_above_topoheight(topoheight).await?;
+
+        self.store_tips(&tips)?;
+        self.set_top_topoheight(topoheight)?;
+        self.set_top_height(height)?;
+
+        Ok((height, topoheight, txs))
+    }
+
+    // A rewind is applied at once, it can't be interrupted
+    fn has_pending_rewind(&self) -> Result<bool, BlockchainError> {
+        Ok(false)
+    }
+
+    async fn recover_pending_rewind(&mut self) -> Result<Option<u64>, BlockchainError> {
+        Ok(None)
+    }
+
+    async fn get_top_block_hash(&self) -> Result<Hash, BlockchainError> {
+        self.get_hash_at_topo_height(self.get_top_topoheight()?).await
+    }
+
+    async fn get_top_block(&self) -> Result<Block, BlockchainError> {
+        let hash = self.get_top_block_hash().await?;
+        self.get_block_by_hash(&hash).await
+    }
+
+    async fn get_top_block_header(&self) -> Result<(Arc<BlockHeader>, Hash), BlockchainError> {
+        let hash = self.get_top_block_hash().await?;
+        Ok((self.get_block_header_by_hash(&hash).await?, hash))
+    }
+
+    async fn get_blocks_range(&self, start_topoheight: u64, end_topoheight: u64) -> Result<Vec<(u64, Hash, Arc<BlockHeader>)>, BlockchainError> {
+        if start_topoheight > end_topoheight {
+            return Ok(Vec::new())
+        }
+
+        let mut blocks = Vec::new();
+        for (topoheight, hash) in self.hash_at_topo.range(start_topoheight..=end_topoheight) {
+            let header = self.get_block_header_by_hash(hash).await?;
+            blocks.push((*topoheight, hash.clone(), header));
+        }
+
+        Ok(blocks)
+    }
+
+    fn get_top_topoheight(&self) -> Result<u64, BlockchainError> {
+        self.top_topoheight.ok_or(BlockchainError::NotFoundOnDisk(DiskContext::TopTopoHeight))
+    }
+
+    fn set_top_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        self.top_topoheight = Some(topoheight);
+        Ok(())
+    }
+
+    fn get_top_height(&self) -> Result<u64, BlockchainError> {
+        self.top_height.ok_or(BlockchainError::NotFoundOnDisk(DiskContext::TopHeight))
+    }
+
+    fn set_top_height(&mut self, height: u64) -> Result<(), BlockchainError> {
+        self.top_height = Some(height);
+        Ok(())
+    }
+
+    async fn get_tips(&self) -> Result<Tips, BlockchainError> {
+        Ok(self.tips.clone())
+    }
+
+    fn store_tips(&mut self, tips: &Tips) -> Result<(), BlockchainError> {
+        self.tips = tips.clone();
+        Ok(())
+    }
+
+    async fn get_size_on_disk(&self) -> Result<u64, BlockchainError> {
+        Ok(0)
+    }
+
+    async fn flush(&self) -> Result<(), BlockchainError> {
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<(), BlockchainError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        config::{ASSET_CREATION_BURN_AMOUNT, XELIS_ASSET},
+        crypto::KeyPair,
+        transaction::AssetCreationPayload
+    };
+    use crate::core::storage::test_utils::add_ordered_block;
+    use super::*;
+
+    // Add a new balance version on top of the last one
+    async fn add_balance(storage: &mut MemoryStorage, key: &PublicKey, asset: &Hash, topoheight: u64) {
+        let previous = storage.get_last_topoheight_for_balance(key, asset).await.unwrap();
+        let mut version = VersionedBalance::zero();
+        version.set_previous_topoheight(if previous > 0 { Some(previous) } else { None });
+        storage.set_last_balance_to(key, asset, topoheight, &version).await.unwrap();
+    }
+
+    async fn holders(storage: &MemoryStorage, asset: &Hash) -> Vec<(PublicKey, u64)> {
+        storage.get_asset_holders(asset, 0, usize::MAX).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_asset_holders_across_reorg() {
+        let mut storage = MemoryStorage::new(Network::Dev);
+        let asset = Hash::new([1u8; 32]);
+        let other = Hash::new([2u8; 32]);
+        let (alice, bob, charlie) = (KeyPair::new().get_public_key().compress(), KeyPair::new().get_public_key().compress(), KeyPair::new().get_public_key().compress());
+
+        for topoheight in 0..=5 {
+            add_ordered_block(&mut storage, topoheight, 0).await;
+        }
+        add_balance(&mut storage, &alice, &asset, 1).await;
+        add_balance(&mut storage, &bob, &asset, 2).await;
+        add_balance(&mut storage, &bob, &other, 3).await;
+        add_balance(&mut storage, &alice, &asset, 4).await;
+        add_balance(&mut storage, &charlie, &asset, 5).await;
+
+        assert_eq!(storage.count_asset_holders(&asset).await.unwrap(), 3);
+        assert_eq!(holders(&storage, &asset).await, vec![(charlie.clone(), 5), (alice.clone(), 4), (bob.clone(), 2)]);
+        assert_eq!(holders(&storage, &other).await, vec![(bob.clone(), 3)]);
+
+        // Rewind the two last blocks
+        let (_, topoheight, _) = storage.pop_blocks(5, 5, 2, 0).await.unwrap();
+        assert_eq!(topoheight, 3);
+        assert_eq!(storage.count_blocks().await.unwrap(), 4);
+        assert_eq!(holders(&storage, &asset).await, vec![(bob.clone(), 2), (alice.clone(), 1)]);
+        assert_eq!(storage.get_last_topoheight_for_balance(&alice, &asset).await.unwrap(), 1);
+
+        // Blocks of the new chain
+        add_ordered_block(&mut storage, 4, 1).await;
+        add_balance(&mut storage, &charlie, &asset, 4).await;
+        assert_eq!(holders(&storage, &asset).await, vec![(charlie.clone(), 4), (bob.clone(), 2), (alice.clone(), 1)]);
+
+        // Rewind until the first balance of bob for the other asset
+        storage.pop_blocks(4, 4, 2, 0).await.unwrap();
+        assert_eq!(holders(&storage, &asset).await, vec![(bob.clone(), 2), (alice.clone(), 1)]);
+        assert_eq!(storage.count_asset_holders(&other).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_asset_creation_rewind() {
+        let mut storage = MemoryStorage::new(Network::Dev);
+        for topoheight in 0..=4 {
+            add_ordered_block(&mut storage, topoheight, 0).await;
+        }
+
+        // Same as a block executing an asset creation TX at topoheight 3
+        let asset = AssetCreationPayload::get_asset(&Hash::new([1u8; 32]));
+        storage.add_asset(&XELIS_ASSET, AssetData::new(0, 8)).await.unwrap();
+        storage.add_asset(&asset, AssetData::new(3, 4)).await.unwrap();
+        storage.set_burned_supply_at_topoheight(&XELIS_ASSET, 3, ASSET_CREATION_BURN_AMOUNT).unwrap();
+
+        storage.pop_blocks(4, 4, 1, 0).await.unwrap();
+        assert!(storage.has_asset(&asset).await.unwrap());
+
+        // The asset and its burn are removed with the block executing it
+        storage.pop_blocks(3, 3, 1, 0).await.unwrap();
+        assert!(!storage.has_asset(&asset).await.unwrap());
+        assert_eq!(storage.count_assets().await.unwrap(), 1);
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&XELIS_ASSET, 3).await.unwrap(), None);
+        assert_eq!(storage.get_top_topoheight().unwrap(), 2);
+    }
+}
@@ -0,0 +1,299 @@
+use log::{debug, info};
+use xelis_common::{
+    account::{BalanceType, CiphertextCache, VersionedBalance},
+    block::BlockHeader,
+    serializer::{Reader, ReaderError, Serializer},
+    transaction::Transaction
+};
+use crate::core::error::BlockchainError;
+use super::SledStorage;
+
+// Current version of the storage schema
+// It must be incremented each time a migration is added
+pub const STORAGE_VERSION: u64 = 3;
+
+// Each how many entries we log the progress of a migration
+const PROGRESS_INTERVAL: usize = 100_000;
+
+// A migration upgrade the storage from `version - 1` to `version`
+pub struct Migration {
+    // Version of the schema once the migration is applied
+    pub version: u64,
+    // Short description of what the migration does
+    pub description: &'static str,
+    // Function applying the changes to the storage
+    pub apply: fn(&SledStorage) -> Result<(), BlockchainError>
+}
+
+// All migrations ordered by their version
+pub const MIGRATIONS: [Migration; 3] = [
+    Migration {
+        version: 1,
+        description: "re-encode versioned balances with a flags byte",
+        apply: reencode_versioned_balances
+    },
+    Migration {
+        version: 2,
+        description: "build the asset holders index from the balances",
+        apply: build_asset_holders_index
+    },
+    Migration {
+        version: 3,
+        description: "store the fees of the transactions executed in each block",
+        apply: build_block_fees
+    }
+];
+
+// Apply all migrations required to go from the version to the latest one
+pub fn apply_migrations(storage: &SledStorage, mut version: u64) -> Result<(), BlockchainError> {
+    if version > STORAGE_VERSION {
+        return Err(BlockchainError::UnsupportedStorageVersion(version, STORAGE_VERSION))
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        info!("Applying storage migration {} ({} -> {}): {}", migration.version, version, migration.version, migration.description);
+        (migration.apply)(storage)?;
+        version = migration.version;
+        // Save the version after each migration so we can resume from it if the next one fail
+        storage.set_storage_version(version)?;
+        info!("Storage migration {} applied", migration.version);
+    }
+
+    Ok(())
+}
+
+// Read a versioned balance using the encoding before version 1
+// Present fields were determined based on the remaining bytes
+pub fn read_legacy_versioned_balance(reader: &mut Reader) -> Result<VersionedBalance, ReaderError> {
+    let final_balance = CiphertextCache::read(reader)?;
+    let balance_type = BalanceType::read(reader)?;
+    let (previous_topoheight, output_balance) = if reader.size() == 0 {
+        (None, None)
+    } else {
+        // Compressed ciphertext is 32 * 2 bytes, + 8 for topoheight
+        let previous_topo = if reader.size() == 8 || (balance_type == BalanceType::Both && reader.size() == 72) {
+            Some(reader.read_u64()?)
+        } else {
+            None
+        };
+
+        if balance_type == BalanceType::Both {
+            (previous_topo, Some(CiphertextCache::read(reader)?))
+        } else {
+            (previous_topo, None)
+        }
+    };
+
+    let mut version = VersionedBalance::new(final_balance, previous_topoheight);
+    version.set_balance_type(balance_type);
+    version.set_output_balance(output_balance);
+    Ok(version)
+}
+
+// Version 1: versioned balances are now serialized with a flags byte
+// All the changes are applied in one batch so the tree is never partially migrated
+fn reencode_versioned_balances(storage: &SledStorage) -> Result<(), BlockchainError> {
+    let total = storage.versioned_balances.len();
+    let mut batch = sled::Batch::default();
+    for (i, el) in storage.versioned_balances.iter().enumerate() {
+        let (key, value) = el?;
+        let mut reader = Reader::new(&value);
+        let version = read_legacy_versioned_balance(&mut reader)?;
+        batch.insert(key, version.to_bytes());
+
+        if (i + 1) % PROGRESS_INTERVAL == 0 {
+            info!("Re-encoding versioned balances: {}/{}", i + 1, total);
+        }
+    }
+
+    debug!("Writing {} re-encoded versioned balances", total);
+    storage.versioned_balances.apply_batch(batch)?;
+    Ok(())
+}
+
+// Version 2: accounts having a balance are indexed per asset
+// The index is rebuilt from the pointers to the highest versioned balances
+fn build_asset_holders_index(storage: &SledStorage) -> Result<(), BlockchainError> {
+    let total = storage.balances.len();
+    storage.asset_holders.clear()?;
+    for (i, el) in storage.balances.iter().enumerate() {
+        let (key, value) = el?;
+        let topoheight = u64::from_bytes(&value)?;
+        storage.set_balance_pointer(&key, topoheight)?;
+
+        if (i + 1) % PROGRESS_INTERVAL == 0 {
+            info!("Indexing asset holders: {}/{}", i + 1, total);
+        }
+    }
+
+    Ok(())
+}
+
+// Version 3: the fees of the transactions executed in a block are stored beside its reward
+// Blocks or transactions deleted by the pruning are skipped
+fn build_block_fees(storage: &SledStorage) -> Result<(), BlockchainError> {
+    let total = storage.hash_at_topo.len();
+    let mut batch = sled::Batch::default();
+    for (i, el) in storage.hash_at_topo.iter().enumerate() {
+        let (topoheight, hash) = el?;
+        let Some(header) = storage.blocks.get(&hash)? else {
+            continue
+        };
+
+        let header = BlockHeader::from_bytes(&header)?;
+        let mut fees: u64 = 0;
+        for tx_hash in header.get_txs_hashes() {
+            // Only the transactions executed in this block
+            if storage.txs_executed.get(tx_hash.as_bytes())?.as_deref() != Some(&*hash) {
+                continue
+            }
+
+            if let Some(tx) = storage.transactions.get(tx_hash.as_bytes())? {
+                fees = fees.checked_add(Transaction::from_bytes(&tx)?.get_fee()).ok_or(BlockchainError::Overflow)?;
+            }
+        }
+        batch.insert(topoheight, fees.to_be_bytes().to_vec());
+
+        if (i + 1) % PROGRESS_INTERVAL == 0 {
+            info!("Storing block fees: {}/{}", i + 1, total);
+        }
+    }
+
+    debug!("Writing fees of {} blocks", total);
+    storage.fees.apply_batch(batch)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use xelis_common::{
+        config::COIN_VALUE,
+        crypto::{elgamal::Ciphertext, Hash, Hashable, KeyPair},
+        network::Network,
+        serializer::Writer,
+        testing::TempDir
+    };
+    use crate::core::{
+        state::build_transfer,
+        storage::{
+            test_utils::{open_storage, open_temp_storage, TestBlock},
+            BalanceProvider,
+            BlockDagProvider,
+            ClientProtocolProvider,
+            DagOrderProvider
+        }
+    };
+    use super::*;
+
+    // Write a versioned balance using the encoding before version 1
+    fn write_legacy_versioned_balance(version: &VersionedBalance) -> Vec<u8> {
+        let (final_balance, output_balance, balance_type, previous_topoheight) = version.clone().consume();
+        let mut writer = Writer::new();
+        final_balance.write(&mut writer);
+        balance_type.write(&mut writer);
+        if let Some(topo) = &previous_topoheight {
+            writer.write_u64(topo);
+        }
+        if let Some(output) = &output_balance {
+            output.write(&mut writer);
+        }
+
+        writer.bytes()
+    }
+
+    // Create a database as written by a binary without schema version
+    fn create_v0_database(dir: &str, balances: &[([u8; 72], VersionedBalance)]) {
+        let db = sled::open(format!("{}{}", dir, Network::Dev.to_string().to_lowercase())).unwrap();
+        let extra = db.open_tree("extra").unwrap();
+        extra.insert(b"NET", Network::Dev.to_bytes()).unwrap();
+
+        let tree = db.open_tree("versioned_balances").unwrap();
+        for (key, version) in balances {
+            tree.insert(key, write_legacy_versioned_balance(version)).unwrap();
+        }
+        db.flush().unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v0_database() {
+        let dir = TempDir::new("migrations-v0");
+        let mut input = VersionedBalance::zero();
+        input.set_previous_topoheight(Some(42));
+
+        let mut both = VersionedBalance::zero();
+        both.set_balance_type(BalanceType::Both);
+        both.set_output_balance(Some(CiphertextCache::Decompressed(Ciphertext::zero())));
+        both.set_previous_topoheight(Some(7));
+
+        let balances = [([1u8; 72], input), ([2u8; 72], both)];
+        create_v0_database(&dir.to_dir_path(), &balances);
+
+        let storage = open_storage(&dir);
+        assert_eq!(storage.get_storage_version().unwrap(), Some(STORAGE_VERSION));
+        for (key, expected) in balances.iter() {
+            let value = storage.versioned_balances.get(key).unwrap().unwrap();
+            let version = VersionedBalance::from_bytes(&value).unwrap();
+            assert_eq!(version, *expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_migrate_asset_holders_index() {
+        let dir = TempDir::new("migrations-holders");
+        let key = KeyPair::new().get_public_key().compress();
+        let asset = Hash::new([1u8; 32]);
+
+        // Balance pointer written before the index existed
+        let storage = open_storage(&dir);
+        storage.balances.insert(storage.get_balance_key_for(&key, &asset), &7u64.to_be_bytes()).unwrap();
+        storage.set_storage_version(1).unwrap();
+        drop(storage);
+
+        let storage = open_storage(&dir);
+        assert_eq!(storage.get_storage_version().unwrap(), Some(STORAGE_VERSION));
+        assert_eq!(storage.get_asset_holders(&asset, 0, 10).await.unwrap(), vec![(key, 7)]);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_block_fees() {
+        let dir = TempDir::new("migrations-fees");
+        let tx = Arc::new(build_transfer(&KeyPair::new(), &KeyPair::new(), 10 * COIN_VALUE, COIN_VALUE));
+        let fee = tx.get_fee();
+        assert!(fee > 0);
+
+        // Both blocks include the TX, only the first one executed it
+        let mut storage = open_storage(&dir);
+        let executor = TestBlock::new(1).txs(vec![tx.clone()]).save_at(&mut storage, 1).await;
+        TestBlock::new(2).txs(vec![tx.clone()]).save_at(&mut storage, 2).await;
+        storage.set_tx_executed_in_block(&tx.hash(), &executor, 0).unwrap();
+        // Block deleted by the pruning
+        storage.set_topo_height_for_block(&Hash::new([9u8; 32]), 3).await.unwrap();
+        storage.set_storage_version(2).unwrap();
+        drop(storage);
+
+        let storage = open_storage(&dir);
+        assert_eq!(storage.get_storage_version().unwrap(), Some(STORAGE_VERSION));
+        assert_eq!(storage.get_block_fees_at_topo_height(1).unwrap(), fee);
+        assert_eq!(storage.get_block_fees_at_topo_height(2).unwrap(), 0);
+        assert!(storage.get_block_fees_at_topo_height(3).is_err());
+    }
+
+    #[test]
+    fn test_new_database_has_latest_version() {
+        let (_dir, storage) = open_temp_storage("migrations-new");
+        assert_eq!(storage.get_storage_version().unwrap(), Some(STORAGE_VERSION));
+    }
+
+    #[test]
+    fn test_refuse_newer_database() {
+        let (dir, storage) = open_temp_storage("migrations-newer");
+        storage.set_storage_version(STORAGE_VERSION + 1).unwrap();
+        drop(storage);
+
+        assert!(matches!(
+            SledStorage::new(dir.to_dir_path(), None, Network::Dev),
+            Err(BlockchainError::UnsupportedStorageVersion(v, STORAGE_VERSION)) if v == STORAGE_VERSION + 1
+        ));
+    }
+}
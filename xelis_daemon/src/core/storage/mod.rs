@@ -1,6 +1,15 @@
+mod journal;
+mod migrations;
 mod providers;
 mod sled;
-
+// In-memory storage to run nodes without any disk usage
+#[cfg(any(test, feature = "testing"))]
+mod memory;
+#[cfg(test)]
+pub mod test_utils;
+
+#[cfg(any(test, feature = "testing"))]
+pub use self::memory::MemoryStorage;
 pub use self::{
     sled::SledStorage,
     providers::*,
@@ -20,7 +29,7 @@ use crate::core::error::BlockchainError;
 pub type Tips = HashSet<Hash>;
 
 #[async_trait]
-pub trait Storage: BlockExecutionOrderProvider + DagOrderProvider + PrunedTopoheightProvider + NonceProvider + AccountProvider + ClientProtocolProvider + BlockDagProvider + MerkleHashProvider + Sync + Send + 'static {
+pub trait Storage: BlockExecutionOrderProvider + DagOrderProvider + PrunedTopoheightProvider + NonceProvider + AccountProvider + ClientProtocolProvider + BlockDagProvider + MerkleHashProvider + BurnedSupplyProvider + MempoolProvider + Sync + Send + 'static {
     // Is the chain running on mainnet
     fn is_mainnet(&self) -> bool;
 
@@ -77,6 +86,12 @@ pub trait Storage: BlockExecutionOrderProvider + DagOrderProvider + PrunedTopohe
     // Count is the number of blocks (topoheight) to rewind
     async fn pop_blocks(&mut self, mut height: u64, mut topoheight: u64, count: u64, stable_height: u64) -> Result<(u64, u64, Vec<(Hash, Arc<Transaction>)>), BlockchainError>;
 
+    // Check if a rewind was interrupted before being completed
+    fn has_pending_rewind(&self) -> Result<bool, BlockchainError>;
+
+    // Complete the interrupted rewind if any, returns the new topoheight
+    async fn recover_pending_rewind(&mut self) -> Result<Option<u64>, BlockchainError>;
+
     // Get the top block hash of the chain
     async fn get_top_block_hash(&self) -> Result<Hash, BlockchainError>;
     
@@ -86,6 +101,10 @@ pub trait Storage: BlockExecutionOrderProvider + DagOrderProvider + PrunedTopohe
     // Get the top block header of the chain, based on top block hash
     async fn get_top_block_header(&self) -> Result<(Arc<BlockHeader>, Hash), BlockchainError>;
 
+    // Get all blocks ordered between start and end topoheights (inclusive) with a single scan
+    // Each entry is (topoheight, hash, header), sorted by topoheight
+    async fn get_blocks_range(&self, start_topoheight: u64, end_topoheight: u64) -> Result<Vec<(u64, Hash, Arc<BlockHeader>)>, BlockchainError>;
+
     // Get the top topoheight of the chain
     fn get_top_topoheight(&self) -> Result<u64, BlockchainError>;
 
@@ -107,6 +126,9 @@ pub trait Storage: BlockExecutionOrderProvider + DagOrderProvider + PrunedTopohe
     // Get the size of the chain on disk in bytes
     async fn get_size_on_disk(&self) -> Result<u64, BlockchainError>;
 
+    // Write all the pending changes on disk
+    async fn flush(&self) -> Result<(), BlockchainError>;
+
     // Stop the storage and wait for it to finish
     async fn stop(&mut self) -> Result<(), BlockchainError>;
 }
\ No newline at end of file
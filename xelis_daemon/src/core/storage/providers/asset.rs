@@ -1,4 +1,4 @@
-use std::sync::atomic::Ordering;
+use std::{ops::Bound, sync::atomic::Ordering};
 use async_trait::async_trait;
 use indexmap::IndexSet;
 use log::trace;
@@ -25,8 +25,9 @@ pub trait AssetProvider {
     async fn get_assets(&self) -> Result<Vec<Hash>, BlockchainError>;
 
     // Get a partial list of assets supporting pagination and filtering by topoheight
+    // If `after` is set, iteration starts right after this asset instead of the first one
     // TODO: replace with impl Iterator<Item = Result<Hash, BlockchainError>> when async trait methods are stable
-    async fn get_partial_assets(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<IndexSet<AssetWithData>, BlockchainError>;
+    async fn get_partial_assets(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64, after: Option<&Hash>) -> Result<IndexSet<AssetWithData>, BlockchainError>;
 
     // Get chunked assets
     // This is useful to not retrieve all assets at once
@@ -41,6 +42,9 @@ pub trait AssetProvider {
 
     // Add an asset to the storage
     async fn add_asset(&mut self, hash: &Hash, data: AssetData) -> Result<(), BlockchainError>;
+
+    // Delete an asset whose registration is orphaned by a reorg
+    async fn delete_asset(&mut self, hash: &Hash) -> Result<(), BlockchainError>;
 }
 
 #[async_trait]
@@ -65,10 +69,18 @@ impl AssetProvider for SledStorage {
         }).collect()
     }
 
-    async fn get_partial_assets(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<IndexSet<AssetWithData>, BlockchainError> {
+    async fn get_partial_assets(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64, after: Option<&Hash>) -> Result<IndexSet<AssetWithData>, BlockchainError> {
+        trace!("get partial assets, maximum: {}, skip: {}, minimum_topoheight: {}, maximum_topoheight: {}", maximum, skip, minimum_topoheight, maximum_topoheight);
+
+        // Assets are ordered by their hash, seek directly after the cursor
+        let iter = match after {
+            Some(asset) => self.assets.range((Bound::Excluded(asset.as_bytes()), Bound::Unbounded)),
+            None => self.assets.iter()
+        };
+
         let mut assets = IndexSet::new();
         let mut skip_count = 0;
-        for el in self.assets.iter() {
+        for el in iter {
             let (key, value) = el?;
             let data = AssetData::from_bytes(&value)?;
             // check that we have a registered asset before the maximum topoheight
@@ -100,7 +112,10 @@ impl AssetProvider for SledStorage {
 
     // Returns all assets that the key has
     async fn get_assets_for(&self, key: &PublicKey) -> Result<Vec<Hash>, BlockchainError> {
-        self.balances.scan_prefix(key.as_bytes()).keys().map(|res| {
+        // All the assets of the key are between the lowest and the highest asset hash
+        let start = self.get_balance_key_for(key, &Hash::zero());
+        let end = self.get_balance_key_for(key, &Hash::max());
+        self.balances.range(start..=end).keys().map(|res| {
             let key = res?;
             // Keys are stored like this: [public key (32 bytes)][asset hash (32 bytes)]
             // See Self::get_balance_key_for
@@ -127,4 +142,95 @@ impl AssetProvider for SledStorage {
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    async fn delete_asset(&mut self, asset: &Hash) -> Result<(), BlockchainError> {
+        trace!("delete asset {}", asset);
+        if self.assets.remove(asset.as_bytes())?.is_some() {
+            self.store_assets_count(self.count_assets().await?.saturating_sub(1))?;
+        }
+
+        if let Some(cache) = &self.assets_cache {
+            let mut cache = cache.lock().await;
+            cache.pop(asset);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        config::{ASSET_CREATION_BURN_AMOUNT, XELIS_ASSET},
+        crypto::KeyPair,
+        transaction::AssetCreationPayload
+    };
+    use crate::core::storage::{
+        test_utils::{add_ordered_block, open_temp_storage},
+        BurnedSupplyProvider,
+        Storage
+    };
+    use super::*;
+
+    #[tokio::test]
+    async fn test_assets_for_key_range() {
+        let (_dir, storage) = open_temp_storage("assets-for");
+        let key = KeyPair::new().get_public_key().compress();
+        let other = KeyPair::new().get_public_key().compress();
+
+        // Lowest and highest assets are the bounds of the range
+        let assets = [Hash::max(), Hash::new([7u8; HASH_SIZE]), Hash::zero()];
+        for asset in assets.iter() {
+            storage.balances.insert(storage.get_balance_key_for(&key, asset), Vec::<u8>::new()).unwrap();
+            storage.balances.insert(storage.get_balance_key_for(&other, asset), Vec::<u8>::new()).unwrap();
+        }
+
+        // Keys are sorted by asset hash
+        let found = storage.get_assets_for(&key).await.unwrap();
+        assert_eq!(found, vec![Hash::zero(), Hash::new([7u8; HASH_SIZE]), Hash::max()]);
+    }
+
+    #[tokio::test]
+    async fn test_asset_creation_rewind() {
+        let (_dir, mut storage) = open_temp_storage("asset-creation");
+        for topoheight in 0..=4 {
+            add_ordered_block(&mut storage, topoheight, 0).await;
+        }
+
+        // Same as a block executing an asset creation TX at topoheight 3
+        let asset = AssetCreationPayload::get_asset(&Hash::new([1u8; HASH_SIZE]));
+        storage.add_asset(&XELIS_ASSET, AssetData::new(0, 8)).await.unwrap();
+        storage.add_asset(&asset, AssetData::new(3, 4)).await.unwrap();
+        storage.set_burned_supply_at_topoheight(&XELIS_ASSET, 3, ASSET_CREATION_BURN_AMOUNT).unwrap();
+        assert_eq!(storage.count_assets().await.unwrap(), 2);
+        assert_eq!(storage.get_asset(&asset).await.unwrap().get_decimals(), 4);
+
+        // Block is still ordered, nothing changes
+        storage.pop_blocks(4, 4, 1, 0).await.unwrap();
+        assert!(storage.has_asset(&asset).await.unwrap());
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&XELIS_ASSET, 3).await.unwrap(), Some((3, ASSET_CREATION_BURN_AMOUNT)));
+
+        // The asset and its burn are removed with the block executing it
+        storage.pop_blocks(3, 3, 1, 0).await.unwrap();
+        assert!(!storage.has_asset(&asset).await.unwrap());
+        assert!(storage.has_asset(&XELIS_ASSET).await.unwrap());
+        assert_eq!(storage.count_assets().await.unwrap(), 1);
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&XELIS_ASSET, 3).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_delete_asset() {
+        let (_dir, mut storage) = open_temp_storage("delete-asset");
+        let asset = AssetCreationPayload::get_asset(&Hash::new([1u8; HASH_SIZE]));
+        storage.add_asset(&XELIS_ASSET, AssetData::new(0, 8)).await.unwrap();
+        storage.add_asset(&asset, AssetData::new(3, 4)).await.unwrap();
+
+        storage.delete_asset(&asset).await.unwrap();
+        assert!(!storage.has_asset(&asset).await.unwrap());
+        assert!(storage.has_asset(&XELIS_ASSET).await.unwrap());
+        assert_eq!(storage.count_assets().await.unwrap(), 1);
+
+        // Already deleted, the count is unchanged
+        storage.delete_asset(&asset).await.unwrap();
+        assert_eq!(storage.count_assets().await.unwrap(), 1);
+    }
+}
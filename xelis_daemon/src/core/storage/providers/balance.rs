@@ -62,6 +62,13 @@ pub trait BalanceProvider: AssetProvider {
     // Delete the last topoheight for asset and key
     // This will only remove the pointer, not the version itself
     fn delete_last_topoheight_for_balance(&mut self, key: &PublicKey, asset: &Hash) -> Result<(), BlockchainError>;
+
+    // Count how many accounts have a balance for this asset
+    async fn count_asset_holders(&self, asset: &Hash) -> Result<usize, BlockchainError>;
+
+    // Get the accounts having a balance for this asset with the topoheight of their last balance change
+    // Most recently active accounts are returned first
+    async fn get_asset_holders(&self, asset: &Hash, skip: usize, maximum: usize) -> Result<Vec<(PublicKey, u64)>, BlockchainError>;
 }
 
 impl SledStorage {
@@ -86,6 +93,35 @@ impl SledStorage {
         bytes
     }
 
+    // Key used by the asset holders index: asset, topoheight and key bytes
+    // Holders of an asset are ordered by the topoheight of their last balance change
+    fn get_asset_holder_key(balance_key: &[u8], topoheight: &[u8]) -> [u8; 72] {
+        let mut bytes = [0; 72];
+        bytes[0..32].copy_from_slice(&balance_key[32..64]);
+        bytes[32..40].copy_from_slice(topoheight);
+        bytes[40..72].copy_from_slice(&balance_key[0..32]);
+        bytes
+    }
+
+    // Update the pointer to the highest versioned balance
+    // The asset holders index is updated at the same time to stay in sync
+    pub fn set_balance_pointer(&self, balance_key: &[u8], topoheight: u64) -> Result<(), BlockchainError> {
+        let topoheight = topoheight.to_be_bytes();
+        if let Some(previous) = self.balances.insert(balance_key, &topoheight)? {
+            self.asset_holders.remove(Self::get_asset_holder_key(balance_key, &previous))?;
+        }
+        self.asset_holders.insert(Self::get_asset_holder_key(balance_key, &topoheight), &[])?;
+        Ok(())
+    }
+
+    // Delete the pointer to the highest versioned balance and its asset holders entry
+    pub fn delete_balance_pointer(&self, balance_key: &[u8]) -> Result<(), BlockchainError> {
+        if let Some(previous) = self.balances.remove(balance_key)? {
+            self.asset_holders.remove(Self::get_asset_holder_key(balance_key, &previous))?;
+        }
+        Ok(())
+    }
+
     async fn has_balance_internal(&self, key: &[u8; 64]) -> Result<bool, BlockchainError> {
         trace!("has balance internal");
         Ok(self.balances.contains_key(key)?)
@@ -120,8 +156,7 @@ impl BalanceProvider for SledStorage {
     fn set_last_topoheight_for_balance(&mut self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<(), BlockchainError> {
         trace!("set last topoheight to {} for balance {} for {}", topoheight, asset, key.as_address(self.is_mainnet()));
         let key = self.get_balance_key_for(key, asset);
-        self.balances.insert(&key, &topoheight.to_be_bytes())?;
-        Ok(())
+        self.set_balance_pointer(&key, topoheight)
     }
 
     // get the balance at a specific topoheight
@@ -158,8 +193,7 @@ impl BalanceProvider for SledStorage {
     fn delete_last_topoheight_for_balance(&mut self, key: &PublicKey, asset: &Hash) -> Result<(), BlockchainError> {
         trace!("delete last topoheight balance {} for {}", asset, key.as_address(self.is_mainnet()));
         let key = self.get_balance_key_for(key, asset);
-        self.balances.remove(&key)?;
-        Ok(())
+        self.delete_balance_pointer(&key)
     }
 
     // get the latest balance at maximum specified topoheight
@@ -294,6 +328,28 @@ impl BalanceProvider for SledStorage {
         Ok(balances)
     }
 
+    async fn count_asset_holders(&self, asset: &Hash) -> Result<usize, BlockchainError> {
+        trace!("count asset holders for {}", asset);
+        let mut count = 0;
+        for el in self.asset_holders.scan_prefix(asset.as_bytes()).keys() {
+            el?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    async fn get_asset_holders(&self, asset: &Hash, skip: usize, maximum: usize) -> Result<Vec<(PublicKey, u64)>, BlockchainError> {
+        trace!("get asset holders for {}, skip: {}, maximum: {}", asset, skip, maximum);
+        let mut holders = Vec::new();
+        for el in self.asset_holders.scan_prefix(asset.as_bytes()).keys().rev().skip(skip).take(maximum) {
+            let key = el?;
+            let topoheight = u64::from_be_bytes(key[32..40].try_into()?);
+            let account = PublicKey::from_bytes(&key[40..72])?;
+            holders.push((account, topoheight));
+        }
+        Ok(holders)
+    }
+
     // save the asset balance at specific topoheight
     async fn set_balance_at_topoheight(&mut self, asset: &Hash, topoheight: u64, key: &PublicKey, balance: &VersionedBalance) -> Result<(), BlockchainError> {
         trace!("set balance {} at topoheight {} for {}", asset, topoheight, key.as_address(self.is_mainnet()));
@@ -301,4 +357,66 @@ impl BalanceProvider for SledStorage {
         self.versioned_balances.insert(key, balance.to_bytes())?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use crate::core::storage::{
+        test_utils::{add_ordered_block, open_temp_storage},
+        Storage
+    };
+    use super::*;
+
+    // Add a new balance version on top of the last one
+    async fn add_balance(storage: &mut SledStorage, key: &PublicKey, asset: &Hash, topoheight: u64) {
+        let previous = storage.get_last_topoheight_for_balance(key, asset).await.unwrap();
+        let mut version = VersionedBalance::zero();
+        version.set_previous_topoheight(if previous > 0 { Some(previous) } else { None });
+        storage.set_last_balance_to(key, asset, topoheight, &version).await.unwrap();
+    }
+
+    async fn holders(storage: &SledStorage, asset: &Hash) -> Vec<(PublicKey, u64)> {
+        storage.get_asset_holders(asset, 0, usize::MAX).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_asset_holders_across_reorg() {
+        let (_dir, mut storage) = open_temp_storage("asset-holders");
+        let asset = Hash::new([1u8; 32]);
+        let other = Hash::new([2u8; 32]);
+        let (alice, bob, charlie) = (KeyPair::new().get_public_key().compress(), KeyPair::new().get_public_key().compress(), KeyPair::new().get_public_key().compress());
+
+        for topoheight in 0..=5 {
+            add_ordered_block(&mut storage, topoheight, 0).await;
+        }
+        add_balance(&mut storage, &alice, &asset, 1).await;
+        add_balance(&mut storage, &bob, &asset, 2).await;
+        add_balance(&mut storage, &bob, &other, 3).await;
+        add_balance(&mut storage, &alice, &asset, 4).await;
+        add_balance(&mut storage, &charlie, &asset, 5).await;
+
+        // Ordered by last activity, an account is present only once
+        assert_eq!(storage.count_asset_holders(&asset).await.unwrap(), 3);
+        assert_eq!(holders(&storage, &asset).await, vec![(charlie.clone(), 5), (alice.clone(), 4), (bob.clone(), 2)]);
+        assert_eq!(storage.get_asset_holders(&asset, 1, 1).await.unwrap(), vec![(alice.clone(), 4)]);
+        assert_eq!(holders(&storage, &other).await, vec![(bob.clone(), 3)]);
+
+        // Rewind the two last blocks
+        let (_, topoheight, _) = storage.pop_blocks(5, 5, 2, 0).await.unwrap();
+        assert_eq!(topoheight, 3);
+        assert_eq!(storage.count_asset_holders(&asset).await.unwrap(), 2);
+        assert_eq!(holders(&storage, &asset).await, vec![(bob.clone(), 2), (alice.clone(), 1)]);
+        assert_eq!(holders(&storage, &other).await, vec![(bob.clone(), 3)]);
+
+        // Blocks of the new chain
+        add_ordered_block(&mut storage, 4, 1).await;
+        add_balance(&mut storage, &charlie, &asset, 4).await;
+        assert_eq!(holders(&storage, &asset).await, vec![(charlie.clone(), 4), (bob.clone(), 2), (alice.clone(), 1)]);
+
+        // Rewind until the first balance of bob for the other asset
+        storage.pop_blocks(4, 4, 2, 0).await.unwrap();
+        assert_eq!(holders(&storage, &asset).await, vec![(bob.clone(), 2), (alice.clone(), 1)]);
+        assert_eq!(storage.count_asset_holders(&other).await.unwrap(), 0);
+    }
+}
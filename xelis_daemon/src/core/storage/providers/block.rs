@@ -12,7 +12,7 @@ use xelis_common::{
     varuint::VarUint
 };
 use crate::core::{error::BlockchainError, storage::{sled::BLOCKS_COUNT, SledStorage}};
-use super::{BlocksAtHeightProvider, DifficultyProvider, TransactionProvider};
+use super::{BlocksAtHeightProvider, ClientProtocolProvider, DifficultyProvider, TransactionProvider};
 
 #[async_trait]
 pub trait BlockProvider: TransactionProvider + DifficultyProvider + BlocksAtHeightProvider {
@@ -75,6 +75,11 @@ impl BlockProvider for SledStorage {
             self.store_transactions_count(self.count_transactions().await? + txs_count)?;
         }
 
+        // Each tx is stored only one time and referenced by all the blocks including it
+        for tx_hash in block.get_transactions() {
+            self.add_block_linked_to_tx_if_not_present(tx_hash, &hash)?;
+        }
+
         // Store block header and increase blocks count if it's a new block
         if self.blocks.insert(hash.as_bytes(), block.to_bytes())?.is_none() {
             self.store_blocks_count(self.count_blocks().await? + 1)?;
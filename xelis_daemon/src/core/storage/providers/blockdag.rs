@@ -19,12 +19,18 @@ pub trait BlockDagProvider: DagOrderProvider + BlockProvider {
     // Get the block reward from using topoheight
     fn get_block_reward_at_topo_height(&self, topoheight: u64) -> Result<u64, BlockchainError>;
 
+    // Get the total fees of the transactions executed in the block at topoheight
+    fn get_block_fees_at_topo_height(&self, topoheight: u64) -> Result<u64, BlockchainError>;
+
     // Get the supply from topoheight
     async fn get_supply_at_topo_height(&self, topoheight: u64) -> Result<u64, BlockchainError>;
 
     // Set the block reward for topoheight
     fn set_block_reward_at_topo_height(&mut self, topoheight: u64, reward: u64) -> Result<(), BlockchainError>;
 
+    // Set the total fees of the transactions executed in the block at topoheight
+    fn set_block_fees_at_topo_height(&mut self, topoheight: u64, fees: u64) -> Result<(), BlockchainError>;
+
     // Set the supply at topoheight
     fn set_supply_at_topo_height(&mut self, topoheight: u64, supply: u64) -> Result<(), BlockchainError>;
 }
@@ -43,6 +49,11 @@ impl BlockDagProvider for SledStorage {
         Ok(self.load_from_disk(&self.rewards, &topoheight.to_be_bytes(), DiskContext::BlockRewardAtTopoHeight)?)
     }
 
+    fn get_block_fees_at_topo_height(&self, topoheight: u64) -> Result<u64, BlockchainError> {
+        trace!("get block fees at topo height {}", topoheight);
+        self.load_from_disk(&self.fees, &topoheight.to_be_bytes(), DiskContext::BlockFeesAtTopoHeight)
+    }
+
     async fn get_supply_at_topo_height(&self, topoheight: u64) -> Result<u64, BlockchainError> {
         trace!("get supply at topo height {}", topoheight);
         self.load_from_disk(&self.supply, &topoheight.to_be_bytes(), DiskContext::SupplyAtTopoHeight)
@@ -54,6 +65,12 @@ impl BlockDagProvider for SledStorage {
         Ok(())
     }
 
+    fn set_block_fees_at_topo_height(&mut self, topoheight: u64, fees: u64) -> Result<(), BlockchainError> {
+        trace!("set block fees to {} at topo height {}", fees, topoheight);
+        self.fees.insert(topoheight.to_be_bytes(), &fees.to_be_bytes())?;
+        Ok(())
+    }
+
     fn set_supply_at_topo_height(&mut self, topoheight: u64, supply: u64) -> Result<(), BlockchainError> {
         trace!("set supply at topo height {}", topoheight);
         self.supply.insert(topoheight.to_be_bytes(), &supply.to_be_bytes())?;
@@ -39,8 +39,12 @@ pub trait ClientProtocolProvider {
     // Get all blocks in which the transaction is included
     fn get_blocks_for_tx(&self, hash: &Hash) -> Result<Tips, BlockchainError>;
 
-    // Set the block hash that executed the transaction
-    fn set_tx_executed_in_block(&mut self, tx: &Hash, block: &Hash) -> Result<(), BlockchainError>;
+    // Get the position of the transaction in the execution order of the block that executed it
+    // None if it was executed before the position was stored
+    fn get_tx_execution_index(&self, tx: &Hash) -> Result<Option<u16>, BlockchainError>;
+
+    // Set the block hash that executed the transaction and its position in the execution order of this block
+    fn set_tx_executed_in_block(&mut self, tx: &Hash, block: &Hash, index: u16) -> Result<(), BlockchainError>;
 
     // Unmark the transaction as executed
     fn remove_tx_executed(&mut self, tx: &Hash) -> Result<(), BlockchainError>;
@@ -50,6 +54,10 @@ pub trait ClientProtocolProvider {
 
     // Add a block in which the transaction is included
     fn add_block_for_tx(&mut self, tx: &Hash, block: &Hash) -> Result<(), BlockchainError>;
+
+    // Remove a block in which the transaction is included
+    // Returns the number of blocks still including it
+    fn remove_block_for_tx(&mut self, tx: &Hash, block: &Hash) -> Result<usize, BlockchainError>;
 }
 
 #[async_trait]
@@ -59,15 +67,22 @@ impl ClientProtocolProvider for SledStorage {
         self.load_from_disk(&self.txs_executed, tx.as_bytes(), DiskContext::BlockExecutorForTx)
     }
 
-    fn set_tx_executed_in_block(&mut self, tx: &Hash, block: &Hash) -> Result<(), BlockchainError> {
-        trace!("set tx {} executed in block {}", tx, block);
+    fn get_tx_execution_index(&self, tx: &Hash) -> Result<Option<u16>, BlockchainError> {
+        trace!("get tx {} execution index", tx);
+        self.load_optional_from_disk(&self.txs_execution_index, tx.as_bytes())
+    }
+
+    fn set_tx_executed_in_block(&mut self, tx: &Hash, block: &Hash, index: u16) -> Result<(), BlockchainError> {
+        trace!("set tx {} executed in block {} at index {}", tx, block, index);
         self.txs_executed.insert(tx.as_bytes(), block.as_bytes())?;
+        self.txs_execution_index.insert(tx.as_bytes(), index.to_bytes())?;
         Ok(())
     }
 
     fn remove_tx_executed(&mut self, tx: &Hash) -> Result<(), BlockchainError> {
         trace!("remove tx {} executed", tx);
         self.txs_executed.remove(tx.as_bytes())?;
+        self.txs_execution_index.remove(tx.as_bytes())?;
         Ok(())
     }
 
@@ -139,4 +154,23 @@ impl ClientProtocolProvider for SledStorage {
         self.tx_blocks.insert(tx.as_bytes(), blocks.to_bytes())?;
         Ok(())
     }
+
+    fn remove_block_for_tx(&mut self, tx: &Hash, block: &Hash) -> Result<usize, BlockchainError> {
+        trace!("remove block {} for tx {}", block, tx);
+        if !self.has_tx_blocks(tx)? {
+            return Ok(0)
+        }
+
+        let mut blocks = self.get_blocks_for_tx(tx)?;
+        if blocks.remove(block) {
+            // No empty set is kept, the tx is not referenced anymore
+            if blocks.is_empty() {
+                self.tx_blocks.remove(tx.as_bytes())?;
+            } else {
+                self.set_blocks_for_tx(tx, &blocks)?;
+            }
+        }
+
+        Ok(blocks.len())
+    }
 }
\ No newline at end of file
@@ -11,7 +11,6 @@ use xelis_common::{
     },
     immutable::Immutable,
     serializer::Serializer,
-    time::TimestampMillis,
     varuint::VarUint
 };
 use crate::core::{
@@ -26,7 +25,7 @@ pub trait DifficultyProvider {
     async fn get_height_for_block_hash(&self, hash: &Hash) -> Result<u64, BlockchainError>;
 
     // Get the timestamp from the block using its hash
-    async fn get_timestamp_for_block_hash(&self, hash: &Hash) -> Result<TimestampMillis, BlockchainError>;
+    async fn get_timestamp_for_block_hash(&self, hash: &Hash) -> Result<u64, BlockchainError>;
 
     // Get the difficulty for a block hash
     async fn get_difficulty_for_block_hash(&self, hash: &Hash) -> Result<Difficulty, BlockchainError>;
@@ -59,7 +58,7 @@ impl DifficultyProvider for SledStorage {
         Ok(block.get_height())
     }
 
-    async fn get_timestamp_for_block_hash(&self, hash: &Hash) -> Result<TimestampMillis, BlockchainError> {
+    async fn get_timestamp_for_block_hash(&self, hash: &Hash) -> Result<u64, BlockchainError> {
         trace!("get timestamp for hash {}", hash);
         let block = self.get_block_header_by_hash(hash).await?;
         Ok(block.get_timestamp())
@@ -117,4 +116,20 @@ impl DifficultyProvider for SledStorage {
         self.difficulty_covariance.insert(hash.as_bytes(), p.to_bytes())?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::storage::test_utils::open_temp_storage;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cumulative_difficulty_above_u64() {
+        let (_dir, mut storage) = open_temp_storage("cumulative-difficulty");
+
+        let hash = Hash::zero();
+        let cumulative_difficulty = VarUint::from_u64(u64::MAX) * 1_000u64;
+        storage.set_cumulative_difficulty_for_block_hash(&hash, cumulative_difficulty).await.unwrap();
+        assert_eq!(storage.get_cumulative_difficulty_for_block_hash(&hash).await.unwrap(), cumulative_difficulty);
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+use async_trait::async_trait;
+use log::trace;
+use xelis_common::{
+    crypto::Hash,
+    serializer::Serializer,
+    transaction::Transaction
+};
+use crate::core::{
+    error::BlockchainError,
+    storage::SledStorage
+};
+
+// Pending transactions are persisted to survive a restart of the daemon
+// They are not part of the chain state, so writes don't require a mutable storage:
+// the mempool is updated while the storage is only read locked
+#[async_trait]
+pub trait MempoolProvider {
+    // Persist a transaction added in mempool
+    fn save_mempool_tx(&self, hash: &Hash, tx: &Transaction) -> Result<(), BlockchainError>;
+
+    // Delete a persisted transaction once mined or evicted from mempool
+    fn delete_mempool_tx(&self, hash: &Hash) -> Result<(), BlockchainError>;
+
+    // Get all the persisted transactions
+    async fn get_mempool_txs(&self) -> Result<Vec<(Hash, Arc<Transaction>)>, BlockchainError>;
+
+    // Delete all the persisted transactions
+    fn clear_mempool_txs(&self) -> Result<(), BlockchainError>;
+}
+
+#[async_trait]
+impl MempoolProvider for SledStorage {
+    fn save_mempool_tx(&self, hash: &Hash, tx: &Transaction) -> Result<(), BlockchainError> {
+        trace!("save mempool tx {}", hash);
+        self.mempool.insert(hash.as_bytes(), tx.to_bytes())?;
+        Ok(())
+    }
+
+    fn delete_mempool_tx(&self, hash: &Hash) -> Result<(), BlockchainError> {
+        trace!("delete mempool tx {}", hash);
+        self.mempool.remove(hash.as_bytes())?;
+        Ok(())
+    }
+
+    async fn get_mempool_txs(&self) -> Result<Vec<(Hash, Arc<Transaction>)>, BlockchainError> {
+        trace!("get mempool txs");
+        let mut txs = Vec::with_capacity(self.mempool.len());
+        for el in self.mempool.iter() {
+            let (key, value) = el?;
+            let hash = Hash::from_bytes(&key)?;
+            let tx = Transaction::from_bytes(&value)?;
+            txs.push((hash, Arc::new(tx)));
+        }
+
+        Ok(txs)
+    }
+
+    fn clear_mempool_txs(&self) -> Result<(), BlockchainError> {
+        trace!("clear mempool txs");
+        self.mempool.clear()?;
+        Ok(())
+    }
+}
@@ -12,6 +12,8 @@ mod blockdag;
 mod merkle;
 mod account;
 mod block_execution_order;
+mod supply;
+mod mempool;
 
 pub use asset::AssetProvider;
 pub use blocks_at_height::BlocksAtHeightProvider;
@@ -21,9 +23,11 @@ pub use pruned_topoheight::PrunedTopoheightProvider;
 pub use nonce::NonceProvider;
 pub use balance::BalanceProvider;
 pub use client_protocol::ClientProtocolProvider;
-pub use transaction::TransactionProvider;
+pub use transaction::{TransactionProvider, TransactionWithMetadata};
 pub use block::BlockProvider;
 pub use blockdag::BlockDagProvider;
 pub use merkle::MerkleHashProvider;
 pub use account::AccountProvider;
-pub use block_execution_order::BlockExecutionOrderProvider;
\ No newline at end of file
+pub use block_execution_order::BlockExecutionOrderProvider;
+pub use supply::BurnedSupplyProvider;
+pub use mempool::MempoolProvider;
\ No newline at end of file
@@ -1,4 +1,4 @@
-use std::sync::atomic::Ordering;
+use std::{ops::Bound, sync::atomic::Ordering};
 
 use async_trait::async_trait;
 use indexmap::IndexSet;
@@ -28,7 +28,9 @@ pub trait NonceProvider: BalanceProvider {
 
     // Get registered accounts supporting pagination and filtering by topoheight
     // Returned keys must have a nonce or a balance updated in the range given
-    async fn get_partial_keys(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<IndexSet<PublicKey>, BlockchainError>;
+    // If `after` is set, iteration starts right after this key instead of the first one
+    // Using the last key of the previous page as cursor prevent walking again all previous pages
+    async fn get_partial_keys(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64, after: Option<&PublicKey>) -> Result<IndexSet<PublicKey>, BlockchainError>;
 
     // Get the last topoheigh that the account has a nonce
     async fn get_last_topoheight_for_nonce(&self, key: &PublicKey) -> Result<u64, BlockchainError>;
@@ -42,6 +44,10 @@ pub trait NonceProvider: BalanceProvider {
     // Get the nonce under or equal topoheight requested for an account
     async fn get_nonce_at_maximum_topoheight(&self, key: &PublicKey, topoheight: u64) -> Result<Option<(u64, VersionedNonce)>, BlockchainError>;
 
+    // Get the nonce versions of the account, newest first
+    // Versions below the minimum topoheight (pruned ones) are not visited
+    async fn get_nonce_history(&self, key: &PublicKey, maximum: usize, minimum_topoheight: u64) -> Result<Vec<(u64, VersionedNonce)>, BlockchainError>;
+
     // Check if the account has a nonce updated in the range given
     // It will also check balances if no nonce found
     async fn has_key_updated_in_range(&self, key: &PublicKey, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<bool, BlockchainError>;
@@ -120,12 +126,18 @@ impl NonceProvider for SledStorage {
     }
 
     // Get all keys that got a changes in their balances/nonces in the range given
-    async fn get_partial_keys(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<IndexSet<PublicKey>, BlockchainError> {
+    async fn get_partial_keys(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64, after: Option<&PublicKey>) -> Result<IndexSet<PublicKey>, BlockchainError> {
         trace!("get partial keys, maximum: {}, skip: {}, minimum_topoheight: {}, maximum_topoheight: {}", maximum, skip, minimum_topoheight, maximum_topoheight);
 
+        // Keys are ordered by their bytes, seek directly after the cursor
+        let iter = match after {
+            Some(key) => self.nonces.range((Bound::Excluded(key.as_bytes()), Bound::Unbounded)),
+            None => self.nonces.iter()
+        };
+
         let mut keys: IndexSet<PublicKey> = IndexSet::new();
         let mut skip_count = 0;
-        for el in self.nonces.iter().keys() {
+        for el in iter.keys() {
             let key = el?;
             let pkey = PublicKey::from_bytes(&key)?;
 
@@ -200,6 +212,43 @@ impl NonceProvider for SledStorage {
         Ok(None)
     }
 
+    async fn get_nonce_history(&self, key: &PublicKey, maximum: usize, minimum_topoheight: u64) -> Result<Vec<(u64, VersionedNonce)>, BlockchainError> {
+        trace!("get nonce history for {} (maximum = {}, minimum topoheight = {})", key.as_address(self.is_mainnet()), maximum, minimum_topoheight);
+        let mut history = Vec::new();
+        if maximum == 0 || !self.has_nonce(key).await? {
+            return Ok(history)
+        }
+
+        let (mut topoheight, mut version) = self.get_last_nonce(key).await?;
+        loop {
+            if topoheight < minimum_topoheight {
+                break;
+            }
+
+            let previous = version.get_previous_topoheight();
+            history.push((topoheight, version));
+            if history.len() >= maximum {
+                break;
+            }
+
+            match previous {
+                // Older versions may have been deleted by the pruning
+                Some(previous) if previous < minimum_topoheight => break,
+                Some(previous) if previous < topoheight => {
+                    version = self.get_nonce_at_exact_topoheight(key, previous).await?;
+                    topoheight = previous;
+                },
+                Some(previous) => {
+                    error!("FATAL ERROR: Previous topoheight ({}) should not be higher than current version ({})!", previous, topoheight);
+                    return Err(BlockchainError::Unknown)
+                },
+                None => break
+            }
+        }
+
+        Ok(history)
+    }
+
     async fn has_key_updated_in_range(&self, key: &PublicKey, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<bool, BlockchainError> {
         trace!("has key {} updated in range min topoheight {} and max topoheight {}", key.as_address(self.is_mainnet()), minimum_topoheight, maximum_topoheight);
         // check first that this address has nonce, if no returns None
@@ -292,4 +341,85 @@ impl NonceProvider for SledStorage {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use xelis_common::crypto::hash;
+    use crate::core::storage::test_utils::open_temp_storage;
+    use super::*;
+
+    const KEYS_COUNT: u64 = 100_000;
+
+    #[tokio::test]
+    async fn test_nonce_history() {
+        let (_dir, mut storage) = open_temp_storage("nonce-history");
+        let key = PublicKey::from_bytes(hash(b"alice").as_bytes()).unwrap();
+        assert!(storage.get_nonce_history(&key, 100, 0).await.unwrap().is_empty());
+
+        // 5 txs sent across 3 blocks: 2 at topoheight 3, 1 at topoheight 5 and 2 at topoheight 8
+        // One version is written per block, with the nonce after its last tx
+        storage.set_last_nonce_to(&key, 3, &VersionedNonce::new(2, None)).await.unwrap();
+        storage.set_last_nonce_to(&key, 5, &VersionedNonce::new(3, Some(3))).await.unwrap();
+        storage.set_last_nonce_to(&key, 8, &VersionedNonce::new(5, Some(5))).await.unwrap();
+
+        let history: Vec<(u64, u64)> = storage.get_nonce_history(&key, 100, 0).await.unwrap()
+            .into_iter()
+            .map(|(topoheight, version)| (topoheight, version.get_nonce()))
+            .collect();
+        assert_eq!(history, vec![(8, 5), (5, 3), (3, 2)]);
+
+        // Capped to the maximum requested
+        let history = storage.get_nonce_history(&key, 2, 0).await.unwrap();
+        assert_eq!(history.iter().map(|(topoheight, _)| *topoheight).collect::<Vec<_>>(), vec![8, 5]);
+        assert!(storage.get_nonce_history(&key, 0, 0).await.unwrap().is_empty());
+
+        // Pruned versions are not visited
+        let history = storage.get_nonce_history(&key, 100, 4).await.unwrap();
+        assert_eq!(history.iter().map(|(topoheight, _)| *topoheight).collect::<Vec<_>>(), vec![8, 5]);
+    }
+    const PAGE_SIZE: usize = 1024;
+
+    #[tokio::test]
+    async fn test_partial_keys_cursor() {
+        let (_dir, mut storage) = open_temp_storage("partial-keys");
+
+        for i in 0..KEYS_COUNT {
+            let key = PublicKey::from_bytes(hash(&i.to_be_bytes()).as_bytes()).unwrap();
+            storage.set_last_nonce_to(&key, i % 1000, &VersionedNonce::new(0, None)).await.unwrap();
+        }
+
+        let start = Instant::now();
+        let mut page = storage.get_partial_keys(PAGE_SIZE, 0, 0, u64::MAX, None).await.unwrap();
+        let first_page_time = start.elapsed();
+
+        // Walk all pages using the last key as cursor
+        let mut all = page.clone();
+        let mut last_page_time = first_page_time;
+        let mut pages = 1;
+        while page.len() == PAGE_SIZE {
+            let start = Instant::now();
+            page = storage.get_partial_keys(PAGE_SIZE, 0, 0, u64::MAX, page.last()).await.unwrap();
+            last_page_time = start.elapsed();
+            all.extend(page.iter().cloned());
+
+            // Same result as the skip based pagination
+            if pages == 50 {
+                let skipped = storage.get_partial_keys(PAGE_SIZE, pages * PAGE_SIZE, 0, u64::MAX, None).await.unwrap();
+                assert_eq!(skipped, page);
+            }
+            pages += 1;
+        }
+        assert_eq!(all.len(), KEYS_COUNT as usize);
+
+        // Last page is served in roughly the same time as the first one
+        assert!(last_page_time <= first_page_time * 5 + Duration::from_millis(20), "first page: {:?}, last page: {:?}", first_page_time, last_page_time);
+
+        // Filtered range is paginated the same way
+        let first = storage.get_partial_keys(PAGE_SIZE, 0, 100, 199, None).await.unwrap();
+        let next = storage.get_partial_keys(PAGE_SIZE, 0, 100, 199, first.last()).await.unwrap();
+        let skipped = storage.get_partial_keys(PAGE_SIZE, PAGE_SIZE, 100, 199, None).await.unwrap();
+        assert_eq!(next, skipped);
+    }
+}
@@ -0,0 +1,122 @@
+use async_trait::async_trait;
+use log::trace;
+use xelis_common::{
+    crypto::{Hash, HASH_SIZE},
+    serializer::Serializer,
+};
+use crate::core::{
+    error::BlockchainError,
+    storage::SledStorage,
+};
+
+// Burned supply is tracked per asset and versioned by topoheight
+// Each version is the cumulative burned amount of the asset at its topoheight
+// Only the topoheights where a burn happened are stored
+#[async_trait]
+pub trait BurnedSupplyProvider {
+    // Get the total burned amount of an asset at or below the requested topoheight
+    // Returns the topoheight of the version found with its burned amount
+    async fn get_burned_supply_at_maximum_topoheight(&self, asset: &Hash, topoheight: u64) -> Result<Option<(u64, u64)>, BlockchainError>;
+
+    // Set the total burned amount of an asset at a specific topoheight
+    fn set_burned_supply_at_topoheight(&mut self, asset: &Hash, topoheight: u64, burned: u64) -> Result<(), BlockchainError>;
+
+    // Delete all the burned supply versions at the exact topoheight
+    async fn delete_burned_supply_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
+
+    // Delete all the burned supply versions above the topoheight
+    async fn delete_burned_supply_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
+}
+
+impl SledStorage {
+    // Versioned key is a 40 bytes key with the asset as first bytes and the topoheight as last bytes
+    // This allow to iterate over all versions of an asset in topoheight order
+    fn get_burned_supply_key(&self, asset: &Hash, topoheight: u64) -> [u8; 40] {
+        let mut bytes = [0; 40];
+        bytes[0..HASH_SIZE].copy_from_slice(asset.as_bytes());
+        bytes[HASH_SIZE..40].copy_from_slice(&topoheight.to_be_bytes());
+
+        bytes
+    }
+}
+
+#[async_trait]
+impl BurnedSupplyProvider for SledStorage {
+    async fn get_burned_supply_at_maximum_topoheight(&self, asset: &Hash, topoheight: u64) -> Result<Option<(u64, u64)>, BlockchainError> {
+        trace!("get burned supply for {} at maximum topoheight {}", asset, topoheight);
+        let start = self.get_burned_supply_key(asset, 0);
+        let end = self.get_burned_supply_key(asset, topoheight);
+        match self.burned_supply.range(start..=end).next_back() {
+            Some(el) => {
+                let (key, value) = el?;
+                let topo = u64::from_bytes(&key[HASH_SIZE..40])?;
+                let burned = u64::from_bytes(&value)?;
+                Ok(Some((topo, burned)))
+            },
+            None => Ok(None)
+        }
+    }
+
+    fn set_burned_supply_at_topoheight(&mut self, asset: &Hash, topoheight: u64, burned: u64) -> Result<(), BlockchainError> {
+        trace!("set burned supply for {} to {} at topoheight {}", asset, burned, topoheight);
+        let key = self.get_burned_supply_key(asset, topoheight);
+        self.burned_supply.insert(key, &burned.to_be_bytes())?;
+        Ok(())
+    }
+
+    async fn delete_burned_supply_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete burned supply at topoheight {}", topoheight);
+        for el in self.assets.iter().keys() {
+            let asset = Hash::from_bytes(&el?)?;
+            let key = self.get_burned_supply_key(&asset, topoheight);
+            self.burned_supply.remove(key)?;
+        }
+        Ok(())
+    }
+
+    async fn delete_burned_supply_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete burned supply above topoheight {}", topoheight);
+        for el in self.burned_supply.iter().keys() {
+            let key = el?;
+            let topo = u64::from_bytes(&key[HASH_SIZE..40])?;
+            if topo > topoheight {
+                self.burned_supply.remove(&key)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::storage::test_utils::open_temp_storage;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_burned_supply_versions() {
+        let (_dir, mut storage) = open_temp_storage("burned-supply");
+        let asset = Hash::new([1u8; HASH_SIZE]);
+        let other = Hash::new([2u8; HASH_SIZE]);
+
+        // Burns at several topoheights
+        storage.set_burned_supply_at_topoheight(&asset, 2, 100).unwrap();
+        storage.set_burned_supply_at_topoheight(&asset, 5, 150).unwrap();
+        storage.set_burned_supply_at_topoheight(&asset, 8, 400).unwrap();
+        storage.set_burned_supply_at_topoheight(&other, 6, 10).unwrap();
+
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&asset, 1).await.unwrap(), None);
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&asset, 2).await.unwrap(), Some((2, 100)));
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&asset, 7).await.unwrap(), Some((5, 150)));
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&asset, 10).await.unwrap(), Some((8, 400)));
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&other, 10).await.unwrap(), Some((6, 10)));
+
+        // Pop the last block
+        storage.delete_burned_supply_above_topoheight(7).await.unwrap();
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&asset, 10).await.unwrap(), Some((5, 150)));
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&other, 10).await.unwrap(), Some((6, 10)));
+
+        storage.delete_burned_supply_above_topoheight(4).await.unwrap();
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&asset, 10).await.unwrap(), Some((2, 100)));
+        assert_eq!(storage.get_burned_supply_at_maximum_topoheight(&other, 10).await.unwrap(), None);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashSet, sync::{atomic::Ordering, Arc}};
+use std::sync::{atomic::Ordering, Arc};
 use async_trait::async_trait;
 use log::trace;
 use xelis_common::{
@@ -12,15 +12,33 @@ use crate::core::{
     },
     storage::{
         sled::TXS_COUNT,
-        SledStorage
+        ClientProtocolProvider,
+        DagOrderProvider,
+        SledStorage,
+        Tips
     }
 };
 
+// Transaction with the context of its execution in the chain
+#[derive(Debug, Clone)]
+pub struct TransactionWithMetadata {
+    pub tx: Arc<Transaction>,
+    // Blocks including it, empty if none
+    pub blocks: Tips,
+    // Block that executed it, None if not executed yet
+    pub executed_in_block: Option<Hash>,
+    // Topoheight of the block that executed it
+    pub executed_at_topoheight: Option<u64>
+}
+
 #[async_trait]
 pub trait TransactionProvider {
     // Get the transaction using its hash
     async fn get_transaction(&self, hash: &Hash) -> Result<Arc<Transaction>, BlockchainError>;
 
+    // Get the transaction with the blocks including it and the block that executed it
+    async fn get_transaction_with_metadata(&self, hash: &Hash) -> Result<TransactionWithMetadata, BlockchainError>;
+
     // Get the transaction size using its hash
     async fn get_transaction_size(&self, hash: &Hash) -> Result<usize, BlockchainError>;
 
@@ -31,6 +49,7 @@ pub trait TransactionProvider {
     async fn has_transaction(&self, hash: &Hash) -> Result<bool, BlockchainError>;
 
     // Delete a transaction from the storage using its hash
+    // It is refused while a block still includes it
     async fn delete_transaction(&mut self, hash: &Hash) -> Result<Arc<Transaction>, BlockchainError>;
 }
 
@@ -50,6 +69,26 @@ impl TransactionProvider for SledStorage {
         self.get_cacheable_arc_data(&self.transactions, &self.transactions_cache, hash, DiskContext::GetTransaction).await
     }
 
+    async fn get_transaction_with_metadata(&self, hash: &Hash) -> Result<TransactionWithMetadata, BlockchainError> {
+        trace!("get transaction with metadata for hash {}", hash);
+        let tx = self.get_transaction(hash).await?;
+        let blocks: Tips = self.load_optional_from_disk(&self.tx_blocks, hash.as_bytes())?.unwrap_or_default();
+        let executed_in_block: Option<Hash> = self.load_optional_from_disk(&self.txs_executed, hash.as_bytes())?;
+
+        // Executor may not be ordered anymore while a reorg is in progress
+        let executed_at_topoheight = match &executed_in_block {
+            Some(block) if self.is_block_topological_ordered(block).await => Some(self.get_topo_height_for_hash(block).await?),
+            _ => None
+        };
+
+        Ok(TransactionWithMetadata {
+            tx,
+            blocks,
+            executed_in_block,
+            executed_at_topoheight
+        })
+    }
+
     async fn get_transaction_size(&self, hash: &Hash) -> Result<usize, BlockchainError> {
         trace!("get transaction size for hash {}", hash);
         let data = self.transactions.get(hash.as_bytes())?;
@@ -67,7 +106,17 @@ impl TransactionProvider for SledStorage {
     }
 
     async fn delete_transaction(&mut self, hash: &Hash) -> Result<Arc<Transaction>, BlockchainError> {
-        self.delete_cacheable_data::<Hash, HashSet<Hash>>(&self.tx_blocks, &None, hash).await?;
-        self.delete_data(&self.transactions, &self.transactions_cache, hash).await
+        trace!("delete transaction {}", hash);
+        if self.has_tx_blocks(hash)? {
+            let blocks = self.get_blocks_for_tx(hash)?;
+            if !blocks.is_empty() {
+                return Err(BlockchainError::TransactionStillReferenced(hash.clone(), blocks.len()))
+            }
+            self.tx_blocks.remove(hash.as_bytes())?;
+        }
+
+        let tx = self.delete_data(&self.transactions, &self.transactions_cache, hash).await?;
+        self.store_transactions_count(self.count_transactions().await?.saturating_sub(1))?;
+        Ok(tx)
     }
 }
\ No newline at end of file
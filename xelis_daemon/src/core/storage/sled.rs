@@ -27,6 +27,9 @@ use sled::Tree;
 use log::{debug, trace, warn, info};
 
 use super::{
+    journal::RewindJournal,
+    migrations::{self, STORAGE_VERSION},
+    AssetProvider,
     BalanceProvider,
     BlocksAtHeightProvider,
     DagOrderProvider,
@@ -45,6 +48,10 @@ const TIPS: &[u8; 4] = b"TIPS";
 const TOP_TOPO_HEIGHT: &[u8; 4] = b"TOPO";
 const TOP_HEIGHT: &[u8; 4] = b"TOPH";
 const NETWORK: &[u8] = b"NET";
+// Version of the storage schema
+const STORAGE_VERSION_KEY: &[u8; 4] = b"VERS";
+// Key of the rewind journal in its tree
+const REWIND_JOURNAL: &[u8; 4] = b"RWND";
 pub(super) const PRUNED_TOPOHEIGHT: &[u8; 4] = b"PRUN";
 // Counters (prevent to perform a O(n))
 pub(super) const ACCOUNTS_COUNT: &[u8; 4] = b"CACC";
@@ -61,6 +68,8 @@ pub struct SledStorage {
     pub(super) transactions: Tree,
     // all txs executed in block
     pub(super) txs_executed: Tree,
+    // position of each executed tx in the execution order of its block
+    pub(super) txs_execution_index: Tree,
     // all blocks execution order
     pub(super) blocks_execution_order: Tree,
     // all blocks on disk
@@ -85,6 +94,8 @@ pub struct SledStorage {
     pub(super) rewards: Tree,
     // supply for each block topoheight
     pub(super) supply: Tree,
+    // total fees of the transactions executed in each block topoheight
+    pub(super) fees: Tree,
     // difficulty for each block hash
     pub(super) difficulty: Tree,
     // tree to store all blocks hashes where a tx was included in 
@@ -95,12 +106,20 @@ pub struct SledStorage {
     pub(super) balances: Tree,
     // Tree that store all versioned balances using hashed keys
     pub(super) versioned_balances: Tree,
+    // Accounts having a balance for each asset, ordered by their last balance topoheight
+    pub(super) asset_holders: Tree,
     // Tree that store all merkle hashes for each topoheight
     pub(super) merkle_hashes: Tree,
     // Account registrations topoheight
     pub(super) registrations: Tree,
     // Account registrations prefixed by their topoheight for easier deletion
     pub(super) registrations_prefixed: Tree,
+    // Total burned supply per asset, versioned by topoheight
+    pub(super) burned_supply: Tree,
+    // Tree that store the pending transactions of the mempool
+    pub(super) mempool: Tree,
+    // Tree that store the journal of the rewind in progress
+    rewind_journal: Tree,
     // opened DB used for assets to create dynamic assets
     db: sled::Db,
 
@@ -138,7 +157,10 @@ pub struct SledStorage {
     // Count of blocks
     pub(super) blocks_count: AtomicU64,
     // Count of blocks added in chain
-    pub(super) blocks_execution_count: AtomicU64
+    pub(super) blocks_execution_count: AtomicU64,
+    // Number of blocks deleted before simulating a crash during a rewind
+    #[cfg(test)]
+    pub(super) rewind_failpoint: Option<usize>
 }
 
 macro_rules! init_cache {
@@ -158,6 +180,7 @@ impl SledStorage {
             network,
             transactions: sled.open_tree("transactions")?,
             txs_executed: sled.open_tree("txs_executed")?,
+            txs_execution_index: sled.open_tree("txs_execution_index")?,
             blocks_execution_order: sled.open_tree("blocks_execution_order")?,
             blocks: sled.open_tree("blocks")?,
             blocks_at_height: sled.open_tree("blocks_at_height")?,
@@ -170,14 +193,19 @@ impl SledStorage {
             nonces: sled.open_tree("nonces")?,
             rewards: sled.open_tree("rewards")?,
             supply: sled.open_tree("supply")?,
+            fees: sled.open_tree("fees")?,
             difficulty: sled.open_tree("difficulty")?,
             tx_blocks: sled.open_tree("tx_blocks")?,
             versioned_nonces: sled.open_tree("versioned_nonces")?,
             balances: sled.open_tree("balances")?,
             versioned_balances: sled.open_tree("versioned_balances")?,
+            asset_holders: sled.open_tree("asset_holders")?,
             merkle_hashes: sled.open_tree("merkle_hashes")?,
             registrations: sled.open_tree("registrations")?,
             registrations_prefixed: sled.open_tree("registrations_prefixed")?,
+            burned_supply: sled.open_tree("burned_supply")?,
+            mempool: sled.open_tree("mempool")?,
+            rewind_journal: sled.open_tree("rewind_journal")?,
             db: sled,
             transactions_cache: init_cache!(cache_size),
             blocks_cache: init_cache!(cache_size),
@@ -194,7 +222,9 @@ impl SledStorage {
             accounts_count: AtomicU64::new(0),
             transactions_count: AtomicU64::new(0),
             blocks_count: AtomicU64::new(0),
-            blocks_execution_count: AtomicU64::new(0)
+            blocks_execution_count: AtomicU64::new(0),
+            #[cfg(test)]
+            rewind_failpoint: None
         };
 
         // Verify that we are opening a DB on same network
@@ -204,8 +234,14 @@ impl SledStorage {
             if storage_network != network {
                 return Err(BlockchainError::InvalidNetwork);
             }
+
+            // Upgrade the storage schema if needed
+            // A DB without any version was created before schema versioning
+            let version = storage.get_storage_version()?.unwrap_or(0);
+            migrations::apply_migrations(&storage, version)?;
         } else {
             storage.set_network(&network)?;
+            storage.set_storage_version(STORAGE_VERSION)?;
         }
 
         // Load tips from disk if available
@@ -257,6 +293,19 @@ impl SledStorage {
         self.network.is_mainnet()
     }
 
+    // Get the version of the storage schema, None if it was created before versioning
+    pub(super) fn get_storage_version(&self) -> Result<Option<u64>, BlockchainError> {
+        trace!("get storage version");
+        self.load_optional_from_disk(&self.extra, STORAGE_VERSION_KEY)
+    }
+
+    // Set the version of the storage schema
+    pub(super) fn set_storage_version(&self, version: u64) -> Result<(), BlockchainError> {
+        trace!("set storage version to {}", version);
+        self.extra.insert(STORAGE_VERSION_KEY, &version.to_be_bytes())?;
+        Ok(())
+    }
+
     pub(super) fn load_optional_from_disk<T: Serializer>(&self, tree: &Tree, key: &[u8]) -> Result<Option<T>, BlockchainError> {
         match tree.get(key)? {
             Some(bytes) => {
@@ -390,6 +439,310 @@ impl SledStorage {
         }
         Ok(())
     }
+
+    // Delete the transactions of a block being deleted and returns those no longer included in any block
+    async fn delete_block_transactions(&mut self, hash: &Hash, block: &BlockHeader) -> Result<Vec<(Hash, Arc<Transaction>)>, BlockchainError> {
+        let mut txs = Vec::new();
+        for tx_hash in block.get_transactions() {
+            // Client protocol allows the same tx in several blocks
+            // it is stored only one time and shared by all of them
+            let blocks_left = self.remove_block_for_tx(tx_hash, hash)?;
+
+            if self.is_tx_executed_in_block(tx_hash, hash)? {
+                trace!("Tx {} was executed in block {}, deleting", tx_hash, hash);
+                self.remove_tx_executed(&tx_hash)?;
+            }
+
+            if blocks_left > 0 {
+                trace!("Tx {} is still included in {} blocks, keeping it", tx_hash, blocks_left);
+                continue;
+            }
+
+            // We have to check first as it may have been deleted by a previous version
+            if self.contains_data(&self.transactions, &self.transactions_cache, tx_hash).await? {
+                trace!("Deleting TX {} in block {}", tx_hash, hash);
+                let tx: Arc<Transaction> = self.delete_data(&self.transactions, &self.transactions_cache, tx_hash).await?;
+                txs.push((tx_hash.clone(), tx));
+            }
+        }
+
+
+        Ok(txs)
+    }
+
+    // Search the blocks to delete and the new pointers of the chain for a rewind
+    // Nothing is modified, the result is journaled before being applied
+    async fn plan_rewind(&self, mut height: u64, mut topoheight: u64, count: u64, stable_topo_height: u64) -> Result<RewindJournal, BlockchainError> {
+        // search the lowest topo height available based on count + 1
+        // (last lowest topo height accepted)
+        let mut lowest_topo = topoheight - count;
+        trace!("Lowest topoheight for rewind: {}", lowest_topo);
+
+        let pruned_topoheight = self.get_pruned_topoheight().await?.unwrap_or(0);
+        if pruned_topoheight != 0 {
+            let safety_pruned_topoheight = pruned_topoheight + PRUNE_SAFETY_LIMIT;
+            if lowest_topo <= safety_pruned_topoheight && stable_topo_height != 0 {
+                warn!("Pruned topoheight is {}, lowest topoheight is {}, rewind only until {}", pruned_topoheight, lowest_topo, safety_pruned_topoheight);
+                lowest_topo = safety_pruned_topoheight;
+            }
+        }
+
+        // new TIPS for chain
+        let mut tips = self.get_tips().await?;
+
+        // Delete all orphaned blocks tips
+        for tip in tips.clone() {
+            if !self.is_block_topological_ordered(&tip).await {
+                debug!("Tip {} is not ordered, removing", tip);
+                tips.remove(&tip);
+            }
+        }
+
+        let mut blocks = Vec::new();
+        let mut pruned_reset = false;
+        let mut done = 0;
+        'main: loop {
+            // stop rewinding if its genesis block or if we reached the lowest topo
+            if topoheight <= lowest_topo || topoheight <= stable_topo_height || height == 0 { // prevent removing genesis block
+                trace!("Done: {done}, count: {count}, height: {height}, topoheight: {topoheight}, lowest topo: {lowest_topo}, stable topo: {stable_topo_height}");
+                break 'main;
+            }
+
+            let hash = self.get_hash_at_topo_height(topoheight).await?;
+            let block = self.get_block_header_by_hash(&hash).await?;
+            trace!("Block {} at topoheight {} will be deleted", hash, topoheight);
+            blocks.push((topoheight, hash.clone(), block.get_height()));
+
+            // generate new tips
+            trace!("Removing {} from {} tips", hash, tips.len());
+            tips.remove(&hash);
+ 
+            for hash in block.get_tips() {
+                trace!("Adding {} to {} tips", hash, tips.len());
+                tips.insert(hash.clone());
+            }
+
+            if topoheight <= pruned_topoheight {
+                warn!("Pruned topoheight is reached, this is not healthy, starting from 0");
+                topoheight = 0;
+                height = 0;
+
+                tips.clear();
+                tips.insert(self.get_hash_at_topo_height(0).await?);
+                pruned_reset = true;
+
+                break 'main;
+            }
+
+            topoheight -= 1;
+            // height of old block become new height
+            if block.get_height() < height {
+                height = block.get_height();
+            }
+            done += 1;
+        }
+
+        debug!("Blocks to rewind {}, new topoheight: {}, new height: {}, tips: {}", done, topoheight, height, tips.len());
+
+        // Reduce the count of blocks stored
+        let blocks_count = self.count_blocks().await? - done;
+        Ok(RewindJournal::new(height, topoheight, pruned_topoheight, pruned_reset, blocks_count, tips, blocks))
+    }
+
+    // Apply a journaled rewind and clear the journal once done
+    // Blocks already deleted by an interrupted attempt are skipped
+    async fn apply_rewind(&mut self, journal: &RewindJournal) -> Result<(u64, u64, Vec<(Hash, Arc<Transaction>)>), BlockchainError> {
+        let height = journal.get_height();
+        let topoheight = journal.get_topoheight();
+        let pruned_topoheight = journal.get_pruned_topoheight();
+
+        // all txs to be rewinded
+        let mut txs = Vec::new();
+        let mut deleted = 0;
+        for (block_topoheight, hash, block_height) in journal.get_blocks() {
+            #[cfg(test)]
+            if self.rewind_failpoint == Some(deleted) {
+                return Err(anyhow::anyhow!("Rewind failpoint reached after {} blocks", deleted).into())
+            }
+
+            if self.hash_at_topo.contains_key(block_topoheight.to_be_bytes())? {
+                let (_, _, block_txs) = self.delete_block_at_topoheight(*block_topoheight).await?;
+                trace!("Block {} at topoheight {} deleted", hash, block_topoheight);
+                txs.extend(block_txs);
+            } else {
+                self.delete_block_leftovers(*block_topoheight, hash, *block_height).await?;
+            }
+            deleted += 1;
+        }
+
+        if journal.is_pruned_reset() {
+            self.extra.remove(PRUNED_TOPOHEIGHT)?;
+            self.pruned_topoheight = None;
+        }
+
+        warn!("Blocks rewinded: {}, new topoheight: {}, new height: {}", deleted, topoheight, height);
+
+        trace!("Cleaning assets");
+
+        // All deleted assets
+        let mut deleted_assets = HashSet::new();
+        
+        // clean all assets
+        for el in self.assets.iter() {
+            let (key, value) = el.context("error on asset iterator")?;
+            let asset = Hash::from_bytes(&key)?;
+            trace!("verifying asset registered: {}", asset);
+
+            let registration_topoheight = u64::from_bytes(&value)?;
+            if registration_topoheight > topoheight {
+                trace!("Asset {} was registered at topoheight {}, deleting", asset, registration_topoheight);
+                // Delete it from registered assets
+                self.assets.remove(&key).context(format!("Error while deleting asset {asset} from registered assets"))?;
+
+                // drop the tree for this asset
+                self.db.drop_tree(key).context(format!("error on dropping asset {asset} tree"))?;
+
+                deleted_assets.insert(asset);
+            }
+        }
+
+        if !deleted_assets.is_empty() {
+            self.store_assets_count(self.count_assets().await?.saturating_sub(deleted_assets.len() as u64))?;
+        }
+
+        trace!("Cleaning nonces");
+        // now let's process nonces versions
+        // we set the new highest topoheight to the highest found under the new topoheight
+        for el in self.nonces.iter() {
+            let (key, value) = el?;
+            let highest_topoheight = u64::from_bytes(&value)?;
+            if highest_topoheight < pruned_topoheight {
+                warn!("wrong nonce topoheight stored, highest topoheight is {}, pruned topoheight is {}", highest_topoheight, pruned_topoheight);
+                self.nonces.remove(key)?;
+                continue;
+            }
+
+            if highest_topoheight > topoheight {
+                if self.nonces.remove(&key)?.is_some() {
+                    self.store_accounts_count(self.count_accounts().await? - 1)?;
+                }
+
+                // find the first version which is under topoheight
+                let pkey = PublicKey::from_bytes(&key)?;
+                let mut version = self.get_nonce_at_exact_topoheight(&pkey, highest_topoheight).await
+                    .context(format!("Error while retrieving nonce at exact topoheight {highest_topoheight}"))?;
+
+                while let Some(previous_topoheight) = version.get_previous_topoheight() {
+                    if previous_topoheight <= topoheight {
+                        // we find the new highest version which is under new topoheight
+                        trace!("New highest version nonce for {} is at topoheight {}", pkey.as_address(self.is_mainnet()), previous_topoheight);
+                        if self.nonces.insert(&key, &previous_topoheight.to_be_bytes())?.is_none() {
+                            self.store_accounts_count(self.count_accounts().await? + 1)?;
+                        }
+                        break;
+                    }
+
+                    // keep searching
+                    version = self.get_nonce_at_exact_topoheight(&pkey, previous_topoheight).await
+                        .context(format!("Error while searching nonce at exact topoheight"))?;
+                }
+            } else {
+                // nothing to do as its under the rewinded topoheight
+            }
+        }
+
+        trace!("Cleaning balances");
+        // do balances too
+        for el in self.balances.iter() {
+            let (key, value) = el?;
+            let asset = Hash::from_bytes(&key[32..64])?;
+            let mut delete = false;
+
+            // if the asset is not deleted, we can process it
+            if !deleted_assets.contains(&asset) {
+                let highest_topoheight = u64::from_bytes(&value)?;
+                if highest_topoheight > topoheight && highest_topoheight >= pruned_topoheight {
+                    // find the first version which is under topoheight
+                    let pkey = PublicKey::from_bytes(&key[0..32])?;
+                    trace!("Highest topoheight for balance {} is {}, above {}", pkey.as_address(self.is_mainnet()), highest_topoheight, topoheight);
+
+                    let mut version = self.get_balance_at_exact_topoheight(&pkey, &asset, highest_topoheight).await
+                        .context(format!("Error while retrieving balance at exact topoheight {highest_topoheight}"))?;
+
+                    // Mark for deletion if we can't find a version under the new topoheight
+                    delete = true;
+
+                    while let Some(previous_topoheight) = version.get_previous_topoheight() {
+                        if previous_topoheight <= topoheight {
+                            // we find the new highest version which is under new topoheight
+                            trace!("New highest version balance for {} is at topoheight {} with asset {}", pkey.as_address(self.is_mainnet()), previous_topoheight, asset);
+                            self.set_balance_pointer(&key, previous_topoheight)?;
+                            delete = false;
+                            break;
+                        }
+    
+                        // keep searching
+                        version = self.get_balance_at_exact_topoheight(&pkey, &asset, previous_topoheight).await?;
+                    }
+                }
+            } else {
+                delete = true;
+            }
+
+            if delete {
+                self.delete_balance_pointer(&key)?;
+            }
+        }
+
+        trace!("Cleaning versioned balances and nonces");
+
+        // now delete all versioned balances and nonces above the new topoheight
+        self.delete_versioned_balances_above_topoheight(topoheight).await?;
+        self.delete_versioned_nonces_above_topoheight(topoheight).await?;
+        // Delete also registrations
+        self.delete_registrations_above_topoheight(topoheight).await?;
+        // And the burned supply versions
+        self.delete_burned_supply_above_topoheight(topoheight).await?;
+
+        trace!("Cleaning caches");
+        // Clear all caches to not have old data after rewind
+        self.clear_caches().await?;
+
+        trace!("Storing new pointers");
+        // store the new tips and topo topoheight
+        self.store_tips(journal.get_tips())?;
+        self.set_top_topoheight(topoheight)?;
+        self.set_top_height(height)?;
+        self.extra.insert(BLOCKS_COUNT, &journal.get_blocks_count().to_be_bytes())?;
+
+        // Rewind is complete
+        self.rewind_journal.remove(REWIND_JOURNAL)?;
+
+        Ok((height, topoheight, txs))
+    }
+
+    // Delete what is left of a block whose deletion was interrupted
+    // Its topoheight pointer is the first thing deleted, so the rest may still be present
+    async fn delete_block_leftovers(&mut self, topoheight: u64, hash: &Hash, height: u64) -> Result<(), BlockchainError> {
+        debug!("Deleting leftovers of block {} at topoheight {}", hash, topoheight);
+        self.topo_by_hash.remove(hash.as_bytes())?;
+        if let Some(value) = self.blocks.remove(hash.as_bytes())? {
+            let block = BlockHeader::from_bytes(&value)?;
+            self.delete_block_transactions(hash, &block).await?;
+        }
+
+        self.supply.remove(topoheight.to_be_bytes())?;
+        self.rewards.remove(topoheight.to_be_bytes())?;
+        self.fees.remove(topoheight.to_be_bytes())?;
+        self.difficulty.remove(hash.as_bytes())?;
+        self.cumulative_difficulty.remove(hash.as_bytes())?;
+
+        if self.has_blocks_at_height(height).await? {
+            self.remove_block_hash_at_height(hash, height).await?;
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -467,6 +820,7 @@ impl Storage for SledStorage {
 
         let reward: u64 = self.delete_cacheable_data(&self.rewards, &None, &topoheight).await?;
         trace!("Reward for block {} was: {}", hash, reward);
+        self.fees.remove(topoheight.to_be_bytes())?;
 
         trace!("Deleting difficulty");
         let _: Difficulty = self.delete_cacheable_data(&self.difficulty, &None, &hash).await?;
@@ -475,29 +829,7 @@ impl Storage for SledStorage {
         let cumulative_difficulty: CumulativeDifficulty = self.delete_cacheable_data(&self.cumulative_difficulty, &self.cumulative_difficulty_cache, &hash).await?;
         trace!("Cumulative difficulty deleted: {}", cumulative_difficulty);
 
-        let mut txs = Vec::new();
-        for tx_hash in block.get_transactions() {
-            if self.has_tx_blocks(tx_hash)? {
-                let mut blocks: Tips = self.delete_cacheable_data(&self.tx_blocks, &None, tx_hash).await?;
-                let blocks_len =  blocks.len();
-                blocks.remove(&hash);
-                self.set_blocks_for_tx(tx_hash, &blocks)?;
-                trace!("Tx was included in {}, blocks left: {}", blocks_len, blocks.into_iter().map(|b| b.to_string()).collect::<Vec<String>>().join(", "));
-            }
-
-            if self.is_tx_executed_in_a_block(tx_hash)? {
-                trace!("Tx {} was executed, deleting", tx_hash);
-                self.remove_tx_executed(&tx_hash)?;
-            }
-
-            // We have to check first as we may have already deleted it because of client protocol
-            // which allow multiple time the same txs in differents blocks
-            if self.contains_data(&self.transactions, &self.transactions_cache, tx_hash).await? {
-                trace!("Deleting TX {} in block {}", tx_hash, hash);
-                let tx: Arc<Transaction> = self.delete_data(&self.transactions, &self.transactions_cache, tx_hash).await?;
-                txs.push((tx_hash.clone(), tx));
-            }
-        }
+        let txs = self.delete_block_transactions(&hash, &block).await?;
 
         // remove the block hash from the set, and delete the set if empty
         if self.has_blocks_at_height(block.get_height()).await? {
@@ -532,11 +864,11 @@ impl Storage for SledStorage {
                 // Now records changes, for each balances
                 let db_key = self.get_balance_key_for(&key, &asset);
                 if let Some(previous_topoheight) = versioned_balance.get_previous_topoheight() {
-                    self.balances.insert(&db_key, &previous_topoheight.to_be_bytes())?;
+                    self.set_balance_pointer(&db_key, previous_topoheight)?;
                 } else {
                     // if there is no previous topoheight, it means that this is the first version
                     // so we can delete the balance
-                    self.balances.remove(&db_key)?;
+                    self.delete_balance_pointer(&db_key)?;
                 }
             }
         }
@@ -645,7 +977,7 @@ impl Storage for SledStorage {
             // otherwise, delete the previous topoheight in VersionedBalance which is under topoheight
             if highest_balance_topoheight <= topoheight {
                 // save the new highest topoheight
-                self.balances.insert(&key_bytes, &topoheight.to_be_bytes())?;
+                self.set_balance_pointer(&key_bytes, topoheight)?;
                 // remove the previous topoheight
                 versioned_balance.set_previous_topoheight(None);
 
@@ -784,218 +1116,39 @@ impl Storage for SledStorage {
         Ok(self.extra.contains_key(NETWORK)?)
     }
 
-    async fn pop_blocks(&mut self, mut height: u64, mut topoheight: u64, count: u64, stable_topo_height: u64) -> Result<(u64, u64, Vec<(Hash, Arc<Transaction>)>), BlockchainError> {
+    async fn pop_blocks(&mut self, height: u64, topoheight: u64, count: u64, stable_topo_height: u64) -> Result<(u64, u64, Vec<(Hash, Arc<Transaction>)>), BlockchainError> {
         trace!("pop blocks from height: {}, topoheight: {}, count: {}", height, topoheight, count);
         if topoheight < count as u64 { // also prevent removing genesis block
             return Err(BlockchainError::NotEnoughBlocks);
         }
 
-        // search the lowest topo height available based on count + 1
-        // (last lowest topo height accepted)
-        let mut lowest_topo = topoheight - count;
-        trace!("Lowest topoheight for rewind: {}", lowest_topo);
-
-        let pruned_topoheight = self.get_pruned_topoheight().await?.unwrap_or(0);
-        if pruned_topoheight != 0 {
-            let safety_pruned_topoheight = pruned_topoheight + PRUNE_SAFETY_LIMIT;
-            if lowest_topo <= safety_pruned_topoheight && stable_topo_height != 0 {
-                warn!("Pruned topoheight is {}, lowest topoheight is {}, rewind only until {}", pruned_topoheight, lowest_topo, safety_pruned_topoheight);
-                lowest_topo = safety_pruned_topoheight;
-            }
-        }
-
-        // new TIPS for chain
-        let mut tips = self.get_tips().await?;
-
-        // Delete all orphaned blocks tips
-        for tip in tips.clone() {
-            if !self.is_block_topological_ordered(&tip).await {
-                debug!("Tip {} is not ordered, removing", tip);
-                tips.remove(&tip);
-            }
-        }
-
-        // all txs to be rewinded
-        let mut txs = Vec::new();
-        let mut done = 0;
-        'main: loop {
-            // stop rewinding if its genesis block or if we reached the lowest topo
-            if topoheight <= lowest_topo || topoheight <= stable_topo_height || height == 0 { // prevent removing genesis block
-                trace!("Done: {done}, count: {count}, height: {height}, topoheight: {topoheight}, lowest topo: {lowest_topo}, stable topo: {stable_topo_height}");
-                break 'main;
-            }
-
-            // Delete the hash at topoheight
-            let (hash, block, block_txs) = self.delete_block_at_topoheight(topoheight).await?;
-            trace!("Block {} at topoheight {} deleted", hash, topoheight);
-            txs.extend(block_txs);
-
-            // generate new tips
-            trace!("Removing {} from {} tips", hash, tips.len());
-            tips.remove(&hash);
- 
-            for hash in block.get_tips() {
-                trace!("Adding {} to {} tips", hash, tips.len());
-                tips.insert(hash.clone());
-            }
-
-            if topoheight <= pruned_topoheight {
-                warn!("Pruned topoheight is reached, this is not healthy, starting from 0");
-                topoheight = 0;
-                height = 0;
-
-                tips.clear();
-                tips.insert(self.get_hash_at_topo_height(0).await?);
-
-                self.extra.remove(PRUNED_TOPOHEIGHT)?;
-                self.pruned_topoheight = None;
-
-                break 'main;
-            }
-
-            topoheight -= 1;
-            // height of old block become new height
-            if block.get_height() < height {
-                height = block.get_height();
-            }
-            done += 1;
-        }
-
-        debug!("Blocks processed {}, new topoheight: {}, new height: {}, tips: {}", done, topoheight, height, tips.len());
-
-        trace!("Cleaning assets");
-
-        // All deleted assets
-        let mut deleted_assets = HashSet::new();
-        
-        // clean all assets
-        for el in self.assets.iter() {
-            let (key, value) = el.context("error on asset iterator")?;
-            let asset = Hash::from_bytes(&key)?;
-            trace!("verifying asset registered: {}", asset);
-
-            let registration_topoheight = u64::from_bytes(&value)?;
-            if registration_topoheight > topoheight {
-                trace!("Asset {} was registered at topoheight {}, deleting", asset, registration_topoheight);
-                // Delete it from registered assets
-                self.assets.remove(&key).context(format!("Error while deleting asset {asset} from registered assets"))?;
-
-                // drop the tree for this asset
-                self.db.drop_tree(key).context(format!("error on dropping asset {asset} tree"))?;
+        let journal = self.plan_rewind(height, topoheight, count, stable_topo_height).await?;
+        // Journal the rewind before deleting anything so it can be completed after a crash
+        self.rewind_journal.insert(REWIND_JOURNAL, journal.to_bytes())?;
+        self.rewind_journal.flush()?;
 
-                deleted_assets.insert(asset);
-            }
-        }
-
-        trace!("Cleaning nonces");
-        // now let's process nonces versions
-        // we set the new highest topoheight to the highest found under the new topoheight
-        for el in self.nonces.iter() {
-            let (key, value) = el?;
-            let highest_topoheight = u64::from_bytes(&value)?;
-            if highest_topoheight < pruned_topoheight {
-                warn!("wrong nonce topoheight stored, highest topoheight is {}, pruned topoheight is {}", highest_topoheight, pruned_topoheight);
-                self.nonces.remove(key)?;
-                continue;
-            }
-
-            if highest_topoheight > topoheight {
-                if self.nonces.remove(&key)?.is_some() {
-                    self.store_accounts_count(self.count_accounts().await? - 1)?;
-                }
-
-                // find the first version which is under topoheight
-                let pkey = PublicKey::from_bytes(&key)?;
-                let mut version = self.get_nonce_at_exact_topoheight(&pkey, highest_topoheight).await
-                    .context(format!("Error while retrieving nonce at exact topoheight {highest_topoheight}"))?;
-
-                while let Some(previous_topoheight) = version.get_previous_topoheight() {
-                    if previous_topoheight <= topoheight {
-                        // we find the new highest version which is under new topoheight
-                        trace!("New highest version nonce for {} is at topoheight {}", pkey.as_address(self.is_mainnet()), previous_topoheight);
-                        if self.nonces.insert(&key, &previous_topoheight.to_be_bytes())?.is_none() {
-                            self.store_accounts_count(self.count_accounts().await? + 1)?;
-                        }
-                        break;
-                    }
-
-                    // keep searching
-                    version = self.get_nonce_at_exact_topoheight(&pkey, previous_topoheight).await
-                        .context(format!("Error while searching nonce at exact topoheight"))?;
-                }
-            } else {
-                // nothing to do as its under the rewinded topoheight
-            }
-        }
-
-        trace!("Cleaning balances");
-        // do balances too
-        for el in self.balances.iter() {
-            let (key, value) = el?;
-            let asset = Hash::from_bytes(&key[32..64])?;
-            let mut delete = false;
-
-            // if the asset is not deleted, we can process it
-            if !deleted_assets.contains(&asset) {
-                let highest_topoheight = u64::from_bytes(&value)?;
-                if highest_topoheight > topoheight && highest_topoheight >= pruned_topoheight {
-                    // find the first version which is under topoheight
-                    let pkey = PublicKey::from_bytes(&key[0..32])?;
-                    trace!("Highest topoheight for balance {} is {}, above {}", pkey.as_address(self.is_mainnet()), highest_topoheight, topoheight);
-
-                    let mut version = self.get_balance_at_exact_topoheight(&pkey, &asset, highest_topoheight).await
-                        .context(format!("Error while retrieving balance at exact topoheight {highest_topoheight}"))?;
+        self.apply_rewind(&journal).await
+    }
 
-                    // Mark for deletion if we can't find a version under the new topoheight
-                    delete = true;
+    fn has_pending_rewind(&self) -> Result<bool, BlockchainError> {
+        trace!("has pending rewind");
+        Ok(self.rewind_journal.contains_key(REWIND_JOURNAL)?)
+    }
 
-                    while let Some(previous_topoheight) = version.get_previous_topoheight() {
-                        if previous_topoheight <= topoheight {
-                            // we find the new highest version which is under new topoheight
-                            trace!("New highest version balance for {} is at topoheight {} with asset {}", pkey.as_address(self.is_mainnet()), previous_topoheight, asset);
-                            self.balances.insert(&key, &previous_topoheight.to_be_bytes())?;
-                            delete = false;
-                            break;
-                        }
-    
-                        // keep searching
-                        version = self.get_balance_at_exact_topoheight(&pkey, &asset, previous_topoheight).await?;
-                    }
-                }
-            } else {
-                delete = true;
-            }
+    async fn recover_pending_rewind(&mut self) -> Result<Option<u64>, BlockchainError> {
+        trace!("recover pending rewind");
+        let journal = match self.load_optional_from_disk::<RewindJournal>(&self.rewind_journal, REWIND_JOURNAL)? {
+            Some(journal) => journal,
+            None => return Ok(None)
+        };
 
-            if delete {
-                self.balances.remove(&key)?;
-            }
+        warn!("An interrupted rewind was found, completing it until topoheight {}", journal.get_topoheight());
+        let (_, topoheight, txs) = self.apply_rewind(&journal).await?;
+        if !txs.is_empty() {
+            warn!("{} transactions from the rewinded blocks are not added back to the mempool", txs.len());
         }
 
-        warn!("Blocks rewinded: {}, new topoheight: {}, new height: {}", done, topoheight, height);
-
-        trace!("Cleaning versioned balances and nonces");
-
-        // now delete all versioned balances and nonces above the new topoheight
-        self.delete_versioned_balances_above_topoheight(topoheight).await?;
-        self.delete_versioned_nonces_above_topoheight(topoheight).await?;
-        // Delete also registrations
-        self.delete_registrations_above_topoheight(topoheight).await?;
-
-        trace!("Cleaning caches");
-        // Clear all caches to not have old data after rewind
-        self.clear_caches().await?;
-
-        trace!("Storing new pointers");
-        // store the new tips and topo topoheight
-        self.store_tips(&tips)?;
-        self.set_top_topoheight(topoheight)?;
-        self.set_top_height(height)?;
-
-        // Reduce the count of blocks stored
-        let count = self.count_blocks().await? - done;
-        self.extra.insert(BLOCKS_COUNT, &count.to_be_bytes())?;
-
-        Ok((height, topoheight, txs))
+        Ok(Some(topoheight))
     }
 
     async fn get_top_block_hash(&self) -> Result<Hash, BlockchainError> {
@@ -1031,6 +1184,25 @@ impl Storage for SledStorage {
         Ok((self.get_block_header_by_hash(&hash).await?, hash))
     }
 
+    async fn get_blocks_range(&self, start_topoheight: u64, end_topoheight: u64) -> Result<Vec<(u64, Hash, Arc<BlockHeader>)>, BlockchainError> {
+        trace!("get blocks range {} - {}", start_topoheight, end_topoheight);
+        if start_topoheight > end_topoheight {
+            return Ok(Vec::new())
+        }
+
+        // Keys are stored in big endian, so they are sorted by topoheight
+        let mut blocks = Vec::with_capacity((end_topoheight - start_topoheight + 1).min(1024) as usize);
+        for el in self.hash_at_topo.range(start_topoheight.to_be_bytes()..=end_topoheight.to_be_bytes()) {
+            let (key, value) = el?;
+            let topoheight = u64::from_be_bytes(key[0..8].try_into()?);
+            let hash = Hash::from_bytes(&value)?;
+            let header = self.get_block_header_by_hash(&hash).await?;
+            blocks.push((topoheight, hash, header));
+        }
+
+        Ok(blocks)
+    }
+
     async fn get_top_block(&self) -> Result<Block, BlockchainError> {
         trace!("get top block");
         let (block, _) = self.get_top_block_header().await?;
@@ -1061,6 +1233,12 @@ impl Storage for SledStorage {
         Ok(self.db.size_on_disk()?)
     }
 
+    async fn flush(&self) -> Result<(), BlockchainError> {
+        trace!("flush");
+        self.db.flush_async().await?;
+        Ok(())
+    }
+
     async fn stop(&mut self) -> Result<(), BlockchainError> {
         info!("Stopping Storage...");
         info!("Flushing Sled database");
@@ -0,0 +1,149 @@
+// Helpers shared by the tests using a storage
+
+use std::sync::Arc;
+use indexmap::IndexSet;
+use xelis_common::{
+    block::{BlockHeader, EXTRA_NONCE_SIZE},
+    crypto::{Hash, Hashable, KeyPair, PublicKey},
+    difficulty::{CumulativeDifficulty, Difficulty},
+    immutable::Immutable,
+    network::Network,
+    testing::TempDir,
+    transaction::Transaction,
+    varuint::VarUint
+};
+use super::{SledStorage, Storage};
+
+// Sled storage of the dev network opened in a new temporary directory
+// The directory must be kept until the end of the test, it is deleted once dropped
+// Declared first, it is dropped after the storage: `let (_dir, storage) = ...`
+pub fn open_temp_storage(name: &str) -> (TempDir, SledStorage) {
+    let dir = TempDir::new(name);
+    let storage = open_storage(&dir);
+    (dir, storage)
+}
+
+// Open the storage of a directory already used or prepared by the test
+pub fn open_storage(dir: &TempDir) -> SledStorage {
+    SledStorage::new(dir.to_dir_path(), None, Network::Dev).unwrap()
+}
+
+// Empty block stored directly in a storage, without any verification
+pub struct TestBlock {
+    height: u64,
+    timestamp: u64,
+    tips: IndexSet<Hash>,
+    salt: u8,
+    miner: PublicKey,
+    difficulty: Difficulty,
+    covariance: VarUint,
+    txs: Vec<Arc<Transaction>>
+}
+
+impl TestBlock {
+    // Block at the height with a timestamp equal to its height
+    pub fn new(height: u64) -> Self {
+        Self {
+            height,
+            timestamp: height,
+            tips: IndexSet::new(),
+            salt: 0,
+            miner: KeyPair::new().get_public_key().compress(),
+            difficulty: Difficulty::from_u64(1),
+            covariance: VarUint::one(),
+            txs: Vec::new()
+        }
+    }
+
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn tips(mut self, tips: IndexSet<Hash>) -> Self {
+        self.tips = tips;
+        self
+    }
+
+    // Give another hash to a block at the same height
+    pub fn salt(mut self, salt: u8) -> Self {
+        self.salt = salt;
+        self
+    }
+
+    pub fn miner(mut self, miner: PublicKey) -> Self {
+        self.miner = miner;
+        self
+    }
+
+    pub fn difficulty(mut self, difficulty: Difficulty) -> Self {
+        self.difficulty = difficulty;
+        self
+    }
+
+    pub fn covariance(mut self, covariance: VarUint) -> Self {
+        self.covariance = covariance;
+        self
+    }
+
+    // Transactions included in the block, they are not executed
+    pub fn txs(mut self, txs: Vec<Arc<Transaction>>) -> Self {
+        self.txs = txs;
+        self
+    }
+
+    pub fn header(&self) -> BlockHeader {
+        let mut extra_nonce = [self.salt; EXTRA_NONCE_SIZE];
+        extra_nonce[0..8].copy_from_slice(&self.height.to_be_bytes());
+        let txs_hashes = self.txs.iter().map(|tx| tx.hash()).collect();
+        BlockHeader::new(0, self.height, self.timestamp, self.tips.clone(), extra_nonce, self.miner.clone(), txs_hashes)
+    }
+
+    // Store the block without ordering it
+    pub async fn save<S: Storage>(self, storage: &mut S) -> Hash {
+        let header = self.header();
+        let hash = header.hash();
+        let txs: Vec<Immutable<Transaction>> = self.txs.iter().map(|tx| Immutable::Arc(tx.clone())).collect();
+        storage.save_block(Arc::new(header), &txs, self.difficulty, self.covariance, hash.clone()).await.unwrap();
+        hash
+    }
+
+    // Store the block and order it at the topoheight
+    pub async fn save_at<S: Storage>(self, storage: &mut S, topoheight: u64) -> Hash {
+        let hash = self.save(storage).await;
+        storage.set_topo_height_for_block(&hash, topoheight).await.unwrap();
+        if topoheight >= storage.get_top_topoheight().unwrap_or(0) {
+            storage.set_top_topoheight(topoheight).unwrap();
+        }
+        hash
+    }
+}
+
+// Linear chain of `count` blocks, each one ordered at its height on top of the previous one
+// The block at each height can be customized, its cumulative difficulty is the sum of the difficulties
+pub async fn add_linear_chain<S: Storage, F: Fn(u64, TestBlock) -> TestBlock>(storage: &mut S, count: u64, customize: F) -> Vec<Hash> {
+    let mut hashes: Vec<Hash> = Vec::new();
+    let mut cumulative_difficulty = CumulativeDifficulty::zero();
+    for height in 0..count {
+        let tips = hashes.last().cloned().into_iter().collect();
+        let block = customize(height, TestBlock::new(height).tips(tips));
+        cumulative_difficulty = cumulative_difficulty + block.difficulty;
+
+        let hash = block.save_at(storage, height).await;
+        storage.set_cumulative_difficulty_for_block_hash(&hash, cumulative_difficulty).await.unwrap();
+        storage.set_top_height(height).unwrap();
+        hashes.push(hash);
+    }
+
+    hashes
+}
+
+// Empty block at the height of its topoheight, stored like a block executed without any reward
+pub async fn add_ordered_block<S: Storage>(storage: &mut S, topoheight: u64, salt: u8) -> Hash {
+    let hash = TestBlock::new(topoheight).salt(salt).save_at(storage, topoheight).await;
+    storage.set_cumulative_difficulty_for_block_hash(&hash, CumulativeDifficulty::from_u64(topoheight)).await.unwrap();
+    storage.set_supply_at_topo_height(topoheight, 0).unwrap();
+    storage.set_block_reward_at_topo_height(topoheight, 0).unwrap();
+    storage.set_top_height(topoheight).unwrap();
+    hash
+}
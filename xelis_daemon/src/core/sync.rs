@@ -0,0 +1,106 @@
+use std::{
+    ops::RangeInclusive,
+    sync::atomic::{AtomicBool, Ordering}
+};
+use crate::config::{SYNC_MAX_TOPOHEIGHT_BEHIND, SYNC_MAX_TOP_BLOCK_AGE_MILLIS};
+
+// Check if our chain is synced with the network
+// Our top block must be recent enough, or we must be close to the best topoheight advertised by our peers
+// Without any peer, we can't be behind anyone
+pub fn is_synced(top_block_timestamp: u64, now: u64, our_topoheight: u64, best_peer_topoheight: Option<u64>) -> bool {
+    if now.saturating_sub(top_block_timestamp) <= SYNC_MAX_TOP_BLOCK_AGE_MILLIS {
+        return true
+    }
+
+    best_peer_topoheight.map_or(true, |best| our_topoheight.saturating_add(SYNC_MAX_TOPOHEIGHT_BEHIND) >= best)
+}
+
+// Next topoheights to request directly from a peer ahead of us
+// At most `max_size` blocks are requested, None if the peer is not ahead
+pub fn get_topoheight_sync_batch(our_topoheight: u64, peer_topoheight: u64, max_size: usize) -> Option<RangeInclusive<u64>> {
+    if peer_topoheight <= our_topoheight || max_size == 0 {
+        return None
+    }
+
+    let end = peer_topoheight.min(our_topoheight.saturating_add(max_size as u64));
+    Some(our_topoheight + 1..=end)
+}
+
+// Keep the last sync state computed to detect when we cross the threshold
+pub struct SyncState {
+    synced: AtomicBool
+}
+
+impl SyncState {
+    pub fn new() -> Self {
+        Self {
+            synced: AtomicBool::new(false)
+        }
+    }
+
+    // Last sync state computed
+    pub fn is_synced(&self) -> bool {
+        self.synced.load(Ordering::SeqCst)
+    }
+
+    // Update the sync state
+    // Returns true only when we just became synced
+    pub fn update(&self, synced: bool) -> bool {
+        let previous = self.synced.swap(synced, Ordering::SeqCst);
+        synced && !previous
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000_000;
+
+    #[test]
+    fn test_synced_with_recent_top_block() {
+        // Exactly at the limit
+        assert!(is_synced(NOW - SYNC_MAX_TOP_BLOCK_AGE_MILLIS, NOW, 0, Some(1_000_000)));
+        assert!(!is_synced(NOW - SYNC_MAX_TOP_BLOCK_AGE_MILLIS - 1, NOW, 0, Some(1_000_000)));
+        // Block slightly in the future
+        assert!(is_synced(NOW + 1_000, NOW, 0, Some(1_000_000)));
+    }
+
+    #[test]
+    fn test_synced_with_peers() {
+        let old = NOW - SYNC_MAX_TOP_BLOCK_AGE_MILLIS - 1;
+        // No peer to compare with
+        assert!(is_synced(old, NOW, 100, None));
+        // Exactly at the limit
+        assert!(is_synced(old, NOW, 100, Some(100 + SYNC_MAX_TOPOHEIGHT_BEHIND)));
+        assert!(!is_synced(old, NOW, 100, Some(100 + SYNC_MAX_TOPOHEIGHT_BEHIND + 1)));
+        // Ahead of our peers
+        assert!(is_synced(old, NOW, 100, Some(50)));
+    }
+
+    #[test]
+    fn test_sync_completed_once() {
+        let state = SyncState::new();
+        assert!(!state.is_synced());
+        assert!(!state.update(false));
+
+        // Crossing the threshold
+        assert!(state.update(true));
+        assert!(state.is_synced());
+        assert!(!state.update(true));
+
+        // Behind again, then synced again
+        assert!(!state.update(false));
+        assert!(!state.is_synced());
+        assert!(state.update(true));
+    }
+
+    #[test]
+    fn test_topoheight_sync_batch() {
+        assert_eq!(get_topoheight_sync_batch(100, 100, 64), None);
+        assert_eq!(get_topoheight_sync_batch(100, 50, 64), None);
+        assert_eq!(get_topoheight_sync_batch(100, 110, 0), None);
+        assert_eq!(get_topoheight_sync_batch(100, 110, 64), Some(101..=110));
+        assert_eq!(get_topoheight_sync_batch(100, 1000, 64), Some(101..=164));
+    }
+}
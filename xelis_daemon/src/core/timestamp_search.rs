@@ -0,0 +1,153 @@
+use log::trace;
+use xelis_common::{
+    config::TIPS_LIMIT
+};
+use crate::config::STABLE_LIMIT;
+use super::{
+    error::BlockchainError,
+    storage::{DagOrderProvider, DifficultyProvider, Storage}
+};
+
+// Blocks are ordered in the DAG by cumulative difficulty, not by timestamp
+// A side block can be ordered after blocks having a higher timestamp than it
+// How many blocks after the binary search result are checked for such inversions
+pub const TIMESTAMP_INVERSION_TOLERANCE: u64 = STABLE_LIMIT * TIPS_LIMIT as u64;
+
+async fn get_timestamp_at_topoheight<P: DagOrderProvider + DifficultyProvider>(provider: &P, topoheight: u64) -> Result<u64, BlockchainError> {
+    let hash = provider.get_hash_at_topo_height(topoheight).await?;
+    provider.get_timestamp_for_block_hash(&hash).await
+}
+
+// Search the highest topoheight in range (inclusive) having a block timestamp under or equal to the one requested
+// Timestamps are nearly increasing with the topoheight: a binary search is done,
+// then the next blocks are checked in case of small inversions
+// Returns None if all blocks in range are above the timestamp
+pub async fn search_topoheight_at_timestamp<P: DagOrderProvider + DifficultyProvider>(provider: &P, timestamp: u64, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<Option<u64>, BlockchainError> {
+    trace!("search topoheight at timestamp {} between {} and {}", timestamp, minimum_topoheight, maximum_topoheight);
+    if minimum_topoheight > maximum_topoheight {
+        return Ok(None)
+    }
+
+    // Lowest topoheight having a timestamp above the one requested
+    let (mut low, mut high) = (minimum_topoheight, maximum_topoheight + 1);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if get_timestamp_at_topoheight(provider, mid).await? <= timestamp {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    let mut result = low.checked_sub(1).filter(|topoheight| *topoheight >= minimum_topoheight);
+    let end = maximum_topoheight.min(low.saturating_add(TIMESTAMP_INVERSION_TOLERANCE));
+    for topoheight in low..=end {
+        if get_timestamp_at_topoheight(provider, topoheight).await? <= timestamp {
+            result = Some(topoheight);
+        }
+    }
+
+    Ok(result)
+}
+
+// Find the topoheight of the chain at the timestamp requested
+// Returns None if the timestamp is before the genesis block
+// A timestamp in the future returns the top topoheight
+// Blocks below the pruned topoheight (except the genesis) are deleted, timestamps in this range can't be resolved
+pub async fn find_topoheight_at_timestamp<S: Storage>(storage: &S, timestamp: u64, top_topoheight: u64) -> Result<Option<u64>, BlockchainError> {
+    let pruned_topoheight = storage.get_pruned_topoheight().await?;
+    if let Some(topoheight) = search_topoheight_at_timestamp(storage, timestamp, pruned_topoheight.unwrap_or(0), top_topoheight).await? {
+        return Ok(Some(topoheight))
+    }
+
+    match pruned_topoheight {
+        Some(pruned_topoheight) if timestamp >= get_timestamp_at_topoheight(storage, 0).await? => {
+            Err(BlockchainError::PrunedTimestamp(timestamp, pruned_topoheight))
+        },
+        _ => Ok(None)
+    }
+}
+
+// Count the blocks having a timestamp between start and end (inclusive)
+pub async fn count_blocks_between<S: Storage>(storage: &S, start_timestamp: u64, end_timestamp: u64, top_topoheight: u64) -> Result<u64, BlockchainError> {
+    if start_timestamp > end_timestamp {
+        return Ok(0)
+    }
+
+    let Some(end) = find_topoheight_at_timestamp(storage, end_timestamp, top_topoheight).await? else {
+        return Ok(0)
+    };
+
+    // Blocks are counted after the last one before the start
+    let start = match start_timestamp.checked_sub(1) {
+        Some(timestamp) => find_topoheight_at_timestamp(storage, timestamp, top_topoheight).await?.map(|topoheight| topoheight + 1).unwrap_or(0),
+        None => 0
+    };
+
+    Ok((end + 1).saturating_sub(start))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::storage::{
+        test_utils::{open_temp_storage, TestBlock},
+        PrunedTopoheightProvider,
+        SledStorage
+    };
+    use super::*;
+
+    // Block timestamps by topoheight, topoheight 4 is a side block ordered after a block with a higher timestamp
+    const TIMESTAMPS: [u64; 8] = [1000, 2000, 3000, 4000, 3900, 5000, 6000, 7000];
+
+    async fn create_test_chain(storage: &mut SledStorage) {
+        for (topoheight, timestamp) in TIMESTAMPS.iter().enumerate() {
+            let topoheight = topoheight as u64;
+            TestBlock::new(topoheight).timestamp(*timestamp).save_at(storage, topoheight).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_topoheight_at_timestamp() {
+        let (_dir, mut storage) = open_temp_storage("timestamp-search");
+        create_test_chain(&mut storage).await;
+        let top = TIMESTAMPS.len() as u64 - 1;
+
+        // Before genesis, and in the future
+        assert_eq!(find_topoheight_at_timestamp(&storage, 999, top).await.unwrap(), None);
+        assert_eq!(find_topoheight_at_timestamp(&storage, 1000, top).await.unwrap(), Some(0));
+        assert_eq!(find_topoheight_at_timestamp(&storage, 100_000, top).await.unwrap(), Some(top));
+
+        assert_eq!(find_topoheight_at_timestamp(&storage, 2500, top).await.unwrap(), Some(1));
+        assert_eq!(find_topoheight_at_timestamp(&storage, 3000, top).await.unwrap(), Some(2));
+
+        // Side block with a lower timestamp is found
+        assert_eq!(find_topoheight_at_timestamp(&storage, 3950, top).await.unwrap(), Some(4));
+        assert_eq!(find_topoheight_at_timestamp(&storage, 4000, top).await.unwrap(), Some(4));
+        assert_eq!(find_topoheight_at_timestamp(&storage, 3899, top).await.unwrap(), Some(2));
+
+        // Binary search stops at topoheight 2, side block is found by the inversion tolerance
+        assert_eq!(search_topoheight_at_timestamp(&storage, 3950, 2, 4).await.unwrap(), Some(4));
+        assert_eq!(search_topoheight_at_timestamp(&storage, 500, 2, 6).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_count_blocks_between() {
+        let (_dir, mut storage) = open_temp_storage("timestamp-count");
+        create_test_chain(&mut storage).await;
+        let top = TIMESTAMPS.len() as u64 - 1;
+
+        assert_eq!(count_blocks_between(&storage, 0, 100_000, top).await.unwrap(), TIMESTAMPS.len() as u64);
+        assert_eq!(count_blocks_between(&storage, 1000, 1000, top).await.unwrap(), 1);
+        assert_eq!(count_blocks_between(&storage, 2000, 3000, top).await.unwrap(), 2);
+        assert_eq!(count_blocks_between(&storage, 5000, 100_000, top).await.unwrap(), 3);
+        assert_eq!(count_blocks_between(&storage, 0, 999, top).await.unwrap(), 0);
+        assert_eq!(count_blocks_between(&storage, 3000, 2000, top).await.unwrap(), 0);
+
+        // Blocks below the pruned topoheight can't be found anymore
+        storage.set_pruned_topoheight(3).await.unwrap();
+        assert!(matches!(find_topoheight_at_timestamp(&storage, 2500, top).await, Err(BlockchainError::PrunedTimestamp(2500, 3))));
+        assert_eq!(find_topoheight_at_timestamp(&storage, 999, top).await.unwrap(), None);
+        assert_eq!(count_blocks_between(&storage, 5000, 7000, top).await.unwrap(), 3);
+        assert!(count_blocks_between(&storage, 2000, 7000, top).await.is_err());
+    }
+}
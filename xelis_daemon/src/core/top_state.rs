@@ -0,0 +1,155 @@
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+use xelis_common::{
+    api::daemon::BlockSummary,
+    crypto::Hash,
+    difficulty::{CumulativeDifficulty, Difficulty}
+};
+
+// State of the chain top
+// It is read by the ping loop, the RPC and the miners without touching the storage
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TopState {
+    // Hash of the block at the highest topoheight
+    pub hash: Hash,
+    // Highest (unstable) height
+    pub height: u64,
+    // Highest topological height
+    pub topoheight: u64,
+    // Difficulty target for the next block
+    pub difficulty: Difficulty,
+    // Cumulative difficulty of the top block
+    pub cumulative_difficulty: CumulativeDifficulty,
+    // No blocks can be added at or below this height
+    pub stable_height: u64,
+    // Topoheight of the stable block
+    pub stable_topoheight: u64,
+    // Topoheight until which the chain was pruned, None if never pruned
+    pub pruned_topoheight: Option<u64>,
+    // Summary of the top block, None until the genesis block is added
+    pub block: Option<BlockSummary>
+}
+
+// Cache of the chain top
+// The whole state is replaced at once, so a reader never
+// mixes the topoheight of a block with the hash of another
+pub struct TopStateCache {
+    state: RwLock<TopState>
+}
+
+impl TopStateCache {
+    pub fn new(state: TopState) -> Self {
+        Self {
+            state: RwLock::new(state)
+        }
+    }
+
+    // A panic while holding the lock can't leave a partially updated state
+    fn read(&self) -> RwLockReadGuard<'_, TopState> {
+        self.state.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Get a copy of the whole state
+    pub fn get(&self) -> TopState {
+        self.read().clone()
+    }
+
+    // Replace the whole state
+    pub fn set(&self, state: TopState) {
+        *self.state.write().unwrap_or_else(PoisonError::into_inner) = state;
+    }
+
+    pub fn get_hash(&self) -> Hash {
+        self.read().hash.clone()
+    }
+
+    pub fn get_height(&self) -> u64 {
+        self.read().height
+    }
+
+    pub fn get_topoheight(&self) -> u64 {
+        self.read().topoheight
+    }
+
+    pub fn get_difficulty(&self) -> Difficulty {
+        self.read().difficulty
+    }
+
+    pub fn get_cumulative_difficulty(&self) -> CumulativeDifficulty {
+        self.read().cumulative_difficulty
+    }
+
+    pub fn get_stable_height(&self) -> u64 {
+        self.read().stable_height
+    }
+
+    pub fn get_stable_topoheight(&self) -> u64 {
+        self.read().stable_topoheight
+    }
+
+    pub fn get_pruned_topoheight(&self) -> Option<u64> {
+        self.read().pruned_topoheight
+    }
+
+    // Pruning doesn't change the top of the chain, only this field is updated
+    pub fn set_pruned_topoheight(&self, pruned_topoheight: Option<u64>) {
+        self.state.write().unwrap_or_else(PoisonError::into_inner).pruned_topoheight = pruned_topoheight;
+    }
+
+    pub fn get_block_summary(&self) -> Option<BlockSummary> {
+        self.read().block.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+    use super::*;
+
+    fn state_at(topoheight: u64) -> TopState {
+        TopState {
+            hash: Hash::new([topoheight as u8; 32]),
+            height: topoheight,
+            topoheight,
+            difficulty: Difficulty::from_u64(topoheight),
+            cumulative_difficulty: CumulativeDifficulty::from_u64(topoheight * 2),
+            stable_height: topoheight.saturating_sub(8),
+            stable_topoheight: topoheight.saturating_sub(8),
+            pruned_topoheight: None,
+            block: None
+        }
+    }
+
+    #[test]
+    fn test_concurrent_reads_are_consistent() {
+        let cache = Arc::new(TopStateCache::new(state_at(0)));
+        let writer = {
+            let cache = Arc::clone(&cache);
+            thread::spawn(move || {
+                for topoheight in 1..=200 {
+                    cache.set(state_at(topoheight));
+                }
+            })
+        };
+
+        let readers: Vec<_> = (0..4).map(|_| {
+            let cache = Arc::clone(&cache);
+            thread::spawn(move || {
+                let mut last = 0;
+                for _ in 0..1000 {
+                    let state = cache.get();
+                    // Every field comes from the same update
+                    assert_eq!(state, state_at(state.topoheight));
+                    assert!(state.topoheight >= last);
+                    last = state.topoheight;
+                }
+            })
+        }).collect();
+
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(cache.get(), state_at(200));
+        assert_eq!(cache.get_hash(), Hash::new([200; 32]));
+    }
+}
@@ -3,6 +3,7 @@ use std::{
         VecDeque,
         BinaryHeap,
         HashMap,
+        HashSet,
         hash_map::Entry
     },
     sync::Arc,
@@ -12,20 +13,45 @@ use xelis_common::{
     transaction::Transaction,
     crypto::{
         Hash,
-        PublicKey
+        PublicKey,
+        HASH_SIZE
     }
 };
+use super::mempool::compare_fee_rate;
 
-// this struct is used to store transaction with its hash and its size in bytes
+// this struct is used to store the data of a transaction needed to select it
+// It is a snapshot of the mempool entry, the transaction itself is not required
+#[derive(Debug, Clone, Copy)]
 pub struct TxSelectorEntry<'a> {
     // Hash of the transaction
     pub hash: &'a Arc<Hash>,
-    // Current transaction
-    pub tx: &'a Arc<Transaction>,
+    // Sender of the transaction
+    pub source: &'a PublicKey,
+    // Nonce used by the transaction
+    pub nonce: u64,
+    // Fees paid by the transaction
+    pub fee: u64,
     // Size in bytes of the TX
     pub size: usize
 }
 
+impl<'a> TxSelectorEntry<'a> {
+    pub fn new(hash: &'a Arc<Hash>, tx: &'a Transaction, size: usize) -> Self {
+        Self {
+            hash,
+            source: tx.get_source(),
+            nonce: tx.get_nonce(),
+            fee: tx.get_fee(),
+            size
+        }
+    }
+
+    // Bytes used in the block by this TX, its hash is also stored in the header
+    fn block_size(&self) -> usize {
+        self.size + HASH_SIZE
+    }
+}
+
 impl PartialEq for TxSelectorEntry<'_> {
     fn eq(&self, other: &Self) -> bool {
         self.hash == other.hash
@@ -35,64 +61,84 @@ impl PartialEq for TxSelectorEntry<'_> {
 impl Eq for TxSelectorEntry<'_> {}
 
 // this struct is used to store transactions in a queue
-// and to order them by fees
-// Each Transactions is for a specific sender
+// and to order them by fee per byte
+// Each Transactions is for a specific sender and is never empty
 #[derive(PartialEq, Eq)]
 struct Transactions<'a>(VecDeque<TxSelectorEntry<'a>>);
 
+impl<'a> Transactions<'a> {
+    fn front(&self) -> &TxSelectorEntry<'a> {
+        self.0.front().expect("Transactions group is never empty")
+    }
+}
+
 impl PartialOrd for Transactions<'_> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.0.front().map(|e| e.tx.get_fee()).partial_cmp(&other.0.front().map(|e| e.tx.get_fee()))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Transactions<'_> {
+    // Highest fee per byte first, then highest fee
+    // The lowest hash wins in case of equality to always select the same TXs
     fn cmp(&self, other: &Self) -> Ordering {
-        self.0.front().map(|e| e.tx.get_fee()).cmp(&other.0.front().map(|e| e.tx.get_fee()))
+        let (a, b) = (self.front(), other.front());
+        compare_fee_rate(a.fee, a.size, b.fee, b.size)
+            .then_with(|| a.fee.cmp(&b.fee))
+            .then_with(|| b.hash.cmp(a.hash))
     }
 }
 
 // TX selector is used to select transactions from the mempool
 // It create sub groups of transactions by sender and order them by nonces
-// It joins all sub groups in a queue that is ordered by fees
+// It joins all sub groups in a queue that is ordered by the fee per byte of their next TX
+// A TX is only selected if it fits in the remaining block size
 pub struct TxSelector<'a> {
-    queue: BinaryHeap<Transactions<'a>>
+    queue: BinaryHeap<Transactions<'a>>,
+    // Bytes still available in the block
+    available_size: usize,
+    // Last nonce selected for each sender
+    nonces: HashMap<&'a PublicKey, u64>,
+    // Senders for which no TX can be selected anymore
+    rejected: HashSet<&'a PublicKey>
 }
 
 impl<'a> TxSelector<'a> {
-    // Create a TxSelector from a list of groups
-    pub fn grouped<I>(groups: I) -> Self
+    // Create a TxSelector from a list of groups, one per sender
+    pub fn grouped<I>(groups: I, available_size: usize) -> Self
     where
         I: Iterator<Item = Vec<TxSelectorEntry<'a>>>
     {
         let mut queue = BinaryHeap::new();
 
-        // push every group to the queue
-        for group in groups {
+        // push every group ordered by nonces to the queue
+        for mut group in groups.filter(|group| !group.is_empty()) {
+            // Best fee per byte first for the same nonce
+            group.sort_by(|a, b| a.nonce.cmp(&b.nonce)
+                .then_with(|| compare_fee_rate(b.fee, b.size, a.fee, a.size))
+                .then_with(|| a.hash.cmp(b.hash))
+            );
             queue.push(Transactions(VecDeque::from(group)));
         }
 
         Self {
-            queue
+            queue,
+            available_size,
+            nonces: HashMap::new(),
+            rejected: HashSet::new()
         }
     }
 
     // Create a TxSelector from a list of transactions with their hash and size
-    pub fn new<I>(iter: I) -> Self
+    pub fn new<I>(iter: I, available_size: usize) -> Self
     where
-        I: Iterator<Item = (usize, &'a Arc<Hash>, &'a Arc<Transaction>)>
+        I: Iterator<Item = TxSelectorEntry<'a>>
     {
         let mut groups: HashMap<&PublicKey, Vec<TxSelectorEntry>> = HashMap::new();
 
         // Create groups of transactions
-        for (size, hash, tx) in iter {
-            let entry = TxSelectorEntry {
-                hash,
-                tx,
-                size
-            };
-
-            match groups.entry(tx.get_source()) {
+        for entry in iter {
+            match groups.entry(entry.source) {
                 Entry::Occupied(mut e) => {
                     e.get_mut().push(entry);
                 },
@@ -102,26 +148,193 @@ impl<'a> TxSelector<'a> {
             }
         }
 
-        // Order each group by nonces and push it to the queue
-        let iter = groups.into_iter().map(|(_, mut v)| {
-            v.sort_by(|a, b| a.tx.get_nonce().cmp(&b.tx.get_nonce()));
-            v
-        });
-        Self::grouped(iter)
+        Self::grouped(groups.into_values(), available_size)
     }
 
-    // Get the next transaction with the highest fee
+    // Get the next transaction with the highest fee per byte
+    // Its sender must have all its previous nonces selected before
     pub fn next(&mut self) -> Option<TxSelectorEntry<'a>> {
-        // get the group with the highest fee
-        let mut group = self.queue.pop()?;
-        // get the entry with the highest fee from this group
-        let entry = group.0.pop_front()?;
-
-        // if its not empty, push it back to the queue
-        if !group.0.is_empty() {
-            self.queue.push(group);
+        // get the group with the highest fee per byte
+        while let Some(mut group) = self.queue.pop() {
+            let entry = *group.front();
+            if self.rejected.contains(entry.source) {
+                continue;
+            }
+
+            let last_nonce = self.nonces.get(entry.source).copied();
+            // Same nonce as a TX already selected, both can't be in the block
+            if last_nonce.is_some_and(|nonce| entry.nonce <= nonce) {
+                group.0.pop_front();
+                if !group.0.is_empty() {
+                    self.queue.push(group);
+                }
+                continue;
+            }
+
+            // A nonce is missing, no TX of this sender can be selected anymore
+            // Same if the TX doesn't fit, next nonces depend on it
+            if last_nonce.is_some_and(|nonce| entry.nonce != nonce + 1) || entry.block_size() > self.available_size {
+                continue;
+            }
+
+            group.0.pop_front();
+            self.available_size -= entry.block_size();
+            self.nonces.insert(entry.source, entry.nonce);
+
+            // if its not empty, push it back to the queue
+            if !group.0.is_empty() {
+                self.queue.push(group);
+            }
+
+            return Some(entry)
+        }
+
+        None
+    }
+
+    // The TX selected can't be included in the block
+    // Its size is available again and the next TXs of its sender are not selected
+    pub fn reject(&mut self, entry: &TxSelectorEntry<'a>) {
+        self.available_size += entry.block_size();
+        self.rejected.insert(entry.source);
+    }
+
+    // Bytes still available in the block
+    pub fn get_available_size(&self) -> usize {
+        self.available_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use super::*;
+
+    struct Snapshot {
+        hashes: Vec<Arc<Hash>>,
+        sources: Vec<PublicKey>
+    }
+
+    impl Snapshot {
+        fn new(txs: usize, sources: usize) -> Self {
+            Self {
+                hashes: (0..txs).map(|i| Arc::new(Hash::new([i as u8; 32]))).collect(),
+                sources: (0..sources).map(|_| KeyPair::new().get_public_key().compress()).collect()
+            }
+        }
+
+        // TX `index` from sender `source`
+        fn entry(&self, index: usize, source: usize, nonce: u64, fee: u64, size: usize) -> TxSelectorEntry<'_> {
+            TxSelectorEntry { hash: &self.hashes[index], source: &self.sources[source], nonce, fee, size }
         }
+    }
+
+    // Selection done by the block template when every TX selected is valid
+    // The rejection of the invalid ones is tested with the block template itself
+    fn select_transactions<'a, I>(groups: I, available_size: usize) -> Vec<TxSelectorEntry<'a>>
+    where
+        I: Iterator<Item = Vec<TxSelectorEntry<'a>>>
+    {
+        let mut selector = TxSelector::grouped(groups, available_size);
+        std::iter::from_fn(|| selector.next()).collect()
+    }
+
+        fn hashes(entries: Vec<TxSelectorEntry>) -> Vec<u8> {
+        entries.into_iter().map(|entry| entry.hash.as_bytes()[0]).collect()
+    }
 
-        Some(entry)
+    #[test]
+    fn test_select_by_fee_per_byte() {
+        let snapshot = Snapshot::new(3, 3);
+        let groups = vec![
+            vec![snapshot.entry(0, 0, 0, 1_000, 1_000)],
+            // Highest fee but lowest fee per byte
+            vec![snapshot.entry(1, 1, 0, 5_000, 10_000)],
+            vec![snapshot.entry(2, 2, 0, 800, 200)]
+        ];
+
+        let selected = select_transactions(groups.into_iter(), usize::MAX);
+        assert_eq!(hashes(selected), vec![2, 0, 1]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_nonce_chain_dependency() {
+        let snapshot = Snapshot::new(4, 2);
+        let groups = vec![
+            // Nonce 7 pays a lot but requires nonce 6 first
+            vec![snapshot.entry(1, 0, 7, 100_000, 100), snapshot.entry(0, 0, 6, 100, 100)],
+            vec![snapshot.entry(2, 1, 0, 1_000, 100), snapshot.entry(3, 1, 1, 500, 100)]
+        ];
+
+        let selected = select_transactions(groups.into_iter(), usize::MAX);
+        assert_eq!(hashes(selected), vec![2, 3, 0, 1]);
+
+        // Missing nonce, only the TXs before the gap can be selected
+        let groups = vec![vec![snapshot.entry(0, 0, 6, 100, 100), snapshot.entry(1, 0, 8, 100_000, 100)]];
+        assert_eq!(hashes(select_transactions(groups.into_iter(), usize::MAX)), vec![0]);
+    }
+
+    #[test]
+    fn test_size_packing() {
+        let snapshot = Snapshot::new(4, 3);
+        let cost = |size: usize| size + HASH_SIZE;
+        let groups = vec![
+            vec![snapshot.entry(0, 0, 0, 10_000, 1_000)],
+            // Doesn't fit after the first one, its next nonce can't be selected
+            vec![snapshot.entry(1, 1, 0, 5_000, 1_000), snapshot.entry(2, 1, 1, 1_000, 10)],
+            // Smaller TX still fits
+            vec![snapshot.entry(3, 2, 0, 100, 100)]
+        ];
+
+        let available_size = cost(1_000) + cost(500);
+        let mut selector = TxSelector::grouped(groups.into_iter(), available_size);
+        assert_eq!(selector.next().map(|e| e.hash.as_bytes()[0]), Some(0));
+        assert_eq!(selector.next().map(|e| e.hash.as_bytes()[0]), Some(3));
+        assert!(selector.next().is_none());
+        assert_eq!(selector.get_available_size(), cost(500) - cost(100));
+    }
+
+    #[test]
+    fn test_conflicting_nonces() {
+        let snapshot = Snapshot::new(3, 1);
+        // Two TXs with the same nonce, only the best one is selected
+        let groups = vec![vec![
+            snapshot.entry(0, 0, 0, 100, 100),
+            snapshot.entry(1, 0, 0, 200, 100),
+            snapshot.entry(2, 0, 1, 100, 100)
+        ]];
+
+        let selected = select_transactions(groups.into_iter(), usize::MAX);
+        assert_eq!(hashes(selected), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_reject() {
+        let snapshot = Snapshot::new(3, 2);
+        let groups = vec![
+            vec![snapshot.entry(0, 0, 0, 1_000, 100), snapshot.entry(1, 0, 1, 1_000, 100)],
+            vec![snapshot.entry(2, 1, 0, 100, 100)]
+        ];
+
+        let mut selector = TxSelector::grouped(groups.into_iter(), usize::MAX - 1_000);
+        let first = selector.next().unwrap();
+        assert_eq!(first.hash.as_bytes()[0], 0);
+        selector.reject(&first);
+        assert_eq!(selector.get_available_size(), usize::MAX - 1_000);
+
+        // Next nonce of a rejected sender is never selected
+        assert_eq!(selector.next().map(|e| e.hash.as_bytes()[0]), Some(2));
+        assert!(selector.next().is_none());
+    }
+
+    #[test]
+    fn test_deterministic_order() {
+        let snapshot = Snapshot::new(4, 4);
+        let entries = |order: [usize; 4]| order.iter().map(|i| vec![snapshot.entry(*i, *i, 0, 1_000, 100)]).collect::<Vec<_>>();
+
+        let first = hashes(select_transactions(entries([0, 1, 2, 3]).into_iter(), usize::MAX));
+        let second = hashes(select_transactions(entries([3, 1, 0, 2]).into_iter(), usize::MAX));
+        assert_eq!(first, vec![0, 1, 2, 3]);
+        assert_eq!(first, second);
+    }
+}
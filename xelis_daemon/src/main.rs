@@ -2,6 +2,9 @@ pub mod rpc;
 pub mod p2p;
 pub mod core;
 pub mod config;
+pub mod net;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 use config::{DEV_PUBLIC_KEY, STABLE_LIMIT};
 use fern::colors::Color;
@@ -35,6 +38,7 @@ use xelis_common::{
             Arg,
             ArgType
         },
+        logger::ModuleLogLevel,
         LogLevel,
         ShareablePrompt
     },
@@ -61,14 +65,16 @@ use crate::{
     },
     config::{
         BLOCK_TIME_MILLIS,
+        BLOCK_TIME_DRIFT_WINDOW,
         MILLIS_PER_SECOND
     }
 };
-use core::blockdag;
+use core::{backup, blockdag, bootstrap};
 use std::{
     fs::File,
     io::Write,
     net::{IpAddr, SocketAddr},
+    path::PathBuf,
     sync::Arc,
     time::Duration
 };
@@ -87,6 +93,18 @@ pub struct NodeConfig {
     /// Set log level
     #[clap(long, value_enum, default_value_t = LogLevel::Info)]
     log_level: LogLevel,
+    /// Set the log level for specific modules
+    /// Format is module=level, multiple modules can be separated by a comma.
+    /// Example: --log-module p2p=trace,storage=warn
+    #[clap(long, value_delimiter = ',')]
+    log_module: Vec<ModuleLogLevel>,
+    /// Maximum size in bytes of the log file before rotating it
+    /// If set, the log file is rotated based on its size instead of its date.
+    #[clap(long)]
+    file_log_max_size: Option<u64>,
+    /// Maximum number of rotated log files to keep
+    #[clap(long, default_value_t = 5)]
+    file_log_max_files: usize,
     /// Disable the log file
     #[clap(long)]
     disable_file_logging: bool,
@@ -101,6 +119,15 @@ pub struct NodeConfig {
     /// You will not be able to write CLI commands in it or to have an updated prompt
     #[clap(long)]
     disable_interactive_mode: bool,
+    /// Execute a command and exit instead of starting the interactive prompt
+    /// 
+    /// Can be set several times, commands are executed in order until one fails.
+    /// Console logs are written to stderr so only the commands output is on stdout.
+    #[clap(long)]
+    exec: Vec<String>,
+    /// Print the output of each executed command as a JSON object
+    #[clap(long)]
+    json: bool,
     /// Log filename
     /// 
     /// By default filename is xelis-daemon.log.
@@ -125,7 +152,9 @@ const BLOCK_TIME: Difficulty = Difficulty::from_u64(BLOCK_TIME_MILLIS / MILLIS_P
 async fn main() -> Result<()> {
     let mut config: NodeConfig = NodeConfig::parse();
 
-    let prompt = Prompt::new(config.log_level, &config.logs_path, &config.filename_log, config.disable_file_logging, config.disable_file_log_date_based, config.disable_log_color, !config.disable_interactive_mode)?;
+    // Commands to execute without the interactive prompt
+    let exec_mode = !config.exec.is_empty();
+    let prompt = Prompt::new(config.log_level, config.log_module.clone(), &config.logs_path, &config.filename_log, config.disable_file_logging, config.disable_file_log_date_based, config.file_log_max_size, config.file_log_max_files, config.disable_log_color, !config.disable_interactive_mode && !exec_mode, exec_mode)?;
     info!("XELIS Blockchain running version: {}", VERSION);
     info!("----------------------------------------------");
 
@@ -152,16 +181,30 @@ async fn main() -> Result<()> {
         SledStorage::new(dir_path, use_cache, config.network)?
     };
 
-    let blockchain = Blockchain::new(blockchain_config, config.network, storage).await?;
-    if let Err(e) = run_prompt(prompt, blockchain.clone(), config.network).await {
+    // Prompt is only used to confirm actions if it is interactive
+    let interactive_prompt = (!config.disable_interactive_mode && !exec_mode).then(|| prompt.clone());
+    let blockchain = Blockchain::new(blockchain_config, config.network, storage, interactive_prompt).await?;
+    let res = run_prompt(prompt, blockchain.clone(), config.network, &config.exec, config.json).await;
+    if let (Err(e), false) = (&res, exec_mode) {
         error!("Error while running prompt: {}", e);
     }
 
     blockchain.stop().await;
+
+    // The command error was already printed
+    if let (Err(e), true) = (res, exec_mode) {
+        let code = match e {
+            PromptError::CommandError(e) => e.exit_code(),
+            _ => 1
+        };
+        std::process::exit(code);
+    }
+
     Ok(())
 }
 
-async fn run_prompt<S: Storage>(prompt: ShareablePrompt, blockchain: Arc<Blockchain<S>>, network: Network) -> Result<(), PromptError> {
+// Run the interactive prompt, or only the commands to execute if any
+async fn run_prompt<S: Storage>(prompt: ShareablePrompt, blockchain: Arc<Blockchain<S>>, network: Network, exec: &[String], json: bool) -> Result<(), PromptError> {
     let mut context = Context::default();
     context.store(blockchain.clone());
 
@@ -175,24 +218,31 @@ async fn run_prompt<S: Storage>(prompt: ShareablePrompt, blockchain: Arc<Blockch
     command_manager.add_command(Command::with_arguments("show_balance", "Show balance of an address", vec![], vec![Arg::new("history", ArgType::Number)], CommandHandler::Async(async_handler!(show_balance::<S>))))?;
     command_manager.add_command(Command::with_required_arguments("print_block", "Print block in json format", vec![Arg::new("hash", ArgType::Hash)], CommandHandler::Async(async_handler!(print_block::<S>))))?;
     command_manager.add_command(Command::new("top_block", "Print top block", CommandHandler::Async(async_handler!(top_block::<S>))))?;
-    command_manager.add_command(Command::with_required_arguments("pop_blocks", "Delete last N blocks", vec![Arg::new("amount", ArgType::Number)], CommandHandler::Async(async_handler!(pop_blocks::<S>))))?;
+    command_manager.add_command(Command::with_arguments("pop_blocks", "Delete last N blocks", vec![Arg::new("amount", ArgType::Number)], vec![Arg::new("force", ArgType::Bool)], CommandHandler::Async(async_handler!(pop_blocks::<S>))))?;
     command_manager.add_command(Command::new("clear_mempool", "Clear all transactions in mempool", CommandHandler::Async(async_handler!(clear_mempool::<S>))))?;
     command_manager.add_command(Command::with_arguments("add_tx", "Add a TX in hex format in mempool", vec![Arg::new("hex", ArgType::String)], vec![Arg::new("broadcast", ArgType::Bool)], CommandHandler::Async(async_handler!(add_tx::<S>))))?;
     command_manager.add_command(Command::with_required_arguments("prune_chain", "Prune the chain until the specified topoheight", vec![Arg::new("topoheight", ArgType::Number)], CommandHandler::Async(async_handler!(prune_chain::<S>))))?;
     command_manager.add_command(Command::new("status", "Current daemon status", CommandHandler::Async(async_handler!(status::<S>))))?;
     command_manager.add_command(Command::with_optional_arguments("blacklist", "View blacklist or add a peer address in it", vec![Arg::new("address", ArgType::String)], CommandHandler::Async(async_handler!(blacklist::<S>))))?;
     command_manager.add_command(Command::with_optional_arguments("whitelist", "View whitelist or add a peer address in it", vec![Arg::new("address", ArgType::String)], CommandHandler::Async(async_handler!(whitelist::<S>))))?;
-    command_manager.add_command(Command::with_optional_arguments("verify_chain", "Check chain supply", vec![Arg::new("topoheight", ArgType::Number)], CommandHandler::Async(async_handler!(verify_chain::<S>))))?;
+    command_manager.add_command(Command::with_optional_arguments("verify_chain", "Check chain consistency and supply", vec![Arg::new("topoheight", ArgType::Number)], CommandHandler::Async(async_handler!(verify_chain::<S>))))?;
     command_manager.add_command(Command::with_required_arguments("kick_peer", "Kick a peer using its ip:port", vec![Arg::new("address", ArgType::String)], CommandHandler::Async(async_handler!(kick_peer::<S>))))?;
     command_manager.add_command(Command::new("clear_caches", "Clear storage caches", CommandHandler::Async(async_handler!(clear_caches::<S>))))?;
     command_manager.add_command(Command::new("clear_rpc_connections", "Clear all WS connections from RPC", CommandHandler::Async(async_handler!(clear_rpc_connections::<S>))))?;
     command_manager.add_command(Command::new("clear_p2p_connections", "Clear all P2P connections", CommandHandler::Async(async_handler!(clear_p2p_connections::<S>))))?;
     command_manager.add_command(Command::new("clear_p2p_peerlist", "Clear P2P peerlist", CommandHandler::Async(async_handler!(clear_p2p_peerlist::<S>))))?;
     command_manager.add_command(Command::with_optional_arguments("difficulty_dataset", "Create a dataset for difficulty from chain", vec![Arg::new("output", ArgType::String)], CommandHandler::Async(async_handler!(difficulty_dataset::<S>))))?;
+    command_manager.add_command(Command::with_arguments("export_chain", "Export the chain to a file to bootstrap new nodes", vec![Arg::new("path", ArgType::String)], vec![Arg::new("until_topoheight", ArgType::Number)], CommandHandler::Async(async_handler!(export_chain::<S>))))?;
+    command_manager.add_command(Command::with_optional_arguments("backup_now", "Backup the chain until the stable topoheight", vec![Arg::new("dir", ArgType::String)], CommandHandler::Async(async_handler!(backup_now::<S>))))?;
     command_manager.add_command(Command::with_optional_arguments("mine_block", "Mine a block on testnet", vec![Arg::new("count", ArgType::Number)], CommandHandler::Async(async_handler!(mine_block::<S>))))?;
     command_manager.add_command(Command::new("p2p_outgoing_connections", "Accept/refuse to connect to outgoing nodes", CommandHandler::Async(async_handler!(p2p_outgoing_connections::<S>))))?;
     command_manager.add_command(Command::with_required_arguments("add_peer", "Connect to a new peer using ip:port format", vec![Arg::new("address", ArgType::String)], CommandHandler::Async(async_handler!(add_peer::<S>))))?;
 
+    if !exec.is_empty() {
+        command_manager.execute_commands(exec, json).await?;
+        return Ok(())
+    }
+
 
     // Don't keep the lock for ever
     let (p2p, getwork) = {
@@ -322,6 +372,11 @@ async fn verify_chain<S: Storage>(manager: &CommandManager, mut args: ArgumentMa
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
 
     let storage = blockchain.get_storage().read().await;
+    if storage.has_pending_rewind().context("Error while checking pending rewind")? {
+        manager.error("A rewind was interrupted and is not completed, restart the node to complete it");
+        return Ok(())
+    }
+
     let mut pruned_topoheight = storage.get_pruned_topoheight().await.context("Error on pruned topoheight")?.unwrap_or(0);
     let mut expected_supply = if pruned_topoheight > 0 {
         let supply = storage.get_supply_at_topo_height(pruned_topoheight).await.context("Error while retrieving starting expected supply")?;
@@ -339,6 +394,13 @@ async fn verify_chain<S: Storage>(manager: &CommandManager, mut args: ArgumentMa
 
     for topo in pruned_topoheight..=topoheight {
         let hash_at_topo = storage.get_hash_at_topo_height(topo).await.context("Error while retrieving hash at topo")?;
+        // Verify the topoheight pointers are consistent
+        let block_topoheight = storage.get_topo_height_for_hash(&hash_at_topo).await.context("Error while retrieving topoheight for hash")?;
+        if block_topoheight != topo || !storage.has_block_with_hash(&hash_at_topo).await.context("Error while checking block")? {
+            manager.error(format!("Block {} at topoheight {} is inconsistent, its topoheight is {}", hash_at_topo, topo, block_topoheight));
+            return Ok(())
+        }
+
         let block_reward = if pruned_topoheight == 0 || topo - pruned_topoheight > STABLE_LIMIT {
             let block_reward = blockchain.get_block_reward(&*storage, &hash_at_topo, expected_supply, topo).await.context("Error while calculating block reward")?;
             let expected_block_reward = storage.get_block_reward_at_topo_height(topo).context("Error while retrieving block reward")?;
@@ -355,7 +417,7 @@ async fn verify_chain<S: Storage>(manager: &CommandManager, mut args: ArgumentMa
         };
 
         let supply = storage.get_supply_at_topo_height(topo).await.context("Error while retrieving supply at topoheight")?;
-        expected_supply += block_reward;
+        expected_supply = expected_supply.checked_add(block_reward).context("Supply is overflowing")?;
 
         // Verify the supply at block
         if supply != expected_supply {
@@ -363,7 +425,7 @@ async fn verify_chain<S: Storage>(manager: &CommandManager, mut args: ArgumentMa
             return Ok(())
         }
     }
-    manager.message("Supply is valid");
+    manager.message("Chain and supply are valid");
 
     Ok(())
 }
@@ -533,9 +595,16 @@ async fn pop_blocks<S: Storage>(manager: &CommandManager, mut arguments: Argumen
         return Err(anyhow::anyhow!("Invalid amount of blocks to pop").into());
     }
 
+    let force = if arguments.has_argument("force") {
+        arguments.get_value("force")?.to_bool()?
+    } else {
+        false
+    };
+
     info!("Trying to pop {} blocks from chain...", amount);
-    let topoheight = blockchain.rewind_chain(amount, false).await.context("Error while rewinding chain")?;
-    info!("Chain as been rewinded until topoheight {}", topoheight);
+    let summary = blockchain.pop_blocks(amount, force).await.context("Error while rewinding chain")?;
+    info!("Chain as been rewinded until topoheight {}", summary.topoheight);
+    manager.message(format!("Removed {} blocks, {} transactions added back to mempool, {} transactions orphaned", summary.removed_blocks, summary.txs_reinjected, summary.txs_orphaned));
 
     Ok(())
 }
@@ -617,6 +686,18 @@ async fn status<S: Storage>(manager: &CommandManager, _: ArgumentManager) -> Res
     manager.message(format!("Top block hash: {}", top_block_hash));
     manager.message(format!("Average Block Time: {:.2}s", avg_block_time as f64 / MILLIS_PER_SECOND as f64));
     manager.message(format!("Target Block Time: {:.2}s", BLOCK_TIME_MILLIS as f64 / MILLIS_PER_SECOND as f64));
+    let block_time_drift = blockchain.get_block_time_drift().await;
+    if let Some(average) = block_time_drift.average {
+        manager.message(format!("Rolling Block Time ({} blocks): {:.2}s", BLOCK_TIME_DRIFT_WINDOW, average as f64 / MILLIS_PER_SECOND as f64));
+    }
+    if block_time_drift.drifting {
+        manager.warn("Block time is drifting from the target for more than an hour!");
+    }
+    let (network_time_offset, clock_skewed) = blockchain.get_network_time_offset().await;
+    manager.message(format!("Network Time Offset: {}ms", network_time_offset));
+    if clock_skewed {
+        manager.warn("System clock deviates from the network time more than the offset can correct!");
+    }
     manager.message(format!("Current Supply: {} XELIS", format_xelis(supply)));
     manager.message(format!("Current Block Reward: {} XELIS", format_xelis(get_block_reward(supply))));
     manager.message(format!("Stored accounts/transactions/blocks/assets: {}/{}/{}/{}", accounts_count, transactions_count, blocks_count, assets));
@@ -632,6 +713,27 @@ async fn status<S: Storage>(manager: &CommandManager, _: ArgumentManager) -> Res
         manager.message("Chain is in full mode");
     }
 
+    if let Some(p2p) = blockchain.get_p2p().read().await.as_ref() {
+        let disconnect_reasons = p2p.get_peer_list().get_disconnect_reasons().await;
+        if !disconnect_reasons.is_empty() {
+            manager.message("Peers disconnected:");
+            for (reason, count) in disconnect_reasons {
+                manager.message(format!("- {}: {}", reason, count));
+            }
+        }
+
+        let (reconstructed, failed) = p2p.get_compact_blocks_stats();
+        if reconstructed + failed > 0 {
+            manager.message(format!("Compact blocks reconstructed: {}/{} ({:.2}%)", reconstructed, reconstructed + failed, reconstructed as f64 * 100f64 / (reconstructed + failed) as f64));
+        }
+    } else {
+        manager.message("P2p server is disabled");
+    }
+
+    if blockchain.get_rpc().read().await.is_none() {
+        manager.message("RPC server is disabled");
+    }
+
     let elapsed_seconds = manager.running_since().as_secs();
     let elapsed = format_duration(Duration::from_secs(elapsed_seconds)).to_string();
     manager.message(format!("Uptime: {}", elapsed));
@@ -814,6 +916,53 @@ async fn difficulty_dataset<S: Storage>(manager: &CommandManager, mut arguments:
     Ok(())
 }
 
+// Export the chain in a file that can be imported with --import-chain
+async fn export_chain<S: Storage>(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let path = arguments.get_value("path")?.to_string_value()?;
+    let until_topoheight = if arguments.has_argument("until_topoheight") {
+        Some(arguments.get_value("until_topoheight")?.to_number()?)
+    } else {
+        None
+    };
+
+    let context = manager.get_context().lock()?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+
+    manager.message(format!("Exporting chain to {}...", path));
+    match bootstrap::export_chain(blockchain, &path, until_topoheight).await {
+        Ok(count) => manager.message(format!("{} blocks exported to {}", count, path)),
+        Err(e) => manager.error(format!("Error while exporting chain: {}", e))
+    };
+
+    Ok(())
+}
+
+// Backup the chain in the backup directory configured or the one given
+async fn backup_now<S: Storage>(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let context = manager.get_context().lock()?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+
+    let dir = if arguments.has_argument("dir") {
+        PathBuf::from(arguments.get_value("dir")?.to_string_value()?)
+    } else {
+        match blockchain.get_backup_dir() {
+            Some(dir) => dir.clone(),
+            None => {
+                manager.error("No backup directory configured, use --backup-dir or give a directory");
+                return Ok(())
+            }
+        }
+    };
+
+    manager.message(format!("Backing up the chain to {}...", dir.display()));
+    match backup::backup_now(blockchain, &dir, blockchain.get_backup_retention()).await {
+        Ok((path, manifest)) => manager.message(format!("Chain backed up until topoheight {} ({}) to {}", manifest.topoheight, manifest.top_hash, path.display())),
+        Err(e) => manager.error(format!("Error while backing up the chain: {}", e))
+    };
+
+    Ok(())
+}
+
 // Mine a block
 async fn mine_block<S: Storage>(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
     let count = if arguments.has_argument("count") {
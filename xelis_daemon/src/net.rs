@@ -0,0 +1,80 @@
+use std::{
+    io,
+    net::{AddrParseError, SocketAddr, TcpListener}
+};
+use log::debug;
+use socket2::{Domain, Socket, Type};
+
+// Parse all the bind addresses configured
+pub fn parse_bind_addresses<S: AsRef<str>>(addresses: &[S]) -> Result<Vec<SocketAddr>, AddrParseError> {
+    addresses.iter()
+        .map(|address| address.as_ref().trim().parse())
+        .collect()
+}
+
+// Create a listener for one of the bind addresses configured
+// An IPv6 listener only accept IPv6 connections when an IPv4 listener is also set on its port,
+// otherwise both listeners would conflict on dual-stack systems
+pub fn bind_tcp_listener(address: &SocketAddr, bind_addresses: &[SocketAddr]) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(*address), Type::STREAM, None)?;
+    if address.is_ipv6() {
+        let only_v6 = bind_addresses.iter().any(|addr| addr.is_ipv4() && addr.port() == address.port());
+        debug!("Binding {} (IPv6 only: {})", address, only_v6);
+        socket.set_only_v6(only_v6)?;
+    }
+
+    socket.set_reuse_address(true)?;
+    socket.bind(&(*address).into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+
+    Ok(socket.into())
+}
+
+// IPv4 clients connected through a dual-stack listener are seen as IPv4-mapped IPv6 addresses
+// Convert them back so a peer is always known under the same address
+pub fn canonical_address(address: SocketAddr) -> SocketAddr {
+    SocketAddr::new(address.ip().to_canonical(), address.port())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr, TcpStream};
+    use super::*;
+
+    #[test]
+    fn test_bind_both_families_on_same_port() {
+        // Find a free port usable by both families
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let addresses = parse_bind_addresses(&[format!("127.0.0.1:{}", port), format!("[::1]:{}", port)]).unwrap();
+
+        let listeners = addresses.iter()
+            .map(|address| bind_tcp_listener(address, &addresses).unwrap())
+            .collect::<Vec<_>>();
+
+        for (listener, address) in listeners.iter().zip(addresses.iter()) {
+            listener.set_nonblocking(false).unwrap();
+            let stream = TcpStream::connect(address).unwrap();
+            let (_, remote) = listener.accept().unwrap();
+            assert_eq!(remote.is_ipv6(), address.is_ipv6());
+            assert_eq!(remote, stream.local_addr().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_canonical_address() {
+        let mapped = SocketAddr::new(Ipv4Addr::new(1, 2, 3, 4).to_ipv6_mapped().into(), 2125);
+        assert_eq!(canonical_address(mapped), SocketAddr::new(Ipv4Addr::new(1, 2, 3, 4).into(), 2125));
+
+        let v6 = SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 2125);
+        assert_eq!(canonical_address(v6), v6);
+    }
+
+    #[test]
+    fn test_parse_bind_addresses() {
+        let addresses = parse_bind_addresses(&["0.0.0.0:2125", " [::]:2125"]).unwrap();
+        assert!(addresses[0].is_ipv4());
+        assert!(addresses[1].is_ipv6());
+        assert!(parse_bind_addresses(&["[::]"]).is_err());
+    }
+}
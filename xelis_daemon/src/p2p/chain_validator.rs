@@ -3,31 +3,44 @@ use async_trait::async_trait;
 use indexmap::{IndexMap, IndexSet};
 use xelis_common::{
     block::BlockHeader,
-    config::TIPS_LIMIT,
     crypto::Hash,
     difficulty::{
         CumulativeDifficulty,
         Difficulty
     },
     immutable::Immutable,
-    time::TimestampMillis,
     varuint::VarUint
 };
-use crate::core::{
-    blockchain::Blockchain,
-    blockdag,
-    error::BlockchainError,
-    storage::{
-        BlocksAtHeightProvider,
-        DagOrderProvider,
-        DifficultyProvider,
-        MerkleHashProvider,
-        PrunedTopoheightProvider,
-        Storage
-    }
+use crate::{
+    config::MAX_TXS_PER_BLOCK,
+    core::{
+        blockchain::Blockchain,
+        error::BlockchainError,
+        storage::{
+            BlocksAtHeightProvider,
+            DagOrderProvider,
+            DifficultyProvider,
+            MerkleHashProvider,
+            PrunedTopoheightProvider,
+            Storage
+        }
+    },
+    p2p::error::P2pError
 };
 use log::{debug, trace};
 
+// Verify that a block header doesn't reference more txs than a block can contain
+// This prevent a peer to make us request a huge number of txs
+pub fn verify_txs_count(hash: &Hash, header: &BlockHeader) -> Result<(), P2pError> {
+    let txs_count = header.get_txs_count();
+    if txs_count > MAX_TXS_PER_BLOCK {
+        debug!("Block {} references {} txs while only {} are accepted", hash, txs_count, MAX_TXS_PER_BLOCK);
+        return Err(P2pError::TooManyTxsInHeader(hash.clone(), txs_count, MAX_TXS_PER_BLOCK))
+    }
+
+    Ok(())
+}
+
 // This struct is used to store the block data in the chain validator
 struct BlockData {
     header: Arc<BlockHeader>,
@@ -98,12 +111,16 @@ impl<'a, S: Storage> ChainValidator<'a, S> {
             return Err(BlockchainError::AlreadyInChain)
         }
 
+        verify_txs_count(&hash, &header)?;
+        // Same checks as the blocks added to the chain
+        self.blockchain.verify_block_header_format(&header, &hash).await?;
+
         let tips = header.get_tips();
         let tips_count = tips.len();
-        
-        // verify tips count
-        if tips_count == 0 || tips_count > TIPS_LIMIT {
-            debug!("Block {} contains {} tips while only {} is accepted", hash, tips_count, TIPS_LIMIT);
+
+        // Genesis block is never received
+        if tips_count == 0 {
+            debug!("Block {} has no tips", hash);
             return Err(BlockchainError::InvalidTipsCount(hash, tips_count))
         }
 
@@ -118,16 +135,10 @@ impl<'a, S: Storage> ChainValidator<'a, S> {
             }
         }
 
-        // Verify the block height by tips
-        {
-            let height_by_tips = blockdag::calculate_height_at_tips(self, header.get_tips().iter()).await?;
-            if height_by_tips != header.get_height() {
-                debug!("Block {} has height {} while expected height is {}", hash, header.get_height(), height_by_tips);
-                return Err(BlockchainError::InvalidBlockHeight(height_by_tips, header.get_height()))
-            }
-        }
+        // Verify the block height and timestamp against its tips
+        self.blockchain.verify_block_header_tips(self, &header, &hash).await?;
 
-        let pow_hash = header.get_pow_hash()?;
+        let pow_hash = self.blockchain.get_block_pow_hash(&header)?;
         trace!("POW hash: {}", pow_hash);
         let (difficulty, p) = self.blockchain.verify_proof_of_work(self, &pow_hash, tips.iter()).await?;
 
@@ -145,6 +156,16 @@ impl<'a, S: Storage> ChainValidator<'a, S> {
         Ok(())
     }
 
+    // Retrieve the height of the common ancestor with our chain
+    // and how many blocks it is below our current height
+    // The common ancestor is the block ordered right before the starting topoheight
+    pub async fn get_common_ancestor_depth(&self) -> Result<(u64, u64), BlockchainError> {
+        let storage = self.blockchain.get_storage().read().await;
+        let hash = storage.get_hash_at_topo_height(self.starting_topoheight.saturating_sub(1)).await?;
+        let height = storage.get_height_for_block_hash(&hash).await?;
+        Ok((height, self.blockchain.get_height().saturating_sub(height)))
+    }
+
     // Retrieve all blocks from the chain validator
     pub fn get_blocks(self) -> impl Iterator<Item = (Hash, Arc<BlockHeader>)> {
         self.blocks.into_iter().map(|(hash, data)| (hash, data.header))
@@ -162,7 +183,7 @@ impl<S: Storage> DifficultyProvider for ChainValidator<'_, S> {
         Ok(storage.get_height_for_block_hash(hash).await?)
     }
 
-    async fn get_timestamp_for_block_hash(&self, hash: &Hash) -> Result<TimestampMillis, BlockchainError> {
+    async fn get_timestamp_for_block_hash(&self, hash: &Hash) -> Result<u64, BlockchainError> {
         if let Some(data) = self.blocks.get(hash) {
             return Ok(data.header.get_timestamp())
         }
@@ -320,4 +341,55 @@ impl<S: Storage> MerkleHashProvider for ChainValidator<'_, S> {
     async fn set_balances_merkle_hash_at_topoheight(&mut self,  _: u64, _: &Hash) -> Result<(), BlockchainError> {
         Err(BlockchainError::UnsupportedOperation)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        block::EXTRA_NONCE_SIZE,
+        crypto::{Hashable, KeyPair},
+        serializer::Serializer
+    };
+    use super::*;
+
+    fn header_with_txs(txs_count: usize) -> BlockHeader {
+        let txs_hashes = (1..=txs_count as u64)
+            .map(|i| {
+                let mut bytes = [0u8; 32];
+                bytes[0..8].copy_from_slice(&i.to_be_bytes());
+                Hash::new(bytes)
+            })
+            .collect();
+
+        let miner = KeyPair::new().get_public_key().compress();
+        BlockHeader::new(0, 1, 0, IndexSet::from([Hash::zero()]), [0u8; EXTRA_NONCE_SIZE], miner, txs_hashes)
+    }
+
+    #[test]
+    fn test_header_with_too_many_txs() {
+        let header = header_with_txs(MAX_TXS_PER_BLOCK);
+        assert!(verify_txs_count(&header.hash(), &header).is_ok());
+
+        let header = header_with_txs(MAX_TXS_PER_BLOCK + 1);
+        assert!(matches!(
+            verify_txs_count(&header.hash(), &header),
+            Err(P2pError::TooManyTxsInHeader(_, count, MAX_TXS_PER_BLOCK)) if count == MAX_TXS_PER_BLOCK + 1
+        ));
+    }
+
+    #[test]
+    fn test_header_with_duplicated_txs() {
+        let header = header_with_txs(2);
+        let mut bytes = header.to_bytes();
+        assert!(BlockHeader::from_bytes(&bytes).is_ok());
+
+        // Replace the second tx hash by the first one
+        let txs: Vec<Hash> = header.get_txs_hashes().iter().cloned().collect();
+        let first = bytes.windows(32).position(|w| w == txs[0].as_bytes()).unwrap();
+        let second = bytes.windows(32).position(|w| w == txs[1].as_bytes()).unwrap();
+        let first_bytes = bytes[first..first + 32].to_vec();
+        bytes[second..second + 32].copy_from_slice(&first_bytes);
+
+        assert!(BlockHeader::from_bytes(&bytes).is_err());
+    }
+}
@@ -20,11 +20,8 @@ use std::{
 use human_bytes::human_bytes;
 use humantime::format_duration;
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::{
-        tcp::{OwnedReadHalf, OwnedWriteHalf},
-        TcpStream
-    },
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
     sync::Mutex,
     time::timeout
 };
@@ -37,6 +34,11 @@ use log::{debug, error, trace, warn};
 
 type P2pResult<T> = Result<T, P2pError>;
 
+// Halves of the stream used by a connection
+// It is a TCP stream for real peers, but any transport can be used (in-memory for tests)
+pub type ReadHalf = Box<dyn AsyncRead + Send + Unpin>;
+pub type WriteHalf = Box<dyn AsyncWrite + Send + Unpin>;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum State {
     Pending, // connection is new, no handshake received
@@ -51,9 +53,9 @@ pub struct Connection {
     // State of the connection
     state: State,
     // write to stream
-    write: Mutex<OwnedWriteHalf>,
+    write: Mutex<WriteHalf>,
     // read from stream
-    read: Mutex<OwnedReadHalf>,
+    read: Mutex<ReadHalf>,
     // TCP Address
     addr: SocketAddr,
     // total bytes read
@@ -80,6 +82,11 @@ const ROTATE_EVERY_N_BYTES: usize = 1024 * 1024 * 1024;
 impl Connection {
     pub fn new(stream: TcpStream, addr: SocketAddr, out: bool) -> Self {
         let (read, write) = stream.into_split();
+        Self::from_halves(Box::new(read), Box::new(write), addr, out)
+    }
+
+    // Create a connection over any transport
+    pub fn from_halves(read: ReadHalf, write: WriteHalf, addr: SocketAddr, out: bool) -> Self {
         Self {
             out,
             state: State::Pending,
@@ -198,7 +205,7 @@ impl Connection {
     // This function will send the packet to the peer without flushing the stream
     // Packet length is ALWAYS sent in raw (not encrypted)
     // Otherwise, we can't know how much bytes to read for each ciphertext/packet
-    async fn send_packet_bytes_internal(&self, stream: &mut OwnedWriteHalf, packet: &[u8]) -> P2pResult<()> {
+    async fn send_packet_bytes_internal(&self, stream: &mut WriteHalf, packet: &[u8]) -> P2pResult<()> {
         let packet_len = packet.len() as u32;
         stream.write_all(&packet_len.to_be_bytes()).await?;
         stream.write_all(packet).await?;
@@ -240,6 +247,13 @@ impl Connection {
         Ok(())
     }
 
+    // Send bytes to the peer with a deadline
+    // If the peer doesn't read its socket, our write buffer will be full and we will wait forever
+    pub async fn send_bytes_with_timeout(&self, packet: &[u8], duration: Duration) -> P2pResult<()> {
+        timeout(duration, self.send_bytes(packet)).await
+            .map_err(|_| P2pError::WriteTimeout(duration.as_millis() as u64))?
+    }
+
     // Read packet bytes from the stream
     pub async fn read_packet_bytes(&self, buf: &mut [u8], max_size: u32) -> P2pResult<Vec<u8>> {
         let mut stream = self.read.lock().await;
@@ -274,9 +288,19 @@ impl Connection {
         self.read_packet_from_bytes(&bytes).await
     }
 
+    // Read a packet with a deadline
+    // If no packet is received before it, the connection is considered as dead
+    // Raw bytes of the packet are returned along it so they can be reused without serializing it again
+    pub async fn read_packet_with_timeout(&self, buf: &mut [u8], max_size: u32, duration: Duration) -> P2pResult<(Packet<'static>, Bytes)> {
+        let bytes = timeout(duration, self.read_packet_bytes(buf, max_size)).await
+            .map_err(|_| P2pError::ReadTimeout(duration.as_millis() as u64))??;
+        let packet = self.read_packet_from_bytes(&bytes).await?;
+        Ok((packet, Bytes::from(bytes)))
+    }
+
     // Read the packet size, this is always sent in raw (not encrypted)
     // And packet size must be a u32 in big endian
-    async fn read_packet_size(&self, stream: &mut OwnedReadHalf, buf: &mut [u8], max_usize: u32) -> P2pResult<u32> {
+    async fn read_packet_size(&self, stream: &mut ReadHalf, buf: &mut [u8], max_usize: u32) -> P2pResult<u32> {
         let read = self.read_bytes_from_stream(stream, &mut buf[0..4]).await?;
         if read != 4 {
             if self.get_state() == State::Success {
@@ -300,7 +324,7 @@ impl Connection {
 
     // Read all bytes until the the buffer is full with the requested size
     // This support fragmented packets and encryption
-    async fn read_all_bytes(&self, stream: &mut OwnedReadHalf, buf: &mut [u8], mut left: u32) -> P2pResult<Vec<u8>> {
+    async fn read_all_bytes(&self, stream: &mut ReadHalf, buf: &mut [u8], mut left: u32) -> P2pResult<Vec<u8>> {
         let buf_size = buf.len() as u32;
         let mut bytes = Vec::new();
         while left > 0 {
@@ -326,7 +350,7 @@ impl Connection {
     // this function will wait until something is sent to the socket if it's in blocking mode
     // this return the size of data read & set in the buffer.
     // used to only lock one time the stream and read on it
-    async fn read_bytes_from_stream(&self, stream: &mut OwnedReadHalf, buf: &mut [u8]) -> P2pResult<usize> {
+    async fn read_bytes_from_stream(&self, stream: &mut ReadHalf, buf: &mut [u8]) -> P2pResult<usize> {
         let mut read = 0;
         let buf_len = buf.len();
         // Packet may have been fragmented, try to read it completely
@@ -415,4 +439,41 @@ impl Display for Connection {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::result::Result<(), Error> {
         write!(f, "Connection[state: {:?}, peer: {}, read: {}, sent: {}, key rotation (in/out): ({}/{}), connected since: {}, closed: {}]", self.state, self.get_address(), human_bytes(self.bytes_in() as f64), human_bytes(self.bytes_out() as f64), self.key_rotation_in(), self.key_rotation_out(), self.get_human_uptime(), self.is_closed())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use super::*;
+
+    // Create a connection with a remote side that is kept open but never used
+    async fn create_silent_connection() -> (Connection, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stream, remote) = tokio::join!(TcpStream::connect(addr), listener.accept());
+        (Connection::new(stream.unwrap(), addr, true), remote.unwrap().0)
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout_on_silent_peer() {
+        let (connection, _remote) = create_silent_connection().await;
+        let mut buf = [0u8; 1024];
+        let res = connection.read_packet_with_timeout(&mut buf, 1024, Duration::from_millis(100)).await;
+        assert!(matches!(res, Err(P2pError::ReadTimeout(100))));
+    }
+
+    #[tokio::test]
+    async fn test_write_timeout_on_silent_peer() {
+        let (connection, _remote) = create_silent_connection().await;
+        // Remote never read its socket, buffers will be full at some point
+        let packet = vec![0u8; 1024 * 1024];
+        let mut res = Ok(());
+        for _ in 0..1024 {
+            res = connection.send_bytes_with_timeout(&packet, Duration::from_millis(100)).await;
+            if res.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(res, Err(P2pError::WriteTimeout(100))));
+    }
+}
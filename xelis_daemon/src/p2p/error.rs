@@ -41,12 +41,16 @@ pub enum P2pError {
     InvalidMaxChainResponseSize,
     #[error("Invalid max peers, it must be greater than 0")]
     InvalidMaxPeers,
+    #[error("No bind address configured")]
+    NoBindAddress,
     #[error("Already closed")]
     AlreadyClosed,
     #[error("Incompatible with configured exclusive nodes")]
     ExclusiveNode,
     #[error("Address is not allowed to connect")]
     NotAllowed,
+    #[error("Address is not in the configured whitelist")]
+    NotWhitelisted,
     #[error("Peer list is full")]
     PeerListFull,
     #[error("Tracker request has expired, we didn't received a valid response in time")]
@@ -95,6 +99,12 @@ pub enum P2pError {
     InvalidCommonPoint(u64),
     #[error("Peer disconnected")]
     Disconnected,
+    #[error("No packet received from peer in {}ms", _0)]
+    ReadTimeout(u64),
+    #[error("Couldn't send packet to peer in {}ms", _0)]
+    WriteTimeout(u64),
+    #[error("Block {} references {} txs while maximum is {}", _0, _1, _2)]
+    TooManyTxsInHeader(Hash, usize, usize),
     #[error("Invalid handshake")]
     InvalidHandshake,
     #[error("Expected Handshake packet")]
@@ -107,6 +117,10 @@ pub enum P2pError {
     InvalidNetworkID,
     #[error("Peer id {} is already used!", _0)]
     PeerIdAlreadyUsed(u64),
+    #[error("Peer id {} is already connected with another connection", _0)]
+    DuplicateConnection(u64),
+    #[error("Connection to ourself")]
+    SelfConnection,
     #[error("Peer already connected: {}", _0)]
     PeerAlreadyConnected(SocketAddr),
     #[error(transparent)]
@@ -145,6 +159,12 @@ pub enum P2pError {
     ObjectHashNotPresentInQueue(Hash),
     #[error("Object requested {} already requested", _0)]
     ObjectAlreadyRequested(ObjectRequest),
+    #[error("Object requested {} is below the pruned topoheight of the peer", _0)]
+    ObjectPrunedByPeer(ObjectRequest),
+    #[error("Object request {} can't be tracked without a hash", _0)]
+    UntrackableObjectRequest(ObjectRequest),
+    #[error("Unexpected object response {} for request", _0)]
+    UnexpectedObjectResponse(ObjectRequest),
     #[error("Invalid object response for request, received hash: {}", _0)]
     InvalidObjectResponse(Hash),
     #[error("Invalid object response type for request")]
@@ -177,6 +197,14 @@ pub enum P2pError {
     EncryptionError(#[from] EncryptionError),
 }
 
+impl P2pError {
+    // We are already connected to this node (or it is ourself)
+    // It is not a failure of the remote address
+    pub fn is_duplicate_connection(&self) -> bool {
+        matches!(self, Self::DuplicateConnection(_) | Self::SelfConnection)
+    }
+}
+
 impl From<BlockchainError> for P2pError {
     fn from(err: BlockchainError) -> Self {
         Self::BlockchainError(Box::new(err))
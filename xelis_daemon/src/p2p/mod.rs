@@ -5,7 +5,13 @@ pub mod packet;
 pub mod peer_list;
 pub mod chain_validator;
 mod tracker;
+mod orphans;
+mod selection;
+pub mod topology;
 mod encryption;
+mod rate_limiter;
+mod verification_queue;
+mod request_scheduler;
 
 pub use encryption::EncryptionKey;
 
@@ -14,6 +20,7 @@ use lru::LruCache;
 use xelis_common::{
     account::VersionedNonce,
     api::daemon::{
+        DeepReorgRejectedEvent,
         Direction,
         NotifyEvent,
         PeerPeerDisconnectedEvent
@@ -23,13 +30,15 @@ use xelis_common::{
     crypto::{Hash, Hashable},
     difficulty::CumulativeDifficulty,
     immutable::Immutable,
+    network::Network,
     serializer::Serializer,
     thread_pool::ThreadPool,
     time::{
         get_current_time_in_millis,
         get_current_time_in_seconds,
-        TimestampMillis
+        TimestampSeconds
     },
+    transaction::Transaction,
     utils::spawn_task
 };
 use crate::{
@@ -41,15 +50,25 @@ use crate::{
         CHAIN_SYNC_TOP_BLOCKS, MILLIS_PER_SECOND, NETWORK_ID, P2P_AUTO_CONNECT_PRIORITY_NODES_DELAY,
         P2P_EXTEND_PEERLIST_DELAY, P2P_PING_DELAY, P2P_PING_PEER_LIST_DELAY, P2P_PING_PEER_LIST_LIMIT,
         PEER_FAIL_LIMIT, PEER_MAX_PACKET_SIZE, PEER_TIMEOUT_INIT_CONNECTION, PEER_TIMEOUT_INIT_OUTGOING_CONNECTION,
-        PRUNE_SAFETY_LIMIT, STABLE_LIMIT, P2P_PING_TIMEOUT, P2P_HEARTBEAT_INTERVAL, PEER_SEND_BYTES_TIMEOUT
+        PRUNE_SAFETY_LIMIT, STABLE_LIMIT, P2P_PING_TIMEOUT, P2P_HEARTBEAT_INTERVAL, PEER_SEND_BYTES_TIMEOUT,
+        P2P_READ_TIMEOUT, HEADERS_RANGE_MAX_COUNT, HEADERS_RANGE_MAX_SIZE, P2P_PROTOCOL_VERSION,
+        P2P_BLOCK_HINTS_PROTOCOL_VERSION, P2P_TOPOLOGY_PROTOCOL_VERSION, P2P_ORPHAN_BLOCKS_MAX_SIZE,
+        P2P_ORPHAN_BLOCK_MAX_AGE, P2P_ORPHAN_BLOCKS_CHECK_DELAY, P2P_BLOCK_AT_TOPOHEIGHT_PROTOCOL_VERSION,
+        P2P_OBJECT_REQUESTS_MAX_CONCURRENT, PEER_OBJECT_REQUESTS_MAX_CONCURRENT, PEER_OBJECT_REQUESTS_MAX_QUEUED,
+        PEER_OBJECT_REQUESTS_QUEUE_TIMEOUT, P2P_CHAIN_SYNC_TOKEN_PROTOCOL_VERSION, CHAIN_SYNC_PIPELINE_BATCH_BLOCKS,
+        P2P_TIMED_PING_PROTOCOL_VERSION
     },
     core::{
         blockchain::Blockchain,
         error::BlockchainError,
-        storage::Storage
+        headers,
+        reorg,
+        storage::Storage,
+        sync::get_topoheight_sync_batch
     },
+    net::{bind_tcp_listener, canonical_address, parse_bind_addresses},
     p2p::{
-        chain_validator::ChainValidator,
+        chain_validator::{self, ChainValidator},
         packet::{
             bootstrap_chain::{
                 BlockMetadata,
@@ -59,9 +78,11 @@ use crate::{
                 MAX_ITEMS_PER_PAGE
             },
             chain::CommonPoint,
+            compact_block::{CompactBlock, ShortIdsResolver},
             inventory::{
+                build_inventory_page,
+                is_inventory_page_allowed,
                 NotifyInventoryRequest,
-                NotifyInventoryResponse,
                 NOTIFY_MAX_LEN
             }
         },
@@ -73,16 +94,20 @@ use self::{
     connection::{Connection, State},
     error::P2pError,
     packet::{
-        chain::{BlockId, ChainRequest, ChainResponse},
-        handshake::Handshake,
-        object::{ObjectRequest, ObjectResponse, OwnedObjectResponse},
-        ping::Ping,
+        chain::{BlockId, ChainContinuationRequest, ChainRequest, ChainResponse, ChainSyncToken},
+        handshake::{sanitize_peer_string, Handshake},
+        object::{sort_blocks_by_hints, BlockHints, ObjectRequest, ObjectResponse, OwnedObjectResponse},
+        ping::{Ping, TimedPing},
         Packet,
         PacketWrapper
     },
-    peer::{Peer, TaskState, Rx},
+    orphans::OrphanPool,
+    peer::{DisconnectReason, PageCursor, Peer, TaskState, Rx},
     peer_list::{PeerList, SharedPeerList},
-    tracker::{ObjectTracker, SharedObjectTracker}
+    selection::{select_sync_candidates, SyncCandidates},
+    topology::PingTopology,
+    tracker::{ObjectTracker, SharedObjectTracker},
+    request_scheduler::RequestScheduler
 };
 use tokio::{
     io::AsyncWriteExt,
@@ -104,12 +129,12 @@ use tokio::{
 use log::{info, warn, error, debug, trace};
 use std::{
     borrow::Cow,
-    collections::{hash_map::Entry, HashSet},
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
     io,
     net::{IpAddr, SocketAddr},
     num::NonZeroUsize,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc
     },
     time::Duration
@@ -127,8 +152,8 @@ pub struct P2pServer<S: Storage> {
     tag: Option<String>,
     // max peers accepted by this server
     max_peers: usize,
-    // ip:port address to receive connections
-    bind_address: SocketAddr,
+    // ip:port addresses to receive connections
+    bind_addresses: Vec<SocketAddr>,
     // all peers accepted
     peer_list: SharedPeerList,
     // reference to the chain to add blocks/txs
@@ -141,8 +166,13 @@ pub struct P2pServer<S: Storage> {
     is_running: AtomicBool,
     // Synced cache to prevent concurrent tasks adding the block
     blocks_propagation_queue: Mutex<LruCache<Hash, ()>>,
+    // Raw bytes of the block headers received from peers
+    // They are reused when relaying the block
+    blocks_header_bytes: Mutex<LruCache<Hash, Bytes>>,
     // Sender for the blocks processing task to have a ordered queue
     blocks_processor: Sender<(Arc<Peer>, BlockHeader, Hash)>,
+    // Blocks propagated before their parents, with the peer that sent them
+    orphan_blocks: Mutex<OrphanPool<(Arc<Peer>, BlockHeader)>>,
     // allow fast syncing (only balances / assets / Smart Contracts changes)
     // without syncing the history
     allow_fast_sync_mode: bool,
@@ -157,9 +187,29 @@ pub struct P2pServer<S: Storage> {
     // Configured exclusive nodes
     // If not empty, no other peer than those listed can connect to this node
     exclusive_nodes: IndexSet<SocketAddr>,
+    // Configured whitelisted peers
+    // If not empty, we only connect and accept connections from these IPs
+    // and the peers shared by others are ignored
+    whitelist: IndexSet<SocketAddr>,
     // Are we allowing others nodes to share us as a potential peer ?
     // Also if we allows to be listed in get_peers RPC API
     sharable: bool,
+    // Do we exchange our mempool inventory with new peers
+    mempool_sync: bool,
+    // Do we propagate and receive blocks as compact blocks
+    compact_blocks: bool,
+    // Do we share our connections count and tag in the ping packets with peer list
+    share_topology: bool,
+    // Do we evict a recent inbound peer when the peerlist is full to accept a new one
+    peer_eviction: bool,
+    // Do we download the next blocks while adding the previous ones during the chain sync
+    sync_pipelining: bool,
+    // Object requests served to our peers
+    object_requests: RequestScheduler,
+    // Compact blocks received that we could rebuild from our mempool
+    compact_blocks_reconstructed: AtomicU64,
+    // Compact blocks received that required to request the full header
+    compact_blocks_failed: AtomicU64,
     // Do we try to connect to others nodes
     // If this is enabled, only way to have peers is to let them connect to us
     outgoing_connections_disabled: AtomicBool,
@@ -170,8 +220,9 @@ pub struct P2pServer<S: Storage> {
 }
 
 impl<S: Storage> P2pServer<S> {
-    pub fn new(concurrency: usize, dir_path: Option<String>, tag: Option<String>, max_peers: usize, bind_address: String, blockchain: Arc<Blockchain<S>>, use_peerlist: bool, exclusive_nodes: Vec<SocketAddr>, allow_fast_sync_mode: bool, allow_boost_sync_mode: bool, max_chain_response_size: Option<usize>, sharable: bool, disable_outgoing_connections: bool) -> Result<Arc<Self>, P2pError> {
-        if tag.as_ref().is_some_and(|tag| tag.len() == 0 || tag.len() > 16) {
+    pub fn new(concurrency: usize, dir_path: Option<String>, tag: Option<String>, max_peers: usize, bind_addresses: Vec<String>, blockchain: Arc<Blockchain<S>>, use_peerlist: bool, exclusive_nodes: Vec<SocketAddr>, whitelist: Vec<SocketAddr>, allow_fast_sync_mode: bool, allow_boost_sync_mode: bool, max_chain_response_size: Option<usize>, sharable: bool, disable_outgoing_connections: bool, mempool_sync: bool, compact_blocks: bool, share_topology: bool, peer_eviction: bool, sync_pipelining: bool) -> Result<Arc<Self>, P2pError> {
+        // Peers would strip the non-printable characters of our tag
+        if tag.as_ref().is_some_and(|tag| tag.len() == 0 || tag.len() > Handshake::MAX_LEN || sanitize_peer_string(tag) != *tag) {
             return Err(P2pError::InvalidTag);
         }
 
@@ -186,7 +237,11 @@ impl<S: Storage> P2pServer<S> {
         // set channel to communicate with listener thread
         let mut rng = rand::thread_rng();
         let peer_id: u64 = rng.gen(); // generate a random peer id for network
-        let addr: SocketAddr = bind_address.parse()?; // parse the bind address
+        let bind_addresses = parse_bind_addresses(&bind_addresses)?;
+        if bind_addresses.is_empty() {
+            return Err(P2pError::NoBindAddress);
+        }
+
         // create mspc channel for connections to peers
         let (connections_sender, connections_receiver) = mpsc::channel(max_peers);
         let (blocks_processor, blocks_processor_receiver) = mpsc::channel(TIPS_LIMIT * STABLE_LIMIT as usize);
@@ -203,19 +258,30 @@ impl<S: Storage> P2pServer<S> {
             peer_id,
             tag,
             max_peers,
-            bind_address: addr,
+            bind_addresses,
             peer_list,
             blockchain,
             connections_sender,
             object_tracker,
             is_running: AtomicBool::new(true),
             blocks_propagation_queue: Mutex::new(LruCache::new(NonZeroUsize::new(STABLE_LIMIT as usize * TIPS_LIMIT).unwrap())),
+            blocks_header_bytes: Mutex::new(LruCache::new(NonZeroUsize::new(STABLE_LIMIT as usize * TIPS_LIMIT).unwrap())),
             blocks_processor,
+            orphan_blocks: Mutex::new(OrphanPool::new(P2P_ORPHAN_BLOCKS_MAX_SIZE, P2P_ORPHAN_BLOCK_MAX_AGE)),
             allow_fast_sync_mode,
             allow_boost_sync_mode,
             max_chain_response_size: max_chain_response_size.unwrap_or(CHAIN_SYNC_DEFAULT_RESPONSE_BLOCKS),
             exclusive_nodes: IndexSet::from_iter(exclusive_nodes.into_iter()),
+            whitelist: IndexSet::from_iter(whitelist.into_iter()),
             sharable,
+            mempool_sync,
+            compact_blocks,
+            share_topology,
+            peer_eviction,
+            sync_pipelining,
+            object_requests: RequestScheduler::new(P2P_OBJECT_REQUESTS_MAX_CONCURRENT, PEER_OBJECT_REQUESTS_MAX_CONCURRENT, PEER_OBJECT_REQUESTS_MAX_QUEUED),
+            compact_blocks_reconstructed: AtomicU64::new(0),
+            compact_blocks_failed: AtomicU64::new(0),
             is_syncing: AtomicBool::new(false),
             outgoing_connections_disabled: AtomicBool::new(disable_outgoing_connections),
             exit_sender,
@@ -305,14 +371,55 @@ impl<S: Storage> P2pServer<S> {
         Ok(())
     }
 
+    // every 5 seconds, reconnect to all the whitelisted peers we are not connected to
+    // they are connected as priority nodes, even if we have reached the peers limit
+    async fn maintains_connection_to_whitelist(self: &Arc<Self>, sender: Sender<SocketAddr>) -> Result<(), P2pError> {
+        debug!("Starting maintains whitelisted peers task...");
+        let mut interval = interval(Duration::from_secs(P2P_AUTO_CONNECT_PRIORITY_NODES_DELAY));
+        let mut exit_receiver = self.exit_sender.subscribe();
+        loop {
+            select! {
+                biased;
+                _ = exit_receiver.recv() => {
+                    debug!("Received exit message, exiting maintains whitelisted peers task");
+                    break;
+                },
+                _ = interval.tick() => {
+                    if !self.is_running() {
+                        debug!("Maintains whitelisted peers task is stopped!");
+                        break;
+                    }
+
+                    for addr in self.whitelist.iter() {
+                        if self.peer_list.is_connected_to_addr(addr).await {
+                            continue;
+                        }
+
+                        trace!("Trying to reconnect to whitelisted peer: {}", addr);
+                        if let Err(e) = sender.send(*addr).await {
+                            error!("Error while sending whitelisted peer to connect: {}", e);
+                        }
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+
     // connect to seed nodes, start p2p server
     // and wait on all new connections
     async fn start(self: &Arc<Self>, receiver: Receiver<(SocketAddr, bool)>, blocks_processor_receiver: Receiver<(Arc<Peer>, BlockHeader, Hash)>, event_receiver: Receiver<Arc<Peer>>, use_peerlist: bool, concurrency: usize) -> Result<(), P2pError> {
-        let listener = TcpListener::bind(self.get_bind_address()).await?;
-        info!("P2p Server will listen on: {}", self.get_bind_address());
+        let mut listeners = Vec::with_capacity(self.bind_addresses.len());
+        for address in self.bind_addresses.iter() {
+            let listener = TcpListener::from_std(bind_tcp_listener(address, &self.bind_addresses)?)?;
+            info!("P2p Server will listen on: {}", address);
+            listeners.push((*address, listener));
+        }
 
         let mut exclusive_nodes = self.exclusive_nodes.clone();
-        if exclusive_nodes.is_empty() {
+        // Seed nodes are not whitelisted, we can't connect to them in whitelist mode
+        if exclusive_nodes.is_empty() && self.whitelist.is_empty() {
             debug!("No exclusive nodes available, using seed nodes...");
             let network = self.blockchain.get_network();
             let seed_nodes = get_seed_nodes(&network);
@@ -320,14 +427,27 @@ impl<S: Storage> P2pServer<S> {
         }
 
         let (priority_sender, priority_connections) = mpsc::channel(1);
-        // create tokio task to maintains connection to exclusive nodes or seed nodes
-        let zelf = Arc::clone(self);
-        spawn_task("p2p-maintain-nodes", async move {
-            info!("Connecting to seed nodes...");
-            if let Err(e) = zelf.maintains_connection_to_nodes(exclusive_nodes, priority_sender).await {
-                error!("Error while maintening connection with seed nodes: {}", e);
-            };
-        });
+        if !self.whitelist.is_empty() {
+            // create tokio task to reconnect to whitelisted peers as priority nodes
+            let zelf = Arc::clone(self);
+            let sender = priority_sender.clone();
+            spawn_task("p2p-maintain-whitelist", async move {
+                if let Err(e) = zelf.maintains_connection_to_whitelist(sender).await {
+                    error!("Error while maintening connection with whitelisted peers: {}", e);
+                };
+            });
+        }
+
+        if !exclusive_nodes.is_empty() {
+            // create tokio task to maintains connection to exclusive nodes or seed nodes
+            let zelf = Arc::clone(self);
+            spawn_task("p2p-maintain-nodes", async move {
+                info!("Connecting to seed nodes...");
+                if let Err(e) = zelf.maintains_connection_to_nodes(exclusive_nodes, priority_sender).await {
+                    error!("Error while maintening connection with seed nodes: {}", e);
+                };
+            });
+        }
 
         // start a new task for chain sync
         spawn_task("p2p-chain-sync", Arc::clone(&self).chain_sync_loop());
@@ -349,7 +469,13 @@ impl<S: Storage> P2pServer<S> {
 
         let (tx, mut rx) = channel(1);
         spawn_task("p2p-outgoing-connections", Arc::clone(&self).handle_outgoing_connections(priority_connections, receiver, tx.clone()));
-        spawn_task("p2p-incoming-connections", Arc::clone(&self).handle_incoming_connections(listener, tx, concurrency));
+
+        // Each listener has its own accept loop, all feeding the same incoming connections task
+        let (incoming_sender, incoming_receiver) = channel(concurrency.max(1));
+        for (address, listener) in listeners {
+            spawn_task(format!("p2p-listener-{}", address), Arc::clone(&self).accept_connections(listener, incoming_sender.clone()));
+        }
+        spawn_task("p2p-incoming-connections", Arc::clone(&self).handle_incoming_connections(incoming_receiver, tx, concurrency));
 
         let mut exit_receiver = self.exit_sender.subscribe();
         loop {
@@ -370,17 +496,30 @@ impl<S: Storage> P2pServer<S> {
                         let peer = Arc::new(peer);
                         match self.handle_new_peer(&peer, rx).await {
                             Ok(_) => {},
-                            Err(e) => match e {
-                                P2pError::PeerListFull => {
-                                    debug!("Peer list is full, we can't accept new connections");
-                                    if let Err(e) = peer.get_connection().close().await {
-                                        debug!("Error while closing unhandled connection: {}", e);
-                                    }
-                                },
-                                _ => {
-                                    error!("Error while handling new connection: {}", e);
-                                    if let Err(e) = peer.get_connection().close().await {
-                                        debug!("Error while closing unhandled connection: {}", e);
+                            Err(e) => {
+                                // Its clock was sampled during the handshake
+                                self.remove_peer_time_sample(&peer).await;
+                                match e {
+                                    P2pError::PeerListFull => {
+                                        debug!("Peer list is full, we can't accept new connections");
+                                        if let Err(e) = peer.get_connection().close().await {
+                                            debug!("Error while closing unhandled connection: {}", e);
+                                        }
+                                    },
+                                    P2pError::DuplicateConnection(_) | P2pError::SelfConnection => {
+                                        debug!("Closing connection with {}: {}", peer, e);
+                                        if let P2pError::DuplicateConnection(_) = e {
+                                            self.peer_list.add_disconnect_reason(DisconnectReason::DuplicateConnection).await;
+                                        }
+                                        if let Err(e) = peer.get_connection().close().await {
+                                            debug!("Error while closing unhandled connection: {}", e);
+                                        }
+                                    },
+                                    _ => {
+                                        error!("Error while handling new connection: {}", e);
+                                        if let Err(e) = peer.get_connection().close().await {
+                                            debug!("Error while closing unhandled connection: {}", e);
+                                        }
                                     }
                                 }
                             }
@@ -462,7 +601,8 @@ impl<S: Storage> P2pServer<S> {
                 Ok(handshake) => handshake,
                 Err(e) => {
                     debug!("Error while verifying connection to address {}: {}", addr, e);
-                    if !priority {
+                    // Not a failure of this address, we are already connected to this node
+                    if !priority && !e.is_duplicate_connection() {
                         self.peer_list.increase_fail_count_for_stored_peer(&addr.ip(), false).await;
                     }
                     continue;
@@ -487,8 +627,9 @@ impl<S: Storage> P2pServer<S> {
 
         // Verify if we can accept new connections
         let reject = !self.is_compatible_with_exclusive_nodes(&addr)
+            || !self.is_whitelisted_ip(&addr.ip())
             // check that this incoming peer isn't blacklisted
-            || !self.accept_new_connections().await
+            || !self.can_accept_inbound_connection().await
             || !self.peer_list.is_allowed(&addr.ip()).await
             || self.is_connected_to_addr(&addr).await;
 
@@ -512,7 +653,9 @@ impl<S: Storage> P2pServer<S> {
                 },
                 Err(e) => {
                     debug!("Error while handling incoming connection {}: {}", addr, e);
-                    zelf.peer_list.increase_fail_count_for_stored_peer(&addr.ip(), true).await;
+                    if !e.is_duplicate_connection() {
+                        zelf.peer_list.increase_fail_count_for_stored_peer(&addr.ip(), true).await;
+                    }
                 }
             };
         }).await?;
@@ -520,10 +663,35 @@ impl<S: Storage> P2pServer<S> {
         Ok(())
     }
 
+    // Accept all connections of a listener and forward them to the incoming connections task
+    async fn accept_connections(self: Arc<Self>, listener: TcpListener, sender: Sender<io::Result<(TcpStream, SocketAddr)>>) {
+        let mut exit_receiver = self.exit_sender.subscribe();
+        loop {
+            select! {
+                _ = exit_receiver.recv() => {
+                    debug!("Received exit message, exiting listener task");
+                    break;
+                }
+                res = listener.accept() => {
+                    trace!("New listener result received (is err: {})", res.is_err());
+                    if !self.is_running() {
+                        break;
+                    }
+
+                    let res = res.map(|(stream, addr)| (stream, canonical_address(addr)));
+                    if sender.send(res).await.is_err() {
+                        debug!("Incoming connections channel is closed, exiting listener task");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
     // This task will handle all incoming connections requests
     // Based on the concurrency set, it will create a thread pool to handle requests and wait when
     // a worker is free to accept a new connection
-    async fn handle_incoming_connections(self: Arc<Self>, listener: TcpListener, tx: Sender<(Peer, Rx)>, concurrency: usize) {
+    async fn handle_incoming_connections(self: Arc<Self>, mut receiver: Receiver<io::Result<(TcpStream, SocketAddr)>>, tx: Sender<(Peer, Rx)>, concurrency: usize) {
         let mut thread_pool = ThreadPool::new(concurrency);
         let mut exit_receiver = self.exit_sender.subscribe();
         loop {
@@ -532,8 +700,11 @@ impl<S: Storage> P2pServer<S> {
                     debug!("Received exit message, exiting incoming connections task");
                     break;
                 }
-                res = listener.accept() => {
-                    trace!("New listener result received (is err: {})", res.is_err());
+                res = receiver.recv() => {
+                    let Some(res) = res else {
+                        debug!("All listeners are closed, exiting incoming connections task");
+                        break;
+                    };
 
                     if !self.is_running() {
                         break;
@@ -564,11 +735,22 @@ impl<S: Storage> P2pServer<S> {
             return Err(P2pError::InvalidNetworkID);
         }
 
-        if self.has_peer_id_used(&handshake.get_peer_id()).await {
-            return Err(P2pError::PeerIdAlreadyUsed(handshake.get_peer_id()));
+        let peer_id = handshake.get_peer_id();
+        if self.is_internal_id(peer_id) {
+            debug!("{} is ourself, closing it", connection);
+            return Err(P2pError::SelfConnection);
+        }
+
+        // The connection with the same peer is replaced only if this one is preferred
+        if self.peer_list.has_peer(&peer_id).await && !self.is_preferred_connection(connection.is_out(), peer_id) {
+            return Err(P2pError::DuplicateConnection(peer_id));
         }
 
-        if *handshake.get_block_genesis_hash() != *get_genesis_block_hash(self.blockchain.get_network()) {
+        let genesis_hash = {
+            let storage = self.blockchain.get_storage().read().await;
+            self.get_genesis_hash(&*storage).await?
+        };
+        if *handshake.get_block_genesis_hash() != genesis_hash {
             debug!("Invalid genesis block hash {}", handshake.get_block_genesis_hash());
             return Err(P2pError::InvalidHandshake)
         }
@@ -584,16 +766,26 @@ impl<S: Storage> P2pServer<S> {
         Ok(())
     }
 
+    // Genesis block hash shared by all the peers of our network
+    // Dev network has no fixed genesis block, the one of our chain is used
+    async fn get_genesis_hash(&self, storage: &S) -> Result<Hash, P2pError> {
+        match self.blockchain.get_network() {
+            Network::Dev => Ok(storage.get_hash_at_topo_height(0).await?),
+            network => Ok(get_genesis_block_hash(network).clone())
+        }
+    }
+
     // Build a handshake packet
     // We feed the packet with all chain data
-    async fn build_handshake(&self) -> Result<Vec<u8>, P2pError> {
+    // The local port is the one we listen on for the address family of the connection
+    async fn build_handshake(&self, local_port: u16) -> Result<Vec<u8>, P2pError> {
         let storage = self.blockchain.get_storage().read().await;
         let (block, top_hash) = storage.get_top_block_header().await?;
         let topoheight = self.blockchain.get_topo_height();
         let pruned_topoheight = storage.get_pruned_topoheight().await?;
         let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(&top_hash).await.unwrap_or_else(|_| CumulativeDifficulty::zero());
-        let genesis_block = get_genesis_block_hash(self.blockchain.get_network());
-        let handshake = Handshake::new(Cow::Owned(VERSION.to_owned()), *self.blockchain.get_network(), Cow::Borrowed(self.get_tag()), Cow::Borrowed(&NETWORK_ID), self.get_peer_id(), self.bind_address.port(), get_current_time_in_seconds(), topoheight, block.get_height(), pruned_topoheight, Cow::Borrowed(&top_hash), Cow::Borrowed(genesis_block), Cow::Borrowed(&cumulative_difficulty), self.sharable);
+        let genesis_block = self.get_genesis_hash(&*storage).await?;
+        let handshake = Handshake::new(Cow::Owned(VERSION.to_owned()), *self.blockchain.get_network(), Cow::Borrowed(self.get_tag()), Cow::Borrowed(&NETWORK_ID), self.get_peer_id(), local_port, get_current_time_in_seconds(), topoheight, block.get_height(), pruned_topoheight, Cow::Borrowed(&top_hash), Cow::Borrowed(&genesis_block), Cow::Borrowed(&cumulative_difficulty), self.sharable, self.mempool_sync, self.compact_blocks, P2P_PROTOCOL_VERSION);
         Ok(Packet::Handshake(Cow::Owned(handshake)).to_bytes())
     }
 
@@ -608,6 +800,10 @@ impl<S: Storage> P2pServer<S> {
             }
         };
 
+        // Peer time is used to adjust our clock if it is skewed
+        // It is removed if the peer is not accepted or once disconnected
+        self.blockchain.add_peer_time_sample(handshake.get_peer_id(), handshake.get_utc_time()).await;
+
         let (peer, rx) = handshake.create_peer(connection, priority, self.peer_list.clone());
         Ok((peer, rx))
     }
@@ -635,7 +831,13 @@ impl<S: Storage> P2pServer<S> {
         };
 
         trace!("received handshake packet!");
-        self.verify_handshake(connection, &mut handshake).await?;
+        if let Err(e) = self.verify_handshake(connection, &mut handshake).await {
+            // Send it back so the other side also detects it and doesn't count it as a failure
+            if e.is_duplicate_connection() && !connection.is_out() {
+                self.send_handshake(&connection).await?;
+            }
+            return Err(e)
+        }
 
         trace!("Handshake has been verified");
         // if it's a outgoing connection, don't send the handshake back
@@ -651,21 +853,55 @@ impl<S: Storage> P2pServer<S> {
         Ok(handshake)
     }
 
+    // Stop using the clock of a peer, unless it is still connected with another connection
+    async fn remove_peer_time_sample(&self, peer: &Peer) {
+        if self.peer_list.get_peer(&peer.get_id()).await.is_none() {
+            self.blockchain.remove_peer_time_sample(peer.get_id()).await;
+        }
+    }
+
     async fn handle_new_peer(self: &Arc<Self>, peer: &Arc<Peer>, rx: Rx) -> Result<(), P2pError> {
         // we can save the peer in our peerlist
         let peer_id = peer.get_id(); // keep in memory the peer_id outside connection (because of moved value)
         if self.is_internal_id(peer_id) {
-            return Err(P2pError::PeerIdAlreadyUsed(peer_id));
+            return Err(P2pError::SelfConnection);
+        }
+
+        // Both nodes may have dialed each other at the same time, keep only one connection
+        if let Some(existing) = self.peer_list.get_peer(&peer_id).await {
+            if !self.is_preferred_connection(peer.get_connection().is_out(), peer_id) {
+                return Err(P2pError::DuplicateConnection(peer_id));
+            }
+
+            debug!("Replacing {} by {}", existing, peer);
+            if let Err(e) = existing.close_with_reason(DisconnectReason::DuplicateConnection).await {
+                debug!("Error while closing duplicated connection {}: {}", existing, e);
+            }
+        }
+
+        // make room for this inbound peer by evicting a newer one
+        if self.peer_eviction && !peer.get_connection().is_out() && !self.accept_new_connections().await {
+            if let Some(evicted) = self.peer_list.find_peer_to_evict().await {
+                debug!("Evicting {} to accept {}", evicted, peer);
+                if let Err(e) = evicted.close_with_reason(DisconnectReason::Evicted).await {
+                    debug!("Error while evicting {}: {}", evicted, e);
+                }
+            }
         }
 
         self.peer_list.add_peer(peer, self.get_max_peers()).await?;
 
+        // Both sides send their mempool inventory to the other, accept the one of this peer
+        if self.mempool_sync && peer.is_mempool_sync_enabled() {
+            peer.set_mempool_inventory_expected(true);
+        }
+
         if peer.sharable() {
             trace!("Locking RPC Server to notify PeerConnected event");
             if let Some(rpc) = self.blockchain.get_rpc().read().await.as_ref() {
                 if rpc.is_event_tracked(&NotifyEvent::PeerConnected).await {
                     debug!("Notifying clients with PeerConnected event");
-                    rpc.notify_clients_with(&NotifyEvent::PeerConnected, get_peer_entry(&peer).await).await;
+                    rpc.notify_clients_with(&NotifyEvent::PeerConnected, get_peer_entry(&peer, self.blockchain.hide_peer_ips()).await).await;
                 }
             }
             trace!("End locking for PeerConnected event");
@@ -679,6 +915,28 @@ impl<S: Storage> P2pServer<S> {
         self.exclusive_nodes.is_empty() || self.exclusive_nodes.contains(addr)
     }
 
+    // Verify that we don't have any whitelist configured OR that this IP is part of it
+    pub fn is_whitelisted_ip(&self, ip: &IpAddr) -> bool {
+        self.whitelist.is_empty() || self.whitelist.iter().any(|addr| addr.ip() == *ip)
+    }
+
+    // Store the addresses shared by a peer as potential peers
+    // In whitelist mode, we only connect to the whitelisted peers and ignore them
+    pub async fn store_shared_peers(&self, addresses: &IndexSet<SocketAddr>) {
+        if !self.whitelist.is_empty() {
+            trace!("Ignoring {} shared peers in whitelist mode", addresses.len());
+            return;
+        }
+
+        for addr in addresses {
+            if !self.is_connected_to_addr(addr).await && self.peer_list.has_peer_stored(&addr.ip()).await {
+                if !self.peer_list.store_peer_address(*addr).await {
+                    debug!("{} already stored in peer list", addr);
+                }
+            }
+        }
+    }
+
     // Connect to a specific peer address
     // Buffer is passed in parameter to prevent the re-allocation each time
     // No check is done, this is done at the moment of the connection
@@ -694,6 +952,31 @@ impl<S: Storage> P2pServer<S> {
         }
     }
 
+    // Accept a connection established over any transport like it was from our listener
+    // This is used to connect nodes running in the same process without any socket
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn accept_connection(self: &Arc<Self>, connection: Connection, priority: bool) -> Result<(), P2pError> {
+        let addr = connection.get_address();
+        if !self.is_whitelisted_ip(&addr.ip()) {
+            debug!("Rejecting connection from {}, not whitelisted", addr);
+            return Err(P2pError::NotWhitelisted);
+        }
+
+        let mut buf = [0; 512];
+        let (peer, rx) = self.create_verified_peer(&mut buf, connection, priority).await?;
+        let peer = Arc::new(peer);
+        if let Err(e) = self.handle_new_peer(&peer, rx).await {
+            debug!("Error while handling new connection {}: {}", peer, e);
+            self.remove_peer_time_sample(&peer).await;
+            if let Err(e) = peer.get_connection().close().await {
+                debug!("Error while closing unhandled connection: {}", e);
+            }
+            return Err(e)
+        }
+
+        Ok(())
+    }
+
     // Connect to a new peer using its socket address
     // Then we send him a handshake
     async fn connect_to_peer(&self, addr: SocketAddr) -> Result<Connection, P2pError> {
@@ -704,6 +987,11 @@ impl<S: Storage> P2pServer<S> {
             return Err(P2pError::ExclusiveNode);
         }
 
+        if !self.is_whitelisted_ip(&addr.ip()) {
+            debug!("Not in whitelist: {}, skipping", addr);
+            return Err(P2pError::NotWhitelisted);
+        }
+
         {
             trace!("peer list locked for trying to connect to peer {}", addr);
             if self.is_connected_to_addr(&addr).await {
@@ -726,42 +1014,16 @@ impl<S: Storage> P2pServer<S> {
     // Handsake is sent only once, when we connect to a new peer, and we get it back from connection to make it a peer
     async fn send_handshake(&self, connection: &Connection) -> Result<(), P2pError> {
         trace!("Sending handshake to {}", connection);
-        let handshake = self.build_handshake().await?;
+        let handshake = self.build_handshake(self.get_local_port_for(connection.get_address())).await?;
         connection.send_bytes(&handshake).await
     }
 
     // build a ping packet with the current state of the blockchain
-    // if a peer is given, we will check and update the peers list
-    async fn build_generic_ping_packet_with_storage(&self, storage: &S) -> Ping<'_> {
-        let (cumulative_difficulty, block_top_hash, pruned_topoheight) = {
-            let pruned_topoheight = match storage.get_pruned_topoheight().await {
-                Ok(pruned_topoheight) => pruned_topoheight,
-                Err(e) => {
-                    error!("Couldn't get the pruned topoheight from storage for generic ping packet: {}", e);
-                    None
-                }
-            };
-
-            match storage.get_top_block_hash().await {
-                Err(e) => {
-                    error!("Couldn't get the top block hash from storage for generic ping packet: {}", e);
-                    (CumulativeDifficulty::zero(), get_genesis_block_hash(self.blockchain.get_network()).clone(), pruned_topoheight)
-                },
-                Ok(hash) => (storage.get_cumulative_difficulty_for_block_hash(&hash).await.unwrap_or_else(|_| CumulativeDifficulty::zero()), hash, pruned_topoheight)
-            }
-        };
-        let highest_topo_height = self.blockchain.get_topo_height();
-        let highest_height = self.blockchain.get_height();
+    // the whole state is read from the blockchain cache, the storage is never locked
+    fn build_generic_ping_packet(&self) -> Ping<'_> {
+        let top_state = self.blockchain.get_top_state();
         let new_peers = IndexSet::new();
-        Ping::new(Cow::Owned(block_top_hash), highest_topo_height, highest_height, pruned_topoheight, cumulative_difficulty, new_peers)
-    }
-
-    // Build a generic ping packet
-    // This will lock the storage for us
-    async fn build_generic_ping_packet(&self) -> Ping<'_> {
-        let storage = self.blockchain.get_storage().read().await;
-        debug!("locking storage to build generic ping packet");
-        self.build_generic_ping_packet_with_storage(&*storage).await
+        Ping::new(Cow::Owned(top_state.hash), top_state.topoheight, top_state.height, top_state.pruned_topoheight, top_state.cumulative_difficulty, new_peers)
     }
 
     // select a random peer which is greater than us to sync chain
@@ -770,7 +1032,7 @@ impl<S: Storage> P2pServer<S> {
     // Cumulative difficulty is used in case two chains are running at same speed
     // We must determine which one has the most work done
     // if we are not in fast sync mode, we must verify its pruned topoheight to be sure
-    // he have the blocks we need
+    // he have the blocks we need, pruned peers above our topoheight are skipped for archive ones
     async fn select_random_best_peer(&self, fast_sync: bool, previous_peer: Option<&(Arc<Peer>, bool)>) -> Result<Option<Arc<Peer>>, BlockchainError> {
         trace!("select random best peer");
         
@@ -790,8 +1052,7 @@ impl<S: Storage> P2pServer<S> {
         // search for peers which are greater than us
         // and that are pruned but before our height so we can sync correctly
         let available_peers = self.peer_list.get_cloned_peers().await;
-        // IndexSet is used to select by random index
-        let mut peers: IndexSet<Arc<Peer>> = IndexSet::with_capacity(available_peers.len());
+        let mut candidates = Vec::with_capacity(available_peers.len());
 
         for p in available_peers {
             // Avoid selecting peers that have a weaker cumulative difficulty than us
@@ -816,23 +1077,35 @@ impl<S: Storage> P2pServer<S> {
                         continue;
                     }
                 }
-            } else {
-                // check that the pruned topoheight is less than our topoheight to sync
-                // so we can sync chain from pruned chains
-                if let Some(pruned_topoheight) = p.get_pruned_topoheight() {
-                    if pruned_topoheight > our_topoheight {
-                        continue;
-                    }
-                }
             }
 
             if !(p.get_height() > our_height || peer_topoheight > our_topoheight) {
                 continue;
             }
 
-            peers.insert(p);
+            // In fast sync, the pruned topoheight was already verified against the safety limit
+            let pruned_topoheight = if fast_sync {
+                None
+            } else {
+                p.get_pruned_topoheight()
+            };
+            candidates.push((p, pruned_topoheight));
         }
 
+        // check that the pruned topoheight is less than our topoheight to sync
+        // so we can sync chain from pruned chains
+        // IndexSet is used to select by random index
+        let mut peers: IndexSet<Arc<Peer>> = match select_sync_candidates(candidates, our_topoheight) {
+            SyncCandidates::Available(peers) => peers.into_iter().collect(),
+            SyncCandidates::NoArchivalPeer => {
+                warn!("No archival peer available to sync from topoheight {}, all better peers are pruned above it", our_topoheight);
+                // Don't penalize them, we just need another peer having the blocks
+                self.connect_to_new_peer().await;
+                return Ok(None)
+            },
+            SyncCandidates::Empty => return Ok(None)
+        };
+
         // Try to not reuse the same peer between each sync
         if let Some((previous_peer, err)) = previous_peer {
             if peers.len() > 1 || (*err && !previous_peer.is_priority()) {
@@ -973,7 +1246,7 @@ impl<S: Storage> P2pServer<S> {
                 break;
             }
 
-            let mut ping = self.build_generic_ping_packet().await;
+            let mut ping = self.build_generic_ping_packet();
             trace!("generic ping packet finished");
 
             // Get all connected peers
@@ -982,68 +1255,7 @@ impl<S: Storage> P2pServer<S> {
             let current_time = get_current_time_in_seconds();
             // check if its time to send our peerlist
             if current_time > last_peerlist_update + P2P_PING_PEER_LIST_DELAY {
-                trace!("Sending ping packet with peerlist...");
-                for peer in all_peers.iter() {
-                    let new_peers = ping.get_mut_peers();
-                    new_peers.clear();
-
-                    if peer.get_connection().is_closed() {
-                        debug!("{} is closed, skipping ping packet", peer);
-                        continue;
-                    }
-
-                    // Is it a peer from our local network
-                    let is_local_peer = is_local_address(peer.get_connection().get_address());
-
-                    // all the peers we already shared with this peer
-                    let mut shared_peers = peer.get_peers().lock().await;
-
-                    // iterate through our peerlist to determinate which peers we have to send
-                    for p in all_peers.iter() {
-                        // don't send him itself
-                        // and don't share a peer that don't want to be shared
-                        if p.get_id() == peer.get_id() || !p.sharable() {
-                            continue;
-                        }
-
-                        // if we haven't send him this peer addr and that he don't have him already, insert it
-                        let addr = p.get_outgoing_address();
-
-                        // Don't share local network addresses if it's external peer
-                        if is_local_address(addr) && !is_local_peer {
-                            debug!("{} is a local address but peer is external, skipping", addr);
-                            continue;
-                        }
-
-                        let send = match shared_peers.entry(*addr) {
-                            Entry::Occupied(mut e) => e.get_mut().update(Direction::Out),
-                            Entry::Vacant(e) => {
-                                e.insert(Direction::Out);
-                                true
-                            }
-                        };
-
-                        if send {
-                            // add it in our side to not re send it again
-                            trace!("{} didn't received {} yet, adding it to peerlist in ping packet", peer.get_outgoing_address(), addr);
-
-                            // add it to new list to send it
-                            new_peers.insert(*addr);
-                            if new_peers.len() >= P2P_PING_PEER_LIST_LIMIT {
-                                break;
-                            }
-                        }
-                    }
-
-                    // update the ping packet with the new peers
-                    debug!("Set peers: {:?}, going to {}", new_peers, peer.get_outgoing_address());
-                    // send the ping packet to the peer
-                    if let Err(e) = peer.send_packet(Packet::Ping(Cow::Borrowed(&ping))).await {
-                        debug!("Error sending specific ping packet to {}: {}", peer, e);
-                    } else {
-                        peer.set_last_ping_sent(current_time);
-                    }
-                }
+                self.send_ping_with_peer_list(&mut ping, &all_peers, current_time).await;
 
                 // update the last time we sent our peerlist
                 // We don't use previous current_time variable because it may have been
@@ -1051,13 +1263,20 @@ impl<S: Storage> P2pServer<S> {
                 last_peerlist_update = get_current_time_in_seconds();
             } else {
                 trace!("Sending generic ping packet...");
-                let packet = Packet::Ping(Cow::Owned(ping));
-                let bytes = Bytes::from(packet.to_bytes());
+                // Peers supporting it receive our time with the ping
+                let timed_bytes = Bytes::from(Packet::TimedPing(TimedPing::new(current_time, Cow::Borrowed(&ping))).to_bytes());
+                let bytes = Bytes::from(Packet::Ping(Cow::Owned(ping)).to_bytes());
                 // broadcast directly the ping packet asap to all peers
                 for peer in all_peers {
                     if current_time - peer.get_last_ping_sent() > P2P_PING_DELAY && !peer.get_connection().is_closed() {
                         trace!("broadcast generic ping packet to {}", peer);
-                        if let Err(e) = peer.send_bytes(bytes.clone()).await {
+                        let bytes = if peer.get_protocol_version() >= P2P_TIMED_PING_PROTOCOL_VERSION {
+                            timed_bytes.clone()
+                        } else {
+                            bytes.clone()
+                        };
+
+                        if let Err(e) = peer.send_bytes(bytes).await {
                             error!("Error while trying to send ping packet to {}: {}", peer, e);
                         } else {
                             peer.set_last_ping_sent(current_time);
@@ -1070,6 +1289,199 @@ impl<S: Storage> P2pServer<S> {
         }
     }
 
+    // Send to each peer a ping packet with the peers it doesn't know yet and our topology if shared
+    async fn send_ping_with_peer_list(&self, ping: &mut Ping<'_>, all_peers: &HashSet<Arc<Peer>>, current_time: TimestampSeconds) {
+        trace!("Sending ping packet with peerlist...");
+        // Only one hop is shared: our connections count, not the peers of our peers
+        let topology = if self.share_topology {
+            Some(PingTopology::new(all_peers.len().min(u16::MAX as usize) as u16, self.tag.clone()))
+        } else {
+            None
+        };
+
+        for peer in all_peers.iter() {
+            // Older peers reject the ping if it contains the topology
+            ping.set_topology(topology.clone().filter(|_| peer.get_protocol_version() >= P2P_TOPOLOGY_PROTOCOL_VERSION));
+
+            let new_peers = ping.get_mut_peers();
+            new_peers.clear();
+
+            if peer.get_connection().is_closed() {
+                debug!("{} is closed, skipping ping packet", peer);
+                continue;
+            }
+
+            // Is it a peer from our local network
+            let is_local_peer = is_local_address(peer.get_connection().get_address());
+
+            // all the peers we already shared with this peer
+            let mut shared_peers = peer.get_peers().lock().await;
+
+            // iterate through our peerlist to determinate which peers we have to send
+            for p in all_peers.iter() {
+                // don't send him itself
+                // and don't share a peer that don't want to be shared
+                if p.get_id() == peer.get_id() || !p.sharable() {
+                    continue;
+                }
+
+                // if we haven't send him this peer addr and that he don't have him already, insert it
+                let addr = p.get_outgoing_address();
+
+                // Don't share local network addresses if it's external peer
+                if is_local_address(addr) && !is_local_peer {
+                    debug!("{} is a local address but peer is external, skipping", addr);
+                    continue;
+                }
+
+                let send = match shared_peers.entry(*addr) {
+                    Entry::Occupied(mut e) => e.get_mut().update(Direction::Out),
+                    Entry::Vacant(e) => {
+                        e.insert(Direction::Out);
+                        true
+                    }
+                };
+
+                if send {
+                    // add it in our side to not re send it again
+                    trace!("{} didn't received {} yet, adding it to peerlist in ping packet", peer.get_outgoing_address(), addr);
+
+                    // add it to new list to send it
+                    new_peers.insert(*addr);
+                    if new_peers.len() >= P2P_PING_PEER_LIST_LIMIT {
+                        break;
+                    }
+                }
+            }
+
+            // update the ping packet with the new peers
+            debug!("Set peers: {:?}, going to {}", new_peers, peer.get_outgoing_address());
+            // send the ping packet to the peer, with our time if it supports it
+            let packet = if peer.get_protocol_version() >= P2P_TIMED_PING_PROTOCOL_VERSION {
+                Packet::TimedPing(TimedPing::new(get_current_time_in_seconds(), Cow::Borrowed(&*ping)))
+            } else {
+                Packet::Ping(Cow::Borrowed(&*ping))
+            };
+
+            if let Err(e) = peer.send_packet(packet).await {
+                debug!("Error sending specific ping packet to {}: {}", peer, e);
+            } else {
+                peer.set_last_ping_sent(current_time);
+            }
+        }
+    }
+
+    // Send our peer list and topology now instead of waiting for the next round of the ping loop
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn broadcast_ping_with_peer_list(&self) {
+        let mut ping = self.build_generic_ping_packet();
+        let all_peers = self.peer_list.get_cloned_peers().await;
+        self.send_ping_with_peer_list(&mut ping, &all_peers, get_current_time_in_seconds()).await;
+    }
+
+    // Update the peer with its ping and store the peers it shared with us
+    async fn handle_ping(&self, peer: &Arc<Peer>, ping: Cow<'_, Ping<'_>>) -> Result<(), P2pError> {
+        let current_time = get_current_time_in_seconds();
+        let empty_peer_list = ping.get_peers().is_empty();
+
+        // update the last ping only if he respect the protocol rules
+        peer.set_last_ping(current_time);
+
+        // we verify the respect of the countdown of peer list updates to prevent any spam
+        if !empty_peer_list {
+            trace!("received peer list from {}: {}", peer, ping.get_peers().len());
+            let last_peer_list = peer.get_last_peer_list();
+            let diff = current_time - last_peer_list;
+            // Don't allow faster than 1/3 of the delay (because of connection latency / packets being queued)
+            if last_peer_list != 0 && diff < (P2P_PING_PEER_LIST_DELAY * 2 / 3) {
+                return Err(P2pError::PeerInvalidPeerListCountdown(P2P_PING_PEER_LIST_DELAY - diff))
+            }
+            peer.set_last_peer_list(current_time);
+        }
+
+        {
+            let is_local_peer = is_local_address(peer.get_connection().get_address());
+            for addr in ping.get_peers() {
+                if is_local_address(addr) && !is_local_peer {
+                    error!("{} is a local address from {} but peer is external", addr, peer);
+                    return Err(P2pError::InvalidPeerlist)
+                }
+            }
+
+            self.store_shared_peers(ping.get_peers()).await;
+        }
+
+        ping.into_owned().update_peer(peer, &self.blockchain).await?;
+
+        Ok(())
+    }
+
+    // Handle a transaction hash propagated by a peer
+    // It is requested to the peer if we don't have it yet
+    async fn process_propagated_tx(&self, peer: &Arc<Peer>, hash: Hash) -> Result<(), P2pError> {
+        // peer should not send us twice the same transaction
+        debug!("Received tx hash {} from {}", hash, peer.get_outgoing_address());
+        {
+            let mut txs_cache = peer.get_txs_cache().lock().await;
+
+            if let Some(direction) = txs_cache.get_mut(&hash) {
+                if !direction.update(Direction::In) {
+                    debug!("{} send us a transaction ({}) already tracked by him ({:?})", peer, hash, direction);
+                    // return Err(P2pError::AlreadyTrackedTx(hash))
+                }
+            } else {
+                txs_cache.put(hash.clone(), Direction::In);
+            }
+        }
+
+        // Check that the tx is not in mempool or on disk already
+        // Known TXs are not counted in the rate limit, honest peers re-announce them
+        if !self.blockchain.has_tx(&hash).await? {
+            // Drop it before any download or verification if the peer is flooding us
+            if !peer.check_tx_rate_limit().await {
+                debug!("{} exceeded its TX rate limit, dropping TX {} ({} dropped)", peer, hash, peer.get_tx_rate_limit_strikes());
+                return Ok(())
+            }
+
+            trace!("Requesting tx {} propagated because we don't have it", hash);
+            if !self.object_tracker.request_object_from_peer(Arc::clone(peer), ObjectRequest::Transaction(hash.clone()), true).await? {
+                debug!("TX propagated {} was already requested, ignoring", hash);
+            }
+        }
+
+        // Avoid sending the TX propagated to a common peer
+        // because we track peerlist of each peers, we can try to determinate it
+        // iterate over all common peers of this peer broadcaster
+        for common_peer in self.get_common_peers_for(&peer).await {
+            debug!("{} is a common peer with {}, adding TX {} to its cache", common_peer, peer, hash);
+            let mut txs_cache = common_peer.get_txs_cache().lock().await;
+            // Set it as Out so we don't send it anymore but we can get it one time in case of bad common peer prediction
+            txs_cache.put(hash.clone(), Direction::Out);
+        }
+
+        Ok(())
+    }
+
+    // Handle a transaction hash like if it was propagated by the peer connected with this address
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn receive_propagated_tx(&self, addr: &SocketAddr, hash: Hash) -> Result<(), P2pError> {
+        let peer = self.peer_list.get_peer_by_addr(addr).await.ok_or(P2pError::Disconnected)?;
+        self.process_propagated_tx(&peer, hash).await
+    }
+
+    // Handle a block header like if it was propagated by the peer connected with this address
+    // Used to feed blocks in any order, the peers only propagate blocks close to our height
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn receive_propagated_block(&self, addr: &SocketAddr, header: BlockHeader) -> Result<(), P2pError> {
+        let peer = self.peer_list.get_peer_by_addr(addr).await.ok_or(P2pError::Disconnected)?;
+        let block_hash = header.hash();
+        if self.track_propagated_block(&peer, &block_hash).await? {
+            self.process_propagated_block(&peer, header, block_hash).await;
+        }
+
+        Ok(())
+    }
+
     // try to extend our peerlist each time its possible by searching in known peerlist from disk
     async fn peerlist_loop(self: Arc<Self>) {
         debug!("Starting peerlist task...");
@@ -1080,19 +1492,24 @@ impl<S: Storage> P2pServer<S> {
                 break;
             }
 
-            if self.accept_new_connections().await {
-                let peer = {
-                    trace!("Locking peer list write mode (peerlist loop)");
-                    self.peer_list.find_peer_to_connect().await
-                };
-                trace!("End locking peer list write mode (peerlist loop)");
+            self.connect_to_new_peer().await;
+        }
+    }
 
-                if let Some(addr) = peer {
-                    debug!("Found peer {}", addr);
-                    self.try_to_connect_to_peer(addr, false).await;
-                } else {
-                    trace!("No peer found to connect to");
-                }
+    // Try to connect to a new peer from our peerlist if we can accept it
+    async fn connect_to_new_peer(&self) {
+        if self.accept_new_connections().await {
+            let peer = {
+                trace!("Locking peer list write mode (peerlist loop)");
+                self.peer_list.find_peer_to_connect().await
+            };
+            trace!("End locking peer list write mode (peerlist loop)");
+
+            if let Some(addr) = peer {
+                debug!("Found peer {}", addr);
+                self.try_to_connect_to_peer(addr, false).await;
+            } else {
+                trace!("No peer found to connect to");
             }
         }
     }
@@ -1111,11 +1528,12 @@ impl<S: Storage> P2pServer<S> {
                 },
                 peer = receiver.recv() => {
                     if let Some(peer) = peer {
+                        self.remove_peer_time_sample(&peer).await;
                         if peer.sharable() {
                             if let Some(rpc) = self.blockchain.get_rpc().read().await.as_ref() {
                                 if rpc.is_event_tracked(&NotifyEvent::PeerDisconnected).await {
                                     debug!("Notifying clients with PeerDisconnected event");
-                                    rpc.notify_clients_with(&NotifyEvent::PeerDisconnected, get_peer_entry(&peer).await).await;
+                                    rpc.notify_clients_with(&NotifyEvent::PeerDisconnected, get_peer_entry(&peer, self.blockchain.hide_peer_ips()).await).await;
                                 }
                             }
                         }
@@ -1134,8 +1552,11 @@ impl<S: Storage> P2pServer<S> {
     async fn blocks_processing_task(self: Arc<Self>, mut receiver: Receiver<(Arc<Peer>, BlockHeader, Hash)>) {
         debug!("Starting blocks processing task");
         let mut server_exit = self.exit_sender.subscribe();
+        let mut orphans_interval = interval(Duration::from_secs(P2P_ORPHAN_BLOCKS_CHECK_DELAY));
 
         loop {
+            // Orphans connected by a block are processed right after it
+            let mut queue = VecDeque::new();
             select! {
                 biased;
                 _ = server_exit.recv() => {
@@ -1143,66 +1564,267 @@ impl<S: Storage> P2pServer<S> {
                     break;
                 }
                 msg = receiver.recv() => {
-                    let Some((peer, header, block_hash)) = msg else {
+                    let Some(block) = msg else {
                         debug!("No more blocks to process, stopping blocks processing task");
                         break;
                     };
+                    queue.push_back(block);
+                },
+                _ = orphans_interval.tick() => {
+                    queue.extend(self.get_connected_orphan_blocks().await);
+                }
+            }
 
-                    let mut response_blockers: Vec<ResponseBlocker> = Vec::new();
-                    for hash in header.get_txs_hashes() {
-                        let contains = { // we don't lock one time because we may wait on p2p response
-                            // Check in ObjectTracker
-                            if let Some(response_blocker) = self.object_tracker.get_response_blocker_for_requested_object(hash).await {
-                                trace!("{} is already requested, waiting on response blocker for block {}", hash, block_hash);
-                                response_blockers.push(response_blocker);
-                                true
-                            } else {
-                                self.blockchain.has_tx(hash).await.unwrap_or(false)
-                            }
-                        };
+            while let Some((peer, header, block_hash)) = queue.pop_front() {
+                queue.extend(self.execute_propagated_block(peer, header, block_hash).await);
+            }
+        }
 
-                        if !contains { // retrieve one by one to prevent acquiring the lock for nothing
-                            debug!("Requesting TX {} to {} for block {}", hash, peer, block_hash);
-                            if let Err(e) = self.object_tracker.request_object_from_peer(Arc::clone(&peer), ObjectRequest::Transaction(hash.clone()), false).await {
-                                    error!("Error while requesting TX {} to {} for block {}: {}", hash, peer, block_hash, e);
-                                    peer.increment_fail_count();
-                                    continue;
-                            }
+        debug!("Blocks processing task ended");
+    }
 
-                            if let Some(response_blocker) = self.object_tracker.get_response_blocker_for_requested_object(hash).await {
-                                response_blockers.push(response_blocker);
-                            }
-                        }
-                    }
+    // Add a propagated block to the chain, or keep it as orphan if one of its parents is missing
+    // Returns the orphans that can now be added as they were only waiting on this block
+    async fn execute_propagated_block(self: &Arc<Self>, peer: Arc<Peer>, header: BlockHeader, block_hash: Hash) -> Vec<(Arc<Peer>, BlockHeader, Hash)> {
+        let missing = match self.get_missing_tips(&header).await {
+            Ok(missing) => missing,
+            Err(e) => {
+                error!("Error while checking tips of block {} from {}: {}", block_hash, peer, e);
+                return Vec::new()
+            }
+        };
 
-                    // Wait on all already requested txs
-                    for mut blocker in response_blockers {
-                        if let Err(e) = blocker.recv().await {
-                            // It's mostly a closed channel error, so we can ignore it
-                            debug!("Error while waiting on response blocker: {}", e);
-                        }
-                    }
+        if !missing.is_empty() {
+            self.add_orphan_block(peer, header, block_hash, missing).await;
+            return Vec::new()
+        }
 
-                    // add immediately the block to chain as we are synced with
-                    let block = match self.blockchain.build_block_from_header(Immutable::Owned(header)).await {
-                        Ok(block) => block,
-                        Err(e) => {
-                            error!("Error while building block {} from peer {}: {}", block_hash, peer, e);
-                            peer.increment_fail_count();
-                            continue;
-                        }
-                    };
-        
-                    debug!("Adding received block {} from {} to chain", block_hash, peer);
-                    if let Err(e) = self.blockchain.add_new_block(block, true, false).await {
-                        error!("Error while adding new block from {}: {}", peer, e);
+        let mut response_blockers: Vec<ResponseBlocker> = Vec::new();
+        for hash in header.get_txs_hashes() {
+            let contains = { // we don't lock one time because we may wait on p2p response
+                // Check in ObjectTracker
+                if let Some(response_blocker) = self.object_tracker.get_response_blocker_for_requested_object(hash).await {
+                    trace!("{} is already requested, waiting on response blocker for block {}", hash, block_hash);
+                    response_blockers.push(response_blocker);
+                    true
+                } else {
+                    self.blockchain.has_tx(hash).await.unwrap_or(false)
+                }
+            };
+
+            if !contains { // retrieve one by one to prevent acquiring the lock for nothing
+                debug!("Requesting TX {} to {} for block {}", hash, peer, block_hash);
+                if let Err(e) = self.object_tracker.request_object_from_peer(Arc::clone(&peer), ObjectRequest::Transaction(hash.clone()), false).await {
+                        error!("Error while requesting TX {} to {} for block {}: {}", hash, peer, block_hash, e);
                         peer.increment_fail_count();
+                        continue;
+                }
+
+                if let Some(response_blocker) = self.object_tracker.get_response_blocker_for_requested_object(hash).await {
+                    response_blockers.push(response_blocker);
+                }
+            }
+        }
+
+        // Wait on all already requested txs
+        for mut blocker in response_blockers {
+            if let Err(e) = blocker.recv().await {
+                // It's mostly a closed channel error, so we can ignore it
+                debug!("Error while waiting on response blocker: {}", e);
+            }
+        }
+
+        // add immediately the block to chain as we are synced with
+        let block = match self.blockchain.build_block_from_header(Immutable::Owned(header)).await {
+            Ok(block) => block,
+            Err(e) => {
+                error!("Error while building block {} from peer {}: {}", block_hash, peer, e);
+                peer.increment_fail_count();
+                return Vec::new()
+            }
+        };
+
+        // The block is only relayed by the chain once executed
+        debug!("Adding received block {} from {} to chain", block_hash, peer);
+        if let Err(e) = self.blockchain.add_new_block(block, true, false).await {
+            error!("Error while adding new block from {}: {}", peer, e);
+            peer.increment_fail_count();
+            return Vec::new()
+        }
+
+        let mut orphans = self.orphan_blocks.lock().await;
+        orphans.on_block_connected(&block_hash)
+            .into_iter()
+            .map(|(hash, (peer, header))| (peer, header, hash))
+            .collect()
+    }
+
+    // Tips of the block that are not in our chain
+    async fn get_missing_tips(&self, header: &BlockHeader) -> Result<HashSet<Hash>, BlockchainError> {
+        let storage = self.blockchain.get_storage().read().await;
+        let mut missing = HashSet::new();
+        for tip in header.get_tips().iter() {
+            if !storage.has_block_with_hash(tip).await? {
+                missing.insert(tip.clone());
+            }
+        }
+
+        Ok(missing)
+    }
+
+    // Keep a block until its missing parents are added in chain
+    // Parents are requested to the peer that sent us the block
+    async fn add_orphan_block(self: &Arc<Self>, peer: Arc<Peer>, header: BlockHeader, block_hash: Hash, missing: HashSet<Hash>) {
+        let (to_request, evicted) = {
+            let mut orphans = self.orphan_blocks.lock().await;
+            // Parents already orphans or waited by another orphan are already requested
+            let to_request: Vec<Hash> = missing.iter()
+                .filter(|parent| !orphans.contains(parent) && !orphans.is_waiting_on(parent))
+                .cloned()
+                .collect();
+
+            debug!("Block {} from {} is orphan, missing {} parents ({} orphans)", block_hash, peer, missing.len(), orphans.len());
+            let evicted = orphans.insert(block_hash, (Arc::clone(&peer), header), missing, get_current_time_in_seconds());
+            (to_request, evicted)
+        };
+
+        // Evicted orphans can be propagated again
+        if !evicted.is_empty() {
+            debug!("{} orphan blocks evicted", evicted.len());
+            let mut blocks_propagation_queue = self.blocks_propagation_queue.lock().await;
+            for hash in evicted.iter() {
+                blocks_propagation_queue.pop(hash);
+            }
+        }
+
+        for parent in to_request {
+            match self.track_propagated_block(&peer, &parent).await {
+                Ok(true) => {},
+                Ok(false) => continue,
+                Err(e) => {
+                    debug!("Error while tracking parent {} of orphan blocks: {}", parent, e);
+                    continue;
+                }
+            };
+
+            debug!("Requesting parent {} of orphan blocks to {}", parent, peer);
+            // Request the header without blocking the blocks processor
+            let zelf = Arc::clone(self);
+            let peer = Arc::clone(&peer);
+            spawn_task("p2p-orphan-parent", async move {
+                match peer.request_blocking_object(ObjectRequest::BlockHeader(parent.clone())).await {
+                    Ok(OwnedObjectResponse::BlockHeader(header, hash)) if hash == parent => {
+                        zelf.process_propagated_block(&peer, header, hash).await;
+                    },
+                    Ok(_) => {
+                        debug!("Invalid response for block header {} from {}", parent, peer);
+                        peer.increment_fail_count();
+                        zelf.blocks_propagation_queue.lock().await.pop(&parent);
+                    },
+                    Err(e) => {
+                        debug!("Error while requesting block header {} from {}: {}", parent, peer, e);
+                        zelf.blocks_propagation_queue.lock().await.pop(&parent);
                     }
                 }
+            });
+        }
+    }
+
+    // Orphans having all their parents in chain
+    // Parents may have been added by the chain sync, expired orphans are evicted
+    async fn get_connected_orphan_blocks(&self) -> Vec<(Arc<Peer>, BlockHeader, Hash)> {
+        let (parents, evicted) = {
+            let mut orphans = self.orphan_blocks.lock().await;
+            if orphans.is_empty() {
+                return Vec::new()
+            }
+            let evicted = orphans.evict_expired(get_current_time_in_seconds());
+            (orphans.get_missing_parents(), evicted)
+        };
+
+        if !evicted.is_empty() {
+            debug!("{} orphan blocks expired", evicted.len());
+            let mut blocks_propagation_queue = self.blocks_propagation_queue.lock().await;
+            for hash in evicted.iter() {
+                blocks_propagation_queue.pop(hash);
             }
         }
 
-        debug!("Blocks processing task ended");
+        let mut connected = Vec::new();
+        for parent in parents {
+            match self.blockchain.has_block(&parent).await {
+                Ok(true) => connected.push(parent),
+                Ok(false) => {},
+                Err(e) => debug!("Error while checking parent {} of orphan blocks: {}", parent, e)
+            };
+        }
+
+        let mut orphans = self.orphan_blocks.lock().await;
+        connected.iter()
+            .flat_map(|parent| orphans.on_block_connected(parent))
+            .map(|(hash, (peer, header))| (peer, header, hash))
+            .collect()
+    }
+
+    // Track a block propagated by a peer
+    // Returns false if the block is already in our chain or already being processed
+    async fn track_propagated_block(&self, peer: &Arc<Peer>, block_hash: &Hash) -> Result<bool, P2pError> {
+        // verify that this block wasn't already sent by him
+        {
+            let mut blocks_propagation = peer.get_blocks_propagation().lock().await;
+            if let Some(direction) = blocks_propagation.get_mut(block_hash) {
+                if !direction.update(Direction::In) {
+                    debug!("{} send us a block ({}) already tracked by him ({:?})", peer, block_hash, direction);
+                    // return Err(P2pError::AlreadyTrackedBlock(block_hash, *direction))
+                }
+            } else {
+                debug!("Saving {} in blocks propagation cache for {}", block_hash, peer);
+                blocks_propagation.put(block_hash.clone(),  Direction::In);
+            }
+        }
+
+        // Avoid sending the same block to a common peer that may have already got it
+        // because we track peerlist of each peers, we can try to determinate it
+        for common_peer in self.get_common_peers_for(peer).await {
+            debug!("{} is a common peer with {}, adding block {} to its propagation cache", common_peer, peer, block_hash);
+            let mut blocks_propagation = common_peer.get_blocks_propagation().lock().await;
+            // Out allow to get "In" again, because it's a prediction, don't block it completely
+            if !blocks_propagation.contains(block_hash) {
+                blocks_propagation.put(block_hash.clone(), Direction::Out);
+            }
+        }
+
+        // check that we don't have this block in our chain
+        {
+            let storage = self.blockchain.get_storage().read().await;
+            if storage.has_block_with_hash(block_hash).await? {
+                debug!("{}: block {} is already in our chain. Skipping", peer, block_hash);
+                return Ok(false)
+            }
+        }
+
+        // Check that we are not already waiting on it
+        {
+            let mut blocks_propagation_queue = self.blocks_propagation_queue.lock().await;
+            if blocks_propagation_queue.contains(block_hash) {
+                debug!("Block {} propagated is already in processing from another peer", block_hash);
+                return Ok(false)
+            }
+            blocks_propagation_queue.put(block_hash.clone(), ());
+        }
+
+        Ok(true)
+    }
+
+    // Send a propagated block to the blocks processor
+    async fn process_propagated_block(&self, peer: &Arc<Peer>, header: BlockHeader, block_hash: Hash) {
+        let block_height = header.get_height();
+        debug!("Received block at height {} from {}", block_height, peer);
+        let peer = Arc::clone(peer);
+        // This will block the task if the bounded channel is full
+        if let Err(e) = self.blocks_processor.send((peer, header, block_hash)).await {
+            error!("Error while sending block propagated to blocks processor task: {}", e);
+        }
     }
 
     // this function handle the logic to send all packets to the peer
@@ -1226,10 +1848,15 @@ impl<S: Storage> P2pServer<S> {
                 _ = interval.tick() => {
                     trace!("Checking heartbeat of {}", peer);
                     // Last time we got a ping packet from him
-                    let last_ping = peer.get_last_ping();
-                    if last_ping != 0 && get_current_time_in_seconds() - last_ping > P2P_PING_TIMEOUT {
+                    // If we never received any, use the time the connection was established
+                    let last_ping = match peer.get_last_ping() {
+                        0 => peer.get_connection().connected_on(),
+                        last_ping => last_ping
+                    };
+                    if get_current_time_in_seconds().saturating_sub(last_ping) > P2P_PING_TIMEOUT {
                         debug!("{} has not sent a ping packet for {} seconds, closing connection...", peer, P2P_PING_TIMEOUT);
-                        peer.close_internal().await?;
+                        peer.close_with_reason(DisconnectReason::PingTimeout).await?;
+                        peer.get_connection().close().await?;
                         break;
                     }
 
@@ -1242,7 +1869,13 @@ impl<S: Storage> P2pServer<S> {
                 Some(bytes) = rx.recv() => {
                     // there is a overhead of 4 for each packet (packet size u32 4 bytes, packet id u8 is counted in the packet size)
                     trace!("Sending packet with ID {}, size sent: {}, real size: {}", bytes[4], u32::from_be_bytes(bytes[0..4].try_into()?), bytes.len());
-                    timeout(Duration::from_millis(PEER_SEND_BYTES_TIMEOUT), peer.get_connection().send_bytes(&bytes)).await??;
+                    if let Err(e) = peer.get_connection().send_bytes_with_timeout(&bytes, Duration::from_millis(PEER_SEND_BYTES_TIMEOUT)).await {
+                        if let P2pError::WriteTimeout(_) = e {
+                            peer.close_with_reason(DisconnectReason::WriteTimeout).await?;
+                            peer.get_connection().close().await?;
+                        }
+                        return Err(e)
+                    }
                     trace!("data sucessfully sent!");
                 }
             }
@@ -1352,12 +1985,23 @@ impl<S: Storage> P2pServer<S> {
             });
         }
 
-        // verify that we are synced with him to receive all TXs correctly
-        let our_topoheight = self.blockchain.get_topo_height();
-        let peer_topoheight = peer.get_topoheight();
-        if peer_topoheight == our_topoheight {
-            if let Err(e) = self.request_inventory_of(&peer).await {
-                warn!("Error while requesting inventory of {}: {}", peer, e);
+        if self.mempool_sync {
+            if peer.is_mempool_sync_enabled() {
+                // send it our mempool, it will do the same for us
+                debug!("Sending our mempool inventory to {}", peer);
+                if let Err(e) = self.send_inventory_page_to(&peer, 0).await {
+                    warn!("Error while sending our inventory to {}: {}", peer, e);
+                }
+            } else if peer.get_connection().is_out() {
+                // peer doesn't send its mempool on connection, request it
+                // verify that we are synced with him to receive all TXs correctly
+                let our_topoheight = self.blockchain.get_topo_height();
+                let peer_topoheight = peer.get_topoheight();
+                if peer_topoheight == our_topoheight {
+                    if let Err(e) = self.request_inventory_of(&peer).await {
+                        warn!("Error while requesting inventory of {}: {}", peer, e);
+                    }
+                }
             }
         }
 
@@ -1377,23 +2021,170 @@ impl<S: Storage> P2pServer<S> {
         let peer_peers = peer.get_peers().lock().await;
         trace!("locked peers received (common peers)");
 
-        let mut common_peers = Vec::new();
-        for (common_peer_addr, _) in peer_peers.iter().filter(|(_, direction)| **direction == Direction::Both) {
-            // if we have a common peer with him
-            if let Some(common_peer) = self.peer_list.get_peer_by_addr(common_peer_addr).await {
-                if peer.get_id() != common_peer.get_id() {
-                    common_peers.push(common_peer);
-                }
+        let mut common_peers = Vec::new();
+        for (common_peer_addr, _) in peer_peers.iter().filter(|(_, direction)| **direction == Direction::Both) {
+            // if we have a common peer with him
+            if let Some(common_peer) = self.peer_list.get_peer_by_addr(common_peer_addr).await {
+                if peer.get_id() != common_peer.get_id() {
+                    common_peers.push(common_peer);
+                }
+            }
+        }
+
+        common_peers
+    }
+
+    // Build the hints of a block sent to a peer, only if it supports them
+    async fn build_block_hints(&self, peer: &Arc<Peer>, hash: &Hash, height: u64) -> Option<BlockHints> {
+        if peer.get_protocol_version() < P2P_BLOCK_HINTS_PROTOCOL_VERSION {
+            return None
+        }
+
+        let storage = self.blockchain.get_storage().read().await;
+        let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(hash).await.ok()?;
+        let topoheight = if storage.is_block_topological_ordered(hash).await {
+            storage.get_topo_height_for_hash(hash).await.ok()
+        } else {
+            None
+        };
+
+        Some(BlockHints {
+            height,
+            topoheight,
+            cumulative_difficulty
+        })
+    }
+
+    // Compare the hints sent with a block against what we computed once it was added
+    // Hints are never used for consensus, a contradiction is only reported
+    async fn verify_block_hints(&self, peer: &Arc<Peer>, hash: &Hash, hints: &BlockHints) -> Result<bool, BlockchainError> {
+        let storage = self.blockchain.get_storage().read().await;
+        let header = storage.get_block_header_by_hash(hash).await?;
+        let topoheight = if storage.is_block_topological_ordered(hash).await {
+            Some(storage.get_topo_height_for_hash(hash).await?)
+        } else {
+            None
+        };
+        let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(hash).await?;
+
+        let consistent = hints.is_consistent_with(&header, topoheight, Some(&cumulative_difficulty));
+        if !consistent {
+            debug!("{} sent hints for block {} contradicting our values, ignoring them", peer, hash);
+        }
+
+        Ok(consistent)
+    }
+
+    // Send the object requested by a peer, or a not found response
+    async fn serve_object_request(&self, peer: &Arc<Peer>, request: ObjectRequest) -> Result<(), P2pError> {
+        match &request {
+            ObjectRequest::Block(hash) => {
+                debug!("{} asked full block {}", peer, hash);
+                let block = {
+                    let storage = self.blockchain.get_storage().read().await;
+                    storage.get_block_by_hash(hash).await
+                };
+
+                match block {
+                    Ok(block) => {
+                        debug!("block {} found, sending it", hash);
+                        let hints = self.build_block_hints(peer, hash, block.get_height()).await;
+                        peer.send_packet(Packet::ObjectResponse(ObjectResponse::Block(Cow::Borrowed(&block), hints))).await?;
+                    },
+                    Err(e) => {
+                        debug!("{} asked block '{}' but not present in our chain: {}", peer, hash, e);
+                        peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
+                    }
+                };
+            },
+            ObjectRequest::BlockHeader(hash) => {
+                debug!("{} asked block header {}", peer, hash);
+                peer.on_block_header_requested(hash).await;
+                let block = {
+                    let storage = self.blockchain.get_storage().read().await;
+                    storage.get_block_header_by_hash(hash).await
+                };
+
+                match block {
+                    Ok(block) => {
+                        debug!("block header {} found, sending it", hash);
+                        peer.send_packet(Packet::ObjectResponse(ObjectResponse::BlockHeader(Cow::Borrowed(&block)))).await?;
+                    },
+                    Err(e) => {
+                        debug!("{} asked block header '{}' but not present in our chain: {}", peer, hash, e);
+                        peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
+                    }
+                };
+            },
+            ObjectRequest::Transaction(hash) => {
+                debug!("{} asked tx {}", peer, hash);
+                match self.blockchain.get_tx(hash).await {
+                    Ok(tx) => {
+                        debug!("tx {} found, sending it", hash);
+                        peer.send_packet(Packet::ObjectResponse(ObjectResponse::Transaction(Cow::Borrowed(&tx)))).await?;
+                    },
+                    Err(e) => {
+                        debug!("{} asked tx '{}' but not present in our chain: {}", peer, hash, e);
+                        peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
+                    }
+                }
+            },
+            ObjectRequest::HeadersRange(hash, count) => {
+                debug!("{} asked {} headers from {}", peer, count, hash);
+                let headers = {
+                    let storage = self.blockchain.get_storage().read().await;
+                    if storage.is_block_topological_ordered(hash).await {
+                        let topoheight = storage.get_topo_height_for_hash(hash).await?;
+                        let count = (*count as u64).min(HEADERS_RANGE_MAX_COUNT);
+                        Some(headers::get_headers_range(&*storage, topoheight, count, HEADERS_RANGE_MAX_SIZE).await?)
+                    } else {
+                        None
+                    }
+                };
+
+                match headers {
+                    Some(headers) => {
+                        debug!("sending {} headers from {}", headers.len(), hash);
+                        peer.send_packet(Packet::ObjectResponse(ObjectResponse::HeadersRange(Cow::Owned(headers), hash.clone(), *count))).await?;
+                    },
+                    None => {
+                        debug!("{} asked headers from '{}' but not ordered in our chain", peer, hash);
+                        peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
+                    }
+                };
+            },
+            ObjectRequest::BlockAtTopoheight(topoheight) => {
+                debug!("{} asked block at topoheight {}", peer, topoheight);
+                let block = if *topoheight <= self.blockchain.get_topo_height() {
+                    let storage = self.blockchain.get_storage().read().await;
+                    match storage.get_hash_at_topo_height(*topoheight).await {
+                        Ok(hash) => storage.get_block_by_hash(&hash).await.ok(),
+                        Err(_) => None
+                    }
+                } else {
+                    None
+                };
+
+                match block {
+                    Some(block) => {
+                        debug!("block at topoheight {} found, sending it", topoheight);
+                        peer.send_packet(Packet::ObjectResponse(ObjectResponse::BlockAtTopoheight(Cow::Borrowed(&block), *topoheight))).await?;
+                    },
+                    None => {
+                        debug!("{} asked block at topoheight {} but not present in our chain", peer, topoheight);
+                        peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
+                    }
+                };
             }
         }
 
-        common_peers
+        Ok(())
     }
 
     // Main function used by every nodes connections
     // This is handling each packet available in our p2p protocol
     // Each packet is a enum variant
-    async fn handle_incoming_packet(self: &Arc<Self>, peer: &Arc<Peer>, packet: Packet<'_>) -> Result<(), P2pError> {
+    async fn handle_incoming_packet(self: &Arc<Self>, peer: &Arc<Peer>, packet: Packet<'_>, packet_bytes: Bytes) -> Result<(), P2pError> {
         match packet {
             Packet::Handshake(_) => {
                 error!("{} sent us handshake packet (not valid!)", peer);
@@ -1411,39 +2202,7 @@ impl<S: Storage> P2pServer<S> {
                 let hash = hash.into_owned();
 
                 ping.into_owned().update_peer(peer, &self.blockchain).await?;
-
-                // peer should not send us twice the same transaction
-                debug!("Received tx hash {} from {}", hash, peer.get_outgoing_address());
-                {
-                    let mut txs_cache = peer.get_txs_cache().lock().await;
-
-                    if let Some(direction) = txs_cache.get_mut(&hash) {
-                        if !direction.update(Direction::In) {
-                            debug!("{} send us a transaction ({}) already tracked by him ({:?})", peer, hash, direction);
-                            // return Err(P2pError::AlreadyTrackedTx(hash))
-                        }
-                    } else {
-                        txs_cache.put(hash.clone(), Direction::In);
-                    }
-                }
-
-                // Check that the tx is not in mempool or on disk already
-                if !self.blockchain.has_tx(&hash).await? {
-                    trace!("Requesting tx {} propagated because we don't have it", hash);
-                    if !self.object_tracker.request_object_from_peer(Arc::clone(peer), ObjectRequest::Transaction(hash.clone()), true).await? {
-                        debug!("TX propagated {} was already requested, ignoring", hash);
-                    }
-                }
-
-                // Avoid sending the TX propagated to a common peer
-                // because we track peerlist of each peers, we can try to determinate it
-                // iterate over all common peers of this peer broadcaster
-                for common_peer in self.get_common_peers_for(&peer).await {
-                    debug!("{} is a common peer with {}, adding TX {} to its cache", common_peer, peer, hash);
-                    let mut txs_cache = common_peer.get_txs_cache().lock().await;
-                    // Set it as Out so we don't send it anymore but we can get it one time in case of bad common peer prediction
-                    txs_cache.put(hash.clone(), Direction::Out);
-                }
+                self.process_propagated_tx(peer, hash).await?;
             },
             Packet::BlockPropagation(packet_wrapper) => {
                 trace!("Received a block propagation packet from {}", peer);
@@ -1454,56 +2213,67 @@ impl<S: Storage> P2pServer<S> {
                 let header = header.into_owned();
                 let block_hash = header.hash();
 
-                // verify that this block wasn't already sent by him
-                {
-                    let mut blocks_propagation = peer.get_blocks_propagation().lock().await;
-                    if let Some(direction) = blocks_propagation.get_mut(&block_hash) {
-                        if !direction.update(Direction::In) {
-                            debug!("{} send us a block ({}) already tracked by him ({:?})", peer, block_hash, direction);
-                            // return Err(P2pError::AlreadyTrackedBlock(block_hash, *direction))
-                        }
-                    } else {
-                        debug!("Saving {} in blocks propagation cache for {}", block_hash, peer);
-                        blocks_propagation.put(block_hash.clone(),  Direction::In);
-                    }
+                if !self.track_propagated_block(peer, &block_hash).await? {
+                    return Ok(())
                 }
 
-                // Avoid sending the same block to a common peer that may have already got it
-                // because we track peerlist of each peers, we can try to determinate it
-                for common_peer in self.get_common_peers_for(&peer).await {
-                    debug!("{} is a common peer with {}, adding block {} to its propagation cache", common_peer, peer, block_hash);
-                    let mut blocks_propagation = common_peer.get_blocks_propagation().lock().await;
-                    // Out allow to get "In" again, because it's a prediction, don't block it completely
-                    if !blocks_propagation.contains(&block_hash) {
-                        blocks_propagation.put(block_hash.clone(), Direction::Out);
-                    }
+                // Keep the raw header bytes to relay it to other peers without serializing it again
+                // Packet bytes are the packet id followed by the header and the ping
+                let header_size = header.size();
+                if packet_bytes.len() > header_size {
+                    let mut blocks_header_bytes = self.blocks_header_bytes.lock().await;
+                    blocks_header_bytes.put(block_hash.clone(), packet_bytes.slice(1..1 + header_size));
                 }
 
-                // check that we don't have this block in our chain
-                {
-                    let storage = self.blockchain.get_storage().read().await;
-                    if storage.has_block_with_hash(&block_hash).await? {
-                        debug!("{}: {} with hash {} is already in our chain. Skipping", peer, header, block_hash);
-                        return Ok(())
-                    }
-                }
+                self.process_propagated_block(peer, header, block_hash).await;
+            },
+            Packet::CompactBlockPropagation(packet_wrapper) => {
+                trace!("Received a compact block propagation packet from {}", peer);
+                let (compact_block, ping) = packet_wrapper.consume();
+                ping.into_owned().update_peer(peer, &self.blockchain).await?;
 
-                // Check that we are not already waiting on it
-                {
-                    let mut blocks_propagation_queue = self.blocks_propagation_queue.lock().await;
-                    if blocks_propagation_queue.contains(&block_hash) {
-                        debug!("Block {} propagated is already in processing from another peer", block_hash);
-                        return Ok(())
-                    }
-                    blocks_propagation_queue.put(block_hash.clone(), ());
+                let block_hash = compact_block.get_block_hash().clone();
+                if !self.track_propagated_block(peer, &block_hash).await? {
+                    return Ok(())
                 }
 
-                let block_height = header.get_height();
-                debug!("Received block at height {} from {}", block_height, peer);
-                let peer = Arc::clone(peer);
-                // This will block the task if the bounded channel is full
-                if let Err(e) = self.blocks_processor.send((peer, header, block_hash)).await {
-                    error!("Error while sending block propagated to blocks processor task: {}", e);
+                // Rebuild the header using our mempool
+                let header = {
+                    let mempool = self.blockchain.get_mempool().read().await;
+                    let resolver = ShortIdsResolver::new(&block_hash, mempool.get_txs().keys().map(|hash| hash.as_ref()));
+                    compact_block.reconstruct(&resolver)
+                };
+
+                match header {
+                    Some(header) => {
+                        let reconstructed = self.compact_blocks_reconstructed.fetch_add(1, Ordering::Relaxed) + 1;
+                        debug!("Compact block {} with {} txs reconstructed from mempool ({} reconstructed, {} failed)", block_hash, compact_block.get_short_ids().len(), reconstructed, self.compact_blocks_failed.load(Ordering::Relaxed));
+                        self.process_propagated_block(peer, header, block_hash).await;
+                    },
+                    None => {
+                        let failed = self.compact_blocks_failed.fetch_add(1, Ordering::Relaxed) + 1;
+                        debug!("Couldn't reconstruct compact block {} from {}, requesting full header ({} failed)", block_hash, peer, failed);
+                        // Request the full header without blocking the read task
+                        // Missing transactions are requested by the blocks processor
+                        let zelf = Arc::clone(self);
+                        let peer = Arc::clone(peer);
+                        spawn_task("p2p-compact-block", async move {
+                            match peer.request_blocking_object(ObjectRequest::BlockHeader(block_hash.clone())).await {
+                                Ok(OwnedObjectResponse::BlockHeader(header, hash)) if hash == block_hash => {
+                                    zelf.process_propagated_block(&peer, header, block_hash).await;
+                                },
+                                Ok(_) => {
+                                    debug!("Invalid response for block header {} from {}", block_hash, peer);
+                                    peer.increment_fail_count();
+                                    zelf.blocks_propagation_queue.lock().await.pop(&block_hash);
+                                },
+                                Err(e) => {
+                                    debug!("Error while requesting block header {} from {}: {}", block_hash, peer, e);
+                                    zelf.blocks_propagation_queue.lock().await.pop(&block_hash);
+                                }
+                            }
+                        });
+                    }
                 }
             },
             Packet::ChainRequest(packet_wrapper) => {
@@ -1537,6 +2307,7 @@ impl<S: Storage> P2pServer<S> {
                 }
 
                 let blocks = request.get_blocks();
+                peer.on_chain_request_served();
                 self.handle_chain_request(&peer, blocks, accepted_response_size).await?;
             },
             Packet::ChainResponse(response) => {
@@ -1550,103 +2321,87 @@ impl<S: Storage> P2pServer<S> {
                     error!("Error while sending chain response to channel of {}", peer);
                 }
             },
-            Packet::Ping(ping) => {
-                trace!("Received a ping packet from {}", peer);
-                let current_time = get_current_time_in_seconds();
-                let empty_peer_list = ping.get_peers().is_empty();
-
-                // update the last ping only if he respect the protocol rules
-                peer.set_last_ping(current_time);
-
-                // we verify the respect of the countdown of peer list updates to prevent any spam
-                if !empty_peer_list {
-                    trace!("received peer list from {}: {}", peer, ping.get_peers().len());
-                    let last_peer_list = peer.get_last_peer_list();
-                    let diff = current_time - last_peer_list;
-                    // Don't allow faster than 1/3 of the delay (because of connection latency / packets being queued)
-                    if last_peer_list != 0 && diff < (P2P_PING_PEER_LIST_DELAY * 2 / 3) {
-                        return Err(P2pError::PeerInvalidPeerListCountdown(P2P_PING_PEER_LIST_DELAY - diff))
-                    }
-                    peer.set_last_peer_list(current_time);
+            Packet::ChainContinuationRequest(request) => {
+                trace!("Received a chain continuation request from {}", peer);
+                // Each request walks our chain again, don't allow a peer to chain them too fast
+                if !peer.check_chain_continuation_rate_limit().await {
+                    debug!("{} requested chain continuations too fast!", peer);
+                    return Err(P2pError::RequestSyncChainTooFast)
                 }
 
-                {
-                    let is_local_peer = is_local_address(peer.get_connection().get_address());
-                    for addr in ping.get_peers() {
-                        if is_local_address(addr) && !is_local_peer {
-                            error!("{} is a local address from {} but peer is external", addr, peer);
-                            return Err(P2pError::InvalidPeerlist)
-                        }
-    
-                        if !self.is_connected_to_addr(addr).await && self.peer_list.has_peer_stored(&addr.ip()).await {
-                            if !self.peer_list.store_peer_address(*addr).await {
-                                debug!("{} already stored in peer list", addr);
-                            }
-                        }
-                    }
+                let accepted_response_size = (request.get_accepted_response_size() as usize).min(self.max_chain_response_size);
+                self.handle_chain_continuation_request(peer, request.get_token(), accepted_response_size).await?;
+            },
+            Packet::Ping(ping) => {
+                trace!("Received a ping packet from {}", peer);
+                self.handle_ping(peer, ping).await?;
+            },
+            Packet::TimedPing(timed_ping) => {
+                trace!("Received a timed ping packet from {}", peer);
+                let (utc_time, ping) = timed_ping.consume();
+                // Ping is verified before using its time
+                self.handle_ping(peer, ping).await?;
+                // A ping read once disconnected must not add back its clock
+                if self.peer_list.get_peer(&peer.get_id()).await.is_some_and(|current| Arc::ptr_eq(&current, peer)) {
+                    self.blockchain.add_peer_time_sample(peer.get_id(), utc_time).await;
                 }
-
-                ping.into_owned().update_peer(peer, &self.blockchain).await?;
             },
             Packet::ObjectRequest(request) => {
                 trace!("Received a object request from {}", peer);
                 let request = request.into_owned();
-                match &request {
-                    ObjectRequest::Block(hash) => {
-                        debug!("{} asked full block {}", peer, hash);
-                        let block = {
-                            let storage = self.blockchain.get_storage().read().await;
-                            storage.get_block_by_hash(hash).await
-                        };
+                let Some(ticket) = self.object_requests.try_acquire(peer.get_id()) else {
+                    debug!("{} has too many object requests queued, dropping {}", peer, request);
+                    peer.on_object_request_dropped();
+                    peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
+                    return Ok(())
+                };
 
-                        match block {
-                            Ok(block) => {
-                                debug!("block {} found, sending it", hash);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::Block(Cow::Borrowed(&block)))).await?;
-                            },
-                            Err(e) => {
-                                debug!("{} asked block '{}' but not present in our chain: {}", peer, hash, e);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
-                            }
-                        };
-                    },
-                    ObjectRequest::BlockHeader(hash) => {
-                        debug!("{} asked block header {}", peer, hash);
-                        let block = {
-                            let storage = self.blockchain.get_storage().read().await;
-                            storage.get_block_header_by_hash(hash).await
-                        };
+                // Served in its own task so we keep reading the next packets of this peer
+                let zelf = Arc::clone(self);
+                let peer = Arc::clone(peer);
+                spawn_task("p2p-object-request", async move {
+                    let Some(_permit) = ticket.wait(Duration::from_millis(PEER_OBJECT_REQUESTS_QUEUE_TIMEOUT)).await else {
+                        debug!("Object request {} from {} waited too long, dropping it", request, peer);
+                        peer.on_object_request_dropped();
+                        if let Err(e) = peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await {
+                            debug!("Error while sending dropped object request to {}: {}", peer, e);
+                        }
+                        return
+                    };
 
-                        match block {
-                            Ok(block) => {
-                                debug!("block header {} found, sending it", hash);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::BlockHeader(Cow::Borrowed(&block)))).await?;
-                            },
-                            Err(e) => {
-                                debug!("{} asked block header '{}' but not present in our chain: {}", peer, hash, e);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
-                            }
-                        };
-                    },
-                    ObjectRequest::Transaction(hash) => {
-                        debug!("{} asked tx {}", peer, hash);
-                        match self.blockchain.get_tx(hash).await {
-                            Ok(tx) => {
-                                debug!("tx {} found, sending it", hash);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::Transaction(Cow::Borrowed(&tx)))).await?;
-                            },
-                            Err(e) => {
-                                debug!("{} asked tx '{}' but not present in our chain: {}", peer, hash, e);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
-                            }
+                    match zelf.serve_object_request(&peer, request).await {
+                        Ok(()) => peer.on_object_request_served(),
+                        Err(e @ (P2pError::Disconnected | P2pError::SendError(_))) => {
+                            debug!("Error while sending object response to {}: {}", peer, e);
+                        },
+                        Err(e) => {
+                            error!("Error occured while serving object request from {}: {}", peer, e);
+                            peer.increment_fail_count();
                         }
                     }
-                }
+                });
             },
             Packet::ObjectResponse(response) => {
                 trace!("Received a object response from {}", peer);
                 let response = response.to_owned();
-                trace!("Object response received is {}", response.get_hash());
+                trace!("Object response received is {}", response.get_request());
+
+                // verify that the block isn't referencing too many txs before requesting them
+                match &response {
+                    OwnedObjectResponse::Block(block, hash, _) => chain_validator::verify_txs_count(hash, block.get_header())?,
+                    OwnedObjectResponse::BlockAtTopoheight(block, hash, _) => chain_validator::verify_txs_count(hash, block.get_header())?,
+                    OwnedObjectResponse::BlockHeader(header, hash) => chain_validator::verify_txs_count(hash, header)?,
+                    OwnedObjectResponse::HeadersRange(headers, hash, _) => {
+                        // Headers must start at the requested block
+                        if let Some(first) = headers.first() {
+                            let first_hash = first.get_header().hash();
+                            if first_hash != *hash {
+                                return Err(P2pError::InvalidObjectResponse(first_hash))
+                            }
+                        }
+                    },
+                    _ => {}
+                };
 
                 // check if we requested it from this peer
                 let request = response.get_request();
@@ -1656,14 +2411,26 @@ impl<S: Storage> P2pServer<S> {
                     if sender.send(response).is_err() {
                         error!("Error while sending object response to sender!");
                     }
-                // check if the Object Tracker has requested this object
-                } else if self.object_tracker.has_requested_object(request.get_hash()).await {
-                    trace!("Object Tracker requested it, handling it");
-                    self.object_tracker.handle_object_response(response).await?;
-                } else if self.object_tracker.is_ignored_request_hash(request.get_hash()).await {
-                    debug!("Object {} was ignored by Object Tracker, ignoring response", request.get_hash());
                 } else {
-                    return Err(P2pError::ObjectNotRequested(request))
+                    // headers range and blocks at topoheight are never requested by the Object Tracker
+                    let tracked_hash = match &request {
+                        ObjectRequest::HeadersRange(..) | ObjectRequest::BlockAtTopoheight(_) => None,
+                        request => request.get_hash()
+                    };
+
+                    let Some(hash) = tracked_hash else {
+                        return Err(P2pError::ObjectNotRequested(request))
+                    };
+
+                    // check if the Object Tracker has requested this object
+                    if self.object_tracker.has_requested_object(hash).await {
+                        trace!("Object Tracker requested it, handling it");
+                        self.object_tracker.handle_object_response(response).await?;
+                    } else if self.object_tracker.is_ignored_request_hash(hash).await {
+                        debug!("Object {} was ignored by Object Tracker, ignoring response", hash);
+                    } else {
+                        return Err(P2pError::ObjectNotRequested(request))
+                    }
                 }
             },
             Packet::NotifyInventoryRequest(packet_wrapper) => {
@@ -1674,32 +2441,12 @@ impl<S: Storage> P2pServer<S> {
                 let request = request.into_owned();
 
                 let page_id = request.page().unwrap_or(0);
-                let skip = page_id as usize * NOTIFY_MAX_LEN;
-
-                let packet = {
-                    let mempool = self.blockchain.get_mempool().read().await;
-                    let nonces_cache = mempool.get_caches();
-                    let txs = nonces_cache.values()
-                        .flat_map(|v| v.get_txs())
-                        .skip(skip).take(NOTIFY_MAX_LEN)
-                        .map(|tx| Cow::Borrowed(tx.as_ref()))
-                        .collect::<IndexSet<_>>();
-                    let mempool_size = mempool.size();
-                    let next_page = {
-                        if txs.len() == NOTIFY_MAX_LEN && mempool_size > skip && mempool_size - skip > NOTIFY_MAX_LEN {
-                            Some(page_id + 1)
-                        } else {
-                            None
-                        }
-                    };
-                    Packet::NotifyInventoryResponse(NotifyInventoryResponse::new(next_page, Cow::Owned(txs))).to_bytes()
-                };
-
-                peer.send_bytes(Bytes::from(packet)).await?
+                self.send_inventory_page_to(peer, page_id).await?;
             },
             Packet::NotifyInventoryResponse(inventory) => {
                 debug!("Received a notify inventory from {}: {} txs", peer, inventory.len());
-                if !peer.has_requested_inventory() {
+                // The first page of its mempool can be sent without request when connecting
+                if !peer.has_requested_inventory() && !peer.take_mempool_inventory_expected() {
                     warn!("Received a notify inventory from {} but we didn't request it", peer);
                     return Err(P2pError::InvalidPacket)
                 }
@@ -1733,10 +2480,10 @@ impl<S: Storage> P2pServer<S> {
                 }
 
                 // request the next page
-                if next_page.is_some() {
+                if next_page.is_some_and(is_inventory_page_allowed) {
                     trace!("Requesting next page of inventory from {}", peer);
                     let packet = Cow::Owned(NotifyInventoryRequest::new(next_page));
-                    let ping = Cow::Owned(self.build_generic_ping_packet().await);
+                    let ping = Cow::Owned(self.build_generic_ping_packet());
                     peer.set_requested_inventory(true);
                     peer.send_packet(Packet::NotifyInventoryRequest(PacketWrapper::new(packet, ping))).await?;
                 }
@@ -1795,10 +2542,20 @@ impl<S: Storage> P2pServer<S> {
     async fn listen_connection(self: &Arc<Self>, buf: &mut [u8], peer: &Arc<Peer>) -> Result<(), P2pError> {
         // Read & parse the packet
         // 16 additional bytes are for AEAD
-        let packet = peer.get_connection().read_packet(buf, PEER_MAX_PACKET_SIZE).await?;
+        // If we don't receive anything in time, the connection is half-open or the peer is dead
+        let (packet, packet_bytes) = match peer.get_connection().read_packet_with_timeout(buf, PEER_MAX_PACKET_SIZE, Duration::from_secs(P2P_READ_TIMEOUT)).await {
+            Ok(value) => value,
+            Err(e) => {
+                if let P2pError::ReadTimeout(_) = e {
+                    peer.close_with_reason(DisconnectReason::ReadTimeout).await?;
+                }
+                return Err(e)
+            }
+        };
+        peer.set_last_seen(get_current_time_in_seconds());
         let packet_id = packet.get_id();
         // Handle the packet
-        if let Err(e) = self.handle_incoming_packet(&peer, packet).await {
+        if let Err(e) = self.handle_incoming_packet(&peer, packet, packet_bytes).await {
             match e {
                 P2pError::Disconnected => {
                     debug!("Peer {} has disconnected, stopping...", peer);
@@ -1870,13 +2627,47 @@ impl<S: Storage> P2pServer<S> {
     async fn handle_chain_request(self: &Arc<Self>, peer: &Arc<Peer>, blocks: IndexSet<BlockId>, accepted_response_size: usize) -> Result<(), BlockchainError> {
         debug!("handle chain request for {} with {} blocks", peer, blocks.len());
         let storage = self.blockchain.get_storage().read().await;
+        // common point used to notify peer if he should rewind or not
+        let common_point = self.find_common_point(&*storage, blocks).await?;
+        let response = self.build_chain_response(peer, &*storage, common_point, accepted_response_size).await?;
+        peer.send_packet(Packet::ChainResponse(response)).await?;
+        Ok(())
+    }
+
+    // Send the blocks following the ones of our previous chain response
+    // The last block sent is used as common point, unless our chain got reorganized since
+    // in this case, no common point is sent back and the peer must send a new chain request
+    async fn handle_chain_continuation_request(&self, peer: &Arc<Peer>, token: &ChainSyncToken, accepted_response_size: usize) -> Result<(), BlockchainError> {
+        debug!("handle chain continuation request for {} from topoheight {}", peer, token.get_topoheight());
+        let storage = self.blockchain.get_storage().read().await;
+        let common_point = match peer.take_chain_sync_token().await {
+            Some((expected, hash)) if expected == *token => match storage.get_hash_at_topo_height(token.get_topoheight()).await {
+                Ok(current) if current == hash => Some(CommonPoint::new(hash, token.get_topoheight())),
+                _ => {
+                    debug!("Chain sync token of {} was invalidated by a reorg", peer);
+                    None
+                }
+            },
+            _ => {
+                debug!("{} sent an unknown chain sync token", peer);
+                None
+            }
+        };
+
+        let response = self.build_chain_response(peer, &*storage, common_point, accepted_response_size).await?;
+        peer.send_packet(Packet::ChainResponse(response)).await?;
+        Ok(())
+    }
+
+    // Build a chain response with our blocks following the common point
+    // A continuation token is given to the peer if we have more blocks than the response can contain
+    async fn build_chain_response(&self, peer: &Arc<Peer>, storage: &S, common_point: Option<CommonPoint>, accepted_response_size: usize) -> Result<ChainResponse, BlockchainError> {
         // blocks hashes sent for syncing (topoheight ordered)
         let mut response_blocks = IndexSet::new();
         let mut top_blocks = IndexSet::new();
-        // common point used to notify peer if he should rewind or not
-        let common_point = self.find_common_point(&*storage, blocks).await?;
         // Lowest height of the blocks sent
         let mut lowest_common_height = None;
+        let mut token = None;
 
         if let Some(common_point) = &common_point {
             let mut topoheight = common_point.get_topoheight();
@@ -1935,6 +2726,13 @@ impl<S: Storage> P2pServer<S> {
             }
             lowest_common_height = Some(lowest_height);
 
+            // We still have blocks to send, the peer can continue from the last one
+            let last_topoheight = topoheight - 1;
+            if last_topoheight < top_topoheight && peer.get_protocol_version() >= P2P_CHAIN_SYNC_TOKEN_PROTOCOL_VERSION {
+                let last_hash = storage.get_hash_at_topo_height(last_topoheight).await?;
+                token = Some((ChainSyncToken::new(self.blockchain.get_top_state().hash, last_topoheight), last_hash));
+            }
+
             // now, lets check if peer is near to be synced, and send him alt tips blocks
             if let Some(mut height) = unstable_height {
                 let top_height = self.blockchain.get_height();
@@ -1955,16 +2753,165 @@ impl<S: Storage> P2pServer<S> {
         }
 
         debug!("Sending {} blocks & {} top blocks as response to {}", response_blocks.len(), top_blocks.len(), peer);
-        peer.send_packet(Packet::ChainResponse(ChainResponse::new(common_point, lowest_common_height, response_blocks, top_blocks))).await?;
+        let mut response = ChainResponse::new(common_point, lowest_common_height, response_blocks, top_blocks);
+        response.set_token(token.as_ref().map(|(token, _)| token.clone()));
+        // A new response always replaces the previous token
+        peer.set_chain_sync_token(token).await;
+
+        Ok(response)
+    }
+
+    // Add a block received during the chain sync and verify the hints sent with it
+    // Its parents we don't have yet are requested and added first,
+    // they may come later in the chain response, or not at all for side blocks
+    async fn add_synced_block(&self, peer: &Arc<Peer>, block: Block, hash: Hash, hints: Option<BlockHints>) -> Result<(), BlockchainError> {
+        let mut pending = vec![(block, hash, hints)];
+        while let Some((block, hash, hints)) = pending.pop() {
+            trace!("Received block {} at height {} from {}", hash, block.get_height(), peer);
+            // It may have been prefetched as the parent of a previous block
+            if self.blockchain.has_block(&hash).await? {
+                trace!("Block {} is already in chain, skipping it", hash);
+                continue;
+            }
+
+            // No more than a batch of parents is prefetched, the block is then rejected for its missing tips
+            if pending.len() < CHAIN_SYNC_PIPELINE_BATCH_BLOCKS {
+                let mut missing_tip = None;
+                for tip in block.get_tips() {
+                    if !self.blockchain.has_block(tip).await? {
+                        missing_tip = Some(tip.clone());
+                        break;
+                    }
+                }
+
+                if let Some(tip) = missing_tip {
+                    debug!("Prefetching parent {} of block {} from {}", tip, hash, peer);
+                    let OwnedObjectResponse::Block(parent, parent_hash, parent_hints) = peer.request_blocking_object(ObjectRequest::Block(tip)).await? else {
+                        error!("{} sent us an invalid block response", peer);
+                        return Err(P2pError::ExpectedBlock.into())
+                    };
+
+                    pending.push((block, hash, hints));
+                    pending.push((parent, parent_hash, parent_hints));
+                    continue;
+                }
+            }
+
+            // Hints contradicting the header are dropped before anything else
+            let hints = hints.filter(|hints| hints.is_consistent_with(block.get_header(), None, None));
+            self.blockchain.add_new_block(block, false, false).await?;
+            if let Some(hints) = hints {
+                if self.verify_block_hints(peer, &hash, &hints).await? {
+                    trace!("Block {} is at topoheight {:?} for {}", hash, hints.topoheight, peer);
+                }
+            }
+        }
+
         Ok(())
     }
 
+    // Pipelining requires to request the blocks one by one and a peer supporting the continuation tokens
+    fn can_pipeline_chain_sync(&self, peer: &Peer) -> bool {
+        self.sync_pipelining && !self.allow_boost_sync() && peer.get_protocol_version() >= P2P_CHAIN_SYNC_TOKEN_PROTOCOL_VERSION
+    }
+
+    // Sync the blocks of a chain response, the next batch of blocks is downloaded while the previous one is added to the chain
+    // Once all its blocks are downloaded, the next chain response is requested using its continuation token
+    // Returns the count of blocks added and the next chain response to handle once they are added
+    async fn sync_chain_pipelined(&self, peer: &Arc<Peer>, blocks: IndexSet<Hash>, token: Option<ChainSyncToken>, max_size: usize) -> Result<(usize, Option<ChainResponse>), BlockchainError> {
+        // Only one batch can be downloaded ahead
+        let (sender, mut receiver) = mpsc::channel(1);
+        let download = self.download_chain_batches(peer, blocks, token, max_size, sender);
+        // The receiver is dropped on failure, which stops the download
+        let apply = async move {
+            let mut added = 0;
+            while let Some(mut batch) = receiver.recv().await {
+                // Validate the blocks of the batch in the order of the DAG of the peer
+                sort_blocks_by_hints(&mut batch);
+                for (block, hash, hints) in batch {
+                    self.add_synced_block(peer, block, hash, hints).await?;
+                    added += 1;
+                }
+            }
+            Ok::<_, BlockchainError>(added)
+        };
+
+        let (downloaded, added) = tokio::join!(download, apply);
+        // A failure while adding a block stops the download, report it first
+        let added = added?;
+        let next = downloaded?;
+        Ok((added, next))
+    }
+
+    // Download the blocks by batches and send them to be added to the chain
+    // Once all the blocks are downloaded, the next chain response is requested with the token
+    // It is returned to be verified like any chain response once the blocks are added
+    async fn download_chain_batches(&self, peer: &Arc<Peer>, blocks: IndexSet<Hash>, token: Option<ChainSyncToken>, max_size: usize, sender: Sender<Vec<(Block, Hash, Option<BlockHints>)>>) -> Result<Option<ChainResponse>, BlockchainError> {
+        let mut pending: VecDeque<Hash> = blocks.into_iter().collect();
+        while self.is_running() {
+            if pending.is_empty() {
+                let Some(token) = token else {
+                    break;
+                };
+
+                let response = peer.request_chain_continuation(ChainContinuationRequest::new(token.clone(), max_size as u16)).await?;
+                if response.blocks_size() > max_size {
+                    return Err(P2pError::InvaliChainResponseSize(response.blocks_size(), max_size).into())
+                }
+
+                let Some(common_point) = response.get_common_point() else {
+                    // Its chain changed since the previous response, a new chain request is needed
+                    debug!("{} invalidated our chain sync token at topoheight {}", peer, token.get_topoheight());
+                    break;
+                };
+
+                // The common point must be the last block of the previous response
+                if common_point.get_topoheight() != token.get_topoheight() {
+                    error!("{} continued the chain sync from topoheight {} instead of {}", peer, common_point.get_topoheight(), token.get_topoheight());
+                    return Err(P2pError::InvalidCommonPoint(common_point.get_topoheight()).into())
+                }
+
+                return Ok(Some(response))
+            }
+
+            // Wait until the previous batch is being added before downloading a new one
+            let Ok(permit) = sender.reserve().await else {
+                debug!("Chain sync with {} was stopped while adding blocks", peer);
+                break;
+            };
+
+            let mut batch = Vec::with_capacity(pending.len().min(CHAIN_SYNC_PIPELINE_BATCH_BLOCKS));
+            while batch.len() < CHAIN_SYNC_PIPELINE_BATCH_BLOCKS {
+                let Some(hash) = pending.pop_front() else {
+                    break;
+                };
+
+                if self.blockchain.has_block(&hash).await? {
+                    trace!("Block {} is already in chain, skipping it", hash);
+                    continue;
+                }
+
+                match peer.request_blocking_object(ObjectRequest::Block(hash)).await? {
+                    OwnedObjectResponse::Block(block, hash, hints) => batch.push((block, hash, hints)),
+                    _ => {
+                        error!("{} sent us an invalid block response", peer);
+                        return Err(P2pError::ExpectedBlock.into())
+                    }
+                }
+            }
+            permit.send(batch);
+        }
+
+        Ok(None)
+    }
+
     // Handle a chain response from another peer
     // We receive a list of blocks hashes ordered by their topoheight
     // It also contains a CommonPoint which is a block hash point where we have the same topoheight as our peer
     // Based on the lowest height of the chain sent, we may need to rewind some blocks
     // NOTE: Only a priority node can rewind below the stable height 
-    async fn handle_chain_response(&self, peer: &Arc<Peer>, mut response: ChainResponse, requested_max_size: usize) -> Result<(), BlockchainError> {
+    // Returns the next chain response received with a continuation token during a pipelined sync
+    async fn handle_chain_response(&self, peer: &Arc<Peer>, mut response: ChainResponse, requested_max_size: usize) -> Result<Option<ChainResponse>, BlockchainError> {
         trace!("handle chain response from {}", peer);
         let response_size = response.blocks_size();
 
@@ -1974,7 +2921,7 @@ impl<S: Storage> P2pServer<S> {
                 warn!("Peer have no common block but send us {} blocks!", response.blocks_size());
                 return Err(P2pError::InvalidPacket.into())
             }
-            return Ok(())
+            return Ok(None)
         };
 
         let common_topoheight = common_point.get_topoheight();
@@ -2014,11 +2961,13 @@ impl<S: Storage> P2pServer<S> {
 
         // Packet verification ended, handle the chain response now
 
+        let token = response.take_token();
         let (mut blocks, top_blocks) = response.consume();
         debug!("handling chain response from {}, {} blocks, {} top blocks, pop count {}", peer, blocks.len(), top_blocks.len(), pop_count);
 
         let our_previous_topoheight = self.blockchain.get_topo_height();
         let our_previous_height = self.blockchain.get_height();
+        let mut next_response = None;
         let top_len = top_blocks.len();
         let blocks_len = blocks.len();
 
@@ -2077,11 +3026,34 @@ impl<S: Storage> P2pServer<S> {
                     return Err(BlockchainError::LowerCumulativeDifficulty)
                 }
 
+                // Even with a heavier chain, we never go below our stable height unless the operator allowed it
+                let (common_height, depth) = chain_validator.get_common_ancestor_depth().await?;
+                let stable_height = self.blockchain.get_stable_height();
+                if reorg::is_deep_reorg(common_height, stable_height) && !self.blockchain.get_deep_reorg_guard().approve(depth, stable_height).await {
+                    error!("{} sent us a heavier chain with a common ancestor at height {} below our stable height {} ({} blocks deep), this may be an attack or a network split!", peer, common_height, stable_height, depth);
+                    if let Some(rpc) = self.blockchain.get_rpc().read().await.as_ref() {
+                        if rpc.is_event_tracked(&NotifyEvent::DeepReorgRejected).await {
+                            let value = DeepReorgRejectedEvent {
+                                peer_id: peer.get_id(),
+                                peer_addr: *peer.get_outgoing_address(),
+                                common_height,
+                                depth,
+                                stable_height
+                            };
+                            rpc.notify_clients_with(&NotifyEvent::DeepReorgRejected, value).await;
+                        }
+                    }
+
+                    return Err(BlockchainError::DeepReorgRejected(depth, stable_height))
+                }
+
                 // peer chain looks correct, lets rewind our chain
                 warn!("Rewinding chain because of {} (pop count: {})", peer, pop_count);
                 self.blockchain.rewind_chain(pop_count, false).await?;
 
                 // now retrieve all txs from all blocks header and add block in chain
+                // txs fetched are cached so a tx shared by several blocks is requested only once
+                let mut txs_cache: HashMap<Hash, Arc<Transaction>> = HashMap::new();
                 for (hash, header) in chain_validator.get_blocks() {
                     trace!("Processing block {} from chain validator", hash);
                     // we don't already have this block, lets retrieve its txs and add in our chain
@@ -2091,14 +3063,19 @@ impl<S: Storage> P2pServer<S> {
                             // check first on disk in case it was already fetch by a previous block
                             // it can happens as TXs can be integrated in multiple blocks and executed only one time
                             // check if we find it
-                            if let Some(tx) = self.blockchain.get_tx(tx_hash).await.ok() {
+                            if let Some(tx) = txs_cache.get(tx_hash) {
+                                trace!("Transaction {} was already fetched in this batch", tx_hash);
+                                transactions.push(Immutable::Arc(Arc::clone(tx)));
+                            } else if let Some(tx) = self.blockchain.get_tx(tx_hash).await.ok() {
                                 trace!("Found the transaction {} on disk", tx_hash);
                                 transactions.push(Immutable::Arc(tx));
                             } else { // otherwise, ask it from peer
                                 let response = peer.request_blocking_object(ObjectRequest::Transaction(tx_hash.clone())).await?;
                                 if let OwnedObjectResponse::Transaction(tx, _) = response {
                                     trace!("Received transaction {} at block {} from {}", tx_hash, hash, peer);
-                                    transactions.push(Immutable::Owned(tx));
+                                    let tx = Arc::new(tx);
+                                    txs_cache.insert(tx_hash.clone(), Arc::clone(&tx));
+                                    transactions.push(Immutable::Arc(tx));
                                 } else {
                                     error!("{} sent us an invalid block response", peer);
                                     return Err(P2pError::ExpectedTransaction.into())
@@ -2112,6 +3089,11 @@ impl<S: Storage> P2pServer<S> {
                     }
                 }
             }
+        } else if self.can_pipeline_chain_sync(peer) {
+            // no rewind are needed, download the next blocks while adding the previous ones
+            let (synced, next) = self.sync_chain_pipelined(peer, blocks, token, requested_max_size).await?;
+            info!("we've synced {} blocks with pipelining from {}", synced, peer);
+            next_response = next;
         } else {
             // no rewind are needed, process normally
             // it will first add blocks to sync, and then all alt-tips blocks if any (top blocks)
@@ -2149,9 +3131,8 @@ impl<S: Storage> P2pServer<S> {
                     } else {
                         // Otherwise, request them one by one and wait for the response
                         let response = peer.request_blocking_object(ObjectRequest::Block(hash)).await?;
-                        if let OwnedObjectResponse::Block(block, hash) = response {
-                            trace!("Received block {} at height {} from {}", hash, block.get_height(), peer);
-                            self.blockchain.add_new_block(block, false, false).await?;
+                        if let OwnedObjectResponse::Block(block, hash, hints) = response {
+                            self.add_synced_block(peer, block, hash, hints).await?;
                         } else {
                             error!("{} sent us an invalid block response", peer);
                             return Err(P2pError::ExpectedBlock.into())
@@ -2188,6 +3169,20 @@ impl<S: Storage> P2pServer<S> {
                 }
             }
             info!("we've synced {} on {} blocks and {} top blocks from {}", total_requested, blocks_len, top_len, peer);
+
+            // The peer has more blocks than it could send, continue directly by topoheight
+            // while its chain is a straight line from ours
+            if pop_count == 0 && blocks_len >= requested_max_size && peer.get_protocol_version() >= P2P_BLOCK_AT_TOPOHEIGHT_PROTOCOL_VERSION {
+                let mut synced = 0;
+                while self.is_running() {
+                    let added = self.sync_blocks_by_topoheight(peer, requested_max_size).await?;
+                    if added == 0 {
+                        break;
+                    }
+                    synced += added;
+                }
+                info!("we've synced {} blocks by topoheight from {}", synced, peer);
+            }
         }
 
         let peer_topoheight = peer.get_topoheight();
@@ -2203,7 +3198,59 @@ impl<S: Storage> P2pServer<S> {
             }
         }
 
-        Ok(())
+        Ok(next_response)
+    }
+
+    // Request the blocks following our topoheight directly by their topoheight
+    // This skips the chain request needed to learn their hashes
+    // The topoheight is only used to locate them, each block is verified as usual
+    // Stops once a block doesn't extend our chain, a chain request is then needed to find the common point
+    // Returns the count of blocks added
+    async fn sync_blocks_by_topoheight(&self, peer: &Arc<Peer>, max_size: usize) -> Result<usize, BlockchainError> {
+        let Some(batch) = get_topoheight_sync_batch(self.blockchain.get_topo_height(), peer.get_topoheight(), max_size) else {
+            return Ok(0)
+        };
+
+        let mut added = 0;
+        for topoheight in batch {
+            let response = match peer.request_blocking_object(ObjectRequest::BlockAtTopoheight(topoheight)).await {
+                Ok(response) => response,
+                // Peer may have rewinded its chain since its last ping
+                Err(P2pError::ObjectNotFound(_)) => {
+                    debug!("{} has no block at topoheight {} anymore", peer, topoheight);
+                    break;
+                },
+                // It pruned its chain since the peer selection, fallback on a chain request
+                Err(P2pError::ObjectPrunedByPeer(_)) => {
+                    debug!("{} has pruned the block at topoheight {}", peer, topoheight);
+                    break;
+                },
+                Err(e) => return Err(e.into())
+            };
+
+            let OwnedObjectResponse::BlockAtTopoheight(block, hash, _) = response else {
+                error!("{} sent us an invalid block response", peer);
+                return Err(P2pError::ExpectedBlock.into())
+            };
+
+            if self.blockchain.has_block(&hash).await? {
+                trace!("Block {} at topoheight {} is already in chain, skipping it", hash, topoheight);
+                continue;
+            }
+
+            for tip in block.get_tips() {
+                if !self.blockchain.has_block(tip).await? {
+                    debug!("Block {} at topoheight {} from {} doesn't extend our chain", hash, topoheight, peer);
+                    return Ok(added)
+                }
+            }
+
+            trace!("Received block {} at topoheight {} from {}", hash, topoheight, peer);
+            self.blockchain.add_new_block(block, false, false).await?;
+            added += 1;
+        }
+
+        Ok(added)
     }
 
     // determine if we are connected to a priority node and that this node is equal / greater to our chain
@@ -2242,6 +3289,13 @@ impl<S: Storage> P2pServer<S> {
         self.get_peer_count().await < self.get_max_peers()
     }
 
+    // Verify if we can accept a new inbound connection
+    // When the peerlist is full, a peer may be evicted to make room for it
+    async fn can_accept_inbound_connection(&self) -> bool {
+        self.accept_new_connections().await
+            || (self.peer_eviction && self.peer_list.find_peer_to_evict().await.is_some())
+    }
+
     // Returns the count of peers connected
     pub async fn get_peer_count(&self) -> usize {
         self.peer_list.size().await
@@ -2267,9 +3321,14 @@ impl<S: Storage> P2pServer<S> {
         self.is_internal_id(*peer_id) || self.peer_list.has_peer(peer_id).await
     }
 
+    // When two nodes are connected twice, both keep the connection dialed by the lowest peer id
+    pub fn is_preferred_connection(&self, is_out: bool, peer_id: u64) -> bool {
+        is_out == (self.peer_id < peer_id)
+    }
+
     // Check if we are already connected to a socket address (IPv4 or IPv6) including its port
     pub async fn is_connected_to_addr(&self, peer_addr: &SocketAddr) -> bool {
-        if *peer_addr == *self.get_bind_address() { // don't try to connect to ourself
+        if self.bind_addresses.contains(peer_addr) { // don't try to connect to ourself
             debug!("Trying to connect to ourself, ignoring.");
             return true
         }
@@ -2277,9 +3336,18 @@ impl<S: Storage> P2pServer<S> {
         self.peer_list.is_connected_to_addr(peer_addr).await
     }
 
-    // get the socket address on which we are listening
-    pub fn get_bind_address(&self) -> &SocketAddr {
-        &self.bind_address
+    // get the socket addresses on which we are listening
+    pub fn get_bind_addresses(&self) -> &[SocketAddr] {
+        &self.bind_addresses
+    }
+
+    // Get the port we are listening on for the address family of the remote address
+    // Fallback on the first bind address if we don't listen on its family
+    pub fn get_local_port_for(&self, addr: &SocketAddr) -> u16 {
+        self.bind_addresses.iter()
+            .find(|bind| bind.is_ipv6() == addr.is_ipv6())
+            .unwrap_or(&self.bind_addresses[0])
+            .port()
     }
 
     // Get our peerlist
@@ -2287,12 +3355,17 @@ impl<S: Storage> P2pServer<S> {
         &self.peer_list
     }
 
+    // Get how many compact blocks were reconstructed from our mempool and how many failed
+    pub fn get_compact_blocks_stats(&self) -> (u64, u64) {
+        (self.compact_blocks_reconstructed.load(Ordering::Relaxed), self.compact_blocks_failed.load(Ordering::Relaxed))
+    }
+
     // Broadcast a new transaction hash using propagation packet
     // This is used so we don't overload the network during spam or high transactions count
     // We simply share its hash to nodes and others nodes can check if they have it already or not
     pub async fn broadcast_tx_hash(&self, tx: Hash) {
         debug!("Broadcasting tx hash {}", tx);
-        let ping = self.build_generic_ping_packet().await;
+        let ping = self.build_generic_ping_packet();
         debug!("Ping packet has been generated for tx broadcast");
         let current_topoheight = ping.get_topoheight();
         let packet = Packet::TransactionPropagation(PacketWrapper::new(Cow::Borrowed(&tx), Cow::Owned(ping)));
@@ -2332,8 +3405,18 @@ impl<S: Storage> P2pServer<S> {
         // we build the ping packet ourself this time (we have enough data for it)
         // because this function can be call from Blockchain, which would lead to a deadlock
         let ping = Ping::new(Cow::Borrowed(hash), our_topoheight, our_height, pruned_topoheight, cumulative_difficulty, IndexSet::new());
-        let block_packet = Packet::BlockPropagation(PacketWrapper::new(Cow::Borrowed(block), Cow::Borrowed(&ping)));
-        let packet_block_bytes = Bytes::from(block_packet.to_bytes());
+        // If the block was relayed, reuse the header bytes received
+        // Otherwise (block produced locally), serialize it
+        let header_bytes = self.blocks_header_bytes.lock().await.pop(hash);
+        let packet_block_bytes = packet::build_block_propagation_packet(block, header_bytes.as_deref(), &ping);
+        // Peers supporting it receive only the short ids of the transactions
+        // A block without transactions is sent as is
+        let packet_compact_block_bytes = if self.compact_blocks && block.get_txs_count() > 0 {
+            let compact_block = CompactBlock::new(block, hash.clone());
+            Some(Bytes::from(Packet::CompactBlockPropagation(PacketWrapper::new(Cow::Owned(compact_block), Cow::Borrowed(&ping))).to_bytes()))
+        } else {
+            None
+        };
         let packet_ping_bytes = Bytes::from(Packet::Ping(Cow::Owned(ping)).to_bytes());
 
         trace!("Locking peer list for broadcasting block {}", hash);
@@ -2357,8 +3440,16 @@ impl<S: Storage> P2pServer<S> {
                     // he should not send it back to us if it's a block found by us
                     blocks_propagation.put(hash.clone(), if lock { Direction::Both } else { Direction::Out });
 
+                    let bytes = match packet_compact_block_bytes.as_ref() {
+                        Some(bytes) if peer.use_compact_blocks() => {
+                            peer.on_compact_block_sent(hash).await;
+                            bytes.clone()
+                        },
+                        _ => packet_block_bytes.clone()
+                    };
+
                     debug!("Broadcast {} to {} (lock: {})", hash, peer, lock);
-                    if let Err(e) = peer.send_bytes(packet_block_bytes.clone()).await {
+                    if let Err(e) = peer.send_bytes(bytes).await {
                         debug!("Error on broadcast block {} to {}: {}", hash, peer, e);
                     }
                     trace!("{} has been broadcasted to {}", hash, peer);
@@ -2422,8 +3513,13 @@ impl<S: Storage> P2pServer<S> {
                 }
 
                 let page = page.unwrap_or(0);
-                let assets = storage.get_partial_assets(MAX_ITEMS_PER_PAGE, page as usize * MAX_ITEMS_PER_PAGE, min, max).await?;
+                // Continue right after the previous page if possible
+                let mut cursor = peer.get_bootstrap_assets_cursor().lock().await;
+                let after = cursor.take().and_then(|c| c.resume(min, max, page));
+                let skip = if after.is_some() { 0 } else { page as usize * MAX_ITEMS_PER_PAGE };
+                let assets = storage.get_partial_assets(MAX_ITEMS_PER_PAGE, skip, min, max, after.as_ref()).await?;
                 let page = if assets.len() == MAX_ITEMS_PER_PAGE {
+                    *cursor = assets.last().map(|asset| PageCursor::new(min, max, page + 1, asset.get_asset().clone()));
                     Some(page + 1)
                 } else {
                     None
@@ -2454,8 +3550,13 @@ impl<S: Storage> P2pServer<S> {
                 }
 
                 let page = page.unwrap_or(0);
-                let keys = storage.get_partial_keys(MAX_ITEMS_PER_PAGE, page as usize * MAX_ITEMS_PER_PAGE, min, max).await?;
+                // Continue right after the previous page if possible
+                let mut cursor = peer.get_bootstrap_keys_cursor().lock().await;
+                let after = cursor.take().and_then(|c| c.resume(min, max, page));
+                let skip = if after.is_some() { 0 } else { page as usize * MAX_ITEMS_PER_PAGE };
+                let keys = storage.get_partial_keys(MAX_ITEMS_PER_PAGE, skip, min, max, after.as_ref()).await?;
                 let page = if keys.len() == MAX_ITEMS_PER_PAGE {
+                    *cursor = keys.last().map(|key| PageCursor::new(min, max, page + 1, key.clone()));
                     Some(page + 1)
                 } else {
                     None
@@ -2759,12 +3860,26 @@ impl<S: Storage> P2pServer<S> {
         Ok(())
     }
 
+    // Send a page of our mempool inventory to a peer
+    async fn send_inventory_page_to(&self, peer: &Arc<Peer>, page_id: u8) -> Result<(), P2pError> {
+        let packet = {
+            let mempool = self.blockchain.get_mempool().read().await;
+            let txs = mempool.get_caches()
+                .values()
+                .flat_map(|v| v.get_txs())
+                .map(|tx| tx.as_ref());
+            Packet::NotifyInventoryResponse(build_inventory_page(txs, mempool.size(), page_id)).to_bytes()
+        };
+
+        peer.send_bytes(Bytes::from(packet)).await
+    }
+
     // Request the inventory of a peer
     // This will sends him a request packet so we get notified of all its TXs hashes in its mempool
     async fn request_inventory_of(&self, peer: &Arc<Peer>) -> Result<(), BlockchainError> {
         debug!("Requesting inventory of {}", peer);
         let packet = Cow::Owned(NotifyInventoryRequest::new(None));
-        let ping = Cow::Owned(self.build_generic_ping_packet().await);
+        let ping = Cow::Owned(self.build_generic_ping_packet());
         peer.set_requested_inventory(true);
         peer.send_packet(Packet::NotifyInventoryRequest(PacketWrapper::new(packet, ping))).await?;
         Ok(())
@@ -2774,7 +3889,7 @@ impl<S: Storage> P2pServer<S> {
     // we send up to CHAIN_SYNC_REQUEST_MAX_BLOCKS blocks id (combinaison of block hash and topoheight)
     // we add at the end the genesis block to be sure to be on the same chain as others peers
     // its used to find a common point with the peer to which we ask the chain
-    pub async fn request_sync_chain_for(&self, peer: &Arc<Peer>, last_chain_sync: &mut TimestampMillis) -> Result<(), BlockchainError> {
+    pub async fn request_sync_chain_for(&self, peer: &Arc<Peer>, last_chain_sync: &mut u64) -> Result<(), BlockchainError> {
         trace!("Requesting chain from {}", peer);
 
         // This can be configured by the node operator, it will be adjusted between protocol bounds
@@ -2786,7 +3901,7 @@ impl<S: Storage> P2pServer<S> {
             let storage = self.blockchain.get_storage().read().await;
             let request = ChainRequest::new(self.build_list_of_blocks_id(&*storage).await?, requested_max_size as u16);
             trace!("Built a chain request with {} blocks", request.size());
-            let ping = self.build_generic_ping_packet_with_storage(&*storage).await;
+            let ping = self.build_generic_ping_packet();
             PacketWrapper::new(Cow::Owned(request), Cow::Owned(ping))
         };
 
@@ -2800,7 +3915,13 @@ impl<S: Storage> P2pServer<S> {
         // Update last chain sync time
         *last_chain_sync = get_current_time_in_millis();
 
-        self.handle_chain_response(peer, response, requested_max_size).await
+        // Continuation responses are verified like the first one, once its blocks are added
+        let mut next_response = self.handle_chain_response(peer, response, requested_max_size).await?;
+        while let Some(response) = next_response {
+            next_response = self.handle_chain_response(peer, response, requested_max_size).await?;
+        }
+
+        Ok(())
     }
 
     // Clear all p2p connections by kicking peers
@@ -2822,4 +3943,39 @@ pub fn is_local_address(socket_addr: &SocketAddr) -> bool {
             ipv6.is_loopback() // || ipv6.is_unique_local()
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::network::Network;
+    use crate::{
+        config::PRUNE_SAFETY_LIMIT,
+        core::storage::PrunedTopoheightProvider,
+        testing::{mine_block, TestNode}
+    };
+
+    #[tokio::test]
+    async fn test_ping_without_storage_reads() {
+        let node = TestNode::new(Network::Dev).await.unwrap();
+        for _ in 0..PRUNE_SAFETY_LIMIT + 10 {
+            mine_block(&node, Vec::new()).await.unwrap();
+        }
+        let blockchain = node.get_blockchain();
+        let pruned_topoheight = blockchain.prune_until_topoheight(5).await.unwrap();
+        let p2p = blockchain.get_p2p().read().await.clone().unwrap();
+
+        // The storage is locked like while a block is being added,
+        // the ping is still built as no storage read is needed
+        let storage = blockchain.get_storage().write().await;
+        let ping = p2p.build_generic_ping_packet();
+        let top_state = blockchain.get_top_state();
+        assert_eq!(ping.get_topoheight(), top_state.topoheight);
+        assert_eq!(ping.get_topoheight(), PRUNE_SAFETY_LIMIT + 10);
+        assert_eq!(ping.get_height(), top_state.height);
+        assert_eq!(ping.get_pruned_topoheight(), Some(pruned_topoheight));
+        assert_eq!(storage.get_pruned_topoheight().await.unwrap(), Some(pruned_topoheight));
+        drop(storage);
+
+        node.stop().await;
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet};
+use xelis_common::{
+    crypto::Hash,
+    time::TimestampSeconds
+};
+
+struct OrphanBlock<T> {
+    data: T,
+    // Parents not yet in our chain
+    missing: HashSet<Hash>,
+    added_at: TimestampSeconds
+}
+
+// Blocks received before their parents
+// They are kept until all their parents are added in chain, or until they are evicted
+// An orphan is never relayed: it is only propagated once executed by the chain
+pub struct OrphanPool<T> {
+    orphans: HashMap<Hash, OrphanBlock<T>>,
+    // missing parent => orphans waiting on it
+    waiting: HashMap<Hash, HashSet<Hash>>,
+    max_size: usize,
+    max_age: TimestampSeconds
+}
+
+impl<T> OrphanPool<T> {
+    pub fn new(max_size: usize, max_age: TimestampSeconds) -> Self {
+        Self {
+            orphans: HashMap::new(),
+            waiting: HashMap::new(),
+            max_size,
+            max_age
+        }
+    }
+
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.orphans.contains_key(hash)
+    }
+
+    // Is an orphan already waiting on this parent
+    pub fn is_waiting_on(&self, parent: &Hash) -> bool {
+        self.waiting.contains_key(parent)
+    }
+
+    pub fn len(&self) -> usize {
+        self.orphans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orphans.is_empty()
+    }
+
+    // All parents that at least one orphan is waiting on
+    pub fn get_missing_parents(&self) -> Vec<Hash> {
+        self.waiting.keys().cloned().collect()
+    }
+
+    // Store a block waiting on its missing parents
+    // Expired orphans are evicted first, then the oldest ones if the pool is full
+    // Returns the hashes of the evicted orphans
+    pub fn insert(&mut self, hash: Hash, data: T, missing: HashSet<Hash>, now: TimestampSeconds) -> Vec<Hash> {
+        if self.contains(&hash) || missing.is_empty() {
+            return Vec::new()
+        }
+
+        let mut evicted = self.evict_expired(now);
+        while self.orphans.len() >= self.max_size {
+            let Some(oldest) = self.orphans.iter()
+                .min_by_key(|(_, orphan)| orphan.added_at)
+                .map(|(hash, _)| hash.clone()) else {
+                break;
+            };
+            self.remove(&oldest);
+            evicted.push(oldest);
+        }
+
+        if self.max_size == 0 {
+            evicted.push(hash);
+            return evicted
+        }
+
+        for parent in missing.iter() {
+            self.waiting.entry(parent.clone()).or_default().insert(hash.clone());
+        }
+        self.orphans.insert(hash, OrphanBlock {
+            data,
+            missing,
+            added_at: now
+        });
+
+        evicted
+    }
+
+    // A parent was added in chain
+    // Returns the orphans that are no longer missing any parent, they are removed from the pool
+    pub fn on_block_connected(&mut self, hash: &Hash) -> Vec<(Hash, T)> {
+        let Some(children) = self.waiting.remove(hash) else {
+            return Vec::new()
+        };
+
+        let mut ready = Vec::new();
+        for child in children {
+            let Some(orphan) = self.orphans.get_mut(&child) else {
+                continue;
+            };
+
+            orphan.missing.remove(hash);
+            if orphan.missing.is_empty() {
+                if let Some(orphan) = self.orphans.remove(&child) {
+                    ready.push((child, orphan.data));
+                }
+            }
+        }
+
+        ready
+    }
+
+    // Evict all orphans older than the max age
+    pub fn evict_expired(&mut self, now: TimestampSeconds) -> Vec<Hash> {
+        let expired: Vec<Hash> = self.orphans.iter()
+            .filter(|(_, orphan)| now.saturating_sub(orphan.added_at) > self.max_age)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+
+        for hash in expired.iter() {
+            self.remove(hash);
+        }
+
+        expired
+    }
+
+    // Remove an orphan from the pool
+    pub fn remove(&mut self, hash: &Hash) -> Option<T> {
+        let orphan = self.orphans.remove(hash)?;
+        for parent in orphan.missing.iter() {
+            if let Some(children) = self.waiting.get_mut(parent) {
+                children.remove(hash);
+                if children.is_empty() {
+                    self.waiting.remove(parent);
+                }
+            }
+        }
+
+        Some(orphan.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(n: u8) -> Hash {
+        Hash::new([n; 32])
+    }
+
+    fn parents(hashes: &[u8]) -> HashSet<Hash> {
+        hashes.iter().map(|n| hash(*n)).collect()
+    }
+
+    // Simulate the chain: connect a block and all the orphans it releases, in dependency order
+    fn connect(pool: &mut OrphanPool<u8>, block: u8) -> Vec<u8> {
+        let mut connected = Vec::new();
+        let mut queue = vec![hash(block)];
+        while let Some(hash) = queue.pop() {
+            for (child, data) in pool.on_block_connected(&hash) {
+                connected.push(data);
+                queue.push(child);
+            }
+        }
+        connected
+    }
+
+    #[test]
+    fn test_child_before_parent() {
+        let mut pool = OrphanPool::new(16, 60);
+        assert!(pool.insert(hash(2), 2, parents(&[1]), 0).is_empty());
+        assert!(pool.contains(&hash(2)));
+        assert!(pool.is_waiting_on(&hash(1)));
+
+        // Unrelated block doesn't release anything
+        assert!(connect(&mut pool, 3).is_empty());
+        assert_eq!(connect(&mut pool, 1), vec![2]);
+        assert!(pool.is_empty());
+        assert!(pool.get_missing_parents().is_empty());
+    }
+
+    #[test]
+    fn test_deep_chain_of_orphans() {
+        let mut pool = OrphanPool::new(16, 60);
+        // Received in reverse order: 5 -> 4 -> 3 -> 2, all waiting on 1
+        for n in (2..=5).rev() {
+            pool.insert(hash(n), n, parents(&[n - 1]), 0);
+        }
+        assert_eq!(pool.len(), 4);
+        assert_eq!(pool.get_missing_parents().len(), 4);
+
+        // Every orphan is connected after its parent
+        assert_eq!(connect(&mut pool, 1), vec![2, 3, 4, 5]);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_orphan_waits_on_all_parents() {
+        let mut pool = OrphanPool::new(16, 60);
+        pool.insert(hash(4), 4, parents(&[1, 2, 3]), 0);
+        assert!(connect(&mut pool, 2).is_empty());
+        assert!(connect(&mut pool, 1).is_empty());
+        assert!(pool.contains(&hash(4)));
+        assert_eq!(connect(&mut pool, 3), vec![4]);
+
+        // Two orphans sharing a parent are both released
+        pool.insert(hash(5), 5, parents(&[10]), 0);
+        pool.insert(hash(6), 6, parents(&[10]), 0);
+        let mut released = connect(&mut pool, 10);
+        released.sort();
+        assert_eq!(released, vec![5, 6]);
+    }
+
+    #[test]
+    fn test_eviction_by_age() {
+        let mut pool = OrphanPool::new(16, 60);
+        pool.insert(hash(2), 2, parents(&[1]), 0);
+        pool.insert(hash(3), 3, parents(&[1]), 30);
+        assert!(pool.evict_expired(60).is_empty());
+
+        assert_eq!(pool.evict_expired(61), vec![hash(2)]);
+        assert!(!pool.contains(&hash(2)));
+        assert_eq!(connect(&mut pool, 1), vec![3]);
+
+        // Expired orphans are evicted on insert
+        pool.insert(hash(4), 4, parents(&[1]), 100);
+        assert_eq!(pool.insert(hash(5), 5, parents(&[1]), 200), vec![hash(4)]);
+        assert!(!pool.contains(&hash(4)));
+    }
+
+    #[test]
+    fn test_eviction_by_size() {
+        let mut pool = OrphanPool::new(2, 60);
+        pool.insert(hash(2), 2, parents(&[1]), 0);
+        pool.insert(hash(3), 3, parents(&[1]), 1);
+        assert_eq!(pool.insert(hash(4), 4, parents(&[9]), 2), vec![hash(2)]);
+        assert_eq!(pool.len(), 2);
+
+        assert_eq!(connect(&mut pool, 1), vec![3]);
+        assert_eq!(connect(&mut pool, 9), vec![4]);
+
+        // Duplicates and blocks without missing parents are ignored
+        pool.insert(hash(5), 5, parents(&[1]), 3);
+        assert!(pool.insert(hash(5), 5, parents(&[1]), 4).is_empty());
+        pool.insert(hash(6), 6, HashSet::new(), 4);
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.remove(&hash(5)), Some(5));
+        assert!(!pool.is_waiting_on(&hash(1)));
+    }
+}
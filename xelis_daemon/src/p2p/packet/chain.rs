@@ -143,6 +143,96 @@ impl Serializer for ChainRequest {
     }
 }
 
+// Given in a chain response to request the next blocks without searching the common point again
+// It is only valid for the peer it was given to and until the chain of the responder is reorganized
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainSyncToken {
+    // Top block hash of the responder when the token was created
+    top_hash: Hash,
+    // Topoheight of the last block sent
+    topoheight: u64
+}
+
+impl ChainSyncToken {
+    pub fn new(top_hash: Hash, topoheight: u64) -> Self {
+        Self {
+            top_hash,
+            topoheight
+        }
+    }
+
+    pub fn get_top_hash(&self) -> &Hash {
+        &self.top_hash
+    }
+
+    pub fn get_topoheight(&self) -> u64 {
+        self.topoheight
+    }
+}
+
+impl Serializer for ChainSyncToken {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_hash(&self.top_hash);
+        writer.write_u64(&self.topoheight);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let top_hash = reader.read_hash()?;
+        let topoheight = reader.read_u64()?;
+        Ok(Self { top_hash, topoheight })
+    }
+
+    fn size(&self) -> usize {
+        self.top_hash.size() + self.topoheight.size()
+    }
+}
+
+// Request the blocks following the ones of a previous chain response
+#[derive(Clone, Debug)]
+pub struct ChainContinuationRequest {
+    token: ChainSyncToken,
+    accepted_response_size: u16
+}
+
+impl ChainContinuationRequest {
+    pub fn new(token: ChainSyncToken, accepted_response_size: u16) -> Self {
+        Self {
+            token,
+            accepted_response_size
+        }
+    }
+
+    pub fn get_token(&self) -> &ChainSyncToken {
+        &self.token
+    }
+
+    pub fn get_accepted_response_size(&self) -> u16 {
+        self.accepted_response_size
+    }
+}
+
+impl Serializer for ChainContinuationRequest {
+    fn write(&self, writer: &mut Writer) {
+        self.token.write(writer);
+        writer.write_u16(self.accepted_response_size);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let token = ChainSyncToken::read(reader)?;
+        let accepted_response_size = reader.read_u16()?;
+        if accepted_response_size < CHAIN_SYNC_RESPONSE_MIN_BLOCKS as u16 || accepted_response_size > CHAIN_SYNC_RESPONSE_MAX_BLOCKS as u16 {
+            debug!("Invalid accepted response size: {}", accepted_response_size);
+            return Err(ReaderError::InvalidValue)
+        }
+
+        Ok(Self { token, accepted_response_size })
+    }
+
+    fn size(&self) -> usize {
+        self.token.size() + self.accepted_response_size.size()
+    }
+}
+
 #[derive(Debug)]
 pub struct CommonPoint {
     hash: Hash,
@@ -191,7 +281,10 @@ pub struct ChainResponse {
     // Lowest height of the blocks in the response
     lowest_height: Option<u64>,
     blocks: IndexSet<Hash>,
-    top_blocks: IndexSet<Hash>
+    top_blocks: IndexSet<Hash>,
+    // Set if the responder has more blocks to send
+    // Only sent to peers supporting it
+    token: Option<ChainSyncToken>
 }
 
 impl ChainResponse {
@@ -201,10 +294,20 @@ impl ChainResponse {
             common_point,
             lowest_height,
             blocks,
-            top_blocks
+            top_blocks,
+            token: None
         }
     }
 
+    pub fn set_token(&mut self, token: Option<ChainSyncToken>) {
+        self.token = token;
+    }
+
+    // Get the token to request the next blocks
+    pub fn take_token(&mut self) -> Option<ChainSyncToken> {
+        self.token.take()
+    }
+
     // Get the common point for this response
     pub fn get_common_point(&mut self) -> Option<CommonPoint> {
         self.common_point.take()
@@ -248,6 +351,10 @@ impl Serializer for ChainResponse {
         for hash in &self.top_blocks {
             writer.write_hash(hash);
         }
+
+        if let Some(token) = &self.token {
+            token.write(writer);
+        }
     }
 
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
@@ -288,7 +395,12 @@ impl Serializer for ChainResponse {
             }
         }
 
-        Ok(Self::new(common_point, Some(lowest_height), blocks, top_blocks))
+        let mut response = Self::new(common_point, Some(lowest_height), blocks, top_blocks);
+        if reader.size() > 0 {
+            response.set_token(Some(ChainSyncToken::read(reader)?));
+        }
+
+        Ok(response)
     }
 
     fn size(&self) -> usize {
@@ -301,6 +413,31 @@ impl Serializer for ChainResponse {
             size += lowest_height.size();
         }
 
-        size + 2 + self.blocks.len() + 1 + self.top_blocks.len()
+        size + 2 + self.blocks.len() + 1 + self.top_blocks.len() + self.token.as_ref().map_or(0, |token| token.size())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_response_token() {
+        let blocks: IndexSet<Hash> = (1..=3u8).map(|i| Hash::new([i; 32])).collect();
+        let mut response = ChainResponse::new(Some(CommonPoint::new(Hash::zero(), 10)), Some(5), blocks.clone(), IndexSet::new());
+        let mut decoded = ChainResponse::from_bytes(&response.to_bytes()).unwrap();
+        assert!(decoded.take_token().is_none());
+
+        let token = ChainSyncToken::new(Hash::max(), 13);
+        response.set_token(Some(token.clone()));
+        let mut decoded = ChainResponse::from_bytes(&response.to_bytes()).unwrap();
+        assert_eq!(decoded.take_token(), Some(token.clone()));
+        assert_eq!(decoded.get_common_point().map(|point| point.get_topoheight()), Some(10));
+        assert_eq!(decoded.consume().0, blocks);
+
+        let request = ChainContinuationRequest::new(token.clone(), CHAIN_SYNC_RESPONSE_MIN_BLOCKS as u16);
+        let decoded = ChainContinuationRequest::from_bytes(&request.to_bytes()).unwrap();
+        assert_eq!(decoded.get_token(), &token);
+        assert!(ChainContinuationRequest::from_bytes(&ChainContinuationRequest::new(token, 1).to_bytes()).is_err());
     }
 }
\ No newline at end of file
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use indexmap::IndexSet;
+use log::debug;
+use xelis_common::{
+    block::BlockHeader,
+    crypto::{hash, Hash, Hashable, HASH_SIZE},
+    serializer::{Reader, ReaderError, Serializer, Writer}
+};
+
+// Size in bytes of a short transaction id
+pub const SHORT_TX_ID_SIZE: usize = 8;
+
+pub type ShortTxId = [u8; SHORT_TX_ID_SIZE];
+
+// Compute the short id of a transaction for a block
+// The block hash is used as salt so collisions can't be precomputed for every block
+pub fn short_tx_id(block_hash: &Hash, tx_hash: &Hash) -> ShortTxId {
+    let mut bytes = [0u8; HASH_SIZE * 2];
+    bytes[..HASH_SIZE].copy_from_slice(block_hash.as_bytes());
+    bytes[HASH_SIZE..].copy_from_slice(tx_hash.as_bytes());
+
+    let mut id = [0u8; SHORT_TX_ID_SIZE];
+    id.copy_from_slice(&hash(&bytes).as_bytes()[..SHORT_TX_ID_SIZE]);
+    id
+}
+
+// Short ids of the transactions we know, resolved to their full hash
+// If two transactions share the same short id, it can't be resolved and is set to None
+pub struct ShortIdsResolver<'a> {
+    ids: HashMap<ShortTxId, Option<&'a Hash>>
+}
+
+impl<'a> ShortIdsResolver<'a> {
+    pub fn new<I: Iterator<Item = &'a Hash>>(block_hash: &Hash, candidates: I) -> Self {
+        let mut resolver = Self {
+            ids: HashMap::new()
+        };
+
+        for tx_hash in candidates {
+            resolver.insert(short_tx_id(block_hash, tx_hash), tx_hash);
+        }
+
+        resolver
+    }
+
+    fn insert(&mut self, id: ShortTxId, tx_hash: &'a Hash) {
+        self.ids.entry(id)
+            .and_modify(|v| if v.is_some_and(|h| h != tx_hash) {
+                debug!("Short id collision for transaction {}", tx_hash);
+                *v = None;
+            })
+            .or_insert(Some(tx_hash));
+    }
+
+    pub fn resolve(&self, id: &ShortTxId) -> Option<&'a Hash> {
+        self.ids.get(id).copied().flatten()
+    }
+}
+
+// A block header sent with short transaction ids instead of the full hashes
+// The receiver rebuild the header using the transactions from its mempool
+#[derive(Clone, Debug)]
+pub struct CompactBlock {
+    // Header without any transaction hash
+    header: BlockHeader,
+    block_hash: Hash,
+    short_ids: Vec<ShortTxId>
+}
+
+impl CompactBlock {
+    pub fn new(header: &BlockHeader, block_hash: Hash) -> Self {
+        let short_ids = header.get_txs_hashes().iter()
+            .map(|tx| short_tx_id(&block_hash, tx))
+            .collect();
+
+        let mut header = header.clone();
+        header.set_txs_hashes(IndexSet::new());
+
+        Self {
+            header,
+            block_hash,
+            short_ids
+        }
+    }
+
+    pub fn get_block_hash(&self) -> &Hash {
+        &self.block_hash
+    }
+
+    pub fn get_short_ids(&self) -> &Vec<ShortTxId> {
+        &self.short_ids
+    }
+
+    // Rebuild the full header using the resolver
+    // Returns None if a transaction is unknown, ambiguous or if the rebuilt header doesn't match the block hash
+    pub fn reconstruct(&self, resolver: &ShortIdsResolver) -> Option<BlockHeader> {
+        let mut txs_hashes = IndexSet::with_capacity(self.short_ids.len());
+        for id in self.short_ids.iter() {
+            let tx_hash = resolver.resolve(id)?;
+            if !txs_hashes.insert(tx_hash.clone()) {
+                return None
+            }
+        }
+
+        let mut header = self.header.clone();
+        header.set_txs_hashes(txs_hashes);
+        if header.hash() != self.block_hash {
+            debug!("Compact block {} reconstructed with an invalid hash", self.block_hash);
+            return None
+        }
+
+        Some(header)
+    }
+}
+
+impl Serializer for CompactBlock {
+    fn write(&self, writer: &mut Writer) {
+        self.header.write(writer);
+        writer.write_hash(&self.block_hash);
+        writer.write_u16(self.short_ids.len() as u16);
+        for id in self.short_ids.iter() {
+            writer.write_bytes(id);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let header = BlockHeader::read(reader)?;
+        if header.get_txs_count() != 0 {
+            debug!("Compact block header contains transactions");
+            return Err(ReaderError::InvalidValue)
+        }
+
+        let block_hash = reader.read_hash()?;
+        let count = reader.read_u16()? as usize;
+        if reader.size() < count * SHORT_TX_ID_SIZE {
+            return Err(ReaderError::InvalidSize)
+        }
+
+        let mut short_ids = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut id = [0u8; SHORT_TX_ID_SIZE];
+            id.copy_from_slice(reader.read_bytes_ref(SHORT_TX_ID_SIZE)?);
+            short_ids.push(id);
+        }
+
+        Ok(Self {
+            header,
+            block_hash,
+            short_ids
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.header.size() + self.block_hash.size() + 2 + self.short_ids.len() * SHORT_TX_ID_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        block::EXTRA_NONCE_SIZE,
+        crypto::KeyPair
+    };
+    use super::*;
+
+    fn create_header(txs: usize) -> (BlockHeader, Vec<Hash>) {
+        let txs_hashes: Vec<Hash> = (0..txs).map(|i| hash(&(i as u64).to_be_bytes())).collect();
+        let header = BlockHeader::new(0, 10, 1000, IndexSet::from([Hash::zero()]), [0u8; EXTRA_NONCE_SIZE], KeyPair::new().get_public_key().compress(), txs_hashes.iter().cloned().collect());
+        (header, txs_hashes)
+    }
+
+    #[test]
+    fn test_compact_block_reconstruction() {
+        let (header, txs) = create_header(100);
+        let block_hash = header.hash();
+        let compact = CompactBlock::from_bytes(&CompactBlock::new(&header, block_hash.clone()).to_bytes()).unwrap();
+
+        // Mempool contains all txs and more
+        let mut mempool = txs.clone();
+        mempool.push(hash(b"unrelated"));
+        let resolver = ShortIdsResolver::new(&block_hash, mempool.iter());
+        let rebuilt = compact.reconstruct(&resolver).unwrap();
+        assert_eq!(rebuilt.hash(), block_hash);
+        assert_eq!(rebuilt.get_txs_hashes(), header.get_txs_hashes());
+
+        // A missing tx can't be resolved
+        let resolver = ShortIdsResolver::new(&block_hash, txs.iter().skip(1));
+        assert!(compact.reconstruct(&resolver).is_none());
+
+        // Compact block is much smaller than the full header
+        assert!(compact.size() * 3 < header.size());
+    }
+
+    #[test]
+    fn test_compact_block_short_id_collision() {
+        let (header, txs) = create_header(2);
+        let block_hash = header.hash();
+        let compact = CompactBlock::new(&header, block_hash.clone());
+        let other = hash(b"collision");
+
+        let mut resolver = ShortIdsResolver::new(&block_hash, txs.iter());
+        assert!(compact.reconstruct(&resolver).is_some());
+
+        // Another tx with the same short id make it ambiguous
+        resolver.insert(short_tx_id(&block_hash, &txs[0]), &other);
+        assert!(resolver.resolve(&short_tx_id(&block_hash, &txs[0])).is_none());
+        assert!(compact.reconstruct(&resolver).is_none());
+
+        // Same tx inserted twice is not a collision
+        let mut resolver = ShortIdsResolver::new(&block_hash, txs.iter().chain(txs.iter()));
+        assert_eq!(resolver.resolve(&short_tx_id(&block_hash, &txs[1])), Some(&txs[1]));
+        resolver.insert(short_tx_id(&block_hash, &txs[1]), &txs[1]);
+        assert!(compact.reconstruct(&resolver).is_some());
+    }
+}
@@ -52,13 +52,20 @@ pub struct Handshake<'a> {
     cumulative_difficulty: Cow<'a, CumulativeDifficulty>,
     // By default it's true, and peer allow to be shared to others and/or through API
     // If false, we must not share it
-    can_be_shared: bool
+    can_be_shared: bool,
+    // If true, peer wants to receive our mempool inventory once connected
+    // Pruned or light nodes may opt out
+    mempool_sync: bool,
+    // If true, peer supports receiving blocks with short transaction ids
+    compact_blocks: bool,
+    // Version of the P2P protocol supported by the peer
+    protocol_version: u8
 } // Server reply with his own list of peers, but we remove all already known by requester for the response.
 
 impl<'a> Handshake<'a> {
     pub const MAX_LEN: usize = 16;
 
-    pub fn new(version: Cow<'a, String>, network: Network, node_tag: Cow<'a, Option<String>>, network_id: Cow<'a, [u8; 16]>, peer_id: u64, local_port: u16, utc_time: TimestampSeconds, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, top_hash: Cow<'a, Hash>, genesis_hash: Cow<'a, Hash>, cumulative_difficulty: Cow<'a, CumulativeDifficulty>, can_be_shared: bool) -> Self {
+    pub fn new(version: Cow<'a, String>, network: Network, node_tag: Cow<'a, Option<String>>, network_id: Cow<'a, [u8; 16]>, peer_id: u64, local_port: u16, utc_time: TimestampSeconds, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, top_hash: Cow<'a, Hash>, genesis_hash: Cow<'a, Hash>, cumulative_difficulty: Cow<'a, CumulativeDifficulty>, can_be_shared: bool, mempool_sync: bool, compact_blocks: bool, protocol_version: u8) -> Self {
         debug_assert!(version.len() > 0 && version.len() <= Handshake::MAX_LEN);
         // version cannot be greater than 16 chars
         if let Some(node_tag) = node_tag.as_ref() {
@@ -80,14 +87,17 @@ impl<'a> Handshake<'a> {
             top_hash,
             genesis_hash,
             cumulative_difficulty,
-            can_be_shared
+            can_be_shared,
+            mempool_sync,
+            compact_blocks,
+            protocol_version
         }
     }
 
     // Create a new peer using its connection and this handshake packet
     pub fn create_peer(self, connection: Connection, priority: bool, peer_list: SharedPeerList) -> (Peer, Rx) {
         let peers = HashSet::new();
-        Peer::new(connection, self.get_peer_id(), self.node_tag.into_owned(), self.local_port, self.version.into_owned(), self.top_hash.into_owned(), self.topoheight, self.height, self.pruned_topoheight, priority, self.cumulative_difficulty.into_owned(), peer_list, peers, self.can_be_shared)
+        Peer::new(connection, self.get_peer_id(), self.node_tag.into_owned(), self.local_port, self.version.into_owned(), self.top_hash.into_owned(), self.topoheight, self.height, self.pruned_topoheight, priority, self.cumulative_difficulty.into_owned(), peer_list, peers, self.can_be_shared, self.mempool_sync, self.compact_blocks, self.protocol_version)
     }
 
     pub fn get_version(&self) -> &String {
@@ -133,6 +143,18 @@ impl<'a> Handshake<'a> {
     pub fn get_pruned_topoheight(&self) -> &Option<u64> {
         &self.pruned_topoheight
     }
+
+    pub fn is_mempool_sync_enabled(&self) -> bool {
+        self.mempool_sync
+    }
+
+    pub fn is_compact_blocks_enabled(&self) -> bool {
+        self.compact_blocks
+    }
+
+    pub fn get_protocol_version(&self) -> u8 {
+        self.protocol_version
+    }
 }
 
 impl Serializer for Handshake<'_> {
@@ -158,6 +180,9 @@ impl Serializer for Handshake<'_> {
         writer.write_hash(&self.genesis_hash); // Genesis Hash
         self.cumulative_difficulty.write(writer); // Cumulative Difficulty
         writer.write_bool(self.can_be_shared); // Can be shared
+        writer.write_bool(self.mempool_sync); // Mempool sync
+        writer.write_bool(self.compact_blocks); // Compact blocks
+        writer.write_u8(self.protocol_version); // Protocol version
     }
 
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
@@ -166,21 +191,22 @@ impl Serializer for Handshake<'_> {
         // if we don't verify each time, it can create a panic error and crash the node
 
         // Daemon version
-        let version = reader.read_string()?;
-        if version.len() == 0 || version.len() > Handshake::MAX_LEN {
+        let size = reader.read_u8()? as usize;
+        if size == 0 || size > Handshake::MAX_LEN {
+            debug!("Invalid version size ({}) in handshake packet", size);
             return Err(ReaderError::InvalidSize)
         }
+        let version = sanitize_peer_string(&reader.read_string_with_size(size)?);
+        if version.is_empty() {
+            debug!("Invalid version in handshake packet: only non-printable characters");
+            return Err(ReaderError::InvalidValue)
+        }
 
         // Network
         let network = Network::read(reader)?;
 
         // Node Tag
-        let node_tag = reader.read_optional_string()?;
-        if let Some(tag) = &node_tag {
-            if tag.len() > Handshake::MAX_LEN {
-                return Err(ReaderError::InvalidSize)
-            }
-        }
+        let node_tag = read_node_tag(reader)?;
 
         let network_id: [u8; 16] = reader.read_bytes(16)?;
         let peer_id = reader.read_u64()?;
@@ -199,8 +225,26 @@ impl Serializer for Handshake<'_> {
         let genesis_hash = reader.read_hash()?;
         let cumulative_difficulty = CumulativeDifficulty::read(reader)?;
         let can_be_shared = reader.read_bool()?;
+        // Older nodes don't send this flag and only serve their inventory on request
+        let mempool_sync = if reader.size() > 0 {
+            reader.read_bool()?
+        } else {
+            false
+        };
+        // Older nodes only support full block headers propagation
+        let compact_blocks = if reader.size() > 0 {
+            reader.read_bool()?
+        } else {
+            false
+        };
+        // Older nodes don't send their protocol version
+        let protocol_version = if reader.size() > 0 {
+            reader.read_u8()?
+        } else {
+            0
+        };
 
-        Ok(Handshake::new(Cow::Owned(version), network, Cow::Owned(node_tag), Cow::Owned(network_id), peer_id, local_port, utc_time, topoheight, height, pruned_topoheight, Cow::Owned(top_hash), Cow::Owned(genesis_hash), Cow::Owned(cumulative_difficulty), can_be_shared))
+        Ok(Handshake::new(Cow::Owned(version), network, Cow::Owned(node_tag), Cow::Owned(network_id), peer_id, local_port, utc_time, topoheight, height, pruned_topoheight, Cow::Owned(top_hash), Cow::Owned(genesis_hash), Cow::Owned(cumulative_difficulty), can_be_shared, mempool_sync, compact_blocks, protocol_version))
     }
 
     fn size(&self) -> usize {
@@ -231,10 +275,42 @@ impl Serializer for Handshake<'_> {
         // Cumulative Difficulty
         self.cumulative_difficulty.size() +
         // Can be shared
-        self.can_be_shared.size()
+        self.can_be_shared.size() +
+        // Mempool sync
+        self.mempool_sync.size() +
+        // Compact blocks
+        self.compact_blocks.size() +
+        // Protocol version
+        self.protocol_version.size()
     }
 }
 
+// Remove the characters that could alter our logs or the terminal output (escape sequences, new lines, bidi overrides...)
+// It must be used on every string provided by a peer before storing or displaying it
+pub fn sanitize_peer_string(value: &str) -> String {
+    value.chars()
+        .filter(|c| !c.is_control() && !matches!(c, '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2069}' | '\u{FEFF}'))
+        .collect()
+}
+
+// Read an optional node tag sent by a peer
+// Its size is verified before reading it, and it is sanitized
+// A tag without any printable character is considered as not set
+pub fn read_node_tag(reader: &mut Reader) -> Result<Option<String>, ReaderError> {
+    let size = reader.read_u8()? as usize;
+    if size == 0 {
+        return Ok(None)
+    }
+
+    if size > Handshake::MAX_LEN {
+        debug!("Invalid node tag size ({})", size);
+        return Err(ReaderError::InvalidSize)
+    }
+
+    let tag = sanitize_peer_string(&reader.read_string_with_size(size)?);
+    Ok(if tag.is_empty() { None } else { Some(tag) })
+}
+
 const NO_NODE_TAG: &str = "None";
 
 impl Display for Handshake<'_> {
@@ -246,4 +322,103 @@ impl Display for Handshake<'_> {
         };
         write!(f, "Handshake[version: {}, node tag: {}, network_id: {}, peer_id: {}, utc_time: {}, block_height: {}, block_top_hash: {}]", self.get_version(), node_tag, hex::encode(self.get_network_id()), self.get_peer_id(), self.get_utc_time(), self.get_block_height(), self.get_block_top_hash())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_handshake(mempool_sync: bool, compact_blocks: bool) -> Handshake<'static> {
+        Handshake::new(Cow::Owned("1.0.0".to_owned()), Network::Dev, Cow::Owned(None), Cow::Owned([0u8; 16]), 1, 2125, 0, 10, 10, None, Cow::Owned(Hash::zero()), Cow::Owned(Hash::zero()), Cow::Owned(CumulativeDifficulty::from_u64(1)), true, mempool_sync, compact_blocks, 1)
+    }
+
+    #[test]
+    fn test_handshake_mempool_sync_flag() {
+        for mempool_sync in [true, false] {
+            let handshake = create_handshake(mempool_sync, true);
+            let bytes = handshake.to_bytes();
+            assert_eq!(bytes.len(), handshake.size());
+
+            let decoded = Handshake::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.is_mempool_sync_enabled(), mempool_sync);
+        }
+    }
+
+    #[test]
+    fn test_handshake_without_mempool_sync_flag() {
+        // Older nodes don't send the flags
+        let mut bytes = create_handshake(true, true).to_bytes();
+        bytes.truncate(bytes.len() - 3);
+
+        let decoded = Handshake::from_bytes(&bytes).unwrap();
+        assert!(!decoded.is_mempool_sync_enabled());
+        assert!(!decoded.is_compact_blocks_enabled());
+    }
+
+    #[test]
+    fn test_handshake_without_compact_blocks_flag() {
+        let mut bytes = create_handshake(true, true).to_bytes();
+        bytes.truncate(bytes.len() - 2);
+
+        let decoded = Handshake::from_bytes(&bytes).unwrap();
+        assert!(decoded.is_mempool_sync_enabled());
+        assert!(!decoded.is_compact_blocks_enabled());
+    }
+
+    fn create_handshake_with(version: &str, node_tag: Option<&str>) -> Handshake<'static> {
+        let mut handshake = create_handshake(true, true);
+        handshake.version = Cow::Owned(version.to_owned());
+        handshake.node_tag = Cow::Owned(node_tag.map(str::to_owned));
+        handshake
+    }
+
+    #[test]
+    fn test_handshake_oversized_node_tag() {
+        let tag = "a".repeat(Handshake::MAX_LEN);
+        let decoded = Handshake::from_bytes(&create_handshake_with("1.0.0", Some(&tag)).to_bytes()).unwrap();
+        assert_eq!(decoded.get_node_tag().as_deref(), Some(tag.as_str()));
+
+        let tag = "a".repeat(Handshake::MAX_LEN + 1);
+        assert!(matches!(Handshake::from_bytes(&create_handshake_with("1.0.0", Some(&tag)).to_bytes()), Err(ReaderError::InvalidSize)));
+
+        // Longest tag the size prefix allows, rejected before reading its content
+        let tag = "a".repeat(u8::MAX as usize);
+        assert!(matches!(Handshake::from_bytes(&create_handshake_with("1.0.0", Some(&tag)).to_bytes()), Err(ReaderError::InvalidSize)));
+
+        let version = "1".repeat(Handshake::MAX_LEN + 1);
+        assert!(matches!(Handshake::from_bytes(&create_handshake_with(&version, None).to_bytes()), Err(ReaderError::InvalidSize)));
+    }
+
+    #[test]
+    fn test_handshake_node_tag_sanitized() {
+        let bytes = create_handshake_with("1.0.0\r\n", Some("\x1b[2Jevil\u{202E}\x07")).to_bytes();
+        let decoded = Handshake::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.get_version(), "1.0.0");
+        assert_eq!(decoded.get_node_tag().as_deref(), Some("[2Jevil"));
+
+        // Only non-printable characters
+        let decoded = Handshake::from_bytes(&create_handshake_with("1.0.0", Some("\x1b\x1b\n")).to_bytes()).unwrap();
+        assert_eq!(*decoded.get_node_tag(), None);
+
+        assert!(matches!(Handshake::from_bytes(&create_handshake_with("\x1b\x00", None).to_bytes()), Err(ReaderError::InvalidValue)));
+    }
+
+    #[test]
+    fn test_sanitize_peer_string() {
+        assert_eq!(sanitize_peer_string("node-1"), "node-1");
+        assert_eq!(sanitize_peer_string("tag\u{1b}]0;title\u{7}"), "tag]0;title");
+        assert_eq!(sanitize_peer_string("a\u{200B}b\u{FEFF}c\td"), "abcd");
+        assert_eq!(sanitize_peer_string("nœud"), "nœud");
+    }
+
+    #[test]
+    fn test_handshake_protocol_version() {
+        let bytes = create_handshake(true, true).to_bytes();
+        assert_eq!(Handshake::from_bytes(&bytes).unwrap().get_protocol_version(), 1);
+
+        // Older nodes don't send their protocol version
+        let decoded = Handshake::from_bytes(&bytes[..bytes.len() - 1]).unwrap();
+        assert!(decoded.is_compact_blocks_enabled());
+        assert_eq!(decoded.get_protocol_version(), 0);
+    }
+}
@@ -12,6 +12,8 @@ use xelis_common::{
 };
 
 pub const NOTIFY_MAX_LEN: usize = 16384; // 16384 * 32 bytes = 512 KiB
+// Maximum hashes exchanged for a whole inventory (all pages)
+pub const NOTIFY_MAX_TOTAL: usize = NOTIFY_MAX_LEN * 4;
 
 #[derive(Debug, Clone)]
 pub struct NotifyInventoryRequest {
@@ -45,6 +47,31 @@ impl Serializer for NotifyInventoryRequest {
     }
 }
 
+// Build the requested page of an inventory from all the txs hashes
+// No page is served past NOTIFY_MAX_TOTAL hashes
+pub fn build_inventory_page<'a, I: Iterator<Item = &'a Hash>>(txs: I, total: usize, page_id: u8) -> NotifyInventoryResponse<'a> {
+    let skip = page_id as usize * NOTIFY_MAX_LEN;
+    let txs = if skip < NOTIFY_MAX_TOTAL {
+        txs.skip(skip).take(NOTIFY_MAX_LEN).map(Cow::Borrowed).collect::<IndexSet<_>>()
+    } else {
+        IndexSet::new()
+    };
+
+    let next_skip = skip + NOTIFY_MAX_LEN;
+    let next = if txs.len() == NOTIFY_MAX_LEN && total > next_skip && next_skip < NOTIFY_MAX_TOTAL {
+        Some(page_id + 1)
+    } else {
+        None
+    };
+
+    NotifyInventoryResponse::new(next, Cow::Owned(txs))
+}
+
+// Check if the next page of an inventory can be requested
+pub fn is_inventory_page_allowed(page_id: u8) -> bool {
+    (page_id as usize) * NOTIFY_MAX_LEN < NOTIFY_MAX_TOTAL
+}
+
 #[derive(Debug)]
 pub struct NotifyInventoryResponse<'a> {
     next: Option<u8>,
@@ -101,4 +128,52 @@ impl<'a> Serializer for NotifyInventoryResponse<'a> {
     fn size(&self) -> usize {
         1 + 2 + self.txs.len() * HASH_SIZE
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(count: usize) -> Vec<Hash> {
+        (0..count).map(|i| {
+            let mut bytes = [0u8; HASH_SIZE];
+            bytes[0..8].copy_from_slice(&(i as u64).to_be_bytes());
+            Hash::new(bytes)
+        }).collect()
+    }
+
+    #[test]
+    fn test_inventory_single_page() {
+        let txs = hashes(50);
+        let page = build_inventory_page(txs.iter(), txs.len(), 0);
+        assert_eq!(page.next(), None);
+
+        let decoded = NotifyInventoryResponse::from_bytes(&page.to_bytes()).unwrap();
+        let received = decoded.get_txs().into_owned().into_iter().map(Cow::into_owned).collect::<Vec<_>>();
+        assert_eq!(received, txs);
+    }
+
+    #[test]
+    fn test_inventory_pages_are_bounded() {
+        let txs = hashes(NOTIFY_MAX_TOTAL + NOTIFY_MAX_LEN);
+        let mut total = 0;
+        let mut page_id = 0;
+        loop {
+            let page = build_inventory_page(txs.iter(), txs.len(), page_id);
+            total += page.len();
+            match page.next() {
+                Some(next) => {
+                    assert!(is_inventory_page_allowed(next));
+                    page_id = next;
+                },
+                None => break
+            }
+        }
+        assert_eq!(total, NOTIFY_MAX_TOTAL);
+
+        // A page past the limit is empty
+        let page = build_inventory_page(txs.iter(), txs.len(), (NOTIFY_MAX_TOTAL / NOTIFY_MAX_LEN) as u8);
+        assert_eq!(page.len(), 0);
+        assert!(!is_inventory_page_allowed((NOTIFY_MAX_TOTAL / NOTIFY_MAX_LEN) as u8));
+    }
+}
@@ -5,15 +5,18 @@ pub mod object;
 pub mod inventory;
 pub mod bootstrap_chain;
 pub mod peer_disconnected;
+pub mod compact_block;
 
 use self::bootstrap_chain::{BootstrapChainRequest, BootstrapChainResponse};
 use self::inventory::{NotifyInventoryResponse, NotifyInventoryRequest};
 use self::object::{ObjectRequest, ObjectResponse};
-use self::chain::{ChainRequest, ChainResponse};
+use self::chain::{ChainContinuationRequest, ChainRequest, ChainResponse};
 use self::handshake::Handshake;
 use self::peer_disconnected::PacketPeerDisconnected;
-use self::ping::Ping;
+use self::compact_block::CompactBlock;
+use self::ping::{Ping, TimedPing};
 use std::borrow::Cow;
+use bytes::Bytes;
 use log::{debug, trace};
 use xelis_common::{
     serializer::{Serializer, Reader, ReaderError, Writer},
@@ -38,6 +41,9 @@ const NOTIFY_INV_RESPONSE_ID: u8 = 10;
 const BOOTSTRAP_CHAIN_REQUEST_ID: u8 = 11;
 const BOOTSTRAP_CHAIN_RESPONSE_ID: u8 = 12;
 const PEER_DISCONNECTED_ID: u8 = 13;
+const COMPACT_BLOCK_PROPAGATION_ID: u8 = 14;
+const CHAIN_CONTINUATION_REQUEST_ID: u8 = 15;
+const TIMED_PING_ID: u8 = 16;
 
 // PacketWrapper allows us to link any Packet to a Ping
 #[derive(Debug)]
@@ -97,6 +103,15 @@ pub enum Packet<'a> {
     BootstrapChainRequest(BootstrapChainRequest<'a>),
     BootstrapChainResponse(BootstrapChainResponse),
     PeerDisconnected(PacketPeerDisconnected),
+    // Block header with short transaction ids
+    // Only sent to peers supporting it, see handshake
+    CompactBlockPropagation(PacketWrapper<'a, CompactBlock>),
+    // Next blocks of a chain response
+    // Only sent to peers supporting it, see handshake
+    ChainContinuationRequest(ChainContinuationRequest),
+    // Ping with the time of the sender
+    // Only sent to peers supporting it, see handshake
+    TimedPing(TimedPing<'a>),
     // Encryption
     KeyExchange(Cow<'a, EncryptionKey>),
 }
@@ -117,6 +132,9 @@ impl Packet<'_> {
             Packet::BootstrapChainRequest(_) => BOOTSTRAP_CHAIN_REQUEST_ID,
             Packet::BootstrapChainResponse(_) => BOOTSTRAP_CHAIN_RESPONSE_ID,
             Packet::PeerDisconnected(_) => PEER_DISCONNECTED_ID,
+            Packet::CompactBlockPropagation(_) => COMPACT_BLOCK_PROPAGATION_ID,
+            Packet::ChainContinuationRequest(_) => CHAIN_CONTINUATION_REQUEST_ID,
+            Packet::TimedPing(_) => TIMED_PING_ID,
             Packet::KeyExchange(_) => KEY_EXCHANGE_ID,
         }
     }
@@ -141,6 +159,9 @@ impl<'a> Serializer for Packet<'a> {
             BOOTSTRAP_CHAIN_REQUEST_ID => Packet::BootstrapChainRequest(BootstrapChainRequest::read(reader)?),
             BOOTSTRAP_CHAIN_RESPONSE_ID => Packet::BootstrapChainResponse(BootstrapChainResponse::read(reader)?),
             PEER_DISCONNECTED_ID => Packet::PeerDisconnected(PacketPeerDisconnected::read(reader)?),
+            COMPACT_BLOCK_PROPAGATION_ID => Packet::CompactBlockPropagation(PacketWrapper::read(reader)?),
+            CHAIN_CONTINUATION_REQUEST_ID => Packet::ChainContinuationRequest(ChainContinuationRequest::read(reader)?),
+            TIMED_PING_ID => Packet::TimedPing(TimedPing::read(reader)?),
             id => {
                 debug!("invalid packet id received: {}", id);
                 return Err(ReaderError::InvalidValue)
@@ -170,10 +191,82 @@ impl<'a> Serializer for Packet<'a> {
             Packet::BootstrapChainRequest(request) => (BOOTSTRAP_CHAIN_REQUEST_ID, request),
             Packet::BootstrapChainResponse(response) => (BOOTSTRAP_CHAIN_RESPONSE_ID, response),
             Packet::PeerDisconnected(disconnected) => (PEER_DISCONNECTED_ID, disconnected),
+            Packet::CompactBlockPropagation(block) => (COMPACT_BLOCK_PROPAGATION_ID, block),
+            Packet::ChainContinuationRequest(request) => (CHAIN_CONTINUATION_REQUEST_ID, request),
+            Packet::TimedPing(ping) => (TIMED_PING_ID, ping),
         };
 
         let packet = serializer.to_bytes();
         writer.write_u8(id);
         writer.write_bytes(&packet);
     }
-}
\ No newline at end of file
+}
+// Build the bytes of a block propagation packet
+// If the header bytes are provided (block relayed from a peer), they are reused as is
+// Otherwise, the header is serialized
+pub fn build_block_propagation_packet<H: Serializer>(header: &H, header_bytes: Option<&[u8]>, ping: &Ping<'_>) -> Bytes {
+    let mut writer = Writer::new();
+    writer.write_u8(BLOCK_PROPAGATION_ID);
+    match header_bytes {
+        Some(bytes) => writer.write_bytes(bytes),
+        None => header.write(&mut writer)
+    };
+    ping.write(&mut writer);
+
+    Bytes::from(writer.bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use indexmap::IndexSet;
+    use xelis_common::{
+        block::EXTRA_NONCE_SIZE,
+        crypto::KeyPair,
+        difficulty::CumulativeDifficulty
+    };
+    use super::*;
+
+    // Header wrapper counting how many times it is serialized
+    struct CountingHeader {
+        header: BlockHeader,
+        writes: Cell<usize>
+    }
+
+    impl Serializer for CountingHeader {
+        fn write(&self, writer: &mut Writer) {
+            self.writes.set(self.writes.get() + 1);
+            self.header.write(writer);
+        }
+
+        fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+            Ok(Self {
+                header: BlockHeader::read(reader)?,
+                writes: Cell::new(0)
+            })
+        }
+    }
+
+    #[test]
+    fn test_block_propagation_packet_reuse_bytes() {
+        let miner = KeyPair::new().get_public_key().compress();
+        let header = BlockHeader::new(0, 1, 0, IndexSet::from([Hash::zero()]), [0u8; EXTRA_NONCE_SIZE], miner, IndexSet::new());
+        let hash = Hash::zero();
+        let ping = Ping::new(Cow::Borrowed(&hash), 1, 1, None, CumulativeDifficulty::from_u64(1), IndexSet::new());
+        let expected = Packet::BlockPropagation(PacketWrapper::new(Cow::Borrowed(&header), Cow::Borrowed(&ping))).to_bytes();
+
+        let header_bytes = header.to_bytes();
+        let counting = CountingHeader { header, writes: Cell::new(0) };
+
+        // Block produced locally, serialized only one time for all peers
+        let bytes = build_block_propagation_packet(&counting, None, &ping);
+        assert_eq!(counting.writes.get(), 1);
+        assert_eq!(bytes.as_ref(), expected.as_slice());
+
+        // Block relayed, received bytes are reused
+        counting.writes.set(0);
+        let bytes = build_block_propagation_packet(&counting, Some(&header_bytes), &ping);
+        assert_eq!(counting.writes.get(), 0);
+        assert_eq!(bytes.as_ref(), expected.as_slice());
+    }
+}
@@ -8,6 +8,7 @@ use xelis_common::{
         Block,
         BlockHeader
     },
+    difficulty::CumulativeDifficulty,
     transaction::Transaction,
     serializer::{
         Reader,
@@ -17,20 +18,37 @@ use xelis_common::{
     },
 };
 use std::{borrow::Cow, fmt::{Display, Formatter, self}};
+use log::debug;
+use crate::{config::HEADERS_RANGE_MAX_COUNT, core::headers::HeaderEntry};
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum ObjectRequest {
     Block(Hash),
     BlockHeader(Hash),
-    Transaction(Hash)
+    Transaction(Hash),
+    // Headers in topological order starting at the block hash (included)
+    HeadersRange(Hash, u16),
+    // Block ordered at this topoheight in the DAG of the peer
+    BlockAtTopoheight(u64)
 }
 
 impl ObjectRequest {
-    pub fn get_hash(&self) -> &Hash {
+    // Hash of the object requested, None if it is requested by topoheight
+    pub fn get_hash(&self) -> Option<&Hash> {
         match self {
-            Self::Block(hash) => hash,
-            Self::BlockHeader(hash) => hash,
-            Self::Transaction(hash) => hash
+            Self::Block(hash) => Some(hash),
+            Self::BlockHeader(hash) => Some(hash),
+            Self::Transaction(hash) => Some(hash),
+            Self::HeadersRange(hash, _) => Some(hash),
+            Self::BlockAtTopoheight(_) => None
+        }
+    }
+
+    // Topoheight of the object requested, only known for the blocks requested by topoheight
+    pub fn get_topoheight(&self) -> Option<u64> {
+        match self {
+            Self::BlockAtTopoheight(topoheight) => Some(*topoheight),
+            _ => None
         }
     }
 }
@@ -49,6 +67,15 @@ impl Serializer for ObjectRequest {
             Self::Transaction(hash) => {
                 writer.write_u8(2);
                 writer.write_hash(hash);
+            },
+            Self::HeadersRange(hash, count) => {
+                writer.write_u8(3);
+                writer.write_hash(hash);
+                writer.write_u16(*count);
+            },
+            Self::BlockAtTopoheight(topoheight) => {
+                writer.write_u8(4);
+                writer.write_u64(topoheight);
             }
         }
     }
@@ -59,12 +86,26 @@ impl Serializer for ObjectRequest {
             0 => ObjectRequest::Block(reader.read_hash()?),
             1 => ObjectRequest::BlockHeader(reader.read_hash()?),
             2 => ObjectRequest::Transaction(reader.read_hash()?),
+            3 => {
+                let hash = reader.read_hash()?;
+                let count = reader.read_u16()?;
+                if count == 0 || count as u64 > HEADERS_RANGE_MAX_COUNT {
+                    debug!("Invalid headers range count requested: {}", count);
+                    return Err(ReaderError::InvalidValue)
+                }
+                ObjectRequest::HeadersRange(hash, count)
+            },
+            4 => ObjectRequest::BlockAtTopoheight(reader.read_u64()?),
             _ => return Err(ReaderError::InvalidValue)
         })
     }
 
     fn size(&self) -> usize {
-        1 + HASH_SIZE
+        1 + match self {
+            Self::HeadersRange(_, count) => HASH_SIZE + count.size(),
+            Self::BlockAtTopoheight(topoheight) => topoheight.size(),
+            _ => HASH_SIZE
+        }
     }
 }
 
@@ -73,34 +114,107 @@ impl Display for ObjectRequest {
         match self {
             Self::Block(hash) => write!(f, "ObjectRequest[type=Block, {}]", hash),
             Self::BlockHeader(hash) => write!(f, "ObjectRequest[type=BlockHeader, {}]", hash),
-            Self::Transaction(hash) => write!(f, "ObjectRequest[type=Transaction, {}]", hash)
+            Self::Transaction(hash) => write!(f, "ObjectRequest[type=Transaction, {}]", hash),
+            Self::HeadersRange(hash, count) => write!(f, "ObjectRequest[type=HeadersRange, {}, count={}]", hash, count),
+            Self::BlockAtTopoheight(topoheight) => write!(f, "ObjectRequest[type=BlockAtTopoheight, {}]", topoheight)
         }
     }
 }
 
+// Metadata of a block as seen by the node sending it
+// They are only hints used to prioritize the validation and are never trusted for consensus
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockHints {
+    pub height: u64,
+    // None if the block is not ordered in the DAG of the sender
+    pub topoheight: Option<u64>,
+    pub cumulative_difficulty: CumulativeDifficulty
+}
+
+impl BlockHints {
+    // Verify the hints against the header and the values we computed locally, if any
+    // Hints contradicting one of them must be ignored
+    pub fn is_consistent_with(&self, header: &BlockHeader, topoheight: Option<u64>, cumulative_difficulty: Option<&CumulativeDifficulty>) -> bool {
+        if self.height != header.get_height() {
+            return false
+        }
+
+        if let (Some(hint), Some(local)) = (self.topoheight, topoheight) {
+            if hint != local {
+                return false
+            }
+        }
+
+        cumulative_difficulty.map_or(true, |local| *local == self.cumulative_difficulty)
+    }
+}
+
+// Order the blocks received together so they are validated like in the DAG of the sender
+// Parents are always before their children as they have a lower height,
+// then the blocks ordered by the sender come by their topoheight before the side blocks
+// Hints contradicting the header are ignored
+pub fn sort_blocks_by_hints(blocks: &mut [(Block, Hash, Option<BlockHints>)]) {
+    blocks.sort_by_key(|(block, _, hints)| {
+        let topoheight = hints.as_ref()
+            .filter(|hints| hints.is_consistent_with(block.get_header(), None, None))
+            .and_then(|hints| hints.topoheight);
+        (block.get_height(), topoheight.unwrap_or(u64::MAX))
+    });
+}
+
+impl Serializer for BlockHints {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u64(&self.height);
+        self.topoheight.write(writer);
+        self.cumulative_difficulty.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let height = reader.read_u64()?;
+        let topoheight = Option::read(reader)?;
+        let cumulative_difficulty = CumulativeDifficulty::read(reader)?;
+        Ok(Self {
+            height,
+            topoheight,
+            cumulative_difficulty
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.height.size() + self.topoheight.size() + self.cumulative_difficulty.size()
+    }
+}
+
 #[derive(Debug)]
 pub enum OwnedObjectResponse {
-    Block(Block, Hash),
+    Block(Block, Hash, Option<BlockHints>),
     BlockHeader(BlockHeader, Hash),
     Transaction(Transaction, Hash),
+    HeadersRange(Vec<HeaderEntry>, Hash, u16),
+    BlockAtTopoheight(Block, Hash, u64),
     NotFound(ObjectRequest)
 }
 
 impl OwnedObjectResponse {
-    pub fn get_hash(&self) -> &Hash {
+    // Hash of the object, None if it was not found and requested by topoheight
+    pub fn get_hash(&self) -> Option<&Hash> {
         match self {
-            Self::Block(_, hash) => hash,
-            Self::BlockHeader(_, hash) => hash,
-            Self::Transaction(_, hash) => hash,
+            Self::Block(_, hash, _) => Some(hash),
+            Self::BlockHeader(_, hash) => Some(hash),
+            Self::Transaction(_, hash) => Some(hash),
+            Self::HeadersRange(_, hash, _) => Some(hash),
+            Self::BlockAtTopoheight(_, hash, _) => Some(hash),
             Self::NotFound(request) => request.get_hash(),
         }
     }
 
     pub fn get_request(&self) -> ObjectRequest {
         match &self {
-            Self::Block(_, hash) => ObjectRequest::Block(hash.clone()),
+            Self::Block(_, hash, _) => ObjectRequest::Block(hash.clone()),
             Self::BlockHeader(_, hash) => ObjectRequest::BlockHeader(hash.clone()),
             Self::Transaction(_, hash) => ObjectRequest::Transaction(hash.clone()),
+            Self::HeadersRange(_, hash, count) => ObjectRequest::HeadersRange(hash.clone(), *count),
+            Self::BlockAtTopoheight(_, _, topoheight) => ObjectRequest::BlockAtTopoheight(*topoheight),
             Self::NotFound(request) => request.clone(),
         }
     }
@@ -108,28 +222,37 @@ impl OwnedObjectResponse {
 
 #[derive(Debug)]
 pub enum ObjectResponse<'a> {
-    Block(Cow<'a, Block>),
+    // Hints are only sent to peers supporting them
+    Block(Cow<'a, Block>, Option<BlockHints>),
     BlockHeader(Cow<'a, BlockHeader>),
     Transaction(Cow<'a, Transaction>),
+    // Headers with the request they are answering
+    // The response may contain less headers than requested due to the size budget
+    HeadersRange(Cow<'a, Vec<HeaderEntry>>, Hash, u16),
+    // Block with the topoheight it was requested at
+    // The topoheight is only used to match the request, it is never trusted
+    BlockAtTopoheight(Cow<'a, Block>, u64),
     NotFound(ObjectRequest)
 }
 
 impl ObjectResponse<'_> {
     pub fn get_request(&self) -> Cow<'_, ObjectRequest> {
         match &self {
-            Self::Block(block) => Cow::Owned(ObjectRequest::Block(block.hash())),
+            Self::Block(block, _) => Cow::Owned(ObjectRequest::Block(block.hash())),
             Self::BlockHeader(header) => Cow::Owned(ObjectRequest::BlockHeader(header.hash())),
             Self::Transaction(tx) => Cow::Owned(ObjectRequest::Transaction(tx.hash())),
+            Self::HeadersRange(_, hash, count) => Cow::Owned(ObjectRequest::HeadersRange(hash.clone(), *count)),
+            Self::BlockAtTopoheight(_, topoheight) => Cow::Owned(ObjectRequest::BlockAtTopoheight(*topoheight)),
             Self::NotFound(request) => Cow::Borrowed(request)
         }
     }
 
     pub fn to_owned(self) -> OwnedObjectResponse {
         match self {
-            Self::Block(block) => {
+            Self::Block(block, hints) => {
                 let block = block.into_owned();
                 let hash = block.hash();
-                OwnedObjectResponse::Block(block, hash)
+                OwnedObjectResponse::Block(block, hash, hints)
             },
             Self::BlockHeader(header) => {
                 let hash = header.hash();
@@ -140,6 +263,12 @@ impl ObjectResponse<'_> {
                 let hash = tx.hash();
                 OwnedObjectResponse::Transaction(tx, hash)
             },
+            Self::HeadersRange(headers, hash, count) => OwnedObjectResponse::HeadersRange(headers.into_owned(), hash, count),
+            Self::BlockAtTopoheight(block, topoheight) => {
+                let block = block.into_owned();
+                let hash = block.hash();
+                OwnedObjectResponse::BlockAtTopoheight(block, hash, topoheight)
+            },
             ObjectResponse::NotFound(request) => OwnedObjectResponse::NotFound(request)
         }
     }
@@ -148,9 +277,13 @@ impl ObjectResponse<'_> {
 impl<'a> Serializer for ObjectResponse<'a> {
     fn write(&self, writer: &mut Writer) {
         match &self {
-            Self::Block(block) => {
+            Self::Block(block, hints) => {
                 writer.write_u8(0);
                 block.write(writer);
+                // Nothing is written without hints so older nodes can still read it
+                if let Some(hints) = hints {
+                    hints.write(writer);
+                }
             },
             Self::BlockHeader(header) => {
                 writer.write_u8(1);
@@ -163,6 +296,20 @@ impl<'a> Serializer for ObjectResponse<'a> {
             Self::NotFound(obj) => {
                 writer.write_u8(3);
                 obj.write(writer);
+            },
+            Self::HeadersRange(headers, hash, count) => {
+                writer.write_u8(4);
+                writer.write_hash(hash);
+                writer.write_u16(*count);
+                writer.write_u16(headers.len() as u16);
+                for header in headers.iter() {
+                    header.write(writer);
+                }
+            },
+            Self::BlockAtTopoheight(block, topoheight) => {
+                writer.write_u8(5);
+                writer.write_u64(topoheight);
+                block.write(writer);
             }
         }
     }
@@ -170,20 +317,192 @@ impl<'a> Serializer for ObjectResponse<'a> {
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
         let id = reader.read_u8()?;
         Ok(match id {
-            0 => Self::Block(Cow::Owned(Block::read(reader)?)),
+            0 => {
+                let block = Block::read(reader)?;
+                let hints = if reader.size() > 0 {
+                    Some(BlockHints::read(reader)?)
+                } else {
+                    None
+                };
+                Self::Block(Cow::Owned(block), hints)
+            },
             1 => Self::BlockHeader(Cow::Owned(BlockHeader::read(reader)?)),
             2 => Self::Transaction(Cow::Owned(Transaction::read(reader)?)),
             3 => Self::NotFound(ObjectRequest::read(reader)?),
+            4 => {
+                let hash = reader.read_hash()?;
+                let count = reader.read_u16()?;
+                let len = reader.read_u16()?;
+                if len > count || len as u64 > HEADERS_RANGE_MAX_COUNT {
+                    debug!("Invalid headers range response: {} headers for {} requested", len, count);
+                    return Err(ReaderError::InvalidSize)
+                }
+
+                let mut headers = Vec::with_capacity(len as usize);
+                for _ in 0..len {
+                    headers.push(HeaderEntry::read(reader)?);
+                }
+                Self::HeadersRange(Cow::Owned(headers), hash, count)
+            },
+            5 => {
+                let topoheight = reader.read_u64()?;
+                Self::BlockAtTopoheight(Cow::Owned(Block::read(reader)?), topoheight)
+            },
             _ => return Err(ReaderError::InvalidValue)
         })
     }
 
     fn size(&self) -> usize {
         1 + match &self {
-            Self::Block(block) => block.size(),
+            Self::Block(block, hints) => block.size() + hints.as_ref().map_or(0, |hints| hints.size()),
             Self::BlockHeader(header) => header.size(),
             Self::Transaction(transaction) => transaction.size(),
-            Self::NotFound(obj) => obj.size()
+            Self::NotFound(obj) => obj.size(),
+            Self::HeadersRange(headers, hash, count) => hash.size() + count.size() + 2 + headers.iter().map(|header| header.size()).sum::<usize>(),
+            Self::BlockAtTopoheight(block, topoheight) => topoheight.size() + block.size()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexSet;
+    use xelis_common::{
+        block::EXTRA_NONCE_SIZE,
+        crypto::KeyPair,
+        immutable::Immutable
+    };
+    use super::*;
+
+    fn create_block() -> Block {
+        create_block_at(10)
+    }
+
+    fn create_block_at(height: u64) -> Block {
+        let header = BlockHeader::new(0, height, 1000, IndexSet::from([Hash::zero()]), [0u8; EXTRA_NONCE_SIZE], KeyPair::new().get_public_key().compress(), IndexSet::new());
+        Block::new(Immutable::Owned(header), Vec::new())
+    }
+
+    #[test]
+    fn test_block_response_with_hints() {
+        let block = create_block();
+        let hints = BlockHints {
+            height: 10,
+            topoheight: Some(12),
+            cumulative_difficulty: CumulativeDifficulty::from_u64(5000)
+        };
+        let response = ObjectResponse::Block(Cow::Borrowed(&block), Some(hints.clone()));
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+
+        let OwnedObjectResponse::Block(decoded, hash, decoded_hints) = ObjectResponse::from_bytes(&bytes).unwrap().to_owned() else {
+            panic!("expected a block response");
+        };
+        assert_eq!(hash, block.hash());
+        assert_eq!(decoded.hash(), hash);
+        assert_eq!(decoded_hints, Some(hints));
+    }
+
+    #[test]
+    fn test_block_response_without_hints() {
+        // Same bytes as older nodes
+        let block = create_block();
+        let response = ObjectResponse::Block(Cow::Borrowed(&block), None);
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), 1 + block.size());
+
+        let OwnedObjectResponse::Block(_, _, hints) = ObjectResponse::from_bytes(&bytes).unwrap().to_owned() else {
+            panic!("expected a block response");
+        };
+        assert!(hints.is_none());
+    }
+
+    #[test]
+    fn test_block_at_topoheight_request() {
+        let request = ObjectRequest::BlockAtTopoheight(42);
+        let bytes = request.to_bytes();
+        assert_eq!(bytes.len(), request.size());
+        assert_eq!(ObjectRequest::from_bytes(&bytes).unwrap(), request);
+        assert!(request.get_hash().is_none());
+
+        // Requests by hash keep the same size
+        assert_eq!(ObjectRequest::Block(Hash::zero()).size(), 1 + HASH_SIZE);
+    }
+
+    #[test]
+    fn test_block_at_topoheight_response() {
+        let block = create_block();
+        let response = ObjectResponse::BlockAtTopoheight(Cow::Borrowed(&block), 42);
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+
+        let owned = ObjectResponse::from_bytes(&bytes).unwrap().to_owned();
+        // It answers the request by topoheight with the hash computed locally
+        assert_eq!(owned.get_request(), ObjectRequest::BlockAtTopoheight(42));
+        assert_eq!(owned.get_hash(), Some(&block.hash()));
+
+        let not_found = ObjectResponse::NotFound(ObjectRequest::BlockAtTopoheight(42)).to_owned();
+        assert_eq!(not_found.get_request(), ObjectRequest::BlockAtTopoheight(42));
+        assert!(not_found.get_hash().is_none());
+    }
+
+    #[test]
+    fn test_block_hints_contradicting_local_values() {
+        let block = create_block();
+        let header = block.get_header();
+        let cumulative_difficulty = CumulativeDifficulty::from_u64(5000);
+        let hints = BlockHints {
+            height: 10,
+            topoheight: Some(12),
+            cumulative_difficulty
+        };
+
+        // Nothing computed locally yet, only the header is checked
+        assert!(hints.is_consistent_with(header, None, None));
+        assert!(hints.is_consistent_with(header, Some(12), Some(&cumulative_difficulty)));
+
+        // Wrong height
+        let wrong_height = BlockHints { height: 11, ..hints.clone() };
+        assert!(!wrong_height.is_consistent_with(header, None, None));
+
+        // Different topoheight or cumulative difficulty than what we computed
+        assert!(!hints.is_consistent_with(header, Some(13), None));
+        assert!(!hints.is_consistent_with(header, None, Some(&CumulativeDifficulty::from_u64(4999))));
+
+        // Block not ordered by the sender
+        let unordered = BlockHints { topoheight: None, ..hints };
+        assert!(unordered.is_consistent_with(header, Some(12), Some(&cumulative_difficulty)));
+    }
+
+    #[test]
+    fn test_sort_blocks_by_hints() {
+        let hints = |height: u64, topoheight: Option<u64>| Some(BlockHints {
+            height,
+            topoheight,
+            cumulative_difficulty: CumulativeDifficulty::from_u64(height)
+        });
+        let entry = |height: u64, hints: Option<BlockHints>| {
+            let block = create_block_at(height);
+            let hash = block.hash();
+            (block, hash, hints)
+        };
+
+        let mut blocks = vec![
+            // Child received before its parents
+            entry(6, hints(6, Some(7))),
+            // Side block of the sender
+            entry(5, hints(5, None)),
+            entry(5, hints(5, Some(6))),
+            // No hints sent
+            entry(5, None),
+            // Topoheight is ignored as the height contradicts the header
+            entry(5, hints(4, Some(1))),
+            entry(4, hints(4, Some(5)))
+        ];
+        let expected: Vec<Hash> = [5, 2, 1, 3, 4, 0].iter().map(|i| blocks[*i].1.clone()).collect();
+
+        sort_blocks_by_hints(&mut blocks);
+        let sorted: Vec<Hash> = blocks.into_iter().map(|(_, hash, _)| hash).collect();
+        assert_eq!(sorted, expected);
+    }
+}
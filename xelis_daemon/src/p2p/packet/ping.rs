@@ -3,6 +3,7 @@ use xelis_common::{
     api::daemon::{Direction, NotifyEvent, PeerPeerListUpdatedEvent},
     crypto::Hash,
     difficulty::CumulativeDifficulty,
+    time::{get_current_time_in_seconds, TimestampSeconds},
     serializer::{
         Reader,
         ReaderError,
@@ -19,6 +20,7 @@ use crate::{
     p2p::{
         error::P2pError,
         peer::Peer,
+        topology::{PingTopology, TopologyEntry},
         is_local_address,
     },
     rpc::rpc::get_peer_entry
@@ -38,7 +40,9 @@ pub struct Ping<'a> {
     height: u64,
     pruned_topoheight: Option<u64>,
     cumulative_difficulty: CumulativeDifficulty,
-    peer_list: IndexSet<SocketAddr>
+    peer_list: IndexSet<SocketAddr>,
+    // Topology shared with the peer list, see P2P_TOPOLOGY_PROTOCOL_VERSION
+    topology: Option<PingTopology>
 }
 
 impl<'a> Ping<'a> {
@@ -49,7 +53,8 @@ impl<'a> Ping<'a> {
             height,
             pruned_topoheight,
             cumulative_difficulty,
-            peer_list
+            peer_list,
+            topology: None
         }
     }
 
@@ -83,12 +88,17 @@ impl<'a> Ping<'a> {
             trace!("Locking RPC Server to notify PeerStateUpdated event");
             if let Some(rpc) = blockchain.get_rpc().read().await.as_ref() {
                 if rpc.is_event_tracked(&NotifyEvent::PeerStateUpdated).await {
-                    rpc.notify_clients_with(&NotifyEvent::PeerStateUpdated, get_peer_entry(peer).await).await;
+                    rpc.notify_clients_with(&NotifyEvent::PeerStateUpdated, get_peer_entry(peer, blockchain.hide_peer_ips()).await).await;
                 }
             }
             trace!("End locking for PeerStateUpdated event");
         }
 
+        if let Some(topology) = self.topology {
+            trace!("Received topology {:?} from {}", topology, peer);
+            peer.set_topology(TopologyEntry::new(topology, get_current_time_in_seconds())).await;
+        }
+
         if !self.peer_list.is_empty() {
             debug!("Received a peer list ({:?}) for {}", self.peer_list, peer.get_outgoing_address());
             let mut shared_peers = peer.get_peers().lock().await;
@@ -142,6 +152,10 @@ impl<'a> Ping<'a> {
         self.topoheight
     }
 
+    pub fn get_pruned_topoheight(&self) -> Option<u64> {
+        self.pruned_topoheight
+    }
+
     pub fn get_peers(&self) -> &IndexSet<SocketAddr> {
         &self.peer_list
     }
@@ -149,6 +163,15 @@ impl<'a> Ping<'a> {
     pub fn get_mut_peers(&mut self) -> &mut IndexSet<SocketAddr> {
         &mut self.peer_list
     }
+
+    pub fn get_topology(&self) -> Option<&PingTopology> {
+        self.topology.as_ref()
+    }
+
+    // Only set it for peers supporting it, older nodes reject the extra bytes
+    pub fn set_topology(&mut self, topology: Option<PingTopology>) {
+        self.topology = topology;
+    }
 }
 
 impl Serializer for Ping<'_> {
@@ -162,6 +185,11 @@ impl Serializer for Ping<'_> {
         for peer in &self.peer_list {
             peer.write(writer);
         }
+
+        // Ping is always at the end of the packet, so its presence is known by the remaining bytes
+        if let Some(topology) = &self.topology {
+            topology.write(writer);
+        }
     }
 
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
@@ -191,7 +219,13 @@ impl Serializer for Ping<'_> {
             }
         }
 
-        Ok(Self { top_hash, topoheight, height, pruned_topoheight, cumulative_difficulty, peer_list })
+        let topology = if reader.size() > 0 {
+            Some(PingTopology::read(reader)?)
+        } else {
+            None
+        };
+
+        Ok(Self { top_hash, topoheight, height, pruned_topoheight, cumulative_difficulty, peer_list, topology })
     }
 
     fn size(&self) -> usize {
@@ -202,7 +236,51 @@ impl Serializer for Ping<'_> {
         self.cumulative_difficulty.size() +
         // u8 for the length of the peer list
         1 +
-        self.peer_list.iter().map(|p| p.size()).sum::<usize>()
+        self.peer_list.iter().map(|p| p.size()).sum::<usize>() +
+        self.topology.as_ref().map_or(0, |topology| topology.size())
+    }
+}
+
+// Ping with the time of the sender, used to compute the network time
+// Only sent to peers supporting it, see P2P_TIMED_PING_PROTOCOL_VERSION
+#[derive(Clone, Debug)]
+pub struct TimedPing<'a> {
+    utc_time: TimestampSeconds,
+    ping: Cow<'a, Ping<'a>>
+}
+
+impl<'a> TimedPing<'a> {
+    pub fn new(utc_time: TimestampSeconds, ping: Cow<'a, Ping<'a>>) -> Self {
+        Self {
+            utc_time,
+            ping
+        }
+    }
+
+    pub fn get_utc_time(&self) -> TimestampSeconds {
+        self.utc_time
+    }
+
+    pub fn consume(self) -> (TimestampSeconds, Cow<'a, Ping<'a>>) {
+        (self.utc_time, self.ping)
+    }
+}
+
+impl Serializer for TimedPing<'_> {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u64(&self.utc_time);
+        // Ping is last as it detects its topology by the remaining bytes
+        self.ping.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let utc_time = reader.read_u64()?;
+        let ping = Cow::Owned(Ping::read(reader)?);
+        Ok(Self::new(utc_time, ping))
+    }
+
+    fn size(&self) -> usize {
+        self.utc_time.size() + self.ping.size()
     }
 }
 
@@ -210,4 +288,42 @@ impl Display for Ping<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Ping[top_hash: {}, topoheight: {}, height: {}, pruned topoheight: {:?}, peers length: {}]", self.top_hash, self.topoheight, self.height, self.pruned_topoheight, self.peer_list.len())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::varuint::VarUint;
+    use super::*;
+
+    #[test]
+    fn test_ping_with_ipv6_peers() {
+        let peers: IndexSet<SocketAddr> = ["1.2.3.4:2125", "[2001:db8::1]:2125", "[2001:db8::2]:2126"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+
+        let ping = Ping::new(Cow::Owned(Hash::zero()), 10, 8, None, VarUint::from_u64(100), peers.clone());
+        let bytes = ping.to_bytes();
+        assert_eq!(bytes.len(), ping.size());
+
+        let decoded = Ping::from_bytes(&bytes).unwrap();
+        assert_eq!(*decoded.get_peers(), peers);
+    }
+
+    #[test]
+    fn test_timed_ping_with_topology() {
+        let peers: IndexSet<SocketAddr> = ["1.2.3.4:2125".parse().unwrap()].into_iter().collect();
+        let mut ping = Ping::new(Cow::Owned(Hash::zero()), 10, 8, Some(2), VarUint::from_u64(100), peers.clone());
+        ping.set_topology(Some(PingTopology::new(3, Some("node".to_owned()))));
+
+        let timed_ping = TimedPing::new(1_700_000_000, Cow::Borrowed(&ping));
+        let bytes = timed_ping.to_bytes();
+        assert_eq!(bytes.len(), timed_ping.size());
+
+        let (utc_time, decoded) = TimedPing::from_bytes(&bytes).unwrap().consume();
+        assert_eq!(utc_time, 1_700_000_000);
+        assert_eq!(*decoded.get_peers(), peers);
+        assert_eq!(decoded.get_pruned_topoheight(), Some(2));
+        assert!(decoded.get_topology().is_some());
+    }
+}
@@ -3,13 +3,17 @@ use crate::{
         PEER_FAIL_TIME_RESET, PEER_BLOCK_CACHE_SIZE, PEER_TX_CACHE_SIZE,
         PEER_TEMP_BAN_TIME, PEER_TIMEOUT_BOOTSTRAP_STEP,
         PEER_TIMEOUT_REQUEST_OBJECT, CHAIN_SYNC_TIMEOUT_SECS,
-        PEER_PACKET_CHANNEL_SIZE
+        PEER_PACKET_CHANNEL_SIZE, PEER_COMPACT_BLOCKS_MAX_FAILURES,
+        PEER_TX_RATE_LIMIT_PER_SECOND, PEER_TX_RATE_LIMIT_BURST,
+        PEER_TX_RATE_LIMIT_STRIKES, PEER_CHAIN_CONTINUATION_RATE_LIMIT_PER_SECOND,
+        PEER_CHAIN_CONTINUATION_RATE_LIMIT_BURST
     },
+    core::headers::HeaderEntry,
     p2p::packet::PacketWrapper
 };
 use xelis_common::{
     api::daemon::Direction,
-    crypto::Hash,
+    crypto::{Hash, PublicKey},
     difficulty::CumulativeDifficulty,
     serializer::Serializer,
     time::{
@@ -25,8 +29,10 @@ use super::{
             StepResponse
         },
         chain::{
+            ChainContinuationRequest,
             ChainRequest,
-            ChainResponse
+            ChainResponse,
+            ChainSyncToken
         },
         object::{
             ObjectRequest,
@@ -36,7 +42,10 @@ use super::{
     },
     peer_list::SharedPeerList,
     connection::Connection,
-    error::P2pError
+    error::P2pError,
+    topology::TopologyEntry,
+    rate_limiter::TokenBucket,
+    selection::can_serve_object
 };
 use std::{
     num::NonZeroUsize,
@@ -46,7 +55,7 @@ use std::{
     hash::{Hash as StdHash, Hasher},
     net::{IpAddr, SocketAddr},
     sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
-    time::Duration
+    time::{Duration, Instant}
 };
 use tokio::{
     sync::{broadcast, mpsc, oneshot::Sender, Mutex},
@@ -83,6 +92,62 @@ pub enum TaskState {
     Unknown,
 }
 
+// Last element sent to a peer for a paginated bootstrap step
+// The next page of the same range is served by seeking right after it
+// instead of skipping again all the elements of the previous pages
+pub struct PageCursor<T> {
+    // min topoheight, max topoheight and the page expected next
+    request: (u64, u64, u64),
+    last: T
+}
+
+impl<T> PageCursor<T> {
+    pub fn new(min_topoheight: u64, max_topoheight: u64, next_page: u64, last: T) -> Self {
+        Self {
+            request: (min_topoheight, max_topoheight, next_page),
+            last
+        }
+    }
+
+    // Returns the last element sent if the request is the next page of the same range
+    pub fn resume(self, min_topoheight: u64, max_topoheight: u64, page: u64) -> Option<T> {
+        if self.request == (min_topoheight, max_topoheight, page) {
+            Some(self.last)
+        } else {
+            None
+        }
+    }
+}
+
+// Reason of a disconnection initiated by us
+// It is kept for the peer list stats
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DisconnectReason {
+    // No packet received in time
+    ReadTimeout,
+    // Packet couldn't be sent in time
+    WriteTimeout,
+    // Peer didn't send any ping in time
+    PingTimeout,
+    // Peer was evicted to accept a new inbound connection
+    Evicted,
+    // Another connection is open with the same peer
+    DuplicateConnection
+}
+
+impl Display for DisconnectReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let value = match self {
+            Self::ReadTimeout => "read timeout",
+            Self::WriteTimeout => "write timeout",
+            Self::PingTimeout => "ping timeout",
+            Self::Evicted => "evicted",
+            Self::DuplicateConnection => "duplicate connection"
+        };
+        write!(f, "{}", value)
+    }
+}
+
 // A Peer represents a connection to another node in the network
 // It is used to propagate and receive blocks / transactions and do chain sync
 // It contains all the necessary information to manage the connection and the communication
@@ -123,6 +188,10 @@ pub struct Peer {
     last_ping: AtomicU64,
     // last time we sent a ping packet to this peer
     last_ping_sent: AtomicU64,
+    // when the connection with this peer was established
+    first_seen: TimestampSeconds,
+    // last time we received a packet from this peer
+    last_seen: AtomicU64,
     // cumulative difficulty of peer chain
     cumulative_difficulty: Mutex<CumulativeDifficulty>,
     // All transactions propagated from/to this peer
@@ -140,12 +209,30 @@ pub struct Peer {
     is_pruned: AtomicBool,
     // used for await on bootstrap chain packets
     bootstrap_chain: Mutex<Option<Sender<StepResponse>>>,
+    // pagination cursors of the bootstrap chain requests served to this peer
+    bootstrap_keys_cursor: Mutex<Option<PageCursor<PublicKey>>>,
+    bootstrap_assets_cursor: Mutex<Option<PageCursor<Hash>>>,
     // used to wait on chain response when syncing chain
     sync_chain: Mutex<Option<Sender<ChainResponse>>>,
+    // Last chain sync token given to this peer with the hash of the last block sent
+    chain_sync_token: Mutex<Option<(ChainSyncToken, Hash)>>,
     // IP address with local port
     outgoing_address: SocketAddr,
     // Determine if this peer allows to be shared to others and/or through API
     sharable: bool,
+    // Determine if this peer wants to receive our mempool inventory once connected
+    mempool_sync: bool,
+    // If we accept its mempool inventory without requesting it
+    // Only the first page can be sent this way, next ones are requested
+    mempool_inventory_expected: AtomicBool,
+    // Determine if this peer supports compact blocks propagation
+    compact_blocks: bool,
+    // How many compact blocks this peer failed to reconstruct
+    compact_blocks_failures: AtomicU8,
+    // Last blocks sent to this peer as compact blocks
+    compact_blocks_sent: Mutex<LruCache<Hash, ()>>,
+    // Version of the P2P protocol supported by this peer
+    protocol_version: u8,
     // Channel to send bytes to the writer task
     tx: Tx,
     // Channel to notify the tasks to exit
@@ -153,10 +240,29 @@ pub struct Peer {
     // Tracking dedicated tasks
     read_task: Mutex<TaskState>,
     write_task: Mutex<TaskState>,
+    // Reason of the disconnection if we closed it
+    disconnect_reason: Mutex<Option<DisconnectReason>>,
+    // Last topology shared by this peer in its ping packets
+    topology: Mutex<Option<TopologyEntry>>,
+    // Round trip time in milliseconds of the last object request
+    // u64::MAX until the first response is received
+    latency: AtomicU64,
+    // Limit the TXs propagated by this peer
+    tx_rate_limiter: Mutex<TokenBucket>,
+    // TXs dropped because of the rate limit
+    tx_rate_limit_strikes: AtomicU64,
+    // Limit the chain continuation requests of this peer
+    chain_continuation_rate_limiter: Mutex<TokenBucket>,
+    // Object requests of this peer we served
+    object_requests_served: AtomicU64,
+    // Object requests of this peer dropped because of too many queued
+    object_requests_dropped: AtomicU64,
+    // Chain requests of this peer accepted
+    chain_requests_served: AtomicU64,
 }
 
 impl Peer {
-    pub fn new(connection: Connection, id: u64, node_tag: Option<String>, local_port: u16, version: String, top_hash: Hash, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, priority: bool, cumulative_difficulty: CumulativeDifficulty, peer_list: SharedPeerList, peers_received: HashSet<SocketAddr>, sharable: bool) -> (Self, Rx) {
+    pub fn new(connection: Connection, id: u64, node_tag: Option<String>, local_port: u16, version: String, top_hash: Hash, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, priority: bool, cumulative_difficulty: CumulativeDifficulty, peer_list: SharedPeerList, peers_received: HashSet<SocketAddr>, sharable: bool, mempool_sync: bool, compact_blocks: bool, protocol_version: u8) -> (Self, Rx) {
         let mut outgoing_address = *connection.get_address();
         outgoing_address.set_port(local_port);
 
@@ -167,6 +273,7 @@ impl Peer {
 
         let (exit_channel, _) = broadcast::channel(1);
         let (tx, rx) = mpsc::channel(PEER_PACKET_CHANNEL_SIZE);
+        let first_seen = get_current_time_in_seconds();
 
         (Self {
             connection,
@@ -187,6 +294,8 @@ impl Peer {
             last_peer_list: AtomicU64::new(0),
             last_ping: AtomicU64::new(0),
             last_ping_sent: AtomicU64::new(0),
+            first_seen,
+            last_seen: AtomicU64::new(first_seen),
             cumulative_difficulty: Mutex::new(cumulative_difficulty),
             txs_cache: Mutex::new(LruCache::new(NonZeroUsize::new(PEER_TX_CACHE_SIZE).unwrap())),
             blocks_propagation: Mutex::new(LruCache::new(NonZeroUsize::new(PEER_BLOCK_CACHE_SIZE).unwrap())),
@@ -195,13 +304,31 @@ impl Peer {
             pruned_topoheight: AtomicU64::new(pruned_topoheight.unwrap_or(0)),
             is_pruned: AtomicBool::new(pruned_topoheight.is_some()),
             bootstrap_chain: Mutex::new(None),
+            bootstrap_keys_cursor: Mutex::new(None),
+            bootstrap_assets_cursor: Mutex::new(None),
             sync_chain: Mutex::new(None),
+            chain_sync_token: Mutex::new(None),
             outgoing_address,
             sharable,
+            mempool_sync,
+            mempool_inventory_expected: AtomicBool::new(false),
+            compact_blocks,
+            compact_blocks_failures: AtomicU8::new(0),
+            compact_blocks_sent: Mutex::new(LruCache::new(NonZeroUsize::new(PEER_BLOCK_CACHE_SIZE).unwrap())),
+            protocol_version,
             exit_channel,
             tx,
             read_task: Mutex::new(TaskState::Inactive),
             write_task: Mutex::new(TaskState::Inactive),
+            disconnect_reason: Mutex::new(None),
+            topology: Mutex::new(None),
+            latency: AtomicU64::new(u64::MAX),
+            tx_rate_limiter: Mutex::new(TokenBucket::new(PEER_TX_RATE_LIMIT_PER_SECOND, PEER_TX_RATE_LIMIT_BURST)),
+            tx_rate_limit_strikes: AtomicU64::new(0),
+            chain_continuation_rate_limiter: Mutex::new(TokenBucket::new(PEER_CHAIN_CONTINUATION_RATE_LIMIT_PER_SECOND, PEER_CHAIN_CONTINUATION_RATE_LIMIT_BURST)),
+            object_requests_served: AtomicU64::new(0),
+            object_requests_dropped: AtomicU64::new(0),
+            chain_requests_served: AtomicU64::new(0),
         }, rx)
     }
 
@@ -331,6 +458,49 @@ impl Peer {
         self.sharable
     }
 
+    // Get the mempool sync flag of the peer
+    pub fn is_mempool_sync_enabled(&self) -> bool {
+        self.mempool_sync
+    }
+
+    // Allow the peer to send us its mempool inventory without request
+    pub fn set_mempool_inventory_expected(&self, value: bool) {
+        self.mempool_inventory_expected.store(value, Ordering::Release)
+    }
+
+    // Returns true only once if we were expecting its mempool inventory
+    pub fn take_mempool_inventory_expected(&self) -> bool {
+        self.mempool_inventory_expected.swap(false, Ordering::AcqRel)
+    }
+
+    // Get the P2P protocol version supported by the peer
+    pub fn get_protocol_version(&self) -> u8 {
+        self.protocol_version
+    }
+
+    // Check if we should propagate blocks to this peer using compact blocks
+    // Once it failed too many times to reconstruct them, we fallback to full headers
+    pub fn use_compact_blocks(&self) -> bool {
+        self.compact_blocks && self.compact_blocks_failures.load(Ordering::Acquire) < PEER_COMPACT_BLOCKS_MAX_FAILURES
+    }
+
+    // Mark the block as sent to this peer using a compact block
+    pub async fn on_compact_block_sent(&self, hash: &Hash) {
+        self.compact_blocks_sent.lock().await.put(hash.clone(), ());
+    }
+
+    // The peer is requesting the full header of a block
+    // If we sent it as a compact block, it means the peer couldn't reconstruct it
+    pub async fn on_block_header_requested(&self, hash: &Hash) {
+        if self.compact_blocks_sent.lock().await.pop(hash).is_some() {
+            let failures = self.compact_blocks_failures.fetch_add(1, Ordering::AcqRel) + 1;
+            debug!("{} couldn't reconstruct compact block {} ({} failures)", self, hash, failures);
+            if failures == PEER_COMPACT_BLOCKS_MAX_FAILURES {
+                debug!("Fallback to full headers propagation for {}", self);
+            }
+        }
+    }
+
     // Get the last time we got a fail from the peer
     pub fn get_last_fail_count(&self) -> u64 {
         self.last_fail_count.load(Ordering::Acquire)
@@ -377,6 +547,54 @@ impl Peer {
         self.set_last_fail_count(current_time);
     }
 
+    // Check if the peer is allowed to propagate a new TX
+    // Every PEER_TX_RATE_LIMIT_STRIKES TXs dropped are counted as a fail
+    pub async fn check_tx_rate_limit(&self) -> bool {
+        if self.tx_rate_limiter.lock().await.try_take() {
+            return true
+        }
+
+        let strikes = self.tx_rate_limit_strikes.fetch_add(1, Ordering::AcqRel) + 1;
+        if strikes % PEER_TX_RATE_LIMIT_STRIKES == 0 {
+            self.increment_fail_count();
+        }
+        false
+    }
+
+    // Check if the peer is allowed to continue its chain sync with a new chain continuation request
+    pub async fn check_chain_continuation_rate_limit(&self) -> bool {
+        self.chain_continuation_rate_limiter.lock().await.try_take()
+    }
+
+    // Get the count of TXs dropped because of the rate limit
+    pub fn get_tx_rate_limit_strikes(&self) -> u64 {
+        self.tx_rate_limit_strikes.load(Ordering::Acquire)
+    }
+
+    pub fn on_object_request_served(&self) {
+        self.object_requests_served.fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub fn get_object_requests_served(&self) -> u64 {
+        self.object_requests_served.load(Ordering::Acquire)
+    }
+
+    pub fn on_object_request_dropped(&self) {
+        self.object_requests_dropped.fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub fn get_object_requests_dropped(&self) -> u64 {
+        self.object_requests_dropped.load(Ordering::Acquire)
+    }
+
+    pub fn on_chain_request_served(&self) {
+        self.chain_requests_served.fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub fn get_chain_requests_served(&self) -> u64 {
+        self.chain_requests_served.load(Ordering::Acquire)
+    }
+
     // Get the last time we got a chain sync request
     // This is used to prevent spamming the chain sync packet
     pub fn get_last_chain_sync(&self) -> TimestampSeconds {
@@ -408,6 +626,11 @@ impl Peer {
     // Request a object from this peer and wait on it until we receive it or until timeout 
     pub async fn request_blocking_object(&self, request: ObjectRequest) -> Result<OwnedObjectResponse, P2pError> {
         trace!("Requesting {} from {}", request, self);
+        // Nothing is sent if it can't have it, so the peer isn't penalized for it
+        if !can_serve_object(self.get_pruned_topoheight(), &request) {
+            debug!("{} is pruned above the {} requested", self, request);
+            return Err(P2pError::ObjectPrunedByPeer(request))
+        }
         let receiver = {
             let mut objects = self.objects_requested.lock().await;
             if objects.contains_key(&request) {
@@ -418,6 +641,7 @@ impl Peer {
             objects.insert(request.clone(), sender); // clone is necessary in case timeout has occured
             receiver
         };
+        let start = Instant::now();
         let object = match timeout(Duration::from_millis(PEER_TIMEOUT_REQUEST_OBJECT), receiver).await {
             Ok(res) => res?,
             Err(e) => {
@@ -427,11 +651,12 @@ impl Peer {
                 return Err(P2pError::AsyncTimeOut(e));
             }
         };
+        self.latency.store(start.elapsed().as_millis() as u64, Ordering::Release);
 
         // Verify that the object is the one we requested
-        let object_hash = object.get_hash();
-        if *object_hash != *request.get_hash() {
-            return Err(P2pError::InvalidObjectResponse(object_hash.clone()))
+        let object_request = object.get_request();
+        if object_request != request {
+            return Err(P2pError::UnexpectedObjectResponse(object_request))
         }
 
         // Returns error if the object is not found
@@ -442,6 +667,15 @@ impl Peer {
         Ok(object)
     }
 
+    // Request up to `count` headers in topological order starting at the block hash
+    // Used to sync headers first before requesting the full blocks
+    pub async fn request_headers_range(&self, hash: Hash, count: u16) -> Result<Vec<HeaderEntry>, P2pError> {
+        match self.request_blocking_object(ObjectRequest::HeadersRange(hash, count)).await? {
+            OwnedObjectResponse::HeadersRange(headers, _, _) => Ok(headers),
+            _ => Err(P2pError::InvalidObjectResponseType)
+        }
+    }
+
     // Request a bootstrap chain from this peer and wait on it until we receive it or until timeout
     pub async fn request_boostrap_chain(&self, step: StepRequest<'_>) -> Result<StepResponse, P2pError> {
         debug!("Requesting bootstrap chain step: {:?}", step.kind());
@@ -476,6 +710,16 @@ impl Peer {
     // Request a sync chain from this peer and wait on it until we receive it or until timeout
     pub async fn request_sync_chain(&self, request: PacketWrapper<'_, ChainRequest>) -> Result<ChainResponse, P2pError> {
         debug!("Requesting sync chain");
+        self.request_chain_response(Packet::ChainRequest(request)).await
+    }
+
+    // Request the next blocks of a previous chain response
+    pub async fn request_chain_continuation(&self, request: ChainContinuationRequest) -> Result<ChainResponse, P2pError> {
+        debug!("Requesting chain continuation from topoheight {}", request.get_token().get_topoheight());
+        self.request_chain_response(Packet::ChainContinuationRequest(request)).await
+    }
+
+    async fn request_chain_response(&self, packet: Packet<'_>) -> Result<ChainResponse, P2pError> {
         let (sender, receiver) = tokio::sync::oneshot::channel();
         {
             let mut sender_lock = self.sync_chain.lock().await;
@@ -483,7 +727,7 @@ impl Peer {
         }
 
         trace!("sending chain request packet");
-        self.send_packet(Packet::ChainRequest(request)).await?;
+        self.send_packet(packet).await?;
 
         trace!("waiting for chain response");
         let response: ChainResponse = match timeout(Duration::from_secs(CHAIN_SYNC_TIMEOUT_SECS), receiver).await {
@@ -503,12 +747,32 @@ impl Peer {
         &self.bootstrap_chain
     }
 
+    // Get the cursor of the last keys page served to this peer
+    pub fn get_bootstrap_keys_cursor(&self) -> &Mutex<Option<PageCursor<PublicKey>>> {
+        &self.bootstrap_keys_cursor
+    }
+
+    // Get the cursor of the last assets page served to this peer
+    pub fn get_bootstrap_assets_cursor(&self) -> &Mutex<Option<PageCursor<Hash>>> {
+        &self.bootstrap_assets_cursor
+    }
+
     // Get the sync chain channel
     // This is used for chain sync requests to be fully awaited
     pub fn get_sync_chain_channel(&self) -> &Mutex<Option<Sender<ChainResponse>>> {
         &self.sync_chain
     }
 
+    // Set the chain sync token given to this peer, replacing the previous one
+    pub async fn set_chain_sync_token(&self, token: Option<(ChainSyncToken, Hash)>) {
+        *self.chain_sync_token.lock().await = token;
+    }
+
+    // Take the chain sync token given to this peer, a token can only be used once
+    pub async fn take_chain_sync_token(&self) -> Option<(ChainSyncToken, Hash)> {
+        self.chain_sync_token.lock().await.take()
+    }
+
     // Get all shared peers between this peer and us
     pub fn get_peers(&self) -> &Mutex<HashMap<SocketAddr, Direction>> {
         &self.peers
@@ -535,6 +799,41 @@ impl Peer {
         self.last_ping.store(value, Ordering::Release)
     }
 
+    // Get when the connection with this peer was established
+    pub fn get_first_seen(&self) -> TimestampSeconds {
+        self.first_seen
+    }
+
+    // Get the last time we received a packet from this peer
+    pub fn get_last_seen(&self) -> TimestampSeconds {
+        self.last_seen.load(Ordering::Acquire)
+    }
+
+    // Track the last time we received a packet from this peer
+    pub fn set_last_seen(&self, value: TimestampSeconds) {
+        self.last_seen.store(value, Ordering::Release)
+    }
+
+    // Get the last topology shared by this peer
+    pub async fn get_topology(&self) -> Option<TopologyEntry> {
+        self.topology.lock().await.clone()
+    }
+
+    // Store the topology received in a ping packet
+    pub async fn set_topology(&self, topology: TopologyEntry) {
+        *self.topology.lock().await = Some(topology);
+    }
+
+    // Get the round trip time in milliseconds of the last object request
+    pub fn get_latency(&self) -> Option<u64> {
+        let latency = self.latency.load(Ordering::Acquire);
+        if latency == u64::MAX {
+            None
+        } else {
+            Some(latency)
+        }
+    }
+
     // Get the last time we sent a ping packet to this peer
     pub fn get_last_ping_sent(&self) -> TimestampSeconds {
         self.last_ping_sent.load(Ordering::Acquire)
@@ -542,7 +841,7 @@ impl Peer {
 
     // Track the last time we sent a ping packet to this peer
     pub fn set_last_ping_sent(&self, value: TimestampSeconds) {
-        self.last_ping.store(value, Ordering::Release)
+        self.last_ping_sent.store(value, Ordering::Release)
     }
 
     // Get the last time a inventory has been requested
@@ -584,7 +883,7 @@ impl Peer {
                 debug!("{} is a priority peer, closing only", self);
             }
     
-            self.peer_list.remove_peer(self.get_id()).await?;
+            self.peer_list.remove_peer(self).await?;
         }
         res?;
         
@@ -603,7 +902,7 @@ impl Peer {
         trace!("Closing connection with {}", self);
 
         // Remove this peer from peer list
-        let res = self.peer_list.remove_peer(self.get_id()).await;
+        let res = self.peer_list.remove_peer(self).await;
 
         // Notify the writer task to exit
         self.signal_exit().await?;
@@ -611,13 +910,31 @@ impl Peer {
         res
     }
 
+    // Close the peer connection for a specific reason
+    // The reason is recorded when removing it from the peer list
+    pub async fn close_with_reason(&self, reason: DisconnectReason) -> Result<(), P2pError> {
+        debug!("Closing connection with {} due to {}", self, reason);
+        self.set_disconnect_reason(reason).await;
+        self.close().await
+    }
+
+    // Get the reason of the disconnection if any
+    pub async fn get_disconnect_reason(&self) -> Option<DisconnectReason> {
+        *self.disconnect_reason.lock().await
+    }
+
+    // Set the reason of the disconnection
+    pub async fn set_disconnect_reason(&self, reason: DisconnectReason) {
+        *self.disconnect_reason.lock().await = Some(reason);
+    }
+
     // Close the peer connection and remove it from the peer list
     pub async fn close_internal(&self) -> Result<(), P2pError> {
         trace!("Closing internal connection with {}", self);
         let res_notify = self.exit_channel.send(()).map_err(|e| P2pError::SendError(e.to_string()));
 
         if !self.get_connection().is_closed() {            
-            let res = self.peer_list.remove_peer(self.get_id()).await;
+            let res = self.peer_list.remove_peer(self).await;
             self.get_connection().close().await?;
             res?;
         }
@@ -8,7 +8,12 @@ use crate::{
     },
     p2p::packet::peer_disconnected::PacketPeerDisconnected
 };
-use super::{peer::Peer, packet::Packet, error::P2pError};
+use super::{
+    peer::{DisconnectReason, Peer},
+    packet::Packet,
+    error::P2pError,
+    selection::{select_peer_to_evict, EvictionCandidate}
+};
 use std::{
     collections::{hash_map::Entry, HashMap, HashSet},
     fmt::{self, Display, Formatter},
@@ -16,6 +21,7 @@ use std::{
     time::Duration
 };
 use humantime::format_duration;
+use indexmap::IndexMap;
 use serde::{Serialize, Deserialize};
 use tokio::{sync::{mpsc::Sender, RwLock}, time::timeout};
 use xelis_common::{
@@ -43,7 +49,9 @@ pub struct PeerList {
     // used to notify the server that a peer disconnected
     // this is done through a channel to not have to handle generic types
     // and to be flexible in the future
-    peer_disconnect_channel: Option<Sender<Arc<Peer>>>
+    peer_disconnect_channel: Option<Sender<Arc<Peer>>>,
+    // Count of disconnections initiated by us for each reason
+    disconnect_reasons: RwLock<HashMap<DisconnectReason, u64>>
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq)]
@@ -132,7 +140,8 @@ impl PeerList {
                 peers: RwLock::new(HashMap::with_capacity(capacity)),
                 stored_peers: RwLock::new(stored_peers),
                 filename,
-                peer_disconnect_channel
+                peer_disconnect_channel,
+                disconnect_reasons: RwLock::new(HashMap::new())
             }
         )
     }
@@ -150,13 +159,27 @@ impl PeerList {
 
     // Remove a peer from the list
     // We will notify all peers that have this peer in common
-    pub async fn remove_peer(&self, peer_id: u64) -> Result<(), P2pError> {
+    pub async fn remove_peer(&self, peer: &Peer) -> Result<(), P2pError> {
+        let peer_id = peer.get_id();
         let (peer, peers) = {
             let mut peers = self.peers.write().await;
+            // A connection replaced by another one with the same id must not remove it
+            match peers.get(&peer_id) {
+                Some(current) if std::ptr::eq(Arc::as_ptr(current), peer) => {},
+                _ => return Err(P2pError::PeerNotFoundById(peer_id))
+            }
             let peer = peers.remove(&peer_id).ok_or(P2pError::PeerNotFoundById(peer_id))?;
             let peers = peers.values().cloned().collect::<Vec<Arc<Peer>>>();
             (peer, peers)
         };
+
+        // keep the last time we received something from it
+        {
+            let mut stored_peers = self.stored_peers.write().await;
+            if let Some(stored_peer) = stored_peers.get_mut(&peer.get_outgoing_address().ip()) {
+                stored_peer.set_last_seen(peer.get_last_seen());
+            }
+        }
  
         // If peer allows us to share it, we have to notify all peers that have this peer in common
         if peer.sharable() {
@@ -186,7 +209,13 @@ impl PeerList {
             }
         }
 
-        info!("Peer disconnected: {}", peer);
+        if let Some(reason) = peer.get_disconnect_reason().await {
+            info!("Peer disconnected: {} ({})", peer, reason);
+            self.add_disconnect_reason(reason).await;
+        } else {
+            info!("Peer disconnected: {}", peer);
+        }
+
         if let Some(peer_disconnect_channel) = &self.peer_disconnect_channel {
             debug!("Notifying server that {} disconnected", peer);
             if let Err(e) = peer_disconnect_channel.send(peer).await {
@@ -197,6 +226,18 @@ impl PeerList {
         Ok(())
     }
 
+    // Count a disconnection initiated by us
+    // It is also used for connections closed before being added to the list
+    pub async fn add_disconnect_reason(&self, reason: DisconnectReason) {
+        let mut disconnect_reasons = self.disconnect_reasons.write().await;
+        *disconnect_reasons.entry(reason).or_insert(0) += 1;
+    }
+
+    // Get the count of disconnections initiated by us for each reason
+    pub async fn get_disconnect_reasons(&self) -> HashMap<DisconnectReason, u64> {
+        self.disconnect_reasons.read().await.clone()
+    }
+
     // Add a new peer to the list
     // This will returns an error if peerlist is full
     pub async fn add_peer(&self, peer: &Arc<Peer>, max_peers: usize) -> Result<(), P2pError> {
@@ -235,12 +276,31 @@ impl PeerList {
         }
     }
 
+    // Find the peer to evict to make room for a new inbound connection
+    pub async fn find_peer_to_evict(&self) -> Option<Arc<Peer>> {
+        let peers = self.peers.read().await;
+        let candidates = peers.values().map(|peer| EvictionCandidate {
+            id: peer.get_id(),
+            priority: peer.is_priority(),
+            outgoing: peer.get_connection().is_out(),
+            first_seen: peer.get_first_seen()
+        }).collect();
+
+        select_peer_to_evict(candidates).and_then(|id| peers.get(&id).cloned())
+    }
+
     // Verify if the peer is connected (in peerlist)
     pub async fn has_peer(&self, peer_id: &u64) -> bool {
         let peers = self.peers.read().await;
         peers.contains_key(peer_id)
     }
 
+    // Get a connected peer using its id
+    pub async fn get_peer(&self, peer_id: &u64) -> Option<Arc<Peer>> {
+        let peers = self.peers.read().await;
+        peers.get(peer_id).cloned()
+    }
+
     // Check if the peer is known from our peerlist
     pub async fn has_peer_stored(&self, ip: &IpAddr) -> bool {
         let stored_peers = self.stored_peers.read().await;
@@ -451,7 +511,7 @@ impl PeerList {
             error!("Error while trying to close {} for being temp banned: {}", peer, e);
         }
 
-        if let Err(e) = self.remove_peer(peer.get_id()).await {
+        if let Err(e) = self.remove_peer(peer).await {
             error!("Error while removing peer from peerlist for being temp banned: {}", e);
         }
     }
@@ -495,10 +555,16 @@ impl PeerList {
 
     // find among stored peers a peer to connect to with the requested StoredPeerState
     // we check that we're not already connected to this peer and that we didn't tried to connect to it recently
+    // the most recently seen peers are tried first
     fn find_peer_to_connect_to_with_state(&self, peers: &HashMap<u64, Arc<Peer>>, stored_peers: &mut HashMap<IpAddr, StoredPeer>, current_time: TimestampSeconds, state: StoredPeerState) -> Option<SocketAddr> {
+        let mut stored_peers = stored_peers.iter_mut()
+            .filter(|(_, stored_peer)| *stored_peer.get_state() == state)
+            .collect::<Vec<_>>();
+        stored_peers.sort_by(|(_, a), (_, b)| b.get_last_seen().cmp(&a.get_last_seen()));
+
         for (ip, stored_peer) in stored_peers {
             let addr = SocketAddr::new(*ip, stored_peer.get_local_port());
-            if *stored_peer.get_state() == state && stored_peer.get_last_connection_try() + (stored_peer.get_fail_count() as u64 * P2P_EXTEND_PEERLIST_DELAY) <= current_time && Self::internal_get_peer_by_addr(peers, &addr).is_none() {
+            if stored_peer.get_last_connection_try() + (stored_peer.get_fail_count() as u64 * P2P_EXTEND_PEERLIST_DELAY) <= current_time && Self::internal_get_peer_by_addr(peers, &addr).is_none() {
                 stored_peer.set_last_connection_try(current_time);
                 return Some(addr);
             }
@@ -545,7 +611,10 @@ impl PeerList {
     // serialize the stored peers to a file
     fn save_peers_to_file(&self, stored_peers: &HashMap<IpAddr, StoredPeer>) -> Result<(), P2pError> {
         trace!("saving peerlist to file");
-        let content = serde_json::to_string_pretty(&stored_peers)?;
+        // most recently seen peers first
+        let mut sorted = stored_peers.iter().collect::<Vec<_>>();
+        sorted.sort_by(|(_, a), (_, b)| b.get_last_seen().cmp(&a.get_last_seen()));
+        let content = serde_json::to_string_pretty(&sorted.into_iter().collect::<IndexMap<_, _>>())?;
         fs::write(&self.filename, content)?;
 
         Ok(())
@@ -574,6 +643,10 @@ impl StoredPeer {
         &self.state
     }
 
+    fn get_last_seen(&self) -> TimestampSeconds {
+        self.last_seen
+    }
+
     fn set_last_seen(&mut self, last_seen: TimestampSeconds) {
         self.last_seen = last_seen;
     }
@@ -0,0 +1,79 @@
+use std::time::Instant;
+
+// Token bucket refilled continuously at a fixed rate
+// Each allowed action consumes one token
+pub struct TokenBucket {
+    // Maximum tokens stored, this is the allowed burst
+    capacity: f64,
+    // Tokens added per second
+    refill_per_second: f64,
+    // Tokens currently available
+    tokens: f64,
+    // Last time the tokens got refilled
+    last_refill: Instant
+}
+
+impl TokenBucket {
+    // Create a full bucket
+    pub fn new(refill_per_second: u64, capacity: u64) -> Self {
+        Self {
+            capacity: capacity as f64,
+            refill_per_second: refill_per_second as f64,
+            tokens: capacity as f64,
+            last_refill: Instant::now()
+        }
+    }
+
+    // Consume a token if one is available
+    pub fn try_take(&mut self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+
+    fn try_take_at(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn test_burst_then_limited() {
+        let mut bucket = TokenBucket::new(50, 200);
+        let now = bucket.last_refill;
+        let allowed = (0..1000).filter(|_| bucket.try_take_at(now)).count();
+        assert_eq!(allowed, 200);
+        assert!(!bucket.try_take_at(now));
+    }
+
+    #[test]
+    fn test_refill_over_time() {
+        let mut bucket = TokenBucket::new(50, 200);
+        let now = bucket.last_refill;
+        while bucket.try_take_at(now) {}
+
+        // 100ms is enough for 5 TXs
+        let later = now + Duration::from_millis(100);
+        let allowed = (0..100).filter(|_| bucket.try_take_at(later)).count();
+        assert_eq!(allowed, 5);
+    }
+
+    #[test]
+    fn test_refill_capped_to_capacity() {
+        let mut bucket = TokenBucket::new(50, 200);
+        let now = bucket.last_refill + Duration::from_secs(3600);
+        let allowed = (0..1000).filter(|_| bucket.try_take_at(now)).count();
+        assert_eq!(allowed, 200);
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration
+};
+use indexmap::IndexMap;
+use tokio::{sync::oneshot, time::timeout};
+
+// Limit the object requests served at the same time
+// Each peer has its own limit and its own queue, and queued requests are served
+// peer by peer (round robin) so a peer flooding us can't delay the requests of others
+pub struct RequestScheduler {
+    state: Arc<Mutex<SchedulerState>>
+}
+
+struct SchedulerState {
+    // Requests served at the same time by all peers
+    max_in_flight: usize,
+    // Requests served at the same time by a peer
+    max_in_flight_per_peer: usize,
+    // Requests waiting for a slot per peer
+    max_queued_per_peer: usize,
+    in_flight: usize,
+    // Queues by peer id, only peers with requests in flight or queued are kept
+    peers: IndexMap<u64, PeerQueue>,
+    // Index of the next peer to serve
+    next: usize
+}
+
+#[derive(Default)]
+struct PeerQueue {
+    in_flight: usize,
+    waiting: VecDeque<oneshot::Sender<()>>
+}
+
+// Request accepted by the scheduler, waiting or not for a slot
+// The slot given to it is released if it is dropped before being served
+pub struct RequestTicket {
+    state: Arc<Mutex<SchedulerState>>,
+    peer_id: u64,
+    // None if a slot was available directly
+    receiver: Option<oneshot::Receiver<()>>,
+    // Set once its slot is owned by a permit
+    has_permit: bool
+}
+
+// Slot used to serve a request, released on drop
+pub struct RequestPermit {
+    state: Arc<Mutex<SchedulerState>>,
+    peer_id: u64
+}
+
+impl RequestScheduler {
+    pub fn new(max_in_flight: usize, max_in_flight_per_peer: usize, max_queued_per_peer: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SchedulerState {
+                max_in_flight,
+                max_in_flight_per_peer,
+                max_queued_per_peer,
+                in_flight: 0,
+                peers: IndexMap::new(),
+                next: 0
+            }))
+        }
+    }
+
+    // Register a new request from a peer
+    // None if the queue of this peer is full and the request must be dropped
+    pub fn try_acquire(&self, peer_id: u64) -> Option<RequestTicket> {
+        let mut guard = self.state.lock().expect("request scheduler lock");
+        let state = &mut *guard;
+        let has_slot = state.in_flight < state.max_in_flight;
+        let (max_per_peer, max_queued) = (state.max_in_flight_per_peer, state.max_queued_per_peer);
+        let queue = state.peers.entry(peer_id).or_default();
+        // Requests that stopped waiting don't count in the queue
+        queue.waiting.retain(|sender| !sender.is_closed());
+
+        // A free slot means no queued request can use it, so we don't skip anyone
+        let receiver = if has_slot && queue.waiting.is_empty() && queue.in_flight < max_per_peer {
+            queue.in_flight += 1;
+            state.in_flight += 1;
+            None
+        } else if queue.waiting.len() >= max_queued {
+            return None
+        } else {
+            let (sender, receiver) = oneshot::channel();
+            queue.waiting.push_back(sender);
+            Some(receiver)
+        };
+
+        Some(RequestTicket {
+            state: Arc::clone(&self.state),
+            peer_id,
+            receiver,
+            has_permit: false
+        })
+    }
+
+    // Count of requests currently served
+    #[cfg(test)]
+mod tests {
+    use std::iter::repeat;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_limits_and_queue() {
+        let scheduler = RequestScheduler::new(4, 2, 1);
+        let first = scheduler.try_acquire(1).unwrap().wait(Duration::ZERO).await.unwrap();
+        let _second = scheduler.try_acquire(1).unwrap().wait(Duration::ZERO).await.unwrap();
+        assert_eq!(scheduler.get_in_flight(), 2);
+
+        // Peer limit is reached, one request can be queued and the next one is dropped
+        let queued = scheduler.try_acquire(1).unwrap();
+        assert!(scheduler.try_acquire(1).is_none());
+
+        // Another peer isn't impacted
+        let _other = scheduler.try_acquire(2).unwrap().wait(Duration::ZERO).await.unwrap();
+        assert_eq!(scheduler.get_in_flight(), 3);
+
+        // The queued request gets the released slot
+        drop(first);
+        assert!(queued.wait(Duration::ZERO).await.is_some());
+        assert_eq!(scheduler.get_in_flight(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_queue_timeout_releases_nothing() {
+        let scheduler = RequestScheduler::new(1, 1, 8);
+        let permit = scheduler.try_acquire(1).unwrap().wait(Duration::ZERO).await.unwrap();
+        assert!(scheduler.try_acquire(2).unwrap().wait(Duration::from_millis(10)).await.is_none());
+
+        drop(permit);
+        assert_eq!(scheduler.get_in_flight(), 0);
+        assert!(scheduler.try_acquire(2).unwrap().wait(Duration::ZERO).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_tickets_release_their_slot() {
+        let scheduler = RequestScheduler::new(1, 1, 1);
+
+        // Slot given directly but the request is dropped before being served
+        drop(scheduler.try_acquire(1).unwrap());
+        assert_eq!(scheduler.get_in_flight(), 0);
+
+        // Slot given to a queued request that is dropped without waiting for it
+        let permit = scheduler.try_acquire(1).unwrap().wait(Duration::ZERO).await.unwrap();
+        let queued = scheduler.try_acquire(2).unwrap();
+        drop(permit);
+        assert_eq!(scheduler.get_in_flight(), 1);
+        drop(queued);
+        assert_eq!(scheduler.get_in_flight(), 0);
+
+        // Dropped before getting a slot, it doesn't fill the queue of its peer
+        let permit = scheduler.try_acquire(1).unwrap().wait(Duration::ZERO).await.unwrap();
+        drop(scheduler.try_acquire(2).unwrap());
+        let queued = scheduler.try_acquire(2).unwrap();
+        drop(permit);
+        assert!(queued.wait(Duration::ZERO).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_flooding_peer_does_not_starve_others() {
+        // Only one slot, so requests are served in the order of the scheduler
+        let scheduler = RequestScheduler::new(1, 1, 256);
+        let first = scheduler.try_acquire(1).unwrap().wait(Duration::ZERO).await.unwrap();
+        let served = Arc::new(Mutex::new(Vec::new()));
+
+        // First peer queues a lot of requests before the second one
+        let mut requests = Vec::new();
+        for peer_id in repeat(1).take(200).chain(repeat(2).take(5)) {
+            let ticket = scheduler.try_acquire(peer_id).unwrap();
+            let served = Arc::clone(&served);
+            requests.push(tokio::spawn(async move {
+                let permit = ticket.wait(Duration::from_secs(10)).await.unwrap();
+                served.lock().unwrap().push(peer_id);
+                drop(permit);
+            }));
+        }
+
+        drop(first);
+        for request in requests {
+            request.await.unwrap();
+        }
+        assert_eq!(scheduler.get_in_flight(), 0);
+
+        // Requests of the second peer are served one after another with the flood, not after it
+        let served = served.lock().unwrap();
+        assert_eq!(served.len(), 205);
+        let last = served.iter().rposition(|peer_id| *peer_id == 2).unwrap();
+        assert!(last < 10, "last request of the second peer served at {}", last);
+    }
+}
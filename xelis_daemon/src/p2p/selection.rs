@@ -0,0 +1,171 @@
+use xelis_common::time::TimestampSeconds;
+use super::packet::object::ObjectRequest;
+
+// A pruned peer only has the blocks above its pruned topoheight
+pub fn can_serve_topoheight(pruned_topoheight: Option<u64>, topoheight: u64) -> bool {
+    pruned_topoheight.map_or(true, |pruned_topoheight| pruned_topoheight <= topoheight)
+}
+
+// Objects requested by hash can't be located, they are always requested
+pub fn can_serve_object(pruned_topoheight: Option<u64>, request: &ObjectRequest) -> bool {
+    request.get_topoheight().map_or(true, |topoheight| can_serve_topoheight(pruned_topoheight, topoheight))
+}
+
+// Result of the peers selection for a sync starting at a topoheight
+#[derive(Debug, PartialEq, Eq)]
+pub enum SyncCandidates<T> {
+    // Peers able to serve the whole range
+    Available(Vec<T>),
+    // Candidates exist but all of them are pruned above the needed topoheight
+    NoArchivalPeer,
+    // No candidate at all
+    Empty
+}
+
+// Keep only the candidates with their pruned topoheight able to serve blocks from `needed_topoheight`
+// Pruned peers above it are skipped, so we fallback on archive peers
+pub fn select_sync_candidates<T>(candidates: Vec<(T, Option<u64>)>, needed_topoheight: u64) -> SyncCandidates<T> {
+    if candidates.is_empty() {
+        return SyncCandidates::Empty
+    }
+
+    let available: Vec<T> = candidates.into_iter()
+        .filter(|(_, pruned_topoheight)| can_serve_topoheight(*pruned_topoheight, needed_topoheight))
+        .map(|(candidate, _)| candidate)
+        .collect();
+
+    if available.is_empty() {
+        SyncCandidates::NoArchivalPeer
+    } else {
+        SyncCandidates::Available(available)
+    }
+}
+
+// Connected peer that may be evicted to accept a new inbound connection
+#[derive(Debug, Clone)]
+pub struct EvictionCandidate<T> {
+    pub id: T,
+    pub priority: bool,
+    pub outgoing: bool,
+    pub first_seen: TimestampSeconds
+}
+
+// Select the peer to evict when the peerlist is full and a new inbound connection arrives
+// Priority peers, outgoing peers and the longest-connected half of all peers are protected
+// The newest inbound peer remaining is evicted, if any
+pub fn select_peer_to_evict<T>(mut candidates: Vec<EvictionCandidate<T>>) -> Option<T> {
+    // Oldest connections first
+    candidates.sort_by_key(|candidate| candidate.first_seen);
+    let protected = candidates.len() / 2;
+
+    candidates.into_iter()
+        .skip(protected)
+        .rev()
+        .find(|candidate| !candidate.priority && !candidate.outgoing)
+        .map(|candidate| candidate.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::Hash;
+    use super::*;
+
+    #[test]
+    fn test_can_serve_topoheight() {
+        assert!(can_serve_topoheight(None, 0));
+        assert!(can_serve_topoheight(Some(100), 100));
+        assert!(can_serve_topoheight(Some(100), 150));
+        assert!(!can_serve_topoheight(Some(100), 99));
+    }
+
+    #[test]
+    fn test_can_serve_object() {
+        assert!(can_serve_object(None, &ObjectRequest::BlockAtTopoheight(0)));
+        assert!(can_serve_object(Some(100), &ObjectRequest::BlockAtTopoheight(100)));
+        assert!(!can_serve_object(Some(100), &ObjectRequest::BlockAtTopoheight(99)));
+
+        // Its topoheight is unknown
+        assert!(can_serve_object(Some(100), &ObjectRequest::Block(Hash::zero())));
+    }
+
+    #[test]
+    fn test_pruned_peers_are_skipped() {
+        let candidates = vec![(1, Some(500)), (2, None), (3, Some(100)), (4, Some(200))];
+        assert_eq!(select_sync_candidates(candidates, 150), SyncCandidates::Available(vec![2, 3]));
+    }
+
+    #[test]
+    fn test_fallback_on_archive_peers() {
+        let candidates = vec![(1, Some(500)), (2, Some(300)), (3, None)];
+        assert_eq!(select_sync_candidates(candidates, 0), SyncCandidates::Available(vec![3]));
+    }
+
+    #[test]
+    fn test_no_archival_peer_available() {
+        let candidates = vec![(1, Some(500)), (2, Some(300))];
+        assert_eq!(select_sync_candidates(candidates, 100), SyncCandidates::NoArchivalPeer);
+        assert_eq!(select_sync_candidates(Vec::<(u64, Option<u64>)>::new(), 100), SyncCandidates::Empty);
+    }
+
+    fn candidate(id: u64, priority: bool, outgoing: bool, first_seen: TimestampSeconds) -> EvictionCandidate<u64> {
+        EvictionCandidate { id, priority, outgoing, first_seen }
+    }
+
+    #[test]
+    fn test_evict_newest_inbound_peer() {
+        let candidates = vec![
+            candidate(1, false, false, 100),
+            candidate(2, false, false, 400),
+            candidate(3, false, false, 200),
+            candidate(4, false, false, 300)
+        ];
+        assert_eq!(select_peer_to_evict(candidates), Some(2));
+    }
+
+    #[test]
+    fn test_longest_connected_half_is_protected() {
+        // Only the two oldest are inbound, but they are in the protected half
+        let candidates = vec![
+            candidate(1, false, false, 100),
+            candidate(2, false, false, 200),
+            candidate(3, false, true, 300),
+            candidate(4, false, true, 400)
+        ];
+        assert_eq!(select_peer_to_evict(candidates), None);
+
+        // With an odd count, the newest half is the biggest one
+        let candidates = vec![
+            candidate(1, false, false, 100),
+            candidate(2, false, false, 200),
+            candidate(3, false, true, 300)
+        ];
+        assert_eq!(select_peer_to_evict(candidates), Some(2));
+    }
+
+    #[test]
+    fn test_priority_and_outgoing_peers_are_protected() {
+        let candidates = vec![
+            candidate(1, false, false, 100),
+            candidate(2, false, false, 200),
+            candidate(3, false, false, 300),
+            candidate(4, false, false, 400),
+            candidate(5, true, false, 600),
+            candidate(6, false, true, 500)
+        ];
+        assert_eq!(select_peer_to_evict(candidates), Some(4));
+
+        let candidates = vec![
+            candidate(1, true, false, 100),
+            candidate(2, true, false, 200),
+            candidate(3, false, true, 300)
+        ];
+        assert_eq!(select_peer_to_evict(candidates), None);
+    }
+
+    #[test]
+    fn test_no_peer_to_evict() {
+        assert_eq!(select_peer_to_evict(Vec::<EvictionCandidate<u64>>::new()), None);
+        // A single peer is always protected
+        assert_eq!(select_peer_to_evict(vec![candidate(1, false, false, 100)]), None);
+    }
+}
@@ -0,0 +1,180 @@
+use xelis_common::{
+    serializer::{Reader, ReaderError, Serializer, Writer},
+    time::TimestampSeconds
+};
+use crate::config::P2P_TOPOLOGY_STALE_DELAY;
+use super::packet::handshake::read_node_tag;
+
+// Topology data shared by a peer in its ping packet with peer list
+// Only sent by nodes that opted in, and only to peers supporting it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PingTopology {
+    // How many connections the peer has
+    peers_count: u16,
+    // Node tag of the peer
+    node_tag: Option<String>
+}
+
+impl PingTopology {
+    pub fn new(peers_count: u16, node_tag: Option<String>) -> Self {
+        Self {
+            peers_count,
+            node_tag
+        }
+    }
+
+    pub fn get_peers_count(&self) -> u16 {
+        self.peers_count
+    }
+
+    pub fn get_node_tag(&self) -> &Option<String> {
+        &self.node_tag
+    }
+}
+
+impl Serializer for PingTopology {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u16(self.peers_count);
+        writer.write_optional_string(&self.node_tag);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let peers_count = reader.read_u16()?;
+        let node_tag = read_node_tag(reader)?;
+
+        Ok(Self { peers_count, node_tag })
+    }
+
+    fn size(&self) -> usize {
+        // u8 for the length of the optional node tag
+        self.peers_count.size() + 1 + self.node_tag.as_ref().map_or(0, |tag| tag.len())
+    }
+}
+
+// Last topology received from a peer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TopologyEntry {
+    topology: PingTopology,
+    // When we received it
+    updated_at: TimestampSeconds
+}
+
+impl TopologyEntry {
+    pub fn new(topology: PingTopology, updated_at: TimestampSeconds) -> Self {
+        Self {
+            topology,
+            updated_at
+        }
+    }
+
+    pub fn get_topology(&self) -> &PingTopology {
+        &self.topology
+    }
+
+    pub fn get_updated_at(&self) -> TimestampSeconds {
+        self.updated_at
+    }
+
+    // Data not refreshed since more than the stale delay is still reported, but marked as stale
+    pub fn is_stale(&self, now: TimestampSeconds) -> bool {
+        now.saturating_sub(self.updated_at) > P2P_TOPOLOGY_STALE_DELAY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap, net::SocketAddr};
+    use indexmap::IndexSet;
+    use xelis_common::{crypto::Hash, difficulty::CumulativeDifficulty};
+    use crate::p2p::packet::{handshake::Handshake, ping::Ping};
+    use super::*;
+
+    // Minimal in-process node exchanging ping packets with its peers
+    struct TestNode {
+        tag: Option<String>,
+        share_topology: bool,
+        peers: Vec<SocketAddr>,
+        // Topology received from each peer
+        received: HashMap<SocketAddr, TopologyEntry>
+    }
+
+    impl TestNode {
+        fn new(tag: &str, share_topology: bool, peers: &[&str]) -> Self {
+            Self {
+                tag: Some(tag.to_owned()),
+                share_topology,
+                peers: peers.iter().map(|addr| addr.parse().unwrap()).collect(),
+                received: HashMap::new()
+            }
+        }
+
+        // Build the ping packet with peer list as sent on the wire
+        fn ping_bytes(&self) -> Vec<u8> {
+            let mut ping = Ping::new(Cow::Owned(Hash::zero()), 10, 10, None, CumulativeDifficulty::from_u64(10), IndexSet::new());
+            if self.share_topology {
+                ping.set_topology(Some(PingTopology::new(self.peers.len() as u16, self.tag.clone())));
+            }
+            ping.to_bytes()
+        }
+
+        fn receive_ping(&mut self, from: &str, bytes: &[u8], now: TimestampSeconds) {
+            let ping = Ping::from_bytes(bytes).unwrap();
+            if let Some(topology) = ping.get_topology() {
+                self.received.insert(from.parse().unwrap(), TopologyEntry::new(topology.clone(), now));
+            }
+        }
+    }
+
+    #[test]
+    fn test_topology_serialization() {
+        let topology = PingTopology::new(8, Some("node".to_owned()));
+        let bytes = topology.to_bytes();
+        assert_eq!(bytes.len(), topology.size());
+        assert_eq!(PingTopology::from_bytes(&bytes).unwrap(), topology);
+
+        let topology = PingTopology::new(0, None);
+        assert_eq!(topology.to_bytes().len(), topology.size());
+
+        // Node tag is limited like in the handshake
+        let topology = PingTopology::new(8, Some("a".repeat(Handshake::MAX_LEN + 1)));
+        assert!(PingTopology::from_bytes(&topology.to_bytes()).is_err());
+
+        // And sanitized
+        let topology = PingTopology::new(8, Some("\x1b[31mnode".to_owned()));
+        assert_eq!(*PingTopology::from_bytes(&topology.to_bytes()).unwrap().get_node_tag(), Some("[31mnode".to_owned()));
+    }
+
+    #[test]
+    fn test_ping_without_topology() {
+        let ping = Ping::new(Cow::Owned(Hash::zero()), 10, 10, None, CumulativeDifficulty::from_u64(10), IndexSet::new());
+        let bytes = ping.to_bytes();
+        assert_eq!(bytes.len(), ping.size());
+        assert!(Ping::from_bytes(&bytes).unwrap().get_topology().is_none());
+    }
+
+    #[test]
+    fn test_three_nodes_topology() {
+        // A is connected to B and C, B is also connected to D and E
+        // C doesn't share its topology
+        let mut a = TestNode::new("a", true, &["1.1.1.2:2125", "1.1.1.3:2125"]);
+        let b = TestNode::new("b", true, &["1.1.1.1:2125", "1.1.1.4:2125", "1.1.1.5:2125"]);
+        let c = TestNode::new("c", false, &["1.1.1.1:2125"]);
+
+        a.receive_ping("1.1.1.2:2125", &b.ping_bytes(), 1000);
+        a.receive_ping("1.1.1.3:2125", &c.ping_bytes(), 1000);
+
+        assert_eq!(a.received.len(), 1);
+        let entry = &a.received[&"1.1.1.2:2125".parse().unwrap()];
+        assert_eq!(entry.get_topology().get_peers_count(), 3);
+        assert_eq!(*entry.get_topology().get_node_tag(), Some("b".to_owned()));
+        assert_eq!(entry.get_updated_at(), 1000);
+
+        // Data is marked stale after 5 minutes without update
+        assert!(!entry.is_stale(1000 + P2P_TOPOLOGY_STALE_DELAY));
+        assert!(entry.is_stale(1000 + P2P_TOPOLOGY_STALE_DELAY + 1));
+
+        // A new ping refresh it
+        a.receive_ping("1.1.1.2:2125", &b.ping_bytes(), 2000);
+        assert!(!a.received[&"1.1.1.2:2125".parse().unwrap()].is_stale(2000 + P2P_TOPOLOGY_STALE_DELAY));
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     borrow::Cow,
+    mem,
     time::{Duration, Instant},
     sync::{
         Arc,
@@ -14,6 +15,7 @@ use tokio::{
         RwLock,
         oneshot,
         Mutex,
+        Notify,
         broadcast
     },
     select,
@@ -23,6 +25,7 @@ use xelis_common::{
     crypto::Hash,
     queue::Queue,
     serializer::Serializer,
+    transaction::Transaction,
     utils::spawn_task
 };
 use crate::{
@@ -41,7 +44,8 @@ use super::{
         Packet
     },
     error::P2pError,
-    peer::Peer
+    peer::Peer,
+    verification_queue::VerificationQueue
 };
 use log::{
     error,
@@ -72,12 +76,35 @@ impl Listener {
             }
         }
     }
+
+    pub fn subscribe(&mut self) -> ResponseBlocker {
+        if let Some(sender) = &self.sender {
+            sender.subscribe()
+        } else {
+            let (sender, receiver) = broadcast::channel(1);
+            self.sender = Some(sender);
+            receiver
+        }
+    }
+}
+
+// TX received from a peer and waiting for its verification
+struct PendingTx {
+    tx: Transaction,
+    // Peer from which we received it
+    peer: Arc<Peer>,
+    // If it has to be broadcast once added or not
+    broadcast: bool,
+    // Notified once the TX got verified
+    listener: Listener
 }
 
 // Element of the queue for this Object Tracker
 struct Request {
     // The object requested
     request: ObjectRequest,
+    // Hash of the object requested
+    hash: Hash,
     // The peer from which it has to be requested
     peer: Arc<Peer>,
     // Channel sender to be notified of success/timeout
@@ -93,9 +120,10 @@ struct Request {
 }
 
 impl Request {
-    pub fn new(request: ObjectRequest, peer: Arc<Peer>, group_id: Option<u64>, broadcast: bool) -> Self {
+    pub fn new(request: ObjectRequest, hash: Hash, peer: Arc<Peer>, group_id: Option<u64>, broadcast: bool) -> Self {
         Self {
             request,
+            hash,
             peer,
             sender: None,
             response: None,
@@ -138,7 +166,7 @@ impl Request {
     }
 
     pub fn get_hash(&self) -> &Hash {
-        self.request.get_hash()
+        &self.hash
     }
 
     pub fn get_response_blocker(&mut self) -> ResponseBlocker {
@@ -251,7 +279,15 @@ pub struct ObjectTracker {
     group: GroupManager,
     // Requests that should be ignored
     // They got canceled but already requested
-    cache: ExpirableCache
+    cache: ExpirableCache,
+    // TXs received waiting for their verification
+    // This is done by a pool of workers to not block the responses handling
+    verification_queue: Mutex<VerificationQueue<PendingTx>>,
+    // Listeners of the TXs currently verified by a worker
+    // Always locked after the verification queue
+    verifying: Mutex<HashMap<Hash, Listener>>,
+    // Wake up a worker when a TX is queued
+    verification_notify: Notify
 }
 
 // How many requests can be queued in the channel
@@ -259,6 +295,10 @@ const REQUESTER_CHANNEL_BUFFER: usize = 128;
 // How many responses can be queued in the channel
 // It is set to 1 by default to not be spammed by the peer
 const HANDLER_CHANNEL_BUFFER: usize = 16;
+// How many TXs can wait for their verification
+const VERIFICATION_QUEUE_SIZE: usize = 4096;
+// How many TXs can be verified in parallel
+const VERIFICATION_WORKERS: usize = 4;
 
 // Duration constant for timeout instead of building it at each iteration
 const TIME_OUT: Duration = Duration::from_millis(PEER_TIMEOUT_REQUEST_OBJECT);
@@ -273,7 +313,10 @@ impl ObjectTracker {
             handler_sender,
             queue: RwLock::new(Queue::new()),
             group: GroupManager::new(),
-            cache: ExpirableCache::new()
+            cache: ExpirableCache::new(),
+            verification_queue: Mutex::new(VerificationQueue::new(VERIFICATION_QUEUE_SIZE)),
+            verifying: Mutex::new(HashMap::new()),
+            verification_notify: Notify::new()
         });
         
         // start the requester task loop which send requests to peers
@@ -285,6 +328,16 @@ impl ObjectTracker {
             });
         }
 
+        // start the workers verifying the TXs received
+        for i in 0..VERIFICATION_WORKERS {
+            let server_exit = server_exit.resubscribe();
+            let zelf = zelf.clone();
+            let blockchain = blockchain.clone();
+            spawn_task(format!("p2p-tracker-verifier-{}", i), async move {
+                zelf.verifier_loop(blockchain, server_exit).await;
+            });
+        }
+
         // start the handler task loop which handle the responses based on request queue order
         {
             let server_exit = server_exit.resubscribe();
@@ -335,7 +388,7 @@ impl ObjectTracker {
             OwnedObjectResponse::Transaction(tx, hash) => {
                 blockchain.add_tx_to_mempool_with_hash(tx, hash, broadcast).await?;
             },
-            OwnedObjectResponse::Block(block, _) => {
+            OwnedObjectResponse::Block(block, _, _) => {
                 // We don't broadcast it to others peers but we broadcast it to our miners in case
                 blockchain.add_new_block(block, broadcast, false).await?;
             }
@@ -359,10 +412,9 @@ impl ObjectTracker {
                 },
                 response = handler_receiver.recv() => {
                     if let Some(response) = response {
-                        trace!("Received object response: {}", response.get_hash());
-                        let object = response.get_hash();
+                        trace!("Received object response: {}", response.get_request());
                         let mut queue = self.queue.write().await;
-                        if let Some(request) = queue.get_mut(object) {
+                        if let Some(request) = response.get_hash().and_then(|hash| queue.get_mut(hash)) {
                             request.set_response(response);
                         }
                     } else {
@@ -382,6 +434,10 @@ impl ObjectTracker {
             let mut queue = self.queue.write().await;
             while let Some((_, request)) = queue.peek_mut() {
                 match request.take_response() {
+                    Some(OwnedObjectResponse::Transaction(tx, hash)) => {
+                        let (_, mut request) = queue.pop().unwrap();
+                        self.queue_tx_verification(tx, hash, &mut request).await;
+                    },
                     Some(response) => {
                         let (_, request) = queue.pop().unwrap();
                         if let Err(e) = self.handle_object_response_internal(&blockchain, response, request.broadcast(), request.get_peer()).await {
@@ -411,6 +467,77 @@ impl ObjectTracker {
         }
     }
 
+    // Queue the TX received for its verification by a worker
+    // Its response blockers are notified once verified
+    async fn queue_tx_verification(&self, tx: Transaction, hash: Hash, request: &mut Request) {
+        let source = tx.get_source().clone();
+        let (nonce, fee, size) = (tx.get_nonce(), tx.get_fee(), tx.size());
+        let pending = PendingTx {
+            tx,
+            peer: request.get_peer().clone(),
+            broadcast: request.broadcast(),
+            listener: request.to_listener()
+        };
+
+        let mut verification_queue = self.verification_queue.lock().await;
+        if verification_queue.push(hash.clone(), source, nonce, fee, size, pending) {
+            self.verification_notify.notify_one();
+        } else {
+            debug!("TX {} from {} not queued for verification ({} waiting)", hash, request.get_peer(), verification_queue.len());
+        }
+    }
+
+    // Task loop verifying the TXs queued by highest fee per byte
+    async fn verifier_loop<S: Storage>(&self, blockchain: Arc<Blockchain<S>>, mut server_exit: broadcast::Receiver<()>) {
+        debug!("Starting verifier loop...");
+        loop {
+            let next = {
+                let mut verification_queue = self.verification_queue.lock().await;
+                let mut next = verification_queue.pop();
+                // Wake up another worker if there is still some work
+                if next.is_some() && !verification_queue.is_empty() {
+                    self.verification_notify.notify_one();
+                }
+
+                // Its listener stays reachable while the TX is verified
+                if let Some((hash, _, pending)) = next.as_mut() {
+                    let listener = mem::replace(&mut pending.listener, Listener::new(None));
+                    self.verifying.lock().await.insert(hash.clone(), listener);
+                }
+                next
+            };
+
+            let Some((hash, source, pending)) = next else {
+                select! {
+                    biased;
+                    _ = server_exit.recv() => {
+                        break;
+                    },
+                    _ = self.verification_notify.notified() => {}
+                }
+                continue;
+            };
+
+            let PendingTx { tx, peer, broadcast, .. } = pending;
+            if let Err(e) = blockchain.add_tx_to_mempool_with_hash(tx, hash.clone(), broadcast).await {
+                warn!("Error while verifying TX {} in ObjectTracker from {}: {}", hash, peer, e);
+                let mut queue = self.queue.write().await;
+                self.clean_queue(&mut queue, peer.get_id(), None).await;
+            }
+
+            // Added to the mempool or rejected, it can be requested again
+            let listener = {
+                let mut verification_queue = self.verification_queue.lock().await;
+                verification_queue.release(&source);
+                self.verifying.lock().await.remove(&hash)
+            };
+
+            if let Some(listener) = listener {
+                listener.notify();
+            }
+        }
+    }
+
     // Task loop to request all objects in order
     async fn requester_loop(&self, mut request_receiver: Receiver<Hash>, mut server_exit: broadcast::Receiver<()>) {
         debug!("Starting requester loop...");
@@ -439,10 +566,21 @@ impl ObjectTracker {
     }
 
     // Get the response blocker for the requested object
+    // It may also be waiting for its verification
     pub async fn get_response_blocker_for_requested_object(&self, object_hash: &Hash) -> Option<ResponseBlocker> {
         let mut queue = self.queue.write().await;
-        let request = queue.get_mut(object_hash)?;
-        Some(request.get_response_blocker())
+        if let Some(request) = queue.get_mut(object_hash) {
+            return Some(request.get_response_blocker())
+        }
+
+        let mut verification_queue = self.verification_queue.lock().await;
+        if let Some(pending) = verification_queue.get_mut(object_hash) {
+            return Some(pending.listener.subscribe())
+        }
+
+        let mut verifying = self.verifying.lock().await;
+        let listener = verifying.get_mut(object_hash)?;
+        Some(listener.subscribe())
     }
 
     // This function is called from P2p Server when a peer sends an object response that we requested
@@ -450,10 +588,11 @@ impl ObjectTracker {
     pub async fn handle_object_response(&self, response: OwnedObjectResponse) -> Result<(), P2pError> {
         {
             let queue = self.queue.read().await;
-            if let Some(request) = queue.get(response.get_hash()) {
-                if request.get_hash() != response.get_hash() {
-                    debug!("Invalid object hash in ObjectTracker: expected {}, got {}", request.get_hash(), response.get_hash());
-                    return Err(P2pError::InvalidObjectHash(request.get_hash().clone(), response.get_hash().clone()));
+            let tracked = response.get_hash().and_then(|hash| queue.get(hash).map(|request| (request, hash)));
+            if let Some((request, hash)) = tracked {
+                if request.get_hash() != hash {
+                    debug!("Invalid object hash in ObjectTracker: expected {}, got {}", request.get_hash(), hash);
+                    return Err(P2pError::InvalidObjectHash(request.get_hash().clone(), hash.clone()));
                 }
             } else {
                 let request = response.get_request();
@@ -477,11 +616,15 @@ impl ObjectTracker {
 
     // Request the object from the peer and returns the response blocker
     pub async fn request_object_from_peer_with(&self, peer: Arc<Peer>, request: ObjectRequest, group_id: Option<u64>, blocker: bool, broadcast: bool) -> Result<Option<ResponseBlocker>, P2pError> {
-        trace!("Requesting object {} from {}", request.get_hash(), peer);
+        trace!("Requesting object {} from {}", request, peer);
+        // Only objects requested by hash can be tracked
+        let Some(hash) = request.get_hash().cloned() else {
+            return Err(P2pError::UntrackableObjectRequest(request))
+        };
+
         let (listener, hash) = {
             let mut queue = self.queue.write().await;
-            let hash = request.get_hash().clone();
-            let mut req = Request::new(request, peer, group_id, broadcast);
+            let mut req = Request::new(request, hash.clone(), peer, group_id, broadcast);
 
             let listener = if blocker {
                 Some(req.get_response_blocker())
@@ -489,6 +632,11 @@ impl ObjectTracker {
                 None
             };
 
+            if self.verification_queue.lock().await.contains(&hash) {
+                debug!("Object already waiting for its verification in ObjectTracker: {}", hash);
+                return Ok(None)
+            }
+
             if !queue.push(hash.clone(), req) {
                 debug!("Object already requested in ObjectTracker: {}", hash);
                 return Ok(None)
@@ -0,0 +1,217 @@
+use std::collections::{HashMap, VecDeque};
+use xelis_common::crypto::{Hash, PublicKey};
+use crate::core::mempool::compare_fee_rate;
+
+struct QueuedTx<T> {
+    hash: Hash,
+    nonce: u64,
+    fee: u64,
+    size: usize,
+    data: T
+}
+
+// Bounded queue of TXs waiting for their verification
+// TXs are grouped by sender and ordered by nonce
+// The next TX is the one with the highest fee per byte among the first TX of each sender
+pub struct VerificationQueue<T> {
+    // TXs waiting, grouped by sender
+    groups: HashMap<PublicKey, VecDeque<QueuedTx<T>>>,
+    // Sender of each TX waiting
+    sources: HashMap<Hash, PublicKey>,
+    // Senders having a TX currently verified, with its hash
+    // Their next TX waits until it is done to keep the nonce order
+    in_flight: HashMap<PublicKey, Hash>,
+    capacity: usize
+}
+
+impl<T> VerificationQueue<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            groups: HashMap::new(),
+            sources: HashMap::new(),
+            in_flight: HashMap::new(),
+            capacity
+        }
+    }
+
+    // Count of TXs waiting
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    // A TX is still contained while it is verified, until its sender is released
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.sources.contains_key(hash) || self.in_flight.values().any(|in_flight| in_flight == hash)
+    }
+
+    // Get the data of a TX waiting
+    pub fn get_mut(&mut self, hash: &Hash) -> Option<&mut T> {
+        let source = self.sources.get(hash)?;
+        self.groups.get_mut(source)?
+            .iter_mut()
+            .find(|entry| entry.hash == *hash)
+            .map(|entry| &mut entry.data)
+    }
+
+    // Add a TX to the queue
+    // Returns false if the queue is full or the TX is already waiting
+    pub fn push(&mut self, hash: Hash, source: PublicKey, nonce: u64, fee: u64, size: usize, data: T) -> bool {
+        if self.len() >= self.capacity || self.contains(&hash) {
+            return false
+        }
+
+        let group = self.groups.entry(source.clone()).or_default();
+        let index = group.partition_point(|entry| entry.nonce <= nonce);
+        group.insert(index, QueuedTx { hash: hash.clone(), nonce, fee, size, data });
+        self.sources.insert(hash, source);
+        true
+    }
+
+    // Take the next TX to verify and mark its sender as in flight
+    // `release` must be called with the returned sender once verified
+    pub fn pop(&mut self) -> Option<(Hash, PublicKey, T)> {
+        let source = self.groups.iter()
+            .filter(|(source, _)| !self.in_flight.contains_key(*source))
+            .filter_map(|(source, group)| group.front().map(|entry| (source, entry)))
+            .max_by(|(_, a), (_, b)| compare_fee_rate(a.fee, a.size, b.fee, b.size))
+            .map(|(source, _)| source.clone())?;
+
+        let group = self.groups.get_mut(&source)?;
+        let entry = group.pop_front()?;
+        if group.is_empty() {
+            self.groups.remove(&source);
+        }
+
+        self.sources.remove(&entry.hash);
+        self.in_flight.insert(source.clone(), entry.hash.clone());
+        Some((entry.hash, source, entry.data))
+    }
+
+    // Allow the next TXs of this sender to be verified
+    // This must be done once the TX verified is added or rejected
+    pub fn release(&mut self, source: &PublicKey) {
+        self.in_flight.remove(source);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use crate::{
+        config::{PEER_TX_RATE_LIMIT_BURST, PEER_TX_RATE_LIMIT_PER_SECOND},
+        p2p::rate_limiter::TokenBucket
+    };
+    use super::*;
+
+    fn source() -> PublicKey {
+        KeyPair::new().get_public_key().compress()
+    }
+
+    fn hash(i: u64) -> Hash {
+        let mut bytes = [0u8; 32];
+        bytes[..8].copy_from_slice(&i.to_be_bytes());
+        Hash::new(bytes)
+    }
+
+    #[test]
+    fn test_pop_by_fee_per_byte() {
+        let mut queue = VerificationQueue::new(16);
+        assert!(queue.push(hash(0), source(), 0, 1000, 1000, 0));
+        assert!(queue.push(hash(1), source(), 0, 5000, 1000, 1));
+        assert!(queue.push(hash(2), source(), 0, 5000, 2000, 2));
+
+        let order: Vec<_> = std::iter::from_fn(|| {
+            let (_, source, data) = queue.pop()?;
+            queue.release(&source);
+            Some(data)
+        }).collect();
+        assert_eq!(order, vec![1, 2, 0]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_same_sender_in_nonce_order() {
+        let mut queue = VerificationQueue::new(16);
+        let sender = source();
+        assert!(queue.push(hash(0), sender.clone(), 1, 9000, 1000, 1));
+        assert!(queue.push(hash(1), sender.clone(), 0, 1000, 1000, 0));
+        assert!(queue.push(hash(2), source(), 0, 5000, 1000, 2));
+
+        // Lowest nonce first even if the next one pays more
+        let (_, first, data) = queue.pop().unwrap();
+        assert_eq!(data, 2);
+        queue.release(&first);
+
+        let (_, sender_in_flight, data) = queue.pop().unwrap();
+        assert_eq!(data, 0);
+        // Sender is in flight, its next TX has to wait
+        assert!(queue.pop().is_none());
+
+        queue.release(&sender_in_flight);
+        assert_eq!(queue.pop().unwrap().2, 1);
+    }
+
+    #[test]
+    fn test_contains_while_verified() {
+        let mut queue = VerificationQueue::new(16);
+        let sender = source();
+        assert!(queue.push(hash(0), sender.clone(), 0, 1000, 1000, ()));
+
+        // Not waiting anymore, but still known until it is added or rejected
+        let (popped, in_flight, _) = queue.pop().unwrap();
+        assert_eq!(popped, hash(0));
+        assert!(queue.is_empty());
+        assert!(queue.contains(&hash(0)));
+        assert!(!queue.push(hash(0), sender, 0, 1000, 1000, ()));
+
+        queue.release(&in_flight);
+        assert!(!queue.contains(&hash(0)));
+    }
+
+    #[test]
+    fn test_bounded_and_no_duplicate() {
+        let mut queue = VerificationQueue::new(2);
+        assert!(queue.push(hash(0), source(), 0, 1000, 1000, ()));
+        assert!(!queue.push(hash(0), source(), 0, 1000, 1000, ()));
+        assert!(queue.push(hash(1), source(), 0, 1000, 1000, ()));
+        assert!(!queue.push(hash(2), source(), 0, 1000, 1000, ()));
+        assert_eq!(queue.len(), 2);
+        assert!(queue.get_mut(&hash(1)).is_some());
+    }
+
+    #[test]
+    fn test_flood_does_not_delay_other_peers() {
+        let mut queue = VerificationQueue::new(4096);
+        // Each peer has its own bucket
+        let mut flooder = TokenBucket::new(PEER_TX_RATE_LIMIT_PER_SECOND, PEER_TX_RATE_LIMIT_BURST);
+        let mut honest = TokenBucket::new(PEER_TX_RATE_LIMIT_PER_SECOND, PEER_TX_RATE_LIMIT_BURST);
+
+        // Flood of 10k TXs from a single peer paying the lowest fee
+        let spammer = source();
+        let mut accepted = 0;
+        for nonce in 0..10_000 {
+            if flooder.try_take() {
+                assert!(queue.push(hash(nonce), spammer.clone(), nonce, 1000, 1000, false));
+                accepted += 1;
+            }
+        }
+        assert!(accepted < 10_000);
+        assert!(accepted <= PEER_TX_RATE_LIMIT_BURST + PEER_TX_RATE_LIMIT_PER_SECOND);
+
+        // The other peer is not limited by the flood
+        assert!(honest.try_take());
+        let honest_hash = hash(u64::MAX);
+        assert!(queue.push(honest_hash.clone(), source(), 0, 2000, 1000, true));
+
+        // It is verified first even with the queue full of spam
+        let (hash, source, is_honest) = queue.pop().unwrap();
+        assert!(is_honest);
+        assert_eq!(hash, honest_hash);
+        queue.release(&source);
+        assert_eq!(queue.len(), accepted as usize);
+    }
+}
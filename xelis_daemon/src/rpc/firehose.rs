@@ -0,0 +1,454 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::Arc
+};
+use actix::{
+    Actor,
+    AsyncContext,
+    Handler,
+    Message as TMessage,
+    StreamHandler,
+    Addr
+};
+use actix_web_actors::ws::{
+    ProtocolError,
+    Message,
+    WebsocketContext
+};
+use log::{debug, trace, warn};
+use tokio::sync::Mutex;
+use xelis_common::{
+    api::{
+        daemon::{
+            FirehoseAck,
+            FirehoseBatch,
+            FirehoseBlock
+        },
+        RPCTransaction
+    },
+    rpc_server::{
+        InternalRpcError,
+        RpcResponseError
+    }
+};
+use crate::{
+    config::FIREHOSE_BATCH_SIZE,
+    core::{
+        blockchain::Blockchain,
+        error::BlockchainError,
+        storage::Storage
+    }
+};
+
+pub type SharedFirehoseServer<S> = Arc<FirehoseServer<S>>;
+
+// Serialized batch to send to a firehose client
+pub struct FirehoseMessage(String);
+
+impl TMessage for FirehoseMessage {
+    type Result = ();
+}
+
+// Position of a firehose client in the chain
+// The replay and the live blocks are sent the same way, so there is no gap between both
+#[derive(Debug)]
+pub struct FirehoseCursor {
+    // Next topoheight to send
+    next_topoheight: u64,
+    // Last topoheight of the batch waiting for an ack
+    pending_ack: Option<u64>,
+    // Blocks already sent were re-ordered
+    rewind: bool
+}
+
+impl FirehoseCursor {
+    pub fn new(from_topoheight: u64) -> Self {
+        Self {
+            next_topoheight: from_topoheight,
+            pending_ack: None,
+            rewind: false
+        }
+    }
+
+    // Range of the next batch to send with its rewind flag
+    // Nothing is returned while the previous batch is not acked or if there is no new block
+    pub fn next_batch(&mut self, top_topoheight: u64, max_blocks: u64) -> Option<(u64, u64, bool)> {
+        if self.pending_ack.is_some() || self.next_topoheight > top_topoheight {
+            return None
+        }
+
+        let start = self.next_topoheight;
+        let end = top_topoheight.min(start + max_blocks.max(1) - 1);
+        self.next_topoheight = end + 1;
+        self.pending_ack = Some(end);
+
+        let rewind = self.rewind;
+        self.rewind = false;
+        Some((start, end, rewind))
+    }
+
+    // Ack the batch sent, only its last topoheight is accepted
+    pub fn ack(&mut self, topoheight: u64) -> bool {
+        if self.pending_ack != Some(topoheight) {
+            return false
+        }
+
+        self.pending_ack = None;
+        true
+    }
+
+    // Blocks from this topoheight were re-ordered, send them again
+    pub fn rewind(&mut self, topoheight: u64) {
+        if topoheight < self.next_topoheight {
+            self.next_topoheight = topoheight;
+            self.rewind = true;
+        }
+    }
+}
+
+// Build the batch of blocks between both topoheights (inclusive) with their executed transactions
+pub async fn build_batch<S: Storage>(storage: &S, start_topoheight: u64, end_topoheight: u64, rewind: bool) -> Result<String, BlockchainError> {
+    let mainnet = storage.is_mainnet();
+    let range = storage.get_blocks_range(start_topoheight, end_topoheight).await?;
+
+    let mut executed = Vec::with_capacity(range.len());
+    for (_, hash, header) in range.iter() {
+        let mut txs = Vec::new();
+        for tx_hash in header.get_txs_hashes() {
+            if storage.is_tx_executed_in_block(tx_hash, hash)? {
+                txs.push((tx_hash, storage.get_transaction(tx_hash).await?));
+            }
+        }
+        executed.push(txs);
+    }
+
+    let blocks = range.iter().zip(executed.iter()).map(|((topoheight, hash, header), txs)| FirehoseBlock {
+        topoheight: *topoheight,
+        hash: Cow::Borrowed(hash),
+        header: Cow::Borrowed(header.as_ref()),
+        executed_transactions: txs.iter().map(|(tx_hash, tx)| RPCTransaction::from_tx(tx, tx_hash, mainnet)).collect()
+    }).collect();
+
+    serde_json::to_string(&FirehoseBatch { rewind, blocks }).map_err(|e| BlockchainError::Any(e.into()))
+}
+
+pub struct FirehoseWebSocketHandler<S: Storage> {
+    server: SharedFirehoseServer<S>
+}
+
+impl<S: Storage> FirehoseWebSocketHandler<S> {
+    pub fn new(server: SharedFirehoseServer<S>) -> Self {
+        Self {
+            server
+        }
+    }
+}
+
+impl<S: Storage> Actor for FirehoseWebSocketHandler<S> {
+    type Context = WebsocketContext<Self>;
+}
+
+impl<S: Storage> StreamHandler<Result<Message, ProtocolError>> for FirehoseWebSocketHandler<S> {
+    fn handle(&mut self, msg: Result<Message, ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(Message::Text(text)) => {
+                trace!("New message incoming from firehose client: {}", text);
+                let address = ctx.address();
+                let ack: FirehoseAck = match serde_json::from_slice(text.as_bytes()) {
+                    Ok(ack) => ack,
+                    Err(e) => {
+                        debug!("Error while decoding firehose ack from {:?}: {}", address, e);
+                        return;
+                    }
+                };
+
+                let server = self.server.clone();
+                ctx.wait(actix::fut::wrap_future(async move {
+                    server.handle_ack(&address, ack.topoheight).await;
+                }));
+            },
+            Ok(Message::Close(reason)) => {
+                ctx.close(reason);
+            },
+            msg => {
+                debug!("Abnormal message received: {:?}. Closing connection", msg);
+                let error = RpcResponseError::new(None, InternalRpcError::InvalidJSONRequest);
+                ctx.text(error.to_json().to_string());
+                ctx.close(None);
+            }
+        }
+    }
+
+    fn finished(&mut self, ctx: &mut Self::Context) {
+        debug!("firehose client has disconnected");
+        let server = self.server.clone();
+        let address = ctx.address();
+        let fut = async move {
+            server.delete_client(&address).await;
+        };
+        ctx.wait(actix::fut::wrap_future(fut));
+    }
+}
+
+impl<S: Storage> Handler<FirehoseMessage> for FirehoseWebSocketHandler<S> {
+    type Result = ();
+
+    fn handle(&mut self, msg: FirehoseMessage, ctx: &mut Self::Context) -> Self::Result {
+        ctx.text(msg.0);
+    }
+}
+
+// Stream the ordered blocks to indexers
+// Each client replays from the topoheight requested, then receives the new blocks
+pub struct FirehoseServer<S: Storage> {
+    clients: Mutex<HashMap<Addr<FirehoseWebSocketHandler<S>>, FirehoseCursor>>,
+    blockchain: Arc<Blockchain<S>>
+}
+
+impl<S: Storage> FirehoseServer<S> {
+    pub fn new(blockchain: Arc<Blockchain<S>>) -> Self {
+        Self {
+            clients: Mutex::new(HashMap::new()),
+            blockchain
+        }
+    }
+
+    // Returns the number of clients connected to the firehose
+    pub async fn count_clients(&self) -> usize {
+        trace!("count firehose clients");
+        self.clients.lock().await.len()
+    }
+
+    pub async fn add_client(&self, addr: Addr<FirehoseWebSocketHandler<S>>, from_topoheight: u64) {
+        debug!("Adding new firehose client replaying from topoheight {}", from_topoheight);
+        let storage = self.blockchain.get_storage().read().await;
+        let mut clients = self.clients.lock().await;
+        let cursor = clients.entry(addr.clone()).or_insert_with(|| FirehoseCursor::new(from_topoheight));
+        Self::send_next_batch(&*storage, &addr, cursor).await;
+    }
+
+    pub async fn delete_client(&self, addr: &Addr<FirehoseWebSocketHandler<S>>) {
+        trace!("delete firehose client");
+        let mut clients = self.clients.lock().await;
+        clients.remove(addr);
+    }
+
+    // The client processed its batch, send the next one if any
+    pub async fn handle_ack(&self, addr: &Addr<FirehoseWebSocketHandler<S>>, topoheight: u64) {
+        let storage = self.blockchain.get_storage().read().await;
+        let mut clients = self.clients.lock().await;
+        if let Some(cursor) = clients.get_mut(addr) {
+            if !cursor.ack(topoheight) {
+                debug!("Invalid firehose ack for topoheight {} with {:?}", topoheight, cursor);
+                return;
+            }
+            Self::send_next_batch(&*storage, addr, cursor).await;
+        }
+    }
+
+    // New blocks were ordered in the DAG
+    // All blocks starting `reordered_from` have been written again and must be resent
+    pub async fn notify_new_topoheight(&self, reordered_from: Option<u64>) {
+        let storage = self.blockchain.get_storage().read().await;
+        let mut clients = self.clients.lock().await;
+        for (addr, cursor) in clients.iter_mut() {
+            if let Some(topoheight) = reordered_from {
+                cursor.rewind(topoheight);
+            }
+            Self::send_next_batch(&*storage, addr, cursor).await;
+        }
+    }
+
+    async fn send_next_batch(storage: &S, addr: &Addr<FirehoseWebSocketHandler<S>>, cursor: &mut FirehoseCursor) {
+        let top_topoheight = match storage.get_top_topoheight() {
+            Ok(topoheight) => topoheight,
+            Err(e) => {
+                warn!("Error while retrieving top topoheight for firehose: {}", e);
+                return;
+            }
+        };
+
+        if let Some((start, end, rewind)) = cursor.next_batch(top_topoheight, FIREHOSE_BATCH_SIZE) {
+            trace!("Sending firehose batch {} - {} (rewind: {})", start, end, rewind);
+            match build_batch(storage, start, end, rewind).await {
+                Ok(batch) => addr.do_send(FirehoseMessage(batch)),
+                Err(e) => warn!("Error while building firehose batch {} - {}: {}", start, end, e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        crypto::{Hash, Hashable},
+        testing::TempDir
+    };
+    use crate::core::storage::{
+        test_utils::{open_temp_storage, TestBlock},
+        BlockProvider,
+        DagOrderProvider,
+        SledStorage
+    };
+    use super::*;
+
+    struct TestChain {
+        // Declared first to be closed before its directory is deleted
+        storage: SledStorage,
+        _dir: TempDir
+    }
+
+    impl TestChain {
+        fn new(name: &str) -> Self {
+            let (dir, storage) = open_temp_storage(&format!("firehose-{}", name));
+            Self { storage, _dir: dir }
+        }
+
+        // Store a new block and order it at the topoheight
+        // The salt allows to get another hash for the same topoheight
+        async fn set_block_at(&mut self, topoheight: u64, salt: u8) -> Hash {
+            TestBlock::new(topoheight).salt(salt).save_at(&mut self.storage, topoheight).await
+        }
+    }
+
+
+    // Indexer consuming the firehose, checking that no topoheight is skipped
+    #[derive(Default)]
+    struct Consumer {
+        blocks: Vec<(u64, Hash)>
+    }
+
+    impl Consumer {
+        // Apply the batch and returns the topoheight to ack
+        fn apply(&mut self, batch: &str) -> u64 {
+            let batch: FirehoseBatch = serde_json::from_str(batch).unwrap();
+            let first = batch.blocks.first().unwrap().topoheight;
+            if batch.rewind {
+                self.blocks.retain(|(topoheight, _)| *topoheight < first);
+            }
+
+            for block in batch.blocks {
+                let expected = self.blocks.last().map_or(0, |(topoheight, _)| topoheight + 1);
+                assert_eq!(block.topoheight, expected);
+                assert_eq!(block.header.hash(), *block.hash);
+                self.blocks.push((block.topoheight, block.hash.into_owned()));
+            }
+
+            self.blocks.last().unwrap().0
+        }
+
+        // Receive and ack all the batches available
+        async fn pump(&mut self, storage: &SledStorage, cursor: &mut FirehoseCursor) {
+            let top_topoheight = storage.get_top_topoheight().unwrap();
+            while let Some((start, end, rewind)) = cursor.next_batch(top_topoheight, FIREHOSE_BATCH_SIZE) {
+                let batch = build_batch(storage, start, end, rewind).await.unwrap();
+                // Nothing else is sent until the batch is acked
+                assert!(cursor.next_batch(top_topoheight, FIREHOSE_BATCH_SIZE).is_none());
+
+                let topoheight = self.apply(&batch);
+                assert_eq!(topoheight, end);
+                assert!(cursor.ack(topoheight));
+            }
+        }
+
+        async fn assert_synced(&self, storage: &SledStorage) {
+            let top_topoheight = storage.get_top_topoheight().unwrap();
+            assert_eq!(self.blocks.len() as u64, top_topoheight + 1);
+            for (topoheight, hash) in self.blocks.iter() {
+                assert_eq!(storage.get_hash_at_topo_height(*topoheight).await.unwrap(), *hash);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_blocks_range() {
+        let mut chain = TestChain::new("range");
+        let mut hashes = Vec::new();
+        for topoheight in 0..300 {
+            hashes.push(chain.set_block_at(topoheight, 0).await);
+        }
+
+        // Sorted by topoheight, even across the byte boundary of 256
+        let range = chain.storage.get_blocks_range(250, 260).await.unwrap();
+        assert_eq!(range.len(), 11);
+        for (i, (topoheight, hash, header)) in range.iter().enumerate() {
+            assert_eq!(*topoheight, 250 + i as u64);
+            assert_eq!(*hash, hashes[*topoheight as usize]);
+            assert_eq!(header.hash(), *hash);
+        }
+
+        // End above the top topoheight
+        assert_eq!(chain.storage.get_blocks_range(290, 1000).await.unwrap().len(), 10);
+        assert!(chain.storage.get_blocks_range(10, 9).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_replay_then_live_without_gap() {
+        let mut chain = TestChain::new("replay");
+        for topoheight in 0..150 {
+            chain.set_block_at(topoheight, 0).await;
+        }
+
+        // Replay the existing chain
+        let mut consumer = Consumer::default();
+        let mut cursor = FirehoseCursor::new(0);
+        consumer.pump(&chain.storage, &mut cursor).await;
+        consumer.assert_synced(&chain.storage).await;
+
+        // Live blocks, some of them are ordered while a batch is waiting for its ack
+        let mut topoheight = 150;
+        while topoheight < 200 {
+            chain.set_block_at(topoheight, 0).await;
+            let top_topoheight = chain.storage.get_top_topoheight().unwrap();
+            let (start, end, rewind) = cursor.next_batch(top_topoheight, FIREHOSE_BATCH_SIZE).unwrap();
+            assert_eq!((start, end, rewind), (topoheight, topoheight, false));
+            let batch = build_batch(&chain.storage, start, end, rewind).await.unwrap();
+
+            chain.set_block_at(topoheight + 1, 0).await;
+            consumer.apply(&batch);
+            assert!(cursor.ack(end));
+            consumer.pump(&chain.storage, &mut cursor).await;
+            topoheight += 2;
+        }
+
+        consumer.assert_synced(&chain.storage).await;
+        assert_eq!(consumer.blocks.len(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_rewind_on_reorg() {
+        let mut chain = TestChain::new("rewind");
+        for topoheight in 0..200 {
+            chain.set_block_at(topoheight, 0).await;
+        }
+
+        let mut consumer = Consumer::default();
+        let mut cursor = FirehoseCursor::new(0);
+        consumer.pump(&chain.storage, &mut cursor).await;
+
+        // The DAG re-ordered the last blocks
+        for topoheight in 195..200 {
+            chain.set_block_at(topoheight, 1).await;
+        }
+        cursor.rewind(195);
+        consumer.pump(&chain.storage, &mut cursor).await;
+        consumer.assert_synced(&chain.storage).await;
+    }
+
+    #[test]
+    fn test_cursor_ack() {
+        let mut cursor = FirehoseCursor::new(10);
+        assert!(cursor.next_batch(9, FIREHOSE_BATCH_SIZE).is_none());
+        assert_eq!(cursor.next_batch(12, FIREHOSE_BATCH_SIZE), Some((10, 12, false)));
+
+        // Only the last topoheight of the batch is accepted
+        assert!(!cursor.ack(11));
+        assert!(cursor.ack(12));
+        assert!(!cursor.ack(12));
+
+        // Rewind above what was sent is ignored
+        cursor.rewind(13);
+        assert_eq!(cursor.next_batch(13, FIREHOSE_BATCH_SIZE), Some((13, 13, false)));
+    }
+}
@@ -42,7 +42,8 @@ use xelis_common::{
     },
     block::{
         BlockHeader,
-        MinerWork
+        MinerWork,
+        MINER_WORK_EXTRA_NONCE_OFFSET
     },
     crypto::{
         Hash,
@@ -56,10 +57,7 @@ use xelis_common::{
         RpcResponseError
     },
     serializer::Serializer,
-    time::{
-        get_current_time_in_millis,
-        TimestampMillis
-    },
+    time::get_current_time_in_millis,
     utils::spawn_task
 };
 use crate::{
@@ -91,7 +89,7 @@ pub struct Miner {
     // Used to display correctly its address
     mainnet: bool,
     // timestamp of first connection
-    first_seen: TimestampMillis,
+    first_seen: u64,
     // public key of account (address)
     key: PublicKey,
     // worker name
@@ -101,7 +99,7 @@ pub struct Miner {
     // blocks rejected since he is connected
     blocks_rejected: usize,
     // timestamp of the last invalid block received
-    last_invalid_block: TimestampMillis
+    last_invalid_block: u64
 }
 
 impl Miner {
@@ -258,7 +256,7 @@ impl<S: Storage> GetWorkServer<S> {
                     error!("No mining job found! How is it possible ?");
                     InternalRpcError::InternalError("No mining job found")
                 })?;
-                job = MinerWork::new(header.get_work_hash(), get_current_time_in_millis());
+                job = MinerWork::new(header.get_work_hash(), self.blockchain.get_network_time_in_millis().await);
                 height = header.height;
                 difficulty = *diff;
             } else {
@@ -267,7 +265,7 @@ impl<S: Storage> GetWorkServer<S> {
                 let header = self.blockchain.get_block_template_for_storage(&storage, DEV_PUBLIC_KEY.clone()).await.context("Error while retrieving block template")?;
                 (difficulty, _) = self.blockchain.get_difficulty_at_tips(&*storage, header.get_tips().iter()).await.context("Error while retrieving difficulty at tips")?;
 
-                job = MinerWork::new(header.get_work_hash(), get_current_time_in_millis());
+                job = MinerWork::new(header.get_work_hash(), self.blockchain.get_network_time_in_millis().await);
                 height = header.height;
 
                 // save the mining job, and set it as last job
@@ -285,7 +283,7 @@ impl<S: Storage> GetWorkServer<S> {
 
         let topoheight = self.blockchain.get_topo_height();
         debug!("Sending job to new miner");
-        addr.send(Response::NewJob(GetMinerWorkResult { template: job.to_hex(), height, topoheight, difficulty })).await.context("error while sending block template")??;
+        addr.send(Response::NewJob(GetMinerWorkResult { template: job.to_hex(), height, topoheight, difficulty, extra_nonce_offset: MINER_WORK_EXTRA_NONCE_OFFSET })).await.context("error while sending block template")??;
         Ok(())
     }
 
@@ -423,7 +421,7 @@ impl<S: Storage> GetWorkServer<S> {
     // check if the last notify is older than the rate limit
     // if it's the case, we can notify miners
     // Returns a tuple with a boolean indicating if the rate limit is reached, and the current timestamp
-    fn is_rate_limited(&self) -> (bool, TimestampMillis) {
+    fn is_rate_limited(&self) -> (bool, u64) {
         let now = get_current_time_in_millis();
         let last_notify = self.last_notify.load(Ordering::SeqCst);
         (now - last_notify < self.notify_rate_limit_ms, now)
@@ -492,7 +490,7 @@ impl<S: Storage> GetWorkServer<S> {
             // New task for each miner in case a miner is slow
             // we don't want to wait for him
             spawn_task("getwork-notify-new-job", async move {
-                match addr.send(Response::NewJob(GetMinerWorkResult { template, height, topoheight, difficulty })).await {
+                match addr.send(Response::NewJob(GetMinerWorkResult { template, height, topoheight, difficulty, extra_nonce_offset: MINER_WORK_EXTRA_NONCE_OFFSET })).await {
                     Ok(request) => {
                         if let Err(e) = request {
                             warn!("Error while sending new job to addr {:?}: {}", addr, e);
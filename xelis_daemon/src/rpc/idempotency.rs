@@ -0,0 +1,229 @@
+use std::{
+    future::Future,
+    net::IpAddr,
+    num::NonZeroUsize,
+    sync::Arc,
+    time::{Duration, Instant}
+};
+use anyhow::anyhow;
+use lru::LruCache;
+use serde_json::Value;
+use tokio::sync::{watch, Mutex};
+use xelis_common::{crypto::Hash, rpc_server::InternalRpcError};
+use crate::core::error::resolve_rpc_error_code;
+
+// Error flattened to be replayed to every retry
+#[derive(Clone)]
+struct CachedError {
+    code: i16,
+    message: String,
+    data: Option<Value>
+}
+
+impl CachedError {
+    fn new(err: &InternalRpcError) -> Self {
+        // Resolve the code now as the replayed error is not a blockchain error anymore
+        let code = match err {
+            InternalRpcError::AnyError(e) => resolve_rpc_error_code(e).unwrap_or_else(|| err.get_code()),
+            _ => err.get_code()
+        };
+
+        Self {
+            code,
+            message: err.to_string(),
+            data: err.get_data()
+        }
+    }
+
+    fn to_error(&self) -> InternalRpcError {
+        match &self.data {
+            Some(data) => InternalRpcError::CustomAnyWithData(self.code, anyhow!(self.message.clone()), data.clone()),
+            None => InternalRpcError::Custom(self.code, self.message.clone())
+        }
+    }
+}
+
+struct CacheEntry {
+    created_at: Instant,
+    // Hash of the request payload, a key can't be reused for another one
+    payload: Hash,
+    // Set by the submission task once done, retries wait on it
+    response: watch::Receiver<Option<Result<Value, CachedError>>>
+}
+
+// Responses cached by client IP and idempotency key
+// Concurrent requests with the same key are only executed once
+// Each client has its own bounded cache so it can't evict the keys of the others
+pub struct IdempotencyCache {
+    clients: Mutex<LruCache<Option<IpAddr>, LruCache<String, Arc<CacheEntry>>>>,
+    keys_per_client: NonZeroUsize,
+    ttl: Duration
+}
+
+impl IdempotencyCache {
+    pub fn new(clients: usize, keys_per_client: usize, ttl: Duration) -> Self {
+        Self {
+            clients: Mutex::new(LruCache::new(NonZeroUsize::new(clients).expect("Non zero idempotency clients count"))),
+            keys_per_client: NonZeroUsize::new(keys_per_client).expect("Non zero idempotency cache size"),
+            ttl
+        }
+    }
+
+    // Execute the request if the key is unknown or expired, otherwise replay its response
+    // The request runs in its own task, a cancelled caller doesn't cancel it and retries still get its response
+    // A key reused with a different payload hash is rejected instead of replaying the response of another request
+    pub async fn execute<F>(&self, client: Option<IpAddr>, key: String, payload: Hash, request: F) -> Result<Value, InternalRpcError>
+    where
+        F: Future<Output = Result<Value, InternalRpcError>> + Send + 'static
+    {
+        let mut response = {
+            let mut clients = self.clients.lock().await;
+            let keys = clients.get_or_insert_mut(client, || LruCache::new(self.keys_per_client));
+            match keys.get(&key) {
+                Some(entry) if entry.created_at.elapsed() < self.ttl => {
+                    if entry.payload != payload {
+                        return Err(InternalRpcError::InvalidParams("Idempotency key already used with a different payload"))
+                    }
+                    entry.response.clone()
+                },
+                _ => {
+                    let (sender, receiver) = watch::channel(None);
+                    tokio::spawn(async move {
+                        let result = request.await.map_err(|e| CachedError::new(&e));
+                        let _ = sender.send(Some(result));
+                    });
+
+                    let entry = Arc::new(CacheEntry {
+                        created_at: Instant::now(),
+                        payload,
+                        response: receiver.clone()
+                    });
+                    keys.put(key, entry);
+                    receiver
+                }
+            }
+        };
+
+        let result = response.wait_for(Option::is_some).await
+            .map_err(|_| InternalRpcError::InternalError("Idempotent request was interrupted"))?;
+
+        match &*result {
+            Some(Ok(value)) => Ok(value.clone()),
+            Some(Err(e)) => Err(e.to_error()),
+            None => Err(InternalRpcError::InternalError("Idempotent request has no response"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use serde_json::json;
+    use xelis_common::crypto::hash;
+    use super::*;
+
+    async fn broadcast(counter: Arc<AtomicUsize>, result: Result<Value, InternalRpcError>) -> Result<Value, InternalRpcError> {
+        counter.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        result
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_retries_execute_once() {
+        let cache = IdempotencyCache::new(16, 16, Duration::from_secs(60));
+        let counter = Arc::new(AtomicUsize::new(0));
+        let client = Some("127.0.0.1".parse().unwrap());
+
+        let (first, second) = tokio::join!(
+            cache.execute(client, "payment-1".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))),
+            cache.execute(client, "payment-1".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(false))))
+        );
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+        assert_eq!(first.unwrap(), json!(true));
+        assert_eq!(second.unwrap(), json!(true));
+    }
+
+    #[tokio::test]
+    async fn test_error_replayed() {
+        let cache = IdempotencyCache::new(16, 16, Duration::from_secs(60));
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let first = cache.execute(None, "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Err(InternalRpcError::InvalidParams("invalid nonce")))).await.unwrap_err();
+        let second = cache.execute(None, "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))).await.unwrap_err();
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+        assert_eq!(first.get_code(), second.get_code());
+        assert_eq!(first.to_string(), second.to_string());
+        assert_eq!(first.get_code(), -32602);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_still_cached() {
+        let cache = IdempotencyCache::new(16, 16, Duration::from_secs(60));
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        // Client gives up before the submission is done
+        let first = tokio::time::timeout(Duration::from_millis(10), cache.execute(None, "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true))))).await;
+        assert!(first.is_err());
+
+        // Its retry gets the response of the first submission
+        let second = cache.execute(None, "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(false)))).await.unwrap();
+        assert_eq!(second, json!(true));
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_keys_namespaced_by_client() {
+        let cache = IdempotencyCache::new(16, 16, Duration::from_secs(60));
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        cache.execute(Some("10.0.0.1".parse().unwrap()), "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))).await.unwrap();
+        cache.execute(Some("10.0.0.2".parse().unwrap()), "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_keys_bounded_per_client() {
+        let cache = IdempotencyCache::new(16, 2, Duration::from_secs(60));
+        let counter = Arc::new(AtomicUsize::new(0));
+        let (client, other) = (Some("10.0.0.1".parse().unwrap()), Some("10.0.0.2".parse().unwrap()));
+
+        cache.execute(other, "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))).await.unwrap();
+        for i in 0..3 {
+            cache.execute(client, format!("key-{}", i), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))).await.unwrap();
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 4);
+
+        // Oldest key of the client is evicted by its own keys
+        cache.execute(client, "key-0".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 5);
+
+        // But the key of the other client is kept
+        cache.execute(other, "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_key_reused_with_another_payload() {
+        let cache = IdempotencyCache::new(16, 16, Duration::from_secs(60));
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        cache.execute(None, "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))).await.unwrap();
+        let err = cache.execute(None, "key".to_owned(), hash(b"other tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))).await.unwrap_err();
+        assert_eq!(err.get_code(), -32602);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+        // The original payload is still replayed
+        assert_eq!(cache.execute(None, "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(false)))).await.unwrap(), json!(true));
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_expired_key_executed_again() {
+        let cache = IdempotencyCache::new(16, 16, Duration::ZERO);
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        cache.execute(None, "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))).await.unwrap();
+        cache.execute(None, "key".to_owned(), hash(b"tx"), broadcast(Arc::clone(&counter), Ok(json!(true)))).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,14 +1,24 @@
 pub mod rpc;
 pub mod getwork_server;
+pub mod firehose;
+pub mod idempotency;
+pub mod tls;
 
 use crate::{
+    config::HEALTH_MAX_TOPOHEIGHT_BEHIND,
     core::{
         storage::Storage,
-        error::BlockchainError,
+        error::{resolve_rpc_error_code, BlockchainError},
         blockchain::Blockchain
     },
-    rpc::getwork_server::GetWorkServer,
+    net::{bind_tcp_listener, parse_bind_addresses},
+    rpc::{
+        firehose::{FirehoseServer, FirehoseWebSocketHandler, SharedFirehoseServer},
+        getwork_server::GetWorkServer,
+        tls::TlsCertificate
+    },
 };
+use anyhow::Context;
 use actix_web::{
     get,
     HttpServer,
@@ -23,15 +33,17 @@ use actix_web::{
         Payload
     },
     dev::ServerHandle,
-    error::Error
+    error::{Error, ErrorNotFound, ErrorUnauthorized}
 };
 use actix_web_actors::ws::WsResponseBuilder;
+use actix_web_httpauth::extractors::basic::BasicAuth;
 use serde_json::{Value, json};
 use tokio::sync::Mutex;
 use xelis_common::{
     api::daemon::NotifyEvent,
     config,
-    crypto::Address,
+    context::Context as RpcContext,
+    crypto::{hash, Address, PublicKey},
     rpc_server::{
         json_rpc,
         websocket,
@@ -65,10 +77,41 @@ use self::getwork_server::{
 
 pub type SharedDaemonRpcServer<S> = Arc<DaemonRpcServer<S>>;
 
+// Credentials required to call the admin methods
+pub struct AdminAuthConfig {
+    pub username: String,
+    pub password: String
+}
+
+impl AdminAuthConfig {
+    // Credentials are compared through their hashes, so the time spent
+    // doesn't depend on how many bytes of the given ones are matching
+    pub fn is_valid(&self, username: &str, password: Option<&str>) -> bool {
+        let Some(password) = password else {
+            return false
+        };
+
+        let valid_username = hash(self.username.as_bytes()) == hash(username.as_bytes());
+        let valid_password = hash(self.password.as_bytes()) == hash(password.as_bytes());
+        valid_username & valid_password
+    }
+}
+
+// Admin methods are not available on the public endpoints
+struct AdminRpc<S: Storage> {
+    auth: AdminAuthConfig,
+    handler: RPCHandler<Arc<Blockchain<S>>>
+}
+
 pub struct DaemonRpcServer<S: Storage> {
     handle: Mutex<Option<ServerHandle>>,
     websocket: WebSocketServerShared<EventWebSocketHandler<Arc<Blockchain<S>>, NotifyEvent>>,
-    getwork: Option<SharedGetWorkServer<S>>
+    getwork: Option<SharedGetWorkServer<S>>,
+    firehose: SharedFirehoseServer<S>,
+    // Certificate used by the HTTPS listeners if enabled
+    tls: Option<Arc<TlsCertificate>>,
+    // Admin endpoint, only enabled if credentials are configured
+    admin: Option<AdminRpc<S>>
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -80,11 +123,15 @@ pub enum ApiError {
     #[error("P2p engine is not running")]
     NoP2p,
     #[error("WebSocket server is not started")]
-    NoWebSocketServer
+    NoWebSocketServer,
+    #[error("TLS is not enabled on RPC server")]
+    NoTls
 }
 
 impl<S: Storage> DaemonRpcServer<S> {
-    pub async fn new(bind_address: String, blockchain: Arc<Blockchain<S>>, disable_getwork_server: bool) -> Result<SharedDaemonRpcServer<S>, BlockchainError> {
+    // `tls` contains the HTTPS bind addresses with the certificate and private key paths
+    // Admin methods are served on /admin/json_rpc only if `admin` credentials are set
+    pub async fn new(bind_addresses: Vec<String>, tls: Option<(Vec<String>, String, String)>, blockchain: Arc<Blockchain<S>>, disable_getwork_server: bool, admin: Option<AdminAuthConfig>) -> Result<SharedDaemonRpcServer<S>, BlockchainError> {
         let getwork: Option<SharedGetWorkServer<S>> = if !disable_getwork_server {
             info!("Creating GetWork server...");
             Some(Arc::new(GetWorkServer::new(blockchain.clone())))
@@ -92,8 +139,28 @@ impl<S: Storage> DaemonRpcServer<S> {
             None
         };
 
+        let firehose = Arc::new(FirehoseServer::new(blockchain.clone()));
+
+        let (tls_bind_addresses, tls) = match tls {
+            Some((addresses, cert_path, key_path)) => {
+                let certificate = TlsCertificate::new(cert_path, key_path)
+                    .context("Error while loading RPC TLS certificate")?;
+                (addresses, Some(Arc::new(certificate)))
+            },
+            None => (Vec::new(), None)
+        };
+
+        let admin = admin.map(|auth| {
+            info!("Admin RPC methods are enabled on /admin/json_rpc");
+            let mut handler = RPCHandler::new(blockchain.clone());
+            handler.set_error_code_resolver(resolve_rpc_error_code);
+            rpc::register_admin_methods(&mut handler);
+            AdminRpc { auth, handler }
+        });
+
         // create the RPC Handler which will register and contains all available methods
         let mut rpc_handler = RPCHandler::new(blockchain);
+        rpc_handler.set_error_code_resolver(resolve_rpc_error_code);
         rpc::register_methods(&mut rpc_handler, !disable_getwork_server);
 
         // create the default websocket server (support event & rpc methods)
@@ -103,23 +170,50 @@ impl<S: Storage> DaemonRpcServer<S> {
             handle: Mutex::new(None),
             websocket: ws,
             getwork,
+            firehose,
+            tls,
+            admin
         });
 
         {
             let clone = Arc::clone(&server);
-            let http_server = HttpServer::new(move || {
+            let mut http_server = HttpServer::new(move || {
                 let server = Arc::clone(&clone);
                 App::new().app_data(web::Data::from(server))
                     // Traditional HTTP
                     .route("/json_rpc", web::post().to(json_rpc::<Arc<Blockchain<S>>, DaemonRpcServer<S>>))
                     // WebSocket support
                     .route("/json_rpc", web::get().to(websocket::<EventWebSocketHandler<Arc<Blockchain<S>>, NotifyEvent>, DaemonRpcServer<S>>))
+                    // Admin methods, HTTP only
+                    .route("/admin/json_rpc", web::post().to(admin_json_rpc::<S>))
                     .route("/getwork/{address}/{worker}", web::get().to(getwork_endpoint::<S>))
+                    // Ordered blocks stream for indexers
+                    .route("/firehose/{topoheight}", web::get().to(firehose_endpoint::<S>))
+                    // Liveness / readiness probe
+                    .route("/health", web::get().to(health_endpoint::<S>))
                     .service(index)
             })
-            .disable_signals()
-            .bind(&bind_address)?
-            .run();
+            .disable_signals();
+
+            // Listen on every address configured, IPv4 and IPv6 can share the same port
+            let bind_addresses = parse_bind_addresses(&bind_addresses)
+                .context("Invalid RPC bind address")?;
+            for address in bind_addresses.iter() {
+                http_server = http_server.listen(bind_tcp_listener(address, &bind_addresses)?)?;
+            }
+
+            // HTTPS listeners share the same routes, WebSocket clients connect using WSS
+            if let Some(tls) = server.tls.as_ref() {
+                let tls_bind_addresses = parse_bind_addresses(&tls_bind_addresses)
+                    .context("Invalid RPC TLS bind address")?;
+                for address in tls_bind_addresses.iter() {
+                    http_server = http_server.listen_rustls_0_22(bind_tcp_listener(address, &tls_bind_addresses)?, tls.server_config())?;
+                }
+
+                #[cfg(unix)]
+                Self::reload_tls_on_sighup(Arc::clone(tls))?;
+            }
+            let http_server = http_server.run();
 
             { // save the server handle to be able to stop it later
                 let handle = http_server.handle();
@@ -151,6 +245,21 @@ impl<S: Storage> DaemonRpcServer<S> {
         Ok(())
     }
 
+    // Notify only the clients that subscribed with a filter on this account
+    pub async fn notify_clients_for_key(&self, event: &NotifyEvent, key: &PublicKey, value: Value) {
+        self.get_websocket().get_handler().notify_filtered(event, value, |filter| filter.is_some_and(|f| f.has_key(key))).await;
+    }
+
+    // Get all the accounts watched by the clients through `subscribe_address`
+    pub async fn get_subscribed_keys(&self) -> HashSet<PublicKey> {
+        self.get_websocket().get_handler().get_subscribed_keys().await
+    }
+
+    // Notify only the clients watching this account
+    pub async fn notify_clients_for_address(&self, event: &NotifyEvent, key: &PublicKey, value: Value) {
+        self.get_websocket().get_handler().notify_address(event, key, value).await;
+    }
+
     pub async fn stop(&self) {
         info!("Stopping RPC Server...");
         let mut handle = self.handle.lock().await;
@@ -162,9 +271,38 @@ impl<S: Storage> DaemonRpcServer<S> {
         }
     }
 
+    // Reload the TLS certificate and private key from their files
+    pub fn reload_tls(&self) -> Result<(), anyhow::Error> {
+        match self.tls.as_ref() {
+            Some(tls) => tls.reload(),
+            None => Err(ApiError::NoTls.into())
+        }
+    }
+
+    // Reload the TLS certificate each time the process receives a SIGHUP
+    #[cfg(unix)]
+    fn reload_tls_on_sighup(tls: Arc<TlsCertificate>) -> Result<(), BlockchainError> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut stream = signal(SignalKind::hangup())?;
+        spawn_task("rpc-tls-reload", async move {
+            while stream.recv().await.is_some() {
+                info!("SIGHUP received, reloading TLS certificate...");
+                if let Err(e) = tls.reload() {
+                    error!("Error while reloading TLS certificate: {:#}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+
     pub fn getwork_server(&self) -> &Option<SharedGetWorkServer<S>> {
         &self.getwork
     }
+
+    pub fn firehose_server(&self) -> &SharedFirehoseServer<S> {
+        &self.firehose
+    }
 }
 
 impl<S: Storage> WebSocketServerHandler<EventWebSocketHandler<Arc<Blockchain<S>>, NotifyEvent>> for DaemonRpcServer<S> {
@@ -185,6 +323,20 @@ async fn index() -> impl Responder {
     HttpResponse::Ok().body(format!("Hello, world!\nRunning on: {}", config::VERSION))
 }
 
+// JSON RPC endpoint of the admin methods, protected by HTTP basic authentication
+async fn admin_json_rpc<S: Storage>(server: Data<DaemonRpcServer<S>>, credentials: BasicAuth, body: web::Bytes) -> Result<HttpResponse, Error> {
+    let admin = server.admin.as_ref().ok_or(ErrorNotFound("Admin methods are not enabled"))?;
+    if !admin.auth.is_valid(credentials.user_id(), credentials.password()) {
+        warn!("Invalid credentials used on admin RPC endpoint");
+        return Err(ErrorUnauthorized("Username/password are invalid"))
+    }
+
+    let mut context = RpcContext::new();
+    context.store(admin.handler.get_data().clone());
+    let result = admin.handler.handle_request_with_context(context, &body).await?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
 async fn getwork_endpoint<S: Storage>(server: Data<DaemonRpcServer<S>>, request: HttpRequest, stream: Payload, path: Path<(String, String)>) -> Result<HttpResponse, Error> {
     match &server.getwork {
         Some(getwork) => {
@@ -217,4 +369,145 @@ async fn getwork_endpoint<S: Storage>(server: Data<DaemonRpcServer<S>>, request:
         },
         None => Ok(HttpResponse::NotFound().reason("GetWork server is not enabled").finish()) // getwork server is not started
     }
-}
\ No newline at end of file
+}
+
+// Replay the ordered blocks from the topoheight requested, then stream the new ones
+async fn firehose_endpoint<S: Storage>(server: Data<DaemonRpcServer<S>>, request: HttpRequest, stream: Payload, path: Path<u64>) -> Result<HttpResponse, Error> {
+    let topoheight = path.into_inner();
+    let pruned_topoheight = {
+        let storage = server.get_rpc_handler().get_data().get_storage().read().await;
+        match storage.get_pruned_topoheight().await {
+            Ok(pruned_topoheight) => pruned_topoheight,
+            Err(e) => {
+                warn!("Firehose request failed, storage is unavailable: {}", e);
+                return Ok(HttpResponse::ServiceUnavailable().body("Storage is unavailable"))
+            }
+        }
+    };
+
+    if pruned_topoheight.is_some_and(|pruned_topoheight| topoheight < pruned_topoheight) {
+        return Ok(HttpResponse::BadRequest().body("Topoheight requested is below the pruned topoheight"))
+    }
+
+    let firehose = server.firehose.clone();
+    let (addr, response) = WsResponseBuilder::new(FirehoseWebSocketHandler::new(firehose.clone()), &request, stream).start_with_addr()?;
+    trace!("New client connected to firehose WebSocket: {:?}", addr);
+    firehose.add_client(addr, topoheight).await;
+    Ok(response)
+}
+
+// Check if our topoheight is close enough to the median topoheight of our peers
+// If we don't have any peer, we can't be behind anyone
+pub fn is_synced_with_peers(our_topoheight: u64, mut peers_topoheights: Vec<u64>, max_behind: u64) -> bool {
+    if peers_topoheights.is_empty() {
+        return true
+    }
+
+    peers_topoheights.sort_unstable();
+    let median = peers_topoheights[peers_topoheights.len() / 2];
+    our_topoheight.saturating_add(max_behind) >= median
+}
+
+// Returns 200 if the node is healthy and synced with its peers, 503 otherwise
+async fn health_endpoint<S: Storage>(server: Data<DaemonRpcServer<S>>) -> Result<HttpResponse, Error> {
+    let blockchain = server.get_rpc_handler().get_data();
+    let network = blockchain.get_network().to_string();
+
+    let (top_topoheight, is_synced) = {
+        let storage = blockchain.get_storage().read().await;
+        (storage.get_top_topoheight(), blockchain.is_synced_for_storage(&storage).await)
+    };
+
+    let top_topoheight = match top_topoheight {
+        Ok(topoheight) => topoheight,
+        Err(e) => {
+            warn!("Health check failed, storage is unavailable: {}", e);
+            return Ok(HttpResponse::ServiceUnavailable().json(json!({
+                "status": "unavailable",
+                "version": config::VERSION,
+                "network": network
+            })))
+        }
+    };
+
+    let (peers, peers_topoheights) = match blockchain.get_p2p().read().await.as_ref() {
+        Some(p2p) => {
+            let peers = p2p.get_peer_list().get_cloned_peers().await;
+            let topoheights = peers.iter().map(|peer| peer.get_topoheight()).collect::<Vec<_>>();
+            (peers.len(), topoheights)
+        },
+        None => (0, Vec::new())
+    };
+
+    // A sustained block time drift may mean we are following a chain abandoned by the network
+    let block_time_drift = blockchain.get_block_time_drift().await;
+    let synced = is_synced_with_peers(top_topoheight, peers_topoheights, HEALTH_MAX_TOPOHEIGHT_BEHIND) && !block_time_drift.drifting;
+    let status = if synced {
+        "ok"
+    } else if block_time_drift.drifting {
+        "drifting"
+    } else {
+        "behind"
+    };
+
+    let body = json!({
+        "status": status,
+        "version": config::VERSION,
+        "network": network,
+        "top_topoheight": top_topoheight,
+        "synced": synced,
+        "is_synced": is_synced,
+        "peers": peers,
+        "rolling_block_time": block_time_drift.average,
+        "block_time_drifting": block_time_drift.drifting
+    });
+
+    Ok(if synced {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_credentials() {
+        let auth = AdminAuthConfig {
+            username: "admin".to_owned(),
+            password: "secret".to_owned()
+        };
+        assert!(auth.is_valid("admin", Some("secret")));
+        assert!(!auth.is_valid("admin", Some("wrong")));
+        assert!(!auth.is_valid("admin", None));
+        assert!(!auth.is_valid("other", Some("secret")));
+        assert!(!auth.is_valid("admin", Some("secre")));
+        assert!(!auth.is_valid("", Some("")));
+    }
+
+    #[test]
+    fn test_synced_without_peers() {
+        assert!(is_synced_with_peers(0, Vec::new(), HEALTH_MAX_TOPOHEIGHT_BEHIND));
+    }
+
+    #[test]
+    fn test_synced_with_peers_median() {
+        let max = HEALTH_MAX_TOPOHEIGHT_BEHIND;
+        // One peer far ahead doesn't change the median
+        assert!(is_synced_with_peers(1000, vec![1000, 1001, 1_000_000], max));
+        // Exactly at the limit
+        assert!(is_synced_with_peers(1000, vec![1000 + max, 1000 + max, 0], max));
+        // Ahead of our peers
+        assert!(is_synced_with_peers(1000, vec![10, 20, 30, 40], max));
+    }
+
+    #[test]
+    fn test_behind_peers_median() {
+        let max = HEALTH_MAX_TOPOHEIGHT_BEHIND;
+        assert!(!is_synced_with_peers(1000, vec![1000 + max + 1; 3], max));
+        // Majority of peers are ahead
+        assert!(!is_synced_with_peers(0, vec![0, 5000, 5000, 5000], max));
+    }
+}
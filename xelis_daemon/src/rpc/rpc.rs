@@ -1,53 +1,92 @@
 use crate::{
     config::{
+        get_genesis_block_hash,
         BLOCK_TIME_MILLIS,
         DEV_FEES,
-        DEV_PUBLIC_KEY
+        DEV_PUBLIC_KEY,
+        DIFFICULTY_HISTORY_MAX_SAMPLES,
+        CHAIN_STATS_MAX_BLOCKS,
+        HEADERS_RANGE_MAX_COUNT,
+        HEADERS_RANGE_MAX_SIZE,
+        IDEMPOTENCY_CACHE_SIZE,
+        IDEMPOTENCY_CACHE_CLIENTS,
+        IDEMPOTENCY_KEY_MAX_LEN,
+        IDEMPOTENCY_KEY_TTL_SECS,
+        MAX_BLOCK_SIZE
     },
     core::{
         blockchain::{
+            add_block_fee,
             get_block_dev_fee,
+            get_block_transactions_execution,
             get_block_reward,
             Blockchain
         },
+        blockdag,
+        chain_view::ChainStateView,
+        dag_height,
+        difficulty_history,
+        timestamp_search,
+        bootstrap,
+        chain_stats,
         error::BlockchainError,
+        headers,
         mempool::Mempool,
-        storage::Storage
+        state::StateOverlay,
+        storage::{Storage, Tips}
     },
     p2p::peer::Peer,
     BLOCK_TIME
 };
-use super::{InternalRpcError, ApiError};
+use super::{idempotency::IdempotencyCache, InternalRpcError, ApiError};
 use xelis_common::{
     api::{
         daemon::{
             AccountHistoryEntry,
             AccountHistoryType,
+            AssetHolder,
             BlockType,
             CreateMinerWorkParams,
             CreateMinerWorkResult,
+            DifficultyHistoryEntry,
             GetAccountAssetsParams,
             GetAccountHistoryParams,
             GetAccountRegistrationParams,
             GetAccountsParams,
+            GetAssetHoldersParams,
+            GetAssetHoldersResult,
             GetAssetParams,
+            GetAssetSupplyParams,
+            GetAssetSupplyResult,
             GetAssetsParams,
             GetBalanceAtTopoHeightParams,
             GetBalanceParams,
             GetBalanceResult,
             GetBlockAtTopoHeightParams,
             GetBlockByHashParams,
+            GetBlockExecutedTransactionsParams,
             GetBlockTemplateParams,
             GetBlockTemplateResult,
             GetBlocksAtHeightParams,
+            BlockAtHeightResponse,
+            GetDifficultyHistoryParams,
+            GetTopoHeightAtTimestampParams,
+            GetBlockCountBetweenParams,
             GetDifficultyResult,
+            GetHeadersRangeParams,
             GetHeightRangeParams,
+            HeadersRangeEntry,
+            NonceHistoryEntry,
             GetInfoResult,
             GetMempoolCacheParams,
             GetNonceAtTopoHeightParams,
+            GetNonceHistoryParams,
             GetNonceParams,
             GetNonceResult,
+            Direction,
+            GetPeersParams,
             GetPeersResponse,
+            GetTipsInfoResult,
             GetTopBlockParams,
             GetTopoHeightRangeParams,
             GetTransactionParams,
@@ -58,7 +97,10 @@ use xelis_common::{
             HasNonceResult,
             IsAccountRegisteredParams,
             IsTxExecutedInBlockParams,
+            NewBlockEvent,
             P2pStatusResult,
+            P2pTopologyPeer,
+            P2pTopologyResult,
             PeerEntry,
             RPCBlockResponse,
             SizeOnDiskResult,
@@ -70,39 +112,63 @@ use xelis_common::{
             ExtractKeyFromAddressParams,
             ExtractKeyFromAddressResult,
             GetTransactionExecutorParams,
-            GetTransactionExecutorResult
+            GetTransactionExecutorResult,
+            GetTxMerkleProofParams,
+            GetTxMerkleProofResult,
+            SetLogLevelParams,
+            ExportChainParams,
+            GetChainStatsParams,
+            GetChainStatsResult,
+            SimulateTransactionParams,
+            SimulateTransactionResult,
+            SimulatedBalance,
+            TipInfo
         },
         RPCTransaction,
         RPCTransactionType as RPCTransactionType,
         SplitAddressParams,
         SplitAddressResult,
     },
+    account::CiphertextCache,
     async_handler,
     block::{
+        compute_txs_merkle_root,
         Block,
         BlockHeader,
-        MinerWork
+        MerkleProof,
+        MinerWork,
+        HEADER_EXTRA_NONCE_OFFSET
     },
     config::{
+        ASSET_CREATION_BURN_AMOUNT,
         MAXIMUM_SUPPLY,
         MAX_TRANSACTION_SIZE,
         VERSION,
         XELIS_ASSET
     },
     context::Context,
-    crypto::Hash,
+    crypto::{
+        bech32::Bech32Error,
+        hash,
+        Address,
+        Hash,
+        Hashable
+    },
     difficulty::{
         CumulativeDifficulty,
         Difficulty
     },
     immutable::Immutable,
-    rpc_server::{
-        parse_params,
-        RPCHandler
+    network::Network,
+    prompt::{
+        logger::{self, ModuleLogLevel},
+        LogLevel
     },
+    rpc_server::{ClientIp, RPCHandler},
     serializer::Serializer,
-    time::TimestampSeconds,
+    time::{format_timestamp_millis, format_timestamp_seconds, get_current_time_in_seconds, TimestampSeconds},
     transaction::{
+        AssetCreationPayload,
         Transaction,
         TransactionType
     },
@@ -111,7 +177,14 @@ use xelis_common::{
 use anyhow::Context as AnyContext;
 use human_bytes::human_bytes;
 use serde_json::{json, Value};
-use std::{sync::Arc, borrow::Cow};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::Duration
+};
 use log::{info, debug, trace};
 
 // Get the block type using the block hash and the blockchain current state
@@ -151,6 +224,24 @@ async fn get_block_data<S: Storage>(blockchain: &Blockchain<S>, storage: &S, has
 }
 
 pub async fn get_block_response<S: Storage>(blockchain: &Blockchain<S>, storage: &S, hash: &Hash, block: &Block, total_size_in_bytes: usize) -> Result<Value, InternalRpcError> {
+    let response = build_block_response(blockchain, storage, hash, block, total_size_in_bytes).await?;
+    Ok(json!(response))
+}
+
+// Value of the new block event, the summary of the top block is taken from cache
+// It must be built once the top state is updated, other blocks have no summary
+pub async fn get_new_block_event<S: Storage>(blockchain: &Blockchain<S>, storage: &S, hash: &Hash, block: &Block, total_size_in_bytes: usize) -> Result<Value, InternalRpcError> {
+    let response = build_block_response(blockchain, storage, hash, block, total_size_in_bytes).await?;
+    let summary = blockchain.get_top_block_summary()
+        .filter(|summary| summary.hash == *hash);
+
+    Ok(json!(NewBlockEvent {
+        block: response,
+        summary: summary.map(Cow::Owned)
+    }))
+}
+
+async fn build_block_response<'a, S: Storage>(blockchain: &Blockchain<S>, storage: &S, hash: &'a Hash, block: &'a Block, total_size_in_bytes: usize) -> Result<RPCBlockResponse<'a>, InternalRpcError> {
     let (topoheight, supply, reward, block_type, cumulative_difficulty, difficulty) = get_block_data(blockchain, storage, hash).await?;
     let mut total_fees = 0;
     if block_type != BlockType::Orphaned {
@@ -158,7 +249,7 @@ pub async fn get_block_response<S: Storage>(blockchain: &Blockchain<S>, storage:
             // check that the TX was correctly executed in this block
             // retrieve all fees for valid txs
             if storage.is_tx_executed_in_block(tx_hash, &hash).context("Error while checking if tx was executed")? {
-                total_fees += tx.get_fee();
+                total_fees = add_block_fee(total_fees, tx.get_fee()).context("Error while summing the block fees")?;
             }
         }
     }
@@ -177,6 +268,7 @@ pub async fn get_block_response<S: Storage>(blockchain: &Blockchain<S>, storage:
                 source_commitments: Cow::Borrowed(tx.get_source_commitments()),
                 range_proof: Cow::Borrowed(tx.get_range_proof()),
                 reference: Cow::Borrowed(tx.get_reference()),
+                extra_data: tx.get_extra_data().as_ref().map(Cow::Borrowed),
                 signature: Cow::Borrowed(tx.get_signature()),
             }
         }).collect::<Vec<RPCTransaction<'_>>>();
@@ -185,12 +277,12 @@ pub async fn get_block_response<S: Storage>(blockchain: &Blockchain<S>, storage:
         (Some(dev_reward), Some(miner_reward))
     }).unwrap_or((None, None));
 
-    Ok(json!(RPCBlockResponse {
+    Ok(RPCBlockResponse {
         hash: Cow::Borrowed(hash),
         topoheight,
         block_type,
-        cumulative_difficulty: Cow::Borrowed(&cumulative_difficulty),
-        difficulty: Cow::Borrowed(&difficulty),
+        cumulative_difficulty: Cow::Owned(cumulative_difficulty),
+        difficulty: Cow::Owned(difficulty),
         supply,
         reward,
         dev_reward,
@@ -198,7 +290,8 @@ pub async fn get_block_response<S: Storage>(blockchain: &Blockchain<S>, storage:
         total_fees: Some(total_fees),
         total_size_in_bytes,
         extra_nonce: Cow::Borrowed(header.get_extra_nonce()),
-        timestamp: header.get_timestamp(),
+        timestamp: header.get_timestamp().into(),
+        timestamp_human: format_timestamp_millis(header.get_timestamp()),
         nonce: header.get_nonce(),
         height: header.get_height(),
         version: header.get_version(),
@@ -206,7 +299,7 @@ pub async fn get_block_response<S: Storage>(blockchain: &Blockchain<S>, storage:
         tips: Cow::Borrowed(header.get_tips()),
         txs_hashes: Cow::Borrowed(header.get_txs_hashes()),
         transactions
-    }))
+    })
 }
 
 fn get_block_rewards(height: u64, reward: Option<u64>) -> Option<(u64, u64)> {
@@ -224,7 +317,7 @@ fn get_block_rewards(height: u64, reward: Option<u64>) -> Option<(u64, u64)> {
 // Get a block response based on data in chain and from parameters
 pub async fn get_block_response_for_hash<S: Storage>(blockchain: &Blockchain<S>, storage: &S, hash: &Hash, include_txs: bool) -> Result<Value, InternalRpcError> {
     if !storage.has_block_with_hash(&hash).await.context("Error while checking if block exist")? {
-        return Err(InternalRpcError::InvalidParamsAny(BlockchainError::BlockNotFound(hash.clone()).into()))
+        return Err(BlockchainError::BlockNotFound(hash.clone()).into())
     }
 
     let value: Value = if include_txs {
@@ -259,7 +352,8 @@ pub async fn get_block_response_for_hash<S: Storage>(blockchain: &Blockchain<S>,
             total_fees: None,
             total_size_in_bytes,
             extra_nonce: Cow::Borrowed(header.get_extra_nonce()),
-            timestamp: header.get_timestamp(),
+            timestamp: header.get_timestamp().into(),
+            timestamp_human: format_timestamp_millis(header.get_timestamp()),
             nonce: header.get_nonce(),
             height: header.get_height(),
             version: header.get_version(),
@@ -281,15 +375,24 @@ pub async fn get_transaction_response<S: Storage>(storage: &S, tx: &Arc<Transact
         None
     };
 
-    let data = RPCTransaction::from_tx(tx, hash, storage.is_mainnet());
     let executed_in_block = storage.get_block_executor_for_tx(hash).ok();
-    Ok(json!(TransactionResponse { blocks, executed_in_block, data, in_mempool, first_seen }))
+    Ok(build_transaction_response(tx, hash, storage.is_mainnet(), blocks, executed_in_block, in_mempool, first_seen))
+}
+
+// Same response for a transaction stored on disk or in mempool
+fn build_transaction_response(tx: &Transaction, hash: &Hash, mainnet: bool, blocks: Option<Tips>, executed_in_block: Option<Hash>, in_mempool: bool, first_seen: Option<TimestampSeconds>) -> Value {
+    let data = RPCTransaction::from_tx(tx, hash, mainnet);
+    let first_seen_human = first_seen.map(format_timestamp_seconds);
+    json!(TransactionResponse { blocks, executed_in_block, data, in_mempool, first_seen, first_seen_human })
 }
 
 // first check on disk, then check in mempool
 pub async fn get_transaction_response_for_hash<S: Storage>(storage: &S, mempool: &Mempool, hash: &Hash) -> Result<Value, InternalRpcError> {
-    match storage.get_transaction(hash).await {
-        Ok(tx) => get_transaction_response(storage, &tx, hash, false, None).await,
+    match storage.get_transaction_with_metadata(hash).await {
+        Ok(metadata) => {
+            let blocks = (!metadata.blocks.is_empty()).then_some(metadata.blocks);
+            Ok(build_transaction_response(&metadata.tx, hash, storage.is_mainnet(), blocks, metadata.executed_in_block, false, None))
+        },
         Err(_) => {
             let tx = mempool.get_sorted_tx(hash).context("Error while retrieving transaction from disk and mempool")?;
             get_transaction_response(storage, &tx.get_tx(), hash, true, Some(tx.get_first_seen())).await
@@ -297,14 +400,44 @@ pub async fn get_transaction_response_for_hash<S: Storage>(storage: &S, mempool:
     }
 }
 
+// Keep only the network part of an address: /24 for IPv4 and /48 for IPv6
+// The port is also removed as it could help to identify the peer
+pub fn mask_peer_address(addr: &SocketAddr) -> SocketAddr {
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            IpAddr::V4(Ipv4Addr::new(a, b, c, 0))
+        },
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            IpAddr::V6(Ipv6Addr::new(segments[0], segments[1], segments[2], 0, 0, 0, 0, 0))
+        }
+    };
+    SocketAddr::new(ip, 0)
+}
+
 // Get a Peer Entry based on peer data
-pub async fn get_peer_entry(peer: &Peer) -> PeerEntry {
+// If hide_ip is set, the peer address and its advertised peers are masked
+pub async fn get_peer_entry(peer: &Peer, hide_ip: bool) -> PeerEntry {
     let top_block_hash = { peer.get_top_block_hash().lock().await.clone() };
     let peers = { peer.get_peers().lock().await.clone() };
     let cumulative_difficulty = { peer.get_cumulative_difficulty().lock().await.clone() };
+    let (addr, peers) = if hide_ip {
+        // Two peers in the same network are merged, keep the most complete direction
+        let mut masked: HashMap<SocketAddr, Direction> = HashMap::with_capacity(peers.len());
+        for (addr, direction) in peers {
+            masked.entry(mask_peer_address(&addr))
+                .and_modify(|d| { d.update(direction); })
+                .or_insert(direction);
+        }
+        (Cow::Owned(mask_peer_address(peer.get_connection().get_address())), masked)
+    } else {
+        (Cow::Borrowed(peer.get_connection().get_address()), peers)
+    };
+
     PeerEntry {
         id: peer.get_id(),
-        addr: Cow::Borrowed(peer.get_connection().get_address()),
+        addr,
         local_port: peer.get_local_port(),
         tag: Cow::Borrowed(peer.get_node_tag()),
         version: Cow::Borrowed(peer.get_version()),
@@ -315,7 +448,35 @@ pub async fn get_peer_entry(peer: &Peer) -> PeerEntry {
         peers: Cow::Owned(peers),
         pruned_topoheight: peer.get_pruned_topoheight(),
         cumulative_difficulty: Cow::Owned(cumulative_difficulty),
-        connected_on: peer.get_connection().connected_on()
+        connected_on: peer.get_connection().connected_on(),
+        direction: if peer.is_out() { Direction::Out } else { Direction::In },
+        priority: peer.is_priority(),
+        latency: peer.get_latency(),
+        bytes_in: peer.get_connection().bytes_in(),
+        bytes_out: peer.get_connection().bytes_out(),
+        fail_count: peer.get_fail_count(),
+        object_requests_served: peer.get_object_requests_served(),
+        object_requests_dropped: peer.get_object_requests_dropped(),
+        chain_requests_served: peer.get_chain_requests_served()
+    }
+}
+
+// Apply the requested filters on the sharable peers
+fn build_peers_response<'a>(params: &GetPeersParams, entries: Vec<PeerEntry<'a>>, total_peers: usize) -> GetPeersResponse<'a> {
+    let hidden_peers = total_peers - entries.len();
+    let mut peers: Vec<PeerEntry<'a>> = entries.into_iter()
+        .filter(|entry| params.matches(entry))
+        .collect();
+    let matching_peers = peers.len();
+    if params.count_only {
+        peers.clear();
+    }
+
+    GetPeersResponse {
+        peers,
+        matching_peers,
+        total_peers,
+        hidden_peers
     }
 }
 
@@ -326,54 +487,81 @@ pub fn register_methods<S: Storage>(handler: &mut RPCHandler<Arc<Blockchain<S>>>
     handler.register_method("get_height", async_handler!(get_height::<S>));
     handler.register_method("get_topoheight", async_handler!(get_topoheight::<S>));
     handler.register_method("get_stableheight", async_handler!(get_stableheight::<S>));
-    handler.register_method("get_block_at_topoheight", async_handler!(get_block_at_topoheight::<S>));
-    handler.register_method("get_blocks_at_height", async_handler!(get_blocks_at_height::<S>));
-    handler.register_method("get_block_by_hash", async_handler!(get_block_by_hash::<S>));
-    handler.register_method("get_top_block", async_handler!(get_top_block::<S>));
-    handler.register_method("get_balance", async_handler!(get_balance::<S>));
-    handler.register_method("has_balance", async_handler!(has_balance::<S>));
-    handler.register_method("get_balance_at_topoheight", async_handler!(get_balance_at_topoheight::<S>));
+    handler.register_typed("get_block_at_topoheight", async_handler!(get_block_at_topoheight::<S>));
+    handler.register_typed("get_blocks_at_height", async_handler!(get_blocks_at_height::<S>));
+    handler.register_typed("get_block_by_hash", async_handler!(get_block_by_hash::<S>));
+    handler.register_typed("get_top_block", async_handler!(get_top_block::<S>));
+    handler.register_method("get_top_block_summary", async_handler!(get_top_block_summary::<S>));
+    handler.register_typed("get_balance", async_handler!(get_balance::<S>));
+    handler.register_typed("has_balance", async_handler!(has_balance::<S>));
+    handler.register_typed("get_balance_at_topoheight", async_handler!(get_balance_at_topoheight::<S>));
     handler.register_method("get_info", async_handler!(get_info::<S>));
-    handler.register_method("get_nonce", async_handler!(get_nonce::<S>));
-    handler.register_method("has_nonce", async_handler!(has_nonce::<S>));
-    handler.register_method("get_nonce_at_topoheight", async_handler!(get_nonce_at_topoheight::<S>));
-    handler.register_method("get_asset", async_handler!(get_asset::<S>));
-    handler.register_method("get_assets", async_handler!(get_assets::<S>));
+    handler.register_typed("get_nonce", async_handler!(get_nonce::<S>));
+    handler.register_typed("has_nonce", async_handler!(has_nonce::<S>));
+    handler.register_typed("get_nonce_at_topoheight", async_handler!(get_nonce_at_topoheight::<S>));
+    handler.register_typed("get_nonce_history", async_handler!(get_nonce_history::<S>));
+    handler.register_typed("get_asset", async_handler!(get_asset::<S>));
+    handler.register_typed("get_assets", async_handler!(get_assets::<S>));
+    handler.register_typed("get_asset_supply", async_handler!(get_asset_supply::<S>));
+    handler.register_typed("get_asset_holders", async_handler!(get_asset_holders::<S>));
     handler.register_method("count_assets", async_handler!(count_assets::<S>));
     handler.register_method("count_accounts", async_handler!(count_accounts::<S>));
     handler.register_method("count_transactions", async_handler!(count_transactions::<S>));
-    handler.register_method("submit_transaction", async_handler!(submit_transaction::<S>));
-    handler.register_method("get_transaction", async_handler!(get_transaction::<S>));
-    handler.register_method("get_transaction_executor", async_handler!(get_transaction_executor::<S>));
+    let submissions = Arc::new(IdempotencyCache::new(IDEMPOTENCY_CACHE_CLIENTS, IDEMPOTENCY_CACHE_SIZE, Duration::from_secs(IDEMPOTENCY_KEY_TTL_SECS)));
+    handler.register_typed("submit_transaction", move |context, params| {
+        let submissions = Arc::clone(&submissions);
+        Box::pin(async move { submit_transaction::<S>(context, &submissions, params).await })
+    });
+    handler.register_typed("simulate_transaction", async_handler!(simulate_transaction::<S>));
+    handler.register_typed("get_transaction", async_handler!(get_transaction::<S>));
+    handler.register_typed("get_transaction_executor", async_handler!(get_transaction_executor::<S>));
+    handler.register_typed("get_tx_merkle_proof", async_handler!(get_tx_merkle_proof::<S>));
+    handler.register_typed("get_block_executed_transactions", async_handler!(get_block_executed_transactions::<S>));
     handler.register_method("p2p_status", async_handler!(p2p_status::<S>));
-    handler.register_method("get_peers", async_handler!(get_peers::<S>));
+    handler.register_method("p2p_topology", async_handler!(p2p_topology::<S>));
+    handler.register_typed("get_peers", async_handler!(get_peers::<S>));
     handler.register_method("get_mempool", async_handler!(get_mempool::<S>));
     handler.register_method("get_tips", async_handler!(get_tips::<S>));
-    handler.register_method("get_dag_order", async_handler!(get_dag_order::<S>));
-    handler.register_method("get_blocks_range_by_topoheight", async_handler!(get_blocks_range_by_topoheight::<S>));
-    handler.register_method("get_blocks_range_by_height", async_handler!(get_blocks_range_by_height::<S>));
-    handler.register_method("get_transactions", async_handler!(get_transactions::<S>));
-    handler.register_method("get_account_history", async_handler!(get_account_history::<S>));
-    handler.register_method("get_account_assets", async_handler!(get_account_assets::<S>));
-    handler.register_method("get_accounts", async_handler!(get_accounts::<S>));
-    handler.register_method("is_account_registered", async_handler!(is_account_registered::<S>));
-    handler.register_method("get_account_registration_topoheight", async_handler!(get_account_registration_topoheight::<S>));
-    handler.register_method("is_tx_executed_in_block", async_handler!(is_tx_executed_in_block::<S>));
+    handler.register_method("get_tips_info", async_handler!(get_tips_info::<S>));
+    handler.register_typed("get_dag_order", async_handler!(get_dag_order::<S>));
+    handler.register_typed("get_blocks_range_by_topoheight", async_handler!(get_blocks_range_by_topoheight::<S>));
+    handler.register_typed("get_blocks_range_by_height", async_handler!(get_blocks_range_by_height::<S>));
+    handler.register_typed("get_headers_range", async_handler!(get_headers_range::<S>));
+    handler.register_typed("get_transactions", async_handler!(get_transactions::<S>));
+    handler.register_typed("get_account_history", async_handler!(get_account_history::<S>));
+    handler.register_typed("get_account_assets", async_handler!(get_account_assets::<S>));
+    handler.register_typed("get_accounts", async_handler!(get_accounts::<S>));
+    handler.register_typed("is_account_registered", async_handler!(is_account_registered::<S>));
+    handler.register_typed("get_account_registration_topoheight", async_handler!(get_account_registration_topoheight::<S>));
+    handler.register_typed("is_tx_executed_in_block", async_handler!(is_tx_executed_in_block::<S>));
     handler.register_method("get_dev_fee_thresholds", async_handler!(get_dev_fee_thresholds::<S>));
     handler.register_method("get_size_on_disk", async_handler!(get_size_on_disk::<S>));
-    handler.register_method("get_mempool_cache", async_handler!(get_mempool_cache::<S>));
+    handler.register_typed("get_mempool_cache", async_handler!(get_mempool_cache::<S>));
     handler.register_method("get_difficulty", async_handler!(get_difficulty::<S>));
-    handler.register_method("validate_address", async_handler!(validate_address::<S>));
-    handler.register_method("split_address", async_handler!(split_address::<S>));
-    handler.register_method("extract_key_from_address", async_handler!(extract_key_from_address::<S>));
+    handler.register_typed("get_difficulty_history", async_handler!(get_difficulty_history::<S>));
+    handler.register_typed("get_chain_stats", async_handler!(get_chain_stats::<S>));
+    handler.register_method("get_block_processing_stats", async_handler!(get_block_processing_stats::<S>));
+    handler.register_typed("get_topoheight_at_timestamp", async_handler!(get_topoheight_at_timestamp::<S>));
+    handler.register_typed("get_block_count_between", async_handler!(get_block_count_between::<S>));
+    handler.register_typed("validate_address", async_handler!(validate_address::<S>));
+    handler.register_typed("split_address", async_handler!(split_address::<S>));
+    handler.register_typed("extract_key_from_address", async_handler!(extract_key_from_address::<S>));
 
     if allow_mining_methods {
-        handler.register_method("get_block_template", async_handler!(get_block_template::<S>));
-        handler.register_method("create_miner_work", async_handler!(create_miner_work::<S>));
-        handler.register_method("submit_block", async_handler!(submit_block::<S>));
+        handler.register_typed("get_block_template", async_handler!(get_block_template::<S>));
+        handler.register_typed("create_miner_work", async_handler!(create_miner_work::<S>));
+        handler.register_typed("submit_block", async_handler!(submit_block::<S>));
     }
 }
 
+// Admin methods are registered on their own handler, served only to authenticated clients
+pub fn register_admin_methods<S: Storage>(handler: &mut RPCHandler<Arc<Blockchain<S>>>) {
+    info!("Registering admin RPC methods...");
+    handler.register_typed("set_log_level", async_handler!(set_log_level::<S>));
+    handler.register_typed("export_chain", async_handler!(export_chain::<S>));
+    handler.register_method("reload_tls", async_handler!(reload_tls::<S>));
+}
+
 async fn version<S: Storage>(_: &Context, body: Value) -> Result<Value, InternalRpcError> {
     if body != Value::Null {
         return Err(InternalRpcError::UnexpectedParams)
@@ -386,7 +574,7 @@ async fn get_height<S: Storage>(context: &Context, body: Value) -> Result<Value,
         return Err(InternalRpcError::UnexpectedParams)
     }
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    Ok(json!(blockchain.get_height()))
+    Ok(json!(blockchain.get_top_state().height))
 }
 
 async fn get_topoheight<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
@@ -394,7 +582,7 @@ async fn get_topoheight<S: Storage>(context: &Context, body: Value) -> Result<Va
         return Err(InternalRpcError::UnexpectedParams)
     }
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    Ok(json!(blockchain.get_topo_height()))
+    Ok(json!(blockchain.get_top_state().topoheight))
 }
 
 async fn get_stableheight<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
@@ -402,34 +590,40 @@ async fn get_stableheight<S: Storage>(context: &Context, body: Value) -> Result<
         return Err(InternalRpcError::UnexpectedParams)
     }
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    Ok(json!(blockchain.get_stable_height()))
+    Ok(json!(blockchain.get_top_state().stable_height))
 }
 
-async fn get_block_at_topoheight<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetBlockAtTopoHeightParams = parse_params(body)?;
+async fn get_block_at_topoheight<S: Storage>(context: &Context, params: GetBlockAtTopoHeightParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let hash = storage.get_hash_at_topo_height(params.topoheight).await.context("Error while retrieving hash at topo height")?;
     get_block_response_for_hash(&blockchain, &storage, &hash, params.include_txs).await
 }
 
-async fn get_block_by_hash<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetBlockByHashParams = parse_params(body)?;
+async fn get_block_by_hash<S: Storage>(context: &Context, params: GetBlockByHashParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     get_block_response_for_hash(&blockchain, &storage, &params.hash, params.include_txs).await
 }
 
-async fn get_top_block<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetTopBlockParams = parse_params(body)?;
+async fn get_top_block<S: Storage>(context: &Context, params: GetTopBlockParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let hash = blockchain.get_top_block_hash_for_storage(&storage).await.context("Error while retrieving top block hash")?;
     get_block_response_for_hash(&blockchain, &storage, &hash, params.include_txs).await
 }
 
-async fn get_block_template<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetBlockTemplateParams = parse_params(body)?;
+// Served from the cached top state, no storage access
+async fn get_top_block_summary<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let summary = blockchain.get_top_block_summary().context("Top block summary is not available yet")?;
+    Ok(json!(summary))
+}
+
+async fn get_block_template<S: Storage>(context: &Context, params: GetBlockTemplateParams) -> Result<Value, InternalRpcError> {
     if !params.address.is_normal() {
         return Err(InternalRpcError::InvalidParamsAny(ApiError::ExpectedNormalAddress.into()))
     }
@@ -439,16 +633,28 @@ async fn get_block_template<S: Storage>(context: &Context, body: Value) -> Resul
         return Err(InternalRpcError::InvalidParamsAny(BlockchainError::InvalidNetwork.into()))
     }
 
-    let storage = blockchain.get_storage().read().await;
-    let block = blockchain.get_block_template_for_storage(&storage, params.address.into_owned().to_public_key()).await.context("Error while retrieving block template")?;
+    let storage = blockchain.get_chain_view().await;
+    let (block, summary) = blockchain.get_block_template_with_summary_for_storage(&storage, params.address.into_owned().to_public_key(), params.max_txs).await.context("Error while retrieving block template")?;
     let (difficulty, _) = blockchain.get_difficulty_at_tips(&*storage, block.get_tips().iter()).await.context("Error while retrieving difficulty at tips")?;
     let height = block.height;
-    let topoheight = blockchain.get_topo_height();
-    Ok(json!(GetBlockTemplateResult { template: block.to_hex(), height, topoheight, difficulty }))
+    // Template is built on our current topoheight
+    let topoheight = summary.topoheight - 1;
+    Ok(json!(GetBlockTemplateResult {
+        template: block.to_hex(),
+        height,
+        topoheight,
+        difficulty,
+        block_topoheight: summary.topoheight,
+        total_fees: summary.total_fees,
+        expected_reward: summary.expected_reward,
+        txs_count: summary.txs_count,
+        size: summary.size,
+        remaining_size: MAX_BLOCK_SIZE.saturating_sub(summary.size),
+        extra_nonce_offset: HEADER_EXTRA_NONCE_OFFSET
+    }))
 }
 
-async fn create_miner_work<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: CreateMinerWorkParams = parse_params(body)?;
+async fn create_miner_work<S: Storage>(context: &Context, params: CreateMinerWorkParams) -> Result<Value, InternalRpcError> {
     
     let header = BlockHeader::from_hex(params.template.into_owned())?;
     let mut work = MinerWork::from_block(header);
@@ -468,8 +674,7 @@ async fn create_miner_work<S: Storage>(context: &Context, body: Value) -> Result
     Ok(json!(CreateMinerWorkResult { miner_work: work.to_hex() }))
 }
 
-async fn submit_block<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: SubmitBlockParams = parse_params(body)?;
+async fn submit_block<S: Storage>(context: &Context, params: SubmitBlockParams) -> Result<Value, InternalRpcError> {
     let mut header = BlockHeader::from_hex(params.block_template)?;
     if let Some(work) = params.miner_work {
         let work = MinerWork::from_hex(work)?;
@@ -479,34 +684,38 @@ async fn submit_block<S: Storage>(context: &Context, body: Value) -> Result<Valu
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
 
     let block = blockchain.build_block_from_header(Immutable::Owned(header)).await?;
-    blockchain.add_new_block(block, true, true).await?;
+    // Verified with a read lock first, an invalid block never waits for the write lock on storage
+    let verified = {
+        let storage = blockchain.get_storage().read().await;
+        blockchain.verify_block(&storage, block).await?
+    };
+    blockchain.add_verified_block(verified, true, true).await?;
     Ok(json!(true))
 }
 
-async fn get_balance<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetBalanceParams = parse_params(body)?;
+async fn get_balance<S: Storage>(context: &Context, params: GetBalanceParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
     if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
         return Err(InternalRpcError::InvalidParamsAny(BlockchainError::InvalidNetwork.into()))
     }
 
-    let storage = blockchain.get_storage().read().await;
-    let (topoheight, version) = storage.get_last_balance(params.address.get_public_key(), &params.asset).await.context("Error while retrieving last balance")?;
+    let view = blockchain.get_chain_view().await;
+    let (topoheight, version) = view.get_last_balance(params.address.get_public_key(), &params.asset).await.context("Error while retrieving last balance")?;
     Ok(json!(GetBalanceResult {
         version,
-        topoheight
+        topoheight,
+        snapshot_topoheight: view.get_topoheight()
     }))
 }
 
-async fn has_balance<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: HasBalanceParams = parse_params(body)?;
+async fn has_balance<S: Storage>(context: &Context, params: HasBalanceParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
     if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
         return Err(InternalRpcError::InvalidParamsAny(BlockchainError::InvalidNetwork.into()))
     }
 
     let key = params.address.get_public_key();
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let exist = if let Some(topoheight) = params.topoheight {
         storage.has_balance_at_exact_topoheight(key, &params.asset, topoheight).await.context("Error while checking balance at topo for account")?
     } else {
@@ -521,19 +730,30 @@ async fn get_info<S: Storage>(context: &Context, body: Value) -> Result<Value, I
         return Err(InternalRpcError::UnexpectedParams)
     }
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let height = blockchain.get_height();
-    let topoheight = blockchain.get_topo_height();
-    let stableheight = blockchain.get_stable_height();
-    let (top_block_hash, circulating_supply, pruned_topoheight, average_block_time) = {
-        let storage = blockchain.get_storage().read().await;
-        let top_block_hash = storage.get_hash_at_topo_height(topoheight).await.context("Error while retrieving hash at topo height")?;
+    // All the top fields come from the same snapshot than the storage reads
+    let (top_state, circulating_supply, pruned_topoheight, average_block_time, is_synced, genesis_block_hash) = {
+        let storage = blockchain.get_chain_view().await;
+        let top_state = storage.get_top_state().clone();
+        let topoheight = top_state.topoheight;
         let supply = storage.get_supply_at_topo_height(topoheight).await.context("Error while retrieving supply at topo height")?;
         let pruned_topoheight = storage.get_pruned_topoheight().await.context("Error while retrieving pruned topoheight")?;
         let average_block_time = blockchain.get_average_block_time::<S>(&storage).await.context("Error while retrieving average block time")?;
-        (top_block_hash, supply, pruned_topoheight, average_block_time)
+        let is_synced = blockchain.is_synced_for_storage(&storage).await;
+        // Dev network has no fixed genesis block
+        let genesis_block_hash = match blockchain.get_network() {
+            Network::Dev => storage.get_hash_at_topo_height(0).await.ok(),
+            network => Some(get_genesis_block_hash(network).clone())
+        };
+        (top_state, supply, pruned_topoheight, average_block_time, is_synced, genesis_block_hash)
     };
-    let difficulty = blockchain.get_difficulty().await;
+    let height = top_state.height;
+    let topoheight = top_state.topoheight;
+    let stableheight = top_state.stable_height;
+    let top_block_hash = top_state.hash;
+    let difficulty = top_state.difficulty;
     let block_time_target = BLOCK_TIME_MILLIS;
+    let block_time_drift = blockchain.get_block_time_drift().await;
+    let (network_time_offset, clock_skewed) = blockchain.get_network_time_offset().await;
     let block_reward = get_block_reward(circulating_supply);
     let mempool_size = blockchain.get_mempool_size().await;
     let version = VERSION.into();
@@ -550,38 +770,42 @@ async fn get_info<S: Storage>(context: &Context, body: Value) -> Result<Value, I
         difficulty,
         block_time_target,
         average_block_time,
+        rolling_block_time: block_time_drift.average,
+        block_time_drifting: block_time_drift.drifting,
         block_reward,
         mempool_size,
         version,
-        network
+        network,
+        is_synced,
+        genesis_block_hash,
+        replace_by_fee: true,
+        network_time_offset,
+        clock_skewed
     }))
 }
 
-async fn get_balance_at_topoheight<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetBalanceAtTopoHeightParams = parse_params(body)?;
+async fn get_balance_at_topoheight<S: Storage>(context: &Context, params: GetBalanceAtTopoHeightParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let topoheight = blockchain.get_topo_height();
-    if params.topoheight > topoheight {
-        return Err(InternalRpcError::UnexpectedParams).context("Topoheight cannot be greater than current chain topoheight")?
-    }
-
     if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
         return Err(InternalRpcError::InvalidParamsAny(BlockchainError::InvalidNetwork.into()))
     }
 
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
+    if params.topoheight > storage.get_topoheight() {
+        return Err(InternalRpcError::UnexpectedParams).context("Topoheight cannot be greater than current chain topoheight")?
+    }
+
     let balance = storage.get_balance_at_exact_topoheight(params.address.get_public_key(), &params.asset, params.topoheight).await.context("Error while retrieving balance at exact topo height")?;
     Ok(json!(balance))
 }
 
-async fn has_nonce<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: HasNonceParams = parse_params(body)?;
+async fn has_nonce<S: Storage>(context: &Context, params: HasNonceParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
     if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
         return Err(InternalRpcError::InvalidParamsAny(BlockchainError::InvalidNetwork.into()))
     }
 
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let exist = if let Some(topoheight) = params.topoheight {
         storage.has_nonce_at_exact_topoheight(params.address.get_public_key(), topoheight).await.context("Error while checking nonce at topo for account")?
     } else {
@@ -591,49 +815,111 @@ async fn has_nonce<S: Storage>(context: &Context, body: Value) -> Result<Value,
     Ok(json!(HasNonceResult { exist }))
 }
 
-async fn get_nonce<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetNonceParams = parse_params(body)?;
+async fn get_nonce<S: Storage>(context: &Context, params: GetNonceParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
     if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
         return Err(InternalRpcError::InvalidParamsAny(BlockchainError::InvalidNetwork.into()))
     }
 
-    let storage = blockchain.get_storage().read().await;
-    let (topoheight, version) = storage.get_last_nonce(params.address.get_public_key()).await
+    let view = blockchain.get_chain_view().await;
+    let (topoheight, version) = view.get_last_nonce(params.address.get_public_key()).await
         .context("Error while retrieving nonce for account")?;
 
-    Ok(json!(GetNonceResult { topoheight, version }))
+    Ok(json!(GetNonceResult { topoheight, version, snapshot_topoheight: view.get_topoheight() }))
 }
 
-async fn get_nonce_at_topoheight<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetNonceAtTopoHeightParams = parse_params(body)?;
+async fn get_nonce_at_topoheight<S: Storage>(context: &Context, params: GetNonceAtTopoHeightParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let topoheight = blockchain.get_topo_height();
-    if params.topoheight > topoheight {
+    if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
+        return Err(InternalRpcError::InvalidParamsAny(BlockchainError::InvalidNetwork.into()))
+    }
+
+    let storage = blockchain.get_chain_view().await;
+    if params.topoheight > storage.get_topoheight() {
         return Err(InternalRpcError::UnexpectedParams).context("Topoheight cannot be greater than current chain topoheight")?
     }
 
+    let nonce = storage.get_nonce_at_exact_topoheight(params.address.get_public_key(), params.topoheight).await.context("Error while retrieving nonce at exact topo height")?;
+    Ok(json!(nonce))
+}
+
+const MAX_NONCE_HISTORY: usize = 100;
+// retrieve the nonce versions of an account, newest first
+async fn get_nonce_history<S: Storage>(context: &Context, params: GetNonceHistoryParams) -> Result<Value, InternalRpcError> {
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
     if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
         return Err(InternalRpcError::InvalidParamsAny(BlockchainError::InvalidNetwork.into()))
     }
 
-    let storage = blockchain.get_storage().read().await;
-    let nonce = storage.get_nonce_at_exact_topoheight(params.address.get_public_key(), params.topoheight).await.context("Error while retrieving nonce at exact topo height")?;
-    Ok(json!(nonce))
+    let max_entries = params.max_entries.unwrap_or(MAX_NONCE_HISTORY);
+    if max_entries > MAX_NONCE_HISTORY {
+        return Err(InternalRpcError::InvalidJSONRequest).context(format!("Max entries cannot be greater than {}", MAX_NONCE_HISTORY))?
+    }
+
+    let storage = blockchain.get_chain_view().await;
+    let pruned_topoheight = storage.get_pruned_topoheight().await.context("Error while retrieving pruned topoheight")?.unwrap_or(0);
+    let history = storage.get_nonce_history(params.address.get_public_key(), max_entries, pruned_topoheight).await
+        .context("Error while retrieving nonce history")?
+        .into_iter()
+        .map(|(topoheight, version)| NonceHistoryEntry {
+            topoheight,
+            nonce: version.get_nonce()
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json!(history))
 }
 
-async fn get_asset<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetAssetParams = parse_params(body)?;
+async fn get_asset<S: Storage>(context: &Context, params: GetAssetParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let asset = storage.get_asset(&params.asset).await.context("Asset was not found")?;
     Ok(json!(asset))
 }
 
+async fn get_asset_supply<S: Storage>(context: &Context, params: GetAssetSupplyParams) -> Result<Value, InternalRpcError> {
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let storage = blockchain.get_chain_view().await;
+    let current_topoheight = storage.get_topoheight();
+    let topoheight = params.topoheight.unwrap_or(current_topoheight);
+    if topoheight > current_topoheight {
+        return Err(InternalRpcError::UnexpectedParams).context("Topoheight cannot be greater than current chain topoheight")?
+    }
+
+    if let Some(pruned_topoheight) = storage.get_pruned_topoheight().await.context("Error while retrieving pruned topoheight")? {
+        if topoheight < pruned_topoheight {
+            return Err(BlockchainError::PrunedData(topoheight, pruned_topoheight).into())
+        }
+    }
+
+    let asset = storage.get_asset(&params.asset).await.context("Asset was not found")?;
+    if asset.get_topoheight() > topoheight {
+        return Err(InternalRpcError::UnexpectedParams).context("Asset was not registered at this topoheight")?
+    }
+
+    // Only XELIS is emitted through block rewards for now
+    let emitted = if *params.asset == XELIS_ASSET {
+        storage.get_supply_at_topo_height(topoheight).await.context("Error while retrieving supply")?
+    } else {
+        0
+    };
+
+    let burned = storage.get_burned_supply_at_maximum_topoheight(&params.asset, topoheight).await
+        .context("Error while retrieving burned supply")?
+        .map(|(_, burned)| burned)
+        .unwrap_or(0);
+
+    Ok(json!(GetAssetSupplyResult {
+        asset: params.asset,
+        topoheight,
+        supply: emitted.saturating_sub(burned),
+        burned
+    }))
+}
+
 const MAX_ASSETS: usize = 100;
 
-async fn get_assets<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetAssetsParams = parse_params(body)?;
+async fn get_assets<S: Storage>(context: &Context, params: GetAssetsParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
     let maximum = if let Some(maximum) = params.maximum {
         if maximum > MAX_ASSETS {
@@ -644,21 +930,59 @@ async fn get_assets<S: Storage>(context: &Context, body: Value) -> Result<Value,
         MAX_ASSETS
     };
     let skip = params.skip.unwrap_or(0);
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let min = params.minimum_topoheight.unwrap_or(0);
-    let max =  params.maximum_topoheight.unwrap_or_else(|| blockchain.get_topo_height());
-    let assets = storage.get_partial_assets(maximum, skip, min, max).await
+    let max =  params.maximum_topoheight.unwrap_or_else(|| storage.get_topoheight());
+    let assets = storage.get_partial_assets(maximum, skip, min, max, params.after.as_ref()).await
         .context("Error while retrieving registered assets")?;
 
     Ok(json!(assets))
 }
 
+const MAX_ASSET_HOLDERS: usize = 100;
+// retrieve the accounts having a balance for an asset, most recently active first
+async fn get_asset_holders<S: Storage>(context: &Context, params: GetAssetHoldersParams<'_>) -> Result<Value, InternalRpcError> {
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let maximum = if let Some(maximum) = params.maximum {
+        if maximum > MAX_ASSET_HOLDERS {
+            return Err(InternalRpcError::InvalidJSONRequest).context(format!("Maximum asset holders requested cannot be greater than {}", MAX_ASSET_HOLDERS))?
+        }
+        maximum
+    } else {
+        MAX_ASSET_HOLDERS
+    };
+    let skip = params.skip.unwrap_or(0);
+
+    let storage = blockchain.get_chain_view().await;
+    if !storage.has_asset(&params.asset).await.context("Error while checking if asset exists")? {
+        return Err(InternalRpcError::InvalidJSONRequest).context(format!("Asset {} not found", params.asset))?
+    }
+
+    let holder_count = storage.count_asset_holders(&params.asset).await.context("Error while counting asset holders")?;
+    let mainnet = storage.is_mainnet();
+    let mut accounts = Vec::new();
+    for (key, last_topoheight) in storage.get_asset_holders(&params.asset, skip, maximum).await.context("Error while retrieving asset holders")? {
+        let registered_at_topoheight = storage.get_account_registration_topoheight(&key).await.context("Error while retrieving registration topoheight")?;
+        accounts.push(AssetHolder {
+            address: Cow::Owned(key.to_address(mainnet)),
+            registered_at_topoheight,
+            last_topoheight
+        });
+    }
+
+    Ok(json!(GetAssetHoldersResult {
+        asset: params.asset,
+        holder_count,
+        accounts
+    }))
+}
+
 async fn count_assets<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     if body != Value::Null {
         return Err(InternalRpcError::UnexpectedParams)
     }
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let count = storage.count_assets().await.context("Error while retrieving assets count")?;
     Ok(json!(count))
 }
@@ -668,7 +992,7 @@ async fn count_accounts<S: Storage>(context: &Context, body: Value) -> Result<Va
         return Err(InternalRpcError::UnexpectedParams)
     }
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let count = storage.count_accounts().await.context("Error while retrieving accounts count")?;
     Ok(json!(count))
 }
@@ -678,13 +1002,48 @@ async fn count_transactions<S: Storage>(context: &Context, body: Value) -> Resul
         return Err(InternalRpcError::UnexpectedParams)
     }
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let count = storage.count_transactions().await.context("Error while retrieving transactions count")?;
     Ok(json!(count))
 }
 
-async fn submit_transaction<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: SubmitTransactionParams = parse_params(body)?;
+// Retries using the same idempotency key get the response of the first request
+async fn submit_transaction<S: Storage>(context: &Context, submissions: &IdempotencyCache, params: SubmitTransactionParams) -> Result<Value, InternalRpcError> {
+    match params.idempotency_key {
+        Some(key) => {
+            if key.is_empty() || key.len() > IDEMPOTENCY_KEY_MAX_LEN {
+                return Err(InternalRpcError::InvalidJSONRequest).context(format!("Idempotency key must be between 1 and {} characters", IDEMPOTENCY_KEY_MAX_LEN))?
+            }
+
+            // Keys are namespaced by client to not interfere between them
+            let client = context.get_optional::<ClientIp>().map(|client| client.0);
+            let blockchain: Arc<Blockchain<S>> = Arc::clone(context.get()?);
+            let payload = hash(params.data.as_bytes());
+            submissions.execute(client, key, payload, async move { add_transaction(&blockchain, params.data).await }).await
+        },
+        None => {
+            let blockchain: &Arc<Blockchain<S>> = context.get()?;
+            add_transaction(blockchain, params.data).await
+        }
+    }
+}
+
+async fn add_transaction<S: Storage>(blockchain: &Blockchain<S>, data: String) -> Result<Value, InternalRpcError> {
+    // x2 because of hex encoding
+    if data.len() > MAX_TRANSACTION_SIZE * 2 {
+        return Err(InternalRpcError::InvalidJSONRequest).context(format!("Transaction size cannot be greater than {}", human_bytes(MAX_TRANSACTION_SIZE as f64)))?
+    }
+
+    let transaction = Transaction::from_hex(data)
+        .map_err(|err| InternalRpcError::InvalidParamsAny(err.into()))?;
+
+    blockchain.add_tx_to_mempool(transaction, true).await?;
+
+    Ok(json!(true))
+}
+
+// Verify and execute a transaction against the current chain state without committing anything
+async fn simulate_transaction<S: Storage>(context: &Context, params: SimulateTransactionParams) -> Result<Value, InternalRpcError> {
     // x2 because of hex encoding
     if params.data.len() > MAX_TRANSACTION_SIZE * 2 {
         return Err(InternalRpcError::InvalidJSONRequest).context(format!("Transaction size cannot be greater than {}", human_bytes(MAX_TRANSACTION_SIZE as f64)))?
@@ -692,26 +1051,61 @@ async fn submit_transaction<S: Storage>(context: &Context, body: Value) -> Resul
 
     let transaction = Transaction::from_hex(params.data)
         .map_err(|err| InternalRpcError::InvalidParamsAny(err.into()))?;
+    let hash = transaction.hash();
 
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    blockchain.add_tx_to_mempool(transaction, true).await?;
+    let storage = blockchain.get_chain_view().await;
+    let mut state = StateOverlay::new(&*storage, storage.get_topoheight());
+    let result = blockchain.simulate_tx_with_storage(&*storage, &transaction, &hash, &mut state).await;
 
-    Ok(json!(true))
+    let mainnet = storage.is_mainnet();
+    let source = transaction.get_source();
+    let response = match result {
+        Ok(()) => SimulateTransactionResult {
+            accepted: true,
+            error: None,
+            fee: transaction.get_fee(),
+            nonce: state.get_nonce(source).unwrap_or(transaction.get_nonce() + 1),
+            balances: state.get_balances()
+                .map(|(key, asset, balance)| SimulatedBalance {
+                    address: Cow::Owned(key.as_address(mainnet)),
+                    asset: Cow::Borrowed(asset),
+                    balance: CiphertextCache::Decompressed(balance.clone())
+                })
+                .collect()
+        },
+        Err(e) => {
+            debug!("Simulated transaction {} would be rejected: {}", hash, e);
+            // Nothing is applied, report the current nonce of the sender
+            let nonce = storage.get_nonce_at_maximum_topoheight(source, storage.get_topoheight()).await
+                .context("Error while retrieving nonce")?
+                .map(|(_, v)| v.get_nonce())
+                .unwrap_or(0);
+
+            SimulateTransactionResult {
+                accepted: false,
+                error: Some(e.to_string()),
+                fee: transaction.get_fee(),
+                nonce,
+                balances: Vec::new()
+            }
+        }
+    };
+
+    Ok(json!(response))
 }
 
-async fn get_transaction<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetTransactionParams = parse_params(body)?;
+async fn get_transaction<S: Storage>(context: &Context, params: GetTransactionParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let mempool = blockchain.get_mempool().read().await;
 
     get_transaction_response_for_hash(&*storage, &mempool, &params.hash).await
 }
 
-async fn get_transaction_executor<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetTransactionExecutorParams = parse_params(body)?;
+async fn get_transaction_executor<S: Storage>(context: &Context, params: GetTransactionExecutorParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
 
     let block_executor = storage.get_block_executor_for_tx(&params.hash)?;
     let block_topoheight = storage.get_topo_height_for_hash(&block_executor).await?;
@@ -724,6 +1118,79 @@ async fn get_transaction_executor<S: Storage>(context: &Context, body: Value) ->
     ))
 }
 
+async fn get_block_executed_transactions<S: Storage>(context: &Context, params: GetBlockExecutedTransactionsParams<'_>) -> Result<Value, InternalRpcError> {
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let storage = blockchain.get_chain_view().await;
+    let transactions = get_block_transactions_execution(&*storage, &params.hash).await?;
+    Ok(json!(transactions))
+}
+
+async fn get_tx_merkle_proof<S: Storage>(context: &Context, params: GetTxMerkleProofParams<'_>) -> Result<Value, InternalRpcError> {
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let storage = blockchain.get_chain_view().await;
+
+    let block_hash = match params.block_hash {
+        Some(hash) => hash.into_owned(),
+        None => storage.get_block_executor_for_tx(&params.tx_hash)?
+    };
+    let header = storage.get_block_header_by_hash(&block_hash).await?;
+
+    let txs_hashes: Vec<Hash> = header.get_txs_hashes().iter().cloned().collect();
+    let proof = header.get_txs_hashes().get_index_of(params.tx_hash.as_ref())
+        .and_then(|index| MerkleProof::new(&txs_hashes, index))
+        .ok_or_else(|| BlockchainError::TxNotInBlock(params.tx_hash.into_owned(), block_hash.clone()))?;
+
+    Ok(json!(GetTxMerkleProofResult {
+        block_version: header.get_version(),
+        txs_merkle_root: compute_txs_merkle_root(&txs_hashes),
+        block_hash: Cow::Owned(block_hash),
+        proof
+    }))
+}
+
+// Snapshot of our peers with the topology they advertised, only one hop is known
+async fn p2p_topology<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let p2p = { blockchain.get_p2p().read().await.clone() };
+    match p2p.as_ref() {
+        Some(p2p) => {
+            let now = get_current_time_in_seconds();
+            let hide_ip = blockchain.hide_peer_ips();
+            let peers_availables = p2p.get_peer_list().get_cloned_peers().await;
+            let mut peers = Vec::new();
+            // Like get_peers, peers that don't want to be shared are hidden
+            for peer in peers_availables.iter().filter(|p| p.sharable()) {
+                let topology = peer.get_topology().await;
+                let addr = peer.get_connection().get_address();
+                peers.push(P2pTopologyPeer {
+                    id: peer.get_id(),
+                    addr: if hide_ip { Cow::Owned(mask_peer_address(addr)) } else { Cow::Borrowed(addr) },
+                    tag: Cow::Borrowed(peer.get_node_tag()),
+                    version: Cow::Borrowed(peer.get_version()),
+                    topoheight: peer.get_topoheight(),
+                    height: peer.get_height(),
+                    latency: peer.get_latency(),
+                    advertised_peers_count: topology.as_ref().map(|entry| entry.get_topology().get_peers_count()),
+                    advertised_tag: topology.as_ref().and_then(|entry| entry.get_topology().get_node_tag().clone()),
+                    last_topology_update: topology.as_ref().map(|entry| entry.get_updated_at()),
+                    is_stale: topology.as_ref().map_or(false, |entry| entry.is_stale(now))
+                });
+            }
+
+            Ok(json!(P2pTopologyResult {
+                peer_id: p2p.get_peer_id(),
+                tag: Cow::Borrowed(p2p.get_tag()),
+                peers
+            }))
+        },
+        None => Err(InternalRpcError::InvalidParamsAny(ApiError::NoP2p.into()))
+    }
+}
+
 async fn p2p_status<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     if body != Value::Null {
         return Err(InternalRpcError::UnexpectedParams)
@@ -755,28 +1222,20 @@ async fn p2p_status<S: Storage>(context: &Context, body: Value) -> Result<Value,
     }
 }
 
-async fn get_peers<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    if body != Value::Null {
-        return Err(InternalRpcError::UnexpectedParams)
-    }
+async fn get_peers<S: Storage>(context: &Context, params: GetPeersParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
     let p2p = { blockchain.get_p2p().read().await.clone() };
     match p2p.as_ref() {
         Some(p2p) => {
             let peer_list = p2p.get_peer_list();
+            let hide_ip = blockchain.hide_peer_ips();
             let mut peers = Vec::new();
             let peers_availables = peer_list.get_cloned_peers().await;
             let total_peers = peers_availables.len();
-            let mut sharable_peers = 0;
             for p in peers_availables.iter().filter(|p| p.sharable()) {
-                peers.push(get_peer_entry(p).await);
-                sharable_peers += 1;
+                peers.push(get_peer_entry(p, hide_ip).await);
             }
-            Ok(json!(GetPeersResponse {
-                peers,
-                total_peers,
-                hidden_peers: total_peers - sharable_peers,
-            }))
+            Ok(json!(build_peers_response(&params, peers, total_peers)))
         },
         None => Err(InternalRpcError::InvalidParamsAny(ApiError::NoP2p.into()))
     }
@@ -787,7 +1246,8 @@ async fn get_mempool<S: Storage>(context: &Context, body: Value) -> Result<Value
         return Err(InternalRpcError::UnexpectedParams)
     }
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
+    blockchain.validate_deferred_mempool_txs(&*storage).await;
     let mempool = blockchain.get_mempool().read().await;
     let mut transactions: Vec<Value> = Vec::new();
     for (hash, sorted_tx) in mempool.get_txs() {
@@ -797,14 +1257,17 @@ async fn get_mempool<S: Storage>(context: &Context, body: Value) -> Result<Value
     Ok(json!(transactions))
 }
 
-async fn get_blocks_at_height<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetBlocksAtHeightParams = parse_params(body)?;
+async fn get_blocks_at_height<S: Storage>(context: &Context, params: GetBlocksAtHeightParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
 
     let mut blocks = Vec::new();
-    for hash in storage.get_blocks_at_height(params.height).await.context("Error while retrieving blocks at height")? {
-        blocks.push(get_block_response_for_hash(&blockchain, &storage, &hash, params.include_txs).await?)
+    for block in dag_height::get_dag_blocks_at_height(&*storage, params.height, params.include_orphaned).await? {
+        blocks.push(BlockAtHeightResponse {
+            block: get_block_response_for_hash(&blockchain, &storage, &block.hash, params.include_txs).await?,
+            is_ordered: block.topoheight.is_some(),
+            is_tip: block.is_tip
+        });
     }
     Ok(json!(blocks))
 }
@@ -814,23 +1277,62 @@ async fn get_tips<S: Storage>(context: &Context, body: Value) -> Result<Value, I
         return Err(InternalRpcError::UnexpectedParams)
     }
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let tips = storage.get_tips().await.context("Error while retrieving tips")?;
     Ok(json!(tips))
 }
 
+async fn get_tips_info<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let storage = blockchain.get_chain_view().await;
+    let height = storage.get_top_state().height;
+    let stable_height = storage.get_top_state().stable_height;
+
+    let candidates = blockchain.get_tips_candidates(&storage).await.context("Error while retrieving tips")?;
+    let selected_tips = blockdag::select_tips(&candidates, height);
+
+    let mut tips = Vec::with_capacity(candidates.len());
+    for candidate in candidates {
+        let topoheight = if storage.is_block_topological_ordered(&candidate.hash).await {
+            Some(storage.get_topo_height_for_hash(&candidate.hash).await.context("Error while retrieving topo height")?)
+        } else {
+            None
+        };
+
+        tips.push(TipInfo {
+            selected: selected_tips.contains(&candidate.hash),
+            hash: Cow::Owned(candidate.hash),
+            height: candidate.height,
+            topoheight,
+            difficulty: candidate.difficulty,
+            cumulative_difficulty: candidate.cumulative_difficulty,
+            distance_from_mainchain: candidate.distance_from_mainchain,
+            distance_to_stable_height: candidate.height.saturating_sub(stable_height)
+        });
+    }
+
+    Ok(json!(GetTipsInfoResult {
+        height,
+        stable_height,
+        tips,
+        selected_tips
+    }))
+}
+
 const MAX_DAG_ORDER: u64 = 64;
 // get dag order based on params
 // if no params found, get order of last 64 blocks
-async fn get_dag_order<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetTopoHeightRangeParams = parse_params(body)?;
+async fn get_dag_order<S: Storage>(context: &Context, params: GetTopoHeightRangeParams) -> Result<Value, InternalRpcError> {
 
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let current = blockchain.get_topo_height();
+    let storage = blockchain.get_chain_view().await;
+    let current = storage.get_topoheight();
     let (start_topoheight, end_topoheight) = get_range(params.start_topoheight, params.end_topoheight, MAX_DAG_ORDER, current)?;
     let count = end_topoheight - start_topoheight;
 
-    let storage = blockchain.get_storage().read().await;
     let mut order = Vec::with_capacity(count as usize);
     for i in start_topoheight..=end_topoheight {
         let hash = storage.get_hash_at_topo_height(i).await.context("Error while retrieving hash at topo height")?;
@@ -868,14 +1370,13 @@ fn get_range(start: Option<u64>, end: Option<u64>, maximum: u64, current: u64) -
 
 // get blocks between range of topoheight
 // if no params found, get last 20 blocks header
-async fn get_blocks_range_by_topoheight<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetTopoHeightRangeParams = parse_params(body)?;
+async fn get_blocks_range_by_topoheight<S: Storage>(context: &Context, params: GetTopoHeightRangeParams) -> Result<Value, InternalRpcError> {
 
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let current_topoheight = blockchain.get_topo_height();
+    let storage = blockchain.get_chain_view().await;
+    let current_topoheight = storage.get_topoheight();
     let (start_topoheight, end_topoheight) = get_range(params.start_topoheight, params.end_topoheight, MAX_BLOCKS, current_topoheight)?;
 
-    let storage = blockchain.get_storage().read().await;
     let mut blocks = Vec::with_capacity((end_topoheight - start_topoheight) as usize);
     for i in start_topoheight..=end_topoheight {
         let hash = storage.get_hash_at_topo_height(i).await.context("Error while retrieving hash at topo height")?;
@@ -889,13 +1390,12 @@ async fn get_blocks_range_by_topoheight<S: Storage>(context: &Context, body: Val
 // get blocks between range of height
 // if no params found, get last 20 blocks header
 // you can only request 
-async fn get_blocks_range_by_height<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetHeightRangeParams = parse_params(body)?;
+async fn get_blocks_range_by_height<S: Storage>(context: &Context, params: GetHeightRangeParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let current_height = blockchain.get_height();
+    let storage = blockchain.get_chain_view().await;
+    let current_height = storage.get_top_state().height;
     let (start_height, end_height) = get_range(params.start_height, params.end_height, MAX_BLOCKS, current_height)?;
 
-    let storage = blockchain.get_storage().read().await;
     let mut blocks = Vec::with_capacity((end_height - start_height) as usize);
     for i in start_height..=end_height {
         let blocks_at_height = storage.get_blocks_at_height(i).await.context("Error while retrieving blocks at height")?;
@@ -908,11 +1408,40 @@ async fn get_blocks_range_by_height<S: Storage>(context: &Context, body: Value)
     Ok(json!(blocks))
 }
 
+// get up to 512 block headers with their DAG context starting at a topoheight
+// less headers are returned if the response size budget is reached
+async fn get_headers_range<S: Storage>(context: &Context, params: GetHeadersRangeParams) -> Result<Value, InternalRpcError> {
+    if params.count == 0 || params.count > HEADERS_RANGE_MAX_COUNT {
+        return Err(InternalRpcError::InvalidJSONRequest).context(format!("Invalid headers count requested, received {} but maximum is {}", params.count, HEADERS_RANGE_MAX_COUNT))?
+    }
+
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let storage = blockchain.get_chain_view().await;
+    if params.start_topoheight > storage.get_topoheight() {
+        return Err(InternalRpcError::InvalidJSONRequest).context(format!("Invalid start topoheight {}, current is {}", params.start_topoheight, storage.get_topoheight()))?
+    }
+
+    let headers = headers::get_headers_range(&*storage, params.start_topoheight, params.count, HEADERS_RANGE_MAX_SIZE).await
+        .context("Error while retrieving headers range")?;
+
+    let entries = headers.iter().map(|entry| {
+        let header = entry.get_header();
+        HeadersRangeEntry {
+            hash: Cow::Owned(header.hash()),
+            topoheight: entry.get_topoheight(),
+            difficulty: *entry.get_difficulty(),
+            cumulative_difficulty: *entry.get_cumulative_difficulty(),
+            data: header.to_hex()
+        }
+    }).collect::<Vec<_>>();
+
+    Ok(json!(entries))
+}
+
 const MAX_TXS: usize = 20;
 // get up to 20 transactions at once
 // if a tx hash is not present, we keep the order and put json "null" value
-async fn get_transactions<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetTransactionsParams = parse_params(body)?;
+async fn get_transactions<S: Storage>(context: &Context, params: GetTransactionsParams) -> Result<Value, InternalRpcError> {
 
     let hashes = params.tx_hashes;
     if  hashes.len() > MAX_TXS {
@@ -920,7 +1449,7 @@ async fn get_transactions<S: Storage>(context: &Context, body: Value) -> Result<
     }
 
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let mempool = blockchain.get_mempool().read().await;
     let mut transactions: Vec<Option<Value>> = Vec::with_capacity(hashes.len());
     for hash in hashes {
@@ -939,8 +1468,7 @@ async fn get_transactions<S: Storage>(context: &Context, body: Value) -> Result<
 
 const MAX_HISTORY: usize = 20;
 // retrieve all history changes for an account on an asset
-async fn get_account_history<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetAccountHistoryParams = parse_params(body)?;
+async fn get_account_history<S: Storage>(context: &Context, params: GetAccountHistoryParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
     if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
         return Err(InternalRpcError::InvalidParamsAny(BlockchainError::InvalidNetwork.into()))
@@ -948,11 +1476,11 @@ async fn get_account_history<S: Storage>(context: &Context, body: Value) -> Resu
 
     let key = params.address.get_public_key();
     let minimum_topoheight = params.minimum_topoheight.unwrap_or(0);
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let pruned_topoheight = storage.get_pruned_topoheight().await.context("Error while retrieving pruned topoheight")?.unwrap_or(0);
     let mut version = if let Some(topo) = params.maximum_topoheight {
         if topo < pruned_topoheight {
-            return Err(InternalRpcError::InvalidParams("Maximum topoheight is lower than pruned topoheight"));
+            return Err(BlockchainError::PrunedData(topo, pruned_topoheight).into())
         }
         storage.get_balance_at_maximum_topoheight(key, &params.asset, topo).await.context(format!("Error while retrieving balance at topo height {topo}"))?
     } else {
@@ -984,7 +1512,8 @@ async fn get_account_history<S: Storage>(context: &Context, body: Value) -> Resu
                                 topoheight: topo,
                                 hash: hash.clone(),
                                 history_type: AccountHistoryType::DevFee { reward: dev_fee },
-                                block_timestamp: block_header.get_timestamp()
+                                block_timestamp: block_header.get_timestamp().into(),
+                                block_timestamp_human: format_timestamp_millis(block_header.get_timestamp())
                             });
                         }
                         reward -= dev_fee;
@@ -996,7 +1525,8 @@ async fn get_account_history<S: Storage>(context: &Context, body: Value) -> Resu
                             topoheight: topo,
                             hash: hash.clone(),
                             history_type,
-                            block_timestamp: block_header.get_timestamp()
+                            block_timestamp: block_header.get_timestamp().into(),
+                            block_timestamp_human: format_timestamp_millis(block_header.get_timestamp())
                         });
                     }
                 }
@@ -1005,7 +1535,7 @@ async fn get_account_history<S: Storage>(context: &Context, body: Value) -> Resu
             // Reverse the order of transactions to get the latest first
             for tx_hash in block_header.get_transactions().iter().rev() {
                 trace!("Searching tx {} in block {}", tx_hash, hash);
-                let tx = storage.get_transaction(tx_hash).await.context(format!("Error while retrieving transaction {tx_hash} from block {hash}"))?;
+                let tx = storage.get_transaction_with_metadata(tx_hash).await.context(format!("Error while retrieving transaction {tx_hash} from block {hash}"))?.tx;
                 let is_sender = *tx.get_source() == *key;
                 match tx.get_data() {
                     TransactionType::Transfers(transfers) => {
@@ -1018,7 +1548,8 @@ async fn get_account_history<S: Storage>(context: &Context, body: Value) -> Resu
                                         history_type: AccountHistoryType::Incoming {
                                             from: tx.get_source().as_address(blockchain.get_network().is_mainnet())
                                         },
-                                        block_timestamp: block_header.get_timestamp()
+                                        block_timestamp: block_header.get_timestamp().into(),
+                                        block_timestamp_human: format_timestamp_millis(block_header.get_timestamp())
                                     });
                                 }
 
@@ -1029,7 +1560,8 @@ async fn get_account_history<S: Storage>(context: &Context, body: Value) -> Resu
                                         history_type: AccountHistoryType::Outgoing {
                                             to: transfer.get_destination().as_address(blockchain.get_network().is_mainnet())
                                         },
-                                        block_timestamp: block_header.get_timestamp()
+                                        block_timestamp: block_header.get_timestamp().into(),
+                                        block_timestamp_human: format_timestamp_millis(block_header.get_timestamp())
                                     });
                                 }
                             }
@@ -1042,11 +1574,27 @@ async fn get_account_history<S: Storage>(context: &Context, body: Value) -> Resu
                                     topoheight: topo,
                                     hash: tx_hash.clone(),
                                     history_type: AccountHistoryType::Burn { amount: payload.amount },
-                                    block_timestamp: block_header.get_timestamp()
+                                    block_timestamp: block_header.get_timestamp().into(),
+                                    block_timestamp_human: format_timestamp_millis(block_header.get_timestamp())
                                 });
                             }
                         }
                     }
+                    TransactionType::AssetCreation(_) => {
+                        // Registration cost is burned from the native asset
+                        if params.asset == XELIS_ASSET && is_sender {
+                            history.push(AccountHistoryEntry {
+                                topoheight: topo,
+                                hash: tx_hash.clone(),
+                                history_type: AccountHistoryType::AssetCreation {
+                                    asset: AssetCreationPayload::get_asset(tx_hash),
+                                    amount: ASSET_CREATION_BURN_AMOUNT
+                                },
+                                block_timestamp: block_header.get_timestamp().into(),
+                                block_timestamp_human: format_timestamp_millis(block_header.get_timestamp())
+                            });
+                        }
+                    }
                 }
             }
 
@@ -1069,25 +1617,24 @@ async fn get_account_history<S: Storage>(context: &Context, body: Value) -> Resu
     Ok(json!(history))
 }
 
-async fn get_account_assets<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetAccountAssetsParams = parse_params(body)?;
+async fn get_account_assets<S: Storage>(context: &Context, params: GetAccountAssetsParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
     if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
         return Err(InternalRpcError::InvalidParamsAny(BlockchainError::InvalidNetwork.into()))
     }
 
     let key = params.address.get_public_key();
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let assets = storage.get_assets_for(key).await.context("Error while retrieving assets for account")?;
     Ok(json!(assets))
 }
 
 const MAX_ACCOUNTS: usize = 100;
 // retrieve all available accounts (each account got at least one interaction on chain)
-async fn get_accounts<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetAccountsParams = parse_params(body)?;
+async fn get_accounts<S: Storage>(context: &Context, params: GetAccountsParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let topoheight = blockchain.get_topo_height();
+    let storage = blockchain.get_chain_view().await;
+    let topoheight = storage.get_topoheight();
     let maximum = if let Some(maximum) = params.maximum {
         if maximum > MAX_ACCOUNTS {
             return Err(InternalRpcError::InvalidJSONRequest).context(format!("Maximum accounts requested cannot be greater than {}", MAX_ACCOUNTS))?
@@ -1119,9 +1666,9 @@ async fn get_accounts<S: Storage>(context: &Context, body: Value) -> Result<Valu
         topoheight
     };
 
-    let storage = blockchain.get_storage().read().await;
     let mainnet = storage.is_mainnet();
-    let accounts = storage.get_partial_keys(maximum, skip, minimum_topoheight, maximum_topoheight).await
+    let after = params.after.map(|address| address.to_public_key());
+    let accounts = storage.get_partial_keys(maximum, skip, minimum_topoheight, maximum_topoheight, after.as_ref()).await
         .context("Error while retrieving accounts")?
         .into_iter().map(|key| key.to_address(mainnet)).collect::<Vec<_>>();
 
@@ -1129,13 +1676,12 @@ async fn get_accounts<S: Storage>(context: &Context, body: Value) -> Result<Valu
 }
 
 // Check if the account is registered on chain or not
-async fn is_account_registered<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: IsAccountRegisteredParams = parse_params(body)?;
+async fn is_account_registered<S: Storage>(context: &Context, params: IsAccountRegisteredParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let key = params.address.get_public_key();
     let registered = if params.in_stable_height {
-        storage.is_account_registered_below_topoheight(key, blockchain.get_stable_topoheight()).await
+        storage.is_account_registered_below_topoheight(key, storage.get_top_state().stable_topoheight).await
             .context("Error while checking if account is registered in stable height")?
     } else {
         storage.is_account_registered(key).await
@@ -1146,20 +1692,18 @@ async fn is_account_registered<S: Storage>(context: &Context, body: Value) -> Re
 }
 
 // Search the account registration topoheight
-async fn get_account_registration_topoheight<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetAccountRegistrationParams = parse_params(body)?;
+async fn get_account_registration_topoheight<S: Storage>(context: &Context, params: GetAccountRegistrationParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let key = params.address.get_public_key();
     let topoheight = storage.get_account_registration_topoheight(key).await.context("Error while retrieving registration topoheight")?;
     Ok(json!(topoheight))
 }
 
 // Check if the asked TX is executed in the block
-async fn is_tx_executed_in_block<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: IsTxExecutedInBlockParams = parse_params(body)?;
+async fn is_tx_executed_in_block<S: Storage>(context: &Context, params: IsTxExecutedInBlockParams) -> Result<Value, InternalRpcError> {
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     Ok(json!(storage.is_tx_executed_in_block(&params.tx_hash, &params.block_hash).context("Error while checking if tx was executed in block")?))
 }
 
@@ -1178,7 +1722,7 @@ async fn get_size_on_disk<S: Storage>(context: &Context, body: Value) -> Result<
         return Err(InternalRpcError::UnexpectedParams)
     }
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let storage = blockchain.get_storage().read().await;
+    let storage = blockchain.get_chain_view().await;
     let size_bytes = storage.get_size_on_disk().await.context("Error while retrieving size on disk")?;
     let size_formatted = human_bytes(size_bytes as f64);
 
@@ -1189,8 +1733,7 @@ async fn get_size_on_disk<S: Storage>(context: &Context, body: Value) -> Result<
 }
 
 // Retrieve the mempool cache for an account
-async fn get_mempool_cache<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: GetMempoolCacheParams = parse_params(body)?;
+async fn get_mempool_cache<S: Storage>(context: &Context, params: GetMempoolCacheParams) -> Result<Value, InternalRpcError> {
     if !params.address.is_normal() {
         return Err(InternalRpcError::InvalidParamsAny(ApiError::ExpectedNormalAddress.into()))    
     }
@@ -1213,7 +1756,7 @@ async fn get_difficulty<S: Storage>(context: &Context, body: Value) -> Result<Va
     }
 
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
-    let difficulty = blockchain.get_difficulty().await;
+    let difficulty = blockchain.get_top_state().difficulty;
     let hashrate = difficulty / BLOCK_TIME;
     let hashrate_formatted = format_hashrate(hashrate.into());
     Ok(json!(GetDifficultyResult {
@@ -1223,20 +1766,175 @@ async fn get_difficulty<S: Storage>(context: &Context, body: Value) -> Result<Va
     }))
 }
 
-async fn validate_address<S: Storage>(_: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: ValidateAddressParams = parse_params(body)?;
+// Sample the difficulty every `step` blocks to chart the network hashrate
+async fn get_difficulty_history<S: Storage>(context: &Context, params: GetDifficultyHistoryParams) -> Result<Value, InternalRpcError> {
+    if params.step == 0 {
+        return Err(InternalRpcError::InvalidJSONRequest).context("Step must be greater than 0")?
+    }
+
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let storage = blockchain.get_chain_view().await;
+    let topoheight = storage.get_topoheight();
+    if params.start_topoheight > params.end_topoheight || params.end_topoheight > topoheight {
+        return Err(InternalRpcError::InvalidJSONRequest).context(format!("Invalid range {} to {}, current topoheight is {}", params.start_topoheight, params.end_topoheight, topoheight))?
+    }
+
+    let count = difficulty_history::count_samples(params.start_topoheight, params.end_topoheight, params.step);
+    if count > DIFFICULTY_HISTORY_MAX_SAMPLES {
+        return Err(InternalRpcError::InvalidJSONRequest).context(format!("Too many samples requested: {}, maximum is {}", count, DIFFICULTY_HISTORY_MAX_SAMPLES))?
+    }
+
+    let samples = difficulty_history::get_difficulty_history(&*storage, params.start_topoheight, params.end_topoheight, params.step).await
+        .context("Error while retrieving difficulty history")?;
+
+    let entries = samples.into_iter().map(|sample| {
+        let hashrate = sample.difficulty / BLOCK_TIME;
+        DifficultyHistoryEntry {
+            topoheight: sample.topoheight,
+            hash: Cow::Owned(sample.hash),
+            timestamp: sample.timestamp.into(),
+            timestamp_human: format_timestamp_millis(sample.timestamp),
+            difficulty: sample.difficulty,
+            solve_time: sample.solve_time,
+            hashrate,
+            hashrate_formatted: format_hashrate(hashrate.into()),
+            covariance: sample.covariance
+        }
+    }).collect::<Vec<_>>();
+
+    Ok(json!(entries))
+}
+
+// Time spent by the node in each stage of the blocks processed since it started
+async fn get_block_processing_stats<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    Ok(json!(blockchain.get_block_processing_stats().await))
+}
+
+// Aggregate the emission, fees and blocks stats over a range for the explorers
+async fn get_chain_stats<S: Storage>(context: &Context, params: GetChainStatsParams) -> Result<Value, InternalRpcError> {
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let storage = blockchain.get_chain_view().await;
+    let topoheight = storage.get_topoheight();
+    if params.start_topoheight > params.end_topoheight || params.end_topoheight > topoheight {
+        return Err(InternalRpcError::InvalidJSONRequest).context(format!("Invalid range {} to {}, current topoheight is {}", params.start_topoheight, params.end_topoheight, topoheight))?
+    }
+
+    let count = params.end_topoheight - params.start_topoheight + 1;
+    if count > CHAIN_STATS_MAX_BLOCKS {
+        return Err(InternalRpcError::InvalidJSONRequest).context(format!("Too many blocks requested: {}, maximum is {}", count, CHAIN_STATS_MAX_BLOCKS))?
+    }
+
+    if let Some(pruned_topoheight) = storage.get_pruned_topoheight().await.context("Error while retrieving pruned topoheight")? {
+        if params.start_topoheight < pruned_topoheight {
+            return Err(BlockchainError::PrunedData(params.start_topoheight, pruned_topoheight).into())
+        }
+    }
+
+    let stats = chain_stats::get_chain_stats(&*storage, params.start_topoheight, params.end_topoheight).await
+        .context("Error while computing chain stats")?;
+
+    Ok(json!(GetChainStatsResult {
+        start_topoheight: params.start_topoheight,
+        end_topoheight: params.end_topoheight,
+        blocks_count: stats.blocks_count,
+        total_emission: stats.total_emission,
+        total_fees: stats.total_fees,
+        transfers_count: stats.transfers_count,
+        average_block_interval: stats.average_block_interval,
+        average_block_size: stats.average_block_size
+    }))
+}
+
+// Highest topoheight having a block timestamp under or equal to the one requested
+// A timestamp before the genesis block returns 0, a timestamp in the future returns the current topoheight
+async fn get_topoheight_at_timestamp<S: Storage>(context: &Context, params: GetTopoHeightAtTimestampParams) -> Result<Value, InternalRpcError> {
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let storage = blockchain.get_chain_view().await;
+    let topoheight = timestamp_search::find_topoheight_at_timestamp(&*storage, params.timestamp.as_millis(), storage.get_topoheight()).await
+        .context("Error while searching topoheight at timestamp")?;
 
+    Ok(json!(topoheight.unwrap_or(0)))
+}
+
+async fn get_block_count_between<S: Storage>(context: &Context, params: GetBlockCountBetweenParams) -> Result<Value, InternalRpcError> {
+    if params.timestamp_start > params.timestamp_end {
+        return Err(InternalRpcError::InvalidJSONRequest).context(format!("Invalid range {} to {}", params.timestamp_start, params.timestamp_end))?
+    }
+
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let storage = blockchain.get_chain_view().await;
+    let count = timestamp_search::count_blocks_between(&*storage, params.timestamp_start.as_millis(), params.timestamp_end.as_millis(), storage.get_topoheight()).await
+        .context("Error while counting blocks between timestamps")?;
+
+    Ok(json!(count))
+}
+
+async fn set_log_level<S: Storage>(_: &Context, params: SetLogLevelParams) -> Result<Value, InternalRpcError> {
+    let level = LogLevel::from_str(&params.level).map_err(|e| InternalRpcError::InvalidParamsAny(anyhow::anyhow!(e)))?;
+    match params.module {
+        Some(module) => {
+            info!("Log level set to {} for module {} through RPC", level, module);
+            logger::set_module_log_level(ModuleLogLevel { module, level });
+        },
+        None => {
+            info!("Log level set to {} through RPC", level);
+            logger::set_log_level(level);
+        }
+    };
+
+    Ok(json!(true))
+}
+
+async fn export_chain<S: Storage>(context: &Context, params: ExportChainParams<'_>) -> Result<Value, InternalRpcError> {
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    info!("Exporting chain to {} through RPC", params.path);
+    let count = bootstrap::export_chain(blockchain, &params.path, params.until_topoheight).await
+        .context("Error while exporting chain")?;
+
+    Ok(json!(count))
+}
+
+async fn reload_tls<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    match blockchain.get_rpc().read().await.as_ref() {
+        Some(rpc) => {
+            info!("Reloading TLS certificate through RPC");
+            rpc.reload_tls().context("Error while reloading TLS certificate")?;
+            Ok(json!(true))
+        },
+        None => Err(InternalRpcError::InvalidParamsAny(ApiError::NoTls.into()))
+    }
+}
+
+// Parse an address given as a string
+// An invalid character is reported with its position in the error data
+fn parse_address(address: &str) -> Result<Address, InternalRpcError> {
+    Address::from_str(address).map_err(|e| match e.downcast::<Bech32Error>() {
+        Ok(e) => BlockchainError::ErrorOnBech32(e).into(),
+        Err(e) => InternalRpcError::InvalidParamsAny(e)
+    })
+}
+
+async fn validate_address<S: Storage>(_: &Context, params: ValidateAddressParams) -> Result<Value, InternalRpcError> {
+    let address = parse_address(&params.address)?;
     Ok(json!(ValidateAddressResult {
-        is_valid: (params.address.is_normal() || (!params.address.is_normal() && params.allow_integrated))
-            && params.max_integrated_data_size.and_then(|size| params.address.get_extra_data().map(|data| data.size() <= size))
+        is_valid: (address.is_normal() || (!address.is_normal() && params.allow_integrated))
+            && params.max_integrated_data_size.and_then(|size| address.get_extra_data().map(|data| data.size() <= size))
             .unwrap_or(true),
-        is_integrated: !params.address.is_normal(),
+        is_integrated: !address.is_normal(),
     }))
 }
 
-async fn extract_key_from_address<S: Storage>(_: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: ExtractKeyFromAddressParams = parse_params(body)?;
-
+async fn extract_key_from_address<S: Storage>(_: &Context, params: ExtractKeyFromAddressParams) -> Result<Value, InternalRpcError> {
     if params.as_hex {
         Ok(json!(ExtractKeyFromAddressResult::Hex(params.address.get_public_key().to_hex())))
     } else {
@@ -1246,8 +1944,7 @@ async fn extract_key_from_address<S: Storage>(_: &Context, body: Value) -> Resul
 
 
 // Split an integrated address into its address and data
-async fn split_address<S: Storage>(_: &Context, body: Value) -> Result<Value, InternalRpcError> {
-    let params: SplitAddressParams = parse_params(body)?;
+async fn split_address<S: Storage>(_: &Context, params: SplitAddressParams) -> Result<Value, InternalRpcError> {
     let address = params.address;
 
     let (data, address) = address.extract_data();
@@ -1257,4 +1954,263 @@ async fn split_address<S: Storage>(_: &Context, body: Value) -> Result<Value, In
         address,
         integrated_data
     }))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use xelis_common::crypto::KeyPair;
+    use crate::{
+        core::storage::MemoryStorage,
+        testing::{connect, mine_block, TestNode}
+    };
+    use super::*;
+
+    fn peer_entry(id: u64, addr: &str, direction: Direction, height: u64) -> PeerEntry<'static> {
+        PeerEntry {
+            id,
+            addr: Cow::Owned(addr.parse().unwrap()),
+            local_port: 2125,
+            tag: Cow::Owned(None),
+            version: Cow::Owned(VERSION.to_owned()),
+            top_block_hash: Cow::Owned(Hash::zero()),
+            topoheight: height,
+            height,
+            last_ping: 0,
+            pruned_topoheight: None,
+            peers: Cow::Owned(HashMap::new()),
+            cumulative_difficulty: Cow::Owned(CumulativeDifficulty::from_u64(height)),
+            connected_on: 0,
+            direction,
+            priority: false,
+            latency: Some(50),
+            bytes_in: 0,
+            bytes_out: 0,
+            fail_count: 0,
+            object_requests_served: 0,
+            object_requests_dropped: 0,
+            chain_requests_served: 0
+        }
+    }
+
+    fn synthetic_peers() -> Vec<PeerEntry<'static>> {
+        vec![
+            peer_entry(1, "10.0.0.1:2125", Direction::Out, 100),
+            peer_entry(2, "10.0.0.2:2125", Direction::In, 200),
+            peer_entry(3, "10.0.0.3:2125", Direction::Out, 300),
+        ]
+    }
+
+    #[test]
+    fn test_get_peers_no_filter() {
+        let response = build_peers_response(&GetPeersParams::default(), synthetic_peers(), 4);
+        assert_eq!(response.peers.len(), 3);
+        assert_eq!(response.matching_peers, 3);
+        assert_eq!(response.total_peers, 4);
+        assert_eq!(response.hidden_peers, 1);
+    }
+
+    #[test]
+    fn test_get_peers_filters() {
+        let params = GetPeersParams { only_out: true, ..Default::default() };
+        let response = build_peers_response(&params, synthetic_peers(), 3);
+        assert_eq!(response.peers.iter().map(|p| p.id).collect::<Vec<_>>(), vec![1, 3]);
+
+        let params = GetPeersParams { only_out: true, min_height: Some(150), count_only: false };
+        let response = build_peers_response(&params, synthetic_peers(), 3);
+        assert_eq!(response.peers.iter().map(|p| p.id).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(response.hidden_peers, 0);
+    }
+
+    #[test]
+    fn test_get_peers_count_only() {
+        let params = GetPeersParams { min_height: Some(200), count_only: true, ..Default::default() };
+        let response = build_peers_response(&params, synthetic_peers(), 3);
+        assert!(response.peers.is_empty());
+        assert_eq!(response.matching_peers, 2);
+    }
+
+    #[test]
+    fn test_mask_peer_address() {
+        let masked = mask_peer_address(&"192.168.1.42:2125".parse().unwrap());
+        assert_eq!(masked, "192.168.1.0:0".parse().unwrap());
+
+        let masked = mask_peer_address(&"[2001:db8:85a3:8d3:1319:8a2e:370:7348]:2125".parse().unwrap());
+        assert_eq!(masked, "[2001:db8:85a3::]:0".parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_address_invalid_character() {
+        let address = KeyPair::new().get_public_key().to_address(false).to_string();
+        assert!(parse_address(&address).is_ok());
+
+        let mut invalid = address.clone();
+        invalid.replace_range(12..13, "b");
+        let err = parse_address(&invalid).unwrap_err();
+        assert_eq!(err.get_code(), BlockchainError::ErrorOnBech32(Bech32Error::InvalidChecksum).get_rpc_code());
+        assert_eq!(err.get_data(), Some(json!({ "position": 12 })));
+
+        // Not a bech32 error, no data to give
+        assert!(parse_address("xet:").unwrap_err().get_data().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_top_block_summary_parity() {
+        let node = TestNode::new(Network::Dev).await.unwrap();
+        let hash = mine_block(&node, Vec::new()).await.unwrap();
+        let blockchain = node.get_blockchain();
+
+        let mut context = Context::default();
+        context.store(Arc::clone(blockchain));
+        let summary = get_top_block_summary::<MemoryStorage>(&context, Value::Null).await.unwrap();
+        assert_eq!(summary["hash"], json!(hash));
+        assert_eq!(summary["topoheight"], json!(1));
+
+        let event = {
+            let storage = blockchain.get_storage().read().await;
+            let block = storage.get_block_by_hash(&hash).await.unwrap();
+            get_new_block_event(blockchain, &*storage, &hash, &block, block.size()).await.unwrap()
+        };
+        // Push and pull paths give the same data
+        assert_eq!(event["summary"], summary);
+        assert_eq!(event["miner"], summary["miner"]);
+
+        let event: NewBlockEvent<'static> = serde_json::from_value(event).unwrap();
+        assert_eq!(*event.block.hash, hash);
+        assert_eq!(event.summary.map(Cow::into_owned), blockchain.get_top_block_summary());
+
+        node.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_reads_during_block_application() {
+        let node = TestNode::new(Network::Dev).await.unwrap();
+        let blockchain = Arc::clone(node.get_blockchain());
+        let mut handler = RPCHandler::new(Arc::clone(&blockchain));
+        register_methods(&mut handler, false);
+        let handler = Arc::new(handler);
+        let miner = KeyPair::new();
+        let address = miner.get_public_key().to_address(false);
+
+        // Each block waits between its storage writes and the update of the top state
+        blockchain.set_block_application_delay(Duration::from_millis(50));
+        let applying = Arc::new(AtomicBool::new(false));
+        let done = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let (handler, applying, done) = (Arc::clone(&handler), Arc::clone(&applying), Arc::clone(&done));
+            tokio::spawn(async move {
+                let body = serde_json::to_vec(&json!([
+                    { "jsonrpc": "2.0", "id": 1, "method": "get_info" },
+                    { "jsonrpc": "2.0", "id": 2, "method": "get_balance", "params": { "address": address, "asset": XELIS_ASSET } },
+                    { "jsonrpc": "2.0", "id": 3, "method": "get_top_block" }
+                ])).unwrap();
+
+                let mut infos = Vec::new();
+                let mut raced = 0;
+                while !done.load(Ordering::Relaxed) {
+                    let during_application = applying.load(Ordering::Relaxed);
+                    let responses = handler.handle_request(&body).await.unwrap();
+
+                    // Every result carries the topoheight it was served from
+                    let info: GetInfoResult = serde_json::from_value(responses[0]["result"].clone()).unwrap();
+
+                    // The miner has no balance before the first block, then one at each block it mined
+                    let balance = &responses[1];
+                    if balance.get("error").is_none() {
+                        let result: GetBalanceResult = serde_json::from_value(balance["result"].clone()).unwrap();
+                        assert_eq!(result.topoheight, result.snapshot_topoheight);
+                    }
+
+                    // The top block can't be older than the snapshot read just before
+                    let top_block = responses[2]["result"]["topoheight"].as_u64().unwrap();
+                    assert!(top_block >= info.topoheight);
+                    infos.push(info);
+
+                    if during_application {
+                        raced += 1;
+                    }
+                    tokio::task::yield_now().await;
+                }
+                (infos, raced)
+            })
+        };
+
+        let miner = miner.get_public_key().compress();
+        for _ in 0..10 {
+            let header = blockchain.get_block_template(miner.clone()).await.unwrap();
+            let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+            applying.store(true, Ordering::Relaxed);
+            blockchain.add_new_block(block, false, false).await.unwrap();
+            applying.store(false, Ordering::Relaxed);
+        }
+        done.store(true, Ordering::Relaxed);
+
+        let (infos, raced) = reader.await.unwrap();
+        assert!(raced > 0);
+
+        // The chain is linear, the values of each snapshot are still the ones at its topoheight
+        let storage = blockchain.get_storage().read().await;
+        for info in infos {
+            assert_eq!(info.top_block_hash, storage.get_hash_at_topo_height(info.topoheight).await.unwrap());
+            assert_eq!(info.circulating_supply, storage.get_supply_at_topo_height(info.topoheight).await.unwrap());
+        }
+        drop(storage);
+
+        node.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_p2p_topology_snapshot() {
+        // First node is connected to both others, only the second one shares its topology
+        let first = TestNode::with_args(Network::Dev, &["--tag", "first", "--p2p-share-topology"]).await.unwrap();
+        let second = TestNode::with_args(Network::Dev, &["--tag", "second", "--p2p-share-topology"]).await.unwrap();
+        let third = TestNode::with_args(Network::Dev, &["--tag", "third"]).await.unwrap();
+        connect(&second, &first).await.unwrap();
+        connect(&third, &first).await.unwrap();
+
+        for node in [&second, &third] {
+            let p2p = node.get_blockchain().get_p2p().read().await.clone().unwrap();
+            p2p.broadcast_ping_with_peer_list().await;
+        }
+
+        let mut context = Context::default();
+        context.store(Arc::clone(first.get_blockchain()));
+        let mut result = None;
+        for _ in 0..50 {
+            let snapshot: P2pTopologyResult = serde_json::from_value(p2p_topology::<MemoryStorage>(&context, Value::Null).await.unwrap()).unwrap();
+            if snapshot.peers.iter().any(|peer| peer.advertised_peers_count.is_some()) {
+                result = Some(snapshot);
+                break
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let result = result.expect("topology of the second node");
+        assert_eq!(*result.tag, Some("first".to_owned()));
+        assert_eq!(result.peers.len(), 2);
+        for peer in result.peers.iter() {
+            assert_eq!(peer.topoheight, 0);
+            assert!(!peer.is_stale);
+            match peer.tag.as_deref() {
+                // One hop only: its connections count, not its peers
+                Some("second") => {
+                    assert_eq!(*peer.addr, *second.get_address());
+                    assert_eq!(peer.advertised_peers_count, Some(1));
+                    assert_eq!(peer.advertised_tag.as_deref(), Some("second"));
+                    assert!(peer.last_topology_update.is_some());
+                },
+                Some("third") => {
+                    assert_eq!(*peer.addr, *third.get_address());
+                    assert!(peer.advertised_peers_count.is_none());
+                    assert!(peer.advertised_tag.is_none());
+                    assert!(peer.last_topology_update.is_none());
+                },
+                tag => panic!("unexpected peer {:?}", tag)
+            }
+        }
+
+        for node in [first, second, third] {
+            node.stop().await;
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    sync::{Arc, RwLock}
+};
+use anyhow::{Context, Result};
+use log::info;
+use rustls::{
+    crypto::ring::sign::any_supported_type,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    ServerConfig
+};
+
+// Load the certificate chain and its private key from PEM files
+fn load_certified_key(cert_path: &str, key_path: &str) -> Result<CertifiedKey> {
+    let mut reader = BufReader::new(File::open(cert_path).with_context(|| format!("Error while opening TLS certificate {}", cert_path))?);
+    let certs: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut reader)
+        .collect::<Result<_, _>>()
+        .context("Error while reading TLS certificate")?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificate found in {}", cert_path);
+    }
+
+    let mut reader = BufReader::new(File::open(key_path).with_context(|| format!("Error while opening TLS private key {}", key_path))?);
+    let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut reader)
+        .context("Error while reading TLS private key")?
+        .with_context(|| format!("No private key found in {}", key_path))?;
+    let key = any_supported_type(&key).context("Unsupported TLS private key")?;
+
+    Ok(CertifiedKey::new(certs, key))
+}
+
+// Certificate used by the HTTPS listeners
+// It can be reloaded from the same files without restarting the RPC server,
+// new connections use the new certificate while established ones are not affected
+#[derive(Debug)]
+pub struct TlsCertificate {
+    cert_path: String,
+    key_path: String,
+    key: RwLock<Arc<CertifiedKey>>
+}
+
+impl TlsCertificate {
+    pub fn new(cert_path: String, key_path: String) -> Result<Self> {
+        let key = load_certified_key(&cert_path, &key_path)?;
+        Ok(Self {
+            cert_path,
+            key_path,
+            key: RwLock::new(Arc::new(key))
+        })
+    }
+
+    // Read again the certificate and the private key from their files
+    // The current certificate is kept if the new one is invalid
+    pub fn reload(&self) -> Result<()> {
+        let key = load_certified_key(&self.cert_path, &self.key_path)?;
+        let mut lock = self.key.write().map_err(|_| anyhow::anyhow!("TLS certificate lock is poisoned"))?;
+        *lock = Arc::new(key);
+        info!("TLS certificate reloaded from {}", self.cert_path);
+        Ok(())
+    }
+
+    // Build the server config resolving always our current certificate
+    pub fn server_config(self: &Arc<Self>) -> ServerConfig {
+        ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::clone(self) as Arc<dyn ResolvesServerCert>)
+    }
+}
+
+impl ResolvesServerCert for TlsCertificate {
+    fn resolve(&self, _: ClientHello) -> Option<Arc<CertifiedKey>> {
+        self.key.read().ok().map(|key| Arc::clone(&key))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use rustls::{ClientConfig, RootCertStore, pki_types::ServerName};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream
+    };
+    use tokio_rustls::{client::TlsStream, TlsConnector};
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/");
+
+    pub(crate) fn fixture(name: &str) -> String {
+        format!("{}{}", FIXTURES, name)
+    }
+
+    // Client trusting only the self-signed certificate given
+    pub(crate) fn connector(cert_name: &str) -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        let mut reader = BufReader::new(File::open(fixture(cert_name)).unwrap());
+        for cert in rustls_pemfile::certs(&mut reader) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+
+        let config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    }
+
+    // Open a TLS connection to the local port, the certificate must be valid for localhost
+    pub(crate) async fn tls_connect(connector: &TlsConnector, port: u16) -> std::io::Result<TlsStream<TcpStream>> {
+        let stream = TcpStream::connect(("127.0.0.1", port)).await?;
+        connector.connect(ServerName::try_from("localhost").unwrap(), stream).await
+    }
+
+    // Send a raw HTTP request over TLS and returns the response
+    pub(crate) async fn https_request(connector: &TlsConnector, port: u16, request: &str) -> std::io::Result<String> {
+        let mut stream = tls_connect(connector, port).await?;
+        stream.write_all(request.as_bytes()).await?;
+
+        // The connection may be closed without TLS close notify, keep what was received
+        let mut response = Vec::new();
+        let mut buffer = [0u8; 1024];
+        loop {
+            match stream.read(&mut buffer).await {
+                Ok(0) => break,
+                Ok(n) => response.extend_from_slice(&buffer[..n]),
+                Err(e) if response.is_empty() => return Err(e),
+                Err(_) => break
+            }
+        }
+        Ok(String::from_utf8_lossy(&response).into_owned())
+    }
+
+    #[test]
+    fn test_invalid_certificate() {
+        assert!(TlsCertificate::new(fixture("missing.pem"), fixture("first.key.pem")).is_err());
+        // Key given as certificate
+        assert!(TlsCertificate::new(fixture("first.key.pem"), fixture("first.key.pem")).is_err());
+    }
+}
@@ -0,0 +1,916 @@
+// In-process harness to run test networks of several nodes
+// Each node uses the in-memory storage and peers are connected through in-memory streams,
+// no socket is used between them so a lot of nodes can be started in one test
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicU16, Ordering},
+        Arc
+    },
+    time::Duration
+};
+use anyhow::{bail, Error};
+use clap::Parser;
+use indexmap::IndexSet;
+use lazy_static::lazy_static;
+use xelis_common::{
+    account::{CiphertextCache, VersionedBalance, VersionedNonce},
+    block::{Block, BlockHeader, EXTRA_NONCE_SIZE},
+    config::XELIS_ASSET,
+    crypto::{Hash, Hashable, KeyPair},
+    immutable::Immutable,
+    network::Network,
+    testing::TempDir,
+    time::get_current_time_in_millis,
+    transaction::Transaction
+};
+use crate::{
+    config::DEV_PUBLIC_KEY,
+    core::{
+        blockchain::{Blockchain, Config},
+        storage::{BalanceProvider, MemoryStorage, NonceProvider}
+    },
+    p2p::{connection::Connection, error::P2pError}
+};
+
+// Size of the in-memory buffer between two peers
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+// Default time to wait for two nodes to be in sync
+pub const DEFAULT_SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Used to give a different address to each node
+static NODES_COUNT: AtomicU16 = AtomicU16::new(0);
+
+lazy_static! {
+    // Dev network has no hardcoded genesis block, all the nodes share this one
+    // so they can complete the handshake with each other
+    static ref DEV_GENESIS_BLOCK: Block = {
+        let header = BlockHeader::new(0, 0, get_current_time_in_millis(), IndexSet::new(), [0u8; EXTRA_NONCE_SIZE], DEV_PUBLIC_KEY.clone(), IndexSet::new());
+        Block::new(Immutable::Owned(header), Vec::new())
+    };
+}
+
+// Genesis block of the dev network shared by all the nodes started in tests
+pub fn get_dev_genesis_block() -> Block {
+    DEV_GENESIS_BLOCK.clone()
+}
+
+#[derive(Parser)]
+struct TestArgs {
+    #[clap(flatten)]
+    config: Config
+}
+
+pub struct TestNode {
+    blockchain: Arc<Blockchain<MemoryStorage>>,
+    // Address seen by its peers
+    addr: SocketAddr,
+    // Directory used for its peerlist
+    dir: TempDir
+}
+
+impl TestNode {
+    // Start a node without any RPC server and without any outgoing connection
+    pub async fn new(network: Network) -> Result<Self, Error> {
+        Self::with_args(network, &[]).await
+    }
+
+    // Start a node with additional command line arguments
+    pub async fn with_args(network: Network, args: &[&str]) -> Result<Self, Error> {
+        let id = NODES_COUNT.fetch_add(1, Ordering::Relaxed);
+        let dir = TempDir::new("test-node");
+        let dir_path = dir.to_dir_path();
+
+        let mut cli = vec![
+            "xelis_daemon",
+            "--dir-path", &dir_path,
+            "--skip-pow-verification",
+            "--no-mempool-persistence",
+            "--disable-rpc-server",
+            "--disable-getwork-server",
+            "--disable-p2p-outgoing-connections",
+            "--p2p-bind-address", "127.0.0.1:0"
+        ];
+        cli.extend_from_slice(args);
+        let config = TestArgs::try_parse_from(cli)?.config;
+
+        let genesis = match network {
+            Network::Dev => Some(get_dev_genesis_block()),
+            _ => None
+        };
+        let blockchain = Blockchain::with_genesis(config, network, MemoryStorage::new(network), None, genesis).await?;
+        let [high, low] = id.to_be_bytes();
+        Ok(Self {
+            blockchain,
+            addr: SocketAddr::from((Ipv4Addr::new(127, 1, high, low), 2125)),
+            dir
+        })
+    }
+
+    pub fn get_blockchain(&self) -> &Arc<Blockchain<MemoryStorage>> {
+        &self.blockchain
+    }
+
+    pub fn get_address(&self) -> &SocketAddr {
+        &self.addr
+    }
+
+    // Get the count of peers connected to this node
+    pub async fn get_peer_count(&self) -> usize {
+        match self.blockchain.get_p2p().read().await.as_ref() {
+            Some(p2p) => p2p.get_peer_count().await,
+            None => 0
+        }
+    }
+
+    // Get the bytes sent by this node to another one, zero if they are not connected
+    pub async fn get_bytes_sent_to(&self, other: &TestNode) -> usize {
+        let p2p = match self.blockchain.get_p2p().read().await.clone() {
+            Some(p2p) => p2p,
+            None => return 0
+        };
+
+        p2p.get_peer_list().get_cloned_peers().await
+            .into_iter()
+            .find(|peer| *peer.get_connection().get_address() == other.addr)
+            .map(|peer| peer.get_connection().bytes_out())
+            .unwrap_or(0)
+    }
+
+    // Give a XELIS balance to the account at the current topoheight and reset its nonce
+    // Nothing is propagated, it must be done on every node verifying its transactions
+    pub async fn fund_account(&self, account: &KeyPair, amount: u64) -> Result<(), Error> {
+        let key = account.get_public_key().compress();
+        let balance = VersionedBalance::new(CiphertextCache::Decompressed(account.get_public_key().encrypt(amount)), None);
+        let topoheight = self.blockchain.get_topo_height();
+        let mut storage = self.blockchain.get_storage().write().await;
+        storage.set_last_balance_to(&key, &XELIS_ASSET, topoheight, &balance).await?;
+        storage.set_last_nonce_to(&key, topoheight, &VersionedNonce::new(0, None)).await?;
+        Ok(())
+    }
+
+    // Stop the node, its directory is deleted once dropped
+    pub async fn stop(self) {
+        self.blockchain.stop().await;
+    }
+}
+
+// Mine a new block on top of the node chain including the transactions given
+// Transactions are added to the mempool first so they are selected in the block template
+// The block is broadcasted to the peers like a block submitted by a miner
+pub async fn mine_block(node: &TestNode, txs: Vec<Transaction>) -> Result<Hash, Error> {
+    let blockchain = node.get_blockchain();
+    for tx in txs {
+        blockchain.add_tx_to_mempool(tx, true).await?;
+    }
+
+    let miner = KeyPair::new().get_public_key().compress();
+    let header = blockchain.get_block_template(miner).await?;
+    let block = blockchain.build_block_from_header(Immutable::Owned(header)).await?;
+    let hash = block.hash();
+    blockchain.add_new_block(block, true, true).await?;
+
+    Ok(hash)
+}
+
+// Connect the two nodes through an in-memory stream
+// The first node is the one dialing the second
+pub async fn connect(a: &TestNode, b: &TestNode) -> Result<(), P2pError> {
+    let (a_p2p, b_p2p) = match (a.blockchain.get_p2p().read().await.clone(), b.blockchain.get_p2p().read().await.clone()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err(P2pError::Disconnected)
+    };
+
+    let (a_stream, b_stream) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+    let (a_read, a_write) = tokio::io::split(a_stream);
+    let (b_read, b_write) = tokio::io::split(b_stream);
+    let a_connection = Connection::from_halves(Box::new(a_read), Box::new(a_write), b.addr, true);
+    let b_connection = Connection::from_halves(Box::new(b_read), Box::new(b_write), a.addr, false);
+
+    // Both sides must run at the same time for the key exchange and the handshake
+    let (a_res, b_res) = tokio::join!(a_p2p.accept_connection(a_connection, false), b_p2p.accept_connection(b_connection, false));
+    a_res?;
+    b_res
+}
+
+// Wait until both nodes have the same top block
+pub async fn wait_for_sync(a: &TestNode, b: &TestNode) -> Result<(), Error> {
+    wait_for_sync_with_timeout(a, b, DEFAULT_SYNC_TIMEOUT).await
+}
+
+pub async fn wait_for_sync_with_timeout(a: &TestNode, b: &TestNode, timeout: Duration) -> Result<(), Error> {
+    let interval = Duration::from_millis(100);
+    let mut waited = Duration::ZERO;
+    loop {
+        let (a_state, b_state) = (a.blockchain.get_top_state(), b.blockchain.get_top_state());
+        if a_state.hash == b_state.hash && a_state.topoheight == b_state.topoheight {
+            return Ok(())
+        }
+
+        if waited >= timeout {
+            bail!("Nodes not in sync after {:?}: {} at topoheight {} and {} at topoheight {}", timeout, a_state.hash, a_state.topoheight, b_state.hash, b_state.topoheight)
+        }
+
+        tokio::time::sleep(interval).await;
+        waited += interval;
+    }
+}
+
+// Wait until the node has the expected count of transactions in its mempool
+pub async fn wait_for_mempool_size(node: &TestNode, size: usize) -> Result<(), Error> {
+    let interval = Duration::from_millis(100);
+    let mut waited = Duration::ZERO;
+    loop {
+        let current = node.blockchain.get_mempool_size().await;
+        if current == size {
+            return Ok(())
+        }
+
+        if waited >= DEFAULT_SYNC_TIMEOUT {
+            bail!("Mempool has {} txs instead of {} after {:?}", current, size, DEFAULT_SYNC_TIMEOUT)
+        }
+
+        tokio::time::sleep(interval).await;
+        waited += interval;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        config::FEE_PER_KB,
+        serializer::Serializer,
+        transaction::builder::FeeBuilder
+    };
+    use crate::{
+        config::{CHAIN_SYNC_PIPELINE_BATCH_BLOCKS, PEER_TX_RATE_LIMIT_BURST, STABLE_LIMIT},
+        core::{
+            error::BlockchainError,
+            state::{build_transfer, build_transfer_with_fee, BALANCE},
+            storage::ClientProtocolProvider
+        },
+        p2p::packet::object::{ObjectRequest, OwnedObjectResponse}
+    };
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sync_chain_on_connect() {
+        let (first, second) = (TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap());
+        for _ in 0..10 {
+            mine_block(&first, Vec::new()).await.unwrap();
+        }
+        assert_eq!(first.get_blockchain().get_topo_height(), 10);
+        assert_eq!(second.get_blockchain().get_topo_height(), 0);
+
+        // Second node is behind and syncs the chain from its new peer
+        connect(&second, &first).await.unwrap();
+        assert_eq!(first.get_peer_count().await, 1);
+        wait_for_sync(&first, &second).await.unwrap();
+        assert_eq!(second.get_blockchain().get_topo_height(), 10);
+
+        first.stop().await;
+        second.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_self_connection_closed() {
+        let node = TestNode::new(Network::Dev).await.unwrap();
+        // Detected by the handshake, the dialing side only sees the connection closed
+        assert!(connect(&node, &node).await.is_err());
+        assert_eq!(node.get_peer_count().await, 0);
+        // Our own clock is not a sample of the network time
+        let p2p = node.get_blockchain().get_p2p().read().await.clone().unwrap();
+        assert!(!node.get_blockchain().has_peer_time_sample(p2p.get_peer_id()).await);
+
+        node.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_connection_replaced() {
+        let (first, second) = (TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap());
+        let first_p2p = first.get_blockchain().get_p2p().read().await.clone().unwrap();
+        let second_p2p = second.get_blockchain().get_p2p().read().await.clone().unwrap();
+        // Both nodes keep the connection dialed by the lowest peer id
+        let ((low, low_p2p), (high, high_p2p)) = if first_p2p.get_peer_id() < second_p2p.get_peer_id() {
+            ((&first, first_p2p), (&second, second_p2p))
+        } else {
+            ((&second, second_p2p), (&first, first_p2p))
+        };
+
+        connect(high, low).await.unwrap();
+        let replaced = low_p2p.get_peer_list().get_peer(&high_p2p.get_peer_id()).await.unwrap();
+        assert!(!replaced.get_connection().is_out());
+
+        // Preferred connection replaces the first one on both sides
+        connect(low, high).await.unwrap();
+        let kept = low_p2p.get_peer_list().get_peer(&high_p2p.get_peer_id()).await.unwrap();
+        assert!(kept.get_connection().is_out());
+        assert!(!Arc::ptr_eq(&kept, &replaced));
+        assert!(!high_p2p.get_peer_list().get_peer(&low_p2p.get_peer_id()).await.unwrap().get_connection().is_out());
+
+        // Late close of the replaced connection doesn't remove the one kept
+        assert!(replaced.close().await.is_err());
+        let current = low_p2p.get_peer_list().get_peer(&high_p2p.get_peer_id()).await.unwrap();
+        assert!(Arc::ptr_eq(&current, &kept));
+
+        // Not preferred, rejected by both sides
+        assert!(matches!(connect(high, low).await, Err(P2pError::DuplicateConnection(_))));
+        let current = low_p2p.get_peer_list().get_peer(&high_p2p.get_peer_id()).await.unwrap();
+        assert!(Arc::ptr_eq(&current, &kept));
+        assert_eq!(low.get_peer_count().await, 1);
+        assert_eq!(high.get_peer_count().await, 1);
+
+        first.stop().await;
+        second.stop().await;
+    }
+
+    // Wait until the node uses the clock of the peer or stops using it
+    async fn wait_for_time_sample(node: &TestNode, peer_id: u64, expected: bool) -> bool {
+        for _ in 0..50 {
+            if node.get_blockchain().has_peer_time_sample(peer_id).await == expected {
+                return true
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn test_network_time_samples() {
+        let (first, second) = (TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap());
+        let first_p2p = first.get_blockchain().get_p2p().read().await.clone().unwrap();
+        let second_p2p = second.get_blockchain().get_p2p().read().await.clone().unwrap();
+        let second_id = second_p2p.get_peer_id();
+
+        // Sampled at the handshake
+        connect(&first, &second).await.unwrap();
+        assert!(first.get_blockchain().has_peer_time_sample(second_id).await);
+
+        // Sampled again with each timed ping
+        first.get_blockchain().remove_peer_time_sample(second_id).await;
+        second_p2p.broadcast_ping_with_peer_list().await;
+        assert!(wait_for_time_sample(&first, second_id, true).await);
+
+        // Forgotten once disconnected
+        let peer = first_p2p.get_peer_list().get_peer(&second_id).await.unwrap();
+        peer.close().await.unwrap();
+        assert!(wait_for_time_sample(&first, second_id, false).await);
+
+        first.stop().await;
+        second.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_block_propagation() {
+        let nodes = [TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap()];
+        // Nodes are connected as a line, the last one only knows the block through the middle one
+        connect(&nodes[0], &nodes[1]).await.unwrap();
+        connect(&nodes[1], &nodes[2]).await.unwrap();
+
+        let hash = mine_block(&nodes[0], Vec::new()).await.unwrap();
+        wait_for_sync(&nodes[0], &nodes[2]).await.unwrap();
+        wait_for_sync(&nodes[0], &nodes[1]).await.unwrap();
+        for node in nodes.iter() {
+            assert_eq!(node.get_blockchain().get_top_block_hash().await.unwrap(), hash);
+        }
+
+        // Blocks mined on the other side are propagated back
+        let hash = mine_block(&nodes[2], Vec::new()).await.unwrap();
+        wait_for_sync(&nodes[2], &nodes[0]).await.unwrap();
+        assert_eq!(nodes[0].get_blockchain().get_top_block_hash().await.unwrap(), hash);
+
+        for node in nodes {
+            node.stop().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reorg_to_heavier_chain() {
+        let (first, second) = (TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap());
+        // Both nodes mine their own chain while they are not connected
+        let mut orphaned = Vec::new();
+        for _ in 0..3 {
+            orphaned.push(mine_block(&first, Vec::new()).await.unwrap());
+        }
+        for _ in 0..10 {
+            mine_block(&second, Vec::new()).await.unwrap();
+        }
+
+        // First node rewinds its blocks to follow the chain with the higher cumulative difficulty
+        connect(&first, &second).await.unwrap();
+        wait_for_sync(&first, &second).await.unwrap();
+        assert_eq!(first.get_blockchain().get_topo_height(), 10);
+        assert_eq!(first.get_blockchain().get_top_block_hash().await.unwrap(), second.get_blockchain().get_top_block_hash().await.unwrap());
+        for hash in orphaned {
+            assert!(!first.get_blockchain().has_block(&hash).await.unwrap());
+        }
+
+        first.stop().await;
+        second.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_whitelist_rejects_inbound() {
+        let first = TestNode::new(Network::Dev).await.unwrap();
+        let allowed = first.get_address().to_string();
+        let second = TestNode::with_args(Network::Dev, &["--p2p-whitelist", &allowed]).await.unwrap();
+        let third = TestNode::new(Network::Dev).await.unwrap();
+
+        // Third node is not whitelisted and is rejected before the handshake
+        assert!(connect(&third, &second).await.is_err());
+        assert_eq!(second.get_peer_count().await, 0);
+
+        connect(&first, &second).await.unwrap();
+        assert_eq!(second.get_peer_count().await, 1);
+
+        for node in [first, second, third] {
+            node.stop().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_whitelist_ignores_shared_peers() {
+        let node = TestNode::with_args(Network::Dev, &["--p2p-whitelist", "10.0.0.1"]).await.unwrap();
+        let p2p = node.get_blockchain().get_p2p().read().await.clone().unwrap();
+
+        // Even the whitelisted peers are not stored, they are maintained as priority nodes
+        let whitelisted: SocketAddr = "10.0.0.1:2125".parse().unwrap();
+        let gossiped: SocketAddr = "10.0.0.2:2125".parse().unwrap();
+        p2p.store_shared_peers(&[whitelisted, gossiped].into_iter().collect()).await;
+        assert!(!p2p.get_peer_list().has_peer_stored(&whitelisted.ip()).await);
+        assert!(!p2p.get_peer_list().has_peer_stored(&gossiped.ip()).await);
+
+        node.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_shared_peers_not_stored_without_whitelist() {
+        let node = TestNode::new(Network::Dev).await.unwrap();
+        let p2p = node.get_blockchain().get_p2p().read().await.clone().unwrap();
+
+        // Gossip doesn't add new addresses to the peerlist
+        let gossiped: SocketAddr = "10.0.0.2:2125".parse().unwrap();
+        p2p.store_shared_peers(&[gossiped].into_iter().collect()).await;
+        assert!(!p2p.get_peer_list().has_peer_stored(&gossiped.ip()).await);
+
+        node.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_block_processing_stages_recorded() {
+        let node = TestNode::with_args(Network::Dev, &["--log-block-timings"]).await.unwrap();
+        let before = node.get_blockchain().get_block_processing_stats().await.blocks;
+        mine_block(&node, Vec::new()).await.unwrap();
+
+        let stats = node.get_blockchain().get_block_processing_stats().await;
+        assert_eq!(stats.blocks, before + 1);
+        assert_eq!(stats.stages.len(), 6);
+        for stage in stats.stages {
+            // Every stage of the block accepted must be measured
+            assert_eq!(stage.count, stats.blocks, "{:?} not recorded", stage.stage);
+            assert!(stage.last.is_some());
+            assert_eq!(stage.buckets.iter().map(|bucket| bucket.count).sum::<u64>(), stage.count);
+        }
+
+        node.stop().await;
+    }
+
+    // Sync the chain of the source on a new node adding each block slowly
+    // Returns the highest count of blocks downloaded by the node but not added yet to its chain
+    async fn sync_with_slow_node(source: &TestNode, args: &[&str]) -> u64 {
+        let node = TestNode::with_args(Network::Dev, args).await.unwrap();
+        node.get_blockchain().set_block_application_delay(Duration::from_millis(5));
+        connect(&node, source).await.unwrap();
+
+        let p2p = source.get_blockchain().get_p2p().read().await.clone().unwrap();
+        let peer = p2p.get_peer_list().get_peer_by_addr(node.get_address()).await.unwrap();
+        let mut max_ahead = 0;
+        while node.get_blockchain().get_topo_height() < source.get_blockchain().get_topo_height() {
+            let ahead = peer.get_object_requests_served().saturating_sub(node.get_blockchain().get_topo_height());
+            max_ahead = max_ahead.max(ahead);
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        wait_for_sync_with_timeout(source, &node, Duration::from_secs(120)).await.unwrap();
+
+        // The blocks after the first chain response are learnt with the continuation token
+        assert_eq!(peer.get_chain_requests_served(), 1);
+        node.stop().await;
+        max_ahead
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_chain_sync() {
+        // Longer than one chain response so the continuation token is used
+        let source = TestNode::with_args(Network::Dev, &["--max-chain-response-size", "512"]).await.unwrap();
+        for _ in 0..600 {
+            mine_block(&source, Vec::new()).await.unwrap();
+        }
+
+        // Without pipelining, a block is requested once the previous one is added
+        let sequential = sync_with_slow_node(&source, &["--disable-p2p-sync-pipelining", "--max-chain-response-size", "512"]).await;
+        assert!(sequential <= 1, "{} blocks downloaded ahead without pipelining", sequential);
+
+        // With pipelining, the next batch is downloaded while the previous one is added, but no more
+        let pipelined = sync_with_slow_node(&source, &["--max-chain-response-size", "512"]).await;
+        assert!(pipelined > 1, "no block downloaded ahead with pipelining");
+        assert!(pipelined <= 2 * CHAIN_SYNC_PIPELINE_BATCH_BLOCKS as u64, "{} blocks downloaded ahead with pipelining", pipelined);
+
+        source.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_sync_blocks_by_topoheight() {
+        // Longer than one chain response
+        let source = TestNode::with_args(Network::Dev, &["--max-chain-response-size", "512"]).await.unwrap();
+        for _ in 0..600 {
+            mine_block(&source, Vec::new()).await.unwrap();
+        }
+
+        let node = TestNode::with_args(Network::Dev, &["--disable-p2p-sync-pipelining", "--max-chain-response-size", "512"]).await.unwrap();
+        connect(&node, &source).await.unwrap();
+        wait_for_sync_with_timeout(&source, &node, Duration::from_secs(120)).await.unwrap();
+
+        // The blocks after the first chain response are requested by topoheight
+        // without a second chain request to learn their hashes
+        let p2p = source.get_blockchain().get_p2p().read().await.clone().unwrap();
+        let peer = p2p.get_peer_list().get_peer_by_addr(node.get_address()).await.unwrap();
+        assert_eq!(peer.get_chain_requests_served(), 1);
+
+        source.stop().await;
+        node.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_mempool_sync_on_connect() {
+        let (first, second) = (TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap());
+        // One sender per tx, funded on both nodes so the second one can verify them
+        let receiver = KeyPair::new();
+        let mut hashes = Vec::new();
+        for _ in 0..50 {
+            let sender = KeyPair::new();
+            first.fund_account(&sender, BALANCE).await.unwrap();
+            second.fund_account(&sender, BALANCE).await.unwrap();
+
+            let tx = build_transfer(&sender, &receiver, BALANCE, 1);
+            hashes.push(tx.hash());
+            first.get_blockchain().add_tx_to_mempool(tx, false).await.unwrap();
+        }
+        assert_eq!(first.get_blockchain().get_mempool_size().await, 50);
+        assert_eq!(second.get_blockchain().get_mempool_size().await, 0);
+
+        // Mempool inventory is sent to the new peer without waiting for a new tx
+        connect(&second, &first).await.unwrap();
+        wait_for_mempool_size(&second, 50).await.unwrap();
+        {
+            let mempool = second.get_blockchain().get_mempool().read().await;
+            for hash in hashes.iter() {
+                assert!(mempool.contains_tx(hash));
+            }
+        }
+
+        first.stop().await;
+        second.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_tx_propagation_rate_limit() {
+        let (source, node) = (TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap());
+        connect(&node, &source).await.unwrap();
+        let p2p = node.get_blockchain().get_p2p().read().await.clone().unwrap();
+        let peer = p2p.get_peer_list().get_peer_by_addr(source.get_address()).await.unwrap();
+
+        // One sender per tx, only the source has them in its mempool
+        let receiver = KeyPair::new();
+        let count = PEER_TX_RATE_LIMIT_BURST * 2;
+        let mut hashes = Vec::new();
+        for _ in 0..count {
+            let sender = KeyPair::new();
+            source.fund_account(&sender, BALANCE).await.unwrap();
+            node.fund_account(&sender, BALANCE).await.unwrap();
+
+            let tx = build_transfer(&sender, &receiver, BALANCE, 1);
+            hashes.push(tx.hash());
+            source.get_blockchain().add_tx_to_mempool(tx, false).await.unwrap();
+        }
+
+        // Once the burst is used, the flood is dropped before requesting anything
+        // Others are requested to the peer through the object tracker and verified
+        for hash in hashes.iter() {
+            p2p.receive_propagated_tx(source.get_address(), hash.clone()).await.unwrap();
+        }
+        let dropped = peer.get_tx_rate_limit_strikes();
+        assert!(dropped > 0);
+        wait_for_mempool_size(&node, (count - dropped) as usize).await.unwrap();
+
+        // Re-announcing known txs is not counted in the rate limit
+        let mut known = Vec::new();
+        for hash in hashes.iter() {
+            if node.get_blockchain().has_tx(hash).await.unwrap() {
+                known.push(hash.clone());
+            }
+        }
+        assert_eq!(known.len() as u64, count - dropped);
+        for hash in known.iter().chain(known.iter()) {
+            p2p.receive_propagated_tx(source.get_address(), hash.clone()).await.unwrap();
+        }
+        assert_eq!(peer.get_tx_rate_limit_strikes(), dropped);
+        assert_eq!(node.get_peer_count().await, 1);
+
+        source.stop().await;
+        node.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_object_requests_flood() {
+        let server = TestNode::new(Network::Dev).await.unwrap();
+        let hash = mine_block(&server, Vec::new()).await.unwrap();
+        let (flooder, honest) = (TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap());
+        connect(&flooder, &server).await.unwrap();
+        connect(&honest, &server).await.unwrap();
+
+        let server_p2p = server.get_blockchain().get_p2p().read().await.clone().unwrap();
+        let flooder_p2p = flooder.get_blockchain().get_p2p().read().await.clone().unwrap();
+        let honest_p2p = honest.get_blockchain().get_p2p().read().await.clone().unwrap();
+        let server_peer = flooder_p2p.get_peer_list().get_peer(&server_p2p.get_peer_id()).await.unwrap();
+
+        // First peer sends a lot more requests than it can have served or queued at once
+        let count: u16 = 500;
+        let flood = futures_util::future::join_all((0..count).map(|i| {
+            let mut bytes = [0u8; 32];
+            bytes[..2].copy_from_slice(&i.to_be_bytes());
+            server_peer.request_blocking_object(ObjectRequest::Block(Hash::new(bytes)))
+        }));
+
+        // Second peer is served while the flood is in progress
+        let peer = honest_p2p.get_peer_list().get_peer(&server_p2p.get_peer_id()).await.unwrap();
+        let (flood, response) = tokio::join!(flood, peer.request_blocking_object(ObjectRequest::BlockHeader(hash.clone())));
+        assert!(matches!(response.unwrap(), OwnedObjectResponse::BlockHeader(_, response_hash) if response_hash == hash));
+        assert!(flood.into_iter().all(|res| res.is_err()));
+
+        // Every request of the flood is counted once served or dropped
+        let flooder_peer = server_p2p.get_peer_list().get_peer(&flooder_p2p.get_peer_id()).await.unwrap();
+        let honest_peer = server_p2p.get_peer_list().get_peer(&honest_p2p.get_peer_id()).await.unwrap();
+        for _ in 0..100 {
+            if flooder_peer.get_object_requests_served() + flooder_peer.get_object_requests_dropped() >= count as u64 {
+                break
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(flooder_peer.get_object_requests_served() + flooder_peer.get_object_requests_dropped(), count as u64);
+        assert_eq!(honest_peer.get_object_requests_dropped(), 0);
+        assert!(honest_peer.get_object_requests_served() >= 1);
+
+        for node in [server, flooder, honest] {
+            node.stop().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compact_block_propagation() {
+        let miner = TestNode::new(Network::Dev).await.unwrap();
+        let compact = TestNode::new(Network::Dev).await.unwrap();
+        let full = TestNode::with_args(Network::Dev, &["--disable-p2p-compact-blocks"]).await.unwrap();
+
+        // All the nodes already have the txs of the block in their mempool
+        let receiver = KeyPair::new();
+        let txs_count = 50;
+        for _ in 0..txs_count {
+            let sender = KeyPair::new();
+            let tx = build_transfer(&sender, &receiver, BALANCE, 1);
+            for node in [&miner, &compact, &full] {
+                node.fund_account(&sender, BALANCE).await.unwrap();
+                node.get_blockchain().add_tx_to_mempool(tx.clone(), false).await.unwrap();
+            }
+        }
+
+        connect(&compact, &miner).await.unwrap();
+        connect(&full, &miner).await.unwrap();
+        let (compact_before, full_before) = (miner.get_bytes_sent_to(&compact).await, miner.get_bytes_sent_to(&full).await);
+
+        mine_block(&miner, Vec::new()).await.unwrap();
+        wait_for_sync(&miner, &compact).await.unwrap();
+        wait_for_sync(&miner, &full).await.unwrap();
+        for node in [&miner, &compact, &full] {
+            assert_eq!(node.get_blockchain().get_mempool_size().await, 0);
+        }
+
+        // Only the node supporting them rebuilt a compact block from its mempool
+        let compact_stats = compact.get_blockchain().get_p2p().read().await.as_ref().unwrap().get_compact_blocks_stats();
+        assert_eq!(compact_stats, (1, 0));
+        let full_stats = full.get_blockchain().get_p2p().read().await.as_ref().unwrap().get_compact_blocks_stats();
+        assert_eq!(full_stats, (0, 0));
+
+        // Each tx hash is replaced by a short id, no tx had to be requested by the peers
+        let compact_bytes = miner.get_bytes_sent_to(&compact).await - compact_before;
+        let full_bytes = miner.get_bytes_sent_to(&full).await - full_before;
+        assert!(compact_bytes < full_bytes, "compact relay sent {} bytes against {} bytes for full relay", compact_bytes, full_bytes);
+
+        for node in [miner, compact, full] {
+            node.stop().await;
+        }
+    }
+
+    // Mine two chains from the genesis, the heavier one is deeper than the stable height of the other
+    async fn mine_forks(light: &TestNode, heavy: &TestNode) {
+        for _ in 0..STABLE_LIMIT * 2 {
+            mine_block(light, Vec::new()).await.unwrap();
+        }
+        for _ in 0..STABLE_LIMIT * 3 {
+            mine_block(heavy, Vec::new()).await.unwrap();
+        }
+        assert!(light.get_blockchain().get_stable_height() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_deep_reorg_rejected() {
+        let (first, second) = (TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap());
+        mine_forks(&first, &second).await;
+        let top_hash = first.get_blockchain().get_top_block_hash().await.unwrap();
+        let topoheight = first.get_blockchain().get_topo_height();
+
+        // The heavier chain is refused because the common ancestor is the genesis block
+        connect(&first, &second).await.unwrap();
+        assert!(wait_for_sync_with_timeout(&first, &second, Duration::from_secs(3)).await.is_err());
+        assert_eq!(first.get_blockchain().get_top_block_hash().await.unwrap(), top_hash);
+        assert_eq!(first.get_blockchain().get_topo_height(), topoheight);
+
+        first.stop().await;
+        second.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_deep_reorg_allowed_once() {
+        let first = TestNode::with_args(Network::Dev, &["--allow-deep-reorg"]).await.unwrap();
+        let second = TestNode::new(Network::Dev).await.unwrap();
+        first.get_blockchain().get_deep_reorg_guard().confirm_without_prompt();
+        mine_forks(&first, &second).await;
+
+        // Allowed by the operator, the node follows the heavier chain and the allowance is consumed
+        connect(&first, &second).await.unwrap();
+        wait_for_sync(&first, &second).await.unwrap();
+        assert_eq!(first.get_blockchain().get_topo_height(), STABLE_LIMIT * 3);
+        assert!(!first.get_blockchain().get_deep_reorg_guard().is_allowed());
+
+        first.stop().await;
+        second.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_min_fee_policy_rejects_tx() {
+        let min_fee_per_kb = (FEE_PER_KB * 2).to_string();
+        let node = TestNode::with_args(Network::Dev, &["--min-fee-per-kb", &min_fee_per_kb]).await.unwrap();
+        let (sender, other_sender, receiver) = (KeyPair::new(), KeyPair::new(), KeyPair::new());
+        node.fund_account(&sender, BALANCE).await.unwrap();
+        node.fund_account(&other_sender, BALANCE).await.unwrap();
+
+        // Protocol minimum is below our policy, the threshold is returned with the error
+        let tx = build_transfer(&sender, &receiver, BALANCE, 1);
+        let fee = tx.get_fee();
+        match node.get_blockchain().add_tx_to_mempool(tx, false).await {
+            Err(BlockchainError::TxFeeTooLow(required, provided, threshold)) => {
+                assert!(required > provided);
+                assert_eq!(provided, fee);
+                assert_eq!(threshold, FEE_PER_KB * 2);
+            },
+            res => panic!("TX below the policy must be rejected, got {:?}", res)
+        }
+        assert_eq!(node.get_blockchain().get_mempool_size().await, 0);
+
+        let tx = build_transfer_with_fee(&other_sender, &receiver, BALANCE, 1, FeeBuilder::Multiplier(3f64));
+        node.get_blockchain().add_tx_to_mempool(tx, false).await.unwrap();
+        assert_eq!(node.get_blockchain().get_mempool_size().await, 1);
+
+        node.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_mempool_eviction_order() {
+        let receiver = KeyPair::new();
+        let senders = [KeyPair::new(), KeyPair::new(), KeyPair::new(), KeyPair::new()];
+        // All the transfers have the same size, only their fee is different
+        let txs: Vec<Transaction> = senders.iter()
+            .zip([2f64, 4f64, 3f64, 1f64])
+            .map(|(sender, multiplier)| build_transfer_with_fee(sender, &receiver, BALANCE, 1, FeeBuilder::Multiplier(multiplier)))
+            .collect();
+        let tx_size = txs[0].size();
+        assert!(txs.iter().all(|tx| tx.size() == tx_size));
+
+        // Room for two TXs only
+        let max_size = (tx_size * 2 + tx_size / 2).to_string();
+        let node = TestNode::with_args(Network::Dev, &["--mempool-max-size", &max_size]).await.unwrap();
+        for sender in senders.iter() {
+            node.fund_account(sender, BALANCE).await.unwrap();
+        }
+        let hashes: Vec<Hash> = txs.iter().map(Transaction::hash).collect();
+        let mut txs = txs.into_iter();
+        let blockchain = node.get_blockchain();
+
+        blockchain.add_tx_to_mempool(txs.next().unwrap(), false).await.unwrap();
+        blockchain.add_tx_to_mempool(txs.next().unwrap(), false).await.unwrap();
+        assert_eq!(blockchain.get_mempool_size().await, 2);
+
+        // Paying more per byte than the first one, it takes its place
+        blockchain.add_tx_to_mempool(txs.next().unwrap(), false).await.unwrap();
+        {
+            let mempool = blockchain.get_mempool().read().await;
+            assert_eq!(mempool.size(), 2);
+            assert!(!mempool.contains_tx(&hashes[0]));
+            assert!(mempool.contains_tx(&hashes[1]));
+            assert!(mempool.contains_tx(&hashes[2]));
+        }
+
+        // Paying less than the next evicted one, it is refused
+        let lowest = txs.next().unwrap();
+        let fee = lowest.get_fee();
+        match blockchain.add_tx_to_mempool(lowest, false).await {
+            Err(BlockchainError::TxFeeTooLow(required, provided, _)) => {
+                assert!(required > provided);
+                assert_eq!(provided, fee);
+            },
+            res => panic!("TX below the eviction threshold must be rejected, got {:?}", res)
+        }
+        assert!(!blockchain.get_mempool().read().await.contains_tx(&hashes[3]));
+
+        node.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_block_with_tx_below_min_fee_policy() {
+        let min_fee_per_kb = (FEE_PER_KB * 2).to_string();
+        let strict = TestNode::with_args(Network::Dev, &["--min-fee-per-kb", &min_fee_per_kb]).await.unwrap();
+        let miner = TestNode::new(Network::Dev).await.unwrap();
+        let (sender, receiver) = (KeyPair::new(), KeyPair::new());
+        strict.fund_account(&sender, BALANCE).await.unwrap();
+        miner.fund_account(&sender, BALANCE).await.unwrap();
+
+        let tx = build_transfer(&sender, &receiver, BALANCE, 1);
+        let hash = tx.hash();
+        assert!(matches!(strict.get_blockchain().add_tx_to_mempool(tx.clone(), false).await, Err(BlockchainError::TxFeeTooLow(..))));
+
+        // The policy only applies to the mempool, the block paying the protocol minimum is valid
+        mine_block(&miner, vec![tx]).await.unwrap();
+        connect(&strict, &miner).await.unwrap();
+        wait_for_sync(&strict, &miner).await.unwrap();
+        assert!(strict.get_blockchain().get_storage().read().await.is_tx_executed_in_a_block(&hash).unwrap());
+
+        strict.stop().await;
+        miner.stop().await;
+    }
+
+    // Mine a block on top of the node chain without propagating it to its peers
+    async fn mine_block_without_broadcast(node: &TestNode) -> BlockHeader {
+        let blockchain = node.get_blockchain();
+        let header = blockchain.get_block_template(KeyPair::new().get_public_key().compress()).await.unwrap();
+        let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+        let header = block.get_header().clone();
+        blockchain.add_new_block(block, false, false).await.unwrap();
+        header
+    }
+
+    // Feed the blocks to the node like if they were propagated by the source
+    async fn receive_blocks(node: &TestNode, source: &TestNode, headers: Vec<BlockHeader>) {
+        let p2p = node.get_blockchain().get_p2p().read().await.clone().unwrap();
+        for header in headers {
+            p2p.receive_propagated_block(source.get_address(), header).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_orphan_block_before_parent() {
+        let (source, node) = (TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap());
+        connect(&node, &source).await.unwrap();
+
+        let parent = mine_block_without_broadcast(&source).await;
+        let child = mine_block_without_broadcast(&source).await;
+        assert_eq!(node.get_blockchain().get_topo_height(), 0);
+
+        // The child is kept until its parent is added, both are connected
+        receive_blocks(&node, &source, vec![child, parent]).await;
+        wait_for_sync_with_timeout(&node, &source, Duration::from_secs(3)).await.unwrap();
+        assert_eq!(node.get_blockchain().get_topo_height(), 2);
+
+        source.stop().await;
+        node.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_deep_chain_of_orphan_blocks() {
+        let (source, node) = (TestNode::new(Network::Dev).await.unwrap(), TestNode::new(Network::Dev).await.unwrap());
+        connect(&node, &source).await.unwrap();
+
+        let mut headers = Vec::new();
+        for _ in 0..10 {
+            headers.push(mine_block_without_broadcast(&source).await);
+        }
+
+        // Only the tip is received, each missing parent is requested to the peer one after the other
+        let tip = headers.pop().unwrap();
+        receive_blocks(&node, &source, vec![tip]).await;
+        wait_for_sync_with_timeout(&node, &source, Duration::from_secs(3)).await.unwrap();
+        assert_eq!(node.get_blockchain().get_topo_height(), 10);
+
+        source.stop().await;
+        node.stop().await;
+    }
+}
@@ -52,6 +52,7 @@ use xelis_common::{
     },
     prompt::{
         command::CommandManager,
+        logger::ModuleLogLevel,
         LogLevel,
         Prompt,
         ShareablePrompt
@@ -92,6 +93,18 @@ pub struct MinerConfig {
     /// Set log level
     #[clap(long, value_enum, default_value_t = LogLevel::Info)]
     log_level: LogLevel,
+    /// Set the log level for specific modules
+    /// Format is module=level, multiple modules can be separated by a comma.
+    /// Example: --log-module p2p=trace,storage=warn
+    #[clap(long, value_delimiter = ',')]
+    log_module: Vec<ModuleLogLevel>,
+    /// Maximum size in bytes of the log file before rotating it
+    /// If set, the log file is rotated based on its size instead of its date.
+    #[clap(long)]
+    file_log_max_size: Option<u64>,
+    /// Maximum number of rotated log files to keep
+    #[clap(long, default_value_t = 5)]
+    file_log_max_files: usize,
     /// Enable the benchmark mode
     #[clap(long)]
     benchmark: bool,
@@ -165,7 +178,7 @@ const UPDATE_EVERY_NONCE: u64 = 10;
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     let config: MinerConfig = MinerConfig::parse();
-    let prompt = Prompt::new(config.log_level, &config.logs_path, &config.filename_log, config.disable_file_logging, config.disable_file_log_date_based, config.disable_log_color, !config.disable_interactive_mode)?;
+    let prompt = Prompt::new(config.log_level, config.log_module.clone(), &config.logs_path, &config.filename_log, config.disable_file_logging, config.disable_file_log_date_based, config.file_log_max_size, config.file_log_max_files, config.disable_log_color, !config.disable_interactive_mode, false)?;
 
     let detected_threads = match thread::available_parallelism() {
         Ok(value) => value.get() as u16,
@@ -0,0 +1,65 @@
+use xelis_common::crypto::{KeyPair, PrivateKey};
+
+// Name of the account using the wallet master key
+pub const DEFAULT_ACCOUNT_NAME: &str = "default";
+// Maximum size in bytes of an account name
+pub const MAX_ACCOUNT_NAME_SIZE: usize = 32;
+
+// Derive the keypair of the account at the index provided
+// Index 0 is the master key itself so the wallet address and seed are kept
+pub fn derive_account_keypair(master_key: &PrivateKey, index: u32) -> KeyPair {
+    let private_key = if index == 0 {
+        master_key.clone()
+    } else {
+        master_key.derive_child(index)
+    };
+
+    KeyPair::from_private_key(private_key)
+}
+
+// Account names are used as command arguments, they can't contain any whitespace
+pub fn is_valid_account_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_ACCOUNT_NAME_SIZE
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use super::*;
+
+    #[test]
+    fn test_account_derivation_is_deterministic() {
+        let master = KeyPair::new();
+        let first = derive_account_keypair(master.get_private_key(), 1);
+        let second = derive_account_keypair(master.get_private_key(), 1);
+        assert_eq!(first.get_public_key().compress(), second.get_public_key().compress());
+
+        // Default account is the master key
+        let default = derive_account_keypair(master.get_private_key(), 0);
+        assert_eq!(default.get_public_key().compress(), master.get_public_key().compress());
+    }
+
+    #[test]
+    fn test_account_addresses_never_collide() {
+        let masters = [KeyPair::new(), KeyPair::new()];
+        let mut addresses = HashSet::new();
+        for master in masters.iter() {
+            for index in 0..5_000 {
+                let keypair = derive_account_keypair(master.get_private_key(), index);
+                let address = keypair.get_public_key().to_address(false).to_string();
+                assert!(addresses.insert(address), "collision at index {}", index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_account_name_validation() {
+        assert!(is_valid_account_name(DEFAULT_ACCOUNT_NAME));
+        assert!(is_valid_account_name("business-2024"));
+        assert!(!is_valid_account_name(""));
+        assert!(!is_valid_account_name("my account"));
+        assert!(!is_valid_account_name(&"a".repeat(MAX_ACCOUNT_NAME_SIZE + 1)));
+    }
+}
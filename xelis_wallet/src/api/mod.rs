@@ -1,6 +1,7 @@
 mod rpc;
 mod rpc_server;
 mod xswd;
+mod permissions;
 
 use serde::ser::Serialize;
 use xelis_common::{api::wallet::NotifyEvent, rpc_server::WebSocketServerHandler};
@@ -13,7 +14,16 @@ pub use self::{
         PermissionResult,
         PermissionRequest,
         XSWDPermissionHandler,
-        XSWDNodeMethodHandler
+        XSWDNodeMethodHandler,
+        Permission
+    },
+    permissions::{
+        ApplicationInfo,
+        AppPermissions,
+        AppPermissionError,
+        AppPermissionHandler,
+        MethodClass,
+        verify_app_permission
     },
     rpc::register_methods as register_rpc_methods
 };
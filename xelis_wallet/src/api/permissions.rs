@@ -0,0 +1,572 @@
+use std::{collections::HashMap, fmt};
+use actix_web::http::header::HeaderMap;
+use anyhow::Error;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use xelis_common::{
+    crypto::{hash, Hash},
+    rpc_server::InternalRpcError,
+    serializer::{
+        Reader,
+        ReaderError,
+        Serializer,
+        Writer
+    }
+};
+use super::xswd::{Permission, PermissionResult};
+
+// Headers sent by the applications to identify themselves on the RPC Server
+pub const APPLICATION_ID_HEADER: &str = "X-Application-Id";
+pub const APPLICATION_NAME_HEADER: &str = "X-Application-Name";
+// Secret chosen by the application, the first decision saved binds it to the application ID
+pub const APPLICATION_SECRET_HEADER: &str = "X-Application-Secret";
+
+// Same limits as the XSWD applications
+const MAX_APPLICATION_ID_SIZE: usize = 64;
+const MAX_APPLICATION_NAME_SIZE: usize = 32;
+const MIN_APPLICATION_SECRET_SIZE: usize = 32;
+const MAX_APPLICATION_SECRET_SIZE: usize = 128;
+
+// Class of RPC methods approved at once by the user
+// Only get_version is callable without any approval
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MethodClass {
+    ReadBalance,
+    ReadHistory,
+    BuildTransaction,
+    Sign,
+    ReadWallet,
+    ManageWallet,
+    Storage
+}
+
+impl MethodClass {
+    pub const ALL: [MethodClass; 7] = [
+        Self::ReadBalance,
+        Self::ReadHistory,
+        Self::BuildTransaction,
+        Self::Sign,
+        Self::ReadWallet,
+        Self::ManageWallet,
+        Self::Storage
+    ];
+
+    pub fn get_id(&self) -> u8 {
+        match self {
+            Self::ReadBalance => 0,
+            Self::ReadHistory => 1,
+            Self::BuildTransaction => 2,
+            Self::Sign => 3,
+            Self::ReadWallet => 4,
+            Self::ManageWallet => 5,
+            Self::Storage => 6
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|class| class.to_string() == name)
+    }
+}
+
+impl fmt::Display for MethodClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::ReadBalance => "read_balance",
+            Self::ReadHistory => "read_history",
+            Self::BuildTransaction => "build_transaction",
+            Self::Sign => "sign",
+            Self::ReadWallet => "read_wallet",
+            Self::ManageWallet => "manage_wallet",
+            Self::Storage => "storage"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AppPermissionError {
+    #[error("Application must identify itself with the {}, {} and {} headers", APPLICATION_ID_HEADER, APPLICATION_NAME_HEADER, APPLICATION_SECRET_HEADER)]
+    ApplicationNotIdentified,
+    #[error("Invalid application ID, expected 1 to {} alphanumeric characters", MAX_APPLICATION_ID_SIZE)]
+    InvalidApplicationId,
+    #[error("Invalid application name, expected 1 to {} characters", MAX_APPLICATION_NAME_SIZE)]
+    InvalidApplicationName,
+    #[error("Permission denied for {} methods", _0)]
+    PermissionDenied(MethodClass),
+    #[error("Invalid application secret, expected {} to {} characters", MIN_APPLICATION_SECRET_SIZE, MAX_APPLICATION_SECRET_SIZE)]
+    InvalidApplicationSecret,
+    #[error("Application secret doesn't match the one saved for this application ID")]
+    ApplicationSecretMismatch,
+    #[error(transparent)]
+    Any(#[from] Error)
+}
+
+impl AppPermissionError {
+    fn code(&self) -> i16 {
+        match self {
+            Self::ApplicationNotIdentified => 40,
+            Self::InvalidApplicationId => 41,
+            Self::InvalidApplicationName => 42,
+            Self::PermissionDenied(_) => 43,
+            Self::Any(_) => 44,
+            Self::InvalidApplicationSecret => 45,
+            Self::ApplicationSecretMismatch => 46
+        }
+    }
+}
+
+impl From<AppPermissionError> for InternalRpcError {
+    fn from(e: AppPermissionError) -> Self {
+        InternalRpcError::CustomAny(e.code(), e.into())
+    }
+}
+
+// Application connected to the RPC Server
+// Only the hash of its secret is kept
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApplicationInfo {
+    id: String,
+    name: String,
+    secret_hash: Hash
+}
+
+impl ApplicationInfo {
+    pub fn new(id: String, name: String, secret: &str) -> Result<Self, AppPermissionError> {
+        if id.is_empty() || id.len() > MAX_APPLICATION_ID_SIZE || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(AppPermissionError::InvalidApplicationId)
+        }
+
+        if name.is_empty() || name.len() > MAX_APPLICATION_NAME_SIZE || name.chars().any(char::is_control) {
+            return Err(AppPermissionError::InvalidApplicationName)
+        }
+
+        if secret.len() < MIN_APPLICATION_SECRET_SIZE || secret.len() > MAX_APPLICATION_SECRET_SIZE {
+            return Err(AppPermissionError::InvalidApplicationSecret)
+        }
+
+        Ok(Self {
+            id,
+            name,
+            secret_hash: hash(secret.as_bytes())
+        })
+    }
+
+    // Read the application identity from the request headers
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, AppPermissionError> {
+        let read = |name: &str| headers.get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+
+        match (read(APPLICATION_ID_HEADER), read(APPLICATION_NAME_HEADER), read(APPLICATION_SECRET_HEADER)) {
+            (Some(id), Some(name), Some(secret)) => Self::new(id, name, &secret),
+            _ => Err(AppPermissionError::ApplicationNotIdentified)
+        }
+    }
+
+    pub fn get_id(&self) -> &String {
+        &self.id
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_secret_hash(&self) -> &Hash {
+        &self.secret_hash
+    }
+}
+
+// Decisions of the user saved for an application
+#[derive(Clone, Debug)]
+pub struct AppPermissions {
+    // Name of the application when the decisions were saved
+    name: String,
+    // Another application can't reuse the same ID without knowing the secret
+    secret_hash: Hash,
+    permissions: HashMap<MethodClass, Permission>
+}
+
+impl AppPermissions {
+    pub fn new(app: &ApplicationInfo) -> Self {
+        Self {
+            name: app.get_name().clone(),
+            secret_hash: app.get_secret_hash().clone(),
+            permissions: HashMap::new()
+        }
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    // Decisions can only be used by the application knowing the secret
+    pub fn verify_secret(&self, app: &ApplicationInfo) -> Result<(), AppPermissionError> {
+        if self.secret_hash != *app.get_secret_hash() {
+            return Err(AppPermissionError::ApplicationSecretMismatch)
+        }
+
+        Ok(())
+    }
+
+    // Methods class never decided are asked to the user
+    pub fn get(&self, class: MethodClass) -> Permission {
+        self.permissions.get(&class).copied().unwrap_or(Permission::Ask)
+    }
+
+    pub fn set(&mut self, class: MethodClass, permission: Permission) {
+        match permission {
+            Permission::Ask => self.permissions.remove(&class),
+            permission => self.permissions.insert(class, permission)
+        };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.permissions.is_empty()
+    }
+
+    // Add a decision to the ones saved, the first one saved binds the secret of the application
+    pub fn with_decision(saved: Option<Self>, app: &ApplicationInfo, class: MethodClass, permission: Permission) -> Result<Self, AppPermissionError> {
+        let mut permissions = match saved {
+            Some(permissions) => {
+                permissions.verify_secret(app)?;
+                permissions
+            },
+            None => Self::new(app)
+        };
+        permissions.set(class, permission);
+        Ok(permissions)
+    }
+}
+
+impl Serializer for AppPermissions {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let name = reader.read_string()?;
+        let secret_hash = reader.read_hash()?;
+        let count = reader.read_u8()?;
+        let mut permissions = HashMap::with_capacity(count as usize);
+        for _ in 0..count {
+            let class = MethodClass::from_id(reader.read_u8()?).ok_or(ReaderError::InvalidValue)?;
+            let permission = Permission::from_id(reader.read_u8()?).ok_or(ReaderError::InvalidValue)?;
+            permissions.insert(class, permission);
+        }
+
+        Ok(Self {
+            name,
+            secret_hash,
+            permissions
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_string(&self.name);
+        writer.write_hash(&self.secret_hash);
+        writer.write_u8(self.permissions.len() as u8);
+        for (class, permission) in &self.permissions {
+            writer.write_u8(class.get_id());
+            writer.write_u8(permission.get_id());
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.name.size() + self.secret_hash.size() + 1 + self.permissions.len() * 2
+    }
+}
+
+#[async_trait]
+pub trait AppPermissionHandler {
+    // Decisions saved for this application
+    async fn get_app_permissions(&self, app: &ApplicationInfo) -> Result<Option<AppPermissions>, Error>;
+    // Save the decision for this class of methods, the saved decisions must be read and written at once
+    async fn save_app_permission(&self, app: &ApplicationInfo, class: MethodClass, permission: Permission) -> Result<(), Error>;
+    // Ask the user to allow once, always or deny this class of methods
+    async fn request_app_permission(&self, app: &ApplicationInfo, class: MethodClass) -> Result<PermissionResult, Error>;
+}
+
+// Decision saved for this class, None if the user must be asked
+async fn get_saved_decision<H: AppPermissionHandler + ?Sized>(handler: &H, app: &ApplicationInfo, class: MethodClass) -> Result<Option<bool>, AppPermissionError> {
+    let Some(permissions) = handler.get_app_permissions(app).await? else {
+        return Ok(None)
+    };
+
+    permissions.verify_secret(app)?;
+    Ok(match permissions.get(class) {
+        Permission::AcceptAlways => Some(true),
+        Permission::DenyAlways => Some(false),
+        Permission::Ask => None
+    })
+}
+
+// Verify that the application is allowed to call a method of this class
+// The user is asked on the first request, "always" decisions are saved
+// Saved decisions are answered right away, while the requests to ask are queued with the lock
+// so the user is not asked twice for the same decision
+pub async fn verify_app_permission<H: AppPermissionHandler + ?Sized>(handler: &H, requests: &Mutex<()>, app: &ApplicationInfo, class: MethodClass) -> Result<(), AppPermissionError> {
+    let allowed = match get_saved_decision(handler, app, class).await? {
+        Some(allowed) => allowed,
+        None => {
+            let _lock = requests.lock().await;
+            // A request answered while we were waiting may have saved a decision
+            match get_saved_decision(handler, app, class).await? {
+                Some(allowed) => allowed,
+                None => {
+                    let (allowed, save) = match handler.request_app_permission(app, class).await? {
+                        PermissionResult::Allow => (true, None),
+                        PermissionResult::Deny => (false, None),
+                        PermissionResult::AlwaysAllow => (true, Some(Permission::AcceptAlways)),
+                        PermissionResult::AlwaysDeny => (false, Some(Permission::DenyAlways))
+                    };
+
+                    if let Some(permission) = save {
+                        handler.save_app_permission(app, class, permission).await?;
+                    }
+                    allowed
+                }
+            }
+        }
+    };
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(AppPermissionError::PermissionDenied(class))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, sync::Arc};
+    use actix_web::http::header::{HeaderName, HeaderValue};
+    use tokio::sync::{oneshot, Notify};
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    // Answers are given in order, the test fails if the user is asked more than expected
+    struct ScriptedPrompt {
+        answers: Mutex<VecDeque<PermissionResult>>,
+        saved: Mutex<HashMap<String, AppPermissions>>,
+        requests: Mutex<()>
+    }
+
+    impl ScriptedPrompt {
+        fn new(answers: Vec<PermissionResult>) -> Self {
+            Self {
+                answers: Mutex::new(answers.into()),
+                saved: Mutex::new(HashMap::new()),
+                requests: Mutex::new(())
+            }
+        }
+
+        async fn remaining(&self) -> usize {
+            self.answers.lock().await.len()
+        }
+
+        async fn verify(&self, app: &ApplicationInfo, class: MethodClass) -> Result<(), AppPermissionError> {
+            verify_app_permission(self, &self.requests, app, class).await
+        }
+    }
+
+    #[async_trait]
+    impl AppPermissionHandler for ScriptedPrompt {
+        async fn get_app_permissions(&self, app: &ApplicationInfo) -> Result<Option<AppPermissions>, Error> {
+            Ok(self.saved.lock().await.get(app.get_id()).cloned())
+        }
+
+        async fn save_app_permission(&self, app: &ApplicationInfo, class: MethodClass, permission: Permission) -> Result<(), Error> {
+            let mut saved = self.saved.lock().await;
+            let permissions = AppPermissions::with_decision(saved.remove(app.get_id()), app, class, permission)?;
+            saved.insert(app.get_id().clone(), permissions);
+            Ok(())
+        }
+
+        async fn request_app_permission(&self, _: &ApplicationInfo, _: MethodClass) -> Result<PermissionResult, Error> {
+            Ok(self.answers.lock().await.pop_front().expect("user was asked more than expected"))
+        }
+    }
+
+    // The user only answers once notified
+    struct WaitingPrompt {
+        inner: ScriptedPrompt,
+        asked: Mutex<Option<oneshot::Sender<()>>>,
+        answer: Notify
+    }
+
+    #[async_trait]
+    impl AppPermissionHandler for WaitingPrompt {
+        async fn get_app_permissions(&self, app: &ApplicationInfo) -> Result<Option<AppPermissions>, Error> {
+            self.inner.get_app_permissions(app).await
+        }
+
+        async fn save_app_permission(&self, app: &ApplicationInfo, class: MethodClass, permission: Permission) -> Result<(), Error> {
+            self.inner.save_app_permission(app, class, permission).await
+        }
+
+        async fn request_app_permission(&self, app: &ApplicationInfo, class: MethodClass) -> Result<PermissionResult, Error> {
+            if let Some(asked) = self.asked.lock().await.take() {
+                asked.send(()).unwrap();
+            }
+            self.answer.notified().await;
+            self.inner.request_app_permission(app, class).await
+        }
+    }
+
+    fn app(id: &str) -> ApplicationInfo {
+        ApplicationInfo::new(id.to_owned(), format!("App {}", id), SECRET).unwrap()
+    }
+
+    fn is_denied(result: Result<(), AppPermissionError>, expected: MethodClass) -> bool {
+        matches!(result, Err(AppPermissionError::PermissionDenied(class)) if class == expected)
+    }
+
+    #[tokio::test]
+    async fn test_allow_once_asks_again() {
+        let prompt = ScriptedPrompt::new(vec![PermissionResult::Allow, PermissionResult::Allow]);
+        let app = app("a");
+        assert!(prompt.verify(&app, MethodClass::ReadBalance).await.is_ok());
+        assert!(prompt.verify(&app, MethodClass::ReadBalance).await.is_ok());
+        assert_eq!(prompt.remaining().await, 0);
+        assert!(prompt.saved.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_always_allow_is_saved_per_class() {
+        let prompt = ScriptedPrompt::new(vec![PermissionResult::AlwaysAllow, PermissionResult::Deny]);
+        let app = app("a");
+        for _ in 0..3 {
+            assert!(prompt.verify(&app, MethodClass::ReadHistory).await.is_ok());
+        }
+        assert_eq!(prompt.remaining().await, 1);
+
+        // Another class is asked separately
+        assert!(is_denied(prompt.verify(&app, MethodClass::Sign).await, MethodClass::Sign));
+        assert_eq!(prompt.remaining().await, 0);
+
+        let saved = prompt.saved.lock().await;
+        assert!(matches!(saved["a"].get(MethodClass::ReadHistory), Permission::AcceptAlways));
+        assert!(matches!(saved["a"].get(MethodClass::Sign), Permission::Ask));
+    }
+
+    #[tokio::test]
+    async fn test_deny_always_is_saved_per_app() {
+        let prompt = ScriptedPrompt::new(vec![PermissionResult::AlwaysDeny, PermissionResult::Allow]);
+        let first = app("a");
+        let second = app("b");
+        for _ in 0..2 {
+            assert!(is_denied(prompt.verify(&first, MethodClass::BuildTransaction).await, MethodClass::BuildTransaction));
+        }
+        assert_eq!(prompt.remaining().await, 1);
+
+        // Decisions are not shared between applications
+        assert!(prompt.verify(&second, MethodClass::BuildTransaction).await.is_ok());
+        assert_eq!(prompt.remaining().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_saved_decision_is_updated() {
+        let prompt = ScriptedPrompt::new(vec![PermissionResult::AlwaysAllow]);
+        let app = app("a");
+        prompt.save_app_permission(&app, MethodClass::ReadBalance, Permission::DenyAlways).await.unwrap();
+        assert!(is_denied(prompt.verify(&app, MethodClass::ReadBalance).await, MethodClass::ReadBalance));
+
+        // Reset from the permissions command, the user is asked again
+        prompt.save_app_permission(&app, MethodClass::ReadBalance, Permission::Ask).await.unwrap();
+        assert!(prompt.saved.lock().await["a"].is_empty());
+        assert!(prompt.verify(&app, MethodClass::ReadBalance).await.is_ok());
+        assert_eq!(prompt.remaining().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_application_id_bound_to_secret() {
+        let prompt = ScriptedPrompt::new(vec![PermissionResult::AlwaysAllow]);
+        let app = app("a");
+        assert!(prompt.verify(&app, MethodClass::Sign).await.is_ok());
+
+        // Another application using the same ID and name can't use the saved decisions
+        let spoofed = ApplicationInfo::new("a".to_owned(), "App a".to_owned(), "another secret of thirty two chars").unwrap();
+        assert!(matches!(prompt.verify(&spoofed, MethodClass::Sign).await, Err(AppPermissionError::ApplicationSecretMismatch)));
+        // Nor save new ones, without asking the user
+        assert!(matches!(prompt.verify(&spoofed, MethodClass::ReadBalance).await, Err(AppPermissionError::ApplicationSecretMismatch)));
+        assert!(prompt.save_app_permission(&spoofed, MethodClass::ReadBalance, Permission::AcceptAlways).await.is_err());
+        assert_eq!(prompt.remaining().await, 0);
+
+        assert!(prompt.verify(&app, MethodClass::Sign).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_saved_decision_not_blocked_by_prompt() {
+        let (asked, asked_receiver) = oneshot::channel();
+        let prompt = Arc::new(WaitingPrompt {
+            inner: ScriptedPrompt::new(vec![PermissionResult::AlwaysAllow]),
+            asked: Mutex::new(Some(asked)),
+            answer: Notify::new()
+        });
+        let saved = app("saved");
+        prompt.save_app_permission(&saved, MethodClass::ReadBalance, Permission::AcceptAlways).await.unwrap();
+
+        let handle = {
+            let prompt = Arc::clone(&prompt);
+            tokio::spawn(async move {
+                verify_app_permission(prompt.as_ref(), &prompt.inner.requests, &app("asking"), MethodClass::Sign).await
+            })
+        };
+        asked_receiver.await.unwrap();
+
+        // The user didn't answer yet, saved decisions are still served
+        assert!(verify_app_permission(prompt.as_ref(), &prompt.inner.requests, &saved, MethodClass::ReadBalance).await.is_ok());
+
+        prompt.answer.notify_one();
+        assert!(handle.await.unwrap().is_ok());
+        assert_eq!(prompt.inner.remaining().await, 0);
+    }
+
+    #[test]
+    fn test_permissions_serialization() {
+        let app = app("a");
+        let mut permissions = AppPermissions::new(&app);
+        permissions.set(MethodClass::ReadBalance, Permission::AcceptAlways);
+        permissions.set(MethodClass::Storage, Permission::DenyAlways);
+
+        let bytes = permissions.to_bytes();
+        assert_eq!(bytes.len(), permissions.size());
+        let decoded = AppPermissions::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.get_name(), "App a");
+        assert!(decoded.verify_secret(&app).is_ok());
+        assert!(matches!(decoded.get(MethodClass::ReadBalance), Permission::AcceptAlways));
+        assert!(matches!(decoded.get(MethodClass::Storage), Permission::DenyAlways));
+        assert!(matches!(decoded.get(MethodClass::ReadHistory), Permission::Ask));
+    }
+
+    #[test]
+    fn test_application_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert!(matches!(ApplicationInfo::from_headers(&headers), Err(AppPermissionError::ApplicationNotIdentified)));
+
+        headers.insert(HeaderName::from_static("x-application-id"), HeaderValue::from_static("abc123"));
+        headers.insert(HeaderName::from_static("x-application-name"), HeaderValue::from_static("Explorer"));
+        assert!(matches!(ApplicationInfo::from_headers(&headers), Err(AppPermissionError::ApplicationNotIdentified)));
+
+        headers.insert(HeaderName::from_static("x-application-secret"), HeaderValue::from_static("too short"));
+        assert!(matches!(ApplicationInfo::from_headers(&headers), Err(AppPermissionError::InvalidApplicationSecret)));
+
+        headers.insert(HeaderName::from_static("x-application-secret"), HeaderValue::from_static(SECRET));
+        assert_eq!(ApplicationInfo::from_headers(&headers).unwrap(), ApplicationInfo::new("abc123".to_owned(), "Explorer".to_owned(), SECRET).unwrap());
+
+        headers.insert(HeaderName::from_static("x-application-id"), HeaderValue::from_static("../etc"));
+        assert!(matches!(ApplicationInfo::from_headers(&headers), Err(AppPermissionError::InvalidApplicationId)));
+    }
+
+    #[test]
+    fn test_method_classes() {
+        for class in MethodClass::ALL {
+            assert_eq!(MethodClass::from_id(class.get_id()), Some(class));
+            assert_eq!(MethodClass::from_name(&class.to_string()), Some(class));
+        }
+    }
+}
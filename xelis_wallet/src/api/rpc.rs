@@ -3,6 +3,7 @@ use anyhow::Context as AnyContext;
 use xelis_common::{
     api::{
         wallet::{
+            NotifyEvent,
             BuildTransactionParams,
             DeleteParams,
             EstimateFeesParams,
@@ -31,22 +32,28 @@ use xelis_common::{
     crypto::Hashable,
     rpc_server::{
         parse_params,
-        websocket::WebSocketSessionShared,
+        websocket::{EventWebSocketHandler, WebSocketSessionShared},
         InternalRpcError,
         RPCHandler
     },
     serializer::Serializer,
     transaction::builder::FeeBuilder
 };
+use actix_web::http::header::HeaderMap;
 use serde_json::{Value, json};
 use crate::{
     wallet::Wallet,
     error::WalletError
 };
-use super::xswd::XSWDWebSocketHandler;
+use super::{
+    xswd::XSWDWebSocketHandler,
+    ApplicationInfo,
+    MethodClass
+};
 use log::{info, warn};
 
 // Register all RPC methods
+// With the app permissions enabled, every method except get_version must be approved by the user
 pub fn register_methods(handler: &mut RPCHandler<Arc<Wallet>>) {
     info!("Registering RPC methods...");
     handler.register_method("get_version", async_handler!(get_version));
@@ -96,10 +103,33 @@ async fn get_network(context: &Context, body: Value) -> Result<Value, InternalRp
     }
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ReadWallet).await?;
     let network = wallet.get_network();
     Ok(json!(network))
 }
 
+// Applications calling the RPC Server must be approved by the user for this class of methods
+// Nothing is verified if the app permissions are not enabled
+async fn verify_rpc_app_permission(context: &Context, class: MethodClass) -> Result<(), InternalRpcError> {
+    let wallet: &Arc<Wallet> = context.get()?;
+    if !wallet.is_app_permissions_enabled() {
+        return Ok(())
+    }
+
+    // Headers are stored by the HTTP endpoint, or kept in the WebSocket session
+    let headers = match context.get_optional::<HeaderMap>() {
+        Some(headers) => headers,
+        None => {
+            let session: &WebSocketSessionShared<EventWebSocketHandler<Arc<Wallet>, NotifyEvent>> = context.get()?;
+            session.get_request().headers()
+        }
+    };
+
+    let app = ApplicationInfo::from_headers(headers)?;
+    wallet.verify_app_permission(&app, class).await?;
+    Ok(())
+}
+
 // Retrieve the current nonce of the wallet
 async fn get_nonce(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     if body != Value::Null {
@@ -107,6 +137,7 @@ async fn get_nonce(context: &Context, body: Value) -> Result<Value, InternalRpcE
     }
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ReadBalance).await?;
     let storage = wallet.get_storage().read().await;
     let nonce = storage.get_nonce()?;
     Ok(json!(nonce))
@@ -119,6 +150,7 @@ async fn get_topoheight(context: &Context, body: Value) -> Result<Value, Interna
     }
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ReadWallet).await?;
     let storage = wallet.get_storage().read().await;
     let topoheight = storage.get_synced_topoheight()?;
     Ok(json!(topoheight))
@@ -129,6 +161,7 @@ async fn get_address(context: &Context, body: Value) -> Result<Value, InternalRp
     let params: GetAddressParams = parse_params(body)?;
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ReadWallet).await?;
     let address = if let Some(data) = params.integrated_data {
         wallet.get_address_with(data)
     } else {
@@ -139,8 +172,9 @@ async fn get_address(context: &Context, body: Value) -> Result<Value, InternalRp
 }
 
 // Split an integrated address into its address and data
-async fn split_address(_: &Context, body: Value) -> Result<Value, InternalRpcError> {
+async fn split_address(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: SplitAddressParams = parse_params(body)?;
+    verify_rpc_app_permission(context, MethodClass::ReadWallet).await?;
     let address = params.address;
 
     let (data, address) = address.extract_data();
@@ -156,6 +190,7 @@ async fn split_address(_: &Context, body: Value) -> Result<Value, InternalRpcErr
 async fn rescan(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: RescanParams = parse_params(body)?;
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ManageWallet).await?;
     wallet.rescan(params.until_topoheight.unwrap_or(0), params.auto_reconnect).await?;
     Ok(json!(true))
 }
@@ -166,6 +201,7 @@ async fn get_balance(context: &Context, body: Value) -> Result<Value, InternalRp
     let params: GetBalanceParams = parse_params(body)?;
     let asset = params.asset.unwrap_or(XELIS_ASSET);
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ReadBalance).await?;
     let storage = wallet.get_storage().read().await;
 
     // If the asset is not found, it will returns 0
@@ -179,6 +215,7 @@ async fn has_balance(context: &Context, body: Value) -> Result<Value, InternalRp
     let params: GetBalanceParams = parse_params(body)?;
     let asset = params.asset.unwrap_or(XELIS_ASSET);
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ReadBalance).await?;
     let storage = wallet.get_storage().read().await;
 
     let exist = storage.has_balance_for(&asset).await.context("Error while checking if balance exists")?;
@@ -192,6 +229,7 @@ async fn get_tracked_assets(context: &Context, body: Value) -> Result<Value, Int
     }
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ReadBalance).await?;
     let storage = wallet.get_storage().read().await;
     let tracked_assets = storage.get_assets().await?;
 
@@ -203,6 +241,7 @@ async fn get_asset_precision(context: &Context, body: Value) -> Result<Value, In
     let params: GetAssetPrecisionParams = parse_params(body)?;
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ReadWallet).await?;
     let storage = wallet.get_storage().read().await;
     let precision = storage.get_asset_decimals(&params.asset)?;
     Ok(json!(precision))
@@ -213,6 +252,7 @@ async fn get_transaction(context: &Context, body: Value) -> Result<Value, Intern
     let params: GetTransactionParams = parse_params(body)?;
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ReadHistory).await?;
     let storage = wallet.get_storage().read().await;
     let transaction = storage.get_transaction(&params.hash)?;
 
@@ -223,6 +263,7 @@ async fn get_transaction(context: &Context, body: Value) -> Result<Value, Intern
 async fn build_transaction(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: BuildTransactionParams = parse_params(body)?;
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::BuildTransaction).await?;
     // request ask to broadcast the TX but wallet is not connected to any daemon
     if !wallet.is_online().await && params.broadcast {
         return Err(WalletError::NotOnlineMode)?
@@ -233,21 +274,31 @@ async fn build_transaction(context: &Context, body: Value) -> Result<Value, Inte
     }
 
     // create the TX
-    // The lock is kept until the TX is applied to the storage
+    // The nonce is reserved until the TX is applied to the storage
     // So even if we have few requests building a TX, they wait for the previous one to be applied
-    let mut storage = wallet.get_storage().write().await;
-    let (mut state, tx) = wallet.create_transaction_with_storage(&storage, params.tx_type, params.fee.unwrap_or(FeeBuilder::Multiplier(1f64))).await?;
+    // The storage itself is only locked for writing when the changes are applied
+    let mut reservation = wallet.reserve_nonce().await;
+    let fee = params.fee.unwrap_or(FeeBuilder::Multiplier(1f64));
+    let (mut state, mut tx) = {
+        let storage = wallet.get_storage().read().await;
+        wallet.create_transaction_with_storage(&storage, params.tx_type.clone(), fee.clone(), params.extra_data.clone(), params.encrypt_extra_data).await?
+    };
 
     // if requested, broadcast the TX ourself
+    // it may be rebuilt with another nonce if the daemon rejected it
     if params.broadcast {
-        if let Err(e) = wallet.submit_transaction(&tx).await {
-            warn!("Clearing Tx cache because of broadcasting error: {}", e);
-            storage.clear_tx_cache();
-            return Err(e.into());
-        }
+        tx = match wallet.submit_transaction_with_recovery(&mut reservation, &mut state, tx, params.tx_type, fee, params.extra_data, params.encrypt_extra_data).await {
+            Ok(tx) => tx,
+            Err(e) => {
+                warn!("Clearing Tx cache because of broadcasting error: {}", e);
+                wallet.get_storage().write().await.clear_tx_cache();
+                // Dropping the reservation release the nonce
+                return Err(e.into());
+            }
+        };
     }
 
-    state.apply_changes(&mut storage).await
+    reservation.commit(&mut state).await
         .context("Error while applying state changes")?;
 
     // returns the created TX and its hash
@@ -268,7 +319,8 @@ async fn build_transaction(context: &Context, body: Value) -> Result<Value, Inte
 async fn estimate_fees(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: EstimateFeesParams = parse_params(body)?;
     let wallet: &Arc<Wallet> = context.get()?;
-    let fees = wallet.estimate_fees(params.tx_type).await?;
+    verify_rpc_app_permission(context, MethodClass::BuildTransaction).await?;
+    let fees = wallet.estimate_fees(params.tx_type, params.extra_data, params.encrypt_extra_data).await?;
 
     Ok(json!(fees))
 }
@@ -283,6 +335,7 @@ async fn list_transactions(context: &Context, body: Value) -> Result<Value, Inte
     }
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ReadHistory).await?;
     let storage = wallet.get_storage().read().await;
     let opt_key = params.address.map(|addr| addr.to_public_key());
     
@@ -302,6 +355,7 @@ async fn is_online(context: &Context, body: Value) -> Result<Value, InternalRpcE
     }
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ReadWallet).await?;
     let is_connected = wallet.is_online().await;
     Ok(json!(is_connected))
 }
@@ -311,6 +365,7 @@ async fn set_online_mode(context: &Context, body: Value) -> Result<Value, Intern
     let params: SetOnlineModeParams = parse_params(body)?;
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ManageWallet).await?;
     if wallet.is_online().await {
         return Err(InternalRpcError::InvalidRequestStr("Wallet is already connected to a daemon"))
     }
@@ -327,6 +382,7 @@ async fn set_offline_mode(context: &Context, body: Value) -> Result<Value, Inter
     }
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::ManageWallet).await?;
     if !wallet.is_online().await {
         return Err(InternalRpcError::InvalidRequestStr("Wallet is already in offline mode"))
     }
@@ -341,6 +397,7 @@ async fn sign_data(context: &Context, body: Value) -> Result<Value, InternalRpcE
     let params: DataElement = parse_params(body)?;
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::Sign).await?;
     let signature = wallet.sign_data(&params.to_bytes());
     Ok(json!(signature))
 }
@@ -371,6 +428,7 @@ async fn get_matching_keys(context: &Context, body: Value) -> Result<Value, Inte
     }
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::Storage).await?;
     let tree = get_tree_name(&context, params.tree).await?;
     let storage = wallet.get_storage().read().await;
     let keys = storage.get_custom_tree_keys(&tree, &params.query)?;
@@ -382,6 +440,7 @@ async fn get_matching_keys(context: &Context, body: Value) -> Result<Value, Inte
 async fn get_value_from_key(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: GetValueFromKeyParams = parse_params(body)?;
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::Storage).await?;
     let tree = get_tree_name(&context, params.tree).await?;
 
     let storage = wallet.get_storage().read().await;
@@ -394,6 +453,7 @@ async fn get_value_from_key(context: &Context, body: Value) -> Result<Value, Int
 async fn store(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: StoreParams = parse_params(body)?;
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::Storage).await?;
     let tree = get_tree_name(&context, params.tree).await?;
     let mut storage = wallet.get_storage().write().await;
     storage.set_custom_data(&tree, &params.key, &params.value)?;
@@ -404,6 +464,7 @@ async fn store(context: &Context, body: Value) -> Result<Value, InternalRpcError
 async fn delete(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: DeleteParams = parse_params(body)?;
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::Storage).await?;
     let tree = get_tree_name(&context, params.tree).await?;
     let mut storage = wallet.get_storage().write().await;
     storage.delete_custom_data(&tree, &params.key)?;
@@ -414,6 +475,7 @@ async fn delete(context: &Context, body: Value) -> Result<Value, InternalRpcErro
 async fn has_key(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: HasKeyParams = parse_params(body)?;
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::Storage).await?;
     let tree = get_tree_name(&context, params.tree).await?;
 
     let storage = wallet.get_storage().read().await;
@@ -430,6 +492,7 @@ async fn query_db(context: &Context, body: Value) -> Result<Value, InternalRpcEr
     }
 
     let wallet: &Arc<Wallet> = context.get()?;
+    verify_rpc_app_permission(context, MethodClass::Storage).await?;
     let tree = get_tree_name(&context, params.tree).await?;
     let storage = wallet.get_storage().read().await;
     let result = storage.query_db(&tree, params.key, params.value, params.return_on_first)?;
@@ -10,8 +10,8 @@ use tokio::sync::Mutex;
 use xelis_common::{
     api::wallet::NotifyEvent,
     config,
+    context::Context,
     rpc_server::{
-        json_rpc,
         websocket,
         websocket::{
             EventWebSocketHandler,
@@ -20,12 +20,14 @@ use xelis_common::{
         },
         RPCHandler,
         RPCServerHandler,
+        RpcResponseError,
         WebSocketServerHandler
     },
     utils::spawn_task
 };
 use actix_web::{
     get,
+    HttpRequest,
     HttpResponse,
     Responder,
     HttpServer,
@@ -74,7 +76,7 @@ where
                     // WebSocket support
                     .route("/json_rpc", web::get().to(websocket::<EventWebSocketHandler<W, NotifyEvent>, Self>))
                     // HTTP support
-                    .route("/json_rpc", web::post().to(json_rpc::<W, WalletRpcServer<W>>))
+                    .route("/json_rpc", web::post().to(json_rpc::<W>))
                     .service(index)
             })
             .disable_signals()
@@ -150,6 +152,21 @@ where
     }
 }
 
+// HTTP JSON RPC handler endpoint
+// Request headers are kept in the context to identify the application calling it
+async fn json_rpc<W>(server: Data<WalletRpcServer<W>>, request: HttpRequest, body: web::Bytes) -> Result<impl Responder, RpcResponseError>
+where
+    W: Clone + Send + Sync + 'static
+{
+    let rpc_handler = server.get_rpc_handler();
+    let mut context = Context::new();
+    context.store(rpc_handler.get_data().clone());
+    context.store(request.headers().clone());
+
+    let result = rpc_handler.handle_request_with_context(context, &body).await?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
 #[get("/")]
 async fn index() -> impl Responder {
     HttpResponse::Ok().body(format!("Hello, world!\nRunning on: {}", config::VERSION))
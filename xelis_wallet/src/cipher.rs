@@ -1,6 +1,6 @@
 use anyhow::Result;
 use chacha20poly1305::{
-    aead::Aead,
+    aead::{Aead, Payload},
     XNonce,
     aead::OsRng,
     XChaCha20Poly1305,
@@ -11,9 +11,11 @@ use xelis_common::crypto::{
     HASH_SIZE,
     hash
 };
+use zeroize::Zeroizing;
 use crate::{error::WalletError, config::SALT_SIZE};
 
 
+#[derive(Clone)]
 pub struct Cipher {
     cipher: XChaCha20Poly1305,
     // this salt is used for keys and values
@@ -32,15 +34,17 @@ impl Cipher {
 
     // encrypt value passed in param and add plaintext nonce before encrypted value
     // a Nonce is generated randomly at each call
-    pub fn encrypt_value(&self, value: &[u8]) -> Result<Vec<u8>, WalletError> {
+    // associated data is authenticated but not stored, the same one must be given to decrypt
+    pub fn encrypt_value(&self, value: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, WalletError> {
         // generate unique random nonce
         let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
-        self.encrypt_value_with_nonce(value, &nonce.into())
+        self.encrypt_value_with_nonce(value, &nonce.into(), associated_data)
     }
 
     // encrypt value passed in param and add plaintext nonce before encrypted value
-    pub fn encrypt_value_with_nonce(&self, value: &[u8], nonce: &[u8; Self::NONCE_SIZE]) -> Result<Vec<u8>, WalletError> {
-        let mut plaintext: Vec<u8> = Vec::with_capacity(SALT_SIZE + value.len());
+    pub fn encrypt_value_with_nonce(&self, value: &[u8], nonce: &[u8; Self::NONCE_SIZE], associated_data: &[u8]) -> Result<Vec<u8>, WalletError> {
+        // plaintext is wiped from memory once encrypted
+        let mut plaintext: Zeroizing<Vec<u8>> = Zeroizing::new(Vec::with_capacity(SALT_SIZE + value.len()));
         // add salt to the plaintext value
         if let Some(salt) = &self.salt {
             plaintext.extend_from_slice(salt);
@@ -48,7 +52,11 @@ impl Cipher {
         plaintext.extend_from_slice(value);
 
         // encrypt data using plaintext and nonce
-        let data = &self.cipher.encrypt(nonce.into(), plaintext.as_slice()).map_err(|e| WalletError::CryptoError(e))?;
+        let payload = Payload {
+            msg: plaintext.as_slice(),
+            aad: associated_data
+        };
+        let data = &self.cipher.encrypt(nonce.into(), payload).map_err(|e| WalletError::CryptoError(e))?;
 
         // append unique nonce to the encrypted data
         let mut encrypted = Vec::with_capacity(Self::NONCE_SIZE + data.len());
@@ -59,7 +67,8 @@ impl Cipher {
     }
 
     // decrypt any value loaded from disk, with the format of above function
-    pub fn decrypt_value(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
+    // fails if the value or the associated data were modified
+    pub fn decrypt_value(&self, encrypted: &[u8], associated_data: &[u8]) -> Result<Vec<u8>> {
         // nonce is 24 bytes and is mandatory in encrypted slice
         if encrypted.len() < 25 {
             return Err(WalletError::InvalidEncryptedValue.into())
@@ -67,8 +76,12 @@ impl Cipher {
 
         // read the nonce for this data 
         let nonce = XNonce::from_slice(&encrypted[0..24]);
+        let payload = Payload {
+            msg: &encrypted[nonce.len()..],
+            aad: associated_data
+        };
         // decrypt the value using the nonce previously decoded
-        let mut decrypted = self.cipher.decrypt(nonce, &encrypted[nonce.len()..]).map_err(|e| WalletError::CryptoError(e))?;
+        let mut decrypted = self.cipher.decrypt(nonce, payload).map_err(|e| WalletError::CryptoError(e))?;
         // delete the salt from the decrypted slice
         if let Some(salt) = &self.salt {
             decrypted.drain(0..salt.len());
@@ -86,4 +99,45 @@ impl Cipher {
         data.extend_from_slice(key.as_ref());
         hash(&data).to_bytes()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher() -> Cipher {
+        Cipher::new(&[1u8; 32], Some([2u8; SALT_SIZE])).unwrap()
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_value() {
+        let cipher = cipher();
+        let encrypted = cipher.encrypt_value(b"hello world", b"tree").unwrap();
+        assert_eq!(cipher.decrypt_value(&encrypted, b"tree").unwrap(), b"hello world");
+
+        // Each encryption use its own nonce
+        let other = cipher.encrypt_value(b"hello world", b"tree").unwrap();
+        assert_ne!(encrypted[..Cipher::NONCE_SIZE], other[..Cipher::NONCE_SIZE]);
+    }
+
+    #[test]
+    fn test_bit_flip_is_detected() {
+        let cipher = cipher();
+        let encrypted = cipher.encrypt_value(b"hello world", b"tree").unwrap();
+        // Flip each bit of the nonce, the ciphertext and the tag
+        for i in 0..encrypted.len() * 8 {
+            let mut tampered = encrypted.clone();
+            tampered[i / 8] ^= 1 << (i % 8);
+            assert!(cipher.decrypt_value(&tampered, b"tree").is_err());
+        }
+    }
+
+    #[test]
+    fn test_associated_data_is_authenticated() {
+        let cipher = cipher();
+        let encrypted = cipher.encrypt_value(b"hello world", b"tree").unwrap();
+        // Value moved to another tree
+        assert!(cipher.decrypt_value(&encrypted, b"other").is_err());
+        assert!(cipher.decrypt_value(&encrypted, &[]).is_err());
+    }
 }
\ No newline at end of file
@@ -11,6 +11,10 @@ pub const KEY_SIZE: usize = 32;
 pub const DEFAULT_DAEMON_ADDRESS: &str = "http://127.0.0.1:8080";
 // Auto reconnect interval in seconds for Network Handler
 pub const AUTO_RECONNECT_INTERVAL: u64 = 5;
+// Seconds without being executed before a pending transaction is considered stale
+pub const PENDING_TX_STALE_TIMEOUT: u64 = 10 * 60;
+// Fee of a replacement transaction compared to the one it cancels
+pub const CANCEL_FEE_MULTIPLIER: u64 = 2;
 
 lazy_static! {
     pub static ref PASSWORD_ALGORITHM: Argon2<'static> = {
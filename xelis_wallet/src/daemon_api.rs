@@ -19,9 +19,14 @@ use xelis_common::{
         SubmitTransactionParams,
         BlockResponse,
         GetBlockAtTopoHeightParams,
+        GetBlockByHashParams,
         GetTransactionParams,
         GetNonceParams,
         GetNonceResult,
+        HasNonceParams,
+        HasNonceResult,
+        GetNonceHistoryParams,
+        NonceHistoryEntry,
         GetAssetsParams,
         IsTxExecutedInBlockParams,
         NotifyEvent,
@@ -92,7 +97,7 @@ impl DaemonAPI {
         self.client.call_with(method.as_str(), params).await
     }
 
-    pub async fn on_new_block_event(&self) -> Result<EventReceiver<NewBlockEvent>> {
+    pub async fn on_new_block_event(&self) -> Result<EventReceiver<NewBlockEvent<'static>>> {
         let receiver = self.client.subscribe_event(NotifyEvent::NewBlock).await?;
         Ok(receiver)
     }
@@ -151,7 +156,8 @@ impl DaemonAPI {
             maximum,
             skip,
             minimum_topoheight,
-            maximum_topoheight
+            maximum_topoheight,
+            after: None
         }).await?;
         Ok(assets)
     }
@@ -181,6 +187,14 @@ impl DaemonAPI {
         Ok(block)
     }
 
+    pub async fn get_block_by_hash(&self, hash: &Hash) -> Result<BlockResponse> {
+        let block = self.client.call_with("get_block_by_hash", &GetBlockByHashParams {
+            hash: Cow::Borrowed(hash),
+            include_txs: false
+        }).await.context(format!("Error while fetching block {}", hash))?;
+        Ok(block)
+    }
+
     pub async fn get_block_with_txs_at_topoheight(&self, topoheight: u64) -> Result<BlockResponse> {
         let block = self.client.call_with("get_block_at_topoheight", &GetBlockAtTopoHeightParams {
             topoheight,
@@ -205,7 +219,8 @@ impl DaemonAPI {
 
     pub async fn submit_transaction(&self, transaction: &Transaction) -> Result<()> {
         let _: bool = self.client.call_with("submit_transaction", &SubmitTransactionParams {
-            data: transaction.to_hex()
+            data: transaction.to_hex(),
+            idempotency_key: None
         }).await?;
         Ok(())
     }
@@ -217,6 +232,22 @@ impl DaemonAPI {
         Ok(nonce)
     }
 
+    pub async fn has_nonce(&self, address: &Address) -> Result<bool> {
+        let result: HasNonceResult = self.client.call_with("has_nonce", &HasNonceParams {
+            address: Cow::Borrowed(address),
+            topoheight: None
+        }).await.context(format!("Error while checking if address {} has a nonce", address))?;
+        Ok(result.exist)
+    }
+
+    pub async fn get_nonce_history(&self, address: &Address, max_entries: Option<usize>) -> Result<Vec<NonceHistoryEntry>> {
+        let history = self.client.call_with("get_nonce_history", &GetNonceHistoryParams {
+            address: Cow::Borrowed(address),
+            max_entries
+        }).await.context(format!("Error while fetching nonce history from address {}", address))?;
+        Ok(history)
+    }
+
     pub async fn is_tx_executed_in_block(&self, tx_hash: &Hash, block_hash: &Hash) -> Result<bool> {
         let is_executed = self.client.call_with("is_tx_executed_in_block", &IsTxExecutedInBlockParams {
             tx_hash: Cow::Borrowed(tx_hash),
@@ -0,0 +1,226 @@
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{ErrorKind, Read, Write},
+    path::{Component, Path, PathBuf}
+};
+use thiserror::Error;
+use log::{debug, warn};
+use xelis_common::time::TimestampSeconds;
+
+// File locked in the wallet directory while it is opened
+// It contains the PID of the process using it
+pub const LOCK_FILE_NAME: &str = "wallet.lock";
+
+#[derive(Error, Debug)]
+pub enum DirectoryError {
+    #[error("Wallet name cannot be empty")]
+    EmptyName,
+    #[error("Invalid wallet name '{}': it must not contain path separators or relative components", _0)]
+    InvalidName(String),
+    #[error("Wallet is already opened by process {}", _0)]
+    AlreadyOpened(u32),
+    #[error(transparent)]
+    Io(#[from] std::io::Error)
+}
+
+// Check that the wallet name is a single directory name
+// so it can't be used to go outside the wallets directory
+pub fn validate_wallet_name(name: &str) -> Result<(), DirectoryError> {
+    if name.is_empty() {
+        return Err(DirectoryError::EmptyName)
+    }
+
+    if name.contains(['/', '\\', '\0']) || name.trim() != name {
+        return Err(DirectoryError::InvalidName(name.to_owned()))
+    }
+
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(DirectoryError::InvalidName(name.to_owned()))
+    }
+}
+
+// Build the path of a wallet in the wallets directory
+pub fn get_wallet_path(wallets_dir: &Path, name: &str) -> Result<PathBuf, DirectoryError> {
+    validate_wallet_name(name)?;
+    Ok(wallets_dir.join(name))
+}
+
+// PID written in the lock file of this wallet directory
+fn read_lock_pid(file: &mut File) -> Option<u32> {
+    let mut content = String::new();
+    file.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
+
+// Returns the PID of the process having this wallet opened
+// The lock is held by the OS, so a PID left by a process not running anymore is ignored
+pub fn get_lock_owner(wallet_dir: &Path) -> Option<u32> {
+    let mut file = File::open(wallet_dir.join(LOCK_FILE_NAME)).ok()?;
+    match file.try_lock_shared() {
+        Ok(()) => None,
+        Err(TryLockError::WouldBlock) => read_lock_pid(&mut file),
+        Err(TryLockError::Error(e)) => {
+            debug!("Error while checking the lock of wallet {}: {}", wallet_dir.display(), e);
+            None
+        }
+    }
+}
+
+// Lock preventing a wallet to be opened by two processes at the same time
+// The lock is released when dropped or when the process exits, even if it crashed
+#[derive(Debug)]
+pub struct WalletLock {
+    // Locked exclusively while it is kept open
+    file: File
+}
+
+impl WalletLock {
+    // Lock the wallet directory, creating it if needed
+    pub fn acquire(wallet_dir: &Path) -> Result<Self, DirectoryError> {
+        fs::create_dir_all(wallet_dir)?;
+        let path = wallet_dir.join(LOCK_FILE_NAME);
+        // Not truncated before being locked, the PID of its owner is still readable
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {},
+            Err(TryLockError::WouldBlock) => return Err(DirectoryError::AlreadyOpened(read_lock_pid(&mut file).unwrap_or_default())),
+            Err(TryLockError::Error(e)) => return Err(e.into())
+        }
+
+        // A file left by a previous process is reused
+        let pid = std::process::id();
+        file.set_len(0)?;
+        file.write_all(pid.to_string().as_bytes())?;
+        file.sync_all()?;
+        debug!("Wallet {} locked by process {}", wallet_dir.display(), pid);
+
+        Ok(Self { file })
+    }
+}
+
+impl Drop for WalletLock {
+    fn drop(&mut self) {
+        // The file is kept: removing it while locked would let another process lock a deleted file
+        if let Err(e) = self.file.unlock() {
+            warn!("Error while unlocking wallet: {}", e);
+        }
+    }
+}
+
+// Wallet detected in the wallets directory
+#[derive(Debug)]
+pub struct WalletInfo {
+    pub name: String,
+    // Creation time of its directory if known
+    pub created_at: Option<TimestampSeconds>,
+    // PID of the process having it opened
+    pub opened_by: Option<u32>
+}
+
+// List all the wallets in the wallets directory sorted by name
+// Their storage is not opened, so it is fast and works while they are used by another process
+// The network is encrypted in the storage, it is not known without the password
+pub fn list_wallets(wallets_dir: &Path) -> Result<Vec<WalletInfo>, DirectoryError> {
+    let entries = match fs::read_dir(wallets_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into())
+    };
+
+    let mut wallets = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let path = entry.path();
+        // Only directories containing a wallet storage
+        if !path.join("conf").exists() {
+            continue;
+        }
+
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let opened_by = get_lock_owner(&path);
+        let created_at = entry.metadata()?
+            .created()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+
+        wallets.push(WalletInfo { name, created_at, opened_by });
+    }
+
+    wallets.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(wallets)
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::testing::TempDir;
+    use crate::storage::Storage;
+    use super::*;
+
+    #[test]
+    fn test_traversal_rejected() {
+        let base = Path::new("wallets");
+        for name in ["..", ".", "../other", "a/b", "a\\b", "/etc", "", " name", "na\0me"] {
+            assert!(get_wallet_path(base, name).is_err(), "{:?} should be rejected", name);
+        }
+
+        assert_eq!(get_wallet_path(base, "my-wallet").unwrap(), base.join("my-wallet"));
+        assert!(validate_wallet_name("wallet..2").is_ok());
+    }
+
+    #[test]
+    fn test_lock_held_until_dropped() {
+        let dir = TempDir::new("wallet-dir-lock");
+        let lock = WalletLock::acquire(&dir).unwrap();
+        assert_eq!(get_lock_owner(&dir), Some(std::process::id()));
+
+        // Already locked by the current process
+        assert!(matches!(WalletLock::acquire(&dir), Err(DirectoryError::AlreadyOpened(pid)) if pid == std::process::id()));
+
+        drop(lock);
+        assert!(get_lock_owner(&dir).is_none());
+        assert!(WalletLock::acquire(&dir).is_ok());
+    }
+
+    #[test]
+    fn test_stale_lock_replaced() {
+        let dir = TempDir::new("wallet-dir-stale");
+        // Left by a crashed process, its PID may be reused by any other process
+        fs::write(dir.join(LOCK_FILE_NAME), "1").unwrap();
+        assert!(get_lock_owner(&dir).is_none());
+
+        let lock = WalletLock::acquire(&dir).unwrap();
+        assert_eq!(get_lock_owner(&dir), Some(std::process::id()));
+        drop(lock);
+
+        // Corrupted lock file is stale too
+        fs::write(dir.join(LOCK_FILE_NAME), "not a pid").unwrap();
+        assert!(WalletLock::acquire(&dir).is_ok());
+    }
+
+    #[test]
+    fn test_list_wallets() {
+        let dir = TempDir::new("wallet-dir-list");
+        assert!(list_wallets(&dir).unwrap().is_empty());
+
+        let _storage = Storage::new(dir.join("b").to_string_lossy().into_owned()).unwrap();
+        drop(Storage::new(dir.join("a").to_string_lossy().into_owned()).unwrap());
+        // Not a wallet
+        fs::create_dir_all(dir.join("c")).unwrap();
+        let _lock = WalletLock::acquire(&dir.join("a")).unwrap();
+
+        // Listed even while a storage is opened
+        let wallets = list_wallets(&dir).unwrap();
+        assert_eq!(wallets.len(), 2);
+        assert_eq!(wallets[0].name, "a");
+        assert_eq!(wallets[0].opened_by, Some(std::process::id()));
+        assert_eq!(wallets[1].name, "b");
+        assert!(wallets[1].opened_by.is_none());
+    }
+}
@@ -8,8 +8,9 @@ use xelis_common::{
             TransferOut as RPCTransferOut
         }
     },
-    config::XELIS_ASSET,
+    config::{ASSET_CREATION_BURN_AMOUNT, XELIS_ASSET},
     crypto::{
+        memo,
         Hash,
         PublicKey
     },
@@ -174,6 +175,11 @@ pub enum EntryData {
         fee: u64,
         // Nonce used
         nonce: u64
+    },
+    // Registration cost is burned from XELIS_ASSET
+    AssetCreation {
+        asset: Hash,
+        decimals: u8
     }
 }
 
@@ -208,6 +214,10 @@ impl Serializer for EntryData {
 
                 Self::Outgoing { transfers, fee, nonce }
             }
+            4 => Self::AssetCreation {
+                asset: reader.read_hash()?,
+                decimals: reader.read_u8()?
+            },
             _ => return Err(ReaderError::InvalidValue)
         }) 
     }
@@ -239,6 +249,11 @@ impl Serializer for EntryData {
                 }
                 writer.write_u64(fee);
                 writer.write_u64(nonce);
+            },
+            Self::AssetCreation { asset, decimals } => {
+                writer.write_u8(4);
+                writer.write_hash(asset);
+                writer.write_u8(*decimals);
             }
         }
     }
@@ -252,24 +267,42 @@ impl Serializer for EntryData {
             },
             Self::Outgoing { transfers, fee, nonce } => {
                 2 + transfers.iter().map(|t| t.size()).sum::<usize>() + fee.size() + nonce.size()
-            }
+            },
+            Self::AssetCreation { asset, decimals } => asset.size() + decimals.size()
         }
     }
 }
 
+// Display the transaction extra data as text if possible
+// Otherwise, it is considered as binary data and displayed in hex
+// Extra data still encrypted could not be decrypted by us
+pub fn format_extra_data(data: &[u8]) -> String {
+    if memo::is_encrypted(data) {
+        return "encrypted".to_owned()
+    }
+
+    match std::str::from_utf8(data) {
+        Ok(text) if !text.chars().any(|c| c.is_control()) => text.to_owned(),
+        _ => format!("0x{}", hex::encode(data))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TransactionEntry {
     hash: Hash,
     topoheight: u64,
     entry: EntryData,
+    // Public extra data attached to the transaction
+    extra_data: Option<Vec<u8>>,
 }
 
 impl TransactionEntry {
-    pub fn new(hash: Hash, topoheight: u64, entry: EntryData) -> Self {
+    pub fn new(hash: Hash, topoheight: u64, entry: EntryData, extra_data: Option<Vec<u8>>) -> Self {
         Self {
             hash,
             topoheight,
             entry,
+            extra_data,
         }
     }
 
@@ -289,6 +322,10 @@ impl TransactionEntry {
         &mut self.entry
     }
 
+    pub fn get_extra_data(&self) -> &Option<Vec<u8>> {
+        &self.extra_data
+    }
+
     // Convert to RPC Transaction Entry
     // This is a necessary step to serialize correctly the public key into an address
     pub fn serializable(self, mainnet: bool) -> RPCTransactionEntry {
@@ -298,6 +335,7 @@ impl TransactionEntry {
             entry: match self.entry {
                 EntryData::Coinbase { reward } => RPCEntryType::Coinbase { reward },
                 EntryData::Burn { asset, amount } => RPCEntryType::Burn { asset, amount },
+                EntryData::AssetCreation { asset, decimals } => RPCEntryType::AssetCreation { asset, decimals },
                 EntryData::Incoming { from, transfers } => {
                     let transfers = transfers.into_iter().map(|t| RPCTransferIn {
                         asset: t.asset,
@@ -315,24 +353,30 @@ impl TransactionEntry {
                     }).collect();
                     RPCEntryType::Outgoing { transfers, fee, nonce }
                 }
-            }
+            },
+            extra_data: self.extra_data
         }
     }
 
     pub fn summary(&self, mainnet: bool, storage: &EncryptedStorage) -> Result<String> {
+        // Assets not tracked anymore are shown in atomic units
+        let get_decimals = |asset: &Hash| storage.get_asset_decimals(asset).unwrap_or(0);
         let entry_str = match self.get_entry() {
             EntryData::Coinbase { reward } => format!("Coinbase {} XELIS", format_xelis(*reward)),
             EntryData::Burn { asset, amount } => {
-                let decimals = storage.get_asset_decimals(asset)?;
+                let decimals = get_decimals(asset);
                 format!("Burn {} of {}", format_coin(*amount, decimals), asset)
             },
+            EntryData::AssetCreation { asset, decimals } => {
+                format!("Created asset {} with {} decimals for {} XELIS", asset, decimals, format_xelis(ASSET_CREATION_BURN_AMOUNT))
+            },
             EntryData::Incoming { from, transfers } => {
                 let mut str = String::new();
                 for transfer in transfers {
                     if *transfer.get_asset() == XELIS_ASSET {
                         str.push_str(&format!("Received {} XELIS from {}", format_xelis(transfer.get_amount()), from.as_address(mainnet)));
                     } else {
-                        let decimals = storage.get_asset_decimals(transfer.get_asset())?;
+                        let decimals = get_decimals(transfer.get_asset());
                         str.push_str(&format!("Received {} {} from {}", format_coin(transfer.get_amount(), decimals), transfer.get_asset(), from.as_address(mainnet)));
                     }
                 }
@@ -344,7 +388,7 @@ impl TransactionEntry {
                     if *transfer.get_asset() == XELIS_ASSET {
                         str.push_str(&format!("Sent {} XELIS to {}", format_xelis(transfer.get_amount()), transfer.get_destination().as_address(mainnet)));
                     } else {
-                        let decimals = storage.get_asset_decimals(transfer.get_asset())?;
+                        let decimals = get_decimals(transfer.get_asset());
                         str.push_str(&format!("Sent {} {} to {}", format_coin(transfer.get_amount(), decimals), transfer.get_asset(), transfer.get_destination().as_address(mainnet)));
                     }
                 }
@@ -352,7 +396,12 @@ impl TransactionEntry {
             }
         };
 
-        Ok(format!("Hash {} at TopoHeight {}: {}", self.hash, self.topoheight, entry_str))
+        let mut summary = format!("Hash {} at TopoHeight {}: {}", self.hash, self.topoheight, entry_str);
+        if let Some(extra_data) = &self.extra_data {
+            summary.push_str(&format!(", Memo: {}", format_extra_data(extra_data)));
+        }
+
+        Ok(summary)
     }
 }
 
@@ -361,11 +410,18 @@ impl Serializer for TransactionEntry {
         let hash = reader.read_hash()?;
         let topoheight = reader.read_u64()?;
         let entry = EntryData::read(reader)?;
+        // Entries stored before extra data support end here
+        let extra_data = if reader.size() > 0 {
+            Option::read(reader)?
+        } else {
+            None
+        };
 
         Ok(Self {
             hash,
             topoheight,
-            entry
+            entry,
+            extra_data
         })
     }
 
@@ -373,10 +429,11 @@ impl Serializer for TransactionEntry {
         writer.write_hash(&self.hash);
         writer.write_u64(&self.topoheight);
         self.entry.write(writer);
+        self.extra_data.write(writer);
     }
 
     fn size(&self) -> usize {
-        self.hash.size() + self.topoheight.size() + self.entry.size()
+        self.hash.size() + self.topoheight.size() + self.entry.size() + self.extra_data.size()
     }
 }
 
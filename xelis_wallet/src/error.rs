@@ -1,6 +1,6 @@
 use thiserror::Error;
 use chacha20poly1305::Error as CryptoError;
-use super::network_handler::NetworkError;
+use super::{account::MAX_ACCOUNT_NAME_SIZE, config::KEY_SIZE, network_handler::NetworkError};
 use xelis_common::{
     crypto::Hash,
     rpc_server::InternalRpcError,
@@ -46,6 +46,10 @@ pub enum WalletError {
     NoSaltFound,
     #[error("Your wallet contains only {} instead of {} for asset {}", format_coin(*_0, *_2), format_coin(*_1, *_2), _3)]
     NotEnoughFunds(u64, u64, u8, Hash),
+    #[error("Only {} of asset {} is available, the rest is used by pending transactions, but {} is needed", format_coin(*_0, *_2), _3, format_coin(*_1, *_2))]
+    AvailableBalanceExceeded(u64, u64, u8, Hash),
+    #[error("{} pending transactions were created before the wallet was opened, wait for them to be executed before sending a new one", _0)]
+    PendingTransactionsNotCached(u64),
     #[error("Your wallet don't have enough funds to pay fees: expected {} but have only {}", format_xelis(*_0), format_xelis(*_1))]
     NotEnoughFundsForFee(u64, u64),
     #[error("Invalid address params")]
@@ -82,6 +86,40 @@ pub enum WalletError {
     CiphertextDecode,
     #[error(transparent)]
     AEADCipherFormatError(#[from] CipherFormatError),
+    #[error("Wallet state has changed since the transaction was built")]
+    TxStateOutdated,
+    #[error("A transaction is being built, please try again later")]
+    TransactionBuildingInProgress,
+    #[error("Invalid account name, expected 1 to {} alphanumeric characters", MAX_ACCOUNT_NAME_SIZE)]
+    InvalidAccountName,
+    #[error("Account '{}' already exists", _0)]
+    AccountAlreadyExists(String),
+    #[error("Account '{}' was not found", _0)]
+    AccountNotFound(String),
+    #[error("The default and the active accounts cannot be deleted")]
+    CannotDeleteAccount,
+    #[error("Cannot switch account while the API Server is running")]
+    APIServerRunning,
+    #[error("Entry with key {} in tree {} was modified or corrupted", _1, _0)]
+    TamperedEntry(String, String),
+    #[error("Invalid private key, expected {} bytes in hex", KEY_SIZE)]
+    InvalidPrivateKey,
+    #[error("Invalid keyfile: {}", _0)]
+    InvalidKeyFile(String),
+    #[error("Unsupported keyfile version {}", _0)]
+    UnsupportedKeyFileVersion(u8),
+    #[error("Invalid passphrase or corrupted keyfile")]
+    InvalidPassphrase,
+    #[error("Asset {} is not tracked by the wallet", _0)]
+    AssetNotTracked(Hash),
+    #[error("XELIS asset is always tracked")]
+    NativeAssetAlwaysTracked,
+    #[error("Transaction {} is not pending", _0)]
+    PendingTransactionNotFound(Hash),
+    #[error("Daemon doesn't replace a pending transaction by one with the same nonce and a higher fee, it can't be cancelled")]
+    ReplaceByFeeNotSupported,
+    #[error("Only the pending transaction with nonce {} can be cancelled, this one has nonce {}", _0, _1)]
+    CannotCancelTransaction(u64, u64),
 }
 
 impl WalletError {
@@ -0,0 +1,267 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use xelis_common::{
+    crypto::PrivateKey,
+    serializer::{Serializer, Writer}
+};
+use zeroize::Zeroizing;
+use crate::{
+    cipher::Cipher,
+    config::{KEY_SIZE, PASSWORD_HASH_SIZE, SALT_SIZE},
+    error::WalletError
+};
+
+// Current version of the keyfile format
+// It is authenticated with the encrypted key and the KDF parameters, so a keyfile can't be downgraded
+pub const KEYFILE_VERSION: u8 = 1;
+// Only KDF supported by the keyfile
+pub const KEYFILE_KDF_ALGORITHM: &str = "argon2id";
+// Maximum KDF parameters accepted from a keyfile, so a crafted one can't exhaust the memory or hang
+// Memory cost in KiB, 1 GiB
+pub const KEYFILE_KDF_MAX_MEMORY_COST: u32 = 1024 * 1024;
+pub const KEYFILE_KDF_MAX_ITERATIONS: u32 = 256;
+pub const KEYFILE_KDF_MAX_PARALLELISM: u32 = 16;
+
+// Parameters of the KDF used to derive the encryption key from the passphrase
+// They are stored in the keyfile so it can still be opened if the defaults change
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct KeyFileKdf {
+    pub algorithm: String,
+    // Memory cost in KiB
+    pub memory_cost: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    // Hex encoded salt
+    pub salt: String
+}
+
+impl Default for KeyFileKdf {
+    // Same cost as the one used for the wallet password
+    fn default() -> Self {
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+
+        Self {
+            algorithm: KEYFILE_KDF_ALGORITHM.to_owned(),
+            memory_cost: 15 * 1000,
+            iterations: 16,
+            parallelism: 1,
+            salt: hex::encode(salt)
+        }
+    }
+}
+
+impl KeyFileKdf {
+    // Derive the encryption key from the passphrase
+    fn derive_key(&self, passphrase: &str) -> Result<Zeroizing<[u8; PASSWORD_HASH_SIZE]>, WalletError> {
+        if self.algorithm != KEYFILE_KDF_ALGORITHM {
+            return Err(WalletError::InvalidKeyFile(format!("unsupported KDF algorithm '{}'", self.algorithm)))
+        }
+
+        if self.memory_cost > KEYFILE_KDF_MAX_MEMORY_COST || self.iterations > KEYFILE_KDF_MAX_ITERATIONS || self.parallelism > KEYFILE_KDF_MAX_PARALLELISM {
+            return Err(WalletError::InvalidKeyFile(format!(
+                "KDF parameters above the maximum of {} KiB, {} iterations and {} lanes",
+                KEYFILE_KDF_MAX_MEMORY_COST, KEYFILE_KDF_MAX_ITERATIONS, KEYFILE_KDF_MAX_PARALLELISM
+            )))
+        }
+
+        let salt = hex::decode(&self.salt).map_err(|_| WalletError::InvalidKeyFile("invalid salt".to_owned()))?;
+        if salt.len() != SALT_SIZE {
+            return Err(WalletError::InvalidKeyFile("invalid salt size".to_owned()))
+        }
+
+        let params = Params::new(self.memory_cost, self.iterations, self.parallelism, Some(PASSWORD_HASH_SIZE))
+            .map_err(|e| WalletError::InvalidKeyFile(format!("invalid KDF parameters: {}", e)))?;
+        let algorithm = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+        let mut output = Zeroizing::new([0; PASSWORD_HASH_SIZE]);
+        algorithm.hash_password_into(passphrase.as_bytes(), &salt, &mut *output).map_err(|e| WalletError::AlgorithmHashingError(e.to_string()))?;
+        Ok(output)
+    }
+
+    // Additional data authenticated with the encrypted key
+    // The keyfile version and the KDF parameters can't be modified without failing the decryption
+    fn to_aad(&self, version: u8) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer.write_u8(version);
+        writer.write_string(&self.algorithm);
+        writer.write_u32(&self.memory_cost);
+        writer.write_u32(&self.iterations);
+        writer.write_u32(&self.parallelism);
+        writer.write_string(&self.salt);
+        writer.bytes()
+    }
+}
+
+// JSON keyfile containing the private key encrypted with a passphrase
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeyFile {
+    pub version: u8,
+    pub kdf: KeyFileKdf,
+    // Hex encoded nonce and encrypted private key
+    pub ciphertext: String
+}
+
+impl KeyFile {
+    // Encrypt the private key using the passphrase with the default KDF parameters
+    pub fn encrypt(private_key: &PrivateKey, passphrase: &str) -> Result<Self, WalletError> {
+        Self::encrypt_with_kdf(private_key, passphrase, KeyFileKdf::default())
+    }
+
+    // Encrypt the private key using the passphrase and the KDF parameters provided
+    pub fn encrypt_with_kdf(private_key: &PrivateKey, passphrase: &str, kdf: KeyFileKdf) -> Result<Self, WalletError> {
+        let key = kdf.derive_key(passphrase)?;
+        let cipher = Cipher::new(&*key, None)?;
+        let encrypted = cipher.encrypt_value(&Zeroizing::new(private_key.to_bytes()), &kdf.to_aad(KEYFILE_VERSION))?;
+
+        Ok(Self {
+            version: KEYFILE_VERSION,
+            kdf,
+            ciphertext: hex::encode(encrypted)
+        })
+    }
+
+    // Decrypt the private key using the passphrase
+    // A wrong passphrase can't be distinguished from a modified ciphertext
+    pub fn decrypt(&self, passphrase: &str) -> Result<PrivateKey, WalletError> {
+        if self.version != KEYFILE_VERSION {
+            return Err(WalletError::UnsupportedKeyFileVersion(self.version))
+        }
+
+        let encrypted = hex::decode(&self.ciphertext).map_err(|_| WalletError::InvalidKeyFile("invalid ciphertext".to_owned()))?;
+        let key = self.kdf.derive_key(passphrase)?;
+        let cipher = Cipher::new(&*key, None)?;
+        let decrypted = Zeroizing::new(cipher.decrypt_value(&encrypted, &self.kdf.to_aad(self.version)).map_err(|_| WalletError::InvalidPassphrase)?);
+
+        PrivateKey::from_bytes(&decrypted).map_err(|_| WalletError::InvalidKeyFile("invalid private key".to_owned()))
+    }
+
+    pub fn to_json(&self) -> Result<String, WalletError> {
+        serde_json::to_string_pretty(self).map_err(|e| WalletError::InvalidKeyFile(e.to_string()))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, WalletError> {
+        serde_json::from_str(json).map_err(|e| WalletError::InvalidKeyFile(e.to_string()))
+    }
+}
+
+// Parse a raw private key encoded in hex
+pub fn private_key_from_hex(value: &str) -> Result<PrivateKey, WalletError> {
+    let bytes = Zeroizing::new(hex::decode(value.trim()).map_err(|_| WalletError::InvalidPrivateKey)?);
+    if bytes.len() != KEY_SIZE {
+        return Err(WalletError::InvalidPrivateKey)
+    }
+
+    PrivateKey::from_bytes(&bytes).map_err(|_| WalletError::InvalidPrivateKey)
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use super::*;
+
+    // Low cost parameters to keep the tests fast
+    fn test_kdf() -> KeyFileKdf {
+        KeyFileKdf {
+            memory_cost: 64,
+            iterations: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_keyfile_round_trip() {
+        let keypair = KeyPair::new();
+        let keyfile = KeyFile::encrypt_with_kdf(keypair.get_private_key(), "passphrase", test_kdf()).unwrap();
+        assert_eq!(keyfile.version, KEYFILE_VERSION);
+
+        let json = keyfile.to_json().unwrap();
+        let private_key = KeyFile::from_json(&json).unwrap().decrypt("passphrase").unwrap();
+        // Same key gives the same address
+        let imported = KeyPair::from_private_key(private_key);
+        assert_eq!(imported.get_public_key().to_address(true), keypair.get_public_key().to_address(true));
+    }
+
+    #[test]
+    fn test_keyfile_wrong_passphrase() {
+        let keypair = KeyPair::new();
+        let keyfile = KeyFile::encrypt_with_kdf(keypair.get_private_key(), "passphrase", test_kdf()).unwrap();
+        assert!(matches!(keyfile.decrypt("wrong"), Err(WalletError::InvalidPassphrase)));
+    }
+
+    #[test]
+    fn test_keyfile_corrupted() {
+        let keypair = KeyPair::new();
+        let keyfile = KeyFile::encrypt_with_kdf(keypair.get_private_key(), "passphrase", test_kdf()).unwrap();
+
+        // Modified ciphertext
+        let mut corrupted = keyfile.clone();
+        let mut bytes = hex::decode(&corrupted.ciphertext).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        corrupted.ciphertext = hex::encode(bytes);
+        assert!(matches!(corrupted.decrypt("passphrase"), Err(WalletError::InvalidPassphrase)));
+
+        // Modified KDF parameters derive another key
+        let mut corrupted = keyfile.clone();
+        corrupted.kdf.iterations += 1;
+        assert!(matches!(corrupted.decrypt("passphrase"), Err(WalletError::InvalidPassphrase)));
+
+        // Unknown version
+        let mut corrupted = keyfile.clone();
+        corrupted.version = KEYFILE_VERSION + 1;
+        assert!(matches!(corrupted.decrypt("passphrase"), Err(WalletError::UnsupportedKeyFileVersion(_))));
+
+        // Invalid JSON
+        let json = keyfile.to_json().unwrap();
+        assert!(matches!(KeyFile::from_json(&json[..json.len() / 2]), Err(WalletError::InvalidKeyFile(_))));
+    }
+
+    #[test]
+    fn test_keyfile_kdf_above_maximum() {
+        let keypair = KeyPair::new();
+        let keyfile = KeyFile::encrypt_with_kdf(keypair.get_private_key(), "passphrase", test_kdf()).unwrap();
+
+        // Rejected before deriving any key, otherwise these would allocate 4 TiB or never end
+        let mut crafted = keyfile.clone();
+        crafted.kdf.memory_cost = u32::MAX;
+        assert!(matches!(crafted.decrypt("passphrase"), Err(WalletError::InvalidKeyFile(_))));
+
+        let mut crafted = keyfile.clone();
+        crafted.kdf.iterations = u32::MAX;
+        assert!(matches!(crafted.decrypt("passphrase"), Err(WalletError::InvalidKeyFile(_))));
+
+        let mut crafted = keyfile.clone();
+        crafted.kdf.parallelism = KEYFILE_KDF_MAX_PARALLELISM + 1;
+        assert!(matches!(crafted.decrypt("passphrase"), Err(WalletError::InvalidKeyFile(_))));
+
+        // Can't be used to export a keyfile either
+        let kdf = KeyFileKdf {
+            memory_cost: KEYFILE_KDF_MAX_MEMORY_COST + 1,
+            ..test_kdf()
+        };
+        assert!(matches!(KeyFile::encrypt_with_kdf(keypair.get_private_key(), "passphrase", kdf), Err(WalletError::InvalidKeyFile(_))));
+
+        // Maximum parallelism is still accepted
+        assert!(KeyFile::encrypt_with_kdf(keypair.get_private_key(), "passphrase", KeyFileKdf {
+            iterations: 2,
+            parallelism: KEYFILE_KDF_MAX_PARALLELISM,
+            memory_cost: 8 * KEYFILE_KDF_MAX_PARALLELISM,
+            ..test_kdf()
+        }).is_ok());
+    }
+
+    #[test]
+    fn test_private_key_from_hex() {
+        let keypair = KeyPair::new();
+        let value = hex::encode(keypair.get_private_key().to_bytes());
+        let private_key = private_key_from_hex(&value).unwrap();
+        assert_eq!(private_key.as_scalar(), keypair.get_private_key().as_scalar());
+
+        assert!(private_key_from_hex(&value[2..]).is_err());
+        assert!(private_key_from_hex("zz").is_err());
+        assert!(private_key_from_hex(&"00".repeat(KEY_SIZE)).is_err());
+    }
+}
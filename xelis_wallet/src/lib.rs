@@ -1,5 +1,6 @@
 pub mod storage;
 pub mod wallet;
+pub mod account;
 pub mod config;
 pub mod cipher;
 pub mod daemon_api;
@@ -8,6 +9,10 @@ pub mod entry;
 pub mod mnemonics;
 pub mod transaction_builder;
 pub mod error;
+pub mod keyfile;
+pub mod directory;
+#[cfg(test)]
+mod test_utils;
 
 #[cfg(feature = "api_server")]
 pub mod api;
\ No newline at end of file
@@ -1,24 +1,29 @@
 use std::{
     ops::ControlFlow,
-    path::Path,
+    path::{Path, PathBuf},
     sync::Arc,
     time::Duration
 };
 use anyhow::{Result, Context};
 use fern::colors::Color;
-use log::{error, info};
+use log::{error, info, warn};
 use clap::Parser;
 use xelis_common::{
     async_handler,
     config::{
+        ASSET_CREATION_BURN_AMOUNT,
         COIN_DECIMALS,
+        MAX_ASSET_DECIMALS,
         VERSION,
         XELIS_ASSET
     },
     crypto::{
         ecdlp,
+        memo,
         Address,
-        Hashable
+        Hash,
+        Hashable,
+        PrivateKey
     },
     network::Network,
     prompt::{
@@ -33,26 +38,40 @@ use xelis_common::{
             CommandHandler,
             CommandManager
         },
+        logger::ModuleLogLevel,
         LogLevel,
         Prompt,
-        PromptError
+        PromptError,
+        ShareablePrompt
     },
     serializer::Serializer,
+    time::format_timestamp_seconds,
     transaction::{
         builder::{FeeBuilder, TransactionTypeBuilder, TransferBuilder},
+        AssetCreationPayload,
         BurnPayload,
-        Transaction
+        Transaction,
+        TransactionType,
+        TX_EXTRA_DATA_LIMIT_SIZE
     },
     utils::{
         format_coin,
-        format_xelis
+        format_xelis,
+        parse_amount,
+        spawn_task
     }
 };
-#[cfg(feature = "api_server")]
-use xelis_common::utils::spawn_task;
+use tokio::sync::broadcast::error::RecvError;
+use zeroize::{Zeroize, Zeroizing};
 use xelis_wallet::{
-    wallet::Wallet,
-    config::{DEFAULT_DAEMON_ADDRESS, DIR_PATH}
+    directory::{get_wallet_path, list_wallets},
+    entry::format_extra_data,
+    keyfile::{private_key_from_hex, KeyFile},
+    storage::PendingState,
+    transaction_builder::{NonceReservation, TransactionBuilderState},
+    wallet::{Event, Wallet},
+    config::{CANCEL_FEE_MULTIPLIER, DEFAULT_DAEMON_ADDRESS, DIR_PATH},
+    error::WalletError
 };
 
 #[cfg(feature = "api_server")]
@@ -61,14 +80,13 @@ use {
         api::{
             AuthConfig,
             PermissionResult,
-            AppStateShared
+            AppStateShared,
+            ApplicationInfo,
+            MethodClass
         },
         wallet::XSWDEvent,
     },
-    xelis_common::{
-        rpc_server::RpcRequest,
-        prompt::ShareablePrompt
-    },
+    xelis_common::rpc_server::RpcRequest,
     anyhow::Error,
     tokio::sync::mpsc::UnboundedReceiver
 };
@@ -87,7 +105,11 @@ pub struct RPCConfig {
     rpc_username: Option<String>,
     /// password for RPC authentication
     #[clap(long)]
-    rpc_password: Option<String>
+    rpc_password: Option<String>,
+    /// Ask the user to approve each application calling the RPC Server
+    /// Applications must identify themselves with the X-Application-Id and X-Application-Name headers
+    #[clap(long)]
+    rpc_app_permissions: bool
 }
 
 #[derive(Parser)]
@@ -95,14 +117,30 @@ pub struct RPCConfig {
 #[command(styles = xelis_common::get_cli_styles())]
 pub struct Config {
     /// Daemon address to use
-    #[clap(long, default_value_t = String::from(DEFAULT_DAEMON_ADDRESS))]
-    daemon_address: String,
+    /// 
+    /// Can be set several times to configure failover daemons,
+    /// the next one is used automatically when the connection is lost.
+    /// By default, the daemon saved with the `set_daemon` command is used.
+    #[clap(long)]
+    daemon_address: Vec<String>,
     /// Disable online mode
     #[clap(long)]
     offline_mode: bool,
     /// Set log level
     #[clap(long, value_enum, default_value_t = LogLevel::Info)]
     log_level: LogLevel,
+    /// Set the log level for specific modules
+    /// Format is module=level, multiple modules can be separated by a comma.
+    /// Example: --log-module p2p=trace,storage=warn
+    #[clap(long, value_delimiter = ',')]
+    log_module: Vec<ModuleLogLevel>,
+    /// Maximum size in bytes of the log file before rotating it
+    /// If set, the log file is rotated based on its size instead of its date.
+    #[clap(long)]
+    file_log_max_size: Option<u64>,
+    /// Maximum number of rotated log files to keep
+    #[clap(long, default_value_t = 5)]
+    file_log_max_files: usize,
     /// Disable the log file
     #[clap(long)]
     disable_file_logging: bool,
@@ -117,6 +155,15 @@ pub struct Config {
     /// You will not be able to write CLI commands in it or to have an updated prompt
     #[clap(long)]
     disable_interactive_mode: bool,
+    /// Execute a command and exit instead of starting the interactive prompt
+    /// 
+    /// Can be set several times, commands are executed in order until one fails.
+    /// Console logs are written to stderr so only the commands output is on stdout.
+    #[clap(long)]
+    exec: Vec<String>,
+    /// Print the output of each executed command as a JSON object
+    #[clap(long)]
+    json: bool,
     /// Log filename
     /// 
     /// By default filename is xelis-wallet.log.
@@ -133,6 +180,11 @@ pub struct Config {
     /// Set the path for wallet storage to open/create a wallet at this location
     #[clap(long)]
     wallet_path: Option<String>,
+    /// Directory containing the wallets opened or created by their name
+    /// 
+    /// By default it will be wallets/ of the current directory.
+    #[clap(long, default_value_t = String::from(DIR_PATH))]
+    wallet_dir: String,
     /// Set the path to use for precomputed tables
     /// 
     /// By default, it will be from current directory.
@@ -154,9 +206,24 @@ pub struct Config {
     /// XSWD Server configuration
     #[cfg(feature = "api_server")]
     #[clap(long)]
-    enable_xswd: bool
+    enable_xswd: bool,
+    /// Disable the automatic nonce recovery
+    /// 
+    /// By default, a transaction rejected by the daemon because of its nonce
+    /// is rebuilt with the nonce expected and submitted again once.
+    #[clap(long)]
+    no_auto_nonce: bool,
+    /// Allow new transactions to spend the balance used by our pending transactions
+    /// 
+    /// By default, a transaction sending more than the confirmed balance minus the pending outgoing amounts is refused.
+    /// When allowed, only a warning is shown and the transaction or a pending one may fail.
+    #[clap(long)]
+    allow_spending_pending_balance: bool
 }
 
+// Directory containing the wallets, stored in the commands context
+struct WalletDir(PathBuf);
+
 /// This struct is used to log the progress of the table generation
 struct LogProgressTableGenerationReportFunction;
 
@@ -170,7 +237,9 @@ impl ecdlp::ProgressTableGenerationReportFunction for LogProgressTableGeneration
 #[tokio::main]
 async fn main() -> Result<()> {
     let config: Config = Config::parse();
-    let prompt = Prompt::new(config.log_level, &config.logs_path, &config.filename_log, config.disable_file_logging, config.disable_file_log_date_based, config.disable_log_color, !config.disable_interactive_mode)?;
+    // Commands to execute without the interactive prompt
+    let exec_mode = !config.exec.is_empty();
+    let prompt = Prompt::new(config.log_level, config.log_module.clone(), &config.logs_path, &config.filename_log, config.disable_file_logging, config.disable_file_log_date_based, config.file_log_max_size, config.file_log_max_files, config.disable_log_color, !config.disable_interactive_mode && !exec_mode, exec_mode)?;
 
     #[cfg(feature = "api_server")]
     {
@@ -196,6 +265,7 @@ async fn main() -> Result<()> {
 
     let command_manager = CommandManager::new(prompt.clone());
     command_manager.store_in_context(config.network)?;
+    command_manager.store_in_context(WalletDir(PathBuf::from(&config.wallet_dir)))?;
 
     command_manager.register_default_commands()?;
 
@@ -223,14 +293,25 @@ async fn main() -> Result<()> {
         command_manager.add_command(Command::new("open", "Open a wallet", CommandHandler::Async(async_handler!(open_wallet))))?;
         command_manager.add_command(Command::new("create", "Create a new wallet", CommandHandler::Async(async_handler!(create_wallet))))?;
         command_manager.add_command(Command::new("recover", "Recover a wallet using a seed", CommandHandler::Async(async_handler!(recover_wallet))))?;
-
-        // Display available commands
-        command_manager.display_commands()?;
+        command_manager.add_command(Command::with_required_arguments("import_key", "Create a new wallet from a private key in hex", vec![Arg::new("key", ArgType::String)], CommandHandler::Async(async_handler!(import_key))))?;
+        command_manager.add_command(Command::with_required_arguments("import_keyfile", "Create a new wallet from an encrypted keyfile", vec![Arg::new("path", ArgType::String)], CommandHandler::Async(async_handler!(import_keyfile))))?;
+        command_manager.add_command(Command::new("list_wallets", "List the wallets available in the wallets directory", CommandHandler::Async(async_handler!(list_wallets_command))))?;
+
+        // Display available commands and wallets
+        if !exec_mode {
+            command_manager.display_commands()?;
+            display_wallets(&command_manager, Path::new(&config.wallet_dir));
+        }
     }
 
-    if let Err(e) = prompt.start(Duration::from_millis(1000), Box::new(async_handler!(prompt_message_builder)), Some(&command_manager)).await {
-        error!("Error while running prompt: {}", e);
-    }
+    let res = if exec_mode {
+        command_manager.execute_commands(&config.exec, config.json).await
+    } else {
+        if let Err(e) = prompt.start(Duration::from_millis(1000), Box::new(async_handler!(prompt_message_builder)), Some(&command_manager)).await {
+            error!("Error while running prompt: {}", e);
+        }
+        Ok(())
+    };
 
     if let Ok(context) = command_manager.get_context().lock() {
         if let Ok(wallet) = context.get::<Arc<Wallet>>() {
@@ -238,6 +319,10 @@ async fn main() -> Result<()> {
         }
     }
 
+    if let Err(e) = res {
+        std::process::exit(e.exit_code());
+    }
+
     Ok(())
 }
 
@@ -266,6 +351,12 @@ async fn xswd_handler(mut receiver: UnboundedReceiver<XSWDEvent>, prompt: Sharea
                 if callback.send(res).is_err() {
                     error!("Error while sending permission response back to XSWD");
                 }
+            },
+            XSWDEvent::RequestAppPermission(app, class, callback) => {
+                let res = rpc_handle_request_app_permission(&prompt, app, class).await;
+                if callback.send(res).is_err() {
+                    error!("Error while sending application permission response back to RPC Server");
+                }
             }
         };
     }
@@ -310,13 +401,48 @@ async fn xswd_handle_request_permission(prompt: &ShareablePrompt, app_state: App
     })
 }
 
+#[cfg(feature = "api_server")]
+async fn rpc_handle_request_app_permission(prompt: &ShareablePrompt, app: ApplicationInfo, class: MethodClass) -> Result<PermissionResult, Error> {
+    let message = format!(
+        "RPC: Application {} ({}) requests access to {} methods\r\nDo you want to allow it ?\r\n([A]llow once / [AA] Always Allow / [D]eny): ",
+        app.get_name(),
+        app.get_id(),
+        class
+    );
+
+    let answer = prompt.read_valid_str_value(prompt.colorize_string(Color::Blue, &message), vec!["a", "aa", "d"]).await?;
+    Ok(match answer.as_str() {
+        "a" => PermissionResult::Allow,
+        "aa" => PermissionResult::AlwaysAllow,
+        // Denied requests are saved so the application can't spam the user
+        "d" => PermissionResult::AlwaysDeny,
+        _ => unreachable!()
+    })
+}
+
 // Apply the config passed in params
 async fn apply_config(wallet: &Arc<Wallet>, #[cfg(feature = "api_server")] prompt: &ShareablePrompt) {
     let config: Config = Config::parse();
 
+    if config.no_auto_nonce {
+        info!("Automatic nonce recovery is disabled");
+        wallet.set_auto_nonce_recovery(false);
+    }
+
+    if config.allow_spending_pending_balance {
+        warn!("Balance used by pending transactions can be spent");
+        wallet.set_spending_pending_balance(true);
+    }
+
     if !config.offline_mode {
-        info!("Trying to connect to daemon at '{}'", config.daemon_address);
-        if let Err(e) = wallet.set_online_mode(&config.daemon_address, true).await {
+        let daemon_addresses = if config.daemon_address.is_empty() {
+            vec![get_default_daemon_address(wallet).await]
+        } else {
+            config.daemon_address
+        };
+
+        info!("Trying to connect to daemon at '{}'", daemon_addresses.join("', '"));
+        if let Err(e) = wallet.set_online_mode_with_failover(daemon_addresses, true).await {
             error!("Couldn't connect to daemon: {}", e);
             info!("You can activate online mode using 'online_mode [daemon_address]'");
         } else {
@@ -342,9 +468,15 @@ async fn apply_config(wallet: &Arc<Wallet>, #[cfg(feature = "api_server")] promp
             };
 
             info!("Enabling RPC Server on {} {}", address, if auth_config.is_some() { "with authentication" } else { "without authentication" });
-            if let Err(e) = wallet.enable_rpc_server(address, auth_config).await {
-                error!("Error while enabling RPC Server: {}", e);
-            }
+            match wallet.enable_rpc_server(address, auth_config, config.rpc.rpc_app_permissions).await {
+                Ok(Some(receiver)) => {
+                    info!("Applications must be approved to use the RPC Server");
+                    let prompt = prompt.clone();
+                    spawn_task("rpc-permissions-handler", xswd_handler(receiver, prompt));
+                },
+                Ok(None) => {},
+                Err(e) => error!("Error while enabling RPC Server: {}", e)
+            };
         } else if config.enable_xswd {
             match wallet.enable_xswd().await {
                 Ok(receiver) => {
@@ -358,36 +490,61 @@ async fn apply_config(wallet: &Arc<Wallet>, #[cfg(feature = "api_server")] promp
     }
 }
 
+// Daemon address saved in the wallet or the default one
+async fn get_default_daemon_address(wallet: &Wallet) -> String {
+    let storage = wallet.get_storage().read().await;
+    match storage.get_daemon_address() {
+        Ok(Some(daemon_address)) => daemon_address,
+        Ok(None) => DEFAULT_DAEMON_ADDRESS.to_string(),
+        Err(e) => {
+            warn!("Error while reading saved daemon address: {}", e);
+            DEFAULT_DAEMON_ADDRESS.to_string()
+        }
+    }
+}
+
 // Function to build the CommandManager when a wallet is open
 async fn setup_wallet_command_manager(wallet: Arc<Wallet>, command_manager: &CommandManager) -> Result<(), CommandError> {
     // Delete commands for opening a wallet
     command_manager.remove_command("open")?;
     command_manager.remove_command("recover")?;
     command_manager.remove_command("create")?;
+    command_manager.remove_command("import_key")?;
+    command_manager.remove_command("import_keyfile")?;
+    command_manager.remove_command("list_wallets")?;
 
     // Add wallet commands
     command_manager.add_command(Command::new("change_password", "Set a new password to open your wallet", CommandHandler::Async(async_handler!(change_password))))?;
-    command_manager.add_command(Command::with_optional_arguments("transfer", "Send asset to a specified address", vec![Arg::new("asset", ArgType::Hash)], CommandHandler::Async(async_handler!(transfer))))?;
+    command_manager.add_command(Command::with_optional_arguments("transfer", "Send asset to a specified address", vec![Arg::new("asset", ArgType::Hash), Arg::new("yes", ArgType::Bool), Arg::new("estimate_only", ArgType::Bool), Arg::new("memo", ArgType::String), Arg::new("encrypt_memo", ArgType::Bool)], CommandHandler::Async(async_handler!(transfer))))?;
     command_manager.add_command(Command::with_optional_arguments("transfer_all", "Send all your asset balance to a specified address", vec![Arg::new("asset", ArgType::Hash)], CommandHandler::Async(async_handler!(transfer_all))))?;
-    command_manager.add_command(Command::with_required_arguments("burn", "Burn amount of asset", vec![Arg::new("asset", ArgType::Hash), Arg::new("amount", ArgType::Number)], CommandHandler::Async(async_handler!(burn))))?;
+    command_manager.add_command(Command::with_required_arguments("burn", "Burn amount of asset", vec![Arg::new("asset", ArgType::Hash), Arg::new("amount", ArgType::String)], CommandHandler::Async(async_handler!(burn))))?;
+    command_manager.add_command(Command::with_optional_arguments("create_asset", "Register a new asset by burning the creation cost", vec![Arg::new("decimals", ArgType::Number)], CommandHandler::Async(async_handler!(create_asset))))?;
     command_manager.add_command(Command::new("display_address", "Show your wallet address", CommandHandler::Async(async_handler!(display_address))))?;
     command_manager.add_command(Command::with_optional_arguments("balance", "List all non-zero balances or show the selected one", vec![Arg::new("asset", ArgType::Hash)], CommandHandler::Async(async_handler!(balance))))?;
-    command_manager.add_command(Command::with_optional_arguments("history", "Show all your transactions", vec![Arg::new("page", ArgType::Number)], CommandHandler::Async(async_handler!(history))))?;
+    command_manager.add_command(Command::with_optional_arguments("history", "Show all your transactions", vec![Arg::new("page", ArgType::Number), Arg::new("pending", ArgType::Bool)], CommandHandler::Async(async_handler!(history))))?;
+    command_manager.add_command(Command::with_required_arguments("cancel", "Cancel a pending transaction by replacing it with a higher fee", vec![Arg::new("tx_hash", ArgType::Hash)], CommandHandler::Async(async_handler!(cancel))))?;
+    command_manager.add_command(Command::with_required_arguments("rebroadcast", "Broadcast again a pending transaction", vec![Arg::new("tx_hash", ArgType::Hash)], CommandHandler::Async(async_handler!(rebroadcast))))?;
     command_manager.add_command(Command::with_optional_arguments("online_mode", "Set your wallet in online mode", vec![Arg::new("daemon_address", ArgType::String)], CommandHandler::Async(async_handler!(online_mode))))?;
     command_manager.add_command(Command::new("offline_mode", "Set your wallet in offline mode", CommandHandler::Async(async_handler!(offline_mode))))?;
+    command_manager.add_command(Command::with_required_arguments("set_daemon", "Switch to another daemon and use it by default", vec![Arg::new("daemon_address", ArgType::String)], CommandHandler::Async(async_handler!(set_daemon))))?;
     command_manager.add_command(Command::with_optional_arguments("rescan", "Rescan balance and transactions", vec![Arg::new("topoheight", ArgType::Number)], CommandHandler::Async(async_handler!(rescan))))?;
     command_manager.add_command(Command::with_optional_arguments("seed", "Show seed of selected language", vec![Arg::new("language", ArgType::Number)], CommandHandler::Async(async_handler!(seed))))?;
+    command_manager.add_command(Command::with_required_arguments("export_key", "Export your private key in a keyfile encrypted with a passphrase", vec![Arg::new("file", ArgType::String)], CommandHandler::Async(async_handler!(export_key))))?;
     command_manager.add_command(Command::new("nonce", "Show current nonce", CommandHandler::Async(async_handler!(nonce))))?;
     command_manager.add_command(Command::new("set_nonce", "Set new nonce", CommandHandler::Async(async_handler!(set_nonce))))?;
+    command_manager.add_command(Command::new("audit_nonce", "Compare the local nonce against the daemon and fix it if needed", CommandHandler::Async(async_handler!(audit_nonce))))?;
+    command_manager.add_command(Command::with_arguments("asset", "Manage the assets tracked (list, add, remove)", vec![Arg::new("action", ArgType::String)], vec![Arg::new("asset", ArgType::Hash)], CommandHandler::Async(async_handler!(asset))))?;
+    command_manager.add_command(Command::with_arguments("account", "Manage your accounts (list, create, switch, rename, delete)", vec![Arg::new("action", ArgType::String)], vec![Arg::new("name", ArgType::String), Arg::new("new_name", ArgType::String)], CommandHandler::Async(async_handler!(account))))?;
 
     #[cfg(feature = "api_server")]
     {
         // Unauthenticated RPC Server can only be created by launch arguments option
-        command_manager.add_command(Command::with_required_arguments("start_rpc_server", "Start the RPC Server", vec![
+        command_manager.add_command(Command::with_arguments("start_rpc_server", "Start the RPC Server", vec![
             Arg::new("bind_address", ArgType::String),
             Arg::new("username", ArgType::String),
             Arg::new("password", ArgType::String)
-        ], CommandHandler::Async(async_handler!(start_rpc_server))))?;
+        ], vec![Arg::new("app_permissions", ArgType::Bool)], CommandHandler::Async(async_handler!(start_rpc_server))))?;
+        command_manager.add_command(Command::with_arguments("app_permissions", "Manage the permissions of RPC applications (list, revoke)", vec![Arg::new("action", ArgType::String)], vec![Arg::new("app_id", ArgType::String)], CommandHandler::Async(async_handler!(app_permissions))))?;
 
         command_manager.add_command(Command::new("start_xswd", "Start the XSWD Server",  CommandHandler::Async(async_handler!(start_xswd))))?;
 
@@ -395,12 +552,32 @@ async fn setup_wallet_command_manager(wallet: Arc<Wallet>, command_manager: &Com
         command_manager.add_command(Command::new("stop_api_server", "Stop the API (XSWD/RPC) Server", CommandHandler::Async(async_handler!(stop_api_server))))?;
     }
 
+    refresh_prompt_on_events(&wallet, command_manager.get_prompt().clone()).await;
+
     let mut context = command_manager.get_context().lock()?;
     context.store(wallet);
 
     command_manager.display_commands()
 }
 
+// Rebuild the prompt as soon as the wallet state changes instead of waiting the next update
+// The task stops once the wallet events channel is closed
+async fn refresh_prompt_on_events(wallet: &Wallet, prompt: ShareablePrompt) {
+    let mut receiver = wallet.subscribe_events().await;
+    spawn_task("prompt-events", async move {
+        loop {
+            match receiver.recv().await {
+                Ok(Event::NewAsset(data)) => {
+                    info!("New asset {} tracked with {} decimals", data.get_asset(), data.get_data().get_decimals());
+                    prompt.request_update();
+                },
+                Ok(_) | Err(RecvError::Lagged(_)) => prompt.request_update(),
+                Err(RecvError::Closed) => break
+            }
+        }
+    });
+}
+
 // Function passed as param to prompt to build the prompt message shown
 async fn prompt_message_builder(prompt: &Prompt, command_manager: Option<&CommandManager>) -> Result<String, PromptError> {
     if let Some(manager) = command_manager {
@@ -414,18 +591,33 @@ async fn prompt_message_builder(prompt: &Prompt, command_manager: Option<&Comman
             };
     
             let storage = wallet.get_storage().read().await;
-            let topoheight_str = format!(
-                "{}: {}",
-                prompt.colorize_str(Color::Yellow, "TopoHeight"),
-                prompt.colorize_string(Color::Green, &format!("{}", storage.get_synced_topoheight().unwrap_or(0)))
-            );
+            let synced_topoheight = storage.get_synced_topoheight().unwrap_or(0);
+            let topoheight_str = match wallet.get_rescan_target(synced_topoheight) {
+                Some(target) => format!(
+                    "{}: {}",
+                    prompt.colorize_str(Color::Yellow, "Rescan"),
+                    prompt.colorize_string(Color::Green, &format!("{}/{}", synced_topoheight, target))
+                ),
+                None => format!(
+                    "{}: {}",
+                    prompt.colorize_str(Color::Yellow, "TopoHeight"),
+                    prompt.colorize_string(Color::Green, &format!("{}", synced_topoheight))
+                )
+            };
             let balance = format!(
                 "{}: {}",
                 prompt.colorize_str(Color::Yellow, "Balance"),
                 prompt.colorize_string(Color::Green, &format_xelis(storage.get_plaintext_balance_for(&XELIS_ASSET).await.unwrap_or(0))),
             );
             let status = if wallet.is_online().await {
-                prompt.colorize_str(Color::Green, "Online")
+                match wallet.get_daemon_address().await {
+                    Some(daemon_address) => format!(
+                        "{} {}",
+                        prompt.colorize_str(Color::Green, "Online"),
+                        prompt.colorize_string(Color::BrightBlack, &format!("({})", daemon_address))
+                    ),
+                    None => prompt.colorize_str(Color::Green, "Online")
+                }
             } else {
                 prompt.colorize_str(Color::Red, "Offline")
             };
@@ -460,18 +652,71 @@ async fn prompt_message_builder(prompt: &Prompt, command_manager: Option<&Comman
     )
 }
 
+// Build the path of the wallet in the wallets directory
+// The error is displayed if the name is not valid
+fn get_wallet_dir(manager: &CommandManager, name: &str) -> Result<Option<String>, CommandError> {
+    let path = {
+        let context = manager.get_context().lock()?;
+        let dir: &WalletDir = context.get()?;
+        get_wallet_path(&dir.0, name)
+    };
+
+    match path {
+        Ok(path) => Ok(Some(path.to_string_lossy().into_owned())),
+        Err(e) => {
+            manager.error(e);
+            Ok(None)
+        }
+    }
+}
+
+// Show the wallets detected in the directory with their creation date
+fn display_wallets(manager: &CommandManager, dir: &Path) {
+    let wallets = match list_wallets(dir) {
+        Ok(wallets) => wallets,
+        Err(e) => {
+            manager.error(format!("Error while listing wallets in {}: {}", dir.display(), e));
+            return
+        }
+    };
+
+    if wallets.is_empty() {
+        manager.message(format!("No wallet found in {}", dir.display()));
+        return
+    }
+
+    manager.message(format!("Wallets in {}:", dir.display()));
+    for wallet in wallets {
+        let created_at = wallet.created_at.map(format_timestamp_seconds).unwrap_or_else(|| "unknown".to_owned());
+        let status = match wallet.opened_by {
+            Some(pid) => format!(" (opened by process {})", pid),
+            None => String::new()
+        };
+        manager.message(format!("- {} created at {}{}", wallet.name, created_at, status));
+    }
+}
+
+// List the wallets available in the wallets directory
+async fn list_wallets_command(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+    let dir = {
+        let context = manager.get_context().lock()?;
+        let dir: &WalletDir = context.get()?;
+        dir.0.clone()
+    };
+
+    display_wallets(manager, &dir);
+    Ok(())
+}
+
 // Open a wallet based on the wallet name and its password
 async fn open_wallet(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let prompt = manager.get_prompt();
     let name = prompt.read_input("Wallet name: ", false)
         .await.context("Error while reading wallet name")?;
 
-    if name.is_empty() {
-        manager.error("Wallet name cannot be empty");
+    let Some(dir) = get_wallet_dir(manager, &name)? else {
         return Ok(())
-    }
-
-    let dir = format!("{}{}", DIR_PATH, name);
+    };
     if !Path::new(&dir).is_dir() {
         manager.message("No wallet found with this name");
         return Ok(())
@@ -502,12 +747,9 @@ async fn create_wallet(manager: &CommandManager, _: ArgumentManager) -> Result<(
     let name = prompt.read_input("Wallet name: ", false)
         .await.context("Error while reading wallet name")?;
 
-    if name.is_empty() {
-        manager.error("Wallet name cannot be empty");
+    let Some(dir) = get_wallet_dir(manager, &name)? else {
         return Ok(())
-    }
-
-    let dir = format!("{}{}", DIR_PATH, name);
+    };
     // check if it doesn't exists yet
     if Path::new(&dir).is_dir() {
         manager.message("Wallet already exist with this name!");
@@ -515,12 +757,13 @@ async fn create_wallet(manager: &CommandManager, _: ArgumentManager) -> Result<(
     }
 
     // ask and verify password
-    let password = prompt.read_input("Password: ", true)
-        .await.context("Error while reading password")?;
-    let confirm_password = prompt.read_input("Confirm Password: ", true)
+    let mut password = prompt.read_input("Password: ", true)
         .await.context("Error while reading password")?;
+    let confirm_password = Zeroizing::new(prompt.read_input("Confirm Password: ", true)
+        .await.context("Error while reading password")?);
 
-    if password != confirm_password {
+    if password != *confirm_password {
+        password.zeroize();
         manager.message("Confirm password doesn't match password");        
         return Ok(())
     }
@@ -537,7 +780,7 @@ async fn create_wallet(manager: &CommandManager, _: ArgumentManager) -> Result<(
 
     // Display the seed in prompt
     {
-        let seed = wallet.get_seed(0)?; // TODO language index
+        let seed = wallet.get_seed(0).await?; // TODO language index
         prompt.read_input(format!("Seed: {}\r\nPress ENTER to continue", seed), false)
             .await.context("Error while displaying seed")?;
     }
@@ -563,12 +806,9 @@ async fn recover_wallet(manager: &CommandManager, _: ArgumentManager) -> Result<
     let name = prompt.read_input("Wallet name: ", false)
         .await.context("Error while reading wallet name")?;
 
-    if name.is_empty() {
-        manager.error("Wallet name cannot be empty");
+    let Some(dir) = get_wallet_dir(manager, &name)? else {
         return Ok(())
-    }
-
-    let dir = format!("{}{}", DIR_PATH, name);
+    };
     // check if it doesn't exists yet
     if Path::new(&dir).is_dir() {
         manager.message("Wallet already exist with this name!");
@@ -576,12 +816,13 @@ async fn recover_wallet(manager: &CommandManager, _: ArgumentManager) -> Result<
     }
 
     // ask and verify password
-    let password = prompt.read_input("Password: ", true)
-        .await.context("Error while reading password")?;
-    let confirm_password = prompt.read_input("Confirm Password: ", true)
+    let mut password = prompt.read_input("Password: ", true)
         .await.context("Error while reading password")?;
+    let confirm_password = Zeroizing::new(prompt.read_input("Confirm Password: ", true)
+        .await.context("Error while reading password")?);
 
-    if password != confirm_password {
+    if password != *confirm_password {
+        password.zeroize();
         manager.message("Confirm password doesn't match password");        
         return Ok(())
     }
@@ -602,6 +843,129 @@ async fn recover_wallet(manager: &CommandManager, _: ArgumentManager) -> Result<
     Ok(())
 }
 
+// Create a new wallet from a private key given in hex
+async fn import_key(manager: &CommandManager, mut args: ArgumentManager) -> Result<(), CommandError> {
+    let value = Zeroizing::new(args.get_value("key")?.to_string_value()?);
+    let private_key = match private_key_from_hex(&value) {
+        Ok(private_key) => private_key,
+        Err(e) => {
+            manager.error(e.to_string());
+            return Ok(())
+        }
+    };
+
+    import_wallet(manager, private_key).await
+}
+
+// Create a new wallet from a keyfile exported with the export_key command
+async fn import_keyfile(manager: &CommandManager, mut args: ArgumentManager) -> Result<(), CommandError> {
+    let path = args.get_value("path")?.to_string_value()?;
+    let content = std::fs::read_to_string(&path)
+        .context("Error while reading keyfile")?;
+
+    let keyfile = match KeyFile::from_json(&content) {
+        Ok(keyfile) => keyfile,
+        Err(e) => {
+            manager.error(e.to_string());
+            return Ok(())
+        }
+    };
+
+    let prompt = manager.get_prompt();
+    let passphrase = Zeroizing::new(prompt.read_input("Keyfile passphrase: ", true)
+        .await.context("Error while reading passphrase")?);
+
+    let private_key = match keyfile.decrypt(&passphrase) {
+        Ok(private_key) => private_key,
+        Err(e) => {
+            manager.error(e.to_string());
+            return Ok(())
+        }
+    };
+
+    import_wallet(manager, private_key).await
+}
+
+// Create a new wallet with an imported private key by requesting name, password
+async fn import_wallet(manager: &CommandManager, private_key: PrivateKey) -> Result<(), CommandError> {
+    let prompt = manager.get_prompt();
+
+    let name = prompt.read_input("Wallet name: ", false)
+        .await.context("Error while reading wallet name")?;
+
+    let Some(dir) = get_wallet_dir(manager, &name)? else {
+        return Ok(())
+    };
+    // check if it doesn't exists yet
+    if Path::new(&dir).is_dir() {
+        manager.message("Wallet already exist with this name!");
+        return Ok(())
+    }
+
+    // ask and verify password
+    let mut password = prompt.read_input("Password: ", true)
+        .await.context("Error while reading password")?;
+    let confirm_password = Zeroizing::new(prompt.read_input("Confirm Password: ", true)
+        .await.context("Error while reading password")?);
+
+    if password != *confirm_password {
+        password.zeroize();
+        manager.message("Confirm password doesn't match password");        
+        return Ok(())
+    }
+
+    let wallet = {
+        let context = manager.get_context().lock()?;
+        let network = context.get::<Network>()?;
+        let precomputed_tables = Wallet::read_or_generate_precomputed_tables(None, LogProgressTableGenerationReportFunction)?;
+        Wallet::create_from_private_key(dir, password, private_key, *network, precomputed_tables)?
+    };
+
+    manager.message(format!("Wallet sucessfully imported: {}", wallet.get_address()));
+    apply_config(&wallet, #[cfg(feature = "api_server")] prompt).await;
+
+    setup_wallet_command_manager(wallet, manager).await?;
+
+    Ok(())
+}
+
+// Export the private key in a keyfile encrypted with a passphrase
+async fn export_key(manager: &CommandManager, mut args: ArgumentManager) -> Result<(), CommandError> {
+    let path = args.get_value("file")?.to_string_value()?;
+    // Never overwrite an existing file, it may be another keyfile
+    if Path::new(&path).exists() {
+        manager.error(format!("File {} already exists", path));
+        return Ok(())
+    }
+
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let prompt = manager.get_prompt();
+
+    let password = prompt.read_input("Password: ", true)
+        .await.context("Error while reading password")?;
+    // check if password is valid
+    wallet.is_valid_password(password).await?;
+
+    let passphrase = Zeroizing::new(prompt.read_input("Keyfile passphrase: ", true)
+        .await.context("Error while reading passphrase")?);
+    let confirm_passphrase = Zeroizing::new(prompt.read_input("Confirm keyfile passphrase: ", true)
+        .await.context("Error while reading passphrase")?);
+
+    if passphrase != confirm_passphrase {
+        manager.message("Confirm passphrase doesn't match passphrase");
+        return Ok(())
+    }
+
+    manager.message("Encrypting private key...");
+    let keyfile = wallet.export_keyfile(&passphrase).await?;
+    std::fs::write(&path, keyfile.to_json().context("Error while serializing keyfile")?)
+        .context("Error while writing keyfile")?;
+
+    manager.message(format!("Private key exported to {}", path));
+    Ok(())
+}
+
 // Change wallet password
 async fn change_password(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
@@ -623,22 +987,75 @@ async fn change_password(manager: &CommandManager, _: ArgumentManager) -> Result
     Ok(())
 }
 
+// Parse an amount typed by the user using the decimals of the asset
+fn parse_asset_amount(value: &str, asset: &Hash, decimals: u8) -> Result<u64, CommandError> {
+    parse_amount(value, decimals).map_err(|e| {
+        let asset = if *asset == XELIS_ASSET {
+            "XELIS".to_owned()
+        } else {
+            asset.to_string()
+        };
+        CommandError::InvalidArgument(format!("{} for {}", e, asset))
+    })
+}
+
 // Create a new transfer to a specified address
-async fn transfer(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+async fn transfer(manager: &CommandManager, mut args: ArgumentManager) -> Result<(), CommandError> {
     let prompt = manager.get_prompt();
     let context = manager.get_context().lock()?;
     let wallet: &Arc<Wallet> = context.get()?;
 
+    let skip_confirmation = if args.has_argument("yes") {
+        args.get_value("yes")?.to_bool()?
+    } else {
+        false
+    };
+
+    let estimate_only = if args.has_argument("estimate_only") {
+        args.get_value("estimate_only")?.to_bool()?
+    } else {
+        false
+    };
+
+    // Memo is readable only by us and the recipient
+    let encrypt_memo = if args.has_argument("encrypt_memo") {
+        args.get_value("encrypt_memo")?.to_bool()?
+    } else {
+        false
+    };
+
+    // Memo is stored on chain as UTF-8, public unless encrypted
+    let memo = if args.has_argument("memo") {
+        let memo = args.get_value("memo")?.to_string_value()?;
+        // Encryption overhead is counted in the limit
+        let max_size = if encrypt_memo {
+            TX_EXTRA_DATA_LIMIT_SIZE - memo::encrypted_size(0, 1)
+        } else {
+            TX_EXTRA_DATA_LIMIT_SIZE
+        };
+
+        if memo.len() > max_size {
+            return Err(CommandError::InvalidArgument(format!("Memo is too large: {} bytes, maximum is {} bytes", memo.len(), max_size)))
+        }
+        Some(memo.into_bytes()).filter(|memo| !memo.is_empty())
+    } else {
+        None
+    };
+
     // read address
     let str_address = prompt.read_input(
         prompt.colorize_str(Color::Green, "Address: "),
         false
     ).await.context("Error while reading address")?;
-    let address = Address::from_string(&str_address).context("Invalid address")?;
+    let address = Address::from_string(&str_address)
+        .map_err(|e| CommandError::InvalidArgument(format!("Invalid address: {}", e)))?;
 
-    let asset = prompt.read_hash(
-        prompt.colorize_str(Color::Green, "Asset (default XELIS): ")
-    ).await.ok();
+    let mut asset = args.get_value("asset").and_then(|v| v.to_hash()).ok();
+    if asset.is_none() {
+        asset = prompt.read_hash(
+            prompt.colorize_str(Color::Green, "Asset (default XELIS): ")
+        ).await.ok();
+    }
 
     let asset = asset.unwrap_or(XELIS_ASSET);
 
@@ -650,31 +1067,59 @@ async fn transfer(manager: &CommandManager, _: ArgumentManager) -> Result<(), Co
     };
 
     // read amount
-    let float_amount: f64 = prompt.read(
-        prompt.colorize_string(Color::Green, &format!("Amount (max: {}): ", format_coin(max_balance, decimals)))
+    let str_amount = prompt.read_input(
+        prompt.colorize_string(Color::Green, &format!("Amount (max: {}): ", format_coin(max_balance, decimals))),
+        false
     ).await.context("Error while reading amount")?;
 
-    let amount = (float_amount * 10u32.pow(decimals as u32) as f64) as u64;
-    manager.message(format!("Sending {} of {} to {}", format_coin(amount, decimals), asset, address.to_string()));
+    let amount = parse_asset_amount(&str_amount, &asset, decimals)?;
 
-    if !prompt.ask_confirmation().await.context("Error while confirming action")? {
-        manager.message("Transaction has been aborted");
+    let transfer = TransferBuilder {
+        destination: address.clone(),
+        amount,
+        asset: asset.clone(),
+        extra_data: None
+    };
+    let tx_type = TransactionTypeBuilder::Transfers(vec![transfer]);
+
+    if estimate_only {
+        let (fee, size) = wallet.estimate_fees_and_size(tx_type, memo, encrypt_memo).await
+            .context("Error while estimating fees")?;
+        manager.message(format!("Estimated fee: {}", format_xelis(fee)));
+        manager.message(format!("Estimated size: {} bytes", size));
         return Ok(())
     }
 
+    // Reserve the nonce, this prevent any rescan or other transaction until it is applied or aborted
+    let reservation = wallet.reserve_nonce().await;
+
     manager.message("Building transaction...");
 
-    let transfer = TransferBuilder {
-        destination: address,
-        amount,
-        asset,
-        extra_data: None
+    // Build the transaction without updating the storage
+    // Changes are only applied once the transaction has been confirmed
+    let (state, tx) = {
+        let storage = wallet.get_storage().read().await;
+        wallet.create_transaction_with_storage(&storage, tx_type.clone(), FeeBuilder::default(), memo.clone(), encrypt_memo).await
+            .context("Error while creating transaction")?
     };
-    let tx = wallet.create_transaction(TransactionTypeBuilder::Transfers(vec![transfer]), FeeBuilder::default()).await
-        .context("Error while creating transaction")?;
 
-    broadcast_tx(wallet, manager, tx).await;
-    Ok(())
+    let new_balance = state.get_balance_amount(&asset).unwrap_or(0);
+    manager.message(format!("Recipient: {}", address));
+    manager.message(format!("Amount: {}", format_coin(amount, decimals)));
+    manager.message(format!("Asset: {}", asset));
+    if let Some(memo) = &memo {
+        manager.message(format!("Memo: {}{}", format_extra_data(memo), if encrypt_memo { " (encrypted)" } else { "" }));
+    }
+    manager.message(format!("Fee: {}", format_xelis(tx.get_fee())));
+    manager.message(format!("Balance after transaction: {}", format_coin(new_balance, decimals)));
+    manager.message(format!("Nonce: {}", tx.get_nonce()));
+
+    if !skip_confirmation && !prompt.ask_confirmation().await.context("Error while confirming action")? {
+        manager.message("Transaction has been aborted");
+        return Ok(())
+    }
+
+    broadcast_tx(wallet, manager, reservation, state, tx, tx_type, memo, encrypt_memo).await
 }
 
 
@@ -689,7 +1134,8 @@ async fn transfer_all(manager: &CommandManager, mut args: ArgumentManager) -> Re
         prompt.colorize_str(Color::Green, "Address: "),
         false
     ).await.context("Error while reading address")?;
-    let address = Address::from_string(&str_address).context("Invalid address")?;
+    let address = Address::from_string(&str_address)
+        .map_err(|e| CommandError::InvalidArgument(format!("Invalid address: {}", e)))?;
 
     let mut asset = args.get_value("asset").and_then(|v| v.to_hash()).ok();
     if asset.is_none() {
@@ -713,7 +1159,7 @@ async fn transfer_all(manager: &CommandManager, mut args: ArgumentManager) -> Re
         extra_data: None
     };
     let tx_type = TransactionTypeBuilder::Transfers(vec![transfer]);
-    let estimated_fees = wallet.estimate_fees(tx_type.clone()).await.context("Error while estimating fees")?;
+    let estimated_fees = wallet.estimate_fees(tx_type.clone(), None, false).await.context("Error while estimating fees")?;
 
     if asset == XELIS_ASSET {
         amount -= estimated_fees;
@@ -734,35 +1180,72 @@ async fn transfer_all(manager: &CommandManager, mut args: ArgumentManager) -> Re
     };
     let tx_type = TransactionTypeBuilder::Transfers(vec![transfer]);
 
+    let reservation = wallet.reserve_nonce().await;
+
     manager.message("Building transaction...");
 
-    let tx = wallet.create_transaction(tx_type, FeeBuilder::default()).await
-        .context("Error while creating transaction")?;
+    let (state, tx) = {
+        let storage = wallet.get_storage().read().await;
+        wallet.create_transaction_with_storage(&storage, tx_type.clone(), FeeBuilder::default(), None, false).await
+            .context("Error while creating transaction")?
+    };
 
-    broadcast_tx(wallet, manager, tx).await;
-    Ok(())
+    broadcast_tx(wallet, manager, reservation, state, tx, tx_type, None, false).await
 }
 
 async fn burn(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
-    let amount = arguments.get_value("amount")?.to_number()?;
+    let str_amount = arguments.get_value("amount")?.to_string_value()?;
     let asset = arguments.get_value("asset")?.to_hash()?;
     let context = manager.get_context().lock()?;
     let wallet: &Arc<Wallet> = context.get()?;
-    {
+    let amount = {
         let storage = wallet.get_storage().read().await;
         let decimals = storage.get_asset_decimals(&asset).unwrap_or(COIN_DECIMALS);
+        let amount = parse_asset_amount(&str_amount, &asset, decimals)?;
 
         manager.message(format!("Burning {} of {}", format_coin(amount, decimals), asset));
-    }
+        amount
+    };
     let payload = BurnPayload {
         amount,
         asset
     };
-    let tx = wallet.create_transaction(TransactionTypeBuilder::Burn(payload), FeeBuilder::Multiplier(1f64)).await
-        .context("Error while creating transaction")?;
+    let tx_type = TransactionTypeBuilder::Burn(payload);
+    let reservation = wallet.reserve_nonce().await;
+    let (state, tx) = {
+        let storage = wallet.get_storage().read().await;
+        wallet.create_transaction_with_storage(&storage, tx_type.clone(), FeeBuilder::default(), None, false).await
+            .context("Error while creating transaction")?
+    };
 
-    broadcast_tx(wallet, manager, tx).await;
-    Ok(())
+    broadcast_tx(wallet, manager, reservation, state, tx, tx_type, None, false).await
+}
+
+// Create a new asset, its hash is derived from the transaction hash
+async fn create_asset(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let decimals = if arguments.has_argument("decimals") {
+        arguments.get_value("decimals")?.to_number()?
+    } else {
+        COIN_DECIMALS as u64
+    };
+
+    if decimals > MAX_ASSET_DECIMALS as u64 {
+        return Err(CommandError::InvalidArgument(format!("Decimals must be at most {}", MAX_ASSET_DECIMALS)));
+    }
+
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    manager.message(format!("Creating asset with {} decimals, {} XELIS will be burned", decimals, format_xelis(ASSET_CREATION_BURN_AMOUNT)));
+
+    let tx_type = TransactionTypeBuilder::AssetCreation(AssetCreationPayload { decimals: decimals as u8 });
+    let reservation = wallet.reserve_nonce().await;
+    let (state, tx) = {
+        let storage = wallet.get_storage().read().await;
+        wallet.create_transaction_with_storage(&storage, tx_type.clone(), FeeBuilder::default(), None, false).await
+            .context("Error while creating transaction")?
+    };
+
+    broadcast_tx(wallet, manager, reservation, state, tx, tx_type, None, false).await
 }
 
 // Show current wallet address
@@ -779,16 +1262,28 @@ async fn balance(manager: &CommandManager, mut arguments: ArgumentManager) -> Re
     let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
 
+    // Balances still being decrypted are shown with their expected value
+    let approx = |approximated: bool| if approximated { " (approx)" } else { "" };
+    // Amounts sent by transactions not executed yet and the balance left to spend
+    let pending = |outgoing: u64, available: u64, decimals: u8| if outgoing > 0 {
+        format!(" (pending out: {}, available: {})", format_coin(outgoing, decimals), format_coin(available, decimals))
+    } else {
+        String::new()
+    };
     if arguments.has_argument("asset") {
         let asset = arguments.get_value("asset")?.to_hash()?;
-        let balance = storage.get_plaintext_balance_for(&asset).await.unwrap_or(0);
+        let (balance, approximated) = storage.get_display_balance_for(&asset).await.unwrap_or((0, false));
         let decimals = storage.get_asset_decimals(&asset).unwrap_or(0);
-        manager.message(format!("Balance for asset {}: {}", asset, format_coin(balance, decimals)));
+        let outgoing = storage.get_pending_outgoing_amount(&asset)?;
+        let available = balance.saturating_sub(outgoing);
+        manager.message(format!("Balance for asset {}: {}{}{}", asset, format_coin(balance, decimals), approx(approximated), pending(outgoing, available, decimals)));
     } else {
         for (asset, decimals) in storage.get_assets_with_decimals().await? {
-            let balance = storage.get_plaintext_balance_for(&asset).await.unwrap_or(0);
-            if balance > 0 {
-                manager.message(format!("Balance for asset {}: {}", asset, format_coin(balance, decimals)));
+            let (balance, approximated) = storage.get_display_balance_for(&asset).await.unwrap_or((0, false));
+            let outgoing = storage.get_pending_outgoing_amount(&asset)?;
+            if balance > 0 || outgoing > 0 {
+                let available = balance.saturating_sub(outgoing);
+                manager.message(format!("Balance for asset {}: {}{}{}", asset, format_coin(balance, decimals), approx(approximated), pending(outgoing, available, decimals)));
             }
         }
     }
@@ -809,9 +1304,41 @@ async fn history(manager: &CommandManager, mut arguments: ArgumentManager) -> Re
         return Err(CommandError::InvalidArgument("Page must be greater than 0".to_string()));
     }
 
+    let pending = if arguments.has_argument("pending") {
+        arguments.get_value("pending")?.to_bool()?
+    } else {
+        false
+    };
+
     let context = manager.get_context().lock()?;
     let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
+
+    // Outgoing transactions broadcasted but not executed yet
+    if pending {
+        let transactions = storage.get_pending_transactions()?;
+        if transactions.is_empty() {
+            manager.message("No pending transactions");
+            return Ok(())
+        }
+
+        manager.message(format!("Pending transactions (total {}):", transactions.len()));
+        for (hash, pending) in transactions {
+            let state = match pending.state {
+                PendingState::Pending => "pending",
+                PendingState::Stale => "stale"
+            };
+            let mut amounts = Vec::new();
+            for (asset, amount) in pending.amounts.iter() {
+                let decimals = storage.get_asset_decimals(asset).unwrap_or(COIN_DECIMALS);
+                amounts.push(format!("{} of {}", format_coin(*amount, decimals), asset));
+            }
+            manager.message(format!("- {} ({}) nonce {} sent at {}: {}", hash, state, pending.transaction.get_nonce(), format_timestamp_seconds(pending.created_at), amounts.join(", ")));
+        }
+
+        return Ok(())
+    }
+
     let mut transactions = storage.get_transactions()?;
 
     // if we don't have any txs, no need proceed further
@@ -839,6 +1366,51 @@ async fn history(manager: &CommandManager, mut arguments: ArgumentManager) -> Re
     Ok(())
 }
 
+// Replace a pending transaction by one with the same nonce and a higher fee
+async fn cancel(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let hash = arguments.get_value("tx_hash")?.to_hash()?;
+    let prompt = manager.get_prompt();
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+
+    let fee = {
+        let storage = wallet.get_storage().read().await;
+        if !storage.has_pending_transaction(&hash)? {
+            manager.error(format!("Transaction {} is not pending", hash));
+            return Ok(())
+        }
+        storage.get_pending_transaction(&hash)?.transaction.get_fee()
+    };
+
+    let (index, destination) = wallet.get_cancel_destination().await.context("Error while retrieving the account receiving the replacement")?;
+    manager.message(format!("Transaction {} will be replaced by a transfer of the smallest amount of XELIS to your account {} ({}) with a fee of {}", hash, index, destination, format_xelis(fee.saturating_mul(CANCEL_FEE_MULTIPLIER))));
+    if !prompt.ask_confirmation().await.context("Error while confirming action")? {
+        manager.message("Transaction has not been cancelled");
+        return Ok(())
+    }
+
+    match wallet.cancel_transaction(&hash).await {
+        Ok(tx) => manager.message(format!("Transaction {} has been replaced by {}", hash, tx.hash())),
+        Err(WalletError::ReplaceByFeeNotSupported) => {
+            manager.error("Transaction can't be cancelled: the daemon rejects any transaction using the nonce of one already in its mempool");
+            manager.error("Wait for it to be executed, or for it to be orphaned if it is invalid");
+        },
+        Err(e) => manager.error(format!("Couldn't cancel transaction: {}", e))
+    };
+
+    Ok(())
+}
+
+// Broadcast again a pending transaction, mostly used for stale ones
+async fn rebroadcast(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let hash = arguments.get_value("tx_hash")?.to_hash()?;
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    wallet.rebroadcast_transaction(&hash).await.context("Error while broadcasting transaction")?;
+    manager.message(format!("Transaction {} has been broadcasted again", hash));
+    Ok(())
+}
+
 // Set your wallet in online mode
 async fn online_mode(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
@@ -849,7 +1421,7 @@ async fn online_mode(manager: &CommandManager, mut arguments: ArgumentManager) -
         let daemon_address = if arguments.has_argument("daemon_address") {
             arguments.get_value("daemon_address")?.to_string_value()?
         } else {
-            DEFAULT_DAEMON_ADDRESS.to_string()
+            get_default_daemon_address(wallet).await
         };
 
         wallet.set_online_mode(&daemon_address, true).await.context("Couldn't enable online mode")?;
@@ -858,6 +1430,17 @@ async fn online_mode(manager: &CommandManager, mut arguments: ArgumentManager) -
     Ok(())
 }
 
+// Switch to another daemon and save it for the next starts
+async fn set_daemon(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let daemon_address = arguments.get_value("daemon_address")?.to_string_value()?;
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    manager.message(format!("Connecting to daemon {}...", daemon_address));
+    wallet.set_daemon_address(daemon_address.clone(), true).await.context("Couldn't switch to this daemon")?;
+    manager.message(format!("Wallet is now using daemon {}", daemon_address));
+    Ok(())
+}
+
 // Set your wallet in offline mode
 async fn offline_mode(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
@@ -902,7 +1485,7 @@ async fn seed(manager: &CommandManager, mut arguments: ArgumentManager) -> Resul
         0
     };
 
-    let seed = wallet.get_seed(language as usize)?;
+    let seed = wallet.get_seed(language as usize).await?;
     prompt.read_input(
         prompt.colorize_string(Color::Green, &format!("Seed: {}\r\nPress ENTER to continue", seed)),
         false
@@ -938,6 +1521,154 @@ async fn set_nonce(manager: &CommandManager, _: ArgumentManager) -> Result<(), C
     Ok(())
 }
 
+async fn audit_nonce(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+    let prompt = manager.get_prompt();
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let audit = wallet.audit_nonce().await.context("Error while auditing nonce")?;
+
+    manager.message(format!("Local nonce: {}", audit.local_nonce));
+    manager.message(format!("Unconfirmed nonce: {}", audit.unconfirmed_nonce));
+    manager.message(format!("Daemon nonce: {}", audit.daemon_nonce));
+    manager.message(format!("Pending transactions in mempool: {}", audit.pending));
+
+    let discrepancies = audit.get_discrepancies();
+    if discrepancies.is_empty() {
+        manager.message("Nonce is consistent with the daemon");
+        return Ok(())
+    }
+
+    for discrepancy in discrepancies {
+        manager.warn(discrepancy);
+    }
+
+    let expected_nonce = audit.expected_nonce();
+    manager.message(format!("Set local nonce to {}?", expected_nonce));
+    if !prompt.ask_confirmation().await.context("Error while confirming action")? {
+        manager.message("Nonce has not been changed");
+        return Ok(())
+    }
+
+    // Wait for any transaction being built before resetting the nonce
+    let mut reservation = wallet.reserve_nonce().await;
+    reservation.reset_nonce(expected_nonce).await.context("Error while setting nonce")?;
+    manager.message(format!("New nonce is: {}", expected_nonce));
+    Ok(())
+}
+
+// Manage the accounts derived from the wallet master key
+async fn account(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let action = arguments.get_value("action")?.to_string_value()?;
+    let name = if arguments.has_argument("name") {
+        Some(arguments.get_value("name")?.to_string_value()?)
+    } else {
+        None
+    };
+
+    let prompt = manager.get_prompt();
+    let mut context = manager.get_context().lock()?;
+    let wallet = Arc::clone(context.get::<Arc<Wallet>>()?);
+
+    match (action.as_str(), name) {
+        ("list", None) => {
+            let active = wallet.get_account_name().await?;
+            for (name, index, address) in wallet.get_accounts().await? {
+                let marker = if name == active { "*" } else { "-" };
+                manager.message(format!("{} {} (index {}): {}", marker, name, index, address));
+            }
+        },
+        ("create", Some(name)) => {
+            let address = wallet.create_account(&name).await?;
+            manager.message(format!("Account '{}' created with address {}", name, address));
+        },
+        ("switch", Some(name)) => {
+            if wallet.get_account_name().await? == name {
+                manager.message(format!("Account '{}' is already in use", name));
+                return Ok(())
+            }
+
+            let wallet = wallet.switch_account(&name).await?;
+            manager.message(format!("Switched to account '{}' with address {}", name, wallet.get_address()));
+            refresh_prompt_on_events(&wallet, prompt.clone()).await;
+            context.store(wallet);
+        },
+        ("rename", Some(name)) => {
+            if !arguments.has_argument("new_name") {
+                return Err(CommandError::ExpectedRequiredArg("new_name".to_owned()))
+            }
+
+            let new_name = arguments.get_value("new_name")?.to_string_value()?;
+            wallet.rename_account(&name, &new_name).await?;
+            manager.message(format!("Account '{}' renamed to '{}'", name, new_name));
+        },
+        ("delete", Some(name)) => {
+            if wallet.account_has_balance(&name).await? {
+                manager.warn(format!("Account '{}' has a non-zero balance, its funds will no longer be accessible from this wallet", name));
+            }
+
+            if !prompt.ask_confirmation().await.context("Error while confirming action")? {
+                manager.message("Account deletion has been aborted");
+                return Ok(())
+            }
+
+            wallet.delete_account(&name).await?;
+            manager.message(format!("Account '{}' has been deleted", name));
+        },
+        ("create" | "switch" | "rename" | "delete", None) => return Err(CommandError::ExpectedRequiredArg("name".to_owned())),
+        _ => return Err(CommandError::InvalidArgument(action))
+    }
+
+    Ok(())
+}
+
+// Track the assets of the wallet
+async fn asset(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let action = arguments.get_value("action")?.to_string_value()?;
+    let asset = if arguments.has_argument("asset") {
+        Some(arguments.get_value("asset")?.to_hash()?)
+    } else {
+        None
+    };
+
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+
+    match (action.as_str(), asset) {
+        ("list", None) => {
+            let storage = wallet.get_storage().read().await;
+            let mut assets = Vec::new();
+            for (asset, _) in storage.get_assets_with_decimals().await? {
+                let tracked = storage.get_tracked_asset(&asset)?;
+                assets.push((asset, tracked));
+            }
+
+            if assets.is_empty() {
+                manager.message("No asset tracked");
+                return Ok(())
+            }
+
+            assets.sort_by_key(|(_, tracked)| tracked.tracked_since);
+            for (asset, tracked) in assets {
+                let ticker = if asset == XELIS_ASSET { "XELIS".to_owned() } else { asset.to_string() };
+                let since = tracked.tracked_since.map(|topoheight| topoheight.to_string()).unwrap_or_else(|| "unknown".to_owned());
+                manager.message(format!("- {} ({} decimals) tracked since topoheight {}", ticker, tracked.decimals, since));
+            }
+        },
+        ("add", Some(asset)) => {
+            let data = wallet.track_asset(asset.clone()).await.context("Error while tracking asset")?;
+            manager.message(format!("Asset {} with {} decimals is now tracked", asset, data.get_decimals()));
+        },
+        ("remove", Some(asset)) => {
+            wallet.untrack_asset(&asset).await.context("Error while untracking asset")?;
+            manager.message(format!("Asset {} is no longer tracked", asset));
+        },
+        ("add" | "remove", None) => return Err(CommandError::ExpectedRequiredArg("asset".to_owned())),
+        _ => return Err(CommandError::InvalidArgument(action))
+    }
+
+    Ok(())
+}
+
 #[cfg(feature = "api_server")]
 async fn stop_api_server(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
@@ -954,14 +1685,71 @@ async fn start_rpc_server(manager: &CommandManager, mut arguments: ArgumentManag
     let bind_address = arguments.get_value("bind_address")?.to_string_value()?;
     let username = arguments.get_value("username")?.to_string_value()?;
     let password = arguments.get_value("password")?.to_string_value()?;
+    let app_permissions = if arguments.has_argument("app_permissions") {
+        arguments.get_value("app_permissions")?.to_bool()?
+    } else {
+        false
+    };
 
     let auth_config = Some(AuthConfig {
         username,
         password
     });
 
-    wallet.enable_rpc_server(bind_address, auth_config).await.context("Error while enabling RPC Server")?;
-    manager.message("RPC Server has been enabled");
+    let receiver = wallet.enable_rpc_server(bind_address, auth_config, app_permissions).await.context("Error while enabling RPC Server")?;
+    if let Some(receiver) = receiver {
+        let prompt = manager.get_prompt().clone();
+        spawn_task("rpc-permissions-handler", xswd_handler(receiver, prompt));
+        manager.message("RPC Server has been enabled, applications must be approved");
+    } else {
+        manager.message("RPC Server has been enabled");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "api_server")]
+async fn app_permissions(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let action = arguments.get_value("action")?.to_string_value()?;
+    let app_id = if arguments.has_argument("app_id") {
+        Some(arguments.get_value("app_id")?.to_string_value()?)
+    } else {
+        None
+    };
+
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+
+    match (action.as_str(), app_id) {
+        ("list", None) => {
+            let storage = wallet.get_storage().read().await;
+            let apps = storage.get_all_app_permissions().context("Error while retrieving application permissions")?;
+            if apps.is_empty() {
+                manager.message("No application permissions saved");
+                return Ok(())
+            }
+
+            for (id, permissions) in apps {
+                let classes = MethodClass::ALL.iter()
+                    .map(|class| format!("{}: {:?}", class, permissions.get(*class)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                manager.message(format!("- {} ({}): {}", permissions.get_name(), id, classes));
+            }
+        },
+        ("revoke", Some(app_id)) => {
+            let mut storage = wallet.get_storage().write().await;
+            if storage.get_app_permissions(&app_id).context("Error while retrieving application permissions")?.is_none() {
+                manager.message(format!("No permissions saved for application '{}'", app_id));
+                return Ok(())
+            }
+
+            storage.delete_app_permissions(&app_id).context("Error while revoking application permissions")?;
+            manager.message(format!("Permissions of application '{}' have been revoked", app_id));
+        },
+        ("revoke", None) => return Err(CommandError::ExpectedRequiredArg("app_id".to_owned())),
+        _ => return Err(CommandError::InvalidArgument(action))
+    }
+
     Ok(())
 }
 
@@ -982,20 +1770,42 @@ async fn start_xswd(manager: &CommandManager, _: ArgumentManager) -> Result<(),
 }
 
 // broadcast tx if possible
-// submit_transaction increase the local nonce in storage in case of success
-async fn broadcast_tx(wallet: &Wallet, manager: &CommandManager, tx: Transaction) {
-    let tx_hash = tx.hash();
-    manager.message(format!("Transaction hash: {}", tx_hash));
+// Changes are applied to the storage once the daemon accepted the transaction
+// In offline mode, they are applied directly as the transaction must be sent manually
+// Asset hash depends on the final transaction hash
+fn display_created_asset(manager: &CommandManager, tx: &Transaction) {
+    if let TransactionType::AssetCreation(_) = tx.get_data() {
+        manager.message(format!("Asset hash: {}", AssetCreationPayload::get_asset(&tx.hash())));
+    }
+}
 
-    if wallet.is_online().await {
-        if let Err(e) = wallet.submit_transaction(&tx).await {
-            manager.error(format!("Couldn't submit transaction: {}", e));
-            manager.error("You can try to rescan your balance with the command 'rescan'");
-        } else {
-            manager.message("Transaction submitted successfully!");
-        }
-    } else {
+async fn broadcast_tx(wallet: &Wallet, manager: &CommandManager, mut reservation: NonceReservation<'_>, mut state: TransactionBuilderState, tx: Transaction, tx_type: TransactionTypeBuilder, extra_data: Option<Vec<u8>>, encrypt_extra_data: bool) -> Result<(), CommandError> {
+    if !wallet.is_online().await {
+        reservation.commit(&mut state).await
+            .context("Error while applying transaction changes")?;
+
+        manager.message(format!("Transaction hash: {}", tx.hash()));
+        display_created_asset(manager, &tx);
         manager.warn("You are currently offline, transaction cannot be send automatically. Please send it manually to the network.");
         manager.message(format!("Transaction in hex format: {}", tx.to_hex()));
+        return Ok(())
     }
+
+    // The transaction may be rebuilt with another nonce if the daemon rejected it
+    match wallet.submit_transaction_with_recovery(&mut reservation, &mut state, tx, tx_type, FeeBuilder::default(), extra_data, encrypt_extra_data).await {
+        Ok(tx) => {
+            reservation.commit(&mut state).await
+                .context("Error while applying transaction changes")?;
+
+            manager.message(format!("Transaction hash: {}", tx.hash()));
+            display_created_asset(manager, &tx);
+            manager.message("Transaction submitted successfully!");
+        },
+        Err(e) => {
+            manager.error(format!("Couldn't submit transaction: {}", e));
+            manager.error("You can try to rescan your balance with the command 'rescan'");
+        }
+    };
+
+    Ok(())
 }
\ No newline at end of file
@@ -8,6 +8,7 @@ use xelis_common::{
     crypto::PrivateKey,
     serializer::Serializer
 };
+use zeroize::Zeroizing;
 use languages::*;
 
 const KEY_SIZE: usize = 32;
@@ -102,7 +103,7 @@ pub fn words_to_key(words: &Vec<String>) -> Result<PrivateKey> {
     let (indices, language_index) = find_indices(words)?.context("No indices found")?;
     debug!("Language found: {}", LANGUAGES[language_index].name);
 
-    let mut dest = Vec::with_capacity(KEY_SIZE);
+    let mut dest = Zeroizing::new(Vec::with_capacity(KEY_SIZE));
     for i in (0..SEED_LENGTH).step_by(3) {
         let a = indices.get(i).context("Index out of bounds")?;
         let b = indices.get(i + 1).context("Index out of bounds")?;
@@ -130,7 +131,7 @@ pub fn key_to_words_with_language(key: &PrivateKey, language: &Language) -> Resu
         return Err(anyhow!("Invalid word list length"));
     }
 
-    let bytes = key.to_bytes();
+    let bytes = Zeroizing::new(key.to_bytes());
     if bytes.len() != KEY_SIZE {
         return Err(anyhow!("Invalid key length"));
     }
@@ -3,36 +3,46 @@ use std::{
         HashMap,
         HashSet
     },
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+        PoisonError,
+        RwLock
+    },
     time::Duration
 };
 use thiserror::Error;
-use anyhow::Error;
-use log::{debug, error, trace, warn};
+use anyhow::{anyhow, Error};
+use log::{debug, error, info, trace, warn};
 use tokio::{sync::Mutex, task::JoinHandle, time::sleep};
 use xelis_common::{
     account::CiphertextCache,
     api::{
         daemon::{
             BlockResponse,
+            GetInfoResult,
             NewBlockEvent
         },
         wallet::BalanceChanged,
         RPCTransactionType
     },
     asset::AssetWithData,
-    config::XELIS_ASSET,
+    config::{ASSET_CREATION_BURN_AMOUNT, XELIS_ASSET},
     crypto::{
         elgamal::Ciphertext,
         Address,
-        Hash
+        Hash,
+        PublicKey
     },
+    json_rpc::JsonRPCError,
+    network::Network,
     serializer::Serializer,
-    transaction::Role,
+    time::get_current_time_in_seconds,
+    transaction::{AssetCreationPayload, Role},
     utils::{sanitize_daemon_address, spawn_task}
 };
 use crate::{
-    config::AUTO_RECONNECT_INTERVAL,
+    config::{AUTO_RECONNECT_INTERVAL, PENDING_TX_STALE_TIMEOUT},
     daemon_api::DaemonAPI,
     entry::{
         EntryData,
@@ -60,7 +70,119 @@ pub enum NetworkError {
     #[error(transparent)]
     DaemonAPIError(#[from] Error),
     #[error("Network mismatch")]
-    NetworkMismatch
+    NetworkMismatch,
+    #[error("Daemon is on {} while the wallet is on {}, refusing to sync", _0, _1)]
+    DaemonNetworkMismatch(Network, Network),
+    #[error("Daemon genesis block {} is different from the one synced by the wallet {}, refusing to sync", _0, _1)]
+    GenesisHashMismatch(Hash, Hash),
+    #[error("Block {} synced by the wallet is unknown by the daemon, it may be on another fork. Use 'rescan' to sync the wallet with this daemon", _0)]
+    UnknownTopBlock(Hash)
+}
+
+impl NetworkError {
+    // The daemon is not on the chain of the wallet, retrying to sync would fail again
+    pub fn is_chain_mismatch(&self) -> bool {
+        matches!(self, Self::DaemonNetworkMismatch(_, _) | Self::GenesisHashMismatch(_, _) | Self::UnknownTopBlock(_))
+    }
+}
+
+// Error code of the daemon when a block is not found
+const BLOCK_NOT_FOUND_ERROR_CODE: i16 = 1000;
+
+// Verify that the daemon is on the network and the chain of the wallet
+// The genesis hash is only known once the wallet has been synced with a daemon reporting it
+pub fn verify_daemon_chain(network: &Network, genesis_hash: Option<&Hash>, info: &GetInfoResult) -> Result<(), NetworkError> {
+    if info.network != *network {
+        return Err(NetworkError::DaemonNetworkMismatch(info.network, *network))
+    }
+
+    if let (Some(expected), Some(daemon_genesis_hash)) = (genesis_hash, info.genesis_block_hash.as_ref()) {
+        if expected != daemon_genesis_hash {
+            return Err(NetworkError::GenesisHashMismatch(daemon_genesis_hash.clone(), expected.clone()))
+        }
+    }
+
+    Ok(())
+}
+
+// Check if the daemon reported that the requested block doesn't exist
+pub fn is_block_not_found(err: &Error) -> bool {
+    err.chain().any(|cause| matches!(cause.downcast_ref::<JsonRPCError>(), Some(JsonRPCError::ServerError { code, .. }) if *code == BLOCK_NOT_FOUND_ERROR_CODE))
+}
+
+// Connect to a daemon and verify that it is on the network and the chain of the wallet
+pub async fn connect_to_daemon(daemon_address: &str, network: &Network, genesis_hash: Option<&Hash>) -> Result<DaemonAPI, Error> {
+    let api = DaemonAPI::new(format!("{}/json_rpc", sanitize_daemon_address(daemon_address))).await?;
+    // check that we can correctly get version from daemon
+    let version = api.get_version().await?;
+    debug!("Connected to daemon {} running version {}", daemon_address, version);
+
+    let res = match api.get_info().await {
+        Ok(info) => verify_daemon_chain(network, genesis_hash, &info).map_err(Error::from),
+        Err(e) => Err(e)
+    };
+
+    if let Err(e) = res {
+        if let Err(e) = api.disconnect().await {
+            debug!("Error while closing websocket connection: {}", e);
+        }
+        return Err(e)
+    }
+
+    Ok(api)
+}
+
+// Connect to the first usable daemon of the list, starting from the one at `start` index
+// Returns the index of the daemon selected with its API
+pub async fn connect_to_daemons(daemon_addresses: &[String], start: usize, network: &Network, genesis_hash: Option<&Hash>) -> Result<(usize, DaemonAPI), Error> {
+    let mut last_error = None;
+    for i in 0..daemon_addresses.len() {
+        let index = (start + i) % daemon_addresses.len();
+        let daemon_address = &daemon_addresses[index];
+        match connect_to_daemon(daemon_address, network, genesis_hash).await {
+            Ok(api) => return Ok((index, api)),
+            Err(e) => {
+                warn!("Couldn't use daemon {}: {}", daemon_address, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| anyhow!("No daemon address configured")))
+}
+
+// Changes applied to our balances by a transaction entry
+// Fee provided is the one paid by the transaction, only deducted when we are its source
+pub fn get_balance_changes(entry: &EntryData, fee: u64, key: &PublicKey) -> HashMap<Hash, i128> {
+    let mut changes: HashMap<Hash, i128> = HashMap::new();
+    match entry {
+        EntryData::Coinbase { reward } => {
+            *changes.entry(XELIS_ASSET).or_insert(0) += *reward as i128;
+        },
+        EntryData::Burn { asset, amount } => {
+            *changes.entry(asset.clone()).or_insert(0) -= *amount as i128;
+            *changes.entry(XELIS_ASSET).or_insert(0) -= fee as i128;
+        },
+        EntryData::AssetCreation { .. } => {
+            *changes.entry(XELIS_ASSET).or_insert(0) -= (ASSET_CREATION_BURN_AMOUNT + fee) as i128;
+        },
+        EntryData::Incoming { transfers, .. } => {
+            for transfer in transfers {
+                *changes.entry(transfer.get_asset().clone()).or_insert(0) += transfer.get_amount() as i128;
+            }
+        },
+        EntryData::Outgoing { transfers, fee, .. } => {
+            for transfer in transfers {
+                // Transfers to ourself don't change the balance
+                if transfer.get_destination() != key {
+                    *changes.entry(transfer.get_asset().clone()).or_insert(0) -= transfer.get_amount() as i128;
+                }
+            }
+            *changes.entry(XELIS_ASSET).or_insert(0) -= *fee as i128;
+        }
+    }
+
+    changes
 }
 
 pub struct NetworkHandler {
@@ -71,16 +193,35 @@ pub struct NetworkHandler {
     // api to communicate with daemon
     // It is behind a Arc to be shared across several wallets
     // in case someone make a custom service and don't want to create a new connection
-    api: Arc<DaemonAPI>
+    // It is replaced when switching to another daemon of the failover list
+    api: RwLock<Arc<DaemonAPI>>,
+    // Daemon addresses used in order on connection failure
+    // Empty if the API was given directly
+    daemon_addresses: Vec<String>,
+    // Index of the daemon address currently used
+    active_daemon: AtomicUsize
 }
 
 impl NetworkHandler {
     // Create a new network handler with a wallet and a daemon address
     // This will create itself a DaemonAPI and verify if connection is possible
     pub async fn new<S: ToString>(wallet: Arc<Wallet>, daemon_address: S) -> Result<SharedNetworkHandler, Error> {
-        let s = daemon_address.to_string();
-        let api = DaemonAPI::new(format!("{}/json_rpc", sanitize_daemon_address(s.as_str()))).await?;
-        Self::with_api(wallet, Arc::new(api)).await
+        Self::with_failover(wallet, vec![daemon_address.to_string()]).await
+    }
+
+    // Create a new network handler using the first daemon reachable of the list
+    // Next ones are used if the connection is lost and can't be established again
+    pub async fn with_failover(wallet: Arc<Wallet>, daemon_addresses: Vec<String>) -> Result<SharedNetworkHandler, Error> {
+        let genesis_hash = wallet.get_storage().read().await.get_genesis_hash()?;
+        let (index, api) = connect_to_daemons(&daemon_addresses, 0, wallet.get_network(), genesis_hash.as_ref()).await?;
+
+        Ok(Arc::new(Self {
+            task: Mutex::new(None),
+            wallet,
+            api: RwLock::new(Arc::new(api)),
+            daemon_addresses,
+            active_daemon: AtomicUsize::new(index)
+        }))
     }
 
     // Create a new network handler with an already created daemon API
@@ -92,7 +233,9 @@ impl NetworkHandler {
         Ok(Arc::new(Self {
             task: Mutex::new(None),
             wallet,
-            api
+            api: RwLock::new(api),
+            daemon_addresses: Vec::new(),
+            active_daemon: AtomicUsize::new(0)
         }))
     }
 
@@ -104,20 +247,29 @@ impl NetworkHandler {
             return Err(NetworkError::AlreadyRunning)
         }
 
-        if !self.api.is_online() {
+        if !self.get_api().is_online() {
             debug!("API is offline, trying to reconnect");
-            if !self.api.reconnect().await? {
+            if !self.get_api().reconnect().await? {
                 error!("Couldn't reconnect to server");
                 return Err(NetworkError::NotRunning)
             }
         }
 
+        // Notify that we are online before the first sync events
+        self.wallet.propagate_event(Event::Online).await;
+
         let zelf = Arc::clone(&self);
         *self.task.lock().await = Some(spawn_task("network-handler", async move {
             loop {
                 let res =  zelf.start_syncing().await;
                 if let Err(e) = res.as_ref() {
                     error!("Error while syncing: {}", e);
+
+                    // Don't try again if the daemon isn't on our chain
+                    if e.downcast_ref::<NetworkError>().is_some_and(NetworkError::is_chain_mismatch) {
+                        zelf.wallet.propagate_event(Event::Offline).await;
+                        break res;
+                    }
                 }
 
                 // Notify that we are offline
@@ -125,17 +277,31 @@ impl NetworkHandler {
 
                 if !auto_reconnect {
                     // Turn off the websocket connection
-                    if let Err(e) = zelf.api.disconnect().await {
+                    if let Err(e) = zelf.get_api().disconnect().await {
                         debug!("Error while closing websocket connection: {}", e);
                     }
 
                     break res;
                 } else {
-                    if !zelf.api.is_online() {
+                    if !zelf.get_api().is_online() {
                         debug!("API is offline, trying to reconnect");
-                        if !zelf.api.reconnect().await? {
-                            error!("Couldn't reconnect to server, trying again in {} seconds", AUTO_RECONNECT_INTERVAL);
-                            sleep(Duration::from_secs(AUTO_RECONNECT_INTERVAL)).await;
+                        // A daemon down must not stop the loop, the next ones are tried
+                        let reconnected = match zelf.get_api().reconnect().await {
+                            Ok(reconnected) => reconnected,
+                            Err(e) => {
+                                debug!("Error while reconnecting to daemon: {}", e);
+                                false
+                            }
+                        };
+
+                        if !reconnected {
+                            // Try the other daemons configured before waiting
+                            if zelf.failover().await {
+                                zelf.wallet.propagate_event(Event::Online).await;
+                            } else {
+                                error!("Couldn't reconnect to server, trying again in {} seconds", AUTO_RECONNECT_INTERVAL);
+                                sleep(Duration::from_secs(AUTO_RECONNECT_INTERVAL)).await;
+                            }
                         } else {
                             // Notify that we are back online
                             zelf.wallet.propagate_event(Event::Online).await;
@@ -148,10 +314,6 @@ impl NetworkHandler {
             }
         }));
 
-
-        // Notify that we are online
-        self.wallet.propagate_event(Event::Online).await;
-
         Ok(())
     }
 
@@ -170,7 +332,7 @@ impl NetworkHandler {
             }
 
             // Turn off the websocket connection
-            if let Err(e) = self.api.disconnect().await {
+            if let Err(e) = self.get_api().disconnect().await {
                 debug!("Error while closing websocket connection: {}", e);
             }
 
@@ -181,15 +343,69 @@ impl NetworkHandler {
     }
 
     // Retrieve the daemon API used
-    pub fn get_api(&self) -> &DaemonAPI {
-        &self.api
+    pub fn get_api(&self) -> Arc<DaemonAPI> {
+        Arc::clone(&self.api.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    // Retrieve the address of the daemon currently used
+    // Returns None if the daemon API was given directly
+    pub fn get_daemon_address(&self) -> Option<&str> {
+        self.daemon_addresses.get(self.active_daemon.load(Ordering::SeqCst)).map(String::as_str)
+    }
+
+    // Addresses of the daemons configured, empty if the daemon API was given directly
+    pub fn get_daemon_addresses(&self) -> &[String] {
+        &self.daemon_addresses
+    }
+
+    // Switch to the next daemon usable of the failover list
+    // Returns false if no other daemon is configured or reachable
+    async fn failover(&self) -> bool {
+        if self.daemon_addresses.len() < 2 {
+            return false
+        }
+
+        let genesis_hash = match self.wallet.get_storage().read().await.get_genesis_hash() {
+            Ok(genesis_hash) => genesis_hash,
+            Err(e) => {
+                error!("Error while reading genesis hash for failover: {}", e);
+                return false
+            }
+        };
+
+        let next = (self.active_daemon.load(Ordering::SeqCst) + 1) % self.daemon_addresses.len();
+        match connect_to_daemons(&self.daemon_addresses, next, self.wallet.get_network(), genesis_hash.as_ref()).await {
+            Ok((index, api)) => {
+                info!("Switching to daemon {}", self.daemon_addresses[index]);
+                let previous = {
+                    let mut lock = self.api.write().unwrap_or_else(PoisonError::into_inner);
+                    std::mem::replace(&mut *lock, Arc::new(api))
+                };
+                self.active_daemon.store(index, Ordering::SeqCst);
+
+                // Stop the auto reconnect of the previous connection
+                if let Err(e) = previous.disconnect().await {
+                    debug!("Error while closing websocket connection: {}", e);
+                }
+                true
+            },
+            Err(e) => {
+                debug!("No daemon available for failover: {}", e);
+                false
+            }
+        }
+    }
+
+    // Retrieve the daemon API used to share it with another wallet
+    pub fn get_shared_api(&self) -> Arc<DaemonAPI> {
+        self.get_api()
     }
 
     // check if the network handler is running (that we have a task and its not finished)
     pub async fn is_running(&self) -> bool {
         let task = self.task.lock().await;
         if let Some(handle) = task.as_ref() {
-            !handle.is_finished() && self.api.is_online()
+            !handle.is_finished() && self.get_api().is_online()
         } else {
             false
         }
@@ -219,7 +435,7 @@ impl NetworkHandler {
             debug!("Block {} at topoheight {} is mined by us", block_hash, topoheight);
             if let Some(reward) = block.miner_reward {
                 let coinbase = EntryData::Coinbase { reward };
-                let entry = TransactionEntry::new(block_hash.clone(), topoheight, coinbase);
+                let entry = TransactionEntry::new(block_hash.clone(), topoheight, coinbase, None);
                 assets_changed.insert(XELIS_ASSET);
 
                 let broadcast = {
@@ -228,6 +444,7 @@ impl NetworkHandler {
                         false
                     } else {
                         storage.save_transaction(entry.get_hash(), &entry)?;
+                        storage.add_balance_delta(&XELIS_ASSET, reward as i128);
     
                         // Store the changes for history
                         if !changes_stored {
@@ -263,6 +480,16 @@ impl NetworkHandler {
                         None
                     }
                 },
+                RPCTransactionType::AssetCreation(payload) => {
+                    if is_owner {
+                        // Registration cost is burned from our XELIS balance
+                        assets_changed.insert(XELIS_ASSET);
+                        let asset = AssetCreationPayload::get_asset(&tx.hash);
+                        Some(EntryData::AssetCreation { asset, decimals: payload.decimals })
+                    } else {
+                        None
+                    }
+                },
                 RPCTransactionType::Transfers(txs) => {
                     let mut transfers_in: Vec<TransferIn> = Vec::new();
                     let mut transfers_out: Vec<TransferOut> = Vec::new();
@@ -344,10 +571,10 @@ impl NetworkHandler {
                 let mut tx_topoheight = topoheight;
 
                 // New transaction entry that may be linked to us, check if TX was executed
-                if !self.api.is_tx_executed_in_block(&tx.hash, &block_hash).await? {
+                if !self.get_api().is_tx_executed_in_block(&tx.hash, &block_hash).await? {
                     warn!("Transaction {} was a good candidate but was not executed in block {}, searching its block executor", tx.hash, block_hash);
                     // Don't skip the TX, we may have missed it
-                    match self.api.get_transaction_executor(&tx.hash).await {
+                    match self.get_api().get_transaction_executor(&tx.hash).await {
                         Ok(executor) => {
                             tx_topoheight = executor.block_topoheight;
                             debug!("Transaction {} was executed in block {} at topoheight {}", tx.hash, executor.block_hash, executor.block_topoheight);
@@ -366,10 +593,17 @@ impl NetworkHandler {
                 }
 
                 // Save the transaction
-                let entry = TransactionEntry::new(tx.hash.into_owned(), tx_topoheight, entry);
+                let changes = get_balance_changes(&entry, tx.fee, address.get_public_key());
+                // Encrypted extra data is stored in plaintext if it was encrypted for us
+                let extra_data = tx.extra_data.map(|data| self.wallet.decrypt_tx_extra_data(data.into_owned()));
+                let entry = TransactionEntry::new(tx.hash.into_owned(), tx_topoheight, entry, extra_data);
                 {
                     let mut storage = self.wallet.get_storage().write().await;
                     storage.save_transaction(entry.get_hash(), &entry)?;
+                    // Known changes of our balances to verify the next ones without decrypting them
+                    for (asset, delta) in changes {
+                        storage.add_balance_delta(&asset, delta);
+                    }
                     // Store the changes for history
                     if !changes_stored {
                         storage.add_topoheight_to_changes(topoheight, &block_hash)?;
@@ -394,7 +628,7 @@ impl NetworkHandler {
     // When the block is requested, we don't limit the syncing to asset in parameter
     async fn get_balance_and_transactions(&self, topoheight_processed: &mut HashSet<u64>, address: &Address, asset: &Hash, min_topoheight: u64, balances: bool, highest_nonce: &mut Option<u64>) -> Result<(), Error> {
         // Retrieve the highest version
-        let (mut topoheight, mut version) = self.api.get_balance(address, asset).await.map(|res| (res.topoheight, res.version))?;
+        let (mut topoheight, mut version) = self.get_api().get_balance(address, asset).await.map(|res| (res.topoheight, res.version))?;
         // don't sync already synced blocks
         if min_topoheight >= topoheight {
             return Ok(())
@@ -408,41 +642,39 @@ impl NetworkHandler {
             // add this topoheight in cache to not re-process it (blocks are independant of asset to have faster sync)
             // if its not already processed, do it
             if topoheight_processed.insert(topoheight) {
-                let response = self.api.get_block_with_txs_at_topoheight(topoheight).await?;
+                let response = self.get_api().get_block_with_txs_at_topoheight(topoheight).await?;
                 let changes = self.process_block(address, response, topoheight).await?;
 
                 // Check if a change occured, we are the highest version and update balances is requested
                 if let Some((_, nonce)) = changes.filter(|_| balances && highest_version) {
-                    let mut storage = self.wallet.get_storage().write().await;
+                    let store = {
+                        let mut storage = self.wallet.get_storage().write().await;
 
-                    if highest_nonce.is_none() {
-                        // Get the highest nonce from storage
-                        *highest_nonce = Some(storage.get_nonce()?);
-                    }
+                        if highest_nonce.is_none() {
+                            // Get the highest nonce from storage
+                            *highest_nonce = Some(storage.get_nonce()?);
+                        }
 
-                    // Store only the highest nonce
-                    // Because if we are building queued transactions, it may break our queue
-                    // Our we couldn't submit new txs before they get removed from mempool
-                    if let Some(nonce) = nonce.filter(|n| highest_nonce.as_ref().map(|h| *h < *n).unwrap_or(true)) {
-                        debug!("Storing new highest nonce {}", nonce);
-                        storage.set_nonce(nonce)?;
-                        *highest_nonce = Some(nonce);
-                    }
+                        // Store only the highest nonce
+                        // Because if we are building queued transactions, it may break our queue
+                        // Our we couldn't submit new txs before they get removed from mempool
+                        if let Some(nonce) = nonce.filter(|n| highest_nonce.as_ref().map(|h| *h < *n).unwrap_or(true)) {
+                            debug!("Storing new highest nonce {}", nonce);
+                            storage.set_nonce(nonce)?;
+                            *highest_nonce = Some(nonce);
+                        }
+
+                        // If we have no balance in storage OR the stored ciphertext isn't the same, we should store it
+                        storage.get_balance_for(asset).await.map(|b| b.ciphertext != balance).unwrap_or(true)
+                    };
 
-                    // If we have no balance in storage OR the stored ciphertext isn't the same, we should store it
-                    let store = storage.get_balance_for(asset).await.map(|b| b.ciphertext != balance).unwrap_or(true);
                     if store {
                         debug!("Storing balance for asset {}", asset);
-                        let plaintext_balance = if let Some(plaintext_balance) = storage.get_unconfirmed_balance_decoded_for(&asset, &balance.compressed()).await? {
-                            plaintext_balance
-                        } else {
-                            trace!("Decrypting balance for asset {}", asset);
-                            let ciphertext = balance.decompressed()?;
-                            Arc::clone(&self.wallet).decrypt_ciphertext(ciphertext.clone()).await?
-                        };
+                        // Storage is not locked while decrypting so the last balance can still be read
+                        let plaintext_balance = self.decrypt_balance(asset, &mut balance).await?;
 
                         // Store the new balance
-                        storage.set_balance_for(asset, Balance::new(plaintext_balance, balance)).await?;
+                        self.wallet.get_storage().write().await.set_balance_for(asset, Balance::new(plaintext_balance, balance)).await?;
 
                         // Propagate the event
                         self.wallet.propagate_event(Event::BalanceChanged(BalanceChanged {
@@ -461,7 +693,7 @@ impl NetworkHandler {
                 }
 
                 topoheight = previous;
-                version = self.api.get_balance_at_topoheight(address, asset, previous).await?;
+                version = self.get_api().get_balance_at_topoheight(address, asset, previous).await?;
             } else {
                 return Ok(())
             }
@@ -471,22 +703,65 @@ impl NetworkHandler {
         }
     }
 
+    // Decrypt the new balance of an asset
+    // We first check if it's a balance we built ourself, or the plaintext expected from the known changes
+    // Verifying a plaintext is instant, while decrypting a high balance can take some time
+    // During the full decryption, the expected (or last) plaintext is shown as an approximation
+    async fn decrypt_balance(&self, asset: &Hash, ciphertext: &mut CiphertextCache) -> Result<u64, Error> {
+        let (unconfirmed, expected, last) = {
+            let storage = self.wallet.get_storage().read().await;
+            let unconfirmed = storage.get_unconfirmed_balance_decoded_for(asset, ciphertext.compressed()).await?;
+            (unconfirmed, storage.get_expected_balance_for(asset).await, storage.get_plaintext_balance_for(asset).await.ok())
+        };
+
+        if let Some(amount) = unconfirmed {
+            return Ok(amount)
+        }
+
+        let ciphertext = ciphertext.decompressed()?.clone();
+        if let Some(expected) = expected {
+            if self.wallet.verify_balance(&ciphertext, expected) {
+                debug!("Balance for asset {} found using the known changes", asset);
+                return Ok(expected)
+            }
+            debug!("Expected balance {} for asset {} is invalid, decrypting it", expected, asset);
+        }
+
+        if let Some(approximation) = expected.or(last) {
+            self.wallet.get_storage().write().await.set_pending_balance_for(asset, approximation);
+        }
+
+        trace!("Decrypting balance for asset {}", asset);
+        let result = Arc::clone(&self.wallet).decrypt_ciphertext(ciphertext).await;
+        if result.is_err() {
+            self.wallet.get_storage().write().await.remove_pending_balance_for(asset);
+        }
+
+        Ok(result?)
+    }
+
     // Locate the last topoheight valid for syncing, this support soft forks, DAG reorgs, etc...
     // Balances and nonce may be outdated, but we will sync them later
     // All transactions / changes above the last valid topoheight will be deleted
     // Returns daemon topoheight along wallet stable topoheight and if back sync is needed
     async fn locate_sync_topoheight_and_clean(&self) -> Result<(u64, Hash, u64, bool), NetworkError> {
-        let info = self.api.get_info().await?;
+        let info = self.get_api().get_info().await?;
         let daemon_topoheight = info.topoheight;
         let daemon_block_hash = info.top_block_hash;
         let pruned_topoheight = info.pruned_topoheight.unwrap_or(0);
 
-        // Verify that we are on the same network
+        // Verify that we are on the same network and chain
         {
-            let network = self.wallet.get_network();
-            if info.network != *network {
-                error!("Network mismatch! Our network is {} while daemon is {}", network, info.network);
-                return Err(NetworkError::NetworkMismatch)
+            let genesis_hash = self.wallet.get_storage().read().await.get_genesis_hash()?;
+            if let Err(e) = verify_daemon_chain(self.wallet.get_network(), genesis_hash.as_ref(), &info) {
+                error!("{}", e);
+                return Err(e)
+            }
+
+            // Record it on the first sync with a daemon reporting it
+            if let (None, Some(daemon_genesis_hash)) = (genesis_hash, info.genesis_block_hash.as_ref()) {
+                debug!("Recording genesis block hash {}", daemon_genesis_hash);
+                self.wallet.get_storage().write().await.set_genesis_hash(daemon_genesis_hash)?;
             }
         }
 
@@ -513,12 +788,21 @@ impl NetworkHandler {
 
                 if synced_topoheight > pruned_topoheight {
                     // Check if it's still a correct block
-                    let header = self.api.get_block_at_topoheight(synced_topoheight).await?;
+                    let header = self.get_api().get_block_at_topoheight(synced_topoheight).await?;
                     let block_hash = header.hash.into_owned();
                     if block_hash == top_block_hash {
                         // topoheight and block hash are equal, we are still on right chain
                         return Ok((daemon_topoheight, daemon_block_hash, synced_topoheight, false))
                     }
+
+                    // A reorg keeps the block in the daemon, if it never saw it,
+                    // we were synced with a daemon on another fork
+                    if let Err(e) = self.get_api().get_block_by_hash(&top_block_hash).await {
+                        if is_block_not_found(&e) {
+                            return Err(NetworkError::UnknownTopBlock(top_block_hash))
+                        }
+                        return Err(e.into())
+                    }
                 }
 
                 synced_topoheight
@@ -555,7 +839,7 @@ impl NetworkHandler {
 
             // Check if we are on the same chain
             debug!("Checking if we are on the same chain at topoheight {}", maximum);
-            let header = self.api.get_block_at_topoheight(maximum).await?;
+            let header = self.get_api().get_block_at_topoheight(maximum).await?;
             let block_hash = header.hash.into_owned();
             if block_hash == local_hash {
                 break Some(local_hash);
@@ -569,7 +853,7 @@ impl NetworkHandler {
         let block_hash = if let Some(block_hash) = block_hash {
             block_hash
         } else {
-            let response = self.api.get_block_at_topoheight(maximum).await?;
+            let response = self.get_api().get_block_at_topoheight(maximum).await?;
             response.hash.into_owned()
         };
 
@@ -597,6 +881,17 @@ impl NetworkHandler {
         Ok((daemon_topoheight, daemon_block_hash, maximum, true))
     }
 
+    // Sync the balance of an asset that just got tracked
+    // Nothing to sync if our account never had a balance for it
+    pub async fn sync_asset(&self, asset: &Hash) -> Result<(), Error> {
+        let address = self.wallet.get_address();
+        if self.get_api().get_account_assets(&address).await?.contains(asset) {
+            self.sync_head_state(&address, Some(HashSet::from([asset.clone()])), None, false).await?;
+        }
+
+        Ok(())
+    }
+
     // Sync the latest version of our balances and nonces and determine if we should parse all blocks
     // If assets are provided, we'll only sync these assets
     // TODO: this may bug with Smart Contract integration as we could receive a new asset and not detect it
@@ -607,7 +902,7 @@ impl NetworkHandler {
             nonce
         } else if sync_nonce {
             trace!("no nonce provided, fetching it from daemon");
-            match self.api.get_nonce(&address).await.map(|v| v.version) {
+            match self.get_api().get_nonce(&address).await.map(|v| v.version) {
                 Ok(v) => Some(v.get_nonce()),
                 Err(e) => {
                     debug!("Error while fetching last nonce: {}", e);
@@ -631,7 +926,7 @@ impl NetworkHandler {
             assets
         } else {
             trace!("no assets provided, fetching all assets");
-            self.api.get_account_assets(address).await?
+            self.get_api().get_account_assets(address).await?
         };
 
         trace!("assets: {}", assets.len());
@@ -642,11 +937,19 @@ impl NetworkHandler {
         for asset in &assets {
             trace!("asset: {}", asset);
             // check if we have this asset locally
-            if !{
+            let (known, untracked) = {
                 let storage = self.wallet.get_storage().read().await;
-                storage.contains_asset(&asset).await?
-            } {
-                let data = self.api.get_asset(&asset).await?;
+                (storage.contains_asset(&asset).await?, storage.is_asset_untracked(&asset)?)
+            };
+
+            // Removed by the user, don't add it again
+            if untracked {
+                debug!("Skipping untracked asset {}", asset);
+                continue;
+            }
+
+            if !known {
+                let data = self.get_api().get_asset(&asset).await?;
                 
                 // Add the asset to the storage
                 {
@@ -659,7 +962,7 @@ impl NetworkHandler {
             }
 
             // get the balance for this asset
-            let result = self.api.get_balance(&address, &asset).await?;
+            let result = self.get_api().get_balance(&address, &asset).await?;
             trace!("found balance at topoheight: {}", result.topoheight);
             balances.insert(asset, result.version.take_balance());
         }
@@ -678,32 +981,18 @@ impl NetworkHandler {
             }
 
             for (asset, mut ciphertext) in balances {
-                let (must_update, balance_cache) = {
+                let must_update = {
                     let storage = self.wallet.get_storage().read().await;
-                    let must_update = match storage.get_balance_for(&asset).await {
+                    match storage.get_balance_for(&asset).await {
                         Ok(mut previous) => previous.ciphertext.compressed() != ciphertext.compressed(),
                         // If we don't have a balance for this asset, we should update it
                         Err(_) => true
-                    };
-
-                    // If we must update, check if we have a cache for this balance
-                    let balance_cache = if must_update {
-                        storage.get_unconfirmed_balance_decoded_for(&asset, &ciphertext.compressed()).await?
-                    } else {
-                        None
-                    };
-
-                    (must_update, balance_cache)
+                    }
                 };
 
                 if must_update {
                     trace!("must update balance for asset: {}, ct: {:?}", asset, ciphertext.to_bytes());
-                    let value = if let Some(cache) = balance_cache {
-                        cache
-                    } else {
-                        trace!("Decrypting balance for asset {}", asset);
-                        Arc::clone(&self.wallet).decrypt_ciphertext(ciphertext.decompressed()?.clone()).await?
-                    };
+                    let value = self.decrypt_balance(asset, &mut ciphertext).await?;
 
                     // Inform the change of the balance
                     self.wallet.propagate_event(Event::BalanceChanged(BalanceChanged {
@@ -726,7 +1015,7 @@ impl NetworkHandler {
 
     // Locate the highest valid topoheight we synced to, clean wallet storage
     // then sync again the head state
-    async fn sync(&self, address: &Address, event: Option<NewBlockEvent>) -> Result<(), Error> {
+    async fn sync(&self, address: &Address, event: Option<NewBlockEvent<'static>>) -> Result<(), Error> {
         trace!("sync");
         // First, locate the last topoheight valid for syncing
         let (daemon_topoheight, daemon_block_hash, wallet_topoheight, sync_back) = self.locate_sync_topoheight_and_clean().await?;
@@ -740,7 +1029,7 @@ impl NetworkHandler {
             sync_new_blocks = self.sync_head_state(&address, None, None, true).await?;
         }
 
-        if let Some(block) = event {
+        if let Some(NewBlockEvent { block, .. }) = event {
             trace!("new block event received");
             // We can safely handle it by hand because `locate_sync_topoheight_and_clean` secure us from being on a wrong chain
             if let Some(topoheight) = block.topoheight {
@@ -778,6 +1067,8 @@ impl NetworkHandler {
             let mut storage = self.wallet.get_storage().write().await;
             storage.set_synced_topoheight(daemon_topoheight)?;
             storage.set_top_block_hash(&daemon_block_hash)?;
+            // All balances are up-to-date, older blocks synced may have left changes already included
+            storage.clear_balances_deltas();
         }
 
         // Propagate the event
@@ -797,19 +1088,19 @@ impl NetworkHandler {
 
         // Thanks to websocket, we can be notified when a new block is added in chain
         // this allows us to have a instant sync of each new block instead of polling periodically
-        let mut on_new_block = self.api.on_new_block_event().await?;
+        let mut on_new_block = self.get_api().on_new_block_event().await?;
 
         // Because DAG can reorder any blocks in stable height, its possible we missed some txs because they were not executed
         // when the block was added. We must check on DAG reorg for each block just to be sure
-        let mut on_block_ordered = self.api.on_block_ordered_event().await?;
+        let mut on_block_ordered = self.get_api().on_block_ordered_event().await?;
 
         // For better security, verify that an orphaned TX isn't in our ledger
         // This is rare event but may happen if someone try to do something shady
-        let mut on_transaction_orphaned = self.api.on_transaction_orphaned_event().await?;
+        let mut on_transaction_orphaned = self.get_api().on_transaction_orphaned_event().await?;
 
         // Network events to detect if we are online or offline
-        let mut on_connection = self.api.on_connection().await;
-        let mut on_connection_lost = self.api.on_connection_lost().await;
+        let mut on_connection = self.get_api().on_connection().await;
+        let mut on_connection_lost = self.get_api().on_connection_lost().await;
 
         loop {
             tokio::select! {
@@ -819,6 +1110,7 @@ impl NetworkHandler {
                     trace!("on_new_block_event");
                     let event = res?;
                     self.sync(&address, Some(event)).await?;
+                    self.check_pending_transactions().await?;
                 },
                 res = on_block_ordered.next() => {
                     trace!("on_block_ordered_event");
@@ -843,7 +1135,7 @@ impl NetworkHandler {
 
                     if process_block {
                         // Sync this block again as it may have some TXs executed
-                        let block = self.api.get_block_at_topoheight(topoheight).await?;
+                        let block = self.get_api().get_block_at_topoheight(topoheight).await?;
                         if let Some((assets, _)) = self.process_block(&address, block, topoheight).await? {
                             debug!("Found changes for assets: {}", assets.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", "));
                         }
@@ -860,6 +1152,11 @@ impl NetworkHandler {
                         storage.delete_transaction(&tx.hash)?;
                     }
 
+                    if storage.has_pending_transaction(&tx.hash)? {
+                        warn!("Pending transaction {} was orphaned, it will not be executed", tx.hash);
+                        storage.delete_pending_transaction(&tx.hash)?;
+                    }
+
                     if storage.get_tx_cache().is_some_and(|cache| cache.last_tx_hash_created == *tx.hash) {
                         warn!("Transaction {} was orphaned, deleting it from cache", tx.hash);
                         storage.clear_tx_cache();
@@ -883,6 +1180,28 @@ impl NetworkHandler {
         }
     }
 
+    // Pending transactions whose nonce is already used on chain are deleted,
+    // the ones not executed since too long are marked as stale
+    // The user can then decide to broadcast them again
+    async fn check_pending_transactions(&self) -> Result<(), Error> {
+        let (replaced, stale) = {
+            let mut storage = self.wallet.get_storage().write().await;
+            let nonce = storage.get_nonce().unwrap_or(0);
+            let replaced = storage.delete_pending_transactions_below_nonce(nonce)?;
+            (replaced, storage.mark_stale_pending_transactions(get_current_time_in_seconds(), PENDING_TX_STALE_TIMEOUT)?)
+        };
+
+        for hash in replaced {
+            warn!("Pending transaction {} was replaced by another one using its nonce, it will not be executed", hash);
+        }
+
+        for hash in stale {
+            warn!("Transaction {} was not executed after {} seconds, it may need to be broadcasted again", hash, PENDING_TX_STALE_TIMEOUT);
+        }
+
+        Ok(())
+    }
+
     // Sync all new blocks until the current topoheight
     async fn sync_new_blocks(&self, address: &Address, current_topoheight: u64, balances: bool) -> Result<(), Error> {
         let assets = {
@@ -904,4 +1223,766 @@ impl NetworkHandler {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use futures_util::{SinkExt, StreamExt};
+    use indexmap::{IndexMap, IndexSet};
+    use serde_json::{json, Value};
+    use tokio::{net::TcpListener, sync::broadcast, task::JoinSet, time::timeout};
+    use tokio_tungstenite::{accept_async, tungstenite::Message};
+    use xelis_common::{
+        account::{VersionedBalance, VersionedNonce},
+        api::{
+            daemon::{BlockType, GetBalanceResult, GetNonceResult},
+            wallet::EntryType,
+            RPCTransaction
+        },
+        asset::AssetData,
+        block::EXTRA_NONCE_SIZE,
+        config::{COIN_DECIMALS, COIN_VALUE, VERSION},
+        crypto::{Hashable, KeyPair},
+        difficulty::{CumulativeDifficulty, Difficulty},
+        testing::TempDir,
+        time::TimestampMillis,
+        transaction::{
+            builder::{FeeBuilder, TransactionBuilder, TransactionTypeBuilder, TransferBuilder},
+            Reference,
+            Transaction,
+            TransactionType
+        }
+    };
+    use crate::{
+        account::derive_account_keypair,
+        config::CANCEL_FEE_MULTIPLIER,
+        error::WalletError,
+        storage::{PendingState, PendingTransaction, TrackedAsset},
+        transaction_builder::TransactionBuilderState,
+        wallet::{get_outgoing_amounts, PrecomputedTables}
+    };
+    use super::*;
+
+    fn daemon_info(network: Network, genesis_block_hash: Option<Hash>) -> GetInfoResult {
+        GetInfoResult {
+            height: 10,
+            topoheight: 10,
+            stableheight: 2,
+            pruned_topoheight: None,
+            top_block_hash: Hash::zero(),
+            circulating_supply: 0,
+            maximum_supply: 0,
+            difficulty: Difficulty::from_u64(1),
+            block_time_target: 15000,
+            average_block_time: 15000,
+            rolling_block_time: Some(15000),
+            block_time_drifting: false,
+            block_reward: 0,
+            mempool_size: 0,
+            version: "1.0.0".to_owned(),
+            network,
+            is_synced: true,
+            genesis_block_hash,
+            replace_by_fee: false,
+            network_time_offset: 0,
+            clock_skewed: false
+        }
+    }
+
+    fn block_at_topoheight(topoheight: u64) -> BlockResponse {
+        BlockResponse {
+            hash: Cow::Owned(Hash::zero()),
+            topoheight: Some(topoheight),
+            block_type: BlockType::Sync,
+            difficulty: Cow::Owned(Difficulty::from_u64(1)),
+            supply: None,
+            reward: None,
+            miner_reward: None,
+            dev_reward: None,
+            cumulative_difficulty: Cow::Owned(CumulativeDifficulty::from_u64(1)),
+            total_fees: None,
+            total_size_in_bytes: 0,
+            version: 0,
+            tips: Cow::Owned(IndexSet::new()),
+            timestamp: TimestampMillis::new(0),
+            timestamp_human: String::new(),
+            height: topoheight,
+            nonce: 0,
+            extra_nonce: Cow::Owned([0u8; EXTRA_NONCE_SIZE]),
+            miner: Cow::Owned(KeyPair::new().get_public_key().to_address(false)),
+            txs_hashes: Cow::Owned(IndexSet::new()),
+            transactions: Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_balance_changes() {
+        let key = xelis_common::crypto::KeyPair::new().get_public_key().compress();
+        let other = xelis_common::crypto::KeyPair::new().get_public_key().compress();
+        let asset = Hash::new([1u8; 32]);
+
+        let coinbase = EntryData::Coinbase { reward: 50 };
+        assert_eq!(get_balance_changes(&coinbase, 0, &key), HashMap::from([(XELIS_ASSET, 50)]));
+
+        let incoming = EntryData::Incoming {
+            from: other.clone(),
+            transfers: vec![TransferIn::new(asset.clone(), 100, None), TransferIn::new(asset.clone(), 20, None)]
+        };
+        assert_eq!(get_balance_changes(&incoming, 5, &key), HashMap::from([(asset.clone(), 120)]));
+
+        // Transfer to ourself only costs the fee
+        let outgoing = EntryData::Outgoing {
+            transfers: vec![TransferOut::new(other, asset.clone(), 30, None), TransferOut::new(key.clone(), XELIS_ASSET, 10, None)],
+            fee: 2,
+            nonce: 0
+        };
+        assert_eq!(get_balance_changes(&outgoing, 2, &key), HashMap::from([(asset.clone(), -30), (XELIS_ASSET, -2)]));
+
+        let burn = EntryData::Burn { asset: asset.clone(), amount: 7 };
+        assert_eq!(get_balance_changes(&burn, 1, &key), HashMap::from([(asset, -7), (XELIS_ASSET, -1)]));
+
+        // Registration cost is burned with the fee
+        let creation = EntryData::AssetCreation { asset: Hash::new([2u8; 32]), decimals: 8 };
+        assert_eq!(get_balance_changes(&creation, 1, &key), HashMap::from([(XELIS_ASSET, -(ASSET_CREATION_BURN_AMOUNT as i128) - 1)]));
+    }
+
+    #[test]
+    fn test_daemon_network_mismatch() {
+        let genesis_hash = Hash::new([1u8; 32]);
+        let info = daemon_info(Network::Testnet, Some(genesis_hash.clone()));
+        assert!(matches!(verify_daemon_chain(&Network::Mainnet, None, &info), Err(NetworkError::DaemonNetworkMismatch(Network::Testnet, Network::Mainnet))));
+        assert!(verify_daemon_chain(&Network::Testnet, None, &info).is_ok());
+        assert!(verify_daemon_chain(&Network::Testnet, Some(&genesis_hash), &info).is_ok());
+    }
+
+    #[test]
+    fn test_daemon_genesis_hash_mismatch() {
+        let wallet_genesis_hash = Hash::new([1u8; 32]);
+        let info = daemon_info(Network::Dev, Some(Hash::new([2u8; 32])));
+        let err = verify_daemon_chain(&Network::Dev, Some(&wallet_genesis_hash), &info).unwrap_err();
+        assert!(matches!(err, NetworkError::GenesisHashMismatch(_, _)));
+        assert!(err.is_chain_mismatch());
+
+        // Daemons not reporting it can't be verified
+        let info = daemon_info(Network::Dev, None);
+        assert!(verify_daemon_chain(&Network::Dev, Some(&wallet_genesis_hash), &info).is_ok());
+    }
+
+    #[test]
+    fn test_unknown_top_block_on_another_fork() {
+        let not_found: Result<(), JsonRPCError> = Err(JsonRPCError::ServerError {
+            code: BLOCK_NOT_FOUND_ERROR_CODE,
+            message: "Block not found".to_owned(),
+            data: None
+        });
+        let err = anyhow::Context::context(not_found, "Error while fetching block").unwrap_err();
+        assert!(is_block_not_found(&err));
+
+        // Other errors don't mean the daemon is on another fork
+        assert!(!is_block_not_found(&anyhow::anyhow!("connection lost")));
+        let err: Error = JsonRPCError::ServerError { code: 1004, message: "Not found on disk".to_owned(), data: None }.into();
+        assert!(!is_block_not_found(&err));
+
+        assert!(NetworkError::UnknownTopBlock(Hash::zero()).is_chain_mismatch());
+        assert!(!NetworkError::NetworkMismatch.is_chain_mismatch());
+    }
+
+    // Minimal daemon answering the requests used to verify it
+    // Aborting the task closes all its connections
+    async fn mock_daemon(network: Network) -> (String, JoinHandle<()>) {
+        mock_daemon_with_assets(network, Vec::new()).await
+    }
+
+    // Same as mock_daemon but serving the data of the assets registered
+    async fn mock_daemon_with_assets(network: Network, assets: Vec<(Hash, AssetData)>) -> (String, JoinHandle<()>) {
+        mock_daemon_with(network, assets, &[]).await
+    }
+
+    // Failing methods answer with an error like the daemon does for an unknown account
+    async fn mock_daemon_with(network: Network, assets: Vec<(Hash, AssetData)>, failing: &'static [&'static str]) -> (String, JoinHandle<()>) {
+        mock_daemon_scripted(network, assets, failing, |_, _| None).await
+    }
+
+    // The script answers first with the method and params of each request
+    // Requests it doesn't answer get the default responses of the mock
+    async fn mock_daemon_scripted<F>(network: Network, assets: Vec<(Hash, AssetData)>, failing: &'static [&'static str], script: F) -> (String, JoinHandle<()>)
+    where
+        F: Fn(&str, &Value) -> Option<Value> + Send + Sync + 'static
+    {
+        let assets = Arc::new(assets);
+        let script = Arc::new(script);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handle = tokio::spawn(async move {
+            let mut connections = JoinSet::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                let assets = Arc::clone(&assets);
+                let script = Arc::clone(&script);
+                connections.spawn(async move {
+                    let mut ws = accept_async(stream).await.unwrap();
+                    while let Some(Ok(Message::Text(text))) = ws.next().await {
+                        let request: Value = serde_json::from_str(&text).unwrap();
+                        if let Some(method) = request["method"].as_str().filter(|method| failing.contains(method)) {
+                            let error = json!({ "code": -32004, "message": format!("{} failed", method) });
+                            let response = json!({ "jsonrpc": "2.0", "id": request["id"], "error": error });
+                            if ws.send(Message::Text(response.to_string())).await.is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+
+                        if let Some(result) = request["method"].as_str().and_then(|method| script(method, &request["params"])) {
+                            let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": result });
+                            if ws.send(Message::Text(response.to_string())).await.is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+
+                        let result = match request["method"].as_str() {
+                            Some("get_version") => json!(VERSION),
+                            Some("get_info") => json!(daemon_info(network, Some(Hash::zero()))),
+                            Some("get_block_at_topoheight") => json!(block_at_topoheight(request["params"]["topoheight"].as_u64().unwrap_or(0))),
+                            Some("subscribe") => json!(true),
+                            Some("get_asset") => assets.iter()
+                                .find(|(asset, _)| request["params"]["asset"].as_str() == Some(&asset.to_hex()))
+                                .map(|(_, data)| json!(data))
+                                .unwrap_or(Value::Null),
+                            // No balance for any asset
+                            Some("get_account_assets") => json!([]),
+                            Some("has_nonce") => json!({ "exist": false }),
+                            Some("is_tx_executed_in_block") => json!(true),
+                            // Account is not registered
+                            _ => Value::Null
+                        };
+                        let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": result });
+                        if ws.send(Message::Text(response.to_string())).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        (address, handle)
+    }
+
+    #[tokio::test]
+    async fn test_daemons_failover() {
+        let (first, first_handle) = mock_daemon(Network::Dev).await;
+        let (second, second_handle) = mock_daemon(Network::Dev).await;
+        let addresses = vec![first, second];
+
+        let (index, api) = connect_to_daemons(&addresses, 0, &Network::Dev, Some(&Hash::zero())).await.unwrap();
+        assert_eq!(index, 0);
+        assert!(api.get_version().await.is_ok());
+
+        // First daemon goes down in the middle of the session
+        first_handle.abort();
+        let _ = first_handle.await;
+        assert!(api.get_version().await.is_err());
+
+        // Next daemon of the list is used
+        let (index, api) = connect_to_daemons(&addresses, 1, &Network::Dev, Some(&Hash::zero())).await.unwrap();
+        assert_eq!(index, 1);
+        assert!(api.get_version().await.is_ok());
+
+        // Unreachable daemons are skipped
+        let (index, _) = connect_to_daemons(&addresses, 0, &Network::Dev, None).await.unwrap();
+        assert_eq!(index, 1);
+
+        second_handle.abort();
+        let _ = second_handle.await;
+        assert!(connect_to_daemons(&addresses, 0, &Network::Dev, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wallet_daemons_failover() {
+        let (first, first_handle) = mock_daemon(Network::Dev).await;
+        let (second, second_handle) = mock_daemon(Network::Dev).await;
+        let dir = TempDir::new("wallet-failover");
+
+        let tables = Arc::new(PrecomputedTables::new(10));
+        let wallet = Wallet::create(dir.to_string_lossy().into_owned(), "password".to_owned(), None, Network::Dev, tables).unwrap();
+        let mut events = wallet.subscribe_events().await;
+
+        wallet.set_online_mode_with_failover(vec![first.clone(), second.clone()], true).await.unwrap();
+        assert!(matches!(next_event(&mut events).await, Event::Online));
+        assert!(matches!(next_event(&mut events).await, Event::NewTopoHeight { topoheight: 10 }));
+        assert_eq!(wallet.get_daemon_address().await, Some(first));
+
+        // First daemon is killed in the middle of the session
+        first_handle.abort();
+        assert!(matches!(next_event(&mut events).await, Event::Offline));
+
+        // The wallet switches to the second one without a restart
+        assert!(matches!(next_event(&mut events).await, Event::Online));
+        assert_eq!(wallet.get_daemon_address().await, Some(second));
+        assert!(wallet.is_online().await);
+        assert_eq!(wallet.get_storage().read().await.get_synced_topoheight().unwrap(), 10);
+
+        let _ = wallet.set_offline_mode().await;
+        wallet.close().await;
+        second_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_daemon_on_another_chain_rejected() {
+        let (testnet, testnet_handle) = mock_daemon(Network::Testnet).await;
+        let (dev, dev_handle) = mock_daemon(Network::Dev).await;
+
+        let err = connect_to_daemon(&testnet, &Network::Dev, None).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<NetworkError>(), Some(NetworkError::DaemonNetworkMismatch(Network::Testnet, Network::Dev))));
+
+        // Not the genesis block synced by the wallet
+        let err = connect_to_daemon(&dev, &Network::Dev, Some(&Hash::new([1u8; 32]))).await.unwrap_err();
+        assert!(err.downcast_ref::<NetworkError>().is_some_and(NetworkError::is_chain_mismatch));
+
+        let (index, _) = connect_to_daemons(&[testnet, dev], 0, &Network::Dev, None).await.unwrap();
+        assert_eq!(index, 1);
+
+        testnet_handle.abort();
+        dev_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_asset_creation_entry() {
+        let (daemon, handle) = mock_daemon(Network::Dev).await;
+        let dir = TempDir::new("wallet-asset-creation");
+        let tables = Arc::new(PrecomputedTables::new(10));
+        let wallet = Wallet::create(dir.to_string_lossy().into_owned(), "password".to_owned(), None, Network::Dev, tables).unwrap();
+        let handler = NetworkHandler::new(Arc::clone(&wallet), &daemon).await.unwrap();
+
+        // Asset created by the account and executed at topoheight 5
+        let keypair = KeyPair::new();
+        let address = keypair.get_public_key().to_address(false);
+        let reference = Reference {
+            hash: Hash::zero(),
+            topoheight: 0
+        };
+        let mut state = TransactionBuilderState::new(false, reference, 3);
+        let balance = ASSET_CREATION_BURN_AMOUNT * 2;
+        state.add_balance(XELIS_ASSET, Balance::new(balance, CiphertextCache::Decompressed(keypair.get_public_key().encrypt(balance))));
+        let data = TransactionTypeBuilder::AssetCreation(AssetCreationPayload { decimals: 4 });
+        let tx = TransactionBuilder::new(0, keypair.get_public_key().compress(), data, FeeBuilder::Value(1)).build(&mut state, &keypair).unwrap();
+        let tx_hash = tx.hash();
+        // Owned as received from the daemon
+        let mut block = block_at_topoheight(5);
+        block.transactions.push(serde_json::from_value(json!(RPCTransaction::from_tx(&tx, &tx_hash, false))).unwrap());
+
+        let (assets, nonce) = handler.process_block(&address, block, 5).await.unwrap().unwrap();
+        assert_eq!(assets, HashSet::from([XELIS_ASSET]));
+        assert_eq!(nonce, Some(4));
+
+        // Stored with the asset derived from the TX hash
+        let entry = wallet.get_storage().read().await.get_transaction(&tx_hash).unwrap();
+        assert_eq!(entry.get_topoheight(), 5);
+        assert!(matches!(entry.get_entry(), EntryData::AssetCreation { asset, decimals: 4 } if *asset == AssetCreationPayload::get_asset(&tx_hash)));
+
+        wallet.close().await;
+        handle.abort();
+    }
+
+    async fn next_event(events: &mut broadcast::Receiver<Event>) -> Event {
+        timeout(Duration::from_secs(10), events.recv()).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sync_events_in_order() {
+        let dir = TempDir::new("wallet-events");
+
+        // No balance is decrypted, the tables are never read
+        let tables = Arc::new(PrecomputedTables::new(10));
+        let wallet = Wallet::create(dir.to_string_lossy().into_owned(), "password".to_owned(), None, Network::Dev, tables).unwrap();
+        let mut events = wallet.subscribe_events().await;
+
+        // Our balance is the reward of the block we mined at topoheight 5
+        let address = wallet.get_address();
+        let ciphertext = CiphertextCache::Decompressed(address.get_public_key().decompress().unwrap().encrypt(100u64));
+        // Known as if we built it ourself, so it's not decrypted
+        wallet.get_storage().read().await.set_unconfirmed_balance_for(XELIS_ASSET, Balance::new(100, ciphertext.clone())).await.unwrap();
+
+        // The forked chain replaced the blocks above topoheight 5 and has two more blocks
+        let chain = move |forked: bool| {
+            let address = address.clone();
+            let ciphertext = ciphertext.clone();
+            move |method: &str, params: &Value| {
+                let topoheight = params["topoheight"].as_u64().unwrap_or(0);
+                match method {
+                    "get_info" if forked => {
+                        let mut info = daemon_info(Network::Dev, Some(Hash::zero()));
+                        info.topoheight = 12;
+                        info.top_block_hash = Hash::new([12u8; 32]);
+                        Some(json!(info))
+                    },
+                    "get_block_at_topoheight" => {
+                        let mut block = block_at_topoheight(topoheight);
+                        if topoheight == 5 {
+                            block.miner = Cow::Owned(address.clone());
+                            block.miner_reward = Some(100);
+                        } else if forked && topoheight > 5 {
+                            block.hash = Cow::Owned(Hash::new([topoheight as u8; 32]));
+                        }
+                        Some(json!(block))
+                    },
+                    // Our previous top block is still known, orphaned by the fork
+                    "get_block_by_hash" => Some(json!(block_at_topoheight(10))),
+                    "get_nonce" => Some(json!(GetNonceResult {
+                        topoheight: 5,
+                        version: VersionedNonce::new(0, None),
+                        snapshot_topoheight: 10
+                    })),
+                    "get_account_assets" => Some(json!([XELIS_ASSET])),
+                    "get_balance" => Some(json!(GetBalanceResult {
+                        version: VersionedBalance::new(ciphertext.clone(), None),
+                        topoheight: 5,
+                        snapshot_topoheight: 10
+                    })),
+                    _ => None
+                }
+            }
+        };
+        let assets = vec![(XELIS_ASSET, AssetData::new(0, COIN_DECIMALS))];
+        let (daemon, handle) = mock_daemon_scripted(Network::Dev, assets.clone(), &[], chain(false)).await;
+        let (forked, forked_handle) = mock_daemon_scripted(Network::Dev, assets, &[], chain(true)).await;
+
+        wallet.set_online_mode(&daemon, false).await.unwrap();
+        assert!(matches!(next_event(&mut events).await, Event::Online));
+        // First sync: head state, then the blocks with our changes, up to the daemon topoheight
+        assert!(matches!(next_event(&mut events).await, Event::NewAsset(data) if *data.get_asset() == XELIS_ASSET));
+        assert!(matches!(next_event(&mut events).await, Event::BalanceChanged(BalanceChanged { asset, balance: 100 }) if asset == XELIS_ASSET));
+        assert!(matches!(next_event(&mut events).await, Event::NewTransaction(entry) if entry.topoheight == 5 && matches!(entry.entry, EntryType::Coinbase { reward: 100 })));
+        assert!(matches!(next_event(&mut events).await, Event::NewTopoHeight { topoheight: 10 }));
+        assert_eq!(wallet.get_storage().read().await.get_synced_topoheight().unwrap(), 10);
+
+        wallet.set_offline_mode().await.unwrap();
+        assert!(matches!(next_event(&mut events).await, Event::Offline));
+        handle.abort();
+
+        // Our top block was orphaned, the history is kept up to our last change
+        wallet.set_online_mode(&forked, false).await.unwrap();
+        assert!(matches!(next_event(&mut events).await, Event::Online));
+        assert!(matches!(next_event(&mut events).await, Event::Rescan { start_topoheight: 5 }));
+        // Nothing changed for us, the transaction is not found again
+        assert!(matches!(next_event(&mut events).await, Event::NewTopoHeight { topoheight: 12 }));
+        assert_eq!(wallet.get_storage().read().await.get_synced_topoheight().unwrap(), 12);
+
+        // Daemon goes down
+        forked_handle.abort();
+        assert!(matches!(next_event(&mut events).await, Event::Offline));
+
+        let _ = wallet.set_offline_mode().await;
+        wallet.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_all_accounts_synced() {
+        let (daemon, handle) = mock_daemon(Network::Dev).await;
+        let dir = TempDir::new("wallet-accounts-sync");
+
+        let tables = Arc::new(PrecomputedTables::new(10));
+        let wallet = Wallet::create(dir.to_string_lossy().into_owned(), "password".to_owned(), None, Network::Dev, tables).unwrap();
+        wallet.create_account("work").await.unwrap();
+        let mut events = wallet.subscribe_events().await;
+
+        wallet.set_online_mode(&daemon, false).await.unwrap();
+        assert!(matches!(next_event(&mut events).await, Event::Online));
+        assert!(matches!(next_event(&mut events).await, Event::NewTopoHeight { topoheight: 10 }));
+
+        // The other account is synced in background with its own storage
+        let account = wallet.get_synced_account(1).await.unwrap();
+        timeout(Duration::from_secs(10), async {
+            while account.get_storage().read().await.get_synced_topoheight().unwrap_or(0) != 10 {
+                sleep(Duration::from_millis(50)).await;
+            }
+        }).await.unwrap();
+        assert_eq!(wallet.get_storage().read().await.open_account(1).unwrap().get_synced_topoheight().unwrap(), 10);
+
+        // Created while online, it is synced right away
+        wallet.create_account("savings").await.unwrap();
+        assert!(wallet.get_synced_account(2).await.is_some());
+
+        // Its sync is stopped before its data is deleted
+        wallet.delete_account("savings").await.unwrap();
+        assert!(wallet.get_synced_account(2).await.is_none());
+
+        wallet.set_offline_mode().await.unwrap();
+        assert!(wallet.get_synced_account(1).await.is_none());
+        assert!(!account.is_online().await);
+
+        handle.abort();
+        wallet.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_audit_nonce_daemon_errors() {
+        let (daemon, handle) = mock_daemon_with(Network::Dev, Vec::new(), &["get_mempool_cache"]).await;
+        let dir = TempDir::new("wallet-audit-nonce");
+
+        let tables = Arc::new(PrecomputedTables::new(10));
+        let wallet = Wallet::create(dir.to_string_lossy().into_owned(), "password".to_owned(), None, Network::Dev, tables).unwrap();
+        let mut events = wallet.subscribe_events().await;
+        wallet.set_online_mode(&daemon, false).await.unwrap();
+        assert!(matches!(next_event(&mut events).await, Event::Online));
+
+        // No nonce and no mempool cache on the daemon for a new account
+        let audit = wallet.audit_nonce().await.unwrap();
+        assert_eq!(audit.daemon_nonce, 0);
+        assert_eq!(audit.pending, 0);
+
+        wallet.set_offline_mode().await.unwrap();
+        handle.abort();
+
+        // The nonce of the daemon can't be retrieved, no nonce is guessed
+        let (daemon, handle) = mock_daemon_with(Network::Dev, Vec::new(), &["has_nonce"]).await;
+        wallet.set_online_mode(&daemon, false).await.unwrap();
+        assert!(wallet.audit_nonce().await.is_err());
+
+        // Daemon unreachable
+        handle.abort();
+        let _ = handle.await;
+        assert!(wallet.audit_nonce().await.is_err());
+
+        let _ = wallet.set_offline_mode().await;
+        wallet.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_track_assets() {
+        let first = Hash::new([1u8; 32]);
+        let second = Hash::new([2u8; 32]);
+        let assets = vec![(first.clone(), AssetData::new(5, 2)), (second.clone(), AssetData::new(7, 4))];
+        let (daemon, handle) = mock_daemon_with_assets(Network::Dev, assets).await;
+        let dir = TempDir::new("wallet-assets");
+
+        let tables = Arc::new(PrecomputedTables::new(10));
+        let wallet = Wallet::create(dir.to_string_lossy().into_owned(), "password".to_owned(), None, Network::Dev, tables).unwrap();
+        let mut events = wallet.subscribe_events().await;
+        wallet.set_online_mode(&daemon, false).await.unwrap();
+        assert!(matches!(next_event(&mut events).await, Event::Online));
+        assert!(matches!(next_event(&mut events).await, Event::NewTopoHeight { topoheight: 10 }));
+
+        assert_eq!(wallet.track_asset(first.clone()).await.unwrap().get_decimals(), 2);
+        assert!(matches!(next_event(&mut events).await, Event::NewAsset(data) if *data.get_asset() == first));
+        assert_eq!(wallet.track_asset(second.clone()).await.unwrap().get_decimals(), 4);
+        {
+            let storage = wallet.get_storage().read().await;
+            assert_eq!(storage.get_tracked_asset(&first).unwrap(), TrackedAsset { decimals: 2, tracked_since: Some(10) });
+            assert_eq!(storage.get_asset_decimals(&second).unwrap(), 4);
+            assert_eq!(storage.get_assets().await.unwrap(), HashSet::from([first.clone(), second.clone()]));
+        }
+
+        // Already tracked or not registered on the daemon
+        assert!(wallet.track_asset(first.clone()).await.is_err());
+        assert!(wallet.track_asset(Hash::new([3u8; 32])).await.is_err());
+
+        wallet.untrack_asset(&first).await.unwrap();
+        {
+            let storage = wallet.get_storage().read().await;
+            assert!(!storage.contains_asset(&first).await.unwrap());
+            assert!(storage.is_asset_untracked(&first).unwrap());
+            assert_eq!(storage.get_assets().await.unwrap(), HashSet::from([second.clone()]));
+        }
+        assert!(matches!(wallet.untrack_asset(&first).await, Err(WalletError::Any(_))));
+        assert!(matches!(wallet.untrack_asset(&XELIS_ASSET).await, Err(WalletError::NativeAssetAlwaysTracked)));
+
+        // Added back by the user
+        wallet.track_asset(first.clone()).await.unwrap();
+        assert!(!wallet.get_storage().read().await.is_asset_untracked(&first).unwrap());
+
+        handle.abort();
+        let _ = wallet.set_offline_mode().await;
+        wallet.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_cancel_pending_transaction() {
+        let submitted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let daemon_submitted = Arc::clone(&submitted);
+        let (daemon, handle) = mock_daemon_scripted(Network::Dev, Vec::new(), &[], move |method, params| match method {
+            "get_info" => Some(json!(GetInfoResult { replace_by_fee: true, ..daemon_info(Network::Dev, Some(Hash::zero())) })),
+            "submit_transaction" => {
+                daemon_submitted.lock().unwrap().push(params["data"].as_str().unwrap().to_owned());
+                Some(json!(true))
+            },
+            _ => None
+        }).await;
+
+        let dir = TempDir::new("wallet-cancel");
+        // No balance is decrypted, the tables are never read
+        let tables = Arc::new(PrecomputedTables::new(10));
+        let wallet = Wallet::create(dir.to_string_lossy().into_owned(), "password".to_owned(), None, Network::Dev, tables).unwrap();
+
+        // Synced at the top of the daemon with a transfer pending at nonce 5
+        let transfer = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: XELIS_ASSET,
+            amount: COIN_VALUE,
+            destination: KeyPair::new().get_public_key().to_address(false),
+            extra_data: None
+        }]);
+        let (pending_hash, master_key) = {
+            let mut storage = wallet.get_storage().write().await;
+            let amount = 10 * COIN_VALUE;
+            let ciphertext = CiphertextCache::Decompressed(wallet.get_address().get_public_key().decompress().unwrap().encrypt(amount));
+            storage.set_balance_for(&XELIS_ASSET, Balance::new(amount, ciphertext)).await.unwrap();
+            storage.set_synced_topoheight(10).unwrap();
+            storage.set_top_block_hash(&Hash::zero()).unwrap();
+            storage.set_nonce(5).unwrap();
+
+            let (_, transaction) = wallet.create_transaction_with_storage(&storage, transfer.clone(), FeeBuilder::Value(1000), None, false).await.unwrap();
+            let hash = transaction.hash();
+            let now = get_current_time_in_seconds();
+            storage.add_pending_transaction(&hash, &PendingTransaction {
+                amounts: get_outgoing_amounts(&transfer, transaction.get_fee()),
+                transaction,
+                created_at: now,
+                last_broadcast: now,
+                state: PendingState::Pending
+            }).unwrap();
+            (hash, storage.get_private_key().unwrap())
+        };
+
+        let mut events = wallet.subscribe_events().await;
+        wallet.set_online_mode(&daemon, false).await.unwrap();
+        assert!(matches!(next_event(&mut events).await, Event::Online));
+        assert!(matches!(next_event(&mut events).await, Event::NewTopoHeight { topoheight: 10 }));
+
+        // Same nonce with a higher fee, sending the smallest amount to the first account derived
+        let replacement = wallet.cancel_transaction(&pending_hash).await.unwrap();
+        assert_eq!(replacement.get_nonce(), 5);
+        assert_eq!(replacement.get_fee(), 1000 * CANCEL_FEE_MULTIPLIER);
+        let TransactionType::Transfers(transfers) = replacement.get_data() else {
+            panic!("replacement is not a transfer")
+        };
+        assert_eq!(transfers.len(), 1);
+        let account = derive_account_keypair(&master_key, 1).get_public_key().compress();
+        assert_eq!(*transfers[0].get_destination(), account);
+        assert_ne!(*transfers[0].get_destination(), *wallet.get_address().get_public_key());
+        assert_eq!(*submitted.lock().unwrap(), vec![replacement.to_hex()]);
+
+        // Only the replacement is pending and the next transaction is built after it
+        {
+            let storage = wallet.get_storage().read().await;
+            let pending = storage.get_pending_transactions().unwrap();
+            assert_eq!(pending.len(), 1);
+            assert_eq!(pending[0].0, replacement.hash());
+            assert_eq!(pending[0].1.amounts, IndexMap::from([(XELIS_ASSET, 1 + 1000 * CANCEL_FEE_MULTIPLIER)]));
+            assert_eq!(storage.get_unconfirmed_nonce(), 6);
+        }
+
+        // Can't cancel it twice
+        assert!(matches!(wallet.cancel_transaction(&pending_hash).await, Err(WalletError::PendingTransactionNotFound(_))));
+
+        handle.abort();
+        let _ = wallet.set_offline_mode().await;
+        wallet.close().await;
+    }
+
+    // Send a transfer as the RPC server does
+    async fn send_transfer(wallet: &Wallet, amount: u64) -> Result<Transaction, WalletError> {
+        let transfer = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: XELIS_ASSET,
+            amount,
+            destination: KeyPair::new().get_public_key().to_address(false),
+            extra_data: None
+        }]);
+        let fee = FeeBuilder::Value(COIN_VALUE);
+
+        let mut reservation = wallet.reserve_nonce().await;
+        let (mut state, transaction) = {
+            let storage = wallet.get_storage().read().await;
+            wallet.create_transaction_with_storage(&storage, transfer.clone(), fee.clone(), None, false).await?
+        };
+        let transaction = wallet.submit_transaction_with_recovery(&mut reservation, &mut state, transaction, transfer, fee, None, false).await?;
+        reservation.commit(&mut state).await?;
+
+        Ok(transaction)
+    }
+
+    #[tokio::test]
+    async fn test_transfers_chained_on_pending_transactions() {
+        // Daemon accepting the transactions without executing them during the test
+        let submitted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let daemon_submitted = Arc::clone(&submitted);
+        let (daemon, handle) = mock_daemon_scripted(Network::Dev, Vec::new(), &[], move |method, params| match method {
+            "submit_transaction" => {
+                daemon_submitted.lock().unwrap().push(params["data"].as_str().unwrap().to_owned());
+                Some(json!(true))
+            },
+            _ => None
+        }).await;
+
+        let dir = TempDir::new("wallet-pending-transfers");
+        // No balance is decrypted, the tables are never read
+        let tables = Arc::new(PrecomputedTables::new(10));
+        let wallet = Wallet::create(dir.to_string_lossy().into_owned(), "password".to_owned(), None, Network::Dev, tables).unwrap();
+        {
+            let mut storage = wallet.get_storage().write().await;
+            let amount = 100 * COIN_VALUE;
+            let ciphertext = CiphertextCache::Decompressed(wallet.get_address().get_public_key().decompress().unwrap().encrypt(amount));
+            storage.set_balance_for(&XELIS_ASSET, Balance::new(amount, ciphertext)).await.unwrap();
+            storage.set_synced_topoheight(10).unwrap();
+            storage.set_top_block_hash(&Hash::zero()).unwrap();
+            storage.set_nonce(5).unwrap();
+        }
+
+        let mut events = wallet.subscribe_events().await;
+        wallet.set_online_mode(&daemon, false).await.unwrap();
+        assert!(matches!(next_event(&mut events).await, Event::Online));
+        assert!(matches!(next_event(&mut events).await, Event::NewTopoHeight { topoheight: 10 }));
+
+        // Each transfer is sent before the previous one is executed
+        let mut transactions = Vec::new();
+        for i in 0..3u64 {
+            let transaction = send_transfer(&wallet, 20 * COIN_VALUE).await.unwrap();
+            assert_eq!(transaction.get_nonce(), 5 + i);
+
+            // Confirmed balance is unchanged but the amounts sent and fees are not available anymore
+            let balance = wallet.get_storage().read().await.get_available_balance_for(&XELIS_ASSET).await.unwrap();
+            assert_eq!(balance.confirmed, 100 * COIN_VALUE);
+            assert_eq!(balance.pending_outgoing, (i + 1) * 21 * COIN_VALUE);
+            assert_eq!(balance.get_available(), 100 * COIN_VALUE - (i + 1) * 21 * COIN_VALUE);
+            transactions.push(transaction.to_hex());
+        }
+        assert_eq!(*submitted.lock().unwrap(), transactions);
+
+        // The confirmed balance would be enough but 37 coins are available only
+        assert!(matches!(
+            send_transfer(&wallet, 37 * COIN_VALUE).await,
+            Err(WalletError::AvailableBalanceExceeded(available, needed, _, _)) if available == 37 * COIN_VALUE && needed == 38 * COIN_VALUE
+        ));
+
+        // Once allowed, only the builder refuses to spend more than the unconfirmed balance
+        wallet.set_spending_pending_balance(true);
+        assert!(matches!(send_transfer(&wallet, 37 * COIN_VALUE).await, Err(WalletError::Any(_))));
+        wallet.set_spending_pending_balance(false);
+        assert_eq!(submitted.lock().unwrap().len(), 3);
+
+        let transaction = send_transfer(&wallet, 36 * COIN_VALUE).await.unwrap();
+        assert_eq!(transaction.get_nonce(), 8);
+
+        // Caches are lost as after a restart, the pending transactions must be executed first
+        wallet.get_storage().write().await.delete_unconfirmed_balances().await.unwrap();
+        assert!(matches!(send_transfer(&wallet, COIN_VALUE).await, Err(WalletError::PendingTransactionsNotCached(4))));
+
+        // The first one was executed, its amount is not pending anymore
+        {
+            let mut storage = wallet.get_storage().write().await;
+            storage.set_nonce(6).unwrap();
+            assert_eq!(storage.get_pending_outgoing_amount(&XELIS_ASSET).unwrap(), (21 + 21 + 37) * COIN_VALUE);
+
+            // The others were dropped by the daemon, they don't lock any balance
+            storage.mark_stale_pending_transactions(get_current_time_in_seconds(), 0).unwrap();
+            assert_eq!(storage.get_pending_outgoing_amount(&XELIS_ASSET).unwrap(), 0);
+        }
+
+        let transaction = send_transfer(&wallet, 90 * COIN_VALUE).await.unwrap();
+        assert_eq!(transaction.get_nonce(), 6);
+        assert_eq!(submitted.lock().unwrap().len(), 5);
+
+        handle.abort();
+        let _ = wallet.set_offline_mode().await;
+        wallet.close().await;
+    }
+}
@@ -9,6 +9,7 @@ use sled::{
     Db
 };
 use tokio::sync::Mutex;
+use zeroize::Zeroizing;
 use xelis_common::{
     account::CiphertextCache,
     api::{
@@ -32,7 +33,8 @@ use xelis_common::{
         Serializer,
         Writer
     },
-    transaction::Reference
+    time::TimestampSeconds,
+    transaction::{Reference, Transaction}
 };
 use anyhow::{
     Context,
@@ -40,6 +42,7 @@ use anyhow::{
     anyhow
 };
 use crate::{
+    account::{is_valid_account_name, DEFAULT_ACCOUNT_NAME},
     cipher::Cipher,
     config::SALT_SIZE,
     entry::{
@@ -49,6 +52,8 @@ use crate::{
     },
     error::WalletError
 };
+#[cfg(feature = "api_server")]
+use crate::api::AppPermissions;
 use log::{trace, debug, error};
 
 // keys used to retrieve from storage
@@ -66,6 +71,24 @@ const TOPOHEIGHT_KEY: &[u8] = b"TOPH";
 // represent the daemon top block hash
 const TOP_BLOCK_HASH_KEY: &[u8] = b"TOPBH";
 const NETWORK: &[u8] = b"NET";
+// represent the genesis block hash of the chain synced
+const GENESIS_HASH_KEY: &[u8] = b"GENH";
+// Daemon address set by the user to use by default
+const DAEMON_ADDRESS_KEY: &[u8] = b"DADDR";
+// Index of the account opened with the wallet
+const ACTIVE_ACCOUNT_KEY: &[u8] = b"ACCT";
+// Index of the next account to create
+const NEXT_ACCOUNT_KEY: &[u8] = b"NACCT";
+
+// Version of the storage format, saved in the public storage
+const STORAGE_VERSION_KEY: &[u8] = b"SVER";
+// Version 1: entries are encrypted with their tree name as associated data
+const STORAGE_VERSION: u8 = 1;
+// Tree used by the public storage, its values are not encrypted with the master key
+const DEFAULT_TREE_NAME: &[u8] = b"__sled__default";
+
+// Trees that are scoped to each account
+const ACCOUNT_TREES: [&str; 7] = ["transactions", "balances", "extra", "assets", "untracked_assets", "changes_topoheight", "pending_transactions"];
 
 // Default cache size
 const DEFAULT_CACHE_SIZE: usize = 100;
@@ -101,6 +124,109 @@ impl Serializer for Balance {
     }
 }
 
+// Asset tracked by the wallet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackedAsset {
+    pub decimals: u8,
+    // Synced topoheight of the wallet when the asset started to be tracked
+    // None for the assets stored before it was recorded
+    pub tracked_since: Option<u64>
+}
+
+impl Serializer for TrackedAsset {
+    fn write(&self, writer: &mut Writer) {
+        self.decimals.write(writer);
+        if let Some(topoheight) = self.tracked_since {
+            topoheight.write(writer);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let decimals = u8::read(reader)?;
+        // Assets stored before only contain the decimals
+        let tracked_since = if reader.size() > 0 {
+            Some(u64::read(reader)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            decimals,
+            tracked_since
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingState {
+    // Broadcasted and waiting to be executed in a block
+    Pending,
+    // Not executed before the timeout, it may have been dropped by the daemon
+    Stale
+}
+
+// Outgoing transaction broadcasted but not yet seen executed in a block
+#[derive(Debug, Clone)]
+pub struct PendingTransaction {
+    // Kept to broadcast it again if needed
+    pub transaction: Transaction,
+    // Plaintext amounts sent for each asset, fees included
+    pub amounts: IndexMap<Hash, u64>,
+    pub created_at: TimestampSeconds,
+    pub last_broadcast: TimestampSeconds,
+    pub state: PendingState
+}
+
+impl Serializer for PendingTransaction {
+    fn write(&self, writer: &mut Writer) {
+        self.transaction.write(writer);
+        self.amounts.write(writer);
+        self.created_at.write(writer);
+        self.last_broadcast.write(writer);
+        let state: u8 = match self.state {
+            PendingState::Pending => 0,
+            PendingState::Stale => 1
+        };
+        state.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let transaction = Transaction::read(reader)?;
+        let amounts = IndexMap::read(reader)?;
+        let created_at = TimestampSeconds::read(reader)?;
+        let last_broadcast = TimestampSeconds::read(reader)?;
+        let state = match u8::read(reader)? {
+            0 => PendingState::Pending,
+            1 => PendingState::Stale,
+            _ => return Err(ReaderError::InvalidValue)
+        };
+
+        Ok(Self {
+            transaction,
+            amounts,
+            created_at,
+            last_broadcast,
+            state
+        })
+    }
+}
+
+// Balance of an asset compared to the amounts sent by our pending transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AvailableBalance {
+    // Balance at the last topoheight synced
+    pub confirmed: u64,
+    // Amounts sent by the transactions not executed yet, fees included
+    pub pending_outgoing: u64
+}
+
+impl AvailableBalance {
+    // Amount that can be spent by a new transaction without making a pending one fail
+    pub fn get_available(&self) -> u64 {
+        self.confirmed.saturating_sub(self.pending_outgoing)
+    }
+}
+
 // Use this struct to get access to non-encrypted keys (such as salt for KDF and encrypted master key)
 pub struct Storage {
     db: Db
@@ -121,16 +247,28 @@ pub struct TxCache {
 pub struct EncryptedStorage {
     // cipher used to encrypt/decrypt/hash data
     cipher: Cipher,
+    // Index of the account this storage is scoped to
+    account: u32,
     // All transactions where this wallet is part of
     transactions: Tree,
     // balances for each asset
     balances: Tree,
-    // extra data (network, topoheight, etc)
+    // extra data of the account (nonce, topoheight, etc)
     extra: Tree,
+    // extra data shared by all accounts (network, private key, etc)
+    wallet_extra: Tree,
+    // all accounts created with their index
+    accounts: Tree,
     // all assets tracked by the wallet
     assets: Tree,
+    // assets removed by the user, they are not tracked again until added back
+    untracked_assets: Tree,
+    // decisions of the user for each application connected to the RPC Server
+    app_permissions: Tree,
     // This tree is used to store all topoheight where a change in the wallet occured
     changes_topoheight: Tree,
+    // Outgoing transactions broadcasted but not executed yet
+    pending_transactions: Tree,
     // The inner storage
     inner: Storage,
     // Caches
@@ -140,6 +278,12 @@ pub struct EncryptedStorage {
     // so we can build several txs without having to wait for the confirmation
     // We store it in a VecDeque so for each TX we have an entry and can just retrieve it
     unconfirmed_balances_cache: Mutex<HashMap<Hash, VecDeque<Balance>>>,
+    // Known changes of each balance since its last stored version (incoming/outgoing amounts)
+    // It is used to verify the next balance plaintext instead of decrypting it from scratch
+    balances_deltas: HashMap<Hash, i128>,
+    // Plaintext expected for the balances being decrypted
+    // It is shown as an approximation until the decryption is done
+    pending_balances: HashMap<Hash, u64>,
     tx_cache: Option<TxCache>,
     // Cache for the assets with their decimals
     assets_cache: Mutex<LruCache<Hash, u8>>,
@@ -150,31 +294,107 @@ pub struct EncryptedStorage {
 impl EncryptedStorage {
     pub fn new(inner: Storage, key: &[u8], salt: [u8; SALT_SIZE], network: Network) -> Result<Self> {
         let cipher = Cipher::new(key, Some(salt))?;
-        let mut storage = Self {
-            transactions: inner.db.open_tree(&cipher.hash_key("transactions"))?,
-            balances: inner.db.open_tree(&cipher.hash_key("balances"))?,
-            extra: inner.db.open_tree(&cipher.hash_key("extra"))?,
-            assets: inner.db.open_tree(&cipher.hash_key("assets"))?,
-            changes_topoheight: inner.db.open_tree(&cipher.hash_key("changes_topoheight"))?,
+        let mut storage = Self::with_account(inner, cipher, 0)?;
+
+        if storage.inner.get_storage_version()? < STORAGE_VERSION {
+            storage.migrate_to_authenticated_entries()?;
+            storage.inner.set_storage_version(STORAGE_VERSION)?;
+        }
+
+        if !storage.has_network()? {
+            // Wallets created before the network was recorded are on mainnet
+            let stored_network = if storage.accounts.is_empty() {
+                network
+            } else {
+                Network::Mainnet
+            };
+            storage.set_network(&stored_network)?;
+        }
+
+        let storage_network = storage.get_network()?;
+        if storage_network != network {
+            return Err(anyhow!("Network mismatch for this wallet storage (stored: {})!", storage_network));
+        }
+
+        if storage.accounts.is_empty() {
+            storage.save_to_disk_with_encrypted_key(&storage.accounts, DEFAULT_ACCOUNT_NAME.as_bytes(), &0u32.to_be_bytes())?;
+        }
+
+        Ok(storage)
+    }
+
+    // Open the storage with the trees of the requested account
+    fn with_account(inner: Storage, cipher: Cipher, account: u32) -> Result<Self> {
+        let open_tree = |name: &str| inner.db.open_tree(&cipher.hash_key(account_tree_name(name, account)));
+        Ok(Self {
+            account,
+            transactions: open_tree("transactions")?,
+            balances: open_tree("balances")?,
+            extra: open_tree("extra")?,
+            wallet_extra: inner.db.open_tree(&cipher.hash_key("extra"))?,
+            accounts: inner.db.open_tree(&cipher.hash_key("accounts"))?,
+            assets: open_tree("assets")?,
+            untracked_assets: open_tree("untracked_assets")?,
+            app_permissions: inner.db.open_tree(&cipher.hash_key("app_permissions"))?,
+            changes_topoheight: open_tree("changes_topoheight")?,
+            pending_transactions: open_tree("pending_transactions")?,
             cipher,
             inner,
             balances_cache: Mutex::new(LruCache::new(NonZeroUsize::new(DEFAULT_CACHE_SIZE).unwrap())),
             unconfirmed_balances_cache: Mutex::new(HashMap::new()),
+            balances_deltas: HashMap::new(),
+            pending_balances: HashMap::new(),
             tx_cache: None,
             assets_cache: Mutex::new(LruCache::new(NonZeroUsize::new(DEFAULT_CACHE_SIZE).unwrap())),
             synced_topoheight: None,
-        };
+        })
+    }
 
-        if storage.has_network()? {
-            let storage_network = storage.get_network()?;
-            if storage_network != network {
-                return Err(anyhow!("Network mismatch for this wallet storage (stored: {})!", storage_network));
+    // Entries of wallets created before the storage version 1 are encrypted without associated data
+    // Re-encrypt all of them with their tree name, keys that are encrypted are re-encrypted too
+    // Entries already migrated are skipped, so it can be resumed if interrupted
+    fn migrate_to_authenticated_entries(&self) -> Result<()> {
+        debug!("Migrating storage entries to authenticated encryption");
+        for name in self.inner.db.tree_names() {
+            if name == DEFAULT_TREE_NAME {
+                continue;
             }
-        } else {
-            storage.set_network(&network)?;
+
+            let tree = self.inner.db.open_tree(&name)?;
+            let mut batch = sled::Batch::default();
+            for res in tree.iter() {
+                let (key, value) = res?;
+                if self.cipher.decrypt_value(&value, &name).is_ok() {
+                    continue;
+                }
+
+                let raw_value = Zeroizing::new(self.cipher.decrypt_value(&value, &[])
+                    .map_err(|_| WalletError::TamperedEntry(hex::encode(&name), hex::encode(&key)))?);
+
+                // Keys are either hashed or encrypted
+                let new_key = match self.cipher.decrypt_value(&key, &[]) {
+                    Ok(raw_key) => {
+                        batch.remove(key);
+                        self.create_encrypted_key(&tree, &raw_key)?
+                    },
+                    Err(_) => key.to_vec()
+                };
+                batch.insert(new_key, self.encrypt_entry(&tree, &raw_value)?);
+            }
+            tree.apply_batch(batch)?;
         }
 
-        Ok(storage)
+        Ok(())
+    }
+
+    // Open a storage scoped to another account of this wallet
+    // Both storages are sharing the same database
+    pub fn open_account(&self, account: u32) -> Result<Self> {
+        trace!("open account {}", account);
+        let inner = Storage {
+            db: self.inner.db.clone()
+        };
+        Self::with_account(inner, self.cipher.clone(), account)
     }
 
     // Flush on disk to make sure it is saved
@@ -195,7 +415,8 @@ impl EncryptedStorage {
     // Key must be hashed or encrypted before calling this function
     fn internal_load<V: Serializer>(&self, tree: &Tree, key: &[u8]) -> Result<V> {
         let data = tree.get(key)?.context(format!("load from disk: tree = {:?}, key = {}", tree.name(), String::from_utf8_lossy(key)))?;
-        let bytes = self.cipher.decrypt_value(&data).context("Error while decrypting value from disk")?;
+        // Decrypted bytes may contain sensitive data such as the private key
+        let bytes = Zeroizing::new(self.decrypt_entry(tree, key, &data)?);
         let mut reader = Reader::new(&bytes);
         Ok(V::read(&mut reader).context("Error while de-serializing value from disk")?)
     }
@@ -206,8 +427,21 @@ impl EncryptedStorage {
         self.internal_load(tree, &hashed_key)
     }
 
+    // Encrypt a value to store in the tree
+    // The tree name is used as associated data so a value moved to another tree is detected
+    fn encrypt_entry(&self, tree: &Tree, value: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.cipher.encrypt_value(value, &tree.name())?)
+    }
+
+    // Decrypt a key or a value read from the tree
+    // It fails if the data was modified or moved from another tree
+    fn decrypt_entry(&self, tree: &Tree, key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+        self.cipher.decrypt_value(data, &tree.name())
+            .map_err(|_| WalletError::TamperedEntry(hex::encode(tree.name()), hex::encode(key)).into())
+    }
+
     // Because we can't predict the nonce used for encryption, we make it determistic
-    fn create_encrypted_key(&self, key: &[u8]) -> Result<Vec<u8>> {
+    fn create_encrypted_key(&self, tree: &Tree, key: &[u8]) -> Result<Vec<u8>> {
         // the hashed key is salted so its unique and can't be recover/bruteforced
         let hashed_key = self.cipher.hash_key(key);
 
@@ -215,21 +449,21 @@ impl EncryptedStorage {
         let mut nonce = [0u8; Cipher::NONCE_SIZE];
         nonce.copy_from_slice(&hashed_key[0..Cipher::NONCE_SIZE]);
 
-        let key = self.cipher.encrypt_value_with_nonce(key, &nonce)?;
+        let key = self.cipher.encrypt_value_with_nonce(key, &nonce, &tree.name())?;
         Ok(key)
     }
 
     // load from disk using an encrypted key, decrypt the value and deserialize it
     fn load_from_disk_with_encrypted_key<V: Serializer>(&self, tree: &Tree, key: &[u8]) -> Result<V> {
-        let encrypted_key = self.create_encrypted_key(key)?;
+        let encrypted_key = self.create_encrypted_key(tree, key)?;
         self.internal_load(tree, &encrypted_key)
     }
 
     // Encrypt key, encrypt data and then save to disk
     // We encrypt instead of hashing to be able to retrieve the key
     fn save_to_disk_with_encrypted_key(&self, tree: &Tree, key: &[u8], value: &[u8]) -> Result<()> {
-        let encrypted_key = self.create_encrypted_key(key)?;
-        let encrypted_value = self.cipher.encrypt_value(value)?;
+        let encrypted_key = self.create_encrypted_key(tree, key)?;
+        let encrypted_value = self.encrypt_entry(tree, value)?;
         tree.insert(encrypted_key, encrypted_value)?;
         Ok(())
     }
@@ -237,7 +471,7 @@ impl EncryptedStorage {
     // hash key, encrypt data and then save to disk 
     fn save_to_disk(&self, tree: &Tree, key: &[u8], value: &[u8]) -> Result<()> {
         let hashed_key = self.cipher.hash_key(key);
-        tree.insert(hashed_key, self.cipher.encrypt_value(value)?)?;
+        tree.insert(hashed_key, self.encrypt_entry(tree, value)?)?;
         Ok(())
     }
 
@@ -250,7 +484,7 @@ impl EncryptedStorage {
 
     // hash key, encrypt data and then save to disk 
     fn delete_from_disk_with_encrypted_key(&self, tree: &Tree, key: &[u8]) -> Result<()> {
-        let encrypted_key = self.create_encrypted_key(key)?;
+        let encrypted_key = self.create_encrypted_key(tree, key)?;
         tree.remove(encrypted_key)?;
         Ok(())
     }
@@ -263,7 +497,7 @@ impl EncryptedStorage {
 
     // Encrypt instead of hash the key to recover it later
     fn contains_encrypted_data(&self, tree: &Tree, key: &[u8]) -> Result<bool> {
-        let encrypted_key = self.create_encrypted_key(key)?;
+        let encrypted_key = self.create_encrypted_key(tree, key)?;
         Ok(tree.contains_key(encrypted_key)?)
     }
 
@@ -310,7 +544,7 @@ impl EncryptedStorage {
             let mut key = None;
             let mut value = None;
             if let Some(query) = query_key.as_ref() {
-                let decrypted = self.cipher.decrypt_value(&k)?;
+                let decrypted = self.decrypt_entry(&tree, &k, &k)?;
                 let k = DataValue::from_bytes(&decrypted)?;
                 if !query.verify_value(&k) {
                     continue;
@@ -319,7 +553,7 @@ impl EncryptedStorage {
             }
 
             if let Some(query) = query_value.as_ref() {
-                let decrypted = self.cipher.decrypt_value(&k)?;
+                let decrypted = self.decrypt_entry(&tree, &k, &v)?;
                 let v = DataElement::from_bytes(&decrypted)?;
                 if !query.verify_element(&v) {
                     continue;
@@ -331,14 +565,14 @@ impl EncryptedStorage {
             let key = if let Some(key) = key {
                 key
             } else {
-                let decrypted = self.cipher.decrypt_value(&k)?;
+                let decrypted = self.decrypt_entry(&tree, &k, &k)?;
                 DataValue::from_bytes(&decrypted)?
             };
 
             let value = if let Some(value) = value {
                 value
             } else {
-                let decrypted = self.cipher.decrypt_value(&v)?;
+                let decrypted = self.decrypt_entry(&tree, &k, &v)?;
                 DataElement::from_bytes(&decrypted)?
             };
 
@@ -360,7 +594,7 @@ impl EncryptedStorage {
         let mut keys = Vec::new();
         for e in tree.iter() {
             let (key, _) = e?;
-            let decrypted = self.cipher.decrypt_value(&key)?;
+            let decrypted = self.decrypt_entry(&tree, &key, &key)?;
             let k = DataValue::from_bytes(&decrypted)?;
             if let Some(query) = query {
                 if !query.verify_value(&k) {
@@ -385,14 +619,14 @@ impl EncryptedStorage {
         let mut assets = HashSet::new();
         for res in self.assets.iter() {
             let (key, value) = res?;
-            let raw_key = &self.cipher.decrypt_value(&key)?;
+            let raw_key = &self.decrypt_entry(&self.assets, &key, &key)?;
             let mut reader = Reader::new(raw_key);
             let asset = Hash::read(&mut reader)?;
 
             let decimals = if let Some(decimals) = cache.get(&asset) {
                 *decimals
             } else {
-                let raw_value = &self.cipher.decrypt_value(&value)?;
+                let raw_value = &self.decrypt_entry(&self.assets, &key, &value)?;
                 let mut reader = Reader::new(raw_value);
                 u8::read(&mut reader)?
             };
@@ -414,11 +648,11 @@ impl EncryptedStorage {
         let mut assets = Vec::new();
         for res in self.assets.iter() {
             let (key, value) = res?;
-            let asset = Hash::from_bytes(&self.cipher.decrypt_value(&key)?)?;
+            let asset = Hash::from_bytes(&self.decrypt_entry(&self.assets, &key, &key)?)?;
             let decimals = if let Some(decimals) = cache.get(&asset) {
                 *decimals
             } else {
-                let raw_value = &self.cipher.decrypt_value(&value)?;
+                let raw_value = &self.decrypt_entry(&self.assets, &key, &value)?;
                 let mut reader = Reader::new(raw_value);
                 u8::read(&mut reader)?
             };
@@ -443,20 +677,57 @@ impl EncryptedStorage {
     }
 
     // save asset with its corresponding decimals
+    // It is tracked since the current synced topoheight
     pub async fn add_asset(&mut self, asset: &Hash, decimals: u8) -> Result<()> {
         if self.contains_asset(asset).await? {
             return Err(WalletError::AssetAlreadyRegistered.into());
         }
 
-        self.save_to_disk_with_encrypted_key(&self.assets, asset.as_bytes(), &decimals.to_be_bytes())?;
+        let tracked = TrackedAsset {
+            decimals,
+            tracked_since: Some(self.get_synced_topoheight().unwrap_or(0))
+        };
+        self.save_to_disk_with_encrypted_key(&self.assets, asset.as_bytes(), &tracked.to_bytes())?;
+        // Added back by the user
+        self.delete_from_disk_with_encrypted_key(&self.untracked_assets, asset.as_bytes())?;
 
         let mut cache = self.assets_cache.lock().await;
         cache.put(asset.clone(), decimals);
         Ok(())
     }
 
+    // Stop tracking this asset, its balance is deleted
+    // It is not added again by the sync until added back with add_asset
+    pub async fn remove_asset(&mut self, asset: &Hash) -> Result<()> {
+        if !self.contains_asset(asset).await? {
+            return Err(WalletError::AssetNotTracked(asset.clone()).into());
+        }
+
+        trace!("remove asset {}", asset);
+        self.delete_from_disk_with_encrypted_key(&self.assets, asset.as_bytes())?;
+        self.delete_from_disk(&self.balances, asset.as_bytes())?;
+        self.save_to_disk_with_encrypted_key(&self.untracked_assets, asset.as_bytes(), &[])?;
+
+        self.assets_cache.lock().await.pop(asset);
+        self.balances_cache.lock().await.pop(asset);
+        self.unconfirmed_balances_cache.lock().await.remove(asset);
+        self.balances_deltas.remove(asset);
+        self.pending_balances.remove(asset);
+        Ok(())
+    }
+
+    // Check if the asset was removed by the user
+    pub fn is_asset_untracked(&self, asset: &Hash) -> Result<bool> {
+        self.contains_encrypted_data(&self.untracked_assets, asset.as_bytes())
+    }
+
     // Retrieve the stored decimals for this asset for better display
     pub fn get_asset_decimals(&self, asset: &Hash) -> Result<u8> {
+        self.get_tracked_asset(asset).map(|tracked| tracked.decimals)
+    }
+
+    // Retrieve the asset tracked with its decimals and since when it is tracked
+    pub fn get_tracked_asset(&self, asset: &Hash) -> Result<TrackedAsset> {
         self.load_from_disk_with_encrypted_key(&self.assets, asset.as_bytes())
     }
 
@@ -590,11 +861,60 @@ impl EncryptedStorage {
 
         self.save_to_disk(&self.balances, asset.as_bytes(), &balance.to_bytes())?;
 
+        // Stored balance is now the reference for the next changes
+        self.balances_deltas.remove(asset);
+        self.pending_balances.remove(asset);
+
         let mut cache = self.balances_cache.lock().await;
         cache.put(asset.clone(), balance);
         Ok(())
     }
 
+    // Register a known change of the balance for this asset since its last stored version
+    pub fn add_balance_delta(&mut self, asset: &Hash, delta: i128) {
+        trace!("add balance delta {} for {}", delta, asset);
+        *self.balances_deltas.entry(asset.clone()).or_insert(0) += delta;
+    }
+
+    // Delete all known changes, the next balances will have to be decrypted
+    pub fn clear_balances_deltas(&mut self) {
+        trace!("clear balances deltas");
+        self.balances_deltas.clear();
+    }
+
+    // Plaintext expected for the next balance of this asset, using the last stored balance and the known changes
+    // Returns None if no change is known or if the result would be invalid
+    pub async fn get_expected_balance_for(&self, asset: &Hash) -> Option<u64> {
+        let delta = *self.balances_deltas.get(asset)?;
+        let amount = self.get_plaintext_balance_for(asset).await.unwrap_or(0);
+        u64::try_from(amount as i128 + delta).ok()
+    }
+
+    // Set the plaintext to show while the new balance of this asset is being decrypted
+    pub fn set_pending_balance_for(&mut self, asset: &Hash, amount: u64) {
+        trace!("set pending balance {} for {}", amount, asset);
+        self.pending_balances.insert(asset.clone(), amount);
+    }
+
+    pub fn remove_pending_balance_for(&mut self, asset: &Hash) {
+        self.pending_balances.remove(asset);
+    }
+
+    // Retrieve the approximated plaintext of a balance being decrypted
+    pub fn get_pending_balance_for(&self, asset: &Hash) -> Option<u64> {
+        self.pending_balances.get(asset).copied()
+    }
+
+    // Retrieve the plaintext balance to display for this asset
+    // Returns true if it's an approximation because its latest version is still being decrypted
+    pub async fn get_display_balance_for(&self, asset: &Hash) -> Result<(u64, bool)> {
+        if let Some(amount) = self.get_pending_balance_for(asset) {
+            return Ok((amount, true))
+        }
+
+        self.get_plaintext_balance_for(asset).await.map(|amount| (amount, false))
+    }
+
     // Retrieve a transaction saved in wallet using its hash
     pub fn get_transaction(&self, hash: &Hash) -> Result<TransactionEntry> {
         self.load_from_disk(&self.transactions, hash.as_bytes())
@@ -608,23 +928,26 @@ impl EncryptedStorage {
     // delete all transactions above the specified topoheight
     // This will go through each transaction, deserialize it, check topoheight, and delete it if required
     pub fn delete_transactions_above_topoheight(&mut self, topoheight: u64) -> Result<()> {
-        for el in self.transactions.iter().values() {
-            let value = el?;
-            let entry = TransactionEntry::from_bytes(&self.cipher.decrypt_value(&value)?)?;
+        for el in self.transactions.iter() {
+            let (key, value) = el?;
+            let entry = TransactionEntry::from_bytes(&self.decrypt_entry(&self.transactions, &key, &value)?)?;
             if entry.get_topoheight() > topoheight {
                 self.delete_transaction(entry.get_hash())?;
             }
         }
 
+        // Changes may come from orphaned transactions
+        self.clear_balances_deltas();
+
         Ok(())
     }
 
     // Filter when the data is deserialized to not load all transactions in memory
     pub fn get_filtered_transactions(&self, address: Option<&PublicKey>, min_topoheight: Option<u64>, max_topoheight: Option<u64>, accept_incoming: bool, accept_outgoing: bool, accept_coinbase: bool, accept_burn: bool, query: Option<&Query>) -> Result<Vec<TransactionEntry>> {
         let mut transactions = Vec::new();
-        for el in self.transactions.iter().values() {
-            let value = el?;
-            let mut entry = TransactionEntry::from_bytes(&self.cipher.decrypt_value(&value)?)?;
+        for el in self.transactions.iter() {
+            let (key, value) = el?;
+            let mut entry = TransactionEntry::from_bytes(&self.decrypt_entry(&self.transactions, &key, &value)?)?;
             if let Some(topoheight) = min_topoheight {
                 if entry.get_topoheight() < topoheight {
                     continue;
@@ -640,6 +963,7 @@ impl EncryptedStorage {
             let (save, mut transfers) = match entry.get_mut_entry() {
                 EntryData::Coinbase { .. } if accept_coinbase => (true, None),
                 EntryData::Burn { .. } if accept_burn => (true, None),
+                EntryData::AssetCreation { .. } if accept_burn => (true, None),
                 EntryData::Incoming { from, transfers } if accept_incoming => match address {
                     Some(key) => (*key == *from, Some(transfers.into_iter().map(|t| Transfer::In(t)).collect::<Vec<_>>())),
                     None => (true, None)
@@ -698,6 +1022,7 @@ impl EncryptedStorage {
     // Delete all transactions from this wallet
     pub fn delete_transactions(&mut self) -> Result<()> {
         self.transactions.clear()?;
+        self.clear_balances_deltas();
         Ok(())
     }
 
@@ -706,6 +1031,8 @@ impl EncryptedStorage {
         self.balances.clear()?;
         self.delete_unconfirmed_balances().await?;
         self.balances_cache.lock().await.clear();
+        self.clear_balances_deltas();
+        self.pending_balances.clear();
         Ok(())
     }
 
@@ -731,6 +1058,32 @@ impl EncryptedStorage {
         Ok(())
     }
 
+    // Reset the wallet state to rescan it from the given topoheight
+    // Balances, assets and cached txs are deleted, they will be re-fetched from daemon
+    // Transactions above the topoheight are deleted and the nonce is set to the one from daemon
+    pub async fn reset_for_rescan(&mut self, topoheight: u64, nonce: u64) -> Result<()> {
+        debug!("set synced topoheight to {}", topoheight);
+        self.set_synced_topoheight(topoheight)?;
+        self.delete_top_block_hash()?;
+        // balances will be re-fetched from daemon
+        self.delete_balances().await?;
+        self.delete_assets().await?;
+        // unconfirmed balances are going to be outdated, we delete them
+        self.delete_unconfirmed_balances().await?;
+        self.clear_tx_cache();
+        self.set_nonce(nonce)?;
+
+        if topoheight == 0 {
+            debug!("Deleting all transactions for full rescan");
+            self.delete_transactions()?;
+        } else {
+            debug!("Deleting transactions above {} for partial rescan", topoheight);
+            self.delete_transactions_above_topoheight(topoheight)?;
+        }
+
+        Ok(())
+    }
+
     // Save the transaction with its TX hash as key
     // We hash the hash of the TX to use it as a key to not let anyone being able to see txs saved on disk
     // with no access to the decrypted master key
@@ -742,9 +1095,126 @@ impl EncryptedStorage {
             self.tx_cache = None;
         }
 
+        if self.has_pending_transaction(hash)? {
+            debug!("Pending transaction {} has been executed", hash);
+            self.delete_pending_transaction(hash)?;
+        }
+
         self.save_to_disk(&self.transactions, hash.as_bytes(), &transaction.to_bytes())
     }
 
+    // Save an outgoing transaction until it is seen executed in a block
+    pub fn add_pending_transaction(&mut self, hash: &Hash, pending: &PendingTransaction) -> Result<()> {
+        trace!("add pending transaction {}", hash);
+        self.save_to_disk_with_encrypted_key(&self.pending_transactions, hash.as_bytes(), &pending.to_bytes())
+    }
+
+    pub fn get_pending_transaction(&self, hash: &Hash) -> Result<PendingTransaction> {
+        trace!("get pending transaction {}", hash);
+        self.load_from_disk_with_encrypted_key(&self.pending_transactions, hash.as_bytes())
+    }
+
+    pub fn has_pending_transaction(&self, hash: &Hash) -> Result<bool> {
+        trace!("has pending transaction {}", hash);
+        self.contains_encrypted_data(&self.pending_transactions, hash.as_bytes())
+    }
+
+    pub fn delete_pending_transaction(&mut self, hash: &Hash) -> Result<()> {
+        trace!("delete pending transaction {}", hash);
+        self.delete_from_disk_with_encrypted_key(&self.pending_transactions, hash.as_bytes())
+    }
+
+    // Retrieve all pending transactions ordered by nonce
+    pub fn get_pending_transactions(&self) -> Result<Vec<(Hash, PendingTransaction)>> {
+        trace!("get pending transactions");
+        let mut transactions = Vec::new();
+        for res in self.pending_transactions.iter() {
+            let (key, value) = res?;
+            let hash = Hash::from_bytes(&self.decrypt_entry(&self.pending_transactions, &key, &key)?)?;
+            let pending = PendingTransaction::from_bytes(&self.decrypt_entry(&self.pending_transactions, &key, &value)?)?;
+            transactions.push((hash, pending));
+        }
+
+        transactions.sort_by_key(|(_, pending)| pending.transaction.get_nonce());
+        Ok(transactions)
+    }
+
+    // Total amount of the asset sent by the pending transactions still waiting to be executed
+    // Stale ones and the ones whose nonce was already used are ignored, they will never be executed
+    pub fn get_pending_outgoing_amount(&self, asset: &Hash) -> Result<u64> {
+        let nonce = self.get_nonce().unwrap_or(0);
+        let mut total: u64 = 0;
+        for (_, pending) in self.get_pending_transactions()? {
+            if pending.state != PendingState::Pending || pending.transaction.get_nonce() < nonce {
+                continue;
+            }
+
+            if let Some(amount) = pending.amounts.get(asset) {
+                total = total.saturating_add(*amount);
+            }
+        }
+
+        Ok(total)
+    }
+
+    // Highest nonce used by the pending transactions still waiting to be executed
+    // Stale ones are ignored as they may have been dropped by the daemon
+    pub fn get_highest_pending_nonce(&self) -> Result<Option<u64>> {
+        let transactions = self.get_pending_transactions()?;
+        Ok(transactions.iter()
+            .filter(|(_, pending)| pending.state == PendingState::Pending)
+            .map(|(_, pending)| pending.transaction.get_nonce())
+            .max())
+    }
+
+    // Nonce to use for the next transaction, chained after our pending transactions
+    // The unconfirmed nonce may be behind them if the TX cache was cleared
+    pub fn get_next_nonce(&self) -> Result<u64> {
+        let nonce = self.get_unconfirmed_nonce();
+        Ok(match self.get_highest_pending_nonce()? {
+            Some(pending) if pending >= nonce => pending + 1,
+            _ => nonce
+        })
+    }
+
+    // Confirmed balance of the asset with the amount locked by our pending transactions
+    pub async fn get_available_balance_for(&self, asset: &Hash) -> Result<AvailableBalance> {
+        Ok(AvailableBalance {
+            confirmed: self.get_plaintext_balance_for(asset).await?,
+            pending_outgoing: self.get_pending_outgoing_amount(asset)?
+        })
+    }
+
+    // Delete the pending transactions whose nonce was used by another transaction executed
+    // They were replaced or dropped by the daemon and will never be executed
+    // Returns the hashes of the transactions deleted
+    pub fn delete_pending_transactions_below_nonce(&mut self, nonce: u64) -> Result<Vec<Hash>> {
+        let mut deleted = Vec::new();
+        for (hash, pending) in self.get_pending_transactions()? {
+            if pending.transaction.get_nonce() < nonce {
+                self.delete_pending_transaction(&hash)?;
+                deleted.push(hash);
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    // Mark as stale the pending transactions not executed since their last broadcast
+    // Returns the hashes of the transactions that became stale
+    pub fn mark_stale_pending_transactions(&mut self, now: TimestampSeconds, timeout: u64) -> Result<Vec<Hash>> {
+        let mut stale = Vec::new();
+        for (hash, mut pending) in self.get_pending_transactions()? {
+            if pending.state == PendingState::Pending && pending.last_broadcast.saturating_add(timeout) <= now {
+                pending.state = PendingState::Stale;
+                self.add_pending_transaction(&hash, &pending)?;
+                stale.push(hash);
+            }
+        }
+
+        Ok(stale)
+    }
+
     // Check if the transaction is stored in wallet
     pub fn has_transaction(&self, hash: &Hash) -> Result<bool> {
         trace!("has transaction {}", hash);
@@ -786,13 +1256,13 @@ impl EncryptedStorage {
     // Store the private key
     pub fn set_private_key(&mut self, private_key: &PrivateKey) -> Result<()> {
         trace!("set private key");
-        self.save_to_disk(&self.extra, PRIVATE_KEY, &private_key.to_bytes())
+        self.save_to_disk(&self.wallet_extra, PRIVATE_KEY, &Zeroizing::new(private_key.to_bytes()))
     }
 
     // Retrieve the keypair of this wallet
     pub fn get_private_key(&self) -> Result<PrivateKey> {
         trace!("get private key");
-        self.load_from_disk(&self.extra, PRIVATE_KEY)
+        self.load_from_disk(&self.wallet_extra, PRIVATE_KEY)
     }
 
     // Set the topoheight until which the wallet is synchronized
@@ -839,6 +1309,169 @@ impl EncryptedStorage {
         self.load_from_disk(&self.extra, TOP_BLOCK_HASH_KEY)
     }
 
+    // Index of the account used by this storage
+    pub fn get_account(&self) -> u32 {
+        self.account
+    }
+
+    // Get the account to open with the wallet
+    pub fn get_active_account(&self) -> Result<u32> {
+        trace!("get active account");
+        if !self.contains_data(&self.wallet_extra, ACTIVE_ACCOUNT_KEY)? {
+            return Ok(0)
+        }
+
+        self.load_from_disk(&self.wallet_extra, ACTIVE_ACCOUNT_KEY)
+    }
+
+    // Set the account to open with the wallet
+    pub fn set_active_account(&mut self, account: u32) -> Result<()> {
+        trace!("set active account to {}", account);
+        self.save_to_disk(&self.wallet_extra, ACTIVE_ACCOUNT_KEY, &account.to_be_bytes())
+    }
+
+    // Retrieve all accounts with their index, ordered by index
+    pub fn get_accounts(&self) -> Result<Vec<(String, u32)>> {
+        trace!("get accounts");
+        let mut accounts = Vec::new();
+        for res in self.accounts.iter() {
+            let (key, value) = res?;
+            let name = String::from_utf8(self.decrypt_entry(&self.accounts, &key, &key)?).context("Invalid account name")?;
+            let index = u32::from_bytes(&self.decrypt_entry(&self.accounts, &key, &value)?)?;
+            accounts.push((name, index));
+        }
+        accounts.sort_by_key(|(_, index)| *index);
+
+        Ok(accounts)
+    }
+
+    // Get the index of an account using its name
+    pub fn get_account_index(&self, name: &str) -> Result<Option<u32>> {
+        trace!("get account index for {}", name);
+        if !self.contains_encrypted_data(&self.accounts, name.as_bytes())? {
+            return Ok(None)
+        }
+
+        Ok(Some(self.load_from_disk_with_encrypted_key(&self.accounts, name.as_bytes())?))
+    }
+
+    // Register a new account and returns its index
+    // Indexes are never reused, even if the account is deleted
+    pub fn create_account(&mut self, name: &str) -> Result<u32> {
+        trace!("create account {}", name);
+        if !is_valid_account_name(name) {
+            return Err(WalletError::InvalidAccountName.into())
+        }
+
+        if self.contains_encrypted_data(&self.accounts, name.as_bytes())? {
+            return Err(WalletError::AccountAlreadyExists(name.to_owned()).into())
+        }
+
+        let index: u32 = if self.contains_data(&self.wallet_extra, NEXT_ACCOUNT_KEY)? {
+            self.load_from_disk(&self.wallet_extra, NEXT_ACCOUNT_KEY)?
+        } else {
+            1
+        };
+        let next = index.checked_add(1).context("Maximum accounts reached")?;
+
+        self.save_to_disk(&self.wallet_extra, NEXT_ACCOUNT_KEY, &next.to_be_bytes())?;
+        self.save_to_disk_with_encrypted_key(&self.accounts, name.as_bytes(), &index.to_be_bytes())?;
+
+        Ok(index)
+    }
+
+    // Rename an account, its index and data are kept
+    pub fn rename_account(&mut self, name: &str, new_name: &str) -> Result<()> {
+        trace!("rename account {} to {}", name, new_name);
+        if !is_valid_account_name(new_name) {
+            return Err(WalletError::InvalidAccountName.into())
+        }
+
+        let index = self.get_account_index(name)?.ok_or_else(|| WalletError::AccountNotFound(name.to_owned()))?;
+        if self.contains_encrypted_data(&self.accounts, new_name.as_bytes())? {
+            return Err(WalletError::AccountAlreadyExists(new_name.to_owned()).into())
+        }
+
+        self.save_to_disk_with_encrypted_key(&self.accounts, new_name.as_bytes(), &index.to_be_bytes())?;
+        self.delete_from_disk_with_encrypted_key(&self.accounts, name.as_bytes())
+    }
+
+    // Get the decisions saved for an application
+    #[cfg(feature = "api_server")]
+    pub fn get_app_permissions(&self, app_id: &str) -> Result<Option<AppPermissions>> {
+        trace!("get app permissions for {}", app_id);
+        if !self.contains_encrypted_data(&self.app_permissions, app_id.as_bytes())? {
+            return Ok(None)
+        }
+
+        Ok(Some(self.load_from_disk_with_encrypted_key(&self.app_permissions, app_id.as_bytes())?))
+    }
+
+    // Save the decisions for an application, they are shared by all accounts
+    #[cfg(feature = "api_server")]
+    pub fn set_app_permissions(&mut self, app_id: &str, permissions: &AppPermissions) -> Result<()> {
+        trace!("set app permissions for {}", app_id);
+        if permissions.is_empty() {
+            return self.delete_app_permissions(app_id)
+        }
+
+        self.save_to_disk_with_encrypted_key(&self.app_permissions, app_id.as_bytes(), &permissions.to_bytes())
+    }
+
+    // Revoke all decisions of an application, the user will be asked again
+    #[cfg(feature = "api_server")]
+    pub fn delete_app_permissions(&mut self, app_id: &str) -> Result<()> {
+        trace!("delete app permissions for {}", app_id);
+        self.delete_from_disk_with_encrypted_key(&self.app_permissions, app_id.as_bytes())
+    }
+
+    // Retrieve all applications with their saved decisions
+    #[cfg(feature = "api_server")]
+    pub fn get_all_app_permissions(&self) -> Result<Vec<(String, AppPermissions)>> {
+        trace!("get all app permissions");
+        let mut apps = Vec::new();
+        for res in self.app_permissions.iter() {
+            let (key, value) = res?;
+            let id = String::from_utf8(self.decrypt_entry(&self.app_permissions, &key, &key)?).context("Invalid application id")?;
+            let permissions = AppPermissions::from_bytes(&self.decrypt_entry(&self.app_permissions, &key, &value)?)?;
+            apps.push((id, permissions));
+        }
+        apps.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        Ok(apps)
+    }
+
+    // Delete an account and all its data
+    // The default account and the active one can't be deleted
+    pub fn delete_account(&mut self, name: &str) -> Result<()> {
+        trace!("delete account {}", name);
+        let index = self.get_account_index(name)?.ok_or_else(|| WalletError::AccountNotFound(name.to_owned()))?;
+        if index == 0 || index == self.account || index == self.get_active_account()? {
+            return Err(WalletError::CannotDeleteAccount.into())
+        }
+
+        self.delete_from_disk_with_encrypted_key(&self.accounts, name.as_bytes())?;
+        for tree in ACCOUNT_TREES {
+            self.inner.db.drop_tree(&self.cipher.hash_key(account_tree_name(tree, index)))?;
+        }
+
+        Ok(())
+    }
+
+    // Check if this account has any asset with a non-zero balance
+    pub fn has_non_zero_balance(&self) -> Result<bool> {
+        trace!("has non zero balance");
+        for res in self.balances.iter() {
+            let (key, value) = res?;
+            let balance = Balance::from_bytes(&self.decrypt_entry(&self.balances, &key, &value)?)?;
+            if balance.amount > 0 {
+                return Ok(true)
+            }
+        }
+
+        Ok(false)
+    }
+
     pub fn get_public_storage(&self) -> &Storage {
         trace!("get public storage");
         &self.inner
@@ -852,19 +1485,52 @@ impl EncryptedStorage {
     // Get the network on which this wallet is
     fn get_network(&self) -> Result<Network> {
         trace!("get network");
-        self.load_from_disk(&self.extra, NETWORK)
+        self.load_from_disk(&self.wallet_extra, NETWORK)
     }
 
     // Save the network to disk
     fn set_network(&mut self, network: &Network) -> Result<()> {
         trace!("set network to {}", network);
-        self.save_to_disk(&self.extra, NETWORK, &network.to_bytes())
+        self.save_to_disk(&self.wallet_extra, NETWORK, &network.to_bytes())
     }
 
     // Check if the network is already registered
     fn has_network(&self) -> Result<bool> {
         trace!("has network");
-        self.contains_data(&self.extra, NETWORK)
+        self.contains_data(&self.wallet_extra, NETWORK)
+    }
+
+    // Get the genesis block hash of the chain synced by this wallet
+    // Returns None if it was never synced with a daemon reporting it
+    pub fn get_genesis_hash(&self) -> Result<Option<Hash>> {
+        trace!("get genesis hash");
+        if !self.contains_data(&self.wallet_extra, GENESIS_HASH_KEY)? {
+            return Ok(None)
+        }
+
+        Ok(Some(self.load_from_disk(&self.wallet_extra, GENESIS_HASH_KEY)?))
+    }
+
+    // Save the genesis block hash of the chain synced
+    pub fn set_genesis_hash(&mut self, hash: &Hash) -> Result<()> {
+        trace!("set genesis hash to {}", hash);
+        self.save_to_disk(&self.wallet_extra, GENESIS_HASH_KEY, hash.as_bytes())
+    }
+
+    // Get the daemon address saved to connect by default
+    pub fn get_daemon_address(&self) -> Result<Option<String>> {
+        trace!("get daemon address");
+        if !self.contains_data(&self.wallet_extra, DAEMON_ADDRESS_KEY)? {
+            return Ok(None)
+        }
+
+        Ok(Some(self.load_from_disk(&self.wallet_extra, DAEMON_ADDRESS_KEY)?))
+    }
+
+    // Save the daemon address to connect by default
+    pub fn set_daemon_address(&mut self, daemon_address: &String) -> Result<()> {
+        trace!("set daemon address to {}", daemon_address);
+        self.save_to_disk(&self.wallet_extra, DAEMON_ADDRESS_KEY, &daemon_address.to_bytes())
     }
 
     // Add a topoheight where a change occured
@@ -892,7 +1558,7 @@ impl EncryptedStorage {
         let mut deleted = false;
         for res in self.changes_topoheight.iter().keys() {
             let key = res?;
-            let raw = self.cipher.decrypt_value(&key).context("Error while decrypting key from disk")?;
+            let raw = self.decrypt_entry(&self.changes_topoheight, &key, &key)?;
             let topo = u64::from_bytes(&raw)?;
             if topo > topoheight {
                 trace!("deleting topoheight changes at {}", topo);
@@ -909,8 +1575,8 @@ impl EncryptedStorage {
         trace!("get topoheight changes");
         self.changes_topoheight.iter().rev().map(|res| {
             let (key, value) = res?;
-            let topo = u64::from_bytes(&self.cipher.decrypt_value(&key)?)?;
-            let hash = Hash::from_bytes(&self.cipher.decrypt_value(&value)?)?;
+            let topo = u64::from_bytes(&self.decrypt_entry(&self.changes_topoheight, &key, &key)?)?;
+            let hash = Hash::from_bytes(&self.decrypt_entry(&self.changes_topoheight, &key, &value)?)?;
             Ok((topo, hash))
         })
     }
@@ -921,7 +1587,7 @@ impl EncryptedStorage {
         let mut highest = 0;
         for res in self.changes_topoheight.iter().keys() {
             let key = res?;
-            let raw = self.cipher.decrypt_value(&key).context("Error while decrypting key from disk")?;
+            let raw = self.decrypt_entry(&self.changes_topoheight, &key, &key)?;
             let topo = u64::from_bytes(&raw)?;
             if topo > highest && topo < max {
                 highest = topo;
@@ -932,6 +1598,15 @@ impl EncryptedStorage {
     }
 }
 
+// Trees of the default account keep their original name
+fn account_tree_name(name: &str, account: u32) -> String {
+    if account == 0 {
+        name.to_owned()
+    } else {
+        format!("{}_{}", name, account)
+    }
+}
+
 impl Storage {
     pub fn new(name: String) -> Result<Self> {
         let db = sled::open(name)?;
@@ -1005,4 +1680,280 @@ impl Storage {
         self.db.insert(SALT_KEY, salt)?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    // get the version of the storage format, wallets created before versioning are at 0
+    pub fn get_storage_version(&self) -> Result<u8> {
+        trace!("get storage version");
+        Ok(match self.db.get(STORAGE_VERSION_KEY)? {
+            Some(value) => value.first().copied().context("Invalid storage version")?,
+            None => 0
+        })
+    }
+
+    // set the version of the storage format
+    pub fn set_storage_version(&mut self, version: u8) -> Result<()> {
+        trace!("set storage version {}", version);
+        self.db.insert(STORAGE_VERSION_KEY, &[version])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        config::XELIS_ASSET,
+        crypto::{Hashable, KeyPair}
+    };
+    use crate::{
+        entry::EntryData,
+        test_utils::{build_burn, open_storage, open_storage_on, open_temp_storage}
+    };
+    use super::*;
+
+    fn is_tampered(err: anyhow::Error) -> bool {
+        matches!(err.downcast_ref::<WalletError>(), Some(WalletError::TamperedEntry(_, _)))
+    }
+
+    // Re-encrypt all entries without associated data like wallets created before the storage version 1
+    fn downgrade_to_legacy(storage: &mut EncryptedStorage) {
+        for name in storage.inner.db.tree_names() {
+            if name == DEFAULT_TREE_NAME {
+                continue;
+            }
+
+            let tree = storage.inner.db.open_tree(&name).unwrap();
+            let entries = tree.iter().collect::<Result<Vec<_>, _>>().unwrap();
+            for (key, value) in entries {
+                let raw_value = storage.cipher.decrypt_value(&value, &name).unwrap();
+                let legacy_key = match storage.cipher.decrypt_value(&key, &name) {
+                    Ok(raw_key) => {
+                        let mut nonce = [0u8; Cipher::NONCE_SIZE];
+                        nonce.copy_from_slice(&storage.cipher.hash_key(&raw_key)[0..Cipher::NONCE_SIZE]);
+                        storage.cipher.encrypt_value_with_nonce(&raw_key, &nonce, &[]).unwrap()
+                    },
+                    Err(_) => key.to_vec()
+                };
+                tree.remove(&key).unwrap();
+                tree.insert(legacy_key, storage.cipher.encrypt_value(&raw_value, &[]).unwrap()).unwrap();
+            }
+        }
+        storage.inner.set_storage_version(0).unwrap();
+    }
+
+    #[test]
+    fn test_tampered_entry_is_detected() {
+        let (_dir, mut storage) = open_temp_storage("wallet-tampered");
+        let hash = Hash::zero();
+        let entry = TransactionEntry::new(hash.clone(), 10, EntryData::Coinbase { reward: 10 }, None);
+        storage.save_transaction(&hash, &entry).unwrap();
+        storage.set_nonce(3).unwrap();
+
+        // Flip a bit of the stored transaction
+        let (key, value) = storage.transactions.iter().next().unwrap().unwrap();
+        let mut tampered = value.to_vec();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        storage.transactions.insert(&key, tampered).unwrap();
+        assert!(is_tampered(storage.get_transaction(&hash).unwrap_err()));
+        assert!(is_tampered(storage.get_transactions().unwrap_err()));
+
+        // Restore it
+        storage.transactions.insert(&key, value).unwrap();
+        assert_eq!(storage.get_transaction(&hash).unwrap().get_topoheight(), 10);
+
+        // Move the nonce from the account extra tree to the wallet one
+        let nonce_key = storage.cipher.hash_key(NONCE_KEY);
+        let nonce = storage.extra.get(nonce_key).unwrap().unwrap();
+        storage.wallet_extra.insert(nonce_key, nonce).unwrap();
+        let err = storage.load_from_disk::<u64>(&storage.wallet_extra, NONCE_KEY).unwrap_err();
+        assert!(is_tampered(err));
+        assert_eq!(storage.get_nonce().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_network_and_genesis_hash_recorded() {
+        let (dir, mut storage) = open_temp_storage("wallet-network");
+        assert_eq!(storage.get_network().unwrap(), Network::Dev);
+        assert!(storage.get_genesis_hash().unwrap().is_none());
+        storage.set_genesis_hash(&Hash::zero()).unwrap();
+        drop(storage);
+
+        // Can't be opened on another network
+        assert!(open_storage_on(&dir, Network::Testnet).is_err());
+
+        let mut storage = open_storage(&dir);
+        assert_eq!(storage.get_genesis_hash().unwrap(), Some(Hash::zero()));
+        assert!(storage.get_daemon_address().unwrap().is_none());
+        storage.set_daemon_address(&"127.0.0.1:8080".to_owned()).unwrap();
+        assert_eq!(storage.get_daemon_address().unwrap(), Some("127.0.0.1:8080".to_owned()));
+
+        // Wallets created before the network was recorded are on mainnet
+        let network_key = storage.cipher.hash_key(NETWORK);
+        storage.wallet_extra.remove(network_key).unwrap();
+        drop(storage);
+
+        assert!(open_storage_on(&dir, Network::Dev).is_err());
+        let storage = open_storage_on(&dir, Network::Mainnet).unwrap();
+        assert_eq!(storage.get_network().unwrap(), Network::Mainnet);
+    }
+
+    #[test]
+    fn test_migrate_legacy_entries() {
+        let (dir, mut storage) = open_temp_storage("wallet-migration");
+        let hash = Hash::zero();
+        let entry = TransactionEntry::new(hash.clone(), 10, EntryData::Coinbase { reward: 10 }, None);
+        storage.save_transaction(&hash, &entry).unwrap();
+        storage.set_nonce(3).unwrap();
+        storage.create_account("work").unwrap();
+        let key = DataValue::String("key".to_owned());
+        let value = DataElement::Value(DataValue::U64(42));
+        storage.set_custom_data("custom", &key, &value).unwrap();
+
+        downgrade_to_legacy(&mut storage);
+        // Legacy entries can't be read anymore
+        assert!(storage.get_transaction(&hash).is_err());
+        assert!(storage.get_accounts().is_err());
+        drop(storage);
+
+        // Migrated when opened
+        let storage = open_storage(&dir);
+        assert_eq!(storage.inner.get_storage_version().unwrap(), STORAGE_VERSION);
+        assert_eq!(storage.get_transaction(&hash).unwrap().get_topoheight(), 10);
+        assert_eq!(storage.get_nonce().unwrap(), 3);
+        assert_eq!(storage.get_account_index("work").unwrap(), Some(1));
+        assert_eq!(storage.get_accounts().unwrap().len(), 2);
+        assert_eq!(storage.get_custom_data("custom", &key).unwrap(), value);
+        assert_eq!(storage.get_custom_tree_keys(&"custom".to_owned(), &None).unwrap(), vec![key]);
+
+        // A migration interrupted is resumed, migrated entries are kept
+        let mut storage = storage;
+        storage.inner.set_storage_version(0).unwrap();
+        drop(storage);
+        let storage = open_storage(&dir);
+        assert_eq!(storage.get_nonce().unwrap(), 3);
+        assert_eq!(storage.get_accounts().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_balance_deltas() {
+        use xelis_common::crypto::KeyPair;
+
+        let (_dir, mut storage) = open_temp_storage("wallet-balance-deltas");
+        let keypair = KeyPair::new();
+        let public_key = keypair.get_public_key();
+        let asset = Hash::zero();
+        assert_eq!(storage.get_expected_balance_for(&asset).await, None);
+
+        let ciphertext = public_key.encrypt(1000u64);
+        storage.set_balance_for(&asset, Balance::new(1000, CiphertextCache::Decompressed(ciphertext.clone()))).await.unwrap();
+
+        // Incoming transfer of 500, then outgoing transfer of 200 with 10 of fees
+        storage.add_balance_delta(&asset, 500);
+        storage.add_balance_delta(&asset, -210);
+        let expected = storage.get_expected_balance_for(&asset).await.unwrap();
+        assert_eq!(expected, 1290);
+
+        // Verified against the new ciphertext without decrypting it
+        let updated = ciphertext + public_key.encrypt(500u64) - public_key.encrypt(210u64);
+        assert!(keypair.verify_plaintext(&updated, expected));
+
+        // Shown as an approximation until it is stored
+        storage.set_pending_balance_for(&asset, expected);
+        assert_eq!(storage.get_display_balance_for(&asset).await.unwrap(), (1290, true));
+        storage.set_balance_for(&asset, Balance::new(expected, CiphertextCache::Decompressed(updated))).await.unwrap();
+        assert_eq!(storage.get_display_balance_for(&asset).await.unwrap(), (1290, false));
+        assert_eq!(storage.get_expected_balance_for(&asset).await, None);
+
+        // A reorg deletes the transactions the changes came from
+        storage.add_balance_delta(&asset, 100);
+        storage.delete_transactions_above_topoheight(10).unwrap();
+        assert_eq!(storage.get_expected_balance_for(&asset).await, None);
+
+        // Changes that can't be applied are not used
+        storage.add_balance_delta(&asset, -2000);
+        assert_eq!(storage.get_expected_balance_for(&asset).await, None);
+    }
+
+    #[cfg(feature = "api_server")]
+    #[test]
+    fn test_app_permissions_shared_by_accounts() {
+        use crate::api::{ApplicationInfo, MethodClass, Permission};
+
+        let (dir, mut storage) = open_temp_storage("wallet-app-permissions");
+        assert!(storage.get_app_permissions("explorer").unwrap().is_none());
+
+        let app = ApplicationInfo::new("explorer".to_owned(), "Explorer".to_owned(), "0123456789abcdef0123456789abcdef").unwrap();
+        let mut permissions = AppPermissions::new(&app);
+        permissions.set(MethodClass::ReadBalance, Permission::AcceptAlways);
+        storage.set_app_permissions("explorer", &permissions).unwrap();
+
+        let index = storage.create_account("work").unwrap();
+        let account = storage.open_account(index).unwrap();
+        let saved = account.get_app_permissions("explorer").unwrap().unwrap();
+        assert!(matches!(saved.get(MethodClass::ReadBalance), Permission::AcceptAlways));
+        assert!(saved.verify_secret(&app).is_ok());
+        drop(account);
+        drop(storage);
+
+        // Kept after a restart until revoked
+        let mut storage = open_storage(&dir);
+        let apps = storage.get_all_app_permissions().unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].0, "explorer");
+        assert_eq!(apps[0].1.get_name(), "Explorer");
+
+        storage.delete_app_permissions("explorer").unwrap();
+        assert!(storage.get_app_permissions("explorer").unwrap().is_none());
+        assert!(storage.get_all_app_permissions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pending_transactions() {
+        let (dir, mut storage) = open_temp_storage("wallet-pending-transactions");
+        let keypair = KeyPair::new();
+        let pending = |nonce: u64, last_broadcast: TimestampSeconds| {
+            let transaction = build_burn(&keypair, nonce, 1000);
+            let hash = transaction.hash();
+            (hash, PendingTransaction {
+                transaction,
+                amounts: IndexMap::from([(XELIS_ASSET, 1001)]),
+                created_at: 100,
+                last_broadcast,
+                state: PendingState::Pending
+            })
+        };
+
+        // Saved out of order
+        let transactions = [pending(7, 100), pending(5, 100), pending(6, 200)];
+        for (hash, transaction) in transactions.iter() {
+            storage.add_pending_transaction(hash, transaction).unwrap();
+        }
+        drop(storage);
+
+        // Kept after a restart and ordered by nonce
+        let mut storage = open_storage(&dir);
+        let saved = storage.get_pending_transactions().unwrap();
+        assert_eq!(saved.iter().map(|(_, pending)| pending.transaction.get_nonce()).collect::<Vec<_>>(), vec![5, 6, 7]);
+        let (hash, first) = &saved[0];
+        assert_eq!(*hash, transactions[1].0);
+        assert_eq!(first.transaction.hash(), transactions[1].0);
+        assert_eq!(first.amounts, transactions[1].1.amounts);
+        assert_eq!((first.created_at, first.last_broadcast, first.state), (100, 100, PendingState::Pending));
+
+        // Not broadcasted again since the timeout
+        let stale = storage.mark_stale_pending_transactions(250, 100).unwrap();
+        assert_eq!(stale, vec![transactions[1].0.clone(), transactions[0].0.clone()]);
+        assert_eq!(storage.get_pending_transaction(&transactions[0].0).unwrap().state, PendingState::Stale);
+        assert_eq!(storage.get_pending_transaction(&transactions[2].0).unwrap().state, PendingState::Pending);
+        // Already stale, not reported again
+        assert!(storage.mark_stale_pending_transactions(250, 100).unwrap().is_empty());
+
+        // Nonces 5 and 6 were used by other transactions executed, they are replaced
+        let replaced = storage.delete_pending_transactions_below_nonce(7).unwrap();
+        assert_eq!(replaced, vec![transactions[1].0.clone(), transactions[2].0.clone()]);
+        assert!(!storage.has_pending_transaction(&transactions[1].0).unwrap());
+        let left = storage.get_pending_transactions().unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].0, transactions[0].0);
+    }
+}
@@ -0,0 +1,58 @@
+// Helpers shared by the tests using a wallet storage
+
+use xelis_common::{
+    account::CiphertextCache,
+    config::{COIN_VALUE, XELIS_ASSET},
+    crypto::{Hash, KeyPair},
+    network::Network,
+    testing::TempDir,
+    transaction::{
+        builder::{FeeBuilder, TransactionBuilder, TransactionTypeBuilder},
+        BurnPayload,
+        Reference,
+        Transaction
+    }
+};
+use crate::{
+    config::{KEY_SIZE, SALT_SIZE},
+    storage::{Balance, EncryptedStorage, Storage},
+    transaction_builder::TransactionBuilderState
+};
+
+// Encrypted storage of the dev network opened in a new temporary directory
+// The directory must be kept until the end of the test, it is deleted once dropped
+// Declared first, it is dropped after the storage: `let (_dir, storage) = ...`
+pub fn open_temp_storage(name: &str) -> (TempDir, EncryptedStorage) {
+    let dir = TempDir::new(name);
+    let storage = open_storage(&dir);
+    (dir, storage)
+}
+
+// Open the storage of a directory already used by the test, always with the same key
+pub fn open_storage(dir: &TempDir) -> EncryptedStorage {
+    open_storage_on(dir, Network::Dev).unwrap()
+}
+
+pub fn open_storage_on(dir: &TempDir, network: Network) -> anyhow::Result<EncryptedStorage> {
+    let inner = Storage::new(dir.to_string_lossy().into_owned())?;
+    EncryptedStorage::new(inner, &[1u8; KEY_SIZE], [2u8; SALT_SIZE], network)
+}
+
+// Transaction of the account burning the smallest amount of XELIS with the nonce and fee given
+// It is built from a balance of one coin and is never verified
+pub fn build_burn(keypair: &KeyPair, nonce: u64, fee: u64) -> Transaction {
+    let reference = Reference {
+        hash: Hash::zero(),
+        topoheight: 0
+    };
+    let mut state = TransactionBuilderState::new(false, reference, nonce);
+    let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(COIN_VALUE));
+    state.add_balance(XELIS_ASSET, Balance::new(COIN_VALUE, ciphertext));
+
+    let transaction_type = TransactionTypeBuilder::Burn(BurnPayload {
+        asset: XELIS_ASSET,
+        amount: 1
+    });
+    let builder = TransactionBuilder::new(0, keypair.get_public_key().compress(), transaction_type, FeeBuilder::Value(fee));
+    builder.build(&mut state, keypair).unwrap()
+}
@@ -1,7 +1,13 @@
 use std::collections::{HashMap, HashSet};
+use async_trait::async_trait;
+use indexmap::IndexMap;
+use log::{info, trace, warn};
+use tokio::sync::{Mutex, MutexGuard, RwLock};
 use xelis_common::{
     account::CiphertextCache,
+    api::daemon::{InvalidNonceErrorData, INVALID_NONCE_ERROR_CODE},
     crypto::{elgamal::Ciphertext, Hash, PublicKey},
+    json_rpc::JsonRPCError,
     transaction::{builder::{AccountState, FeeHelper}, Reference}
 };
 use crate::{error::WalletError, storage::{Balance, EncryptedStorage, TxCache}};
@@ -79,6 +85,12 @@ impl TransactionBuilderState {
         self.inner.registered_keys.insert(key);
     }
 
+    // Get the balance amount of an asset
+    // Once the TX is built, this is the balance after the TX is applied
+    pub fn get_balance_amount(&self, asset: &Hash) -> Option<u64> {
+        self.balances.get(asset).map(|b| b.amount)
+    }
+
     // This must be called once the TX has been built
     pub fn set_tx_hash_built(&mut self, tx_hash: Hash) {
         self.tx_hash_built = Some(tx_hash);
@@ -100,6 +112,167 @@ impl TransactionBuilderState {
     }
 }
 
+// A reservation of the next nonce to use for a transaction
+// Only one reservation can be alive at a time, others wait until it is committed or released
+// Dropping it without committing releases the nonce and leave the storage untouched
+pub struct NonceReservation<'a> {
+    storage: &'a RwLock<EncryptedStorage>,
+    nonce: u64,
+    _guard: MutexGuard<'a, ()>
+}
+
+impl<'a> NonceReservation<'a> {
+    pub async fn new(storage: &'a RwLock<EncryptedStorage>, lock: &'a Mutex<()>) -> NonceReservation<'a> {
+        let guard = lock.lock().await;
+        let nonce = storage.read().await.get_unconfirmed_nonce();
+        trace!("reserved nonce {}", nonce);
+
+        Self {
+            storage,
+            nonce,
+            _guard: guard
+        }
+    }
+
+    // Nonce that will be used by the transaction
+    pub fn get_nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    // Use another nonce for the transaction to build
+    // Unconfirmed balances and TX cache are deleted so the transaction is rebuilt from the last synced state
+    pub async fn reset_nonce(&mut self, nonce: u64) -> Result<(), WalletError> {
+        trace!("reset reserved nonce {} to {}", self.nonce, nonce);
+        let mut storage = self.storage.write().await;
+        storage.set_nonce(nonce)?;
+        storage.delete_unconfirmed_balances().await?;
+        self.nonce = nonce;
+
+        Ok(())
+    }
+
+    // Apply the changes of the transaction built using this reservation
+    // The wallet state must not have changed since the reservation was made
+    pub async fn commit(self, state: &mut TransactionBuilderState) -> Result<(), WalletError> {
+        trace!("commit nonce {}", self.nonce);
+        let mut storage = self.storage.write().await;
+        if storage.get_unconfirmed_nonce() != self.nonce {
+            return Err(WalletError::TxStateOutdated);
+        }
+
+        state.apply_changes(&mut storage).await
+    }
+}
+
+// Verify that the amounts sent by a new transaction, fees included, are not used by our pending transactions
+// Otherwise the new transaction or a pending one would fail once executed
+pub async fn verify_available_balances(storage: &EncryptedStorage, amounts: &IndexMap<Hash, u64>) -> Result<(), WalletError> {
+    for (asset, amount) in amounts {
+        let balance = storage.get_available_balance_for(asset).await?;
+        if balance.pending_outgoing > 0 && *amount > balance.get_available() {
+            let decimals = storage.get_asset_decimals(asset).unwrap_or(0);
+            return Err(WalletError::AvailableBalanceExceeded(balance.get_available(), *amount, decimals, asset.clone()))
+        }
+    }
+
+    Ok(())
+}
+
+// Retrieve the nonce expected by the daemon if it rejected a transaction because of its nonce
+pub fn get_daemon_expected_nonce(err: &WalletError) -> Option<u64> {
+    let err = match err {
+        WalletError::Any(e) => e,
+        _ => return None
+    };
+
+    err.chain().find_map(|cause| match cause.downcast_ref::<JsonRPCError>() {
+        Some(JsonRPCError::ServerError { code, data: Some(data), .. }) if *code == INVALID_NONCE_ERROR_CODE => {
+            serde_json::from_str::<InvalidNonceErrorData>(data).ok().map(|data| data.expected_nonce)
+        },
+        _ => None
+    })
+}
+
+// Steps needed to submit a transaction and rebuild it with another nonce
+#[async_trait]
+pub trait NonceRecovery {
+    type Transaction: Send + Sync;
+
+    // Submit the transaction to the daemon
+    async fn submit(&mut self, transaction: &Self::Transaction) -> Result<(), WalletError>;
+
+    // Fetch the nonce of our account from the daemon
+    async fn fetch_nonce(&mut self) -> Result<u64, WalletError>;
+
+    // Build and sign again the transaction using the nonce provided
+    // Balances must be verified again
+    async fn rebuild(&mut self, nonce: u64) -> Result<Self::Transaction, WalletError>;
+}
+
+// Submit the transaction, and if the daemon rejects it because of its nonce,
+// rebuild it with the nonce expected and retry only once
+// Returns the transaction accepted by the daemon
+pub async fn submit_with_nonce_recovery<R: NonceRecovery + Send>(recovery: &mut R, transaction: R::Transaction) -> Result<R::Transaction, WalletError> {
+    let err = match recovery.submit(&transaction).await {
+        Ok(()) => return Ok(transaction),
+        Err(e) => e
+    };
+
+    let expected_nonce = match get_daemon_expected_nonce(&err) {
+        Some(nonce) => nonce,
+        None => return Err(err)
+    };
+
+    // Pending transactions in mempool may not be included in the account nonce
+    let nonce = recovery.fetch_nonce().await?.max(expected_nonce);
+    warn!("Transaction rejected by the daemon: {}, rebuilding it with nonce {}", err, nonce);
+
+    let transaction = recovery.rebuild(nonce).await?;
+    recovery.submit(&transaction).await?;
+    info!("Transaction rebuilt with nonce {} has been accepted", nonce);
+
+    Ok(transaction)
+}
+
+// Nonce tracked by the wallet compared to the account state known by the daemon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonceAudit {
+    // Nonce stored by the wallet
+    pub local_nonce: u64,
+    // Nonce that will be used by the next transaction built
+    pub unconfirmed_nonce: u64,
+    // Nonce of the account on chain
+    pub daemon_nonce: u64,
+    // Transactions of the account waiting in mempool
+    pub pending: u64
+}
+
+impl NonceAudit {
+    // Nonce the daemon expects for the next transaction
+    pub fn expected_nonce(&self) -> u64 {
+        self.daemon_nonce + self.pending
+    }
+
+    // Describe every difference with the daemon state
+    // Stored nonce may be ahead of the daemon nonce while our transactions are in mempool
+    pub fn get_discrepancies(&self) -> Vec<String> {
+        let mut discrepancies = Vec::new();
+        if self.local_nonce < self.daemon_nonce || self.local_nonce > self.expected_nonce() {
+            discrepancies.push(format!("Stored nonce {} is not between the daemon nonce {} and the next nonce expected {}", self.local_nonce, self.daemon_nonce, self.expected_nonce()));
+        }
+
+        if self.unconfirmed_nonce != self.expected_nonce() {
+            discrepancies.push(format!("Next transaction would use nonce {} but the daemon expects {} ({} pending in mempool)", self.unconfirmed_nonce, self.expected_nonce(), self.pending));
+        }
+
+        discrepancies
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.get_discrepancies().is_empty()
+    }
+}
+
 impl FeeHelper for TransactionBuilderState {
     type Error = WalletError;
 
@@ -147,4 +320,263 @@ impl AsMut<EstimateFeesState> for TransactionBuilderState {
     fn as_mut(&mut self) -> &mut EstimateFeesState {
         &mut self.inner
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use anyhow::Context;
+    use crate::test_utils::open_temp_storage;
+    use super::*;
+
+    // Daemon accepting only the transactions using the next nonce of the account
+    // Transactions are represented by their nonce
+    struct MockDaemon {
+        // Nonce stored for the account
+        nonce: u64,
+        // Pending transactions of the account in mempool
+        pending: u64,
+        // Reject every transaction
+        reject_all: bool,
+        submitted: Vec<u64>,
+        rebuilt: Vec<u64>
+    }
+
+    impl MockDaemon {
+        fn new(nonce: u64, pending: u64) -> Self {
+            Self {
+                nonce,
+                pending,
+                reject_all: false,
+                submitted: Vec::new(),
+                rebuilt: Vec::new()
+            }
+        }
+    }
+
+    // Error as returned by the daemon API
+    fn invalid_nonce_error(nonce: u64, expected_nonce: u64) -> WalletError {
+        let data = InvalidNonceErrorData {
+            nonce,
+            expected_nonce
+        };
+        let err = JsonRPCError::ServerError {
+            code: INVALID_NONCE_ERROR_CODE,
+            message: format!("Invalid nonce: expected {}, got {}", expected_nonce, nonce),
+            data: Some(serde_json::to_string_pretty(&data).unwrap())
+        };
+        let result: Result<(), JsonRPCError> = Err(err);
+        WalletError::Any(result.context("Error while submitting transaction").unwrap_err())
+    }
+
+    #[async_trait]
+    impl NonceRecovery for MockDaemon {
+        type Transaction = u64;
+
+        async fn submit(&mut self, nonce: &u64) -> Result<(), WalletError> {
+            self.submitted.push(*nonce);
+            let expected_nonce = self.nonce + self.pending;
+            if self.reject_all || *nonce != expected_nonce {
+                return Err(invalid_nonce_error(*nonce, expected_nonce))
+            }
+
+            self.pending += 1;
+            Ok(())
+        }
+
+        async fn fetch_nonce(&mut self) -> Result<u64, WalletError> {
+            Ok(self.nonce)
+        }
+
+        async fn rebuild(&mut self, nonce: u64) -> Result<u64, WalletError> {
+            self.rebuilt.push(nonce);
+            Ok(nonce)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nonce_recovery_after_rejection() {
+        // Two transactions from this account are in mempool
+        let mut daemon = MockDaemon::new(5, 2);
+        let nonce = submit_with_nonce_recovery(&mut daemon, 5).await.unwrap();
+
+        // Expected nonce from the error is used over the nonce stored
+        assert_eq!(nonce, 7);
+        assert_eq!(daemon.submitted, vec![5, 7]);
+        assert_eq!(daemon.rebuilt, vec![7]);
+    }
+
+    #[tokio::test]
+    async fn test_nonce_recovery_not_needed() {
+        let mut daemon = MockDaemon::new(5, 0);
+        assert_eq!(submit_with_nonce_recovery(&mut daemon, 5).await.unwrap(), 5);
+        assert_eq!(daemon.submitted, vec![5]);
+        assert!(daemon.rebuilt.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_nonce_recovery_retry_only_once() {
+        let mut daemon = MockDaemon::new(5, 0);
+        daemon.reject_all = true;
+
+        let err = submit_with_nonce_recovery(&mut daemon, 3).await.unwrap_err();
+        assert_eq!(get_daemon_expected_nonce(&err), Some(5));
+        assert_eq!(daemon.submitted, vec![3, 5]);
+        assert_eq!(daemon.rebuilt, vec![5]);
+    }
+
+    #[test]
+    fn test_nonce_audit() {
+        let mut audit = NonceAudit {
+            local_nonce: 5,
+            unconfirmed_nonce: 7,
+            daemon_nonce: 5,
+            pending: 2
+        };
+        assert_eq!(audit.expected_nonce(), 7);
+        assert!(audit.is_consistent());
+
+        // Transactions got dropped from mempool
+        audit.pending = 0;
+        assert_eq!(audit.get_discrepancies().len(), 1);
+
+        // Wallet is behind the chain
+        audit.local_nonce = 3;
+        audit.unconfirmed_nonce = 3;
+        assert_eq!(audit.get_discrepancies().len(), 2);
+
+        // Local nonce reset to the expected one while our transactions are still pending
+        let audit = NonceAudit {
+            local_nonce: 7,
+            unconfirmed_nonce: 7,
+            daemon_nonce: 5,
+            pending: 2
+        };
+        assert!(audit.is_consistent());
+    }
+
+    #[test]
+    fn test_daemon_expected_nonce() {
+        assert_eq!(get_daemon_expected_nonce(&invalid_nonce_error(1, 4)), Some(4));
+
+        // Other errors are not recovered
+        let err = JsonRPCError::ServerError {
+            code: 1002,
+            message: "Account not found".to_owned(),
+            data: None
+        };
+        assert_eq!(get_daemon_expected_nonce(&WalletError::Any(err.into())), None);
+        assert_eq!(get_daemon_expected_nonce(&WalletError::NotOnlineMode), None);
+    }
+
+    #[tokio::test]
+    async fn test_reset_reserved_nonce() {
+        let (_dir, mut storage) = open_temp_storage("wallet-reset-nonce");
+        storage.set_nonce(5).unwrap();
+        storage.set_tx_cache(TxCache {
+            reference: Reference {
+                hash: Hash::zero(),
+                topoheight: 0
+            },
+            nonce: 6,
+            last_tx_hash_created: Hash::zero()
+        });
+
+        let storage = RwLock::new(storage);
+        let lock = Mutex::new(());
+        let mut reservation = NonceReservation::new(&storage, &lock).await;
+        assert_eq!(reservation.get_nonce(), 6);
+
+        reservation.reset_nonce(8).await.unwrap();
+        assert_eq!(reservation.get_nonce(), 8);
+        assert!(storage.read().await.get_tx_cache().is_none());
+        assert_eq!(storage.read().await.get_unconfirmed_nonce(), 8);
+
+        // Transaction rebuilt with the new nonce can be committed
+        let mut state = TransactionBuilderState::new(false, Reference { hash: Hash::zero(), topoheight: 0 }, 8);
+        state.update_nonce(9).unwrap();
+        state.set_tx_hash_built(Hash::zero());
+        reservation.commit(&mut state).await.unwrap();
+        assert_eq!(storage.read().await.get_unconfirmed_nonce(), 9);
+    }
+
+    #[tokio::test]
+    async fn test_aborted_transaction_keep_nonce() {
+        let (_dir, mut storage) = open_temp_storage("wallet-builder-state");
+        storage.set_nonce(5).unwrap();
+
+        let reference = Reference {
+            hash: Hash::zero(),
+            topoheight: 0
+        };
+
+        // Transaction is built but never confirmed
+        let mut state = TransactionBuilderState::new(false, reference.clone(), storage.get_unconfirmed_nonce());
+        state.update_nonce(6).unwrap();
+        state.set_tx_hash_built(Hash::zero());
+        drop(state);
+
+        assert_eq!(storage.get_unconfirmed_nonce(), 5);
+        assert_eq!(storage.get_nonce().unwrap(), 5);
+        assert!(storage.get_tx_cache().is_none());
+
+        // Transaction is confirmed, unconfirmed nonce is updated
+        let mut state = TransactionBuilderState::new(false, reference, storage.get_unconfirmed_nonce());
+        state.update_nonce(6).unwrap();
+        state.set_tx_hash_built(Hash::zero());
+        state.apply_changes(&mut storage).await.unwrap();
+
+        assert_eq!(storage.get_unconfirmed_nonce(), 6);
+        assert_eq!(storage.get_nonce().unwrap(), 5);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_nonce_reservations() {
+        let (_dir, mut storage) = open_temp_storage("wallet-nonce-reservation");
+        storage.set_nonce(5).unwrap();
+
+        let storage = Arc::new(RwLock::new(storage));
+        let lock = Arc::new(Mutex::new(()));
+
+        // Half of the transactions are committed, the others are cancelled
+        let mut handles = Vec::new();
+        for i in 0..50u64 {
+            let storage = storage.clone();
+            let lock = lock.clone();
+            handles.push(tokio::spawn(async move {
+                let reservation = NonceReservation::new(&storage, &lock).await;
+                let nonce = reservation.get_nonce();
+                let reference = Reference {
+                    hash: Hash::zero(),
+                    topoheight: 0
+                };
+
+                let mut state = TransactionBuilderState::new(false, reference, nonce);
+                state.update_nonce(nonce + 1).unwrap();
+                state.set_tx_hash_built(Hash::zero());
+                tokio::task::yield_now().await;
+
+                if i % 2 == 0 {
+                    reservation.commit(&mut state).await.unwrap();
+                    Some(nonce)
+                } else {
+                    None
+                }
+            }));
+        }
+
+        let mut committed = HashSet::new();
+        for handle in handles {
+            if let Some(nonce) = handle.await.unwrap() {
+                // Each committed transaction must have its own nonce
+                assert!(committed.insert(nonce));
+            }
+        }
+
+        let storage = storage.read().await;
+        assert_eq!(committed.len(), 25);
+        assert_eq!(committed, (5..30).collect::<HashSet<_>>());
+        assert_eq!(storage.get_unconfirmed_nonce(), 30);
+        assert_eq!(storage.get_nonce().unwrap(), 5);
+    }
+}
@@ -1,11 +1,16 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::{create_dir_all, File},
     io::{Read, Write},
     path::Path,
-    sync::Arc
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc
+    }
 };
 use anyhow::{Error, Context};
+use async_trait::async_trait;
+use indexmap::IndexMap;
 use serde::Serialize;
 use tokio::sync::{
     broadcast::{
@@ -24,22 +29,29 @@ use xelis_common::{
         },
         DataElement
     },
-    asset::AssetWithData,
+    asset::{AssetData, AssetWithData},
+    config::{ASSET_CREATION_BURN_AMOUNT, XELIS_ASSET},
     crypto::{
         ecdlp::{self, ECDLPTablesFileView},
         elgamal::{Ciphertext, DecryptHandle, PublicKey as DecompressedPublicKey},
+        memo,
         Address,
+        Hash,
         Hashable,
         KeyPair,
+        PrivateKey,
         PublicKey,
         Signature
     },
+    json_rpc::JsonRPCError,
     network::Network,
+    time::get_current_time_in_seconds,
     transaction::{
         builder::{
             FeeBuilder,
             TransactionBuilder,
-            TransactionTypeBuilder
+            TransactionTypeBuilder,
+            TransferBuilder
         },
         extra_data::UnknownExtraDataFormat,
         Reference,
@@ -47,15 +59,20 @@ use xelis_common::{
         Transaction
     }
 };
+use zeroize::Zeroizing;
 use crate::{
+    account::derive_account_keypair,
     cipher::Cipher,
     config::{
+        CANCEL_FEE_MULTIPLIER,
         PASSWORD_ALGORITHM,
         PASSWORD_HASH_SIZE,
         SALT_SIZE
     },
     daemon_api::DaemonAPI,
+    directory::WalletLock,
     error::WalletError,
+    keyfile::KeyFile,
     mnemonics,
     network_handler::{
         NetworkHandler,
@@ -63,10 +80,17 @@ use crate::{
     },
     storage::{
         EncryptedStorage,
+        PendingState,
+        PendingTransaction,
         Storage
     },
     transaction_builder::{
+        submit_with_nonce_recovery,
+        verify_available_balances,
         EstimateFeesState,
+        NonceAudit,
+        NonceRecovery,
+        NonceReservation,
         TransactionBuilderState
     }
 };
@@ -76,13 +100,13 @@ use log::{
     trace,
     debug,
     error,
-    info
+    info,
+    warn
 };
 
 #[cfg(feature = "api_server")]
 use {
     serde_json::{json, Value},
-    async_trait::async_trait,
     crate::api::{
         XSWDNodeMethodHandler,
         register_rpc_methods,
@@ -93,7 +117,14 @@ use {
         AppStateShared,
         PermissionResult,
         PermissionRequest,
-        XSWDPermissionHandler
+        XSWDPermissionHandler,
+        ApplicationInfo,
+        AppPermissions,
+        AppPermissionError,
+        AppPermissionHandler,
+        MethodClass,
+        Permission,
+        verify_app_permission
     },
     xelis_common::rpc_server::{
         RPCHandler,
@@ -213,6 +244,8 @@ pub struct Wallet {
     public_key: PublicKey,
     // network handler for online mode to keep wallet synced
     network_handler: Mutex<Option<SharedNetworkHandler>>,
+    // Other accounts of this wallet synced in background while online, by index
+    accounts_sync: Mutex<HashMap<u32, Arc<Wallet>>>,
     // network on which we are connected
     network: Network,
     // RPC Server
@@ -221,15 +254,112 @@ pub struct Wallet {
     // All XSWD requests are routed through this channel
     #[cfg(feature = "api_server")]
     xswd_channel: RwLock<Option<UnboundedSender<XSWDEvent>>>,
+    // Applications must be approved by the user to call the RPC Server
+    #[cfg(feature = "api_server")]
+    app_permissions: AtomicBool,
+    // Only one application permission is asked to the user at a time
+    // so the user is not asked twice for the same decision
+    #[cfg(feature = "api_server")]
+    app_permissions_requests: Mutex<()>,
     // Event broadcaster
     event_broadcaster: Mutex<Option<BroadcastSender<Event>>>,
     // Precomputed tables byte array
-    precomputed_tables: PrecomputedTablesShared
+    precomputed_tables: PrecomputedTablesShared,
+    // Held by the nonce reservation while a transaction is being built and confirmed
+    // A rescan can't be started while it is locked
+    tx_building_lock: Mutex<()>,
+    // Daemon topoheight to reach for the current rescan, 0 if none
+    rescan_target: AtomicU64,
+    // Rebuild and resubmit once a transaction rejected by the daemon because of its nonce
+    auto_nonce_recovery: AtomicBool,
+    // Build transactions spending the balance used by our pending transactions
+    spend_pending_balance: AtomicBool,
+    // Prevent the wallet to be opened by another process, released on close
+    // Accounts synced in background don't hold it
+    lock: Mutex<Option<WalletLock>>
 }
 
-pub fn hash_password(password: String, salt: &[u8]) -> Result<[u8; PASSWORD_HASH_SIZE], WalletError> {
-    let mut output = [0; PASSWORD_HASH_SIZE];
-    PASSWORD_ALGORITHM.hash_password_into(password.as_bytes(), salt, &mut output).map_err(|e| WalletError::AlgorithmHashingError(e.to_string()))?;
+// Determine the topoheight from which the rescan must start
+// If the daemon has pruned its chain above the requested topoheight,
+// we can't retrieve the history below it and start from the pruned topoheight
+pub fn get_rescan_start_topoheight(topoheight: u64, synced_topoheight: u64, pruned_topoheight: Option<u64>) -> Result<u64, WalletError> {
+    if topoheight > synced_topoheight {
+        return Err(WalletError::RescanTopoheightTooHigh)
+    }
+
+    match pruned_topoheight {
+        Some(pruned_topoheight) if pruned_topoheight > topoheight => {
+            warn!("Daemon is pruned at topoheight {}, rescan will start from it instead of {}", pruned_topoheight, topoheight);
+            Ok(pruned_topoheight)
+        },
+        _ => Ok(topoheight)
+    }
+}
+
+// Plaintext amounts sent for each asset by a transaction, fees included
+pub fn get_outgoing_amounts(transaction_type: &TransactionTypeBuilder, fee: u64) -> IndexMap<Hash, u64> {
+    let mut amounts = IndexMap::new();
+    amounts.insert(XELIS_ASSET, fee);
+
+    let mut add = |asset: &Hash, amount: u64| {
+        let total = amounts.entry(asset.clone()).or_insert(0);
+        *total = total.saturating_add(amount);
+    };
+
+    match transaction_type {
+        TransactionTypeBuilder::Transfers(transfers) => {
+            for transfer in transfers {
+                add(&transfer.asset, transfer.amount);
+            }
+        },
+        TransactionTypeBuilder::Burn(payload) => add(&payload.asset, payload.amount),
+        TransactionTypeBuilder::AssetCreation(_) => add(&XELIS_ASSET, ASSET_CREATION_BURN_AMOUNT)
+    }
+
+    amounts
+}
+
+// Rebuild a transaction from its reservation when the daemon expects another nonce
+struct WalletNonceRecovery<'a, 'r> {
+    wallet: &'a Wallet,
+    reservation: &'a mut NonceReservation<'r>,
+    state: &'a mut TransactionBuilderState,
+    transaction_type: TransactionTypeBuilder,
+    fee: FeeBuilder,
+    extra_data: Option<Vec<u8>>,
+    encrypt_extra_data: bool
+}
+
+#[async_trait]
+impl<'a, 'r> NonceRecovery for WalletNonceRecovery<'a, 'r> {
+    type Transaction = Transaction;
+
+    async fn submit(&mut self, transaction: &Transaction) -> Result<(), WalletError> {
+        self.wallet.submit_transaction(transaction).await
+    }
+
+    async fn fetch_nonce(&mut self) -> Result<u64, WalletError> {
+        let network_handler = self.wallet.network_handler.lock().await;
+        let network_handler = network_handler.as_ref().ok_or(WalletError::NotOnlineMode)?;
+        let result = network_handler.get_api().get_nonce(&self.wallet.get_address()).await?;
+        Ok(result.version.get_nonce())
+    }
+
+    async fn rebuild(&mut self, nonce: u64) -> Result<Transaction, WalletError> {
+        self.reservation.reset_nonce(nonce).await?;
+        let storage = self.wallet.storage.read().await;
+        let (state, transaction) = self.wallet.create_transaction_with_nonce(&storage, nonce, self.transaction_type.clone(), self.fee.clone(), self.extra_data.clone(), self.encrypt_extra_data).await?;
+        *self.state = state;
+
+        Ok(transaction)
+    }
+}
+
+// Derive the key from the password
+// The derived key is wiped from memory once dropped
+pub fn hash_password(password: &str, salt: &[u8]) -> Result<Zeroizing<[u8; PASSWORD_HASH_SIZE]>, WalletError> {
+    let mut output = Zeroizing::new([0; PASSWORD_HASH_SIZE]);
+    PASSWORD_ALGORITHM.hash_password_into(password.as_bytes(), salt, &mut *output).map_err(|e| WalletError::AlgorithmHashingError(e.to_string()))?;
     Ok(output)
 }
 
@@ -263,19 +393,29 @@ impl Wallet {
     }
 
     // Create a new wallet with the specificed storage, keypair and its network
-    fn new(storage: EncryptedStorage, keypair: KeyPair, network: Network, precomputed_tables: PrecomputedTablesShared) -> Arc<Self> {
+    fn new(storage: EncryptedStorage, keypair: KeyPair, network: Network, precomputed_tables: PrecomputedTablesShared, lock: Option<WalletLock>) -> Arc<Self> {
         let zelf = Self {
             storage: RwLock::new(storage),
             public_key: keypair.get_public_key().compress(),
             keypair,
             network_handler: Mutex::new(None),
+            accounts_sync: Mutex::new(HashMap::new()),
             network,
             #[cfg(feature = "api_server")]
             api_server: Mutex::new(None),
             #[cfg(feature = "api_server")]
             xswd_channel: RwLock::new(None),
+            #[cfg(feature = "api_server")]
+            app_permissions: AtomicBool::new(false),
+            #[cfg(feature = "api_server")]
+            app_permissions_requests: Mutex::new(()),
             event_broadcaster: Mutex::new(None),
-            precomputed_tables
+            precomputed_tables,
+            tx_building_lock: Mutex::new(()),
+            rescan_target: AtomicU64::new(0),
+            auto_nonce_recovery: AtomicBool::new(true),
+            spend_pending_balance: AtomicBool::new(false),
+            lock: Mutex::new(lock)
         };
 
         Arc::new(zelf)
@@ -283,14 +423,11 @@ impl Wallet {
 
     // Create a new wallet on disk
     pub fn create(name: String, password: String, seed: Option<String>, network: Network, precomputed_tables: PrecomputedTablesShared) -> Result<Arc<Self>, Error> {
-        if name.is_empty() {
-            return Err(WalletError::EmptyName.into())
-        }
-
         // generate random keypair or recover it from seed
         let keypair = if let Some(seed) = seed {
         debug!("Retrieving keypair from seed...");
-        let words: Vec<String> = seed.split_whitespace().map(str::to_string).collect();
+        let seed = Zeroizing::new(seed);
+        let words: Zeroizing<Vec<String>> = Zeroizing::new(seed.split_whitespace().map(str::to_string).collect());
         let key = mnemonics::words_to_key(&words)?;
             KeyPair::from_private_key(key)
         } else {
@@ -298,39 +435,57 @@ impl Wallet {
             KeyPair::new()
         };
 
+        Self::create_with_keypair(name, password, keypair, network, precomputed_tables)
+    }
+
+    // Create a new wallet from an imported private key
+    pub fn create_from_private_key(name: String, password: String, private_key: PrivateKey, network: Network, precomputed_tables: PrecomputedTablesShared) -> Result<Arc<Self>, Error> {
+        debug!("Retrieving keypair from private key...");
+        Self::create_with_keypair(name, password, KeyPair::from_private_key(private_key), network, precomputed_tables)
+    }
+
+    fn create_with_keypair(name: String, password: String, keypair: KeyPair, network: Network, precomputed_tables: PrecomputedTablesShared) -> Result<Arc<Self>, Error> {
+        // password is wiped from memory on any exit path
+        let password = Zeroizing::new(password);
+        if name.is_empty() {
+            return Err(WalletError::EmptyName.into())
+        }
+
         // generate random salt for hashed password
         let mut salt: [u8; SALT_SIZE] = [0; SALT_SIZE];
         OsRng.fill_bytes(&mut salt);
 
         // generate hashed password which will be used as key to encrypt master_key
         debug!("hashing provided password");
-        let hashed_password = hash_password(password, &salt)?;
+        let hashed_password = hash_password(&password, &salt)?;
 
+        let lock = WalletLock::acquire(Path::new(&name))?;
         debug!("Creating storage for {}", name);
         let mut inner = Storage::new(name)?;
 
         // generate the Cipher
-        let cipher = Cipher::new(&hashed_password, None)?;
+        // Values of the public storage are authenticated by the password-based key, no associated data is needed
+        let cipher = Cipher::new(&*hashed_password, None)?;
 
         // save the salt used for password
         debug!("Save password salt in public storage");
         inner.set_password_salt(&salt)?;
 
         // generate the master key which is used for storage and then save it in encrypted form
-        let mut master_key: [u8; 32] = [0; 32];
-        OsRng.fill_bytes(&mut master_key);
-        let encrypted_master_key = cipher.encrypt_value(&master_key)?;
+        let mut master_key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(&mut *master_key);
+        let encrypted_master_key = cipher.encrypt_value(&*master_key, &[])?;
         debug!("Save encrypted master key in public storage");
         inner.set_encrypted_master_key(&encrypted_master_key)?;
         
         // generate the storage salt and save it in encrypted form
         let mut storage_salt = [0; SALT_SIZE];
         OsRng.fill_bytes(&mut storage_salt);
-        let encrypted_storage_salt = cipher.encrypt_value(&storage_salt)?;
+        let encrypted_storage_salt = cipher.encrypt_value(&storage_salt, &[])?;
         inner.set_encrypted_storage_salt(&encrypted_storage_salt)?;
 
         debug!("Creating encrypted storage");
-        let mut storage = EncryptedStorage::new(inner, &master_key, storage_salt, network)?;
+        let mut storage = EncryptedStorage::new(inner, &*master_key, storage_salt, network)?;
 
         // Store the private key
         storage.set_private_key(&keypair.get_private_key())?;
@@ -338,15 +493,18 @@ impl Wallet {
         // Flush the storage to be sure its written on disk
         storage.flush()?;
 
-        Ok(Self::new(storage, keypair, network, precomputed_tables))
+        Ok(Self::new(storage, keypair, network, precomputed_tables, Some(lock)))
     }
 
     // Open an existing wallet on disk
     pub fn open(name: String, password: String, network: Network, precomputed_tables: PrecomputedTablesShared) -> Result<Arc<Self>, Error> {
+        // password is wiped from memory on any exit path
+        let password = Zeroizing::new(password);
         if name.is_empty() {
             return Err(WalletError::EmptyName.into())
         }
 
+        let lock = WalletLock::acquire(Path::new(&name))?;
         debug!("Creating storage for {}", name);
         let storage = Storage::new(name)?;
         
@@ -358,15 +516,15 @@ impl Wallet {
         debug!("Retrieving encrypted master key from public storage");
         let encrypted_master_key = storage.get_encrypted_master_key()?;
 
-        let hashed_password = hash_password(password, &salt)?;
+        let hashed_password = hash_password(&password, &salt)?;
 
         // decrypt the encrypted master key using the hashed password (used as key)
-        let cipher = Cipher::new(&hashed_password, None)?;
-        let master_key = cipher.decrypt_value(&encrypted_master_key).context("Invalid password provided for this wallet")?;
+        let cipher = Cipher::new(&*hashed_password, None)?;
+        let master_key = Zeroizing::new(cipher.decrypt_value(&encrypted_master_key, &[]).context("Invalid password provided for this wallet")?);
 
         // Retrieve the encrypted storage salt
         let encrypted_storage_salt = storage.get_encrypted_storage_salt()?;
-        let storage_salt = cipher.decrypt_value(&encrypted_storage_salt).context("Invalid encrypted storage salt for this wallet")?;
+        let storage_salt = cipher.decrypt_value(&encrypted_storage_salt, &[]).context("Invalid encrypted storage salt for this wallet")?;
         if storage_salt.len() != SALT_SIZE {
             error!("Invalid size received after decrypting storage salt: {} bytes", storage_salt.len());
             return Err(WalletError::InvalidSaltSize.into());
@@ -376,12 +534,18 @@ impl Wallet {
         salt.copy_from_slice(&storage_salt);
 
         debug!("Creating encrypted storage");
-        let storage = EncryptedStorage::new(storage, &master_key, salt, network)?;
+        let mut storage = EncryptedStorage::new(storage, &master_key, salt, network)?;
         debug!("Retrieving private key from encrypted storage");
         let private_key =  storage.get_private_key()?;
-        let keypair = KeyPair::from_private_key(private_key);
 
-        Ok(Self::new(storage, keypair, network, precomputed_tables))
+        let account = storage.get_active_account()?;
+        if account != 0 {
+            debug!("Opening account {}", account);
+            storage = storage.open_account(account)?;
+        }
+        let keypair = derive_account_keypair(&private_key, account);
+
+        Ok(Self::new(storage, keypair, network, precomputed_tables, Some(lock)))
     }
 
     // Close the wallet
@@ -409,7 +573,8 @@ impl Wallet {
             }
         }
 
-        // Stop gracefully the network handler
+        // Stop gracefully the network handlers
+        self.stop_accounts_sync().await;
         {
             let mut lock = self.network_handler.lock().await;
             if let Some(handler) = lock.take() {
@@ -425,6 +590,9 @@ impl Wallet {
             storage.stop().await;
         }
 
+        // Allow the wallet to be opened again
+        self.lock.lock().await.take();
+
         // Close the event broadcaster
         // So all subscribers will be notified
         self.close_events_channel().await;
@@ -476,8 +644,9 @@ impl Wallet {
     }
 
     // Enable RPC Server with requested authentication and bind address
+    // If app permissions are enabled, the receiver of the permission requests is returned
     #[cfg(feature = "api_server")]
-    pub async fn enable_rpc_server(self: &Arc<Self>, bind_address: String, config: Option<AuthConfig>) -> Result<(), Error> {
+    pub async fn enable_rpc_server(self: &Arc<Self>, bind_address: String, config: Option<AuthConfig>, app_permissions: bool) -> Result<Option<UnboundedReceiver<XSWDEvent>>, Error> {
         let mut lock = self.api_server.lock().await;
         if lock.is_some() {
             return Err(WalletError::RPCServerAlreadyRunning.into())
@@ -487,7 +656,28 @@ impl Wallet {
 
         let rpc_server = WalletRpcServer::new(bind_address, rpc_handler, config).await?;
         *lock = Some(APIServer::RPCServer(rpc_server));
-        Ok(())
+
+        self.app_permissions.store(app_permissions, Ordering::SeqCst);
+        if !app_permissions {
+            return Ok(None)
+        }
+
+        let (sender, receiver) = unbounded_channel();
+        let mut channel = self.xswd_channel.write().await;
+        *channel = Some(sender);
+        Ok(Some(receiver))
+    }
+
+    // Are the applications required to be approved by the user on the RPC Server
+    #[cfg(feature = "api_server")]
+    pub fn is_app_permissions_enabled(&self) -> bool {
+        self.app_permissions.load(Ordering::SeqCst)
+    }
+
+    // Verify that the application is allowed to call this class of methods
+    #[cfg(feature = "api_server")]
+    pub async fn verify_app_permission(self: &Arc<Self>, app: &ApplicationInfo, class: MethodClass) -> Result<(), AppPermissionError> {
+        verify_app_permission(self, &self.app_permissions_requests, app, class).await
     }
 
     // Enable XSWD Protocol
@@ -516,6 +706,7 @@ impl Wallet {
         let mut lock = self.api_server.lock().await;
         let rpc_server = lock.take().ok_or(WalletError::RPCServerNotRunning)?;
         rpc_server.stop().await;
+        self.app_permissions.store(false, Ordering::SeqCst);
         Ok(())
     }
 
@@ -526,32 +717,34 @@ impl Wallet {
 
     // Verify if a password is valid or not
     pub async fn is_valid_password(&self, password: String) -> Result<(), Error> {
+        let password = Zeroizing::new(password);
         let mut encrypted_storage = self.storage.write().await;
         let storage = encrypted_storage.get_mutable_public_storage();
         let salt = storage.get_password_salt()?;
-        let hashed_password = hash_password(password, &salt)?;
-        let cipher = Cipher::new(&hashed_password, None)?;
+        let hashed_password = hash_password(&password, &salt)?;
+        let cipher = Cipher::new(&*hashed_password, None)?;
         let encrypted_master_key = storage.get_encrypted_master_key()?;
-        let _ = cipher.decrypt_value(&encrypted_master_key).context("Invalid password provided")?;
+        let _ = cipher.decrypt_value(&encrypted_master_key, &[]).context("Invalid password provided")?;
         Ok(())
     }
 
     // change the current password wallet to a new one
     pub async fn set_password(&self, old_password: String, password: String) -> Result<(), Error> {
+        let (old_password, password) = (Zeroizing::new(old_password), Zeroizing::new(password));
         let mut encrypted_storage = self.storage.write().await;
         let storage = encrypted_storage.get_mutable_public_storage();
         let (master_key, storage_salt) = {
             // retrieve old salt to build key from current password
             let salt = storage.get_password_salt()?;
-            let hashed_password = hash_password(old_password, &salt)?;
+            let hashed_password = hash_password(&old_password, &salt)?;
 
             let encrypted_master_key = storage.get_encrypted_master_key()?;
             let encrypted_storage_salt = storage.get_encrypted_storage_salt()?;
 
             // decrypt the encrypted master key using the provided password
-            let cipher = Cipher::new(&hashed_password, None)?;
-            let master_key = cipher.decrypt_value(&encrypted_master_key).context("Invalid password provided")?;
-            let storage_salt = cipher.decrypt_value(&encrypted_storage_salt)?;
+            let cipher = Cipher::new(&*hashed_password, None)?;
+            let master_key = Zeroizing::new(cipher.decrypt_value(&encrypted_master_key, &[]).context("Invalid password provided")?);
+            let storage_salt = cipher.decrypt_value(&encrypted_storage_salt, &[])?;
             (master_key, storage_salt)
         };
 
@@ -560,14 +753,14 @@ impl Wallet {
         OsRng.fill_bytes(&mut salt);
 
         // generate the password-based derivated key to encrypt the master key
-        let hashed_password = hash_password(password, &salt)?;
-        let cipher = Cipher::new(&hashed_password, None)?;
+        let hashed_password = hash_password(&password, &salt)?;
+        let cipher = Cipher::new(&*hashed_password, None)?;
 
         // encrypt the master key using the new password
-        let encrypted_key = cipher.encrypt_value(&master_key)?;
+        let encrypted_key = cipher.encrypt_value(&master_key, &[])?;
 
         // encrypt the salt with the new password
-        let encrypted_storage_salt = cipher.encrypt_value(&storage_salt)?;
+        let encrypted_storage_salt = cipher.encrypt_value(&storage_salt, &[])?;
 
         // save on disk
         storage.set_password_salt(&salt)?;
@@ -588,31 +781,109 @@ impl Wallet {
         }).await.context("Error while decrypting ciphertext")?
     }
 
+    // Verify that the balance ciphertext encrypts the plaintext expected
+    // It is instant compared to decrypting it
+    pub fn verify_balance(&self, ciphertext: &Ciphertext, amount: u64) -> bool {
+        self.keypair.verify_plaintext(ciphertext, amount)
+    }
+
     // Decrypt the extra data from a transfer
     pub fn decrypt_extra_data(&self, cipher: UnknownExtraDataFormat, handle: &DecryptHandle, role: Role) -> Result<DataElement, WalletError> {
         trace!("decrypt extra data");
         cipher.decrypt(&self.keypair.get_private_key(), handle, role).map_err(|_| WalletError::CiphertextDecode)
     }
 
+    // Decrypt the extra data of a transaction if it was encrypted for us
+    // Otherwise, it is returned as it is
+    pub fn decrypt_tx_extra_data(&self, extra_data: Vec<u8>) -> Vec<u8> {
+        if !memo::is_encrypted(&extra_data) {
+            return extra_data
+        }
+
+        match memo::decrypt(&extra_data, self.keypair.get_private_key()) {
+            Ok(plaintext) => plaintext,
+            Err(_) => {
+                trace!("Transaction extra data is not encrypted for us");
+                extra_data
+            }
+        }
+    }
+
     // Create a transaction with the given transaction type and fee
     // this will apply the changes to the storage if the transaction
-    pub async fn create_transaction(&self, transaction_type: TransactionTypeBuilder, fee: FeeBuilder) -> Result<Transaction, WalletError> {
+    pub async fn create_transaction(&self, transaction_type: TransactionTypeBuilder, fee: FeeBuilder, extra_data: Option<Vec<u8>>, encrypt_extra_data: bool) -> Result<Transaction, WalletError> {
         trace!("create transaction");
-        let mut storage = self.storage.write().await;
-        let (mut state, transaction) = self.create_transaction_with_storage(&storage, transaction_type, fee).await?;
+        let reservation = self.reserve_nonce().await;
+        let (mut state, transaction) = {
+            let storage = self.storage.read().await;
+            self.create_transaction_with_storage(&storage, transaction_type, fee, extra_data, encrypt_extra_data).await?
+        };
 
-        state.apply_changes(&mut storage).await?;
+        reservation.commit(&mut state).await?;
 
         Ok(transaction)
     }
 
+    // Reserve the next nonce to build a transaction
+    // Other transactions and rescan wait until the reservation is committed or dropped
+    // The storage is only locked for writing when the reservation is committed
+    pub async fn reserve_nonce(&self) -> NonceReservation<'_> {
+        NonceReservation::new(&self.storage, &self.tx_building_lock).await
+    }
+
     // create the final transaction with calculated fees and signature
     // also check that we have enough funds for the transaction
     // This will returns the transaction builder state along the transaction
     // You must handle "apply changes" to the storage
-    pub async fn create_transaction_with_storage(&self, storage: &EncryptedStorage, transaction_type: TransactionTypeBuilder, fee: FeeBuilder) -> Result<(TransactionBuilderState, Transaction), WalletError> {
+    // If requested, the extra data is encrypted for us and the recipients only
+    // The nonce is chained after our pending transactions, and the balance they use can't be spent
+    // unless it is allowed, in this case only a warning is shown
+    // Pending transactions not in the TX cache (created before a restart) must be executed first
+    // as the unconfirmed balances don't include what they spend
+    pub async fn create_transaction_with_storage(&self, storage: &EncryptedStorage, transaction_type: TransactionTypeBuilder, fee: FeeBuilder, extra_data: Option<Vec<u8>>, encrypt_extra_data: bool) -> Result<(TransactionBuilderState, Transaction), WalletError> {
         trace!("create transaction with storage");
         let nonce = storage.get_unconfirmed_nonce();
+        let next_nonce = storage.get_next_nonce()?;
+        if next_nonce != nonce {
+            return Err(WalletError::PendingTransactionsNotCached(next_nonce - nonce));
+        }
+
+        // Verified before building, otherwise the builder would fail first on the unconfirmed balance
+        let fixed_fee = match fee {
+            FeeBuilder::Value(value) => value,
+            FeeBuilder::Multiplier(_) => 0
+        };
+        self.check_available_balances(storage, &get_outgoing_amounts(&transaction_type, fixed_fee)).await?;
+
+        let mut amounts = get_outgoing_amounts(&transaction_type, 0);
+        let (state, transaction) = self.create_transaction_with_nonce(storage, nonce, transaction_type, fee, extra_data, encrypt_extra_data).await?;
+
+        // Verified again with the fee estimated
+        if fixed_fee == 0 {
+            let total = amounts.entry(XELIS_ASSET).or_insert(0);
+            *total = total.saturating_add(transaction.get_fee());
+            self.check_available_balances(storage, &amounts).await?;
+        }
+
+        Ok((state, transaction))
+    }
+
+    // Verify that the balances used by our pending transactions are not spent
+    // Only a warning is shown if it is allowed
+    async fn check_available_balances(&self, storage: &EncryptedStorage, amounts: &IndexMap<Hash, u64>) -> Result<(), WalletError> {
+        match verify_available_balances(storage, amounts).await {
+            Err(e @ WalletError::AvailableBalanceExceeded(..)) if self.is_spending_pending_balance_allowed() => {
+                warn!("{}, the transaction or a pending one may fail", e);
+                Ok(())
+            },
+            res => res
+        }
+    }
+
+    // Same as create_transaction_with_storage but using the nonce given
+    // The balance used by our pending transactions is not verified, this is used to replace one of them
+    pub async fn create_transaction_with_nonce(&self, storage: &EncryptedStorage, nonce: u64, transaction_type: TransactionTypeBuilder, fee: FeeBuilder, extra_data: Option<Vec<u8>>, encrypt_extra_data: bool) -> Result<(TransactionBuilderState, Transaction), WalletError> {
+        trace!("create transaction with nonce {}", nonce);
 
         // Build the state for the builder
         let used_assets = transaction_type.used_assets();
@@ -647,7 +918,9 @@ impl Wallet {
         self.add_registered_keys_for_fees_estimation(state.as_mut(), &fee, &transaction_type).await?;
 
         // Create the transaction builder
-        let builder = TransactionBuilder::new(0, self.public_key.clone(), transaction_type, fee);
+        let builder = TransactionBuilder::new(0, self.public_key.clone(), transaction_type, fee)
+            .with_extra_data(extra_data)
+            .with_encrypted_extra_data(encrypt_extra_data);
 
         // Build the final transaction
         let transaction = builder.build(&mut state, &self.keypair)
@@ -674,6 +947,153 @@ impl Wallet {
         }
     }
 
+    // Submit a transaction built using the reservation
+    // If the daemon rejects it because of its nonce and the auto nonce recovery is enabled,
+    // it is rebuilt once with the nonce expected and the state is replaced
+    // Returns the transaction accepted, the reservation must be committed after it
+    pub async fn submit_transaction_with_recovery(&self, reservation: &mut NonceReservation<'_>, state: &mut TransactionBuilderState, transaction: Transaction, transaction_type: TransactionTypeBuilder, fee: FeeBuilder, extra_data: Option<Vec<u8>>, encrypt_extra_data: bool) -> Result<Transaction, WalletError> {
+        trace!("submit transaction with recovery");
+        let transaction = if !self.is_auto_nonce_recovery_enabled() {
+            self.submit_transaction(&transaction).await?;
+            transaction
+        } else {
+            let mut recovery = WalletNonceRecovery {
+                wallet: self,
+                reservation,
+                state,
+                transaction_type: transaction_type.clone(),
+                fee,
+                extra_data,
+                encrypt_extra_data
+            };
+            submit_with_nonce_recovery(&mut recovery, transaction).await?
+        };
+
+        self.add_pending_transaction(&transaction, get_outgoing_amounts(&transaction_type, transaction.get_fee())).await;
+        Ok(transaction)
+    }
+
+    // Track a transaction broadcasted until it is seen executed in a block
+    async fn add_pending_transaction(&self, transaction: &Transaction, amounts: IndexMap<Hash, u64>) {
+        let hash = transaction.hash();
+        let now = get_current_time_in_seconds();
+        let pending = PendingTransaction {
+            transaction: transaction.clone(),
+            amounts,
+            created_at: now,
+            last_broadcast: now,
+            state: PendingState::Pending
+        };
+
+        let mut storage = self.storage.write().await;
+        // It may already be executed if the sync was faster than us
+        let res = match storage.has_transaction(&hash) {
+            Ok(true) => Ok(()),
+            Ok(false) => storage.add_pending_transaction(&hash, &pending),
+            Err(e) => Err(e)
+        };
+
+        // The transaction is already broadcasted, don't fail because of it
+        if let Err(e) = res {
+            error!("Error while saving pending transaction {}: {}", hash, e);
+        }
+    }
+
+    // Broadcast again a pending transaction, used when it became stale
+    pub async fn rebroadcast_transaction(&self, hash: &Hash) -> Result<(), WalletError> {
+        trace!("rebroadcast transaction {}", hash);
+        let mut pending = self.storage.read().await.get_pending_transaction(hash)
+            .map_err(|_| WalletError::PendingTransactionNotFound(hash.clone()))?;
+
+        self.submit_transaction(&pending.transaction).await?;
+
+        pending.last_broadcast = get_current_time_in_seconds();
+        pending.state = PendingState::Pending;
+        self.storage.write().await.add_pending_transaction(hash, &pending)?;
+
+        Ok(())
+    }
+
+    // Account receiving the replacement of a cancelled transaction
+    // A transaction can't send to its own source, so another account of this wallet is used,
+    // or the first account derived from the master key if none was created yet
+    pub async fn get_cancel_destination(&self) -> Result<(u32, Address), Error> {
+        let storage = self.storage.read().await;
+        let account = storage.get_account();
+        let index = storage.get_accounts()?.into_iter()
+            .map(|(_, index)| index)
+            .find(|index| *index != account)
+            .unwrap_or(if account == 0 { 1 } else { 0 });
+
+        let private_key = storage.get_private_key()?;
+        Ok((index, derive_account_keypair(&private_key, index).get_public_key().to_address(self.network.is_mainnet())))
+    }
+
+    // Cancel a pending transaction by replacing it with one using the same nonce and a higher fee
+    // This is only possible if the daemon mempool supports replace-by-fee
+    // The replacement sends the smallest amount of XELIS to another account of this wallet
+    pub async fn cancel_transaction(&self, hash: &Hash) -> Result<Transaction, WalletError> {
+        trace!("cancel transaction {}", hash);
+        let pending = self.storage.read().await.get_pending_transaction(hash)
+            .map_err(|_| WalletError::PendingTransactionNotFound(hash.clone()))?;
+
+        {
+            let handler = self.network_handler.lock().await;
+            let network_handler = handler.as_ref().ok_or(WalletError::NotOnlineMode)?;
+            let info = network_handler.get_api().get_info().await?;
+            if !info.replace_by_fee {
+                return Err(WalletError::ReplaceByFeeNotSupported)
+            }
+        }
+
+        let (_, destination) = self.get_cancel_destination().await?;
+        let mut reservation = self.reserve_nonce().await;
+        // The replacement is built from the last synced balances,
+        // so only the first transaction not executed yet can be replaced
+        let nonce = pending.transaction.get_nonce();
+        let synced_nonce = self.storage.read().await.get_nonce()?;
+        if synced_nonce != nonce {
+            return Err(WalletError::CannotCancelTransaction(synced_nonce, nonce))
+        }
+        reservation.reset_nonce(nonce).await?;
+
+        let transaction_type = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: XELIS_ASSET,
+            amount: 1,
+            destination,
+            extra_data: None
+        }]);
+        let fee = FeeBuilder::Value(pending.transaction.get_fee().saturating_mul(CANCEL_FEE_MULTIPLIER));
+        let (mut state, transaction) = {
+            let storage = self.storage.read().await;
+            self.create_transaction_with_nonce(&storage, nonce, transaction_type.clone(), fee, None, false).await?
+        };
+
+        self.submit_transaction(&transaction).await?;
+        reservation.commit(&mut state).await?;
+
+        self.storage.write().await.delete_pending_transaction(hash)?;
+        self.add_pending_transaction(&transaction, get_outgoing_amounts(&transaction_type, transaction.get_fee())).await;
+
+        Ok(transaction)
+    }
+
+    pub fn set_auto_nonce_recovery(&self, enabled: bool) {
+        self.auto_nonce_recovery.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn is_auto_nonce_recovery_enabled(&self) -> bool {
+        self.auto_nonce_recovery.load(Ordering::SeqCst)
+    }
+
+    pub fn set_spending_pending_balance(&self, allowed: bool) {
+        self.spend_pending_balance.store(allowed, Ordering::SeqCst);
+    }
+
+    pub fn is_spending_pending_balance_allowed(&self) -> bool {
+        self.spend_pending_balance.load(Ordering::SeqCst)
+    }
+
     // Search if possible all registered keys for the transaction type
     pub async fn add_registered_keys_for_fees_estimation(&self, state: &mut EstimateFeesState, fee: &FeeBuilder, transaction_type: &TransactionTypeBuilder) -> Result<(), WalletError> {
         trace!("add registered keys for fees estimation");
@@ -711,21 +1131,37 @@ impl Wallet {
 
     // Estimate fees for a given transaction type
     // Estimated fees returned are the minimum required to be valid on chain
-    pub async fn estimate_fees(&self, tx_type: TransactionTypeBuilder) -> Result<u64, WalletError> {
+    pub async fn estimate_fees(&self, tx_type: TransactionTypeBuilder, extra_data: Option<Vec<u8>>, encrypt_extra_data: bool) -> Result<u64, WalletError> {
         trace!("estimate fees");
+        let (estimated_fees, _) = self.estimate_fees_and_size(tx_type, extra_data, encrypt_extra_data).await?;
+        Ok(estimated_fees)
+    }
+
+    // Estimate fees and serialized size in bytes for a given transaction type
+    // Nothing is signed and the storage is not updated
+    pub async fn estimate_fees_and_size(&self, tx_type: TransactionTypeBuilder, extra_data: Option<Vec<u8>>, encrypt_extra_data: bool) -> Result<(u64, usize), WalletError> {
+        trace!("estimate fees and size");
         let mut state = EstimateFeesState::new();
 
         self.add_registered_keys_for_fees_estimation(&mut state, &FeeBuilder::default(), &tx_type).await?;
 
-        let builder = TransactionBuilder::new(0, self.public_key.clone(), tx_type, FeeBuilder::default());
+        let builder = TransactionBuilder::new(0, self.public_key.clone(), tx_type, FeeBuilder::default())
+            .with_extra_data(extra_data)
+            .with_encrypted_extra_data(encrypt_extra_data);
         let estimated_fees = builder.estimate_fees(&mut state)
             .map_err(|e| WalletError::Any(e.into()))?;
 
-        Ok(estimated_fees)
+        Ok((estimated_fees, builder.estimate_size()))
     }
 
     // set wallet in online mode: start a communication task which will keep the wallet synced
     pub async fn set_online_mode(self: &Arc<Self>, daemon_address: &String, auto_reconnect: bool) -> Result<(), WalletError> {
+        self.set_online_mode_with_failover(vec![daemon_address.clone()], auto_reconnect).await
+    }
+
+    // set wallet in online mode using the first daemon reachable of the list
+    // the next ones are used automatically if the connection is lost
+    pub async fn set_online_mode_with_failover(self: &Arc<Self>, daemon_addresses: Vec<String>, auto_reconnect: bool) -> Result<(), WalletError> {
         trace!("Set online mode");
         if self.is_online().await {
             // user have to set in offline mode himself first
@@ -733,10 +1169,11 @@ impl Wallet {
         }
 
         // create the network handler
-        let network_handler = NetworkHandler::new(Arc::clone(&self), daemon_address).await?;
+        let network_handler = NetworkHandler::with_failover(Arc::clone(&self), daemon_addresses).await?;
         // start the task
         network_handler.start(auto_reconnect).await?;
         *self.network_handler.lock().await = Some(network_handler);
+        self.start_accounts_sync().await;
 
         Ok(())
     }
@@ -755,13 +1192,132 @@ impl Wallet {
         // start the task
         network_handler.start(auto_reconnect).await?;
         *self.network_handler.lock().await = Some(network_handler);
+        self.start_accounts_sync().await;
+
+        Ok(())
+    }
+
+    // Sync in background all the other accounts of this wallet with the daemon used
+    // Failures are only logged, the active account stays online
+    async fn start_accounts_sync(&self) {
+        let indexes = {
+            let storage = self.storage.read().await;
+            storage.get_accounts().map(|accounts| accounts.into_iter()
+                .map(|(_, index)| index)
+                .filter(|index| *index != storage.get_account())
+                .collect::<Vec<_>>()
+            )
+        };
+
+        match indexes {
+            Ok(indexes) => for index in indexes {
+                if let Err(e) = self.start_account_sync(index).await {
+                    warn!("Couldn't sync the account {} in background: {}", index, e);
+                }
+            },
+            Err(e) => warn!("Couldn't retrieve the accounts to sync: {}", e)
+        }
+    }
+
+    // Start to sync an account in background, does nothing if we are offline
+    // Each account has its own network handler to keep its balances and history up to date
+    async fn start_account_sync(&self, index: u32) -> Result<(), Error> {
+        let network_handler = match self.network_handler.lock().await.as_ref() {
+            Some(network_handler) => Arc::clone(network_handler),
+            None => return Ok(())
+        };
+
+        let (storage, keypair) = {
+            let storage = self.storage.read().await;
+            let private_key = storage.get_private_key()?;
+            (storage.open_account(index)?, derive_account_keypair(&private_key, index))
+        };
+
+        let wallet = Self::new(storage, keypair, self.network, self.precomputed_tables.clone(), None);
+        // A connection is opened for each account when the daemon addresses are known,
+        // the connection of the active account is closed as soon as its network handler stops
+        let daemon_addresses = network_handler.get_daemon_addresses();
+        let account_handler = if daemon_addresses.is_empty() {
+            NetworkHandler::with_api(Arc::clone(&wallet), network_handler.get_shared_api()).await?
+        } else {
+            NetworkHandler::with_failover(Arc::clone(&wallet), daemon_addresses.to_vec()).await?
+        };
+        account_handler.start(true).await?;
+        *wallet.network_handler.lock().await = Some(account_handler);
+
+        let previous = self.accounts_sync.lock().await.insert(index, wallet);
+        if let Some(previous) = previous {
+            previous.stop_sync().await;
+        }
 
         Ok(())
     }
 
+    // Stop the sync of all the accounts running in background
+    async fn stop_accounts_sync(&self) {
+        let wallets: Vec<_> = self.accounts_sync.lock().await.drain()
+            .map(|(_, wallet)| wallet)
+            .collect();
+
+        for wallet in wallets {
+            wallet.stop_sync().await;
+        }
+    }
+
+    // Stop the network handler of an account synced in background
+    async fn stop_sync(&self) {
+        if let Some(network_handler) = self.network_handler.lock().await.take() {
+            if let Err(e) = network_handler.stop().await {
+                debug!("Account network handler was not running: {}", e);
+            }
+        }
+    }
+
+    // Get the wallet of an account synced in background
+    pub async fn get_synced_account(&self, index: u32) -> Option<Arc<Self>> {
+        self.accounts_sync.lock().await.get(&index).cloned()
+    }
+
+    // Switch to another daemon without restarting the wallet
+    // The new daemon is verified before disconnecting from the current one,
+    // then the wallet is synced again with it and its address is saved to be used by default
+    pub async fn set_daemon_address(self: &Arc<Self>, daemon_address: String, auto_reconnect: bool) -> Result<(), WalletError> {
+        trace!("Set daemon address to {}", daemon_address);
+        let network_handler = NetworkHandler::new(Arc::clone(&self), &daemon_address).await?;
+
+        self.stop_accounts_sync().await;
+        {
+            let mut handler = self.network_handler.lock().await;
+            if let Some(previous) = handler.take() {
+                // It may have already stopped with an error
+                if let Err(e) = previous.stop().await {
+                    debug!("Previous network handler was not running: {}", e);
+                }
+            }
+        }
+
+        // First sync verifies our last synced block against the new daemon
+        network_handler.start(auto_reconnect).await?;
+        *self.network_handler.lock().await = Some(network_handler);
+        self.start_accounts_sync().await;
+
+        let mut storage = self.storage.write().await;
+        storage.set_daemon_address(&daemon_address)?;
+        storage.flush()?;
+
+        Ok(())
+    }
+
+    // Get the address of the daemon currently used
+    pub async fn get_daemon_address(&self) -> Option<String> {
+        self.network_handler.lock().await.as_ref()
+            .and_then(|network_handler| network_handler.get_daemon_address().map(String::from))
+    }
+
     // set wallet in offline mode: stop communication task if exists
     pub async fn set_offline_mode(&self) -> Result<(), WalletError> {
         trace!("Set offline mode");
+        self.stop_accounts_sync().await;
         let mut handler = self.network_handler.lock().await;
         if let Some(network_handler) = handler.take() {
             network_handler.stop().await?;
@@ -775,49 +1331,37 @@ impl Wallet {
     // rescan the wallet from the given topoheight
     // that will delete all transactions above the given topoheight and all balances
     // then it will re-fetch all transactions and balances from daemon
+    // It can also be used when the network handler stopped because the daemon is on another fork
     pub async fn rescan(&self, topoheight: u64, auto_reconnect: bool) -> Result<(), WalletError> {
         trace!("Rescan wallet from topoheight {}", topoheight);
-        if !self.is_online().await {
-            // user have to set it online
-            return Err(WalletError::NotOnlineMode)
-        }
 
-        let mut storage = self.get_storage().write().await;
-        if topoheight > storage.get_synced_topoheight()? {
-            return Err(WalletError::RescanTopoheightTooHigh)
-        }
+        // Don't reset the wallet state while a transaction is being built
+        let _building_guard = self.tx_building_lock.try_lock()
+            .map_err(|_| WalletError::TransactionBuildingInProgress)?;
 
+        let mut storage = self.get_storage().write().await;
         let handler = self.network_handler.lock().await;
         if let Some(network_handler) = handler.as_ref() {
+            let api = network_handler.get_api();
+            let info = api.get_info().await?;
+            let topoheight = get_rescan_start_topoheight(topoheight, storage.get_synced_topoheight()?, info.pruned_topoheight)?;
+
             debug!("Stopping network handler!");
-            network_handler.stop().await?;
+            // It may have already stopped with an error
+            if let Err(e) = network_handler.stop().await {
+                debug!("Network handler was not running: {}", e);
+            }
             {
-                debug!("set synced topoheight to {}", topoheight);
-                storage.set_synced_topoheight(topoheight)?;
-                storage.delete_top_block_hash()?;
-                // balances will be re-fetched from daemon
-                storage.delete_balances().await?;
-                storage.delete_assets().await?;
-                // unconfirmed balances are going to be outdated, we delete them
-                storage.delete_unconfirmed_balances().await?;
-                storage.clear_tx_cache();
-
                 debug!("Retrieve current wallet nonce");
-                let nonce_result = network_handler.get_api()
+                let nonce_result = api
                     .get_nonce(&self.get_address()).await
                     // User has no transactions/balances yet, set its nonce to 0
                     .map(|v| v.version.get_nonce()).unwrap_or(0);
 
-                storage.set_nonce(nonce_result)?;
-
-                if topoheight == 0 {
-                    debug!("Deleting all transactions for full rescan");
-                    storage.delete_transactions()?;
-                } else {
-                    debug!("Deleting transactions above {} for partial rescan", topoheight);
-                    storage.delete_transactions_above_topoheight(topoheight)?;
-                }
+                storage.reset_for_rescan(topoheight, nonce_result).await?;
             }
+            self.rescan_target.store(info.topoheight, Ordering::SeqCst);
+
             debug!("Starting again network handler");
             network_handler.start(auto_reconnect).await.context("Error while restarting network handler")?;
         } else {
@@ -827,6 +1371,83 @@ impl Wallet {
         Ok(())
     }
 
+    // Track an asset using its data registered on the daemon
+    // Its balance and history are synced from now on
+    pub async fn track_asset(&self, asset: Hash) -> Result<AssetData, WalletError> {
+        trace!("track asset {}", asset);
+        let network_handler = self.network_handler.lock().await.clone().ok_or(WalletError::NotOnlineMode)?;
+        let data = network_handler.get_api().get_asset(&asset).await?;
+        {
+            let mut storage = self.storage.write().await;
+            storage.add_asset(&asset, data.get_decimals()).await?;
+        }
+
+        network_handler.sync_asset(&asset).await?;
+        self.propagate_event(Event::NewAsset(AssetWithData::new(asset, data.clone()))).await;
+
+        Ok(data)
+    }
+
+    // Stop tracking an asset, it won't be added back automatically
+    pub async fn untrack_asset(&self, asset: &Hash) -> Result<(), WalletError> {
+        trace!("untrack asset {}", asset);
+        if *asset == XELIS_ASSET {
+            return Err(WalletError::NativeAssetAlwaysTracked)
+        }
+
+        let mut storage = self.storage.write().await;
+        storage.remove_asset(asset).await?;
+        Ok(())
+    }
+
+    // Compare the nonce tracked locally against the daemon view of our account
+    pub async fn audit_nonce(&self) -> Result<NonceAudit, WalletError> {
+        trace!("audit nonce");
+        let handler = self.network_handler.lock().await;
+        let network_handler = handler.as_ref().ok_or(WalletError::NotOnlineMode)?;
+        let api = network_handler.get_api();
+        let address = self.get_address();
+
+        // Account without any transaction/balance yet uses nonce 0
+        let daemon_nonce = if api.has_nonce(&address).await? {
+            api.get_nonce(&address).await?.version.get_nonce()
+        } else {
+            0
+        };
+
+        // Daemon returns an error if we have no transaction in mempool
+        // Errors while reaching the daemon are still reported
+        let pending = match api.get_mempool_cache(&address).await {
+            Ok(cache) => cache.get_txs().len() as u64,
+            Err(e) if matches!(e.downcast_ref::<JsonRPCError>(), Some(JsonRPCError::ServerError { .. })) => 0,
+            Err(e) => return Err(e.into())
+        };
+
+        let storage = self.storage.read().await;
+        Ok(NonceAudit {
+            local_nonce: storage.get_nonce().unwrap_or(0),
+            unconfirmed_nonce: storage.get_next_nonce()?,
+            daemon_nonce,
+            pending
+        })
+    }
+
+    // Get the daemon topoheight to reach for the rescan in progress
+    // Returns None if no rescan is in progress or once the synced topoheight reached it
+    pub fn get_rescan_target(&self, synced_topoheight: u64) -> Option<u64> {
+        let target = self.rescan_target.load(Ordering::SeqCst);
+        if target == 0 {
+            return None
+        }
+
+        if synced_topoheight >= target {
+            self.rescan_target.store(0, Ordering::SeqCst);
+            return None
+        }
+
+        Some(target)
+    }
+
     // Check if the wallet is in online mode
     pub async fn is_online(&self) -> bool {
         if let Some(network_handler) = self.network_handler.lock().await.as_ref() {
@@ -863,11 +1484,159 @@ impl Wallet {
     }
 
     // Returns the seed using the language index provided
-    pub fn get_seed(&self, language_index: usize) -> Result<String, Error> {
-        let words = mnemonics::key_to_words(self.keypair.get_private_key(), language_index)?;
+    // The seed is the one of the master key, which allow to recover every account
+    pub async fn get_seed(&self, language_index: usize) -> Result<String, Error> {
+        let private_key = self.storage.read().await.get_private_key()?;
+        let words = mnemonics::key_to_words(&private_key, language_index)?;
         Ok(words.join(" "))
     }
 
+    // Export the private key in a keyfile encrypted with the passphrase
+    // Like the seed, this is the master key which allow to recover every account
+    pub async fn export_keyfile(&self, passphrase: &str) -> Result<KeyFile, Error> {
+        let private_key = self.storage.read().await.get_private_key()?;
+        Ok(KeyFile::encrypt(&private_key, passphrase)?)
+    }
+
+    // Name of the account currently used
+    pub async fn get_account_name(&self) -> Result<String, Error> {
+        let storage = self.storage.read().await;
+        let account = storage.get_account();
+        storage.get_accounts()?.into_iter()
+            .find(|(_, index)| *index == account)
+            .map(|(name, _)| name)
+            .context("Active account not found")
+    }
+
+    // List all accounts of this wallet with their index and address
+    pub async fn get_accounts(&self) -> Result<Vec<(String, u32, Address)>, Error> {
+        let storage = self.storage.read().await;
+        let private_key = storage.get_private_key()?;
+        let mainnet = self.network.is_mainnet();
+
+        let accounts = storage.get_accounts()?.into_iter()
+            .map(|(name, index)| {
+                let address = derive_account_keypair(&private_key, index).get_public_key().to_address(mainnet);
+                (name, index, address)
+            })
+            .collect();
+
+        Ok(accounts)
+    }
+
+    // Create a new account derived from the master key and returns its address
+    // If we are online, it is synced in background right away
+    pub async fn create_account(&self, name: &str) -> Result<Address, Error> {
+        let (index, private_key) = {
+            let mut storage = self.storage.write().await;
+            let index = storage.create_account(name)?;
+            let private_key = storage.get_private_key()?;
+            storage.flush()?;
+            (index, private_key)
+        };
+
+        if let Err(e) = self.start_account_sync(index).await {
+            warn!("Couldn't sync the account {} in background: {}", name, e);
+        }
+
+        Ok(derive_account_keypair(&private_key, index).get_public_key().to_address(self.network.is_mainnet()))
+    }
+
+    // Rename an existing account
+    pub async fn rename_account(&self, name: &str, new_name: &str) -> Result<(), Error> {
+        let mut storage = self.storage.write().await;
+        storage.rename_account(name, new_name)?;
+        storage.flush()?;
+        Ok(())
+    }
+
+    // Check if the account has any non-zero balance stored
+    // Balances are only up to date once the account has been synced
+    pub async fn account_has_balance(&self, name: &str) -> Result<bool, Error> {
+        let storage = self.storage.read().await;
+        let index = storage.get_account_index(name)?
+            .ok_or_else(|| WalletError::AccountNotFound(name.to_owned()))?;
+
+        storage.open_account(index)?.has_non_zero_balance()
+    }
+
+    // Delete an account and all its stored data
+    pub async fn delete_account(&self, name: &str) -> Result<(), Error> {
+        // Its sync must be stopped before dropping its data
+        // The default account can't be deleted and the active one isn't synced in background
+        let index = self.storage.read().await.get_account_index(name)?;
+        if let Some(index) = index.filter(|index| *index != 0) {
+            let synced = self.accounts_sync.lock().await.remove(&index);
+            if let Some(wallet) = synced {
+                wallet.stop_sync().await;
+            }
+        }
+
+        let mut storage = self.storage.write().await;
+        storage.delete_account(name)?;
+        storage.flush()?;
+        Ok(())
+    }
+
+    // Switch to another account of this wallet
+    // A new wallet instance is returned for it and this one is set offline
+    // Its sync is resumed from its own synced topoheight, this account keeps being synced in background
+    pub async fn switch_account(self: &Arc<Self>, name: &str) -> Result<Arc<Self>, Error> {
+        trace!("switch account to {}", name);
+        #[cfg(feature = "api_server")]
+        {
+            if self.api_server.lock().await.is_some() {
+                return Err(WalletError::APIServerRunning.into())
+            }
+        }
+
+        let (account_storage, keypair) = {
+            let mut storage = self.storage.write().await;
+            let index = storage.get_account_index(name)?
+                .ok_or_else(|| WalletError::AccountNotFound(name.to_owned()))?;
+
+            let private_key = storage.get_private_key()?;
+            storage.set_active_account(index)?;
+            storage.flush()?;
+
+            (storage.open_account(index)?, derive_account_keypair(&private_key, index))
+        };
+
+        // Keep the same daemon connection for the new account
+        self.stop_accounts_sync().await;
+        let api = {
+            let mut handler = self.network_handler.lock().await;
+            match handler.take() {
+                Some(network_handler) => {
+                    let api = network_handler.get_shared_api();
+                    if let Err(e) = network_handler.stop().await {
+                        debug!("Error while stopping network handler: {}", e);
+                    }
+                    Some(api)
+                },
+                None => None
+            }
+        };
+
+        // The new instance keeps the wallet opened
+        let lock = self.lock.lock().await.take();
+        let wallet = Self::new(account_storage, keypair, self.network, self.precomputed_tables.clone(), lock);
+        if let Some(api) = api {
+            // Connection was closed by the previous network handler
+            let res = match api.reconnect().await {
+                Ok(true) => wallet.set_online_mode_with_api(api, true).await.map_err(Error::from),
+                Ok(false) => Err(anyhow::anyhow!("couldn't reconnect to daemon")),
+                Err(e) => Err(e)
+            };
+
+            if let Err(e) = res {
+                warn!("Couldn't set the account {} in online mode: {}", name, e);
+            }
+        }
+
+        Ok(wallet)
+    }
+
     // Current account nonce for transactions
     // Nonce is used against replay attacks on-chain
     pub async fn get_nonce(&self) -> u64 {
@@ -891,7 +1660,9 @@ pub enum XSWDEvent {
     RequestPermission(AppStateShared, RpcRequest, OneshotSender<Result<PermissionResult, Error>>),
     // bool represents if it was signed or not
     RequestApplication(AppStateShared, bool, OneshotSender<Result<PermissionResult, Error>>),
-    CancelRequest(AppStateShared, OneshotSender<Result<(), Error>>)
+    CancelRequest(AppStateShared, OneshotSender<Result<(), Error>>),
+    // Application connected to the RPC Server requesting a class of methods
+    RequestAppPermission(ApplicationInfo, MethodClass, OneshotSender<Result<PermissionResult, Error>>)
 }
 
 #[cfg(feature = "api_server")]
@@ -938,6 +1709,32 @@ impl XSWDPermissionHandler for Arc<Wallet> {
     }
 }
 
+#[cfg(feature = "api_server")]
+#[async_trait]
+impl AppPermissionHandler for Arc<Wallet> {
+    async fn get_app_permissions(&self, app: &ApplicationInfo) -> Result<Option<AppPermissions>, Error> {
+        let storage = self.storage.read().await;
+        storage.get_app_permissions(app.get_id())
+    }
+
+    async fn save_app_permission(&self, app: &ApplicationInfo, class: MethodClass, permission: Permission) -> Result<(), Error> {
+        let mut storage = self.storage.write().await;
+        let permissions = AppPermissions::with_decision(storage.get_app_permissions(app.get_id())?, app, class, permission)?;
+        storage.set_app_permissions(app.get_id(), &permissions)
+    }
+
+    async fn request_app_permission(&self, app: &ApplicationInfo, class: MethodClass) -> Result<PermissionResult, Error> {
+        if let Some(sender) = self.xswd_channel.read().await.as_ref() {
+            let (callback, receiver) = channel();
+            sender.send(XSWDEvent::RequestAppPermission(app.clone(), class, callback))?;
+
+            return receiver.await?;
+        }
+
+        Err(WalletError::NoHandlerAvailable.into())
+    }
+}
+
 #[cfg(feature = "api_server")]
 #[async_trait]
 impl XSWDNodeMethodHandler for Arc<Wallet> {
@@ -959,4 +1756,195 @@ impl XSWDNodeMethodHandler for Arc<Wallet> {
 
         Err(RpcResponseError::new(id, WalletError::NotOnlineMode))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        account::CiphertextCache,
+        config::COIN_VALUE,
+        testing::TempDir
+    };
+    use crate::{
+        account::DEFAULT_ACCOUNT_NAME,
+        entry::{format_extra_data, EntryData, TransactionEntry},
+        storage::Balance,
+        test_utils::open_temp_storage
+    };
+    use xelis_common::transaction::{AssetCreationPayload, BurnPayload};
+    use super::*;
+
+    // Simulate a wallet synced with a daemon having 100 blocks, each one mined by the wallet
+    fn synced_storage(name: &str) -> (TempDir, EncryptedStorage) {
+        let (dir, mut storage) = open_temp_storage(name);
+        for topoheight in 1..=100u64 {
+            let mut bytes = [0u8; 32];
+            bytes[0..8].copy_from_slice(&topoheight.to_be_bytes());
+            let hash = Hash::new(bytes);
+            let entry = TransactionEntry::new(hash.clone(), topoheight, EntryData::Coinbase { reward: 10 }, None);
+            storage.save_transaction(&hash, &entry).unwrap();
+        }
+        storage.set_synced_topoheight(100).unwrap();
+        storage.set_nonce(3).unwrap();
+
+        (dir, storage)
+    }
+
+    #[tokio::test]
+    async fn test_rescan_from_topoheight() {
+        let (_dir, mut storage) = synced_storage("wallet-rescan");
+
+        let topoheight = get_rescan_start_topoheight(60, storage.get_synced_topoheight().unwrap(), None).unwrap();
+        assert_eq!(topoheight, 60);
+        storage.reset_for_rescan(topoheight, 3).await.unwrap();
+
+        assert_eq!(storage.get_synced_topoheight().unwrap(), 60);
+        assert_eq!(storage.get_nonce().unwrap(), 3);
+        let transactions = storage.get_transactions().unwrap();
+        assert_eq!(transactions.len(), 60);
+        assert!(transactions.iter().all(|tx| tx.get_topoheight() <= 60));
+
+        // Full rescan delete the whole history
+        storage.reset_for_rescan(0, 3).await.unwrap();
+        assert_eq!(storage.get_synced_topoheight().unwrap(), 0);
+        assert!(storage.get_transactions().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rescan_with_pruned_daemon() {
+        let (_dir, mut storage) = synced_storage("wallet-rescan-pruned");
+        let synced_topoheight = storage.get_synced_topoheight().unwrap();
+
+        // Can't rescan above what we have synced
+        assert!(matches!(get_rescan_start_topoheight(101, synced_topoheight, None), Err(WalletError::RescanTopoheightTooHigh)));
+
+        // Daemon is pruned at topoheight 80, we start from it
+        let topoheight = get_rescan_start_topoheight(0, synced_topoheight, Some(80)).unwrap();
+        assert_eq!(topoheight, 80);
+        // Pruned point below the requested topoheight is ignored
+        assert_eq!(get_rescan_start_topoheight(90, synced_topoheight, Some(80)).unwrap(), 90);
+
+        storage.reset_for_rescan(topoheight, 0).await.unwrap();
+        assert_eq!(storage.get_synced_topoheight().unwrap(), 80);
+        assert_eq!(storage.get_transactions().unwrap().len(), 80);
+    }
+
+    #[tokio::test]
+    async fn test_accounts_are_isolated() {
+        let (_dir, mut storage) = synced_storage("wallet-accounts");
+
+        let index = storage.create_account("business").unwrap();
+        assert_eq!(index, 1);
+        assert!(storage.create_account("business").is_err());
+        assert!(storage.create_account("with space").is_err());
+
+        // New account starts with its own empty state
+        let mut account = storage.open_account(index).unwrap();
+        assert!(account.get_transactions().unwrap().is_empty());
+        assert!(account.get_synced_topoheight().is_err());
+        account.set_nonce(7).unwrap();
+        assert_eq!(storage.get_nonce().unwrap(), 3);
+        assert_eq!(storage.get_transactions().unwrap().len(), 100);
+
+        // Renaming keep the index and its data
+        storage.rename_account("business", "work").unwrap();
+        assert_eq!(storage.get_account_index("business").unwrap(), None);
+        assert_eq!(storage.get_account_index("work").unwrap(), Some(index));
+        assert_eq!(storage.open_account(index).unwrap().get_nonce().unwrap(), 7);
+
+        // Default and active accounts can't be deleted
+        assert!(storage.delete_account(DEFAULT_ACCOUNT_NAME).is_err());
+        storage.set_active_account(index).unwrap();
+        assert!(storage.delete_account("work").is_err());
+        storage.set_active_account(0).unwrap();
+        drop(account);
+
+        storage.delete_account("work").unwrap();
+        assert!(storage.open_account(index).unwrap().get_nonce().is_err());
+
+        // Indexes are never reused
+        assert_eq!(storage.create_account("work").unwrap(), 2);
+        let accounts = storage.get_accounts().unwrap();
+        assert_eq!(accounts, vec![(DEFAULT_ACCOUNT_NAME.to_owned(), 0), ("work".to_owned(), 2)]);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_extra_data_round_trip() {
+        // No balance is decrypted, the tables are never read
+        let tables = Arc::new(PrecomputedTables::new(10));
+        let create = |name: &str| {
+            let dir = TempDir::new(name);
+            let wallet = Wallet::create(dir.to_string_lossy().into_owned(), "password".to_owned(), None, Network::Dev, Arc::clone(&tables)).unwrap();
+            (dir, wallet)
+        };
+        let (_alice_dir, alice) = create("wallet-memo-alice");
+        let (_bob_dir, bob) = create("wallet-memo-bob");
+        let (_eve_dir, eve) = create("wallet-memo-eve");
+
+        // Alice is synced with a balance to spend
+        {
+            let mut storage = alice.get_storage().write().await;
+            let amount = 10 * COIN_VALUE;
+            let ciphertext = CiphertextCache::Decompressed(alice.get_address().get_public_key().decompress().unwrap().encrypt(amount));
+            storage.set_balance_for(&XELIS_ASSET, Balance::new(amount, ciphertext)).await.unwrap();
+            storage.set_synced_topoheight(10).unwrap();
+            storage.set_top_block_hash(&Hash::zero()).unwrap();
+            storage.set_nonce(0).unwrap();
+        }
+
+        let memo = b"order #42".to_vec();
+        let transfers = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: XELIS_ASSET,
+            amount: COIN_VALUE,
+            destination: bob.get_address(),
+            extra_data: None
+        }]);
+        let (_, transaction) = {
+            let storage = alice.get_storage().read().await;
+            alice.create_transaction_with_storage(&storage, transfers, FeeBuilder::default(), Some(memo.clone()), true).await.unwrap()
+        };
+        let extra_data = transaction.get_extra_data().clone().unwrap();
+        assert_ne!(extra_data, memo);
+
+        // Stored in plaintext in the history of the sender and the recipient
+        assert_eq!(alice.decrypt_tx_extra_data(extra_data.clone()), memo);
+        assert_eq!(bob.decrypt_tx_extra_data(extra_data.clone()), memo);
+
+        // Anyone else keeps it encrypted
+        let unreadable = eve.decrypt_tx_extra_data(extra_data.clone());
+        assert_eq!(unreadable, extra_data);
+        assert_eq!(format_extra_data(&unreadable), "encrypted");
+
+        alice.close().await;
+        bob.close().await;
+        eve.close().await;
+    }
+
+    #[test]
+    fn test_outgoing_amounts() {
+        let asset = Hash::new([1u8; 32]);
+        let destination = KeyPair::new().get_public_key().to_address(false);
+        let transfer = |asset: &Hash, amount: u64| TransferBuilder {
+            asset: asset.clone(),
+            amount,
+            destination: destination.clone(),
+            extra_data: None
+        };
+
+        // Fees are counted with the XELIS sent
+        let transfers = TransactionTypeBuilder::Transfers(vec![transfer(&XELIS_ASSET, 100), transfer(&asset, 50), transfer(&asset, 25)]);
+        let amounts = get_outgoing_amounts(&transfers, 10);
+        assert_eq!(amounts.get(&XELIS_ASSET), Some(&110));
+        assert_eq!(amounts.get(&asset), Some(&75));
+
+        let burn = TransactionTypeBuilder::Burn(BurnPayload { asset: asset.clone(), amount: 5 });
+        let amounts = get_outgoing_amounts(&burn, 10);
+        assert_eq!(amounts.get(&XELIS_ASSET), Some(&10));
+        assert_eq!(amounts.get(&asset), Some(&5));
+
+        let creation = TransactionTypeBuilder::AssetCreation(AssetCreationPayload { decimals: 8 });
+        let amounts = get_outgoing_amounts(&creation, 10);
+        assert_eq!(amounts.get(&XELIS_ASSET), Some(&(ASSET_CREATION_BURN_AMOUNT + 10)));
+        assert_eq!(amounts.len(), 1);
+    }
+}